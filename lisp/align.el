@@ -1270,6 +1270,75 @@ Otherwise, create a new marker at position POS, with type TYPE."
        (move-marker ,marker-var ,pos)
      (setq ,marker-var (copy-marker ,pos ,type))))
 
+(defun align--exclude-areas (beg end exclude-rules)
+  "Return the areas between BEG and END excluded by EXCLUDE-RULES.
+The value is a list of cons cells (BEG . END), the last area first."
+  (let (exclude-areas)
+    (when exclude-rules
+      (align-region
+       beg end 'entire
+       exclude-rules nil
+       (lambda (b e mode)
+	 (or (and mode (listp mode))
+	     (setq exclude-areas
+		   (cons (cons b e)
+			 exclude-areas)))))
+      (nreverse
+       (sort exclude-areas #'car-less-than-car)))))
+
+(defun align--compile-rule (rule separate exclude-areas)
+  "Return RULE in the form that `align-region-native' takes.
+SEPARATE is the section separator to use for RULE, and EXCLUDE-AREAS
+a list of cons cells (BEG . END) of text that must not be aligned."
+  (let ((groups (or (cdr (assq 'group rule)) 1))
+	(spacing (cdr (assq 'spacing rule)))
+	(column (cdr (assq 'column rule)))
+	(tab-stop (let ((rule-ts (assq 'tab-stop rule)))
+		    (cond (rule-ts
+			   (cdr rule-ts))
+			  ((symbolp align-to-tab-stop)
+			   (symbol-value align-to-tab-stop))
+			  (t
+			   align-to-tab-stop)))))
+    ;; A single spacing or tab-stop applies to every group of a line,
+    ;; while the groups beyond the end of a list get the defaults.
+    (list :regexp (cdr (assq 'regexp rule))
+	  :group (if (listp groups) groups (list groups))
+	  :spacing (mapcar (lambda (s) (or s align-default-spacing))
+			   (if (listp spacing)
+			       (append spacing (list nil))
+			     (list spacing)))
+	  :tab-stop (if (listp tab-stop)
+			(append tab-stop (list nil))
+		      (list tab-stop))
+	  :column (if (symbolp column) (symbol-value column) column)
+	  :justify (cdr (assq 'justify rule))
+	  :repeat (cdr (assq 'repeat rule))
+	  :separate separate
+	  :exclude exclude-areas)))
+
+(defun align--region-native (beg end rule separate exclude-rules func)
+  "Align BEG to END according to RULE with `align-region-native'.
+SEPARATE, EXCLUDE-RULES and FUNC are as for `align-region'.  Return
+non-nil if the region was aligned, or nil if RULE needs the generality
+of `align-region': when BEG or END is nil, when FUNC is non-nil, or when
+the regexp or the separator of RULE is a function, or it has a `valid'
+attribute."
+  (let* ((regexp (cdr (assq 'regexp rule)))
+	 (rulesep (assq 'separate rule))
+	 (thissep (if rulesep (cdr rulesep) separate)))
+    (when (and beg end (not func) (stringp regexp)
+	       (not (assq 'valid rule))
+	       (not (functionp thissep)))
+      (let* ((exclude-areas (align--exclude-areas beg end exclude-rules))
+	     (case-fold (assq 'case-fold rule))
+	     (case-fold-search (if case-fold
+				   (cdr case-fold)
+				 case-fold-search)))
+	(align-region-native beg end (align--compile-rule rule thissep
+							  exclude-areas)))
+      t)))
+
 (defun align-region (beg end separate rules exclude-rules
 			 &optional func)
   "Align a region based on a given set of alignment rules.
@@ -1323,7 +1392,11 @@ aligner would have dealt with are."
 	;; unless the `run-if' form tells us not to, look for the
 	;; rule..
 	(unless (or (and modes (not (apply #'derived-mode-p (eval (cdr modes)))))
-		    (and run-if (not (funcall (cdr run-if)))))
+		    (and run-if (not (funcall (cdr run-if))))
+		    ;; most rules can be applied natively, which is much
+		    ;; faster for large regions
+		    (align--region-native real-beg end-mark rule separate
+					  exclude-rules func))
 	  (let* ((case-fold-search case-fold-search)
 		 (case-fold (assq 'case-fold rule))
 		 (regexp  (cdr (assq 'regexp rule)))
@@ -1402,21 +1475,11 @@ aligner would have dealt with are."
 	      ;; reports back that the region is ok, then align it.
 	      (when (or (not func)
 			(funcall func beg end rule))
-                (let (rule-beg exclude-areas)
-                  ;; determine first of all where the exclusions
-                  ;; lie in this region
-                  (when exclude-rules
-                    (align-region
-                     beg end 'entire
-                     exclude-rules nil
-                     (lambda (b e mode)
-                       (or (and mode (listp mode))
-                           (setq exclude-areas
-                                 (cons (cons b e)
-                                       exclude-areas)))))
-                    (setq exclude-areas
-                          (nreverse
-                           (sort exclude-areas #'car-less-than-car))))
+                (let ((exclude-areas
+                       ;; determine first of all where the exclusions
+                       ;; lie in this region
+                       (align--exclude-areas beg end exclude-rules))
+                      rule-beg)
 
                   ;; set `case-fold-search' according to the
                   ;; (optional) `case-fold' property
@@ -76,6 +76,7 @@
 ;;; Code:
 
 (defvar comint-last-output-start)
+(defvar compilation-filter-start)
 
 ;; Customization
 
@@ -223,12 +224,54 @@ This is a good function to put in `comint-output-filter-functions'."
 			  comint-last-output-start
 			(point-min-marker)))
 	(end-marker (process-mark (get-buffer-process (current-buffer)))))
+    (when ansi-color-for-comint-mode
+      (ansi-color-apply-osc-on-region start-marker end-marker))
     (cond ((eq ansi-color-for-comint-mode nil))
 	  ((eq ansi-color-for-comint-mode 'filter)
 	   (ansi-color-filter-region start-marker end-marker))
 	  (t
 	   (ansi-color-apply-on-region start-marker end-marker)))))
 
+;; Hyperlinks and window titles
+
+(defvar-local ansi-color-window-title nil
+  "The window title last set by an OSC 0 or OSC 2 sequence, or nil.
+It is set in the buffer the sequence is inserted into.  To show it,
+use it in `frame-title-format', for instance with
+  (:eval (or ansi-color-window-title \"%b\")).")
+
+(defvar-local ansi-color-osc-context nil
+  "Context saved between two calls to `ansi-color-apply-osc-on-region'.")
+
+(defun ansi-color-follow-hyperlink (button)
+  "Visit the URI of the OSC 8 hyperlink BUTTON with `browse-url'."
+  (browse-url (button-get button 'ansi-color-hyperlink)))
+
+(define-button-type 'ansi-color-hyperlink
+  'action #'ansi-color-follow-hyperlink
+  'follow-link t)
+
+(defun ansi-color-apply-osc-on-region (begin end)
+  "Interpret the OSC sequences between BEGIN and END.
+OSC 8 hyperlinks are made into buttons that visit their URI, and
+OSC 0 and OSC 2 set `ansi-color-window-title'.  All OSC sequences
+are deleted; other control sequences are left alone.
+
+Every call to this function will set and use the buffer-local
+variable `ansi-color-osc-context', so that a sequence or a
+hyperlink can span two calls."
+  (when (fboundp 'ansi-color-apply-osc-on-region-native)
+    (setq ansi-color-osc-context
+          (ansi-color-apply-osc-on-region-native
+           begin end ansi-color-osc-context))))
+
+;;;###autoload
+(defun ansi-color-osc-compilation-filter ()
+  "Interpret the OSC sequences in the last output of a compilation.
+This is a good function to put in `compilation-filter-hook'."
+  (let ((inhibit-read-only t))
+    (ansi-color-apply-osc-on-region compilation-filter-start (point))))
+
 (define-obsolete-function-alias 'ansi-color-unfontify-region
   'font-lock-default-unfontify-region "24.1")
 
@@ -1911,6 +1911,15 @@ This doesn't recover lost files, it just undoes changes in the buffer itself."
 
 (defun archive-zip-extract (archive name)
   (cond
+   ((and (fboundp 'archive-extract-entry-native)
+         ;; Members compressed with methods other than deflate are
+         ;; left to the external programs.
+         (ignore-errors
+           (archive-extract-entry-native
+            archive
+            (if archive-file-name-coding-system
+                (encode-coding-string name archive-file-name-coding-system)
+              name)))))
    ((member-ignore-case (car archive-zip-extract) '("pkunzip" "pkzip"))
     (archive-*-extract archive name archive-zip-extract))
    ((equal (car archive-zip-extract) archive-7z-program)
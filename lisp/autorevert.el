@@ -291,6 +291,19 @@ You should set this variable through Custom."
   :initialize 'custom-initialize-default
   :version "24.4")
 
+(defcustom auto-revert-content-hash-threshold (* 1024 1024)
+  "Size above which Auto-Revert Mode compares file contents by hash.
+When a file notification says that a file of at least this many
+bytes changed, the file is first hashed in the background, and the
+buffer is only reverted if the hash differs from the one recorded
+the last time.  This avoids rereading large files whose contents
+did not change, for instance when they were just touched.
+If nil, always revert on change notifications."
+  :group 'auto-revert
+  :type '(choice (const :tag "Never" nil)
+                 (integer :tag "Size in bytes"))
+  :version "27.1")
+
 (defcustom auto-revert-notify-exclude-dir-regexp
   (concat
    ;; No mounted file systems.
@@ -319,6 +332,11 @@ the list of old buffers.")
 (defvar auto-revert-tail-pos 0
   "Position of last known end of file.")
 
+(defvar-local auto-revert--content-hash nil
+  "Hash of the visited file's contents, as last seen by Auto-Revert Mode.
+This is only maintained for files that are compared by hash, see
+`auto-revert-content-hash-threshold'.")
+
 (defun auto-revert-find-file-function ()
   (setq-local auto-revert-tail-pos
               (nth 7 (file-attributes buffer-file-name))))
@@ -542,6 +560,15 @@ will use an up-to-date value of `auto-revert-interval'"
 	       'auto-revert-notify-handler))))
     (when auto-revert-notify-watch-descriptor
       (setq auto-revert-notify-modified-p t)
+      ;; Record the current contents, to compare the next change with.
+      (when (auto-revert--use-content-hash-p)
+        (let ((buffer (current-buffer)))
+          (file-content-hash
+           buffer-file-name
+           (lambda (_file hash)
+             (when (buffer-live-p buffer)
+               (with-current-buffer buffer
+                 (setq auto-revert--content-hash hash)))))))
       (puthash
       auto-revert-notify-watch-descriptor
        (cons (current-buffer)
@@ -626,19 +653,55 @@ no more reverts are possible until the next call of
                      ;; A buffer w/o a file, like dired.
                      (and (null buffer-file-name)
                           (memq action '(created renamed deleted))))
-                ;; Mark buffer modified.
-                (setq auto-revert-notify-modified-p t)
-
-                ;; Revert the buffer now if we're not locked out.
-                (when (/= auto-revert-buffers-counter-lockedout
-                          auto-revert-buffers-counter)
-                  (auto-revert-handler)
-                  (setq auto-revert-buffers-counter-lockedout
-                        auto-revert-buffers-counter))
+                (if (and (eq action 'changed)
+                         (auto-revert--use-content-hash-p))
+                    ;; Only revert if the contents really changed.
+                    (auto-revert--check-content-hash)
+                  (auto-revert--notify-changed))
 
                 ;; No need to check other buffers.
                 (cl-return)))))))))
 
+(defun auto-revert--notify-changed ()
+  "Note that the current buffer's file changed, and maybe revert it."
+  ;; Mark buffer modified.
+  (setq auto-revert-notify-modified-p t)
+
+  ;; Revert the buffer now if we're not locked out.
+  (when (/= auto-revert-buffers-counter-lockedout
+            auto-revert-buffers-counter)
+    (auto-revert-handler)
+    (setq auto-revert-buffers-counter-lockedout
+          auto-revert-buffers-counter)))
+
+(defun auto-revert--use-content-hash-p ()
+  "Non-nil if changes of the current buffer's file are checked by hash.
+See `auto-revert-content-hash-threshold'."
+  (and auto-revert-content-hash-threshold
+       buffer-file-name
+       (not auto-revert-tail-mode)
+       (not (file-remote-p buffer-file-name))
+       (fboundp 'file-content-hash)
+       (let ((size (file-attribute-size (file-attributes buffer-file-name))))
+         (and size (>= size auto-revert-content-hash-threshold)))))
+
+(defun auto-revert--check-content-hash ()
+  "Hash the current buffer's file and revert if the contents changed.
+The file is hashed in the background.  When the hash equals
+`auto-revert--content-hash', only the modification time of the file
+changed, so just record that instead of reverting."
+  (let ((buffer (current-buffer)))
+    (file-content-hash
+     buffer-file-name
+     (lambda (_file hash)
+       (when (buffer-live-p buffer)
+         (with-current-buffer buffer
+           (if (and hash (equal hash auto-revert--content-hash))
+               (unless (buffer-modified-p)
+                 (set-visited-file-modtime))
+             (setq auto-revert--content-hash hash)
+             (auto-revert--notify-changed))))))))
+
 (defun auto-revert-active-p ()
   "Check if auto-revert is active (in current buffer or globally)."
   (or auto-revert-mode
@@ -52,7 +52,10 @@
   :group 'battery)
 
 (defcustom battery-status-function
-  (cond ((and (eq system-type 'gnu/linux)
+  (cond ((and (fboundp 'battery-status-native)
+	      (battery-status-native))
+	 #'battery-native)
+	((and (eq system-type 'gnu/linux)
 	      (file-readable-p "/proc/apm"))
 	 #'battery-linux-proc-apm)
 	((and (eq system-type 'gnu/linux)
@@ -728,6 +731,57 @@ The following %-sequences are provided:
 	  (cons ?t (or remaining-time "N/A")))))
 
 
+;;; Native interface.
+
+(declare-function battery-status-native "sysinfo.rs" ())
+
+(defun battery-native ()
+  "Get battery status information from the operating system.
+This uses `battery-status-native', which reads the status the same
+way on all systems that support it, without external programs.
+
+The following %-sequences are provided:
+%L AC line status (verbose)
+%B Battery status (verbose)
+%b Battery status, empty means high, `-' means low,
+   `!' means critical, and `+' means charging
+%p Battery load percentage
+%r Current rate (in watts)
+%d Temperature (in degrees Celsius)
+%s Remaining time (to charge or discharge) in seconds
+%m Remaining time (to charge or discharge) in minutes
+%h Remaining time (to charge or discharge) in hours
+%t Remaining time (to charge or discharge) in the form `h:min'"
+  (let* ((status (battery-status-native))
+	 (state (plist-get status :status))
+	 (line (plist-get status :ac-line))
+	 (percentage (plist-get status :percentage))
+	 (rate (plist-get status :rate))
+	 (temperature (plist-get status :temperature))
+	 (seconds (plist-get status :remaining))
+	 (minutes (and seconds (/ seconds 60))))
+    (list (cons ?L (cond ((eq line 'ac) "AC")
+			 ((eq line 'battery) "BAT")
+			 (t "N/A")))
+	  (cons ?B (if state
+		       (replace-regexp-in-string "-" " " (symbol-name state))
+		     "N/A"))
+	  (cons ?b (cond ((eq state 'charging) "+")
+			 ((null percentage) "")
+			 ((< percentage battery-load-critical) "!")
+			 ((< percentage battery-load-low) "-")
+			 (t "")))
+	  (cons ?p (if percentage (format "%.0f" percentage) "N/A"))
+	  (cons ?r (if rate (format "%.1f" rate) "N/A"))
+	  (cons ?d (if temperature (format "%.1f" temperature) "N/A"))
+	  (cons ?s (if seconds (number-to-string seconds) "N/A"))
+	  (cons ?m (if minutes (number-to-string minutes) "N/A"))
+	  (cons ?h (if minutes (number-to-string (/ minutes 60)) "N/A"))
+	  (cons ?t (if minutes
+		       (format "%d:%02d" (/ minutes 60) (% minutes 60))
+		     "N/A")))))
+
+
 ;;; Private functions.
 
 (defun battery-format (format alist)
@@ -250,6 +250,7 @@ or the deprecated form (BOOKMARK-NAME PARAM-ALIST).
   (position . POS)
   (front-context-string . STR-AFTER-POS)
   (rear-context-string  . STR-BEFORE-POS)
+  (buffer-size . SIZE)
   (handler . HANDLER)
   (annotation . ANNOTATION)
 
@@ -257,6 +258,7 @@ or the deprecated form (BOOKMARK-NAME PARAM-ALIST).
  POS is the bookmarked buffer position.
  STR-AFTER-POS is buffer text that immediately follows POS.
  STR-BEFORE-POS is buffer text that immediately precedes POS.
+ SIZE is the size of the buffer when the bookmark was set.
  ANNOTATION is a string that describes the bookmark.
    See options `bookmark-use-annotations' and
    `bookmark-automatically-show-annotations'.
@@ -562,6 +564,7 @@ If POSN is non-nil, record POSN as the point instead of `(point)'."
                                    (point)
                                    (- (point) bookmark-search-size))
                                   nil))))
+    ,@(unless no-context `((buffer-size . ,(buffer-size))))
     (position . ,(or posn (point)))))
 
 
@@ -1203,16 +1206,13 @@ Changes current buffer and point and returns nil, or signals a `file-error'."
       ((and buf (get-buffer buf)))
       (t ;; If not, raise error.
        (signal 'bookmark-error-no-filename (list 'stringp file)))))
-    (if place (goto-char place))
-    ;; Go searching forward first.  Then, if forward-str exists and
-    ;; was found in the file, we can search backward for behind-str.
-    ;; Rationale is that if text was inserted between the two in the
-    ;; file, it's better to be put before it so you can read it,
-    ;; rather than after and remain perhaps unaware of the changes.
-    (when (and forward-str (search-forward forward-str (point-max) t))
-      (goto-char (match-beginning 0)))
-    (when (and behind-str (search-backward behind-str (point-min) t))
-      (goto-char (match-end 0)))
+    ;; Find the place where the context strings are, nearest to the
+    ;; saved position.  If the file changed so much that they are
+    ;; gone, the position is adjusted for the change of its size.
+    (goto-char (location-reconcile
+                (list (or place (point))
+                      (bookmark-prop-get bmk-record 'buffer-size)
+                      forward-str behind-str)))
     nil))
 
 ;;;###autoload
@@ -466,6 +466,28 @@ If value is t, all buffers are restored immediately."
   :group 'desktop
   :version "22.1")
 
+(defcustom desktop-binary-buffers nil
+  "Non-nil means save the buffer section of the desktop in binary form.
+The buffers are then written to a separate file, named after the
+desktop file with \".bin\" appended, which is much faster to write
+and to read back than Lisp code when there are many buffers.  The
+desktop file itself loads that file.  This requires desktop file
+format version 208."
+  :type 'boolean
+  :group 'desktop
+  :version "27.1")
+
+(defcustom desktop-save-text-properties nil
+  "Text properties of buffers to save in the desktop.
+This is a list of property names, or t to save all text properties.
+Text properties are only saved if `desktop-binary-buffers' is non-nil,
+since they can't be reliably printed and read back as Lisp code."
+  :type '(choice (const :tag "None" nil)
+                 (const :tag "All" t)
+                 (repeat :tag "Properties" symbol))
+  :group 'desktop
+  :version "27.1")
+
 (defcustom desktop-lazy-verbose t
   "Verbose reporting of lazily created buffers."
   :type 'boolean
@@ -621,6 +643,12 @@ See also `desktop-minor-mode-table'.")
 DIRNAME omitted or nil means use `desktop-dirname'."
   (expand-file-name desktop-base-file-name (or dirname desktop-dirname)))
 
+(defun desktop-full-binary-file-name (&optional dirname)
+  "Return the full name of the binary buffers file in DIRNAME.
+DIRNAME omitted or nil means use `desktop-dirname'.
+See `desktop-binary-buffers'."
+  (concat (desktop-full-file-name dirname) ".bin"))
+
 (defun desktop-full-lock-name (&optional dirname)
   "Return the full name of the desktop lock file in DIRNAME.
 DIRNAME omitted or nil means use `desktop-dirname'."
@@ -1042,7 +1070,8 @@ without further confirmation."
   (setq desktop-dirname (file-name-as-directory (expand-file-name dirname)))
   (save-excursion
     (let ((eager desktop-restore-eager)
-	  (new-modtime (nth 5 (file-attributes (desktop-full-file-name)))))
+	  (new-modtime (nth 5 (file-attributes (desktop-full-file-name))))
+	  binary-data)
       (when
 	  (or (not new-modtime)		; nothing to overwrite
 	      (equal desktop-file-modtime new-modtime)
@@ -1106,24 +1135,41 @@ without further confirmation."
 	     " kill-ring))\n"))
 
 	  (insert "\n;; Buffer section -- buffers listed in same order as in buffer list:\n")
-	  (dolist (l (mapcar 'desktop-buffer-info (buffer-list)))
-	    (let ((base (pop l)))
-	      (when (apply 'desktop-save-buffer-p l)
-		(insert "("
-			(if (or (not (integerp eager))
-				(if (zerop eager)
-				    nil
-				  (setq eager (1- eager))))
-			    "desktop-create-buffer"
-			  "desktop-append-buffer-args")
-			" "
-			(format "%d" desktop-io-file-version))
-		;; If there's a non-empty base name, we save it instead of the buffer name
-		(when (and base (not (string= base "")))
-		  (setcar (nthcdr 1 l) base))
-		(dolist (e l)
-		  (insert "\n  " (desktop-value-to-string e)))
-		(insert ")\n\n"))))
+	  (let ((binary (and desktop-binary-buffers
+			     (fboundp 'desktop-binary-encode)
+			     (eq desktop-io-file-version 208)))
+		records)
+	    (dolist (buffer (buffer-list))
+	      (let* ((l (desktop-buffer-info buffer))
+		     (base (pop l)))
+		(when (apply 'desktop-save-buffer-p l)
+		  (let ((function
+			 (if (or (not (integerp eager))
+				 (if (zerop eager)
+				     nil
+				   (setq eager (1- eager))))
+			     'desktop-create-buffer
+			   'desktop-append-buffer-args)))
+		    ;; If there's a non-empty base name, we save it instead of the buffer name
+		    (when (and base (not (string= base "")))
+		      (setcar (nthcdr 1 l) base))
+		    (if binary
+			(push `(,function ,desktop-io-file-version ,@l
+					  ,(desktop-buffer-state
+					    buffer desktop-save-text-properties))
+			      records)
+		      (insert "(" (symbol-name function) " "
+			      (format "%d" desktop-io-file-version))
+		      (dolist (e l)
+			(insert "\n  " (desktop-value-to-string e)))
+		      (insert ")\n\n"))))))
+	    (when binary
+	      (setq binary-data (desktop-binary-encode (nreverse records)))
+	      ;; The checksum makes the desktop file change whenever the
+	      ;; buffers do, see `only-if-changed' below.
+	      (insert (format "(desktop-read-binary-buffers %S %S)\n"
+			      (desktop-full-binary-file-name)
+			      (md5 binary-data)))))
 
 	  (setq default-directory desktop-dirname)
 	  ;; When auto-saving, avoid writing if nothing has changed since the last write.
@@ -1141,6 +1187,10 @@ without further confirmation."
 			     (point))))
 		 (checksum (and beg (md5 (current-buffer) beg (point-max) 'emacs-mule))))
 	    (unless (and checksum (equal checksum desktop-file-checksum))
+	      (when binary-data
+		(let ((coding-system-for-write 'no-conversion))
+		  (write-region binary-data nil (desktop-full-binary-file-name)
+				nil 'nomessage)))
 	      (let ((coding-system-for-write 'emacs-mule))
 		(write-region (point-min) (point-max) (desktop-full-file-name) nil 'nomessage))
 	      (setq desktop-file-checksum checksum)
@@ -1154,10 +1204,13 @@ without further confirmation."
 This function also sets `desktop-dirname' to nil."
   (interactive)
   (when desktop-dirname
-    (let ((filename (desktop-full-file-name)))
+    (let ((filename (desktop-full-file-name))
+          (binary-filename (desktop-full-binary-file-name)))
       (setq desktop-dirname nil)
       (when (file-exists-p filename)
-        (delete-file filename)))))
+        (delete-file filename))
+      (when (file-exists-p binary-filename)
+        (delete-file binary-filename)))))
 
 (defvar desktop-buffer-args-list nil
   "List of args for `desktop-create-buffer'.")
@@ -1460,6 +1513,7 @@ and try to load that."
      &optional
      buffer-locals
      compacted-vars
+     buffer-state
      &rest _unsupported)
 
   (setq desktop-io-file-version file-version)
@@ -1570,9 +1624,49 @@ and try to load that."
 	      (let*
 		  ((var (car record))
 		   (deser-fun (nth 2 (assq var desktop-var-serdes-funs))))
-		(if deser-fun (set var (funcall deser-fun (cadr record))))))))
+		(if deser-fun (set var (funcall deser-fun (cadr record)))))))
+	  (when buffer-state
+	    (desktop-restore-buffer-state buffer-state)))
 	result))))
 
+(defun desktop-restore-buffer-state (state)
+  "Restore the STATE of the current buffer made by `desktop-buffer-state'."
+  (let ((size (1+ (buffer-size))))
+    (when (plist-get state :mark-ring)
+      (setq mark-ring
+	    (mapcar (lambda (pos)
+		      (copy-marker (min pos size)))
+		    (plist-get state :mark-ring))))
+    (let ((inhibit-read-only t)
+	  (modified (buffer-modified-p)))
+      (with-silent-modifications
+	(dolist (interval (plist-get state :text-properties))
+	  (let ((start (nth 0 interval))
+		(end (min (nth 1 interval) size)))
+	    (when (< start end)
+	      (add-text-properties start end (nth 2 interval))))))
+      (restore-buffer-modified-p modified))
+    (let ((restriction (plist-get state :restriction)))
+      (when restriction
+	(narrow-to-region (min (car restriction) size)
+			  (min (cdr restriction) size))))))
+
+(defun desktop-read-binary-buffers (file &optional checksum)
+  "Restore the buffers saved in binary form in FILE.
+This is called from desktop files written with `desktop-binary-buffers'
+non-nil.  If CHECKSUM is non-nil, it is the MD5 of the contents of
+FILE; if that doesn't match, FILE was changed behind our back and is
+ignored."
+  (let ((data (with-temp-buffer
+		(set-buffer-multibyte nil)
+		(insert-file-contents-literally file)
+		(buffer-string))))
+    (if (and checksum (not (equal (md5 data) checksum)))
+	(message "Desktop: %s does not match the desktop file, ignoring it" file)
+      (dolist (record (desktop-binary-decode data))
+	(when (memq (car record) '(desktop-create-buffer desktop-append-buffer-args))
+	  (apply (car record) (cdr record)))))))
+
 ;; ----------------------------------------------------------------------------
 ;; Backward compatibility -- update parameters to 205 standards.
 (defun desktop-buffer (buffer-filename buffer-name buffer-majormode
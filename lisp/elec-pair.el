@@ -103,6 +103,17 @@ quotes."
   :group 'electricity
   :type 'boolean)
 
+(defcustom electric-pair-balance-scan-limit 100000
+  "How far after point to look for the ends of lists when balancing.
+Balancing, see `electric-pair-preserve-balance', looks at how the
+lists that enclose point are closed.  Close parentheses more than
+this many characters after point are not looked at.  A value of
+nil means to look as far as the end of the buffer."
+  :version "27.1"
+  :group 'electricity
+  :type '(choice (const :tag "No limit" nil)
+                 integer))
+
 (defcustom electric-pair-delete-adjacent-pairs t
   "If non-nil, backspacing an open paren also deletes adjacent closer.
 
@@ -273,10 +284,19 @@ when to fallback to `parse-partial-sexp'."
 ;; for different types of parentheses, so that having your {}'s
 ;; unbalanced doesn't keep `electric-pair-mode' from balancing your
 ;; ()'s and your []'s.
+(defun electric-pair--string-or-comment-end (start)
+  "Return the end of the string or comment that starts at START."
+  (save-excursion
+    (let ((state (parse-partial-sexp start (point-max) nil nil
+                                     (syntax-ppss start) 'syntax-table)))
+      (parse-partial-sexp (point) (point-max) nil nil state 'syntax-table)
+      (point))))
+
 (defun electric-pair--balance-info (direction string-or-comment)
   "Examine lists forward or backward according to DIRECTION's sign.
 
-STRING-OR-COMMENT is info suitable for running `parse-partial-sexp'.
+STRING-OR-COMMENT is the start of the string or comment point is
+in, if it is to be parsed with `electric-pair-text-syntax-table'.
 
 Return a cons of two descriptions (MATCHED-P . PAIR) for the
 innermost and outermost lists that enclose point.  The outermost
@@ -284,83 +304,58 @@ list enclosing point is either the first top-level or first
 mismatched list found by listing up.
 
 If the outermost list is matched, don't rely on its PAIR.
-If point is not enclosed by any lists, return ((t) . (t))."
-  (let* (innermost
-         outermost
-         (table (if string-or-comment
+If point is not enclosed by any lists, return ((t) . (t)).
+
+Lists are not looked for beyond `electric-pair-balance-scan-limit'
+characters after point, nor after the end of the string or
+comment."
+  (let* ((table (if string-or-comment
                     electric-pair-text-syntax-table
                   (syntax-table)))
-         (at-top-level-or-equivalent-fn
-          ;; called when `scan-sexps' ran perfectly, when it found
-          ;; a parenthesis pointing in the direction of travel.
-          ;; Also when travel started inside a comment and exited it.
-          #'(lambda ()
-              (setq outermost (list t))
-              (unless innermost
-                (setq innermost (list t)))))
-         (ended-prematurely-fn
-          ;; called when `scan-sexps' crashed against a parenthesis
-          ;; pointing opposite the direction of travel.  After
-          ;; traversing that character, the idea is to travel one sexp
-          ;; in the opposite direction looking for a matching
-          ;; delimiter.
-          #'(lambda ()
-              (let* ((pos (point))
-                     (matched
-                      (save-excursion
-                        (cond ((< direction 0)
-                               (condition-case nil
-                                   (eq (char-after pos)
-                                       (electric-pair--with-uncached-syntax
-                                           (table)
-                                         (matching-paren
-                                          (char-before
-                                           (scan-sexps (point) 1)))))
-                                 (scan-error nil)))
-                              (t
-                               ;; In this case, no need to use
-                               ;; `scan-sexps', we can use some
-                               ;; `electric-pair--syntax-ppss' in this
-                               ;; case (which uses the quicker
-                               ;; `syntax-ppss' in some cases)
-                               (let* ((ppss (electric-pair--syntax-ppss
-                                             (1- (point))))
-                                      (start (car (last (nth 9 ppss))))
-                                      (opener (char-after start)))
-                                 (and start
-                                      (eq (char-before pos)
-                                          (or (with-syntax-table table
-                                                (matching-paren opener))
-                                              opener))))))))
-                     (actual-pair (if (> direction 0)
-                                      (char-before (point))
-                                    (char-after (point)))))
-                (unless innermost
-                  (setq innermost (cons matched actual-pair)))
-                (unless matched
-                  (setq outermost (cons matched actual-pair)))))))
-    (save-excursion
-      (while (not outermost)
-        (condition-case err
-            (electric-pair--with-uncached-syntax (table)
-              (scan-sexps (point) (if (> direction 0)
-                                      (point-max)
-                                    (- (point-max))))
-              (funcall at-top-level-or-equivalent-fn))
-          (scan-error
-           (cond ((or
-                   ;; some error happened and it is not of the "ended
-                   ;; prematurely" kind...
-                   (not (string-match "ends prematurely" (nth 1 err)))
-                   ;; ... or we were in a comment and just came out of
-                   ;; it.
-                   (and string-or-comment
-                        (not (nth 8 (syntax-ppss)))))
-                  (funcall at-top-level-or-equivalent-fn))
-                 (t
-                  ;; exit the sexp
-                  (goto-char (nth 3 err))
-                  (funcall ended-prematurely-fn)))))))
+         (end (if string-or-comment
+                  (electric-pair--string-or-comment-end string-or-comment)
+                (point-max)))
+         (limit (if electric-pair-balance-scan-limit
+                    (min end (+ (point) electric-pair-balance-scan-limit))
+                  end))
+         (state (if string-or-comment
+                    (electric-pair--syntax-ppss (point))
+                  (syntax-ppss)))
+         (lists (electric-pair--with-uncached-syntax (table)
+                  (scan-enclosing-lists (point) limit state)))
+         (matching (lambda (pos)
+                     (with-syntax-table table
+                       (matching-paren (char-after pos)))))
+         innermost
+         outermost)
+    (while (and lists (not outermost))
+      (let* ((open (caar lists))
+             (close (cdar lists))
+             ;; A description of the list, or nil if there is no
+             ;; list to describe in DIRECTION, as when leaving it
+             ;; lands at top level.
+             (info (if (> direction 0)
+                       (and close
+                            (cons (and open
+                                       (eq (char-after close)
+                                           (or (funcall matching open)
+                                               (char-after open))))
+                                  (char-after close)))
+                     (and open
+                          (cons (and close
+                                     (eq (char-after open)
+                                         (funcall matching close)))
+                                (char-after open))))))
+        (setq lists (and info (cdr lists)))
+        (when info
+          (unless innermost
+            (setq innermost info))
+          (unless (car info)
+            (setq outermost info)))))
+    (unless outermost
+      (setq outermost (list t))
+      (unless innermost
+        (setq innermost (list t))))
     (cons innermost outermost)))
 
 (defvar electric-pair-string-bound-function 'point-max
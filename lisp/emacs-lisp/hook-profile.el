@@ -0,0 +1,96 @@
+;;; hook-profile.el --- Find the slow functions on hooks  -*- lexical-binding: t -*-
+
+;; Copyright (C) 2018 Free Software Foundation, Inc.
+
+;; Keywords: lisp, tools
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Commentary:
+
+;; While `hook-profiling' is non-nil, `run-hooks' and its relatives
+;; count and time the calls of each function on each hook, in
+;; hookprof.rs.  `hook-profile-start' starts recording, and
+;; `hook-profile-report' lists the functions that took the most time,
+;; which helps to find out which function on `post-command-hook' or
+;; `after-change-functions' makes typing slow.
+
+;;; Code:
+
+;;;###autoload
+(defun hook-profile-start ()
+  "Start recording the calls of the functions on hooks.
+What was recorded before is forgotten.  Use `hook-profile-report'
+to see the results, and `hook-profile-stop' to stop."
+  (interactive)
+  (hook-profile-reset)
+  (setq hook-profiling t))
+
+(defun hook-profile-stop ()
+  "Stop recording the calls of the functions on hooks."
+  (interactive)
+  (setq hook-profiling nil))
+
+(define-derived-mode hook-profile-mode tabulated-list-mode "Hook Profile"
+  "Major mode for the list of functions on hooks made by `hook-profile-report'."
+  (setq tabulated-list-format
+        [("Hook" 28 t)
+         ("Function" 36 t)
+         ("Calls" 8 hook-profile--sort-calls :right-align t)
+         ("Seconds" 10 hook-profile--sort-seconds :right-align t)
+         ("Average ms" 10 nil :right-align t)])
+  (setq tabulated-list-padding 1)
+  (add-hook 'tabulated-list-revert-hook #'hook-profile--refresh nil t)
+  (tabulated-list-init-header))
+
+(defun hook-profile--sort-calls (a b)
+  (< (nth 2 (car a)) (nth 2 (car b))))
+
+(defun hook-profile--sort-seconds (a b)
+  (< (nth 3 (car a)) (nth 3 (car b))))
+
+(defun hook-profile--refresh ()
+  "Fill `tabulated-list-entries' with what was recorded."
+  (setq tabulated-list-entries
+        (mapcar (lambda (entry)
+                  (pcase-let ((`(,hook ,function ,count ,seconds) entry))
+                    (list entry
+                          (vector (format "%s" hook)
+                                  (if (symbolp function)
+                                      (symbol-name function)
+                                    (format "%S" function))
+                                  (number-to-string count)
+                                  (format "%.3f" seconds)
+                                  (format "%.3f" (/ (* 1000 seconds) count))))))
+                (hook-profile-data))))
+
+;;;###autoload
+(defun hook-profile-report ()
+  "Show the time taken by the functions on hooks.
+The functions are listed with the hook that ran them, how many
+times they were called and how long that took in all, the slowest
+first.  Set `hook-profiling' or use `hook-profile-start' to record
+these numbers."
+  (interactive)
+  (with-current-buffer (get-buffer-create "*Hook Profile*")
+    (hook-profile-mode)
+    (hook-profile--refresh)
+    (tabulated-list-print)
+    (pop-to-buffer (current-buffer))))
+
+(provide 'hook-profile)
+
+;;; hook-profile.el ends here
@@ -124,6 +124,12 @@ than that of a simplified version:
      (concat (car paren)
              (mapconcat 'regexp-quote strings \"\\\\|\")
              (cdr paren))))"
+  (if (fboundp 'regexp-opt-native)
+      (regexp-opt-native strings paren)
+    (regexp-opt--lisp strings paren)))
+
+(defun regexp-opt--lisp (strings &optional paren)
+  "Lisp implementation of `regexp-opt', which see."
   (save-match-data
     ;; Recurse on the sorted list.
     (let* ((max-lisp-eval-depth 10000)
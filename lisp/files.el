@@ -1019,13 +1019,31 @@ customize the variable `user-emacs-directory-warning'."
 					errtype user-emacs-directory)))))
        bestname))))
 
+(defcustom persistent-store-file nil
+  "File of the default persistent store, or nil for the standard place.
+The persistent store is where packages like recentf and savehist
+save their data between sessions, see `persistent-store-put'.
+If nil, the store is the file \"persistent-store\" in
+`user-emacs-directory'."
+  :type '(choice (const :tag "Default" nil) file)
+  :group 'initialization
+  :version "27.1")
+
+(defun persistent-store-default-file ()
+  "Return the file name of the default persistent store.
+This is `persistent-store-file' if non-nil, otherwise the file
+\"persistent-store\" in `user-emacs-directory'."
+  (or persistent-store-file
+      (locate-user-emacs-file "persistent-store")))
+
 
 (defun executable-find (command)
   "Search for COMMAND in `exec-path' and return the absolute file name.
 Return nil if COMMAND is not found anywhere in `exec-path'."
-  ;; Use 1 rather than file-executable-p to better match the behavior of
-  ;; call-process.
-  (locate-file command exec-path exec-suffixes 1))
+  ;; This is equivalent to
+  ;; (locate-file command exec-path exec-suffixes 1)
+  ;; but caches its results until the search path changes.
+  (executable-find-internal command))
 
 (defun load-library (library)
   "Load the Emacs Lisp library named LIBRARY.
@@ -2000,6 +2018,20 @@ When nil, never request confirmation."
   :version "22.1"
   :type '(choice integer (const :tag "Never request confirmation" nil)))
 
+(defcustom supersession-content-hash-limit 10000000
+  "Maximum size of visited files whose contents are compared.
+Before the first change to a buffer visiting a file, Emacs checks
+whether the file changed on disk since it was visited or saved,
+see `file-superseded-p'.  The modification time alone can report
+changes that aren't there, for instance when a tool rewrites the
+file with the same contents.  For local files up to this size, a
+hash of the contents is kept, and a file whose modification time
+changed but whose contents did not isn't reported.
+When nil, only the modification time and size are compared."
+  :group 'files
+  :version "27.1"
+  :type '(choice integer (const :tag "Compare modification times only" nil)))
+
 (defcustom out-of-memory-warning-percentage nil
   "Warn if file size exceeds this percentage of available free memory.
 When nil, never issue warning.  Beware: This probably doesn't do what you
@@ -3357,63 +3389,10 @@ is neither nil nor t, we do the same, except that any settings of
 `mode' and `coding' are ignored.  If HANDLE-MODE is t, we ignore
 all settings in the line except for `mode', which \(if present) we
 return as the symbol specifying the mode."
-  (catch 'malformed-line
-    (save-excursion
-      (goto-char (point-min))
-      (let ((end (set-auto-mode-1))
-	    result)
-	(cond ((not end)
-	       nil)
-	      ((looking-at "[ \t]*\\([^ \t\n\r:;]+\\)\\([ \t]*-\\*-\\)")
-	       ;; Simple form: "-*- MODENAME -*-".
-	       (if (eq handle-mode t)
-		   (intern (concat (match-string 1) "-mode"))))
-	      (t
-	       ;; Hairy form: '-*-' [ <variable> ':' <value> ';' ]* '-*-'
-	       ;; (last ";" is optional).
-	       ;; If HANDLE-MODE is t, just check for `mode'.
-	       ;; Otherwise, parse the -*- line into the RESULT alist.
-	       (while (not (or (and (eq handle-mode t) result)
-                               (>= (point) end)))
-		 (unless (looking-at hack-local-variable-regexp)
-		   (message "Malformed mode-line: %S"
-                            (buffer-substring-no-properties (point) end))
-		   (throw 'malformed-line nil))
-		 (goto-char (match-end 0))
-		 ;; There used to be a downcase here,
-		 ;; but the manual didn't say so,
-		 ;; and people want to set var names that aren't all lc.
-		 (let* ((key (intern (match-string 1)))
-			(val (save-restriction
-			       (narrow-to-region (point) end)
-			       (let ((read-circle nil))
-				 (read (current-buffer)))))
-			;; It is traditional to ignore
-			;; case when checking for `mode' in set-auto-mode,
-			;; so we must do that here as well.
-			;; That is inconsistent, but we're stuck with it.
-			;; The same can be said for `coding' in set-auto-coding.
-			(keyname (downcase (symbol-name key))))
-                   (cond
-                    ((eq handle-mode t)
-                     (and (equal keyname "mode")
-                          (setq result
-                                (intern (concat (downcase (symbol-name val))
-                                                "-mode")))))
-                    ((equal keyname "coding"))
-                    (t
-                     (when (or (not handle-mode)
-                               (not (equal keyname "mode")))
-                       (condition-case nil
-                           (push (cons (cond ((eq key 'eval) 'eval)
-                                             ;; Downcase "Mode:".
-                                             ((equal keyname "mode") 'mode)
-                                             (t (indirect-variable key)))
-                                       val)
-                                 result)
-                         (error nil)))))
-		   (skip-chars-forward " \t;")))
-	       result))))))
+  (save-excursion
+    (goto-char (point-min))
+    (let ((end (set-auto-mode-1)))
+      (and end (file-local-variables-prop-line (point) end handle-mode)))))
 
 (defun hack-local-variables-filter (variables dir-name)
   "Filter local variable settings, querying the user if necessary.
@@ -3525,106 +3504,19 @@ local variables, but directory-local variables may still be applied."
                                      handle-mode))
 		       (eq handle-mode t)))
 	;; Look for "Local variables:" line in last page.
-	(save-excursion
-	  (goto-char (point-max))
-	  (search-backward "\n\^L" (max (- (point-max) 3000) (point-min))
-			   'move)
-	  (when (let ((case-fold-search t))
-		  (search-forward "Local Variables:" nil t))
-	    (skip-chars-forward " \t")
-	    ;; suffix is what comes after "local variables:" in its line.
-	    ;; prefix is what comes before "local variables:" in its line.
-	    (let ((suffix
-		   (concat
-		    (regexp-quote (buffer-substring (point)
-						    (line-end-position)))
-		    "$"))
-		  (prefix
-		   (concat "^" (regexp-quote
-				(buffer-substring (line-beginning-position)
-						  (match-beginning 0))))))
-
-	      (forward-line 1)
-	      (let ((startpos (point))
-		    endpos
-		    (thisbuf (current-buffer)))
-		(save-excursion
-		  (unless (let ((case-fold-search t))
-			    (re-search-forward
-			     (concat prefix "[ \t]*End:[ \t]*" suffix)
-			     nil t))
-		    ;; This used to be an error, but really all it means is
-		    ;; that this may simply not be a local-variables section,
-		    ;; so just ignore it.
-		    (message "Local variables list is not properly terminated"))
-		  (beginning-of-line)
-		  (setq endpos (point)))
-
-		(with-temp-buffer
-		  (insert-buffer-substring thisbuf startpos endpos)
-		  (goto-char (point-min))
-		  (subst-char-in-region (point) (point-max) ?\^m ?\n)
-		  (while (not (eobp))
-		    ;; Discard the prefix.
-		    (if (looking-at prefix)
-			(delete-region (point) (match-end 0))
-		      (error "Local variables entry is missing the prefix"))
-		    (end-of-line)
-		    ;; Discard the suffix.
-		    (if (looking-back suffix (line-beginning-position))
-			(delete-region (match-beginning 0) (point))
-		      (error "Local variables entry is missing the suffix"))
-		    (forward-line 1))
-		  (goto-char (point-min))
-
-		  (while (not (or (eobp)
-                                  (and (eq handle-mode t) result)))
-		    ;; Find the variable name;
-		    (unless (looking-at hack-local-variable-regexp)
-                      (error "Malformed local variable line: %S"
-                             (buffer-substring-no-properties
-                              (point) (line-end-position))))
-                    (goto-char (match-end 1))
-		    (let* ((str (match-string 1))
-			   (var (intern str))
-			   val val2)
-		      (and (equal (downcase (symbol-name var)) "mode")
-			   (setq var 'mode))
-		      ;; Read the variable value.
-		      (skip-chars-forward "^:")
-		      (forward-char 1)
-		      (let ((read-circle nil))
-			(setq val (read (current-buffer))))
-		      (if (eq handle-mode t)
-			  (and (eq var 'mode)
-			       ;; Specifying minor-modes via mode: is
-			       ;; deprecated, but try to reject them anyway.
-			       (not (string-match
-				     "-minor\\'"
-				     (setq val2 (downcase (symbol-name val)))))
-			       (setq result (intern (concat val2 "-mode"))))
-			(cond ((eq var 'coding))
-			      ((eq var 'lexical-binding)
-			       (unless hack-local-variables--warned-lexical
-				 (setq hack-local-variables--warned-lexical t)
-				 (display-warning
-                                  'files
-                                  (format-message
-                                   "%s: `lexical-binding' at end of file unreliable"
-                                   (file-name-nondirectory
-                                    ;; We are called from
-                                    ;; 'with-temp-buffer', so we need
-                                    ;; to use 'thisbuf's name in the
-                                    ;; warning message.
-                                    (or (buffer-file-name thisbuf) ""))))))
-                              ((and (eq var 'mode) handle-mode))
-			      (t
-			       (ignore-errors
-				 (push (cons (if (eq var 'eval)
-						 'eval
-					       (indirect-variable var))
-					     val) result))))))
-		    (forward-line 1))))))))
+	(let ((block (file-local-variables-block handle-mode)))
+	  (if (eq handle-mode t)
+	      (setq result block)
+	    (when (assq 'lexical-binding block)
+	      (unless hack-local-variables--warned-lexical
+		(setq hack-local-variables--warned-lexical t)
+		(display-warning
+		 'files
+		 (format-message
+		  "%s: `lexical-binding' at end of file unreliable"
+		  (file-name-nondirectory (or buffer-file-name ""))))))
+	    (setq result (nconc (assq-delete-all 'lexical-binding block)
+				result)))))
       ;; Now we've read all the local variables.
       ;; If HANDLE-MODE is t, return whether the mode was specified.
       (if (eq handle-mode t) result
@@ -4049,6 +3941,16 @@ Return the new class name, which is a symbol named DIR."
   :type 'boolean
   :group 'find-file)
 
+(defcustom dir-locals-use-editorconfig nil
+  "Non-nil means also take directory-local variables from `.editorconfig' files.
+The EditorConfig properties that correspond to Emacs variables are
+applied like the variables of a `dir-locals-file', which takes
+precedence over them.  See `dir-locals-resolve' for the properties
+that are supported."
+  :version "27.1"
+  :type 'boolean
+  :group 'find-file)
+
 (defvar hack-dir-local-variables--warned-coding nil)
 
 (defun hack-dir-local-variables ()
@@ -4064,33 +3966,41 @@ This does nothing if either `enable-local-variables' or
 		 (not (file-remote-p (or (buffer-file-name)
 					 default-directory)))))
     ;; Find the variables file.
-    (let ((dir-or-cache (dir-locals-find-file
-                         (or (buffer-file-name) default-directory)))
-	  (class nil)
-	  (dir-name nil))
+    (let* ((file (or (buffer-file-name) default-directory))
+           (dir-or-cache (dir-locals-find-file file))
+	   (dir-name nil)
+	   (variables nil))
       (cond
-       ((stringp dir-or-cache)
-	(setq dir-name dir-or-cache
-	      class (dir-locals-read-from-dir dir-or-cache)))
        ((consp dir-or-cache)
 	(setq dir-name (nth 0 dir-or-cache))
-	(setq class (nth 1 dir-or-cache))))
-      (when class
-	(let ((variables
-	       (dir-locals-collect-variables
-		(dir-locals-get-class-variables class) dir-name nil)))
-	  (when variables
-	    (dolist (elt variables)
-	      (if (eq (car elt) 'coding)
-                  (unless hack-dir-local-variables--warned-coding
-                    (setq hack-dir-local-variables--warned-coding t)
-                    (display-warning 'files
-                                     "Coding cannot be specified by dir-locals"))
-		(unless (memq (car elt) '(eval mode))
-		  (setq dir-local-variables-alist
-			(assq-delete-all (car elt) dir-local-variables-alist)))
-		(push elt dir-local-variables-alist)))
-	    (hack-local-variables-filter variables dir-name)))))))
+	(setq variables (dir-locals-collect-variables
+			 (dir-locals-get-class-variables (nth 1 dir-or-cache))
+			 dir-name nil)))
+       ;; `dir-locals-resolve' doesn't look at remote directories.
+       ((and (stringp dir-or-cache) (file-remote-p dir-or-cache))
+	(setq dir-name dir-or-cache)
+	(setq variables (dir-locals-collect-variables
+			 (dir-locals-get-class-variables
+			  (dir-locals-read-from-dir dir-or-cache))
+			 dir-name nil)))
+       ((or dir-or-cache dir-locals-use-editorconfig)
+	(let ((resolved (with-demoted-errors "Error reading dir-locals: %S"
+			  (dir-locals-resolve file major-mode
+					      dir-locals-use-editorconfig))))
+	  (setq dir-name (car resolved))
+	  (setq variables (cdr resolved)))))
+      (when variables
+	(dolist (elt variables)
+	  (if (eq (car elt) 'coding)
+              (unless hack-dir-local-variables--warned-coding
+                (setq hack-dir-local-variables--warned-coding t)
+                (display-warning 'files
+                                 "Coding cannot be specified by dir-locals"))
+	    (unless (memq (car elt) '(eval mode))
+	      (setq dir-local-variables-alist
+		    (assq-delete-all (car elt) dir-local-variables-alist)))
+	    (push elt dir-local-variables-alist)))
+	(hack-local-variables-filter variables dir-name)))))
 
 (defun hack-dir-local-variables-non-file-buffer ()
   "Apply directory-local variables to a non-file buffer.
@@ -6305,6 +6215,31 @@ by `sh' are supported."
   :type 'string
   :group 'dired)
 
+(defcustom wildcard-match-hidden-files t
+  "Non-nil means wildcards match file names that start with a dot.
+If nil, `*', `?' and character classes don't match the leading dot
+of a file name, as in the shell, and `**' doesn't descend into
+hidden directories.  This affects `file-expand-wildcards' for local
+files."
+  :type 'boolean
+  :group 'files
+  :version "27.1")
+
+(defcustom wildcard-ignore-case (memq system-type '(windows-nt ms-dos))
+  "Non-nil means wildcards ignore case when matching file names.
+This affects `file-expand-wildcards' for local files."
+  :type 'boolean
+  :group 'files
+  :version "27.1")
+
+(defcustom wildcard-brace-expansion nil
+  "Non-nil means `file-expand-wildcards' expands braces.
+A pattern like \"*.{c,h}\" then matches the files ending in \".c\"
+and those ending in \".h\".  This affects local files only."
+  :type 'boolean
+  :group 'files
+  :version "27.1")
+
 (defun file-expand-wildcards (pattern &optional full)
   "Expand wildcard pattern PATTERN.
 This returns a list of file names which match the pattern.
@@ -6316,7 +6251,19 @@ the values are absolute also.
 If PATTERN is written as a relative file name, it is interpreted
 relative to the current default directory, `default-directory'.
 The file names returned are normally also relative to the current
-default directory.  However, if FULL is non-nil, they are absolute."
+default directory.  However, if FULL is non-nil, they are absolute.
+
+For local files, a component \"**\" of PATTERN matches any number
+of directories, and the options `wildcard-match-hidden-files',
+`wildcard-ignore-case' and `wildcard-brace-expansion' apply."
+  (if (and (fboundp 'file-expand-wildcards-native)
+           (not (find-file-name-handler (expand-file-name pattern)
+                                        'file-expand-wildcards)))
+      (file-expand-wildcards-native pattern full)
+    (file-expand-wildcards--lisp pattern full)))
+
+(defun file-expand-wildcards--lisp (pattern full)
+  "Expand wildcard pattern PATTERN for `file-expand-wildcards'."
   (save-match-data
     (let* ((nondir (file-name-nondirectory pattern))
 	   (dirpart (file-name-directory pattern))
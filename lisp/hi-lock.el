@@ -711,19 +711,16 @@ with completion and history."
                (search-end
                 (min (point-max)
                      (+ range-max (max 0 (- (point-min) range-min))))))
-          (save-excursion
-            (goto-char search-start)
-            (while (re-search-forward regexp search-end t)
-              (when no-matches (setq no-matches nil))
-              (let ((overlay (make-overlay (match-beginning 0) (match-end 0))))
-                (overlay-put overlay 'hi-lock-overlay t)
-                (overlay-put overlay 'hi-lock-overlay-regexp regexp)
-                (overlay-put overlay 'face face))
-              (goto-char (match-end 0)))
-            (when no-matches
-              (add-to-list 'hi-lock--unused-faces (face-name face))
-              (setq hi-lock-interactive-patterns
-                    (cdr hi-lock-interactive-patterns)))))))))
+          (dolist (range (regexp-match-ranges regexp search-start search-end))
+            (setq no-matches nil)
+            (let ((overlay (make-overlay (car range) (cdr range))))
+              (overlay-put overlay 'hi-lock-overlay t)
+              (overlay-put overlay 'hi-lock-overlay-regexp regexp)
+              (overlay-put overlay 'face face)))
+          (when no-matches
+            (add-to-list 'hi-lock--unused-faces (face-name face))
+            (setq hi-lock-interactive-patterns
+                  (cdr hi-lock-interactive-patterns))))))))
 
 (defun hi-lock-set-file-patterns (patterns)
   "Replace file patterns list with PATTERNS and refontify."
@@ -43,7 +43,8 @@
 ;; useful in non-editing buffers such as Gnus or PCL-CVS though.
 
 ;; An overlay is used.  In the non-sticky cases, this overlay is
-;; active only on the selected window.  A hook is added to
+;; active only on the selected window, and a highlight of the selected
+;; window (see `set-window-highlight') is used instead when available.  A hook is added to
 ;; `post-command-hook' to activate the overlay and move it to the line
 ;; about point.  To get the non-sticky behavior, `hl-line-unhighlight'
 ;; is added to `pre-command-hook' as well.  This function deactivates
@@ -72,6 +73,17 @@
 Global-Hl-Line keeps displaying one overlay in each buffer
 when `global-hl-line-sticky-flag' is non-nil.")
 
+(defvar hl-line-window nil
+  "Window with the highlight used by Hl-Line mode, if any.
+Instead of an overlay, Hl-Line mode highlights the current line with
+a highlight of the selected window when `hl-line-sticky-flag' is nil.")
+
+(defvar global-hl-line-window nil
+  "Window with the highlight used by Global-Hl-Line mode, if any.
+Instead of an overlay, Global-Hl-Line mode highlights the current line
+with a highlight of the selected window when
+`global-hl-line-sticky-flag' is nil.")
+
 (defgroup hl-line nil
   "Highlight the current line."
   :version "21.1"
@@ -169,19 +181,29 @@ addition to `hl-line-highlight' on `post-command-hook'."
 
 (defun hl-line-highlight ()
   "Activate the Hl-Line overlay on the current line."
-  (if hl-line-mode	; Might be changed outside the mode function.
-      (progn
-        (unless hl-line-overlay
-          (setq hl-line-overlay (hl-line-make-overlay))) ; To be moved.
-        (overlay-put hl-line-overlay
-                     'window (unless hl-line-sticky-flag (selected-window)))
-	(hl-line-move hl-line-overlay))
-    (hl-line-unhighlight)))
+  (cond
+   ((not hl-line-mode)		; Might be changed outside the mode function.
+    (hl-line-unhighlight))
+   ((and (not hl-line-sticky-flag) (fboundp 'set-window-highlight))
+    (when hl-line-overlay
+      (delete-overlay hl-line-overlay))
+    (when (eq (window-buffer) (current-buffer))
+      (setq hl-line-window
+            (hl-line-move-window-highlight 'hl-line hl-line-window))))
+   (t
+    (unless hl-line-overlay
+      (setq hl-line-overlay (hl-line-make-overlay))) ; To be moved.
+    (overlay-put hl-line-overlay
+                 'window (unless hl-line-sticky-flag (selected-window)))
+    (hl-line-move hl-line-overlay))))
 
 (defun hl-line-unhighlight ()
   "Deactivate the Hl-Line overlay on the current line."
   (when hl-line-overlay
-    (delete-overlay hl-line-overlay)))
+    (delete-overlay hl-line-overlay))
+  (when (and (window-live-p hl-line-window)
+             (eq (window-buffer hl-line-window) (current-buffer)))
+    (remove-window-highlight hl-line-window 'hl-line)))
 
 (defun hl-line-maybe-unhighlight ()
   "Maybe deactivate the Hl-Line overlay on the current line.
@@ -230,7 +252,16 @@ and `global-hl-line-maybe-unhighlight' on `post-command-hook'."
 (defun global-hl-line-highlight ()
   "Highlight the current line in the current window."
   (when global-hl-line-mode	; Might be changed outside the mode function.
-    (unless (window-minibuffer-p)
+    (cond
+     ((window-minibuffer-p))
+     ((and (not global-hl-line-sticky-flag) (fboundp 'set-window-highlight))
+      (when global-hl-line-overlay
+        (delete-overlay global-hl-line-overlay))
+      (when (eq (window-buffer) (current-buffer))
+        (setq global-hl-line-window
+              (hl-line-move-window-highlight 'global-hl-line
+                                             global-hl-line-window))))
+     (t
       (unless global-hl-line-overlay
         (setq global-hl-line-overlay (hl-line-make-overlay))) ; To be moved.
       (unless (member global-hl-line-overlay global-hl-line-overlays)
@@ -238,7 +269,7 @@ and `global-hl-line-maybe-unhighlight' on `post-command-hook'."
       (overlay-put global-hl-line-overlay 'window
 		   (unless global-hl-line-sticky-flag
 		     (selected-window)))
-      (hl-line-move global-hl-line-overlay))))
+      (hl-line-move global-hl-line-overlay)))))
 
 (defun global-hl-line-highlight-all ()
   "Highlight the current line in all live windows."
@@ -250,7 +281,10 @@ and `global-hl-line-maybe-unhighlight' on `post-command-hook'."
 (defun global-hl-line-unhighlight ()
   "Deactivate the Global-Hl-Line overlay on the current line."
   (when global-hl-line-overlay
-    (delete-overlay global-hl-line-overlay)))
+    (delete-overlay global-hl-line-overlay))
+  (when (and (window-live-p global-hl-line-window)
+             (eq (window-buffer global-hl-line-window) (current-buffer)))
+    (remove-window-highlight global-hl-line-window 'global-hl-line)))
 
 (defun global-hl-line-maybe-unhighlight ()
   "Maybe deactivate the Global-Hl-Line overlay on the current line.
@@ -275,7 +309,10 @@ all such overlays in all buffers except the current one."
 		(with-current-buffer ovb
 		  (global-hl-line-unhighlight)))))
 	global-hl-line-overlays)
-  (setq global-hl-line-overlays nil))
+  (setq global-hl-line-overlays nil)
+  (when (window-live-p global-hl-line-window)
+    (remove-window-highlight global-hl-line-window 'global-hl-line))
+  (setq global-hl-line-window nil))
 
 (defun hl-line-move (overlay)
   "Move the Hl-Line overlay.
@@ -294,6 +331,22 @@ the line including the point by OVERLAY."
 	(move-overlay overlay b e)
       (move-overlay overlay 1 1))))
 
+(defun hl-line-move-window-highlight (key window)
+  "Move the highlight KEY of the selected window to the current line.
+If WINDOW, the window that had the highlight before, is another
+window, remove the highlight from it.  The range of the highlight is
+that of `hl-line-move'.  Return the selected window."
+  (when (and (window-live-p window) (not (eq window (selected-window))))
+    (remove-window-highlight window key))
+  (let ((range (if hl-line-range-function
+                   (funcall hl-line-range-function)
+                 (cons (line-beginning-position) (line-beginning-position 2)))))
+    (if range
+        ;; Below other highlights, like the overlay (bug#16192).
+        (set-window-highlight nil key (car range) (cdr range) hl-line-face -50)
+      (remove-window-highlight nil key)))
+  (selected-window))
+
 (defun hl-line-unload-function ()
   "Unload the Hl-Line library."
   (global-hl-line-mode -1)
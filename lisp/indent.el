@@ -35,6 +35,7 @@
   "Default number of columns for margin-changing functions to indent."
   :group 'indent
   :type 'integer)
+(put 'standard-indent 'safe-local-variable 'integerp)
 
 (defvar indent-line-function 'indent-relative
   "Function to indent the current line.
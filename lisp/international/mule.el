@@ -1916,104 +1916,36 @@ If nothing is specified, the return value is nil."
 							    (+ (point) size))))
 	(if coding-system
 	    (cons coding-system 'auto-coding-regexp-alist)))
-      (let* ((case-fold-search t)
-	     (head-start (point))
+      (let* ((head-start (point))
 	     (head-end (+ head-start (min size 1024)))
 	     (tail-start (+ head-start (max (- size 3072) 0)))
 	     (tail-end (+ head-start size))
-	     coding-system head-found tail-found char-trans)
-	;; Try a short cut by searching for the string "coding:"
-	;; and for "unibyte:" at the head and tail of SIZE bytes.
-	(setq head-found (or (search-forward "coding:" head-end t)
-			     (search-forward "unibyte:" head-end t)
-			     (search-forward "enable-character-translation:"
-					     head-end t)))
-	(if (and head-found (> head-found tail-start))
-	    ;; Head and tail are overlapped.
-	    (setq tail-found head-found)
-	  (goto-char tail-start)
-	  (setq tail-found (or (search-forward "coding:" tail-end t)
-			       (search-forward "unibyte:" tail-end t)
-			       (search-forward "enable-character-translation:"
-					       tail-end t))))
-
-	;; At first check the head.
-	(when head-found
-	  (goto-char head-start)
-	  (setq head-end (set-auto-mode-1))
-	  (setq head-start (point))
-	  (when (and head-end (< head-found head-end))
-	    (goto-char head-start)
-	    (when (and set-auto-coding-for-load
-		       (re-search-forward
-			"\\(.*;\\)?[ \t]*unibyte:[ \t]*\\([^ ;]+\\)"
-			head-end t))
-              (display-warning 'mule
-                               (format "\"unibyte: t\" (in %s) is obsolete; \
+	     ;; The bounds of the contents of the -*- line, if there is
+	     ;; a tag in it.  Look for one first as a short cut.
+	     (prop-line (save-excursion
+			  (let* ((case-fold-search t)
+				 (found (re-search-forward
+					 "coding:\\|unibyte:\\|enable-character-translation:"
+					 head-end t))
+				 (end (and found
+					   (progn (goto-char head-start)
+						  (set-auto-mode-1)))))
+			    (and end (< found end) (cons (point) end)))))
+	     (tags (file-local-coding-tags (car prop-line) (cdr prop-line)
+					   tail-start tail-end
+					   set-auto-coding-for-load))
+	     (coding-system (nth 0 tags))
+	     (char-trans (nth 1 tags)))
+	(cond
+	 ((eq (nth 2 tags) 'head)
+	  (display-warning 'mule
+			   (format "\"unibyte: t\" (in %s) is obsolete; \
 use \"coding: 'raw-text\" instead."
-                                       (file-relative-name filename))
-                               :warning)
-	      (setq coding-system 'raw-text))
-	    (when (and (not coding-system)
-		       (re-search-forward
-			"\\(.*;\\)?[ \t]*coding:[ \t]*\\([^ ;]+\\)"
-			head-end t))
-	      (setq coding-system (intern (match-string 2))))
-	    (when (re-search-forward
-		   "\\(.*;\\)?[ \t]*enable-character-translation:[ \t]*\\([^ ;]+\\)"
-		   head-end t)
-	      (setq char-trans (match-string 2)))))
-
-	;; If no coding: tag in the head, check the tail.
-	;; Here we must pay attention to the case that the end-of-line
-	;; is just "\r" and we can't use "^" nor "$" in regexp.
-	(when (and tail-found (or (not coding-system) (not char-trans)))
-	  (goto-char tail-start)
-	  (re-search-forward "[\r\n]\^L" tail-end t)
-	  (if (re-search-forward
-	       "[\r\n]\\([^\r\n]*\\)[ \t]*Local Variables:[ \t]*\\([^\r\n]*\\)[\r\n]"
-	       tail-end t)
-	      ;; The prefix is what comes before "local variables:" in its
-	      ;; line.  The suffix is what comes after "local variables:"
-	      ;; in its line.
-	      (let* ((prefix (regexp-quote (match-string 1)))
-		     (suffix (regexp-quote (match-string 2)))
-		     (re-coding
-		      (concat
-		       "[\r\n]" prefix
-		       ;; N.B. without the \n below, the regexp can
-		       ;; eat newlines.
-		       "[ \t]*coding[ \t]*:[ \t]*\\([^ \t\r\n]+\\)[ \t]*"
-		       suffix "[\r\n]"))
-		     (re-unibyte
-		      (concat
-		       "[\r\n]" prefix
-		       "[ \t]*unibyte[ \t]*:[ \t]*\\([^ \t\r\n]+\\)[ \t]*"
-		       suffix "[\r\n]"))
-		     (re-char-trans
-		      (concat
-		       "[\r\n]" prefix
-		       "[ \t]*enable-character-translation[ \t]*:[ \t]*\\([^ \t\r\n]+\\)[ \t]*"
-		       suffix "[\r\n]"))
-		     (re-end
-		      (concat "[\r\n]" prefix "[ \t]*End *:[ \t]*" suffix
-			      "[\r\n]?"))
-		     (pos (1- (point))))
-		(forward-char -1)	; skip back \r or \n.
-		(re-search-forward re-end tail-end 'move)
-		(setq tail-end (point))
-		(goto-char pos)
-		(when (and set-auto-coding-for-load
-			   (re-search-forward re-unibyte tail-end t))
-                  (display-warning 'mule "\"unibyte: t\" is obsolete; \
-use \"coding: 'raw-text\" instead." :warning)
-		  (setq coding-system 'raw-text))
-		(when (and (not coding-system)
-			   (re-search-forward re-coding tail-end t))
-		  (setq coding-system (intern (match-string 1))))
-		(when (and (not char-trans)
-			   (re-search-forward re-char-trans tail-end t))
-		  (setq char-trans (match-string 1))))))
+				   (file-relative-name filename))
+			   :warning))
+	 ((eq (nth 2 tags) 'tail)
+	  (display-warning 'mule "\"unibyte: t\" is obsolete; \
+use \"coding: 'raw-text\" instead." :warning)))
 	(if coding-system
 	    ;; If the coding-system name ends with "!", remove it and
 	    ;; set char-trans to "nil".
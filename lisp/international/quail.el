@@ -1266,16 +1266,18 @@ The returned value is a Quail map specific to KEY."
 	(map (quail-map))
 	(kbd-translate (quail-kbd-translate))
 	slot ch translation def)
-    (while (and map (< idx len))
-      (setq ch (if kbd-translate (quail-keyboard-translate (aref key idx))
-		 (aref key idx)))
-      (setq idx (1+ idx))
-      (if (and (cdr map) (symbolp (cdr map)))
-	  (setcdr map (funcall (cdr map) key idx)))
-      (setq slot (assq ch (cdr map)))
-      (if (and (cdr slot) (symbolp (cdr slot)))
-	  (setcdr slot (funcall (cdr slot) key idx)))
-      (setq map (cdr slot)))
+    (if (fboundp 'quail-lookup-map-native)
+	(setq map (quail-lookup-map-native map key len kbd-translate))
+      (while (and map (< idx len))
+	(setq ch (if kbd-translate (quail-keyboard-translate (aref key idx))
+		   (aref key idx)))
+	(setq idx (1+ idx))
+	(if (and (cdr map) (symbolp (cdr map)))
+	    (setcdr map (funcall (cdr map) key idx)))
+	(setq slot (assq ch (cdr map)))
+	(if (and (cdr slot) (symbolp (cdr slot)))
+	    (setcdr slot (funcall (cdr slot) key idx)))
+	(setq map (cdr slot))))
     (setq def (car map))
     (setq quail-current-translations nil)
     (if (and map (setq translation (quail-get-translation def key len)))
@@ -347,11 +347,13 @@ is active."
       ;; Mark the area as defer-fontified so that the redisplay engine
       ;; is happy and so that the idle timer can find the places to fontify.
       (with-buffer-prepared-for-jit-lock
-       (put-text-property start
-			  (next-single-property-change
-			   start 'fontified nil
-			   (min (point-max) (+ start jit-lock-chunk-size)))
-			  'fontified 'defer)))))
+       (if (fboundp 'jit-lock-defer-native)
+           (jit-lock-defer-native start jit-lock-chunk-size)
+         (put-text-property start
+			    (next-single-property-change
+			     start 'fontified nil
+			     (min (point-max) (+ start jit-lock-chunk-size)))
+			    'fontified 'defer))))))
 
 (defun jit-lock--run-functions (beg end)
   (let ((tight-beg nil) (tight-end nil)
@@ -470,8 +472,12 @@ This applies to the buffer associated with marker START."
 (defsubst jit-lock-stealth-chunk-start (around)
   "Return the start of the next chunk to fontify around position AROUND.
 Value is nil if there is nothing more to fontify."
-  (if (zerop (buffer-size))
-      nil
+  (cond
+   ((fboundp 'jit-lock-stealth-chunk-start-native)
+    (jit-lock-stealth-chunk-start-native around jit-lock-chunk-size))
+   ((zerop (buffer-size))
+    nil)
+   (t
     (let* ((next (text-property-not-all around (point-max) 'fontified t))
            (prev (previous-single-property-change around 'fontified))
            (prop (get-text-property (max (point-min) (1- around))
@@ -504,7 +510,7 @@ Value is nil if there is nothing more to fontify."
                          ((null next) start)
                          ((< (- around start) (- next around)) start)
                          (t next))))
-      result)))
+      result))))
 
 (defun jit-lock-stealth-fontify (&optional repeat)
   "Fontify buffers stealthily.
@@ -571,16 +577,18 @@ non-nil in a repeated invocation of this function."
 	(with-current-buffer buffer
 	  ;; (message "Jit-Defer %s" (buffer-name))
 	  (with-buffer-prepared-for-jit-lock
-	   (let ((pos (point-min)))
-	     (while
-		 (progn
-		   (when (eq (get-text-property pos 'fontified) 'defer)
-		     (put-text-property
-		      pos (setq pos (next-single-property-change
-				     pos 'fontified nil (point-max)))
-		      'fontified nil))
-		   (setq pos (next-single-property-change
-                              pos 'fontified)))))))))
+	   (if (fboundp 'jit-lock-undefer-native)
+	       (jit-lock-undefer-native)
+	     (let ((pos (point-min)))
+	       (while
+		   (progn
+		     (when (eq (get-text-property pos 'fontified) 'defer)
+		       (put-text-property
+			pos (setq pos (next-single-property-change
+				       pos 'fontified nil (point-max)))
+			'fontified nil))
+		     (setq pos (next-single-property-change
+				pos 'fontified))))))))))
     ;; Force fontification of the visible parts.
     (let ((buffers jit-lock-defer-buffers)
           (jit-lock-defer-timer nil))
@@ -47,43 +47,14 @@ them into characters should be done separately."
     (setq coding-system nil))
   (save-excursion
     (save-restriction
-      ;; RFC 2045:  ``An "=" followed by two hexadecimal digits, one
-      ;; or both of which are lowercase letters in "abcdef", is
-      ;; formally illegal. A robust implementation might choose to
-      ;; recognize them as the corresponding uppercase letters.''
-      (let ((case-fold-search t))
-	(narrow-to-region from to)
-	;; Do this in case we're called from Gnus, say, in a buffer
-	;; which already contains non-ASCII characters which would
-	;; then get doubly-decoded below.
-	(if coding-system
-	    (encode-coding-region (point-min) (point-max) coding-system))
-	(goto-char (point-min))
-	(while (and (skip-chars-forward "^=")
-		    (not (eobp)))
-	  (cond ((eq (char-after (1+ (point))) ?\n)
-		 (delete-char 2))
-		((looking-at "\\(=[0-9A-F][0-9A-F]\\)+")
-		 ;; Decode this sequence at once; i.e. by a single
-		 ;; deletion and insertion.
-		 (let* ((n (/ (- (match-end 0) (point)) 3))
-			(str (make-string n 0)))
-		   (dotimes (i n)
-                     (let ((n1 (char-after (1+ (point))))
-                           (n2 (char-after (+ 2 (point)))))
-                       (aset str i
-                             (+ (* 16 (- n1 (if (<= n1 ?9) ?0
-                                              (if (<= n1 ?F) (- ?A 10)
-                                                (- ?a 10)))))
-                                (- n2 (if (<= n2 ?9) ?0
-                                        (if (<= n2 ?F) (- ?A 10)
-                                          (- ?a 10)))))))
-		     (forward-char 3))
-		   (delete-region (match-beginning 0) (match-end 0))
-		   (insert str)))
-		(t
-		 (message "Malformed quoted-printable text")
-		 (forward-char)))))
+      (narrow-to-region from to)
+      ;; Do this in case we're called from Gnus, say, in a buffer
+      ;; which already contains non-ASCII characters which would
+      ;; then get doubly-decoded below.
+      (if coding-system
+	  (encode-coding-region (point-min) (point-max) coding-system))
+      (if (quoted-printable-decode-region-native (point-min) (point-max))
+	  (message "Malformed quoted-printable text"))
       (if coding-system
 	  (decode-coding-region (point-min) (point-max) coding-system)))))
 
@@ -109,10 +80,16 @@ You should probably avoid non-ASCII characters in this arg.
 If `mm-use-ultra-safe-encoding' is set, fold lines unconditionally and
 encode lines starting with \"From\"."
   (interactive "r")
-  (unless class
-    ;; Avoid using 8bit characters. = is \075.
-    ;; Equivalent to "^\000-\007\013\015-\037\200-\377="
-    (setq class "\010-\012\014\040-\074\076-\177"))
+  (if (not class)
+      (quoted-printable-encode-region-native
+       from to fold (and (boundp 'mm-use-ultra-safe-encoding)
+			 mm-use-ultra-safe-encoding))
+    (quoted-printable-encode-region-1 from to fold class)))
+
+(defun quoted-printable-encode-region-1 (from to fold class)
+  "Encode the region between FROM and TO, translating CLASS.
+This is `quoted-printable-encode-region' for a CLASS other than the
+default one."
   (save-excursion
     (goto-char from)
     (if (re-search-forward (string-to-multibyte "[^\x0-\x7f\x80-\xff]")
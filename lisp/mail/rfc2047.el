@@ -985,6 +985,35 @@ ENCODED-WORD)."
 If ADDRESS-MIME is non-nil, strip backslashes which precede characters
 other than `\"' and `\\' in quoted strings."
   (interactive "r")
+  (if (or rfc2047-quote-decoded-words-containing-tspecials
+	  (not rfc2047-allow-incomplete-encoded-text))
+      (rfc2047-decode-region-1 start end address-mime)
+    (save-excursion
+      (save-restriction
+	(narrow-to-region start end)
+	(when address-mime
+	  (rfc2047-strip-backslashes-in-quoted-strings))
+	(let ((decoded (rfc2047-decode-region-native
+			(point-min) (point-max)
+			(lambda (charset)
+			  (rfc2047-charset-to-coding-system charset t))
+			rfc2047-allow-irregular-q-encoded-words))
+	      (e (point-max)))
+	  (when (and (mm-multibyte-p)
+		     mail-parse-charset
+		     (not (eq mail-parse-charset 'us-ascii))
+		     (not (eq mail-parse-charset 'gnus-decoded)))
+	    ;; Decode the text between the encoded words, from the end
+	    ;; so that the positions of the earlier ones stay valid.
+	    (dolist (span (reverse decoded))
+	      (decode-coding-region (cdr span) e mail-parse-charset)
+	      (setq e (car span)))
+	    (decode-coding-region (point-min) e mail-parse-charset)))))))
+
+(defun rfc2047-decode-region-1 (start end address-mime)
+  "Decode MIME-encoded words in region between START and END in Lisp.
+This is used by `rfc2047-decode-region' when decoded words are quoted
+or successive encoded words are decoded separately."
   (let ((case-fold-search t)
 	(eword-regexp
 	 (if rfc2047-allow-irregular-q-encoded-words
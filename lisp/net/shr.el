@@ -689,37 +689,67 @@ size, and full-buffer size."
 			   `,(shr-face-background face))))
     (setq start (point))
     (setq shr-indentation (or continuation shr-indentation))
+    (if (and (not shr-use-fonts)
+	     (not (text-property-not-all start (line-end-position)
+					 'display nil)))
+	;; Characters have fixed widths, and there are no images, so
+	;; the line can be folded natively.
+	(shr-fill-line-natively start)
+      (shr-fill-line-by-motion start))))
+
+(defun shr-fill-line-natively (start)
+  "Fold the text from START to the end of the line with `shr-fill-text'."
+  (let ((lines (shr-fill-text (list (buffer-substring start
+						      (line-end-position)))
+			      (- shr-internal-width shr-indentation)
+			      (- shr-internal-width (current-column)))))
+    (when (cdr lines)
+      (delete-region start (line-end-position))
+      (insert (car lines))
+      (dolist (line (cdr lines))
+	(let ((props `(face ,(get-text-property 0 'face line)
+			    image-displayer
+			    ,(get-text-property 0 'image-displayer line)))
+	      (gap-start (point)))
+	  (insert "\n")
+	  (shr-indent)
+	  (add-text-properties gap-start (point) props)
+	  (insert line))))
+    (end-of-line)))
+
+(defun shr-fill-line-by-motion (start)
+  "Fold the text from START to the end of the line by moving to columns."
+  (shr-vertical-motion shr-internal-width)
+  (when (looking-at " $")
+    (delete-region (point) (line-end-position)))
+  (while (not (eolp))
+    ;; We have to do some folding.  First find the first
+    ;; previous point suitable for folding.
+    (if (or (not (shr-find-fill-point (line-beginning-position)))
+	    (= (point) start))
+	;; We had unbreakable text (for this width), so just go to
+	;; the first space and carry on.
+	(progn
+	  (beginning-of-line)
+	  (skip-chars-forward " ")
+	  (search-forward " " (line-end-position) 'move)))
+    ;; Success; continue.
+    (when (= (preceding-char) ?\s)
+      (delete-char -1))
+    (let ((props `(face ,(get-text-property (point) 'face)
+			;; Don't break the image-displayer property
+			;; as it will cause `gnus-article-show-images'
+			;; to show the two or more same images.
+			image-displayer
+			,(get-text-property (point) 'image-displayer)))
+	  (gap-start (point)))
+      (insert "\n")
+      (shr-indent)
+      (add-text-properties gap-start (point) props))
+    (setq start (point))
     (shr-vertical-motion shr-internal-width)
     (when (looking-at " $")
-      (delete-region (point) (line-end-position)))
-    (while (not (eolp))
-      ;; We have to do some folding.  First find the first
-      ;; previous point suitable for folding.
-      (if (or (not (shr-find-fill-point (line-beginning-position)))
-	      (= (point) start))
-	  ;; We had unbreakable text (for this width), so just go to
-	  ;; the first space and carry on.
-	  (progn
-	    (beginning-of-line)
-	    (skip-chars-forward " ")
-	    (search-forward " " (line-end-position) 'move)))
-      ;; Success; continue.
-      (when (= (preceding-char) ?\s)
-	(delete-char -1))
-      (let ((props `(face ,(get-text-property (point) 'face)
-			  ;; Don't break the image-displayer property
-			  ;; as it will cause `gnus-article-show-images'
-			  ;; to show the two or more same images.
-			  image-displayer
-			  ,(get-text-property (point) 'image-displayer)))
-	    (gap-start (point)))
-	(insert "\n")
-	(shr-indent)
-	(add-text-properties gap-start (point) props))
-      (setq start (point))
-      (shr-vertical-motion shr-internal-width)
-      (when (looking-at " $")
-	(delete-region (point) (line-end-position))))))
+      (delete-region (point) (line-end-position)))))
 
 (defun shr-find-fill-point (start)
   (let ((bp (point))
@@ -1266,6 +1266,12 @@ entry does not exist, return nil."
   (tramp-compat-file-name-unquote (tramp-file-name-localname vec)))
 
 ;;;###tramp-autoload
+(defsubst tramp-native-syntax-p ()
+  "Return non-nil if Tramp file names are parsed natively.
+This is the case for the default `tramp-syntax', which
+`tramp-dissect-file-name-native' understands."
+  (eq (tramp-compat-tramp-syntax) 'default))
+
 (defun tramp-tramp-file-p (name)
   "Return t if NAME is a string with Tramp file name syntax."
   (and (stringp name)
@@ -1274,7 +1280,9 @@ entry does not exist, return nil."
 	     (if (memq system-type '(cygwin windows-nt))
 		 "^/[[:alpha:]]?:" "^/:")
 	     name))
-       (string-match-p tramp-file-name-regexp name)
+       (if (tramp-native-syntax-p)
+	   (tramp-tramp-file-p-native name)
+	 (string-match-p tramp-file-name-regexp name))
        t))
 
 (defun tramp-find-method (method user host)
@@ -1338,6 +1346,35 @@ This is HOST, if non-nil. Otherwise, it is `tramp-default-host'."
 	lhost)
       tramp-default-host))
 
+(defun tramp-dissect-file-name-regexp (name)
+  "Dissect NAME with `tramp-file-name-structure'.
+Return a list (METHOD USER DOMAIN HOST PORT LOCALNAME HOP), like
+`tramp-dissect-file-name-native'.  This is used for the syntaxes
+that are not parsed natively."
+  (if (not (string-match (nth 0 tramp-file-name-structure) name))
+      (error "`tramp-file-name-structure' didn't match!")
+    (let ((method    (match-string (nth 1 tramp-file-name-structure) name))
+	  (user      (match-string (nth 2 tramp-file-name-structure) name))
+	  (host      (match-string (nth 3 tramp-file-name-structure) name))
+	  (localname (match-string (nth 4 tramp-file-name-structure) name))
+	  (hop       (match-string (nth 5 tramp-file-name-structure) name))
+	  domain port)
+      (when user
+	(when (string-match tramp-user-with-domain-regexp user)
+	  (setq domain (match-string 2 user)
+		user (match-string 1 user))))
+
+      (when host
+	(when (string-match tramp-host-with-port-regexp host)
+	  (setq port (match-string 2 host)
+		host (match-string 1 host)))
+	(when (string-match tramp-prefix-ipv6-regexp host)
+	  (setq host (replace-match "" nil t host)))
+	(when (string-match tramp-postfix-ipv6-regexp host)
+	  (setq host (replace-match "" nil t host))))
+
+      (list method user domain host port localname hop))))
+
 (defun tramp-dissect-file-name (name &optional nodefault)
   "Return a `tramp-file-name' structure.
 The structure consists of remote method, remote user, remote host,
@@ -1347,36 +1384,18 @@ values."
   (save-match-data
     (unless (tramp-tramp-file-p name)
       (tramp-compat-user-error nil "Not a Tramp file name: \"%s\"" name))
-    (if (not (string-match (nth 0 tramp-file-name-structure) name))
-        (error "`tramp-file-name-structure' didn't match!")
-      (let ((method    (match-string (nth 1 tramp-file-name-structure) name))
-	    (user      (match-string (nth 2 tramp-file-name-structure) name))
-	    (host      (match-string (nth 3 tramp-file-name-structure) name))
-	    (localname (match-string (nth 4 tramp-file-name-structure) name))
-	    (hop       (match-string (nth 5 tramp-file-name-structure) name))
-	    domain port)
-	(when user
-	  (when (string-match tramp-user-with-domain-regexp user)
-	    (setq domain (match-string 2 user)
-		  user (match-string 1 user))))
-
-	(when host
-	  (when (string-match tramp-host-with-port-regexp host)
-	    (setq port (match-string 2 host)
-		  host (match-string 1 host)))
-	  (when (string-match tramp-prefix-ipv6-regexp host)
-	    (setq host (replace-match "" nil t host)))
-	  (when (string-match tramp-postfix-ipv6-regexp host)
-	    (setq host (replace-match "" nil t host))))
-
-	(unless nodefault
-	  (setq method (tramp-find-method method user host)
-		user (tramp-find-user method user host)
-		host (tramp-find-host method user host)))
-
-	(make-tramp-file-name
-	 :method method :user user :domain domain :host host :port port
-	 :localname localname :hop hop)))))
+    (pcase-let ((`(,method ,user ,domain ,host ,port ,localname ,hop)
+		 (if (tramp-native-syntax-p)
+		     (tramp-dissect-file-name-native name)
+		   (tramp-dissect-file-name-regexp name))))
+      (unless nodefault
+	(setq method (tramp-find-method method user host)
+	      user (tramp-find-user method user host)
+	      host (tramp-find-host method user host)))
+
+      (make-tramp-file-name
+       :method method :user user :domain domain :host host :port port
+       :localname localname :hop hop))))
 
 (defun tramp-buffer-name (vec)
   "A name for the connection buffer VEC."
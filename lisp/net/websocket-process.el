@@ -0,0 +1,239 @@
+;;; websocket-process.el --- WebSocket connections as processes  -*- lexical-binding: t -*-
+
+;; Copyright (C) 2018 Free Software Foundation, Inc.
+
+;; Keywords: comm, processes
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Commentary:
+
+;; `make-websocket-process' opens a WebSocket connection (RFC 6455) and
+;; returns its network process.  The opening handshake is checked and
+;; the frames from the server are parsed by the primitives of
+;; websocket.rs, so the filter of the connection gets complete text and
+;; binary messages.  Pings from the server are answered, and pings are
+;; sent every `websocket-process-keepalive' seconds to notice dead
+;; connections.
+
+;;; Code:
+
+(require 'cl-lib)
+(require 'url-parse)
+
+(defcustom websocket-process-keepalive 30
+  "Seconds between the pings sent on WebSocket connections.
+If the server sends nothing between two pings, the connection is
+taken to be dead and closed.  nil means not to send pings."
+  :type '(choice (const :tag "Never" nil) number)
+  :group 'processes
+  :version "27.1")
+
+;;;###autoload
+(defun make-websocket-process (&rest args)
+  "Open a WebSocket connection and return its process.
+The arguments ARGS are keyword/argument pairs:
+
+:name NAME -- NAME is the name of the process, \"websocket\" by
+default.
+
+:buffer BUFFER -- BUFFER is the buffer of the process, as for
+`make-network-process'.  Messages are not inserted in it.
+
+:url URL -- URL is the ws or wss URL to connect to.
+
+:filter FILTER -- FILTER is called with three arguments for each
+message from the server: the process, the message, and its type,
+`text' or `binary'.  Text messages are decoded from UTF-8, and
+binary messages are unibyte strings.
+
+:sentinel SENTINEL -- SENTINEL is called like a process sentinel.
+It gets the event \"open\\n\" when the server accepts the handshake,
+and the usual events when the connection is closed.  If the
+handshake fails or the server sends something that is not
+WebSocket, the event starts with \"failed\".
+
+:protocols PROTOCOLS -- PROTOCOLS is a list of the subprotocols to
+ask for.
+
+:headers HEADERS -- HEADERS is an alist of (NAME . VALUE) of more
+headers to send with the handshake.
+
+:keepalive SECONDS -- Send a ping every SECONDS seconds, instead of
+every `websocket-process-keepalive' seconds.  nil means never.
+
+Send messages with `websocket-process-send', and close the
+connection with `websocket-process-close'.  Messages sent before the
+server accepts the handshake are sent when it does."
+  (let* ((url (url-generic-parse-url (plist-get args :url)))
+         (tls (equal (url-type url) "wss"))
+         (port (progn
+                 (unless (member (url-type url) '("ws" "wss"))
+                   (error "Not a WebSocket URL: %s" (plist-get args :url)))
+                 (or (url-portspec url) (if tls 443 80))))
+         (key (base64-encode-string
+               (apply #'unibyte-string (cl-loop repeat 16
+                                                collect (random 256)))))
+         (process (open-network-stream
+                   (or (plist-get args :name) "websocket")
+                   (plist-get args :buffer) (url-host url) port
+                   :type (if tls 'tls 'plain)))
+         (path (if (equal (url-filename url) "") "/" (url-filename url)))
+         (protocols (plist-get args :protocols)))
+    (set-process-coding-system process 'binary 'binary)
+    (process-put process 'websocket-parser (websocket-native-parser key))
+    (process-put process 'websocket-state 'connecting)
+    (process-put process 'websocket-filter (plist-get args :filter))
+    (process-put process 'websocket-sentinel (plist-get args :sentinel))
+    (process-put process 'websocket-keepalive
+                 (if (plist-member args :keepalive)
+                     (plist-get args :keepalive)
+                   websocket-process-keepalive))
+    (set-process-filter process #'websocket-process--filter)
+    (set-process-sentinel process #'websocket-process--sentinel)
+    (process-send-string
+     process
+     (concat "GET " path " HTTP/1.1\r\n"
+             "Host: " (url-host url)
+             (and (url-portspec url) (format ":%d" (url-portspec url)))
+             "\r\n"
+             "Upgrade: websocket\r\n"
+             "Connection: Upgrade\r\n"
+             "Sec-WebSocket-Key: " key "\r\n"
+             "Sec-WebSocket-Version: 13\r\n"
+             (and protocols
+                  (concat "Sec-WebSocket-Protocol: "
+                          (mapconcat #'identity protocols ", ") "\r\n"))
+             (mapconcat (lambda (header)
+                          (concat (car header) ": " (cdr header) "\r\n"))
+                        (plist-get args :headers) "")
+             "\r\n"))
+    process))
+
+(defun websocket-process-send (process message &optional binary)
+  "Send MESSAGE, a string, on the WebSocket connection PROCESS.
+The message is sent as text, encoded in UTF-8, unless BINARY is
+non-nil."
+  (let ((frame (websocket-native-frame (if binary 'binary 'text) message)))
+    (pcase (process-get process 'websocket-state)
+      ('connecting
+       (process-put process 'websocket-queue
+                    (cons frame (process-get process 'websocket-queue))))
+      ('open (process-send-string process frame))
+      (_ (error "WebSocket connection %s is closed" (process-name process))))))
+
+(defun websocket-process--close-payload (code reason)
+  "Return the payload of a close frame with CODE and REASON."
+  (concat (unibyte-string (ash code -8) (logand code 255))
+          (encode-coding-string (or reason "") 'utf-8)))
+
+(defun websocket-process-close (process &optional code reason)
+  "Close the WebSocket connection PROCESS.
+CODE is the status code to send, 1000 (normal closure) by default,
+and REASON a string that explains it.  The connection is closed when
+the server answers, or after a few seconds."
+  (if (not (eq (process-get process 'websocket-state) 'open))
+      (delete-process process)
+    (process-put process 'websocket-state 'closing)
+    (process-send-string
+     process (websocket-native-frame
+              'close (websocket-process--close-payload (or code 1000)
+                                                       reason)))
+    (run-at-time 5 nil #'delete-process process)))
+
+(defun websocket-process--notify (process event)
+  "Call the sentinel of the WebSocket connection PROCESS with EVENT."
+  (let ((sentinel (process-get process 'websocket-sentinel)))
+    (when sentinel
+      (funcall sentinel process event))))
+
+(defun websocket-process--fail (process message)
+  "Close the WebSocket connection PROCESS because of MESSAGE."
+  (process-put process 'websocket-state 'failed)
+  (websocket-process--notify process (format "failed: %s\n" message))
+  (delete-process process))
+
+(defun websocket-process--keepalive (process)
+  "Send a ping on PROCESS, or close it if it did not answer the last one."
+  (cond
+   ((not (process-live-p process)))
+   ((not (process-get process 'websocket-alive))
+    (websocket-process--fail process "no answer to ping"))
+   ((eq (process-get process 'websocket-state) 'open)
+    (process-put process 'websocket-alive nil)
+    (process-send-string process (websocket-native-frame 'ping "")))))
+
+(defun websocket-process--open (process)
+  "Start using the WebSocket connection PROCESS after the handshake."
+  (process-put process 'websocket-state 'open)
+  (dolist (frame (nreverse (process-get process 'websocket-queue)))
+    (process-send-string process frame))
+  (process-put process 'websocket-queue nil)
+  (let ((keepalive (process-get process 'websocket-keepalive)))
+    (when keepalive
+      (process-put process 'websocket-timer
+                   (run-at-time keepalive keepalive
+                                #'websocket-process--keepalive process))))
+  (websocket-process--notify process "open\n"))
+
+(defun websocket-process--filter (process string)
+  "Handle STRING, output of the WebSocket connection PROCESS."
+  (process-put process 'websocket-alive t)
+  (let ((events (condition-case err
+                    (websocket-native-parser-feed
+                     (process-get process 'websocket-parser) string)
+                  (error
+                   (websocket-process--fail process (error-message-string err))
+                   nil))))
+    (dolist (event events)
+      (pcase event
+        (`(open) (websocket-process--open process))
+        (`(,(and type (or 'text 'binary)) . ,message)
+         (let ((filter (process-get process 'websocket-filter)))
+           (when filter
+             (funcall filter process message type))))
+        (`(ping . ,data)
+         (process-send-string process (websocket-native-frame 'pong data)))
+        (`(close ,code ,_reason)
+         ;; Answer the server's close, unless it answers ours.
+         (when (eq (process-get process 'websocket-state) 'open)
+           (process-put process 'websocket-state 'closing)
+           (process-send-string
+            process (websocket-native-frame
+                     'close (if code
+                                (websocket-process--close-payload code nil)
+                              ""))))
+         (delete-process process))))))
+
+(defun websocket-process--sentinel (process event)
+  "Handle EVENT, a change in the status of the WebSocket connection PROCESS."
+  (unless (process-live-p process)
+    (let ((timer (process-get process 'websocket-timer))
+          (parser (process-get process 'websocket-parser)))
+      (when timer
+        (cancel-timer timer))
+      (when parser
+        (websocket-native-parser-free parser))
+      (process-put process 'websocket-timer nil)
+      (process-put process 'websocket-parser nil)))
+  (unless (eq (process-get process 'websocket-state) 'failed)
+    (unless (process-live-p process)
+      (process-put process 'websocket-state 'closed))
+    (websocket-process--notify process event)))
+
+(provide 'websocket-process)
+
+;;; websocket-process.el ends here
@@ -2664,6 +2664,16 @@ This function assumes the table is already analyzed (i.e., using
     (cons var (cons value org-tbl-calc-modes)))
   org-tbl-calc-modes)
 
+(defun org-table--native-modes-p (modes)
+  "Non-nil if Calc MODES are those `org-table-eval-native' follows.
+These are the modes of the default `org-calc-default-modes'."
+  (and (eql (plist-get modes 'calc-internal-prec) 12)
+       (equal (plist-get modes 'calc-float-format) '(float 8))
+       (not (plist-get modes 'calc-prefer-frac))
+       (not (plist-get modes 'calc-symbolic-mode))
+       (or (not (boundp 'calc-multiplication-has-precedence))
+	   calc-multiplication-has-precedence)))
+
 ;;;###autoload
 (defun org-table-eval-formula (&optional arg equation
 					 suppress-align suppress-const
@@ -2730,7 +2740,7 @@ location of point."
 	   (org-tbl-calc-modes (copy-sequence org-calc-default-modes))
 	   (numbers nil)	   ; was a variable, now fixed default
 	   (keep-empty nil)
-	   n form form0 formrpl formrg bw fmt x ev orig c lispp literal
+	   n form form0 formrpl formrg bw fmt x ev orig c lispp literal native
 	   duration duration-output-format)
       ;; Parse the format string.  Since we have a lot of modes, this is
       ;; a lot of work.  However, I think calc still uses most of the time.
@@ -2815,111 +2825,124 @@ location of point."
 
 	;; Check for old vertical references
 	(org-table--error-on-old-row-references form)
-	;; Insert remote references
-	(setq form (org-table-remote-reference-indirection form))
-	(while (string-match "\\<remote([ \t]*\\([^,)]+\\)[ \t]*,[ \t]*\\([^\n)]+\\))" form)
-	  (setq form
-		(replace-match
-		 (save-match-data
-		   (org-table-make-reference
-		    (let ((rmtrng (org-table-get-remote-range
-				   (match-string 1 form) (match-string 2 form))))
-		      (if duration
-			  (if (listp rmtrng)
-			      (mapcar (lambda(x) (org-table-time-string-to-seconds x)) rmtrng)
-			    (org-table-time-string-to-seconds rmtrng))
-			rmtrng))
-		    keep-empty numbers lispp))
-		 t t form)))
-	;; Insert complex ranges
-	(while (and (string-match org-table-range-regexp form)
-		    (> (length (match-string 0 form)) 1))
-	  (setq formrg
-		(save-match-data
-		  (org-table-get-range
-		   (match-string 0 form) org-table-current-begin-pos n0)))
-	  (setq formrpl
-		(save-match-data
-		  (org-table-make-reference
-		   ;; possibly handle durations
-		   (if duration
-		       (if (listp formrg)
-			   (mapcar (lambda(x) (org-table-time-string-to-seconds x)) formrg)
-			 (org-table-time-string-to-seconds formrg))
-		     formrg)
-		   keep-empty numbers lispp)))
-	  (if (not (save-match-data
-		     (string-match (regexp-quote form) formrpl)))
-	      (setq form (replace-match formrpl t t form))
-	    (user-error "Spreadsheet error: invalid reference \"%s\"" form)))
-	;; Insert simple ranges, i.e. included in the current row.
-	(while (string-match
-		"\\$\\(\\([-+]\\)?[0-9]+\\)\\.\\.\\$\\(\\([-+]\\)?[0-9]+\\)"
-		form)
-	  (setq form
-		(replace-match
-		 (save-match-data
-		   (org-table-make-reference
-		    (cl-subseq fields
-			       (+ (if (match-end 2) n0 0)
-				  (string-to-number (match-string 1 form))
-				  -1)
-			       (+ (if (match-end 4) n0 0)
-				  (string-to-number (match-string 3 form))))
-		    keep-empty numbers lispp))
-		 t t form)))
-	(setq form0 form)
-	;; Insert the references to fields in same row
-	(while (string-match "\\$\\(\\([-+]\\)?[0-9]+\\)" form)
-	  (setq n (+ (string-to-number (match-string 1 form))
-		     (if (match-end 2) n0 0))
-		x (nth (1- (if (= n 0) n0 (max n 1))) fields)
-		formrpl (save-match-data
-			  (org-table-make-reference
-			   x keep-empty numbers lispp)))
-	  (when (or (not x)
-		    (save-match-data
-		      (string-match (regexp-quote formula) formrpl)))
-	    (user-error "Invalid field specifier \"%s\""
-			(match-string 0 form)))
-	  (setq form (replace-match formrpl t t form)))
-
-	(if lispp
-	    (setq ev (condition-case nil
-			 (eval (eval (read form)))
-		       (error "#ERROR"))
-		  ev (if (numberp ev) (number-to-string ev) ev)
+	;; Evaluate simple arithmetic natively, and leave the rest to
+	;; Calc.
+	(setq native
+	      (and (not lispp) (not duration) (not org-table-formula-debug)
+		   (org-table--native-modes-p org-tbl-calc-modes)
+		   (not (string-match-p "\\<remote(" form))
+		   (org-table-eval-native
+		    form org-table-current-begin-pos (org-table-end)
+		    (count-lines org-table-current-begin-pos
+				 (line-beginning-position))
+		    n0 numbers keep-empty)))
+	(if native
+	    (setq ev native)
+	  ;; Insert remote references
+	  (setq form (org-table-remote-reference-indirection form))
+	  (while (string-match "\\<remote([ \t]*\\([^,)]+\\)[ \t]*,[ \t]*\\([^\n)]+\\))" form)
+	    (setq form
+		  (replace-match
+		   (save-match-data
+		     (org-table-make-reference
+		      (let ((rmtrng (org-table-get-remote-range
+				     (match-string 1 form) (match-string 2 form))))
+			(if duration
+			    (if (listp rmtrng)
+				(mapcar (lambda(x) (org-table-time-string-to-seconds x)) rmtrng)
+			      (org-table-time-string-to-seconds rmtrng))
+			  rmtrng))
+		      keep-empty numbers lispp))
+		   t t form)))
+	  ;; Insert complex ranges
+	  (while (and (string-match org-table-range-regexp form)
+		      (> (length (match-string 0 form)) 1))
+	    (setq formrg
+		  (save-match-data
+		    (org-table-get-range
+		     (match-string 0 form) org-table-current-begin-pos n0)))
+	    (setq formrpl
+		  (save-match-data
+		    (org-table-make-reference
+		     ;; possibly handle durations
+		     (if duration
+			 (if (listp formrg)
+			     (mapcar (lambda(x) (org-table-time-string-to-seconds x)) formrg)
+			   (org-table-time-string-to-seconds formrg))
+		       formrg)
+		     keep-empty numbers lispp)))
+	    (if (not (save-match-data
+		       (string-match (regexp-quote form) formrpl)))
+		(setq form (replace-match formrpl t t form))
+	      (user-error "Spreadsheet error: invalid reference \"%s\"" form)))
+	  ;; Insert simple ranges, i.e. included in the current row.
+	  (while (string-match
+		  "\\$\\(\\([-+]\\)?[0-9]+\\)\\.\\.\\$\\(\\([-+]\\)?[0-9]+\\)"
+		  form)
+	    (setq form
+		  (replace-match
+		   (save-match-data
+		     (org-table-make-reference
+		      (cl-subseq fields
+				 (+ (if (match-end 2) n0 0)
+				    (string-to-number (match-string 1 form))
+				    -1)
+				 (+ (if (match-end 4) n0 0)
+				    (string-to-number (match-string 3 form))))
+		      keep-empty numbers lispp))
+		   t t form)))
+	  (setq form0 form)
+	  ;; Insert the references to fields in same row
+	  (while (string-match "\\$\\(\\([-+]\\)?[0-9]+\\)" form)
+	    (setq n (+ (string-to-number (match-string 1 form))
+		       (if (match-end 2) n0 0))
+		  x (nth (1- (if (= n 0) n0 (max n 1))) fields)
+		  formrpl (save-match-data
+			    (org-table-make-reference
+			     x keep-empty numbers lispp)))
+	    (when (or (not x)
+		      (save-match-data
+			(string-match (regexp-quote formula) formrpl)))
+	      (user-error "Invalid field specifier \"%s\""
+			  (match-string 0 form)))
+	    (setq form (replace-match formrpl t t form)))
+
+	  (if lispp
+	      (setq ev (condition-case nil
+			   (eval (eval (read form)))
+			 (error "#ERROR"))
+		    ev (if (numberp ev) (number-to-string ev) ev)
+		    ev (if duration (org-table-time-seconds-to-string
+				     (string-to-number ev)
+				     duration-output-format) ev))
+
+	    ;; Use <...> time-stamps so that Calc can handle them.
+	    (setq form
+		  (replace-regexp-in-string org-ts-regexp-inactive "<\\1>" form))
+	    ;; Internationalize local time-stamps by setting locale to
+	    ;; "C".
+	    (setq form
+		  (replace-regexp-in-string
+		   org-ts-regexp
+		   (lambda (ts)
+		     (let ((system-time-locale "C"))
+		       (format-time-string
+			(org-time-stamp-format
+			 (string-match-p "[0-9]\\{1,2\\}:[0-9]\\{2\\}" ts))
+			(apply #'encode-time
+			       (save-match-data (org-parse-time-string ts))))))
+		   form t t))
+
+	    (setq ev (if (and duration (string-match "^[0-9]+:[0-9]+\\(?::[0-9]+\\)?$" form))
+			 form
+		       (calc-eval (cons form org-tbl-calc-modes)
+				  (when (and (not keep-empty) numbers) 'num)))
 		  ev (if duration (org-table-time-seconds-to-string
-				   (string-to-number ev)
-				   duration-output-format) ev))
-
-	  ;; Use <...> time-stamps so that Calc can handle them.
-	  (setq form
-		(replace-regexp-in-string org-ts-regexp-inactive "<\\1>" form))
-	  ;; Internationalize local time-stamps by setting locale to
-	  ;; "C".
-	  (setq form
-		(replace-regexp-in-string
-		 org-ts-regexp
-		 (lambda (ts)
-		   (let ((system-time-locale "C"))
-		     (format-time-string
-		      (org-time-stamp-format
-		       (string-match-p "[0-9]\\{1,2\\}:[0-9]\\{2\\}" ts))
-		      (apply #'encode-time
-			     (save-match-data (org-parse-time-string ts))))))
-		 form t t))
-
-	  (setq ev (if (and duration (string-match "^[0-9]+:[0-9]+\\(?::[0-9]+\\)?$" form))
-		       form
-		     (calc-eval (cons form org-tbl-calc-modes)
-				(when (and (not keep-empty) numbers) 'num)))
-		ev (if duration (org-table-time-seconds-to-string
-				 (if (string-match "^[0-9]+:[0-9]+\\(?::[0-9]+\\)?$" ev)
-				     (string-to-number (org-table-time-string-to-seconds ev))
-				   (string-to-number ev))
-				 duration-output-format)
-		     ev)))
+				   (if (string-match "^[0-9]+:[0-9]+\\(?::[0-9]+\\)?$" ev)
+				       (string-to-number (org-table-time-string-to-seconds ev))
+				     (string-to-number ev))
+				   duration-output-format)
+		       ev))))
 
 	(when org-table-formula-debug
 	  (with-output-to-temp-buffer "*Substitution History*"
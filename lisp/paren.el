@@ -81,6 +81,15 @@ whitespace there."
   :type 'boolean
   :version "25.1")
 
+(defcustom show-paren-time-budget 0.05
+  "Number of seconds to spend looking for a matching paren, or nil.
+When the paren at point has no match close by, the search for it
+widens only while it can finish in this time; if it cannot, the paren
+is not highlighted.  A value of nil means no limit."
+  :type '(choice (const :tag "No limit" nil)
+                 number)
+  :version "27.1")
+
 (defcustom show-paren-highlight-openparen t
   "Non-nil turns on openparen highlighting when matching forward.
 When nil, and point stands just before an open paren, the paren
@@ -188,48 +197,23 @@ It is the default value of `show-paren-data-function'."
     (when dir
       (setq here-beg (if (eq dir 1) outside (1- outside))
 	    here-end (if (eq dir 1) (1+ outside) outside))
-      (save-restriction
-	;; Determine the range within which to look for a match.
-	(when blink-matching-paren-distance
-	  (narrow-to-region
-	   (max (point-min) (- (point) blink-matching-paren-distance))
-	   (min (point-max) (+ (point) blink-matching-paren-distance))))
-	;; Scan across one sexp within that range.
-	;; Errors or nil mean there is a mismatch.
-	(condition-case ()
-	    (setq pos (scan-sexps outside dir))
-	  (error (setq pos t mismatch t)))
-	;; Move back the other way and verify we get back to the
-	;; starting point.  If not, these two parens don't really match.
-	;; Maybe the one at point is escaped and doesn't really count,
-	;; or one is inside a comment.
-	(when (integerp pos)
-	  (unless (condition-case ()
-		      (eq outside (scan-sexps pos (- dir)))
-		    (error nil))
-	    (setq pos nil)))
-	;; If found a "matching" paren, see if it is the right
-	;; kind of paren to match the one we started at.
-	(if (not (integerp pos))
-	    (if mismatch (list here-beg here-end nil nil t))
-	  (let ((beg (min pos outside)) (end (max pos outside)))
-	    (unless (eq (syntax-class (syntax-after beg)) 8)
-	      (setq mismatch
-		    (not (or (eq (char-before end)
-				 ;; This can give nil.
-				 (cdr (syntax-after beg)))
-			     (eq (char-after beg)
-				 ;; This can give nil.
-				 (cdr (syntax-after (1- end))))
-			     ;; The cdr might hold a new paren-class
-			     ;; info rather than a matching-char info,
-			     ;; in which case the two CDRs should match.
-			     (eq (cdr (syntax-after (1- end)))
-				 (cdr (syntax-after beg)))))))
-	    (list here-beg here-end
-		  (if (= dir 1) (1- pos) pos)
-		  (if (= dir 1) pos (1+ pos))
-		  mismatch)))))))
+      (let ((found (find-matching-paren-native
+                    outside dir blink-matching-paren-distance
+                    show-paren-time-budget)))
+	;; FOUND is nil when the parens don't really match, maybe
+	;; because the one at point is escaped or one is inside a
+	;; comment, or when the search ran out of time.
+	(cond
+	 ;; No match at all.
+	 ((eq found t) (list here-beg here-end nil nil t))
+	 ;; A match, of the right kind of paren or not.
+	 ((consp found)
+	  (setq pos (car found)
+		mismatch (cdr found))
+	  (list here-beg here-end
+		(if (= dir 1) (1- pos) pos)
+		(if (= dir 1) pos (1+ pos))
+		mismatch)))))))
 
 (defun show-paren-function ()
   "Highlight the parentheses until the next input arrives."
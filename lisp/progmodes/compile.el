@@ -61,12 +61,14 @@ If nil, use Emacs default."
 		 integer)
   :group 'compilation)
 
-(defvar compilation-filter-hook nil
+(defvar compilation-filter-hook '(ansi-color-osc-compilation-filter)
   "Hook run after `compilation-filter' has inserted a string into the buffer.
 It is called with the variable `compilation-filter-start' bound
 to the position of the start of the inserted text, and point at
 its end.
 
+By default, it makes the OSC 8 hyperlinks in the output clickable.
+
 If Emacs lacks asynchronous process support, this hook is run
 after `call-process' inserts the grep output into the buffer.")
 
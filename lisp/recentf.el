@@ -91,6 +91,16 @@ file.  See also the function `set-file-modes'."
   :type '(choice (const :tag "Don't change" nil)
           integer))
 
+(defcustom recentf-use-persistent-store nil
+  "Non-nil means save the recent list in the persistent store.
+The list is then saved with `persistent-store-put', which only
+writes what changed, instead of rewriting `recentf-save-file'.  If
+the store has no recent list yet, it is loaded from
+`recentf-save-file' once."
+  :group 'recentf
+  :version "27.1"
+  :type 'boolean)
+
 (defcustom recentf-exclude nil
   "List of regexps and predicates for filenames excluded from the recent list.
 When a filename matches any of the regexps or satisfies any of the
@@ -1278,37 +1288,62 @@ Optional argument N must be a valid digit number.  It defaults to 1.
 
 (defun recentf-save-list ()
   "Save the recent list.
-Write data into the file specified by `recentf-save-file'."
+Write data into the file specified by `recentf-save-file', or into
+the persistent store if `recentf-use-persistent-store' is non-nil."
   (interactive)
   (condition-case error
-      (with-temp-buffer
-        (erase-buffer)
-        (set-buffer-file-coding-system recentf-save-file-coding-system)
-        (insert (format-message recentf-save-file-header
-				(current-time-string)))
-        (recentf-dump-variable 'recentf-list recentf-max-saved-items)
-        (recentf-dump-variable 'recentf-filter-changer-current)
-        (insert "\n\n;; Local Variables:\n"
-                (format ";; coding: %s\n" recentf-save-file-coding-system)
-                ";; End:\n")
-        (write-file (expand-file-name recentf-save-file))
-        (when recentf-save-file-modes
-          (set-file-modes recentf-save-file recentf-save-file-modes))
-        nil)
+      (if recentf-use-persistent-store
+          (progn
+            (persistent-store-put
+             'recentf-list
+             (if (natnump recentf-max-saved-items)
+                 (recentf-trunc-list recentf-list recentf-max-saved-items)
+               recentf-list))
+            (persistent-store-put 'recentf-filter-changer-current
+                                  recentf-filter-changer-current)
+            nil)
+        (with-temp-buffer
+          (erase-buffer)
+          (set-buffer-file-coding-system recentf-save-file-coding-system)
+          (insert (format-message recentf-save-file-header
+                                  (current-time-string)))
+          (recentf-dump-variable 'recentf-list recentf-max-saved-items)
+          (recentf-dump-variable 'recentf-filter-changer-current)
+          (insert "\n\n;; Local Variables:\n"
+                  (format ";; coding: %s\n" recentf-save-file-coding-system)
+                  ";; End:\n")
+          (write-file (expand-file-name recentf-save-file))
+          (when recentf-save-file-modes
+            (set-file-modes recentf-save-file recentf-save-file-modes))
+          nil))
     (error
      (warn "recentf mode: %s" (error-message-string error)))))
 
+(defun recentf--load-from-store ()
+  "Load the recent list from the persistent store.
+Return nil if the store has no recent list."
+  (let ((list (persistent-store-get 'recentf-list 'recentf--none)))
+    (unless (eq list 'recentf--none)
+      (setq recentf-list list
+            recentf-filter-changer-current
+            (persistent-store-get 'recentf-filter-changer-current))
+      t)))
+
 (defun recentf-load-list ()
   "Load a previously saved recent list.
-Read data from the file specified by `recentf-save-file'.
+Read data from the file specified by `recentf-save-file', or from
+the persistent store if `recentf-use-persistent-store' is non-nil.
 When `recentf-initialize-file-name-history' is non-nil, initialize an
 empty `file-name-history' with the recent list."
   (interactive)
   (let ((file (expand-file-name recentf-save-file))
         ;; We do not want Tramp asking for passwords.
         (non-essential t))
-    (when (file-readable-p file)
-      (load-file file)
+    (when (cond ((and recentf-use-persistent-store
+                      (recentf--load-from-store)))
+                ((file-readable-p file)
+                 (load-file file)
+                 t))
       (and recentf-initialize-file-name-history
            (not file-name-history)
            (setq file-name-history (mapcar 'abbreviate-file-name
@@ -66,9 +66,11 @@ NAME is a character (a number).  CONTENTS is a string, number, marker, list
 or a struct returned by `registerv-make'.
 A list of strings represents a rectangle.
 A list of the form (file . FILE-NAME) represents the file named FILE-NAME.
-A list of the form (file-query FILE-NAME POSITION) represents
+A list of the form (file-query FILE-NAME POSITION [LOCATION]) represents
  position POSITION in the file named FILE-NAME, but query before
- visiting it.
+ visiting it.  LOCATION, if present, is a record made by
+ `location-record', used to find the position again if the file
+ changed.
 A list of the form (WINDOW-CONFIGURATION POSITION)
  represents a saved window configuration plus a saved value of point.
 A list of the form (FRAME-CONFIGURATION POSITION)
@@ -280,19 +282,56 @@ ARG is the value of the prefix argument or nil."
 	(y-or-n-p (format "Visit file %s again? " (nth 1 val)))
 	(user-error "Register access aborted"))
     (find-file (nth 1 val))
-    (goto-char (nth 2 val)))
+    (goto-char (if (nth 3 val)
+                   (location-reconcile (nth 3 val))
+                 (nth 2 val))))
    (t (cl-call-next-method val delete))))
 
+(defun register--file-query (marker)
+  "Return a file-query reference to the position of MARKER.
+MARKER must point into a buffer that visits a file."
+  (with-current-buffer (marker-buffer marker)
+    (list 'file-query
+          buffer-file-name
+          (marker-position marker)
+          (location-record marker))))
+
 (defun register-swap-out ()
   "Turn markers into file-query references when a buffer is killed."
   (and buffer-file-name
        (dolist (elem register-alist)
 	 (and (markerp (cdr elem))
 	      (eq (marker-buffer (cdr elem)) (current-buffer))
-	      (setcdr elem
-		      (list 'file-query
-			    buffer-file-name
-			    (marker-position (cdr elem))))))))
+	      (setcdr elem (register--file-query (cdr elem)))))))
+
+(add-hook 'kill-buffer-hook #'register-swap-out)
+
+(defun register-save-positions ()
+  "Save the registers that hold positions in files in the persistent store.
+Positions in buffers visiting files are saved as file-query
+references, see `register-alist'.  Use `register-restore-positions'
+to restore them in a later session, for instance from
+`after-init-hook'."
+  (interactive)
+  (let ((saved ()))
+    (dolist (elem register-alist)
+      (let ((val (cdr elem)))
+        (cond
+         ((and (markerp val)
+               (marker-buffer val)
+               (buffer-file-name (marker-buffer val)))
+          (push (cons (car elem) (register--file-query val)) saved))
+         ((memq (car-safe val) '(file file-query))
+          (push elem saved)))))
+    (persistent-store-put 'register-positions (nreverse saved))))
+
+(defun register-restore-positions ()
+  "Restore the registers saved by `register-save-positions'.
+Registers that are already set are left alone."
+  (interactive)
+  (dolist (elem (persistent-store-get 'register-positions))
+    (unless (get-register (car elem))
+      (set-register (car elem) (cdr elem)))))
 
 (defun number-to-register (number register)
   "Store a number in a register.
@@ -1629,9 +1629,6 @@ See also `multi-occur'."
 		(prev-line nil)         ;; line number of prev match endpt
 		(prev-after-lines nil)  ;; context lines of prev match
 		(matchbeg 0)
-		(origpt nil)
-		(begpt nil)
-		(endpt nil)
 		(marker nil)
 		(curstring "")
 		(ret nil)
@@ -1649,29 +1646,22 @@ See also `multi-occur'."
 		    (not (local-variable-p 'buffer-file-coding-system))
 		    (setq coding buffer-file-coding-system))
 	        (save-excursion
-		  (goto-char (point-min)) ;; begin searching in the buffer
-		  (while (not (eobp))
-		    (setq origpt (point))
-		    (when (setq endpt (re-search-forward regexp nil t))
+		  ;; Each match is (FIRST-LINE LAST-LINE BEG END MATCHES ...),
+		  ;; with BEG and END the bounds of the matching lines.
+		  (dolist (match (cdar (collect-matches regexp buf 0 case-fold)))
+		    (let ((begpt (nth 2 match))
+			  (endpt (nth 3 match))
+			  (bounds (nth 4 match)))
 		      (setq lines (1+ lines)) ;; increment matching lines count
-		      (setq matchbeg (match-beginning 0))
-		      ;; Get beginning of first match line and end of the last.
-		      (save-excursion
-		        (goto-char matchbeg)
-		        (setq begpt (line-beginning-position))
-		        (goto-char endpt)
-		        (setq endpt (line-end-position)))
-		      ;; Sum line numbers up to the first match line.
-		      (setq curr-line (+ curr-line (count-lines origpt begpt)))
+		      (setq matchbeg (caar bounds))
+		      ;; Line numbers count from the start of the region.
+		      (setq curr-line (+ (or occur--region-start-line 1)
+					 (nth 0 match) -1))
 		      (setq marker (make-marker))
 		      (set-marker marker matchbeg)
 		      (setq curstring (occur-engine-line begpt endpt keep-props))
 		      ;; Highlight the matches
-		      (let ((len (length curstring))
-			    (start 0))
-		        ;; Count empty lines that don't use next loop (Bug#22062).
-		        (when (zerop len)
-			  (setq matches (1+ matches)))
+		      (let ((len (length curstring)))
 			(when (and list-matching-lines-jump-to-current-line
 				   (not multi-occur-p))
 			  (when (= curr-line orig-line)
@@ -1682,20 +1672,17 @@ See also `multi-occur'."
 				     (<= orig-line (+ curr-line nlines)))
 			    ;; Shown either here or will be shown by occur-context-lines
 			    (setq orig-line-shown-p t)))
-		        (while (and (< start len)
-				    (string-match regexp curstring start))
+		        ;; An empty line has one empty match (Bug#22062).
+		        (dolist (bound bounds)
 			  (setq matches (1+ matches))
 			  (add-text-properties
-			   (match-beginning 0) (match-end 0)
+			   (- (car bound) begpt) (- (cdr bound) begpt)
 			   '(occur-match t) curstring)
 			  (when match-face
 			    ;; Add `match-face' to faces copied from the buffer.
 			    (add-face-text-property
-			     (match-beginning 0) (match-end 0)
-			     match-face nil curstring))
-			  ;; Avoid infloop (Bug#7593).
-			  (let ((end (match-end 0)))
-			    (setq start (if (= start end) (1+ start) end)))))
+			     (- (car bound) begpt) (- (cdr bound) begpt)
+			     match-face nil curstring))))
 		      ;; Generate the string to insert for this match
 		      (let* ((match-prefix
 			      ;; Using 7 digits aligns tabs properly.
@@ -1770,19 +1757,8 @@ See also `multi-occur'."
 			    (insert (car (occur-engine-add-prefix
 					  (list orig-line-str) prefix-face))))
 			  (insert data)))
-		      (goto-char endpt))
-		    (if endpt
-		        (progn
-			  ;; Sum line numbers between first and last match lines.
-			  (setq curr-line (+ curr-line (count-lines begpt endpt)
-					     ;; Add 1 for empty last match line
-					     ;; since count-lines returns one
-					     ;; line less.
-					     (if (and (bolp) (eolp)) 1 0)))
-			  ;; On to the next match...
-			  (forward-line 1))
-		      (goto-char (point-max)))
-		    (setq prev-line (1- curr-line)))
+		      (setq prev-line (+ (or occur--region-start-line 1)
+					 (nth 1 match) -1))))
 		  ;; Flush remaining context after-lines.
 		  (when prev-after-lines
 		    (with-current-buffer out-buf
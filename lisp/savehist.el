@@ -107,6 +107,16 @@ the user's privacy."
   :type 'integer
   :group 'savehist)
 
+(defcustom savehist-use-persistent-store nil
+  "Non-nil means save the histories in the persistent store.
+Each variable is then saved with `persistent-store-put', which only
+writes the values that changed, instead of rewriting `savehist-file'.
+If the store has no histories yet, they are loaded from
+`savehist-file' once."
+  :type 'boolean
+  :group 'savehist
+  :version "27.1")
+
 (defcustom savehist-autosave-interval (* 5 60)
   "The interval between autosaves of minibuffer history.
 If set to nil, disables timer-based autosaving."
@@ -187,14 +197,20 @@ histories, which is probably undesirable."
   (if (not savehist-mode)
       (savehist-uninstall)
     (when (and (not savehist-loaded)
-	       (file-exists-p savehist-file))
+	       (or savehist-use-persistent-store
+		   (file-exists-p savehist-file)))
       (condition-case errvar
 	  (progn
-	    ;; Don't set coding-system-for-read -- we rely on the
-	    ;; coding cookie to convey that information.  That way, if
-	    ;; the user changes the value of savehist-coding-system,
-	    ;; we can still correctly load the old file.
-	    (load savehist-file nil (not (called-interactively-p 'interactive)))
+	    (cond ((and savehist-use-persistent-store
+			(savehist--load-from-store)))
+		  ((file-exists-p savehist-file)
+		   ;; Don't set coding-system-for-read -- we rely on the
+		   ;; coding cookie to convey that information.  That way,
+		   ;; if the user changes the value of
+		   ;; savehist-coding-system, we can still correctly load
+		   ;; the old file.
+		   (load savehist-file nil
+			 (not (called-interactively-p 'interactive)))))
 	    (setq savehist-loaded t))
 	(error
 	 ;; Don't install the mode if reading failed.  Doing so would
@@ -266,90 +282,136 @@ Normally invoked by calling `savehist-mode' to unset the minor mode."
   "Save the values of minibuffer history variables.
 Unbound symbols referenced in `savehist-additional-variables' are ignored.
 If AUTO-SAVE is non-nil, compare the saved contents to the one last saved,
- and don't save the buffer if they are the same."
+ and don't save the buffer if they are the same.
+If `savehist-use-persistent-store' is non-nil, save the variables in
+the persistent store instead of `savehist-file'."
   (interactive)
-  (with-temp-buffer
-    (insert
-     (format-message
-      (concat
-       ";; -*- mode: emacs-lisp; coding: %s -*-\n"
-       ";; Minibuffer history file, automatically generated by `savehist'.\n"
-       "\n")
-      savehist-coding-system))
-    (run-hooks 'savehist-save-hook)
-    (let ((print-length nil)
-	  (print-string-length nil)
-	  (print-level nil)
-	  (print-readably t)
-	  (print-quoted t))
-      ;; Save the minibuffer histories, along with the value of
-      ;; savehist-minibuffer-history-variables itself.
-      (when savehist-save-minibuffer-history
-	(prin1 `(setq savehist-minibuffer-history-variables
-		      ',savehist-minibuffer-history-variables)
-	       (current-buffer))
-	(insert ?\n)
-	(dolist (symbol savehist-minibuffer-history-variables)
-	  (when (and (boundp symbol)
-		     (not (memq symbol savehist-ignored-variables)))
-	    (let ((value (savehist-trim-history (symbol-value symbol)))
-		  excess-space)
-	      (when value		; Don't save empty histories.
-		(insert "(setq ")
-		(prin1 symbol (current-buffer))
-		(insert " '(")
-		;; We will print an extra space before the first element.
-		;; Record where that is.
-		(setq excess-space (point))
-		;; Print elements of VALUE one by one, carefully.
-		(dolist (elt value)
-		  (let ((start (point)))
-		    (insert " ")
-		    ;; Try to print and then to read an element.
-		    (condition-case nil
-			(progn
-			  (prin1 elt (current-buffer))
-			  (save-excursion
-			    (goto-char start)
-			    (read (current-buffer))))
-		      (error
-		       ;; If writing or reading gave an error, comment it out.
-		       (goto-char start)
-		       (insert "\n")
-		       (while (not (eobp))
-			 (insert ";;; ")
-			 (forward-line 1))
-		       (insert "\n")))
-		    (goto-char (point-max))))
-		;; Delete the extra space before the first element.
-		(save-excursion
-		  (goto-char excess-space)
-		  (if (eq (following-char) ?\s)
-		      (delete-region (point) (1+ (point)))))
-		(insert "))\n"))))))
-      ;; Save the additional variables.
-      (dolist (symbol savehist-additional-variables)
-	(when (boundp symbol)
-	  (let ((value (symbol-value symbol)))
-	    (when (savehist-printable value)
-	      (prin1 `(setq ,symbol ',value) (current-buffer))
-	      (insert ?\n))))))
-    ;; If autosaving, avoid writing if nothing has changed since the
-    ;; last write.
-    (let ((checksum (md5 (current-buffer) nil nil savehist-no-conversion)))
-      (unless (and auto-save (equal checksum savehist-last-checksum))
-	;; Set file-precious-flag when saving the buffer because we
-	;; don't want a half-finished write ruining the entire
-	;; history.  Remember that this is run from a timer and from
-	;; kill-emacs-hook, and also that multiple Emacs instances
-	;; could write to this file at once.
-	(let ((file-precious-flag t)
-	      (coding-system-for-write savehist-coding-system))
-	  (write-region (point-min) (point-max) savehist-file nil
-			(unless (called-interactively-p 'interactive) 'quiet)))
-	(when savehist-file-modes
-	  (set-file-modes savehist-file savehist-file-modes))
-	(setq savehist-last-checksum checksum)))))
+  (if savehist-use-persistent-store
+      (savehist--save-to-store)
+    (with-temp-buffer
+      (insert
+       (format-message
+	(concat
+	 ";; -*- mode: emacs-lisp; coding: %s -*-\n"
+	 ";; Minibuffer history file, automatically generated by `savehist'.\n"
+	 "\n")
+	savehist-coding-system))
+      (run-hooks 'savehist-save-hook)
+      (let ((print-length nil)
+	    (print-string-length nil)
+	    (print-level nil)
+	    (print-readably t)
+	    (print-quoted t))
+	;; Save the minibuffer histories, along with the value of
+	;; savehist-minibuffer-history-variables itself.
+	(when savehist-save-minibuffer-history
+	  (prin1 `(setq savehist-minibuffer-history-variables
+			',savehist-minibuffer-history-variables)
+		 (current-buffer))
+	  (insert ?\n)
+	  (dolist (symbol savehist-minibuffer-history-variables)
+	    (when (and (boundp symbol)
+		       (not (memq symbol savehist-ignored-variables)))
+	      (let ((value (savehist-trim-history (symbol-value symbol)))
+		    excess-space)
+		(when value		; Don't save empty histories.
+		  (insert "(setq ")
+		  (prin1 symbol (current-buffer))
+		  (insert " '(")
+		  ;; We will print an extra space before the first element.
+		  ;; Record where that is.
+		  (setq excess-space (point))
+		  ;; Print elements of VALUE one by one, carefully.
+		  (dolist (elt value)
+		    (let ((start (point)))
+		      (insert " ")
+		      ;; Try to print and then to read an element.
+		      (condition-case nil
+			  (progn
+			    (prin1 elt (current-buffer))
+			    (save-excursion
+			      (goto-char start)
+			      (read (current-buffer))))
+			(error
+			 ;; If writing or reading gave an error, comment it out.
+			 (goto-char start)
+			 (insert "\n")
+			 (while (not (eobp))
+			   (insert ";;; ")
+			   (forward-line 1))
+			 (insert "\n")))
+		      (goto-char (point-max))))
+		  ;; Delete the extra space before the first element.
+		  (save-excursion
+		    (goto-char excess-space)
+		    (if (eq (following-char) ?\s)
+			(delete-region (point) (1+ (point)))))
+		  (insert "))\n"))))))
+	;; Save the additional variables.
+	(dolist (symbol savehist-additional-variables)
+	  (when (boundp symbol)
+	    (let ((value (symbol-value symbol)))
+	      (when (savehist-printable value)
+		(prin1 `(setq ,symbol ',value) (current-buffer))
+		(insert ?\n))))))
+      ;; If autosaving, avoid writing if nothing has changed since the
+      ;; last write.
+      (let ((checksum (md5 (current-buffer) nil nil savehist-no-conversion)))
+	(unless (and auto-save (equal checksum savehist-last-checksum))
+	  ;; Set file-precious-flag when saving the buffer because we
+	  ;; don't want a half-finished write ruining the entire
+	  ;; history.  Remember that this is run from a timer and from
+	  ;; kill-emacs-hook, and also that multiple Emacs instances
+	  ;; could write to this file at once.
+	  (let ((file-precious-flag t)
+		(coding-system-for-write savehist-coding-system))
+	    (write-region (point-min) (point-max) savehist-file nil
+			  (unless (called-interactively-p 'interactive) 'quiet)))
+	  (when savehist-file-modes
+	    (set-file-modes savehist-file savehist-file-modes))
+	  (setq savehist-last-checksum checksum))))))
+
+(defun savehist--store-key (symbol)
+  "Return the key of SYMBOL's value in the persistent store."
+  (concat "savehist:" (symbol-name symbol)))
+
+(defun savehist--save-to-store ()
+  "Save the values of the history variables in the persistent store.
+The list of the saved variables is saved under the key
+\"savehist:variables\"."
+  (run-hooks 'savehist-save-hook)
+  (let ((variables ()))
+    ;; Save the minibuffer histories, along with the value of
+    ;; savehist-minibuffer-history-variables itself.
+    (when savehist-save-minibuffer-history
+      (push 'savehist-minibuffer-history-variables variables)
+      (persistent-store-put
+       (savehist--store-key 'savehist-minibuffer-history-variables)
+       savehist-minibuffer-history-variables)
+      (dolist (symbol savehist-minibuffer-history-variables)
+	(when (and (boundp symbol)
+		   (not (memq symbol savehist-ignored-variables)))
+	  (push symbol variables)
+	  (persistent-store-put (savehist--store-key symbol)
+				(savehist-trim-history
+				 (symbol-value symbol))))))
+    (dolist (symbol savehist-additional-variables)
+      (when (and (boundp symbol)
+		 (savehist-printable (symbol-value symbol)))
+	(push symbol variables)
+	(persistent-store-put (savehist--store-key symbol)
+			      (symbol-value symbol))))
+    (persistent-store-put "savehist:variables" (nreverse variables))))
+
+(defun savehist--load-from-store ()
+  "Load the variables saved by `savehist--save-to-store'.
+Return nil if the persistent store has no saved variables."
+  (let ((variables (persistent-store-get "savehist:variables"
+					 'savehist--none)))
+    (unless (eq variables 'savehist--none)
+      (dolist (symbol variables)
+	(set symbol (persistent-store-get (savehist--store-key symbol))))
+      t)))
 
 (defun savehist-autosave ()
   "Save the minibuffer history if it has been modified since the last save.
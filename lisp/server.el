@@ -488,33 +488,6 @@ If CLIENT is non-nil, add a description of it to the logged message."
       (file-error                       ;The pipe/socket was closed.
        (ignore-errors (server-delete-client proc))))))
 
-(defun server-unquote-arg (arg)
-  "Remove &-quotation from ARG.
-See `server-quote-arg' and `server-process-filter'."
-  (replace-regexp-in-string
-   "&." (lambda (s)
-	  (pcase (aref s 1)
-	    (?& "&")
-	    (?- "-")
-	    (?n "\n")
-	    (_ " ")))
-   arg t t))
-
-(defun server-quote-arg (arg)
-  "In ARG, insert a & before each &, each space, each newline, and -.
-Change spaces to underscores, too, so that the return value never
-contains a space.
-
-See `server-unquote-arg' and `server-process-filter'."
-  (replace-regexp-in-string
-   "[-&\n ]" (lambda (s)
-	       (pcase (aref s 0)
-		 (?& "&&")
-		 (?- "&-")
-		 (?\n "&n")
-		 (?\s "&_")))
-   arg t t))
-
 (defun server-send-string (proc string)
   "A wrapper around `process-send-string' for logging."
   (server-log (concat "Sent " string) proc)
@@ -1042,32 +1015,32 @@ The following commands are accepted by the client:
   (server-log (concat "Received " string) proc)
   ;; First things first: let's check the authentication
   (unless (process-get proc :authenticated)
-    (if (and (string-match "-auth \\([!-~]+\\)\n?" string)
-	     (equal (match-string 1 string) (process-get proc :auth-key)))
-	(progn
-	  (setq string (substring string (match-end 0)))
-	  (process-put proc :authenticated t)
-	  (server-log "Authentication successful" proc))
-      (server-log "Authentication failed" proc)
-      (server-send-string
-       proc (concat "-error " (server-quote-arg "Authentication failed")))
-      ;; Before calling `delete-process', give emacsclient time to
-      ;; receive the error string and shut down on its own.
-      (sit-for 1)
-      (delete-process proc)
-      ;; We return immediately.
-      (cl-return-from server-process-filter)))
+    (let ((end (server-authenticate string (process-get proc :auth-key))))
+      (if end
+          (progn
+            (setq string (substring string end))
+            (process-put proc :authenticated t)
+            (server-log "Authentication successful" proc))
+        (server-log "Authentication failed" proc)
+        (server-send-string
+         proc (concat "-error " (server-quote-arg "Authentication failed")))
+        ;; Before calling `delete-process', give emacsclient time to
+        ;; receive the error string and shut down on its own.
+        (sit-for 1)
+        (delete-process proc)
+        ;; We return immediately.
+        (cl-return-from server-process-filter))))
   (let ((prev (process-get proc 'previous-string)))
     (when prev
       (setq string (concat prev string))
       (process-put proc 'previous-string nil)))
   (condition-case err
-      (progn
+      (let (request)
 	(server-add-client proc)
 	;; Send our pid
 	(server-send-string proc (concat "-emacs-pid "
 					 (number-to-string (emacs-pid)) "\n"))
-	(if (not (string-match "\n" string))
+	(if (not (setq request (server-parse-request string)))
             ;; Save for later any partial line that remains.
             (when (> (length string) 0)
               (process-put proc 'previous-string string))
@@ -1075,9 +1048,8 @@ The following commands are accepted by the client:
           ;; In earlier versions of server.el (where we used an `emacsserver'
           ;; process), there could be multiple lines.  Nowadays this is not
           ;; supported any more.
-          (cl-assert (eq (match-end 0) (length string)))
-	  (let ((request (substring string 0 (match-beginning 0)))
-		(coding-system (or file-name-coding-system
+          (cl-assert (zerop (length (cdr request))))
+	  (let ((coding-system (or file-name-coding-system
 				   default-file-name-coding-system))
 		nowait     ; t if emacsclient does not want to wait for us.
 		frame      ; Frame opened for the client (if any).
@@ -1092,49 +1064,44 @@ The following commands are accepted by the client:
 		tty-type   ; string.
 		files
 		filepos
-		args-left)
+		(args-left (car request)))
 	    ;; Remove this line from STRING.
-	    (setq string (substring string (match-end 0)))
-	    (setq args-left
-		  (mapcar 'server-unquote-arg (split-string request " " t)))
+	    (setq string (cdr request))
 	    (while args-left
               (pcase (pop args-left)
                 ;; -version CLIENT-VERSION: obsolete at birth.
-                (`"-version" (pop args-left))
+                (`(version ,_))
 
                 ;; -nowait:  Emacsclient won't wait for a result.
-                (`"-nowait" (setq nowait t))
+                (`(nowait) (setq nowait t))
 
                 ;; -current-frame:  Don't create frames.
-                (`"-current-frame" (setq use-current-frame t))
+                (`(current-frame) (setq use-current-frame t))
 
                 ;; -frame-parameters: Set frame parameters
-                (`"-frame-parameters"
-                 (let ((alist (pop args-left)))
-                   (if coding-system
-                       (setq alist (decode-coding-string alist coding-system)))
-                   (setq frame-parameters (car (read-from-string alist)))))
+                (`(frame-parameters ,alist)
+                 (if coding-system
+                     (setq alist (decode-coding-string alist coding-system)))
+                 (setq frame-parameters (car (read-from-string alist))))
 
                 ;; -display DISPLAY:
                 ;; Open X frames on the given display instead of the default.
-                (`"-display"
-                 (setq display (pop args-left))
-                 (if (zerop (length display)) (setq display nil)))
+                (`(display ,name)
+                 (setq display (unless (zerop (length name)) name)))
 
                 ;; -parent-id ID:
                 ;; Open X frame within window ID, via XEmbed.
-                (`"-parent-id"
-                 (setq parent-id (pop args-left))
-                 (if (zerop (length parent-id)) (setq parent-id nil)))
+                (`(parent-id ,id)
+                 (setq parent-id (unless (zerop (length id)) id)))
 
                 ;; -window-system:  Open a new X frame.
-                (`"-window-system"
+                (`(window-system)
 		 (if (fboundp 'x-create-frame)
 		     (setq dontkill t
 			   tty-name 'window-system)))
 
                 ;; -resume:  Resume a suspended tty frame.
-                (`"-resume"
+                (`(resume)
                  (let ((terminal (process-get proc 'terminal)))
                    (setq dontkill t)
                    (push (lambda ()
@@ -1145,7 +1112,7 @@ The following commands are accepted by the client:
                 ;; -suspend:  Suspend the client's frame.  (In case we
                 ;; get out of sync, and a C-z sends a SIGTSTP to
                 ;; emacsclient.)
-                (`"-suspend"
+                (`(suspend)
                  (let ((terminal (process-get proc 'terminal)))
                    (setq dontkill t)
                    (push (lambda ()
@@ -1155,15 +1122,14 @@ The following commands are accepted by the client:
 
                 ;; -ignore COMMENT:  Noop; useful for debugging emacsclient.
                 ;; (The given comment appears in the server log.)
-                (`"-ignore"
-                 (setq dontkill t)
-                 (pop args-left))
+                (`(ignore ,_)
+                 (setq dontkill t))
 
 		;; -tty DEVICE-NAME TYPE:  Open a new tty frame.
 		;; (But if we see -window-system later, use that.)
-                (`"-tty"
-                 (setq tty-name (pop args-left)
-                       tty-type (pop args-left)
+                (`(tty ,name ,type)
+                 (setq tty-name name
+                       tty-type type
                        dontkill (or dontkill
                                     (not use-current-frame)))
                  ;; On Windows, emacsclient always asks for a tty
@@ -1176,66 +1142,53 @@ The following commands are accepted by the client:
                  (when (and (eq system-type 'windows-nt)
                             (or (daemonp)
                                 (eq window-system 'w32)))
-                   (push "-window-system" args-left)))
+                   (push '(window-system) args-left)))
 
                 ;; -position LINE[:COLUMN]:  Set point to the given
                 ;;  position in the next file.
-                (`"-position"
-                 (if (not (string-match "\\+\\([0-9]+\\)\\(?::\\([0-9]+\\)\\)?"
-                                        (car args-left)))
-                     (error "Invalid -position command in client args"))
-                 (let ((arg (pop args-left)))
-                   (setq filepos
-                         (cons (string-to-number (match-string 1 arg))
-                               (string-to-number (or (match-string 2 arg)
-                                                     ""))))))
+                (`(position ,line ,column)
+                 (setq filepos (cons line column)))
 
                 ;; -file FILENAME:  Load the given file.
-                (`"-file"
-                 (let ((file (pop args-left)))
-                   (if coding-system
-                       (setq file (decode-coding-string file coding-system)))
-                   ;; Allow Cygwin's emacsclient to be used as a file
-                   ;; handler on MS-Windows, in which case FILENAME
-                   ;; might start with a drive letter.
-                   (when (and (fboundp 'cygwin-convert-file-name-from-windows)
-                              (string-match "\\`[A-Za-z]:" file))
-                     (setq file (cygwin-convert-file-name-from-windows file)))
-                   (setq file (expand-file-name file dir))
-                   (push (cons file filepos) files)
-                   (server-log (format "New file: %s %s"
-                                       file (or filepos ""))
-                               proc))
+                (`(file ,file)
+                 (if coding-system
+                     (setq file (decode-coding-string file coding-system)))
+                 ;; Allow Cygwin's emacsclient to be used as a file
+                 ;; handler on MS-Windows, in which case FILENAME
+                 ;; might start with a drive letter.
+                 (when (and (fboundp 'cygwin-convert-file-name-from-windows)
+                            (string-match "\\`[A-Za-z]:" file))
+                   (setq file (cygwin-convert-file-name-from-windows file)))
+                 (setq file (expand-file-name file dir))
+                 (push (cons file filepos) files)
+                 (server-log (format "New file: %s %s"
+                                     file (or filepos ""))
+                             proc)
                  (setq filepos nil))
 
                 ;; -eval EXPR:  Evaluate a Lisp expression.
-                (`"-eval"
+                (`(eval ,expr)
                  (if use-current-frame
                      (setq use-current-frame 'always))
-                 (let ((expr (pop args-left)))
-                   (if coding-system
-                       (setq expr (decode-coding-string expr coding-system)))
-                   (push (lambda () (server-eval-and-print expr proc))
-                         commands)
-                   (setq filepos nil)))
+                 (if coding-system
+                     (setq expr (decode-coding-string expr coding-system)))
+                 (push (lambda () (server-eval-and-print expr proc))
+                       commands)
+                 (setq filepos nil))
 
                 ;; -env NAME=VALUE:  An environment variable.
-                (`"-env"
-                 (let ((var (pop args-left)))
-                   ;; XXX Variables should be encoded as in getenv/setenv.
-                   (process-put proc 'env
-                                (cons var (process-get proc 'env)))))
+                (`(env ,var)
+                 ;; XXX Variables should be encoded as in getenv/setenv.
+                 (process-put proc 'env
+                              (cons var (process-get proc 'env))))
 
                 ;; -dir DIRNAME:  The cwd of the emacsclient process.
-                (`"-dir"
-                 (setq dir (pop args-left))
+                (`(dir ,name)
+                 (setq dir name)
                  (if coding-system
                      (setq dir (decode-coding-string dir coding-system)))
                  (setq dir (command-line-normalize-file-name dir))
-                 (process-put proc 'server-client-directory dir))
-
-                ;; Unknown command.
-                (arg (error "Unknown command: %s" arg))))
+                 (process-put proc 'server-client-directory dir))))
 
 	    ;; If both -no-wait and -tty are given with file or sexp
 	    ;; arguments, use an existing frame.
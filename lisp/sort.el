@@ -204,13 +204,7 @@ REVERSE (non-nil means reverse order), BEG and END (region to sort).
 The variable `sort-fold-case' determines whether alphabetic case affects
 the sort order."
   (interactive "P\nr")
-  (save-excursion
-    (save-restriction
-      (narrow-to-region beg end)
-      (goto-char (point-min))
-      (let ;; To make `end-of-line' and etc. to ignore fields.
-	  ((inhibit-field-text-motion t))
-	(sort-subr reverse 'forward-line 'end-of-line)))))
+  (sort-lines-native beg end reverse nil nil sort-fold-case))
 
 ;;;###autoload
 (defun sort-paragraphs (reverse beg end)
@@ -277,27 +271,7 @@ With a negative arg, sorts by the ARGth field counted from the right.
 Called from a program, there are three arguments:
 FIELD, BEG and END.  BEG and END specify region to sort."
   (interactive "p\nr")
-  (let ;; To make `end-of-line' and etc. to ignore fields.
-      ((inhibit-field-text-motion t))
-    (sort-fields-1 field beg end
-		   (lambda ()
-		     (sort-skip-fields field)
-		     (let* ((case-fold-search t)
-			    (base
-			     (if (looking-at "\\(0x\\)[0-9a-f]\\|\\(0\\)[0-7]")
-				 (cond ((match-beginning 1)
-					(goto-char (match-end 1))
-					16)
-				       ((match-beginning 2)
-					(goto-char (match-end 2))
-					8)
-				       (t nil)))))
-		       (string-to-number (buffer-substring (point)
-							   (save-excursion
-							     (forward-sexp 1)
-							     (point)))
-					 (or base sort-numeric-base))))
-		   nil)))
+  (sort-lines-native beg end nil field sort-numeric-base))
 
 ;;;;;###autoload
 ;;(defun sort-float-fields (field beg end)
@@ -330,13 +304,7 @@ FIELD, BEG and END.  BEG and END specify region to sort.
 The variable `sort-fold-case' determines whether alphabetic case affects
 the sort order."
   (interactive "p\nr")
-  (let ;; To make `end-of-line' and etc. to ignore fields.
-      ((inhibit-field-text-motion t))
-    (sort-fields-1 field beg end
-		   (function (lambda ()
-			       (sort-skip-fields field)
-			       nil))
-		   (function (lambda () (skip-chars-forward "^ \t\n"))))))
+  (sort-lines-native beg end nil field nil sort-fold-case))
 
 (defun sort-fields-1 (field beg end startkeyfun endkeyfun)
   (let ((tbl (syntax-table)))
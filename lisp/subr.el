@@ -2880,72 +2880,6 @@ Note: :data and :device are currently not supported on Windows."
       (play-sound-internal sound)
     (error "This Emacs binary lacks sound support")))
 
-(declare-function w32-shell-dos-semantics "w32-fns" nil)
-
-(defun shell-quote-argument (argument)
-  "Quote ARGUMENT for passing as argument to an inferior shell.
-
-This function is designed to work with the syntax of your system's
-standard shell, and might produce incorrect results with unusual shells.
-See Info node `(elisp)Security Considerations'."
-  (cond
-   ((eq system-type 'ms-dos)
-    ;; Quote using double quotes, but escape any existing quotes in
-    ;; the argument with backslashes.
-    (let ((result "")
-          (start 0)
-          end)
-      (if (or (null (string-match "[^\"]" argument))
-              (< (match-end 0) (length argument)))
-          (while (string-match "[\"]" argument start)
-            (setq end (match-beginning 0)
-                  result (concat result (substring argument start end)
-                                 "\\" (substring argument end (1+ end)))
-                  start (1+ end))))
-      (concat "\"" result (substring argument start) "\"")))
-
-   ((and (eq system-type 'windows-nt) (w32-shell-dos-semantics))
-
-    ;; First, quote argument so that CommandLineToArgvW will
-    ;; understand it.  See
-    ;; http://msdn.microsoft.com/en-us/library/17w5ykft%28v=vs.85%29.aspx
-    ;; After we perform that level of quoting, escape shell
-    ;; metacharacters so that cmd won't mangle our argument.  If the
-    ;; argument contains no double quote characters, we can just
-    ;; surround it with double quotes.  Otherwise, we need to prefix
-    ;; each shell metacharacter with a caret.
-
-    (setq argument
-          ;; escape backslashes at end of string
-          (replace-regexp-in-string
-           "\\(\\\\*\\)$"
-           "\\1\\1"
-           ;; escape backslashes and quotes in string body
-           (replace-regexp-in-string
-            "\\(\\\\*\\)\""
-            "\\1\\1\\\\\""
-            argument)))
-
-    (if (string-match "[%!\"]" argument)
-        (concat
-         "^\""
-         (replace-regexp-in-string
-          "\\([%!()\"<>&|^]\\)"
-          "^\\1"
-          argument)
-         "^\"")
-      (concat "\"" argument "\"")))
-
-   (t
-    (if (equal argument "")
-        "''"
-      ;; Quote everything except POSIX filename characters.
-      ;; This should be safe enough even for really weird shells.
-      (replace-regexp-in-string
-       "\n" "'\n'"
-       (replace-regexp-in-string "[^-0-9a-zA-Z_./\n]" "\\\\\\&" argument))))
-   ))
-
 (defsubst string-to-list (string)
   "Return a list of characters in STRING."
   (append string nil))
@@ -3887,23 +3821,6 @@ Modifies the match data; use `save-match-data' if necessary."
 
     (nreverse list)))
 
-(defun combine-and-quote-strings (strings &optional separator)
-  "Concatenate the STRINGS, adding the SEPARATOR (default \" \").
-This tries to quote the strings to avoid ambiguity such that
-  (split-string-and-unquote (combine-and-quote-strings strs)) == strs
-Only some SEPARATORs will work properly.
-
-Note that this is not intended to protect STRINGS from
-interpretation by shells, use `shell-quote-argument' for that."
-  (let* ((sep (or separator " "))
-         (re (concat "[\\\"]" "\\|" (regexp-quote sep))))
-    (mapconcat
-     (lambda (str)
-       (if (string-match re str)
-	   (concat "\"" (replace-regexp-in-string "[\\\"]" "\\\\\\&" str) "\"")
-	 str))
-     strings sep)))
-
 (defun split-string-and-unquote (string &optional separator)
   "Split the STRING into a list of strings.
 It understands Emacs Lisp quoting within STRING, such that
@@ -831,6 +831,24 @@ Notice that a setting of 0 means \"don't truncate anything\".  This variable
 is buffer-local."
   :group 'term
   :type 'integer)
+
+(defcustom term-native-emulation t
+  "Non-nil means terminal output is interpreted by the native emulator.
+The native emulator keeps the screen of the terminal outside the
+buffer, and only rewrites the screen part of the buffer when output
+arrives, which is much faster than `term-emulate-terminal' for
+programs that write a lot or redraw the whole screen.  It takes
+effect for processes started after it is set."
+  :group 'term
+  :type 'boolean
+  :version "27.1")
+
+(defvar-local term-native nil
+  "The number of the native terminal emulator of this buffer, or nil.
+See `term-native-emulation'.")
+
+(defvar-local term-native-title nil
+  "The title the program in this terminal last set, or nil.")
 
 ;; Set up term-raw-map, etc.
 
@@ -1138,6 +1156,8 @@ Entry to this mode runs the hooks on `term-mode-hook'."
       (setq term-current-row nil)
       (setq term-current-column nil)
       (term-set-scroll-region 0 height)
+      (when term-native
+        (term-native-resize term-native width height))
       (goto-char point))))
 
 ;; Recursive routine used to check if any string in term-kill-echo-list
@@ -1412,6 +1432,14 @@ buffer.  The hook `term-exec-hook' is run after each exec."
       (set-marker (process-mark proc) (point))
       (set-process-filter proc 'term-emulate-terminal)
       (set-process-sentinel proc 'term-sentinel)
+      (term-native--free)
+      (when term-native-emulation
+        (setq term-native
+              (term-native-new term-width term-height
+                               (and (> term-buffer-maximum-size 0)
+                                    term-buffer-maximum-size)))
+        (set-process-filter proc 'term-native-filter)
+        (add-hook 'kill-buffer-hook #'term-native--free nil t))
       ;; Feed it the startfile.
       (when startfile
         ;;This is guaranteed to wait long enough
@@ -1436,6 +1464,7 @@ The main purpose is to get rid of the local keymap."
 	  ;; buffer killed
 	  (set-process-buffer proc nil)
 	(with-current-buffer buffer
+          (term-native--free)
           ;; Write something in the compilation buffer
           ;; and hack its mode line.
           ;; Get rid of local keymap.
@@ -3076,6 +3105,50 @@ See `term-prompt-regexp'."
     (when (get-buffer-window (current-buffer))
       (redisplay))))
 
+;; The native emulator.
+
+(defun term-native--free ()
+  "Free the native terminal emulator of the current buffer, if any."
+  (when term-native
+    (term-native-free term-native)
+    (setq term-native nil)))
+
+(defun term-native-filter (proc str)
+  "Process filter for terms using the native emulator.
+The terminal emulator in `term-native' interprets STR, the output of
+PROC, after which the screen at the end of the process buffer is
+redrawn and the process mark moved to the cursor.  Point, in the
+buffer and in the windows showing it, follows the cursor if it was
+at the process mark, or if the buffer is in char mode."
+  (let ((buffer (process-buffer proc)))
+    (when (buffer-live-p buffer)
+      (with-current-buffer buffer
+        (let* ((inhibit-read-only t)
+               (buffer-undo-list t)
+               (mark (marker-position (process-mark proc)))
+               (char-mode (term-in-char-mode))
+               (follow (or char-mode (eql (point) mark)))
+               windows cursor)
+          (dolist (win (get-buffer-window-list buffer nil t))
+            (when (or char-mode (eql (window-point win) mark))
+              (push win windows)))
+          (dolist (event (term-native-feed term-native str))
+            (pcase (car event)
+              ('reply (process-send-string proc (cdr event)))
+              ('title
+               (setq term-native-title (cdr event))
+               (force-mode-line-update))
+              ('bell (beep t))))
+          (save-restriction
+            (widen)
+            (save-excursion
+              (setq cursor (term-native-redraw term-native))))
+          (set-marker (process-mark proc) cursor)
+          (when follow
+            (goto-char cursor))
+          (dolist (win windows)
+            (set-window-point win cursor)))))))
+
 (defvar-local term-goto-process-mark t
   "Whether to reset point to the current process mark after this command.
 
@@ -376,6 +376,15 @@ be tested.  If it returns t, fill commands do not break the line there."
   :type 'boolean
   :group 'fill)
 
+(defcustom fill-optimal-line-breaks nil
+  "Non-nil means fill commands make the lines of a paragraph even.
+The line breaks are chosen to leave as little room as possible at the
+end of every line but the last.  Otherwise each line is made as long
+as it can be, which can leave a lot of room at the end of some lines."
+  :type 'boolean
+  :group 'fill
+  :version "27.1")
+
 (defun fill-nobreak-p ()
   "Return nil if breaking the line at point is allowed.
 Can be customized with the variables `fill-nobreak-predicate'
@@ -644,6 +653,42 @@ The break position will be always after LINEBEG and generally before point."
     (indent-line-to (current-left-margin))
     (put-text-property beg (point) 'face 'default)))
 
+(defun fill--break-lines-natively (from to justify)
+  "Break the text between FROM and TO into lines by `fill-line-breaks'.
+JUSTIFY is as for `fill-region-as-paragraph'.  Return nil without
+changing anything when the text needs the general filling loop: when
+there are predicates for places not to break, when invisible text is
+not broken, when the indentation comes from the major mode, or when
+the text has newlines."
+  (when (and (null fill-nobreak-predicate)
+	     (not fill-nobreak-invisible)
+	     (or fill-prefix (not fill-indent-according-to-mode))
+	     (save-excursion
+	       (goto-char from)
+	       (not (search-forward "\n" to t))))
+    ;; The categories of the kinsoku rules are defined by kinsoku.el.
+    (if enable-kinsoku
+	(autoload-do-load (symbol-function 'kinsoku) 'kinsoku))
+    (let ((breaks (fill-line-breaks
+		   from to (current-fill-column)
+		   (+ (current-left-margin) (string-width (or fill-prefix "")))
+		   fill-optimal-line-breaks))
+	  (shift 0))
+      (dolist (pos breaks)
+	(let ((size (buffer-size)))
+	  (goto-char (+ pos shift))
+	  (fill-newline)
+	  (when justify
+	    ;; Justify the line just ended, if desired.
+	    (save-excursion
+	      (forward-line -1)
+	      (justify-current-line justify nil t)))
+	  (setq shift (+ shift (- (buffer-size) size)))))
+      (goto-char to)
+      ;; Justify this last line, if desired.
+      (if justify (justify-current-line justify t t))
+      t)))
+
 (defun fill-region-as-paragraph (from to &optional justify
 				      nosqueeze squeeze-after)
   "Fill the region as one paragraph.
@@ -752,34 +797,35 @@ space does not end a sentence, so don't break a line there."
 
 	;; This is the actual filling loop.
 	(goto-char from)
-	(let (linebeg)
-	  (while (< (point) to)
-	    (setq linebeg (point))
-	    (move-to-column (current-fill-column))
-	    (if (when (< (point) to)
-		  ;; Find the position where we'll break the line.
-		  ;; Use an immediately following space, if any.
-		  ;; However, note that `move-to-column' may overshoot
-		  ;; if there are wide characters (Bug#3234).
-		  (unless (> (current-column) (current-fill-column))
-		    (forward-char 1))
-		  (fill-move-to-break-point linebeg)
-		  ;; Check again to see if we got to the end of
-		  ;; the paragraph.
-		  (skip-chars-forward " \t")
-		  (< (point) to))
-		;; Found a place to cut.
-		(progn
-		  (fill-newline)
-		  (when justify
-		    ;; Justify the line just ended, if desired.
-		    (save-excursion
-		      (forward-line -1)
-		      (justify-current-line justify nil t))))
-
-	      (goto-char to)
-	      ;; Justify this last line, if desired.
-	      (if justify (justify-current-line justify t t))))))
+	(unless (fill--break-lines-natively from to justify)
+	  (let (linebeg)
+	    (while (< (point) to)
+	      (setq linebeg (point))
+	      (move-to-column (current-fill-column))
+	      (if (when (< (point) to)
+		    ;; Find the position where we'll break the line.
+		    ;; Use an immediately following space, if any.
+		    ;; However, note that `move-to-column' may overshoot
+		    ;; if there are wide characters (Bug#3234).
+		    (unless (> (current-column) (current-fill-column))
+		      (forward-char 1))
+		    (fill-move-to-break-point linebeg)
+		    ;; Check again to see if we got to the end of
+		    ;; the paragraph.
+		    (skip-chars-forward " \t")
+		    (< (point) to))
+		  ;; Found a place to cut.
+		  (progn
+		    (fill-newline)
+		    (when justify
+		      ;; Justify the line just ended, if desired.
+		      (save-excursion
+			(forward-line -1)
+			(justify-current-line justify nil t))))
+
+		(goto-char to)
+		;; Justify this last line, if desired.
+		(if justify (justify-current-line justify t t)))))))
       ;; Leave point after final newline.
       (goto-char to)
       (unless (eobp) (forward-char 1))
@@ -277,6 +277,20 @@ If this variable is nil, all regions are treated as small."
   :version "21.1"
   :type '(choice number (const :tag "All small" nil)))
 
+(defcustom flyspell-builtin-dictionary nil
+  "Hunspell dictionary used instead of an Ispell process.
+If non-nil, this is the file name of a Hunspell word list, such as
+\"/usr/share/hunspell/en_US.dic\", with its affix file next to it.
+Words are then checked by `spell-check-word' and `spell-check-region',
+without starting the program named by `ispell-program-name'.
+
+Words saved from the correction menu are kept in the persistent
+store (see `persistent-store-put') rather than in the personal
+dictionary of the spell checker program."
+  :group 'flyspell
+  :version "27.1"
+  :type '(choice (const :tag "Use an Ispell process" nil) file))
+
 (defcustom flyspell-insert-function (function insert)
   "Function for inserting word by flyspell upon correction."
   :group 'flyspell
@@ -585,8 +599,10 @@ in your init file.
     ;; but SET-BUFFER was called to point to an invisible buffer, this ispell
     ;; call will reset the buffer to the buffer in the current window.
     ;; However, it only happens at startup (fix by Albert L. Ting).
-    (save-current-buffer
-      (ispell-accept-buffer-local-defs))
+    (if flyspell-builtin-dictionary
+        (flyspell--add-builtin-words)
+      (save-current-buffer
+        (ispell-accept-buffer-local-defs)))
     (unless (and (eq flyspell-dash-dictionary ispell-dictionary)
                  (eq flyspell-dash-local-dictionary ispell-local-dictionary))
       ;; The dictionary has changed
@@ -1094,6 +1110,52 @@ Mostly we check word delimiters."
 
 (defvar flyspell-word) ;Backward compatibility; some predicates made use of it!
 
+;;*---------------------------------------------------------------------*/
+;;*    flyspell--check-word ...                                         */
+;;*---------------------------------------------------------------------*/
+(defun flyspell--check-word (word)
+  "Check the spelling of WORD.
+The value is what `ispell-parse-output' returns for WORD: t if it is
+correct, or a list (WORD OFFSET MISS-LIST GUESS-LIST) if it is not."
+  (if flyspell-builtin-dictionary
+      (let ((result (spell-check-word word flyspell-builtin-dictionary)))
+        (if (eq result t)
+            t
+          (list word 1 result nil)))
+    (let (ispell-filter)
+      (ispell-send-string "%\n")          ;put in verbose mode
+      (ispell-send-string (concat "^" word "\n"))
+      ;; we mark the ispell process so it can be killed
+      ;; when emacs is exited without query
+      (set-process-query-on-exit-flag ispell-process nil)
+      ;; Wait until ispell has processed word.
+      (while (progn
+               (accept-process-output ispell-process)
+               (not (string= "" (car ispell-filter)))))
+      ;; Remove leading empty element
+      (setq ispell-filter (cdr ispell-filter))
+      ;; ispell process should return something after word is sent.
+      ;; Tag word as valid (i.e., skip) otherwise
+      (or ispell-filter
+          (setq ispell-filter '(*)))
+      (if (consp ispell-filter)
+          (ispell-parse-output (car ispell-filter))))))
+
+(defun flyspell--add-builtin-words ()
+  "Add the words accepted by the user to `flyspell-builtin-dictionary'.
+These are the words saved with `flyspell--save-builtin-word', and the
+words accepted for this buffer in this session."
+  (dolist (word (append (persistent-store-get 'flyspell-personal-words)
+                        ispell-buffer-session-localwords))
+    (spell-add-word word flyspell-builtin-dictionary)))
+
+(defun flyspell--save-builtin-word (word)
+  "Add WORD to `flyspell-builtin-dictionary', in this and later sessions."
+  (spell-add-word word flyspell-builtin-dictionary)
+  (let ((words (persistent-store-get 'flyspell-personal-words)))
+    (unless (member word words)
+      (persistent-store-put 'flyspell-personal-words (cons word words)))))
+
 ;;*---------------------------------------------------------------------*/
 ;;*    flyspell-word ...                                                */
 ;;*---------------------------------------------------------------------*/
@@ -1111,7 +1173,7 @@ misspelling and skips redundant spell-checking step."
     (flyspell-accept-buffer-local-defs)
     (let* ((cursor-location (point))
            (flyspell-word (flyspell-get-word following))
-           start end poss word ispell-filter)
+           start end poss word)
       (if (or (eq flyspell-word nil)
               (and (functionp flyspell-generic-check-word-predicate)
                    (not (funcall flyspell-generic-check-word-predicate))))
@@ -1168,27 +1230,7 @@ misspelling and skips redundant spell-checking step."
 	    (setq flyspell-word-cache-word word)
 	    ;; now check spelling of word.
             (if (not known-misspelling)
-                (progn
-                  (ispell-send-string "%\n")
-                  ;; put in verbose mode
-                  (ispell-send-string (concat "^" word "\n"))
-                  ;; we mark the ispell process so it can be killed
-                  ;; when emacs is exited without query
-		  (set-process-query-on-exit-flag ispell-process nil)
-                  ;; Wait until ispell has processed word.
-                  (while (progn
-                           (accept-process-output ispell-process)
-                           (not (string= "" (car ispell-filter)))))
-                  ;; (ispell-send-string "!\n")
-                  ;; back to terse mode.
-                  ;; Remove leading empty element
-                  (setq ispell-filter (cdr ispell-filter))
-                  ;; ispell process should return something after word is sent.
-                  ;; Tag word as valid (i.e., skip) otherwise
-                  (or ispell-filter
-                      (setq ispell-filter '(*)))
-                  (if (consp ispell-filter)
-                      (setq poss (ispell-parse-output (car ispell-filter)))))
+                (setq poss (flyspell--check-word word))
               ;; Else, this was a known misspelling to begin with, and
               ;; we should forge an ispell return value.
               (setq poss (list word 1 nil nil)))
@@ -1543,6 +1585,23 @@ The buffer to mark them in is `flyspell-large-region-buffer'."
       (backward-word 1))
     (flyspell-word)))
 
+;;*---------------------------------------------------------------------*/
+;;*    flyspell-builtin-region ...                                      */
+;;*---------------------------------------------------------------------*/
+(defun flyspell-builtin-region (beg end)
+  "Flyspell text between BEG and END with `flyspell-builtin-dictionary'.
+The whole region is checked by `spell-check-region', and only the
+misspelled words are passed to `flyspell-word', which highlights them."
+  (flyspell-accept-buffer-local-defs)
+  (flyspell-delete-region-overlays beg end)
+  (if flyspell-issue-message-flag (message "Checking region..."))
+  (save-excursion
+    (dolist (range (spell-check-region beg end flyspell-builtin-dictionary))
+      (goto-char (cdr range))
+      (flyspell-word)))
+  (flyspell-check-region-doublons beg end)
+  (if flyspell-issue-message-flag (message "Checking region...done")))
+
 ;;*---------------------------------------------------------------------*/
 ;;*    flyspell-large-region ...                                        */
 ;;*---------------------------------------------------------------------*/
@@ -1639,9 +1698,12 @@ The buffer to mark them in is `flyspell-large-region-buffer'."
 	  (let ((old beg))
 	    (setq beg end)
 	    (setq end old)))
-      (if (and flyspell-large-region (> (- end beg) flyspell-large-region))
-	  (flyspell-large-region beg end)
-	(flyspell-small-region beg end)))))
+      (cond (flyspell-builtin-dictionary
+	     (flyspell-builtin-region beg end))
+	    ((and flyspell-large-region (> (- end beg) flyspell-large-region))
+	     (flyspell-large-region beg end))
+	    (t
+	     (flyspell-small-region beg end))))))
 
 ;;*---------------------------------------------------------------------*/
 ;;*    flyspell-buffer ...                                              */
@@ -1962,23 +2024,10 @@ This command proposes various successive corrections for the current word."
               (let ((start (car (cdr word)))
                     (end (car (cdr (cdr word))))
                     (word (car word))
-                    poss ispell-filter)
+                    (poss nil))
                 (setq flyspell-auto-correct-word word)
                 ;; Now check spelling of word..
-                (ispell-send-string "%\n") ;Put in verbose mode.
-                (ispell-send-string (concat "^" word "\n"))
-                ;; Wait until ispell has processed word.
-                (while (progn
-                         (accept-process-output ispell-process)
-                         (not (string= "" (car ispell-filter)))))
-                ;; Remove leading empty element.
-                (setq ispell-filter (cdr ispell-filter))
-                ;; Ispell process should return something after word is sent.
-                ;; Tag word as valid (i.e., skip) otherwise.
-                (or ispell-filter
-                    (setq ispell-filter '(*)))
-                (if (consp ispell-filter)
-                    (setq poss (ispell-parse-output (car ispell-filter))))
+                (setq poss (flyspell--check-word word))
                 (cond
                  ((or (eq poss t) (stringp poss))
                   ;; Don't correct word.
@@ -2121,22 +2170,9 @@ If OPOINT is non-nil, restore point there after adjusting it for replacement."
 	(let ((start (car (cdr word)))
 	      (end (car (cdr (cdr word))))
 	      (word (car word))
-	      poss ispell-filter)
+	      (poss nil))
 	  ;; now check spelling of word.
-	  (ispell-send-string "%\n")	;put in verbose mode
-	  (ispell-send-string (concat "^" word "\n"))
-	  ;; wait until ispell has processed word
-	  (while (progn
-		   (accept-process-output ispell-process)
-		   (not (string= "" (car ispell-filter)))))
-	  ;; Remove leading empty element
-	  (setq ispell-filter (cdr ispell-filter))
-	  ;; ispell process should return something after word is sent.
-	  ;; Tag word as valid (i.e., skip) otherwise
-	  (or ispell-filter
-	      (setq ispell-filter '(*)))
-	  (if (consp ispell-filter)
-	      (setq poss (ispell-parse-output (car ispell-filter))))
+	  (setq poss (flyspell--check-word word))
 	  (cond
 	   ((or (eq poss t) (stringp poss))
 	    ;; don't correct word
@@ -2160,12 +2196,16 @@ If OPOINT is non-nil, restore point there after adjusting it for replacement."
 	 nil)
 	((eq replace 'save)
          (goto-char save)
-	 (ispell-send-string (concat "*" word "\n"))
-	 (ispell-send-string "#\n")
 	 (flyspell-unhighlight-at cursor-location)
-	 (setq ispell-pdict-modified-p '(t)))
+	 (if flyspell-builtin-dictionary
+	     (flyspell--save-builtin-word word)
+	   (ispell-send-string (concat "*" word "\n"))
+	   (ispell-send-string "#\n")
+	   (setq ispell-pdict-modified-p '(t))))
 	((or (eq replace 'buffer) (eq replace 'session))
-	 (ispell-send-string (concat "@" word "\n"))
+	 (if flyspell-builtin-dictionary
+	     (spell-add-word word flyspell-builtin-dictionary)
+	   (ispell-send-string (concat "@" word "\n")))
 	 (add-to-list 'ispell-buffer-session-localwords word)
 	 (or ispell-buffer-local-name ; session localwords might conflict
 	     (setq ispell-buffer-local-name (buffer-name)))
@@ -0,0 +1,352 @@
+;;; url-http-native.el --- HTTP requests with a native response parser -*- lexical-binding: t -*-
+
+;; Copyright (C) 2018 Free Software Foundation, Inc.
+
+;; Keywords: comm, data, processes
+
+;; This file is part of GNU Emacs.
+;;
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Commentary:
+
+;; `url-http-native' sends an HTTP/1.1 request and streams the body of
+;; the response into a buffer, then calls a function.  Unlike
+;; `url-retrieve', it does not keep the headers in the buffer or go
+;; through `url-http''s after-change machinery: the responses are
+;; parsed by the primitives of http.rs as they arrive, with chunked
+;; transfer coding removed and compressed content decoded.
+;;
+;; Connections are made asynchronously, over TLS for https URLs, and
+;; through the proxies of `url-proxy-services', with CONNECT tunnels
+;; for https.  Redirections are followed, and a request fails if no
+;; data arrives for `url-http-native-timeout' seconds.
+
+;;; Code:
+
+(require 'cl-lib)
+(require 'gnutls)
+(require 'url-expand)
+(require 'url-parse)
+(require 'url-vars)
+
+(declare-function url-find-proxy-for-url "url-proxy" (url host))
+(declare-function nsm-verify-connection "nsm"
+                  (process host port &optional save-fingerprint
+                           warn-unencrypted))
+
+(defcustom url-http-native-timeout 60
+  "Seconds `url-http-native' waits for data before giving up.
+nil means to wait forever."
+  :type '(choice (const :tag "Wait forever" nil) number)
+  :group 'url
+  :version "27.1")
+
+(cl-defstruct (url-http-native--request
+               (:constructor url-http-native--make-request)
+               (:copier nil))
+  url method headers data buffer callback redirects timeout proxy decode
+  ;; The state of the connection in progress.  PROXIED is non-nil if
+  ;; the request goes to an HTTP proxy, and TUNNEL if a CONNECT
+  ;; request to the proxy is under way.
+  process parser timer proxied tunnel)
+
+;;;###autoload
+(cl-defun url-http-native (url callback &key (method "GET") headers data
+                               buffer (redirects url-max-redirections)
+                               (timeout url-http-native-timeout) proxy
+                               (decode t))
+  "Send an HTTP request for URL, and call CALLBACK with the response.
+Return the buffer the body of the response goes to.
+
+The body is inserted in the buffer as it arrives, without the status
+line and headers.  When the response is complete, CALLBACK is called
+with the buffer current and one argument, a property list:
+
+  (:status STATUS :reason REASON :headers HEADERS :url URL)
+
+STATUS is the status code, REASON the reason phrase, HEADERS an alist
+of (NAME . VALUE) with NAME in lower case, and URL the URL the
+response came from, after any redirections.  If the request fails,
+the argument is (:error ERROR :url URL), where ERROR is a list
+(error MESSAGE . DATA), and the buffer holds whatever was received.
+
+The keyword arguments are:
+
+:method is the method of the request, \"GET\" by default.
+:headers is an alist of (NAME . VALUE) of headers to send.
+:data is the body of the request, a string that is encoded in
+  UTF-8 if it is multibyte.
+:buffer is the buffer to insert the body in.  By default it is a
+  new buffer, and it is made unibyte.
+:redirects is the largest number of redirections to follow,
+  `url-max-redirections' by default.
+:timeout is the number of seconds to wait for data before giving
+  up, `url-http-native-timeout' by default; nil means to wait
+  forever.
+:proxy is the URL of the HTTP proxy to go through, or `direct' to
+  go through none.  By default, it is the one given by
+  `url-proxy-services'.
+:decode, if non-nil, which is the default, says to accept bodies
+  compressed with gzip, deflate, Brotli or Zstandard, and to
+  decompress them as they arrive.  This is not done if :headers
+  has an Accept-Encoding header of its own.
+
+Only HTTP/1.1 is spoken, and each request has its own connection."
+  (let* ((buffer (or buffer
+                     (with-current-buffer (generate-new-buffer " *http*")
+                       (set-buffer-multibyte nil)
+                       (current-buffer))))
+         (request (url-http-native--make-request
+                   :url url :method method :headers headers
+                   :data (if (and data (multibyte-string-p data))
+                             (encode-coding-string data 'utf-8)
+                           data)
+                   :buffer buffer :callback callback :redirects redirects
+                   :timeout timeout :proxy proxy
+                   :decode (and decode
+                                (not (assoc-string "accept-encoding" headers
+                                                   t))))))
+    (url-http-native--start request)
+    buffer))
+
+(defun url-http-native--proxy (request url)
+  "Return the URL object of the proxy to reach URL through for REQUEST."
+  (let ((proxy (url-http-native--request-proxy request)))
+    (cond
+     ((eq proxy 'direct) nil)
+     (proxy (url-generic-parse-url proxy))
+     (t
+      (require 'url-proxy)
+      (let ((proxy (url-find-proxy-for-url url (url-host url))))
+        (cond
+         ((null proxy) nil)
+         ((string-prefix-p "http:" proxy) (url-generic-parse-url proxy))
+         (t (error "Unsupported proxy: %s" proxy))))))))
+
+(defun url-http-native--authority (url)
+  "Return the host of URL, followed by its port if it is not the default."
+  (let ((port (url-port-if-non-default url)))
+    (if port
+        (format "%s:%d" (url-host url) port)
+      (url-host url))))
+
+(defun url-http-native--start (request)
+  "Open the connection for REQUEST."
+  (let* ((url (url-generic-parse-url (url-http-native--request-url request)))
+         (https (equal (url-type url) "https"))
+         (proxy (progn
+                  (unless (member (url-type url) '("http" "https"))
+                    (error "Not an HTTP URL: %s"
+                           (url-http-native--request-url request)))
+                  (url-http-native--proxy request url)))
+         (server (or proxy url))
+         (process (open-network-stream
+                   "url-http-native" nil (url-host server) (url-port server)
+                   :type (if (and https (not proxy)) 'tls 'plain)
+                   :nowait t)))
+    (set-process-query-on-exit-flag process nil)
+    (set-process-coding-system process 'binary 'binary)
+    (set-process-filter process (lambda (process string)
+                                  (url-http-native--filter
+                                   request process string)))
+    (set-process-sentinel process (lambda (process event)
+                                    (url-http-native--sentinel
+                                     request process event)))
+    (setf (url-http-native--request-process request) process
+          (url-http-native--request-proxied request) (and proxy (not https))
+          (url-http-native--request-tunnel request) (and proxy https))
+    (url-http-native--reset-timer request)))
+
+(defun url-http-native--request-string (request)
+  "Return the text of REQUEST to send to the server."
+  (let* ((url (url-generic-parse-url (url-http-native--request-url request)))
+         (path (if (equal (url-filename url) "") "/" (url-filename url)))
+         (data (url-http-native--request-data request)))
+    (concat (url-http-native--request-method request) " "
+            (if (url-http-native--request-proxied request)
+                (concat (url-type url) "://" (url-http-native--authority url)
+                        path)
+              path)
+            " HTTP/1.1\r\n"
+            "Host: " (url-http-native--authority url) "\r\n"
+            (mapconcat (lambda (header)
+                         (concat (car header) ": " (cdr header) "\r\n"))
+                       (url-http-native--request-headers request) "")
+            (and (url-http-native--request-decode request)
+                 "Accept-Encoding: gzip, deflate, br, zstd\r\n")
+            (and data (format "Content-Length: %d\r\n" (length data)))
+            "Connection: close\r\n\r\n"
+            data)))
+
+(defun url-http-native--send (request process)
+  "Send REQUEST, or the CONNECT request of its tunnel, on PROCESS."
+  (let ((tunnel (url-http-native--request-tunnel request)))
+    (setf (url-http-native--request-parser request)
+          (http-response-parser
+           (or tunnel
+               (equal (url-http-native--request-method request) "HEAD"))
+           (url-http-native--request-decode request)))
+    (process-send-string
+     process
+     (if tunnel
+         (let* ((url (url-generic-parse-url
+                      (url-http-native--request-url request)))
+                (authority (format "%s:%d" (url-host url) (url-port url))))
+           (concat "CONNECT " authority " HTTP/1.1\r\n"
+                   "Host: " authority "\r\n\r\n"))
+       (url-http-native--request-string request)))))
+
+(defun url-http-native--stop (request)
+  "Close the connection of REQUEST, if any."
+  (let ((process (url-http-native--request-process request))
+        (parser (url-http-native--request-parser request))
+        (timer (url-http-native--request-timer request)))
+    (setf (url-http-native--request-process request) nil
+          (url-http-native--request-parser request) nil
+          (url-http-native--request-timer request) nil)
+    (when timer
+      (cancel-timer timer))
+    (when parser
+      (http-response-parser-free parser))
+    (when process
+      (delete-process process))))
+
+(defun url-http-native--finish (request status)
+  "Close the connection of REQUEST and call its callback with STATUS."
+  (url-http-native--stop request)
+  (let ((buffer (url-http-native--request-buffer request)))
+    (when (buffer-live-p buffer)
+      (with-current-buffer buffer
+        (funcall (url-http-native--request-callback request)
+                 (append status
+                         (list :url (url-http-native--request-url
+                                     request))))))))
+
+(defun url-http-native--fail (request error)
+  "Give up on REQUEST because of ERROR, a list (error MESSAGE . DATA)."
+  (when (url-http-native--request-process request)
+    (url-http-native--finish request (list :error error))))
+
+(defun url-http-native--reset-timer (request)
+  "Restart the timer that gives up on REQUEST when no data arrives."
+  (let ((timer (url-http-native--request-timer request))
+        (timeout (url-http-native--request-timeout request)))
+    (when timer
+      (cancel-timer timer))
+    (setf (url-http-native--request-timer request)
+          (and timeout
+               (run-at-time timeout nil #'url-http-native--fail request
+                            '(error "Timed out"))))))
+
+(defun url-http-native--sentinel (request process event)
+  "Handle EVENT, a change in the status of PROCESS, for REQUEST."
+  (when (eq process (url-http-native--request-process request))
+    (let ((parser (url-http-native--request-parser request)))
+      (cond
+       ((string-prefix-p "open" event)
+        (condition-case err
+            (url-http-native--send request process)
+          (error (url-http-native--fail request err))))
+       ((memq (process-status process) '(open connect run)))
+       ((and parser (http-response-parser-finish parser))
+        (url-http-native--done request))
+       (t
+        (url-http-native--fail
+         request
+         (list 'error "Connection closed" (car (split-string event "\n")))))))))
+
+(defun url-http-native--open-tunnel (request process status)
+  "Start TLS on PROCESS for REQUEST, after a CONNECT request got STATUS."
+  (if (not (and status (<= 200 status 299)))
+      (url-http-native--fail
+       request (list 'error "Proxy refused to connect" status))
+    (http-response-parser-free (url-http-native--request-parser request))
+    (setf (url-http-native--request-parser request) nil
+          (url-http-native--request-tunnel request) nil)
+    (let ((url (url-generic-parse-url (url-http-native--request-url request))))
+      (gnutls-negotiate :process process :hostname (url-host url)
+                        :verify-error nil)
+      (if (nsm-verify-connection process (url-host url) (url-port url))
+          (url-http-native--send request process)
+        (url-http-native--fail
+         request (list 'error "Certificate rejected" (url-host url)))))))
+
+(defun url-http-native--redirect (request status headers)
+  "Follow the redirection in the response to REQUEST, if there is one.
+STATUS and HEADERS are those of the response.  Return non-nil if
+the redirection is followed."
+  (let ((location (cdr (assoc "location" headers))))
+    (when (and location (memq status '(301 302 303 307 308)))
+      (if (<= (url-http-native--request-redirects request) 0)
+          (progn
+            (url-http-native--fail
+             request (list 'error "Too many redirections" location))
+            t)
+        (url-http-native--stop request)
+        (cl-decf (url-http-native--request-redirects request))
+        (setf (url-http-native--request-url request)
+              (url-expand-file-name
+               (decode-coding-string location 'utf-8)
+               (url-http-native--request-url request)))
+        ;; Like browsers, turn POST into GET except for 307 and 308.
+        (when (or (= status 303)
+                  (and (memq status '(301 302))
+                       (equal (url-http-native--request-method request)
+                              "POST")))
+          (setf (url-http-native--request-method request) "GET"
+                (url-http-native--request-data request) nil))
+        (with-current-buffer (url-http-native--request-buffer request)
+          (erase-buffer))
+        (condition-case err
+            (url-http-native--start request)
+          (error (url-http-native--fail request err)))
+        t))))
+
+(defun url-http-native--done (request)
+  "Deliver the complete response to REQUEST."
+  (let ((head (http-response-parser-head
+               (url-http-native--request-parser request))))
+    (url-http-native--finish
+     request (list :status (nth 0 head) :reason (nth 1 head)
+                   :headers (nth 2 head)))))
+
+(defun url-http-native--filter (request process string)
+  "Handle STRING, output of PROCESS for REQUEST."
+  (when (eq process (url-http-native--request-process request))
+    (url-http-native--reset-timer request)
+    (condition-case err
+        (let* ((parser (url-http-native--request-parser request))
+               (body (http-response-parser-feed parser string))
+               (head (http-response-parser-head parser)))
+          (cond
+           ((url-http-native--request-tunnel request)
+            (when (http-response-parser-done-p parser)
+              (url-http-native--open-tunnel request process (car head))))
+           ;; Don't wait for the body of a redirection.
+           ((and head (url-http-native--redirect
+                       request (nth 0 head) (nth 2 head))))
+           (t
+            (unless (zerop (length body))
+              (with-current-buffer (url-http-native--request-buffer request)
+                (save-excursion
+                  (goto-char (point-max))
+                  (insert body))))
+            (when (http-response-parser-done-p parser)
+              (url-http-native--done request)))))
+      (error (url-http-native--fail request err)))))
+
+(provide 'url-http-native)
+
+;;; url-http-native.el ends here
@@ -905,19 +905,27 @@ should be shown to the user."
     (goto-char (point-min))
     success))
 
-(declare-function zlib-decompress-region "decompress.c" (start end))
+(declare-function zlib-decompress-region "decompress.rs" (start end))
+(declare-function brotli-decompress-region "decompress.rs" (start end))
 
 (defun url-handle-content-transfer-encoding ()
-  (let ((encoding (mail-fetch-field "content-encoding")))
-    (when (and encoding
-	       (fboundp 'zlib-available-p)
-	       (zlib-available-p)
-	       (equal (downcase encoding) "gzip"))
+  (let* ((encoding (mail-fetch-field "content-encoding"))
+         (decompress
+          (cond
+           ((null encoding) nil)
+           ((and (equal (downcase encoding) "gzip")
+                 (fboundp 'zlib-available-p)
+                 (zlib-available-p))
+            #'zlib-decompress-region)
+           ((and (equal (downcase encoding) "br")
+                 (fboundp 'brotli-decompress-region))
+            #'brotli-decompress-region))))
+    (when decompress
       (save-restriction
 	(widen)
 	(goto-char (point-min))
 	(when (search-forward "\n\n")
-	  (zlib-decompress-region (point) (point-max)))))))
+	  (funcall decompress (point) (point-max)))))))
 
 ;; Miscellaneous
 (defun url-http-activate-callback ()
@@ -148,81 +148,24 @@ parses to
   FULLNESS = t"
   (if (null url)
       (url-parse-make-urlobj)
-    (with-temp-buffer
-      ;; Don't let those temp-buffer modifications accidentally
-      ;; deactivate the mark of the current-buffer.
-      (let ((deactivate-mark nil))
-        (set-syntax-table url-parse-syntax-table)
-	(erase-buffer)
-	(insert url)
-	(goto-char (point-min))
-        (let ((save-pos (point))
-              scheme user pass host port file fragment full
-              (inhibit-read-only t))
-
-          ;; 3.1. Scheme
-	  ;; This is nil for a URI that is not fully specified.
-          (when (looking-at "\\([a-zA-Z][-a-zA-Z0-9+.]*\\):")
-	    (goto-char (match-end 0))
-            (setq save-pos (point))
-	    (setq scheme (downcase (match-string 1))))
-
-          ;; 3.2. Authority
-          (when (looking-at "//")
-            (setq full t)
-            (forward-char 2)
-            (setq save-pos (point))
-            (skip-chars-forward "^/?#")
-            (setq host (buffer-substring save-pos (point)))
-	    ;; 3.2.1 User Information
-            (if (string-match "^\\([^@]+\\)@" host)
-                (setq user (match-string 1 host)
-                      host (substring host (match-end 0))))
-            (if (and user (string-match "\\`\\([^:]*\\):\\(.*\\)" user))
-                (setq pass (match-string 2 user)
-                      user (match-string 1 user)))
-            (cond
-	     ;; IPv6 literal address.
-	     ((string-match "^\\(\\[[^]]+\\]\\)\\(?::\\([0-9]*\\)\\)?$" host)
-	      (setq port (match-string 2 host)
-		    host (match-string 1 host)))
-	     ;; Registered name or IPv4 address.
-	     ((string-match ":\\([0-9]*\\)$" host)
-	      (setq port (match-string 1 host)
-		    host (substring host 0 (match-beginning 0)))))
-	    (cond ((equal port "")
-		   (setq port nil))
-		  (port
-		   (setq port (string-to-number port))))
-            (setq host (downcase host)))
-
-	  ;; Now point is on the / ? or # which terminates the
-	  ;; authority, or at the end of the URI, or (if there is no
-	  ;; authority) at the beginning of the absolute path.
-
-          (setq save-pos (point))
-          (if (string= "data" scheme)
-	      ;; For the "data" URI scheme, all the rest is the FILE.
-	      (setq file (buffer-substring save-pos (point-max)))
-	    ;; For hysterical raisins, our data structure returns the
-	    ;; path and query components together in one slot.
-	    ;; 3.3. Path
-	    (skip-chars-forward "^?#")
-	    ;; 3.4. Query
-	    (when (looking-at "?")
-	      (skip-chars-forward "^#"))
-	    (setq file (buffer-substring save-pos (point)))
-	    ;; 3.5 Fragment
-	    (when (looking-at "#")
-	      (let ((opoint (point)))
-		(forward-char 1)
-                (setq fragment (buffer-substring (point) (point-max)))
-		(delete-region opoint (point-max)))))
-
-          (if (and host (string-match "%[0-9][0-9]" host))
-              (setq host (url-unhex-string host)))
-          (url-parse-make-urlobj scheme user pass host port file
-				 fragment nil full))))))
+    (let* ((parts (url-parse-native url))
+           (scheme (plist-get parts :scheme))
+           (host (plist-get parts :host))
+           (query (plist-get parts :query))
+           ;; For hysterical raisins, our data structure returns the
+           ;; path and query components together in one slot.
+           (file (concat (plist-get parts :path) (and query "?") query))
+           (fragment (plist-get parts :fragment)))
+      ;; For the "data" URI scheme, all the rest is the FILE.
+      (when (and (equal scheme "data") fragment)
+        (setq file (concat file "#" fragment)
+              fragment nil))
+      (if (and host (string-match "%[0-9][0-9]" host))
+          (setq host (url-unhex-string host)))
+      (url-parse-make-urlobj scheme (plist-get parts :user)
+                             (plist-get parts :password) host
+                             (plist-get parts :port) file fragment nil
+                             (and host t)))))
 
 (defmacro url-bit-for-url (method lookfor url)
   `(let* ((urlobj (url-generic-parse-url ,url))
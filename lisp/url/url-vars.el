@@ -217,9 +217,12 @@ Should be an assoc list of headers/contents.")
 
 (defvar url-request-method nil "The method to use for the next request.")
 
-(defvar url-mime-encoding-string (and (fboundp 'zlib-available-p)
-				      (zlib-available-p)
-				      "gzip")
+(defvar url-mime-encoding-string (cond
+                                  ((fboundp 'brotli-decompress-region)
+                                   "gzip, br")
+                                  ((and (fboundp 'zlib-available-p)
+                                        (zlib-available-p))
+                                   "gzip"))
   "String to send in the Accept-encoding: field in HTTP requests.")
 
 (defvar mm-mime-mule-charset-alist)
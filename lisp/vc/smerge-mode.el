@@ -570,6 +570,24 @@ major modes.  Uses `smerge-resolve-function' to do the actual work."
               (zerop (call-process diff-command nil buf nil "-bc" b m)))
             (set-match-data md)
 	    (smerge-keep-n 3))
+	   ;; Try a three-way merge of UPPER and LOWER.  Leave BUF alone,
+	   ;; since the next test reuses its patch.
+	   ((when (and (not safe) m2e (not (eq m2b m2e)))
+	      (let ((source (current-buffer))
+		    (merged (generate-new-buffer " *smerge-merge*")))
+		(unwind-protect
+		    (when (zerop (merge-regions-native (list source m1b m1e)
+						       (list source m2b m2e)
+						       (list source m3b m3e)
+						       merged))
+		      (save-restriction
+			(narrow-to-region m0b m0e)
+			(smerge-remove-props m0b m0e)
+			(replace-buffer-contents merged))
+		      t)
+		  (kill-buffer merged))))
+	    ;; Nothing to do: the conflict was replaced by the merge.
+	    nil)
 	   ;; Try "diff -b BASE UPPER | patch LOWER".
 	   ((when (and (not safe) m2e b
                        ;; If the BASE is empty, this would just concatenate
@@ -954,9 +972,9 @@ It has the following disadvantages:
 
 (defvar smerge--refine-long-words)
 
-(defun smerge--refine-chopup-region (beg end file &optional preproc)
+(defun smerge--refine-chopup-region (beg end buffer &optional preproc)
   "Chopup the region into small elements, one per line.
-Save the result into FILE.
+Put the result into BUFFER.
 If non-nil, PREPROC is called with no argument in a buffer that contains
 a copy of the text, just before chopping it up.  It can be used to replace
 chars to try and eliminate some spurious differences."
@@ -966,7 +984,8 @@ chars to try and eliminate some spurious differences."
   ;; there aren't any, so the resulting "change" didn't make much sense.
   ;; You can still get this behavior by setting
   ;; `smerge-refine-forward-function' to `forward-char'.
-  (with-temp-buffer
+  (with-current-buffer buffer
+    (erase-buffer)
     (insert-buffer-substring (marker-buffer beg) beg end)
     (when preproc (goto-char (point-min)) (funcall preproc))
     (when smerge-refine-ignore-whitespace
@@ -1016,17 +1035,17 @@ chars to try and eliminate some spurious differences."
                   (insert short)
                   (setq s short)))
               (dotimes (_i (1- len)) (insert s)))))))
-    (unless (bolp) (error "Smerge refine internal error"))
-    (let ((coding-system-for-write 'utf-8-emacs-unix))
-      (write-region (point-min) (point-max) file nil 'nomessage))))
+    (unless (bolp) (error "Smerge refine internal error"))))
 
-(defun smerge--refine-highlight-change (beg match-num1 match-num2 props)
+(defun smerge--refine-highlight-change (beg startline nlines props)
+  "Highlight the elements of a chopped up region that changed.
+BEG is the start of the region, and the changed elements are the
+NLINES lines after the first STARTLINE lines of its chopped up copy."
   ;; TODO: Add a property pointing to the corresponding text in the
   ;; other region.
   (with-current-buffer (marker-buffer beg)
     (goto-char beg)
-    (let* ((startline (- (string-to-number match-num1) 1))
-           (beg (progn (funcall (if smerge-refine-weight-hack
+    (let* ((beg (progn (funcall (if smerge-refine-weight-hack
                                     #'forward-char
                                   smerge-refine-forward-function)
                                 startline)
@@ -1034,10 +1053,7 @@ chars to try and eliminate some spurious differences."
            (end (progn (funcall (if smerge-refine-weight-hack
                                     #'forward-char
                                   smerge-refine-forward-function)
-                          (if match-num2
-                              (- (string-to-number match-num2)
-                                 startline)
-                            1))
+                                nlines)
                        (point))))
       (when smerge-refine-ignore-whitespace
         (skip-chars-backward " \t\n" beg) (setq end (point))
@@ -1069,82 +1085,63 @@ a copy of a region, just before preparing it to for `diff'.  It can be
 used to replace chars to try and eliminate some spurious differences."
   (let* ((pos (point))
          deactivate-mark         ; The code does not modify any visible buffer.
-         (file1 (make-temp-file "diff1"))
-         (file2 (make-temp-file "diff2"))
+         (buffer1 (generate-new-buffer " *smerge-refine-1*"))
+         (buffer2 (generate-new-buffer " *smerge-refine-2*"))
          (smerge--refine-long-words
           (if smerge-refine-weight-hack (make-hash-table :test #'equal))))
     (unless (markerp beg1) (setq beg1 (copy-marker beg1)))
     (unless (markerp beg2) (setq beg2 (copy-marker beg2)))
-    ;; Chop up regions into smaller elements and save into files.
-    (smerge--refine-chopup-region beg1 end1 file1 preproc)
-    (smerge--refine-chopup-region beg2 end2 file2 preproc)
-
-    ;; Call diff on those files.
     (unwind-protect
-        (with-temp-buffer
-          ;; Allow decoding the EOL format, as on MS-Windows the Diff
-          ;; utility might produce CR-LF EOLs.
-          (let ((coding-system-for-read 'utf-8-emacs))
-            (call-process diff-command nil t nil
-                          (if (and smerge-refine-ignore-whitespace
-                                   (not smerge-refine-weight-hack))
-                              ;; Pass -a so diff treats it as a text file even
-                              ;; if it contains \0 and such.
-                              ;; Pass -d so as to get the smallest change, but
-                              ;; also and more importantly because otherwise it
-                              ;; may happen that diff doesn't behave like
-                              ;; smerge-refine-weight-hack expects it to.
-                              ;; See https://lists.gnu.org/r/emacs-devel/2007-11/msg00401.html
-                              "-awd" "-ad")
-                          file1 file2))
-          ;; Process diff's output.
-          (goto-char (point-min))
-          (let ((last1 nil)
-                (last2 nil))
-            (while (not (eobp))
-              (if (not (looking-at "\\([0-9]+\\)\\(?:,\\([0-9]+\\)\\)?\\([acd]\\)\\([0-9]+\\)\\(?:,\\([0-9]+\\)\\)?$"))
-                  (error "Unexpected patch hunk header: %s"
-                         (buffer-substring (point) (line-end-position))))
-              (let ((op (char-after (match-beginning 3)))
-                    (m1 (match-string 1))
-                    (m2 (match-string 2))
-                    (m4 (match-string 4))
-                    (m5 (match-string 5)))
-                (when (memq op '(?d ?c))
-                  (setq last1
-                        (smerge--refine-highlight-change
-			 beg1 m1 m2
-			 ;; Try to use props-c only for changed chars,
-			 ;; fallback to props-r for changed/removed chars,
-			 ;; but if props-r is nil then fallback to props-c.
-			 (or (and (eq op '?c) props-c) props-r props-c))))
-                (when (memq op '(?a ?c))
-                  (setq last2
-                        (smerge--refine-highlight-change
-			 beg2 m4 m5
-			 ;; Same logic as for removed chars above.
-			 (or (and (eq op '?c) props-c) props-a props-c)))))
-              (forward-line 1)                            ;Skip hunk header.
-              (and (re-search-forward "^[0-9]" nil 'move) ;Skip hunk body.
-                   (goto-char (match-beginning 0))))
-            ;; (cl-assert (or (null last1) (< (overlay-start last1) end1)))
-            ;; (cl-assert (or (null last2) (< (overlay-start last2) end2)))
-            (if smerge-refine-weight-hack
-                (progn
-                  ;; (cl-assert (or (null last1) (<= (overlay-end last1) end1)))
-                  ;; (cl-assert (or (null last2) (<= (overlay-end last2) end2)))
-                  )
-              ;; smerge-refine-forward-function when calling in chopup may
-              ;; have stopped because it bumped into EOB whereas in
-              ;; smerge-refine-weight-hack it may go a bit further.
-              (if (and last1 (> (overlay-end last1) end1))
-                  (move-overlay last1 (overlay-start last1) end1))
-              (if (and last2 (> (overlay-end last2) end2))
-                  (move-overlay last2 (overlay-start last2) end2))
-              )))
+        (let ((last1 nil)
+              (last2 nil))
+          ;; Chop up regions into smaller elements, one per line.
+          (smerge--refine-chopup-region beg1 end1 buffer1 preproc)
+          (smerge--refine-chopup-region beg2 end2 buffer2 preproc)
+          ;; Compare them line by line.  Ignoring whitespace is the
+          ;; equivalent of diff's -w; smerge-refine-weight-hack relies on
+          ;; every space being reported.
+          (dolist (hunk (diff-regions-native
+                         buffer1 buffer2 'lines nil nil
+                         (and smerge-refine-ignore-whitespace
+                              (not smerge-refine-weight-hack))))
+            (pcase-let* ((`(,hbeg1 ,hend1 ,hbeg2 ,hend2) hunk)
+                         (changed (and (< hbeg1 hend1) (< hbeg2 hend2))))
+              (when (< hbeg1 hend1)
+                (setq last1
+                      (smerge--refine-highlight-change
+                       beg1
+                       (with-current-buffer buffer1 (count-lines 1 hbeg1))
+                       (with-current-buffer buffer1 (count-lines hbeg1 hend1))
+                       ;; Try to use props-c only for changed chars,
+                       ;; fallback to props-r for changed/removed chars,
+                       ;; but if props-r is nil then fallback to props-c.
+                       (or (and changed props-c) props-r props-c))))
+              (when (< hbeg2 hend2)
+                (setq last2
+                      (smerge--refine-highlight-change
+                       beg2
+                       (with-current-buffer buffer2 (count-lines 1 hbeg2))
+                       (with-current-buffer buffer2 (count-lines hbeg2 hend2))
+                       ;; Same logic as for removed chars above.
+                       (or (and changed props-c) props-a props-c))))))
+          ;; (cl-assert (or (null last1) (< (overlay-start last1) end1)))
+          ;; (cl-assert (or (null last2) (< (overlay-start last2) end2)))
+          (if smerge-refine-weight-hack
+              (progn
+                ;; (cl-assert (or (null last1) (<= (overlay-end last1) end1)))
+                ;; (cl-assert (or (null last2) (<= (overlay-end last2) end2)))
+                )
+            ;; smerge-refine-forward-function when calling in chopup may
+            ;; have stopped because it bumped into EOB whereas in
+            ;; smerge-refine-weight-hack it may go a bit further.
+            (if (and last1 (> (overlay-end last1) end1))
+                (move-overlay last1 (overlay-start last1) end1))
+            (if (and last2 (> (overlay-end last2) end2))
+                (move-overlay last2 (overlay-start last2) end2))
+            ))
       (goto-char pos)
-      (delete-file file1)
-      (delete-file file2))))
+      (kill-buffer buffer1)
+      (kill-buffer buffer2))))
 (define-obsolete-function-alias 'smerge-refine-subst
   #'smerge-refine-regions "26.1")
 
@@ -0,0 +1,432 @@
+//! The core of `align-region'.
+//!
+//! Aligning a region with a rule searches for the rule's regexp, groups
+//! the matches into sections, and then moves the text after the
+//! matching groups of each section to a common column.  Doing that from
+//! Lisp costs markers, `match-data' copies and column computations for
+//! every match, which makes aligning large tables slow.  Here the
+//! matches are collected as plain positions first, and the sections are
+//! aligned from the last to the first so that the edits in one section
+//! do not move the others.
+
+use libc::ptrdiff_t;
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    buffers::validate_region,
+    cmds::forward_line,
+    editfns::{bolp, char_after, char_before, line_end_position, point, save_excursion_save},
+    indent::current_column,
+    lisp::{defsubr, LispObject},
+    lists::plist_get,
+    obarray::intern,
+    occur::count_newlines,
+    remacs_sys::{del_range, record_unwind_protect, save_excursion_restore, set_point, EmacsInt},
+    remacs_sys::{Findent_to, Fmove_to_column, Qnil, Qt},
+    search::{re_search_forward, MatchData, SaveMatchData},
+    threads::ThreadState,
+};
+
+/// How the matches of a rule are divided into sections, as for
+/// `align-region-separate'.
+enum Separator {
+    /// The whole region is one section.
+    Entire,
+    /// A section ends at a line without a match.
+    Group,
+    /// A section ends at a match for a regexp.
+    Regexp(LispObject),
+    /// A section ends at one of these positions.
+    Positions(Vec<ptrdiff_t>),
+}
+
+impl Separator {
+    fn from_lisp(separate: LispObject) -> Self {
+        if separate.is_nil() || separate.eq(intern("entire").as_lisp_obj()) {
+            Separator::Entire
+        } else if separate.eq(intern("group").as_lisp_obj()) {
+            Separator::Group
+        } else if separate.is_string() {
+            Separator::Regexp(separate)
+        } else {
+            Separator::Positions(
+                separate
+                    .iter_cars()
+                    .map(|pos| pos.as_fixnum_or_error() as ptrdiff_t)
+                    .collect(),
+            )
+        }
+    }
+
+    /// Return true if there is a section divider between BEG and END.
+    fn divides(&self, beg: ptrdiff_t, end: ptrdiff_t) -> bool {
+        match self {
+            Separator::Entire => false,
+            Separator::Group => count_newlines(beg, end) >= 2,
+            Separator::Regexp(regexp) => {
+                unsafe { set_point(beg) };
+                re_search_forward(*regexp, LispObject::from(end), Qt, Qnil).is_not_nil()
+            }
+            Separator::Positions(positions) => {
+                positions.iter().any(|&pos| pos >= beg && pos <= end)
+            }
+        }
+    }
+}
+
+/// An alignment rule, as compiled by `align--compile-rule'.
+struct Rule {
+    regexp: LispObject,
+    groups: Vec<usize>,
+    spacing: Vec<EmacsInt>,
+    tab_stop: Vec<bool>,
+    column: Option<EmacsInt>,
+    justify: bool,
+    repeat: bool,
+    separate: Separator,
+    exclude: Vec<(ptrdiff_t, ptrdiff_t)>,
+}
+
+impl Rule {
+    fn from_plist(plist: LispObject) -> Self {
+        let get = |prop: &str| plist_get(plist, intern(prop).as_lisp_obj());
+
+        let regexp = get(":regexp");
+        regexp.as_string_or_error();
+        let groups: Vec<usize> = get(":group")
+            .iter_cars()
+            .map(|group| group.as_natnum_or_error() as usize)
+            .collect();
+        if groups.is_empty() {
+            error!("Alignment rule has no groups");
+        }
+        let spacing: Vec<EmacsInt> = get(":spacing")
+            .iter_cars()
+            .map(LispObject::as_fixnum_or_error)
+            .collect();
+        if spacing.is_empty() {
+            error!("Alignment rule has no spacing");
+        }
+        let exclude = get(":exclude")
+            .iter_cars()
+            .map(|area| {
+                let (beg, end) = area.as_cons_or_error().as_tuple();
+                (
+                    beg.as_fixnum_coerce_marker_or_error() as ptrdiff_t,
+                    end.as_fixnum_coerce_marker_or_error() as ptrdiff_t,
+                )
+            })
+            .collect();
+
+        Self {
+            regexp,
+            groups,
+            spacing,
+            tab_stop: get(":tab-stop")
+                .iter_cars()
+                .map(|t| t.is_not_nil())
+                .collect(),
+            column: get(":column").as_fixnum(),
+            justify: get(":justify").is_not_nil(),
+            repeat: get(":repeat").is_not_nil(),
+            separate: Separator::from_lisp(get(":separate")),
+            exclude,
+        }
+    }
+
+    /// Return the spacing after the aligned column INDEX of a line.
+    /// The last element of the rule's list applies to the rest.
+    fn spacing(&self, index: usize) -> EmacsInt {
+        self.spacing[index.min(self.spacing.len() - 1)]
+    }
+
+    /// Return whether the spacing after the aligned column INDEX is
+    /// counted in tab stops.
+    fn tab_stop(&self, index: usize) -> bool {
+        self.tab_stop
+            .get(index.min(self.tab_stop.len().saturating_sub(1)))
+            .cloned()
+            .unwrap_or(false)
+    }
+
+    /// Return true if the text between BEG and END overlaps an excluded
+    /// area.
+    fn is_excluded(&self, beg: ptrdiff_t, end: ptrdiff_t) -> bool {
+        self.exclude
+            .iter()
+            .any(|&(excl_beg, excl_end)| beg < excl_end && end > excl_beg)
+    }
+}
+
+/// The text matched by a group of the rule, whose end is moved to the
+/// alignment column.  With `justify', TEXT is the end of the whitespace
+/// at the start of the group, and the text after it is right-justified.
+struct Area {
+    beg: ptrdiff_t,
+    end: ptrdiff_t,
+    text: Option<ptrdiff_t>,
+}
+
+impl Area {
+    fn shift(&mut self, delta: ptrdiff_t) {
+        self.beg += delta;
+        self.end += delta;
+        self.text = self.text.map(|text| text + delta);
+    }
+}
+
+/// The areas of a line, in order; the Nth area of every line of a
+/// section is aligned to the same column.
+type Row = Vec<Area>;
+
+fn zv() -> ptrdiff_t {
+    ThreadState::current_buffer().zv
+}
+
+fn column_at(pos: ptrdiff_t) -> EmacsInt {
+    unsafe { set_point(pos) };
+    current_column()
+}
+
+fn indent_to(column: EmacsInt) {
+    unsafe { Findent_to(LispObject::from(column), Qnil) };
+}
+
+fn move_to_column(column: EmacsInt) {
+    unsafe { Fmove_to_column(LispObject::from(column), Qt) };
+}
+
+fn is_blank(c: Option<EmacsInt>) -> bool {
+    c == Some(EmacsInt::from(b' ')) || c == Some(EmacsInt::from(b'\t'))
+}
+
+/// Return COLUMN moved on by SPACING, counted in tab stops if TAB-STOP,
+/// like `align-adjust-col-for-rule'.
+fn adjust_column(column: EmacsInt, spacing: EmacsInt, tab_stop: bool) -> EmacsInt {
+    if spacing <= 0 {
+        column
+    } else if !tab_stop {
+        column + spacing
+    } else {
+        let next_tab_stop = intern("indent-next-tab-stop").as_lisp_obj();
+        (0..spacing).fold(column, |column, _| {
+            call!(next_tab_stop, LispObject::from(column)).as_fixnum_or_error()
+        })
+    }
+}
+
+/// Return the sections of matches for RULE between BEG and END.
+fn collect_sections(rule: &Rule, beg: ptrdiff_t, end: ptrdiff_t) -> Vec<Vec<Row>> {
+    let mut sections = Vec::new();
+    let mut rows: Vec<Row> = Vec::new();
+    let mut last_point = None;
+    let mut same = false;
+    let mut eol = 0;
+
+    let mut pos = beg;
+    while pos < end {
+        let search_start = pos;
+        unsafe { set_point(pos) };
+        if re_search_forward(rule.regexp, LispObject::from(end), Qt, Qnil).is_nil() {
+            break;
+        }
+        // A match that ends at the start of a line belongs to the line
+        // before.
+        if bolp() && point() as ptrdiff_t > search_start {
+            unsafe { set_point(point() as ptrdiff_t - 1) };
+        }
+        if point() as ptrdiff_t > eol {
+            same = false;
+            eol = line_end_position(None) as ptrdiff_t;
+        }
+
+        let areas: Vec<(ptrdiff_t, ptrdiff_t)> = rule
+            .groups
+            .iter()
+            .map(|&group| match MatchData::register(group) {
+                Some(range) => range,
+                None => {
+                    error!("No match for subexpression {}", group);
+                }
+            })
+            .collect();
+        let rule_beg = areas[0].0;
+        let next = point() as ptrdiff_t;
+
+        if let Some(last) = last_point {
+            if rule.separate.divides(last, rule_beg) && !rows.is_empty() {
+                sections.push(rows);
+                rows = Vec::new();
+            }
+        }
+        last_point = Some(rule_beg);
+
+        let (excl_beg, excl_end) = (areas[0].0, areas[areas.len() - 1].1);
+        if !rule.is_excluded(excl_beg, excl_end) {
+            let areas = areas.into_iter().map(|(beg, end)| Area {
+                beg,
+                end,
+                text: None,
+            });
+            match rows.last_mut() {
+                Some(row) if same => row.extend(areas),
+                _ => rows.push(areas.collect()),
+            }
+            same = true;
+        }
+
+        unsafe { set_point(next) };
+        if !rule.repeat && !bolp() {
+            forward_line(None);
+        }
+        pos = point() as ptrdiff_t;
+        if pos == search_start {
+            pos += 1;
+        }
+    }
+    if !rows.is_empty() {
+        sections.push(rows);
+    }
+    sections
+}
+
+/// Move the end of AREA to COLUMN, or the start of its text with
+/// `justify', by adjusting the whitespace before it.
+fn align_area(rule: &Rule, index: usize, area: &Area, column: EmacsInt) {
+    let (target, goal) = match area.text {
+        Some(text) => (text, column - (column_at(area.end) - column_at(text))),
+        None => (area.end, column),
+    };
+    let current = column_at(target);
+    if goal < 0 || current == goal {
+        return;
+    }
+
+    if current < goal {
+        // Remove the whitespace before the target first, so that no
+        // tabs come after spaces.
+        let mut start = target;
+        while is_blank(char_before(LispObject::from(start))) {
+            start -= 1;
+        }
+        unsafe { del_range(start, target) };
+        indent_to(goal);
+    } else {
+        let abuts = goal <= column_at(area.beg);
+        if abuts {
+            unsafe { set_point(area.beg) };
+        } else {
+            move_to_column(goal);
+        }
+        let here = point() as ptrdiff_t;
+        move_to_column(current);
+        unsafe { del_range(here, point() as ptrdiff_t) };
+        if abuts {
+            indent_to(adjust_column(
+                current_column(),
+                rule.spacing(index),
+                rule.tab_stop(index),
+            ));
+        }
+    }
+}
+
+/// Align the areas INDEX of ROWS, like `align-areas'.
+fn align_areas(rule: &Rule, rows: &mut [Row], index: usize) {
+    let mut column = rule.column.unwrap_or(0);
+    let mut width = 0;
+    let mut end_column = None;
+    let mut change = false;
+
+    for area in rows.iter_mut().filter_map(|row| row.get_mut(index)) {
+        if rule.column.is_none() {
+            column = column.max(column_at(area.beg));
+        }
+        if !change {
+            let current = column_at(area.end);
+            match end_column {
+                Some(end_column) => change = end_column != current,
+                None => end_column = Some(current),
+            }
+        }
+        if rule.justify {
+            let mut text = area.beg;
+            while text < area.end && is_blank(char_after(LispObject::from(text))) {
+                text += 1;
+            }
+            if text != area.end {
+                area.text = Some(text);
+                width = width.max(column_at(area.end) - column_at(text));
+            }
+        }
+    }
+
+    if rule.column.is_none() {
+        column = adjust_column(column, rule.spacing(index), rule.tab_stop(index)) + width;
+    }
+    if !change && end_column.map_or(true, |end_column| end_column == column) {
+        return;
+    }
+
+    // The edits in a row move the rest of the row and the rows after.
+    let mut shift = 0;
+    for row in rows.iter_mut() {
+        for area in row.iter_mut() {
+            area.shift(shift);
+        }
+        if let Some(area) = row.get(index) {
+            let size = zv();
+            align_area(rule, index, area, column);
+            let delta = zv() - size;
+            for area in row.iter_mut().skip(index + 1) {
+                area.shift(delta);
+            }
+            shift += delta;
+        }
+    }
+}
+
+/// Align the region between BEG and END according to RULE.
+/// RULE is an alignment rule in the form `align--compile-rule' returns
+/// it, a property list with these properties:
+///
+/// :regexp    The regexp that matches the text to align.
+/// :group     The list of the parenthesized groups of the regexp whose
+///            ends are aligned.
+/// :spacing   The list of spacings after the aligned columns of a line;
+///            the last one applies to the rest.
+/// :tab-stop  The list of whether each spacing counts tab stops.
+/// :column    The column to align to, or nil to align to the furthest
+///            group.
+/// :justify   Non-nil to right-justify the text after the whitespace
+///            at the start of each group.
+/// :repeat    Non-nil to align more than one match on a line.
+/// :separate  How the region is divided into sections, as for
+///            `align-region-separate', except that it cannot be a
+///            function.
+/// :exclude   A list of conses (BEG . END) of text that is not aligned.
+///
+/// Point and the match data are preserved.
+#[lisp_fn]
+pub fn align_region_native(mut beg: LispObject, mut end: LispObject, rule: LispObject) {
+    unsafe { validate_region(&mut beg, &mut end) };
+    let beg = beg.as_fixnum_or_error() as ptrdiff_t;
+    let end = end.as_fixnum_or_error() as ptrdiff_t;
+    let rule = Rule::from_plist(rule);
+
+    let _match_data = SaveMatchData::save();
+    unsafe { record_unwind_protect(Some(save_excursion_restore), save_excursion_save()) };
+
+    let mut sections = collect_sections(&rule, beg, end);
+    // Align the last section first, so that the positions in the
+    // others stay valid.
+    for rows in sections.iter_mut().rev() {
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        for index in 0..columns {
+            align_areas(&rule, rows, index);
+        }
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/align_exports.rs"));
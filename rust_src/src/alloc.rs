@@ -1,12 +1,15 @@
 //! Storage allocation and gc
 
+use libc::c_int;
+
 use remacs_macros::lisp_fn;
 
 use crate::{
     lisp::{defsubr, LispObject},
-    remacs_sys::globals,
-    remacs_sys::EmacsInt,
+    obarray::intern,
+    remacs_sys::{alloc_context, experimental_gc, globals, record_unwind_protect_int},
     remacs_sys::{bool_vector_fill, bool_vector_set, bounded_number, make_uninit_bool_vector},
+    remacs_sys::{EmacsInt, Qnil},
 };
 
 /// Return a list of counters that measure how much consing there has been.
@@ -22,20 +25,200 @@ use crate::{
 ///   (but the contents of a buffer's text do not count here).
 #[lisp_fn]
 pub fn memory_use_counts() -> Vec<LispObject> {
+    consed()
+        .iter()
+        .map(|&n| unsafe { bounded_number(n) })
+        .collect()
+}
+
+/// The number of counters returned by `memory-use-counts'.
+const COUNTERS: usize = 8;
+
+/// The counters of `memory-use-counts', in its order.
+fn consed() -> [EmacsInt; COUNTERS] {
     unsafe {
-        vec![
-            bounded_number(globals.cons_cells_consed),
-            bounded_number(globals.floats_consed),
-            bounded_number(globals.vector_cells_consed),
-            bounded_number(globals.symbols_consed),
-            bounded_number(globals.string_chars_consed),
-            bounded_number(globals.misc_objects_consed),
-            bounded_number(globals.intervals_consed),
-            bounded_number(globals.strings_consed),
+        [
+            globals.cons_cells_consed,
+            globals.floats_consed,
+            globals.vector_cells_consed,
+            globals.symbols_consed,
+            globals.string_chars_consed,
+            globals.misc_objects_consed,
+            globals.intervals_consed,
+            globals.strings_consed,
         ]
     }
 }
 
+/// The allocations charged to each subsystem while
+/// `gc-allocation-accounting' is non-nil.  Rather than being tagged one
+/// by one, allocations are charged by taking the difference of the
+/// counters of `memory-use-counts' whenever the context changes.
+struct AllocAccounts {
+    /// Whether the allocations since `snapshot' are being charged.
+    active: bool,
+    /// The context the current allocations are charged to.
+    context: alloc_context::Type,
+    /// The counters when `context' was entered.
+    snapshot: [EmacsInt; COUNTERS],
+    totals: [[EmacsInt; COUNTERS]; alloc_context::ALLOC_CONTEXTS as usize],
+}
+
+static mut ALLOC_ACCOUNTS: AllocAccounts = AllocAccounts {
+    active: false,
+    context: alloc_context::ALLOC_OTHER,
+    snapshot: [0; COUNTERS],
+    totals: [[0; COUNTERS]; alloc_context::ALLOC_CONTEXTS as usize],
+};
+
+/// Charge the allocations since the last switch to the current context,
+/// and start charging them to CONTEXT.
+fn switch_alloc_context(context: alloc_context::Type) {
+    let accounts = unsafe { &mut ALLOC_ACCOUNTS };
+    let now = consed();
+    if accounts.active {
+        let totals = &mut accounts.totals[accounts.context as usize];
+        for i in 0..COUNTERS {
+            totals[i] = totals[i].wrapping_add(now[i].wrapping_sub(accounts.snapshot[i]));
+        }
+    }
+    accounts.active = unsafe { globals.gc_allocation_accounting };
+    accounts.snapshot = now;
+    accounts.context = context;
+}
+
+extern "C" fn pop_alloc_context(previous: c_int) {
+    switch_alloc_context(previous as alloc_context::Type);
+}
+
+/// Charge the allocations to CONTEXT until the specpdl is unbound to
+/// where it is now.  The contexts nest, and the allocations are charged
+/// to the innermost one.  This does nothing but test
+/// `gc-allocation-accounting' while it is nil.
+#[no_mangle]
+pub extern "C" fn push_alloc_context(context: alloc_context::Type) {
+    let previous = unsafe { ALLOC_ACCOUNTS.context };
+    if !unsafe { ALLOC_ACCOUNTS.active || globals.gc_allocation_accounting } || previous == context
+    {
+        return;
+    }
+    switch_alloc_context(context);
+    unsafe { record_unwind_protect_int(Some(pop_alloc_context), previous as c_int) };
+}
+
+/// Return the allocations made by each subsystem.
+/// They are counted while `gc-allocation-accounting' is non-nil.  The
+/// value is an alist whose elements are (SUBSYSTEM CONSES FLOATS
+/// VECTOR-CELLS SYMBOLS STRING-CHARS MISCS INTERVALS STRINGS), where
+/// SUBSYSTEM is `reader', `evaluator', `fontification' or `other', and
+/// the counts are those of `memory-use-counts' for the objects that the
+/// subsystem allocated.  Allocations made by the evaluator while it
+/// runs the reader, or the other way around, are charged to the
+/// innermost one.
+#[lisp_fn]
+pub fn gc_allocation_report() -> LispObject {
+    let current = unsafe { ALLOC_ACCOUNTS.context };
+    switch_alloc_context(current);
+    let names = ["other", "reader", "evaluator", "fontification"];
+    let report: Vec<LispObject> = names
+        .iter()
+        .enumerate()
+        .map(|(context, name)| {
+            let counts = unsafe { ALLOC_ACCOUNTS.totals[context] };
+            let counts: Vec<LispObject> = counts
+                .iter()
+                .map(|&n| unsafe { bounded_number(n) })
+                .collect();
+            LispObject::cons(intern(name).as_lisp_obj(), LispObject::from(counts))
+        })
+        .collect();
+    LispObject::from(report)
+}
+
+/// Forget the allocations counted by `gc-allocation-accounting'.
+#[lisp_fn]
+pub fn gc_allocation_report_reset() {
+    let current = unsafe { ALLOC_ACCOUNTS.context };
+    switch_alloc_context(current);
+    unsafe { ALLOC_ACCOUNTS.totals = [[0; COUNTERS]; alloc_context::ALLOC_CONTEXTS as usize] };
+}
+
+/// The write barrier of the generational collector, enabled by
+/// `--experimental-gc'.  A minor collection would only mark from the
+/// roots and from the objects that were changed to point to others
+/// since the last collection.  The stores that C code makes directly
+/// bypass the barrier, so the collections are still all full ones, and
+/// the barrier only counts the stores it sees, to measure how much a
+/// minor collection would have to mark.  It doesn't remember the
+/// objects, since nothing would use them yet.
+struct WriteBarrier {
+    /// The number of stores seen by the barrier.
+    stores: EmacsInt,
+    /// The number of stores seen since the last collection.
+    recent: EmacsInt,
+    /// The largest number of stores seen between two collections.
+    largest: EmacsInt,
+    /// The number of collections since the barrier was enabled.
+    collections: EmacsInt,
+}
+
+static mut WRITE_BARRIER: WriteBarrier = WriteBarrier {
+    stores: 0,
+    recent: 0,
+    largest: 0,
+    collections: 0,
+};
+
+/// Note that CONTAINER was changed to hold VALUE.  Only stores of
+/// objects that live in the heap are counted.
+pub fn write_barrier(_container: LispObject, value: LispObject) {
+    if !unsafe { experimental_gc } || value.is_nil() || value.is_fixnum() {
+        return;
+    }
+    let barrier = unsafe { &mut WRITE_BARRIER };
+    barrier.stores += 1;
+    barrier.recent += 1;
+}
+
+/// Start counting the stores anew, at the end of a collection.
+#[no_mangle]
+pub extern "C" fn gc_write_barrier_reset() {
+    if !unsafe { experimental_gc } {
+        return;
+    }
+    let barrier = unsafe { &mut WRITE_BARRIER };
+    barrier.collections += 1;
+    barrier.largest = barrier.largest.max(barrier.recent);
+    barrier.recent = 0;
+}
+
+/// Return what the write barrier of the generational collector saw.
+/// The barrier is enabled by starting Emacs with `--experimental-gc';
+/// otherwise, the value is nil.  The value is a plist:
+///
+/// :stores       The number of stores of objects by `setcar', `setcdr' and `aset'.
+/// :recent       The number of those stores since the last collection.
+/// :largest      The largest number of stores between two collections.
+/// :collections  The number of collections.
+#[lisp_fn]
+pub fn gc_write_barrier_statistics() -> LispObject {
+    if !unsafe { experimental_gc } {
+        return Qnil;
+    }
+    let barrier = unsafe { &WRITE_BARRIER };
+    let keyword = |name: &str| intern(name).as_lisp_obj();
+    list!(
+        keyword(":stores"),
+        LispObject::from(barrier.stores),
+        keyword(":recent"),
+        LispObject::from(barrier.recent),
+        keyword(":largest"),
+        LispObject::from(barrier.largest.max(barrier.recent)),
+        keyword(":collections"),
+        LispObject::from(barrier.collections)
+    )
+}
+
 /// Return a new bool-vector of length LENGTH, using INIT for each element.
 /// LENGTH must be a number.  INIT matters only in whether it is t or nil.
 #[lisp_fn]
@@ -0,0 +1,924 @@
+//! Native interpretation of ANSI escape sequences in process output.
+//!
+//! The parser splits raw output into plain text runs and control
+//! sequences.  SGR ("Select Graphic Rendition") sequences update a
+//! `SgrState', which is turned into an anonymous face for the text that
+//! follows; every other complete CSI sequence is dropped.  An escape
+//! sequence cut in half at the end of a chunk of output is kept as a
+//! fragment and prepended to the next chunk, so callers can feed
+//! process output to it as it arrives.
+//!
+//! Of the OSC ("Operating System Command") sequences, OSC 8 hyperlinks
+//! are turned into buttons that visit the link, and OSC 0 and OSC 2 set
+//! `ansi-color-window-title', which `frame-title-format' can show.
+//! Other OSC sequences are dropped.
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    buffers::{force_mode_line_update, validate_region},
+    data::set,
+    editfns::{buffer_substring_no_properties, save_excursion_save},
+    eval::unbind_to,
+    lisp::defsubr,
+    lisp::LispObject,
+    lists::get,
+    multibyte::LispStringRef,
+    obarray::intern,
+    remacs_sys::{
+        del_range, insert_1_both, make_specified_string, record_unwind_protect,
+        save_excursion_restore, set_point,
+    },
+    remacs_sys::{Fadd_face_text_property, Fadd_text_properties, Fput_text_property},
+    remacs_sys::{
+        QCbackground, QCforeground, QCinverse_video, QCslant, QCstrike_through, QCunderline,
+        QCweight, Qbold, Qcategory, Qface, Qhelp_echo, Qitalic, Qlight, Qnil, Qt,
+    },
+    remacs_sys::{EmacsInt, Fcopy_sequence},
+    threads::{c_specpdl_index, ThreadState},
+};
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+
+/// The longest OSC sequence that is waited for.  An `ESC ]' that is not
+/// terminated within this many bytes is dropped by itself, so that a
+/// stray one does not swallow the rest of the output.
+const MAX_OSC_LENGTH: usize = 4096;
+
+/// A color selected by an SGR sequence.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnsiColor {
+    /// One of the 256 indexed xterm colors.
+    Indexed(u8),
+    /// A 24-bit color.
+    Rgb(u8, u8, u8),
+}
+
+impl AnsiColor {
+    /// The standard xterm RGB value of this color.
+    pub fn rgb(self) -> (u8, u8, u8) {
+        const BASE: [(u8, u8, u8); 16] = [
+            (0x00, 0x00, 0x00),
+            (0xcd, 0x00, 0x00),
+            (0x00, 0xcd, 0x00),
+            (0xcd, 0xcd, 0x00),
+            (0x00, 0x00, 0xee),
+            (0xcd, 0x00, 0xcd),
+            (0x00, 0xcd, 0xcd),
+            (0xe5, 0xe5, 0xe5),
+            (0x7f, 0x7f, 0x7f),
+            (0xff, 0x00, 0x00),
+            (0x00, 0xff, 0x00),
+            (0xff, 0xff, 0x00),
+            (0x5c, 0x5c, 0xff),
+            (0xff, 0x00, 0xff),
+            (0x00, 0xff, 0xff),
+            (0xff, 0xff, 0xff),
+        ];
+
+        match self {
+            AnsiColor::Rgb(r, g, b) => (r, g, b),
+            AnsiColor::Indexed(n) if n < 16 => BASE[n as usize],
+            AnsiColor::Indexed(n) if n < 232 => {
+                // The 6x6x6 color cube.
+                let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+                let n = n - 16;
+                (level(n / 36), level((n / 6) % 6), level(n % 6))
+            }
+            AnsiColor::Indexed(n) => {
+                // The grayscale ramp.
+                let v = 8 + (n - 232) * 10;
+                (v, v, v)
+            }
+        }
+    }
+
+    pub fn to_hex(self) -> String {
+        let (r, g, b) = self.rgb();
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    }
+}
+
+/// The graphic rendition in effect at some point of the output.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SgrState {
+    pub bold: bool,
+    pub faint: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub blink: bool,
+    pub inverse: bool,
+    pub conceal: bool,
+    pub strike: bool,
+    pub foreground: Option<AnsiColor>,
+    pub background: Option<AnsiColor>,
+}
+
+/// Parse the color following a 38 or 48 parameter, advancing PARAMS.
+fn parse_extended_color<'a, I>(params: &mut I) -> Option<AnsiColor>
+where
+    I: Iterator<Item = &'a u32>,
+{
+    match params.next() {
+        Some(5) => params.next().map(|&n| AnsiColor::Indexed(n.min(255) as u8)),
+        Some(2) => {
+            let mut component = || params.next().map_or(0, |&n| n.min(255) as u8);
+            let r = component();
+            let g = component();
+            let b = component();
+            Some(AnsiColor::Rgb(r, g, b))
+        }
+        _ => None,
+    }
+}
+
+impl SgrState {
+    /// Apply the parameters of one SGR sequence.  An empty parameter
+    /// list is equivalent to a reset.
+    pub fn apply(&mut self, params: &[u32]) {
+        if params.is_empty() {
+            *self = SgrState::default();
+            return;
+        }
+
+        let mut iter = params.iter();
+        while let Some(&param) = iter.next() {
+            match param {
+                0 => *self = SgrState::default(),
+                1 => self.bold = true,
+                2 => self.faint = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                5 | 6 => self.blink = true,
+                7 => self.inverse = true,
+                8 => self.conceal = true,
+                9 => self.strike = true,
+                21 | 22 => {
+                    self.bold = false;
+                    self.faint = false;
+                }
+                23 => self.italic = false,
+                24 => self.underline = false,
+                25 => self.blink = false,
+                27 => self.inverse = false,
+                28 => self.conceal = false,
+                29 => self.strike = false,
+                30..=37 => self.foreground = Some(AnsiColor::Indexed((param - 30) as u8)),
+                38 => {
+                    if let Some(color) = parse_extended_color(&mut iter) {
+                        self.foreground = Some(color);
+                    }
+                }
+                39 => self.foreground = None,
+                40..=47 => self.background = Some(AnsiColor::Indexed((param - 40) as u8)),
+                48 => {
+                    if let Some(color) = parse_extended_color(&mut iter) {
+                        self.background = Some(color);
+                    }
+                }
+                49 => self.background = None,
+                90..=97 => self.foreground = Some(AnsiColor::Indexed((param - 90 + 8) as u8)),
+                100..=107 => self.background = Some(AnsiColor::Indexed((param - 100 + 8) as u8)),
+                _ => {}
+            }
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == SgrState::default()
+    }
+
+    /// Return an anonymous face (a property list of face attributes)
+    /// for this rendition, or nil for the default rendition.
+    pub fn to_face(&self) -> LispObject {
+        let mut attrs = Vec::new();
+        if self.bold {
+            attrs.push(QCweight);
+            attrs.push(Qbold);
+        } else if self.faint {
+            attrs.push(QCweight);
+            attrs.push(Qlight);
+        }
+        if self.italic {
+            attrs.push(QCslant);
+            attrs.push(Qitalic);
+        }
+        if self.underline {
+            attrs.push(QCunderline);
+            attrs.push(Qt);
+        }
+        if self.strike {
+            attrs.push(QCstrike_through);
+            attrs.push(Qt);
+        }
+        if self.inverse {
+            attrs.push(QCinverse_video);
+            attrs.push(Qt);
+        }
+        // Concealed text is drawn in the background color.
+        let foreground = if self.conceal && self.background.is_some() {
+            self.background
+        } else {
+            self.foreground
+        };
+        if let Some(fg) = foreground {
+            attrs.push(QCforeground);
+            attrs.push(LispObject::from(fg.to_hex().as_str()));
+        }
+        if let Some(bg) = self.background {
+            attrs.push(QCbackground);
+            attrs.push(LispObject::from(bg.to_hex().as_str()));
+        }
+
+        LispObject::from(attrs)
+    }
+
+    /// Serialize this state for the CONTEXT argument of the Lisp
+    /// functions below.
+    fn to_lisp(&self) -> LispObject {
+        let flags = [
+            self.bold,
+            self.faint,
+            self.italic,
+            self.underline,
+            self.blink,
+            self.inverse,
+            self.conceal,
+            self.strike,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |acc, (i, &set)| if set { acc | (1 << i) } else { acc });
+
+        let color = |c: Option<AnsiColor>| match c {
+            None => Qnil,
+            Some(AnsiColor::Indexed(n)) => LispObject::from(EmacsInt::from(n)),
+            Some(AnsiColor::Rgb(r, g, b)) => LispObject::from(
+                (EmacsInt::from(r) << 16) | (EmacsInt::from(g) << 8) | EmacsInt::from(b) | 1 << 24,
+            ),
+        };
+
+        list!(
+            LispObject::from(flags as EmacsInt),
+            color(self.foreground),
+            color(self.background)
+        )
+    }
+
+    fn from_lisp(obj: LispObject) -> SgrState {
+        let mut iter = obj.iter_cars_safe();
+        let flags = iter.next().and_then(|f| f.as_fixnum()).unwrap_or(0);
+        let color = |c: Option<LispObject>| {
+            c.and_then(|c| c.as_fixnum()).map(|n| {
+                if n < 256 {
+                    AnsiColor::Indexed(n as u8)
+                } else {
+                    AnsiColor::Rgb((n >> 16) as u8, (n >> 8) as u8, n as u8)
+                }
+            })
+        };
+        let bit = |i: u32| flags & (1 << i) != 0;
+
+        SgrState {
+            bold: bit(0),
+            faint: bit(1),
+            italic: bit(2),
+            underline: bit(3),
+            blink: bit(4),
+            inverse: bit(5),
+            conceal: bit(6),
+            strike: bit(7),
+            foreground: color(iter.next()),
+            background: color(iter.next()),
+        }
+    }
+}
+
+/// One element of parsed output.
+#[derive(Debug, PartialEq)]
+pub enum AnsiToken<'a> {
+    /// Plain text.
+    Text(&'a [u8]),
+    /// An SGR sequence with its numeric parameters.
+    Sgr(Vec<u32>),
+    /// An OSC 8 sequence, which starts a hyperlink to a URI, or ends the
+    /// current one if the URI is empty.
+    Hyperlink(Option<&'a [u8]>),
+    /// An OSC 0 or OSC 2 sequence, which sets the window title.
+    Title(&'a [u8]),
+    /// Any other complete control sequence, which is dropped.
+    Other(&'a [u8]),
+}
+
+/// Return the length of the control sequence at the start of INPUT
+/// (which begins with ESC), or `None' if the sequence is incomplete.
+fn escape_length(input: &[u8]) -> Option<usize> {
+    match input.get(1) {
+        None => None,
+        Some(b'[') => {
+            // CSI: parameter bytes 0x30-0x3F, intermediate bytes
+            // 0x20-0x2F, then a final byte 0x40-0x7E.
+            input[2..]
+                .iter()
+                .position(|&b| b >= 0x40 && b <= 0x7e)
+                .map(|idx| idx + 3)
+        }
+        Some(b']') => {
+            // OSC: a string terminated by BEL or ST (`ESC \\').  Any
+            // other escape sequence cancels it.
+            let body = &input[2..];
+            match body.iter().position(|&b| b == BEL || b == ESC) {
+                Some(idx) if body[idx] == BEL => Some(idx + 3),
+                Some(idx) => match body.get(idx + 1) {
+                    Some(b'\\') => Some(idx + 4),
+                    Some(_) => Some(idx + 2),
+                    None if idx < MAX_OSC_LENGTH => None,
+                    None => Some(2),
+                },
+                None if body.len() < MAX_OSC_LENGTH => None,
+                None => Some(2),
+            }
+        }
+        // A two-character escape sequence.
+        Some(_) => Some(2),
+    }
+}
+
+fn parse_sgr_params(params: &[u8]) -> Vec<u32> {
+    if params.is_empty() {
+        return Vec::new();
+    }
+    params
+        .split(|&b| b == b';' || b == b':')
+        .map(|p| {
+            p.iter()
+                .take_while(|b| b.is_ascii_digit())
+                .fold(0u32, |acc, &d| acc.saturating_mul(10).saturating_add(u32::from(d - b'0')))
+        })
+        .collect()
+}
+
+/// Parse the complete OSC sequence SEQ.
+fn parse_osc(seq: &[u8]) -> AnsiToken {
+    let end = if seq.ends_with(&[BEL]) {
+        seq.len() - 1
+    } else if seq.ends_with(&[ESC, b'\\']) {
+        seq.len() - 2
+    } else {
+        // Cancelled by another escape sequence.
+        return AnsiToken::Other(seq);
+    };
+    let mut fields = seq[2..end].splitn(2, |&b| b == b';');
+    match (fields.next(), fields.next()) {
+        (Some(b"0"), Some(title)) | (Some(b"2"), Some(title)) => AnsiToken::Title(title),
+        (Some(b"8"), Some(link)) => {
+            // The parameters, such as an id, come before the URI.
+            let uri = link.splitn(2, |&b| b == b';').nth(1).unwrap_or(b"");
+            AnsiToken::Hyperlink(if uri.is_empty() { None } else { Some(uri) })
+        }
+        _ => AnsiToken::Other(seq),
+    }
+}
+
+/// Split INPUT into tokens.  Return them together with the length of
+/// the trailing incomplete escape sequence, if any.
+pub fn tokenize(input: &[u8]) -> (Vec<AnsiToken>, usize) {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < input.len() {
+        match input[pos..].iter().position(|&b| b == ESC) {
+            None => {
+                tokens.push(AnsiToken::Text(&input[pos..]));
+                break;
+            }
+            Some(idx) => {
+                if idx > 0 {
+                    tokens.push(AnsiToken::Text(&input[pos..pos + idx]));
+                }
+                let start = pos + idx;
+                match escape_length(&input[start..]) {
+                    None => return (tokens, input.len() - start),
+                    Some(len) => {
+                        let seq = &input[start..start + len];
+                        if seq[1] == b'[' && seq[len - 1] == b'm' {
+                            tokens.push(AnsiToken::Sgr(parse_sgr_params(&seq[2..len - 1])));
+                        } else if seq[1] == b']' && len > 2 {
+                            tokens.push(parse_osc(seq));
+                        } else {
+                            tokens.push(AnsiToken::Other(seq));
+                        }
+                        pos = start + len;
+                    }
+                }
+            }
+        }
+    }
+
+    (tokens, 0)
+}
+
+/// The result of interpreting one chunk of output.
+struct Interpreted {
+    /// The text with all control sequences removed.
+    text: Vec<u8>,
+    /// Runs of `text', as (start byte, end byte, rendition).
+    runs: Vec<(usize, usize, SgrState)>,
+    /// Runs of `text' inside a hyperlink, as (start byte, end byte, URI).
+    links: Vec<(usize, usize, Vec<u8>)>,
+    /// Byte ranges of the removed control sequences in the input.
+    removed: Vec<(usize, usize)>,
+    /// The rendition in effect at the end of the chunk.
+    state: SgrState,
+    /// The URI of the hyperlink open at the end of the chunk.
+    link: Option<Vec<u8>>,
+    /// The last window title set in the chunk.
+    title: Option<Vec<u8>>,
+    /// The incomplete escape sequence at the end of the chunk.
+    fragment: Vec<u8>,
+}
+
+impl Interpreted {
+    /// Append T to the text, in the current rendition and hyperlink.
+    fn push_text(&mut self, t: &[u8]) {
+        let start = self.text.len();
+        self.text.extend_from_slice(t);
+        let end = self.text.len();
+        let state = self.state;
+        match self.runs.last_mut() {
+            Some(&mut (_, ref mut run_end, ref s)) if *run_end == start && *s == state => {
+                *run_end = end
+            }
+            _ => self.runs.push((start, end, state)),
+        }
+        if let Some(ref uri) = self.link {
+            match self.links.last_mut() {
+                Some(&mut (_, ref mut run_end, ref u)) if *run_end == start && u == uri => {
+                    *run_end = end
+                }
+                _ => self.links.push((start, end, uri.clone())),
+            }
+        }
+    }
+}
+
+/// Interpret INPUT, starting with rendition STATE inside the hyperlink
+/// to LINK, if any.  If OSC_ONLY, only OSC sequences are interpreted
+/// and removed, and the other control sequences are kept in the text.
+fn interpret(input: &[u8], state: SgrState, link: Option<Vec<u8>>, osc_only: bool) -> Interpreted {
+    let (tokens, fragment_len) = tokenize(input);
+    let mut result = Interpreted {
+        text: Vec::with_capacity(input.len()),
+        runs: Vec::new(),
+        links: Vec::new(),
+        removed: Vec::new(),
+        state,
+        link,
+        title: None,
+        fragment: input[input.len() - fragment_len..].to_vec(),
+    };
+    let mut offset = 0;
+
+    // Other incomplete sequences are left to the caller as well.
+    if osc_only && !result.fragment.starts_with(&[ESC, b']']) {
+        result.fragment.clear();
+    }
+
+    for token in tokens {
+        let len = match token {
+            AnsiToken::Text(t) => t.len(),
+            _ => escape_length(&input[offset..]).unwrap_or(0),
+        };
+        let seq = &input[offset..offset + len];
+        match token {
+            AnsiToken::Text(t) => result.push_text(t),
+            AnsiToken::Sgr(_) | AnsiToken::Other(_) if osc_only && seq[1] != b']' => {
+                result.push_text(seq)
+            }
+            AnsiToken::Sgr(ref params) => {
+                result.removed.push((offset, offset + len));
+                result.state.apply(params);
+            }
+            AnsiToken::Hyperlink(uri) => {
+                result.removed.push((offset, offset + len));
+                result.link = uri.map(<[u8]>::to_vec);
+            }
+            AnsiToken::Title(title) => {
+                result.removed.push((offset, offset + len));
+                result.title = Some(title.to_vec());
+            }
+            AnsiToken::Other(_) => result.removed.push((offset, offset + len)),
+        }
+        offset += len;
+    }
+
+    if offset < input.len() && result.fragment.is_empty() {
+        let rest = &input[offset..];
+        result.push_text(rest);
+    }
+
+    result
+}
+
+/// Count the characters in BYTES, which are in Emacs' internal
+/// representation if MULTIBYTE.
+fn char_count(bytes: &[u8], multibyte: bool) -> usize {
+    if multibyte {
+        // Every character starts with a byte that is not 10xxxxxx.
+        bytes.iter().filter(|&&b| b & 0xc0 != 0x80).count()
+    } else {
+        bytes.len()
+    }
+}
+
+/// Return the rendition, open hyperlink and fragment saved in CONTEXT.
+/// The hyperlink is kept after the rendition in the state list.
+fn context_state_and_fragment(context: LispObject) -> (SgrState, Option<Vec<u8>>, Vec<u8>) {
+    match context.as_cons() {
+        None => (SgrState::default(), None, Vec::new()),
+        Some(cons) => {
+            let fragment = cons
+                .cdr()
+                .as_string()
+                .map_or_else(Vec::new, |s| s.as_slice().to_vec());
+            let link = cons
+                .car()
+                .iter_cars_safe()
+                .nth(3)
+                .and_then(|l| l.as_string())
+                .map(|l| l.as_slice().to_vec());
+            (SgrState::from_lisp(cons.car()), link, fragment)
+        }
+    }
+}
+
+fn make_context(result: &Interpreted, multibyte: bool) -> LispObject {
+    let fragment = if result.fragment.is_empty() {
+        Qnil
+    } else {
+        make_lisp_string(&result.fragment, multibyte)
+    };
+    let state = match result.link {
+        None => result.state.to_lisp(),
+        Some(ref uri) => {
+            let mut items: Vec<LispObject> = result.state.to_lisp().iter_cars().collect();
+            items.push(make_lisp_string(uri, multibyte));
+            LispObject::from(items)
+        }
+    };
+    LispObject::cons(state, fragment)
+}
+
+/// Return the text properties of a hyperlink to URI.  They make it a
+/// button of type `ansi-color-hyperlink' if that type is defined.
+fn hyperlink_properties(uri: LispObject) -> LispObject {
+    let category = get(
+        intern("ansi-color-hyperlink"),
+        intern("button-category-symbol").as_lisp_obj(),
+    );
+    let mut props = vec![
+        intern("ansi-color-hyperlink").as_lisp_obj(),
+        uri,
+        Qhelp_echo,
+        uri,
+    ];
+    if category.is_not_nil() {
+        props.extend_from_slice(&[
+            intern("button").as_lisp_obj(),
+            list!(Qt),
+            Qcategory,
+            category,
+        ]);
+    }
+    LispObject::from(props)
+}
+
+/// Add the hyperlinks of RESULT to OBJECT, whose text from position
+/// BEG on is the text of RESULT, and set the window title if RESULT
+/// sets it.
+fn apply_osc(result: &Interpreted, beg: usize, object: LispObject, multibyte: bool) {
+    for &(start, end, ref uri) in &result.links {
+        let from = beg + char_count(&result.text[..start], multibyte);
+        let to = from + char_count(&result.text[start..end], multibyte);
+        unsafe {
+            Fadd_text_properties(
+                LispObject::from(from),
+                LispObject::from(to),
+                hyperlink_properties(make_lisp_string(uri, multibyte)),
+                object,
+            )
+        };
+    }
+    if let Some(ref title) = result.title {
+        set(
+            intern("ansi-color-window-title"),
+            make_lisp_string(title, multibyte),
+        );
+        force_mode_line_update(false);
+    }
+}
+
+fn make_lisp_string(bytes: &[u8], multibyte: bool) -> LispObject {
+    unsafe {
+        make_specified_string(
+            bytes.as_ptr() as *const c_char,
+            char_count(bytes, multibyte) as ptrdiff_t,
+            bytes.len() as ptrdiff_t,
+            multibyte,
+        )
+    }
+}
+
+/// Translate ANSI SGR control sequences in STRING into text properties.
+/// Return a cons (RESULT . NEW-CONTEXT), where RESULT is STRING with all
+/// ANSI control sequences removed and the `face' property set according
+/// to the SGR sequences.  Standard, bright, 256-color and 24-bit
+/// foreground and background colors are supported.
+///
+/// Text inside an OSC 8 hyperlink gets the `ansi-color-hyperlink' and
+/// `help-echo' properties, whose value is the URI, and is made a button
+/// of type `ansi-color-hyperlink' if that type is defined.  An OSC 0 or
+/// OSC 2 sequence sets `ansi-color-window-title' to its title.
+///
+/// CONTEXT carries the rendition, the open hyperlink and any incomplete
+/// escape sequence from the previous chunk of output; pass nil for the
+/// first chunk, and NEW-CONTEXT from the previous call for the following
+/// ones.  It should be treated as an opaque value.
+#[lisp_fn(min = "1")]
+pub fn ansi_color_apply_native(string: LispStringRef, context: LispObject) -> LispObject {
+    let multibyte = string.is_multibyte();
+    let (state, link, mut input) = context_state_and_fragment(context);
+    input.extend_from_slice(string.as_slice());
+
+    let result = interpret(&input, state, link, false);
+    let lisp_text = make_lisp_string(&result.text, multibyte);
+
+    for &(run_start, run_end, ref run_state) in &result.runs {
+        if run_state.is_default() {
+            continue;
+        }
+        let start = char_count(&result.text[..run_start], multibyte);
+        let end = start + char_count(&result.text[run_start..run_end], multibyte);
+        unsafe {
+            Fput_text_property(
+                LispObject::from(start),
+                LispObject::from(end),
+                Qface,
+                run_state.to_face(),
+                lisp_text,
+            )
+        };
+    }
+    apply_osc(&result, 0, lisp_text, multibyte);
+
+    LispObject::cons(lisp_text, make_context(&result, multibyte))
+}
+
+/// Interpret the control sequences between START and END, and delete the
+/// ones that are interpreted.  Return the result, and the position of
+/// the start of its text in the buffer.
+fn interpret_region(
+    mut start: LispObject,
+    mut end: LispObject,
+    context: LispObject,
+    osc_only: bool,
+) -> (Interpreted, usize) {
+    unsafe { validate_region(&mut start, &mut end) };
+    let beg = start.as_fixnum_or_error() as usize;
+    let multibyte = ThreadState::current_buffer().multibyte_characters_enabled();
+
+    let text = buffer_substring_no_properties(start, end).as_string_or_error();
+    let (state, link, fragment) = context_state_and_fragment(context);
+    let fragment_chars = char_count(&fragment, multibyte);
+    let mut input = fragment;
+    input.extend_from_slice(text.as_slice());
+
+    let result = interpret(&input, state, link, osc_only);
+
+    // Character position in the buffer of byte offset OFFSET of INPUT,
+    // before anything is deleted.
+    let position = |offset: usize| beg + char_count(&input[..offset], multibyte) - fragment_chars;
+
+    // Delete the control sequences from the end, so that earlier
+    // positions stay valid.
+    let fragment_start = input.len() - result.fragment.len();
+    let mut deletions = result.removed.clone();
+    if fragment_start < input.len() {
+        deletions.push((fragment_start, input.len()));
+    }
+    let text_start = input.len() - text.as_slice().len();
+    for &(from, to) in deletions.iter().rev() {
+        if to <= text_start {
+            continue;
+        }
+        let from = position(from.max(text_start));
+        let to = position(to);
+        if from < to {
+            unsafe { del_range(from as ptrdiff_t, to as ptrdiff_t) };
+        }
+    }
+
+    // The fragment of the previous chunk was deleted from the buffer
+    // with that chunk.  If it turned out not to be a control sequence
+    // after all, like an OSC sequence that ran past MAX_OSC_LENGTH, its
+    // bytes start the text of the result; put them back.
+    let deleted: usize = deletions
+        .iter()
+        .map(|&(from, to)| to.min(text_start) - from.min(text_start))
+        .sum();
+    let restored = &result.text[..text_start - deleted];
+    if !restored.is_empty() {
+        let count = c_specpdl_index();
+        unsafe {
+            record_unwind_protect(Some(save_excursion_restore), save_excursion_save());
+            set_point(beg as ptrdiff_t);
+            insert_1_both(
+                restored.as_ptr() as *const c_char,
+                char_count(restored, multibyte) as ptrdiff_t,
+                restored.len() as ptrdiff_t,
+                false,
+                true,
+                false,
+            );
+        }
+        unbind_to(count, Qnil);
+    }
+
+    (result, beg)
+}
+
+/// Translate ANSI SGR control sequences between START and END into faces.
+/// The control sequences are deleted from the buffer, and the faces they
+/// select are added to the text in between with `add-face-text-property'.
+/// OSC sequences are handled as by `ansi-color-apply-native'.
+///
+/// CONTEXT is as for `ansi-color-apply-native'; the value is the new
+/// context.  An incomplete escape sequence at the end of the region is
+/// deleted too, and carried over in the returned context.
+#[lisp_fn(min = "2")]
+pub fn ansi_color_apply_on_region_native(
+    start: LispObject,
+    end: LispObject,
+    context: LispObject,
+) -> LispObject {
+    let multibyte = ThreadState::current_buffer().multibyte_characters_enabled();
+    let (result, beg) = interpret_region(start, end, context, false);
+
+    // The runs are in terms of the text with all sequences removed,
+    // which is now what the buffer holds starting at BEG.
+    for &(run_start, run_end, ref run_state) in &result.runs {
+        if run_state.is_default() {
+            continue;
+        }
+        let from = (beg + char_count(&result.text[..run_start], multibyte)) as EmacsInt;
+        let to = from + char_count(&result.text[run_start..run_end], multibyte) as EmacsInt;
+        if from < to {
+            unsafe {
+                Fadd_face_text_property(
+                    LispObject::from(from),
+                    LispObject::from(to),
+                    run_state.to_face(),
+                    Qt,
+                    Qnil,
+                )
+            };
+        }
+    }
+    apply_osc(&result, beg, Qnil, multibyte);
+
+    make_context(&result, multibyte)
+}
+
+/// Interpret the OSC sequences between START and END.
+/// This is like `ansi-color-apply-on-region-native', except that the
+/// other control sequences are left in the buffer, for instance for
+/// `ansi-color-apply-on-region' to translate.  Only an incomplete OSC
+/// sequence at the end of the region is carried over in the context.
+#[lisp_fn(min = "2")]
+pub fn ansi_color_apply_osc_on_region_native(
+    start: LispObject,
+    end: LispObject,
+    context: LispObject,
+) -> LispObject {
+    let multibyte = ThreadState::current_buffer().multibyte_characters_enabled();
+    let (result, beg) = interpret_region(start, end, context, true);
+    apply_osc(&result, beg, Qnil, multibyte);
+    make_context(&result, multibyte)
+}
+
+/// Return a copy of STRING with all ANSI control sequences removed.
+/// Unlike `ansi-color-apply-native', no faces are added.
+#[lisp_fn]
+pub fn ansi_color_filter_native(string: LispStringRef) -> LispObject {
+    let result = interpret(string.as_slice(), SgrState::default(), None, false);
+    if result.removed.is_empty() && result.fragment.is_empty() {
+        return unsafe { Fcopy_sequence(string.as_lisp_obj()) };
+    }
+    make_lisp_string(&result.text, string.is_multibyte())
+}
+
+include!(concat!(env!("OUT_DIR"), "/ansi_exports.rs"));
+
+#[test]
+fn test_sgr_state() {
+    let mut state = SgrState::default();
+    state.apply(&[1, 31]);
+    assert!(state.bold);
+    assert_eq!(state.foreground, Some(AnsiColor::Indexed(1)));
+    state.apply(&[38, 2, 1, 2, 3, 48, 5, 200]);
+    assert_eq!(state.foreground, Some(AnsiColor::Rgb(1, 2, 3)));
+    assert_eq!(state.background, Some(AnsiColor::Indexed(200)));
+    state.apply(&[]);
+    assert!(state.is_default());
+    assert_eq!(AnsiColor::Indexed(196).to_hex(), "#ff0000");
+    assert_eq!(AnsiColor::Indexed(244).to_hex(), "#808080");
+}
+
+#[test]
+fn test_tokenize() {
+    let (tokens, rest) = tokenize(b"a\x1b[1;31mb\x1b[Kc\x1b[3");
+    assert_eq!(
+        tokens,
+        vec![
+            AnsiToken::Text(b"a"),
+            AnsiToken::Sgr(vec![1, 31]),
+            AnsiToken::Text(b"b"),
+            AnsiToken::Other(b"\x1b[K"),
+            AnsiToken::Text(b"c"),
+        ]
+    );
+    assert_eq!(rest, 4);
+}
+
+#[test]
+fn test_tokenize_osc() {
+    let (tokens, rest) = tokenize(
+        b"\x1b]8;id=1;http://a/\x1b\\link\x1b]8;;\x07\x1b]2;title\x07\x1b]7;file:/\x07\x1b]8;",
+    );
+    assert_eq!(
+        tokens,
+        vec![
+            AnsiToken::Hyperlink(Some(b"http://a/")),
+            AnsiToken::Text(b"link"),
+            AnsiToken::Hyperlink(None),
+            AnsiToken::Title(b"title"),
+            AnsiToken::Other(b"\x1b]7;file:/\x07"),
+        ]
+    );
+    assert_eq!(rest, 4);
+
+    // An escape sequence cancels an unterminated OSC sequence.
+    let (tokens, _) = tokenize(b"\x1b]0;x\x1b[1mb");
+    assert_eq!(tokens[0], AnsiToken::Other(b"\x1b]0;x"));
+    assert_eq!(tokens[1], AnsiToken::Sgr(vec![1]));
+}
+
+#[test]
+fn test_interpret_hyperlinks() {
+    let result = interpret(
+        b"a\x1b]8;;u\x07b\x1b[1mc\x1b]8;;\x07d",
+        SgrState::default(),
+        None,
+        false,
+    );
+    assert_eq!(result.text, b"abcd");
+    assert_eq!(result.links, vec![(1, 3, b"u".to_vec())]);
+    assert_eq!(result.link, None);
+
+    // The link stays open across chunks, and only OSC sequences are
+    // removed if OSC_ONLY.
+    let result = interpret(
+        b"\x1b[1mx\x1b]8",
+        SgrState::default(),
+        Some(b"u".to_vec()),
+        true,
+    );
+    assert_eq!(result.text, b"\x1b[1mx");
+    assert_eq!(result.links, vec![(0, 5, b"u".to_vec())]);
+    assert_eq!(result.fragment, b"\x1b]8");
+    let result = interpret(b"x\x1b[1", SgrState::default(), None, true);
+    assert_eq!(result.text, b"x\x1b[1");
+    assert!(result.fragment.is_empty());
+}
+
+#[test]
+fn test_interpret_overlong_osc() {
+    // An OSC sequence that is not terminated within MAX_OSC_LENGTH
+    // bytes is carried over until then, and then only its `ESC ]' is
+    // dropped.
+    let mut input = b"\x1b]0;".to_vec();
+    input.extend_from_slice(&[b'a'; 3000]);
+    let first = interpret(&input, SgrState::default(), None, false);
+    assert!(first.text.is_empty());
+    assert_eq!(first.fragment, input);
+
+    let mut input = first.fragment;
+    input.extend_from_slice(&[b'b'; 2000]);
+    let second = interpret(&input, SgrState::default(), None, false);
+    assert_eq!(second.removed, vec![(0, 2)]);
+    assert!(second.fragment.is_empty());
+    assert_eq!(second.text, &input[2..]);
+}
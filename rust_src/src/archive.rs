@@ -0,0 +1,359 @@
+//! Reading tar and zip archives.
+//!
+//! Listing an archive only reads the member headers, and extracting a
+//! member decompresses nothing but that member (and, for compressed
+//! tar files, what precedes it), so large archives are handled without
+//! visiting them or running external programs.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+#[cfg(test)]
+use std::io::{Cursor, Write};
+
+use flate2::read::GzDecoder;
+use libc::{c_char, ptrdiff_t};
+use tar::Archive as TarArchive;
+use zip::ZipArchive;
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    callproc::lisp_file_name_to_path,
+    lisp::{defsubr, LispObject},
+    obarray::intern,
+    remacs_sys::{insert_from_string, make_unibyte_string},
+    remacs_sys::{EmacsInt, Fexpand_file_name, Qnil},
+};
+
+/// The largest member that is extracted, in bytes.  The sizes stored
+/// in the headers are not trusted, so this is checked while reading.
+const MAX_MEMBER_SIZE: u64 = 1 << 30;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Tar,
+    GzipTar,
+    ZstdTar,
+    Zip,
+}
+
+/// Return the format of the archive read by READER, judging from its
+/// first bytes, and rewind it.
+fn detect_format<R: Read + Seek>(reader: &mut R) -> io::Result<Format> {
+    let mut magic = Vec::with_capacity(4);
+    reader.by_ref().take(4).read_to_end(&mut magic)?;
+    reader.seek(SeekFrom::Start(0))?;
+    Ok(if magic == b"PK\x03\x04" || magic == b"PK\x05\x06" {
+        Format::Zip
+    } else if magic.starts_with(&[0x1f, 0x8b]) {
+        Format::GzipTar
+    } else if magic == [0x28, 0xb5, 0x2f, 0xfd] {
+        Format::ZstdTar
+    } else {
+        Format::Tar
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EntryType {
+    File,
+    Directory,
+    Symlink,
+    Other,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mtime {
+    /// Seconds since the epoch, as stored by tar.
+    Seconds(u64),
+    /// Year, month, day, hour, minute and second in local time, as
+    /// stored by zip.
+    Local(u16, u8, u8, u8, u8, u8),
+}
+
+/// The metadata of an archive member.
+#[derive(Debug)]
+struct Entry {
+    name: Vec<u8>,
+    size: u64,
+    mode: u32,
+    mtime: Mtime,
+    entry_type: EntryType,
+    /// The target of a symbolic link.
+    link: Option<Vec<u8>>,
+}
+
+fn list_tar<R: Read>(reader: R) -> io::Result<Vec<Entry>> {
+    let mut archive = TarArchive::new(reader);
+    let mut entries = Vec::new();
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let header = entry.header();
+        let kind = header.entry_type();
+        let entry_type = if kind.is_dir() {
+            EntryType::Directory
+        } else if kind.is_symlink() {
+            EntryType::Symlink
+        } else if kind.is_file() || kind.is_contiguous() {
+            EntryType::File
+        } else {
+            EntryType::Other
+        };
+        entries.push(Entry {
+            name: entry.path_bytes().into_owned(),
+            size: header.size()?,
+            mode: header.mode()?,
+            mtime: Mtime::Seconds(header.mtime()?),
+            entry_type,
+            link: entry.link_name_bytes().map(|link| link.into_owned()),
+        });
+    }
+    Ok(entries)
+}
+
+/// Read all of READER, failing if it holds more than LIMIT bytes.
+fn read_member<R: Read>(reader: R, limit: u64) -> io::Result<Vec<u8>> {
+    let mut contents = Vec::new();
+    reader.take(limit + 1).read_to_end(&mut contents)?;
+    if contents.len() as u64 > limit {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("member larger than {} bytes", limit),
+        ));
+    }
+    Ok(contents)
+}
+
+/// Return the contents of the member NAME of the tar archive read by
+/// READER, or None if there is no such member.
+fn extract_tar<R: Read>(reader: R, name: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    let mut archive = TarArchive::new(reader);
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if entry.path_bytes().as_ref() == name {
+            return read_member(entry, MAX_MEMBER_SIZE).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// The file type bits of a Unix mode.
+const S_IFMT: u32 = 0o170_000;
+const S_IFLNK: u32 = 0o120_000;
+
+fn list_zip<R: Read + Seek>(reader: R) -> io::Result<Vec<Entry>> {
+    let mut archive = ZipArchive::new(reader)?;
+    let mut entries = Vec::new();
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        let time = file.last_modified();
+        let mode = file.unix_mode();
+        let entry_type = if file.is_dir() {
+            EntryType::Directory
+        } else if mode.map_or(false, |mode| mode & S_IFMT == S_IFLNK) {
+            EntryType::Symlink
+        } else {
+            EntryType::File
+        };
+        entries.push(Entry {
+            name: file.name_raw().to_vec(),
+            size: file.size(),
+            mode: mode.unwrap_or(0),
+            mtime: Mtime::Local(
+                time.year(),
+                time.month(),
+                time.day(),
+                time.hour(),
+                time.minute(),
+                time.second(),
+            ),
+            entry_type,
+            link: None,
+        });
+    }
+    Ok(entries)
+}
+
+fn extract_zip<R: Read + Seek>(reader: R, name: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    let mut archive = ZipArchive::new(reader)?;
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        if file.name_raw() == name {
+            return read_member(file, MAX_MEMBER_SIZE).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+fn list_archive(mut file: File) -> io::Result<Vec<Entry>> {
+    match detect_format(&mut file)? {
+        Format::Tar => list_tar(file),
+        Format::GzipTar => list_tar(GzDecoder::new(file)),
+        Format::ZstdTar => list_tar(zstd::stream::read::Decoder::new(file)?),
+        Format::Zip => list_zip(file),
+    }
+}
+
+fn extract_archive(mut file: File, name: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    match detect_format(&mut file)? {
+        Format::Tar => extract_tar(file, name),
+        Format::GzipTar => extract_tar(GzDecoder::new(file), name),
+        Format::ZstdTar => extract_tar(zstd::stream::read::Decoder::new(file)?, name),
+        Format::Zip => extract_zip(file, name),
+    }
+}
+
+fn make_unibyte(bytes: &[u8]) -> LispObject {
+    unsafe { make_unibyte_string(bytes.as_ptr() as *const c_char, bytes.len() as ptrdiff_t) }
+}
+
+fn entry_to_lisp(entry: &Entry) -> LispObject {
+    let keyword = |name: &str| intern(name).as_lisp_obj();
+    let mtime = match entry.mtime {
+        Mtime::Seconds(seconds) => LispObject::int_or_float_from_fixnum(seconds as EmacsInt),
+        Mtime::Local(year, month, day, hour, minute, second) => call!(
+            intern("encode-time").as_lisp_obj(),
+            LispObject::from(EmacsInt::from(second)),
+            LispObject::from(EmacsInt::from(minute)),
+            LispObject::from(EmacsInt::from(hour)),
+            LispObject::from(EmacsInt::from(day)),
+            LispObject::from(EmacsInt::from(month)),
+            LispObject::from(EmacsInt::from(year))
+        ),
+    };
+    let entry_type = match entry.entry_type {
+        EntryType::File => "file",
+        EntryType::Directory => "directory",
+        EntryType::Symlink => "symlink",
+        EntryType::Other => "other",
+    };
+    list!(
+        keyword(":name"),
+        make_unibyte(&entry.name),
+        keyword(":size"),
+        LispObject::int_or_float_from_fixnum(entry.size as EmacsInt),
+        keyword(":modes"),
+        LispObject::from(EmacsInt::from(entry.mode & 0o7777)),
+        keyword(":mtime"),
+        mtime,
+        keyword(":type"),
+        intern(entry_type).as_lisp_obj(),
+        keyword(":link"),
+        entry.link.as_ref().map_or(Qnil, |link| make_unibyte(link))
+    )
+}
+
+fn open_archive(file: LispObject) -> File {
+    let file = unsafe { Fexpand_file_name(file, Qnil) };
+    let path = lisp_file_name_to_path(file);
+    File::open(&path).unwrap_or_else(|err| error!("Cannot open {}: {}", path.display(), err))
+}
+
+/// Return the members of the archive FILE.
+///
+/// FILE can be a tar file, possibly compressed with gzip or Zstandard,
+/// or a zip file.  Each member is a plist (:name NAME :size SIZE :modes
+/// MODES :mtime MTIME :type TYPE :link LINK), where NAME is a unibyte
+/// string as stored in the archive, MODES are the permission bits, and
+/// MTIME is a Lisp time value.  TYPE is one of `file', `directory',
+/// `symlink' and `other', and LINK is the target of a symbolic link
+/// stored in a tar file, or nil.
+#[lisp_fn]
+pub fn archive_list_entries_native(file: LispObject) -> LispObject {
+    let entries = list_archive(open_archive(file))
+        .unwrap_or_else(|err| error!("Cannot read {}: {}", file, err));
+    LispObject::from(entries.iter().map(entry_to_lisp).collect::<Vec<_>>())
+}
+
+/// Insert the contents of the member NAME of the archive FILE at point.
+///
+/// FILE is as for `archive-list-entries-native', and NAME must be the
+/// name of one of its members as returned by that function.  The
+/// contents are inserted as raw bytes, without decoding.  Return the
+/// number of bytes inserted.
+#[lisp_fn]
+pub fn archive_extract_entry_native(file: LispObject, name: LispObject) -> EmacsInt {
+    let bytes = name.as_string_or_error();
+    let contents = match extract_archive(open_archive(file), bytes.as_slice()) {
+        Ok(Some(contents)) => contents,
+        Ok(None) => error!("No member {} in {}", name, file),
+        Err(err) => error!("Cannot read {}: {}", file, err),
+    };
+    let length = contents.len() as ptrdiff_t;
+    unsafe {
+        insert_from_string(make_unibyte(&contents), 0, 0, length, length, false);
+    }
+    length as EmacsInt
+}
+
+include!(concat!(env!("OUT_DIR"), "/archive_exports.rs"));
+
+#[cfg(test)]
+fn tar_archive() -> Vec<u8> {
+    let mut builder = tar::Builder::new(Vec::new());
+    let mut header = tar::Header::new_gnu();
+    header.set_size(5);
+    header.set_mode(0o644);
+    header.set_mtime(1_500_000_000);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, "dir/hello.txt", &b"hello"[..])
+        .unwrap();
+    builder.into_inner().unwrap()
+}
+
+#[test]
+fn test_tar() {
+    let data = tar_archive();
+    let entries = list_tar(Cursor::new(&data)).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].name, b"dir/hello.txt");
+    assert_eq!(entries[0].size, 5);
+    assert_eq!(entries[0].mode, 0o644);
+    assert_eq!(entries[0].mtime, Mtime::Seconds(1_500_000_000));
+    assert_eq!(entries[0].entry_type, EntryType::File);
+    assert_eq!(
+        extract_tar(Cursor::new(&data), b"dir/hello.txt").unwrap(),
+        Some(b"hello".to_vec())
+    );
+    assert_eq!(extract_tar(Cursor::new(&data), b"hello.txt").unwrap(), None);
+}
+
+#[test]
+fn test_zip() {
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    writer.add_directory("dir/", Default::default()).unwrap();
+    writer.start_file("dir/a", Default::default()).unwrap();
+    writer.write_all(b"contents").unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let mut cursor = Cursor::new(&data);
+    assert_eq!(detect_format(&mut cursor).unwrap(), Format::Zip);
+    let entries = list_zip(cursor).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].entry_type, EntryType::Directory);
+    assert_eq!(entries[1].name, b"dir/a");
+    assert_eq!(entries[1].size, 8);
+    assert_eq!(
+        extract_zip(Cursor::new(&data), b"dir/a").unwrap(),
+        Some(b"contents".to_vec())
+    );
+}
+
+#[test]
+fn test_detect_format() {
+    let mut tar = Cursor::new(tar_archive());
+    assert_eq!(detect_format(&mut tar).unwrap(), Format::Tar);
+    assert_eq!(tar.position(), 0);
+    let mut gzip = Cursor::new(vec![0x1f, 0x8b, 8, 0]);
+    assert_eq!(detect_format(&mut gzip).unwrap(), Format::GzipTar);
+    let mut empty = Cursor::new(Vec::new());
+    assert_eq!(detect_format(&mut empty).unwrap(), Format::Tar);
+}
+
+#[test]
+fn test_member_size_limit() {
+    assert_eq!(read_member(&b"hello"[..], 5).unwrap(), b"hello".to_vec());
+    let err = read_member(&b"hello!"[..], 5).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
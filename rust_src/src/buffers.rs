@@ -1,7 +1,7 @@
 //! Functions operating on buffers.
 
 use std::sync::Mutex;
-use std::{self, mem, ptr};
+use std::{self, mem, ptr, slice};
 
 use libc::{self, c_char, c_int, c_uchar, c_void, ptrdiff_t};
 
@@ -19,29 +19,36 @@ use crate::{
     lisp::defsubr,
     lisp::{ExternalPtr, LispMiscRef, LispObject, LiveBufferIter},
     lists::{car, cdr, list, member},
-    marker::{marker_buffer, marker_position_lisp, set_marker_both, LispMarkerRef},
+    marker::{
+        clear_charpos_cache, marker_buffer, marker_position_lisp, set_marker_both, LispMarkerRef,
+    },
     multibyte::LispStringRef,
-    multibyte::{multibyte_length_by_head, string_char},
+    multibyte::{multibyte_length, multibyte_length_by_head, string_char},
     numbers::MOST_POSITIVE_FIXNUM,
+    obarray::intern,
+    process::get_buffer_process_internal,
     remacs_sys::{
         allocate_misc, bset_update_mode_line, buffer_local_flags, buffer_local_value,
-        buffer_window_count, concat2, del_range, delete_all_overlays, globals, internal_equal,
-        last_per_buffer_idx, lookup_char_property, marker_position, modify_overlay,
-        set_buffer_internal_1, specbind, unchain_both, unchain_marker, update_mode_lines,
+        buffer_window_count, concat2, del_range, delete_all_overlays, flush_interval_offsets,
+        globals, internal_equal, invalidate_buffer_caches, last_per_buffer_idx, make_gap,
+        marker_position, modify_overlay, move_gap_both, set_buffer_internal_1,
+        set_intervals_multibyte, setup_process_coding_systems, specbind, unchain_both,
+        unchain_marker, update_mode_lines,
     },
     remacs_sys::{
-        equal_kind, pvec_type, EmacsInt, Lisp_Buffer, Lisp_Buffer_Local_Value, Lisp_Misc_Type,
-        Lisp_Overlay, Lisp_Type, Vbuffer_alist,
+        equal_kind, interval, pvec_type, EmacsInt, Lisp_Buffer, Lisp_Buffer_Local_Value,
+        Lisp_Misc_Type, Lisp_Overlay, Lisp_Type, Vbuffer_alist,
     },
     remacs_sys::{
         windows_or_buffers_changed, Fcopy_sequence, Fexpand_file_name, Ffind_file_name_handler,
-        Fget_text_property, Fnconc, Fnreverse, Fwiden,
+        Fget_text_property, Fnconc, Fnreverse, Fset_buffer_modified_p, Fwiden,
     },
     remacs_sys::{
-        Qafter_string, Qbefore_string, Qbuffer_read_only, Qbufferp, Qget_file_buffer,
+        Qafter_string, Qapply, Qbefore_string, Qbuffer_read_only, Qbufferp, Qget_file_buffer,
         Qinhibit_quit, Qinhibit_read_only, Qnil, Qoverlayp, Qt, Qunbound, Qvoid_variable,
     },
     strings::string_equal,
+    textprop::{forget_buffer_intervals, lookup_char_property, IntervalRef},
     threads::{c_specpdl_index, ThreadState},
 };
 
@@ -250,6 +257,29 @@ impl LispBufferRef {
         unsafe { *(self.beg_addr().offset(offset + n - self.beg_byte())) as u8 }
     }
 
+    /// Return a copy of the text between byte positions START and END.
+    pub fn bytes_between(self, start: ptrdiff_t, end: ptrdiff_t) -> Vec<u8> {
+        let mut out = Vec::with_capacity(std::cmp::max(end - start, 0) as usize);
+        let gpt = self.gpt_byte();
+        unsafe {
+            if start < gpt {
+                let stop = std::cmp::min(end, gpt);
+                out.extend_from_slice(slice::from_raw_parts(
+                    self.beg_addr().offset(start - BEG_BYTE),
+                    (stop - start) as usize,
+                ));
+            }
+            if end > gpt {
+                let from = std::cmp::max(start, gpt);
+                out.extend_from_slice(slice::from_raw_parts(
+                    self.beg_addr().offset(from - BEG_BYTE + self.gap_size()),
+                    (end - from) as usize,
+                ));
+            }
+        }
+        out
+    }
+
     /// Return character at byte position POS.  See the caveat WARNING for
     /// FETCH_MULTIBYTE_CHAR below.
     pub fn fetch_char(self, n: ptrdiff_t) -> c_int {
@@ -364,6 +394,31 @@ impl LispBufferRef {
         unsafe { (*self.text).z }
     }
 
+    /// The root of the interval tree of the buffer's text, which may be
+    /// null.  Like `buffer_intervals', this first flushes the lengths
+    /// of insertions that were not yet added up the tree.
+    pub fn intervals(self) -> *mut interval {
+        self.flush_offsets();
+        unsafe { (*self.text).intervals }
+    }
+
+    /// Make I the root of the interval tree of the buffer's text.
+    pub fn set_intervals(&mut self, i: *mut interval) {
+        self.flush_offsets();
+        unsafe { (*self.text).intervals = i };
+    }
+
+    /// Add the lengths of the insertions into the pending interval of the
+    /// buffer's text to the intervals above it.  See
+    /// `flush_interval_offsets' in intervals.c.
+    pub fn flush_offsets(self) {
+        unsafe {
+            if !(*self.text).pending_interval.is_null() {
+                flush_interval_offsets(self.text);
+            }
+        }
+    }
+
     pub fn overlays_before(self) -> Option<LispOverlayRef> {
         unsafe { self.overlays_before.as_ref().map(|m| mem::transmute(m)) }
     }
@@ -1001,7 +1056,7 @@ pub extern "C" fn build_overlay(
 /// Get the property of overlay OVERLAY with property name PROP.
 #[lisp_fn]
 pub fn overlay_get(overlay: LispOverlayRef, prop: LispObject) -> LispObject {
-    unsafe { lookup_char_property(overlay.plist, prop, false) }
+    lookup_char_property(overlay.plist, prop, false)
 }
 
 // Mark OV as no longer associated with BUF.
@@ -1129,6 +1184,275 @@ pub fn generate_new_buffer_name(name: LispStringRef, ignore: LispObject) -> Lisp
     }
 }
 
+/// How `set-buffer-multibyte' rewrites the text of a buffer.
+#[derive(Clone, Copy)]
+enum Conversion {
+    /// Eight-bit characters become the raw bytes they stand for, and
+    /// the bytes of other characters become characters of their own.
+    ToUnibyte,
+    /// Raw bytes become eight-bit characters, except for the sequences
+    /// of bytes that are the multibyte form of a character, which
+    /// become that character if KEEP_SEQUENCES.
+    ToMultibyte { keep_sequences: bool },
+}
+
+/// What a conversion does with the character at the start of a text.
+#[derive(Debug, PartialEq)]
+enum Step {
+    /// Keep its N bytes.
+    Keep(usize),
+    /// Replace its N bytes with the first LEN of BYTES.
+    Replace(usize, [u8; 2], usize),
+}
+
+impl Conversion {
+    fn step(self, text: &[u8]) -> Step {
+        let head = text[0];
+        match self {
+            Conversion::ToUnibyte if head & 0xFE == 0xC0 => {
+                // An eight-bit character.
+                Step::Replace(2, [0x80 | ((head & 1) << 6) | (text[1] & 0x3F), 0], 1)
+            }
+            Conversion::ToUnibyte => Step::Keep(multibyte_length_by_head(head).min(text.len())),
+            Conversion::ToMultibyte { .. } if head < 0x80 => Step::Keep(1),
+            Conversion::ToMultibyte { keep_sequences } => {
+                match multibyte_length(text, false).filter(|_| keep_sequences) {
+                    Some(n) => Step::Keep(n),
+                    None => Step::Replace(1, [0xC0 | ((head >> 6) & 1), 0x80 | (head & 0x3F)], 2),
+                }
+            }
+        }
+    }
+}
+
+/// Rewrite the text of the current buffer BUFFER in place with
+/// CONVERSION.  POSITIONS are byte positions in the text, in order;
+/// return the byte and character positions where they end up, a
+/// position inside a character ending up after it.
+///
+/// The gap is moved to the beginning of the text and the new text is
+/// written from there, reading the old text after the gap, so that no
+/// copy of the text is made: the buffer only grows by the bytes the
+/// text gains.
+unsafe fn convert_buffer_text(
+    buffer: LispBufferRef,
+    conversion: Conversion,
+    positions: &[ptrdiff_t],
+) -> Vec<(ptrdiff_t, ptrdiff_t)> {
+    let old_len = (buffer.z_byte() - BEG_BYTE) as usize;
+    let (old_z, old_z_byte) = (buffer.z(), buffer.z_byte());
+    if let Conversion::ToMultibyte { .. } = conversion {
+        // Make sure the gap can hold what the text gains.  Enlarging the
+        // gap is cheap with the gap at the end.
+        move_gap_both(old_z, old_z_byte);
+        let text = slice::from_raw_parts(buffer.beg_addr(), old_len);
+        let (mut read, mut growth) = (0, 0);
+        while read < old_len {
+            match conversion.step(&text[read..]) {
+                Step::Keep(n) => read += n,
+                Step::Replace(n, _, len) => {
+                    read += n;
+                    growth += len - n;
+                }
+            }
+        }
+        let growth = growth as ptrdiff_t;
+        if growth > buffer.gap_size() {
+            make_gap(growth - buffer.gap_size());
+        }
+    }
+    move_gap_both(BEG, BEG_BYTE);
+
+    let gap = buffer.gap_size() as usize;
+    let beg = buffer.beg_addr();
+    let mut new_positions = Vec::with_capacity(positions.len());
+    let (mut read, mut write, mut chars) = (0, 0, 0);
+    // The bytes just before READ that are kept, and not yet copied to
+    // just before WRITE.
+    let mut kept = 0;
+    loop {
+        while new_positions.len() < positions.len()
+            && positions[new_positions.len()] - BEG_BYTE <= read as ptrdiff_t
+        {
+            new_positions.push((BEG_BYTE + write as ptrdiff_t, BEG + chars));
+        }
+        if read == old_len {
+            break;
+        }
+        let text = slice::from_raw_parts(beg.add(gap + read), old_len - read);
+        let (n, len) = match conversion.step(text) {
+            Step::Keep(n) => {
+                kept += n;
+                (n, n)
+            }
+            Step::Replace(n, bytes, len) => {
+                ptr::copy(beg.add(gap + read - kept), beg.add(write - kept), kept);
+                kept = 0;
+                ptr::copy_nonoverlapping(bytes.as_ptr(), beg.add(write), len);
+                (n, len)
+            }
+        };
+        read += n;
+        write += len;
+        chars += match conversion {
+            Conversion::ToUnibyte => len as ptrdiff_t,
+            Conversion::ToMultibyte { .. } => 1,
+        };
+    }
+    ptr::copy(beg.add(gap + read - kept), beg.add(write - kept), kept);
+
+    let text = &mut *buffer.text;
+    text.gap_size += (old_len - write) as ptrdiff_t;
+    text.gpt_byte = BEG_BYTE + write as ptrdiff_t;
+    text.z_byte = text.gpt_byte;
+    text.gpt = BEG + chars;
+    text.z = text.gpt;
+    if text.gap_size > 0 {
+        // Put an anchor.
+        *buffer.gap_start_addr() = 0;
+    }
+    if text.z != old_z || text.z_byte != old_z_byte {
+        text.modiff += 1;
+        text.chars_modiff = text.modiff;
+    }
+    new_positions
+}
+
+/// Set the multibyte flag of the current buffer to FLAG.
+/// If FLAG is t, this makes the buffer a multibyte buffer.
+/// If FLAG is nil, this makes the buffer a single-byte buffer.
+/// In these cases, the buffer contents remain unchanged as a sequence of
+/// bytes but the contents viewed as characters do change.
+/// If FLAG is `to', this makes the buffer a multibyte buffer by changing
+/// all eight-bit bytes to eight-bit characters.
+/// If the multibyte flag was really changed, undo information of the
+/// current buffer is cleared.
+#[lisp_fn]
+pub fn set_buffer_multibyte(flag: LispObject) -> LispObject {
+    let mut buffer = ThreadState::current_buffer();
+    if buffer.base_buffer().is_some() {
+        error!("Cannot do `set-buffer-multibyte' on an indirect buffer");
+    }
+    // Do nothing if nothing actually changes.
+    if flag.is_nil() != buffer.multibyte_characters_enabled() {
+        return flag;
+    }
+    if buffer.begv != buffer.beg() || buffer.zv != buffer.z() {
+        error!("Changing multibyteness in a narrowed buffer");
+    }
+
+    let modified = buffer_modified_p(LispBufferOrCurrent::Current);
+    // Don't record these buffer changes.  We will put a special undo
+    // entry instead.
+    let old_undo = buffer.undo_list_;
+    buffer.undo_list_ = Qt;
+    clear_charpos_cache(buffer.as_mut());
+    unsafe { invalidate_buffer_caches(buffer.as_mut(), buffer.begv, buffer.zv) };
+
+    let conversion = if flag.is_nil() {
+        // Make the intervals count bytes, while the characters they
+        // count can still be found.
+        unsafe { set_intervals_multibyte(false) };
+        Conversion::ToUnibyte
+    } else {
+        Conversion::ToMultibyte {
+            keep_sequences: flag.eq(Qt),
+        }
+    };
+
+    // Everything that points into the text follows it: point, the
+    // markers and the ends of the intervals.
+    let markers: Vec<LispMarkerRef> = buffer
+        .markers()
+        .map_or_else(Vec::new, |m| m.iter().collect());
+    let root = IntervalRef::from_raw(buffer.intervals());
+    let ends: Vec<ptrdiff_t> = root.map_or_else(Vec::new, |root| {
+        root.iter_ordered()
+            .map(|(_, end, _)| end as ptrdiff_t)
+            .collect()
+    });
+    let mut positions: Vec<ptrdiff_t> = markers
+        .iter()
+        .map(|marker| marker.bytepos_or_error())
+        .chain(ends.iter().cloned())
+        .collect();
+    positions.push(buffer.pt_byte);
+    positions.sort();
+    positions.dedup();
+    let new_positions = unsafe { convert_buffer_text(buffer, conversion, &positions) };
+    let new_position = |old: ptrdiff_t| new_positions[positions.binary_search(&old).unwrap()];
+
+    buffer.enable_multibyte_characters_ = LispObject::from(flag.is_not_nil());
+    for mut marker in markers {
+        let (byte, charpos) = new_position(marker.bytepos_or_error());
+        marker.set_charpos(charpos);
+        marker.set_bytepos(byte);
+    }
+    let (pt_byte, pt) = new_position(buffer.pt_byte);
+    buffer.set_pt_both(pt, pt_byte);
+    let (z, z_byte) = (buffer.z(), buffer.z_byte());
+    buffer.set_begv_both(buffer.beg(), buffer.beg_byte());
+    buffer.set_zv_both(z, z_byte);
+    if let Some(root) = root {
+        let mut start = BEG_BYTE;
+        let mut lengths = ends.iter().map(|&end| {
+            let (end, _) = new_position(end);
+            let length = end - start;
+            start = end;
+            length as EmacsInt
+        });
+        root.set_lengths(&mut lengths);
+        if flag.is_not_nil() {
+            // Now that the text is multibyte, make the intervals count
+            // characters.
+            unsafe { set_intervals_multibyte(true) };
+        }
+    }
+    forget_buffer_intervals(buffer.as_mut());
+
+    if !old_undo.eq(Qt) {
+        // Represent all the above changes by a special undo entry.
+        let entry = list!(
+            Qapply,
+            intern("set-buffer-multibyte").as_lisp_obj(),
+            LispObject::from(flag.is_nil())
+        );
+        buffer.undo_list_ = LispObject::cons(entry, old_undo);
+    }
+
+    buffer.set_prevent_redisplay_optimizations_p(true);
+    // If the buffer is shown in a window, let redisplay consider other
+    // windows.
+    if unsafe { buffer_window_count(buffer.as_mut()) } > 0 {
+        unsafe { windows_or_buffers_changed = 10 };
+    }
+
+    // Copy this buffer's new multibyte status into all of its indirect
+    // buffers.
+    for mut other in LiveBufferIter::new() {
+        if other
+            .base_buffer()
+            .map_or(false, |base| base.as_ptr() == buffer.as_ptr())
+        {
+            other.enable_multibyte_characters_ = buffer.enable_multibyte_characters_;
+            other.set_prevent_redisplay_optimizations_p(true);
+        }
+    }
+
+    // Restore the modifiedness of the buffer.
+    if !modified && buffer_modified_p(LispBufferOrCurrent::Current) {
+        unsafe { Fset_buffer_modified_p(Qnil) };
+    }
+
+    // Update the coding systems of this buffer's process, if any.
+    let process = get_buffer_process_internal(Some(buffer));
+    if process.is_process() {
+        unsafe { setup_process_coding_systems(process) };
+    }
+
+    flag
+}
+
 pub unsafe fn per_buffer_idx(offset: isize) -> isize {
     let flags = &mut buffer_local_flags as *mut Lisp_Buffer as *mut LispObject;
     let obj = flags.offset(offset);
@@ -1146,3 +1470,32 @@ pub extern "C" fn rust_syms_of_buffer() {
 }
 
 include!(concat!(env!("OUT_DIR"), "/buffers_exports.rs"));
+
+#[test]
+fn test_conversion_step() {
+    let to_multibyte = Conversion::ToMultibyte {
+        keep_sequences: true,
+    };
+    let eight_bit = Conversion::ToMultibyte {
+        keep_sequences: false,
+    };
+    assert_eq!(Conversion::ToUnibyte.step(b"a"), Step::Keep(1));
+    assert_eq!(Conversion::ToUnibyte.step(b"\xC3\xA9"), Step::Keep(2));
+    assert_eq!(
+        Conversion::ToUnibyte.step(b"\xC1\xBF"),
+        Step::Replace(2, [0xFF, 0], 1)
+    );
+    assert_eq!(to_multibyte.step(b"\xC3\xA9"), Step::Keep(2));
+    assert_eq!(
+        to_multibyte.step(b"\xC3a"),
+        Step::Replace(1, [0xC1, 0x83], 2)
+    );
+    assert_eq!(
+        eight_bit.step(b"\xC3\xA9"),
+        Step::Replace(1, [0xC1, 0x83], 2)
+    );
+    assert_eq!(
+        to_multibyte.step(b"\x80"),
+        Step::Replace(1, [0xC0, 0x80], 2)
+    );
+}
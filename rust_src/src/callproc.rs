@@ -1,18 +1,866 @@
 //! Synchronous subprocess invocation for GNU Emacs.
 
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString, OsString};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::mem;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
+use std::process::{ChildStdin, Command, Stdio};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use libc::{c_char, c_int, c_void, pid_t, ptrdiff_t};
+
 use crate::{
+    buffers::{current_buffer, set_buffer, validate_region, LispBufferRef},
+    dispnew::is_interactive,
+    editfns::delete_region,
+    environ::{self, env_entry_name, env_name_eq},
     eval::unbind_to,
+    fileio::file_name_absolute_p_lisp,
+    frames::selected_frame,
     lisp::{defsubr, LispObject},
+    lists::{car, plist_get},
+    multibyte::LispStringRef,
+    obarray::intern,
     remacs_macros::lisp_fn,
-    remacs_sys::Fexpand_file_name,
     remacs_sys::NULL_DEVICE,
     remacs_sys::{
-        build_string, call_process, close_file_unwind, emacs_open, encode_file_name,
-        record_unwind_protect_int, report_file_error,
+        block_child_signal, build_string, build_unibyte_string, close_file_unwind,
+        code_convert_string_norecord, complement_process_encoding_system, decode_coding_c_string,
+        decode_file_name, del_range, del_range_2, emacs_close, emacs_open, emacs_pipe,
+        emacs_read_quit, encode_current_directory, encode_file_name, insert_1_both,
+        make_buffer_string, make_string, make_unibyte_string, message1, prepare_to_modify_buffer,
+        raw_text_coding_system, record_deleted_pid, record_unwind_protect,
+        record_unwind_protect_int, redisplay_preserve_echo_area, report_file_errno,
+        report_file_error, setup_coding_system, sigset_t, specbind,
+        synchronize_system_messages_locale, unblock_child_signal, wait_for_termination,
+    },
+    remacs_sys::{
+        coding_system, EmacsInt, Vcoding_system_hash_table, CODING_MODE_LAST_BLOCK,
+        CODING_REQUIRE_DECODING_MASK, CODING_REQUIRE_DETECTION_MASK,
+    },
+    remacs_sys::{
+        globals, QCascii_compatible_p, QCfile, Qcall_process, Qcall_process_region, Qdisplay,
+        Qinhibit_modification_hooks, Qnil, Qraw_text, Qt,
+    },
+    remacs_sys::{
+        Fcheck_coding_system, Fcoding_system_plist, Fexpand_file_name,
+        Ffind_operation_coding_system, Fframe_parameter, Fget_buffer_create, Fwiden,
     },
     threads::{c_specpdl_index, ThreadState},
 };
 
+/// Indexes of file descriptors that need closing on `call_process_kill'.
+const CALLPROC_STDOUT: usize = 0;
+const CALLPROC_STDERR: usize = 1;
+/// How to read from a pipe from the subsidiary process.
+const CALLPROC_PIPEREAD: usize = 2;
+/// A bound on the number of file descriptors.
+const CALLPROC_FDS: usize = 3;
+
+/// The synchronous subprocess `call-process' is running.  The unwind
+/// handlers use this to clean up when `call-process' exits nonlocally.
+/// It's safe to use a static here, as `call-process' is never invoked
+/// reentrantly.
+struct SynchProcess {
+    /// If nonzero, a process-ID that has not been reaped.
+    pid: pid_t,
+    /// Descriptors to close, or -1.
+    fds: [c_int; CALLPROC_FDS],
+    /// The thread writing the process's input, if any.
+    feeder: Option<InputFeeder>,
+}
+
+static mut SYNCH_PROCESS: SynchProcess = SynchProcess {
+    pid: 0,
+    fds: [-1; CALLPROC_FDS],
+    feeder: None,
+};
+
+/// How long the input feeder waits for the pipe to drain before it
+/// checks whether it should stop, in milliseconds.
+const FEEDER_POLL_TIMEOUT: c_int = 100;
+
+/// A thread writing text to the standard input of a synchronous
+/// subprocess, so that a child that writes a lot of output before it
+/// has read all of its input can't block us.
+struct InputFeeder {
+    stop: Arc<AtomicBool>,
+    thread: thread::JoinHandle<()>,
+}
+
+impl InputFeeder {
+    fn start(mut stdin: ChildStdin, text: Vec<u8>) -> InputFeeder {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&stop);
+        let fd = stdin.as_raw_fd();
+        unsafe {
+            libc::fcntl(
+                fd,
+                libc::F_SETFL,
+                libc::fcntl(fd, libc::F_GETFL) | libc::O_NONBLOCK,
+            )
+        };
+
+        let thread = thread::spawn(move || {
+            let mut text = &text[..];
+            while !text.is_empty() && !stopped.load(Ordering::SeqCst) {
+                match stdin.write(text) {
+                    Ok(written) => text = &text[written..],
+                    Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        let mut pollfd = libc::pollfd {
+                            fd,
+                            events: libc::POLLOUT,
+                            revents: 0,
+                        };
+                        unsafe { libc::poll(&mut pollfd, 1, FEEDER_POLL_TIMEOUT) };
+                    }
+                    Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                    // The child is gone, or has closed its input.
+                    Err(_) => break,
+                }
+            }
+            // Dropping STDIN closes the write end of the pipe.
+        });
+
+        InputFeeder { stop, thread }
+    }
+
+    /// Stop writing, and wait until the write end of the pipe is closed.
+    fn finish(self) {
+        self.stop.store(true, Ordering::SeqCst);
+        let _ = self.thread.join();
+    }
+}
+
+/// Permissions of files created for process output, before the umask.
+const DEFAULT_OUTPUT_MODE: c_int = 0o666;
+
+/// Size of the buffer process output is read into.  It grows as more
+/// output is read, up to `CALLPROC_BUFFER_SIZE_MAX'.
+const CALLPROC_BUFFER_SIZE_MIN: usize = 16 * 1024;
+const CALLPROC_BUFFER_SIZE_MAX: usize = 4 * CALLPROC_BUFFER_SIZE_MIN;
+
+/// Where the standard output of a synchronous subprocess goes.
+#[derive(Clone, Copy)]
+enum OutputDestination {
+    /// Discard it.  If the flag is false, don't wait for the process.
+    Discard(bool),
+    /// Insert it before point in BUFFER.
+    Buffer(LispBufferRef),
+    /// Write it to a file; the name is encoded.
+    File(LispObject),
+}
+
+/// Where the standard error of a synchronous subprocess goes.
+#[derive(Clone, Copy)]
+enum ErrorDestination {
+    /// Mix it with standard output.
+    Output,
+    /// Discard it.
+    Discard,
+    /// Write it to a file; the name is encoded.
+    File(LispObject),
+}
+
+/// What a synchronous subprocess reads as its standard input.
+pub enum ProcessInput {
+    /// An open descriptor, which remains owned by the caller.
+    Fd(c_int),
+    /// Text, already encoded.  It is fed through a pipe, or through a
+    /// temporary file when `call-process' doesn't wait for the process.
+    Text(Vec<u8>),
+}
+
+/// Parse the DESTINATION argument of `call-process'.
+fn parse_destination(mut buffer: LispObject) -> (OutputDestination, ErrorDestination) {
+    let mut error_file = Qt;
+    let mut output_file = Qnil;
+
+    // If BUFFER is a list, its meaning is (BUFFER-FOR-STDOUT
+    // FILE-FOR-STDERR), unless the first element is :file, in which case
+    // see the next paragraph.
+    if let Some(cons) = buffer.as_cons() {
+        if !cons.car().eq(QCfile) {
+            if let Some(rest) = cons.cdr().as_cons() {
+                let stderr_file = rest.car();
+                error_file = if stderr_file.is_nil() || stderr_file.eq(Qt) {
+                    stderr_file
+                } else {
+                    unsafe { Fexpand_file_name(stderr_file, Qnil) }
+                };
+            }
+            buffer = cons.car();
+        }
+    }
+
+    // If the buffer is (still) a list, it might be a (:file "file") spec.
+    if let Some(cons) = buffer.as_cons() {
+        if cons.car().eq(QCfile) {
+            output_file = unsafe {
+                Fexpand_file_name(car(cons.cdr()), ThreadState::current_buffer().directory_)
+            };
+            output_file.as_string_or_error();
+            buffer = Qnil;
+        }
+    }
+
+    let output = if buffer.is_integer() {
+        OutputDestination::Discard(false)
+    } else if output_file.is_not_nil() {
+        OutputDestination::File(unsafe { encode_file_name(output_file) })
+    } else if buffer.is_nil() {
+        OutputDestination::Discard(true)
+    } else if buffer.eq(Qt) {
+        OutputDestination::Buffer(ThreadState::current_buffer())
+    } else {
+        // Mention the buffer name for a better error message.
+        let created = unsafe { Fget_buffer_create(buffer) };
+        if created.is_nil() {
+            buffer.as_buffer_or_error();
+        }
+        OutputDestination::Buffer(created.as_buffer_or_error())
+    };
+
+    let error = if error_file.is_string() {
+        ErrorDestination::File(unsafe { encode_file_name(error_file) })
+    } else if error_file.is_nil() {
+        match output {
+            OutputDestination::Discard(_) => ErrorDestination::Output,
+            _ => ErrorDestination::Discard,
+        }
+    } else {
+        ErrorDestination::Output
+    };
+
+    (output, error)
+}
+
+/// Call `find-operation-coding-system' for OPERATION with ARGS.
+fn find_operation_coding_system(operation: LispObject, args: &[LispObject]) -> LispObject {
+    let mut args2 = Vec::with_capacity(args.len() + 1);
+    args2.push(operation);
+    args2.extend_from_slice(args);
+    unsafe { Ffind_operation_coding_system(args2.len() as ptrdiff_t, args2.as_mut_ptr()) }
+}
+
+/// Encode the command arguments of `call-process', ARGS[4] and onward,
+/// for passing them to the subprocess.  CODING_SYSTEMS caches the
+/// result of `find-operation-coding-system'; t means it has not been
+/// called yet.
+fn encode_arguments(args: &[LispObject], coding_systems: &mut LispObject) -> Vec<Vec<u8>> {
+    if args.len() < 5 {
+        return Vec::new();
+    }
+
+    let strings: Vec<LispStringRef> = args[4..]
+        .iter()
+        .map(|arg| arg.as_string_or_error())
+        .collect();
+    let must_encode = strings.iter().any(|arg| arg.is_multibyte());
+
+    let val = unsafe {
+        if globals.Vcoding_system_for_write.is_not_nil() {
+            globals.Vcoding_system_for_write
+        } else if !must_encode {
+            Qraw_text
+        } else {
+            *coding_systems = find_operation_coding_system(Qcall_process, args);
+            coding_systems.as_cons().map_or(Qnil, |c| c.cdr())
+        }
+    };
+    let mut val = unsafe { Fcheck_coding_system(complement_process_encoding_system(val)) };
+
+    // We should not use an ASCII incompatible coding system.
+    if plist_get(unsafe { Fcoding_system_plist(val) }, QCascii_compatible_p).is_nil() {
+        val = unsafe { raw_text_coding_system(val) };
+    }
+
+    strings
+        .into_iter()
+        .map(|arg| {
+            let arg = if arg.is_multibyte() || !val.eq(Qraw_text) {
+                unsafe { code_convert_string_norecord(arg.as_lisp_obj(), val, true) }
+                    .as_string_or_error()
+            } else {
+                arg
+            };
+            arg.as_slice().to_vec()
+        })
+        .collect()
+}
+
+/// Set up CODING for decoding the output of `call-process' into the
+/// current buffer.
+fn setup_output_coding(
+    args: &[LispObject],
+    coding_systems: LispObject,
+    coding: &mut coding_system,
+) {
+    let mut val = unsafe { globals.Vcoding_system_for_read };
+    if val.is_nil() {
+        let coding_systems = if coding_systems.eq(Qt) {
+            find_operation_coding_system(Qcall_process, args)
+        } else {
+            coding_systems
+        };
+        val = if let Some(cons) = coding_systems.as_cons() {
+            cons.car()
+        } else if let Some(cons) = unsafe { globals.Vdefault_process_coding_system }.as_cons() {
+            cons.car()
+        } else {
+            Qnil
+        };
+    }
+    unsafe { Fcheck_coding_system(val) };
+
+    // In unibyte mode, character code conversion should not take place
+    // but EOL conversion should.  So, setup raw-text or one of the
+    // subsidiary according to the information just setup.
+    let multibyte = ThreadState::current_buffer().multibyte_characters_enabled();
+    if !multibyte && val.is_not_nil() {
+        val = unsafe { raw_text_coding_system(val) };
+    }
+    unsafe { setup_coding_system(val, coding) };
+    coding.set_dst_multibyte(multibyte);
+    coding.set_src_multibyte(false);
+}
+
+fn coding_requires_detection(coding: &coding_system) -> bool {
+    coding.common_flags() & CODING_REQUIRE_DETECTION_MASK != 0
+}
+
+fn coding_may_require_decoding(coding: &coding_system) -> bool {
+    coding.common_flags() & (CODING_REQUIRE_DECODING_MASK | CODING_REQUIRE_DETECTION_MASK) != 0
+}
+
+/// Return the name of the coding system CODING was set up for.
+fn coding_system_name(coding: &coding_system) -> LispObject {
+    unsafe { Vcoding_system_hash_table }
+        .as_hash_table_or_error()
+        .get_hash_key(coding.id)
+}
+
+/// Read the output of the subprocess from FD until EOF, and insert it
+/// into the current buffer before point, decoding it with CODING.  If
+/// DISPLAY, redisplay as output arrives.  Return the number of bytes
+/// read.
+fn insert_process_output(fd: c_int, coding: &mut coding_system, display: bool) -> usize {
+    let mut buf = [0u8; CALLPROC_BUFFER_SIZE_MAX];
+    let mut bufsize = CALLPROC_BUFFER_SIZE_MIN;
+    let mut total_read = 0;
+    let mut carryover = 0;
+    let mut display_on_the_fly = display;
+    let mut saved_coding = *coding;
+
+    loop {
+        // Repeatedly read until we've filled as much as possible of the
+        // buffer size we have.  But don't read less than 1024--save that
+        // for the next bufferful.
+        let mut nread = carryover;
+        while nread < bufsize - 1024 {
+            let this_read = unsafe {
+                emacs_read_quit(
+                    fd,
+                    buf[nread..].as_mut_ptr() as *mut c_void,
+                    (bufsize - nread) as ptrdiff_t,
+                )
+            };
+            if this_read < 0 {
+                return total_read;
+            }
+            if this_read == 0 {
+                coding.set_mode(coding.mode() | CODING_MODE_LAST_BLOCK);
+                break;
+            }
+
+            nread += this_read as usize;
+            total_read += this_read as usize;
+
+            if display_on_the_fly {
+                break;
+            }
+        }
+
+        // Now NREAD is the total amount of data in the buffer.
+        let mut current = ThreadState::current_buffer();
+        if nread == 0 {
+            // Nothing to insert.
+        } else if !current.multibyte_characters_enabled() && !coding_may_require_decoding(coding) {
+            unsafe {
+                insert_1_both(
+                    buf.as_ptr() as *const c_char,
+                    nread as ptrdiff_t,
+                    nread as ptrdiff_t,
+                    false,
+                    true,
+                    false,
+                )
+            };
+        } else {
+            // We have to decode the input.
+            let count = c_specpdl_index();
+            unsafe {
+                // FIXME: Call signal_after_change!
+                prepare_to_modify_buffer(current.pt, current.pt, ptr::null_mut());
+                // We cannot allow after-change-functions be run during
+                // decoding, because that might modify the buffer, while
+                // we rely on `produced' to faithfully reflect inserted
+                // text until we set point below.
+                specbind(Qinhibit_modification_hooks, Qt);
+                decode_coding_c_string(
+                    coding,
+                    buf.as_ptr(),
+                    nread as ptrdiff_t,
+                    current.as_lisp_obj(),
+                );
+            }
+            unbind_to(count, Qnil);
+
+            if display_on_the_fly
+                && coding_requires_detection(&saved_coding)
+                && !coding_requires_detection(coding)
+            {
+                // We have detected some coding system, but the detection
+                // may have been via insufficient data.  So give up
+                // displaying on the fly.
+                if coding.produced > 0 {
+                    unsafe {
+                        del_range_2(
+                            coding.dst_pos,
+                            coding.dst_pos_byte,
+                            coding.dst_pos + coding.produced_char,
+                            coding.dst_pos_byte + coding.produced,
+                            false,
+                        )
+                    };
+                }
+                display_on_the_fly = false;
+                *coding = saved_coding;
+                carryover = nread;
+                // Make the above condition always fail in the future.
+                saved_coding
+                    .set_common_flags(saved_coding.common_flags() & !CODING_REQUIRE_DETECTION_MASK);
+                continue;
+            }
+
+            let (pt, pt_byte) = (current.pt, current.pt_byte);
+            current.set_pt_both(pt + coding.produced_char, pt_byte + coding.produced);
+            carryover = coding.carryover_bytes as usize;
+            buf[..carryover].copy_from_slice(&coding.carryover[..carryover]);
+        }
+
+        if coding.mode() & CODING_MODE_LAST_BLOCK != 0 {
+            return total_read;
+        }
+
+        // Make the buffer bigger as we continue to read more data, but
+        // not past CALLPROC_BUFFER_SIZE_MAX.
+        if bufsize < CALLPROC_BUFFER_SIZE_MAX && total_read > 32 * bufsize {
+            bufsize = (bufsize * 2).min(CALLPROC_BUFFER_SIZE_MAX);
+        }
+
+        if display {
+            unsafe { redisplay_preserve_echo_area(1) };
+            // This might have been set to false for code detection.  In
+            // that case, set it back to true because we should have
+            // already detected a coding system.
+            display_on_the_fly = true;
+        }
+    }
+}
+
+/// Close the descriptors of the synchronous subprocess and kill it, if
+/// it's still running.  This runs when `call-process' exits nonlocally
+/// a second time, so don't wait any further.
+extern "C" fn call_process_kill(_: LispObject) {
+    unsafe {
+        if let Some(feeder) = SYNCH_PROCESS.feeder.take() {
+            feeder.finish();
+        }
+
+        for fd in SYNCH_PROCESS.fds.iter_mut() {
+            if *fd >= 0 {
+                emacs_close(*fd);
+                *fd = -1;
+            }
+        }
+
+        if SYNCH_PROCESS.pid != 0 {
+            record_deleted_pid(SYNCH_PROCESS.pid, Qnil);
+            libc::kill(-SYNCH_PROCESS.pid, libc::SIGKILL);
+            SYNCH_PROCESS.pid = 0;
+        }
+    }
+}
+
+/// Clean up when exiting `call-process': restore BUFFER, and interrupt
+/// the subsidiary process group if the process still exists.
+extern "C" fn call_process_cleanup(buffer: LispObject) {
+    set_buffer(buffer.into());
+
+    unsafe {
+        let pid = SYNCH_PROCESS.pid;
+        if pid != 0 {
+            libc::kill(-pid, libc::SIGINT);
+            message1(
+                "Waiting for process to die...(type C-g again to kill it instantly)\0".as_ptr()
+                    as *const c_char,
+            );
+
+            // This will quit on C-g.
+            let wait_ok = wait_for_termination(pid, ptr::null_mut(), true);
+            SYNCH_PROCESS.pid = 0;
+            message1(if wait_ok {
+                "Waiting for process to die...done\0".as_ptr() as *const c_char
+            } else {
+                "Waiting for process to die...internal error\0".as_ptr() as *const c_char
+            });
+        }
+    }
+}
+
+/// Open the file named by the encoded Lisp string FILE for writing
+/// process output.  NAME_FOR_ERROR is reported if that fails.
+fn open_output_file(file: LispObject, message: &str, name_for_error: LispObject) -> c_int {
+    let fd = unsafe {
+        emacs_open(
+            file.as_string_or_error().const_data_ptr() as *const c_char,
+            libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC,
+            DEFAULT_OUTPUT_MODE,
+        )
+    };
+    if fd < 0 {
+        let open_errno = errno::errno().0;
+        let message = CString::new(message).unwrap();
+        unsafe { report_file_errno(message.as_ptr(), name_for_error, open_errno) };
+    }
+    fd
+}
+
+fn open_null_device(message: &str) -> c_int {
+    let null_device = unsafe { build_string(NULL_DEVICE.as_ptr() as *const c_char) };
+    open_output_file(null_device, message, null_device)
+}
+
+/// Return a new descriptor for FD that is owned by a `File', so it can
+/// be handed over to the child's standard streams.
+fn dup_for_child(fd: c_int) -> fs::File {
+    let dup = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) };
+    if dup < 0 {
+        unsafe {
+            report_file_error(
+                "Duplicating file descriptor\0".as_ptr() as *const c_char,
+                Qnil,
+            )
+        };
+    }
+    unsafe { fs::File::from_raw_fd(dup) }
+}
+
+/// Return an unlinked temporary file holding TEXT, positioned at its
+/// start, to serve as the input of a process we don't wait for.
+fn input_file(text: &[u8]) -> fs::File {
+    let pattern = unsafe {
+        encode_file_name(Fexpand_file_name(
+            build_string("emacsXXXXXX\0".as_ptr() as *const c_char),
+            globals.Vtemporary_file_directory,
+        ))
+    };
+    let mut template = pattern.as_string_or_error().as_slice().to_vec();
+    template.push(0);
+
+    let fd = unsafe { libc::mkstemp(template.as_mut_ptr() as *mut c_char) };
+    if fd < 0 {
+        unsafe {
+            report_file_error(
+                "Failed to open temporary file using pattern\0".as_ptr() as *const c_char,
+                pattern,
+            )
+        };
+    }
+    unsafe {
+        libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+        libc::unlink(template.as_ptr() as *const c_char);
+    }
+
+    let mut file = unsafe { fs::File::from_raw_fd(fd) };
+    let written = file
+        .write_all(text)
+        .and_then(|_| file.seek(SeekFrom::Start(0)));
+    if let Err(err) = written {
+        // Close the file first, as reporting the error doesn't return.
+        drop(file);
+        unsafe {
+            report_file_errno(
+                "Writing to temporary file\0".as_ptr() as *const c_char,
+                pattern,
+                err.raw_os_error().unwrap_or(libc::EIO),
+            )
+        };
+    }
+    file
+}
+
+/// Convert the encoded file name or argument BYTES for `Command'.
+fn bytes_to_os_string(bytes: &[u8]) -> OsString {
+    #[cfg(unix)]
+    {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        OsStr::from_bytes(bytes).to_os_string()
+    }
+
+    #[cfg(not(unix))]
+    {
+        OsString::from(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+/// Describe the waitpid-style STATUS of a terminated process the way
+/// `call-process' returns it: the exit code, or a signal description.
+fn process_exit_value(status: c_int) -> LispObject {
+    unsafe {
+        if libc::WIFSIGNALED(status) {
+            synchronize_system_messages_locale();
+            let signame = libc::strsignal(libc::WTERMSIG(status));
+            let signame = if signame.is_null() {
+                "unknown\0".as_ptr() as *const c_char
+            } else {
+                signame as *const c_char
+            };
+            code_convert_string_norecord(
+                build_string(signame),
+                globals.Vlocale_coding_system,
+                false,
+            )
+        } else {
+            LispObject::from(EmacsInt::from(libc::WEXITSTATUS(status)))
+        }
+    }
+}
+
+/// Like `call-process' with ARGS, except that the program's standard
+/// input comes from INPUT rather than from the file named by ARGS[1].
+pub fn call_process(args: &mut [LispObject], input: ProcessInput) -> LispObject {
+    let count = c_specpdl_index();
+
+    unsafe {
+        if SYNCH_PROCESS.pid != 0 {
+            error!("call-process invoked recursively");
+        }
+    }
+
+    let program = args[0].as_string_or_error();
+
+    // Qt denotes that Ffind_operation_coding_system is not yet called.
+    let mut coding_systems = Qt;
+    let argv = encode_arguments(args, &mut coding_systems);
+
+    let (output, error) = parse_destination(if args.len() >= 3 { args[2] } else { Qnil });
+
+    // Make sure that the child will be able to chdir to the current
+    // buffer's current directory, or its unhandled equivalent.
+    let current_dir = unsafe { encode_current_directory() }.as_string_or_error();
+
+    let display = is_interactive() && args.len() >= 4 && args[3].is_not_nil();
+
+    unsafe {
+        SYNCH_PROCESS.fds = [-1; CALLPROC_FDS];
+        record_unwind_protect(Some(call_process_kill), Qnil);
+    }
+
+    // Search for program; barf if not found.
+    let path = find_executable(program);
+    if path.is_none() {
+        unsafe {
+            report_file_error(
+                "Searching for program\0".as_ptr() as *const c_char,
+                program.as_lisp_obj(),
+            )
+        };
+    }
+
+    let fd_output = match output {
+        OutputDestination::Discard(_) => open_null_device("Opening null device"),
+        OutputDestination::File(file) => {
+            open_output_file(file, "Opening process output file", unsafe {
+                decode_file_name(file)
+            })
+        }
+        OutputDestination::Buffer(_) => {
+            let mut fd = [-1; 2];
+            if unsafe { emacs_pipe(fd.as_mut_ptr()) } != 0 {
+                unsafe {
+                    report_file_error("Creating process pipe\0".as_ptr() as *const c_char, Qnil)
+                };
+            }
+            unsafe { SYNCH_PROCESS.fds[CALLPROC_PIPEREAD] = fd[0] };
+            fd[1]
+        }
+    };
+    unsafe { SYNCH_PROCESS.fds[CALLPROC_STDOUT] = fd_output };
+
+    let fd_error = match error {
+        ErrorDestination::Output => fd_output,
+        ErrorDestination::Discard => open_null_device("Cannot redirect stderr"),
+        ErrorDestination::File(file) => open_output_file(file, "Cannot redirect stderr", unsafe {
+            decode_file_name(file)
+        }),
+    };
+    if fd_error != fd_output {
+        unsafe { SYNCH_PROCESS.fds[CALLPROC_STDERR] = fd_error };
+    }
+
+    unsafe { record_unwind_protect(Some(call_process_cleanup), current_buffer()) };
+
+    let mut command = Command::new(path.unwrap());
+    command
+        .args(argv.iter().map(|arg| bytes_to_os_string(arg)))
+        .current_dir(bytes_to_os_string(current_dir.as_slice()))
+        .env_clear()
+        .stdout(dup_for_child(fd_output))
+        .stderr(dup_for_child(fd_error));
+    for entry in make_environment_block(current_dir.as_slice()) {
+        let entry = entry.as_bytes();
+        let name = env_entry_name(entry);
+        command.env(
+            bytes_to_os_string(name),
+            bytes_to_os_string(&entry[name.len() + 1..]),
+        );
+    }
+    let wait = match output {
+        OutputDestination::Discard(wait) => wait,
+        _ => true,
+    };
+    let text = match input {
+        ProcessInput::Fd(fd) => {
+            command.stdin(dup_for_child(fd));
+            None
+        }
+        ProcessInput::Text(text) => {
+            if wait {
+                command.stdin(Stdio::piped());
+                Some(text)
+            } else {
+                // Nothing would be left to feed a pipe once we return.
+                command.stdin(input_file(&text));
+                None
+            }
+        }
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.before_exec(|| {
+            unsafe {
+                libc::setsid();
+                // Emacs ignores SIGPIPE, but the child should not; the
+                // standard library already restores it.  Likewise for
+                // SIGPROF.
+                libc::signal(libc::SIGPROF, libc::SIG_DFL);
+            }
+            Ok(())
+        });
+    }
+
+    // Block SIGCHLD until the child is recorded, so that the handler
+    // can't reap it first.  The standard library clears the signal mask
+    // in the child.
+    let mut oldset: sigset_t = unsafe { mem::zeroed() };
+    unsafe { block_child_signal(&mut oldset) };
+    let spawned = command.spawn();
+    if let Ok(ref child) = spawned {
+        let pid = child.id() as pid_t;
+        unsafe {
+            if wait {
+                SYNCH_PROCESS.pid = pid;
+            } else {
+                record_deleted_pid(pid, Qnil);
+            }
+        }
+    }
+    unsafe { unblock_child_signal(&oldset) };
+
+    // Dropping the command closes our copies of the child's descriptors,
+    // so that we see EOF when the child exits.
+    drop(command);
+    if let Err(ref err) = spawned {
+        unsafe {
+            report_file_errno(
+                "Doing vfork\0".as_ptr() as *const c_char,
+                Qnil,
+                err.raw_os_error().unwrap_or(libc::EIO),
+            )
+        };
+    }
+    let mut child = spawned.unwrap();
+    let pid = child.id() as pid_t;
+
+    if let (Some(text), Some(stdin)) = (text, child.stdin.take()) {
+        unsafe { SYNCH_PROCESS.feeder = Some(InputFeeder::start(stdin, text)) };
+    }
+
+    unsafe {
+        // Close our file descriptors, except for the pipe, since we
+        // will use that to read input from.
+        for (i, fd) in SYNCH_PROCESS.fds.iter_mut().enumerate() {
+            if i != CALLPROC_PIPEREAD && *fd >= 0 {
+                emacs_close(*fd);
+                *fd = -1;
+            }
+        }
+    }
+
+    if !wait {
+        return unbind_to(count, Qnil);
+    }
+
+    let fd0 = unsafe { SYNCH_PROCESS.fds[CALLPROC_PIPEREAD] };
+    if let OutputDestination::Buffer(buffer) = output {
+        set_buffer(buffer.as_lisp_obj().into());
+
+        let mut process_coding: coding_system = unsafe { mem::zeroed() };
+        setup_output_coding(args, coding_systems, &mut process_coding);
+        let total_read = insert_process_output(fd0, &mut process_coding, display);
+
+        unsafe {
+            globals.Vlast_coding_system_used = coding_system_name(&process_coding);
+            // If the caller required, let the buffer inherit the
+            // coding-system used to decode the process output.
+            if globals.inherit_process_coding_system {
+                call!(
+                    intern("after-insert-file-set-buffer-file-coding-system").as_lisp_obj(),
+                    LispObject::from(total_read as EmacsInt)
+                );
+            }
+        }
+    }
+
+    // Wait for it to terminate, unless it already has.
+    let mut status = 0;
+    let wait_ok = unsafe { wait_for_termination(pid, &mut status, fd0 < 0) };
+
+    // Don't kill any children that the subprocess may have left behind
+    // when exiting.
+    unsafe { SYNCH_PROCESS.pid = 0 };
+
+    unbind_to(count, Qnil);
+
+    if wait_ok {
+        process_exit_value(status)
+    } else {
+        unsafe { build_unibyte_string("internal error\0".as_ptr() as *const c_char) }
+    }
+}
+
 /// Call PROGRAM synchronously in separate process.
 /// The remaining arguments are optional.
 /// The program's input comes from file INFILE (nil means `/dev/null').
@@ -64,19 +912,563 @@ pub fn call_process_lisp(args: &mut [LispObject]) -> LispObject {
     };
 
     if filefd < 0 {
-        unsafe { report_file_error("Opening process input file".as_ptr() as *const i8, infile) };
+        unsafe { report_file_error("Opening process input file\0".as_ptr() as *const i8, infile) };
     }
 
     unsafe { record_unwind_protect_int(Some(close_file_unwind), filefd) };
 
-    unbind_to(count, unsafe {
-        call_process(
-            args.len() as isize,
-            args.as_mut_ptr() as *mut LispObject,
-            filefd,
-            -1,
-        )
-    })
+    let value = call_process(args, ProcessInput::Fd(filefd));
+    unbind_to(count, value)
+}
+
+/// Send text from START to END to a synchronous process running PROGRAM.
+///
+/// START and END are normally buffer positions specifying the part of the
+/// buffer to send to the process.
+/// If START is nil, that means to use the entire buffer contents; END is
+/// ignored.
+/// If START is a string, then send that string to the process
+/// instead of any buffer contents; END is ignored.
+/// The remaining arguments are optional.
+/// Delete the text if fourth arg DELETE is non-nil.
+///
+/// Insert output in BUFFER before point; t means current buffer; nil for
+///  BUFFER means discard it; 0 means discard and don't wait; and `(:file
+///  FILE)', where FILE is a file name string, means that it should be
+///  written to that file (if the file already exists it is overwritten).
+/// BUFFER can also have the form (REAL-BUFFER STDERR-FILE); in that case,
+/// REAL-BUFFER says what to do with standard output, as above,
+/// while STDERR-FILE says what to do with standard error in the child.
+/// STDERR-FILE may be nil (discard standard error output),
+/// t (mix it with ordinary output), or a file name string.
+///
+/// Sixth arg DISPLAY non-nil means redisplay buffer as output is inserted.
+/// Remaining args are passed to PROGRAM at startup as command args.
+///
+/// If BUFFER is 0, `call-process-region' returns immediately with value nil.
+/// Otherwise it waits for PROGRAM to terminate
+/// and returns a numeric exit status or a signal description string.
+/// If you quit, the process is killed with SIGINT, or SIGKILL if you quit again.
+///
+/// usage: (call-process-region START END PROGRAM &optional DELETE BUFFER DISPLAY &rest ARGS)
+#[lisp_fn(min = "3")]
+pub fn call_process_region(args: &mut [LispObject]) -> LispObject {
+    let (mut start, mut end) = (args[0], args[1]);
+    let current = ThreadState::current_buffer();
+
+    let text = if let Some(string) = start.as_string() {
+        string.as_lisp_obj()
+    } else if start.is_nil() {
+        unsafe { make_buffer_string(current.beg(), current.z(), false) }
+    } else {
+        unsafe { validate_region(&mut start, &mut end) };
+        unsafe {
+            make_buffer_string(
+                start.as_fixnum_or_error() as ptrdiff_t,
+                end.as_fixnum_or_error() as ptrdiff_t,
+                false,
+            )
+        }
+    };
+
+    // Decide the coding system the text is sent in.
+    let val = unsafe {
+        if globals.Vcoding_system_for_write.is_not_nil() {
+            globals.Vcoding_system_for_write
+        } else if !current.multibyte_characters_enabled() {
+            Qraw_text
+        } else {
+            let coding_systems = find_operation_coding_system(Qcall_process_region, args);
+            coding_systems.as_cons().map_or(Qnil, |c| c.cdr())
+        }
+    };
+    let val = unsafe { complement_process_encoding_system(val) };
+    let encoded = unsafe { code_convert_string_norecord(text, val, true) };
+    let input = ProcessInput::Text(encoded.as_string_or_error().as_slice().to_vec());
+
+    if args.len() > 3 && args[3].is_not_nil() {
+        if start.is_nil() {
+            // No need to save restrictions since we delete everything
+            // anyway.
+            unsafe {
+                Fwiden();
+                del_range(current.beg(), current.z());
+            }
+        } else {
+            delete_region(start, end);
+        }
+    }
+
+    // Shift the arguments into the form `call-process' expects; the
+    // INFILE slot is unused.
+    let args = if args.len() > 3 {
+        &mut args[2..]
+    } else {
+        args[1] = args[2];
+        &mut args[1..]
+    };
+    args[1] = Qnil;
+
+    call_process(args, input)
+}
+
+/// The result of looking up a variable in an environment list.
+#[derive(Clone, Copy)]
+enum EnvEntry {
+    /// The variable is set; the value starts at the given byte offset
+    /// of the entry.
+    Value(LispStringRef, usize),
+    /// A lone variable name: the variable is removed from the environment.
+    Unset,
+}
+
+impl EnvEntry {
+    fn value(self) -> Option<Vec<u8>> {
+        match self {
+            EnvEntry::Value(entry, offset) => Some(entry.as_slice()[offset..].to_vec()),
+            EnvEntry::Unset => None,
+        }
+    }
+}
+
+/// Search the environment list ENV for VAR.  Equivalent to
+/// `getenv_internal_1' in the C code.
+fn getenv_in_list(var: &[u8], env: LispObject) -> Option<EnvEntry> {
+    env.iter_cars_safe()
+        .filter_map(|entry| entry.as_string())
+        .find_map(|entry| {
+            let bytes = entry.as_slice();
+            if bytes.len() < var.len() || !env_name_eq(&bytes[..var.len()], var) {
+                None
+            } else if bytes.len() == var.len() {
+                Some(EnvEntry::Unset)
+            } else if bytes[var.len()] == b'=' {
+                Some(EnvEntry::Value(entry, var.len() + 1))
+            } else {
+                None
+            }
+        })
+}
+
+/// Where the value of an environment variable was found.
+enum EnvValue {
+    /// In a Lisp string, starting at the given byte offset.
+    Lisp(LispStringRef, usize),
+    /// In the environment of the Emacs process itself.
+    #[allow(dead_code)]
+    Process(&'static CStr),
+}
+
+impl EnvValue {
+    fn to_bytes(&self) -> Vec<u8> {
+        match *self {
+            EnvValue::Lisp(string, offset) => string.as_slice()[offset..].to_vec(),
+            EnvValue::Process(value) => value.to_bytes().to_vec(),
+        }
+    }
+
+    fn as_ptr(&self) -> *mut c_char {
+        match *self {
+            EnvValue::Lisp(string, offset) => unsafe {
+                string.const_sdata_ptr().add(offset) as *mut c_char
+            },
+            EnvValue::Process(value) => value.as_ptr() as *mut c_char,
+        }
+    }
+}
+
+/// Look up VAR the way subprocesses will see it: first in
+/// `process-environment', then, for DISPLAY, in FRAME's display
+/// parameter and `initial-environment'.
+fn getenv_internal(var: &[u8], frame: LispObject) -> Option<EnvValue> {
+    if let Some(entry) = getenv_in_list(var, unsafe { globals.Vprocess_environment }) {
+        return match entry {
+            EnvEntry::Value(string, offset) => Some(EnvValue::Lisp(string, offset)),
+            EnvEntry::Unset => None,
+        };
+    }
+
+    // On Windows we make some modifications to Emacs' environment
+    // without recording them in `process-environment'.
+    #[cfg(windows)]
+    {
+        if let Some(value) = environ::getenv(var) {
+            return Some(EnvValue::Process(value));
+        }
+        // Variables that the startup code sets after the environment map
+        // was read are only in the process environment.
+        if let Ok(cvar) = CString::new(var) {
+            let value = unsafe { libc::getenv(cvar.as_ptr()) };
+            if !value.is_null() {
+                return Some(EnvValue::Process(unsafe { CStr::from_ptr(value) }));
+            }
+        }
+    }
+
+    // For DISPLAY try to get the values from the frame or the initial env.
+    if var == b"DISPLAY" {
+        let frame = if frame.is_nil() {
+            selected_frame().as_lisp_obj()
+        } else {
+            frame
+        };
+        let display = unsafe { Fframe_parameter(frame, Qdisplay) };
+        if let Some(display) = display.as_string() {
+            return Some(EnvValue::Lisp(display, 0));
+        }
+        if let Some(EnvEntry::Value(string, offset)) =
+            getenv_in_list(var, unsafe { globals.Vinitial_environment })
+        {
+            return Some(EnvValue::Lisp(string, offset));
+        }
+    }
+
+    None
+}
+
+fn make_lisp_env_string(value: &[u8]) -> LispObject {
+    unsafe { make_string(value.as_ptr() as *const c_char, value.len() as ptrdiff_t) }
+}
+
+/// Get the value of environment variable VARIABLE.
+/// VARIABLE should be a string.  Value is nil if VARIABLE is undefined in
+/// the environment.  Otherwise, value is a string.
+///
+/// This function searches `process-environment' for VARIABLE.
+///
+/// If optional parameter ENV is a list, then search this list instead of
+/// `process-environment', and return t when encountering a negative entry
+/// \(an entry for a variable with no value).
+#[lisp_fn(min = "1", name = "getenv-internal", c_name = "getenv_internal")]
+pub fn getenv_internal_lisp(variable: LispStringRef, env: LispObject) -> LispObject {
+    let var = variable.as_slice();
+
+    if env.is_cons() {
+        getenv_in_list(var, env).map_or(Qnil, |entry| {
+            entry
+                .value()
+                .map_or(Qt, |value| make_lisp_env_string(&value))
+        })
+    } else {
+        getenv_internal(var, env).map_or(Qnil, |value| make_lisp_env_string(&value.to_bytes()))
+    }
+}
+
+/// A version of getenv that consults the Lisp environment lists,
+/// easily callable from C.  This is usually called from egetenv.
+#[no_mangle]
+pub unsafe extern "C" fn egetenv_internal(var: *const c_char, len: ptrdiff_t) -> *mut c_char {
+    let var = ::std::slice::from_raw_parts(var as *const u8, len as usize);
+    getenv_internal(var, Qnil).map_or(ptr::null_mut(), |value| value.as_ptr())
+}
+
+/// Merge the environment entries in ENTRIES the way `child_setup' does:
+/// when a variable is defined several times, the first definition wins,
+/// and a lone variable name suppresses any later definition of that
+/// variable without appearing in the result itself.
+pub fn merge_environment<'a, I>(entries: I) -> Vec<&'a [u8]>
+where
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    let mut seen: Vec<&[u8]> = Vec::new();
+    let mut result = Vec::new();
+
+    for entry in entries {
+        let name = env_entry_name(entry);
+        if seen.iter().any(|s| env_name_eq(s, name)) {
+            continue;
+        }
+        seen.push(name);
+        if name.len() < entry.len() {
+            result.push(entry);
+        }
+    }
+
+    result
+}
+
+/// Encode the `process-environment' entry ENTRY for a subprocess.
+/// Multibyte entries are encoded with `locale-coding-system', as
+/// `setenv' does.
+fn encode_env_entry(entry: LispObject) -> LispObject {
+    let coding = unsafe { globals.Vlocale_coding_system };
+    if entry.as_string_or_error().is_multibyte() && coding.is_not_nil() {
+        unsafe { code_convert_string_norecord(entry, coding, true) }
+    } else {
+        entry
+    }
+}
+
+/// Build the environment block for a subprocess from
+/// `process-environment', adding the selected frame's DISPLAY if the
+/// environment doesn't specify one.  PWD, when present, is set to
+/// CURRENT_DIR.
+pub fn make_environment_block(current_dir: &[u8]) -> Vec<CString> {
+    let mut display_entry = None;
+    let process_environment = unsafe { globals.Vprocess_environment };
+
+    if getenv_in_list(b"DISPLAY", process_environment).is_none() {
+        display_entry = getenv_internal(b"DISPLAY", Qnil).map(|display| {
+            let mut entry = b"DISPLAY=".to_vec();
+            entry.extend_from_slice(&display.to_bytes());
+            entry
+        });
+    }
+
+    let pwd_entry = if getenv_internal(b"PWD", Qnil).is_some() {
+        let mut dir = current_dir;
+        // Strip trailing slashes for PWD, but leave "/" and "//" alone.
+        while dir.len() > 2 && dir.ends_with(b"/") {
+            dir = &dir[..dir.len() - 1];
+        }
+        let mut entry = b"PWD=".to_vec();
+        entry.extend_from_slice(dir);
+        Some(entry)
+    } else {
+        None
+    };
+
+    let strings: Vec<LispStringRef> = process_environment
+        .iter_cars_safe()
+        .take_while(|entry| entry.is_string())
+        .map(|entry| encode_env_entry(entry).as_string_or_error())
+        .collect();
+
+    let merged = merge_environment(
+        pwd_entry
+            .iter()
+            .chain(display_entry.iter())
+            .map(|e| e.as_slice())
+            .chain(strings.iter().map(|s| s.as_slice())),
+    );
+
+    merged
+        .into_iter()
+        .filter_map(|entry| CString::new(entry).ok())
+        .collect()
+}
+
+/// Return the environment a subprocess started now would receive.
+/// The value is a list of strings of the form VARIABLE=VALUE, built from
+/// `process-environment' the same way `make-process' and `call-process'
+/// do: the first definition of a variable wins, and variables listed
+/// without a value are left out.
+#[lisp_fn]
+pub fn process_environment_block() -> LispObject {
+    let dir = ThreadState::current_buffer().directory_;
+    let dir = dir
+        .as_string()
+        .map_or_else(Vec::new, |d| d.as_slice().to_vec());
+    let entries: Vec<LispObject> = make_environment_block(&dir)
+        .iter()
+        .map(|entry| make_lisp_env_string(entry.as_bytes()))
+        .collect();
+    LispObject::from(entries)
+}
+
+/// Cache of resolved executables, keyed by command name.  It is only
+/// valid for the search path it was filled with, identified by
+/// `fingerprint'.  Commands that were not found are not cached, since
+/// they may be installed at any time.
+struct ExecutableCache {
+    fingerprint: u64,
+    entries: HashMap<Vec<u8>, PathBuf>,
+}
+
+lazy_static! {
+    static ref EXECUTABLE_CACHE: Mutex<ExecutableCache> = Mutex::new(ExecutableCache {
+        fingerprint: 0,
+        entries: HashMap::new(),
+    });
+}
+
+/// Compute a hash of everything the result of an executable search
+/// depends on: `exec-path', `exec-suffixes', the PATH in
+/// `process-environment' and, if `exec-path' has a nil or relative
+/// entry, `default-directory'.
+fn search_path_fingerprint() -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let exec_path = unsafe { globals.Vexec_path };
+    let mut relative = false;
+
+    for dir in exec_path.iter_cars_safe() {
+        match dir.as_string() {
+            Some(d) => {
+                d.as_slice().hash(&mut hasher);
+                relative |= !lisp_file_name_to_path(dir).is_absolute();
+            }
+            None => {
+                0u8.hash(&mut hasher);
+                relative = true;
+            }
+        }
+    }
+    if relative {
+        if let Some(d) = ThreadState::current_buffer().directory_.as_string() {
+            d.as_slice().hash(&mut hasher);
+        }
+    }
+    for suffix in unsafe { globals.Vexec_suffixes }.iter_cars_safe() {
+        if let Some(s) = suffix.as_string() {
+            s.as_slice().hash(&mut hasher);
+        }
+    }
+    getenv_internal(b"PATH", Qnil)
+        .map(|path| path.to_bytes())
+        .hash(&mut hasher);
+
+    hasher.finish()
+}
+
+/// Convert the Lisp file name NAME to a path in the file system's encoding.
+pub fn lisp_file_name_to_path(name: LispObject) -> PathBuf {
+    let encoded = unsafe { encode_file_name(name) }.as_string_or_error();
+
+    #[cfg(unix)]
+    {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        PathBuf::from(OsStr::from_bytes(encoded.as_slice()))
+    }
+
+    #[cfg(not(unix))]
+    {
+        PathBuf::from(encoded.to_string())
+    }
+}
+
+pub fn path_to_lisp_file_name(path: &Path) -> LispObject {
+    #[cfg(unix)]
+    let bytes = {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes().to_vec()
+    };
+
+    #[cfg(not(unix))]
+    let bytes = path.to_string_lossy().into_owned().into_bytes();
+
+    unsafe {
+        decode_file_name(make_unibyte_string(
+            bytes.as_ptr() as *const c_char,
+            bytes.len() as ptrdiff_t,
+        ))
+    }
+}
+
+/// Return true if PATH is a regular file that can be executed.  This
+/// mirrors the PREDICATE argument 1 that `executable-find' passes to
+/// `locate-file'.
+fn is_executable_file(path: &Path) -> bool {
+    match fs::metadata(path) {
+        Ok(ref meta) if !meta.is_dir() => {
+            #[cfg(unix)]
+            {
+                let cpath = match CString::new(path_to_bytes(path)) {
+                    Ok(p) => p,
+                    Err(_) => return false,
+                };
+                unsafe { libc::access(cpath.as_ptr(), libc::X_OK) == 0 }
+            }
+            #[cfg(not(unix))]
+            {
+                true
+            }
+        }
+        _ => false,
+    }
+}
+
+#[cfg(unix)]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+/// Search `exec-path' for COMMAND, trying each of `exec-suffixes'.
+/// Like `locate-file', an absolute COMMAND is only tried as it is,
+/// while any other, even one with a directory part like "bin/prog",
+/// is looked up relative to each directory of `exec-path'.
+fn search_executable(command: LispStringRef) -> Option<PathBuf> {
+    let mut suffixes: Vec<LispStringRef> = unsafe { globals.Vexec_suffixes }
+        .iter_cars_safe()
+        .filter_map(|s| s.as_string())
+        .collect();
+    if suffixes.is_empty() {
+        suffixes.push(LispObject::empty_unibyte_string());
+    }
+
+    let try_dir = |dir: LispObject| {
+        let base = unsafe { Fexpand_file_name(command.as_lisp_obj(), dir) };
+        let base = lisp_file_name_to_path(base);
+        suffixes
+            .iter()
+            .map(|suffix| {
+                let mut candidate = base.clone().into_os_string();
+                candidate.push(lisp_file_name_to_path(suffix.as_lisp_obj()).as_os_str());
+                PathBuf::from(candidate)
+            })
+            .find(|candidate| is_executable_file(candidate))
+    };
+
+    if file_name_absolute_p_lisp(command) {
+        return try_dir(Qnil);
+    }
+
+    unsafe { globals.Vexec_path }
+        .iter_cars_safe()
+        .filter_map(|dir| try_dir(dir))
+        .next()
+}
+
+/// Search for COMMAND in `exec-path' and return the absolute file name.
+/// Return nil if COMMAND is not found anywhere in `exec-path'.
+///
+/// Each of `exec-suffixes' is tried in turn, and only files that can be
+/// executed are considered.  Programs found are remembered until
+/// `exec-path', `exec-suffixes' or the PATH in `process-environment'
+/// change.
+#[lisp_fn]
+pub fn executable_find_internal(command: LispStringRef) -> LispObject {
+    find_executable(command).map_or(Qnil, |path| path_to_lisp_file_name(&path))
+}
+
+/// Search `exec-path' for COMMAND like `executable-find-internal', and
+/// return the file name in the file system's encoding.
+pub fn find_executable(command: LispStringRef) -> Option<PathBuf> {
+    // Finding an absolute command takes no search.
+    if file_name_absolute_p_lisp(command) {
+        return search_executable(command);
+    }
+
+    let fingerprint = search_path_fingerprint();
+    let mut cache = EXECUTABLE_CACHE.lock().unwrap();
+
+    if cache.fingerprint != fingerprint {
+        cache.fingerprint = fingerprint;
+        cache.entries.clear();
+    }
+
+    let key = command.as_slice().to_vec();
+    match cache.entries.get(&key) {
+        // A hit is only trusted if the file is still there.
+        Some(path) if is_executable_file(path) => return Some(path.clone()),
+        _ => {}
+    }
+    let found = search_executable(command);
+    match found {
+        Some(ref path) => cache.entries.insert(key, path.clone()),
+        None => cache.entries.remove(&key),
+    };
+    found
 }
 
 include!(concat!(env!("OUT_DIR"), "/callproc_exports.rs"));
+
+#[test]
+fn test_merge_environment() {
+    let entries: Vec<&[u8]> = vec![b"A=1", b"B", b"A=2", b"B=3", b"C=", b"D=4"];
+    assert_eq!(
+        merge_environment(entries),
+        vec![b"A=1" as &[u8], b"C=", b"D=4"]
+    );
+}
@@ -1,9 +1,12 @@
 //! Routines to deal with category tables.
 
+use libc::c_int;
+
 use crate::{
     chartable::LispCharTableRef,
     lisp::{defsubr, LispObject},
-    remacs_sys::Qcategory_table,
+    multibyte::Codepoint,
+    remacs_sys::{char_category_set, Qcategory_table},
     threads::ThreadState,
 };
 
@@ -23,4 +26,12 @@ pub fn category_table() -> LispObject {
     buffer_ref.category_table_
 }
 
+/// Return true if the character C has CATEGORY in the current category
+/// table.  This is `CHAR_HAS_CATEGORY' in C.
+pub fn char_has_category(c: Codepoint, category: u8) -> bool {
+    unsafe { char_category_set(c as c_int) }
+        .as_bool_vector()
+        .map_or(false, |set| set.get(category as usize).is_not_nil())
+}
+
 include!(concat!(env!("OUT_DIR"), "/category_exports.rs"));
@@ -0,0 +1,1325 @@
+//! The interpreter of CCL, the Code Conversion Language.
+//!
+//! A CCL program is a vector of integers made by `ccl-compile', whose
+//! first two elements are the magnification of the output buffer and the
+//! address of the code run at the end of the input.  Each code word has
+//! a command in its low 5 bits, and register numbers, constants or
+//! relative addresses in the rest:
+//!
+//!     |------- 17-bit ------|- 3-bit --|- 3-bit --|- 5-bit -|
+//!     |--constant argument--|-register-|-register-|-command-|
+//!
+//! The syntax of the language is described in `ccl.el', and each
+//! command in the comments of ccl.c, which sets up the programs and
+//! defines the Lisp interface.  This is the interpreter itself, which
+//! runs a program on a block of characters and can be suspended when
+//! its input runs out or its output is full.  It treats malformed code,
+//! such as words that are not integers, division by zero or references
+//! to tables that don't exist, as invalid commands.
+
+use libc::{c_int, c_uint};
+
+use crate::{
+    hashtable::HashLookupResult,
+    lisp::LispObject,
+    remacs_sys::{ccl_program, char_charset, charset_table, charset_unicode, decode_char},
+    remacs_sys::{globals, setup_ccl_program, translate_char, Vccl_program_table},
+    remacs_sys::{EmacsInt, Qlambda, Qnil, Qt},
+    remacs_sys::{CCL_STAT_INVALID_CMD, CCL_STAT_QUIT, CCL_STAT_SUCCESS},
+    remacs_sys::{CCL_STAT_SUSPEND_BY_DST, CCL_STAT_SUSPEND_BY_SRC},
+};
+
+/// The indices of the header of a compiled CCL program.
+const CCL_HEADER_EOF: c_int = 1;
+const CCL_HEADER_MAIN: c_int = 2;
+
+/// The range of the code words of a program.
+const CCL_CODE_MAX: EmacsInt = (1 << (28 - 1)) - 1;
+const CCL_CODE_MIN: EmacsInt = -1 - CCL_CODE_MAX;
+
+// The commands.
+const CCL_SET_REGISTER: c_int = 0x00;
+const CCL_SET_SHORT_CONST: c_int = 0x01;
+const CCL_SET_CONST: c_int = 0x02;
+const CCL_SET_ARRAY: c_int = 0x03;
+const CCL_JUMP: c_int = 0x04;
+const CCL_JUMP_COND: c_int = 0x05;
+const CCL_WRITE_REGISTER_JUMP: c_int = 0x06;
+const CCL_WRITE_REGISTER_READ_JUMP: c_int = 0x07;
+const CCL_WRITE_CONST_JUMP: c_int = 0x08;
+const CCL_WRITE_CONST_READ_JUMP: c_int = 0x09;
+const CCL_WRITE_STRING_JUMP: c_int = 0x0A;
+const CCL_WRITE_ARRAY_READ_JUMP: c_int = 0x0B;
+const CCL_READ_JUMP: c_int = 0x0C;
+const CCL_BRANCH: c_int = 0x0D;
+const CCL_READ_REGISTER: c_int = 0x0E;
+const CCL_WRITE_EXPR_CONST: c_int = 0x0F;
+const CCL_READ_BRANCH: c_int = 0x10;
+const CCL_WRITE_REGISTER: c_int = 0x11;
+const CCL_WRITE_EXPR_REGISTER: c_int = 0x12;
+const CCL_CALL: c_int = 0x13;
+const CCL_WRITE_CONST_STRING: c_int = 0x14;
+const CCL_WRITE_ARRAY: c_int = 0x15;
+const CCL_END: c_int = 0x16;
+const CCL_EXPR_SELF_CONST: c_int = 0x17;
+const CCL_EXPR_SELF_REG: c_int = 0x18;
+const CCL_SET_EXPR_CONST: c_int = 0x19;
+const CCL_SET_EXPR_REG: c_int = 0x1A;
+const CCL_JUMP_COND_EXPR_CONST: c_int = 0x1B;
+const CCL_JUMP_COND_EXPR_REG: c_int = 0x1C;
+const CCL_READ_JUMP_COND_EXPR_CONST: c_int = 0x1D;
+const CCL_READ_JUMP_COND_EXPR_REG: c_int = 0x1E;
+const CCL_EXTENSION: c_int = 0x1F;
+
+// The extended commands.
+const CCL_READ_MULTIBYTE_CHAR2: c_int = 0x00;
+const CCL_WRITE_MULTIBYTE_CHAR2: c_int = 0x01;
+const CCL_TRANSLATE_CHARACTER: c_int = 0x02;
+const CCL_TRANSLATE_CHARACTER_CONST_TBL: c_int = 0x03;
+const CCL_ITERATE_MULTIPLE_MAP: c_int = 0x10;
+const CCL_MAP_MULTIPLE: c_int = 0x11;
+const CCL_MAP_SINGLE: c_int = 0x12;
+const CCL_LOOKUP_INT_CONST_TBL: c_int = 0x13;
+const CCL_LOOKUP_CHAR_CONST_TBL: c_int = 0x14;
+
+// The operators of expressions.
+const CCL_PLUS: c_int = 0x00;
+const CCL_MINUS: c_int = 0x01;
+const CCL_MUL: c_int = 0x02;
+const CCL_DIV: c_int = 0x03;
+const CCL_MOD: c_int = 0x04;
+const CCL_AND: c_int = 0x05;
+const CCL_OR: c_int = 0x06;
+const CCL_XOR: c_int = 0x07;
+const CCL_LSH: c_int = 0x08;
+const CCL_RSH: c_int = 0x09;
+const CCL_LSH8: c_int = 0x0A;
+const CCL_RSH8: c_int = 0x0B;
+const CCL_DIVMOD: c_int = 0x0C;
+const CCL_LS: c_int = 0x10;
+const CCL_GT: c_int = 0x11;
+const CCL_EQ: c_int = 0x12;
+const CCL_LE: c_int = 0x13;
+const CCL_GE: c_int = 0x14;
+const CCL_NE: c_int = 0x15;
+const CCL_DECODE_SJIS: c_int = 0x16;
+const CCL_ENCODE_SJIS: c_int = 0x17;
+
+/// How deep calls of other CCL programs can be nested.
+const MAX_CALL_DEPTH: usize = 256;
+
+/// How deep the map sets of `CCL_MapMultiple' can be nested.
+const MAX_MAP_SET_LEVEL: usize = 30;
+
+/// Where to return to from a called CCL program.
+#[derive(Clone, Copy)]
+struct Frame {
+    prog: *mut LispObject,
+    ic: c_int,
+    eof_ic: c_int,
+}
+
+/// The callers of the running CCL program.  Like its registers, they
+/// survive the suspension of the program.
+static mut CALL_STACK: [Frame; MAX_CALL_DEPTH] = [Frame {
+    prog: 0 as *mut LispObject,
+    ic: 0,
+    eof_ic: 0,
+}; MAX_CALL_DEPTH];
+
+/// If positive, the depth of the program called by `CCL_MapMultiple',
+/// which must not run `CCL_MapMultiple' itself.
+static mut STACK_IDX_OF_MAP_MULTIPLE: c_int = 0;
+
+/// Why the interpreter stops running the current instruction.
+enum Stop {
+    /// Go on with the instruction at IC.
+    Repeat,
+    /// Stop running the program with a status.
+    Finish(c_int),
+    /// Stop running the program with a status, and write a message
+    /// about it to the output.
+    Error(c_int),
+}
+
+type Step = Result<(), Stop>;
+
+/// What `CCL_MapMultiple' does after looking at one of its maps.
+enum MapStep {
+    Next,
+    EndOfSet,
+}
+
+fn in_int_range(n: EmacsInt) -> bool {
+    EmacsInt::from(c_int::min_value()) <= n && n <= EmacsInt::from(c_int::max_value())
+}
+
+/// Return OBJECT if it is an integer that fits in an int.
+fn as_int(object: LispObject) -> Option<c_int> {
+    object
+        .as_fixnum()
+        .filter(|&n| in_int_range(n))
+        .map(|n| n as c_int)
+}
+
+/// Convert CODE, the two bytes of a Shift-JIS character, to JIS.
+fn sjis_to_jis(code: c_int) -> c_int {
+    let (s1, s2) = (code >> 8, code & 0xFF);
+    let (j1, j2) = if s2 >= 0x9F {
+        (s1 * 2 - if s1 >= 0xE0 { 0x160 } else { 0xE0 }, s2 - 0x7E)
+    } else {
+        (
+            s1 * 2 - if s1 >= 0xE0 { 0x161 } else { 0xE1 },
+            s2 - if s2 >= 0x7F { 0x20 } else { 0x1F },
+        )
+    };
+    (j1 << 8) | j2
+}
+
+/// Convert CODE, the two bytes of a JIS character, to Shift-JIS.
+fn jis_to_sjis(code: c_int) -> c_int {
+    let (j1, j2) = (code >> 8, code & 0xFF);
+    let (s1, s2) = if j1 & 1 != 0 {
+        (
+            j1 / 2 + if j1 < 0x5F { 0x71 } else { 0xB1 },
+            j2 + if j2 >= 0x60 { 0x20 } else { 0x1F },
+        )
+    } else {
+        (j1 / 2 + if j1 < 0x5F { 0x70 } else { 0xB0 }, j2 + 0x7E)
+    };
+    (s1 << 8) | s2
+}
+
+/// Return the element ID of the vector VECTOR, or None if there is no
+/// such element.
+fn vector_elt(vector: LispObject, id: EmacsInt) -> Option<LispObject> {
+    if id < 0 {
+        return None;
+    }
+    vector
+        .as_vector()
+        .and_then(|v| v.as_slice().get(id as usize).cloned())
+}
+
+/// Return the size of VECTOR, or -1 if it is not a vector.
+fn vector_size(vector: LispObject) -> EmacsInt {
+    vector.as_vector().map_or(-1, |v| v.len() as EmacsInt)
+}
+
+/// Look up OP in the code conversion map with ID POINT.  Return None if
+/// there is no such map, or if the map has no value or nil for OP.
+///
+/// A map is either [STARTPOINT VAL1 VAL2 ...], which maps STARTPOINT to
+/// VAL1 and so on, or [t VAL STARTPOINT ENDPOINT], which maps the codes
+/// from STARTPOINT to ENDPOINT - 1 to VAL.
+fn map_lookup(point: EmacsInt, op: c_int) -> Option<LispObject> {
+    let map = vector_elt(unsafe { globals.Vcode_conversion_map_vector }, point)?;
+    let map = map.as_cons()?.cdr().as_vector()?;
+    let size = map.len() as EmacsInt;
+    if size <= 1 {
+        return None;
+    }
+    let op = EmacsInt::from(op);
+    let first = map.get(0);
+    let content = if let Some(start) = first.as_fixnum() {
+        if !(start <= op && op - start + 1 < size) {
+            return None;
+        }
+        map.get((op - start + 1) as usize)
+    } else if first.eq(Qt) {
+        if size != 4 {
+            return None;
+        }
+        match (map.get(2).as_fixnum(), map.get(3).as_fixnum()) {
+            (Some(start), Some(end)) if start <= op && op < end => map.get(1),
+            _ => return None,
+        }
+    } else {
+        return None;
+    };
+    if content.is_nil() {
+        None
+    } else {
+        Some(content)
+    }
+}
+
+/// Return the value of a cons VALUE in a map, which is (ATTRIB . VALUE)
+/// with two integers, or None if CONTENT is not such a cons.
+fn map_cons_value(content: LispObject) -> Option<c_int> {
+    let (attrib, value) = content.as_cons()?.as_tuple();
+    attrib.as_fixnum()?;
+    as_int(value)
+}
+
+struct Driver<'a> {
+    ccl: &'a mut ccl_program,
+    reg: [c_int; 8],
+    prog: *mut LispObject,
+    ic: c_int,
+    eof_ic: c_int,
+    stack_idx: c_int,
+    /// The address and the first word of the instruction being run.
+    this_ic: c_int,
+    code: c_int,
+    src: Option<&'a [c_int]>,
+    consumed: usize,
+    dst: Option<&'a mut [c_int]>,
+    produced: usize,
+    /// False if nothing may be written to DST.
+    can_write: bool,
+    charset_list: LispObject,
+    mapping_stack: Vec<(c_int, c_int)>,
+}
+
+impl<'a> Driver<'a> {
+    fn invalid<T>(&self) -> Result<T, Stop> {
+        Err(Stop::Error(CCL_STAT_INVALID_CMD as c_int))
+    }
+
+    /// Suspend the program with STATUS, so that the current I/O command
+    /// runs again when the program is resumed.
+    fn suspend<T>(&mut self, status: u32) -> Result<T, Stop> {
+        self.ic -= 1;
+        Err(Stop::Finish(status as c_int))
+    }
+
+    fn word(&self, ic: c_int) -> LispObject {
+        unsafe { *self.prog.offset(ic as isize) }
+    }
+
+    /// Return the integer at IC, which must be in the range LO to HI.
+    fn range_at(&self, ic: c_int, lo: EmacsInt, hi: EmacsInt) -> Result<EmacsInt, Stop> {
+        match self.word(ic).as_fixnum() {
+            Some(n) if lo <= n && n <= hi => Ok(n),
+            _ => self.invalid(),
+        }
+    }
+
+    fn int_at(&self, ic: c_int) -> Result<c_int, Stop> {
+        self.range_at(
+            ic,
+            EmacsInt::from(c_int::min_value()),
+            EmacsInt::from(c_int::max_value()),
+        )
+        .map(|n| n as c_int)
+    }
+
+    /// Return the integer at IC, and move IC past it.
+    fn next_int(&mut self) -> Result<c_int, Stop> {
+        let ic = self.ic;
+        self.ic += 1;
+        self.int_at(ic)
+    }
+
+    /// Return the code word at IC, and move IC past it.
+    fn next_code(&mut self) -> Result<c_int, Stop> {
+        let ic = self.ic;
+        self.ic += 1;
+        let code = self.range_at(ic, CCL_CODE_MIN, CCL_CODE_MAX)? as c_int;
+        self.code = code;
+        Ok(code)
+    }
+
+    /// Read the next character of the input.  At the end of the last
+    /// block of input, jump to the end-of-file code.
+    fn read_char(&mut self) -> Result<c_int, Stop> {
+        let src = match self.src {
+            Some(src) => src,
+            None => return self.invalid(),
+        };
+        if let Some(&c) = src.get(self.consumed) {
+            self.consumed += 1;
+            Ok(c)
+        } else if self.ccl.last_block() {
+            self.ic = self.ccl.eof_ic;
+            Err(Stop::Repeat)
+        } else {
+            self.suspend(CCL_STAT_SUSPEND_BY_SRC)
+        }
+    }
+
+    /// Read the next character of the input into register R, which is
+    /// set to -1 at the end of the input.
+    fn read_reg(&mut self, r: usize) -> Step {
+        match self.read_char() {
+            Ok(c) => {
+                self.reg[r] = c;
+                Ok(())
+            }
+            Err(Stop::Repeat) => {
+                self.reg[r] = -1;
+                Err(Stop::Repeat)
+            }
+            Err(stop) => Err(stop),
+        }
+    }
+
+    fn write_char(&mut self, c: c_int) -> Step {
+        if !self.can_write {
+            return self.invalid();
+        }
+        let produced = self.produced;
+        match self.dst.as_mut().and_then(|dst| dst.get_mut(produced)) {
+            Some(slot) => {
+                *slot = c;
+                self.produced += 1;
+                Ok(())
+            }
+            None => self.suspend(CCL_STAT_SUSPEND_BY_DST),
+        }
+    }
+
+    /// Write the string of LEN characters at IC.  Its characters are
+    /// either in the low 24 bits of one word each, if the first word has
+    /// bit 24 set, or packed three bytes to a word.
+    fn write_string(&mut self, len: c_int) -> Step {
+        if !self.can_write {
+            return self.invalid();
+        }
+        let room = self.dst.as_ref().map_or(0, |dst| dst.len()) - self.produced;
+        if len > 0 && len as usize > room {
+            return self.suspend(CCL_STAT_SUSPEND_BY_DST);
+        }
+        let (ic, wide) = (self.ic, self.int_at(self.ic)? & 0x100_0000 != 0);
+        for k in 0..len.max(0) {
+            let c = if wide {
+                self.int_at(ic + k)? & 0xFF_FFFF
+            } else {
+                (self.int_at(ic + k / 3)? >> ((2 - k % 3) * 8)) & 0xFF
+            };
+            if let Some(dst) = self.dst.as_mut() {
+                dst[self.produced] = c;
+            }
+            self.produced += 1;
+        }
+        Ok(())
+    }
+
+    /// Decode CODE by the charset with ID, or return it as is if ID is 0.
+    fn decode(&self, id: c_int, code: c_int) -> Result<c_int, Stop> {
+        if id == 0 {
+            Ok(code)
+        } else if id < 0 {
+            self.invalid()
+        } else {
+            Ok(unsafe { decode_char(charset_table.offset(id as isize), code as c_uint) })
+        }
+    }
+
+    /// Encode the character C by one of the charsets of the charset list
+    /// of the program, or of the current priority list, and put its
+    /// charset and code in registers ID and CODE.  The registers are left
+    /// alone if no charset has C.
+    fn encode(&mut self, c: c_int, id: usize, code: usize) {
+        let mut ncode: c_uint = 0;
+        let mut charset = unsafe { char_charset(c, self.charset_list, &mut ncode) };
+        if charset.is_null() && self.charset_list.is_not_nil() {
+            charset = unsafe { char_charset(c, Qnil, &mut ncode) };
+        }
+        if let Some(charset) = unsafe { charset.as_ref() } {
+            self.reg[id] = charset.id;
+            self.reg[code] = ncode as c_int;
+        }
+    }
+
+    /// Return the translation table with ID.
+    fn translation_table(&self, id: EmacsInt) -> Result<LispObject, Stop> {
+        match vector_elt(unsafe { globals.Vtranslation_table_vector }, id)
+            .and_then(|slot| slot.as_cons())
+        {
+            Some(slot) => Ok(slot.cdr()),
+            None => self.invalid(),
+        }
+    }
+
+    /// Look up KEY in the translation hash table with ID.
+    fn hash_lookup(&self, id: EmacsInt, key: c_int) -> Result<Option<LispObject>, Stop> {
+        let table = vector_elt(unsafe { globals.Vtranslation_hash_table_vector }, id)
+            .and_then(|slot| slot.as_cons())
+            .map(|slot| slot.cdr())
+            .filter(|table| table.is_hash_table())
+            .map(|table| table.as_hash_table_or_error());
+        let table = match table {
+            Some(table) => table,
+            None => return self.invalid(),
+        };
+        match table.lookup(LispObject::from(EmacsInt::from(key))) {
+            HashLookupResult::Found(idx) => Ok(Some(table.get_hash_value(idx))),
+            HashLookupResult::Missing(_) => Ok(None),
+        }
+    }
+
+    /// Go back to the outermost program after a failed call.
+    fn unwind_calls(&mut self) {
+        if self.stack_idx > 0 {
+            let frame = unsafe { CALL_STACK[0] };
+            self.prog = frame.prog;
+            self.ic = frame.ic;
+            self.eof_ic = frame.eof_ic;
+        }
+    }
+
+    /// Call the program PROG, returning to the instruction at RET_IC.
+    fn call(&mut self, prog: *mut LispObject, ret_ic: c_int) -> Step {
+        unsafe {
+            CALL_STACK[self.stack_idx as usize] = Frame {
+                prog: self.prog,
+                ic: ret_ic,
+                eof_ic: self.eof_ic,
+            }
+        };
+        self.stack_idx += 1;
+        self.prog = prog;
+        self.ic = CCL_HEADER_MAIN;
+        self.eof_ic = self.int_at(CCL_HEADER_EOF)?;
+        Ok(())
+    }
+
+    /// Call the CCL program SYMBOL for a map instruction, returning to
+    /// the instruction at RET_IC.
+    fn call_for_map(&mut self, symbol: LispObject, ret_ic: c_int) -> Step {
+        let mut called: ccl_program = unsafe { std::mem::zeroed() };
+        if self.stack_idx as usize >= MAX_CALL_DEPTH
+            || !unsafe { setup_ccl_program(&mut called, symbol) }
+        {
+            self.unwind_calls();
+            return self.invalid();
+        }
+        self.call(called.prog, ret_ic)?;
+        Err(Stop::Repeat)
+    }
+
+    /// Run the instructions of the program until it stops.
+    fn run(&mut self) -> Stop {
+        loop {
+            let quit = unsafe { globals.Vquit_flag.is_not_nil() && globals.Vinhibit_quit.is_nil() };
+            if quit {
+                // Don't signal `quit' here, but stop as if all the input
+                // was processed, leaving `quit-flag' to be handled later.
+                if let Some(src) = self.src {
+                    self.consumed = src.len();
+                }
+                return Stop::Error(CCL_STAT_QUIT as c_int);
+            }
+            match self.step() {
+                Ok(()) | Err(Stop::Repeat) => {}
+                Err(stop) => return stop,
+            }
+        }
+    }
+
+    fn step(&mut self) -> Step {
+        self.this_ic = self.ic;
+        let code = self.next_code()?;
+        let mut field1 = code >> 8;
+        let mut rrr = ((code & 0xFF) >> 5) as usize;
+        let reg_rrr = (field1 & 7) as usize;
+        let reg_rrr2 = ((field1 >> 3) & 7) as usize;
+
+        // The operands of an expression and where to jump after it.
+        let (op, i, j, jump_address);
+        match code & 0x1F {
+            CCL_SET_REGISTER => {
+                self.reg[rrr] = self.reg[reg_rrr];
+                return Ok(());
+            }
+            CCL_SET_SHORT_CONST => {
+                self.reg[rrr] = field1;
+                return Ok(());
+            }
+            CCL_SET_CONST => {
+                self.reg[rrr] = self.next_int()?;
+                return Ok(());
+            }
+            CCL_SET_ARRAY => {
+                let (i, j) = (self.reg[reg_rrr], field1 >> 3);
+                if 0 <= i && i < j {
+                    self.reg[rrr] = self.int_at(self.ic + i)?;
+                }
+                self.ic += j;
+                return Ok(());
+            }
+            CCL_JUMP => {
+                self.ic += field1;
+                return Ok(());
+            }
+            CCL_JUMP_COND => {
+                if self.reg[rrr] == 0 {
+                    self.ic += field1;
+                }
+                return Ok(());
+            }
+            CCL_WRITE_REGISTER_JUMP => {
+                self.write_char(self.reg[rrr])?;
+                self.ic += field1;
+                return Ok(());
+            }
+            CCL_WRITE_REGISTER_READ_JUMP => {
+                self.write_char(self.reg[rrr])?;
+                self.ic += 1;
+                self.read_reg(rrr)?;
+                self.ic += field1 - 1;
+                return Ok(());
+            }
+            CCL_WRITE_CONST_JUMP => {
+                let c = self.int_at(self.ic)?;
+                self.write_char(c)?;
+                self.ic += field1;
+                return Ok(());
+            }
+            CCL_WRITE_CONST_READ_JUMP => {
+                let c = self.int_at(self.ic)?;
+                self.write_char(c)?;
+                self.ic += 1;
+                self.read_reg(rrr)?;
+                self.ic += field1 - 1;
+                return Ok(());
+            }
+            CCL_WRITE_STRING_JUMP => {
+                let len = self.next_int()?;
+                self.write_string(len)?;
+                self.ic += field1 - 1;
+                return Ok(());
+            }
+            CCL_WRITE_ARRAY_READ_JUMP => {
+                let (i, j) = (self.reg[rrr], self.int_at(self.ic)?);
+                if 0 <= i && i < j {
+                    let c = self.int_at(self.ic + 1 + i)?;
+                    self.write_char(c)?;
+                }
+                self.ic += j + 2;
+                self.read_reg(rrr)?;
+                self.ic += field1 - (j + 2);
+                return Ok(());
+            }
+            CCL_READ_JUMP => {
+                self.read_reg(rrr)?;
+                self.ic += field1;
+                return Ok(());
+            }
+            CCL_READ_BRANCH | CCL_BRANCH => {
+                if code & 0x1F == CCL_READ_BRANCH {
+                    self.read_reg(rrr)?;
+                }
+                let r = self.reg[rrr];
+                let offset = if 0 <= r && r < field1 { r } else { field1 };
+                self.ic += self.int_at(self.ic + offset)?;
+                return Ok(());
+            }
+            CCL_READ_REGISTER => loop {
+                self.read_reg(rrr)?;
+                if field1 == 0 {
+                    return Ok(());
+                }
+                let code = self.next_code()?;
+                field1 = code >> 8;
+                rrr = ((code & 0xFF) >> 5) as usize;
+            },
+            CCL_WRITE_EXPR_CONST => {
+                rrr = 7;
+                i = self.reg[reg_rrr];
+                j = self.int_at(self.ic)?;
+                op = field1 >> 6;
+                jump_address = self.ic + 1;
+            }
+            CCL_WRITE_REGISTER => loop {
+                self.write_char(self.reg[rrr])?;
+                if field1 == 0 {
+                    return Ok(());
+                }
+                let code = self.next_code()?;
+                field1 = code >> 8;
+                rrr = ((code & 0xFF) >> 5) as usize;
+            },
+            CCL_WRITE_EXPR_REGISTER => {
+                rrr = 7;
+                i = self.reg[reg_rrr];
+                j = self.reg[reg_rrr2];
+                op = field1 >> 6;
+                jump_address = self.ic;
+            }
+            CCL_CALL => {
+                // If FFF is nonzero, the ID of the program is in the next
+                // word.
+                let prog_id = if rrr != 0 { self.next_int()? } else { field1 };
+                let prog = vector_elt(unsafe { Vccl_program_table }, EmacsInt::from(prog_id))
+                    .and_then(|slot| slot.as_vector())
+                    .filter(|slot| slot.len() > 1)
+                    .and_then(|slot| slot.get(1).as_vector());
+                match prog {
+                    Some(mut prog) if (self.stack_idx as usize) < MAX_CALL_DEPTH => {
+                        let ic = self.ic;
+                        self.call(prog.as_mut_slice().as_mut_ptr(), ic)?;
+                    }
+                    _ => {
+                        self.unwind_calls();
+                        return self.invalid();
+                    }
+                }
+                return Ok(());
+            }
+            CCL_WRITE_CONST_STRING => {
+                if rrr == 0 {
+                    self.write_char(field1)?;
+                } else {
+                    self.write_string(field1)?;
+                    self.ic += (field1 + 2) / 3;
+                }
+                return Ok(());
+            }
+            CCL_WRITE_ARRAY => {
+                let i = self.reg[rrr];
+                if 0 <= i && i < field1 {
+                    let c = self.int_at(self.ic + i)?;
+                    self.write_char(c)?;
+                }
+                self.ic += field1;
+                return Ok(());
+            }
+            CCL_END => {
+                if self.stack_idx > 0 {
+                    self.stack_idx -= 1;
+                    let frame = unsafe { CALL_STACK[self.stack_idx as usize] };
+                    self.prog = frame.prog;
+                    self.ic = frame.ic;
+                    self.eof_ic = frame.eof_ic;
+                    return Ok(());
+                }
+                if let Some(src) = self.src {
+                    self.consumed = src.len();
+                }
+                // Leave IC at this command, so that the program doesn't
+                // do anything more if it is run again.
+                self.ic -= 1;
+                return Err(Stop::Finish(CCL_STAT_SUCCESS as c_int));
+            }
+            CCL_EXPR_SELF_CONST | CCL_EXPR_SELF_REG => {
+                let operand = if code & 0x1F == CCL_EXPR_SELF_CONST {
+                    self.next_int()?
+                } else {
+                    self.reg[reg_rrr]
+                };
+                return self.expr_self(field1 >> 6, rrr, operand);
+            }
+            CCL_SET_EXPR_CONST => {
+                i = self.reg[reg_rrr];
+                j = self.next_int()?;
+                op = field1 >> 6;
+                jump_address = self.ic;
+            }
+            CCL_SET_EXPR_REG => {
+                i = self.reg[reg_rrr];
+                j = self.reg[reg_rrr2];
+                op = field1 >> 6;
+                jump_address = self.ic;
+            }
+            CCL_READ_JUMP_COND_EXPR_CONST | CCL_JUMP_COND_EXPR_CONST => {
+                if code & 0x1F == CCL_READ_JUMP_COND_EXPR_CONST {
+                    self.read_reg(rrr)?;
+                }
+                i = self.reg[rrr];
+                jump_address = self.ic + field1;
+                op = self.next_int()?;
+                j = self.next_int()?;
+                rrr = 7;
+            }
+            CCL_READ_JUMP_COND_EXPR_REG | CCL_JUMP_COND_EXPR_REG => {
+                if code & 0x1F == CCL_READ_JUMP_COND_EXPR_REG {
+                    self.read_reg(rrr)?;
+                }
+                i = self.reg[rrr];
+                jump_address = self.ic + field1;
+                op = self.next_int()?;
+                let ic = self.ic;
+                self.ic += 1;
+                j = self.reg[self.range_at(ic, 0, 7)? as usize];
+                rrr = 7;
+            }
+            CCL_EXTENSION => return self.extension(field1 >> 6, rrr, reg_rrr, reg_rrr2),
+            _ => return self.invalid(),
+        }
+
+        self.set_expr(op, rrr, i, j)?;
+        let command = code & 0x1F;
+        if command == CCL_WRITE_EXPR_CONST || command == CCL_WRITE_EXPR_REGISTER {
+            self.write_char(self.reg[rrr])?;
+            self.ic = jump_address;
+        } else if self.reg[rrr] == 0 {
+            self.ic = jump_address;
+        }
+        Ok(())
+    }
+
+    /// Run REG[R] OP= OPERAND.
+    fn expr_self(&mut self, op: c_int, r: usize, operand: c_int) -> Step {
+        let value = self.reg[r];
+        self.reg[r] = match op {
+            CCL_PLUS => value.wrapping_add(operand),
+            CCL_MINUS => value.wrapping_sub(operand),
+            CCL_MUL => value.wrapping_mul(operand),
+            CCL_DIV | CCL_MOD | CCL_DIVMOD if operand == 0 => return self.invalid(),
+            CCL_DIV => value.wrapping_div(operand),
+            CCL_MOD => value.wrapping_rem(operand),
+            CCL_AND => value & operand,
+            CCL_OR => value | operand,
+            CCL_XOR => value ^ operand,
+            CCL_LSH => value.wrapping_shl(operand as u32),
+            CCL_RSH => value.wrapping_shr(operand as u32),
+            CCL_LSH8 => value.wrapping_shl(8) | operand,
+            CCL_RSH8 => {
+                self.reg[7] = value & 0xFF;
+                value >> 8
+            }
+            CCL_DIVMOD => {
+                self.reg[7] = value.wrapping_rem(operand);
+                value.wrapping_div(operand)
+            }
+            CCL_LS => (value < operand) as c_int,
+            CCL_GT => (value > operand) as c_int,
+            CCL_EQ => (value == operand) as c_int,
+            CCL_LE => (value <= operand) as c_int,
+            CCL_GE => (value >= operand) as c_int,
+            CCL_NE => (value != operand) as c_int,
+            _ => return self.invalid(),
+        };
+        Ok(())
+    }
+
+    /// Run REG[R] = I OP J.
+    fn set_expr(&mut self, op: c_int, r: usize, i: c_int, j: c_int) -> Step {
+        self.reg[r] = match op {
+            CCL_PLUS => i.wrapping_add(j),
+            CCL_MINUS => i.wrapping_sub(j),
+            CCL_MUL => i.wrapping_mul(j),
+            CCL_DIV | CCL_MOD | CCL_DIVMOD if j == 0 => return self.invalid(),
+            CCL_DIV => i.wrapping_div(j),
+            CCL_MOD => i.wrapping_rem(j),
+            CCL_AND => i & j,
+            CCL_OR => i | j,
+            CCL_XOR => i ^ j,
+            CCL_LSH => i.wrapping_shl(j as u32),
+            CCL_RSH => i.wrapping_shr(j as u32),
+            CCL_LSH8 => i.wrapping_shl(8) | j,
+            CCL_RSH8 => {
+                self.reg[7] = i & 0xFF;
+                i >> 8
+            }
+            CCL_DIVMOD => {
+                self.reg[7] = i.wrapping_rem(j);
+                i.wrapping_div(j)
+            }
+            CCL_LS => (i < j) as c_int,
+            CCL_GT => (i > j) as c_int,
+            CCL_EQ => (i == j) as c_int,
+            CCL_LE => (i <= j) as c_int,
+            CCL_GE => (i >= j) as c_int,
+            CCL_NE => (i != j) as c_int,
+            CCL_DECODE_SJIS | CCL_ENCODE_SJIS => {
+                let code = i.wrapping_shl(8) | j;
+                let code = if op == CCL_DECODE_SJIS {
+                    sjis_to_jis(code)
+                } else {
+                    jis_to_sjis(code)
+                };
+                self.reg[7] = code & 0xFF;
+                code >> 8
+            }
+            _ => return self.invalid(),
+        };
+        Ok(())
+    }
+
+    /// Run the extended command EXCMD, with the registers RRR, RRR2 and
+    /// RRR3 (rrr, RRR and Rrr in the comments of ccl.c).
+    fn extension(&mut self, excmd: c_int, rrr: usize, rrr2: usize, rrr3: usize) -> Step {
+        match excmd {
+            CCL_READ_MULTIBYTE_CHAR2 => {
+                let c = self.read_char()?;
+                self.encode(c, rrr2, rrr);
+            }
+            CCL_WRITE_MULTIBYTE_CHAR2 => {
+                if !self.can_write {
+                    return self.invalid();
+                }
+                let c = self.decode(self.reg[rrr2], self.reg[rrr])?;
+                self.write_char(c)?;
+            }
+            CCL_TRANSLATE_CHARACTER | CCL_TRANSLATE_CHARACTER_CONST_TBL => {
+                let id = if excmd == CCL_TRANSLATE_CHARACTER {
+                    EmacsInt::from(self.reg[rrr3])
+                } else {
+                    let size = vector_size(unsafe { globals.Vtranslation_table_vector });
+                    let ic = self.ic;
+                    self.ic += 1;
+                    self.range_at(ic, 0, size)?
+                };
+                let c = self.decode(self.reg[rrr2], self.reg[rrr])?;
+                let c = unsafe { translate_char(self.translation_table(id)?, c) };
+                self.encode(c, rrr2, rrr);
+            }
+            CCL_LOOKUP_INT_CONST_TBL | CCL_LOOKUP_CHAR_CONST_TBL => {
+                let size = vector_size(unsafe { globals.Vtranslation_hash_table_vector });
+                let ic = self.ic;
+                self.ic += 1;
+                let id = self.range_at(ic, 0, size)?;
+                if excmd == CCL_LOOKUP_INT_CONST_TBL {
+                    // Look up a character by the integer in REG[RRR2].
+                    match self.hash_lookup(id, self.reg[rrr2])? {
+                        Some(value) => {
+                            if !value.is_character() {
+                                return self.invalid();
+                            }
+                            self.reg[rrr2] = unsafe { charset_unicode };
+                            self.reg[rrr] = value.as_fixnum_or_error() as c_int;
+                            self.reg[7] = 1;
+                        }
+                        None => self.reg[7] = 0,
+                    }
+                } else {
+                    // Look up an integer by a character.
+                    let c = self.decode(self.reg[rrr2], self.reg[rrr])?;
+                    match self.hash_lookup(id, c)? {
+                        Some(value) => {
+                            self.reg[rrr2] = match as_int(value) {
+                                Some(n) => n,
+                                None => return self.invalid(),
+                            };
+                            self.reg[7] = 1;
+                        }
+                        None => self.reg[7] = 0,
+                    }
+                }
+            }
+            CCL_ITERATE_MULTIPLE_MAP => return self.iterate_multiple_map(rrr, rrr2),
+            CCL_MAP_MULTIPLE => return self.map_multiple(rrr, rrr2),
+            CCL_MAP_SINGLE => return self.map_single(rrr, rrr2),
+            _ => return self.invalid(),
+        }
+        Ok(())
+    }
+
+    /// Map REG[RRR] by the first of the maps that follow, starting with
+    /// the REG[RRR2]th, that has a value for it, and set REG[RRR2] to the
+    /// index of that map, or to -1 if there is none.
+    fn iterate_multiple_map(&mut self, rrr: usize, rrr2: usize) -> Step {
+        let j = self.next_int()?;
+        let fin_ic = self.ic + j;
+        let op = self.reg[rrr];
+        let mut i = self.reg[rrr2];
+        if !(j > i && j >= 0) {
+            self.reg[rrr2] = -1;
+            self.ic = fin_ic;
+            return Ok(());
+        }
+        self.ic += i;
+
+        while i < j {
+            if !unsafe { globals.Vcode_conversion_map_vector }.is_vector() {
+                i += 1;
+                continue;
+            }
+            let point = EmacsInt::from(self.next_int()?);
+            let content = match map_lookup(point, op) {
+                Some(content) => content,
+                None => {
+                    i += 1;
+                    continue;
+                }
+            };
+            if let Some(value) = as_int(content) {
+                self.reg[rrr2] = i;
+                self.reg[rrr] = value;
+                break;
+            } else if content.eq(Qt) || content.eq(Qlambda) {
+                self.reg[rrr2] = i;
+                break;
+            } else if content.is_cons() {
+                if let Some(value) = map_cons_value(content) {
+                    self.reg[rrr2] = i;
+                    self.reg[rrr] = value;
+                    break;
+                }
+            } else if content.is_symbol() {
+                return self.call_for_map(content, fin_ic);
+            } else {
+                return self.invalid();
+            }
+            i += 1;
+        }
+        if i == j {
+            self.reg[rrr2] = -1;
+        }
+        self.ic = fin_ic;
+        Ok(())
+    }
+
+    fn pop_mapping(&mut self) -> Result<(c_int, c_int), Stop> {
+        match self.mapping_stack.pop() {
+            Some(entry) => Ok(entry),
+            None => self.invalid(),
+        }
+    }
+
+    fn push_mapping(&mut self, rest_length: c_int, orig: c_int) -> Step {
+        if self.mapping_stack.len() >= MAX_MAP_SET_LEVEL {
+            return self.invalid();
+        }
+        self.mapping_stack.push((rest_length, orig));
+        Ok(())
+    }
+
+    /// Map REG[RRR] by the nested sets of maps that follow, starting with
+    /// the REG[RRR2]th map or separator.  See the comment of
+    /// `CCL_MapMultiple' in ccl.c for the details.
+    fn map_multiple(&mut self, rrr: usize, rrr2: usize) -> Step {
+        let current_ic = self.this_ic;
+
+        // Don't let a program called for a map run `CCL_MapMultiple'.
+        unsafe {
+            if STACK_IDX_OF_MAP_MULTIPLE > 0 {
+                if STACK_IDX_OF_MAP_MULTIPLE <= self.stack_idx {
+                    STACK_IDX_OF_MAP_MULTIPLE = 0;
+                    self.mapping_stack.clear();
+                    return self.invalid();
+                }
+            } else {
+                self.mapping_stack.clear();
+            }
+            STACK_IDX_OF_MAP_MULTIPLE = 0;
+        }
+
+        // The number of maps and separators.
+        let mut rest = self.next_int()?;
+        let fin_ic = self.ic + rest;
+        let mut op = self.reg[rrr];
+        let mut i = self.reg[rrr2];
+        if !(rest > i && i >= 0) {
+            self.ic = fin_ic;
+            self.reg[rrr2] = -1;
+            self.mapping_stack.clear();
+            return Ok(());
+        }
+        self.ic += i;
+        rest -= i;
+
+        if self.mapping_stack.len() <= 1 {
+            self.mapping_stack.clear();
+            self.mapping_stack.push((0, op));
+            self.reg[rrr2] = -1;
+        } else {
+            // Recover after calling another CCL program, whose result is
+            // in OP.
+            let (_, orig_op) = self.pop_mapping()?;
+            let (saved_rest, saved) = self.pop_mapping()?;
+            rest = saved_rest;
+            self.reg[rrr] = saved;
+            match op {
+                // Regard it as nil.
+                -1 => {
+                    op = orig_op;
+                    i += 1;
+                    self.ic += 1;
+                    rest -= 1;
+                }
+                // Regard it as t.
+                -2 => {
+                    op = self.reg[rrr];
+                    i += 1;
+                    self.ic += 1;
+                    rest -= 1;
+                }
+                // Regard it as lambda.
+                -3 => {
+                    op = orig_op;
+                    i += rest;
+                    self.ic += rest;
+                    rest = 0;
+                }
+                // Regard it as a normal mapping.
+                _ => {
+                    i += rest;
+                    self.ic += rest;
+                    let (saved_rest, saved) = self.pop_mapping()?;
+                    rest = saved_rest;
+                    self.reg[rrr] = saved;
+                }
+            }
+        }
+        let map_vector_size = vector_size(unsafe { globals.Vcode_conversion_map_vector });
+        if map_vector_size < 0 {
+            return self.invalid();
+        }
+
+        loop {
+            while rest > 0 {
+                let step = self.map_multiple_1(
+                    (rrr, rrr2),
+                    (&mut i, &mut rest, &mut op),
+                    map_vector_size,
+                    current_ic,
+                )?;
+                if let MapStep::EndOfSet = step {
+                    break;
+                }
+                i += 1;
+                self.ic += 1;
+                rest -= 1;
+            }
+            if self.mapping_stack.len() <= 1 {
+                break;
+            }
+            let (saved_rest, saved) = self.pop_mapping()?;
+            rest = saved_rest;
+            self.reg[rrr] = saved;
+            i += rest;
+            self.ic += rest;
+            let (saved_rest, saved) = self.pop_mapping()?;
+            rest = saved_rest;
+            self.reg[rrr] = saved;
+        }
+
+        self.ic = fin_ic;
+        self.reg[rrr] = op;
+        Ok(())
+    }
+
+    /// Look at the map or separator at IC for `map_multiple'.
+    fn map_multiple_1(
+        &mut self,
+        (rrr, rrr2): (usize, usize),
+        (i, rest, op): (&mut c_int, &mut c_int, &mut c_int),
+        map_vector_size: EmacsInt,
+        current_ic: c_int,
+    ) -> Result<MapStep, Stop> {
+        let point = EmacsInt::from(self.int_at(self.ic)?);
+        if point < 0 {
+            // A separator starts a new map set; +1 is for the separator
+            // itself.
+            let length = (-point + 1) as c_int;
+            let saved = self.reg[rrr];
+            self.push_mapping(*rest - length, saved)?;
+            *rest = length;
+            self.reg[rrr] = *op;
+            return Ok(MapStep::Next);
+        }
+        if point >= map_vector_size {
+            return Ok(MapStep::Next);
+        }
+        let content = match map_lookup(point, *op) {
+            Some(content) => content,
+            None => return Ok(MapStep::Next),
+        };
+
+        self.reg[rrr2] = *i;
+        let value = if content.is_cons() {
+            match map_cons_value(content) {
+                Some(value) => Some(value),
+                None => return Ok(MapStep::Next),
+            }
+        } else {
+            as_int(content)
+        };
+        if let Some(value) = value {
+            // Skip the rest of this map set.
+            *op = value;
+            *i += *rest - 1;
+            self.ic += *rest - 1;
+            let (saved_rest, saved) = self.pop_mapping()?;
+            *rest = saved_rest + 1;
+            self.reg[rrr] = saved;
+        } else if content.eq(Qt) {
+            *op = self.reg[rrr];
+        } else if content.eq(Qlambda) {
+            *i += *rest;
+            self.ic += *rest;
+            return Ok(MapStep::EndOfSet);
+        } else if content.is_symbol() {
+            let saved = self.reg[rrr];
+            self.push_mapping(*rest, saved)?;
+            self.push_mapping(*rest, *op)?;
+            unsafe { STACK_IDX_OF_MAP_MULTIPLE = self.stack_idx + 1 };
+            self.call_for_map(content, current_ic)?;
+        } else {
+            return self.invalid();
+        }
+        Ok(MapStep::Next)
+    }
+
+    /// Map REG[RRR] by the map that follows, and set REG[RRR2] to -1 if
+    /// it has no value for it.
+    fn map_single(&mut self, rrr: usize, rrr2: usize) -> Step {
+        let id = EmacsInt::from(self.next_int()?);
+        let op = self.reg[rrr];
+        let map = vector_elt(unsafe { globals.Vcode_conversion_map_vector }, id)
+            .and_then(|map| map.as_cons())
+            .and_then(|map| map.cdr().as_vector());
+        let map = match map {
+            Some(map) => map,
+            None => {
+                self.reg[rrr2] = -1;
+                return Ok(());
+            }
+        };
+        let op = EmacsInt::from(op);
+        let start = match map.as_slice().first().and_then(|start| start.as_fixnum()) {
+            Some(start) if start <= op && op - start + 1 < map.len() as EmacsInt => start,
+            _ => {
+                self.reg[rrr2] = -1;
+                return Ok(());
+            }
+        };
+        let content = map.get((op - start + 1) as usize);
+        self.reg[rrr2] = 0;
+        if content.is_nil() {
+            self.reg[rrr2] = -1;
+        } else if let Some(value) = as_int(content) {
+            self.reg[rrr] = value;
+        } else if content.eq(Qt) {
+        } else if content.is_cons() {
+            if let Some(value) = map_cons_value(content) {
+                self.reg[rrr] = value;
+            }
+        } else if content.is_symbol() {
+            let ic = self.ic;
+            return self.call_for_map(content, ic);
+        } else {
+            self.reg[rrr2] = -1;
+        }
+        Ok(())
+    }
+
+    /// Write a message about the error STATUS to the output, if there is
+    /// room for it.
+    fn write_error(&mut self, status: c_int) {
+        let message = if status == CCL_STAT_INVALID_CMD as c_int {
+            format!(
+                "\nCCL: Invalid command {:x} (ccl_code = {:x}) at {}.",
+                self.code & 0x1F,
+                self.code as c_uint,
+                self.this_ic
+            )
+        } else if status == CCL_STAT_QUIT as c_int {
+            if self.ccl.quit_silently() {
+                String::new()
+            } else {
+                "\nCCL: Quitted.".to_string()
+            }
+        } else {
+            format!("\nCCL: Unknown error type ({})", status)
+        };
+        if !self.can_write {
+            self.produced = 0;
+        }
+        let produced = self.produced;
+        if let Some(dst) = self.dst.as_mut() {
+            if message.len() <= dst.len() - produced {
+                for (slot, &b) in dst[produced..].iter_mut().zip(message.as_bytes()) {
+                    *slot = c_int::from(b);
+                }
+                self.produced += message.len();
+            }
+        }
+    }
+}
+
+/// Run the CCL program CCL on the SRC_SIZE characters at SOURCE, writing
+/// at most DST_SIZE characters of output to DESTINATION.  The numbers of
+/// characters consumed and produced are stored in CCL, along with the
+/// registers and the state needed to resume the program.  If SOURCE or
+/// DESTINATION is null, only operations on registers are permitted.
+/// CHARSET_LIST is the list of charsets to try first when encoding
+/// characters.
+#[no_mangle]
+pub unsafe extern "C" fn ccl_driver(
+    ccl: *mut ccl_program,
+    source: *mut c_int,
+    destination: *mut c_int,
+    src_size: c_int,
+    dst_size: c_int,
+    charset_list: LispObject,
+) {
+    let ccl = &mut *ccl;
+    let src = if source.is_null() {
+        None
+    } else {
+        Some(std::slice::from_raw_parts(
+            source as *const c_int,
+            src_size.max(0) as usize,
+        ))
+    };
+    let dst = if destination.is_null() {
+        None
+    } else {
+        Some(std::slice::from_raw_parts_mut(
+            destination,
+            dst_size.max(0) as usize,
+        ))
+    };
+    // We can't produce anything without an output buffer.
+    let can_write = dst.is_some() && ccl.buf_magnification != 0;
+
+    let mut driver = Driver {
+        reg: ccl.reg,
+        prog: ccl.prog,
+        ic: ccl.ic,
+        eof_ic: ccl.eof_ic,
+        stack_idx: ccl.stack_idx,
+        ccl,
+        this_ic: 0,
+        code: 0,
+        src,
+        consumed: 0,
+        dst,
+        produced: 0,
+        can_write,
+        charset_list,
+        mapping_stack: Vec::with_capacity(MAX_MAP_SET_LEVEL),
+    };
+
+    match driver.run() {
+        Stop::Finish(status) => driver.ccl.status = status,
+        Stop::Error(status) => {
+            driver.ccl.status = status;
+            if driver.dst.is_some() {
+                driver.write_error(status);
+                if status == CCL_STAT_INVALID_CMD as c_int {
+                    // Signal that everything was consumed.
+                    driver.consumed = src_size.max(0) as usize;
+                }
+            }
+        }
+        Stop::Repeat => unreachable!(),
+    }
+
+    // Nothing is produced without an output buffer, except an error
+    // message.
+    let Driver {
+        reg,
+        prog,
+        ic,
+        stack_idx,
+        consumed,
+        produced,
+        ccl,
+        ..
+    } = driver;
+    ccl.reg = reg;
+    ccl.ic = ic;
+    ccl.stack_idx = stack_idx;
+    ccl.prog = prog;
+    ccl.consumed = consumed as c_int;
+    ccl.produced = produced as c_int;
+}
+
+#[test]
+fn test_sjis_roundtrip() {
+    // The first and last characters of JIS X 0208.
+    assert_eq!(sjis_to_jis(0x8140), 0x2121);
+    assert_eq!(jis_to_sjis(0x2121), 0x8140);
+    assert_eq!(sjis_to_jis(0xEAA4), 0x7426);
+    assert_eq!(jis_to_sjis(0x7426), 0xEAA4);
+    for &code in &[0x2422, 0x3021, 0x4B6A, 0x5F7E] {
+        assert_eq!(sjis_to_jis(jis_to_sjis(code)), code);
+    }
+}
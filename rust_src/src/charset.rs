@@ -1,11 +1,25 @@
 //! Basic character set support.
+//!
+//! Charsets of the `map' method get their code-points from charset map
+//! files in `charset-map-path'.  Those files are read here, by mapping
+//! them into memory, and the entries of each are kept so that the
+//! decoder and encoder of a charset, which charset.c builds on first
+//! use, don't parse the file again.
+
+use std::collections::HashMap;
+use std::ptr;
+use std::slice;
+use std::sync::Mutex;
+
+use libc::{c_char, c_int, c_uint, c_void};
 
 use remacs_macros::lisp_fn;
 
 use crate::{
     hashtable::HashLookupResult,
     lisp::{defsubr, LispObject},
-    remacs_sys::Vcharset_hash_table,
+    multibyte::MAX_CHAR,
+    remacs_sys::{charset_map_entry, report_file_error, Vcharset_hash_table},
 };
 
 impl LispObject {
@@ -26,4 +40,234 @@ pub fn charsetp(object: LispObject) -> bool {
     object.is_charset()
 }
 
+/// The parsed charset map files, by file name and range of code-points.
+type CharsetMapCache = HashMap<(Vec<u8>, c_uint, c_uint), Box<[charset_map_entry]>>;
+
+lazy_static! {
+    static ref CHARSET_MAPS: Mutex<CharsetMapCache> = Mutex::new(HashMap::new());
+}
+
+/// A reader of the hexadecimal numbers of a charset map file.
+struct HexReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> HexReader<'a> {
+    /// Return the next byte, or -1 at the end of the data.
+    fn getc(&mut self) -> c_int {
+        match self.data.get(self.pos) {
+            Some(&b) => {
+                self.pos += 1;
+                c_int::from(b)
+            }
+            None => -1,
+        }
+    }
+
+    /// Read a hexadecimal number preceded by "0x", skipping comments
+    /// that start with `#'.  LOOKAHEAD is the byte already read, or -1.
+    /// Return the number and the byte after it, which is -1 at the end
+    /// of the data, and set *OVERFLOW if the number doesn't fit.
+    fn read_hex(&mut self, lookahead: c_int, overflow: &mut bool) -> (c_uint, c_int) {
+        let mut c = if lookahead < 0 {
+            self.getc()
+        } else {
+            lookahead
+        };
+        loop {
+            if c == c_int::from(b'#') {
+                while c >= 0 && c != c_int::from(b'\n') {
+                    c = self.getc();
+                }
+            } else if c == c_int::from(b'0') {
+                c = self.getc();
+                if c < 0 || c == c_int::from(b'x') {
+                    break;
+                }
+            }
+            if c < 0 {
+                break;
+            }
+            c = self.getc();
+        }
+
+        let mut n: c_uint = 0;
+        if c >= 0 {
+            loop {
+                c = self.getc();
+                let digit = match (c as u8 as char).to_digit(16) {
+                    Some(digit) if c >= 0 => digit,
+                    _ => break,
+                };
+                *overflow |= n > c_uint::max_value() >> 4;
+                n = n.wrapping_shl(4) + digit;
+            }
+        }
+        (n, c)
+    }
+}
+
+/// Parse DATA, the contents of a charset map file, into its entries.
+/// Each line has the form "0xAAAA 0xCCCC", where 0xAAAA is a code-point
+/// and 0xCCCC is its character, or "0xAAAA-0xBBBB 0xCCCC" for a range of
+/// code-points starting with the character 0xCCCC.  Entries that are
+/// out of the range MIN-CODE to MAX-CODE, or whose numbers overflow, are
+/// left out.
+fn parse_charset_map(data: &[u8], min_code: c_uint, max_code: c_uint) -> Vec<charset_map_entry> {
+    let mut reader = HexReader { data, pos: 0 };
+    let mut entries = Vec::new();
+    let mut ch = -1;
+    loop {
+        let mut overflow = false;
+        let (from, next) = reader.read_hex(ch, &mut overflow);
+        if next < 0 {
+            break;
+        }
+        let to = if next == c_int::from(b'-') {
+            let (to, next) = reader.read_hex(-1, &mut overflow);
+            if next < 0 {
+                break;
+            }
+            ch = next;
+            to
+        } else {
+            ch = -1;
+            from
+        };
+        let (c, next) = reader.read_hex(ch, &mut overflow);
+        if next < 0 {
+            break;
+        }
+        ch = next;
+
+        if overflow || from < min_code || to > max_code || from > to || c > MAX_CHAR {
+            continue;
+        }
+        entries.push(charset_map_entry {
+            from,
+            to,
+            c: c as c_int,
+        });
+    }
+    entries
+}
+
+/// Call F with the contents of the file open on FD, which is mapped
+/// into memory if it can be.  Return None if the file can't be read.
+fn with_file_contents<T>(fd: c_int, f: impl FnOnce(&[u8]) -> T) -> Option<T> {
+    #[cfg(unix)]
+    unsafe {
+        let mut st: libc::stat = std::mem::zeroed();
+        if libc::fstat(fd, &mut st) == 0 && st.st_size > 0 {
+            let len = st.st_size as usize;
+            let addr = libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                fd,
+                0,
+            );
+            if addr != libc::MAP_FAILED {
+                let value = f(slice::from_raw_parts(addr as *const u8, len));
+                libc::munmap(addr, len);
+                return Some(value);
+            }
+        }
+    }
+
+    let mut data = Vec::new();
+    let mut buf = [0u8; 0x4000];
+    loop {
+        let n = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut c_void, buf.len()) };
+        if n < 0 {
+            return None;
+        }
+        if n == 0 {
+            break;
+        }
+        data.extend_from_slice(&buf[..n as usize]);
+    }
+    Some(f(&data))
+}
+
+/// Return the entries of the charset map FILE, open on FD, whose
+/// code-points are in the range MIN_CODE to MAX_CODE, and store their
+/// number in *N_ENTRIES.  The entries are kept until the next call of
+/// `clear_charset_map_cache', so that FILE is read only once.
+#[no_mangle]
+pub extern "C" fn read_charset_map(
+    file: LispObject,
+    fd: c_int,
+    min_code: c_uint,
+    max_code: c_uint,
+    n_entries: *mut c_int,
+) -> *const charset_map_entry {
+    let key = (
+        file.as_string_or_error().as_slice().to_vec(),
+        min_code,
+        max_code,
+    );
+    if let Some(entries) = CHARSET_MAPS.lock().unwrap().get(&key) {
+        unsafe { *n_entries = entries.len() as c_int };
+        return entries.as_ptr();
+    }
+
+    let entries = with_file_contents(fd, |data| parse_charset_map(data, min_code, max_code));
+    if entries.is_none() {
+        unsafe { report_file_error("Reading charset map\0".as_ptr() as *const c_char, file) };
+    }
+    let mut cache = CHARSET_MAPS.lock().unwrap();
+    let entries = cache
+        .entry(key)
+        .or_insert_with(|| entries.unwrap_or_default().into_boxed_slice());
+    unsafe { *n_entries = entries.len() as c_int };
+    entries.as_ptr()
+}
+
+/// Forget the entries of the charset map files read so far.
+#[no_mangle]
+pub extern "C" fn clear_charset_map_cache() {
+    CHARSET_MAPS.lock().unwrap().clear();
+}
+
 include!(concat!(env!("OUT_DIR"), "/charset_exports.rs"));
+
+#[cfg(test)]
+fn parse_test_map(text: &str, min_code: c_uint, max_code: c_uint) -> Vec<(c_uint, c_uint, c_int)> {
+    parse_charset_map(text.as_bytes(), min_code, max_code)
+        .into_iter()
+        .map(|e| (e.from, e.to, e.c))
+        .collect()
+}
+
+#[test]
+fn test_parse_charset_map() {
+    let text = "# A comment with 0x20 in it\n\
+                0x20 0x0020\n\
+                0x21-0x23 0x3000 # Trailing comment\n\
+                0xA1A1 0x3001\n";
+    assert_eq!(
+        parse_test_map(text, 0, 0xFFFF),
+        vec![
+            (0x20, 0x20, 0x20),
+            (0x21, 0x23, 0x3000),
+            (0xA1A1, 0xA1A1, 0x3001)
+        ]
+    );
+    // Entries out of the code space are left out.
+    assert_eq!(parse_test_map(text, 0x21, 0xFF), vec![(0x21, 0x23, 0x3000)]);
+}
+
+#[test]
+fn test_parse_invalid_charset_map() {
+    assert!(parse_test_map("", 0, 0xFFFF).is_empty());
+    // Numbers that overflow and characters out of range are ignored.
+    assert_eq!(
+        parse_test_map("0x123456789 0x41\n0x42 0x7FFFFFF\n0x43 0x43\n", 0, 0xFFFF),
+        vec![(0x43, 0x43, 0x43)]
+    );
+    // So is the last line if it is not terminated.
+    assert!(parse_test_map("0x41 0x41", 0, 0xFFFF).is_empty());
+}
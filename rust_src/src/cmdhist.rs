@@ -0,0 +1,270 @@
+//! Command usage.
+//!
+//! `execute-extended-command' records each command it runs here, and
+//! the M-x completions are sorted by how often and how recently each
+//! command was used, as amx and smex do.  The counts are kept in a hash
+//! map, so that sorting the thousands of commands that match an empty
+//! input only takes a lookup for each of them.  If `command-usage-store'
+//! is non-nil, the counts are also saved in the persistent store, under
+//! keys made of `KEY_PREFIX' and the command name.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    lisp::{defsubr, LispObject},
+    persistent,
+    remacs_sys::{globals, EmacsDouble, EmacsInt},
+    remacs_sys::{Qnil, Qt},
+};
+
+/// The prefix of the keys of the persistent store that hold the usage
+/// of commands.
+const KEY_PREFIX: &[u8] = b"command-usage:";
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Usage {
+    count: u64,
+    /// When the command was last used, in seconds since the epoch.
+    last: u64,
+}
+
+impl Usage {
+    /// The rank of the command at time NOW: the number of times it was
+    /// used, weighted by how recently it was last used.
+    fn score(self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last);
+        let weight = if age < HOUR {
+            4.0
+        } else if age < DAY {
+            2.0
+        } else if age < WEEK {
+            1.0
+        } else {
+            0.5
+        };
+        self.count as f64 * weight
+    }
+
+    fn to_lisp(self) -> LispObject {
+        list!(
+            LispObject::from(self.count as EmacsInt),
+            LispObject::from(self.last as EmacsInt)
+        )
+    }
+
+    fn from_lisp(value: LispObject) -> Option<Usage> {
+        let natnum = |n: LispObject| n.as_fixnum().filter(|&n| n >= 0);
+        let cons = value.as_cons()?;
+        let count = natnum(cons.car())?;
+        let last = natnum(cons.cdr().as_cons()?.car())?;
+        Some(Usage {
+            count: count as u64,
+            last: last as u64,
+        })
+    }
+}
+
+struct Usages {
+    commands: HashMap<Vec<u8>, Usage>,
+    /// The store whose counts were added to `commands', if any.
+    loaded: Option<PathBuf>,
+}
+
+lazy_static! {
+    static ref USAGES: Mutex<Usages> = Mutex::new(Usages {
+        commands: HashMap::new(),
+        loaded: None,
+    });
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn command_name(command: LispObject) -> Vec<u8> {
+    command.symbol_or_string_as_string().as_slice().to_vec()
+}
+
+/// The file of the store selected by `command-usage-store', or None.
+fn store() -> Option<PathBuf> {
+    let store = unsafe { globals.Vcommand_usage_store };
+    if store.is_nil() {
+        None
+    } else if store.eq(Qt) {
+        Some(persistent::store_path(Qnil))
+    } else {
+        Some(persistent::store_path(store))
+    }
+}
+
+/// Call F with the usage of the commands, after adding the counts of the
+/// store selected by `command-usage-store' the first time it is used.
+/// F must not exit non-locally, as the lock would stay held.
+fn with_usages<T, F>(f: F) -> T
+where
+    F: FnOnce(&mut HashMap<Vec<u8>, Usage>) -> T,
+{
+    let path = store();
+    let stale = path.is_some() && USAGES.lock().unwrap().loaded != path;
+    // The store is read before taking the lock, since reading it can
+    // signal an error.
+    let saved = match path {
+        Some(ref path) if stale => persistent::entries_with_prefix(path, KEY_PREFIX),
+        _ => Vec::new(),
+    };
+    let saved: Vec<(Vec<u8>, Usage)> = saved
+        .into_iter()
+        .filter_map(|(name, value)| Usage::from_lisp(value).map(|usage| (name, usage)))
+        .collect();
+
+    let mut usages = USAGES.lock().unwrap();
+    if stale {
+        for (name, usage) in saved {
+            usages.commands.entry(name).or_insert(usage);
+        }
+        usages.loaded = path;
+    }
+    f(&mut usages.commands)
+}
+
+/// Record that COMMAND was run.
+/// COMMAND is a symbol or the name of a command.  Return the number of
+/// times it was recorded, including this one.
+#[lisp_fn]
+pub fn command_usage_record(command: LispObject) -> EmacsInt {
+    let name = command_name(command);
+    let usage = with_usages(|commands| {
+        let usage = commands.entry(name.clone()).or_default();
+        usage.count += 1;
+        usage.last = now();
+        *usage
+    });
+    if let Some(path) = store() {
+        let mut key = KEY_PREFIX.to_vec();
+        key.extend_from_slice(&name);
+        persistent::put(&path, key, usage.to_lisp());
+    }
+    usage.count as EmacsInt
+}
+
+/// Return COMMANDS sorted by how often and how recently they were used.
+/// COMMANDS is a list of symbols or command names.  The commands used
+/// the most come first; a command used in the last hour counts twice as
+/// much as one used earlier in the day, and so on for the week and
+/// before.  Commands with the same rank, like those that were never
+/// used, are sorted by name.  This is used to sort the completions of
+/// `execute-extended-command'.
+#[lisp_fn]
+pub fn command_usage_sort(commands: LispObject) -> LispObject {
+    let now = now();
+    let names: Vec<(LispObject, Vec<u8>)> = commands
+        .iter_cars()
+        .map(|command| (command, command_name(command)))
+        .collect();
+    let mut scored: Vec<(LispObject, Vec<u8>, f64)> = with_usages(|usages| {
+        names
+            .into_iter()
+            .map(|(command, name)| {
+                let score = usages.get(&name).map_or(0.0, |usage| usage.score(now));
+                (command, name, score)
+            })
+            .collect()
+    });
+    scored.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap().then_with(|| a.1.cmp(&b.1)));
+    let sorted: Vec<LispObject> = scored.into_iter().map(|(command, _, _)| command).collect();
+    LispObject::from(sorted)
+}
+
+/// Return the recorded usage of commands.
+/// The value is a list of elements (COMMAND COUNT LAST SCORE), where
+/// COMMAND is the name of a command that was run COUNT times, last at
+/// time LAST, in seconds since the epoch, and SCORE is its rank for
+/// `command-usage-sort'.  The elements are sorted by SCORE, the highest
+/// first.
+#[lisp_fn]
+pub fn command_usage_statistics() -> LispObject {
+    let now = now();
+    let mut usages: Vec<(Vec<u8>, Usage)> = with_usages(|commands| {
+        commands
+            .iter()
+            .map(|(name, usage)| (name.clone(), *usage))
+            .collect()
+    });
+    usages.sort_by(|a, b| {
+        b.1.score(now)
+            .partial_cmp(&a.1.score(now))
+            .unwrap()
+            .then_with(|| a.0.cmp(&b.0))
+    });
+    let list: Vec<LispObject> = usages
+        .into_iter()
+        .map(|(name, usage)| {
+            list!(
+                LispObject::from(String::from_utf8_lossy(&name).as_ref()),
+                LispObject::from(usage.count as EmacsInt),
+                LispObject::from(usage.last as EmacsInt),
+                LispObject::from_float(usage.score(now) as EmacsDouble)
+            )
+        })
+        .collect();
+    LispObject::from(list)
+}
+
+/// Forget the recorded usage of commands.
+/// If `command-usage-store' is non-nil, the usage is also removed from
+/// the persistent store.
+#[lisp_fn]
+pub fn command_usage_reset() {
+    let names: Vec<Vec<u8>> =
+        with_usages(|commands| commands.drain().map(|(name, _)| name).collect());
+    if let Some(path) = store() {
+        for name in names {
+            let mut key = KEY_PREFIX.to_vec();
+            key.extend_from_slice(&name);
+            persistent::delete(&path, &key);
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn syms_of_cmdhist() {
+    /// Where to save the usage of commands recorded for M-x.
+    /// nil means to only keep it for this session, t means to save it in
+    /// the file of `persistent-store-default-file', and a string is the
+    /// file name of a persistent store to save it in.
+    defvar_lisp!(Vcommand_usage_store, "command-usage-store", Qnil);
+}
+
+include!(concat!(env!("OUT_DIR"), "/cmdhist_exports.rs"));
+
+#[test]
+fn test_usage_score() {
+    let now = 100 * WEEK;
+    let recent = Usage {
+        count: 3,
+        last: now - 10,
+    };
+    let old = Usage {
+        count: 10,
+        last: now - 2 * WEEK,
+    };
+    let yesterday = Usage {
+        count: 3,
+        last: now - DAY - 1,
+    };
+    assert_eq!(recent.score(now), 12.0);
+    assert_eq!(old.score(now), 5.0);
+    assert_eq!(yesterday.score(now), 3.0);
+    assert!(recent.score(now) > old.score(now));
+}
@@ -0,0 +1,70 @@
+//! Automatic composition support.
+//!
+//! Redisplay calls `auto-composition-function' for each run of
+//! characters that `composition-function-table' says may be composed.
+//! The glyph strings it returns are cached by their text, in the
+//! `composition-get-gstring' cache, but when it fails to compose the
+//! text, as it does when the font cannot shape it, nothing used to be
+//! remembered: the function was called again for the same characters
+//! every time the line was laid out.  This remembers those failures by
+//! text content as well.
+
+use crate::{
+    hashtable::{clrhash, gethash, hash_table_count, puthash, LispHashTableRef},
+    lisp::LispObject,
+    obarray::intern,
+    remacs_sys::{globals, EmacsInt},
+    remacs_sys::{QCtest, Qequal, Qnil, Qt},
+};
+
+/// Texts that automatic composition failed to compose, keyed by a list
+/// (AUTO-FUNC FUNC FONT-OBJECT CHAR ...) of `auto-composition-function',
+/// the composition function, and the glyph string header.
+declare_GC_protected_static!(autocmp_failures, Qnil);
+
+/// The number of failures remembered at most.  The cache starts over
+/// when it grows beyond that, as text that was composed once may well
+/// not be seen again.
+const MAX_AUTOCMP_FAILURES: EmacsInt = 10_000;
+
+fn failures_table() -> LispHashTableRef {
+    unsafe {
+        if autocmp_failures.is_nil() {
+            autocmp_failures = call!(intern("make-hash-table").as_lisp_obj(), QCtest, Qequal);
+        }
+        autocmp_failures.as_hash_table_or_error()
+    }
+}
+
+/// Return the key in the failure cache of the characters of glyph
+/// string header HEADER, composed by FUNC.
+fn failure_key(func: LispObject, header: LispObject) -> LispObject {
+    let mut key = vec![unsafe { globals.Vauto_composition_function }, func];
+    key.extend(header.as_vector_or_error().iter());
+    LispObject::from(key)
+}
+
+/// Return true if FUNC failed to compose the characters of glyph string
+/// header HEADER before, with the same font.
+#[no_mangle]
+pub extern "C" fn autocmp_failed_p(func: LispObject, header: LispObject) -> bool {
+    gethash(failure_key(func, header), failures_table(), Qnil).is_not_nil()
+}
+
+/// Remember that FUNC failed to compose the characters of glyph string
+/// header HEADER.
+#[no_mangle]
+pub extern "C" fn autocmp_note_failure(func: LispObject, header: LispObject) {
+    let table = failures_table();
+    if hash_table_count(table) >= MAX_AUTOCMP_FAILURES {
+        clrhash(table);
+    }
+    puthash(failure_key(func, header), Qt, table);
+}
+
+/// Forget the failures of automatic composition, for when the fonts or
+/// the composition functions change.
+#[no_mangle]
+pub extern "C" fn autocmp_clear_failures() {
+    unsafe { autocmp_failures = Qnil };
+}
@@ -0,0 +1,380 @@
+//! Crash reports.
+//!
+//! When Emacs dies from a fatal signal, a failed assertion or a Rust
+//! panic, a report is written to a file so that it can be attached to a
+//! bug report.  The report has the reason for the crash, the Lisp
+//! functions that were running, the native backtrace and the last keys
+//! that were typed.
+//!
+//! A fatal signal leaves the process in whatever state it was in, with
+//! the malloc lock possibly held and the Lisp heap possibly corrupt, so
+//! the report is written with async-signal-safe calls only.  Its text
+//! is built in fixed buffers and written with write(2), the native
+//! backtrace comes from backtrace_symbols_fd, and the Lisp data in it
+//! is read from memory without calling Lisp.
+
+use std::panic;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use libc::{c_char, c_int, c_void};
+
+use crate::{
+    lisp::LispObject,
+    remacs_sys::{backtrace_function, backtrace_next, backtrace_p, backtrace_top},
+    remacs_sys::{globals, recent_key, Qnil},
+};
+
+extern "C" {
+    fn backtrace(buffer: *mut *mut c_void, size: c_int) -> c_int;
+    fn backtrace_symbols_fd(buffer: *const *mut c_void, size: c_int, fd: c_int);
+}
+
+/// Whether a report was written already.  A crash while writing the
+/// report, or the abort that follows a panic, must not overwrite it.
+static REPORT_WRITTEN: AtomicBool = AtomicBool::new(false);
+
+/// The number of Lisp frames included in a report.
+const MAX_LISP_FRAMES: usize = 100;
+
+/// The number of native frames included in a report.
+const MAX_NATIVE_FRAMES: usize = 100;
+
+/// The size of the buffers in which the report is put together.
+const BUFFER_SIZE: usize = 4096;
+
+/// A byte string in a fixed buffer, which can be built without
+/// allocating.  Bytes that don't fit are dropped.
+struct FixedBuf {
+    bytes: [u8; BUFFER_SIZE],
+    len: usize,
+}
+
+/// The directory in which reports are written when
+/// `crash-report-directory' is nil.  It is found at startup, since
+/// finding it allocates.
+static mut TEMP_DIR: FixedBuf = FixedBuf {
+    bytes: [0; BUFFER_SIZE],
+    len: 0,
+};
+
+impl FixedBuf {
+    fn new() -> Self {
+        FixedBuf {
+            bytes: [0; BUFFER_SIZE],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8]) -> &mut Self {
+        let n = bytes.len().min(BUFFER_SIZE - self.len);
+        self.bytes[self.len..self.len + n].copy_from_slice(&bytes[..n]);
+        self.len += n;
+        self
+    }
+
+    fn push_number(&mut self, n: i64) -> &mut Self {
+        if n < 0 {
+            self.push(b"-");
+        }
+        let mut n = n.wrapping_abs() as u64;
+        let mut digits = [0; 20];
+        let mut i = digits.len();
+        loop {
+            i -= 1;
+            digits[i] = b'0' + (n % 10) as u8;
+            n /= 10;
+            if n == 0 {
+                break;
+            }
+        }
+        self.push(&digits[i..])
+    }
+
+    /// Add the text of OBJECT, if it is a string.  Return false if it
+    /// isn't.
+    fn push_string(&mut self, object: LispObject) -> bool {
+        match object.as_string() {
+            Some(string) => {
+                self.push(string.as_slice());
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    /// Return the contents as a C string, or None if they fill the
+    /// buffer and there is no room for the terminating null.
+    fn as_c_str(&mut self) -> Option<*const c_char> {
+        if self.len < BUFFER_SIZE {
+            self.bytes[self.len] = 0;
+            Some(self.bytes.as_ptr() as *const c_char)
+        } else {
+            None
+        }
+    }
+
+    /// Write the contents to FD and empty the buffer.
+    fn flush(&mut self, fd: c_int) {
+        write_all(fd, self.as_bytes());
+        self.len = 0;
+    }
+}
+
+/// Write all of BYTES to FD, giving up at the first error.
+fn write_all(fd: c_int, mut bytes: &[u8]) {
+    while !bytes.is_empty() {
+        let n = unsafe { libc::write(fd, bytes.as_ptr() as *const c_void, bytes.len()) };
+        if n <= 0 {
+            return;
+        }
+        bytes = &bytes[n as usize..];
+    }
+}
+
+/// Add a description of FUNCTION for a backtrace to BUF: the name of a
+/// symbol, or the kind of the object.
+fn describe_function(buf: &mut FixedBuf, function: LispObject) {
+    if let Some(symbol) = function.as_symbol() {
+        if !buf.push_string(symbol.symbol_name()) {
+            buf.push(b"?");
+        }
+    } else if function.is_cons() {
+        buf.push(b"(lambda)");
+    } else if function.is_byte_code_function() {
+        buf.push(b"#[byte-code]");
+    } else {
+        buf.push(b"#<unknown>");
+    }
+}
+
+/// Add a description of the input event KEY to BUF, roughly as
+/// `key-description' would give it.  Events with modifiers and other
+/// non-ASCII characters are given by their code.
+fn describe_key(buf: &mut FixedBuf, key: LispObject) {
+    if let Some(c) = key.as_fixnum() {
+        match c {
+            0x09 => buf.push(b"TAB"),
+            0x0d => buf.push(b"RET"),
+            0x1b => buf.push(b"ESC"),
+            0x20 => buf.push(b"SPC"),
+            0x7f => buf.push(b"DEL"),
+            0x01..=0x1a => buf.push(b"C-").push(&[b'a' + c as u8 - 1]),
+            0x21..=0x7e => buf.push(&[c as u8]),
+            _ => buf.push(b"#").push_number(c),
+        };
+    } else if let Some(symbol) = key.as_symbol() {
+        if !buf.push_string(symbol.symbol_name()) {
+            buf.push(b"?");
+        }
+    } else {
+        buf.push(b"?");
+    }
+}
+
+/// Write the functions of the Lisp backtrace to FD, the innermost
+/// first, one per line.
+fn write_lisp_backtrace(fd: c_int) {
+    let mut line = FixedBuf::new();
+    let mut frames = 0;
+    unsafe {
+        let mut pdl = backtrace_top();
+        while backtrace_p(pdl) && frames < MAX_LISP_FRAMES {
+            line.push(b"  ");
+            describe_function(&mut line, backtrace_function(pdl));
+            line.push(b"\n").flush(fd);
+            frames += 1;
+            pdl = backtrace_next(pdl);
+        }
+    }
+    if frames == 0 {
+        write_all(fd, b"  (none)\n");
+    }
+}
+
+/// Write the last keys that were typed to FD, as by `view-lossage'.
+/// The commands that `recent-keys' can include are left out.
+fn write_recent_keys(fd: c_int) {
+    let mut line = FixedBuf::new();
+    let mut n = 0;
+    loop {
+        let key = unsafe { recent_key(n) };
+        if key.is_nil() {
+            break;
+        }
+        if !key.is_cons() {
+            describe_key(&mut line, key);
+            line.push(b" ");
+        }
+        n += 1;
+    }
+    line.push(b"\n").flush(fd);
+}
+
+/// Return the name of the fatal signal SIG, if it is a common one.
+/// strsignal is not async-signal-safe.
+fn signal_name(sig: c_int) -> Option<&'static str> {
+    match sig {
+        libc::SIGABRT => Some("Aborted"),
+        libc::SIGBUS => Some("Bus error"),
+        libc::SIGFPE => Some("Floating point exception"),
+        libc::SIGILL => Some("Illegal instruction"),
+        libc::SIGSEGV => Some("Segmentation fault"),
+        _ => None,
+    }
+}
+
+/// Write a crash report giving REASON, unless one was written already.
+fn write_report(reason: &FixedBuf) {
+    if REPORT_WRITTEN.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    let time = i64::from(unsafe { libc::time(ptr::null_mut()) });
+    let mut path = FixedBuf::new();
+    if !path.push_string(unsafe { globals.Vcrash_report_directory }) {
+        path.push(unsafe { TEMP_DIR.as_bytes() });
+    }
+    if let Some(directory) = path.as_c_str() {
+        // This fails if the directory exists, which is fine.
+        unsafe { libc::mkdir(directory, 0o700) };
+    }
+    path.push(b"/emacs-crash-")
+        .push_number(time)
+        .push(b"-")
+        .push_number(i64::from(unsafe { libc::getpid() }))
+        .push(b".txt");
+    let fd = match path.as_c_str() {
+        Some(name) => unsafe {
+            libc::open(
+                name,
+                libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL | libc::O_CLOEXEC,
+                0o600,
+            )
+        },
+        None => -1,
+    };
+    if fd < 0 {
+        write_all(libc::STDERR_FILENO, b"\nCould not write crash report to ");
+        write_all(libc::STDERR_FILENO, path.as_bytes());
+        write_all(libc::STDERR_FILENO, b"\n");
+        return;
+    }
+
+    let mut text = FixedBuf::new();
+    text.push(b"Emacs crash report\nVersion: ");
+    if !text.push_string(unsafe { globals.Vemacs_version }) {
+        text.push(b"unknown");
+    }
+    text.push(b"\nTime: ")
+        .push_number(time)
+        .push(b"\nReason: ")
+        .push(reason.as_bytes())
+        .push(b"\n\nLisp backtrace:\n")
+        .flush(fd);
+    write_lisp_backtrace(fd);
+
+    write_all(fd, b"\nNative backtrace:\n");
+    let mut frames = [ptr::null_mut(); MAX_NATIVE_FRAMES];
+    unsafe {
+        let count = backtrace(frames.as_mut_ptr(), MAX_NATIVE_FRAMES as c_int);
+        backtrace_symbols_fd(frames.as_ptr(), count, fd);
+    }
+
+    write_all(fd, b"\nRecent keys:\n");
+    write_recent_keys(fd);
+    unsafe { libc::close(fd) };
+
+    write_all(libc::STDERR_FILENO, b"\nCrash report written to ");
+    write_all(libc::STDERR_FILENO, path.as_bytes());
+    write_all(libc::STDERR_FILENO, b"\n");
+}
+
+/// Write a crash report for the fatal signal SIG.  This is called by
+/// `terminate_due_to_signal', so it covers `emacs_abort' and failed
+/// assertions too.
+#[no_mangle]
+pub extern "C" fn write_crash_report(sig: c_int) {
+    let mut reason = FixedBuf::new();
+    reason.push(b"Fatal signal ").push_number(i64::from(sig));
+    if let Some(name) = signal_name(sig) {
+        reason.push(b" (").push(name.as_bytes()).push(b")");
+    }
+    write_report(&reason);
+}
+
+/// Arrange for Rust panics to write a crash report, and get ready to
+/// write one from a signal handler.  Called at every startup, since
+/// none of this is dumped.
+#[no_mangle]
+pub extern "C" fn init_crash() {
+    // The first call to backtrace can load libgcc, which allocates.
+    let mut frame = [ptr::null_mut(); 1];
+    unsafe { backtrace(frame.as_mut_ptr(), 1) };
+    let temp_dir = std::env::temp_dir();
+    unsafe { TEMP_DIR.push(temp_dir.to_string_lossy().as_bytes()) };
+
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "Box<Any>".to_string());
+        let mut reason = FixedBuf::new();
+        reason.push(b"Rust panic");
+        if let Some(location) = info.location() {
+            reason
+                .push(b" at ")
+                .push(location.file().as_bytes())
+                .push(b":")
+                .push_number(i64::from(location.line()));
+        }
+        reason.push(b": ").push(message.as_bytes());
+        write_report(&reason);
+    }));
+}
+
+#[no_mangle]
+pub extern "C" fn syms_of_crash() {
+    /// Directory in which crash reports are written.
+    /// When Emacs crashes, it writes a report that can be attached to a
+    /// bug report, with the Lisp and native backtraces and the recent
+    /// keys.  If this is nil, the system's temporary directory is used.
+    defvar_lisp!(Vcrash_report_directory, "crash-report-directory", Qnil);
+}
+
+#[test]
+fn test_fixed_buf() {
+    let mut buf = FixedBuf::new();
+    buf.push(b"signal ")
+        .push_number(11)
+        .push(b" ")
+        .push_number(0)
+        .push(b" ")
+        .push_number(-42);
+    assert_eq!(buf.as_bytes(), b"signal 11 0 -42");
+    assert!(buf.as_c_str().is_some());
+
+    // What doesn't fit is dropped.
+    let mut buf = FixedBuf::new();
+    buf.push(&[b'x'; BUFFER_SIZE - 2]).push_number(12345);
+    assert_eq!(&buf.as_bytes()[BUFFER_SIZE - 2..], b"12");
+    assert!(buf.as_c_str().is_none());
+}
+
+#[test]
+fn test_describe_key() {
+    let mut buf = FixedBuf::new();
+    for &c in &[0x61, 0x01, 0x0d, 0x20, 0x7f, 0x08_000_061] {
+        describe_key(&mut buf, LispObject::from(c as crate::remacs_sys::EmacsInt));
+        buf.push(b" ");
+    }
+    assert_eq!(buf.as_bytes(), &b"a C-a RET SPC DEL #134217825 "[..]);
+}
@@ -31,6 +31,8 @@ use crate::{
     threads::ThreadState,
 };
 
+pub mod blake3;
+
 #[derive(Clone, Copy)]
 enum HashAlg {
     MD5,
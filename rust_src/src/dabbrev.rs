@@ -0,0 +1,151 @@
+//! Completion candidates for dynamic abbreviations.
+//!
+//! `dabbrev-expand' and `hippie-expand' look for words that start with
+//! the text before point by searching the buffers with regular
+//! expressions, which gets slow when there are many large buffers.
+//! This looks the words up in the word indexes of the buffers instead;
+//! see wordindex.rs.
+
+use std::collections::HashSet;
+
+use libc::ptrdiff_t;
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    buffers::LispBufferRef,
+    lisp::{defsubr, LispObject},
+    multibyte::LispStringRef,
+    remacs_sys::Qt,
+    remacs_sys::{EmacsInt, Vbuffer_alist},
+    threads::ThreadState,
+    wordindex::{
+        make_word_string, with_word_table, word_around, word_chars, word_text, WordSyntax,
+        WordTable,
+    },
+};
+
+/// Return the words of CANDIDATES that occur in the current buffer, as
+/// found in TABLE for a buffer that ends at Z, the ones nearest to
+/// point first.  The word at point is the one being completed, and does
+/// not count.  Ties go to the word before point, as `dabbrev-expand'
+/// searches backward first.
+fn nearest_first(
+    table: &WordTable,
+    z: ptrdiff_t,
+    candidates: Vec<&[u8]>,
+    multibyte: bool,
+) -> Vec<Vec<u8>> {
+    let point = ThreadState::current_buffer().pt;
+    let (here, _) = word_around(point);
+    let mut ranked: Vec<(ptrdiff_t, bool, Vec<u8>)> = candidates
+        .into_iter()
+        .filter_map(|word| {
+            let len = word_chars(word, multibyte) as ptrdiff_t;
+            table
+                .positions(word, z)
+                .into_iter()
+                .filter(|&pos| pos != here)
+                .map(|pos| {
+                    if pos < here {
+                        (point - (pos + len), false)
+                    } else {
+                        (pos - point, true)
+                    }
+                })
+                .min()
+                .map(|(distance, after)| (distance, after, word.to_vec()))
+        })
+        .collect();
+    ranked.sort();
+    ranked.into_iter().map(|(_, _, word)| word).collect()
+}
+
+/// Return completions of PREFIX found in the current buffer and BUFFERS.
+/// The words of the current buffer come first, the ones nearest to point
+/// first, the way `dabbrev-expand' finds them.  Words found only in
+/// BUFFERS follow, buffer by buffer.  A word is a run of word and
+/// symbol constituent characters, as with the default
+/// `dabbrev-abbrev-char-regexp'.  PREFIX itself is never a completion,
+/// and neither is the word at point in the current buffer, which is
+/// taken to be the one being completed.
+///
+/// BUFFERS is a list of buffers to look in after the current buffer, or
+/// t to look in all other live buffers whose names do not start with a
+/// space, most recently selected first.  LIMIT, if non-nil, is the
+/// largest number of completions to return.  If IGNORE-CASE is non-nil,
+/// case is ignored when comparing words with PREFIX.
+///
+/// The words are looked up in the word indexes of the buffers, as
+/// `buffer-word-index' does, so that buffers do not need to be searched
+/// again.
+#[lisp_fn(min = "1")]
+pub fn dabbrev_completions(
+    prefix: LispStringRef,
+    buffers: LispObject,
+    limit: Option<EmacsInt>,
+    ignore_case: bool,
+) -> LispObject {
+    let limit = limit.map_or(usize::max_value(), |n| n.max(0) as usize);
+
+    let current = ThreadState::current_buffer();
+    let others: Vec<LispBufferRef> = if buffers.eq(Qt) {
+        unsafe { Vbuffer_alist }
+            .iter_cars_safe()
+            .filter_map(|elt| elt.as_cons().and_then(|c| c.cdr().as_buffer()))
+            .filter(|b| {
+                b.is_live()
+                    && b.name()
+                        .as_string()
+                        .map_or(false, |name| name.as_slice().first() != Some(&b' '))
+            })
+            .collect()
+    } else {
+        buffers
+            .iter_cars_safe()
+            .filter_map(|b| b.as_buffer())
+            .filter(|b| b.is_live())
+            .collect()
+    };
+
+    let mut seen: HashSet<Vec<u8>> = HashSet::new();
+    let mut completions = Vec::new();
+    for (i, buffer) in Some(current).into_iter().chain(others).enumerate() {
+        if completions.len() >= limit {
+            break;
+        }
+        if i > 0 && buffer == current {
+            continue;
+        }
+        let multibyte = buffer.multibyte_characters_enabled();
+        let prefix = match word_text(prefix, multibyte) {
+            Some(prefix) => prefix,
+            None => continue,
+        };
+        let prefix_chars = word_chars(&prefix, multibyte);
+
+        let words = with_word_table(buffer, WordSyntax::Symbol, |table, z| {
+            let candidates: Vec<&[u8]> = table
+                .matching(&prefix, ignore_case, multibyte)
+                .into_iter()
+                .filter(|word| word_chars(word, multibyte) > prefix_chars)
+                .collect();
+            if i == 0 {
+                nearest_first(table, z, candidates, multibyte)
+            } else {
+                candidates.into_iter().map(<[u8]>::to_vec).collect()
+            }
+        });
+        for word in words {
+            if completions.len() >= limit {
+                break;
+            }
+            if seen.insert(word.clone()) {
+                completions.push(make_word_string(&word, multibyte));
+            }
+        }
+    }
+    LispObject::from(completions)
+}
+
+include!(concat!(env!("OUT_DIR"), "/dabbrev_exports.rs"));
@@ -6,6 +6,7 @@ use libc::c_int;
 use remacs_macros::lisp_fn;
 
 use crate::{
+    alloc::write_barrier,
     buffers::per_buffer_idx,
     frames::selected_frame,
     keymap::get_keymap,
@@ -222,6 +223,7 @@ pub fn aset(array: LispObject, idx: EmacsInt, newelt: LispObject) -> LispObject
         if let Some(mut v) = vl.as_vector() {
             unsafe { CHECK_IMPURE(array, array.get_untaggedptr()) };
             v.set_checked(idx as usize, newelt);
+            write_barrier(array, newelt);
         } else if let Some(mut bv) = vl.as_bool_vector() {
             bv.set_checked(idx as usize, newelt.is_not_nil());
         } else if let Some(_tbl) = vl.as_char_table() {
@@ -229,6 +231,7 @@ pub fn aset(array: LispObject, idx: EmacsInt, newelt: LispObject) -> LispObject
             unsafe { CHAR_TABLE_SET(array, idx as c_int, newelt) };
         } else if let Some(mut record) = vl.as_record() {
             record.set_checked(idx as usize, newelt);
+            write_barrier(array, newelt);
         } else {
             unreachable!();
         }
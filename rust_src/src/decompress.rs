@@ -1,6 +1,8 @@
-//! Interface to zlib.
+//! Interface to zlib, Brotli and Zstandard.
+use std::cell::RefCell;
 use std::cmp::min;
-use std::io::prelude::Read;
+use std::io::{self, prelude::Read, Write};
+use std::rc::Rc;
 use std::slice;
 
 use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
@@ -36,12 +38,88 @@ fn create_buffer_decoder<'a>(buffer: &'a [u8]) -> Box<Read + 'a> {
     }
 }
 
+/// Where a `ContentDecoder' writes what it decodes.
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A decoder for data compressed with content codings, as named in the
+/// Content-Encoding header of HTTP, that decodes the data as it
+/// arrives.
+pub struct ContentDecoder {
+    input: Box<Write>,
+    output: Output,
+}
+
+// Decoders are only used from Lisp, which runs in one thread at a time.
+unsafe impl Send for ContentDecoder {}
+
+impl ContentDecoder {
+    /// Return a decoder that undoes CODINGS, given in the order they
+    /// were applied, or None if one of them is not supported.
+    pub fn new(codings: &[&[u8]]) -> Option<Self> {
+        let output = Output::default();
+        let mut input: Box<Write> = Box::new(output.clone());
+        for coding in codings {
+            input = match coding.to_ascii_lowercase().as_slice() {
+                b"identity" => input,
+                b"gzip" | b"x-gzip" => Box::new(flate2::write::GzDecoder::new(input)),
+                b"deflate" => Box::new(flate2::write::ZlibDecoder::new(input)),
+                b"br" => Box::new(brotli_decompressor::DecompressorWriter::new(input, 4096)),
+                b"zstd" => Box::new(zstd::stream::write::Decoder::new(input).ok()?),
+                _ => return None,
+            };
+        }
+        Some(Self { input, output })
+    }
+
+    /// Decode INPUT, which follows the data decoded so far, and append
+    /// the result to OUT.
+    pub fn decode(&mut self, input: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+        self.input.write_all(input)?;
+        self.input.flush()?;
+        out.append(&mut self.output.0.borrow_mut());
+        Ok(())
+    }
+}
+
 /// Decompress a gzip- or zlib-compressed region.
 /// Replace the text in the region by the decompressed data.
 /// On failure, return nil and leave the data in place.
 /// This function can be called only in unibyte buffers.
 #[lisp_fn]
-pub fn zlib_decompress_region(mut start: LispObject, mut end: LispObject) -> bool {
+pub fn zlib_decompress_region(start: LispObject, end: LispObject) -> bool {
+    decompress_region(start, end, create_buffer_decoder)
+}
+
+/// Decompress a Brotli-compressed region.
+/// Replace the text in the region by the decompressed data.
+/// On failure, return nil and leave the data in place.
+/// This function can be called only in unibyte buffers.
+#[lisp_fn]
+pub fn brotli_decompress_region(start: LispObject, end: LispObject) -> bool {
+    decompress_region(start, end, |buffer| {
+        Box::new(brotli_decompressor::Decompressor::new(buffer, 4096))
+    })
+}
+
+/// Replace the text between START and END in the current buffer by
+/// what the decoder that CREATE_DECODER makes for it reads.
+fn decompress_region(
+    mut start: LispObject,
+    mut end: LispObject,
+    create_decoder: impl for<'a> Fn(&'a [u8]) -> Box<Read + 'a>,
+) -> bool {
     unsafe { validate_region(&mut start, &mut end) };
 
     let mut current_buffer = ThreadState::current_buffer();
@@ -79,7 +157,7 @@ pub fn zlib_decompress_region(mut start: LispObject, mut end: LispObject) -> boo
     };
 
     // The decompressor
-    let mut decoder = create_buffer_decoder(compressed_buffer);
+    let mut decoder = create_decoder(compressed_buffer);
 
     let mut decompressed_bytes: isize = 0;
 
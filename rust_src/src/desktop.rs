@@ -0,0 +1,182 @@
+//! Compact binary storage for desktop files.
+//!
+//! A desktop with hundreds of buffers used to spend most of its time
+//! printing and reading back the buffer section of the desktop file.
+//! The functions here encode that data in the binary format of
+//! `serialize' instead, and collect the buffer state that is not easily reachable
+//! from Lisp (mark ring, narrowing and text properties) natively.
+
+use libc::ptrdiff_t;
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    buffers::{LispBufferRef, BEG},
+    eval::unbind_to,
+    lisp::{defsubr, LispObject},
+    lists::memq,
+    multibyte::LispStringRef,
+    obarray::intern,
+    remacs_sys::{
+        record_unwind_current_buffer, set_buffer_internal, EmacsInt, Fnext_property_change,
+        Fsymbol_value, Ftext_properties_at,
+    },
+    remacs_sys::{Qnil, Qt},
+    serialize::{decode_object, encode_object, encode_object_with_properties},
+    shell::make_lisp_string,
+    threads::c_specpdl_index,
+};
+
+/// Every encoded string starts with this, followed by a format version.
+const MAGIC: &[u8] = b"\0EMD";
+const FORMAT_VERSION: u8 = 2;
+
+/// Encode OBJECT in the binary desktop format, and return a unibyte string.
+/// OBJECT can contain numbers, strings, symbols, lists and vectors.
+/// Markers are replaced by their position, and other objects, which
+/// can't be read back in a later session, are replaced by nil.  The
+/// text properties of strings are dropped unless KEEP-PROPERTIES is
+/// non-nil.
+///
+/// See `desktop-binary-decode' for the inverse operation.
+#[lisp_fn(min = "1")]
+pub fn desktop_binary_encode(object: LispObject, keep_properties: bool) -> LispObject {
+    let mut out = MAGIC.to_vec();
+    out.push(FORMAT_VERSION);
+    if keep_properties {
+        encode_object_with_properties(object, &mut out);
+    } else {
+        encode_object(object, &mut out);
+    }
+    make_lisp_string(&out, false)
+}
+
+/// Decode STRING, made by `desktop-binary-encode', into a Lisp object.
+/// Signal an error if STRING is not in the binary desktop format.
+#[lisp_fn]
+pub fn desktop_binary_decode(string: LispStringRef) -> LispObject {
+    // Copy the data, since decoding allocates Lisp objects and the
+    // garbage collector may relocate the contents of STRING.
+    let data = string.as_slice().to_vec();
+    let header_len = MAGIC.len() + 1;
+    // Version 1 is version 2 without text properties.
+    let version = data.get(MAGIC.len()).cloned().unwrap_or(0);
+    if !data.starts_with(MAGIC)
+        || data.len() < header_len
+        || version < 1
+        || version > FORMAT_VERSION
+    {
+        error!("Not a binary desktop file, or an unsupported version");
+    }
+
+    match decode_object(&data[header_len..]) {
+        Some(object) => object,
+        _ => error!("Invalid binary desktop data"),
+    }
+}
+
+/// Return the text properties of the current buffer between BEG and END
+/// as a list of (START END PLIST), keeping only the properties named in
+/// PROPERTIES, or all of them if PROPERTIES is t.
+fn text_property_intervals(beg: ptrdiff_t, end: ptrdiff_t, properties: LispObject) -> LispObject {
+    let mut intervals = Qnil;
+    let mut pos = beg as EmacsInt;
+    while pos < end as EmacsInt {
+        let position = LispObject::from(pos);
+        let limit = LispObject::from(end as EmacsInt);
+        let next = unsafe { Fnext_property_change(position, Qnil, limit) }
+            .as_fixnum()
+            .unwrap_or(end as EmacsInt);
+
+        let mut plist = Qnil;
+        let mut tail = unsafe { Ftext_properties_at(position, Qnil) };
+        while let Some(cons) = tail.as_cons() {
+            let (prop, rest) = cons.as_tuple();
+            let value = rest.as_cons().map_or(Qnil, |c| c.car());
+            if properties.eq(Qt) || memq(prop, properties).is_not_nil() {
+                plist = LispObject::cons(prop, LispObject::cons(value, plist));
+            }
+            tail = rest.as_cons().map_or(Qnil, |c| c.cdr());
+        }
+        if plist.is_not_nil() {
+            intervals = LispObject::cons(
+                list!(LispObject::from(pos), LispObject::from(next), plist),
+                intervals,
+            );
+        }
+        pos = next;
+    }
+
+    let mut result = Qnil;
+    while let Some(cons) = intervals.as_cons() {
+        result = LispObject::cons(cons.car(), result);
+        intervals = cons.cdr();
+    }
+    result
+}
+
+/// Return the parts of BUFFER's state that `desktop-save' keeps natively.
+/// The value is a plist with these properties:
+///
+///   :mark-ring         the positions of the markers in `mark-ring';
+///   :restriction       (BEG . END) if the buffer is narrowed;
+///   :text-properties   a list of (START END PLIST), giving the text
+///                      properties of the buffer's text.
+///
+/// Properties whose value would be nil are left out.  Text properties are
+/// only collected if PROPERTIES is non-nil: a list of the text properties
+/// to keep, or t to keep all of them.
+#[lisp_fn(min = "1")]
+pub fn desktop_buffer_state(mut buffer: LispBufferRef, properties: LispObject) -> LispObject {
+    let count = c_specpdl_index();
+    unsafe {
+        record_unwind_current_buffer();
+        set_buffer_internal(buffer.as_mut());
+    }
+
+    let mut state = Qnil;
+
+    if properties.is_not_nil() {
+        let intervals = text_property_intervals(BEG, buffer.z(), properties);
+        if intervals.is_not_nil() {
+            state = list!(intern(":text-properties").as_lisp_obj(), intervals);
+        }
+    }
+
+    if buffer.begv != BEG || buffer.zv != buffer.z() {
+        state = LispObject::cons(
+            intern(":restriction").as_lisp_obj(),
+            LispObject::cons(
+                LispObject::cons(
+                    LispObject::from(buffer.begv as EmacsInt),
+                    LispObject::from(buffer.zv as EmacsInt),
+                ),
+                state,
+            ),
+        );
+    }
+
+    let mut positions = Qnil;
+    let mut ring = unsafe { Fsymbol_value(intern("mark-ring").as_lisp_obj()) };
+    while let Some(cons) = ring.as_cons() {
+        if let Some(pos) = cons.car().as_marker().and_then(|m| m.charpos()) {
+            positions = LispObject::cons(LispObject::from(pos as EmacsInt), positions);
+        }
+        ring = cons.cdr();
+    }
+    if positions.is_not_nil() {
+        let mut ordered = Qnil;
+        while let Some(cons) = positions.as_cons() {
+            ordered = LispObject::cons(cons.car(), ordered);
+            positions = cons.cdr();
+        }
+        state = LispObject::cons(
+            intern(":mark-ring").as_lisp_obj(),
+            LispObject::cons(ordered, state),
+        );
+    }
+
+    unbind_to(count, state)
+}
+
+include!(concat!(env!("OUT_DIR"), "/desktop_exports.rs"));
@@ -0,0 +1,1124 @@
+//! Computing the differences between two texts.
+//!
+//! `diff-regions-native' compares strings and buffer regions in memory,
+//! instead of writing them to files and parsing the output of a diff
+//! process.  The texts are split into lines, words or characters, every
+//! distinct element is given a number, and the two sequences of numbers
+//! are compared with Myers' algorithm, optionally anchored on the
+//! elements that occur only once in each text ("patience diff").
+//!
+//! `merge-regions-native' uses the same comparison for three-way merges,
+//! like "diff3 -m".
+
+use std::cmp::{max, min};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::time::Instant;
+
+use libc::ptrdiff_t;
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    eval::unbind_to,
+    lisp::{defsubr, LispObject},
+    multibyte::{multibyte_char_at, write_codepoint, Codepoint, MAX_MULTIBYTE_LENGTH},
+    obarray::intern,
+    remacs_sys::{buf_charpos_to_bytepos, insert_from_string},
+    remacs_sys::{record_unwind_current_buffer, set_buffer_internal_1},
+    remacs_sys::{EmacsInt, Qnil, Qstringp},
+    shell::make_lisp_string,
+    threads::{c_specpdl_index, ThreadState},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Unit {
+    Lines,
+    Words,
+    Chars,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Algorithm {
+    Myers,
+    Patience,
+}
+
+/// A part of the first sequence that was replaced by a part of the
+/// second.  One of the ranges can be empty.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Change {
+    pub(crate) a: Range<usize>,
+    pub(crate) b: Range<usize>,
+}
+
+/// A difference between two texts, as ranges of character offsets.
+#[derive(Debug, PartialEq)]
+struct Hunk {
+    change: Change,
+    /// The words that differ inside the hunk.
+    refined: Vec<Change>,
+}
+
+/// A part of the result of a three-way merge, as a range of the elements
+/// of the text it is taken from.
+#[derive(Clone, Debug, PartialEq)]
+enum MergeRegion {
+    /// A part of BASE that neither MINE nor THEIRS changed.
+    Unchanged(Range<usize>),
+    /// A part of MINE that replaced a part of BASE.  THEIRS left that
+    /// part alone, or changed it the same way.
+    Mine(Range<usize>),
+    /// A part of THEIRS that replaced a part of BASE that MINE left alone.
+    Theirs(Range<usize>),
+    /// A part of BASE that MINE and THEIRS changed differently.
+    Conflict {
+        mine: Range<usize>,
+        base: Range<usize>,
+        theirs: Range<usize>,
+    },
+}
+
+/// Compares two sequences of element numbers, marking the elements that
+/// are not part of a longest common subsequence.
+pub(crate) struct Differ<'a> {
+    a: &'a [u32],
+    b: &'a [u32],
+    deleted: Vec<bool>,
+    inserted: Vec<bool>,
+    /// The furthest reaching paths of the forward and backward searches,
+    /// indexed by diagonal plus `offset'.
+    forward: Vec<isize>,
+    backward: Vec<isize>,
+    offset: isize,
+    /// The number of edits after which a shortest path is no longer
+    /// searched for.
+    max_cost: Option<isize>,
+    deadline: Option<Instant>,
+    timed_out: bool,
+}
+
+impl<'a> Differ<'a> {
+    pub(crate) fn new(a: &'a [u32], b: &'a [u32]) -> Differ<'a> {
+        let d_max = (a.len() + b.len() + 1) / 2 + 1;
+        Differ {
+            a,
+            b,
+            deleted: vec![false; a.len()],
+            inserted: vec![false; b.len()],
+            forward: vec![0; 2 * d_max + 3],
+            backward: vec![0; 2 * d_max + 3],
+            offset: d_max as isize + 1,
+            max_cost: None,
+            deadline: None,
+            timed_out: false,
+        }
+    }
+
+    /// Limit the work done by `myers'.  Past MAX_COST edits in one part
+    /// of the sequences, a cheaper path is accepted instead of a shortest
+    /// one.  Past DEADLINE, the comparison is abandoned.
+    pub(crate) fn with_budget(
+        mut self,
+        max_cost: Option<usize>,
+        deadline: Option<Instant>,
+    ) -> Self {
+        self.max_cost = max_cost.map(|cost| max(cost, 1) as isize);
+        self.deadline = deadline;
+        self
+    }
+
+    /// Return true if the comparison was abandoned because it took
+    /// longer than the deadline.
+    pub(crate) fn timed_out(&self) -> bool {
+        self.timed_out
+    }
+
+    fn mark(&mut self, a: Range<usize>, b: Range<usize>) {
+        for i in a {
+            self.deleted[i] = true;
+        }
+        for j in b {
+            self.inserted[j] = true;
+        }
+    }
+
+    /// Return a point on a shortest edit path from (A0, B0) to (A1, B1):
+    /// the start of the snake where the forward and backward searches
+    /// meet.  This is the divide step of the linear space version of
+    /// Myers' algorithm.
+    fn middle_snake(
+        &mut self,
+        a0: usize,
+        a1: usize,
+        b0: usize,
+        b1: usize,
+    ) -> Option<(usize, usize)> {
+        let n = (a1 - a0) as isize;
+        let m = (b1 - b0) as isize;
+        let delta = n - m;
+        let odd = delta & 1 == 1;
+        let off = self.offset;
+        self.forward[(off + 1) as usize] = 0;
+        self.backward[(off + 1) as usize] = 0;
+
+        let d_max = (n + m + 1) / 2 + 1;
+        for d in 0..d_max {
+            if self
+                .deadline
+                .map_or(false, |deadline| Instant::now() >= deadline)
+            {
+                self.timed_out = true;
+                return None;
+            }
+            if self.max_cost.map_or(false, |max_cost| d > max_cost) {
+                if let Some(point) = self.furthest_forward(a0, b0, n, m, d - 1) {
+                    return Some(point);
+                }
+            }
+
+            let mut k = d;
+            while k >= -d {
+                let vf = &self.forward;
+                let mut x = if k == -d
+                    || (k != d && vf[(off + k - 1) as usize] < vf[(off + k + 1) as usize])
+                {
+                    vf[(off + k + 1) as usize]
+                } else {
+                    vf[(off + k - 1) as usize] + 1
+                };
+                let (x0, y0) = (x, x - k);
+                let mut y = y0;
+                while x < n && y < m && self.a[a0 + x as usize] == self.b[b0 + y as usize] {
+                    x += 1;
+                    y += 1;
+                }
+                self.forward[(off + k) as usize] = x;
+                if odd
+                    && (k - delta).abs() <= d - 1
+                    && x + self.backward[(off - (k - delta)) as usize] >= n
+                {
+                    return Some((a0 + x0 as usize, b0 + y0 as usize));
+                }
+                k -= 2;
+            }
+
+            let mut k = d;
+            while k >= -d {
+                let vb = &self.backward;
+                let mut x = if k == -d
+                    || (k != d && vb[(off + k - 1) as usize] < vb[(off + k + 1) as usize])
+                {
+                    vb[(off + k + 1) as usize]
+                } else {
+                    vb[(off + k - 1) as usize] + 1
+                };
+                let mut y = x - k;
+                while x < n && y < m && self.a[a1 - 1 - x as usize] == self.b[b1 - 1 - y as usize] {
+                    x += 1;
+                    y += 1;
+                }
+                self.backward[(off + k) as usize] = x;
+                if !odd
+                    && (k - delta).abs() <= d
+                    && x + self.forward[(off - (k - delta)) as usize] >= n
+                {
+                    return Some((a1 - x as usize, b1 - y as usize));
+                }
+                k -= 2;
+            }
+        }
+        None
+    }
+
+    /// Return the point that the forward search reached with D edits that
+    /// is furthest from (A0, B0), other than the end.  It is on an edit
+    /// path, though not necessarily on a shortest one; `middle_snake'
+    /// settles for it when a shortest path costs too much to find.
+    fn furthest_forward(
+        &self,
+        a0: usize,
+        b0: usize,
+        n: isize,
+        m: isize,
+        d: isize,
+    ) -> Option<(usize, usize)> {
+        let mut best = None;
+        let mut best_distance = 0;
+        let mut k = d;
+        while k >= -d {
+            let x = self.forward[(self.offset + k) as usize];
+            let y = x - k;
+            if x <= n && y >= 0 && y <= m && x + y > best_distance && (x, y) != (n, m) {
+                best = Some((a0 + x as usize, b0 + y as usize));
+                best_distance = x + y;
+            }
+            k -= 2;
+        }
+        best
+    }
+
+    /// Mark the differences between A[A0..A1] and B[B0..B1] with Myers'
+    /// algorithm.
+    pub(crate) fn myers(&mut self, mut a0: usize, mut a1: usize, mut b0: usize, mut b1: usize) {
+        if self.timed_out {
+            return;
+        }
+        while a0 < a1 && b0 < b1 && self.a[a0] == self.b[b0] {
+            a0 += 1;
+            b0 += 1;
+        }
+        while a0 < a1 && b0 < b1 && self.a[a1 - 1] == self.b[b1 - 1] {
+            a1 -= 1;
+            b1 -= 1;
+        }
+        if a0 == a1 || b0 == b1 {
+            self.mark(a0..a1, b0..b1);
+            return;
+        }
+        match self.middle_snake(a0, a1, b0, b1) {
+            Some((x, y)) if (x, y) != (a0, b0) && (x, y) != (a1, b1) => {
+                self.myers(a0, x, b0, y);
+                self.myers(x, a1, y, b1);
+            }
+            _ => self.mark(a0..a1, b0..b1),
+        }
+    }
+
+    /// Mark the differences between A[A0..A1] and B[B0..B1], matching the
+    /// elements that occur once in each first, and then comparing the
+    /// parts between them with Myers' algorithm.
+    fn patience(&mut self, mut a0: usize, mut a1: usize, mut b0: usize, mut b1: usize) {
+        while a0 < a1 && b0 < b1 && self.a[a0] == self.b[b0] {
+            a0 += 1;
+            b0 += 1;
+        }
+        while a0 < a1 && b0 < b1 && self.a[a1 - 1] == self.b[b1 - 1] {
+            a1 -= 1;
+            b1 -= 1;
+        }
+
+        // For each element, the number of times it occurs on each side,
+        // and where it last occurs.
+        let mut occurrences: HashMap<u32, (usize, usize, usize, usize)> = HashMap::new();
+        for i in a0..a1 {
+            let entry = occurrences.entry(self.a[i]).or_insert((0, 0, 0, 0));
+            entry.0 += 1;
+            entry.1 = i;
+        }
+        for j in b0..b1 {
+            if let Some(entry) = occurrences.get_mut(&self.b[j]) {
+                entry.2 += 1;
+                entry.3 = j;
+            }
+        }
+        let mut unique: Vec<(usize, usize)> = occurrences
+            .values()
+            .filter(|&&(na, _, nb, _)| na == 1 && nb == 1)
+            .map(|&(_, i, _, j)| (i, j))
+            .collect();
+        unique.sort();
+
+        let anchors = longest_increasing(&unique);
+        if anchors.is_empty() {
+            self.myers(a0, a1, b0, b1);
+            return;
+        }
+        let (mut i, mut j) = (a0, b0);
+        for (x, y) in anchors {
+            self.patience(i, x, j, y);
+            i = x + 1;
+            j = y + 1;
+        }
+        self.patience(i, a1, j, b1);
+    }
+
+    /// Return the runs of marked elements.
+    pub(crate) fn changes(&self) -> Vec<Change> {
+        let (n, m) = (self.a.len(), self.b.len());
+        let mut changes = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if (i < n && self.deleted[i]) || (j < m && self.inserted[j]) {
+                let (a0, b0) = (i, j);
+                while i < n && self.deleted[i] {
+                    i += 1;
+                }
+                while j < m && self.inserted[j] {
+                    j += 1;
+                }
+                changes.push(Change { a: a0..i, b: b0..j });
+            } else {
+                i += 1;
+                j += 1;
+            }
+        }
+        changes
+    }
+}
+
+/// Return the longest subsequence of PAIRS, which are sorted by their
+/// first element, whose second elements are increasing too.
+fn longest_increasing(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // The indices of the last pair of the best subsequence of each
+    // length, and the predecessor of each pair in its subsequence.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = Vec::with_capacity(pairs.len());
+    for (index, &(_, j)) in pairs.iter().enumerate() {
+        let length = match tails.binary_search_by(|&t| pairs[t].1.cmp(&j)) {
+            Ok(length) | Err(length) => length,
+        };
+        previous.push(if length > 0 {
+            Some(tails[length - 1])
+        } else {
+            None
+        });
+        if length == tails.len() {
+            tails.push(index);
+        } else {
+            tails[length] = index;
+        }
+    }
+
+    let mut result = Vec::with_capacity(tails.len());
+    let mut index = tails.last().cloned();
+    while let Some(i) = index {
+        result.push(pairs[i]);
+        index = previous[i];
+    }
+    result.reverse();
+    result
+}
+
+fn is_whitespace(c: Codepoint) -> bool {
+    c == ' ' as Codepoint || (c >= '\t' as Codepoint && c <= '\r' as Codepoint)
+}
+
+fn is_word_char(c: Codepoint) -> bool {
+    c == '_' as Codepoint || std::char::from_u32(c).map_or(false, char::is_alphanumeric)
+}
+
+/// Split TEXT into elements of the kind UNIT.  Return the range of
+/// each element.
+fn split(text: &[Codepoint], unit: Unit) -> Vec<Range<usize>> {
+    let mut elements = Vec::new();
+    let mut start = 0;
+    while start < text.len() {
+        let c = text[start];
+        let len = match unit {
+            Unit::Chars => 1,
+            Unit::Lines => text[start..]
+                .iter()
+                .position(|&c| c == '\n' as Codepoint)
+                .map_or(text.len() - start, |i| i + 1),
+            Unit::Words => {
+                let same_kind: fn(Codepoint) -> bool = if is_word_char(c) {
+                    is_word_char
+                } else if is_whitespace(c) {
+                    is_whitespace
+                } else {
+                    |_| false
+                };
+                1 + text[start + 1..]
+                    .iter()
+                    .take_while(|&&c| same_kind(c))
+                    .count()
+            }
+        };
+        elements.push(start..start + len);
+        start += len;
+    }
+    elements
+}
+
+/// Number the elements ELEMENTS of TEXT so that equal elements get equal
+/// numbers.  If IGNORE_WHITESPACE, elements that differ only in
+/// whitespace are equal.
+fn number_elements(
+    text: &[Codepoint],
+    elements: &[Range<usize>],
+    ignore_whitespace: bool,
+    numbers: &mut HashMap<Vec<Codepoint>, u32>,
+) -> Vec<u32> {
+    elements
+        .iter()
+        .map(|range| {
+            let element = &text[range.clone()];
+            let key: Vec<Codepoint> = if ignore_whitespace {
+                element
+                    .iter()
+                    .cloned()
+                    .filter(|&c| !is_whitespace(c))
+                    .collect()
+            } else {
+                element.to_vec()
+            };
+            let next = numbers.len() as u32;
+            *numbers.entry(key).or_insert(next)
+        })
+        .collect()
+}
+
+/// Compare the sequences of elements of A and B.  The ranges of the
+/// changes are converted from element indices to character offsets.
+fn compare(
+    a: &[Codepoint],
+    b: &[Codepoint],
+    unit: Unit,
+    algorithm: Algorithm,
+    ignore_whitespace: bool,
+) -> Vec<Change> {
+    let elements_a = split(a, unit);
+    let elements_b = split(b, unit);
+    let mut numbers = HashMap::new();
+    let numbers_a = number_elements(a, &elements_a, ignore_whitespace, &mut numbers);
+    let numbers_b = number_elements(b, &elements_b, ignore_whitespace, &mut numbers);
+
+    let mut differ = Differ::new(&numbers_a, &numbers_b);
+    let (n, m) = (numbers_a.len(), numbers_b.len());
+    match algorithm {
+        Algorithm::Myers => differ.myers(0, n, 0, m),
+        Algorithm::Patience => differ.patience(0, n, 0, m),
+    }
+
+    let offset = |elements: &[Range<usize>], text: &[Codepoint], i: usize| {
+        elements.get(i).map_or(text.len(), |range| range.start)
+    };
+    differ
+        .changes()
+        .into_iter()
+        .map(|change| Change {
+            a: offset(&elements_a, a, change.a.start)..offset(&elements_a, a, change.a.end),
+            b: offset(&elements_b, b, change.b.start)..offset(&elements_b, b, change.b.end),
+        })
+        .collect()
+}
+
+/// Compare the texts A and B.  If REFINE, compare the words of each
+/// hunk in which text was replaced.
+fn diff_texts(
+    a: &[Codepoint],
+    b: &[Codepoint],
+    unit: Unit,
+    algorithm: Algorithm,
+    refine: bool,
+    ignore_whitespace: bool,
+) -> Vec<Hunk> {
+    compare(a, b, unit, algorithm, ignore_whitespace)
+        .into_iter()
+        .map(|change| {
+            let refined =
+                if refine && unit != Unit::Chars && !change.a.is_empty() && !change.b.is_empty() {
+                    let (a0, b0) = (change.a.start, change.b.start);
+                    compare(
+                        &a[change.a.clone()],
+                        &b[change.b.clone()],
+                        Unit::Words,
+                        Algorithm::Myers,
+                        ignore_whitespace,
+                    )
+                    .into_iter()
+                    .map(|fine| Change {
+                        a: a0 + fine.a.start..a0 + fine.a.end,
+                        b: b0 + fine.b.start..b0 + fine.b.end,
+                    })
+                    .collect()
+                } else {
+                    Vec::new()
+                };
+            Hunk { change, refined }
+        })
+        .collect()
+}
+
+/// Merge the changes from BASE to MINE and from BASE to THEIRS, which
+/// are sequences of element numbers.  Changes of overlapping or adjacent
+/// parts of BASE conflict, unless they are the same.
+fn merge3(base: &[u32], mine: &[u32], theirs: &[u32]) -> Vec<MergeRegion> {
+    let diff = |other: &[u32]| {
+        let mut differ = Differ::new(base, other);
+        differ.myers(0, base.len(), 0, other.len());
+        differ.changes()
+    };
+    let changes_mine = diff(mine);
+    let changes_theirs = diff(theirs);
+
+    // The range of the other text that corresponds to BASE[START..END],
+    // given the CHANGES of that range, and the difference DELTA between
+    // positions in the other text and in BASE before it.
+    let other_range = |changes: &[Change], start: usize, end: usize, delta: isize| match (
+        changes.first(),
+        changes.last(),
+    ) {
+        (Some(first), Some(last)) => {
+            first.b.start - (first.a.start - start)..last.b.end + (end - last.a.end)
+        }
+        _ => (start as isize + delta) as usize..(end as isize + delta) as usize,
+    };
+
+    let mut regions = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let (mut delta_mine, mut delta_theirs) = (0, 0);
+    let mut pos = 0;
+    while i < changes_mine.len() || j < changes_theirs.len() {
+        let start = match (changes_mine.get(i), changes_theirs.get(j)) {
+            (Some(m), Some(t)) => min(m.a.start, t.a.start),
+            (Some(m), None) => m.a.start,
+            (None, Some(t)) => t.a.start,
+            (None, None) => unreachable!(),
+        };
+        // Collect the changes on both sides that overlap or touch the
+        // group so far.
+        let (i0, j0) = (i, j);
+        let mut end = start;
+        loop {
+            let mut grew = false;
+            if let Some(m) = changes_mine.get(i).filter(|m| m.a.start <= end) {
+                end = max(end, m.a.end);
+                i += 1;
+                grew = true;
+            }
+            if let Some(t) = changes_theirs.get(j).filter(|t| t.a.start <= end) {
+                end = max(end, t.a.end);
+                j += 1;
+                grew = true;
+            }
+            if !grew {
+                break;
+            }
+        }
+
+        if pos < start {
+            regions.push(MergeRegion::Unchanged(pos..start));
+        }
+        let group_mine = &changes_mine[i0..i];
+        let group_theirs = &changes_theirs[j0..j];
+        let range_mine = other_range(group_mine, start, end, delta_mine);
+        let range_theirs = other_range(group_theirs, start, end, delta_theirs);
+        regions.push(if group_theirs.is_empty() {
+            MergeRegion::Mine(range_mine)
+        } else if group_mine.is_empty() {
+            MergeRegion::Theirs(range_theirs)
+        } else if mine[range_mine.clone()] == theirs[range_theirs.clone()] {
+            MergeRegion::Mine(range_mine)
+        } else {
+            MergeRegion::Conflict {
+                mine: range_mine,
+                base: start..end,
+                theirs: range_theirs,
+            }
+        });
+
+        if let Some(last) = group_mine.last() {
+            delta_mine = last.b.end as isize - last.a.end as isize;
+        }
+        if let Some(last) = group_theirs.last() {
+            delta_theirs = last.b.end as isize - last.a.end as isize;
+        }
+        pos = end;
+    }
+    if pos < base.len() {
+        regions.push(MergeRegion::Unchanged(pos..base.len()));
+    }
+    regions
+}
+
+/// Merge the changes from the lines of BASE to those of MINE and THEIRS.
+/// Conflicts are put between markers, followed by the corresponding
+/// LABELS, and include the lines of BASE if SHOW_BASE.  Return the
+/// merged text and the number of conflicts.
+fn merge_texts(
+    mine: &[Codepoint],
+    base: &[Codepoint],
+    theirs: &[Codepoint],
+    labels: &[Option<Vec<Codepoint>>; 3],
+    show_base: bool,
+) -> (Vec<Codepoint>, usize) {
+    let texts = [mine, base, theirs];
+    let elements: Vec<Vec<Range<usize>>> =
+        texts.iter().map(|text| split(text, Unit::Lines)).collect();
+    let mut numbers = HashMap::new();
+    let numbered: Vec<Vec<u32>> = texts
+        .iter()
+        .zip(&elements)
+        .map(|(text, elements)| number_elements(text, elements, false, &mut numbers))
+        .collect();
+
+    let mut merged = Vec::new();
+    let mut conflicts = 0;
+    // Append the lines in RANGE of the text with index INDEX.
+    let append = |merged: &mut Vec<Codepoint>, index: usize, range: Range<usize>| {
+        let offset = |i: usize| {
+            elements[index]
+                .get(i)
+                .map_or(texts[index].len(), |e| e.start)
+        };
+        merged.extend_from_slice(&texts[index][offset(range.start)..offset(range.end)]);
+    };
+    let marker = |merged: &mut Vec<Codepoint>, marker: char, label: Option<&Vec<Codepoint>>| {
+        if merged.last().map_or(false, |&c| c != '\n' as Codepoint) {
+            merged.push('\n' as Codepoint);
+        }
+        merged.extend((0..7).map(|_| marker as Codepoint));
+        if let Some(label) = label {
+            merged.push(' ' as Codepoint);
+            merged.extend_from_slice(label);
+        }
+        merged.push('\n' as Codepoint);
+    };
+
+    for region in merge3(&numbered[1], &numbered[0], &numbered[2]) {
+        match region {
+            MergeRegion::Unchanged(range) => append(&mut merged, 1, range),
+            MergeRegion::Mine(range) => append(&mut merged, 0, range),
+            MergeRegion::Theirs(range) => append(&mut merged, 2, range),
+            MergeRegion::Conflict { mine, base, theirs } => {
+                conflicts += 1;
+                marker(&mut merged, '<', labels[0].as_ref());
+                append(&mut merged, 0, mine);
+                if show_base {
+                    marker(&mut merged, '|', labels[1].as_ref());
+                    append(&mut merged, 1, base);
+                }
+                marker(&mut merged, '=', None);
+                append(&mut merged, 2, theirs);
+                marker(&mut merged, '>', labels[2].as_ref());
+            }
+        }
+    }
+    (merged, conflicts)
+}
+
+/// Return the characters of SOURCE, a string, a buffer or a list
+/// (BUFFER START END), and the position of the first one.
+fn source_text(source: LispObject) -> (Vec<Codepoint>, EmacsInt) {
+    if let Some(string) = source.as_string() {
+        return (string.chars().collect(), 0);
+    }
+
+    let (buffer, region) = match source.as_cons() {
+        Some(cons) => {
+            let (buffer, rest) = cons.as_tuple();
+            let mut bounds = rest.iter_cars_safe();
+            let start = bounds.next().unwrap_or(Qnil);
+            let end = bounds.next().unwrap_or(Qnil);
+            (buffer, Some((start, end)))
+        }
+        None => (source, None),
+    };
+    let mut buffer = buffer.as_buffer_or_error();
+    if !buffer.is_live() {
+        error!("Selecting deleted buffer");
+    }
+
+    let (start, end) = match region {
+        Some((start_obj, end_obj)) => {
+            let start = start_obj.as_fixnum_coerce_marker_or_error() as ptrdiff_t;
+            let end = end_obj.as_fixnum_coerce_marker_or_error() as ptrdiff_t;
+            let (start, end) = if start <= end {
+                (start, end)
+            } else {
+                (end, start)
+            };
+            if start < buffer.begv || end > buffer.zv {
+                args_out_of_range!(source, start_obj, end_obj);
+            }
+            (start, end)
+        }
+        None => (buffer.begv, buffer.zv),
+    };
+
+    let (start_byte, end_byte) = unsafe {
+        (
+            buf_charpos_to_bytepos(buffer.as_mut(), start),
+            buf_charpos_to_bytepos(buffer.as_mut(), end),
+        )
+    };
+    let bytes = buffer.bytes_between(start_byte, end_byte);
+    let mut text = Vec::with_capacity(bytes.len());
+    if buffer.multibyte_characters_enabled() {
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let (c, len) = multibyte_char_at(&bytes[offset..]);
+            text.push(c);
+            offset += len;
+        }
+    } else {
+        text.extend(bytes.iter().map(|&b| Codepoint::from(b)));
+    }
+    (text, start as EmacsInt)
+}
+
+fn change_to_lisp(change: &Change, start_a: EmacsInt, start_b: EmacsInt) -> Vec<LispObject> {
+    vec![
+        LispObject::from(start_a + change.a.start as EmacsInt),
+        LispObject::from(start_a + change.a.end as EmacsInt),
+        LispObject::from(start_b + change.b.start as EmacsInt),
+        LispObject::from(start_b + change.b.end as EmacsInt),
+    ]
+}
+
+/// Compare the texts A and B, and return the parts that differ.
+/// A and B are each a string, a buffer or a list (BUFFER START END).  A
+/// buffer stands for its accessible portion.
+///
+/// The value is a list of hunks (A-START A-END B-START B-END REFINED),
+/// in the order they appear in the texts: the text of A between A-START
+/// and A-END was replaced by the text of B between B-START and B-END.
+/// One of the two can be empty.  Positions in strings count from zero.
+///
+/// UNIT says how the texts are split before they are compared: `lines',
+/// the default, `words' or `chars'.
+///
+/// ALGORITHM is `myers', the default, which finds the fewest changes, or
+/// `patience', which first matches the elements that occur once in each
+/// text; this often gives more readable results for code that was moved
+/// around.
+///
+/// If REFINE is non-nil, REFINED is a list of (A-START A-END B-START
+/// B-END) for the words that differ inside the hunk.  Otherwise it is
+/// nil.  If IGNORE-WHITESPACE is non-nil, elements that differ only in
+/// whitespace are equal.
+#[lisp_fn(min = "2")]
+pub fn diff_regions_native(
+    a: LispObject,
+    b: LispObject,
+    unit: LispObject,
+    algorithm: LispObject,
+    refine: bool,
+    ignore_whitespace: bool,
+) -> LispObject {
+    let unit = if unit.is_nil() || unit.eq(intern("lines").as_lisp_obj()) {
+        Unit::Lines
+    } else if unit.eq(intern("words").as_lisp_obj()) {
+        Unit::Words
+    } else if unit.eq(intern("chars").as_lisp_obj()) {
+        Unit::Chars
+    } else {
+        error!("Unknown diff unit; use `lines', `words' or `chars'")
+    };
+    let algorithm = if algorithm.is_nil() || algorithm.eq(intern("myers").as_lisp_obj()) {
+        Algorithm::Myers
+    } else if algorithm.eq(intern("patience").as_lisp_obj()) {
+        Algorithm::Patience
+    } else {
+        error!("Unknown diff algorithm; use `myers' or `patience'")
+    };
+
+    let (text_a, start_a) = source_text(a);
+    let (text_b, start_b) = source_text(b);
+    let hunks = diff_texts(&text_a, &text_b, unit, algorithm, refine, ignore_whitespace);
+
+    let list = |elements: Vec<LispObject>| {
+        elements
+            .into_iter()
+            .rev()
+            .fold(Qnil, |list, element| LispObject::cons(element, list))
+    };
+    list(
+        hunks
+            .iter()
+            .map(|hunk| {
+                let refined = hunk
+                    .refined
+                    .iter()
+                    .map(|change| list(change_to_lisp(change, start_a, start_b)))
+                    .collect();
+                let mut fields = change_to_lisp(&hunk.change, start_a, start_b);
+                fields.push(list(refined));
+                list(fields)
+            })
+            .collect(),
+    )
+}
+
+/// Merge the changes from BASE to MINE and from BASE to THEIRS into BUFFER.
+/// MINE, BASE and THEIRS are each a string, a buffer or a list (BUFFER
+/// START END), as for `diff-regions-native'.  The merged text is
+/// inserted at point in BUFFER, which defaults to the current buffer.
+///
+/// The texts are compared line by line.  Where only one of MINE and
+/// THEIRS changed a part of BASE, or both changed it the same way, the
+/// change is merged.  Where they made different changes to the same or
+/// adjacent lines, both versions are inserted between conflict markers,
+/// as by "diff3 -m":
+///
+///   <<<<<<< MINE-LABEL
+///   lines of MINE
+///   ||||||| BASE-LABEL
+///   lines of BASE
+///   =======
+///   lines of THEIRS
+///   >>>>>>> THEIRS-LABEL
+///
+/// LABELS is a list (MINE-LABEL BASE-LABEL THEIRS-LABEL); a label that
+/// is nil or missing is left out.  The lines of BASE are only included
+/// if SHOW-BASE is non-nil.
+///
+/// Return the number of conflicts.
+#[lisp_fn(min = "3")]
+pub fn merge_regions_native(
+    mine: LispObject,
+    base: LispObject,
+    theirs: LispObject,
+    buffer: LispObject,
+    labels: LispObject,
+    show_base: bool,
+) -> EmacsInt {
+    let mut target = if buffer.is_nil() {
+        ThreadState::current_buffer()
+    } else {
+        buffer.as_buffer_or_error()
+    };
+    if !target.is_live() {
+        error!("Selecting deleted buffer");
+    }
+
+    let mut label_list = labels.iter_cars_safe();
+    let mut next_label = || {
+        let label = label_list.next().unwrap_or(Qnil);
+        if label.is_nil() {
+            None
+        } else {
+            match label.as_string() {
+                Some(string) => Some(string.chars().collect()),
+                None => wrong_type!(Qstringp, label),
+            }
+        }
+    };
+    let labels = [next_label(), next_label(), next_label()];
+
+    let (text_mine, _) = source_text(mine);
+    let (text_base, _) = source_text(base);
+    let (text_theirs, _) = source_text(theirs);
+    let (merged, conflicts) = merge_texts(&text_mine, &text_base, &text_theirs, &labels, show_base);
+
+    let mut bytes = Vec::with_capacity(merged.len());
+    let mut buf = [0_u8; MAX_MULTIBYTE_LENGTH];
+    for c in merged {
+        let len = write_codepoint(&mut buf, c);
+        bytes.extend_from_slice(&buf[..len]);
+    }
+    let string = make_lisp_string(&bytes, true);
+    let nchars = string.as_string_or_error().len_chars();
+
+    let count = c_specpdl_index();
+    unsafe {
+        record_unwind_current_buffer();
+        set_buffer_internal_1(target.as_mut());
+        insert_from_string(string, 0, 0, nchars, bytes.len() as ptrdiff_t, false);
+    }
+    unbind_to(count, Qnil);
+    conflicts as EmacsInt
+}
+
+include!(concat!(env!("OUT_DIR"), "/diff_exports.rs"));
+
+#[cfg(test)]
+fn codepoints(s: &str) -> Vec<Codepoint> {
+    s.chars().map(Codepoint::from).collect()
+}
+
+#[cfg(test)]
+fn apply(a: &str, b: &str, changes: &[Change]) -> String {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut result = String::new();
+    let mut pos = 0;
+    for change in changes {
+        result.extend(&a[pos..change.a.start]);
+        result.extend(&b[change.b.clone()]);
+        pos = change.a.end;
+    }
+    result.extend(&a[pos..]);
+    result
+}
+
+#[test]
+fn test_myers() {
+    let cases = [
+        ("abcabba", "cbabac", 5),
+        ("", "abc", 3),
+        ("abc", "", 3),
+        ("same", "same", 0),
+        ("kitten", "sitting", 5),
+    ];
+    for &(a, b, cost) in &cases {
+        let changes = compare(
+            &codepoints(a),
+            &codepoints(b),
+            Unit::Chars,
+            Algorithm::Myers,
+            false,
+        );
+        let total: usize = changes.iter().map(|c| c.a.len() + c.b.len()).sum();
+        assert_eq!(total, cost, "{} -> {}", a, b);
+        assert_eq!(apply(a, b, &changes), b);
+    }
+}
+
+#[test]
+fn test_lines() {
+    let a = "one\ntwo\nthree\nfour\n";
+    let b = "one\n2\nthree\nfour\nfive\n";
+    let changes = compare(
+        &codepoints(a),
+        &codepoints(b),
+        Unit::Lines,
+        Algorithm::Myers,
+        false,
+    );
+    assert_eq!(
+        changes,
+        vec![
+            Change { a: 4..8, b: 4..6 },
+            Change {
+                a: 19..19,
+                b: 17..22
+            }
+        ]
+    );
+    assert_eq!(apply(a, b, &changes), b);
+}
+
+#[test]
+fn test_patience() {
+    // A function moved below another one.  Myers matches the braces of
+    // the wrong functions; patience anchors on the unique lines.
+    let a = "fn a() {\n    x\n}\nfn b() {\n    y\n}\n";
+    let b = "fn b() {\n    y\n}\nfn a() {\n    x\n}\n";
+    let changes = compare(
+        &codepoints(a),
+        &codepoints(b),
+        Unit::Lines,
+        Algorithm::Patience,
+        false,
+    );
+    assert_eq!(apply(a, b, &changes), b);
+    assert_eq!(changes.len(), 2);
+
+    assert_eq!(
+        longest_increasing(&[(0, 3), (1, 1), (2, 2), (3, 0), (4, 4)]),
+        vec![(1, 1), (2, 2), (4, 4)]
+    );
+}
+
+#[test]
+fn test_refine() {
+    let a = "let x = foo(1);\nsame\n";
+    let b = "let y = foo(2);\nsame\n";
+    let hunks = diff_texts(
+        &codepoints(a),
+        &codepoints(b),
+        Unit::Lines,
+        Algorithm::Myers,
+        true,
+        false,
+    );
+    assert_eq!(hunks.len(), 1);
+    assert_eq!(hunks[0].change, Change { a: 0..16, b: 0..16 });
+    assert_eq!(
+        hunks[0].refined,
+        vec![
+            Change { a: 4..5, b: 4..5 },
+            Change {
+                a: 12..13,
+                b: 12..13
+            }
+        ]
+    );
+}
+
+#[test]
+fn test_ignore_whitespace() {
+    let a = codepoints("a  b\nc\n");
+    let b = codepoints("a b\nd\n");
+    let changes = compare(&a, &b, Unit::Lines, Algorithm::Myers, true);
+    assert_eq!(changes, vec![Change { a: 5..7, b: 4..6 }]);
+    assert_eq!(
+        split(&codepoints("foo_1  (x)"), Unit::Words),
+        vec![0..5, 5..7, 7..8, 8..9, 9..10]
+    );
+}
+
+#[test]
+fn test_budget() {
+    let a: Vec<u32> = (0..200).map(|i| i * 7 % 13).collect();
+    let b: Vec<u32> = (0..180).map(|i| i * 5 % 11).collect();
+    let apply = |changes: &[Change]| {
+        let mut result: Vec<u32> = Vec::new();
+        let mut pos = 0;
+        for change in changes {
+            result.extend(&a[pos..change.a.start]);
+            result.extend(&b[change.b.clone()]);
+            pos = change.a.end;
+        }
+        result.extend(&a[pos..]);
+        result
+    };
+    let cost =
+        |changes: &[Change]| -> usize { changes.iter().map(|c| c.a.len() + c.b.len()).sum() };
+
+    let mut differ = Differ::new(&a, &b);
+    differ.myers(0, a.len(), 0, b.len());
+    let shortest = differ.changes();
+
+    // A cost limit still gives a correct edit script, if a longer one.
+    let mut differ = Differ::new(&a, &b).with_budget(Some(3), None);
+    differ.myers(0, a.len(), 0, b.len());
+    let changes = differ.changes();
+    assert!(!differ.timed_out());
+    assert_eq!(apply(&changes), b);
+    assert!(cost(&changes) >= cost(&shortest));
+
+    let mut differ = Differ::new(&a, &b).with_budget(None, Some(Instant::now()));
+    differ.myers(0, a.len(), 0, b.len());
+    assert!(differ.timed_out());
+}
+
+#[test]
+fn test_merge() {
+    let merge = |mine: &str, base: &str, theirs: &str, show_base: bool| {
+        let labels = [Some(codepoints("mine")), None, Some(codepoints("theirs"))];
+        let (merged, conflicts) = merge_texts(
+            &codepoints(mine),
+            &codepoints(base),
+            &codepoints(theirs),
+            &labels,
+            show_base,
+        );
+        let merged: String = merged.into_iter().filter_map(std::char::from_u32).collect();
+        (merged, conflicts)
+    };
+
+    let base = "a\nb\nc\nd\ne\n";
+    assert_eq!(
+        merge("a\nB\nc\nd\ne\n", base, "a\nb\nc\nD\ne\nf\n", false),
+        ("a\nB\nc\nD\ne\nf\n".to_string(), 0)
+    );
+    // The same change on both sides.
+    assert_eq!(
+        merge("a\nb\nX\nd\ne\n", base, "a\nb\nX\nd\ne\n", false),
+        ("a\nb\nX\nd\ne\n".to_string(), 0)
+    );
+    assert_eq!(
+        merge("a\nb\nX\nd\ne\n", base, "a\nb\nY\nd\ne", true),
+        (
+            "a\nb\n<<<<<<< mine\nX\n|||||||\nc\n=======\nY\n>>>>>>> theirs\nd\ne".to_string(),
+            1
+        )
+    );
+    // Changes of adjacent lines conflict.
+    assert_eq!(
+        merge("a\nB\nc\nd\ne\n", base, "a\nb\nC\nd\ne\n", false).1,
+        1
+    );
+    assert_eq!(merge("", "", "x\n", false), ("x\n".to_string(), 0));
+    // Markers always start a line.
+    assert_eq!(
+        merge("x", "", "y", false),
+        (
+            "<<<<<<< mine\nx\n=======\ny\n>>>>>>> theirs\n".to_string(),
+            1
+        )
+    );
+}
@@ -0,0 +1,883 @@
+//! Resolution of directory-local variables.
+//!
+//! Visiting a file looks for `.dir-locals.el' files in its directory
+//! and all of its parents, and, if asked to, for `.editorconfig' files
+//! as well.  Both are parsed once and cached along with the
+//! modification time and size of the files they were read from, so that
+//! visiting further files in the same tree only has to stat them.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    callproc::{lisp_file_name_to_path, path_to_lisp_file_name},
+    eval::unbind_to,
+    hashtable::{gethash, puthash, LispHashTableRef},
+    lisp::{defsubr, LispObject},
+    lists::{assoc, assq, car_safe, cdr_safe},
+    lread::read,
+    multibyte::LispStringRef,
+    obarray::intern,
+    remacs_sys::{
+        code_convert_string_norecord, find_symbol_value, make_unibyte_string, specbind, EmacsInt,
+    },
+    remacs_sys::{
+        Fdirectory_file_name, Fexpand_file_name, Ffile_name_as_directory, Ffile_name_directory,
+        Ffind_file_name_handler,
+    },
+    remacs_sys::{QCtest, Qequal, Qfile_exists_p, Qnil, Qt, Qundecided},
+    threads::c_specpdl_index,
+};
+
+/// The modification time and size of a file, used to tell whether a
+/// cached parse of it is still valid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+/// Return the stamp of PATH, or None if it is not a regular file.
+fn file_stamp(path: &Path) -> Option<FileStamp> {
+    fs::metadata(path)
+        .ok()
+        .filter(|meta| meta.is_file())
+        .map(|meta| FileStamp {
+            modified: meta.modified().ok(),
+            len: meta.len(),
+        })
+}
+
+fn path_bytes(path: &Path) -> Vec<u8> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes().to_vec()
+    }
+
+    #[cfg(not(unix))]
+    {
+        path.to_string_lossy().into_owned().into_bytes()
+    }
+}
+
+fn trim_bytes(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or_else(|| bytes.len());
+    let end = bytes
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(start, |i| i + 1);
+    &bytes[start..end]
+}
+
+/// One element of a compiled EditorConfig glob.
+#[derive(Clone, Debug, PartialEq)]
+enum GlobToken {
+    Literal(u8),
+    /// `?': any character but a slash.
+    AnyChar,
+    /// `*': any string not containing a slash.
+    Star,
+    /// `**': any string.
+    GlobStar,
+    /// `[...]' or `[!...]'.
+    Class {
+        negated: bool,
+        ranges: Vec<(u8, u8)>,
+    },
+    /// `{a,b,c}'.
+    Alternatives(Vec<Vec<GlobToken>>),
+    /// `{N..M}': an integer between N and M.
+    NumberRange(i64, i64),
+}
+
+/// Return the index of the `}' matching the `{' at START in PATTERN.
+fn matching_brace(pattern: &[u8], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = start;
+    while i < pattern.len() {
+        match pattern[i] {
+            b'\\' => i += 1,
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Split the contents of a brace expression at its top-level commas.
+fn split_alternatives(body: &[u8]) -> Vec<&[u8]> {
+    let mut alternatives = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let mut i = 0;
+    while i < body.len() {
+        match body[i] {
+            b'\\' => i += 1,
+            b'{' => depth += 1,
+            b'}' => depth -= 1,
+            b',' if depth == 0 => {
+                alternatives.push(&body[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    alternatives.push(&body[start..]);
+    alternatives
+}
+
+/// Parse BODY as the `N..M' of a numeric brace expression.
+fn parse_number_range(body: &[u8]) -> Option<(i64, i64)> {
+    let body = std::str::from_utf8(body).ok()?;
+    let mut bounds = body.splitn(2, "..");
+    let low = bounds.next()?.parse().ok()?;
+    let high = bounds.next()?.parse().ok()?;
+    Some((low, high))
+}
+
+/// Parse the bracket expression starting at START in PATTERN.  Return
+/// the token and the index just past the closing bracket, or None if
+/// the bracket is to be taken literally.
+fn parse_class(pattern: &[u8], start: usize) -> Option<(GlobToken, usize)> {
+    let mut i = start + 1;
+    let negated = i < pattern.len() && (pattern[i] == b'!' || pattern[i] == b'^');
+    if negated {
+        i += 1;
+    }
+    let mut ranges = Vec::new();
+    let mut first = true;
+    while i < pattern.len() {
+        let c = pattern[i];
+        if c == b']' && !first {
+            return Some((GlobToken::Class { negated, ranges }, i + 1));
+        }
+        // A slash can't be matched by a bracket expression.
+        if c == b'/' {
+            return None;
+        }
+        if i + 2 < pattern.len() && pattern[i + 1] == b'-' && pattern[i + 2] != b']' {
+            ranges.push((c, pattern[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+        first = false;
+    }
+    None
+}
+
+/// Compile the EditorConfig glob PATTERN.
+fn compile_glob(pattern: &[u8]) -> Vec<GlobToken> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < pattern.len() {
+        match pattern[i] {
+            b'\\' if i + 1 < pattern.len() => {
+                tokens.push(GlobToken::Literal(pattern[i + 1]));
+                i += 2;
+            }
+            b'?' => {
+                tokens.push(GlobToken::AnyChar);
+                i += 1;
+            }
+            b'*' if pattern.get(i + 1) == Some(&b'*') => {
+                tokens.push(GlobToken::GlobStar);
+                i += 2;
+            }
+            b'*' => {
+                tokens.push(GlobToken::Star);
+                i += 1;
+            }
+            b'[' => match parse_class(pattern, i) {
+                Some((token, next)) => {
+                    tokens.push(token);
+                    i = next;
+                }
+                None => {
+                    tokens.push(GlobToken::Literal(b'['));
+                    i += 1;
+                }
+            },
+            b'{' => {
+                let end = matching_brace(pattern, i);
+                let body = end.map(|end| &pattern[i + 1..end]);
+                let alternatives = body.map(split_alternatives);
+                match (end, body, alternatives) {
+                    (Some(end), Some(body), _) if parse_number_range(body).is_some() => {
+                        let (low, high) = parse_number_range(body).unwrap();
+                        tokens.push(GlobToken::NumberRange(low, high));
+                        i = end + 1;
+                    }
+                    (Some(end), _, Some(ref alternatives)) if alternatives.len() > 1 => {
+                        tokens.push(GlobToken::Alternatives(
+                            alternatives.iter().map(|alt| compile_glob(alt)).collect(),
+                        ));
+                        i = end + 1;
+                    }
+                    // `{single}' and an unbalanced brace are literal.
+                    _ => {
+                        tokens.push(GlobToken::Literal(b'{'));
+                        i += 1;
+                    }
+                }
+            }
+            c => {
+                tokens.push(GlobToken::Literal(c));
+                i += 1;
+            }
+        }
+    }
+    tokens
+}
+
+/// Return true if the compiled glob TOKENS matches all of S.
+fn glob_match(tokens: &[GlobToken], s: &[u8]) -> bool {
+    let (token, rest) = match tokens.split_first() {
+        Some(split) => split,
+        None => return s.is_empty(),
+    };
+
+    match *token {
+        GlobToken::Literal(c) => s.first() == Some(&c) && glob_match(rest, &s[1..]),
+        GlobToken::AnyChar => !s.is_empty() && s[0] != b'/' && glob_match(rest, &s[1..]),
+        GlobToken::Star => (0..=s.len())
+            .take_while(|&n| n == 0 || s[n - 1] != b'/')
+            .any(|n| glob_match(rest, &s[n..])),
+        GlobToken::GlobStar => (0..=s.len()).any(|n| glob_match(rest, &s[n..])),
+        GlobToken::Class {
+            negated,
+            ref ranges,
+        } => {
+            !s.is_empty()
+                && s[0] != b'/'
+                && ranges.iter().any(|&(lo, hi)| lo <= s[0] && s[0] <= hi) != negated
+                && glob_match(rest, &s[1..])
+        }
+        GlobToken::Alternatives(ref alternatives) => alternatives.iter().any(|alternative| {
+            let mut sequence = alternative.clone();
+            sequence.extend_from_slice(rest);
+            glob_match(&sequence, s)
+        }),
+        GlobToken::NumberRange(low, high) => {
+            let sign = if s.first() == Some(&b'-') { 1 } else { 0 };
+            let digits = s[sign..].iter().take_while(|b| b.is_ascii_digit()).count();
+            (sign + 1..=sign + digits).any(|end| {
+                let n = std::str::from_utf8(&s[..end])
+                    .ok()
+                    .and_then(|n| n.parse::<i64>().ok());
+                n.map_or(false, |n| low <= n && n <= high) && glob_match(rest, &s[end..])
+            })
+        }
+    }
+}
+
+/// Return true if the section GLOB of an `.editorconfig' file applies to
+/// the file at RELATIVE, a name relative to the directory of the
+/// `.editorconfig' file.  A glob with a slash in it is matched against
+/// the whole of RELATIVE, any other glob just against the last
+/// component, so that it applies at any depth.
+fn section_matches(glob: &[u8], relative: &[u8]) -> bool {
+    if glob.contains(&b'/') {
+        let glob = if glob.first() == Some(&b'/') {
+            &glob[1..]
+        } else {
+            glob
+        };
+        glob_match(&compile_glob(glob), relative)
+    } else {
+        let base = relative
+            .iter()
+            .rposition(|&b| b == b'/')
+            .map_or(relative, |i| &relative[i + 1..]);
+        glob_match(&compile_glob(glob), base)
+    }
+}
+
+/// The contents of an `.editorconfig' file.
+#[derive(Debug, Default, PartialEq)]
+struct EditorConfig {
+    /// Whether the preamble says `root = true', meaning that the
+    /// search for `.editorconfig' files stops here.
+    root: bool,
+    /// The sections of the file, as the glob and the properties set.
+    sections: Vec<(Vec<u8>, Vec<(String, String)>)>,
+}
+
+fn parse_editorconfig(text: &[u8]) -> EditorConfig {
+    let text = if text.starts_with(b"\xef\xbb\xbf") {
+        &text[3..]
+    } else {
+        text
+    };
+    let mut config = EditorConfig::default();
+
+    for line in text.split(|&b| b == b'\n') {
+        let line = trim_bytes(line);
+        if line.is_empty() || line[0] == b'#' || line[0] == b';' {
+            continue;
+        }
+        if line[0] == b'[' {
+            if let Some(end) = line.iter().rposition(|&b| b == b']') {
+                config.sections.push((line[1..end].to_vec(), Vec::new()));
+            }
+            continue;
+        }
+        if let Some(eq) = line.iter().position(|&b| b == b'=') {
+            let key = String::from_utf8_lossy(trim_bytes(&line[..eq])).to_lowercase();
+            let value = String::from_utf8_lossy(trim_bytes(&line[eq + 1..])).into_owned();
+            match config.sections.last_mut() {
+                Some(section) => section.1.push((key, value)),
+                None => {
+                    if key == "root" {
+                        config.root = value.eq_ignore_ascii_case("true");
+                    }
+                }
+            }
+        }
+    }
+    config
+}
+
+/// Set KEY to VALUE in PROPERTIES.  The value `unset' removes KEY.
+fn set_property(properties: &mut Vec<(String, String)>, key: &str, value: &str) {
+    properties.retain(|&(ref k, _)| k != key);
+    if !value.eq_ignore_ascii_case("unset") {
+        properties.push((key.to_string(), value.to_string()));
+    }
+}
+
+struct CachedEditorConfig {
+    stamp: FileStamp,
+    config: EditorConfig,
+}
+
+lazy_static! {
+    static ref EDITORCONFIG_CACHE: Mutex<HashMap<PathBuf, CachedEditorConfig>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Collect the EditorConfig properties that apply to FILE.  Return the
+/// directory of the nearest `.editorconfig' file, if there is any, and
+/// the properties in effect.
+fn editorconfig_properties(file: &Path) -> (Option<PathBuf>, Vec<(String, String)>) {
+    let mut cache = EDITORCONFIG_CACHE.lock().unwrap();
+    let mut applicable = Vec::new();
+
+    for dir in file.ancestors().skip(1) {
+        let config_file = dir.join(".editorconfig");
+        let stamp = match file_stamp(&config_file) {
+            Some(stamp) => stamp,
+            None => {
+                cache.remove(&config_file);
+                continue;
+            }
+        };
+        let fresh = cache
+            .get(&config_file)
+            .map_or(false, |cached| cached.stamp == stamp);
+        if !fresh {
+            match fs::read(&config_file) {
+                Ok(text) => {
+                    let config = parse_editorconfig(&text);
+                    cache.insert(config_file.clone(), CachedEditorConfig { stamp, config });
+                }
+                Err(_) => {
+                    cache.remove(&config_file);
+                    continue;
+                }
+            }
+        }
+        let root = cache[&config_file].config.root;
+        applicable.push((dir.to_path_buf(), config_file));
+        if root {
+            break;
+        }
+    }
+
+    // Files closer to FILE take precedence, so apply them last.
+    let mut properties = Vec::new();
+    for &(ref dir, ref config_file) in applicable.iter().rev() {
+        let relative = match file.strip_prefix(dir) {
+            Ok(relative) => path_bytes(relative),
+            Err(_) => continue,
+        };
+        for &(ref glob, ref section) in &cache[config_file].config.sections {
+            if section_matches(glob, &relative) {
+                for &(ref key, ref value) in section {
+                    set_property(&mut properties, key, value);
+                }
+            }
+        }
+    }
+
+    (applicable.first().map(|(dir, _)| dir.clone()), properties)
+}
+
+/// The value of a variable set from an EditorConfig property.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Setting {
+    Flag(bool),
+    Number(EmacsInt),
+}
+
+impl From<Setting> for LispObject {
+    fn from(setting: Setting) -> Self {
+        match setting {
+            Setting::Flag(true) => Qt,
+            Setting::Flag(false) => Qnil,
+            Setting::Number(n) => LispObject::from(n),
+        }
+    }
+}
+
+/// Translate EditorConfig PROPERTIES to the Emacs variables they
+/// correspond to.  Properties that have no counterpart among the
+/// variables that are safe as file-local variables are ignored.
+fn editorconfig_variables(properties: &[(String, String)]) -> Vec<(&'static str, Setting)> {
+    let get = |key: &str| {
+        properties
+            .iter()
+            .find(|&&(ref k, _)| k == key)
+            .map(|&(_, ref v)| v.to_ascii_lowercase())
+    };
+    let number = |key: &str| get(key).and_then(|v| v.parse::<EmacsInt>().ok());
+    let mut variables = Vec::new();
+
+    match get("indent_style").as_ref().map(String::as_str) {
+        Some("tab") => variables.push(("indent-tabs-mode", Setting::Flag(true))),
+        Some("space") => variables.push(("indent-tabs-mode", Setting::Flag(false))),
+        _ => {}
+    }
+
+    let tab_width = number("tab_width");
+    let indent_size = if get("indent_size").as_ref().map(String::as_str) == Some("tab") {
+        tab_width
+    } else {
+        number("indent_size")
+    };
+    if let Some(width) = tab_width.or(indent_size) {
+        variables.push(("tab-width", Setting::Number(width)));
+    }
+    if let Some(size) = indent_size {
+        variables.push(("standard-indent", Setting::Number(size)));
+    }
+    if let Some(column) = number("max_line_length") {
+        variables.push(("fill-column", Setting::Number(column)));
+    }
+
+    variables
+}
+
+/// Return true if SOURCE holds no Lisp form at all, just whitespace and
+/// comments.
+fn is_blank_lisp_source(source: &[u8]) -> bool {
+    let mut in_comment = false;
+    source.iter().all(|&b| match b {
+        b'\n' => {
+            in_comment = false;
+            true
+        }
+        b';' => {
+            in_comment = true;
+            true
+        }
+        _ => in_comment || b.is_ascii_whitespace(),
+    })
+}
+
+/// Parsed `.dir-locals.el' files, keyed by directory name.  Each value
+/// is a cons of the stamps of the files read and the merged contents.
+declare_GC_protected_static!(dir_locals_cache, Qnil);
+
+fn dir_locals_cache_table() -> LispHashTableRef {
+    unsafe {
+        if dir_locals_cache.is_nil() {
+            dir_locals_cache = call!(intern("make-hash-table").as_lisp_obj(), QCtest, Qequal);
+        }
+        dir_locals_cache.as_hash_table_or_error()
+    }
+}
+
+/// Return the dir-locals files in DIR that exist, with their stamps, in
+/// increasing order of priority: `dir-locals-file' and then its `-2'
+/// variant, as `dir-locals--all-files' does.
+fn dir_locals_files(dir: LispObject) -> Vec<(PathBuf, FileStamp)> {
+    let name = unsafe { find_symbol_value(intern("dir-locals-file").as_lisp_obj()) };
+    let name = if name.is_string() {
+        name
+    } else {
+        LispObject::from(".dir-locals.el")
+    };
+    let file_1 = lisp_file_name_to_path(unsafe { Fexpand_file_name(name, dir) });
+    let file_2 = match (file_1.file_stem(), file_1.extension()) {
+        (Some(stem), Some(ext)) if ext == "el" => {
+            Some(file_1.with_file_name(format!("{}-2.el", stem.to_string_lossy())))
+        }
+        _ => None,
+    };
+
+    Some(file_1)
+        .into_iter()
+        .chain(file_2)
+        .filter_map(|file| file_stamp(&file).map(|stamp| (file, stamp)))
+        .collect()
+}
+
+/// Return the stamps of FILES as a list of integers that can be compared
+/// with `equal'.
+fn stamps_to_lisp(files: &[(PathBuf, FileStamp)]) -> LispObject {
+    let mut stamps = Vec::new();
+    for &(_, stamp) in files {
+        let since_epoch = stamp
+            .modified
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        stamps.push(LispObject::from(since_epoch.as_secs() as EmacsInt));
+        stamps.push(LispObject::from(EmacsInt::from(since_epoch.subsec_nanos())));
+        stamps.push(LispObject::from(stamp.len as EmacsInt));
+    }
+    LispObject::from(stamps)
+}
+
+/// Merge the alists OLD and NEW, giving precedence to NEW, like
+/// `map-merge' does.
+fn merge_alists(old: LispObject, new: LispObject) -> LispObject {
+    let mut merged: Vec<LispObject> = old
+        .iter_cars_safe()
+        .filter(|&entry| assoc(car_safe(entry), new, Qnil).is_nil())
+        .collect();
+    merged.extend(new.iter_cars_safe());
+    LispObject::from(merged)
+}
+
+/// Merge the contents NEW of a dir-locals file into OLD.  The variables
+/// given for the same mode or subdirectory are merged, so that a
+/// `.dir-locals-2.el' can override single variables.
+fn merge_dir_locals(old: LispObject, new: LispObject) -> LispObject {
+    let mut merged: Vec<LispObject> = old
+        .iter_cars_safe()
+        .map(|entry| {
+            let key = car_safe(entry);
+            let update = assoc(key, new, Qnil);
+            if update.is_nil() {
+                entry
+            } else {
+                LispObject::cons(key, merge_alists(cdr_safe(entry), cdr_safe(update)))
+            }
+        })
+        .collect();
+    merged.extend(
+        new.iter_cars_safe()
+            .filter(|&entry| assoc(car_safe(entry), old, Qnil).is_nil()),
+    );
+    LispObject::from(merged)
+}
+
+/// Return the merged contents of the dir-locals files in DIR, or None if
+/// there are none.  The files are only read if they changed since the
+/// last call.
+fn read_dir_locals(dir: LispObject) -> Option<LispObject> {
+    let files = dir_locals_files(dir);
+    if files.is_empty() {
+        return None;
+    }
+
+    let stamps = stamps_to_lisp(&files);
+    let cache = dir_locals_cache_table();
+    if let Some(cached) = gethash(dir, cache, Qnil).as_cons() {
+        if cached.car().equal(stamps) {
+            return Some(cached.cdr());
+        }
+    }
+
+    let count = c_specpdl_index();
+    unsafe { specbind(intern("read-circle").as_lisp_obj(), Qnil) };
+
+    let mut variables = Qnil;
+    for &(ref file, _) in &files {
+        let source = match fs::read(file) {
+            Ok(source) => source,
+            Err(_) => continue,
+        };
+        if is_blank_lisp_source(&source) {
+            continue;
+        }
+        let text = unsafe {
+            code_convert_string_norecord(
+                make_unibyte_string(source.as_ptr() as *const c_char, source.len() as ptrdiff_t),
+                Qundecided,
+                false,
+            )
+        };
+        variables = merge_dir_locals(variables, read(text));
+    }
+
+    unbind_to(count, Qnil);
+    puthash(dir, LispObject::cons(stamps, variables), cache);
+    Some(variables)
+}
+
+/// Return the nearest directory from DIR upwards that has dir-locals
+/// files, and their merged contents.
+fn find_dir_locals(mut dir: LispObject) -> Option<(LispObject, LispObject)> {
+    loop {
+        if let Some(class_variables) = read_dir_locals(dir) {
+            return Some((dir, class_variables));
+        }
+        let parent = unsafe { Ffile_name_directory(Fdirectory_file_name(dir)) };
+        if parent.is_nil() || parent.equal(dir) {
+            return None;
+        }
+        dir = parent;
+    }
+}
+
+/// Add the variables of MODE-VARIABLES to VARIABLES, except for the
+/// entry SKIP.  This is `dir-locals-collect-mode-variables'.
+fn collect_mode_variables(
+    mode_variables: LispObject,
+    skip: LispObject,
+    variables: &mut Vec<(LispObject, LispObject)>,
+) {
+    let pseudo_variables = [intern("mode").as_lisp_obj(), intern("eval").as_lisp_obj()];
+
+    for pair in mode_variables.iter_cars_safe() {
+        let (variable, value) = match pair.as_cons() {
+            Some(ref cons) if pair != skip => (cons.car(), cons.cdr()),
+            _ => continue,
+        };
+        // If a variable is given more than once, the last one wins, but
+        // `mode' and `eval' accumulate.
+        let slot = variables.iter().rposition(|&(var, _)| var == variable);
+        match slot {
+            Some(i) if !pseudo_variables.contains(&variable) => variables[i].1 = value,
+            _ => variables.push((variable, value)),
+        }
+    }
+}
+
+/// Add the entries of CLASS-VARIABLES that apply to a buffer in MODE to
+/// VARIABLES.  SUB-FILE-NAME is the name of the file relative to the
+/// directory of the dir-locals files, and IN-ROOT says whether it is in
+/// that directory itself.  This is `dir-locals-collect-variables'.
+fn collect_variables(
+    class_variables: LispObject,
+    sub_file_name: Option<&[u8]>,
+    mode: LispObject,
+    in_root: bool,
+    variables: &mut Vec<(LispObject, LispObject)>,
+) {
+    for entry in class_variables.iter_cars_safe() {
+        let (key, alist) = match entry.as_cons() {
+            Some(cons) => (cons.car(), cons.cdr()),
+            None => continue,
+        };
+
+        if let Some(prefix) = key.as_string() {
+            if sub_file_name.map_or(false, |sub| sub.starts_with(prefix.as_slice())) {
+                collect_variables(alist, sub_file_name, mode, in_root, variables);
+            }
+        } else if key.is_nil()
+            || call!(intern("provided-mode-derived-p").as_lisp_obj(), mode, key).is_not_nil()
+        {
+            let subdirs = assq(intern("subdirs").as_lisp_obj(), alist);
+            if subdirs.is_nil() || cdr_safe(subdirs).is_not_nil() || in_root {
+                collect_mode_variables(alist, subdirs, variables);
+            }
+        }
+    }
+}
+
+/// Return the directory-local variables for FILE in major mode MODE.
+/// The value has the form (DIR . VARIABLES), where DIR is the directory
+/// the variables come from and VARIABLES is an alist in the format of
+/// `dir-local-variables-alist'.  The value is nil if there are no
+/// directory-local variables for FILE.
+///
+/// FILE may also be a directory name, for a buffer not visiting a file.
+/// The variables are taken from the nearest directory from there upwards
+/// that has a `dir-locals-file'.  Their parsed contents are cached, and
+/// the files are only read again once they change.
+///
+/// If EDITORCONFIG is non-nil, `.editorconfig' files are consulted as
+/// well, and the properties that correspond to Emacs variables are
+/// translated to them: `indent_style' to `indent-tabs-mode', `tab_width'
+/// to `tab-width', `indent_size' to `standard-indent' and
+/// `max_line_length' to `fill-column'.  Variables from a dir-locals file
+/// take precedence over those from `.editorconfig' files.
+///
+/// Files accessed through a file name handler are not searched.
+#[lisp_fn(min = "2")]
+pub fn dir_locals_resolve(file: LispStringRef, mode: LispObject, editorconfig: bool) -> LispObject {
+    let file = unsafe { Fexpand_file_name(file.as_lisp_obj(), Qnil) };
+    if unsafe { Ffind_file_name_handler(file, Qfile_exists_p) }.is_not_nil() {
+        return Qnil;
+    }
+    let file_dir = unsafe { Ffile_name_directory(file) };
+    let mut variables = Vec::new();
+    let mut root = Qnil;
+
+    if editorconfig {
+        let (nearest, properties) = editorconfig_properties(&lisp_file_name_to_path(file));
+        if let Some(dir) = nearest {
+            root = unsafe { Ffile_name_as_directory(path_to_lisp_file_name(&dir)) };
+            variables.extend(
+                editorconfig_variables(&properties)
+                    .into_iter()
+                    .map(|(name, setting)| (intern(name).as_lisp_obj(), setting.into())),
+            );
+        }
+    }
+
+    if let Some((dir, class_variables)) = find_dir_locals(file_dir) {
+        let file_name = file.as_string_or_error();
+        let dir_name = dir.as_string_or_error();
+        let sub_file_name = if file_name.as_slice().starts_with(dir_name.as_slice()) {
+            Some(&file_name.as_slice()[dir_name.len_bytes() as usize..])
+        } else {
+            None
+        };
+        collect_variables(
+            class_variables,
+            sub_file_name,
+            mode,
+            dir.equal(file_dir),
+            &mut variables,
+        );
+        root = dir;
+    }
+
+    if root.is_nil() {
+        return Qnil;
+    }
+    // The last variable collected comes first, as in
+    // `dir-locals-collect-variables'.
+    let alist: Vec<LispObject> = variables
+        .into_iter()
+        .rev()
+        .map(|(variable, value)| LispObject::cons(variable, value))
+        .collect();
+    LispObject::cons(root, LispObject::from(alist))
+}
+
+include!(concat!(env!("OUT_DIR"), "/dirlocals_exports.rs"));
+
+#[test]
+fn test_glob_match() {
+    let matches = |glob: &[u8], s: &[u8]| glob_match(&compile_glob(glob), s);
+    assert!(matches(b"*.rs", b"lib.rs"));
+    assert!(!matches(b"*.rs", b"src/lib.rs"));
+    assert!(matches(b"**.rs", b"src/lib.rs"));
+    assert!(matches(b"lib/**/*.js", b"lib/a/b/c.js"));
+    assert!(matches(b"?.c", b"a.c"));
+    assert!(!matches(b"?.c", b"ab.c"));
+    assert!(matches(b"[abc].c", b"b.c"));
+    assert!(!matches(b"[!abc].c", b"b.c"));
+    assert!(matches(b"[a-z]1", b"q1"));
+    assert!(matches(b"*.{js,py}", b"x.py"));
+    assert!(!matches(b"*.{js,py}", b"x.rb"));
+    assert!(matches(b"{single}.c", b"{single}.c"));
+    assert!(matches(b"file{3..12}", b"file7"));
+    assert!(matches(b"file{3..12}", b"file12"));
+    assert!(!matches(b"file{3..12}", b"file13"));
+    assert!(matches(b"a\\*b", b"a*b"));
+    assert!(!matches(b"a\\*b", b"axb"));
+}
+
+#[test]
+fn test_section_matches() {
+    assert!(section_matches(b"*.md", b"doc/README.md"));
+    assert!(section_matches(b"/doc/*.md", b"doc/README.md"));
+    assert!(section_matches(b"doc/*.md", b"doc/README.md"));
+    assert!(!section_matches(b"doc/*.md", b"src/doc/README.md"));
+    assert!(section_matches(b"Makefile", b"src/Makefile"));
+}
+
+#[test]
+fn test_parse_editorconfig() {
+    let config = parse_editorconfig(
+        b"# top\nroot = true\n\n[*]\nindent_style = space\r\nIndent_Size=4\n; note\n[Makefile]\nindent_style = tab\n",
+    );
+    assert!(config.root);
+    assert_eq!(
+        config.sections,
+        vec![
+            (
+                b"*".to_vec(),
+                vec![
+                    ("indent_style".to_string(), "space".to_string()),
+                    ("indent_size".to_string(), "4".to_string()),
+                ]
+            ),
+            (
+                b"Makefile".to_vec(),
+                vec![("indent_style".to_string(), "tab".to_string())]
+            ),
+        ]
+    );
+    assert!(!parse_editorconfig(b"[*]\nroot = true\n").root);
+}
+
+#[test]
+fn test_editorconfig_variables() {
+    let properties = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|&(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    };
+
+    assert_eq!(
+        editorconfig_variables(&properties(&[
+            ("indent_style", "Tab"),
+            ("indent_size", "8")
+        ])),
+        vec![
+            ("indent-tabs-mode", Setting::Flag(true)),
+            ("tab-width", Setting::Number(8)),
+            ("standard-indent", Setting::Number(8)),
+        ]
+    );
+    assert_eq!(
+        editorconfig_variables(&properties(&[
+            ("indent_size", "tab"),
+            ("tab_width", "4"),
+            ("max_line_length", "off"),
+        ])),
+        vec![
+            ("tab-width", Setting::Number(4)),
+            ("standard-indent", Setting::Number(4)),
+        ]
+    );
+
+    let mut merged = properties(&[("indent_size", "2"), ("tab_width", "8")]);
+    set_property(&mut merged, "indent_size", "unset");
+    assert_eq!(merged, properties(&[("tab_width", "8")]));
+}
+
+#[test]
+fn test_is_blank_lisp_source() {
+    assert!(is_blank_lisp_source(b""));
+    assert!(is_blank_lisp_source(b";;; .dir-locals.el\n\n  ; nothing\n"));
+    assert!(!is_blank_lisp_source(b"; comment\n((nil . ((a . 1))))"));
+}
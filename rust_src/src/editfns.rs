@@ -2,6 +2,7 @@
 
 use std;
 use std::ptr;
+use std::time::{Duration, Instant};
 
 use libc;
 use libc::{c_int, c_uchar, ptrdiff_t};
@@ -9,37 +10,47 @@ use libc::{c_int, c_uchar, ptrdiff_t};
 use remacs_macros::lisp_fn;
 
 use crate::{
-    buffers::{current_buffer, validate_region},
+    buffers::{current_buffer, validate_region, BEG_BYTE},
     buffers::{LispBufferOrCurrent, LispBufferOrName, LispBufferRef, BUF_BYTES_MAX},
     character::{char_head_p, dec_pos},
+    chartable::LispCharTableRef,
+    diff::Differ,
     eval::{progn, unbind_to},
+    fns::concat,
     lisp::{defsubr, LispObject},
     marker::{
         buf_bytepos_to_charpos, buf_charpos_to_bytepos, marker_position_lisp, point_marker,
         set_point_from_marker,
     },
+    msgcat::translate_format,
     multibyte::{
-        is_single_byte_char, multibyte_char_at, raw_byte_codepoint, unibyte_to_char,
+        char_to_byte8, is_single_byte_char, multibyte_char_at, raw_byte_codepoint, unibyte_to_char,
         write_codepoint, MAX_MULTIBYTE_LENGTH,
     },
     multibyte::{Codepoint, LispStringRef},
-    numbers::LispNumber,
+    numbers::{IsLispNatnum, LispNumber},
     remacs_sys::EmacsInt,
     remacs_sys::{
         buffer_overflow, build_string, current_message, del_range, del_range_1, downcase,
         find_before_next_newline, find_newline, get_char_property_and_overlay, globals, insert,
         insert_and_inherit, insert_from_buffer, make_buffer_string, make_buffer_string_both,
         make_save_obj_obj_obj_obj, make_string_from_bytes, maybe_quit, message1, message3,
-        record_unwind_current_buffer, record_unwind_protect, save_excursion_restore,
-        save_restriction_restore, save_restriction_save, scan_newline_from_point,
-        set_buffer_internal_1, set_point, set_point_both, styled_format, update_buffer_properties,
+        modify_text, prepare_to_modify_buffer, record_change, record_unwind_current_buffer,
+        record_unwind_protect, replace_range, save_excursion_restore, save_restriction_restore,
+        save_restriction_save, scan_newline_from_point, set_buffer_internal_1, set_point,
+        set_point_both, signal_after_change, specbind, string_make_unibyte, styled_format,
+        update_buffer_properties, update_compositions, CHECK_HEAD, CHECK_INSIDE, CHECK_TAIL,
         STRING_BYTES,
     },
     remacs_sys::{
         Fadd_text_properties, Fcopy_sequence, Fget_pos_property, Fnext_single_char_property_change,
-        Fprevious_single_char_property_change, Fx_popup_dialog,
+        Fprevious_single_char_property_change, Fundo_boundary, Fx_popup_dialog,
     },
-    remacs_sys::{Qboundary, Qfield, Qinteger_or_marker_p, Qmark_inactive, Qnil, Qt},
+    remacs_sys::{
+        Qboundary, Qfield, Qinhibit_modification_hooks, Qinteger_or_marker_p, Qmark_inactive, Qnil,
+        Qt, Qtranslation_table,
+    },
+    shell::make_lisp_string,
     textprop::get_char_property,
     threads::{c_specpdl_index, ThreadState},
     util::clip_to_bounds,
@@ -1061,9 +1072,13 @@ pub fn format(args: &mut [LispObject]) -> LispObject {
 /// and right quote replacement characters are specified by
 /// `text-quoting-style'.
 ///
+/// If `translate-messages' is non-nil, STRING is first replaced by its
+/// translation; see `translate-message'.
+///
 /// usage: (format-message STRING &rest OBJECTS)
 #[lisp_fn(min = "1")]
 pub fn format_message(args: &mut [LispObject]) -> LispObject {
+    args[0] = translate_format(args[0]);
     unsafe {
         styled_format(
             args.len() as isize,
@@ -1393,4 +1408,456 @@ pub fn delete_and_extract_region(
     }
 }
 
+/// How `translate-region-internal' maps characters.
+enum TranslationTable {
+    /// A unibyte string: the Nth byte is the mapping for the character
+    /// with code N.
+    Bytes(Vec<u8>),
+    /// A multibyte string: the Nth character is the mapping for the
+    /// character with code N.
+    Chars(Vec<Codepoint>),
+    CharTable(LispCharTableRef),
+}
+
+/// What a stretch of text is translated to.
+enum TranslationTarget {
+    /// One character, in the representation of the buffer.
+    Char(Vec<u8>),
+    /// A character or a vector of characters from a char-table.  It stays
+    /// reachable through the table while the buffer is changed.
+    Value(LispObject),
+}
+
+/// A change found by `translate-region-internal'.
+struct Translation {
+    pos: ptrdiff_t,
+    pos_byte: ptrdiff_t,
+    /// The number of characters and bytes to replace.
+    nchars: ptrdiff_t,
+    nbytes: usize,
+    to: TranslationTarget,
+}
+
+/// Return the first character of TEXT and its length in bytes.
+fn char_in_text(text: &[u8], multibyte: bool) -> (Codepoint, usize) {
+    if multibyte {
+        multibyte_char_at(text)
+    } else {
+        (Codepoint::from(text[0]), 1)
+    }
+}
+
+/// Return character C in the representation of a buffer whose
+/// multibyteness is MULTIBYTE.
+fn char_bytes(c: Codepoint, multibyte: bool) -> Vec<u8> {
+    if multibyte {
+        let mut buf = [0_u8; MAX_MULTIBYTE_LENGTH];
+        let len = write_codepoint(&mut buf, c);
+        buf[..len].to_vec()
+    } else {
+        vec![char_to_byte8(c)]
+    }
+}
+
+/// VAL is a list (([FROM-CHAR ...] . TO) ...).  If TEXT starts with the
+/// FROM-CHARs of an element, return their number and the element's TO.
+/// Only the first CHARS_LEFT characters of TEXT are considered.
+fn match_translation(
+    val: LispObject,
+    text: &[u8],
+    chars_left: ptrdiff_t,
+    multibyte: bool,
+) -> Option<(ptrdiff_t, LispObject)> {
+    let mut chars: Vec<Codepoint> = Vec::new();
+    let mut offset = 0;
+    for elt in val.iter_cars_safe() {
+        let (from, to) = match elt.as_cons() {
+            Some(cons) => cons.as_tuple(),
+            None => continue,
+        };
+        let from = match from.as_vector() {
+            Some(from) if from.len() > 0 && from.len() as ptrdiff_t <= chars_left => from,
+            _ => continue,
+        };
+        let matched = (0..from.len()).all(|i| {
+            while chars.len() <= i {
+                let (c, len) = char_in_text(&text[offset..], multibyte);
+                chars.push(c);
+                offset += len;
+            }
+            from.get(i).as_fixnum() == Some(EmacsInt::from(chars[i]))
+        });
+        if matched {
+            return Some((from.len() as ptrdiff_t, to));
+        }
+    }
+    None
+}
+
+impl TranslationTable {
+    fn new(table: LispObject, multibyte: bool) -> Self {
+        if let Some(char_table) = table.as_char_table() {
+            if char_table.purpose != Qtranslation_table {
+                error!("Not a translation table");
+            }
+            return TranslationTable::CharTable(char_table);
+        }
+
+        let mut string = table.as_string_or_error();
+        if !multibyte && string.len_chars() < string.len_bytes() {
+            string = unsafe { string_make_unibyte(table) }.as_string_or_error();
+        }
+        if string.len_chars() < string.len_bytes() {
+            let mut chars = Vec::new();
+            let mut bytes = string.as_slice();
+            while !bytes.is_empty() {
+                let (c, len) = multibyte_char_at(bytes);
+                chars.push(c);
+                bytes = &bytes[len..];
+            }
+            TranslationTable::Chars(chars)
+        } else {
+            TranslationTable::Bytes(string.as_slice().to_vec())
+        }
+    }
+
+    /// Return how the text at the start of TEXT, which begins with the
+    /// character C, is translated, as the number of characters to replace
+    /// and what to replace them with.
+    fn lookup(
+        &self,
+        c: Codepoint,
+        text: &[u8],
+        chars_left: ptrdiff_t,
+        multibyte: bool,
+    ) -> Option<(ptrdiff_t, TranslationTarget)> {
+        match *self {
+            TranslationTable::Bytes(ref bytes) => {
+                let nc = Codepoint::from(*bytes.get(c as usize)?);
+                if nc == c {
+                    None
+                } else if multibyte {
+                    Some((
+                        1,
+                        TranslationTarget::Char(char_bytes(unibyte_to_char(nc), true)),
+                    ))
+                } else {
+                    Some((1, TranslationTarget::Char(vec![nc as u8])))
+                }
+            }
+            TranslationTable::Chars(ref chars) => {
+                let nc = *chars.get(c as usize)?;
+                if nc == c {
+                    None
+                } else {
+                    Some((1, TranslationTarget::Char(char_bytes(nc, multibyte))))
+                }
+            }
+            TranslationTable::CharTable(char_table) => {
+                let val = char_table.get(c as isize);
+                if val.is_character() {
+                    let nc = val.as_fixnum_or_error() as Codepoint;
+                    if nc == c {
+                        None
+                    } else {
+                        Some((1, TranslationTarget::Char(char_bytes(nc, multibyte))))
+                    }
+                } else if val.is_vector() {
+                    Some((1, TranslationTarget::Value(val)))
+                } else if val.is_cons() {
+                    match_translation(val, text, chars_left, multibyte)
+                        .map(|(nchars, to)| (nchars, TranslationTarget::Value(to)))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Overwrite the text of BUFFER at byte position POS_BYTE with BYTES.
+unsafe fn overwrite_buffer_bytes(buffer: LispBufferRef, pos_byte: ptrdiff_t, bytes: &[u8]) {
+    let gap = if pos_byte >= buffer.gpt_byte() {
+        buffer.gap_size()
+    } else {
+        0
+    };
+    let addr = buffer.beg_addr().offset(pos_byte - BEG_BYTE + gap);
+    ptr::copy_nonoverlapping(bytes.as_ptr(), addr, bytes.len());
+}
+
+/// Internal use only.
+/// From START to END, translate characters according to TABLE.
+/// TABLE is a string or a char-table; the Nth character in it is the
+/// mapping for the character with code N.
+/// It returns the number of characters changed.
+#[lisp_fn]
+pub fn translate_region_internal(
+    mut start: LispObject,
+    mut end: LispObject,
+    table: LispObject,
+) -> EmacsInt {
+    unsafe { validate_region(&mut start, &mut end) };
+    let mut cur_buf = ThreadState::current_buffer();
+    let multibyte = cur_buf.multibyte_characters_enabled();
+    let table = TranslationTable::new(table, multibyte);
+
+    // Find all the changes first, so that the buffer is modified, and
+    // the change hooks are run, once for the whole region when every
+    // character is replaced by one of the same length.
+    let start = start.as_fixnum_or_error() as ptrdiff_t;
+    let end = end.as_fixnum_or_error() as ptrdiff_t;
+    let (start_byte, end_byte) = unsafe {
+        (
+            buf_charpos_to_bytepos(cur_buf.as_mut(), start),
+            buf_charpos_to_bytepos(cur_buf.as_mut(), end),
+        )
+    };
+    let text = cur_buf.bytes_between(start_byte, end_byte);
+
+    let mut translations = Vec::new();
+    let (mut pos, mut offset) = (start, 0);
+    while pos < end {
+        let (c, len) = char_in_text(&text[offset..], multibyte);
+        match table.lookup(c, &text[offset..], end - pos, multibyte) {
+            Some((nchars, to)) => {
+                let nbytes = (0..nchars).fold(0, |nbytes, _| {
+                    nbytes + char_in_text(&text[offset + nbytes..], multibyte).1
+                });
+                translations.push(Translation {
+                    pos,
+                    pos_byte: start_byte + offset as ptrdiff_t,
+                    nchars,
+                    nbytes,
+                    to,
+                });
+                pos += nchars;
+                offset += nbytes;
+            }
+            None => {
+                pos += 1;
+                offset += len;
+            }
+        }
+    }
+
+    let (first, last) = match (translations.first(), translations.last()) {
+        (Some(first), Some(last)) => (first.pos, last.pos + last.nchars),
+        _ => return 0,
+    };
+    let in_place = translations.iter().all(|t| match t.to {
+        TranslationTarget::Char(ref bytes) => bytes.len() == t.nbytes,
+        TranslationTarget::Value(_) => false,
+    });
+    let check_border = (CHECK_HEAD | CHECK_TAIL) as c_int;
+
+    if in_place {
+        unsafe {
+            modify_text(first, last);
+            record_change(first, last - first);
+            for t in &translations {
+                if let TranslationTarget::Char(ref bytes) = t.to {
+                    overwrite_buffer_bytes(cur_buf, t.pos_byte, bytes);
+                }
+            }
+            signal_after_change(first, last - first, last - first);
+            update_compositions(first, last, check_border);
+        }
+        return translations.len() as EmacsInt;
+    }
+
+    // Some replacements change the length of the text.  Make them from
+    // the end, so that the positions of the earlier ones stay valid.
+    let mut count = 0;
+    unsafe { modify_text(start, end) };
+    for t in translations.iter().rev() {
+        match t.to {
+            TranslationTarget::Char(ref bytes) if bytes.len() == t.nbytes => unsafe {
+                record_change(t.pos, 1);
+                overwrite_buffer_bytes(cur_buf, t.pos_byte, bytes);
+                signal_after_change(t.pos, 1, 1);
+                update_compositions(t.pos, t.pos + 1, check_border);
+                count += 1;
+            },
+            TranslationTarget::Char(ref bytes) => {
+                let string = make_lisp_string(bytes, true);
+                unsafe { replace_range(t.pos, t.pos + 1, string, true, false, true, false) };
+                count += 1;
+            }
+            TranslationTarget::Value(to) => {
+                let string = if to.is_vector() {
+                    concat(&mut [to])
+                } else {
+                    concat(&mut [list!(to)])
+                };
+                unsafe { replace_range(t.pos, t.pos + t.nchars, string, true, false, true, false) };
+                count += string.as_string_or_error().len_chars() as EmacsInt;
+            }
+        }
+    }
+    count
+}
+
+/// The default limit of `replace-buffer-contents' on the cost of the
+/// comparison, past which a faster, less accurate one is used.
+const REPLACE_MAX_COSTS: EmacsInt = 1_000_000;
+
+/// Return the characters of BUFFER between the byte positions START and
+/// END.  The bytes of a unibyte buffer are converted to multibyte
+/// characters.
+pub(crate) fn buffer_chars(
+    buffer: LispBufferRef,
+    start: ptrdiff_t,
+    end: ptrdiff_t,
+) -> Vec<Codepoint> {
+    let multibyte = buffer.multibyte_characters_enabled();
+    let text = buffer.bytes_between(start, end);
+    let mut chars = Vec::with_capacity(text.len());
+    let mut offset = 0;
+    while offset < text.len() {
+        let (c, len) = char_in_text(&text[offset..], multibyte);
+        chars.push(if multibyte { c } else { unibyte_to_char(c) });
+        offset += len;
+    }
+    chars
+}
+
+/// Return the characters of the accessible portion of BUFFER.
+fn accessible_chars(buffer: LispBufferRef) -> Vec<Codepoint> {
+    buffer_chars(buffer, buffer.begv_byte, buffer.zv_byte)
+}
+
+/// Replace accessible portion of current buffer with that of SOURCE.
+/// SOURCE can be a buffer or a string that names a buffer.
+/// Interactively, prompt for SOURCE.
+///
+/// As far as possible the replacement is non-destructive, i.e. existing
+/// buffer contents, markers, properties, and overlays in the current
+/// buffer stay intact.
+///
+/// Because this function can be very slow if there is a large number of
+/// differences between the two buffers, there are two optional arguments
+/// mitigating this issue.
+///
+/// The MAX-SECS argument, if given, defines a hard limit on the time used
+/// for comparing the buffers.  If it takes longer than MAX-SECS, the
+/// function falls back to a plain `delete-region' and
+/// `insert-buffer-substring'.  (Note that the checks are not performed
+/// too evenly over time, so in some cases it may run a bit longer than
+/// allowed).
+///
+/// The optional argument MAX-COSTS defines the quality of the difference
+/// computation.  If the actual costs exceed this limit, heuristics are
+/// used to provide a faster but suboptimal solution.  The default value
+/// is 1000000.
+///
+/// The modification hooks are run once for the whole accessible portion,
+/// not for each change, unless `inhibit-modification-hooks' is non-nil.
+///
+/// This function returns t if a non-destructive replacement could be
+/// performed.  Otherwise, i.e., if MAX-SECS was exceeded, it returns
+/// nil.
+#[lisp_fn(min = "1", intspec = "bSource buffer: ")]
+pub fn replace_buffer_contents(
+    source: LispBufferOrName,
+    max_secs: Option<LispNumber>,
+    max_costs: Option<EmacsInt>,
+) -> bool {
+    let source_buf = LispBufferRef::from(source)
+        .as_live()
+        .unwrap_or_else(|| error!("Selecting deleted buffer"));
+    let cur_buf = ThreadState::current_buffer();
+    if source_buf == cur_buf {
+        error!("Cannot replace a buffer with itself");
+    }
+    let source = LispBufferOrName::Buffer(source_buf.as_lisp_obj());
+
+    let max_costs = max_costs.unwrap_or(REPLACE_MAX_COSTS);
+    max_costs.check_natnum();
+    let deadline = max_secs.map(|secs| {
+        let secs = match secs {
+            LispNumber::Fixnum(n) => n as f64,
+            LispNumber::Float(f) => f,
+        };
+        Instant::now() + Duration::from_millis((secs.max(0.0) * 1000.0) as u64)
+    });
+
+    let min_a = cur_buf.begv;
+    let min_b = source_buf.begv;
+    let size_a = cur_buf.zv - min_a;
+    let size_b = source_buf.zv - min_b;
+
+    // Handle trivial cases where at least one accessible portion is
+    // empty.
+    if size_a == 0 && size_b == 0 {
+        return true;
+    }
+    if size_a == 0 {
+        insert_buffer_substring(source, None, None);
+        return true;
+    }
+    if size_b == 0 {
+        unsafe { del_range(min_a, cur_buf.zv) };
+        return true;
+    }
+
+    let chars_a = accessible_chars(cur_buf);
+    let chars_b = accessible_chars(source_buf);
+    let mut differ =
+        Differ::new(&chars_a, &chars_b).with_budget(Some(max_costs as usize), deadline);
+    differ.myers(0, chars_a.len(), 0, chars_b.len());
+
+    if differ.timed_out() {
+        unsafe { del_range(min_a, cur_buf.zv) };
+        insert_buffer_substring(source, None, None);
+        return false;
+    }
+
+    unsafe { Fundo_boundary() };
+    let count = c_specpdl_index();
+    unsafe { record_unwind_protect(Some(save_excursion_restore), save_excursion_save()) };
+
+    // We are going to make a lot of small modifications, and running the
+    // modification hooks for each of them would slow us down.  Announce a
+    // single modification of the whole accessible portion instead, unless
+    // the caller inhibited the hooks.
+    let run_hooks = unsafe { !globals.inhibit_modification_hooks };
+    if run_hooks {
+        unsafe {
+            prepare_to_modify_buffer(min_a, cur_buf.zv, ptr::null_mut());
+            specbind(Qinhibit_modification_hooks, Qt);
+        }
+    }
+
+    // Walk backwards through the changes, so that the positions of the
+    // earlier ones stay valid.
+    for change in differ.changes().iter().rev() {
+        let beg_a = min_a + change.a.start as ptrdiff_t;
+        let end_a = min_a + change.a.end as ptrdiff_t;
+        let beg_b = min_b + change.b.start as ptrdiff_t;
+        let end_b = min_b + change.b.end as ptrdiff_t;
+        if beg_a < end_a {
+            unsafe { del_range(beg_a, end_a) };
+        }
+        if beg_b < end_b {
+            unsafe { set_point(beg_a) };
+            insert_buffer_substring(
+                source,
+                Some(LispNumber::Fixnum(beg_b as EmacsInt)),
+                Some(LispNumber::Fixnum(end_b as EmacsInt)),
+            );
+        }
+    }
+    unbind_to(count, Qnil);
+
+    if run_hooks {
+        let cur_buf = ThreadState::current_buffer();
+        unsafe {
+            signal_after_change(cur_buf.begv, size_a, cur_buf.zv - cur_buf.begv);
+            update_compositions(cur_buf.begv, cur_buf.zv, CHECK_INSIDE as c_int);
+        }
+    }
+    true
+}
+
 include!(concat!(env!("OUT_DIR"), "/editfns_exports.rs"));
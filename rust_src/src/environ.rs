@@ -0,0 +1,310 @@
+//! The environment of the Emacs process.
+//!
+//! Changing the environment with `setenv' or `putenv' is undefined
+//! behavior once other threads may be reading it, and Emacs runs
+//! threads of its own.  Emacs's view of its own environment is
+//! therefore a map owned by Rust: it is read from the process
+//! environment once, and a change replaces the map as a whole, so that
+//! a snapshot taken by a reader never changes under it.
+//!
+//! The process environment itself is still changed too, for the sake
+//! of C libraries that read it, by `xputenv' and by the time zone
+//! changes of `set-time-zone-rule'.  That happens under the lock of
+//! the map, but it stays unsafe for threads that call getenv directly.
+//!
+//! The map holds the bytes of the process environment, in the locale's
+//! encoding.  Lisp code decodes and encodes them with
+//! `locale-coding-system', as `getenv' and `setenv' do.
+
+use std::ffi::{CStr, CString};
+use std::ptr;
+use std::sync::{Arc, RwLock, RwLockWriteGuard};
+
+use libc::{c_char, c_int, ptrdiff_t};
+
+use crate::{
+    lisp::LispObject,
+    remacs_sys::{globals, make_string, memory_full, Fcopy_sequence},
+};
+
+/// The variables of an environment, in order, as pairs of a name and a
+/// value.  Like the strings passed to `putenv', values are never freed,
+/// so that C code can keep pointers to them.
+pub type Environment = Vec<(Vec<u8>, &'static CStr)>;
+
+lazy_static! {
+    static ref ENVIRONMENT: RwLock<Arc<Environment>> =
+        RwLock::new(Arc::new(read_process_environment()));
+}
+
+/// Return a copy of VALUE that lives as long as the process.
+fn leak_value(value: &[u8]) -> &'static CStr {
+    let value = CString::new(value).unwrap_or_default();
+    unsafe { CStr::from_ptr(CString::into_raw(value)) }
+}
+
+#[cfg(unix)]
+fn read_process_environment() -> Environment {
+    use std::os::unix::ffi::OsStrExt;
+
+    std::env::vars_os()
+        .map(|(name, value)| (name.as_bytes().to_vec(), leak_value(value.as_bytes())))
+        .collect()
+}
+
+#[cfg(windows)]
+fn read_process_environment() -> Environment {
+    std::env::vars_os()
+        .map(|(name, value)| {
+            let name = name.to_string_lossy().into_owned().into_bytes();
+            (name, leak_value(value.to_string_lossy().as_bytes()))
+        })
+        .collect()
+}
+
+#[cfg(windows)]
+pub fn env_name_eq(a: &[u8], b: &[u8]) -> bool {
+    // NT environment variables are case insensitive.
+    a.eq_ignore_ascii_case(b)
+}
+
+#[cfg(not(windows))]
+pub fn env_name_eq(a: &[u8], b: &[u8]) -> bool {
+    a == b
+}
+
+/// Return the name part of an environment entry, i.e. everything up to
+/// the first `='.
+pub fn env_entry_name(entry: &[u8]) -> &[u8] {
+    entry
+        .iter()
+        .position(|&b| b == b'=')
+        .map_or(entry, |idx| &entry[..idx])
+}
+
+/// Return the value of the variable NAME in ENV.
+fn lookup(env: &Environment, name: &[u8]) -> Option<&'static CStr> {
+    env.iter()
+        .find(|(var, _)| env_name_eq(var, name))
+        .map(|&(_, value)| value)
+}
+
+/// Set the variable NAME of ENV to VALUE, or remove it if VALUE is None.
+fn set_in(env: &mut Environment, name: &[u8], value: Option<&'static CStr>) {
+    let index = env.iter().position(|(var, _)| env_name_eq(var, name));
+    match (index, value) {
+        (Some(index), Some(value)) => env[index].1 = value,
+        (None, Some(value)) => env.push((name.to_vec(), value)),
+        (Some(index), None) => {
+            env.remove(index);
+        }
+        (None, None) => {}
+    }
+}
+
+/// Return a snapshot of the environment of the Emacs process, which
+/// later changes to the environment don't affect.
+pub fn snapshot() -> Arc<Environment> {
+    Arc::clone(&ENVIRONMENT.read().unwrap())
+}
+
+/// Return the value of the variable NAME in the environment of the
+/// Emacs process.
+pub fn getenv(name: &[u8]) -> Option<&'static CStr> {
+    lookup(&snapshot(), name)
+}
+
+/// Set the variable NAME in the environment map to VALUE, or remove it
+/// if VALUE is None.  Snapshots taken before keep the old map.  Return
+/// the lock of the map, which callers hold while they change the
+/// process environment to match.
+fn update(
+    name: &[u8],
+    value: Option<&'static CStr>,
+) -> RwLockWriteGuard<'static, Arc<Environment>> {
+    let mut env = ENVIRONMENT.write().unwrap();
+    set_in(Arc::make_mut(&mut env), name, value);
+    env
+}
+
+/// Like putenv, but running out of memory is reported as for xmalloc.
+unsafe fn putenv_or_die(string: *mut c_char) {
+    if libc::putenv(string) != 0 {
+        memory_full(0);
+    }
+}
+
+/// Like putenv, but (1) running out of memory is reported as for
+/// xmalloc and (2) the argument is a const pointer.  STRING, which
+/// must not be freed, is recorded in the environment map as well.
+#[no_mangle]
+pub unsafe extern "C" fn xputenv(string: *const c_char) {
+    let entry = CStr::from_ptr(string).to_bytes();
+    let name = env_entry_name(entry);
+    // A lone name removes the variable, as with glibc's putenv.
+    let value = if name.len() < entry.len() {
+        Some(CStr::from_ptr(string.add(name.len() + 1)))
+    } else {
+        None
+    };
+    let _env = update(name, value);
+    putenv_or_die(string as *mut c_char);
+}
+
+const TZEQLEN: usize = 3;
+
+/// A buffer holding a string of the form "TZ=value", which is part of
+/// the process environment.  If TZ is supposed to be unset, the buffer
+/// string is "tZ=".  Both are only changed with the lock of the
+/// environment map held.
+static mut TZVALBUF: *mut c_char = ptr::null_mut();
+static mut TZVALBUF_SIZE: usize = 0;
+
+/// Get the local time zone rule.
+#[no_mangle]
+pub unsafe extern "C" fn emacs_getenv_TZ() -> *mut c_char {
+    let _env = ENVIRONMENT.read().unwrap();
+    if !TZVALBUF.is_null() && *TZVALBUF == b'T' as c_char {
+        TZVALBUF.add(TZEQLEN)
+    } else {
+        ptr::null_mut()
+    }
+}
+
+/// Set the local time zone rule to TZSTRING, which can be null to
+/// denote wall clock time.  Do not record the setting in LOCAL_TZ.
+///
+/// The environment map gets a copy of the new value.  Other threads
+/// that invoke localtime etc. may still be adversely affected while
+/// the process environment is changed.
+#[no_mangle]
+pub unsafe extern "C" fn emacs_setenv_TZ(tzstring: *const c_char) -> c_int {
+    let tzstring = if tzstring.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(tzstring).to_bytes())
+    };
+    let tzstringlen = tzstring.map_or(0, <[u8]>::len);
+
+    let _env = update(b"TZ", tzstring.map(leak_value));
+
+    let new_tzvalbuf = TZVALBUF_SIZE <= TZEQLEN + tzstringlen;
+    if new_tzvalbuf {
+        // Do not free the old buffer, since another thread may be using
+        // it.  In practice, the first allocation is large enough and
+        // memory does not leak.
+        let size = (TZEQLEN + tzstringlen + 1).max(TZVALBUF_SIZE + TZVALBUF_SIZE / 2);
+        let buf = Box::into_raw(vec![0 as c_char; size].into_boxed_slice());
+        TZVALBUF = buf as *mut c_char;
+        TZVALBUF_SIZE = size;
+        *TZVALBUF.add(1) = b'Z' as c_char;
+        *TZVALBUF.add(2) = b'=' as c_char;
+    }
+
+    match tzstring {
+        // Modify the buffer in place.  Although this is dicey for
+        // threads that read the process environment, we know of no
+        // portable alternative; calling putenv or setenv could crash
+        // them.
+        Some(tzstring) => {
+            *TZVALBUF = b'T' as c_char;
+            ptr::copy_nonoverlapping(
+                tzstring.as_ptr() as *const c_char,
+                TZVALBUF.add(TZEQLEN),
+                tzstringlen,
+            );
+            *TZVALBUF.add(TZEQLEN + tzstringlen) = 0;
+        }
+        // Turn 'TZ=whatever' into an empty environment variable 'tZ='.
+        // Calling unsetenv here can crash Emacs.  See Bug#8705.
+        None => {
+            *TZVALBUF = b't' as c_char;
+            *TZVALBUF.add(TZEQLEN) = 0;
+        }
+    }
+
+    // Modifying the contents of the buffer merely requires calling
+    // tzset, which is the caller's responsibility.  A new buffer has
+    // to be put in the environment.  MS-Windows' putenv copies its
+    // argument, so there it is needed every time.
+    if new_tzvalbuf || cfg!(windows) {
+        putenv_or_die(TZVALBUF);
+    }
+
+    0
+}
+
+/// Fill `process-environment' and `initial-environment' from the
+/// environment of the Emacs process.
+#[no_mangle]
+pub extern "C" fn set_initial_environment() {
+    for (name, value) in snapshot().iter() {
+        let mut entry = name.clone();
+        entry.push(b'=');
+        entry.extend_from_slice(value.to_bytes());
+        unsafe {
+            let entry = make_string(entry.as_ptr() as *const c_char, entry.len() as ptrdiff_t);
+            globals.Vprocess_environment = LispObject::cons(entry, globals.Vprocess_environment);
+        }
+    }
+    // Ideally, the copy shouldn't be necessary, but it seems it's
+    // frequent to use `delete' and friends on process-environment.
+    unsafe {
+        globals.Vinitial_environment = Fcopy_sequence(globals.Vprocess_environment);
+    }
+}
+
+#[cfg(test)]
+fn value(s: &'static [u8]) -> &'static CStr {
+    CStr::from_bytes_with_nul(s).unwrap()
+}
+
+#[test]
+fn test_env_entry_name() {
+    assert_eq!(env_entry_name(b"HOME=/root"), b"HOME");
+    assert_eq!(env_entry_name(b"A=b=c"), b"A");
+    assert_eq!(env_entry_name(b"UNSET"), b"UNSET");
+}
+
+#[test]
+fn test_set_in() {
+    let mut env = Environment::new();
+    set_in(&mut env, b"A", Some(value(b"1\0")));
+    set_in(&mut env, b"B", Some(value(b"2\0")));
+    set_in(&mut env, b"A", Some(value(b"3\0")));
+    assert_eq!(lookup(&env, b"A"), Some(value(b"3\0")));
+    assert_eq!(env.len(), 2);
+
+    set_in(&mut env, b"A", None);
+    set_in(&mut env, b"C", None);
+    assert_eq!(lookup(&env, b"A"), None);
+    assert_eq!(lookup(&env, b"B"), Some(value(b"2\0")));
+    assert_eq!(env.len(), 1);
+}
+
+#[test]
+fn test_snapshot_is_unaffected_by_changes() {
+    let before = snapshot();
+    update(b"REMACS_ENVIRON_TEST", Some(value(b"1\0")));
+    assert_eq!(lookup(&before, b"REMACS_ENVIRON_TEST"), None);
+    assert_eq!(getenv(b"REMACS_ENVIRON_TEST"), Some(value(b"1\0")));
+    update(b"REMACS_ENVIRON_TEST", None);
+    assert_eq!(getenv(b"REMACS_ENVIRON_TEST"), None);
+}
+
+#[test]
+fn test_setenv_tz() {
+    let before = snapshot();
+    let before_tz = lookup(&before, b"TZ");
+    unsafe {
+        emacs_setenv_TZ(b"UTC0\0".as_ptr() as *const c_char);
+        assert_eq!(CStr::from_ptr(emacs_getenv_TZ()).to_bytes(), b"UTC0");
+        assert_eq!(getenv(b"TZ"), Some(value(b"UTC0\0")));
+
+        emacs_setenv_TZ(ptr::null());
+        assert!(emacs_getenv_TZ().is_null());
+        assert_eq!(getenv(b"TZ"), None);
+    }
+    assert_eq!(getenv(b"tZ"), None);
+    assert_eq!(lookup(&before, b"TZ"), before_tz);
+}
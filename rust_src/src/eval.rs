@@ -1,24 +1,27 @@
 //! Generic Lisp eval functions
 
 use std::ptr;
+use std::slice;
 
 use remacs_macros::lisp_fn;
 
 use crate::{
+    alloc::push_alloc_context,
     data::{defalias, fset, indirect_function, indirect_function_lisp, set, set_default},
+    hookprof::call_hook_function,
     lisp::{defsubr, is_autoload},
     lisp::{LispObject, LispSubrRef},
     lists::{assq, car, cdr, get, memq, nth, put, Fcar, Fcdr, LispCons},
     multibyte::LispStringRef,
     obarray::loadhist_attach,
     objects::equal,
+    remacs_sys::{alloc_context, pvec_type, EmacsInt, Lisp_Compiled, Set_Internal_Bind},
     remacs_sys::{
         backtrace_debug_on_exit, build_string, call_debugger, check_cons_list, do_debug_on_call,
         do_one_unbind, eval_sub, find_symbol_value, funcall_lambda, funcall_subr, globals,
         internal_catch, list2, maybe_gc, maybe_quit, record_in_backtrace, record_unwind_protect,
-        record_unwind_save_match_data, specbind, COMPILEDP, MODULE_FUNCTIONP,
+        specbind, COMPILEDP, MODULE_FUNCTIONP,
     },
-    remacs_sys::{pvec_type, EmacsInt, Lisp_Compiled, Set_Internal_Bind},
     remacs_sys::{Fapply, Fdefault_value, Fload, Fpurecopy},
     remacs_sys::{
         QCdocumentation, Qautoload, Qclosure, Qerror, Qexit, Qfunction, Qinteractive,
@@ -27,6 +30,7 @@ use crate::{
         Qwrong_number_of_arguments,
     },
     remacs_sys::{Vautoload_queue, Vrun_hooks},
+    search::SaveMatchData,
     symbols::{fboundp, symbol_function, LispSymbolRef},
     threads::{c_specpdl_index, ThreadState},
     vectors::length,
@@ -567,6 +571,7 @@ pub fn eval(form: LispObject, lexical: LispObject) -> LispObject {
     unsafe {
         specbind(Qinternal_interpreter_environment, value);
     }
+    push_alloc_context(alloc_context::ALLOC_EVALUATOR);
 
     unbind_to(count, unsafe { eval_sub(form) })
 }
@@ -807,10 +812,12 @@ pub fn autoload_do_load(
                 sym.symbol_name().as_string_or_error()
             );
         }
+    }
 
-        // Preserve the match data.
-        record_unwind_save_match_data();
+    // Preserve the match data.
+    let _match_data = SaveMatchData::save();
 
+    unsafe {
         // If autoloading gets an error (which includes the error of failing
         // to define the function being called), we use Vautoload_queue
         // to undo function definitions and `provide' calls made by
@@ -968,10 +975,10 @@ pub extern "C" fn run_hook(hook: LispObject) {
 /// Call each of the functions in the hook value, passing each of them
 /// as arguments all the rest of ARGS (all NARGS - 1 elements).
 /// FUNCALL specifies how to call each function on the hook.
-fn run_hook_with_args_internal(
-    args: &mut [LispObject],
-    func: fn(&mut [LispObject]) -> LispObject,
-) -> LispObject {
+fn run_hook_with_args_internal<F>(args: &mut [LispObject], mut funcall: F) -> LispObject
+where
+    F: FnMut(&mut [LispObject]) -> LispObject,
+{
     // If we are dying or still initializing,
     // don't do anything -- it would probably crash if we tried.
     if unsafe { Vrun_hooks == Qnil } {
@@ -981,6 +988,7 @@ fn run_hook_with_args_internal(
     let mut ret = Qnil;
     let sym = args[0];
     let val = unsafe { find_symbol_value(sym) };
+    let mut func = |args: &mut [LispObject]| call_hook_function(sym, args, &mut funcall);
 
     if val.eq(Qunbound) || val.is_nil() {
         Qnil
@@ -1028,6 +1036,21 @@ fn run_hook_with_args_internal(
     }
 }
 
+/// Run the hook ARGS[0] for C code, calling each of its functions with
+/// FUNCALL, and return the value of the last call.  See
+/// `run_hook_with_args_internal'.
+#[no_mangle]
+pub extern "C" fn run_hook_with_funcall(
+    nargs: libc::ptrdiff_t,
+    args: *mut LispObject,
+    funcall: extern "C" fn(libc::ptrdiff_t, *mut LispObject) -> LispObject,
+) -> LispObject {
+    let args = unsafe { slice::from_raw_parts_mut(args, nargs as usize) };
+    run_hook_with_args_internal(args, |args| {
+        funcall(args.len() as libc::ptrdiff_t, args.as_mut_ptr())
+    })
+}
+
 enum LispFun {
     SubrFun(LispSubrRef),
     LambdaFun(LispObject),
@@ -0,0 +1,246 @@
+//! Hashing file contents in the background.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use libc::{self, c_char, c_int, c_void};
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    callproc::lisp_file_name_to_path,
+    crypto::blake3::Hasher,
+    lisp::{defsubr, LispObject},
+    lists::{assq, delq},
+    multibyte::LispStringRef,
+    obarray::intern,
+    remacs_sys::{add_read_fd, emacs_pipe, pending_funcalls, report_file_error, EmacsInt},
+    remacs_sys::{Fexpand_file_name, Ffind_file_name_handler, Qnil},
+    shell::make_lisp_string,
+};
+
+/// The number of worker threads.  Further requests wait in a queue, so
+/// that reverting many buffers at once doesn't start a thread for each.
+const HASH_WORKERS: usize = 4;
+
+/// A file for a worker thread to hash.
+struct HashRequest {
+    id: EmacsInt,
+    path: PathBuf,
+    /// The write end of the wakeup pipe.
+    wakeup: c_int,
+}
+
+lazy_static! {
+    /// Hashes computed by the worker threads that the main thread has
+    /// not picked up yet, as (ID, RESULT) pairs.
+    static ref FINISHED_HASHES: Mutex<Vec<(EmacsInt, io::Result<String>)>> =
+        Mutex::new(Vec::new());
+
+    /// The queue of requests for the worker threads, which are started
+    /// on first use.
+    static ref HASH_QUEUE: Mutex<mpsc::Sender<HashRequest>> = Mutex::new(start_workers());
+}
+
+/// The pipe the worker threads write to when they are done, so that
+/// `wait_reading_process_output' wakes up.  Created on first use.
+static mut WAKEUP_PIPE: [c_int; 2] = [-1, -1];
+
+static mut LAST_REQUEST_ID: EmacsInt = 0;
+
+// Requests whose hash is not delivered yet, as an alist of
+// (ID FILE . CALLBACK).
+declare_GC_protected_static!(pending_hash_requests, Qnil);
+
+/// Return the BLAKE3 hash of the file at PATH as hex digits.
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Hasher::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match file.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => hasher.update(&buf[..n]),
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Start the worker threads, and return the sending end of their queue.
+fn start_workers() -> mpsc::Sender<HashRequest> {
+    let (sender, receiver) = mpsc::channel::<HashRequest>();
+    let receiver = Arc::new(Mutex::new(receiver));
+    for _ in 0..HASH_WORKERS {
+        let receiver = Arc::clone(&receiver);
+        thread::spawn(move || loop {
+            // The lock is only held while waiting for the next request.
+            let request = match receiver.lock().unwrap().recv() {
+                Ok(request) => request,
+                Err(_) => break,
+            };
+            let result = hash_file(&request.path);
+            FINISHED_HASHES.lock().unwrap().push((request.id, result));
+            unsafe { libc::write(request.wakeup, [0u8].as_ptr() as *const c_void, 1) };
+        });
+    }
+    sender
+}
+
+/// Called from `wait_reading_process_output' when a worker thread has
+/// written to the wakeup pipe.  Queue the callbacks of all finished
+/// requests on `pending_funcalls', which runs them before the next
+/// timer.
+unsafe extern "C" fn deliver_file_hashes(fd: c_int, _data: *mut c_void) {
+    // Select said FD is readable, so this doesn't block.  Any bytes
+    // left over just make us come back here with nothing to do.
+    let mut buf = [0u8; 64];
+    libc::read(fd, buf.as_mut_ptr() as *mut c_void, buf.len());
+
+    let finished: Vec<_> = FINISHED_HASHES.lock().unwrap().drain(..).collect();
+    for (id, result) in finished {
+        let entry = assq(LispObject::from(id), pending_hash_requests);
+        if entry.is_nil() {
+            continue;
+        }
+        pending_hash_requests = delq(entry, pending_hash_requests);
+
+        let (file, callback) = entry.as_cons_or_error().cdr().as_cons_or_error().as_tuple();
+        let hash = match result {
+            Ok(hex) => make_lisp_string(hex.as_bytes(), false),
+            Err(_) => Qnil,
+        };
+        pending_funcalls = LispObject::cons(list!(callback, file, hash), pending_funcalls);
+    }
+}
+
+/// Return the write end of the wakeup pipe, creating it if needed.
+fn wakeup_pipe() -> c_int {
+    unsafe {
+        if WAKEUP_PIPE[0] < 0 {
+            if emacs_pipe(WAKEUP_PIPE.as_mut_ptr()) != 0 {
+                report_file_error("Creating pipe\0".as_ptr() as *const c_char, Qnil);
+            }
+            add_read_fd(
+                WAKEUP_PIPE[0],
+                Some(deliver_file_hashes),
+                std::ptr::null_mut(),
+            );
+        }
+        WAKEUP_PIPE[1]
+    }
+}
+
+/// Compute a hash of the contents of FILE in the background.
+/// When the hash is ready, CALLBACK is called with two arguments: the
+/// expanded name of FILE, and the hash as a string of hex digits, or
+/// nil if FILE could not be read.
+///
+/// FILE is read and hashed with BLAKE3 by one of a few worker threads,
+/// so this returns at once, without waiting for the hash.  CALLBACK
+/// runs from the command loop, like a timer function.  The value is an integer
+/// that identifies the request.
+#[lisp_fn]
+pub fn file_content_hash(file: LispStringRef, callback: LispObject) -> LispObject {
+    let file = unsafe { Fexpand_file_name(file.as_lisp_obj(), Qnil) };
+
+    // If the file name has its own handler, call it.
+    let operation = intern("file-content-hash").as_lisp_obj();
+    let handler = unsafe { Ffind_file_name_handler(file, operation) };
+    if handler.is_not_nil() {
+        return call!(handler, operation, file, callback);
+    }
+
+    let path = lisp_file_name_to_path(file);
+    let wakeup = wakeup_pipe();
+    let id = unsafe {
+        LAST_REQUEST_ID += 1;
+        LAST_REQUEST_ID
+    };
+    unsafe {
+        pending_hash_requests = LispObject::cons(
+            LispObject::cons(LispObject::from(id), LispObject::cons(file, callback)),
+            pending_hash_requests,
+        );
+    }
+
+    let request = HashRequest { id, path, wakeup };
+    HASH_QUEUE.lock().unwrap().send(request).unwrap();
+
+    LispObject::from(id)
+}
+
+include!(concat!(env!("OUT_DIR"), "/filehash_exports.rs"));
+
+#[test]
+fn test_hash_file() {
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+
+    let path = env::temp_dir().join(format!("filehash-test-{}", std::process::id()));
+    fs::File::create(&path)
+        .and_then(|mut file| file.write_all(b"abc"))
+        .unwrap();
+    let hash = hash_file(&path);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(
+        hash.unwrap(),
+        "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+    );
+    assert!(hash_file(&env::temp_dir().join("filehash-test-missing/file")).is_err());
+}
+
+#[test]
+fn test_hash_workers() {
+    use std::env;
+
+    let mut pipe = [-1; 2];
+    assert_eq!(unsafe { libc::pipe(pipe.as_mut_ptr()) }, 0);
+    let path = env::temp_dir().join("filehash-test-missing/file");
+    let ids: Vec<EmacsInt> = (-20..-10).collect();
+    for &id in &ids {
+        let request = HashRequest {
+            id,
+            path: path.clone(),
+            wakeup: pipe[1],
+        };
+        HASH_QUEUE.lock().unwrap().send(request).unwrap();
+    }
+
+    // Each request is answered once, although there are fewer workers.
+    let mut buf = [0u8; 10];
+    let mut read = 0;
+    while read < buf.len() {
+        let n = unsafe {
+            libc::read(
+                pipe[0],
+                buf[read..].as_mut_ptr() as *mut c_void,
+                buf.len() - read,
+            )
+        };
+        assert!(n > 0);
+        read += n as usize;
+    }
+    let mut finished: Vec<EmacsInt> = FINISHED_HASHES
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|&(id, _)| id)
+        .filter(|id| ids.contains(id))
+        .collect();
+    finished.sort();
+    assert_eq!(finished, ids);
+    unsafe {
+        libc::close(pipe[0]);
+        libc::close(pipe[1]);
+    }
+}
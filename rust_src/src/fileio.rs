@@ -1,22 +1,26 @@
 //! Functions to deal with files
-use errno::{set_errno, Errno};
+use errno::{errno, set_errno, Errno};
 
-use std::path;
+use std::{mem, path};
+
+use libc::{c_long, off_t, timespec, EACCES, ENOENT, ENOTDIR};
 
 use remacs_macros::lisp_fn;
 
 use crate::{
-    lisp::defsubr,
+    lisp::{defsubr, LispObject},
     lists::LispCons,
     math::{arithcompare, ArithComparison},
     multibyte::LispStringRef,
     remacs_sys::{
         check_executable, check_existing, encode_file_name, file_name_absolute_p,
-        file_name_case_insensitive_p,
+        file_name_case_insensitive_p, lisp_time_argument,
     },
-    remacs_sys::{Fexpand_file_name, Ffind_file_name_handler},
+    remacs_sys::{EmacsInt, Fexpand_file_name, Ffind_file_name_handler},
     remacs_sys::{Qfile_executable_p, Qfile_exists_p, Qfile_name_case_insensitive_p, Qnil},
+    remacs_sys::{Qset_visited_file_modtime, Qverify_visited_file_modtime},
     threads::ThreadState,
+    time::make_lisp_time,
 };
 
 /// Return t if (car A) is numerically less than (car B).
@@ -136,4 +140,126 @@ pub fn file_executable_p(filename: LispStringRef) -> bool {
     }
 }
 
+/// The `tv_nsec' of the recorded modification time of a buffer whose
+/// visited file didn't exist.
+const NONEXISTENT_MODTIME_NSECS: c_long = -1;
+
+/// The `tv_nsec' of the recorded modification time of a buffer when the
+/// modification time of its visited file is unknown.
+const UNKNOWN_MODTIME_NSECS: c_long = -2;
+
+/// Return a special time value indicating the error number ERRNUM.
+fn time_error_value(errnum: i32) -> timespec {
+    let nsecs = if errnum == ENOENT || errnum == EACCES || errnum == ENOTDIR {
+        NONEXISTENT_MODTIME_NSECS
+    } else {
+        UNKNOWN_MODTIME_NSECS
+    };
+    timespec {
+        tv_sec: 0,
+        tv_nsec: nsecs,
+    }
+}
+
+/// Return the modification time of the file FILENAME, to the
+/// nanosecond, and its size.  If the file can't be examined, return
+/// the special time value for the error and no size.
+fn file_modtime(filename: LispObject) -> (timespec, Option<off_t>) {
+    let encoded = unsafe { encode_file_name(filename) }.as_string_or_error();
+    let mut st: libc::stat = unsafe { mem::zeroed() };
+    if unsafe { libc::stat(encoded.const_sdata_ptr(), &mut st) } == 0 {
+        let mtime = timespec {
+            tv_sec: st.st_mtime,
+            tv_nsec: st.st_mtime_nsec as c_long,
+        };
+        (mtime, Some(st.st_size))
+    } else {
+        (time_error_value(errno().0), None)
+    }
+}
+
+/// Return t if last mod time of BUF's visited file matches what BUF records.
+/// This means that the file has not been changed since it was visited or saved.
+/// If BUF is omitted or nil, it defaults to the current buffer.
+/// See Info node `(elisp)Modification Time' for more details.
+#[lisp_fn(min = "0")]
+pub fn verify_visited_file_modtime(buf: LispObject) -> bool {
+    let b = if buf.is_nil() {
+        ThreadState::current_buffer()
+    } else {
+        buf.as_buffer_or_error()
+    };
+    let filename = b.filename();
+    if !filename.is_string() || b.modtime.tv_nsec == UNKNOWN_MODTIME_NSECS {
+        return true;
+    }
+
+    // If the file name has special constructs in it,
+    // call the corresponding file handler.
+    let handler = unsafe { Ffind_file_name_handler(filename, Qverify_visited_file_modtime) };
+    if handler.is_not_nil() {
+        return call!(handler, Qverify_visited_file_modtime, buf).into();
+    }
+
+    let (mtime, size) = file_modtime(filename);
+    mtime.tv_sec == b.modtime.tv_sec
+        && mtime.tv_nsec == b.modtime.tv_nsec
+        && (b.modtime_size < 0 || size == Some(b.modtime_size))
+}
+
+/// Return the current buffer's recorded visited file modification time.
+/// The value is a list of the form (HIGH LOW USEC PSEC), like the time values that
+/// `file-attributes' returns.  If the current buffer has no recorded file
+/// modification time, this function returns 0.  If the visited file
+/// doesn't exist, return -1.
+/// See Info node `(elisp)Modification Time' for more details.
+#[lisp_fn]
+pub fn visited_file_modtime() -> LispObject {
+    let modtime = ThreadState::current_buffer().modtime;
+    if modtime.tv_nsec < 0 {
+        LispObject::from((UNKNOWN_MODTIME_NSECS - modtime.tv_nsec) as EmacsInt)
+    } else {
+        make_lisp_time(modtime)
+    }
+}
+
+/// Update buffer's recorded modification time from the visited file's time.
+/// Useful if the buffer was not read from the file normally
+/// or if the file itself has been changed for some known benign reason.
+/// An argument specifies the modification time value to use
+/// (instead of that of the visited file), in the form of a list
+/// (HIGH LOW USEC PSEC) or an integer flag as returned by
+/// `visited-file-modtime'.
+#[lisp_fn(min = "0")]
+pub fn set_visited_file_modtime(time_flag: LispObject) -> LispObject {
+    let mut buffer = ThreadState::current_buffer();
+    if time_flag.is_not_nil() {
+        buffer.modtime = match time_flag.as_fixnum() {
+            Some(flag) if -1 <= flag && flag <= 0 => timespec {
+                tv_sec: 0,
+                tv_nsec: UNKNOWN_MODTIME_NSECS - flag as c_long,
+            },
+            Some(_) => args_out_of_range!(time_flag, LispObject::from(-1), LispObject::from(0)),
+            None => unsafe { lisp_time_argument(time_flag) },
+        };
+        buffer.modtime_size = -1;
+    } else {
+        let filename = unsafe { Fexpand_file_name(buffer.filename(), Qnil) };
+
+        // If the file name has special constructs in it,
+        // call the corresponding file handler.
+        let handler = unsafe { Ffind_file_name_handler(filename, Qset_visited_file_modtime) };
+        if handler.is_not_nil() {
+            // The handler can find the file name the same way we did.
+            return call!(handler, Qset_visited_file_modtime, Qnil);
+        }
+
+        if let (mtime, Some(size)) = file_modtime(filename) {
+            buffer.modtime = mtime;
+            buffer.modtime_size = size;
+        }
+    }
+    Qnil
+}
+
 include!(concat!(env!("OUT_DIR"), "/fileio_exports.rs"));
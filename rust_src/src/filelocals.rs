@@ -0,0 +1,596 @@
+//! Parsing of file-local variable specifications.
+//!
+//! The `-*- ... -*-' line at the start of a file and the `Local
+//! Variables:' block in its last page are parsed here, as are the
+//! `coding:' tags in them that `insert-file-contents' looks for before
+//! it decodes a file.  Deciding whether the variables found are safe to
+//! set is left to `hack-local-variables-filter'.
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    casefiddle::downcase,
+    editfns::{buffer_substring_no_properties, point_max, point_min},
+    eval::unbind_to,
+    fns::concat,
+    lisp::{defsubr, LispObject},
+    obarray::{intern, lisp_intern},
+    remacs_sys::{
+        internal_condition_case_1, message1, specbind, string_byte_to_char, string_char_to_byte,
+        EmacsInt,
+    },
+    remacs_sys::{Fread_from_string, Fsubstring},
+    remacs_sys::{Qerror, Qnil, Qt, Qunbound},
+    shell::make_lisp_string,
+    symbols::{indirect_variable_lisp, symbol_name},
+    threads::c_specpdl_index,
+};
+
+/// Return the position of the first byte at or after POS in TEXT that
+/// is not in SET.
+fn skip_bytes(text: &[u8], pos: usize, set: &[u8]) -> usize {
+    text[pos..]
+        .iter()
+        .position(|b| !set.contains(b))
+        .map_or(text.len(), |i| pos + i)
+}
+
+/// Return the position of the end of the line that POS is in.
+fn line_end(text: &[u8], pos: usize) -> usize {
+    text[pos..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(text.len(), |i| pos + i)
+}
+
+fn starts_with_ignore_case(text: &[u8], prefix: &[u8]) -> bool {
+    text.len() >= prefix.len() && text[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+fn ends_with_ignore_case(text: &[u8], suffix: &[u8]) -> bool {
+    text.len() >= suffix.len() && text[text.len() - suffix.len()..].eq_ignore_ascii_case(suffix)
+}
+
+fn find_ignore_case(text: &[u8], needle: &[u8]) -> Option<usize> {
+    (0..text.len()).find(|&i| starts_with_ignore_case(&text[i..], needle))
+}
+
+/// Match `hack-local-variable-regexp' at POS in TEXT.  Return the bounds
+/// of the variable name and the position after the colon and the
+/// whitespace following it.
+fn match_variable_name(text: &[u8], pos: usize) -> Option<(usize, usize, usize)> {
+    let start = skip_bytes(text, pos, b" \t");
+    let run = text[start..]
+        .iter()
+        .take_while(|b| !b"][;\"'?()\\ \t\n".contains(b))
+        .count();
+    // The name may itself contain colons, so find the last one that is
+    // followed by nothing but whitespace and another colon.
+    (start + 1..=start + run).rev().find_map(|end| {
+        let colon = skip_bytes(text, end, b" \t");
+        if text.get(colon) == Some(&b':') {
+            Some((start, end, skip_bytes(text, colon + 1, b" \t")))
+        } else {
+            None
+        }
+    })
+}
+
+/// The position of a `Local Variables:' block in a text.
+#[derive(Debug, PartialEq)]
+struct LocalVariablesBlock<'a> {
+    /// What precedes `Local Variables:' on its line.
+    prefix: &'a [u8],
+    /// What follows `Local Variables:' on its line, after whitespace.
+    suffix: &'a [u8],
+    /// Where the line after the `Local Variables:' line starts.
+    start: usize,
+    /// Where the `End:' line starts, if there is one.
+    end: Option<usize>,
+}
+
+/// Return true if LINE is the `End:' line of a block with PREFIX and
+/// SUFFIX.
+fn is_block_end(line: &[u8], prefix: &[u8], suffix: &[u8]) -> bool {
+    if !starts_with_ignore_case(line, prefix) {
+        return false;
+    }
+    let pos = skip_bytes(line, prefix.len(), b" \t");
+    if !starts_with_ignore_case(&line[pos..], b"end") {
+        return false;
+    }
+    let pos = skip_bytes(line, pos + 3, b" ");
+    if line.get(pos) != Some(&b':') {
+        return false;
+    }
+    let pos = skip_bytes(line, pos + 1, b" \t");
+    line[pos..].eq_ignore_ascii_case(suffix)
+}
+
+/// Find the first `Local Variables:' block in TEXT.  Lines end at a
+/// newline or, if CR-IS-EOL, at a carriage return as well.
+fn find_block(text: &[u8], cr_is_eol: bool) -> Option<LocalVariablesBlock> {
+    let is_eol = |b: &u8| *b == b'\n' || (cr_is_eol && *b == b'\r');
+    let found = find_ignore_case(text, b"Local Variables:")?;
+    let line_start = text[..found].iter().rposition(is_eol).map_or(0, |i| i + 1);
+    let after = skip_bytes(text, found + b"Local Variables:".len(), b" \t");
+    let line_end = text[after..]
+        .iter()
+        .position(is_eol)
+        .map_or(text.len(), |i| after + i);
+    let prefix = &text[line_start..found];
+    let suffix = &text[after..line_end];
+
+    let start = (line_end + 1).min(text.len());
+    let mut pos = start;
+    let mut end = None;
+    while pos < text.len() {
+        let eol = text[pos..]
+            .iter()
+            .position(is_eol)
+            .map_or(text.len(), |i| pos + i);
+        if is_block_end(&text[pos..eol], prefix, suffix) {
+            end = Some(pos);
+            break;
+        }
+        pos = eol + 1;
+    }
+
+    Some(LocalVariablesBlock {
+        prefix,
+        suffix,
+        start,
+        end,
+    })
+}
+
+/// Remove PREFIX and SUFFIX from each line of BODY, the lines of a
+/// `Local Variables:' block.  Carriage returns end lines too.
+fn strip_block_lines(body: &[u8], prefix: &[u8], suffix: &[u8]) -> Result<Vec<u8>, &'static str> {
+    let body: Vec<u8> = body
+        .iter()
+        .map(|&b| if b == b'\r' { b'\n' } else { b })
+        .collect();
+    let mut lines: Vec<&[u8]> = body.split(|&b| b == b'\n').collect();
+    if body.last().map_or(true, |&b| b == b'\n') {
+        lines.pop();
+    }
+
+    let mut stripped = Vec::with_capacity(body.len());
+    for line in lines {
+        if !starts_with_ignore_case(line, prefix) {
+            return Err("Local variables entry is missing the prefix");
+        }
+        let line = &line[prefix.len()..];
+        if !ends_with_ignore_case(line, suffix) {
+            return Err("Local variables entry is missing the suffix");
+        }
+        stripped.extend_from_slice(&line[..line.len() - suffix.len()]);
+        stripped.push(b'\n');
+    }
+    Ok(stripped)
+}
+
+/// Return the value of the KEYWORD tag in the contents LINE of a `-*-'
+/// line, as `find-auto-coding' looks for it.  A later tag wins.
+fn prop_line_tag<'a>(line: &'a [u8], keyword: &[u8]) -> Option<&'a [u8]> {
+    line.split(|&b| b == b';').rev().find_map(|segment| {
+        let pos = skip_bytes(segment, 0, b" \t");
+        if !starts_with_ignore_case(&segment[pos..], keyword)
+            || segment.get(pos + keyword.len()) != Some(&b':')
+        {
+            return None;
+        }
+        let start = skip_bytes(segment, pos + keyword.len() + 1, b" \t");
+        let len = segment[start..].iter().take_while(|&&b| b != b' ').count();
+        if len > 0 {
+            Some(&segment[start..start + len])
+        } else {
+            None
+        }
+    })
+}
+
+/// Return the value of the KEYWORD tag in the `Local Variables:' BLOCK
+/// of TEXT.  Lines that lack the prefix or suffix are ignored.
+fn block_tag<'a>(text: &'a [u8], block: &LocalVariablesBlock, keyword: &[u8]) -> Option<&'a [u8]> {
+    let body = &text[block.start..block.end.unwrap_or_else(|| text.len())];
+    body.split(|&b| b == b'\n' || b == b'\r').find_map(|line| {
+        if !starts_with_ignore_case(line, block.prefix) {
+            return None;
+        }
+        let line = &line[block.prefix.len()..];
+        if !ends_with_ignore_case(line, block.suffix) {
+            return None;
+        }
+        let line = &line[..line.len() - block.suffix.len()];
+        let pos = skip_bytes(line, 0, b" \t");
+        if !starts_with_ignore_case(&line[pos..], keyword) {
+            return None;
+        }
+        let pos = skip_bytes(line, pos + keyword.len(), b" \t");
+        if line.get(pos) != Some(&b':') {
+            return None;
+        }
+        let start = skip_bytes(line, pos + 1, b" \t");
+        let len = line[start..]
+            .iter()
+            .take_while(|&&b| b != b' ' && b != b'\t')
+            .count();
+        if len > 0 && skip_bytes(line, start + len, b" \t") == line.len() {
+            Some(&line[start..start + len])
+        } else {
+            None
+        }
+    })
+}
+
+/// Return the substring of STRING between the byte positions FROM and TO.
+fn substring_bytes(string: LispObject, from: usize, to: usize) -> LispObject {
+    unsafe {
+        let from = string_byte_to_char(string, from as ptrdiff_t) as EmacsInt;
+        let to = string_byte_to_char(string, to as ptrdiff_t) as EmacsInt;
+        Fsubstring(string, LispObject::from(from), LispObject::from(to))
+    }
+}
+
+/// Read the value of a local variable from STRING at byte position POS.
+/// Return it and the byte position after it.
+fn read_value(string: LispObject, pos: usize) -> (LispObject, usize) {
+    let start = unsafe { string_byte_to_char(string, pos as ptrdiff_t) } as EmacsInt;
+    let (value, end) = unsafe { Fread_from_string(string, LispObject::from(start), Qnil) }
+        .as_cons_or_error()
+        .as_tuple();
+    let end = unsafe { string_char_to_byte(string, end.as_fixnum_or_error() as ptrdiff_t) };
+    (value, end as usize)
+}
+
+/// Return the major mode that the value VALUE of a `mode:' entry stands
+/// for.
+fn mode_function(value: LispObject) -> LispObject {
+    let name = downcase(symbol_name(value.as_symbol_or_error()));
+    lisp_intern(concat(&mut [name, LispObject::from("-mode")]), Qnil)
+}
+
+extern "C" fn indirect_variable_1(name: LispObject) -> LispObject {
+    indirect_variable_lisp(name)
+}
+
+extern "C" fn unbound_on_error(_: LispObject) -> LispObject {
+    Qunbound
+}
+
+/// Return the key under which the variable named NAME is returned: the
+/// pseudo-variables `mode' (in any case) and `eval' as they are, and any
+/// other variable resolved through its aliases.  Return None if NAME is
+/// an alias that can't be resolved, so that its entry is skipped.
+fn entry_key(name: LispObject, is_mode: bool) -> Option<LispObject> {
+    let eval = intern("eval").as_lisp_obj();
+    if is_mode {
+        Some(intern("mode").as_lisp_obj())
+    } else if name == eval {
+        Some(eval)
+    } else {
+        // `indirect-variable' signals if the aliases form a cycle.
+        let key = unsafe {
+            internal_condition_case_1(
+                Some(indirect_variable_1),
+                name,
+                Qerror,
+                Some(unbound_on_error),
+            )
+        };
+        if key == Qunbound {
+            None
+        } else {
+            Some(key)
+        }
+    }
+}
+
+/// Parse the contents of a `-*-' line, the text between BEG and END.
+/// Return an alist of elements (VAR . VAL), where VAR is a variable and
+/// VAL is the specified value, with the last entry of the line first, or
+/// nil if the line is malformed.
+/// `coding:' entries are ignored, since `set-auto-coding' has handled
+/// them.  Variables are resolved through their aliases, except for the
+/// pseudo-variables `mode' and `eval', whose entries are kept as they
+/// are for `hack-local-variables-filter' to vet.  Entries for variables
+/// whose aliases form a cycle are ignored.
+///
+/// If HANDLE-MODE is neither nil nor t, `mode:' entries are ignored as
+/// well.  If HANDLE-MODE is t, all entries are ignored except for
+/// `mode:', and the value is the major mode function it specifies, if
+/// any.  A line of the form "-*- MODENAME -*-" specifies a mode and
+/// nothing else.
+#[lisp_fn(min = "2")]
+pub fn file_local_variables_prop_line(
+    beg: EmacsInt,
+    end: EmacsInt,
+    handle_mode: LispObject,
+) -> LispObject {
+    if end <= beg {
+        return Qnil;
+    }
+    let line = buffer_substring_no_properties(LispObject::from(beg), LispObject::from(end));
+    // Copy the text, as reading values may relocate string data.
+    let text = line.as_string_or_error().as_slice().to_vec();
+
+    // Simple form: "-*- MODENAME -*-".
+    if !text.iter().any(|b| b" \t\n\r:;".contains(b)) {
+        return if handle_mode == Qt {
+            lisp_intern(concat(&mut [line, LispObject::from("-mode")]), Qnil)
+        } else {
+            Qnil
+        };
+    }
+
+    // Hairy form: '-*-' [ <variable> ':' <value> ';' ]* '-*-'.
+    let count = c_specpdl_index();
+    unsafe { specbind(intern("read-circle").as_lisp_obj(), Qnil) };
+    let mut entries = Qnil;
+    let mut mode = Qnil;
+    let mut pos = 0;
+
+    while pos < text.len() && !(handle_mode == Qt && mode.is_not_nil()) {
+        let (name_start, name_end, value_start) = match match_variable_name(&text, pos) {
+            Some(found) => found,
+            None => {
+                call!(
+                    intern("message").as_lisp_obj(),
+                    LispObject::from("Malformed mode-line: %S"),
+                    substring_bytes(line, pos, text.len())
+                );
+                return unbind_to(count, Qnil);
+            }
+        };
+        let name = &text[name_start..name_end];
+        let key = lisp_intern(substring_bytes(line, name_start, name_end), Qnil);
+        let (value, next) = read_value(line, value_start);
+        // It is traditional to ignore case when checking for `mode' and
+        // `coding', but not for other variables.
+        let is_mode = name.eq_ignore_ascii_case(b"mode");
+
+        if handle_mode == Qt {
+            if is_mode {
+                mode = mode_function(value);
+            }
+        } else if !name.eq_ignore_ascii_case(b"coding") && (handle_mode.is_nil() || !is_mode) {
+            if let Some(key) = entry_key(key, is_mode) {
+                entries = LispObject::cons(LispObject::cons(key, value), entries);
+            }
+        }
+        pos = skip_bytes(&text, next, b" \t;");
+    }
+
+    unbind_to(count, if handle_mode == Qt { mode } else { entries })
+}
+
+/// Parse the `Local Variables:' block in the last page of the current
+/// buffer.  Return an alist of elements (VAR . VAL), with the last entry
+/// of the block first, or nil if there is no block.  Entries are treated
+/// as in `file-local-variables-prop-line', which see for HANDLE-MODE,
+/// except that with HANDLE-MODE t `mode:' entries naming a minor mode
+/// are ignored.
+///
+/// Signal an error if the block is malformed.
+#[lisp_fn(min = "0")]
+pub fn file_local_variables_block(handle_mode: LispObject) -> LispObject {
+    let end = point_max();
+    let start = (end - 3000).max(point_min());
+    let page = buffer_substring_no_properties(LispObject::from(start), LispObject::from(end));
+    let page = page.as_string_or_error();
+    let multibyte = page.is_multibyte();
+    let text = page.as_slice().to_vec();
+
+    // Only the last page is searched.
+    let page_start = (0..text.len().saturating_sub(1))
+        .rev()
+        .find(|&i| &text[i..i + 2] == b"\n\x0c")
+        .unwrap_or(0);
+    let text = &text[page_start..];
+    let block = match find_block(text, false) {
+        Some(block) => block,
+        None => return Qnil,
+    };
+    let body = match block.end {
+        Some(block_end) => &text[block.start..block_end],
+        None => {
+            // This may simply not be a local variables section.
+            let message = "Local variables list is not properly terminated\0";
+            unsafe { message1(message.as_ptr() as *const c_char) };
+            &text[block.start..block.start]
+        }
+    };
+    let body = match strip_block_lines(body, block.prefix, block.suffix) {
+        Ok(body) => body,
+        Err(message) => error!(message),
+    };
+    let string = make_lisp_string(&body, multibyte);
+
+    let count = c_specpdl_index();
+    unsafe { specbind(intern("read-circle").as_lisp_obj(), Qnil) };
+    let mut entries = Qnil;
+    let mut mode = Qnil;
+    let mut pos = 0;
+
+    while pos < body.len() && !(handle_mode == Qt && mode.is_not_nil()) {
+        let (name_start, name_end, _) = match match_variable_name(&body, pos) {
+            Some(found) => found,
+            None => error!(
+                "Malformed local variable line: {:?}",
+                String::from_utf8_lossy(&body[pos..line_end(&body, pos)])
+            ),
+        };
+        let name = &body[name_start..name_end];
+        let key = lisp_intern(substring_bytes(string, name_start, name_end), Qnil);
+        let colon = skip_bytes(&body, name_end, b" \t");
+        let (value, next) = read_value(string, colon + 1);
+        let is_mode = name.eq_ignore_ascii_case(b"mode");
+
+        if handle_mode == Qt {
+            if is_mode {
+                let function = mode_function(value);
+                let function_name = symbol_name(function.as_symbol_or_error());
+                // Specifying minor modes via `mode:' is deprecated.
+                if !function_name
+                    .as_string_or_error()
+                    .as_slice()
+                    .ends_with(b"-minor-mode")
+                {
+                    mode = function;
+                }
+            }
+        } else if !name.eq_ignore_ascii_case(b"coding") && (handle_mode.is_nil() || !is_mode) {
+            if let Some(key) = entry_key(key, is_mode) {
+                entries = LispObject::cons(LispObject::cons(key, value), entries);
+            }
+        }
+        pos = (line_end(&body, next) + 1).min(body.len());
+    }
+
+    unbind_to(count, if handle_mode == Qt { mode } else { entries })
+}
+
+/// Look for the tags that determine how a file is decoded in the text
+/// of the current buffer, as `find-auto-coding' does.
+///
+/// PROP-BEG and PROP-END are the bounds of the contents of the `-*-'
+/// line, or nil if there is none, and TAIL-START and TAIL-END those of
+/// the text in which to look for a `Local Variables:' block.  Tags in
+/// the `-*-' line take precedence.
+///
+/// The value is a list (CODING CHAR-TRANS UNIBYTE).  CODING is the
+/// coding system specified by a `coding:' tag, a symbol, or nil.
+/// CHAR-TRANS is the value of an `enable-character-translation:' tag, a
+/// string, or nil.  If FOR-LOAD is non-nil, the obsolete `unibyte:' tag
+/// is looked for too; if one is found, CODING is `raw-text', and UNIBYTE
+/// is `head' or `tail' depending on where the tag is.  Otherwise UNIBYTE
+/// is nil.
+#[lisp_fn(min = "4")]
+pub fn file_local_coding_tags(
+    prop_beg: LispObject,
+    prop_end: LispObject,
+    tail_start: EmacsInt,
+    tail_end: EmacsInt,
+    for_load: bool,
+) -> LispObject {
+    let mut coding: Option<Vec<u8>> = None;
+    let mut char_trans: Option<Vec<u8>> = None;
+    let mut unibyte = Qnil;
+
+    if prop_beg.is_not_nil() && prop_end.is_not_nil() {
+        let line = buffer_substring_no_properties(prop_beg, prop_end);
+        let line = line.as_string_or_error().as_slice().to_vec();
+        if for_load && prop_line_tag(&line, b"unibyte").is_some() {
+            coding = Some(b"raw-text".to_vec());
+            unibyte = intern("head").as_lisp_obj();
+        }
+        if coding.is_none() {
+            coding = prop_line_tag(&line, b"coding").map(<[u8]>::to_vec);
+        }
+        char_trans = prop_line_tag(&line, b"enable-character-translation").map(<[u8]>::to_vec);
+    }
+
+    if (coding.is_none() || char_trans.is_none()) && tail_start < tail_end {
+        let tail = buffer_substring_no_properties(
+            LispObject::from(tail_start),
+            LispObject::from(tail_end),
+        );
+        let tail = tail.as_string_or_error().as_slice().to_vec();
+        let page_start = (0..tail.len().saturating_sub(1))
+            .find(|&i| (tail[i] == b'\n' || tail[i] == b'\r') && tail[i + 1] == b'\x0c')
+            .map_or(0, |i| i + 2);
+        let tail = &tail[page_start..];
+
+        if let Some(block) = find_block(tail, true) {
+            if for_load && block_tag(tail, &block, b"unibyte").is_some() {
+                coding = Some(b"raw-text".to_vec());
+                unibyte = intern("tail").as_lisp_obj();
+            }
+            if coding.is_none() {
+                coding = block_tag(tail, &block, b"coding").map(<[u8]>::to_vec);
+            }
+            if char_trans.is_none() {
+                char_trans =
+                    block_tag(tail, &block, b"enable-character-translation").map(<[u8]>::to_vec);
+            }
+        }
+    }
+
+    // Tag values are ASCII unless the file is malformed, in which case
+    // they are taken as raw bytes.
+    let to_string = |value: Vec<u8>| make_lisp_string(&value, false);
+    list!(
+        coding.map_or(Qnil, |c| lisp_intern(to_string(c), Qnil)),
+        char_trans.map_or(Qnil, to_string),
+        unibyte
+    )
+}
+
+include!(concat!(env!("OUT_DIR"), "/filelocals_exports.rs"));
+
+#[test]
+fn test_match_variable_name() {
+    assert_eq!(match_variable_name(b"mode: foo", 0), Some((0, 4, 6)));
+    assert_eq!(
+        match_variable_name(b"  fill-column :70", 0),
+        Some((2, 13, 15))
+    );
+    assert_eq!(match_variable_name(b"a:b: c", 0), Some((0, 3, 5)));
+    assert_eq!(match_variable_name(b"(foo): 1", 0), None);
+    assert_eq!(match_variable_name(b"no colon here", 0), None);
+}
+
+#[test]
+fn test_find_block() {
+    let text = b"code\n/* Local Variables: */\n/* a: 1 */\n/* End: */\n";
+    let block = find_block(text, false).unwrap();
+    assert_eq!(block.prefix, b"/* ");
+    assert_eq!(block.suffix, b"*/");
+    assert_eq!(&text[block.start..block.end.unwrap()], b"/* a: 1 */\n");
+    assert_eq!(
+        strip_block_lines(
+            &text[block.start..block.end.unwrap()],
+            block.prefix,
+            block.suffix
+        ),
+        Ok(b"a: 1 \n".to_vec())
+    );
+
+    let unterminated = find_block(b";; local variables:\n;; a: 1\n", false).unwrap();
+    assert_eq!(unterminated.end, None);
+    assert_eq!(find_block(b"nothing to see", false), None);
+    assert_eq!(
+        strip_block_lines(b";; a: 1\nb: 2\n", b";; ", b""),
+        Err("Local variables entry is missing the prefix")
+    );
+}
+
+#[test]
+fn test_prop_line_tag() {
+    assert_eq!(
+        prop_line_tag(b"mode: c; coding: utf-8", b"coding"),
+        Some(&b"utf-8"[..])
+    );
+    assert_eq!(
+        prop_line_tag(b"Coding:latin-1", b"coding"),
+        Some(&b"latin-1"[..])
+    );
+    assert_eq!(prop_line_tag(b"my-coding: utf-8", b"coding"), None);
+    assert_eq!(prop_line_tag(b"coding : utf-8", b"coding"), None);
+}
+
+#[test]
+fn test_block_tag() {
+    let text = b"\n# Local Variables:\n# coding: iso-8859-1\n# End:\n";
+    let block = find_block(text, true).unwrap();
+    assert_eq!(block_tag(text, &block, b"coding"), Some(&b"iso-8859-1"[..]));
+    assert_eq!(block_tag(text, &block, b"unibyte"), None);
+
+    let text = b"\r;; Local Variables:\r;; Coding : utf-8 \r;; End:\r";
+    let block = find_block(text, true).unwrap();
+    assert_eq!(block_tag(text, &block, b"coding"), Some(&b"utf-8"[..]));
+}
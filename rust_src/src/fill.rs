@@ -0,0 +1,341 @@
+//! Breaking paragraphs into lines, for filling.
+//!
+//! `fill-region-as-paragraph' used to find each line break by moving to
+//! the fill column and searching backward for a place to break, which
+//! takes several regexp searches per line.  `fill-line-breaks' finds all
+//! the breaks of a paragraph in one pass instead.  The lines are either
+//! filled greedily, as full as they can be, or with the optimal method
+//! of Knuth and Plass, which makes the lines as even as it can.
+//!
+//! Text in scripts that don't separate words with spaces, like Chinese
+//! and Japanese, can also be broken between characters of the category
+//! `|'.  The "kinsoku" rules of `kinsoku.el' forbid some of these breaks:
+//! a line can't end with a character of the category `<', like an
+//! opening bracket, nor start with one of the category `>', like a
+//! closing bracket or a full stop.
+
+use libc::ptrdiff_t;
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    buffers::validate_region,
+    category::char_has_category,
+    editfns::{buffer_chars, save_excursion_save},
+    indent::current_column,
+    lisp::{defsubr, LispObject},
+    lists::list,
+    marker::buf_charpos_to_bytepos,
+    multibyte::Codepoint,
+    obarray::intern,
+    remacs_sys::{record_unwind_protect, save_excursion_restore, set_point, EmacsInt},
+    remacs_sys::{Fchar_width, Qnil},
+    search::{looking_at, SaveMatchData},
+    symbols::symbol_value,
+    threads::ThreadState,
+};
+
+/// A word of a paragraph, and the whitespace after it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Word {
+    /// The width of the word, in columns.
+    pub width: usize,
+    /// The width of the whitespace after the word.
+    pub space: usize,
+    /// Whether a line can end after the word.
+    pub breakable: bool,
+}
+
+/// A run of words that must stay on the same line.
+struct Chunk {
+    /// The index of the first word of the run.
+    first: usize,
+    width: usize,
+    space: usize,
+}
+
+/// Return the runs of WORDS between which lines can be broken.
+fn chunks(words: &[Word]) -> Vec<Chunk> {
+    let mut chunks: Vec<Chunk> = Vec::new();
+    let mut glued = false;
+    for (i, word) in words.iter().enumerate() {
+        match chunks.last_mut() {
+            Some(chunk) if glued => {
+                chunk.width += chunk.space + word.width;
+                chunk.space = word.space;
+            }
+            _ => chunks.push(Chunk {
+                first: i,
+                width: word.width,
+                space: word.space,
+            }),
+        }
+        glued = !word.breakable;
+    }
+    chunks
+}
+
+/// Return the width of the line made of CHUNKS.
+fn line_width(chunks: &[Chunk]) -> usize {
+    chunks.iter().map(|chunk| chunk.width).sum::<usize>()
+        + chunks[..chunks.len() - 1]
+            .iter()
+            .map(|chunk| chunk.space)
+            .sum::<usize>()
+}
+
+/// Return the indexes of the words of WORDS that start a line when they
+/// are filled greedily.  The first line has room for FIRST columns, and
+/// the others for REST columns.  A word that does not fit on a line of
+/// its own gets a line anyway.
+pub fn greedy_breaks(words: &[Word], first: usize, rest: usize) -> Vec<usize> {
+    let chunks = chunks(words);
+    let mut breaks = Vec::new();
+    let mut room = first;
+    let mut width = 0;
+    for (i, chunk) in chunks.iter().enumerate() {
+        if i > 0 {
+            let space = chunks[i - 1].space;
+            if width + space + chunk.width <= room {
+                width += space + chunk.width;
+                continue;
+            }
+            breaks.push(chunk.first);
+            room = rest;
+        }
+        width = chunk.width;
+    }
+    breaks
+}
+
+/// Return the indexes of the words of WORDS that start a line when they
+/// are filled to make the lines as even as possible, by minimizing the
+/// sum of the squares of the room left at the end of the lines, except
+/// the last.  FIRST and REST are as for `greedy_breaks'.
+pub fn optimal_breaks(words: &[Word], first: usize, rest: usize) -> Vec<usize> {
+    let chunks = chunks(words);
+    let n = chunks.len();
+    if n == 0 {
+        return Vec::new();
+    }
+
+    // COST[J] is the least cost of the lines before chunk J, and
+    // START[J] the first chunk of the last of those lines.
+    let mut cost = vec![u64::max_value(); n + 1];
+    let mut start = vec![0; n + 1];
+    cost[0] = 0;
+    for i in 0..n {
+        if cost[i] == u64::max_value() {
+            continue;
+        }
+        let room = if i == 0 { first } else { rest };
+        for j in i + 1..=n {
+            let width = line_width(&chunks[i..j]);
+            if width > room && j > i + 1 {
+                break;
+            }
+            let badness = if width > room {
+                // A chunk that is too wide for any line.
+                let excess = (width - room) as u64;
+                excess * excess * 1000
+            } else if j == n {
+                0
+            } else {
+                let slack = (room - width) as u64;
+                slack * slack
+            };
+            if cost[i] + badness < cost[j] {
+                cost[j] = cost[i] + badness;
+                start[j] = i;
+            }
+        }
+    }
+
+    let mut breaks = Vec::new();
+    let mut j = n;
+    while j > 0 {
+        let i = start[j];
+        if i > 0 {
+            breaks.push(chunks[i].first);
+        }
+        j = i;
+    }
+    breaks.reverse();
+    breaks
+}
+
+fn is_blank(c: Codepoint) -> bool {
+    c == Codepoint::from(b' ') || c == Codepoint::from(b'\t')
+}
+
+/// Return true if the kinsoku rules forbid a line break between the
+/// characters A and B.
+fn kinsoku_forbids(a: Codepoint, b: Codepoint) -> bool {
+    char_has_category(a, b'<') || char_has_category(b, b'>')
+}
+
+/// Return true if a line can break between the characters A and B,
+/// which are not blanks.  If KINSOKU is true, obey the kinsoku rules.
+pub fn breaks_between(a: Codepoint, b: Codepoint, kinsoku: bool) -> bool {
+    (a >= 0x80 || b >= 0x80)
+        && (char_has_category(a, b'|') || char_has_category(b, b'|'))
+        && !(kinsoku && kinsoku_forbids(a, b))
+}
+
+/// Return the width of the character C, as for `char-width'.
+pub fn char_width(c: Codepoint) -> usize {
+    unsafe { Fchar_width(LispObject::from(EmacsInt::from(c))) }.as_fixnum_or_error() as usize
+}
+
+/// Return the value of the variable NAME.
+fn variable(name: &str) -> LispObject {
+    symbol_value(intern(name))
+}
+
+/// Return the positions at which to break the text between FROM and TO
+/// to fill it to COLUMN.
+/// The text must be a single line, as `fill-region-as-paragraph' makes
+/// it before filling.  The first line starts at the column of FROM, and
+/// the others at column INDENT, which defaults to 0.  Each position is
+/// the start of the first word of a line; the whitespace before it is
+/// to be replaced by a newline and the indentation.
+///
+/// Lines are broken at spaces and tabs, but not at a single space after
+/// a period if `sentence-end-double-space' is non-nil, nor, unless
+/// `use-hard-newlines' is non-nil, before a word that would make the
+/// line look like the start of a paragraph.  They are also broken next
+/// to characters of the category `|', like those of Chinese and
+/// Japanese, which don't need spaces around them.  If `enable-kinsoku'
+/// is non-nil, lines don't end with characters of the category `<' nor
+/// start with characters of the category `>', next to non-ASCII text.
+/// Character widths are as for `char-width'.
+///
+/// Lines are filled as much as they can be, unless OPTIMAL is non-nil,
+/// which means to make them as even as possible instead.  A word that
+/// does not fit on a line of its own gets a line anyway.
+#[lisp_fn(min = "3")]
+pub fn fill_line_breaks(
+    mut from: LispObject,
+    mut to: LispObject,
+    column: EmacsInt,
+    indent: Option<EmacsInt>,
+    optimal: bool,
+) -> LispObject {
+    unsafe { validate_region(&mut from, &mut to) };
+    let from = from.as_fixnum_or_error() as ptrdiff_t;
+    let to = to.as_fixnum_or_error() as ptrdiff_t;
+    let mut buffer = ThreadState::current_buffer();
+    let text = unsafe {
+        buffer_chars(
+            buffer,
+            buf_charpos_to_bytepos(buffer.as_mut(), from),
+            buf_charpos_to_bytepos(buffer.as_mut(), to),
+        )
+    };
+
+    let double_space = variable("sentence-end-double-space").is_not_nil();
+    let kinsoku = variable("enable-kinsoku").is_not_nil();
+    let paragraph_start = if variable("use-hard-newlines").is_nil() {
+        variable("paragraph-start")
+    } else {
+        Qnil
+    };
+
+    // Finding the start column and looking for paragraph starts move
+    // point and change the match data.
+    let _match_data = SaveMatchData::save();
+    unsafe {
+        record_unwind_protect(Some(save_excursion_restore), save_excursion_save());
+        set_point(from);
+    }
+    let start_column = current_column();
+
+    let mut words = Vec::new();
+    let mut starts = Vec::new();
+    let mut i = text.iter().take_while(|&&c| is_blank(c)).count();
+    while i < text.len() {
+        let word_start = i;
+        i += 1;
+        while i < text.len() && !is_blank(text[i]) && !breaks_between(text[i - 1], text[i], kinsoku)
+        {
+            i += 1;
+        }
+        let word_end = i;
+        while i < text.len() && is_blank(text[i]) {
+            i += 1;
+        }
+
+        let single_space = i == word_end + 1 && text[word_end] == Codepoint::from(b' ');
+        let after_period = text[word_end - 1] == Codepoint::from(b'.');
+        let (last, next) = (text[word_end - 1], text.get(i).cloned().unwrap_or(0));
+        let breakable = i < text.len()
+            && !(double_space && after_period && single_space)
+            && !(kinsoku && (last >= 0x80 || next >= 0x80) && kinsoku_forbids(last, next))
+            && (paragraph_start.is_nil() || {
+                unsafe { set_point(from + i as ptrdiff_t) };
+                looking_at(paragraph_start).is_nil()
+            });
+
+        words.push(Word {
+            width: text[word_start..word_end]
+                .iter()
+                .map(|&c| char_width(c))
+                .sum(),
+            space: text[word_end..i].iter().map(|&c| char_width(c)).sum(),
+            breakable,
+        });
+        starts.push(from + word_start as ptrdiff_t);
+    }
+
+    let room = |start: EmacsInt| (column - start).max(0) as usize;
+    let (first, rest) = (room(start_column), room(indent.unwrap_or(0)));
+    let breaks = if optimal {
+        optimal_breaks(&words, first, rest)
+    } else {
+        greedy_breaks(&words, first, rest)
+    };
+    let positions: Vec<LispObject> = breaks
+        .into_iter()
+        .map(|word| LispObject::from(starts[word]))
+        .collect();
+    list(&positions)
+}
+
+include!(concat!(env!("OUT_DIR"), "/fill_exports.rs"));
+
+#[cfg(test)]
+fn test_words(text: &str) -> Vec<Word> {
+    text.split(' ')
+        .map(|word| Word {
+            width: word.trim_end_matches('~').len(),
+            space: 1,
+            breakable: !word.ends_with('~'),
+        })
+        .collect()
+}
+
+#[test]
+fn test_greedy_breaks() {
+    let words = test_words("aaa bb cc dddd e");
+    assert_eq!(greedy_breaks(&words, 6, 6), vec![2, 3]);
+    assert_eq!(greedy_breaks(&words, 20, 20), Vec::<usize>::new());
+    assert_eq!(greedy_breaks(&words, 3, 6), vec![1, 3]);
+    // Words that are too wide get a line of their own.
+    assert_eq!(greedy_breaks(&words, 1, 1), vec![1, 2, 3, 4]);
+    // Glued words stay together.
+    let words = test_words("aaa~ bb cc");
+    assert_eq!(greedy_breaks(&words, 6, 6), vec![2]);
+}
+
+#[test]
+fn test_optimal_breaks() {
+    let words = test_words("aaa bb cc ddddd");
+    assert_eq!(greedy_breaks(&words, 6, 6), vec![2, 3]);
+    // "cc" alone would leave a lot of room on its line.
+    assert_eq!(optimal_breaks(&words, 6, 6), vec![1, 3]);
+    assert_eq!(optimal_breaks(&words, 20, 20), Vec::<usize>::new());
+    assert_eq!(optimal_breaks(&[], 9, 9), Vec::<usize>::new());
+    assert_eq!(optimal_breaks(&test_words("aaaaaaaaaa b"), 4, 4), vec![1]);
+    let words = test_words("aaa~ bb cc");
+    assert_eq!(optimal_breaks(&words, 6, 6), vec![2]);
+}
@@ -0,0 +1,222 @@
+//! Applying font-lock keywords natively.
+//!
+//! Most keywords of simple modes are a regexp and a few faces for its
+//! subexpressions.  `font-lock-fontify-keyword-native' searches for the
+//! matches of such a keyword and puts the faces on them without going
+//! back to Lisp for each match.  Keywords whose matcher is a function,
+//! that have anchored highlights, or whose faces are computed from the
+//! match are left to `font-lock-fontify-keywords-region'.
+
+use std::ptr;
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    cmds::forward_char,
+    editfns::{goto_char, point},
+    lisp::{defsubr, LispObject},
+    obarray::intern,
+    remacs_sys::{find_before_next_newline, find_symbol_value, EmacsInt},
+    remacs_sys::{Fnext_single_property_change, Fput_text_property, Ftext_property_not_all},
+    remacs_sys::{Qface, Qnil, Qquote, Qt, Qunbound},
+    search::{match_beginning, match_end, re_search_forward},
+    textprop::text_property_any,
+    threads::ThreadState,
+};
+
+/// How a highlight treats faces that are already in the text.
+#[derive(Clone, Copy, PartialEq)]
+enum Override {
+    /// Only fontify text that has no face yet.
+    Never,
+    /// Replace any face.
+    Always,
+    /// Fill in the parts of the text that have no face.
+    Keep,
+}
+
+/// A highlight (SUBEXP FACENAME [OVERRIDE [LAXMATCH]]) whose face does not
+/// depend on the match.
+struct Highlight {
+    form: LispObject,
+    subexp: EmacsInt,
+    face: LispObject,
+    override_: Override,
+    laxmatch: bool,
+}
+
+impl Highlight {
+    /// Parse HIGHLIGHT, or return `None` if it has to be applied in Lisp.
+    fn parse(highlight: LispObject) -> Option<Self> {
+        let mut items = Vec::new();
+        let mut tail = highlight;
+        while let Some(cons) = tail.as_cons() {
+            if items.len() == 4 {
+                return None;
+            }
+            items.push(cons.car());
+            tail = cons.cdr();
+        }
+        if tail.is_not_nil() || items.len() < 2 {
+            return None;
+        }
+        let subexp = items[0].as_fixnum().filter(|&n| n >= 0)?;
+        let face = constant_face(items[1])?;
+        if face.as_cons().map_or(false, |cons| cons.car().eq(Qface)) {
+            return None;
+        }
+        let override_ = match items.get(2).cloned().unwrap_or(Qnil) {
+            o if o.is_nil() => Override::Never,
+            o if o.eq(Qt) => Override::Always,
+            o if o.eq(intern("keep").as_lisp_obj()) => Override::Keep,
+            _ => return None,
+        };
+        Some(Highlight {
+            form: highlight,
+            subexp,
+            face,
+            override_,
+            laxmatch: items.get(3).map_or(false, |l| l.is_not_nil()),
+        })
+    }
+
+    /// Put the face on the text matched by the subexpression.
+    fn apply(&self) {
+        let (start, end) = match (match_beginning(self.subexp), match_end(self.subexp)) {
+            (Some(start), Some(end)) => (LispObject::from(start), LispObject::from(end)),
+            _ if self.laxmatch => return,
+            _ => {
+                call!(
+                    intern("error").as_lisp_obj(),
+                    LispObject::from("No match %d in highlight %S"),
+                    LispObject::from(self.subexp),
+                    self.form
+                );
+                return;
+            }
+        };
+        if self.face.is_nil() && self.override_ != Override::Always {
+            return;
+        }
+        unsafe {
+            match self.override_ {
+                Override::Never => {
+                    if Ftext_property_not_all(start, end, Qface, Qnil, Qnil).is_nil() {
+                        Fput_text_property(start, end, Qface, self.face, Qnil);
+                    }
+                }
+                Override::Always => {
+                    Fput_text_property(start, end, Qface, self.face, Qnil);
+                }
+                Override::Keep => {
+                    let mut from = text_property_any(start, end, Qface, Qnil, Qnil);
+                    while from.is_not_nil() {
+                        let next = Fnext_single_property_change(from, Qface, Qnil, end);
+                        Fput_text_property(from, next, Qface, self.face, Qnil);
+                        from = text_property_any(next, end, Qface, Qnil, Qnil);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Return the value of the face form FORM if it is a variable or a quoted
+/// constant, whose value is the same for every match.
+fn constant_face(form: LispObject) -> Option<LispObject> {
+    if form.is_symbol() {
+        let value = unsafe { find_symbol_value(form) };
+        if value.eq(Qunbound) {
+            None
+        } else {
+            Some(value)
+        }
+    } else if form.is_string() {
+        Some(form)
+    } else {
+        let cons = form.as_cons()?;
+        let arg = cons.cdr().as_cons()?;
+        if cons.car().eq(Qquote) && arg.cdr().is_nil() {
+            Some(arg.car())
+        } else {
+            None
+        }
+    }
+}
+
+/// Return the position of the start of the line after the one containing
+/// POS, or the end of the accessible portion of the buffer.
+fn next_line_start(pos: EmacsInt) -> EmacsInt {
+    let zv = ThreadState::current_buffer().zv;
+    let eol = unsafe { find_before_next_newline(pos as isize, zv, 1, ptr::null_mut()) };
+    (eol + 1).min(zv) as EmacsInt
+}
+
+/// Fontify the matches of the compiled font-lock KEYWORD between START
+/// and END, like `font-lock-fontify-keywords-region' would.
+/// KEYWORD is handled only if its matcher is a regexp and each of its
+/// highlights is of the form (SUBEXP FACENAME [OVERRIDE [LAXMATCH]]),
+/// where FACENAME is a variable or a quoted face, and OVERRIDE is nil, t
+/// or `keep'.  Return non-nil if KEYWORD was handled, and nil without
+/// doing anything otherwise.
+#[lisp_fn]
+pub fn font_lock_fontify_keyword_native(
+    keyword: LispObject,
+    start: EmacsInt,
+    end: EmacsInt,
+) -> bool {
+    let (matcher, highlights) = match keyword.as_cons() {
+        Some(cons) if cons.car().is_string() => (cons.car(), cons.cdr()),
+        _ => return false,
+    };
+    let mut parsed = Vec::new();
+    let mut tail = highlights;
+    while let Some(cons) = tail.as_cons() {
+        match Highlight::parse(cons.car()) {
+            Some(highlight) => parsed.push(highlight),
+            None => return false,
+        }
+        tail = cons.cdr();
+    }
+    if tail.is_not_nil() {
+        return false;
+    }
+
+    let multiline = unsafe { find_symbol_value(intern("font-lock-multiline").as_lisp_obj()) };
+    let multiline = multiline.is_not_nil() && !multiline.eq(Qunbound);
+    let end_obj = LispObject::from(end);
+    goto_char(LispObject::from(start));
+    while point() < end && re_search_forward(matcher, end_obj, Qt, Qnil).is_not_nil() {
+        let match_start = match_beginning(0).unwrap_or_else(point);
+        // Beware empty matches, which would loop indefinitely.
+        if point() <= match_start {
+            forward_char(Qnil);
+        }
+        if multiline {
+            let line_after = next_line_start(match_start);
+            let pt = point();
+            if pt >= line_after {
+                let from = if pt == line_after {
+                    pt - 1
+                } else {
+                    match_start
+                };
+                unsafe {
+                    Fput_text_property(
+                        LispObject::from(from),
+                        LispObject::from(pt),
+                        intern("font-lock-multiline").as_lisp_obj(),
+                        Qt,
+                        Qnil,
+                    )
+                };
+            }
+        }
+        for highlight in &parsed {
+            highlight.apply();
+        }
+    }
+    true
+}
+
+include!(concat!(env!("OUT_DIR"), "/fontlock_exports.rs"));
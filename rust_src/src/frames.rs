@@ -7,6 +7,9 @@ use crate::{
     lisp::{ExternalPtr, LispObject},
     remacs_sys::Vframe_list,
     remacs_sys::{candidate_frame, delete_frame as c_delete_frame, frame_dimension, output_method},
+    remacs_sys::{
+        frame_has_horizontal_scroll_bars, frame_vertical_scroll_bar_type, vertical_scroll_bar_type,
+    },
     remacs_sys::{pvec_type, selected_frame as current_frame, Lisp_Frame, Lisp_Type},
     remacs_sys::{Qframe_live_p, Qframep, Qicon, Qnil, Qns, Qpc, Qt, Qw32, Qx},
     windows::{select_window_lisp, selected_window, LispWindowRef},
@@ -35,6 +38,49 @@ impl LispFrameRef {
     pub fn total_fringe_width(self) -> i32 {
         self.left_fringe_width + self.right_fringe_width
     }
+
+    /// True if the frame is displayed by a window system, as opposed to
+    /// a text terminal.
+    pub fn is_gui_window(self) -> bool {
+        match self.output_method() {
+            output_method::output_x_window
+            | output_method::output_w32
+            | output_method::output_ns => true,
+            _ => false,
+        }
+    }
+
+    // Pixel-width of the left fringe.
+    pub fn left_fringe_width(self) -> i32 {
+        unsafe { frame_dimension(self.left_fringe_width) }
+    }
+
+    // Pixel-width of the right fringe.
+    pub fn right_fringe_width(self) -> i32 {
+        unsafe { frame_dimension(self.right_fringe_width) }
+    }
+
+    // Pixel-width of the window dividers on the right of windows.
+    pub fn right_divider_width(self) -> i32 {
+        unsafe { frame_dimension(self.right_divider_width) }
+    }
+
+    // Pixel-height of the window dividers at the bottom of windows.
+    pub fn bottom_divider_width(self) -> i32 {
+        unsafe { frame_dimension(self.bottom_divider_width) }
+    }
+
+    /// The side of the vertical scroll bars of windows that use the
+    /// frame's default.
+    pub fn vertical_scroll_bar_type(mut self) -> vertical_scroll_bar_type::Type {
+        unsafe { frame_vertical_scroll_bar_type(self.as_mut()) }
+    }
+
+    /// True if windows that use the frame's default have horizontal
+    /// scroll bars.
+    pub fn has_horizontal_scroll_bars(mut self) -> bool {
+        unsafe { frame_has_horizontal_scroll_bars(self.as_mut()) }
+    }
 }
 
 impl From<LispObject> for LispFrameRef {
@@ -0,0 +1,143 @@
+//! Frame titles.
+//!
+//! On window systems, redisplay formats `frame-title-format', or
+//! `icon-title-format' for an iconified frame, with the same
+//! %-constructs as the mode line, and tells the window system about
+//! the new title when it has changed.  Titles with `:eval' forms that
+//! change all the time, such as a clock, would make that a round trip
+//! to the window system on every redisplay, so a frame's title is
+//! updated at most once every `frame-title-update-interval' seconds.
+
+use crate::{
+    frames::LispFrameRef,
+    hashtable::{gethash, puthash, LispHashTableRef},
+    lisp::LispObject,
+    lists::memq,
+    numbers::LispNumber,
+    obarray::intern,
+    remacs_sys::{format_frame_title, globals, EmacsDouble},
+    remacs_sys::{QCtest, QCweakness, Qeq, Qkey, Qnil, Qt},
+    symbols::symbol_value,
+    time::float_time,
+};
+
+/// The time at which the title of each frame was last changed, as a
+/// float, in a hash table weak on the frames.
+declare_GC_protected_static!(frame_title_times, Qnil);
+
+/// The timer that redisplays the frame titles whose update was put off.
+declare_GC_protected_static!(frame_title_timer, Qnil);
+
+fn title_times() -> LispHashTableRef {
+    unsafe {
+        if frame_title_times.is_nil() {
+            frame_title_times = call!(
+                intern("make-hash-table").as_lisp_obj(),
+                QCtest,
+                Qeq,
+                QCweakness,
+                Qkey
+            );
+        }
+        frame_title_times.as_hash_table_or_error()
+    }
+}
+
+/// Return how many seconds to wait at time NOW before changing a title
+/// that was last changed at time LAST, if at least INTERVAL seconds
+/// must pass between changes, or None if it can be changed now.
+fn update_delay(now: f64, last: Option<f64>, interval: f64) -> Option<f64> {
+    match last {
+        Some(last) if now >= last && now - last < interval => Some(interval - (now - last)),
+        _ => None,
+    }
+}
+
+fn update_interval() -> f64 {
+    match unsafe { globals.Vframe_title_update_interval }.as_number_coerce_marker() {
+        Some(LispNumber::Fixnum(n)) => n as f64,
+        Some(LispNumber::Float(n)) => n,
+        None => 0.0,
+    }
+}
+
+/// Make sure the frame titles are looked at again in DELAY seconds.
+fn schedule_title_update(delay: f64) {
+    unsafe {
+        if frame_title_timer.is_not_nil()
+            && memq(frame_title_timer, symbol_value(intern("timer-list"))).is_not_nil()
+        {
+            return;
+        }
+        frame_title_timer = call!(
+            intern("run-with-timer").as_lisp_obj(),
+            LispObject::from_float(delay as EmacsDouble),
+            Qnil,
+            intern("force-mode-line-update").as_lisp_obj(),
+            Qt
+        );
+    }
+}
+
+/// Return the new title of FRAME if it should be changed now, or nil.
+/// The title is formatted from `icon-title-format' if FRAME is
+/// iconified and `frame-title-format' otherwise, for the selected
+/// window of FRAME and its buffer.
+#[no_mangle]
+pub extern "C" fn frame_title_update(mut frame: LispFrameRef) -> LispObject {
+    let table = title_times();
+    let key = frame.as_lisp_obj();
+    let now = float_time(Qnil);
+    let last = gethash(key, table, Qnil).as_float();
+
+    if let Some(delay) = update_delay(now, last, update_interval()) {
+        schedule_title_update(delay);
+        return Qnil;
+    }
+
+    let format = unsafe {
+        if frame.iconified() {
+            globals.Vicon_title_format
+        } else {
+            globals.Vframe_title_format
+        }
+    };
+    let title = unsafe { format_frame_title(frame.as_mut(), format) };
+
+    let unchanged = frame.name.as_string().map_or(false, |name| {
+        name.as_slice() == title.as_string_or_error().as_slice()
+    });
+    if unchanged {
+        return Qnil;
+    }
+
+    puthash(key, LispObject::from_float(now), table);
+    title
+}
+
+#[no_mangle]
+pub extern "C" fn syms_of_frametitle() {
+    /// Minimum number of seconds between changes of the title of a frame.
+    /// When `frame-title-format' or `icon-title-format' would change the
+    /// title of a frame again sooner than this, the change is put off
+    /// until the time has passed.  This keeps titles that change all the
+    /// time, such as ones showing the time of day with an `:eval' form,
+    /// from talking to the window system at every redisplay.  A value of
+    /// 0 or nil means titles are changed as soon as they are formatted.
+    defvar_lisp!(
+        Vframe_title_update_interval,
+        "frame-title-update-interval",
+        LispObject::from_float(0.25)
+    );
+}
+
+#[test]
+fn test_update_delay() {
+    assert_eq!(update_delay(10.0, None, 0.25), None);
+    assert_eq!(update_delay(10.0, Some(9.5), 0.25), None);
+    assert_eq!(update_delay(10.0, Some(9.875), 0.25), Some(0.125));
+    assert_eq!(update_delay(10.0, Some(10.0), 0.25), Some(0.25));
+    // The clock going backwards does not hold titles back.
+    assert_eq!(update_delay(10.0, Some(20.0), 0.25), None);
+    assert_eq!(update_delay(10.0, Some(10.0), 0.0), None);
+}
@@ -115,6 +115,17 @@ pub extern "C" fn Fsignal(error_symbol: LispObject, data: LispObject) -> ! {
     panic!("Fsignal called during tests");
 }
 
+/// Intervals are allocated on the Rust heap, so that the tests can
+/// free them with `Box::from_raw'.
+#[cfg(test)]
+#[allow(dead_code)]
+#[no_mangle]
+pub extern "C" fn make_interval() -> *mut interval {
+    let mut i: Box<interval> = Box::new(unsafe { ::std::mem::zeroed() });
+    i.plist = Qnil;
+    Box::into_raw(i)
+}
+
 #[cfg(test)]
 #[allow(unused_variables)]
 #[allow(dead_code)]
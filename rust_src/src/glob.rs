@@ -0,0 +1,416 @@
+//! Wildcard expansion of file names.
+//!
+//! `file-expand-wildcards' used to list every directory named by a
+//! pattern with `directory-files' and a regexp made by
+//! `wildcard-to-regexp'.  The expansion here walks the file system
+//! once, matches names with a small glob engine, and supports `**' for
+//! any number of directories and, optionally, brace alternatives.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    callproc::{lisp_file_name_to_path, path_to_lisp_file_name},
+    lisp::{defsubr, LispObject},
+    multibyte::LispStringRef,
+    obarray::intern,
+    remacs_sys::{encode_file_name, find_symbol_value, Fexpand_file_name},
+    remacs_sys::{Qnil, Qunbound},
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+    /// Whether wildcards match a leading dot.
+    pub hidden: bool,
+    pub ignore_case: bool,
+}
+
+fn fold(c: char, ignore_case: bool) -> char {
+    if ignore_case {
+        c.to_lowercase().next().unwrap_or(c)
+    } else {
+        c
+    }
+}
+
+/// Match the character class starting at PATTERN[START], a `[', against
+/// C.  Return whether it matches and the index after the class, or None
+/// if the class is not terminated, in which case the `[' is literal.
+fn match_class(
+    pattern: &[char],
+    start: usize,
+    c: char,
+    ignore_case: bool,
+) -> Option<(bool, usize)> {
+    let mut i = start + 1;
+    let negated = i < pattern.len() && (pattern[i] == '!' || pattern[i] == '^');
+    if negated {
+        i += 1;
+    }
+    let c = fold(c, ignore_case);
+    let mut matched = false;
+    let mut first = true;
+    while i < pattern.len() {
+        if pattern[i] == ']' && !first {
+            return Some((matched != negated, i + 1));
+        }
+        first = false;
+        let low = fold(pattern[i], ignore_case);
+        if i + 2 < pattern.len() && pattern[i + 1] == '-' && pattern[i + 2] != ']' {
+            let high = fold(pattern[i + 2], ignore_case);
+            matched |= low <= c && c <= high;
+            i += 3;
+        } else {
+            matched |= low == c;
+            i += 1;
+        }
+    }
+    None
+}
+
+/// If the element of PATTERN at index P, which is not `*', matches C,
+/// return the index of the next element.
+fn match_one(pattern: &[char], p: usize, c: char, ignore_case: bool) -> Option<usize> {
+    match pattern[p] {
+        '?' => Some(p + 1),
+        '[' => match match_class(pattern, p, c, ignore_case) {
+            Some((true, next)) => Some(next),
+            Some((false, _)) => None,
+            None if c == '[' => Some(p + 1),
+            None => None,
+        },
+        pc if fold(pc, ignore_case) == fold(c, ignore_case) => Some(p + 1),
+        _ => None,
+    }
+}
+
+/// Return true if NAME matches the wildcard PATTERN, which can use `*',
+/// `?' and character classes like `[a-z]' or `[!0-9]'.
+pub fn wildcard_match(pattern: &str, name: &str, options: Options) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    if !options.hidden && name.first() == Some(&'.') && pattern.first() != Some(&'.') {
+        return false;
+    }
+
+    let (mut p, mut n) = (0, 0);
+    // Where to resume when the text after the last `*' fails to match.
+    let mut backtrack = None;
+    while n < name.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            p += 1;
+            backtrack = Some((p, n));
+            continue;
+        }
+        if p < pattern.len() {
+            if let Some(next) = match_one(&pattern, p, name[n], options.ignore_case) {
+                p = next;
+                n += 1;
+                continue;
+            }
+        }
+        match backtrack {
+            Some((star_p, star_n)) => {
+                p = star_p;
+                n = star_n + 1;
+                backtrack = Some((star_p, n));
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Return the patterns that the brace alternatives of PATTERN, like
+/// `*.{c,h}', stand for.  Braces without a comma are literal.
+pub fn expand_braces(pattern: &[u8]) -> Vec<Vec<u8>> {
+    let mut start = 0;
+    while let Some(open) = pattern[start..].iter().position(|&c| c == b'{') {
+        let open = start + open;
+        let mut depth = 0;
+        let mut bounds = vec![open];
+        for (i, &c) in pattern.iter().enumerate().skip(open) {
+            match c {
+                b'{' => depth += 1,
+                b',' if depth == 1 => bounds.push(i),
+                b'}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        bounds.push(i);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if depth == 0 && bounds.len() > 2 {
+            let close = bounds[bounds.len() - 1];
+            return bounds
+                .windows(2)
+                .flat_map(|alternative| {
+                    let mut expanded = pattern[..open].to_vec();
+                    expanded.extend_from_slice(&pattern[alternative[0] + 1..alternative[1]]);
+                    expanded.extend_from_slice(&pattern[close + 1..]);
+                    expand_braces(&expanded)
+                })
+                .collect();
+        }
+        start = open + 1;
+    }
+    vec![pattern.to_vec()]
+}
+
+fn is_wild(component: &[u8]) -> bool {
+    component
+        .iter()
+        .any(|&c| c == b'*' || c == b'?' || c == b'[')
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(unix)]
+fn name_bytes(name: &OsStr) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    name.as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn name_bytes(name: &OsStr) -> Vec<u8> {
+    name.to_string_lossy().into_owned().into_bytes()
+}
+
+/// The names of the files in DIR, or nothing if it can't be read.
+fn directory_names(dir: &Path) -> Vec<Vec<u8>> {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| name_bytes(&entry.file_name()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn join(shown: &[u8], name: &[u8]) -> Vec<u8> {
+    let mut joined = shown.to_vec();
+    joined.extend_from_slice(name);
+    joined
+}
+
+/// Add to OUT the files under DIR that match COMPONENTS, the remaining
+/// components of a pattern.  SHOWN is how DIR is written relative to
+/// where the expansion started, with a trailing slash if not empty.
+fn expand(
+    dir: &Path,
+    shown: &[u8],
+    components: &[&[u8]],
+    options: Options,
+    out: &mut Vec<Vec<u8>>,
+) {
+    let (component, rest) = match components.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+
+    if *component == b"**" {
+        // Zero directories, unless `**' is the last component, in
+        // which case it matches every file below DIR.
+        if !rest.is_empty() {
+            expand(dir, shown, rest, options, out);
+        }
+        for name in directory_names(dir) {
+            if !options.hidden && name.starts_with(b".") {
+                continue;
+            }
+            let path = dir.join(path_from_bytes(&name));
+            if rest.is_empty() {
+                out.push(join(shown, &name));
+            }
+            // Don't follow symbolic links, which could make cycles.
+            if fs::symlink_metadata(&path).map_or(false, |m| m.is_dir()) {
+                let shown = join(shown, &join(&name, b"/"));
+                expand(&path, &shown, components, options, out);
+            }
+        }
+    } else if component.is_empty() {
+        // A trailing slash, or two slashes in a row.
+        if rest.is_empty() {
+            if dir.is_dir() {
+                out.push(shown.to_vec());
+            }
+        } else {
+            expand(dir, shown, rest, options, out);
+        }
+    } else if !is_wild(component) {
+        let path = dir.join(path_from_bytes(component));
+        if rest.is_empty() {
+            if fs::symlink_metadata(&path).is_ok() {
+                out.push(join(shown, component));
+            }
+        } else if path.is_dir() {
+            let shown = join(shown, &join(component, b"/"));
+            expand(&path, &shown, rest, options, out);
+        }
+    } else {
+        let pattern = String::from_utf8_lossy(component);
+        for name in directory_names(dir) {
+            if !wildcard_match(&pattern, &String::from_utf8_lossy(&name), options) {
+                continue;
+            }
+            if rest.is_empty() {
+                out.push(join(shown, &name));
+            } else {
+                let path = dir.join(path_from_bytes(&name));
+                if path.is_dir() {
+                    let shown = join(shown, &join(&name, b"/"));
+                    expand(&path, &shown, rest, options, out);
+                }
+            }
+        }
+    }
+}
+
+/// Return the value of the option NAME, or DEFAULT if it is not defined.
+fn option(name: &str, default: bool) -> bool {
+    let value = unsafe { find_symbol_value(intern(name).as_lisp_obj()) };
+    if value.eq(Qunbound) {
+        default
+    } else {
+        value.is_not_nil()
+    }
+}
+
+/// Expand the wildcard pattern PATTERN, as `file-expand-wildcards' does.
+///
+/// Besides `*', `?' and character classes, a component `**' of PATTERN
+/// matches any number of directories, and when the option
+/// `wildcard-brace-expansion' is non-nil, `{a,b}' matches either A or B.
+/// The options `wildcard-match-hidden-files' and `wildcard-ignore-case'
+/// say whether wildcards match a leading dot and whether case matters.
+///
+/// The file names are sorted, and are relative to `default-directory' if
+/// PATTERN is, unless FULL is non-nil.  File name handlers are not
+/// called; remote patterns are left to `file-expand-wildcards'.
+#[lisp_fn(min = "1")]
+pub fn file_expand_wildcards_native(pattern: LispStringRef, full: bool) -> LispObject {
+    let options = Options {
+        hidden: option("wildcard-match-hidden-files", true),
+        ignore_case: option("wildcard-ignore-case", cfg!(windows)),
+    };
+    let encoded = unsafe { encode_file_name(pattern.into()) }.as_string_or_error();
+    let patterns = if option("wildcard-brace-expansion", false) {
+        expand_braces(encoded.as_slice())
+    } else {
+        vec![encoded.as_slice().to_vec()]
+    };
+
+    let mut files = Vec::new();
+    for pattern in &patterns {
+        let components: Vec<&[u8]> = pattern.split(|&c| c == b'/').collect();
+        // The part before the first wildcard is kept as it was written.
+        let wild = components
+            .iter()
+            .position(|component| is_wild(component))
+            .unwrap_or(components.len() - 1);
+        let mut prefix = components[..wild].join(&b'/');
+        if wild > 0 {
+            prefix.push(b'/');
+        }
+        let dir = if prefix.is_empty() {
+            LispObject::from(".")
+        } else {
+            path_to_lisp_file_name(&path_from_bytes(&prefix))
+        };
+        let dir = lisp_file_name_to_path(unsafe { Fexpand_file_name(dir, Qnil) });
+        let mut matches = Vec::new();
+        expand(&dir, &[], &components[wild..], options, &mut matches);
+        files.extend(matches.into_iter().map(|name| join(&prefix, &name)));
+    }
+    files.sort();
+    files.dedup();
+
+    let names: Vec<LispObject> = files
+        .iter()
+        .map(|file| {
+            let name = path_to_lisp_file_name(&path_from_bytes(file));
+            if full {
+                unsafe { Fexpand_file_name(name, Qnil) }
+            } else {
+                name
+            }
+        })
+        .collect();
+    LispObject::from(names)
+}
+
+include!(concat!(env!("OUT_DIR"), "/glob_exports.rs"));
+
+#[cfg(test)]
+const DEFAULT: Options = Options {
+    hidden: true,
+    ignore_case: false,
+};
+
+#[test]
+fn test_wildcard_match() {
+    assert!(wildcard_match("*.rs", "glob.rs", DEFAULT));
+    assert!(!wildcard_match("*.rs", "glob.rs~", DEFAULT));
+    assert!(wildcard_match("a*b*c", "axxbyyc", DEFAULT));
+    assert!(!wildcard_match("a*b*c", "axxbyy", DEFAULT));
+    assert!(wildcard_match("?.c", "x.c", DEFAULT));
+    assert!(!wildcard_match("?.c", ".c", DEFAULT));
+    assert!(wildcard_match("[a-c]x", "bx", DEFAULT));
+    assert!(!wildcard_match("[!a-c]x", "bx", DEFAULT));
+    assert!(wildcard_match("[]]", "]", DEFAULT));
+    assert!(wildcard_match("[x", "[x", DEFAULT));
+    assert!(wildcard_match("é*", "été", DEFAULT));
+    assert!(wildcard_match("*", ".emacs", DEFAULT));
+    assert!(wildcard_match("*", "", DEFAULT));
+}
+
+#[test]
+fn test_wildcard_match_options() {
+    let no_hidden = Options {
+        hidden: false,
+        ..DEFAULT
+    };
+    assert!(!wildcard_match("*", ".emacs", no_hidden));
+    assert!(!wildcard_match("?emacs", ".emacs", no_hidden));
+    assert!(wildcard_match(".*", ".emacs", no_hidden));
+    let ignore_case = Options {
+        ignore_case: true,
+        ..DEFAULT
+    };
+    assert!(wildcard_match("*.TXT", "readme.txt", ignore_case));
+    assert!(wildcard_match("[A-Z]*", "readme", ignore_case));
+    assert!(!wildcard_match("*.TXT", "readme.txt", DEFAULT));
+}
+
+#[test]
+fn test_expand_braces() {
+    assert_eq!(
+        expand_braces(b"*.{c,h}"),
+        vec![b"*.c".to_vec(), b"*.h".to_vec()]
+    );
+    assert_eq!(
+        expand_braces(b"{a,b{1,2}}x"),
+        vec![b"ax".to_vec(), b"b1x".to_vec(), b"b2x".to_vec()]
+    );
+    assert_eq!(expand_braces(b"{a}"), vec![b"{a}".to_vec()]);
+    assert_eq!(
+        expand_braces(b"{x{a,b}"),
+        vec![b"{xa".to_vec(), b"{xb".to_vec()]
+    );
+}
@@ -0,0 +1,129 @@
+//! Hook profiling.
+//!
+//! While `hook-profiling' is non-nil, the functions that run hooks
+//! record how many times each function on each hook is called and how
+//! long the calls take, so that slow functions on hooks that run all
+//! the time, like `post-command-hook' or `after-change-functions', can
+//! be found.  When it is nil, the only cost is testing the variable.
+
+use std::time::{Duration, Instant};
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    hashtable::{clrhash, gethash, puthash, LispHashTableRef},
+    lisp::{defsubr, LispObject},
+    obarray::intern,
+    remacs_sys::{globals, EmacsDouble, EmacsInt},
+    remacs_sys::{QCtest, Qeq, Qnil},
+};
+
+/// A hash table from hook symbols to hash tables from the functions on
+/// them to (COUNT . NANOSECONDS).
+declare_GC_protected_static!(hook_profile_table, Qnil);
+
+fn make_eq_table() -> LispObject {
+    call!(intern("make-hash-table").as_lisp_obj(), QCtest, Qeq)
+}
+
+fn profile_table() -> LispHashTableRef {
+    unsafe {
+        if hook_profile_table.is_nil() {
+            hook_profile_table = make_eq_table();
+        }
+        hook_profile_table.as_hash_table_or_error()
+    }
+}
+
+/// Count a call of FUNCTION on HOOK that took ELAPSED.  The counters
+/// are updated in place, so that a call only allocates the first time
+/// FUNCTION is seen.
+fn record(hook: LispObject, function: LispObject, elapsed: Duration) {
+    let table = profile_table();
+    let mut functions = gethash(hook, table, Qnil);
+    if functions.is_nil() {
+        functions = make_eq_table();
+        puthash(hook, functions, table);
+    }
+    let functions = functions.as_hash_table_or_error();
+    let nanos = elapsed.as_secs() as EmacsInt * 1_000_000_000 + elapsed.subsec_nanos() as EmacsInt;
+    match gethash(function, functions, Qnil).as_cons() {
+        Some(counts) => {
+            counts.set_car(LispObject::from(counts.car().as_fixnum_or_error() + 1));
+            counts.set_cdr(LispObject::from(counts.cdr().as_fixnum_or_error() + nanos));
+        }
+        None => {
+            let counts = LispObject::cons(LispObject::from(1), LispObject::from(nanos));
+            puthash(function, counts, functions);
+        }
+    }
+}
+
+/// Call FUNCALL with ARGS, whose first element is a function on HOOK,
+/// and return its value.  If `hook-profiling' is non-nil, the call is
+/// counted and timed; calls that exit non-locally are not.
+pub fn call_hook_function<F>(
+    hook: LispObject,
+    args: &mut [LispObject],
+    funcall: &mut F,
+) -> LispObject
+where
+    F: FnMut(&mut [LispObject]) -> LispObject,
+{
+    if !unsafe { globals.hook_profiling } {
+        return funcall(args);
+    }
+    let function = args[0];
+    let start = Instant::now();
+    let value = funcall(args);
+    record(hook, function, start.elapsed());
+    value
+}
+
+/// Return what has been recorded while `hook-profiling' was non-nil.
+/// The value is a list of elements (HOOK FUNCTION COUNT SECONDS), one
+/// for each function that ran on a hook: FUNCTION was called COUNT
+/// times by HOOK, and the calls took SECONDS in all, including the time
+/// spent in hooks that they ran in turn.  The elements are sorted by
+/// SECONDS, the slowest first.
+#[lisp_fn]
+pub fn hook_profile_data() -> LispObject {
+    let mut entries = Vec::new();
+    for (hook, functions) in profile_table().iter() {
+        for (function, counts) in functions.as_hash_table_or_error().iter() {
+            let counts = counts.as_cons_or_error();
+            entries.push((
+                hook,
+                function,
+                counts.car(),
+                counts.cdr().as_fixnum_or_error(),
+            ));
+        }
+    }
+    entries.sort_by(|a, b| b.3.cmp(&a.3));
+    let list: Vec<LispObject> = entries
+        .into_iter()
+        .map(|(hook, function, count, nanos)| {
+            let seconds = nanos as EmacsDouble / 1e9;
+            list!(hook, function, count, LispObject::from_float(seconds))
+        })
+        .collect();
+    LispObject::from(list)
+}
+
+/// Forget what has been recorded while `hook-profiling' was non-nil.
+#[lisp_fn]
+pub fn hook_profile_reset() {
+    clrhash(profile_table());
+}
+
+#[no_mangle]
+pub extern "C" fn syms_of_hookprof() {
+    /// Non-nil means to record the calls of the functions on hooks.
+    /// Each call of a function by `run-hooks', `run-hook-with-args' and
+    /// the like is counted and timed.  Use `hook-profile-report' to see
+    /// the results, and `hook-profile-reset' to start over.
+    defvar_bool!(hook_profiling, "hook-profiling", false);
+}
+
+include!(concat!(env!("OUT_DIR"), "/hookprof_exports.rs"));
@@ -0,0 +1,515 @@
+//! Parsing HTTP/1.1 responses as they arrive.
+//!
+//! `url-http-native' feeds the output of its network processes to a
+//! parser made here, which splits off the status line and the headers
+//! and returns the body with any chunked transfer coding removed.  The
+//! parsers live on the Rust side between calls, and Lisp refers to them
+//! by number.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    decompress::ContentDecoder,
+    lisp::{defsubr, LispObject},
+    multibyte::LispStringRef,
+    remacs_sys::{make_unibyte_string, EmacsInt},
+    remacs_sys::{Qnil, Qt},
+};
+
+lazy_static! {
+    /// The parsers of the responses being received, by number.
+    static ref PARSERS: Mutex<HashMap<EmacsInt, ResponseParser>> = Mutex::new(HashMap::new());
+}
+
+static mut LAST_PARSER_ID: EmacsInt = 0;
+
+/// The largest status line and headers accepted, in bytes.
+const MAX_HEAD_SIZE: usize = 1024 * 1024;
+
+/// The status line and headers of a response.
+#[derive(Debug, PartialEq)]
+pub struct Head {
+    pub version: Vec<u8>,
+    pub status: u16,
+    pub reason: Vec<u8>,
+    /// The headers in the order they came in, with names in lower case.
+    pub headers: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl Head {
+    pub fn header(&self, name: &[u8]) -> Option<&[u8]> {
+        self.headers
+            .iter()
+            .rev()
+            .find(|(n, _)| n.as_slice() == name)
+            .map(|(_, value)| value.as_slice())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Head,
+    /// This many bytes of body are left.
+    Length(u64),
+    /// Waiting for the size line of the next chunk.
+    ChunkSize,
+    /// This many bytes of the current chunk are left.
+    ChunkData(u64),
+    /// Waiting for the line break after a chunk.
+    ChunkEnd,
+    /// Skipping the trailer fields after the last chunk.
+    Trailer,
+    /// The body ends with the connection.
+    UntilClose,
+    Done,
+}
+
+/// An incremental parser for one response.
+pub struct ResponseParser {
+    /// True if the response has no body, whatever its headers say, as
+    /// for requests with the HEAD or CONNECT method.
+    no_body: bool,
+    /// True if the body should be decoded as its Content-Encoding
+    /// header says.
+    decode: bool,
+    decoder: Option<ContentDecoder>,
+    state: State,
+    /// Input that is not consumed yet, because it does not make a
+    /// complete head or line.
+    pending: Vec<u8>,
+    head: Option<Head>,
+}
+
+/// Return the position after the first line of TEXT, and the line
+/// without its line break, or None if TEXT has no complete line.
+fn split_line(text: &[u8]) -> Option<(usize, &[u8])> {
+    let newline = text.iter().position(|&c| c == b'\n')?;
+    let line = &text[..newline];
+    let line = if line.last() == Some(&b'\r') {
+        &line[..line.len() - 1]
+    } else {
+        line
+    };
+    Some((newline + 1, line))
+}
+
+fn trim(text: &[u8]) -> &[u8] {
+    let start = text
+        .iter()
+        .position(|&c| c != b' ' && c != b'\t')
+        .unwrap_or_else(|| text.len());
+    let end = text
+        .iter()
+        .rposition(|&c| c != b' ' && c != b'\t')
+        .map_or(start, |end| end + 1);
+    &text[start..end]
+}
+
+fn parse_number(text: &[u8], radix: u32) -> Option<u64> {
+    let text = std::str::from_utf8(trim(text)).ok()?;
+    u64::from_str_radix(text, radix).ok()
+}
+
+/// Parse the status line and the header lines in LINES.
+fn parse_head(lines: &[&[u8]]) -> Result<Head, String> {
+    let status_line = lines.first().cloned().unwrap_or(b"");
+    let mut fields = status_line.splitn(3, |&c| c == b' ');
+    let version = fields.next().unwrap_or(b"");
+    let status = fields
+        .next()
+        .filter(|status| status.len() == 3)
+        .and_then(|status| parse_number(status, 10));
+    let status = match status {
+        Some(status) if version.starts_with(b"HTTP/") => status as u16,
+        _ => {
+            return Err(format!(
+                "Malformed status line: {}",
+                String::from_utf8_lossy(status_line)
+            ))
+        }
+    };
+    let reason = fields.next().unwrap_or(b"");
+
+    let mut headers: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+    for &line in &lines[1..] {
+        if line.first().map_or(false, |&c| c == b' ' || c == b'\t') {
+            // A continuation of the previous field.
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(b' ');
+                value.extend_from_slice(trim(line));
+                continue;
+            }
+        }
+        match line.iter().position(|&c| c == b':') {
+            Some(colon) if colon > 0 => headers.push((
+                line[..colon].to_ascii_lowercase(),
+                trim(&line[colon + 1..]).to_vec(),
+            )),
+            _ => {
+                return Err(format!(
+                    "Malformed header: {}",
+                    String::from_utf8_lossy(line)
+                ))
+            }
+        }
+    }
+    Ok(Head {
+        version: version.to_vec(),
+        status,
+        reason: reason.to_vec(),
+        headers,
+    })
+}
+
+impl ResponseParser {
+    pub fn new(no_body: bool, decode: bool) -> Self {
+        Self {
+            no_body,
+            decode,
+            decoder: None,
+            state: State::Head,
+            pending: Vec::new(),
+            head: None,
+        }
+    }
+
+    /// Return what came after the complete response, which is the start
+    /// of another protocol after a 101 Switching Protocols response.
+    pub fn take_rest(&mut self) -> Vec<u8> {
+        if self.is_done() {
+            std::mem::replace(&mut self.pending, Vec::new())
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub fn head(&self) -> Option<&Head> {
+        self.head.as_ref()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// Note that the connection was closed, and return true if the
+    /// response is complete.
+    pub fn finish(&mut self) -> bool {
+        if self.state == State::UntilClose {
+            self.state = State::Done;
+        }
+        self.is_done()
+    }
+
+    /// Return the state the body starts in, as given by HEAD.
+    fn body_state(&self, head: &Head) -> Result<State, String> {
+        if self.no_body || head.status / 100 == 1 || head.status == 204 || head.status == 304 {
+            return Ok(State::Done);
+        }
+        if let Some(codings) = head.header(b"transfer-encoding") {
+            let last = codings.rsplit(|&c| c == b',').next().unwrap_or(b"");
+            if trim(last).eq_ignore_ascii_case(b"chunked") {
+                return Ok(State::ChunkSize);
+            }
+            return Ok(State::UntilClose);
+        }
+        match head.header(b"content-length") {
+            Some(length) => match parse_number(length, 10) {
+                Some(0) => Ok(State::Done),
+                Some(length) => Ok(State::Length(length)),
+                None => Err(format!(
+                    "Malformed Content-Length: {}",
+                    String::from_utf8_lossy(length)
+                )),
+            },
+            None => Ok(State::UntilClose),
+        }
+    }
+
+    /// Parse the status line and headers at the start of TEXT, if they
+    /// are complete, and return the number of bytes they take.
+    fn parse_head(&mut self, text: &[u8]) -> Result<Option<usize>, String> {
+        let mut lines = Vec::new();
+        let mut pos = 0;
+        while let Some((next, line)) = split_line(&text[pos..]) {
+            pos += next;
+            if line.is_empty() {
+                // Ignore line breaks before the status line.
+                if lines.is_empty() {
+                    continue;
+                }
+                let head = parse_head(&lines)?;
+                // Informational responses, like 100 Continue, come
+                // before the real one.
+                if head.status / 100 == 1 && head.status != 101 {
+                    return Ok(Some(pos));
+                }
+                self.state = self.body_state(&head)?;
+                if self.decode {
+                    if let Some(codings) = head.header(b"content-encoding") {
+                        let codings: Vec<&[u8]> = codings
+                            .split(|&c| c == b',')
+                            .map(trim)
+                            .filter(|coding| !coding.is_empty())
+                            .collect();
+                        self.decoder = ContentDecoder::new(&codings);
+                    }
+                }
+                self.head = Some(head);
+                return Ok(Some(pos));
+            }
+            lines.push(line);
+        }
+        if text.len() > MAX_HEAD_SIZE {
+            return Err("HTTP response head too large".to_string());
+        }
+        Ok(None)
+    }
+
+    /// Parse INPUT, which follows the input parsed so far, and append
+    /// the bytes of the body it contains to BODY, decoded if the parser
+    /// decodes content.
+    pub fn feed(&mut self, input: &[u8], body: &mut Vec<u8>) -> Result<(), String> {
+        let mut raw = Vec::new();
+        let mut text = std::mem::replace(&mut self.pending, Vec::new());
+        text.extend_from_slice(input);
+        let mut pos = 0;
+
+        while pos < text.len() {
+            let rest = &text[pos..];
+            match self.state {
+                State::Head => match self.parse_head(rest)? {
+                    Some(len) => pos += len,
+                    None => break,
+                },
+                State::Length(left) | State::ChunkData(left) => {
+                    let len = (left.min(rest.len() as u64)) as usize;
+                    raw.extend_from_slice(&rest[..len]);
+                    pos += len;
+                    let left = left - len as u64;
+                    self.state = match self.state {
+                        State::Length(_) if left == 0 => State::Done,
+                        State::Length(_) => State::Length(left),
+                        _ if left == 0 => State::ChunkEnd,
+                        _ => State::ChunkData(left),
+                    };
+                }
+                State::ChunkSize => match split_line(rest) {
+                    Some((len, line)) => {
+                        pos += len;
+                        let size = line.split(|&c| c == b';').next().unwrap_or(b"");
+                        self.state = match parse_number(size, 16) {
+                            Some(0) => State::Trailer,
+                            Some(size) => State::ChunkData(size),
+                            None => {
+                                return Err(format!(
+                                    "Malformed chunk size: {}",
+                                    String::from_utf8_lossy(line)
+                                ))
+                            }
+                        };
+                    }
+                    None => break,
+                },
+                State::ChunkEnd | State::Trailer => match split_line(rest) {
+                    Some((len, line)) => {
+                        pos += len;
+                        if self.state == State::ChunkEnd {
+                            self.state = State::ChunkSize;
+                        } else if line.is_empty() {
+                            self.state = State::Done;
+                        }
+                    }
+                    None => break,
+                },
+                State::UntilClose => {
+                    raw.extend_from_slice(rest);
+                    pos = text.len();
+                }
+                // Anything after the response is kept for `take_rest'.
+                State::Done => break,
+            }
+        }
+
+        text.drain(..pos);
+        self.pending = text;
+        match self.decoder {
+            Some(ref mut decoder) => decoder
+                .decode(&raw, body)
+                .map_err(|err| format!("Content decoding failed: {}", err)),
+            None => {
+                body.append(&mut raw);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn make_unibyte(bytes: &[u8]) -> LispObject {
+    unsafe { make_unibyte_string(bytes.as_ptr() as *const c_char, bytes.len() as ptrdiff_t) }
+}
+
+/// Call F with the parser numbered ID, or signal an error if there is
+/// no such parser.
+fn with_parser<T>(id: EmacsInt, f: impl FnOnce(&mut ResponseParser) -> T) -> T {
+    let mut parsers = PARSERS.lock().unwrap();
+    match parsers.get_mut(&id) {
+        Some(parser) => f(parser),
+        None => {
+            drop(parsers);
+            error!("No HTTP response parser numbered {}", id)
+        }
+    }
+}
+
+/// Make a parser for an HTTP response, and return its number.
+/// If NO-BODY is non-nil, the response is taken to have no body, as is
+/// the case for the responses to HEAD and CONNECT requests.  If DECODE
+/// is non-nil, the body is decoded as its Content-Encoding header says,
+/// if the codings are among gzip, deflate, br and zstd; otherwise it is
+/// left alone.  The parser should be freed with
+/// `http-response-parser-free' when it is no longer needed.
+#[lisp_fn(min = "0")]
+pub fn http_response_parser(no_body: bool, decode: bool) -> EmacsInt {
+    let id = unsafe {
+        LAST_PARSER_ID += 1;
+        LAST_PARSER_ID
+    };
+    PARSERS
+        .lock()
+        .unwrap()
+        .insert(id, ResponseParser::new(no_body, decode));
+    id
+}
+
+/// Free the HTTP response parser numbered PARSER.
+#[lisp_fn]
+pub fn http_response_parser_free(parser: EmacsInt) {
+    PARSERS.lock().unwrap().remove(&parser);
+}
+
+/// Give the HTTP response parser numbered PARSER the next part of the
+/// response, the unibyte string STRING.  Return the part of the body of
+/// the response that STRING completes, as a unibyte string, with any
+/// chunked transfer coding removed and decoded as the parser was asked
+/// to.  Signal an error if the response is
+/// malformed.
+#[lisp_fn]
+pub fn http_response_parser_feed(parser: EmacsInt, string: LispStringRef) -> LispObject {
+    let mut body = Vec::new();
+    let result = with_parser(parser, |p| p.feed(string.as_slice(), &mut body));
+    match result {
+        Ok(()) => make_unibyte(&body),
+        Err(message) => error!("{}", message),
+    }
+}
+
+/// Return the status line and headers of the response the HTTP response
+/// parser numbered PARSER is given, or nil if they are not complete yet.
+/// The value is a list (STATUS REASON HEADERS VERSION), where STATUS is
+/// the status code, REASON is the reason phrase, HEADERS is an alist of
+/// (NAME . VALUE) in the order the headers came in, with NAME in lower
+/// case, and VERSION is the HTTP version, like \"HTTP/1.1\".
+#[lisp_fn]
+pub fn http_response_parser_head(parser: EmacsInt) -> LispObject {
+    let head = with_parser(parser, |p| {
+        p.head().map(|head| {
+            let headers: Vec<LispObject> = head
+                .headers
+                .iter()
+                .map(|(name, value)| LispObject::cons(make_unibyte(name), make_unibyte(value)))
+                .collect();
+            list!(
+                LispObject::from(EmacsInt::from(head.status)),
+                make_unibyte(&head.reason),
+                LispObject::from(headers),
+                make_unibyte(&head.version)
+            )
+        })
+    });
+    head.unwrap_or(Qnil)
+}
+
+/// Return t if the response the HTTP response parser numbered PARSER is
+/// given is complete.
+#[lisp_fn]
+pub fn http_response_parser_done_p(parser: EmacsInt) -> bool {
+    with_parser(parser, |p| p.is_done())
+}
+
+/// Tell the HTTP response parser numbered PARSER that the connection was
+/// closed.  Return t if the response is complete, which is the case for
+/// a response whose body ends with the connection, and nil if it was
+/// cut short.
+#[lisp_fn]
+pub fn http_response_parser_finish(parser: EmacsInt) -> LispObject {
+    if with_parser(parser, |p| p.finish()) {
+        Qt
+    } else {
+        Qnil
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/http_exports.rs"));
+
+#[test]
+fn test_response_parser() {
+    let parse = |no_body: bool, parts: &[&[u8]]| {
+        let mut parser = ResponseParser::new(no_body, false);
+        let mut body = Vec::new();
+        for part in parts {
+            parser.feed(part, &mut body).unwrap();
+        }
+        (parser, body)
+    };
+
+    let (parser, body) = parse(
+        false,
+        &[
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nX-A: 1\r\n",
+            b"\r\nhel",
+            b"lo!",
+        ],
+    );
+    assert!(parser.is_done());
+    assert_eq!(body, b"hello");
+    let head = parser.head().unwrap();
+    assert_eq!(head.status, 200);
+    assert_eq!(head.reason, b"OK");
+    assert_eq!(head.header(b"x-a"), Some(&b"1"[..]));
+
+    let (parser, body) = parse(
+        false,
+        &[
+            b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\n",
+            b"Transfer-Encoding: chunked\r\n\r\n3;x=y\r\nabc\r",
+            b"\n10\r\n0123456789abcdef\r\n0\r\nX-T: 1\r\n\r\n",
+        ],
+    );
+    assert!(parser.is_done());
+    assert_eq!(body, &b"abc0123456789abcdef"[..]);
+
+    let (mut parser, body) = parse(false, &[b"HTTP/1.0 200 OK\n\nuntil close"]);
+    assert!(!parser.is_done());
+    assert_eq!(body, b"until close");
+    assert!(parser.finish());
+
+    let (mut parser, body) = parse(false, &[b"HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\nab"]);
+    assert_eq!(body, b"ab");
+    assert!(!parser.finish());
+
+    let (parser, body) = parse(true, &[b"HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\n"]);
+    assert!(parser.is_done());
+    assert!(body.is_empty());
+
+    let mut parser = ResponseParser::new(false, false);
+    assert!(parser.feed(b"SSH-2.0\r\n\r\n", &mut Vec::new()).is_err());
+    let mut parser = ResponseParser::new(false, false);
+    assert!(parser
+        .feed(b"HTTP/1.1 200 OK\r\nbad\r\n\r\n", &mut Vec::new())
+        .is_err());
+}
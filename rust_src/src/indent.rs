@@ -5,10 +5,11 @@ use std::ptr;
 use remacs_macros::lisp_fn;
 
 use crate::{
-    buffers::{point_byte, point_min_byte},
+    buffers::{point_byte, point_min_byte, LispBufferOrCurrent, LispBufferRef, BEG_BYTE},
     editfns::{point, point_min},
-    lisp::defsubr,
-    remacs_sys::{self, find_newline, position_indentation, EmacsInt},
+    lisp::{defsubr, LispObject},
+    remacs_sys::{self, find_newline, position_indentation, EmacsDouble, EmacsInt, EmacsUint},
+    remacs_sys::Qnil,
 };
 
 /// Return the indentation of the current line.  This is the
@@ -52,4 +53,163 @@ pub fn current_column() -> EmacsInt {
     column as EmacsInt
 }
 
+/// How the lines of some text are indented.
+#[derive(Debug, Default, PartialEq)]
+pub struct IndentProfile {
+    /// The changes of indentation between consecutive nonblank lines,
+    /// in columns.  Changes of one column are left out, as they are
+    /// usually alignment rather than indentation.
+    pub changes: Vec<usize>,
+    /// The number of lines whose indentation has a tab.
+    pub tab_lines: usize,
+    /// The number of lines indented by at least a tab width with spaces
+    /// only, which would have a tab if tabs were used.
+    pub space_lines: usize,
+}
+
+/// The number of lines `detect-indentation' looks at by default.
+const DEFAULT_SAMPLE_LINES: usize = 5000;
+
+/// The share of the indentation changes an offset must divide to be
+/// taken for the indentation step.
+const OFFSET_AGREEMENT: f64 = 0.9;
+
+/// The least number of indentation changes an offset is guessed from.
+const MIN_INDENT_CHANGES: usize = 5;
+
+/// Return how the first MAX_LINES lines of TEXT are indented, with tabs
+/// TAB_WIDTH columns apart.
+pub fn indent_profile<I>(text: I, tab_width: usize, max_lines: usize) -> IndentProfile
+where
+    I: IntoIterator<Item = u8>,
+{
+    let mut profile = IndentProfile::default();
+    let mut previous = None;
+    let mut lines = 0;
+    let mut text = text.into_iter().peekable();
+    while lines < max_lines && text.peek().is_some() {
+        lines += 1;
+        let (mut column, mut tabs) = (0, false);
+        let mut blank = true;
+        for byte in &mut text {
+            match byte {
+                b' ' if blank => column += 1,
+                b'\t' if blank => {
+                    column += tab_width - column % tab_width;
+                    tabs = true;
+                }
+                b'\n' => break,
+                _ => blank = false,
+            }
+        }
+        if blank {
+            continue;
+        }
+
+        if tabs {
+            profile.tab_lines += 1;
+        } else if column >= tab_width {
+            profile.space_lines += 1;
+        }
+        if let Some(previous) = previous {
+            let change = if column > previous {
+                column - previous
+            } else {
+                previous - column
+            };
+            if change > 1 {
+                profile.changes.push(change);
+            }
+        }
+        previous = Some(column);
+    }
+    profile
+}
+
+/// Return the indentation step that explains CHANGES, the changes of
+/// indentation between lines: the largest offset from 2 to 8 that
+/// divides nearly all of them.
+pub fn guess_indent_offset(changes: &[usize]) -> Option<usize> {
+    if changes.len() < MIN_INDENT_CHANGES {
+        return None;
+    }
+    (2..=8).rev().find(|offset| {
+        let agreeing = changes.iter().filter(|&&c| c % offset == 0).count();
+        agreeing as f64 >= OFFSET_AGREEMENT * changes.len() as f64
+    })
+}
+
+fn sane_tab_width(buffer: LispBufferRef) -> usize {
+    match buffer.tab_width_.as_fixnum() {
+        Some(width) if width > 0 && width <= 1000 => width as usize,
+        _ => 8,
+    }
+}
+
+/// Guess how the text of BUFFER is indented.
+/// BUFFER defaults to the current buffer.  The value is a list (OFFSET
+/// TABS-RATIO).  OFFSET is the number of columns by which lines are
+/// indented relative to each other, or nil if there are too few
+/// indented lines, or they agree on no offset from 2 to 8.  TABS-RATIO
+/// is the share of the indented lines that are indented with tabs, as a
+/// float from 0 to 1, or nil if no line tells; lines indented with
+/// spaces by less than `tab-width' do not count.
+///
+/// Only the first MAX-LINES lines of the buffer are looked at, 5000 by
+/// default.  Narrowing is ignored.  This is meant for setting variables
+/// like `indent-tabs-mode' when a file is visited.
+#[lisp_fn(min = "0")]
+pub fn detect_indentation(buffer: LispBufferOrCurrent, max_lines: Option<EmacsUint>) -> LispObject {
+    let buffer: LispBufferRef = buffer.into();
+    let max_lines = max_lines.map_or(DEFAULT_SAMPLE_LINES, |lines| lines as usize);
+    let text = (BEG_BYTE..buffer.z_byte()).map(|pos| buffer.fetch_byte(pos));
+    let profile = indent_profile(text, sane_tab_width(buffer), max_lines);
+
+    let offset = guess_indent_offset(&profile.changes).map(|offset| offset as EmacsInt);
+    let indented = profile.tab_lines + profile.space_lines;
+    let tabs_ratio = if indented > 0 {
+        LispObject::from_float(profile.tab_lines as EmacsDouble / indented as EmacsDouble)
+    } else {
+        Qnil
+    };
+    list!(LispObject::from(offset), tabs_ratio)
+}
+
 include!(concat!(env!("OUT_DIR"), "/indent_exports.rs"));
+
+#[test]
+fn test_indent_profile() {
+    let text = "a\n    b\n\n\tc\n  \n  d\n";
+    assert_eq!(
+        indent_profile(text.bytes(), 8, 100),
+        IndentProfile {
+            changes: vec![4, 4, 6],
+            tab_lines: 1,
+            space_lines: 0,
+        }
+    );
+    assert_eq!(
+        indent_profile(text.bytes(), 4, 2),
+        IndentProfile {
+            changes: vec![4],
+            tab_lines: 0,
+            space_lines: 1,
+        }
+    );
+    // Changes of one column are alignment.
+    assert_eq!(indent_profile("a\n b\n".bytes(), 8, 100).changes, vec![]);
+}
+
+#[test]
+fn test_guess_indent_offset() {
+    assert_eq!(guess_indent_offset(&[4, 4, 8, 4, 12]), Some(4));
+    assert_eq!(guess_indent_offset(&[2, 4, 2, 2, 6]), Some(2));
+    assert_eq!(guess_indent_offset(&[8, 8, 16, 8, 8]), Some(8));
+    assert_eq!(guess_indent_offset(&[3, 6, 3, 3, 3]), Some(3));
+    assert_eq!(guess_indent_offset(&[4, 4]), None);
+    assert_eq!(guess_indent_offset(&[4, 3, 5, 7, 4]), None);
+    // A little alignment is tolerated.
+    let mut changes = vec![4; 19];
+    changes.push(7);
+    assert_eq!(guess_indent_offset(&changes), Some(4));
+}
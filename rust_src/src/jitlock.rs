@@ -0,0 +1,133 @@
+//! Bookkeeping for jit-lock.
+//!
+//! jit-lock records which text has been fontified in its `fontified'
+//! text property: t for fontified text, nil for text that redisplay
+//! must fontify before showing it, and `defer' for text whose
+//! fontification was put off until Emacs is idle.  These functions do
+//! the scanning and marking of that property for the deferred and
+//! stealth fontification timers of jit-lock.el, which still schedules
+//! them.  The callers are expected to make the buffer ready for jit-lock
+//! with `with-buffer-prepared-for-jit-lock', as the changes they make
+//! are not modifications of the buffer.
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    lisp::{defsubr, LispObject},
+    obarray::intern,
+    remacs_sys::{EmacsInt, Qfontified, Qnil, Qt},
+    remacs_sys::{Fget_text_property, Fnext_single_property_change, Fput_text_property},
+    remacs_sys::{Fprevious_single_property_change, Ftext_property_not_all},
+    threads::ThreadState,
+};
+
+/// Return the value of the `fontified' property at POS.
+fn fontified(pos: EmacsInt) -> LispObject {
+    unsafe { Fget_text_property(LispObject::from(pos), Qfontified, Qnil) }
+}
+
+/// Return the position after POS at which `fontified' changes, or LIMIT.
+fn next_change(pos: EmacsInt, limit: EmacsInt) -> EmacsInt {
+    unsafe {
+        Fnext_single_property_change(
+            LispObject::from(pos),
+            Qfontified,
+            Qnil,
+            LispObject::from(limit),
+        )
+    }
+    .as_fixnum_or_error()
+}
+
+/// Return the position before POS at which `fontified' changes, or None
+/// if it is the same up to the beginning of the accessible portion.
+fn previous_change(pos: EmacsInt) -> Option<EmacsInt> {
+    unsafe { Fprevious_single_property_change(LispObject::from(pos), Qfontified, Qnil, Qnil) }
+        .as_fixnum()
+}
+
+fn put_fontified(start: EmacsInt, end: EmacsInt, value: LispObject) {
+    unsafe {
+        Fput_text_property(
+            LispObject::from(start),
+            LispObject::from(end),
+            Qfontified,
+            value,
+            Qnil,
+        )
+    };
+}
+
+/// Return the start of the next chunk to fontify stealthily around
+/// position AROUND, where chunks are CHUNK-SIZE characters long.
+/// This is the part of the text that is not yet fontified nearest to
+/// AROUND.  Value is nil if there is nothing more to fontify.
+#[lisp_fn]
+pub fn jit_lock_stealth_chunk_start_native(
+    around: EmacsInt,
+    chunk_size: EmacsInt,
+) -> Option<EmacsInt> {
+    let buffer = ThreadState::current_buffer();
+    if buffer.z() == buffer.beg() {
+        return None;
+    }
+    let (begv, zv) = (buffer.begv as EmacsInt, buffer.zv as EmacsInt);
+    let next = unsafe {
+        Ftext_property_not_all(
+            LispObject::from(around),
+            LispObject::from(zv),
+            Qfontified,
+            Qt,
+            Qnil,
+        )
+    }
+    .as_fixnum();
+    let fontified_before = fontified((around - 1).max(begv)).eq(Qt);
+    let start = match previous_change(around) {
+        // Everything in front of AROUND is fontified, or nothing is.
+        None if fontified_before => None,
+        None => Some((around - chunk_size / 2).max(begv)),
+        // PREV starts the fontified text containing AROUND.  Start a
+        // chunk before the end of the unfontified text in front of it.
+        Some(prev) if fontified_before => {
+            Some((prev - chunk_size).max(previous_change(prev).unwrap_or(begv)))
+        }
+        // PREV starts the unfontified text containing AROUND.
+        Some(prev) => Some(prev.max(around - chunk_size)),
+    };
+    match (start, next) {
+        (Some(start), Some(next)) if around - start >= next - around => Some(next),
+        (Some(start), _) => Some(start),
+        (None, next) => next,
+    }
+}
+
+/// Mark the text from START to the next change of its `fontified'
+/// property, but at most CHUNK-SIZE characters of it, as deferred, so
+/// that redisplay shows it as is and `jit-lock-undefer-native' finds it
+/// when Emacs becomes idle.
+#[lisp_fn]
+pub fn jit_lock_defer_native(start: EmacsInt, chunk_size: EmacsInt) {
+    let limit = (start + chunk_size).min(ThreadState::current_buffer().zv as EmacsInt);
+    let end = next_change(start, limit);
+    put_fontified(start, end, intern("defer").as_lisp_obj());
+}
+
+/// Mark the text whose fontification was deferred in the accessible
+/// portion of the current buffer as not fontified, so that redisplay
+/// fontifies it.
+#[lisp_fn]
+pub fn jit_lock_undefer_native() {
+    let buffer = ThreadState::current_buffer();
+    let (mut pos, zv) = (buffer.begv as EmacsInt, buffer.zv as EmacsInt);
+    let defer = intern("defer").as_lisp_obj();
+    while pos < zv {
+        let end = next_change(pos, zv);
+        if fontified(pos).eq(defer) {
+            put_fontified(pos, end, Qnil);
+        }
+        pos = end;
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/jitlock_exports.rs"));
@@ -0,0 +1,275 @@
+//! Command loop latency.
+//!
+//! While `latency-watchdog' is non-nil, the command loop tells this
+//! module when it starts to wait for input, to run a command and to
+//! redisplay, and the time spent in each phase is added up.  When the
+//! next command starts, the time taken by the last one and by the
+//! redisplay after it go into histograms, and the slowest commands are
+//! kept with a backtrace taken while they were running, once they were
+//! slower than `latency-watchdog-threshold'.  `latency-report' returns
+//! all of that.
+
+use std::ptr;
+use std::time::{Duration, Instant};
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    lisp::{defsubr, LispObject},
+    numbers::LispNumber,
+    obarray::intern,
+    remacs_sys::{atimer, atimer_type, latency_phase},
+    remacs_sys::{cancel_atimer, dtotimespec, get_backtrace, globals, start_atimer, Fmake_vector},
+    remacs_sys::{EmacsDouble, EmacsInt, Qnil},
+};
+
+/// The number of slowest commands kept.
+const MAX_OFFENDERS: usize = 10;
+
+/// The number of functions kept in a backtrace.
+const BACKTRACE_DEPTH: EmacsInt = 16;
+
+/// The histogram buckets hold the times under 1, 2, 4 ... 1024
+/// milliseconds, and longer ones.
+const BUCKETS: usize = 12;
+
+/// Return the histogram bucket for a time of NANOS nanoseconds.
+fn bucket(nanos: u64) -> usize {
+    let millis = nanos / 1_000_000;
+    let bits = 64 - millis.leading_zeros() as usize;
+    bits.min(BUCKETS - 1)
+}
+
+fn nanos(duration: Duration) -> u64 {
+    duration.as_secs() * 1_000_000_000 + u64::from(duration.subsec_nanos())
+}
+
+fn seconds(nanos: u64) -> EmacsDouble {
+    nanos as EmacsDouble / 1e9
+}
+
+struct Watchdog {
+    /// When the current phase started, or None if it is not timed.
+    since: Option<Instant>,
+    /// The nanoseconds spent in each phase since the last command
+    /// started.
+    elapsed: [u64; 3],
+    histograms: [[u64; BUCKETS]; 3],
+    /// The timer that takes a backtrace of a slow command, or null.
+    timer: *mut atimer,
+    /// Whether `latency_backtrace' holds a backtrace of this command.
+    sampled: bool,
+}
+
+static mut WATCHDOG: Watchdog = Watchdog {
+    since: None,
+    elapsed: [0; 3],
+    histograms: [[0; BUCKETS]; 3],
+    timer: 0 as *mut atimer,
+    sampled: false,
+};
+
+/// The phase the command loop is in.
+static mut CURRENT_PHASE: latency_phase::Type = latency_phase::LATENCY_INPUT;
+
+/// The command whose time is being added up.
+declare_GC_protected_static!(latency_command, Qnil);
+
+/// A vector for the backtrace taken by the timer.
+declare_GC_protected_static!(latency_backtrace, Qnil);
+
+/// The slowest commands, as returned by `latency-report', slowest first.
+declare_GC_protected_static!(latency_offenders, Qnil);
+
+/// The value of `latency-watchdog-threshold', in nanoseconds.
+fn threshold() -> u64 {
+    let seconds = match unsafe { globals.Vlatency_watchdog_threshold }.as_number_coerce_marker() {
+        Some(LispNumber::Fixnum(n)) => n as f64,
+        Some(LispNumber::Float(n)) => n,
+        None => 0.0,
+    };
+    (seconds.max(0.0) * 1e9) as u64
+}
+
+/// Take a backtrace of the command that has been running for
+/// `latency-watchdog-threshold' seconds.  Atimers run from
+/// `process_pending_signals', where the stack can be looked at.
+extern "C" fn take_backtrace(_timer: *mut atimer) {
+    unsafe {
+        WATCHDOG.timer = ptr::null_mut();
+        if CURRENT_PHASE != latency_phase::LATENCY_INPUT && latency_backtrace.is_not_nil() {
+            get_backtrace(latency_backtrace);
+            WATCHDOG.sampled = true;
+        }
+    }
+}
+
+fn cancel_timer(watchdog: &mut Watchdog) {
+    if !watchdog.timer.is_null() {
+        unsafe { cancel_atimer(watchdog.timer) };
+        watchdog.timer = ptr::null_mut();
+    }
+}
+
+/// Account for the command that just finished, now that the next one
+/// is starting.
+fn end_command(watchdog: &mut Watchdog) {
+    for (phase, elapsed) in watchdog.elapsed.iter().enumerate() {
+        if *elapsed > 0 || phase == latency_phase::LATENCY_INPUT as usize {
+            watchdog.histograms[phase][bucket(*elapsed)] += 1;
+        }
+    }
+    let command = watchdog.elapsed[latency_phase::LATENCY_COMMAND as usize];
+    let redisplay = watchdog.elapsed[latency_phase::LATENCY_REDISPLAY as usize];
+    let total = command + redisplay;
+    if total >= threshold() && unsafe { latency_command }.is_not_nil() {
+        let backtrace: Vec<LispObject> = if watchdog.sampled {
+            unsafe { latency_backtrace }
+                .as_vector_or_error()
+                .iter()
+                .take_while(|f| f.is_not_nil())
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let entry = list!(
+            LispObject::from_float(seconds(total)),
+            unsafe { latency_command },
+            LispObject::from_float(seconds(command)),
+            LispObject::from_float(seconds(redisplay)),
+            LispObject::from(backtrace)
+        );
+        let mut offenders: Vec<LispObject> =
+            unsafe { latency_offenders }.iter_cars_safe().collect();
+        let slower = offenders
+            .iter()
+            .take_while(|o| o.as_cons_or_error().car().as_float_or_error() >= seconds(total))
+            .count();
+        offenders.insert(slower, entry);
+        offenders.truncate(MAX_OFFENDERS);
+        unsafe { latency_offenders = LispObject::from(offenders) };
+    }
+    watchdog.elapsed = [0; 3];
+    watchdog.sampled = false;
+    unsafe { latency_command = Qnil };
+
+    cancel_timer(watchdog);
+    unsafe {
+        if latency_backtrace.is_nil() {
+            latency_backtrace = Fmake_vector(LispObject::from(BACKTRACE_DEPTH), Qnil);
+        }
+        watchdog.timer = start_atimer(
+            atimer_type::ATIMER_RELATIVE,
+            dtotimespec(seconds(threshold())),
+            Some(take_backtrace),
+            ptr::null_mut(),
+        );
+    }
+}
+
+/// Tell the watchdog that the command loop enters PHASE, and return the
+/// phase it was in, to go back to when PHASE is over.
+#[no_mangle]
+pub extern "C" fn latency_phase_start(phase: latency_phase::Type) -> latency_phase::Type {
+    let previous = unsafe { CURRENT_PHASE };
+    unsafe { CURRENT_PHASE = phase };
+    let watchdog = unsafe { &mut WATCHDOG };
+    if !unsafe { globals.latency_watchdog } {
+        if watchdog.since.is_some() {
+            cancel_timer(watchdog);
+            watchdog.since = None;
+            watchdog.elapsed = [0; 3];
+        }
+        return previous;
+    }
+
+    let now = Instant::now();
+    if let Some(since) = watchdog.since {
+        watchdog.elapsed[previous as usize] += nanos(now - since);
+    }
+    watchdog.since = Some(now);
+    if phase == latency_phase::LATENCY_INPUT && previous == latency_phase::LATENCY_COMMAND {
+        unsafe { latency_command = globals.Vthis_command };
+    } else if phase == latency_phase::LATENCY_COMMAND && previous == latency_phase::LATENCY_INPUT {
+        end_command(watchdog);
+    }
+    previous
+}
+
+fn histogram(phase: latency_phase::Type) -> LispObject {
+    let counts: Vec<LispObject> = unsafe { WATCHDOG.histograms[phase as usize] }
+        .iter()
+        .map(|&n| LispObject::from(n as EmacsInt))
+        .collect();
+    LispObject::from(counts)
+}
+
+/// Return what the latency watchdog recorded.
+/// The watchdog runs while `latency-watchdog' is non-nil.  The value
+/// is a plist:
+///
+/// :input      The histogram of the time spent waiting for input.
+/// :command    The histogram of the time spent running commands.
+/// :redisplay  The histogram of the time spent redisplaying after them.
+/// :worst      The slowest commands.
+///
+/// A histogram is a list of 12 counts: of the times under 1, 2, 4, 8
+/// ... 1024 milliseconds, and of longer ones.  Each element of the
+/// list of slowest commands is (SECONDS COMMAND COMMAND-SECONDS
+/// REDISPLAY-SECONDS BACKTRACE), where SECONDS is the sum of the time
+/// spent running COMMAND and redisplaying after it, and BACKTRACE is a
+/// list of the functions that were running once the command had taken
+/// longer than `latency-watchdog-threshold', innermost first, or nil.
+#[lisp_fn]
+pub fn latency_report() -> LispObject {
+    let keyword = |name: &str| intern(name).as_lisp_obj();
+    list!(
+        keyword(":input"),
+        histogram(latency_phase::LATENCY_INPUT),
+        keyword(":command"),
+        histogram(latency_phase::LATENCY_COMMAND),
+        keyword(":redisplay"),
+        histogram(latency_phase::LATENCY_REDISPLAY),
+        keyword(":worst"),
+        unsafe { latency_offenders }
+    )
+}
+
+/// Forget what the latency watchdog recorded.
+#[lisp_fn]
+pub fn latency_report_reset() {
+    unsafe {
+        WATCHDOG.histograms = [[0; BUCKETS]; 3];
+        latency_offenders = Qnil;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn syms_of_latency() {
+    /// Non-nil means to time the phases of the command loop.
+    /// The time spent waiting for input, running each command and
+    /// redisplaying after it is recorded, and `latency-report' returns
+    /// what was recorded.
+    defvar_bool!(latency_watchdog, "latency-watchdog", false);
+
+    /// Seconds after which a command is slow, for `latency-watchdog'.
+    /// Commands that take longer than this, with the redisplay after
+    /// them, are listed by `latency-report', with a backtrace taken this
+    /// many seconds into the command.
+    defvar_lisp!(
+        Vlatency_watchdog_threshold,
+        "latency-watchdog-threshold",
+        LispObject::from_float(0.1)
+    );
+}
+
+include!(concat!(env!("OUT_DIR"), "/latency_exports.rs"));
+
+#[test]
+fn test_bucket() {
+    assert_eq!(bucket(nanos(Duration::from_micros(500))), 0);
+    assert_eq!(bucket(nanos(Duration::from_millis(1))), 1);
+    assert_eq!(bucket(nanos(Duration::from_millis(3))), 2);
+    assert_eq!(bucket(nanos(Duration::from_millis(1023))), 10);
+    assert_eq!(bucket(nanos(Duration::from_secs(5))), BUCKETS - 1);
+}
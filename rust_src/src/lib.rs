@@ -18,6 +18,8 @@
 #![feature(const_fn_union)]
 #![feature(ptr_offset_from)]
 
+#[macro_use]
+extern crate bitflags;
 extern crate errno;
 #[macro_use]
 extern crate if_chain;
@@ -25,11 +27,16 @@ extern crate if_chain;
 extern crate lazy_static;
 
 extern crate base64 as base64_crate;
+extern crate brotli_decompressor;
 extern crate libc;
 extern crate md5;
 extern crate rand;
 extern crate sha1;
 extern crate sha2;
+extern crate tar;
+extern crate toml as toml_crate;
+extern crate zip;
+extern crate zstd;
 
 extern crate field_offset;
 extern crate flate2;
@@ -58,7 +65,10 @@ mod frames;
 mod vector_macros;
 mod str2sig;
 
+mod align;
 mod alloc;
+mod ansi;
+mod archive;
 mod base64;
 mod buffers;
 mod bytecode;
@@ -67,57 +77,107 @@ mod callproc;
 mod casefiddle;
 mod casetab;
 mod category;
+mod ccl;
 mod character;
 mod charset;
 mod chartable;
+mod cmdhist;
 mod cmds;
+mod composite;
+mod crash;
 mod crypto;
+mod dabbrev;
 mod data;
 mod decompress;
+mod desktop;
+mod diff;
 mod dired;
 #[cfg(unix)]
 mod dired_unix;
 #[cfg(windows)]
 mod dired_windows;
+mod dirlocals;
 mod dispnew;
 mod editfns;
 mod emacs;
+mod environ;
 mod eval;
 mod ffi;
+mod filehash;
+mod filelocals;
 mod fileio;
+mod fill;
 mod floatfns;
 mod fns;
+mod fontlock;
 mod fonts;
+mod frametitle;
+mod glob;
 mod hashtable;
+mod hookprof;
+mod http;
 mod indent;
 mod interactive;
+mod jitlock;
 mod keyboard;
 mod keymap;
+mod latency;
 mod libm;
+mod lineedit;
+mod linestats;
 mod lists;
+mod locations;
 mod lread;
+mod mail;
 mod marker;
 mod math;
+mod mime;
 mod minibuf;
+mod msgcat;
 mod multibyte;
 mod numbers;
 mod obarray;
 mod objects;
+mod occur;
+mod orgtable;
+mod paren;
+mod persistent;
 mod process;
 mod profiler;
+mod pty;
+mod quail;
+mod regexpopt;
 #[allow(clippy::all)]
 mod remacs_sys;
+mod sandbox;
 mod search;
+mod serialize;
+mod server;
+mod shell;
+mod shr;
+mod sort;
+mod spell;
 mod strings;
+mod supersession;
 mod symbols;
 mod syntax;
+mod sysinfo;
 mod terminal;
+mod termemu;
 mod textprop;
 mod threads;
 mod time;
+mod toml;
+mod tramp;
+mod tzfile;
+mod url;
 mod util;
 mod vectors;
+mod vmotion;
+mod websocket;
 mod windows;
+mod winhighlight;
+mod wordindex;
 mod xml;
 
 #[cfg(all(not(test), target_os = "macos"))]
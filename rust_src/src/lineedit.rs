@@ -0,0 +1,534 @@
+//! Line editing for reading from a terminal in batch mode.
+//!
+//! When Emacs runs with `-batch' or `--script', the minibuffer reads
+//! lines from standard input.  If that is a terminal, the line is read
+//! with a small editor instead of the terminal's cooked mode, which
+//! knows the usual Emacs motion and killing keys and the history of the
+//! minibuffer, so that scripts that prompt for input, and REPLs written
+//! as scripts, are comfortable to use.
+
+use std::mem;
+
+use libc::{c_char, ptrdiff_t};
+
+use crate::{
+    lisp::LispObject,
+    obarray::intern,
+    remacs_sys::{find_symbol_value, globals, make_string},
+    remacs_sys::{Qnil, Qt, Qunbound},
+};
+
+/// What the caller has to do after a key.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    /// Redraw the line and read the next key.
+    Continue,
+    /// The line is complete.
+    Accept,
+    /// The user signaled the end of input.
+    Eof,
+}
+
+/// Where the editor is in an escape sequence.
+#[derive(Clone, Debug, PartialEq)]
+enum KeyState {
+    Normal,
+    /// After ESC.
+    Escape,
+    /// After ESC [ or ESC O, with the parameters so far.
+    Sequence(String),
+}
+
+pub struct LineEditor {
+    line: Vec<char>,
+    point: usize,
+    /// The history, most recent first.
+    history: Vec<String>,
+    /// The history element being shown, if any.
+    history_index: Option<usize>,
+    /// The line that was being edited before moving into the history.
+    saved_line: Vec<char>,
+    killed: Vec<char>,
+    state: KeyState,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric()
+}
+
+impl LineEditor {
+    pub fn new(history: Vec<String>) -> Self {
+        LineEditor {
+            line: Vec::new(),
+            point: 0,
+            history,
+            history_index: None,
+            saved_line: Vec::new(),
+            killed: Vec::new(),
+            state: KeyState::Normal,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.line.iter().collect()
+    }
+
+    /// Return what redraws the line after the prompt PROMPT, which must
+    /// be on the line already, and leaves the cursor at point.
+    pub fn render(&self, prompt: &str) -> String {
+        // Only the last line of the prompt is redrawn.
+        let prompt = prompt.rsplit('\n').next().unwrap_or("");
+        let mut out = format!("\r{}{}\x1b[K", prompt, self.text());
+        let back = self.line.len() - self.point;
+        if back > 0 {
+            out.push_str(&format!("\x1b[{}D", back));
+        }
+        out
+    }
+
+    /// Handle the input character C.
+    pub fn key(&mut self, c: char) -> Action {
+        match mem::replace(&mut self.state, KeyState::Normal) {
+            KeyState::Normal => self.plain_key(c),
+            KeyState::Escape => {
+                match c {
+                    '[' | 'O' => self.state = KeyState::Sequence(String::new()),
+                    'b' | 'B' => self.point = self.word_start(),
+                    'f' | 'F' => self.point = self.word_end(),
+                    'd' | 'D' => {
+                        let end = self.word_end();
+                        self.kill(self.point, end);
+                    }
+                    '\x7f' | '\x08' => {
+                        let start = self.word_start();
+                        self.kill(start, self.point);
+                    }
+                    '<' => self.history_move(Some(self.history.len())),
+                    '>' => self.history_move(None),
+                    _ => {}
+                }
+                Action::Continue
+            }
+            KeyState::Sequence(mut params) => {
+                if c.is_ascii_digit() || c == ';' {
+                    params.push(c);
+                    self.state = KeyState::Sequence(params);
+                } else {
+                    self.sequence(&params, c);
+                }
+                Action::Continue
+            }
+        }
+    }
+
+    fn plain_key(&mut self, c: char) -> Action {
+        match c {
+            '\r' | '\n' => return Action::Accept,
+            '\x04' if self.line.is_empty() => return Action::Eof,
+            '\x01' => self.point = 0,
+            '\x05' => self.point = self.line.len(),
+            '\x02' => self.point = self.point.saturating_sub(1),
+            '\x06' => self.point = (self.point + 1).min(self.line.len()),
+            '\x04' => self.delete_forward(),
+            '\x7f' | '\x08' => {
+                if self.point > 0 {
+                    self.point -= 1;
+                    self.line.remove(self.point);
+                }
+            }
+            '\x0b' => {
+                let end = self.line.len();
+                self.kill(self.point, end);
+            }
+            '\x15' => {
+                let point = self.point;
+                self.kill(0, point);
+            }
+            '\x17' => {
+                let start = self.word_start();
+                self.kill(start, self.point);
+            }
+            '\x19' => {
+                let killed = self.killed.clone();
+                self.insert(&killed);
+            }
+            '\x14' => self.transpose(),
+            '\x10' => self.history_previous(),
+            '\x0e' => self.history_next(),
+            '\x1b' => self.state = KeyState::Escape,
+            _ if c.is_control() => {}
+            _ => self.insert(&[c]),
+        }
+        Action::Continue
+    }
+
+    /// Handle the escape sequence with parameters PARAMS and final
+    /// character C, as sent by cursor and editing keys.
+    fn sequence(&mut self, params: &str, c: char) {
+        match (params, c) {
+            (_, 'A') => self.history_previous(),
+            (_, 'B') => self.history_next(),
+            (_, 'C') => self.point = (self.point + 1).min(self.line.len()),
+            (_, 'D') => self.point = self.point.saturating_sub(1),
+            (_, 'H') | ("1", '~') | ("7", '~') => self.point = 0,
+            (_, 'F') | ("4", '~') | ("8", '~') => self.point = self.line.len(),
+            ("3", '~') => self.delete_forward(),
+            _ => {}
+        }
+    }
+
+    fn insert(&mut self, chars: &[char]) {
+        for &c in chars {
+            self.line.insert(self.point, c);
+            self.point += 1;
+        }
+    }
+
+    fn delete_forward(&mut self) {
+        if self.point < self.line.len() {
+            self.line.remove(self.point);
+        }
+    }
+
+    fn kill(&mut self, start: usize, end: usize) {
+        if start < end {
+            self.killed = self.line.drain(start..end).collect();
+            self.point = start;
+        }
+    }
+
+    fn transpose(&mut self) {
+        let len = self.line.len();
+        if len < 2 || self.point == 0 {
+            return;
+        }
+        // At the end of the line, transpose the last two characters.
+        let point = if self.point == len {
+            len - 1
+        } else {
+            self.point
+        };
+        self.line.swap(point - 1, point);
+        self.point = point + 1;
+    }
+
+    /// The start of the word before point.
+    fn word_start(&self) -> usize {
+        let mut i = self.point;
+        while i > 0 && !is_word_char(self.line[i - 1]) {
+            i -= 1;
+        }
+        while i > 0 && is_word_char(self.line[i - 1]) {
+            i -= 1;
+        }
+        i
+    }
+
+    /// The end of the word after point.
+    fn word_end(&self) -> usize {
+        let len = self.line.len();
+        let mut i = self.point;
+        while i < len && !is_word_char(self.line[i]) {
+            i += 1;
+        }
+        while i < len && is_word_char(self.line[i]) {
+            i += 1;
+        }
+        i
+    }
+
+    fn history_previous(&mut self) {
+        let index = self.history_index.map_or(0, |i| i + 1);
+        self.history_move(Some(index));
+    }
+
+    fn history_next(&mut self) {
+        match self.history_index {
+            Some(0) | None => self.history_move(None),
+            Some(i) => self.history_move(Some(i - 1)),
+        }
+    }
+
+    /// Show history element INDEX, or the line being edited if None.
+    /// An INDEX past the oldest element shows the oldest element.
+    fn history_move(&mut self, index: Option<usize>) {
+        if self.history.is_empty() {
+            return;
+        }
+        let index = index.map(|i| i.min(self.history.len() - 1));
+        if self.history_index.is_none() && index.is_some() {
+            self.saved_line = self.line.clone();
+        }
+        self.line = match index {
+            Some(i) => self.history[i].chars().collect(),
+            None => mem::replace(&mut self.saved_line, Vec::new()),
+        };
+        self.history_index = index;
+        self.point = self.line.len();
+    }
+}
+
+#[cfg(unix)]
+mod tty {
+    use std::io::{self, Write};
+
+    use libc::termios;
+
+    use super::{Action, LineEditor};
+
+    /// The terminal on standard input, switched to raw mode while it
+    /// exists.
+    struct RawMode {
+        saved: termios,
+    }
+
+    impl RawMode {
+        fn enter() -> Option<Self> {
+            unsafe {
+                let mut saved: termios = std::mem::zeroed();
+                if libc::tcgetattr(libc::STDIN_FILENO, &mut saved) != 0 {
+                    return None;
+                }
+                let mut raw = saved;
+                raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::IEXTEN | libc::ISIG);
+                raw.c_iflag &= !(libc::ICRNL | libc::IXON);
+                raw.c_cc[libc::VMIN] = 1;
+                raw.c_cc[libc::VTIME] = 0;
+                if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &raw) != 0 {
+                    return None;
+                }
+                Some(RawMode { saved })
+            }
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &self.saved) };
+        }
+    }
+
+    /// Read one byte from standard input, or None at the end of input.
+    fn read_byte() -> io::Result<Option<u8>> {
+        let mut byte = 0u8;
+        loop {
+            let n = unsafe { libc::read(libc::STDIN_FILENO, &mut byte as *mut u8 as *mut _, 1) };
+            match n {
+                1 => return Ok(Some(byte)),
+                0 => return Ok(None),
+                _ => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn is_terminal() -> bool {
+        unsafe { libc::isatty(libc::STDIN_FILENO) == 1 && libc::isatty(libc::STDOUT_FILENO) == 1 }
+    }
+
+    /// Read a line with EDITOR after PROMPT, which is already shown.
+    /// Return None at the end of input.
+    pub fn read_line(editor: &mut LineEditor, prompt: &str) -> io::Result<Option<String>> {
+        let mut mode = RawMode::enter();
+        let mut stdout = io::stdout();
+        let mut pending = Vec::new();
+        loop {
+            let byte = match read_byte()? {
+                Some(byte) => byte,
+                None if editor.text().is_empty() => return Ok(None),
+                None => break,
+            };
+            // The keys that would send signals in cooked mode still do.
+            let signal = match byte {
+                0x03 => Some(libc::SIGINT),
+                0x1c => Some(libc::SIGQUIT),
+                0x1a => Some(libc::SIGTSTP),
+                _ => None,
+            };
+            if let Some(signal) = signal {
+                writeln!(stdout)?;
+                stdout.flush()?;
+                // Deliver it with the terminal in its normal mode.
+                drop(mode.take());
+                unsafe { libc::raise(signal) };
+                mode = RawMode::enter();
+                write!(stdout, "{}{}", prompt, editor.render(prompt))?;
+                stdout.flush()?;
+                continue;
+            }
+
+            pending.push(byte);
+            let c = match std::str::from_utf8(&pending) {
+                Ok(s) => s.chars().next(),
+                Err(e) if e.error_len().is_none() => continue,
+                Err(_) => None,
+            };
+            pending.clear();
+            let action = match c {
+                Some(c) => editor.key(c),
+                None => Action::Continue,
+            };
+            match action {
+                Action::Continue => {
+                    write!(stdout, "{}", editor.render(prompt))?;
+                    stdout.flush()?;
+                }
+                Action::Accept => break,
+                Action::Eof => return Ok(None),
+            }
+        }
+        drop(mode);
+        writeln!(stdout)?;
+        stdout.flush()?;
+        Ok(Some(editor.text()))
+    }
+}
+
+#[cfg(not(unix))]
+mod tty {
+    use std::io;
+
+    use super::LineEditor;
+
+    pub fn is_terminal() -> bool {
+        false
+    }
+
+    pub fn read_line(_editor: &mut LineEditor, _prompt: &str) -> io::Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+/// Return true if HISTVAR names a history variable, as opposed to
+/// nil or t, which mean no history.
+fn is_history_variable(histvar: LispObject) -> bool {
+    histvar.is_symbol() && !histvar.is_nil() && !histvar.eq(Qt)
+}
+
+/// Return the strings in the value of the history variable HISTVAR.
+fn history_strings(histvar: LispObject) -> Vec<String> {
+    if !is_history_variable(histvar) {
+        return Vec::new();
+    }
+    let value = unsafe { find_symbol_value(histvar) };
+    if value.eq(Qunbound) {
+        return Vec::new();
+    }
+    value
+        .iter_cars_safe()
+        .filter_map(|elt| elt.as_string())
+        .map(|s| String::from_utf8_lossy(s.as_slice()).into_owned())
+        .collect()
+}
+
+/// Read a line from standard input after PROMPT, which is already
+/// shown, with line editing, using the history in HISTVAR.  Return
+/// false if line editing is not possible, because standard input or
+/// output isn't a terminal or `noninteractive-line-editing' is nil.
+/// Otherwise, set *LINE to the line that was read, or nil at the end of
+/// input, and return true.
+#[no_mangle]
+pub extern "C" fn read_line_edited(
+    prompt: LispObject,
+    histvar: LispObject,
+    line: *mut LispObject,
+) -> bool {
+    if !unsafe { globals.noninteractive_line_editing } || !tty::is_terminal() {
+        return false;
+    }
+    let prompt = String::from_utf8_lossy(prompt.as_string_or_error().as_slice()).into_owned();
+    let mut editor = LineEditor::new(history_strings(histvar));
+    let text = match tty::read_line(&mut editor, &prompt) {
+        Ok(Some(text)) => text,
+        Ok(None) | Err(_) => {
+            unsafe { *line = Qnil };
+            return true;
+        }
+    };
+    let string = unsafe { make_string(text.as_ptr() as *const c_char, text.len() as ptrdiff_t) };
+    if !text.is_empty() && is_history_variable(histvar) {
+        call!(intern("add-to-history").as_lisp_obj(), histvar, string);
+    }
+    unsafe { *line = string };
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn syms_of_lineedit() {
+    /// Non-nil means lines read from a terminal in batch mode can be edited.
+    /// When Emacs runs noninteractively and both standard input and
+    /// standard output are terminals, the minibuffer reads lines with a
+    /// simple line editor that knows the usual Emacs keys for moving,
+    /// killing and yanking.  C-p and C-n, or the up and down arrows, move
+    /// through the minibuffer history.
+    defvar_bool!(
+        noninteractive_line_editing,
+        "noninteractive-line-editing",
+        true
+    );
+}
+
+#[cfg(test)]
+fn type_keys(editor: &mut LineEditor, keys: &str) -> Action {
+    let mut action = Action::Continue;
+    for c in keys.chars() {
+        action = editor.key(c);
+    }
+    action
+}
+
+#[test]
+fn test_editing() {
+    let mut editor = LineEditor::new(Vec::new());
+    type_keys(&mut editor, "world\x01hello \x05!\x02\x02\x7f");
+    assert_eq!(editor.text(), "hello word!");
+    assert_eq!(editor.point, 9);
+    assert_eq!(type_keys(&mut editor, "\r"), Action::Accept);
+}
+
+#[test]
+fn test_killing_and_yanking() {
+    let mut editor = LineEditor::new(Vec::new());
+    type_keys(&mut editor, "(foo bar)\x02\x17");
+    assert_eq!(editor.text(), "(foo )");
+    type_keys(&mut editor, "\x01\x19");
+    assert_eq!(editor.text(), "bar(foo )");
+    type_keys(&mut editor, "\x1bf\x0b");
+    assert_eq!(editor.text(), "bar(foo");
+    type_keys(&mut editor, "\x15");
+    assert_eq!(editor.text(), "");
+    assert_eq!(type_keys(&mut editor, "\x04"), Action::Eof);
+}
+
+#[test]
+fn test_escape_sequences() {
+    let mut editor = LineEditor::new(Vec::new());
+    type_keys(&mut editor, "abc\x1b[D\x1b[D\x1b[3~\x1b[H1\x1bOF2");
+    assert_eq!(editor.text(), "1ac2");
+}
+
+#[test]
+fn test_history() {
+    let history = vec!["newer".to_string(), "older".to_string()];
+    let mut editor = LineEditor::new(history);
+    type_keys(&mut editor, "draft\x10");
+    assert_eq!(editor.text(), "newer");
+    type_keys(&mut editor, "\x1b[A\x10");
+    assert_eq!(editor.text(), "older");
+    type_keys(&mut editor, "\x0e");
+    assert_eq!(editor.text(), "newer");
+    type_keys(&mut editor, "\x1b[B");
+    assert_eq!(editor.text(), "draft");
+    assert_eq!(editor.point, 5);
+}
+
+#[test]
+fn test_render() {
+    let mut editor = LineEditor::new(Vec::new());
+    type_keys(&mut editor, "héllo\x02\x02");
+    assert_eq!(editor.render("Eval:\n> "), "\r> héllo\x1b[K\x1b[2D");
+}
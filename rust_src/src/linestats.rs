@@ -0,0 +1,295 @@
+//! Line statistics of buffers.
+//!
+//! Each buffer keeps the number of its newlines and the length of its
+//! longest line up to date as text is inserted and deleted, so that
+//! modes for buffers with very long lines, like `so-long-mode', can
+//! find out about them without scanning the buffer.  The statistics
+//! are valid while the buffer's `chars_modiff' is the value they were
+//! computed for; other changes to the text leave them to be computed
+//! again the next time they are needed.
+
+use std::ptr;
+
+use libc::ptrdiff_t;
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    buffers::{LispBufferOrCurrent, LispBufferRef, BEG},
+    eval::unbind_to,
+    lisp::{defsubr, LispObject},
+    remacs_sys::{
+        find_before_next_newline, find_newline, globals, pending_funcalls,
+        record_unwind_current_buffer, set_buffer_internal_1, EmacsDouble, EmacsInt,
+    },
+    remacs_sys::{Qlong_line_functions, Qnil, Qrun_hook_with_args},
+    threads::{c_specpdl_index, ThreadState},
+};
+
+/// Return the start of the line containing POS in the current buffer,
+/// ignoring any narrowing.
+fn line_start(pos: ptrdiff_t) -> ptrdiff_t {
+    unsafe {
+        find_newline(
+            pos,
+            -1,
+            BEG,
+            -1,
+            -1,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            false,
+        )
+    }
+}
+
+/// Return the end of the line containing POS in the current buffer,
+/// ignoring any narrowing.
+fn line_end(pos: ptrdiff_t) -> ptrdiff_t {
+    let z = ThreadState::current_buffer().z();
+    unsafe { find_before_next_newline(pos, z, 1, ptr::null_mut()) }
+}
+
+/// Return the number of newlines between START and END in the current
+/// buffer.  Unlike `occur::count_newlines', this never quits, as it
+/// runs in the middle of changes to the text.
+fn count_newlines(start: ptrdiff_t, end: ptrdiff_t) -> ptrdiff_t {
+    if start >= end {
+        return 0;
+    }
+    let mut shortage = 0;
+    unsafe {
+        find_newline(
+            start,
+            -1,
+            end,
+            -1,
+            ptrdiff_t::max_value(),
+            &mut shortage,
+            ptr::null_mut(),
+            false,
+        )
+    };
+    ptrdiff_t::max_value() - shortage
+}
+
+/// Return the length of the longest of the lines of the current buffer
+/// that contain the text between FROM and TO.
+fn longest_line(from: ptrdiff_t, to: ptrdiff_t) -> ptrdiff_t {
+    let mut pos = line_start(from);
+    let mut longest = 0;
+    loop {
+        let end = line_end(pos);
+        longest = longest.max(end - pos);
+        if end >= to {
+            return longest;
+        }
+        pos = end + 1;
+    }
+}
+
+/// Return the length of the longest of the lines of the current buffer
+/// that do not contain any of the text between FROM and TO.
+fn longest_line_outside(from: ptrdiff_t, to: ptrdiff_t) -> ptrdiff_t {
+    let (start, end) = (line_start(from), line_end(to));
+    let z = ThreadState::current_buffer().z();
+    let before = if start > BEG {
+        longest_line(BEG, start - 1)
+    } else {
+        0
+    };
+    let after = if end < z { longest_line(end + 1, z) } else { 0 };
+    before.max(after)
+}
+
+/// Return the length of the longest line of the current buffer after
+/// the longest line was changed into the lines containing the text
+/// between FROM and TO, the longest of which is LONGEST characters.
+/// That is normally left unknown, as -1, but if the buffer was reported
+/// to have long lines, we want to know whether it still does.
+fn changed_max(
+    buffer: LispBufferRef,
+    from: ptrdiff_t,
+    to: ptrdiff_t,
+    longest: ptrdiff_t,
+) -> ptrdiff_t {
+    if buffer.long_lines_reported {
+        longest_line_outside(from, to).max(longest)
+    } else {
+        -1
+    }
+}
+
+fn stats_valid(buffer: LispBufferRef) -> bool {
+    buffer.line_stats_modiff == buffer.char_modifications()
+}
+
+/// Return the line length above which `long-line-functions' run, if
+/// any.
+fn long_line_threshold() -> Option<EmacsInt> {
+    unsafe { globals.Vlong_line_threshold }.as_fixnum()
+}
+
+/// Note that the current buffer has a line of LENGTH characters, and
+/// queue a call of `long-line-functions' if that is the first line
+/// above `long-line-threshold' since the buffer had none.
+fn note_line_length(mut buffer: LispBufferRef, length: ptrdiff_t) {
+    match long_line_threshold() {
+        Some(threshold) if length as EmacsInt > threshold => {
+            if !buffer.long_lines_reported {
+                buffer.long_lines_reported = true;
+                unsafe {
+                    pending_funcalls = LispObject::cons(
+                        list!(
+                            Qrun_hook_with_args,
+                            Qlong_line_functions,
+                            buffer.as_lisp_obj()
+                        ),
+                        pending_funcalls,
+                    );
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Forget that the current buffer was reported to have long lines if
+/// its longest line is no longer above `long-line-threshold'.
+fn forget_long_lines(mut buffer: LispBufferRef) {
+    let max = buffer.line_stats_max;
+    if max >= 0 && long_line_threshold().map_or(true, |threshold| max as EmacsInt <= threshold) {
+        buffer.long_lines_reported = false;
+    }
+}
+
+/// Make the statistics of the current buffer valid, scanning it if
+/// needed, and return the number of newlines and the length of the
+/// longest line.
+fn update_stats(mut buffer: LispBufferRef) -> (ptrdiff_t, ptrdiff_t) {
+    let z = buffer.z();
+    if !stats_valid(buffer) {
+        buffer.line_stats_newlines = count_newlines(BEG, z);
+        buffer.line_stats_max = -1;
+        buffer.line_stats_modiff = buffer.char_modifications();
+    }
+    if buffer.line_stats_max < 0 {
+        buffer.line_stats_max = longest_line(BEG, z);
+        note_line_length(buffer, buffer.line_stats_max);
+        forget_long_lines(buffer);
+    }
+    (buffer.line_stats_newlines, buffer.line_stats_max)
+}
+
+/// Called just before text is inserted in the current buffer, which
+/// increments the modification count.  The statistics are kept valid
+/// across the increment if they are valid now.
+#[no_mangle]
+pub extern "C" fn line_stats_before_insert() {
+    let mut buffer = ThreadState::current_buffer();
+    if stats_valid(buffer) {
+        buffer.line_stats_modiff = buffer.modifications() + 1;
+    }
+}
+
+/// Called when the text between FROM and TO has been inserted in the
+/// current buffer.
+#[no_mangle]
+pub extern "C" fn line_stats_insert(from: ptrdiff_t, to: ptrdiff_t) {
+    let mut buffer = ThreadState::current_buffer();
+    if !stats_valid(buffer) {
+        return;
+    }
+    let newlines = count_newlines(from, to);
+    let longest = longest_line(from, to);
+    buffer.line_stats_newlines += newlines;
+
+    let max = buffer.line_stats_max;
+    if max >= 0 && longest >= max {
+        buffer.line_stats_max = longest;
+    } else if newlines > 0 && max >= 0 {
+        // The line the text was inserted in was split; if it was the
+        // longest, we no longer know which one is.
+        let old = (from - line_start(from)) + (line_end(to) - to);
+        if old == max {
+            buffer.line_stats_max = changed_max(buffer, from, to, longest);
+        }
+    }
+    note_line_length(buffer, longest);
+    forget_long_lines(buffer);
+}
+
+/// Called just before the text between FROM and TO is deleted from the
+/// current buffer, which increments the modification count.
+#[no_mangle]
+pub extern "C" fn line_stats_delete(from: ptrdiff_t, to: ptrdiff_t) {
+    let mut buffer = ThreadState::current_buffer();
+    if !stats_valid(buffer) {
+        return;
+    }
+    if from == BEG && to == buffer.z() {
+        buffer.line_stats_newlines = 0;
+        buffer.line_stats_max = 0;
+    } else {
+        buffer.line_stats_newlines -= count_newlines(from, to);
+        let joined = (from - line_start(from)) + (line_end(to) - to);
+        let max = buffer.line_stats_max;
+        if max >= 0 && joined >= max {
+            buffer.line_stats_max = joined;
+        } else if max >= 0 && longest_line(from, to) == max {
+            buffer.line_stats_max = changed_max(buffer, from, to, joined);
+        }
+        note_line_length(buffer, joined);
+    }
+    forget_long_lines(buffer);
+    buffer.line_stats_modiff = buffer.modifications() + 1;
+}
+
+/// Return statistics about the lines of BUFFER.
+/// BUFFER defaults to the current buffer.  The value is a list (LINES
+/// MAX-LENGTH AVERAGE-LENGTH): the number of lines of the buffer, the
+/// length of the longest line, and the average length of the lines, in
+/// characters, not counting newlines.  Narrowing is ignored.
+///
+/// The statistics are kept up to date as text is inserted and deleted,
+/// so this seldom needs to scan the buffer.
+#[lisp_fn(min = "0")]
+pub fn buffer_line_statistics(buffer: LispBufferOrCurrent) -> LispObject {
+    let mut buffer: LispBufferRef = buffer.into();
+    let count = c_specpdl_index();
+    unsafe {
+        record_unwind_current_buffer();
+        set_buffer_internal_1(buffer.as_mut());
+    }
+    let (newlines, max) = update_stats(buffer);
+    let chars = buffer.z() - BEG - newlines;
+    unbind_to(count, Qnil);
+
+    let lines = newlines + 1;
+    list!(
+        LispObject::from(lines as EmacsInt),
+        LispObject::from(max as EmacsInt),
+        LispObject::from_float(chars as EmacsDouble / lines as EmacsDouble)
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn syms_of_linestats() {
+    def_lisp_sym!(Qlong_line_functions, "long-line-functions");
+
+    /// Line length above which a buffer is considered to have long lines.
+    /// When a line of a buffer gets longer than this, because of an
+    /// insertion or deletion, `long-line-functions' are run with the
+    /// buffer, and do not run again for it until its longest line is
+    /// known to be short enough again.  If this is nil, the functions
+    /// are not run.
+    defvar_lisp!(Vlong_line_threshold, "long-line-threshold", Qnil);
+
+    /// Abnormal hook run when a buffer gets a line that is too long.
+    /// The functions are called with the buffer, soon after the change
+    /// that made a line longer than `long-line-threshold'.  Use
+    /// `buffer-line-statistics' to find out more about the lines.
+    defvar_lisp!(Vlong_line_functions, "long-line-functions", Qnil);
+}
+
+include!(concat!(env!("OUT_DIR"), "/linestats_exports.rs"));
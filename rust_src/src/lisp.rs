@@ -15,7 +15,7 @@ use crate::{
     eval::FUNCTIONP,
     lists::{list, CarIter},
     remacs_sys,
-    remacs_sys::{build_string, internal_equal, make_float},
+    remacs_sys::{build_string, internal_equal, make_float, pure_write_error, PURE_P},
     remacs_sys::{
         equal_kind, pvec_type, EmacsDouble, EmacsInt, EmacsUint, Lisp_Bits, USE_LSB_TAG, VALMASK,
     },
@@ -333,6 +333,22 @@ impl LispObject {
     pub fn get_untaggedptr(self) -> *mut c_void {
         (self.to_C() & VALMASK) as intptr_t as *mut c_void
     }
+
+    /// Return true if this object is in pure space, where `purecopy'
+    /// puts the objects shared by all Emacs sessions while dumping.
+    /// They must not be changed.
+    pub fn is_pure(self) -> bool {
+        !self.is_fixnum() && !self.is_symbol() && unsafe { PURE_P(self.get_untaggedptr()) }
+    }
+
+    /// Signal an error if this object is pure.  Functions that change
+    /// objects in place must call this first, as writing to pure space
+    /// can crash Emacs.
+    pub fn check_impure(self) {
+        if self.is_pure() {
+            unsafe { pure_write_error(self) };
+        }
+    }
 }
 
 impl From<LispObject> for EmacsInt {
@@ -5,6 +5,7 @@ use libc::c_void;
 use remacs_macros::lisp_fn;
 
 use crate::{
+    alloc::write_barrier,
     lisp::defsubr,
     lisp::LispObject,
     remacs_sys::{globals, EmacsInt, EmacsUint, Lisp_Cons, Lisp_Type},
@@ -345,6 +346,7 @@ pub fn nlistp(object: LispObject) -> bool {
 pub fn setcar(cell: LispCons, newcar: LispObject) -> LispObject {
     cell.check_impure();
     cell.set_car(newcar);
+    write_barrier(cell.into(), newcar);
     newcar
 }
 
@@ -353,6 +355,7 @@ pub fn setcar(cell: LispCons, newcar: LispObject) -> LispObject {
 pub fn setcdr(cell: LispCons, newcdr: LispObject) -> LispObject {
     cell.check_impure();
     cell.set_cdr(newcdr);
+    write_barrier(cell.into(), newcdr);
     newcdr
 }
 
@@ -631,6 +634,7 @@ where
                 }
                 Some(tail_cdr_cons) => {
                     if cmp(tail.car(), prop) {
+                        tail_cdr_cons.check_impure();
                         tail_cdr_cons.set_car(val);
                         return plist;
                     }
@@ -645,6 +649,7 @@ where
         Some(last_cons) => {
             let last_cons_cdr = last_cons.cdr().as_cons_or_error();
             let newcell = LispObject::cons(prop, LispObject::cons(val, last_cons_cdr.cdr()));
+            last_cons_cdr.check_impure();
             last_cons_cdr.set_cdr(newcell);
             plist
         }
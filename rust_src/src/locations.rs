@@ -0,0 +1,196 @@
+//! Remembering positions in files across edits and sessions.
+//!
+//! Registers and bookmarks can outlive the buffer they point into, and
+//! the file may have changed by the time they are used again.  A
+//! location record keeps the text around the position along with the
+//! position itself, so that the position can be found again after
+//! text was inserted or deleted before it.
+
+use std::cmp::{max, min};
+
+use libc::ptrdiff_t;
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    buffers::{LispBufferOrCurrent, LispBufferRef, BEG},
+    lisp::{defsubr, LispObject},
+    remacs_sys::{buf_bytepos_to_charpos, buf_charpos_to_bytepos},
+    remacs_sys::{EmacsInt, Qnil},
+    shell::make_lisp_string,
+};
+
+/// The number of characters of context saved on each side of a position.
+const CONTEXT_CHARS: ptrdiff_t = 32;
+
+/// The context is first looked for this many bytes around the saved
+/// position, and then in larger and larger parts of the buffer, so that
+/// a small edit doesn't make us scan all of a large file.
+const INITIAL_WINDOW: ptrdiff_t = 64 * 1024;
+
+/// Return the start of the occurrence of NEEDLE in HAYSTACK that is
+/// closest to TARGET.  Of two occurrences at the same distance, the one
+/// after TARGET wins.
+fn nearest_match(haystack: &[u8], needle: &[u8], target: usize) -> Option<usize> {
+    if needle.is_empty() || needle.len() > haystack.len() {
+        return None;
+    }
+    let last = haystack.len() - needle.len();
+    let target = min(target, last + 1);
+    let matches_at = |i: usize| haystack[i..].starts_with(needle);
+
+    let after = (target..=last).find(|&i| matches_at(i));
+    let before = (0..target).rev().find(|&i| matches_at(i));
+    match (before, after) {
+        (Some(b), Some(a)) => Some(if target - b < a - target { b } else { a }),
+        (b, a) => a.or(b),
+    }
+}
+
+/// Find the position in TEXT that is followed by FRONT and preceded by
+/// REAR, closest to TARGET.  If there is no such position, settle for
+/// one that is only followed by FRONT, or only preceded by REAR.
+fn locate(text: &[u8], target: usize, front: &[u8], rear: &[u8]) -> Option<usize> {
+    let before_target = target.saturating_sub(rear.len());
+    if !front.is_empty() && !rear.is_empty() {
+        let both = [rear, front].concat();
+        if let Some(i) = nearest_match(text, &both, before_target) {
+            return Some(i + rear.len());
+        }
+    }
+    nearest_match(text, front, target)
+        .or_else(|| nearest_match(text, rear, before_target).map(|i| i + rear.len()))
+}
+
+/// Return the bytes of the context string CONTEXT, as they would appear
+/// in a buffer whose multibyteness is MULTIBYTE.  Contexts that can't
+/// appear in such a buffer are ignored.
+fn context_bytes(context: LispObject, multibyte: bool) -> Vec<u8> {
+    match context.as_string() {
+        Some(s) if s.is_multibyte() == multibyte || s.len_chars() == s.len_bytes() => {
+            s.as_slice().to_vec()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Return a record of POSITION in BUFFER that `location-reconcile' can
+/// find again after BUFFER was edited.
+/// POSITION defaults to point, and BUFFER to the current buffer.
+///
+/// The value is a list (POSITION SIZE FRONT REAR): SIZE is the size of
+/// BUFFER, and FRONT and REAR are the text after and before POSITION.
+/// It can be saved with `persistent-store-put', to be used in a later
+/// session.
+#[lisp_fn(min = "0")]
+pub fn location_record(position: LispObject, buffer: LispBufferOrCurrent) -> LispObject {
+    let mut buffer: LispBufferRef = buffer.into();
+    let pos = if position.is_nil() {
+        buffer.pt
+    } else {
+        position.as_fixnum_coerce_marker_or_error() as ptrdiff_t
+    };
+    let pos = max(buffer.begv, min(pos, buffer.zv));
+
+    let (pos_byte, front_end, rear_start) = unsafe {
+        (
+            buf_charpos_to_bytepos(buffer.as_mut(), pos),
+            buf_charpos_to_bytepos(buffer.as_mut(), min(pos + CONTEXT_CHARS, buffer.zv)),
+            buf_charpos_to_bytepos(buffer.as_mut(), max(pos - CONTEXT_CHARS, buffer.begv)),
+        )
+    };
+    let multibyte = buffer.multibyte_characters_enabled();
+    let front = buffer.bytes_between(pos_byte, front_end);
+    let rear = buffer.bytes_between(rear_start, pos_byte);
+
+    list!(
+        LispObject::from(pos as EmacsInt),
+        LispObject::from((buffer.z() - BEG) as EmacsInt),
+        make_lisp_string(&front, multibyte),
+        make_lisp_string(&rear, multibyte)
+    )
+}
+
+/// Return the position in BUFFER described by RECORD.
+/// RECORD is a list (POSITION SIZE FRONT REAR), as made by
+/// `location-record'.  BUFFER defaults to the current buffer.
+///
+/// If the text around POSITION is no longer FRONT and REAR, the value is
+/// the position nearest to POSITION where they are found.  If they are
+/// not found, POSITION is moved in proportion to the change of the
+/// buffer's size from SIZE.  SIZE, FRONT and REAR can be nil, if they
+/// are unknown.  The value is always within the accessible portion of
+/// BUFFER.
+#[lisp_fn(min = "1")]
+pub fn location_reconcile(record: LispObject, buffer: LispBufferOrCurrent) -> EmacsInt {
+    let mut buffer: LispBufferRef = buffer.into();
+    let (position, rest) = record.as_cons_or_error().as_tuple();
+    let mut fields = rest.iter_cars_safe();
+    let size = fields.next().and_then(|s| s.as_fixnum());
+    let multibyte = buffer.multibyte_characters_enabled();
+    let front = context_bytes(fields.next().unwrap_or(Qnil), multibyte);
+    let rear = context_bytes(fields.next().unwrap_or(Qnil), multibyte);
+
+    let position = position.as_fixnum_or_error() as ptrdiff_t;
+    let pos = max(buffer.begv, min(position, buffer.zv));
+    let pos_byte = unsafe { buf_charpos_to_bytepos(buffer.as_mut(), pos) };
+
+    if !front.is_empty() || !rear.is_empty() {
+        let mut window = INITIAL_WINDOW;
+        loop {
+            let start = max(buffer.begv_byte, pos_byte - window);
+            let end = min(buffer.zv_byte, pos_byte + window);
+            let text = buffer.bytes_between(start, end);
+            if let Some(offset) = locate(&text, (pos_byte - start) as usize, &front, &rear) {
+                let found = start + offset as ptrdiff_t;
+                return unsafe { buf_bytepos_to_charpos(buffer.as_mut(), found) } as EmacsInt;
+            }
+            if start == buffer.begv_byte && end == buffer.zv_byte {
+                break;
+            }
+            window *= 4;
+        }
+    }
+
+    // The context is gone, or was never known.  Assume the text before
+    // the position grew or shrank like the rest of the file.
+    let current_size = buffer.z() - BEG;
+    let guess = match size {
+        Some(size) if size > 0 && size as ptrdiff_t != current_size => {
+            let offset = (position - BEG) as f64 * current_size as f64 / size as f64;
+            BEG + offset.round() as ptrdiff_t
+        }
+        _ => position,
+    };
+    max(buffer.begv, min(guess, buffer.zv)) as EmacsInt
+}
+
+include!(concat!(env!("OUT_DIR"), "/locations_exports.rs"));
+
+#[test]
+fn test_nearest_match() {
+    let text = b"abc xyz abc xyz abc";
+    assert_eq!(nearest_match(text, b"abc", 0), Some(0));
+    assert_eq!(nearest_match(text, b"abc", 5), Some(8));
+    assert_eq!(nearest_match(text, b"abc", 3), Some(0));
+    assert_eq!(nearest_match(text, b"abc", 100), Some(16));
+    assert_eq!(nearest_match(text, b"xyz", 8), Some(12));
+    assert_eq!(nearest_match(text, b"nope", 8), None);
+    assert_eq!(nearest_match(text, b"", 8), None);
+    assert_eq!(nearest_match(b"ab", b"abc", 0), None);
+}
+
+#[test]
+fn test_locate() {
+    // Text was inserted before the position.
+    let text = b"new text; first line\nsecond line\n";
+    assert_eq!(locate(text, 6, b"second", b"line\n"), Some(21));
+    // The text before the position changed.
+    assert_eq!(locate(text, 6, b"second", b"gone"), Some(21));
+    // The text after the position changed.
+    assert_eq!(locate(text, 30, b"gone", b"first line\n"), Some(21));
+    // Both context strings occur, but only once next to each other.
+    let text = b"line\nxx second\nline\nsecond";
+    assert_eq!(locate(text, 0, b"second", b"line\n"), Some(20));
+    assert_eq!(locate(text, 0, b"gone", b"gone"), None);
+}
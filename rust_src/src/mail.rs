@@ -0,0 +1,399 @@
+//! Scanning mail folders.
+//!
+//! Summaries of mbox files and maildirs need the position and a few
+//! header fields of every message.  Visiting a large archive and
+//! searching it with regexps takes seconds, so `mail-scan-folder' reads
+//! the folder line by line without keeping it in memory, and only the
+//! headers asked for are turned into Lisp strings.
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    callproc::{lisp_file_name_to_path, path_to_lisp_file_name},
+    lisp::{defsubr, LispObject},
+    mime::{charset_coding_system, decode_encoded_words},
+    obarray::intern,
+    remacs_sys::{code_convert_string_norecord, make_unibyte_string},
+    remacs_sys::{EmacsInt, Fexpand_file_name, Qnil, Qutf_8_unix},
+};
+
+/// The headers returned by `mail-scan-folder' by default.
+const DEFAULT_HEADERS: &[&str] = &["from", "to", "subject", "date", "message-id"];
+
+/// Collects the values of some header fields from header lines.
+#[derive(Debug)]
+pub struct Headers<'a> {
+    wanted: &'a [Vec<u8>],
+    /// The value of each field in `wanted', in the same order.
+    pub values: Vec<Option<Vec<u8>>>,
+    /// The field whose continuation lines are being read.
+    current: Option<usize>,
+}
+
+impl<'a> Headers<'a> {
+    /// WANTED are the names of the fields to collect, in lower case.
+    pub fn new(wanted: &'a [Vec<u8>]) -> Self {
+        Headers {
+            wanted,
+            values: vec![None; wanted.len()],
+            current: None,
+        }
+    }
+
+    /// Add LINE, a header line without its line ending.
+    pub fn line(&mut self, line: &[u8]) {
+        if line.starts_with(b" ") || line.starts_with(b"\t") {
+            if let Some(index) = self.current {
+                if let Some(ref mut value) = self.values[index] {
+                    value.extend_from_slice(line);
+                }
+            }
+            return;
+        }
+        self.current = None;
+        let colon = match line.iter().position(|&c| c == b':') {
+            Some(colon) => colon,
+            None => return,
+        };
+        let name = trim(&line[..colon]);
+        let index = self
+            .wanted
+            .iter()
+            .position(|wanted| wanted.eq_ignore_ascii_case(name));
+        if let Some(index) = index {
+            // Only the first occurrence of a field counts.
+            if self.values[index].is_none() {
+                self.values[index] = Some(trim_start(&line[colon + 1..]).to_vec());
+                self.current = Some(index);
+            }
+        }
+    }
+}
+
+fn trim_start(text: &[u8]) -> &[u8] {
+    let start = text
+        .iter()
+        .position(|&c| c != b' ' && c != b'\t')
+        .unwrap_or_else(|| text.len());
+    &text[start..]
+}
+
+fn trim(text: &[u8]) -> &[u8] {
+    let text = trim_start(text);
+    let end = text
+        .iter()
+        .rposition(|&c| c != b' ' && c != b'\t')
+        .map_or(0, |i| i + 1);
+    &text[..end]
+}
+
+/// Strip the line ending of LINE.
+fn chomp(line: &[u8]) -> &[u8] {
+    let line = if line.ends_with(b"\n") {
+        &line[..line.len() - 1]
+    } else {
+        line
+    };
+    if line.ends_with(b"\r") {
+        &line[..line.len() - 1]
+    } else {
+        line
+    }
+}
+
+/// A message of an mbox file, as byte offsets in the file.
+#[derive(Debug)]
+pub struct MboxMessage {
+    /// The start of the "From " line.
+    pub start: u64,
+    /// The start of the body, or `end' if there is none.
+    pub body: u64,
+    /// The start of the next message, or the end of the file.
+    pub end: u64,
+    pub headers: Vec<Option<Vec<u8>>>,
+}
+
+/// Scan the mbox file read by READER, collecting the fields WANTED of
+/// each message.  Messages start with a line beginning with "From ";
+/// anything before the first one is ignored.
+pub fn scan_mbox(mut reader: impl BufRead, wanted: &[Vec<u8>]) -> io::Result<Vec<MboxMessage>> {
+    let mut messages = Vec::new();
+    // The current message, and its headers until the body is reached.
+    let mut current: Option<(MboxMessage, Option<Headers>)> = None;
+    let mut offset = 0;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let length = reader.read_until(b'\n', &mut line)? as u64;
+        if length == 0 {
+            break;
+        }
+        if line.starts_with(b"From ") {
+            if let Some((mut message, headers)) = current.take() {
+                message.end = offset;
+                if let Some(headers) = headers {
+                    message.body = offset;
+                    message.headers = headers.values;
+                }
+                messages.push(message);
+            }
+            let message = MboxMessage {
+                start: offset,
+                body: 0,
+                end: 0,
+                headers: Vec::new(),
+            };
+            current = Some((message, Some(Headers::new(wanted))));
+        } else if let Some((ref mut message, ref mut headers)) = current {
+            let ended = match headers {
+                Some(headers) => {
+                    let content = chomp(&line);
+                    if !content.is_empty() {
+                        headers.line(content);
+                    }
+                    content.is_empty()
+                }
+                None => false,
+            };
+            if ended {
+                message.body = offset + length;
+                message.headers = headers.take().unwrap().values;
+            }
+        }
+        offset += length;
+    }
+    if let Some((mut message, headers)) = current {
+        message.end = offset;
+        if let Some(headers) = headers {
+            message.body = offset;
+            message.headers = headers.values;
+        }
+        messages.push(message);
+    }
+    Ok(messages)
+}
+
+/// Read the fields WANTED from the headers read by READER.
+pub fn scan_headers(mut reader: impl BufRead, wanted: &[Vec<u8>]) -> io::Result<Headers> {
+    let mut headers = Headers::new(wanted);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        let content = chomp(&line);
+        if content.is_empty() {
+            break;
+        }
+        headers.line(content);
+    }
+    Ok(headers)
+}
+
+/// A message of a maildir.
+#[derive(Debug)]
+pub struct MaildirMessage {
+    pub file: std::path::PathBuf,
+    /// The flags at the end of the file name, like "RS".
+    pub flags: Vec<u8>,
+    pub headers: Vec<Option<Vec<u8>>>,
+}
+
+/// Return the flags in NAME, the name of a file in a maildir.
+fn maildir_flags(name: &[u8]) -> &[u8] {
+    name.windows(3)
+        .position(|info| info == b":2,")
+        .map_or(&[][..], |start| &name[start + 3..])
+}
+
+/// Scan the messages in the "cur" and "new" subdirectories of the
+/// maildir DIR, in the order of their file names.
+pub fn scan_maildir(dir: &Path, wanted: &[Vec<u8>]) -> io::Result<Vec<MaildirMessage>> {
+    let mut messages = Vec::new();
+    for subdir in &["cur", "new"] {
+        let subdir = dir.join(subdir);
+        if !subdir.is_dir() {
+            continue;
+        }
+        let mut names: Vec<_> = fs::read_dir(&subdir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name())
+            .filter(|name| !name.to_string_lossy().starts_with('.'))
+            .collect();
+        names.sort();
+        for name in names {
+            let file = subdir.join(&name);
+            if !file.is_file() {
+                continue;
+            }
+            let headers = scan_headers(BufReader::new(File::open(&file)?), wanted)?;
+            let flags = maildir_flags(name.to_string_lossy().as_bytes()).to_vec();
+            messages.push(MaildirMessage {
+                file,
+                flags,
+                headers: headers.values,
+            });
+        }
+    }
+    Ok(messages)
+}
+
+fn make_unibyte(bytes: &[u8]) -> LispObject {
+    unsafe { make_unibyte_string(bytes.as_ptr() as *const c_char, bytes.len() as ptrdiff_t) }
+}
+
+/// Return the alist of the fields found in VALUES, with their text
+/// decoded from UTF-8 and their encoded words decoded.
+fn headers_alist(
+    wanted: &[Vec<u8>],
+    values: Vec<Option<Vec<u8>>>,
+    coding_system: &impl Fn(LispObject) -> LispObject,
+) -> LispObject {
+    let fields: Vec<LispObject> = wanted
+        .iter()
+        .zip(values)
+        .filter_map(|(name, value)| value.map(|value| (name, value)))
+        .map(|(name, value)| {
+            let value =
+                unsafe { code_convert_string_norecord(make_unibyte(&value), Qutf_8_unix, false) }
+                    .as_string_or_error();
+            let value = decode_encoded_words(value.as_slice(), value.is_multibyte(), coding_system);
+            LispObject::cons(make_unibyte(name), value)
+        })
+        .collect();
+    LispObject::from(fields)
+}
+
+/// Scan the mail folder FOLDER and return a list of its messages.
+/// FOLDER is an mbox file, or a maildir, whose "cur" and "new"
+/// subdirectories are scanned.
+///
+/// Each message of an mbox file is a plist (:start START :body BODY :end
+/// END :headers HEADERS), where START is the byte offset of its "From "
+/// line in the file, BODY that of its body and END that of the next
+/// message.  Each message of a maildir is a plist (:file FILE :flags
+/// FLAGS :headers HEADERS), where FLAGS is the string of flags at the
+/// end of the file name, like "RS".
+///
+/// HEADERS is an alist of (NAME . VALUE) of the header fields named in
+/// HEADERS, a list of strings, or From, To, Subject, Date and Message-ID
+/// if it is nil.  NAME is in lower case.  Continuation lines are joined,
+/// and the RFC 2047 encoded words of VALUE are decoded.
+/// CODING-FUNCTION maps the charset of an encoded word to a coding
+/// system, as for `rfc2047-decode-region-native'; by default a charset
+/// names its coding system.
+#[lisp_fn(min = "1")]
+pub fn mail_scan_folder(
+    folder: LispObject,
+    headers: LispObject,
+    coding_function: LispObject,
+) -> LispObject {
+    let folder = unsafe { Fexpand_file_name(folder, Qnil) };
+    let path = lisp_file_name_to_path(folder);
+    let wanted: Vec<Vec<u8>> = if headers.is_nil() {
+        DEFAULT_HEADERS
+            .iter()
+            .map(|name| name.as_bytes().to_vec())
+            .collect()
+    } else {
+        headers
+            .iter_cars()
+            .map(|name| name.as_string_or_error().as_slice().to_ascii_lowercase())
+            .collect()
+    };
+    let coding_system = |charset| {
+        if coding_function.is_nil() {
+            charset_coding_system(charset)
+        } else {
+            call!(coding_function, charset)
+        }
+    };
+    let keyword = |name: &str| intern(name).as_lisp_obj();
+
+    let messages: Vec<LispObject> = if path.is_dir() {
+        let messages = scan_maildir(&path, &wanted)
+            .unwrap_or_else(|err| error!("Cannot scan {}: {}", path.display(), err));
+        messages
+            .into_iter()
+            .map(|message| {
+                list!(
+                    keyword(":file"),
+                    path_to_lisp_file_name(&message.file),
+                    keyword(":flags"),
+                    make_unibyte(&message.flags),
+                    keyword(":headers"),
+                    headers_alist(&wanted, message.headers, &coding_system)
+                )
+            })
+            .collect()
+    } else {
+        let messages = File::open(&path)
+            .and_then(|file| scan_mbox(BufReader::new(file), &wanted))
+            .unwrap_or_else(|err| error!("Cannot scan {}: {}", path.display(), err));
+        messages
+            .into_iter()
+            .map(|message| {
+                list!(
+                    keyword(":start"),
+                    LispObject::from(message.start as EmacsInt),
+                    keyword(":body"),
+                    LispObject::from(message.body as EmacsInt),
+                    keyword(":end"),
+                    LispObject::from(message.end as EmacsInt),
+                    keyword(":headers"),
+                    headers_alist(&wanted, message.headers, &coding_system)
+                )
+            })
+            .collect()
+    };
+    LispObject::from(messages)
+}
+
+include!(concat!(env!("OUT_DIR"), "/mail_exports.rs"));
+
+#[test]
+fn test_headers() {
+    let wanted = vec![b"subject".to_vec(), b"from".to_vec()];
+    let mut headers = Headers::new(&wanted);
+    headers.line(b"Subject: a");
+    headers.line(b"\tlong one ");
+    headers.line(b"X-Other: b");
+    headers.line(b" ignored");
+    headers.line(b"FROM:me");
+    headers.line(b"Subject: second");
+    assert_eq!(headers.values[0], Some(b"a\tlong one ".to_vec()));
+    assert_eq!(headers.values[1], Some(b"me".to_vec()));
+}
+
+#[test]
+fn test_scan_mbox() {
+    let mbox = b"junk\nFrom a@b Mon Jan  1 00:00:00 2018\r\nSubject: one\r\n\r\nbody\n\n\
+                 From c@d Tue Jan  2 00:00:00 2018\nSubject: two\n";
+    let wanted = vec![b"subject".to_vec()];
+    let messages = scan_mbox(&mbox[..], &wanted).unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].start, 5);
+    assert_eq!(messages[0].body, 56);
+    assert_eq!(
+        &mbox[messages[0].body as usize..messages[0].end as usize],
+        b"body\n\n"
+    );
+    assert_eq!(messages[0].headers, vec![Some(b"one".to_vec())]);
+    assert_eq!(messages[1].start, messages[0].end);
+    assert_eq!(messages[1].body, mbox.len() as u64);
+    assert_eq!(messages[1].end, mbox.len() as u64);
+    assert_eq!(messages[1].headers, vec![Some(b"two".to_vec())]);
+}
+
+#[test]
+fn test_maildir_flags() {
+    assert_eq!(maildir_flags(b"1234.M1P2.host:2,RS"), b"RS");
+    assert_eq!(maildir_flags(b"1234.M1P2.host"), b"");
+}
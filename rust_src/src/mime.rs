@@ -0,0 +1,662 @@
+//! MIME encodings used in mail headers and bodies.
+//!
+//! Quoted-printable (RFC 2045) and the encoded words of RFC 2047 are
+//! decoded for every article Gnus and Rmail display, and the versions
+//! in qp.el and rfc2047.el do it with regexp searches and one buffer
+//! change per sequence.  The region is converted here in one pass and
+//! replaced at once.  The quirks of the Lisp versions are kept: hex
+//! digits may be in lower case, a `=' that starts nothing valid is left
+//! alone, and encoded words whose charset is unknown stay as they are.
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    buffers::validate_region,
+    casefiddle::downcase,
+    editfns::buffer_substring_no_properties,
+    fns::concat,
+    lisp::{defsubr, LispObject},
+    multibyte::{char_byte8_p, char_to_byte8},
+    obarray::intern,
+    remacs_sys::Fcoding_system_p,
+    remacs_sys::{code_convert_string_norecord, make_specified_string, replace_range},
+    remacs_sys::{EmacsInt, Qnil},
+    threads::ThreadState,
+    url::hex_value,
+};
+
+/// Append BYTE to OUT, as a raw byte character if MULTIBYTE.
+fn push_byte(out: &mut Vec<u8>, byte: u8, multibyte: bool) {
+    if multibyte && byte >= 0x80 {
+        out.push(0xc0 | ((byte >> 6) & 1));
+        out.push(0x80 | (byte & 0x3f));
+    } else {
+        out.push(byte);
+    }
+}
+
+/// Decode the quoted-printable TEXT, which is in the internal
+/// representation of a string if MULTIBYTE.  The decoded bytes are raw
+/// byte characters in the result if MULTIBYTE.  Return the result and
+/// whether some `=' was not followed by a newline or two hexadecimal
+/// digits; such a `=' is kept.
+pub fn qp_decode(text: &[u8], multibyte: bool) -> (Vec<u8>, bool) {
+    let mut out = Vec::with_capacity(text.len());
+    let mut malformed = false;
+    let mut i = 0;
+    while i < text.len() {
+        if text[i] != b'=' {
+            out.push(text[i]);
+            i += 1;
+        } else if text.get(i + 1) == Some(&b'\n') {
+            // A soft line break.
+            i += 2;
+        } else if let (Some(high), Some(low)) = (
+            text.get(i + 1).and_then(|&c| hex_value(c)),
+            text.get(i + 2).and_then(|&c| hex_value(c)),
+        ) {
+            push_byte(&mut out, high << 4 | low, multibyte);
+            i += 3;
+        } else {
+            malformed = true;
+            out.push(b'=');
+            i += 1;
+        }
+    }
+    (out, malformed)
+}
+
+/// Whether BYTE is left alone by quoted-printable encoding.
+fn is_qp_literal(byte: u8) -> bool {
+    match byte {
+        b'\x08'..=b'\n' | b'\x0c' | b' '..=b'<' | b'>'..=b'\x7f' => true,
+        _ => false,
+    }
+}
+
+fn push_qp_escape(out: &mut Vec<u8>, byte: u8) {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+    out.push(b'=');
+    out.push(HEX[(byte >> 4) as usize]);
+    out.push(HEX[(byte & 0xf) as usize]);
+}
+
+/// Split LINE, which is already encoded, into lines of at most 76
+/// columns ended by soft line breaks, without cutting an escape.
+fn fold_qp_line(mut line: &[u8], out: &mut Vec<u8>) {
+    while line.len() > 76 {
+        let cut = if line[74] == b'=' {
+            74
+        } else if line[73] == b'=' {
+            73
+        } else {
+            75
+        };
+        out.extend_from_slice(&line[..cut]);
+        out.extend_from_slice(b"=\n");
+        line = &line[cut..];
+    }
+    out.extend_from_slice(line);
+}
+
+/// Encode the bytes TEXT as quoted-printable, like
+/// `quoted-printable-encode-region' with the default class.  Lines are
+/// folded if FOLD or ULTRA.  If ULTRA, lines that start with "From " or
+/// `-' are changed too, so that they survive mbox files and signatures.
+pub fn qp_encode(text: &[u8], fold: bool, ultra: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(text.len() + text.len() / 8);
+    let mut line = Vec::new();
+    for (n, input) in text.split(|&c| c == b'\n').enumerate() {
+        if n > 0 {
+            out.push(b'\n');
+        }
+        let trailing = input
+            .iter()
+            .rposition(|&c| c != b' ' && c != b'\t')
+            .map_or(0, |i| i + 1);
+        line.clear();
+        for (i, &c) in input.iter().enumerate() {
+            if is_qp_literal(c) && i < trailing {
+                line.push(c);
+            } else {
+                push_qp_escape(&mut line, c);
+            }
+        }
+        if ultra {
+            if line.starts_with(b"From ") {
+                line[4] = b'=';
+                line.splice(5..5, b"20".iter().cloned());
+            } else if line.starts_with(b"-") {
+                line.splice(0..1, b"=2D".iter().cloned());
+            }
+        }
+        if fold || ultra {
+            fold_qp_line(&line, &mut out);
+        } else {
+            out.extend_from_slice(&line);
+        }
+    }
+    out
+}
+
+fn base64_value(c: u8) -> Option<u8> {
+    match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+/// Decode the text of a B encoded word.  Missing padding is tolerated,
+/// as by `rfc2047-pad-base64'.
+fn decode_b(text: &[u8]) -> Option<Vec<u8>> {
+    let end = text.iter().rposition(|&c| c != b'=').map_or(0, |i| i + 1);
+    let text = if text.len() % 4 == 0 {
+        text
+    } else {
+        &text[..end]
+    };
+    if text.len() % 4 == 1 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut nbits = 0;
+    for (i, &c) in text.iter().enumerate() {
+        if c == b'=' {
+            // Padding may only end the text.
+            if text[i..].iter().any(|&c| c != b'=') || i < text.len() - 2 {
+                return None;
+            }
+            break;
+        }
+        bits = bits << 6 | u32::from(base64_value(c)?);
+        nbits += 6;
+        if nbits >= 8 {
+            nbits -= 8;
+            out.push((bits >> nbits) as u8);
+            bits &= (1 << nbits) - 1;
+        }
+    }
+    Some(out)
+}
+
+/// Decode the text of a Q encoded word.
+fn decode_q(text: &[u8]) -> Vec<u8> {
+    let text: Vec<u8> = text
+        .iter()
+        .map(|&c| if c == b'_' { b' ' } else { c })
+        .collect();
+    qp_decode(&text, false).0
+}
+
+/// An encoded word of RFC 2047, as ranges of the text it was found in.
+#[derive(Debug, PartialEq)]
+pub struct EncodedWord {
+    pub start: usize,
+    pub end: usize,
+    pub charset: (usize, usize),
+    /// `B' or `Q'.
+    pub encoding: u8,
+    pub text: (usize, usize),
+}
+
+fn is_charset_byte(c: u8) -> bool {
+    c > b' ' && !b"[]()<>@,;:*\\\"/?.=".contains(&c)
+}
+
+/// Parse the encoded word that starts at START in TEXT, if any.  If
+/// LOOSE, the text of a Q encoded word may contain `?', and ends at the
+/// first "?=", as with `rfc2047-allow-irregular-q-encoded-words'.
+pub fn parse_encoded_word(text: &[u8], start: usize, loose: bool) -> Option<EncodedWord> {
+    if !text[start..].starts_with(b"=?") {
+        return None;
+    }
+    let charset_start = start + 2;
+    let mut i = charset_start;
+    while i < text.len() && is_charset_byte(text[i]) {
+        i += 1;
+    }
+    let charset = (charset_start, i);
+    if i == charset_start {
+        return None;
+    }
+    // A language, as in RFC 2231.
+    if text.get(i) == Some(&b'*') {
+        let language = i + 1;
+        i = language;
+        while i < text.len() && text[i] != b'?' {
+            i += 1;
+        }
+        if i == language {
+            return None;
+        }
+    }
+    if text.get(i) != Some(&b'?') || text.get(i + 2) != Some(&b'?') {
+        return None;
+    }
+    let encoding = text[i + 1].to_ascii_uppercase();
+    let text_start = i + 3;
+    let mut i = text_start;
+    match encoding {
+        b'B' => {
+            while i < text.len() && base64_value(text[i]).is_some() {
+                i += 1;
+            }
+            while i < text.len() && text[i] == b'=' {
+                i += 1;
+            }
+        }
+        b'Q' => {
+            while i < text.len()
+                && text[i] >= b' '
+                && text[i] <= b'~'
+                && (if loose {
+                    !text[i..].starts_with(b"?=")
+                } else {
+                    text[i] != b'?'
+                })
+            {
+                i += 1;
+            }
+        }
+        _ => return None,
+    }
+    if !text[i..].starts_with(b"?=") {
+        return None;
+    }
+    Some(EncodedWord {
+        start,
+        end: i + 2,
+        charset,
+        encoding,
+        text: (text_start, i),
+    })
+}
+
+/// Find the runs of encoded words in TEXT.  The words of a run are only
+/// separated by whitespace, which is dropped when they are decoded.
+pub fn find_encoded_words(text: &[u8], loose: bool) -> Vec<Vec<EncodedWord>> {
+    let mut runs = Vec::new();
+    let mut i = 0;
+    while i + 1 < text.len() {
+        let first = match parse_encoded_word(text, i, loose) {
+            Some(word) => word,
+            None => {
+                i += 1;
+                continue;
+            }
+        };
+        i = first.end;
+        let mut run = vec![first];
+        loop {
+            let next = i + text[i..]
+                .iter()
+                .take_while(|&&c| c == b' ' || c == b'\t' || c == b'\n')
+                .count();
+            match parse_encoded_word(text, next, loose) {
+                Some(word) => {
+                    i = word.end;
+                    run.push(word);
+                }
+                None => break,
+            }
+        }
+        runs.push(run);
+    }
+    runs
+}
+
+/// Decode the text of WORD, found in TEXT.
+fn decode_word(text: &[u8], word: &EncodedWord) -> Option<Vec<u8>> {
+    let encoded = &text[word.text.0..word.text.1];
+    if word.encoding == b'B' {
+        decode_b(encoded)
+    } else {
+        Some(decode_q(encoded))
+    }
+}
+
+fn char_count(bytes: &[u8], multibyte: bool) -> usize {
+    if multibyte {
+        bytes.iter().filter(|&&b| b & 0xc0 != 0x80).count()
+    } else {
+        bytes.len()
+    }
+}
+
+fn make_lisp_string(bytes: &[u8], multibyte: bool) -> LispObject {
+    unsafe {
+        make_specified_string(
+            bytes.as_ptr() as *const c_char,
+            char_count(bytes, multibyte) as ptrdiff_t,
+            bytes.len() as ptrdiff_t,
+            multibyte,
+        )
+    }
+}
+
+/// Decode a run of encoded words in TEXT, which is in the internal
+/// representation of a string if MULTIBYTE.  Successive words in the
+/// same coding system are decoded together, so a character may be split
+/// between them.  Words that cannot be decoded are kept, separated from
+/// their neighbors by spaces.
+fn decode_run(
+    text: &[u8],
+    multibyte: bool,
+    run: &[EncodedWord],
+    coding_system: &impl Fn(LispObject) -> LispObject,
+) -> LispObject {
+    // (CODING-SYSTEM, BYTES), where CODING-SYSTEM is nil for words left
+    // alone.
+    let mut pieces: Vec<(LispObject, Vec<u8>)> = Vec::new();
+    for word in run {
+        let charset = make_lisp_string(&text[word.charset.0..word.charset.1], multibyte);
+        let coding = coding_system(charset);
+        match decode_word(text, word) {
+            Some(bytes) if coding.is_not_nil() => {
+                if pieces.last().map_or(false, |last| last.0.eq(coding)) {
+                    pieces.last_mut().unwrap().1.extend_from_slice(&bytes);
+                } else {
+                    pieces.push((coding, bytes));
+                }
+            }
+            _ => pieces.push((Qnil, text[word.start..word.end].to_vec())),
+        }
+    }
+
+    let mut strings: Vec<LispObject> = Vec::with_capacity(pieces.len());
+    for (i, (coding, bytes)) in pieces.into_iter().enumerate().rev() {
+        let string = if coding.is_not_nil() {
+            let string = make_lisp_string(&bytes, false);
+            unsafe { code_convert_string_norecord(string, coding, false) }
+        } else {
+            let mut raw = Vec::with_capacity(bytes.len() + 2);
+            if i > 0 {
+                raw.push(b' ');
+            }
+            raw.extend_from_slice(&bytes);
+            let next = strings.last().and_then(|next| next.as_string());
+            if next.map_or(false, |next| {
+                next.len_bytes() > 0 && next.byte_at(0) != b' '
+            }) {
+                raw.push(b' ');
+            }
+            make_lisp_string(&raw, multibyte)
+        };
+        strings.push(string);
+    }
+    strings.reverse();
+    concat(&mut strings)
+}
+
+/// Replace each run of newlines in STRING by a space.
+fn newlines_to_spaces(string: LispObject) -> LispObject {
+    let string = string.as_string_or_error();
+    let bytes = string.as_slice();
+    if !bytes.iter().any(|&c| c == b'\n' || c == b'\r') {
+        return string.as_lisp_obj();
+    }
+    let mut out = Vec::with_capacity(bytes.len());
+    for (i, &c) in bytes.iter().enumerate() {
+        if c != b'\n' && c != b'\r' {
+            out.push(c);
+        } else if i == 0 || (bytes[i - 1] != b'\n' && bytes[i - 1] != b'\r') {
+            out.push(b' ');
+        }
+    }
+    make_lisp_string(&out, string.is_multibyte())
+}
+
+/// Return the coding system named by CHARSET, a string, if there is one.
+pub fn charset_coding_system(charset: LispObject) -> LispObject {
+    let name = downcase(charset).as_string_or_error();
+    let coding = intern(&String::from_utf8_lossy(name.as_slice())).as_lisp_obj();
+    if unsafe { Fcoding_system_p(coding) }.is_not_nil() {
+        coding
+    } else {
+        Qnil
+    }
+}
+
+/// Decode the encoded words in TEXT, which is in the internal
+/// representation of a string if MULTIBYTE, and return the result.
+/// CODING_SYSTEM maps a charset to a coding system, as the
+/// CODING-FUNCTION of `rfc2047-decode-region-native'.
+pub fn decode_encoded_words(
+    text: &[u8],
+    multibyte: bool,
+    coding_system: &impl Fn(LispObject) -> LispObject,
+) -> LispObject {
+    let mut strings = Vec::new();
+    let mut previous = 0;
+    for run in find_encoded_words(text, true) {
+        strings.push(make_lisp_string(&text[previous..run[0].start], multibyte));
+        strings.push(newlines_to_spaces(decode_run(
+            text,
+            multibyte,
+            &run,
+            coding_system,
+        )));
+        previous = run[run.len() - 1].end;
+    }
+    if previous == 0 {
+        return make_lisp_string(text, multibyte);
+    }
+    strings.push(make_lisp_string(&text[previous..], multibyte));
+    concat(&mut strings)
+}
+
+/// Decode quoted-printable text between START and END, per RFC 2045.
+/// Each `=' followed by two hexadecimal digits is replaced by the byte
+/// they stand for, which is a raw byte character in a multibyte buffer,
+/// and soft line breaks are deleted.  Return non-nil if a `=' started
+/// nothing valid; it is left alone.
+#[lisp_fn]
+pub fn quoted_printable_decode_region_native(mut start: LispObject, mut end: LispObject) -> bool {
+    unsafe { validate_region(&mut start, &mut end) };
+    let multibyte = ThreadState::current_buffer().multibyte_characters_enabled();
+    let text = buffer_substring_no_properties(start, end).as_string_or_error();
+    if !text.as_slice().contains(&b'=') {
+        return false;
+    }
+    let (decoded, malformed) = qp_decode(text.as_slice(), multibyte);
+    let decoded = make_lisp_string(&decoded, multibyte);
+    unsafe {
+        replace_range(
+            start.as_fixnum_or_error() as ptrdiff_t,
+            end.as_fixnum_or_error() as ptrdiff_t,
+            decoded,
+            true,
+            false,
+            true,
+            false,
+        )
+    };
+    malformed
+}
+
+/// Encode the region between START and END as quoted-printable, per RFC
+/// 2045.  Control characters other than tab, newline and form feed, `=',
+/// bytes above 127 and whitespace at the end of lines are encoded.  The
+/// region may only contain ASCII and raw bytes.
+///
+/// If FOLD, lines are folded at 76 columns.  If ULTRA, lines are folded
+/// too, and "From " and `-' are encoded at the start of lines, for
+/// `mm-use-ultra-safe-encoding'.
+#[lisp_fn(min = "2")]
+pub fn quoted_printable_encode_region_native(
+    mut start: LispObject,
+    mut end: LispObject,
+    fold: bool,
+    ultra: bool,
+) {
+    unsafe { validate_region(&mut start, &mut end) };
+    let text = buffer_substring_no_properties(start, end).as_string_or_error();
+    let bytes: Vec<u8> = if text.is_multibyte() {
+        text.chars()
+            .map(|c| {
+                if c < 0x80 {
+                    c as u8
+                } else if char_byte8_p(c) {
+                    char_to_byte8(c)
+                } else {
+                    error!("Multibyte character in QP encoding region")
+                }
+            })
+            .collect()
+    } else {
+        text.as_slice().to_vec()
+    };
+    let encoded = qp_encode(&bytes, fold, ultra);
+    if encoded == text.as_slice() {
+        return;
+    }
+    unsafe {
+        replace_range(
+            start.as_fixnum_or_error() as ptrdiff_t,
+            end.as_fixnum_or_error() as ptrdiff_t,
+            make_lisp_string(&encoded, false),
+            true,
+            false,
+            true,
+            false,
+        )
+    };
+}
+
+/// Decode the RFC 2047 encoded words between START and END.
+/// CODING-FUNCTION is called with the charset of each word, a string,
+/// and returns the coding system to decode it with, or nil to leave the
+/// word alone.  Encoded words separated only by whitespace are joined,
+/// and newlines in the decoded text become spaces.  If LOOSE, Q encoded
+/// words may contain `?', as with `rfc2047-allow-irregular-q-encoded-words'.
+///
+/// Return a list of (BEG . END), the positions of the decoded text.
+#[lisp_fn(min = "3")]
+pub fn rfc2047_decode_region_native(
+    mut start: LispObject,
+    mut end: LispObject,
+    coding_function: LispObject,
+    loose: bool,
+) -> LispObject {
+    unsafe { validate_region(&mut start, &mut end) };
+    let beg = start.as_fixnum_or_error() as usize;
+    let multibyte = ThreadState::current_buffer().multibyte_characters_enabled();
+    let text = buffer_substring_no_properties(start, end).as_string_or_error();
+    let bytes = text.as_slice();
+
+    // Decode everything first, as CODING-FUNCTION may do anything.
+    let decoded: Vec<(usize, usize, LispObject)> = find_encoded_words(bytes, loose)
+        .iter()
+        .map(|run| {
+            let from = beg + char_count(&bytes[..run[0].start], multibyte);
+            let to = from + char_count(&bytes[run[0].start..run[run.len() - 1].end], multibyte);
+            let string = decode_run(bytes, multibyte, run, &|charset| {
+                call!(coding_function, charset)
+            });
+            (from, to, newlines_to_spaces(string))
+        })
+        .collect();
+
+    // Replace from the end, so that the earlier positions stay valid.
+    for &(from, to, string) in decoded.iter().rev() {
+        unsafe {
+            replace_range(
+                from as ptrdiff_t,
+                to as ptrdiff_t,
+                string,
+                true,
+                false,
+                true,
+                false,
+            )
+        };
+    }
+
+    let mut shift: isize = 0;
+    let spans: Vec<LispObject> = decoded
+        .iter()
+        .map(|&(from, to, string)| {
+            let len = string.as_string_or_error().len_chars() as isize;
+            let new_from = from as isize + shift;
+            shift += len - (to - from) as isize;
+            LispObject::cons(
+                LispObject::from(new_from as EmacsInt),
+                LispObject::from((new_from + len) as EmacsInt),
+            )
+        })
+        .collect();
+    LispObject::from(spans)
+}
+
+include!(concat!(env!("OUT_DIR"), "/mime_exports.rs"));
+
+#[test]
+fn test_qp_decode() {
+    assert_eq!(
+        qp_decode(b"a=3Db=\nc=e9", false),
+        (b"a=bc\xe9".to_vec(), false)
+    );
+    assert_eq!(qp_decode(b"=E9", true), (b"\xc1\xa9".to_vec(), false));
+    assert_eq!(qp_decode(b"1=2 =", false), (b"1=2 =".to_vec(), true));
+}
+
+#[test]
+fn test_qp_encode() {
+    assert_eq!(
+        qp_encode(b"a=b \n\tc\xe9\r\t", false, false),
+        b"a=3Db=20\n\tc=E9=0D=09".to_vec()
+    );
+    let long = [b'x'; 80];
+    let mut folded = vec![b'x'; 75];
+    folded.extend_from_slice(b"=\nxxxxx");
+    assert_eq!(qp_encode(&long, true, false), folded);
+    let mut long = vec![b'x'; 73];
+    long.push(b'=');
+    long.extend_from_slice(&[b'x'; 5]);
+    let folded = qp_encode(&long, true, false);
+    assert!(folded.starts_with(&[b'x'; 73]));
+    assert_eq!(&folded[73..78], b"=\n=3D");
+    assert_eq!(
+        qp_encode(b"From me\n-- \n", false, true),
+        b"From=20me\n=2D-=20\n".to_vec()
+    );
+}
+
+#[test]
+fn test_decode_b() {
+    assert_eq!(decode_b(b"Zm9vYg=="), Some(b"foob".to_vec()));
+    assert_eq!(decode_b(b"Zm9vYg"), Some(b"foob".to_vec()));
+    assert_eq!(decode_b(b"Zm9vYg="), Some(b"foob".to_vec()));
+    assert_eq!(decode_b(b"Zm9vY"), None);
+    assert_eq!(decode_b(b"Zm9v!"), None);
+}
+
+#[test]
+fn test_encoded_words() {
+    let text = b"Re: =?utf-8?q?caf=C3=A9?=\n =?ISO-8859-1*fr?B?Zm9v?= x =?us-ascii?Q?a?b?=";
+    let runs = find_encoded_words(text, false);
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].len(), 2);
+    assert_eq!(&text[runs[0][0].charset.0..runs[0][0].charset.1], b"utf-8");
+    assert_eq!(runs[0][0].encoding, b'Q');
+    assert_eq!(
+        decode_word(text, &runs[0][0]),
+        Some(b"caf\xc3\xa9".to_vec())
+    );
+    assert_eq!(
+        &text[runs[0][1].charset.0..runs[0][1].charset.1],
+        b"ISO-8859-1"
+    );
+    assert_eq!(decode_word(text, &runs[0][1]), Some(b"foo".to_vec()));
+    let runs = find_encoded_words(text, true);
+    assert_eq!(runs.len(), 2);
+    assert_eq!(decode_word(text, &runs[1][0]), Some(b"a?b".to_vec()));
+    assert!(find_encoded_words(b"=?utf-8?X?a?= =??Q?a?=", true).is_empty());
+}
@@ -0,0 +1,226 @@
+//! Message catalogs.
+//!
+//! A message catalog maps the messages of Emacs and its packages to
+//! their translations in the user's language.  Catalogs are the binary
+//! `.mo' files that GNU gettext's `msgfmt' makes from `.po' files, so
+//! that distributions can translate the messages of Emacs with the
+//! tools they already use for other programs.  The translations must be
+//! in UTF-8.
+//!
+//! Messages are looked up by `translate-message', and, when
+//! `translate-messages' is non-nil, by `format-message' and `message'
+//! for their format string.
+
+use std::collections::HashMap;
+use std::fs;
+use std::str;
+use std::sync::Mutex;
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    callproc::lisp_file_name_to_path,
+    lisp::{defsubr, LispObject},
+    multibyte::LispStringRef,
+    remacs_sys::{globals, EmacsInt, Fexpand_file_name, Qnil},
+    shell::make_lisp_string,
+};
+
+/// The magic number of `.mo' files, in the byte order of the file.
+const MO_MAGIC: u32 = 0x9504_12de;
+
+/// In a message ID, the separator between the context and the message.
+const CONTEXT_SEPARATOR: u8 = 0x04;
+
+lazy_static! {
+    /// The loaded catalogs, most recently loaded first.
+    static ref CATALOGS: Mutex<Vec<HashMap<Vec<u8>, Vec<u8>>>> = Mutex::new(Vec::new());
+}
+
+/// Return the part of BYTES before the first null byte.
+fn first_string(bytes: &[u8]) -> &[u8] {
+    bytes.split(|&b| b == 0).next().unwrap_or(bytes)
+}
+
+/// Parse DATA, the contents of a `.mo' file, into a map from message
+/// IDs to translations.  Messages with a context have IDs of the form
+/// CONTEXT\x04MESSAGE.  Only the singular form of plural messages is
+/// kept, and the header entry and translations that are empty or not
+/// in UTF-8 are left out.  Return None if DATA is not a valid catalog.
+fn parse_catalog(data: &[u8]) -> Option<HashMap<Vec<u8>, Vec<u8>>> {
+    let magic = data
+        .get(0..4)?
+        .iter()
+        .rev()
+        .fold(0, |n, &b| n << 8 | u32::from(b));
+    let big_endian = if magic == MO_MAGIC {
+        false
+    } else if magic.swap_bytes() == MO_MAGIC {
+        true
+    } else {
+        return None;
+    };
+    let word = |pos: usize| -> Option<usize> {
+        let bytes = data.get(pos..pos.checked_add(4)?)?;
+        let n = if big_endian {
+            bytes.iter().fold(0, |n, &b| n << 8 | u32::from(b))
+        } else {
+            bytes.iter().rev().fold(0, |n, &b| n << 8 | u32::from(b))
+        };
+        Some(n as usize)
+    };
+    let string = |table: usize, i: usize| -> Option<&[u8]> {
+        let entry = table.checked_add(i.checked_mul(8)?)?;
+        let (len, offset) = (word(entry)?, word(entry + 4)?);
+        data.get(offset..offset.checked_add(len)?)
+    };
+
+    // Only major revisions 0 and 1 are known.
+    if word(4)? >> 16 > 1 {
+        return None;
+    }
+    let (count, originals, translations) = (word(8)?, word(12)?, word(16)?);
+    let mut messages = HashMap::new();
+    for i in 0..count {
+        let id = first_string(string(originals, i)?);
+        let translation = first_string(string(translations, i)?);
+        if id.is_empty() || translation.is_empty() || str::from_utf8(translation).is_err() {
+            continue;
+        }
+        messages.insert(id.to_vec(), translation.to_vec());
+    }
+    Some(messages)
+}
+
+/// Return the translation of the message with ID in the loaded
+/// catalogs, if any.
+fn lookup(id: &[u8]) -> Option<Vec<u8>> {
+    CATALOGS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|catalog| catalog.get(id))
+        .next()
+        .cloned()
+}
+
+/// Load the message catalog in FILE, a `.mo' file made by GNU gettext.
+/// Its translations take precedence over those of the catalogs loaded
+/// before.  Return the number of translations it has.
+#[lisp_fn]
+pub fn load_message_catalog(file: LispStringRef) -> EmacsInt {
+    let path = lisp_file_name_to_path(unsafe { Fexpand_file_name(file.into(), Qnil) });
+    let data = match fs::read(&path) {
+        Ok(data) => data,
+        Err(e) => error!("Can't read message catalog {}: {}", path.display(), e),
+    };
+    let messages = match parse_catalog(&data) {
+        Some(messages) => messages,
+        None => error!("Invalid message catalog {}", path.display()),
+    };
+    let count = messages.len() as EmacsInt;
+    CATALOGS.lock().unwrap().insert(0, messages);
+    count
+}
+
+/// Forget all the message catalogs loaded by `load-message-catalog'.
+#[lisp_fn]
+pub fn clear_message_catalogs() {
+    CATALOGS.lock().unwrap().clear();
+}
+
+/// Return the translation of the message STRING in the loaded catalogs.
+/// If CONTEXT is non-nil, it is a string that tells apart messages that
+/// are the same in English but not in other languages, like the
+/// `msgctxt' of GNU gettext.  If there is no translation, return
+/// STRING.  See `load-message-catalog'.
+#[lisp_fn(min = "1")]
+pub fn translate_message(string: LispStringRef, context: LispObject) -> LispObject {
+    let mut id = Vec::new();
+    if context.is_not_nil() {
+        id.extend_from_slice(context.as_string_or_error().as_slice());
+        id.push(CONTEXT_SEPARATOR);
+    }
+    id.extend_from_slice(string.as_slice());
+    match lookup(&id) {
+        Some(translation) => make_lisp_string(&translation, true),
+        None => string.into(),
+    }
+}
+
+/// Translate the format string FORMAT of `format-message', if
+/// `translate-messages' is non-nil.
+pub fn translate_format(format: LispObject) -> LispObject {
+    if !unsafe { globals.translate_messages } {
+        return format;
+    }
+    match format.as_string() {
+        Some(string) => translate_message(string, Qnil),
+        None => format,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn syms_of_msgcat() {
+    /// Non-nil means `format-message' and `message' translate their format.
+    /// The format string is replaced by its translation in the catalogs
+    /// loaded by `load-message-catalog', if it has one, before the other
+    /// arguments are formatted.
+    defvar_bool!(translate_messages, "translate-messages", false);
+}
+
+include!(concat!(env!("OUT_DIR"), "/msgcat_exports.rs"));
+
+#[cfg(test)]
+fn push_word(out: &mut Vec<u8>, word: usize) {
+    out.extend((0..4).map(|i| (word >> (8 * i)) as u8));
+}
+
+/// Make a little-endian `.mo' file with ENTRIES, without a hash
+/// table.
+#[cfg(test)]
+fn make_catalog(entries: &[(&str, &str)]) -> Vec<u8> {
+    let n = entries.len();
+    let mut out = Vec::new();
+    for &word in &[MO_MAGIC as usize, 0, n, 28, 28 + 8 * n, 0, 0] {
+        push_word(&mut out, word);
+    }
+    let start = 28 + 16 * n;
+    let mut strings = Vec::new();
+    for column in 0..2 {
+        for entry in entries {
+            let s = if column == 0 { entry.0 } else { entry.1 };
+            push_word(&mut out, s.len());
+            push_word(&mut out, start + strings.len());
+            strings.extend_from_slice(s.as_bytes());
+            strings.push(0);
+        }
+    }
+    out.extend(strings);
+    out
+}
+
+#[test]
+fn test_parse_catalog() {
+    let data = make_catalog(&[
+        ("", "Content-Type: text/plain; charset=UTF-8\n"),
+        ("Quit", "Abbruch"),
+        ("File\0Files", "Datei\0Dateien"),
+        ("menu\x04Open", "Öffnen"),
+        ("Untranslated", ""),
+    ]);
+    let messages = parse_catalog(&data).unwrap();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[&b"Quit"[..]], b"Abbruch");
+    assert_eq!(messages[&b"File"[..]], b"Datei");
+    assert_eq!(messages[&b"menu\x04Open"[..]], "Öffnen".as_bytes());
+}
+
+#[test]
+fn test_parse_invalid_catalog() {
+    assert!(parse_catalog(b"").is_none());
+    assert!(parse_catalog(b"not a catalog at all").is_none());
+    let mut data = make_catalog(&[("Quit", "Abbruch")]);
+    data.truncate(data.len() - 4);
+    assert!(parse_catalog(&data).is_none());
+}
@@ -41,7 +41,7 @@ use libc::{c_char, c_int, c_uchar, c_uint, c_void, memset, ptrdiff_t, size_t};
 use crate::{
     lisp::{ExternalPtr, LispObject},
     remacs_sys::Qstringp,
-    remacs_sys::{char_bits, equal_kind, EmacsDouble, EmacsInt, Lisp_String, Lisp_Type},
+    remacs_sys::{char_bits, equal_kind, interval, EmacsDouble, EmacsInt, Lisp_String, Lisp_Type},
     remacs_sys::{compare_string_intervals, empty_unibyte_string, lisp_string_width},
 };
 
@@ -174,6 +174,16 @@ impl LispStringRef {
         }
     }
 
+    /// The root of the interval tree of the string, which may be null.
+    pub fn intervals(self) -> *mut interval {
+        unsafe { self.u.s.intervals }
+    }
+
+    /// Make I the root of the interval tree of the string.
+    pub fn set_intervals(&mut self, i: *mut interval) {
+        unsafe { self.u.s.intervals = i };
+    }
+
     pub fn set_byte(&mut self, idx: ptrdiff_t, elt: c_uchar) {
         unsafe { ptr::write(self.data_ptr().offset(idx), elt) };
     }
@@ -503,7 +513,7 @@ pub unsafe extern "C" fn str_to_multibyte(
 
 /// Same as `MULTIBYTE_LENGTH` macro in C.
 #[allow(clippy::if_same_then_else)]
-fn multibyte_length(slice: &[c_uchar], allow_encoded_raw: bool) -> Option<usize> {
+pub fn multibyte_length(slice: &[c_uchar], allow_encoded_raw: bool) -> Option<usize> {
     let len = slice.len();
     if len < 1 {
         None
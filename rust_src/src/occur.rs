@@ -0,0 +1,262 @@
+//! Collecting the lines that match a regexp, for `occur'.
+//!
+//! Listing the matches from Lisp costs a `re-search-forward' and a
+//! `count-lines' per matching line, which is too slow for very large
+//! buffers.  `collect-matches' scans whole buffers in one call, and
+//! returns the matching lines with their line numbers and the bounds of
+//! the matches in them.
+
+use std::ptr;
+
+use libc::ptrdiff_t;
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    buffers::LispBufferRef,
+    editfns::save_excursion_save,
+    eval::unbind_to,
+    lisp::{defsubr, LispObject},
+    lists::list,
+    multibyte::LispStringRef,
+    remacs_sys::{
+        find_before_next_newline, find_newline, record_unwind_current_buffer,
+        record_unwind_protect, save_excursion_restore, set_buffer_internal_1, set_point, specbind,
+        EmacsUint,
+    },
+    remacs_sys::{Qcase_fold_search, Qnil, Qt},
+    search::{re_search_forward, MatchData, SaveMatchData},
+    threads::{c_specpdl_index, ThreadState},
+};
+
+/// A line, or several lines for a match that spans them, with the
+/// matches in it.
+struct MatchLine {
+    first_line: ptrdiff_t,
+    last_line: ptrdiff_t,
+    beg: ptrdiff_t,
+    end: ptrdiff_t,
+    matches: Vec<(ptrdiff_t, ptrdiff_t)>,
+    before: Vec<(ptrdiff_t, ptrdiff_t)>,
+    after: Vec<(ptrdiff_t, ptrdiff_t)>,
+}
+
+impl MatchLine {
+    fn to_lisp(&self) -> LispObject {
+        let ranges = |ranges: &[(ptrdiff_t, ptrdiff_t)]| {
+            let conses: Vec<LispObject> = ranges
+                .iter()
+                .map(|&(beg, end)| LispObject::cons(LispObject::from(beg), LispObject::from(end)))
+                .collect();
+            list(&conses)
+        };
+        list(&[
+            LispObject::from(self.first_line),
+            LispObject::from(self.last_line),
+            LispObject::from(self.beg),
+            LispObject::from(self.end),
+            ranges(&self.matches),
+            ranges(&self.before),
+            ranges(&self.after),
+        ])
+    }
+}
+
+/// Return the number of newlines between START and END in the current
+/// buffer.
+pub(crate) fn count_newlines(start: ptrdiff_t, end: ptrdiff_t) -> ptrdiff_t {
+    if start >= end {
+        return 0;
+    }
+    let mut shortage = 0;
+    unsafe {
+        find_newline(
+            start,
+            -1,
+            end,
+            -1,
+            ptrdiff_t::max_value(),
+            &mut shortage,
+            ptr::null_mut(),
+            true,
+        )
+    };
+    ptrdiff_t::max_value() - shortage
+}
+
+/// Return the start of the line containing POS.
+fn line_start(pos: ptrdiff_t) -> ptrdiff_t {
+    let begv = ThreadState::current_buffer().begv;
+    unsafe {
+        find_newline(
+            pos,
+            -1,
+            begv,
+            -1,
+            -1,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            true,
+        )
+    }
+}
+
+/// Return the end of the line containing POS.
+fn line_end(pos: ptrdiff_t) -> ptrdiff_t {
+    let zv = ThreadState::current_buffer().zv;
+    unsafe { find_before_next_newline(pos, zv, 1, ptr::null_mut()) }
+}
+
+/// Return the bounds of up to NLINES lines before the line starting at
+/// BEG, and of up to NLINES lines after the line ending at END.
+fn context_lines(
+    beg: ptrdiff_t,
+    end: ptrdiff_t,
+    nlines: usize,
+) -> (Vec<(ptrdiff_t, ptrdiff_t)>, Vec<(ptrdiff_t, ptrdiff_t)>) {
+    let buffer = ThreadState::current_buffer();
+
+    let mut before = Vec::with_capacity(nlines);
+    let mut pos = beg;
+    while before.len() < nlines && pos > buffer.begv {
+        let start = line_start(pos - 1);
+        before.push((start, pos - 1));
+        pos = start;
+    }
+    before.reverse();
+
+    let mut after = Vec::with_capacity(nlines);
+    let mut pos = end;
+    while after.len() < nlines && pos < buffer.zv {
+        let stop = line_end(pos + 1);
+        after.push((pos + 1, stop));
+        pos = stop;
+    }
+
+    (before, after)
+}
+
+/// Return the matches for REGEXP between BEG and END, the bounds of a
+/// matching line.  A match that begins at END only counts if there is
+/// no other match, as for an empty line.
+fn line_matches(regexp: LispObject, beg: ptrdiff_t, end: ptrdiff_t) -> Vec<(ptrdiff_t, ptrdiff_t)> {
+    let mut matches = Vec::new();
+    let mut pos = beg;
+    while pos <= end {
+        unsafe { set_point(pos) };
+        if re_search_forward(regexp, LispObject::from(end), Qt, Qnil).is_nil() {
+            break;
+        }
+        let (start, stop) = match MatchData::register(0) {
+            Some(range) => range,
+            None => break,
+        };
+        if start >= end && !matches.is_empty() {
+            break;
+        }
+        matches.push((start, stop));
+        pos = if start == stop { stop + 1 } else { stop };
+    }
+    matches
+}
+
+/// Return the matching lines for REGEXP in the current buffer.
+fn buffer_matches(regexp: LispObject, nlines: usize, limit: usize) -> Vec<MatchLine> {
+    let zv = ThreadState::current_buffer().zv;
+    let mut lines = Vec::new();
+    let mut pos = ThreadState::current_buffer().begv;
+    let mut line = 1;
+    while lines.len() < limit && pos < zv {
+        unsafe { set_point(pos) };
+        if re_search_forward(regexp, Qnil, Qt, Qnil).is_nil() {
+            break;
+        }
+        let (match_beg, match_end) = match MatchData::register(0) {
+            Some(range) => range,
+            None => break,
+        };
+        let beg = line_start(match_beg);
+        let end = line_end(match_end);
+        let first_line = line + count_newlines(pos, beg);
+        let last_line = first_line + count_newlines(beg, end);
+        let (before, after) = context_lines(beg, end, nlines);
+
+        lines.push(MatchLine {
+            first_line,
+            last_line,
+            beg,
+            end,
+            matches: line_matches(regexp, beg, end),
+            before,
+            after,
+        });
+
+        // On to the line after the match.
+        line = last_line + 1;
+        pos = end + 1;
+    }
+    lines
+}
+
+/// Collect the lines that match REGEXP in BUFFERS.
+/// BUFFERS is a buffer or a list of buffers; killed buffers are
+/// skipped.  Only the accessible portion of each buffer is scanned.
+///
+/// The value is a list with an element (BUFFER . LINES) for each
+/// buffer.  LINES has an element for each matching line, or each
+/// group of lines for a match that spans several:
+///
+///   (FIRST-LINE LAST-LINE BEG END MATCHES BEFORE AFTER)
+///
+/// FIRST-LINE and LAST-LINE are line numbers, counting from 1 at the
+/// start of the accessible portion, and BEG and END are the bounds of
+/// the lines.  MATCHES is a list of conses (MATCH-BEG . MATCH-END), the
+/// bounds of the matches in the lines.  BEFORE and AFTER are lists of
+/// conses (LINE-BEG . LINE-END), the bounds of up to NLINES lines of
+/// context before and after the lines.
+///
+/// Matching ignores case if CASE-FOLD is non-nil.  If LIMIT is non-nil,
+/// collect at most LIMIT matching lines in each buffer.
+#[lisp_fn(min = "2")]
+pub fn collect_matches(
+    regexp: LispStringRef,
+    buffers: LispObject,
+    nlines: Option<EmacsUint>,
+    case_fold: LispObject,
+    limit: Option<EmacsUint>,
+) -> LispObject {
+    let regexp = LispObject::from(regexp);
+    let buffers: Vec<LispBufferRef> = if let Some(buffer) = buffers.as_buffer() {
+        vec![buffer]
+    } else {
+        buffers
+            .iter_cars()
+            .map(|buffer| buffer.as_buffer_or_error())
+            .collect()
+    };
+    let nlines = nlines.unwrap_or(0) as usize;
+    let limit = limit.map_or(usize::max_value(), |limit| limit as usize);
+
+    // Restoring the match data restores the current buffer too.
+    let _match_data = SaveMatchData::save();
+    unsafe { record_unwind_current_buffer() };
+
+    let mut result = Vec::new();
+    for mut buffer in buffers.into_iter().filter(|b| b.is_live()) {
+        unsafe { set_buffer_internal_1(buffer.as_mut()) };
+        let count = c_specpdl_index();
+        unsafe {
+            record_unwind_protect(Some(save_excursion_restore), save_excursion_save());
+            specbind(Qcase_fold_search, case_fold);
+        }
+        let lines: Vec<LispObject> = buffer_matches(regexp, nlines, limit)
+            .iter()
+            .map(MatchLine::to_lisp)
+            .collect();
+        unbind_to(count, Qnil);
+        result.push(LispObject::cons(buffer.as_lisp_obj(), list(&lines)));
+    }
+    list(&result)
+}
+
+include!(concat!(env!("OUT_DIR"), "/occur_exports.rs"));
@@ -0,0 +1,1141 @@
+//! Evaluation of simple Org table formulas.
+//!
+//! `org-table-eval-formula' turns every reference of a formula into
+//! text with walks over the buffer and hands the result to
+//! `calc-eval', once for each field, so recalculating a large table
+//! takes seconds.  Formulas that only do arithmetic on numbers, with
+//! the vector functions tables use most, are evaluated here instead,
+//! over the table parsed in one pass.  Numbers follow Calc under
+//! `org-calc-default-modes': integers are exact, other numbers are
+//! decimal floats rounded to twelve digits and shown with eight.
+//! Anything else makes the evaluation give up, and Calc does it.
+
+use std::cmp::Ordering;
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    buffers::validate_region,
+    editfns::buffer_substring_no_properties,
+    lisp::{defsubr, LispObject},
+    obarray::intern,
+    remacs_sys::{EmacsInt, Qnil},
+    symbols::symbol_value,
+};
+
+/// The `calc-internal-prec' of `org-calc-default-modes'.
+const PRECISION: i32 = 12;
+
+/// The digits of the `(float 8)' display format.
+const FIGURES: i32 = 8;
+
+/// A Calc number.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Number {
+    Integer(i64),
+    /// MANTISSA times ten to the power EXPONENT, where MANTISSA has no
+    /// trailing zeros.
+    Float(i128, i32),
+}
+
+use self::Number::{Float, Integer};
+
+/// A formula operand.
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Number(Number),
+    Vector(Vec<Number>),
+}
+
+fn power_of_ten(n: i32) -> Option<i128> {
+    10i128.checked_pow(n as u32)
+}
+
+/// Return the number of decimal digits of N.
+fn digits(n: i128) -> i32 {
+    let mut n = n.abs();
+    let mut count = 0;
+    while n > 0 {
+        n /= 10;
+        count += 1;
+    }
+    count
+}
+
+/// Scale N by ten to the power SCALE, rounding half away from zero, like
+/// `math-scale-rounding'.
+fn scale_rounding(n: i128, scale: i32) -> Option<i128> {
+    if scale >= 0 {
+        n.checked_mul(power_of_ten(scale)?)
+    } else if n < 0 {
+        scale_rounding(-n, scale).map(|n| -n)
+    } else {
+        let shifted = power_of_ten(-1 - scale).map_or(0, |p| n / p);
+        Some((shifted + 5) / 10)
+    }
+}
+
+/// Return the float MANTISSA times ten to the power EXPONENT, rounded to
+/// PRECISION digits, like `math-make-float'.
+fn make_float(mut mantissa: i128, mut exponent: i32, precision: i32) -> Option<Number> {
+    if mantissa == 0 {
+        return Some(Float(0, 0));
+    }
+    let excess = precision - digits(mantissa);
+    if excess < 0 {
+        mantissa = scale_rounding(mantissa, excess)?;
+        exponent -= excess;
+    }
+    while mantissa % 10 == 0 {
+        mantissa /= 10;
+        exponent += 1;
+    }
+    Some(Float(mantissa, exponent))
+}
+
+impl Number {
+    fn is_zero(self) -> bool {
+        match self {
+            Integer(n) => n == 0,
+            Float(m, _) => m == 0,
+        }
+    }
+
+    fn is_float(self) -> bool {
+        match self {
+            Integer(_) => false,
+            Float(..) => true,
+        }
+    }
+
+    fn is_negative(self) -> bool {
+        match self {
+            Integer(n) => n < 0,
+            Float(m, _) => m < 0,
+        }
+    }
+
+    /// Return the mantissa and exponent of this number as a float.
+    fn float(self, precision: i32) -> Option<(i128, i32)> {
+        match self {
+            Integer(n) => match make_float(i128::from(n), 0, precision)? {
+                Float(m, e) => Some((m, e)),
+                Integer(_) => None,
+            },
+            Float(m, e) => Some((m, e)),
+        }
+    }
+
+    fn normalize(self, precision: i32) -> Option<Number> {
+        match self {
+            Integer(_) => Some(self),
+            Float(m, e) => make_float(m, e, precision),
+        }
+    }
+
+    fn neg(self) -> Option<Number> {
+        match self {
+            Integer(n) => n.checked_neg().map(Integer),
+            Float(m, e) => Some(Float(-m, e)),
+        }
+    }
+
+    fn add(self, other: Number, precision: i32) -> Option<Number> {
+        if let (Integer(a), Integer(b)) = (self, other) {
+            return a.checked_add(b).map(Integer);
+        }
+        let (mut a, mut b) = (self.float(precision)?, other.float(precision)?);
+        if a.0 == 0 {
+            return Some(Float(b.0, b.1));
+        } else if b.0 == 0 {
+            return Some(Float(a.0, a.1));
+        }
+        if a.1 < b.1 {
+            std::mem::swap(&mut a, &mut b);
+        }
+        let difference = a.1 - b.1;
+        if difference >= 2 * precision {
+            return Some(Float(a.0, a.1));
+        }
+        let scaled = a.0.checked_mul(power_of_ten(difference)?)?;
+        make_float(scaled.checked_add(b.0)?, b.1, precision)
+    }
+
+    fn sub(self, other: Number, precision: i32) -> Option<Number> {
+        self.add(other.neg()?, precision)
+    }
+
+    fn mul(self, other: Number, precision: i32) -> Option<Number> {
+        match (self, other) {
+            (Integer(a), Integer(b)) => a.checked_mul(b).map(Integer),
+            (Integer(0), _) | (_, Integer(0)) => Some(Float(0, 0)),
+            _ => {
+                let (a, b) = (self.float(precision)?, other.float(precision)?);
+                make_float(a.0.checked_mul(b.0)?, a.1 + b.1, precision)
+            }
+        }
+    }
+
+    fn div(self, other: Number, precision: i32) -> Option<Number> {
+        if other.is_zero() {
+            return None;
+        } else if self.is_zero() {
+            return Some(if other.is_float() { Float(0, 0) } else { self });
+        }
+        if let (Integer(a), Integer(b)) = (self, other) {
+            if a.checked_rem(b)? == 0 {
+                return a.checked_div(b).map(Integer);
+            }
+        }
+        let (a, b) = (self.float(precision)?, other.float(precision)?);
+        let scale = std::cmp::max(precision + 1 - (digits(a.0) - digits(b.0)), 0);
+        let quotient = a.0.checked_mul(power_of_ten(scale)?)? / b.0;
+        make_float(quotient, a.1 - b.1 - scale, precision)
+    }
+
+    fn pow(self, other: Number) -> Option<Number> {
+        match (self, other) {
+            (Integer(0), Integer(0)) => None,
+            (Integer(a), Integer(b)) if b >= 0 && b <= i64::from(u32::max_value()) => {
+                a.checked_pow(b as u32).map(Integer)
+            }
+            _ => None,
+        }
+    }
+
+    fn compare(self, other: Number) -> Ordering {
+        let exact = |n: Number| match n {
+            Integer(n) => (i128::from(n), 0),
+            Float(m, e) => (m, e),
+        };
+        let ((a, ea), (b, eb)) = (exact(self), exact(other));
+        let exponent = std::cmp::min(ea, eb);
+        let scaled = |m: i128, e: i32| power_of_ten(e - exponent).and_then(|p| m.checked_mul(p));
+        match (scaled(a, ea), scaled(b, eb)) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => {
+                let (a, b) = (a as f64 * 10f64.powi(ea), b as f64 * 10f64.powi(eb));
+                a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+            }
+        }
+    }
+
+    /// Truncate this number towards zero.
+    fn truncate(self) -> Option<Number> {
+        match self {
+            Integer(_) => Some(self),
+            Float(m, e) if e >= 0 => {
+                let n = m.checked_mul(power_of_ten(e)?)?;
+                if n > i128::from(i64::max_value()) || n < i128::from(i64::min_value()) {
+                    None
+                } else {
+                    Some(Integer(n as i64))
+                }
+            }
+            Float(m, e) => Some(Integer(power_of_ten(-e).map_or(0, |p| m / p) as i64)),
+        }
+    }
+
+    fn is_integral(self) -> bool {
+        match self {
+            Integer(_) => true,
+            Float(_, e) => e >= 0,
+        }
+    }
+
+    fn floor(self) -> Option<Number> {
+        let truncated = self.truncate()?;
+        if self.is_negative() && !self.is_integral() {
+            truncated.add(Integer(-1), PRECISION)
+        } else {
+            Some(truncated)
+        }
+    }
+
+    fn ceiling(self) -> Option<Number> {
+        let truncated = self.truncate()?;
+        if !self.is_negative() && !self.is_integral() {
+            truncated.add(Integer(1), PRECISION)
+        } else {
+            Some(truncated)
+        }
+    }
+
+    fn round(self) -> Option<Number> {
+        if self.is_integral() {
+            self.truncate()
+        } else if self.is_negative() {
+            self.neg()?.round()?.neg()
+        } else {
+            self.add(Float(5, -1), PRECISION)?.floor()
+        }
+    }
+}
+
+/// Format N as Calc does under `org-calc-default-modes'.
+fn format_number(n: Number) -> String {
+    let (mut mantissa, mut exponent) = match n {
+        Integer(n) => return n.to_string(),
+        Float(m, _) if m < 0 => return format!("-{}", format_number(n.neg().unwrap())),
+        Float(m, e) => (m, e),
+    };
+    let excess = FIGURES - digits(mantissa);
+    if excess < 0 {
+        mantissa = scale_rounding(mantissa, excess).unwrap_or(0);
+        exponent -= excess;
+    }
+    let text = mantissa.to_string();
+    let point = exponent + text.len() as i32;
+    if point <= PRECISION && point >= -1 {
+        if point == 0 {
+            format!("0.{}", text)
+        } else if exponent <= 0 && point > 0 {
+            let (integer, fraction) = text.split_at(point as usize);
+            format!("{}.{}", integer, fraction)
+        } else if exponent > 0 {
+            format!("{}{}.", text, "0".repeat(exponent as usize))
+        } else {
+            format!("0.{}{}", "0".repeat(-point as usize), text)
+        }
+    } else {
+        let (first, rest) = text.split_at(1);
+        if rest.is_empty() {
+            format!("{}e{}", first, point - 1)
+        } else {
+            format!("{}.{}e{}", first, rest, point - 1)
+        }
+    }
+}
+
+/// Return the number at the start of TEXT, like `string-to-number'.
+fn string_to_number(text: &[u8]) -> Option<Number> {
+    let mut pos = text
+        .iter()
+        .position(|c| !b" \t\n".contains(c))
+        .unwrap_or_else(|| text.len());
+    let negative = text.get(pos) == Some(&b'-');
+    if negative || text.get(pos) == Some(&b'+') {
+        pos += 1;
+    }
+    let digit_run = |from: usize| {
+        text[from..]
+            .iter()
+            .position(|c| !c.is_ascii_digit())
+            .unwrap_or_else(|| text.len() - from)
+    };
+    let integer = digit_run(pos);
+    let mut end = pos + integer;
+    let mut float = false;
+    if text.get(end) == Some(&b'.') && digit_run(end + 1) > 0 {
+        end += 1 + digit_run(end + 1);
+        float = true;
+    }
+    if end > pos && (text.get(end) == Some(&b'e') || text.get(end) == Some(&b'E')) {
+        let sign = match text.get(end + 1) {
+            Some(b'+') | Some(b'-') => 1,
+            _ => 0,
+        };
+        let exponent = digit_run(end + 1 + sign);
+        if exponent > 0 {
+            end += 1 + sign + exponent;
+            float = true;
+        }
+    }
+    let number = if end == pos {
+        Integer(0)
+    } else if float {
+        Parser::new(&text[pos..end], None).number()?
+    } else {
+        Parser::new(&text[pos..pos + integer], None).number()?
+    };
+    if negative {
+        number.neg()
+    } else {
+        Some(number)
+    }
+}
+
+fn trim(text: &[u8]) -> &[u8] {
+    let start = text
+        .iter()
+        .position(|c| !b" \t".contains(c))
+        .unwrap_or_else(|| text.len());
+    let end = text
+        .iter()
+        .rposition(|c| !b" \t".contains(c))
+        .map_or(start, |end| end + 1);
+    &text[start..end]
+}
+
+/// The lines of a table, with `None' for each hline and the trimmed
+/// fields of each data line.
+struct Table<'a> {
+    rows: Vec<Option<Vec<&'a [u8]>>>,
+}
+
+impl<'a> Table<'a> {
+    /// Parse the table lines at the start of TEXT.
+    fn parse(text: &'a [u8]) -> Self {
+        let mut rows = Vec::new();
+        for line in text.split(|&c| c == b'\n') {
+            let line = trim(line);
+            if line.first() != Some(&b'|') {
+                break;
+            } else if line.get(1) == Some(&b'-') {
+                rows.push(None);
+                continue;
+            }
+            let mut fields: Vec<_> = line[1..].split(|&c| c == b'|').map(trim).collect();
+            if line.len() > 1 && line.ends_with(b"|") {
+                fields.pop();
+            }
+            rows.push(Some(fields));
+        }
+        Table { rows }
+    }
+
+    /// Return non-nil if line I is an hline.  There is an imaginary one
+    /// after the last line, as in `org-table-current-line-types'.
+    fn is_hline(&self, i: usize) -> bool {
+        self.rows.get(i).map_or(true, Option::is_none)
+    }
+
+    fn data_line(&self, n: usize) -> Option<usize> {
+        (0..self.rows.len())
+            .filter(|&i| !self.is_hline(i))
+            .nth(n.checked_sub(1)?)
+    }
+
+    /// Return the line of the Nth line of type HLINE from line I, like
+    /// `org-table--row-type'.  Crossing an hline in a RELATIVE move is
+    /// left to Lisp unless MAY-CROSS.
+    fn row_type(
+        &self,
+        hline: bool,
+        n: usize,
+        i: usize,
+        backwards: bool,
+        relative: bool,
+        may_cross: bool,
+    ) -> Option<usize> {
+        let length = self.rows.len() as isize + 1;
+        let mut i = i as isize;
+        for _ in 0..n {
+            loop {
+                i += if backwards { -1 } else { 1 };
+                if i < 0 || i >= length || self.is_hline(i as usize) == hline {
+                    break;
+                } else if relative && self.is_hline(i as usize) && !may_cross {
+                    return None;
+                }
+            }
+        }
+        if i < 0 || i >= length {
+            None
+        } else if i == length - 1 {
+            Some(i as usize - 1)
+        } else {
+            Some(i as usize)
+        }
+    }
+}
+
+/// Where a formula is evaluated.
+struct Context<'a> {
+    table: Table<'a>,
+    /// The line of the field, counted from 0.
+    line: usize,
+    /// The column of the field, counted from 1.
+    column: usize,
+    /// Non-nil means fields are converted with `string-to-number'.
+    numbers: bool,
+    keep_empty: bool,
+    may_cross_hline: bool,
+}
+
+impl<'a> Context<'a> {
+    /// Return the line a row descriptor stands for, like
+    /// `org-table--descriptor-line'.
+    fn descriptor_line(&self, descriptor: &[u8]) -> Option<usize> {
+        if descriptor.is_empty() {
+            return Some(self.line);
+        } else if descriptor.iter().all(u8::is_ascii_digit) {
+            let n = parse_integer(descriptor)?;
+            return if n == 0 {
+                Some(self.line)
+            } else {
+                self.table.data_line(n as usize)
+            };
+        }
+        let sign = |pos: usize| match descriptor.get(pos) {
+            Some(b'+') => Some(false),
+            Some(b'-') => Some(true),
+            _ => None,
+        };
+        let mut pos = 0;
+        let mut hline_backwards = sign(pos);
+        let hlines = descriptor[hline_backwards.map_or(0, |_| 1)..]
+            .iter()
+            .take_while(|&&c| c == b'I')
+            .count();
+        if hlines > 0 {
+            pos += hline_backwards.map_or(0, |_| 1) + hlines;
+        } else {
+            hline_backwards = None;
+        }
+        let line_backwards = sign(pos);
+        let start = pos + line_backwards.map_or(0, |_| 1);
+        let count = descriptor[start..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        let lines = if count > 0 {
+            Some(parse_integer(&descriptor[start..start + count])? as usize)
+        } else {
+            None
+        };
+        if (hlines == 0 && lines.is_none())
+            || (hlines > 0 && lines.is_some() && line_backwards.is_none())
+        {
+            return None;
+        }
+        let mut line = self.line;
+        if hlines > 0 {
+            let mut hlines = hlines;
+            let backwards = match hline_backwards {
+                Some(backwards) => backwards,
+                None => {
+                    line = 0;
+                    if self.table.is_hline(0) {
+                        hlines -= 1;
+                    }
+                    false
+                }
+            };
+            line = self
+                .table
+                .row_type(true, hlines, line, backwards, false, true)?;
+        }
+        if let Some(lines) = lines {
+            let backwards = line_backwards?;
+            line =
+                self.table
+                    .row_type(false, lines, line, backwards, true, self.may_cross_hline)?;
+        }
+        Some(line)
+    }
+
+    /// Return the column a column descriptor such as `$-1' stands for.
+    fn descriptor_column(&self, descriptor: Option<&[u8]>) -> Option<usize> {
+        let descriptor = match descriptor {
+            Some(descriptor) => descriptor,
+            None => return Some(self.column),
+        };
+        let (relative, n) = match descriptor[0] {
+            b'-' => (true, -parse_integer(&descriptor[1..])?),
+            b'+' => (true, parse_integer(&descriptor[1..])?),
+            _ => (false, parse_integer(descriptor)?),
+        };
+        let column = if n == 0 {
+            self.column as i64
+        } else if relative {
+            self.column as i64 + n
+        } else {
+            n
+        };
+        if column < 1 {
+            None
+        } else {
+            Some(column as usize)
+        }
+    }
+
+    /// Return the value of the range from line FROM and column COLUMN to
+    /// line TO and column TO-COLUMN, like `org-table-get-range'.
+    fn range(
+        &self,
+        (from, column): (usize, usize),
+        (to, to_column): (usize, usize),
+        single: bool,
+    ) -> Option<Value> {
+        let rows = &self.table.rows;
+        if single || (from == to && column == to_column) {
+            let line = (from..rows.len()).find(|&i| !self.table.is_hline(i))?;
+            let field = rows[line].as_ref()?.get(column - 1)?;
+            return self.field(field).map(Value::Number);
+        }
+        let first = (std::cmp::min(from, to)..rows.len()).find(|&i| !self.table.is_hline(i))?;
+        let last = (0..=std::cmp::max(from, to))
+            .rev()
+            .find(|&i| !self.table.is_hline(i))?;
+        let columns = std::cmp::min(column, to_column)..=std::cmp::max(column, to_column);
+        let mut fields = Vec::new();
+        for row in rows.get(first..=last)?.iter().filter_map(Option::as_ref) {
+            for i in columns.clone() {
+                fields.push(*row.get(i - 1)?);
+            }
+        }
+        self.vector(&fields).map(Value::Vector)
+    }
+
+    /// Return the value of a single FIELD, like `org-table-make-reference'.
+    fn field(&self, field: &[u8]) -> Option<Number> {
+        if !field.is_empty() {
+            self.element(field)
+        } else if !self.keep_empty || self.numbers {
+            Some(Integer(0))
+        } else {
+            None
+        }
+    }
+
+    /// Return the values of the FIELDS of a range.
+    fn vector(&self, fields: &[&[u8]]) -> Option<Vec<Number>> {
+        fields
+            .iter()
+            .filter(|field| self.keep_empty || !field.is_empty())
+            .map(|field| {
+                if !field.is_empty() {
+                    self.element(field)
+                } else if self.numbers {
+                    Some(Integer(0))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn element(&self, field: &[u8]) -> Option<Number> {
+        if self.numbers {
+            string_to_number(field)
+        } else {
+            match Parser::new(field, None).evaluate()? {
+                Value::Number(n) => Some(n),
+                Value::Vector(_) => None,
+            }
+        }
+    }
+
+    /// Return the fields of the line of the formula.
+    fn current_fields(&self) -> Option<&[&'a [u8]]> {
+        self.table.rows.get(self.line)?.as_ref().map(Vec::as_slice)
+    }
+}
+
+fn parse_integer(text: &[u8]) -> Option<i64> {
+    if text.is_empty() || !text.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    std::str::from_utf8(text).ok()?.parse().ok()
+}
+
+/// The binding powers of a binary operator, as in `math-standard-opers'
+/// with `calc-multiplication-has-precedence'.
+fn binding_powers(operator: u8) -> Option<(i32, i32)> {
+    match operator {
+        b'^' => Some((201, 200)),
+        b'*' => Some((196, 195)),
+        b'/' => Some((190, 191)),
+        b'+' | b'-' => Some((180, 181)),
+        _ => None,
+    }
+}
+
+/// A parser that evaluates a formula as it reads it.  Without a
+/// context, references are not allowed.
+struct Parser<'a, 'b> {
+    text: &'b [u8],
+    pos: usize,
+    context: Option<&'b Context<'a>>,
+}
+
+impl<'a, 'b> Parser<'a, 'b> {
+    fn new(text: &'b [u8], context: Option<&'b Context<'a>>) -> Self {
+        Parser {
+            text,
+            pos: 0,
+            context,
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        while self
+            .text
+            .get(self.pos)
+            .map_or(false, |c| b" \t\n".contains(c))
+        {
+            self.pos += 1;
+        }
+        self.text.get(self.pos).cloned()
+    }
+
+    fn starts_operand(&mut self) -> bool {
+        self.peek()
+            .map_or(false, |c| c.is_ascii_alphanumeric() || b".($@".contains(&c))
+    }
+
+    /// Evaluate the whole text.
+    fn evaluate(mut self) -> Option<Value> {
+        let value = self.expression(0)?;
+        if self.peek().is_some() {
+            None
+        } else {
+            Some(value)
+        }
+    }
+
+    fn expression(&mut self, min_power: i32) -> Option<Value> {
+        let mut left = match self.peek()? {
+            b'-' => {
+                self.pos += 1;
+                Value::Number(number(self.expression(197)?)?.neg()?)
+            }
+            b'+' => {
+                self.pos += 1;
+                self.expression(197)?
+            }
+            _ => self.primary()?,
+        };
+        loop {
+            let (operator, juxtaposed) = match self.peek() {
+                Some(c) if binding_powers(c).is_some() => (c, false),
+                _ if self.starts_operand() => (b'*', true),
+                _ => break,
+            };
+            let (left_power, right_power) = binding_powers(operator)?;
+            if left_power < min_power {
+                break;
+            }
+            if !juxtaposed {
+                self.pos += 1;
+            }
+            let right = number(self.expression(right_power)?)?;
+            let left_number = number(left)?;
+            left = Value::Number(match operator {
+                b'^' => left_number.pow(right),
+                b'*' => left_number.mul(right, PRECISION),
+                b'/' => left_number.div(right, PRECISION),
+                b'+' => left_number.add(right, PRECISION),
+                _ => left_number.sub(right, PRECISION),
+            }?);
+        }
+        Some(left)
+    }
+
+    fn primary(&mut self) -> Option<Value> {
+        match self.peek()? {
+            b'(' => {
+                self.pos += 1;
+                let value = self.expression(0)?;
+                self.expect(b')')?;
+                Some(value)
+            }
+            b'@' => self.range_reference(),
+            b'$' => self.field_reference(),
+            c if c.is_ascii_digit() || c == b'.' => self.number().map(Value::Number),
+            c if c.is_ascii_alphabetic() => self.call().map(Value::Number),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Option<()> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn digit_run(&mut self) -> &'b [u8] {
+        let start = self.pos;
+        while self.text.get(self.pos).map_or(false, u8::is_ascii_digit) {
+            self.pos += 1;
+        }
+        &self.text[start..self.pos]
+    }
+
+    /// Read a number, such as `12', `1.5' or `2e-3'.
+    fn number(&mut self) -> Option<Number> {
+        self.peek();
+        let integer = self.digit_run();
+        let mut fraction = &[][..];
+        let float = self.text.get(self.pos) == Some(&b'.');
+        if float {
+            self.pos += 1;
+            fraction = self.digit_run();
+        }
+        if integer.is_empty() && fraction.is_empty() {
+            return None;
+        }
+        let mut exponent = 0;
+        let start = self.pos;
+        if self.text.get(self.pos) == Some(&b'e') {
+            self.pos += 1;
+            let negative = self.text.get(self.pos) == Some(&b'-');
+            if negative || self.text.get(self.pos) == Some(&b'+') {
+                self.pos += 1;
+            }
+            let digits = self.digit_run();
+            if digits.is_empty() {
+                self.pos = start;
+            } else {
+                exponent = parse_integer(digits).filter(|&e| e < 1_000_000)? as i32;
+                if negative {
+                    exponent = -exponent;
+                }
+            }
+        }
+        if !float && self.pos == start {
+            return parse_integer(integer).map(Integer);
+        }
+        let mut mantissa: i128 = 0;
+        for &digit in integer.iter().chain(fraction).skip_while(|&&c| c == b'0') {
+            mantissa = mantissa
+                .checked_mul(10)?
+                .checked_add(i128::from(digit - b'0'))?;
+        }
+        make_float(mantissa, exponent - fraction.len() as i32, PRECISION)
+    }
+
+    /// Read a call to one of the supported Calc functions.
+    fn call(&mut self) -> Option<Number> {
+        let start = self.pos;
+        while self
+            .text
+            .get(self.pos)
+            .map_or(false, u8::is_ascii_alphanumeric)
+        {
+            self.pos += 1;
+        }
+        let name = &self.text[start..self.pos];
+        if self.text.get(self.pos) != Some(&b'(') {
+            return None;
+        }
+        self.pos += 1;
+        let mut arguments = Vec::new();
+        if self.peek() == Some(b')') {
+            self.pos += 1;
+        } else {
+            loop {
+                arguments.push(self.expression(0)?);
+                match self.peek()? {
+                    b',' => self.pos += 1,
+                    b')' => {
+                        self.pos += 1;
+                        break;
+                    }
+                    _ => return None,
+                }
+            }
+        }
+        call(name, arguments)
+    }
+
+    /// Read a reference starting with `@', which matches
+    /// `org-table-range-regexp'.
+    fn range_reference(&mut self) -> Option<Value> {
+        let context = self.context?;
+        let start = self.pos;
+        self.pos += 1;
+        let from = self.row_descriptor();
+        let column = self.column_descriptor();
+        let single = !self.text[self.pos..].starts_with(b"..");
+        let (to, to_column) = if single {
+            (&[][..], None)
+        } else {
+            self.pos += 2;
+            if self.text.get(self.pos) == Some(&b'@') {
+                self.pos += 1;
+            }
+            (self.row_descriptor(), self.column_descriptor())
+        };
+        if self.pos - start == 1 {
+            return None;
+        }
+        context.range(
+            (
+                context.descriptor_line(from)?,
+                context.descriptor_column(column)?,
+            ),
+            (
+                context.descriptor_line(to)?,
+                context.descriptor_column(to_column)?,
+            ),
+            single,
+        )
+    }
+
+    /// Read the `[-+]?I*[-+]?[0-9]*' of a row descriptor.
+    fn row_descriptor(&mut self) -> &'b [u8] {
+        let start = self.pos;
+        self.skip_sign();
+        while self.text.get(self.pos) == Some(&b'I') {
+            self.pos += 1;
+        }
+        self.skip_sign();
+        self.digit_run();
+        &self.text[start..self.pos]
+    }
+
+    /// Read a column descriptor such as `$-1', and return it without
+    /// the `$'.
+    fn column_descriptor(&mut self) -> Option<&'b [u8]> {
+        let start = self.pos;
+        if self.text.get(self.pos) != Some(&b'$') {
+            return None;
+        }
+        self.pos += 1;
+        self.skip_sign();
+        if self.digit_run().is_empty() {
+            self.pos = start;
+            None
+        } else {
+            Some(&self.text[start + 1..self.pos])
+        }
+    }
+
+    fn skip_sign(&mut self) {
+        if self.text.get(self.pos).map_or(false, |c| b"+-".contains(c)) {
+            self.pos += 1;
+        }
+    }
+
+    /// Read a reference to a field of the current line, such as `$2',
+    /// or a range of them, such as `$1..$3'.
+    fn field_reference(&mut self) -> Option<Value> {
+        let context = self.context?;
+        let fields = context.current_fields()?;
+        let first = self.column_descriptor()?;
+        let index = |descriptor: &[u8]| -> Option<i64> {
+            match descriptor[0] {
+                b'-' => Some(context.column as i64 - parse_integer(&descriptor[1..])?),
+                b'+' => Some(context.column as i64 + parse_integer(&descriptor[1..])?),
+                _ => parse_integer(descriptor),
+            }
+        };
+        if self.text[self.pos..].starts_with(b"..$") {
+            let start = self.pos;
+            self.pos += 2;
+            if let Some(last) = self.column_descriptor() {
+                let (from, to) = (index(first)? - 1, index(last)?);
+                if from < 0 || from > to || to as usize > fields.len() {
+                    return None;
+                }
+                return context
+                    .vector(&fields[from as usize..to as usize])
+                    .map(Value::Vector);
+            }
+            self.pos = start;
+        }
+        let n = match index(first)? {
+            0 => context.column,
+            n if n < 1 => 1,
+            n => n as usize,
+        };
+        context.field(fields.get(n - 1)?).map(Value::Number)
+    }
+}
+
+fn number(value: Value) -> Option<Number> {
+    match value {
+        Value::Number(n) => Some(n),
+        Value::Vector(_) => None,
+    }
+}
+
+/// Call the Calc function NAME with ARGUMENTS.
+fn call(name: &[u8], arguments: Vec<Value>) -> Option<Number> {
+    let scalars = || -> Option<Vec<Number>> { arguments.iter().cloned().map(number).collect() };
+    let mut elements = Vec::new();
+    for argument in &arguments {
+        match argument {
+            Value::Number(n) => elements.push(*n),
+            Value::Vector(v) => elements.extend(v),
+        }
+    }
+    // The statistics functions work with two more digits.
+    let precision = PRECISION + 2;
+    let fold = |f: &dyn Fn(Number, Number) -> Option<Number>, elements: &[Number]| {
+        let (&first, rest) = elements.split_first()?;
+        rest.iter().try_fold(first, |a, &b| f(a, b))
+    };
+    let max = |a: Number, b: Number| Some(if a.compare(b) == Ordering::Less { b } else { a });
+    let min = |a: Number, b: Number| Some(if b.compare(a) == Ordering::Less { b } else { a });
+    let result = match name {
+        b"vsum" if elements.is_empty() => Integer(0),
+        b"vsum" => fold(&|a, b| a.add(b, precision), &elements)?,
+        b"vprod" if elements.is_empty() => Integer(1),
+        b"vprod" => fold(&|a, b| a.mul(b, precision), &elements)?,
+        b"vcount" => Integer(elements.len() as i64),
+        b"vmean" => fold(&|a, b| a.add(b, precision), &elements)?
+            .div(Integer(elements.len() as i64), precision)?,
+        b"vmax" => fold(&max, &elements)?,
+        b"vmin" => fold(&min, &elements)?,
+        b"max" => fold(&max, &scalars()?)?,
+        b"min" => fold(&min, &scalars()?)?,
+        b"abs" | b"round" | b"floor" | b"ceil" => {
+            let n = match scalars()?.as_slice() {
+                [n] => *n,
+                _ => return None,
+            };
+            match name {
+                b"abs" if n.is_negative() => n.neg()?,
+                b"abs" => n,
+                b"round" => n.round()?,
+                b"floor" => n.floor()?,
+                _ => n.ceiling()?,
+            }
+        }
+        _ => return None,
+    };
+    result.normalize(PRECISION)
+}
+
+/// Evaluate FORMULA for the field at LINE and COLUMN of the table
+/// between START and END, or return nil if Calc has to do it.
+/// FORMULA is a Calc formula of `org-table-eval-formula', after the
+/// names, `@<' and `@#' references in it have been replaced.  LINE
+/// counts from 0 at START, and COLUMN from 1.  NUMBERS and KEEP-EMPTY
+/// are the `N' and `E' formula flags.
+///
+/// The formula can contain numbers, the operators `+', `-', `*', `/'
+/// and `^', field and range references, and calls to `vsum', `vmean',
+/// `vmin', `vmax', `vcount', `vprod', `min', `max', `abs', `round',
+/// `floor' and `ceil'.  The fields it refers to must hold numbers.
+/// The result is formatted as Calc does under `org-calc-default-modes'.
+#[lisp_fn(min = "5")]
+pub fn org_table_eval_native(
+    formula: LispObject,
+    mut start: LispObject,
+    mut end: LispObject,
+    line: EmacsInt,
+    column: EmacsInt,
+    numbers: bool,
+    keep_empty: bool,
+) -> LispObject {
+    let formula = formula.as_string_or_error();
+    unsafe { validate_region(&mut start, &mut end) };
+    if line < 0 || column < 1 {
+        return Qnil;
+    }
+    let text = buffer_substring_no_properties(start, end).as_string_or_error();
+    let context = Context {
+        table: Table::parse(text.as_slice()),
+        line: line as usize,
+        column: column as usize,
+        numbers,
+        keep_empty,
+        may_cross_hline: symbol_value(intern("org-table-relative-ref-may-cross-hline")).is_t(),
+    };
+    match Parser::new(formula.as_slice(), Some(&context))
+        .evaluate()
+        .and_then(number)
+    {
+        Some(n) => LispObject::from(format_number(n).as_str()),
+        None => Qnil,
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/orgtable_exports.rs"));
+
+#[cfg(test)]
+fn eval(formula: &str) -> Option<String> {
+    Parser::new(formula.as_bytes(), None)
+        .evaluate()
+        .and_then(number)
+        .map(format_number)
+}
+
+#[test]
+fn test_arithmetic() {
+    assert_eq!(eval("1+2*3"), Some("7".to_string()));
+    assert_eq!(eval("7/2"), Some("3.5".to_string()));
+    assert_eq!(eval("6/3"), Some("2".to_string()));
+    assert_eq!(eval("1/3"), Some("0.33333333".to_string()));
+    assert_eq!(eval("2/3"), Some("0.66666667".to_string()));
+    assert_eq!(eval("0.1+0.2"), Some("0.3".to_string()));
+    assert_eq!(eval("1.5+1.5"), Some("3.".to_string()));
+    assert_eq!(eval("-2^2"), Some("-4".to_string()));
+    assert_eq!(eval("2^3^2"), Some("512".to_string()));
+    assert_eq!(eval("12/2*3"), Some("2".to_string()));
+    assert_eq!(eval("2 (3)"), Some("6".to_string()));
+    assert_eq!(eval("(1+2)*-3"), Some("-9".to_string()));
+    assert_eq!(eval("1e3"), Some("1000.".to_string()));
+    assert_eq!(eval("0*1.5"), Some("0.".to_string()));
+    assert_eq!(eval("1/0"), None);
+    assert_eq!(eval("2^-1"), None);
+    assert_eq!(eval("x+1"), None);
+    assert_eq!(eval("9223372036854775807+1"), None);
+}
+
+#[test]
+fn test_format_number() {
+    assert_eq!(format_number(Float(123_456_789, 0)), "123456790.");
+    assert_eq!(format_number(Float(123_456_789, 10)), "1.2345679e18");
+    assert_eq!(format_number(Float(1, 20)), "1e20");
+    assert_eq!(format_number(Float(15, -3)), "0.015");
+    assert_eq!(format_number(Float(15, -4)), "1.5e-3");
+    assert_eq!(format_number(Float(-25, -1)), "-2.5");
+    assert_eq!(format_number(Float(1, 11)), "100000000000.");
+}
+
+#[test]
+fn test_functions() {
+    assert_eq!(eval("vsum(1, 2.5, 3)"), Some("6.5".to_string()));
+    assert_eq!(eval("vmean(1, 2)"), Some("1.5".to_string()));
+    assert_eq!(eval("vmean(1, 2, 3)"), Some("2".to_string()));
+    assert_eq!(eval("vmax(1, 3.5, 2)"), Some("3.5".to_string()));
+    assert_eq!(eval("vcount(4, 5)"), Some("2".to_string()));
+    assert_eq!(eval("round(2.5)+round(-2.5)"), Some("0".to_string()));
+    assert_eq!(eval("floor(-2.5)"), Some("-3".to_string()));
+    assert_eq!(eval("ceil(2.1)"), Some("3".to_string()));
+    assert_eq!(eval("abs(-1.5)"), Some("1.5".to_string()));
+    assert_eq!(eval("vmean()"), None);
+    assert_eq!(eval("sin(1)"), None);
+}
+
+#[test]
+fn test_string_to_number() {
+    assert_eq!(string_to_number(b" 12abc"), Some(Integer(12)));
+    assert_eq!(string_to_number(b"-1.50"), Some(Float(-15, -1)));
+    assert_eq!(string_to_number(b"5."), Some(Integer(5)));
+    assert_eq!(string_to_number(b"2e3"), Some(Float(2, 3)));
+    assert_eq!(string_to_number(b"abc"), Some(Integer(0)));
+}
+
+#[cfg(test)]
+fn eval_in(table: &str, line: usize, column: usize, formula: &str) -> Option<String> {
+    let context = Context {
+        table: Table::parse(table.as_bytes()),
+        line,
+        column,
+        numbers: false,
+        keep_empty: false,
+        may_cross_hline: true,
+    };
+    Parser::new(formula.as_bytes(), Some(&context))
+        .evaluate()
+        .and_then(number)
+        .map(format_number)
+}
+
+#[test]
+fn test_references() {
+    let table = "| a | b |\n|---+---|\n| 1 | 2 |\n| 3 |   |\n|---+---|\n| 4 | x |\n";
+    assert_eq!(eval_in(table, 2, 2, "$1*10"), Some("10".to_string()));
+    assert_eq!(eval_in(table, 3, 2, "$1+$2"), Some("3".to_string()));
+    assert_eq!(eval_in(table, 2, 1, "vsum($1..$2)"), Some("3".to_string()));
+    assert_eq!(eval_in(table, 5, 2, "vsum(@I..@II)"), Some("2".to_string()));
+    assert_eq!(
+        eval_in(table, 5, 2, "vsum(@I$1..@II$1)"),
+        Some("4".to_string())
+    );
+    assert_eq!(eval_in(table, 5, 1, "@-1+@-2"), Some("4".to_string()));
+    assert_eq!(eval_in(table, 3, 2, "@2$1+@3$1"), Some("4".to_string()));
+    assert_eq!(eval_in(table, 3, 1, "vmean(@2..@>)"), None);
+    assert_eq!(eval_in(table, 5, 1, "$2"), None);
+    assert_eq!(eval_in(table, 5, 1, "$3"), None);
+    assert_eq!(eval_in(table, 0, 1, "@3$1"), Some("3".to_string()));
+}
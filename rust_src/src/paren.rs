@@ -0,0 +1,153 @@
+//! Finding matching parentheses for `show-paren-mode'.
+//!
+//! `show-paren-mode' looks for the parenthesis that matches the one at
+//! point after every command, and scanning a large or badly unbalanced
+//! buffer for it could hold up redisplay for a long time.  This looks
+//! in a small region around the parenthesis first and widens it only
+//! while a time budget allows.
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    editfns::{char_after, char_before},
+    eval::unbind_to,
+    lisp::{defsubr, LispObject},
+    numbers::LispNumber,
+    obarray::intern,
+    remacs_sys::{
+        record_unwind_protect, save_restriction_restore, save_restriction_save, scan_sexps_noerror,
+        specbind, EmacsInt, Fnarrow_to_region,
+    },
+    remacs_sys::{Qnil, Qt},
+    threads::{c_specpdl_index, ThreadState},
+    time::float_time,
+};
+
+/// The number of characters on each side of the parenthesis looked at
+/// first.
+const INITIAL_SCAN_DISTANCE: EmacsInt = 4096;
+
+/// Scan from FROM by COUNT balanced expressions, as `scan-sexps' does,
+/// looking only at the text between BEG and END.  Return the position
+/// found, or None if the expressions do not end between BEG and END.
+fn scan_sexps_within(
+    from: EmacsInt,
+    count: EmacsInt,
+    beg: EmacsInt,
+    end: EmacsInt,
+) -> Option<EmacsInt> {
+    let specpdl = c_specpdl_index();
+    let result = unsafe {
+        record_unwind_protect(Some(save_restriction_restore), save_restriction_save());
+        Fnarrow_to_region(beg.into(), end.into());
+        scan_sexps_noerror(from, count)
+    };
+    unbind_to(specpdl, Qnil);
+    result.as_fixnum()
+}
+
+/// Return the syntax descriptor of the character at POS, as
+/// `syntax-after' does.
+fn syntax_after(pos: EmacsInt) -> LispObject {
+    call!(intern("syntax-after").as_lisp_obj(), pos.into())
+}
+
+/// Return true if the parentheses at BEG and before END are not of the
+/// same kind, as `show-paren-mode' shows them.
+fn parens_mismatch(beg: EmacsInt, end: EmacsInt) -> bool {
+    let opener = syntax_after(beg);
+    // Paired delimiters, such as `$' in TeX, always match.
+    let class = opener.as_cons().and_then(|c| c.car().as_fixnum());
+    if class.map_or(false, |class| class & 0xFFFF == 8) {
+        return false;
+    }
+    let opener_match = opener.as_cons().map_or(Qnil, |c| c.cdr());
+    let closer_match = syntax_after(end - 1).as_cons().map_or(Qnil, |c| c.cdr());
+    let char_at = |c: Option<EmacsInt>| c.map_or(Qnil, LispObject::from);
+
+    !(char_at(char_before(end.into())).eq(opener_match)
+        || char_at(char_after(beg.into())).eq(closer_match)
+        || closer_match.eq(opener_match))
+}
+
+/// Find the parenthesis that matches the one just after or before
+/// OUTSIDE.
+/// If DIRECTION is 1, the character after OUTSIDE should be an open
+/// parenthesis, and its match is looked for after it.  If DIRECTION is
+/// -1, the character before OUTSIDE should be a close parenthesis, and
+/// its match is looked for before it.
+///
+/// Return (POS . MISMATCH) if the matching parenthesis is found: POS is
+/// the position on its outside, as `scan-sexps' would return, and
+/// MISMATCH is non-nil if the two parentheses are not of the same kind.
+/// Return t if there is no matching parenthesis.  Return nil if the
+/// parenthesis found does not lead back to the one at OUTSIDE, which
+/// happens when one of them is escaped or in a comment, or if it was not
+/// found in time.
+///
+/// DISTANCE, if non-nil, is the largest number of characters around
+/// OUTSIDE to look at, like `blink-matching-paren-distance'.
+///
+/// BUDGET, if non-nil, is the number of seconds the search may take.
+/// The parenthesis is looked for in a small region around OUTSIDE
+/// first, which grows until the parenthesis is found or the search
+/// would run out of time, so that pathological buffers do not hold up
+/// redisplay.
+///
+/// `syntax-table' text properties are obeyed, whatever the value of
+/// `parse-sexp-lookup-properties'.
+#[lisp_fn(min = "2")]
+pub fn find_matching_paren_native(
+    outside: EmacsInt,
+    direction: EmacsInt,
+    distance: Option<EmacsInt>,
+    budget: LispObject,
+) -> LispObject {
+    let buffer = ThreadState::current_buffer();
+    let (begv, zv) = (buffer.begv as EmacsInt, buffer.zv as EmacsInt);
+    let whole = (outside - begv).max(zv - outside);
+    let max_distance = distance.map_or(whole, |d| d.max(0).min(whole));
+    let budget = match budget.as_number_coerce_marker() {
+        Some(LispNumber::Fixnum(n)) => Some(n as f64),
+        Some(LispNumber::Float(n)) => Some(n),
+        None => None,
+    };
+    let direction = if direction < 0 { -1 } else { 1 };
+
+    let specpdl = c_specpdl_index();
+    unsafe { specbind(intern("parse-sexp-lookup-properties").as_lisp_obj(), Qt) };
+
+    let start = float_time(Qnil);
+    let mut distance = INITIAL_SCAN_DISTANCE.min(max_distance);
+    let result = loop {
+        let beg = begv.max(outside - distance);
+        let end = zv.min(outside + distance);
+        let attempt = float_time(Qnil);
+
+        if let Some(pos) = scan_sexps_within(outside, direction, beg, end) {
+            if scan_sexps_within(pos, -direction, beg, end) != Some(outside) {
+                break Qnil;
+            }
+            let mismatch = parens_mismatch(pos.min(outside), pos.max(outside));
+            break LispObject::cons(pos.into(), mismatch.into());
+        }
+        if distance >= max_distance {
+            break Qt;
+        }
+
+        // The next region is twice as large, and should take about
+        // twice as long.
+        let now = float_time(Qnil);
+        if budget.map_or(false, |budget| {
+            now - start + 2.0 * (now - attempt) >= budget
+        }) {
+            break Qnil;
+        }
+        distance = (distance * 2).min(max_distance);
+    };
+
+    unbind_to(specpdl, Qnil);
+    result
+}
+
+include!(concat!(env!("OUT_DIR"), "/paren_exports.rs"));
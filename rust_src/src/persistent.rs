@@ -0,0 +1,383 @@
+//! A persistent key-value store for Lisp data.
+//!
+//! Packages like recentf and savehist used to print their whole state
+//! to a file on every exit.  The store keeps the data in an append-only
+//! log instead: each `persistent-store-put' appends one record, and the
+//! log is compacted when most of it is superseded by later records.
+//!
+//! The log starts with `MAGIC', followed by records of the form
+//!
+//!   OP  KEY-LENGTH  KEY  [VALUE-LENGTH  VALUE]
+//!
+//! where OP is `OP_PUT' or `OP_DELETE', the lengths are varints and the
+//! value is encoded by `serialize'.  A truncated record at the end, left
+//! by a crash, is ignored and overwritten by the next append.
+
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    callproc::lisp_file_name_to_path,
+    lisp::{defsubr, LispObject},
+    obarray::intern,
+    remacs_sys::Fexpand_file_name,
+    remacs_sys::{Qnil, Qt},
+    serialize::{decode_object, encode_object, read_varint, write_varint},
+};
+
+const MAGIC: &[u8] = b"EMPS\x01";
+
+const OP_PUT: u8 = 1;
+const OP_DELETE: u8 = 2;
+
+/// Logs smaller than this are never compacted.
+const COMPACT_MIN_SIZE: u64 = 64 * 1024;
+
+lazy_static! {
+    /// The stores used in this session, by file name.
+    static ref STORES: Mutex<HashMap<PathBuf, Store>> = Mutex::new(HashMap::new());
+}
+
+fn record(op: u8, key: &[u8], value: Option<&[u8]>) -> Vec<u8> {
+    let mut out = vec![op];
+    write_varint(&mut out, key.len() as u64);
+    out.extend_from_slice(key);
+    if let Some(value) = value {
+        write_varint(&mut out, value.len() as u64);
+        out.extend_from_slice(value);
+    }
+    out
+}
+
+/// Read a length-prefixed field from DATA at *POS.
+fn read_field<'a>(data: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    let len = read_varint(data, pos)? as usize;
+    let end = pos.checked_add(len)?;
+    let bytes = data.get(*pos..end)?;
+    *pos = end;
+    Some(bytes)
+}
+
+/// Replay the records of the log DATA.  Return the live entries, and the
+/// length of the part of DATA that holds complete records.
+fn parse_log(data: &[u8]) -> io::Result<(HashMap<Vec<u8>, Vec<u8>>, usize)> {
+    let mut entries = HashMap::new();
+    if data.len() < MAGIC.len() && MAGIC.starts_with(data) {
+        return Ok((entries, 0));
+    }
+    if !data.starts_with(MAGIC) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a persistent store file",
+        ));
+    }
+
+    let mut valid_len = MAGIC.len();
+    loop {
+        let mut pos = valid_len;
+        let op = match data.get(pos) {
+            Some(&op) if op == OP_PUT || op == OP_DELETE => op,
+            _ => break,
+        };
+        pos += 1;
+
+        let key = match read_field(data, &mut pos) {
+            Some(key) => key.to_vec(),
+            None => break,
+        };
+        if op == OP_PUT {
+            match read_field(data, &mut pos) {
+                Some(value) => entries.insert(key, value.to_vec()),
+                None => break,
+            };
+        } else {
+            entries.remove(&key);
+        }
+        valid_len = pos;
+    }
+    Ok((entries, valid_len))
+}
+
+struct Store {
+    entries: HashMap<Vec<u8>, Vec<u8>>,
+    /// The length of the file when we last read or wrote it.  If it
+    /// changes, another session wrote to the file, and we read it again.
+    file_len: u64,
+    /// The length of the complete records at the start of the file.
+    valid_len: u64,
+}
+
+impl Store {
+    fn load(path: &Path) -> io::Result<Store> {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        let (entries, valid_len) = parse_log(&data)?;
+        Ok(Store {
+            entries,
+            file_len: data.len() as u64,
+            valid_len: valid_len as u64,
+        })
+    }
+
+    fn append(&mut self, path: &Path, record: &[u8]) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).write(true).open(path)?;
+        if self.file_len != self.valid_len {
+            file.set_len(self.valid_len)?;
+        }
+        file.seek(SeekFrom::Start(self.valid_len))?;
+
+        let mut data = Vec::new();
+        if self.valid_len == 0 {
+            data.extend_from_slice(MAGIC);
+        }
+        data.extend_from_slice(record);
+        file.write_all(&data)?;
+
+        self.valid_len += data.len() as u64;
+        self.file_len = self.valid_len;
+        Ok(())
+    }
+
+    fn put(&mut self, path: &Path, key: Vec<u8>, value: Vec<u8>) -> io::Result<()> {
+        if self.entries.get(&key) == Some(&value) {
+            return Ok(());
+        }
+        self.append(path, &record(OP_PUT, &key, Some(&value)))?;
+        self.entries.insert(key, value);
+        self.maybe_compact(path)
+    }
+
+    fn delete(&mut self, path: &Path, key: &[u8]) -> io::Result<bool> {
+        if !self.entries.contains_key(key) {
+            return Ok(false);
+        }
+        self.append(path, &record(OP_DELETE, key, None))?;
+        self.entries.remove(key);
+        self.maybe_compact(path)?;
+        Ok(true)
+    }
+
+    /// Rewrite the log so that it only holds the live entries.  The new
+    /// log is written to a temporary file first, so that a crash leaves
+    /// either the old or the new log in place.
+    fn compact(&mut self, path: &Path) -> io::Result<()> {
+        let mut data = MAGIC.to_vec();
+        for (key, value) in &self.entries {
+            data.extend(record(OP_PUT, key, Some(value)));
+        }
+
+        let mut temp = path.as_os_str().to_os_string();
+        temp.push(".tmp");
+        fs::write(&temp, &data)?;
+        fs::rename(&temp, path)?;
+
+        self.valid_len = data.len() as u64;
+        self.file_len = self.valid_len;
+        Ok(())
+    }
+
+    fn maybe_compact(&mut self, path: &Path) -> io::Result<()> {
+        if self.file_len < COMPACT_MIN_SIZE {
+            return Ok(());
+        }
+        let live_len: u64 = self
+            .entries
+            .iter()
+            .map(|(key, value)| record(OP_PUT, key, Some(value)).len() as u64)
+            .sum();
+        if self.file_len > 2 * (live_len + MAGIC.len() as u64) {
+            self.compact(path)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Call F with the store in the file at PATH, reading the file first if
+/// it is not loaded yet or was changed by another session.
+fn with_store<T, F>(path: &Path, f: F) -> T
+where
+    F: FnOnce(&mut Store) -> io::Result<T>,
+{
+    let result = {
+        let mut stores = STORES.lock().unwrap();
+        let disk_len = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        let stale = stores
+            .get(path)
+            .map_or(true, |store| store.file_len != disk_len);
+        let loaded = if stale {
+            Store::load(path).map(|store| {
+                stores.insert(path.to_path_buf(), store);
+            })
+        } else {
+            Ok(())
+        };
+        loaded.and_then(|()| f(stores.get_mut(path).unwrap()))
+    };
+
+    // Signal only after the lock is released, since `error!' doesn't
+    // return.
+    match result {
+        Ok(value) => value,
+        Err(err) => error!("Persistent store {}: {}", path.display(), err),
+    }
+}
+
+/// Return the file name of STORE, or of the default store if STORE is nil.
+pub fn store_path(store: LispObject) -> PathBuf {
+    let file = if store.is_nil() {
+        call!(intern("persistent-store-default-file").as_lisp_obj())
+    } else {
+        store
+    };
+    lisp_file_name_to_path(unsafe { Fexpand_file_name(file, Qnil) })
+}
+
+fn store_key(key: LispObject) -> Vec<u8> {
+    key.symbol_or_string_as_string().as_slice().to_vec()
+}
+
+/// Store VALUE under KEY in the store in the file at PATH.
+pub fn put(path: &Path, key: Vec<u8>, value: LispObject) {
+    let mut data = Vec::new();
+    encode_object(value, &mut data);
+    with_store(path, |store| store.put(path, key, data));
+}
+
+/// Remove KEY from the store in the file at PATH, and return true if it
+/// was there.
+pub fn delete(path: &Path, key: &[u8]) -> bool {
+    with_store(path, |store| store.delete(path, key))
+}
+
+/// Return the keys of the store in the file at PATH that start with
+/// PREFIX, without the prefix, with their values.  Values that can't be
+/// decoded are left out.
+pub fn entries_with_prefix(path: &Path, prefix: &[u8]) -> Vec<(Vec<u8>, LispObject)> {
+    let entries: Vec<(Vec<u8>, Vec<u8>)> = with_store(path, |store| {
+        Ok(store
+            .entries
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key[prefix.len()..].to_vec(), value.clone()))
+            .collect())
+    });
+    entries
+        .into_iter()
+        .filter_map(|(key, value)| decode_object(&value).map(|value| (key, value)))
+        .collect()
+}
+
+/// Return the value stored under KEY in the persistent store.
+/// KEY is a string or a symbol.  If KEY is not in the store, return
+/// DEFAULT.
+///
+/// STORE is the file name of the store; nil means the file returned by
+/// `persistent-store-default-file'.
+#[lisp_fn(min = "1")]
+pub fn persistent_store_get(key: LispObject, default: LispObject, store: LispObject) -> LispObject {
+    let key = store_key(key);
+    let path = store_path(store);
+    let data = with_store(&path, |store| Ok(store.entries.get(&key).cloned()));
+    match data {
+        Some(data) => match decode_object(&data) {
+            Some(value) => value,
+            None => error!("Persistent store {}: invalid value", path.display()),
+        },
+        None => default,
+    }
+}
+
+/// Store VALUE under KEY in the persistent store, and return VALUE.
+/// KEY is a string or a symbol.  VALUE is saved right away, and can be
+/// read back with `persistent-store-get' in later sessions.
+///
+/// VALUE can contain numbers, strings, symbols, lists and vectors.  Text
+/// properties of strings are dropped, and objects that can't be read
+/// back in a later session, like buffers, are replaced by nil.
+///
+/// STORE is the file name of the store; nil means the file returned by
+/// `persistent-store-default-file'.
+#[lisp_fn(min = "2")]
+pub fn persistent_store_put(key: LispObject, value: LispObject, store: LispObject) -> LispObject {
+    put(&store_path(store), store_key(key), value);
+    value
+}
+
+/// Remove KEY from the persistent store.
+/// Return t if KEY was in the store, nil otherwise.
+///
+/// STORE is the file name of the store; nil means the file returned by
+/// `persistent-store-default-file'.
+#[lisp_fn(min = "1")]
+pub fn persistent_store_delete(key: LispObject, store: LispObject) -> bool {
+    delete(&store_path(store), &store_key(key))
+}
+
+/// Rewrite the file of the persistent store, dropping superseded records.
+/// This happens automatically when the file grows, so there is usually
+/// no need to call this.
+///
+/// STORE is the file name of the store; nil means the file returned by
+/// `persistent-store-default-file'.
+#[lisp_fn(min = "0")]
+pub fn persistent_store_compact(store: LispObject) -> LispObject {
+    let path = store_path(store);
+    with_store(&path, |store| store.compact(&path));
+    Qt
+}
+
+include!(concat!(env!("OUT_DIR"), "/persistent_exports.rs"));
+
+#[test]
+fn test_parse_log() {
+    let mut data = MAGIC.to_vec();
+    data.extend(record(OP_PUT, b"a", Some(b"1")));
+    data.extend(record(OP_PUT, b"b", Some(b"2")));
+    data.extend(record(OP_PUT, b"a", Some(b"3")));
+    data.extend(record(OP_DELETE, b"b", None));
+    let complete = data.len();
+    data.extend(record(OP_PUT, b"c", Some(b"4")));
+    data.pop();
+
+    let (entries, valid_len) = parse_log(&data).unwrap();
+    assert_eq!(valid_len, complete);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries.get(&b"a"[..]), Some(&b"3".to_vec()));
+
+    assert_eq!(parse_log(b"").unwrap().1, 0);
+    assert_eq!(parse_log(&MAGIC[..2]).unwrap().1, 0);
+    assert!(parse_log(b"(setq recentf-list nil)").is_err());
+}
+
+#[test]
+fn test_store_compact() {
+    use std::env;
+
+    let path = env::temp_dir().join(format!("persistent-test-{}", std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    let mut store = Store::load(&path).unwrap();
+    let value = vec![b'x'; 1024];
+    for _ in 0..100 {
+        store.put(&path, b"key".to_vec(), value.clone()).unwrap();
+        store.put(&path, b"key".to_vec(), Vec::new()).unwrap();
+    }
+    store.put(&path, b"other".to_vec(), b"y".to_vec()).unwrap();
+    assert!(store.delete(&path, b"key").unwrap());
+    assert!(!store.delete(&path, b"key").unwrap());
+    assert!(store.file_len < COMPACT_MIN_SIZE);
+
+    let reloaded = Store::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(reloaded.file_len, store.file_len);
+    assert_eq!(reloaded.entries, store.entries);
+}
@@ -1,18 +1,22 @@
 //! Functions operating on process.
-use libc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use libc::{self, c_char, ptrdiff_t};
 
 use remacs_macros::lisp_fn;
 
 use crate::{
     buffers::{current_buffer, get_buffer, LispBufferOrName, LispBufferRef},
+    fns::concat,
     lisp::defsubr,
     lisp::{ExternalPtr, LispObject},
     lists::{assoc, car, cdr, plist_put},
-    multibyte::LispStringRef,
+    multibyte::{multibyte_chars_in_text, LispStringRef},
+    pty::{get_pty_window_size, set_pty_window_size},
     remacs_sys::{
-        add_process_read_fd, current_thread, delete_read_fd, emacs_get_tty_pgrp,
-        get_process as cget_process, send_process, setup_process_coding_systems, update_status,
-        Fmapcar, STRING_BYTES,
+        add_process_read_fd, current_thread, delete_read_fd, deliver_process_output,
+        emacs_get_tty_pgrp, get_process as cget_process, make_specified_string, send_process,
+        setup_process_coding_systems, update_status, Fmapcar, STRING_BYTES,
     },
     remacs_sys::{pvec_type, EmacsInt, Lisp_Process, Lisp_Type, Vprocess_alist},
     remacs_sys::{
@@ -475,6 +479,283 @@ pub fn waiting_for_user_input_p() -> bool {
     unsafe { (*current_thread).m_waiting_for_user_input_p != 0 }
 }
 
+fn check_window_dimension(n: EmacsInt) -> u16 {
+    // All known platforms store window sizes as 'unsigned short'.
+    if n < 0 || n > EmacsInt::from(u16::max_value()) {
+        args_out_of_range!(
+            LispObject::from(n),
+            LispObject::from(0),
+            LispObject::from(u16::max_value())
+        );
+    }
+    n as u16
+}
+
+/// Tell PROCESS that it has logical window size WIDTH by HEIGHT.
+/// Value is t if PROCESS was successfully told about the window size,
+/// nil otherwise.
+///
+/// For a process running in a pseudo-terminal, the terminal's foreground
+/// process group receives a SIGWINCH signal, so that full-screen programs
+/// redraw themselves for the new size.
+#[lisp_fn]
+pub fn set_process_window_size(process: LispProcessRef, height: EmacsInt, width: EmacsInt) -> bool {
+    let height = check_window_dimension(height);
+    let width = check_window_dimension(width);
+
+    if process.ptype().eq(Qnetwork) || process.infd < 0 {
+        return false;
+    }
+
+    set_pty_window_size(process.infd, height, width).is_ok()
+}
+
+/// Return the logical window size of PROCESS's terminal.
+/// The value is a cons (HEIGHT . WIDTH), or nil if PROCESS does not
+/// communicate through a terminal whose size can be queried.
+/// See also `set-process-window-size'.
+#[lisp_fn]
+pub fn process_window_size(process: LispProcessRef) -> LispObject {
+    if process.ptype().eq(Qnetwork) || process.infd < 0 {
+        return Qnil;
+    }
+
+    get_pty_window_size(process.infd).map_or(Qnil, |(height, width)| {
+        LispObject::cons(LispObject::from(height), LispObject::from(width))
+    })
+}
+
+/// The largest chunk of output passed at once to the filter of a
+/// line-buffered process, unless specified otherwise.
+const DEFAULT_LINE_BUFFERING_MAX: EmacsInt = 64 * 1024;
+
+/// Upper bound on the number of processes whose reading is suspended
+/// until their filters catch up.  Zero means there are none, which lets
+/// `flush_throttled_process_output' return without scanning the
+/// process list.
+static THROTTLED_PROCESSES: AtomicUsize = AtomicUsize::new(0);
+
+/// Return how many bytes at the start of PENDING should be passed to a
+/// line-buffered filter now: all complete lines within the first MAX
+/// bytes, or MAX bytes of a longer line.  At EOF, incomplete lines are
+/// passed too.
+fn deliverable_output_len(pending: &[u8], max: usize, multibyte: bool, eof: bool) -> usize {
+    if eof && pending.len() <= max {
+        return pending.len();
+    }
+
+    let window = &pending[..pending.len().min(max)];
+    if let Some(idx) = window.iter().rposition(|&b| b == b'\n') {
+        return idx + 1;
+    }
+    if pending.len() < max {
+        return 0;
+    }
+
+    // A line longer than MAX; cut it at a character boundary.
+    let mut cut = window.len();
+    if multibyte {
+        while cut > 0 && pending[cut] & 0xc0 == 0x80 {
+            cut -= 1;
+        }
+    }
+    cut
+}
+
+fn make_output_string(bytes: &[u8], multibyte: bool) -> LispObject {
+    unsafe {
+        let nchars = if multibyte {
+            multibyte_chars_in_text(bytes.as_ptr(), bytes.len() as ptrdiff_t)
+        } else {
+            bytes.len() as ptrdiff_t
+        };
+        make_specified_string(
+            bytes.as_ptr() as *const c_char,
+            nchars,
+            bytes.len() as ptrdiff_t,
+            multibyte,
+        )
+    }
+}
+
+impl LispProcessRef {
+    fn line_buffering_max(self) -> usize {
+        if self.line_buffering_max > 0 {
+            self.line_buffering_max as usize
+        } else {
+            usize::max_value()
+        }
+    }
+
+    /// Split off the part of the pending output that should be passed
+    /// to the filter now, and return it.
+    fn take_deliverable_output(&mut self, eof: bool) -> Option<LispObject> {
+        let pending = self.pending_output.as_string()?;
+        let bytes = pending.as_slice();
+        let multibyte = pending.is_multibyte();
+        let len = if self.line_buffering_max > 0 {
+            deliverable_output_len(bytes, self.line_buffering_max(), multibyte, eof)
+        } else {
+            bytes.len()
+        };
+
+        if len == 0 {
+            return None;
+        }
+        self.pending_output = if len < bytes.len() {
+            make_output_string(&bytes[len..], multibyte)
+        } else {
+            Qnil
+        };
+        Some(make_output_string(&bytes[..len], multibyte))
+    }
+
+    /// Whether the pending output holds more than the filter should
+    /// receive at once.
+    fn output_backlogged(self) -> bool {
+        self.pending_output.as_string().map_or(false, |pending| {
+            let bytes = pending.as_slice();
+            bytes.len() >= self.line_buffering_max() || bytes.contains(&b'\n')
+        })
+    }
+
+    /// Whether output from this process is read when it arrives.
+    fn accepts_output(self) -> bool {
+        self.infd >= 0 && !self.command.eq(Qt) && !self.filter.eq(Qt)
+    }
+}
+
+/// Add TEXT, just read from PROCESS, to its pending output, and return
+/// the part of it that should be passed to PROCESS's filter now.  EOF
+/// non-zero means no more output will arrive.
+///
+/// If more output is pending than the filter should receive at once,
+/// stop reading from PROCESS until `flush_throttled_process_output'
+/// has delivered the backlog.
+#[no_mangle]
+pub extern "C" fn accumulate_process_output(
+    process: LispObject,
+    text: LispObject,
+    eof: bool,
+) -> LispObject {
+    let mut process = process.as_process_or_error();
+    let empty = LispObject::from("");
+
+    process.pending_output = if process.pending_output.is_nil() {
+        text
+    } else {
+        concat(&mut [process.pending_output, text])
+    };
+    let output = process.take_deliverable_output(eof).unwrap_or(empty);
+
+    if process.line_buffering_max > 0
+        && process.output_backlogged()
+        && !process.output_throttled()
+        && process.infd >= 0
+    {
+        unsafe { delete_read_fd(process.infd) };
+        process.set_output_throttled(true);
+        THROTTLED_PROCESSES.fetch_add(1, Ordering::Relaxed);
+    }
+
+    output
+}
+
+/// Pass one chunk of pending output from each process whose reading has
+/// been suspended by `accumulate_process_output' to its filter, and
+/// resume reading from those that have caught up.  Return true if any
+/// output was delivered.
+#[no_mangle]
+pub extern "C" fn flush_throttled_process_output() -> bool {
+    if THROTTLED_PROCESSES.load(Ordering::Relaxed) == 0 {
+        return false;
+    }
+
+    // Filters may create and delete processes, so collect the
+    // throttled ones first.
+    let throttled: Vec<LispProcessRef> = unsafe { Vprocess_alist }
+        .iter_cars_safe()
+        .filter_map(|elt| cdr(elt).as_process())
+        .filter(|p| p.output_throttled())
+        .collect();
+
+    let mut delivered = false;
+    let mut still_throttled = 0;
+    for mut process in throttled {
+        // Once PROCESS is closed, its incomplete last line is delivered
+        // as well.
+        let eof = process.infd < 0;
+        if let Some(chunk) = process.take_deliverable_output(eof) {
+            unsafe { deliver_process_output(process.as_mut(), chunk) };
+            delivered = true;
+        }
+
+        if process.output_backlogged() || (eof && process.pending_output.is_not_nil()) {
+            still_throttled += 1;
+        } else {
+            process.set_output_throttled(false);
+            if process.accepts_output() {
+                unsafe { add_process_read_fd(process.infd) };
+            }
+        }
+    }
+
+    THROTTLED_PROCESSES.store(still_throttled, Ordering::Relaxed);
+    delivered
+}
+
+/// Control whether PROCESS passes its output to the filter line by line.
+/// If FLAG is nil, the filter receives output as soon as it is read,
+/// which is the default.  Otherwise, the filter only receives complete
+/// lines, at most FLAG bytes at a time if FLAG is an integer, or
+/// `64 * 1024' bytes if it is t.  A line longer than that is split.
+/// An incomplete last line is passed to the filter when the process
+/// closes its output.
+///
+/// When PROCESS produces output faster than its filter handles it,
+/// Emacs stops reading from PROCESS until the filter has caught up, so
+/// that redisplay and user input are not starved.
+/// Return FLAG.
+#[lisp_fn]
+pub fn set_process_line_buffering(mut process: LispProcessRef, flag: LispObject) -> LispObject {
+    process.line_buffering_max = if flag.is_nil() {
+        0
+    } else if let Some(max) = flag.as_fixnum() {
+        if max <= 0 {
+            args_out_of_range!(
+                flag,
+                LispObject::from(1),
+                LispObject::from(EmacsInt::max_value())
+            );
+        }
+        max as ptrdiff_t
+    } else {
+        DEFAULT_LINE_BUFFERING_MAX as ptrdiff_t
+    };
+    flag
+}
+
+/// Return the line buffering setting of PROCESS.
+/// The value is nil if PROCESS passes output to its filter as soon as it
+/// is read, or the largest number of bytes passed at once otherwise.
+/// See `set-process-line-buffering'.
+#[lisp_fn]
+pub fn process_line_buffering(process: LispProcessRef) -> Option<EmacsInt> {
+    if process.line_buffering_max > 0 {
+        Some(process.line_buffering_max as EmacsInt)
+    } else {
+        None
+    }
+}
+
+/// Return the output of PROCESS not yet passed to its filter, or nil.
+/// This is the incomplete last line of a line-buffered process, plus any
+/// backlog held back while Emacs stopped reading from it.
+#[lisp_fn]
+pub fn process_pending_output(process: LispProcessRef) -> LispObject {
+    process.pending_output
+}
+
 /// Return the value of inherit-coding-system flag for PROCESS. If this flag is
 /// t, `buffer-file-coding-system` of the buffer associated with process will
 /// inherit the coding system used to decode the process output.
@@ -532,3 +813,14 @@ pub fn process_running_child_p(mut process: LispObject) -> LispObject {
 }
 
 include!(concat!(env!("OUT_DIR"), "/process_exports.rs"));
+
+#[test]
+fn test_deliverable_output_len() {
+    assert_eq!(deliverable_output_len(b"ab\ncd", 100, false, false), 3);
+    assert_eq!(deliverable_output_len(b"ab\ncd", 100, false, true), 6);
+    assert_eq!(deliverable_output_len(b"abcd", 100, false, false), 0);
+    assert_eq!(deliverable_output_len(b"a\nb\ncdef", 4, false, false), 4);
+    assert_eq!(deliverable_output_len(b"abcdef\n", 4, false, false), 4);
+    // Don't split the two-byte sequence of \u{e9}.
+    assert_eq!(deliverable_output_len(b"abc\xc3\xa9", 4, true, false), 3);
+}
@@ -0,0 +1,253 @@
+//! Pseudo-terminal support for subprocesses.
+//!
+//! This replaces the PTY_OPEN / PTY_NAME_SPRINTF / PTY_TTY_NAME_SPRINTF
+//! configuration macros with the POSIX `posix_openpt' interface, which
+//! every Unix we support provides.
+
+use std::io;
+
+use libc::{c_char, c_int};
+
+#[cfg(unix)]
+use std::ffi::CStr;
+
+#[cfg(unix)]
+use libc::{c_ushort, winsize};
+
+#[cfg(unix)]
+use crate::remacs_sys::{
+    block_child_signal, emacs_close, setup_pty, sigset_t, unblock_child_signal,
+};
+
+/// Open the master side of a new pseudo-terminal and return its file
+/// descriptor together with the file name of the slave side.
+#[cfg(unix)]
+pub fn open_pty() -> io::Result<(c_int, Vec<u8>)> {
+    let fd = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let close_with_error = |fd: c_int| {
+        let err = io::Error::last_os_error();
+        unsafe { emacs_close(fd) };
+        Err(err)
+    };
+
+    // Set FD's close-on-exec flag.  This is needed even though we asked
+    // posix_openpt for O_CLOEXEC, since POSIX doesn't require support
+    // for that combination.
+    if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } < 0 {
+        return close_with_error(fd);
+    }
+
+    // grantpt may run a setuid helper and wait for it, so keep the
+    // SIGCHLD handler from reaping that child first.
+    let granted = unsafe {
+        let mut oldset: sigset_t = std::mem::zeroed();
+        block_child_signal(&mut oldset);
+        let granted = libc::grantpt(fd) == 0;
+        let err = errno::errno();
+        unblock_child_signal(&oldset);
+        errno::set_errno(err);
+        granted
+    };
+    if !granted || unsafe { libc::unlockpt(fd) } < 0 {
+        return close_with_error(fd);
+    }
+
+    let name = match slave_name(fd) {
+        Ok(name) => name,
+        Err(_) => return close_with_error(fd),
+    };
+
+    // Check to make certain that both sides are available.
+    // This avoids a nasty yet stupid bug in rlogins.
+    let cname = CStr::from_bytes_with_nul(&name).unwrap();
+    if unsafe { libc::access(cname.as_ptr(), libc::R_OK | libc::W_OK) } != 0 {
+        return close_with_error(fd);
+    }
+
+    Ok((fd, name))
+}
+
+/// Return the NUL-terminated file name of the slave side of the
+/// pseudo-terminal whose master is FD.
+#[cfg(target_os = "linux")]
+fn slave_name(fd: c_int) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0 as c_char; 128];
+    let err = unsafe { libc::ptsname_r(fd, buf.as_mut_ptr(), buf.len()) };
+    if err != 0 {
+        return Err(io::Error::from_raw_os_error(err));
+    }
+    Ok(unsafe { CStr::from_ptr(buf.as_ptr()) }
+        .to_bytes_with_nul()
+        .to_vec())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn slave_name(fd: c_int) -> io::Result<Vec<u8>> {
+    // Emacs only allocates ptys from the main thread, so the static
+    // buffer ptsname returns is safe to use here.
+    let name = unsafe { libc::ptsname(fd) };
+    if name.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { CStr::from_ptr(name) }.to_bytes_with_nul().to_vec())
+}
+
+/// Open an available pty, returning a file descriptor.  Store into
+/// PTY_NAME, a buffer of NAME_SIZE bytes, the file name of the terminal
+/// corresponding to the pty.  Return -1 with errno set on failure.
+#[cfg(unix)]
+#[no_mangle]
+pub unsafe extern "C" fn rust_allocate_pty(pty_name: *mut c_char, name_size: usize) -> c_int {
+    match open_pty() {
+        Ok((fd, ref name)) if name.len() <= name_size => {
+            libc::memcpy(
+                pty_name as *mut libc::c_void,
+                name.as_ptr() as *const libc::c_void,
+                name.len(),
+            );
+            setup_pty(fd);
+            fd
+        }
+        Ok((fd, _)) => {
+            emacs_close(fd);
+            errno::set_errno(errno::Errno(libc::ENAMETOOLONG));
+            -1
+        }
+        Err(err) => {
+            errno::set_errno(errno::Errno(err.raw_os_error().unwrap_or(libc::EIO)));
+            -1
+        }
+    }
+}
+
+/// Tell the terminal FD that its logical window size is HEIGHT lines by
+/// WIDTH columns.  The kernel then sends SIGWINCH to the terminal's
+/// foreground process group.
+#[cfg(unix)]
+pub fn set_pty_window_size(fd: c_int, height: c_ushort, width: c_ushort) -> io::Result<()> {
+    let size = winsize {
+        ws_row: height,
+        ws_col: width,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    if unsafe { libc::ioctl(fd, libc::TIOCSWINSZ, &size) } < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Return the logical window size of the terminal FD as (HEIGHT, WIDTH).
+#[cfg(unix)]
+pub fn get_pty_window_size(fd: c_int) -> io::Result<(c_ushort, c_ushort)> {
+    let mut size: winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) } < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok((size.ws_row, size.ws_col))
+    }
+}
+
+#[cfg(not(unix))]
+pub fn set_pty_window_size(_fd: c_int, _height: u16, _width: u16) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "Terminal window sizes are not supported on this system",
+    ))
+}
+
+#[cfg(not(unix))]
+pub fn get_pty_window_size(_fd: c_int) -> io::Result<(u16, u16)> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "Terminal window sizes are not supported on this system",
+    ))
+}
+
+/// Set the logical window size associated with descriptor FD
+/// to HEIGHT and WIDTH.  This is used mainly with ptys.
+/// Return a negative value on failure.
+#[no_mangle]
+pub extern "C" fn set_window_size(fd: c_int, height: c_int, width: c_int) -> c_int {
+    match set_pty_window_size(fd, height as u16, width as u16) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+#[cfg(target_os = "linux")]
+const CDISABLE: libc::cc_t = 0;
+#[cfg(all(unix, not(target_os = "linux")))]
+const CDISABLE: libc::cc_t = 0xff;
+
+#[cfg(target_os = "linux")]
+const TAB3: libc::tcflag_t = libc::TAB3;
+// Darwin and the BSDs call this OXTABS.
+#[cfg(all(unix, not(target_os = "linux")))]
+const TAB3: libc::tcflag_t = 0x4;
+
+/// Set up the terminal at the other end of a pseudo-terminal that we
+/// will be controlling an inferior through.  It should not echo or do
+/// line-editing, since that is done in Emacs.  No padding needed for
+/// insertion into an Emacs buffer.
+///
+/// This runs in the child between fork and exec, so it must not
+/// allocate.
+#[cfg(unix)]
+#[no_mangle]
+pub extern "C" fn child_setup_tty(out: c_int) {
+    let mut s: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(out, &mut s) } < 0 {
+        return;
+    }
+
+    // Enable output postprocessing, but disable mapping NL to CR-NL,
+    // output delays, tab expansion and upcasing on output.
+    s.c_oflag |= libc::OPOST;
+    s.c_oflag &= !libc::ONLCR;
+    #[cfg(target_os = "linux")]
+    {
+        s.c_oflag &=
+            !(libc::NLDLY | libc::CRDLY | libc::TABDLY | libc::BSDLY | libc::VTDLY | libc::FFDLY);
+        s.c_oflag &= !libc::OLCUC;
+        s.c_iflag &= !libc::IUCLC;
+    }
+    s.c_oflag &= !TAB3;
+
+    // Disable echo, enable signals, and don't strip the 8th bit.
+    s.c_lflag &= !libc::ECHO;
+    s.c_lflag |= libc::ISIG;
+    s.c_iflag &= !libc::ISTRIP;
+    s.c_cflag = (s.c_cflag & !libc::CSIZE) | libc::CS8;
+
+    // Disable erase and kill processing.
+    let cdisable = CDISABLE;
+    s.c_cc[libc::VERASE] = cdisable;
+    s.c_cc[libc::VKILL] = cdisable;
+
+    // The QUIT and INTR characters are used in process_send_signal,
+    // so set them to something useful.
+    if s.c_cc[libc::VQUIT] == cdisable {
+        s.c_cc[libc::VQUIT] = b'\\' & 0o37;
+    }
+    if s.c_cc[libc::VINTR] == cdisable {
+        s.c_cc[libc::VINTR] = b'C' & 0o37;
+    }
+
+    // Keep line editing and EOF processing enabled.  Turning ICANON off
+    // would break sending EOF to the subprocess, and it's up to
+    // subprocesses that disable it themselves to deal with long lines.
+    s.c_lflag |= libc::ICANON;
+    s.c_cc[libc::VEOF] = b'D' & 0o37;
+
+    unsafe { libc::tcsetattr(out, libc::TCSADRAIN, &s) };
+}
+
+#[cfg(not(unix))]
+#[no_mangle]
+pub extern "C" fn child_setup_tty(_out: c_int) {}
@@ -0,0 +1,75 @@
+//! Native support for Quail input methods.
+//!
+//! A Quail map is a trie of the translations of an input method: a cons
+//! (TRANSLATION . ALIST) whose ALIST maps each character that can come
+//! next to the map for the longer key.  Parts of big maps are made on
+//! demand: the ALIST, or a map in it, may be a symbol naming a function
+//! that makes it when called with the key and the length of the key
+//! looked up so far, and it is then replaced by what the function
+//! returns.
+//!
+//! Looking up the current key is done several times for every key
+//! typed, so walking the trie is done here instead of in Lisp.
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    data::aref,
+    lisp::{defsubr, LispObject},
+    lists::assq,
+    obarray::intern,
+    remacs_sys::{EmacsInt, Qnil},
+};
+
+/// If OBJECT names a function that makes part of a Quail map, call it
+/// with KEY and LEN and return the result.  Otherwise, return None.
+fn expand(object: LispObject, key: LispObject, len: EmacsInt) -> Option<LispObject> {
+    if object.is_not_nil() && object.is_symbol() {
+        Some(call!(object, key, LispObject::from(len)))
+    } else {
+        None
+    }
+}
+
+/// Return the Quail map in MAP for the first LEN characters of KEY.
+/// The value is nil if no key starting with them has a translation.
+/// If KBD-TRANSLATE is non-nil, each character of KEY is first
+/// translated by `quail-keyboard-translate'.  Parts of MAP that are
+/// made on demand are made and stored into MAP as they are reached.
+/// This is the walk done by `quail-lookup-key', which see.
+#[lisp_fn]
+pub fn quail_lookup_map_native(
+    map: LispObject,
+    key: LispObject,
+    len: EmacsInt,
+    kbd_translate: bool,
+) -> LispObject {
+    let translate = intern("quail-keyboard-translate").as_lisp_obj();
+    let mut map = map;
+    let mut idx = 0;
+    while map.is_not_nil() && idx < len {
+        let mut ch = aref(key, idx);
+        if kbd_translate {
+            ch = call!(translate, ch);
+        }
+        idx += 1;
+        let cons = map.as_cons_or_error();
+        if let Some(alist) = expand(cons.cdr(), key, idx) {
+            cons.check_impure();
+            cons.set_cdr(alist);
+        }
+        map = match assq(ch, cons.cdr()).as_cons() {
+            Some(slot) => {
+                if let Some(next) = expand(slot.cdr(), key, idx) {
+                    slot.check_impure();
+                    slot.set_cdr(next);
+                }
+                slot.cdr()
+            }
+            None => Qnil,
+        };
+    }
+    map
+}
+
+include!(concat!(env!("OUT_DIR"), "/quail_exports.rs"));
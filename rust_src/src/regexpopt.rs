@@ -0,0 +1,376 @@
+//! Optimized regexps matching a list of strings, for `regexp-opt'.
+//!
+//! The strings are sorted, which makes the list an implicit trie: the
+//! strings below any node of the trie form a contiguous run of the
+//! list.  The regexp is produced by walking it, factoring out common
+//! prefixes, and also common suffixes so that branches that end the
+//! same way are merged, with one-character alternatives turned into
+//! character sets.  The output is the same as that of the Lisp
+//! implementation in regexp-opt.el, which font-lock keyword lists with
+//! hundreds of entries spend a long time in at mode startup.
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    lisp::{defsubr, LispObject},
+    multibyte::{unibyte_to_char, write_codepoint, Codepoint, MAX_MULTIBYTE_LENGTH},
+    obarray::intern,
+    shell::make_lisp_string,
+};
+
+/// The characters that `regexp-quote' escapes.
+const SPECIAL_CHARS: &str = "[*.\\?+^$";
+
+const OPEN_SHY: &[Codepoint] = &[
+    b'\\' as Codepoint,
+    b'(' as Codepoint,
+    b'?' as Codepoint,
+    b':' as Codepoint,
+];
+const OPEN_GROUP: &[Codepoint] = &[b'\\' as Codepoint, b'(' as Codepoint];
+const CLOSE_GROUP: &[Codepoint] = &[b'\\' as Codepoint, b')' as Codepoint];
+const ALTERNATIVE: &[Codepoint] = &[b'\\' as Codepoint, b'|' as Codepoint];
+
+fn push_str(out: &mut Vec<Codepoint>, s: &str) {
+    out.extend(s.chars().map(|c| c as Codepoint));
+}
+
+/// Append S to OUT, quoted as by `regexp-quote'.
+fn push_quoted(out: &mut Vec<Codepoint>, s: &[Codepoint]) {
+    for &c in s {
+        if SPECIAL_CHARS
+            .chars()
+            .any(|special| special as Codepoint == c)
+        {
+            out.push(Codepoint::from(b'\\'));
+        }
+        out.push(c);
+    }
+}
+
+/// Append a character set matching the characters in CHARS to OUT, as
+/// `regexp-opt-charset' does.
+fn push_charset(out: &mut Vec<Codepoint>, chars: &[Codepoint]) {
+    let (bracket, caret, dash) = (
+        Codepoint::from(b']'),
+        Codepoint::from(b'^'),
+        Codepoint::from(b'-'),
+    );
+    let mut sorted: Vec<Codepoint> = chars
+        .iter()
+        .cloned()
+        .filter(|&c| c != bracket && c != caret && c != dash)
+        .collect();
+    sorted.sort();
+    sorted.dedup();
+
+    // Runs of more than three consecutive characters become ranges.
+    let mut set = Vec::new();
+    let mut i = 0;
+    while i < sorted.len() {
+        let mut j = i;
+        while j + 1 < sorted.len() && sorted[j + 1] == sorted[j] + 1 {
+            j += 1;
+        }
+        if j > i + 2 {
+            set.extend_from_slice(&[sorted[i], dash, sorted[j]]);
+        } else {
+            set.extend_from_slice(&sorted[i..=j]);
+        }
+        i = j + 1;
+    }
+
+    let has = |c| chars.contains(&c);
+    // A caret must not come first, and a dash must come first or last.
+    if set.is_empty() && !has(bracket) {
+        if has(dash) {
+            out.push(Codepoint::from(b'['));
+            out.push(dash);
+            if has(caret) {
+                out.push(caret);
+            }
+            out.push(bracket);
+        } else {
+            push_str(out, "\\^");
+        }
+        return;
+    }
+    out.push(Codepoint::from(b'['));
+    if has(bracket) {
+        out.push(bracket);
+    }
+    out.extend_from_slice(&set);
+    if has(caret) {
+        out.push(caret);
+    }
+    if has(dash) {
+        out.push(dash);
+    }
+    out.push(bracket);
+}
+
+/// The length of the longest common prefix of A and B.
+fn common_prefix(a: &[Codepoint], b: &[Codepoint]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+/// The length of the longest common suffix of STRINGS.
+fn common_suffix(strings: &[&[Codepoint]]) -> usize {
+    let first = strings[0];
+    strings[1..].iter().fold(first.len(), |n, s| {
+        first
+            .iter()
+            .rev()
+            .zip(s.iter().rev())
+            .take(n)
+            .take_while(|(x, y)| x == y)
+            .count()
+    })
+}
+
+/// Append a regexp matching the sorted, distinct STRINGS to OUT, as
+/// `regexp-opt-group' does.  OPEN is the opening group construct to
+/// put around the regexp, if any.  If LAX, the group is left out when
+/// the regexp is a single character or character set.
+fn push_group(
+    out: &mut Vec<Codepoint>,
+    strings: &[&[Codepoint]],
+    open: Option<&[Codepoint]>,
+    lax: bool,
+) {
+    let open_group = open.unwrap_or(&[]);
+    let close_group = if open.is_some() { CLOSE_GROUP } else { &[] };
+    let (open_charset, close_charset) = if lax {
+        (&[][..], &[][..])
+    } else {
+        (open_group, close_group)
+    };
+
+    match strings {
+        [] => {}
+        [string] => {
+            if string.len() == 1 {
+                out.extend_from_slice(open_charset);
+                push_quoted(out, string);
+                out.extend_from_slice(close_charset);
+            } else {
+                out.extend_from_slice(open_group);
+                push_quoted(out, string);
+                out.extend_from_slice(close_group);
+            }
+        }
+        _ if strings[0].is_empty() => {
+            // The empty string makes the rest optional.
+            out.extend_from_slice(open_charset);
+            push_group(out, &strings[1..], Some(OPEN_SHY), true);
+            out.push(Codepoint::from(b'?'));
+            out.extend_from_slice(close_charset);
+        }
+        _ if strings[0].len() == 1 && strings[1..].iter().any(|s| s.len() == 1) => {
+            // Several one-character strings make a character set.
+            let letters: Vec<Codepoint> = strings
+                .iter()
+                .filter(|s| s.len() == 1)
+                .map(|s| s[0])
+                .collect();
+            let rest: Vec<&[Codepoint]> =
+                strings.iter().cloned().filter(|s| s.len() != 1).collect();
+            if rest.is_empty() {
+                out.extend_from_slice(open_charset);
+                push_charset(out, &letters);
+                out.extend_from_slice(close_charset);
+            } else {
+                // The longer strings come first, so as to match the
+                // longest.
+                out.extend_from_slice(open_group);
+                push_group(out, &rest, None, false);
+                out.extend_from_slice(ALTERNATIVE);
+                push_charset(out, &letters);
+                out.extend_from_slice(close_group);
+            }
+        }
+        _ => {
+            let first = strings[0];
+            let prefix = common_prefix(first, strings[strings.len() - 1]);
+            if prefix > 0 {
+                let suffixes: Vec<&[Codepoint]> = strings.iter().map(|s| &s[prefix..]).collect();
+                out.extend_from_slice(open_group);
+                push_quoted(out, &first[..prefix]);
+                push_group(out, &suffixes, Some(OPEN_SHY), true);
+                out.extend_from_slice(close_group);
+                return;
+            }
+
+            let suffix = common_suffix(strings);
+            if suffix > 0 {
+                let mut prefixes: Vec<&[Codepoint]> =
+                    strings.iter().map(|s| &s[..s.len() - suffix]).collect();
+                prefixes.sort();
+                out.extend_from_slice(open_group);
+                push_group(out, &prefixes, Some(OPEN_SHY), true);
+                push_quoted(out, &first[first.len() - suffix..]);
+                out.extend_from_slice(close_group);
+                return;
+            }
+
+            // Split the strings into those that start like the first
+            // one and the others.
+            let split = strings.iter().take_while(|s| s[0] == first[0]).count();
+            out.extend_from_slice(open_group);
+            push_group(out, &strings[..split], None, false);
+            out.extend_from_slice(ALTERNATIVE);
+            push_group(out, &strings[split..], None, false);
+            out.extend_from_slice(close_group);
+        }
+    }
+}
+
+/// How the regexp is grouped.  See `regexp-opt'.
+#[derive(Clone, Debug, PartialEq)]
+enum Paren {
+    None,
+    Group,
+    Words,
+    Symbols,
+    Explicit(Vec<Codepoint>),
+}
+
+/// Return a regexp matching any of STRINGS, grouped as PAREN says.
+fn regexp_opt_chars(strings: &[Vec<Codepoint>], paren: &Paren) -> Vec<Codepoint> {
+    let mut sorted: Vec<&[Codepoint]> = strings.iter().map(Vec::as_slice).collect();
+    sorted.sort();
+    sorted.dedup();
+
+    let open = match paren {
+        Paren::None => None,
+        Paren::Explicit(open) => Some(open.as_slice()),
+        _ => Some(OPEN_GROUP),
+    };
+    let mut out = Vec::new();
+    match paren {
+        Paren::Words => push_str(&mut out, "\\<"),
+        Paren::Symbols => push_str(&mut out, "\\_<"),
+        _ => {}
+    }
+    push_group(
+        &mut out,
+        &sorted,
+        Some(open.unwrap_or(OPEN_SHY)),
+        open.is_none(),
+    );
+    match paren {
+        Paren::Words => push_str(&mut out, "\\>"),
+        Paren::Symbols => push_str(&mut out, "\\_>"),
+        _ => {}
+    }
+    out
+}
+
+/// Return the characters of the string OBJECT, with raw bytes of a
+/// unibyte string as eight-bit characters.
+fn string_chars(object: LispObject) -> Vec<Codepoint> {
+    let string = object.as_string_or_error();
+    if string.is_multibyte() {
+        string.chars().collect()
+    } else {
+        string.chars().map(unibyte_to_char).collect()
+    }
+}
+
+/// Return a regexp to match a string in the list STRINGS.
+/// This is the native implementation of `regexp-opt', which see for
+/// the meaning of PAREN; it returns the same regexp.
+#[lisp_fn(min = "1")]
+pub fn regexp_opt_native(strings: LispObject, paren: LispObject) -> LispObject {
+    let strings: Vec<Vec<Codepoint>> = strings.iter_cars().map(string_chars).collect();
+    let paren = if paren.is_string() {
+        Paren::Explicit(string_chars(paren))
+    } else if paren.eq(intern("words")) {
+        Paren::Words
+    } else if paren.eq(intern("symbols")) {
+        Paren::Symbols
+    } else if paren.is_not_nil() {
+        Paren::Group
+    } else {
+        Paren::None
+    };
+
+    let regexp = regexp_opt_chars(&strings, &paren);
+    let multibyte = regexp.iter().any(|&c| c > 0x7f);
+    let mut bytes = Vec::with_capacity(regexp.len());
+    let mut buf = [0_u8; MAX_MULTIBYTE_LENGTH];
+    for c in regexp {
+        let len = write_codepoint(&mut buf, c);
+        bytes.extend_from_slice(&buf[..len]);
+    }
+    make_lisp_string(&bytes, multibyte)
+}
+
+include!(concat!(env!("OUT_DIR"), "/regexpopt_exports.rs"));
+
+#[cfg(test)]
+fn regexp_opt(strings: &[&str], paren: Paren) -> String {
+    let strings: Vec<Vec<Codepoint>> = strings
+        .iter()
+        .map(|s| s.chars().map(|c| c as Codepoint).collect())
+        .collect();
+    regexp_opt_chars(&strings, &paren)
+        .into_iter()
+        .map(|c| std::char::from_u32(c).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_prefixes_and_suffixes() {
+    assert_eq!(
+        regexp_opt(&["cat", "car", "cart", "dog"], Paren::None),
+        "\\(?:ca\\(?:rt\\|[rt]\\)\\|dog\\)"
+    );
+    assert_eq!(regexp_opt(&["ad", "d"], Paren::None), "\\(?:a?d\\)");
+    assert_eq!(
+        regexp_opt(&["foo", "foobar"], Paren::Group),
+        "\\(foo\\(?:bar\\)?\\)"
+    );
+    assert_eq!(regexp_opt(&["abc"], Paren::None), "\\(?:abc\\)");
+    assert_eq!(
+        regexp_opt(&["a.b", "a*b"], Paren::None),
+        "\\(?:a\\(?:[*.]b\\)\\)"
+    );
+}
+
+#[test]
+fn test_charsets() {
+    assert_eq!(
+        regexp_opt(&["a", "b", "c", "d", "x"], Paren::None),
+        "[a-dx]"
+    );
+    assert_eq!(
+        regexp_opt(&["a", "b", "ab"], Paren::None),
+        "\\(?:ab\\|[ab]\\)"
+    );
+    assert_eq!(regexp_opt(&["]", "^", "-", "a"], Paren::None), "[]a^-]");
+    assert_eq!(regexp_opt(&["^", "-"], Paren::None), "[-^]");
+    assert_eq!(regexp_opt(&["x", "^"], Paren::None), "[x^]");
+}
+
+#[test]
+fn test_parens() {
+    let keywords = ["defun", "defmacro", "defvar"];
+    assert_eq!(
+        regexp_opt(&keywords, Paren::Words),
+        "\\<\\(def\\(?:macro\\|un\\|var\\)\\)\\>"
+    );
+    assert_eq!(
+        regexp_opt(&keywords, Paren::Symbols),
+        "\\_<\\(def\\(?:macro\\|un\\|var\\)\\)\\_>"
+    );
+    let explicit = Paren::Explicit("\\(?1:".chars().map(|c| c as Codepoint).collect());
+    assert_eq!(regexp_opt(&["a", "b"], explicit), "\\(?1:[ab]\\)");
+    assert_eq!(regexp_opt(&["a", "b"], Paren::None), "[ab]");
+    assert_eq!(regexp_opt(&[], Paren::None), "");
+}
+
+#[test]
+fn test_duplicates_and_empty_string() {
+    assert_eq!(regexp_opt(&["b", "", "b"], Paren::None), "b?");
+}
@@ -0,0 +1,568 @@
+//! Sandboxing of subprocesses.
+//!
+//! `make-process' takes a `:sandbox' plist that limits what the program
+//! can do to the file system and the network.  The restrictions are
+//! prepared in Emacs before it forks, so that the child, which shares
+//! the memory of Emacs after a vfork, only has to make a few system
+//! calls before it execs the program.
+//!
+//! On GNU/Linux, file access is restricted with Landlock, which needs
+//! Linux 5.13 or later, and network access with a seccomp filter that
+//! makes creating Internet sockets fail.  On macOS, the program is run
+//! by sandbox-exec with a profile made from the plist.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use libc::{c_char, c_void};
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    callproc::lisp_file_name_to_path,
+    lisp::{defsubr, LispObject},
+    obarray::intern,
+    remacs_sys::{Fexpand_file_name, Qerror, Qnil, Qt},
+};
+
+#[cfg(target_os = "linux")]
+use std::{ffi::CString, fs, mem, os::unix::ffi::OsStrExt, ptr};
+
+#[cfg(target_os = "linux")]
+use libc::{c_int, c_long, c_ulong, c_ushort};
+
+#[cfg(target_os = "macos")]
+use std::ffi::{CStr, CString};
+
+/// The restrictions asked for by a `:sandbox' plist.
+#[derive(Debug, PartialEq)]
+struct SandboxSpec {
+    /// The directories the program may read, or None for everywhere.
+    read: Option<Vec<PathBuf>>,
+    /// The directories the program may write, or None for everywhere.
+    write: Option<Vec<PathBuf>>,
+    /// Whether the program may use the network.
+    network: bool,
+}
+
+impl Default for SandboxSpec {
+    fn default() -> Self {
+        SandboxSpec {
+            read: None,
+            write: Some(Vec::new()),
+            network: false,
+        }
+    }
+}
+
+/// Return the directories in the list DIRS, or None if DIRS is t.
+fn directories(dirs: LispObject) -> Option<Vec<PathBuf>> {
+    if dirs.eq(Qt) {
+        return None;
+    }
+    let expand = |dir: LispObject| {
+        dir.as_string_or_error();
+        lisp_file_name_to_path(unsafe { Fexpand_file_name(dir, Qnil) })
+    };
+    Some(dirs.iter_cars().map(expand).collect())
+}
+
+impl SandboxSpec {
+    fn from_lisp(plist: LispObject) -> SandboxSpec {
+        let keyword = |name: &str| intern(name).as_lisp_obj();
+        let mut spec = SandboxSpec::default();
+        let mut tail = plist;
+        while let Some(cons) = tail.as_cons() {
+            let (key, rest) = (cons.car(), cons.cdr().as_cons_or_error());
+            if key.eq(keyword(":read")) {
+                spec.read = directories(rest.car());
+            } else if key.eq(keyword(":write")) {
+                spec.write = directories(rest.car());
+            } else if key.eq(keyword(":network")) {
+                spec.network = rest.car().is_not_nil();
+            } else {
+                xsignal!(Qerror, LispObject::from("Unknown sandbox keyword"), key);
+            }
+            tail = rest.cdr();
+        }
+        spec
+    }
+}
+
+/// Landlock access rights, from linux/landlock.h.
+#[cfg(target_os = "linux")]
+mod access {
+    pub const EXECUTE: u64 = 1 << 0;
+    pub const WRITE_FILE: u64 = 1 << 1;
+    pub const READ_FILE: u64 = 1 << 2;
+    pub const READ_DIR: u64 = 1 << 3;
+    pub const REMOVE_DIR: u64 = 1 << 4;
+    pub const REMOVE_FILE: u64 = 1 << 5;
+    pub const MAKE_CHAR: u64 = 1 << 6;
+    pub const MAKE_DIR: u64 = 1 << 7;
+    pub const MAKE_REG: u64 = 1 << 8;
+    pub const MAKE_SOCK: u64 = 1 << 9;
+    pub const MAKE_FIFO: u64 = 1 << 10;
+    pub const MAKE_BLOCK: u64 = 1 << 11;
+    pub const MAKE_SYM: u64 = 1 << 12;
+
+    pub const READ: u64 = EXECUTE | READ_FILE | READ_DIR;
+    pub const WRITE: u64 = WRITE_FILE
+        | REMOVE_DIR
+        | REMOVE_FILE
+        | MAKE_CHAR
+        | MAKE_DIR
+        | MAKE_REG
+        | MAKE_SOCK
+        | MAKE_FIFO
+        | MAKE_BLOCK
+        | MAKE_SYM;
+    /// The rights that can be granted on a file that is not a directory.
+    pub const FILE: u64 = EXECUTE | WRITE_FILE | READ_FILE;
+}
+
+#[cfg(target_os = "linux")]
+const SYS_LANDLOCK_CREATE_RULESET: c_long = 444;
+#[cfg(target_os = "linux")]
+const SYS_LANDLOCK_ADD_RULE: c_long = 445;
+#[cfg(target_os = "linux")]
+const SYS_LANDLOCK_RESTRICT_SELF: c_long = 446;
+#[cfg(target_os = "linux")]
+const LANDLOCK_CREATE_RULESET_VERSION: c_ulong = 1 << 0;
+#[cfg(target_os = "linux")]
+const LANDLOCK_RULE_PATH_BENEATH: c_int = 1;
+
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct LandlockRulesetAttr {
+    handled_access_fs: u64,
+}
+
+#[cfg(target_os = "linux")]
+#[repr(C, packed)]
+struct LandlockPathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+/// Return the file access rights that SPEC restricts, and the rights
+/// it grants beneath each file, as (HANDLED, RULES).  Many programs
+/// write to /dev/null, so it is always allowed.
+#[cfg(target_os = "linux")]
+fn landlock_rules(spec: &SandboxSpec) -> (u64, Vec<(&Path, u64)>) {
+    let mut handled = 0;
+    if spec.read.is_some() {
+        handled |= access::READ;
+    }
+    if spec.write.is_some() {
+        handled |= access::WRITE;
+    }
+    let mut rules = Vec::new();
+    if handled == 0 {
+        return (handled, rules);
+    }
+    for dir in spec.read.iter().flatten() {
+        rules.push((dir.as_path(), access::READ));
+    }
+    for dir in spec.write.iter().flatten() {
+        rules.push((dir.as_path(), (access::READ | access::WRITE) & handled));
+    }
+    rules.push((
+        Path::new("/dev/null"),
+        (access::READ_FILE | access::WRITE_FILE) & handled,
+    ));
+    (handled, rules)
+}
+
+/// Allow RIGHTS beneath PATH in the Landlock ruleset RULESET.
+#[cfg(target_os = "linux")]
+fn add_landlock_rule(ruleset: c_int, path: &Path, rights: u64) -> io::Result<()> {
+    let with_path = |e: io::Error| io::Error::new(e.kind(), format!("{}: {}", path.display(), e));
+    let name = CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| with_path(io::Error::from(io::ErrorKind::InvalidInput)))?;
+    let is_dir = fs::metadata(path).map_err(with_path)?.is_dir();
+    let parent = unsafe { libc::open(name.as_ptr(), libc::O_PATH | libc::O_CLOEXEC) };
+    if parent < 0 {
+        return Err(with_path(io::Error::last_os_error()));
+    }
+    let attr = LandlockPathBeneathAttr {
+        allowed_access: if is_dir {
+            rights
+        } else {
+            rights & access::FILE
+        },
+        parent_fd: parent,
+    };
+    let result = unsafe {
+        libc::syscall(
+            SYS_LANDLOCK_ADD_RULE,
+            ruleset,
+            LANDLOCK_RULE_PATH_BENEATH,
+            &attr as *const LandlockPathBeneathAttr,
+            0,
+        )
+    };
+    let err = io::Error::last_os_error();
+    unsafe { libc::close(parent) };
+    if result < 0 {
+        Err(with_path(err))
+    } else {
+        Ok(())
+    }
+}
+
+/// Return a Landlock ruleset that enforces the file restrictions of
+/// SPEC, or -1 if it has none.
+#[cfg(target_os = "linux")]
+fn landlock_ruleset(spec: &SandboxSpec) -> io::Result<c_int> {
+    let (handled, rules) = landlock_rules(spec);
+    if handled == 0 {
+        return Ok(-1);
+    }
+    let attr = LandlockRulesetAttr {
+        handled_access_fs: handled,
+    };
+    let ruleset = unsafe {
+        libc::syscall(
+            SYS_LANDLOCK_CREATE_RULESET,
+            &attr as *const LandlockRulesetAttr,
+            mem::size_of::<LandlockRulesetAttr>(),
+            0,
+        )
+    } as c_int;
+    if ruleset < 0 {
+        let err = io::Error::last_os_error();
+        return Err(match err.raw_os_error() {
+            Some(libc::ENOSYS) | Some(libc::EOPNOTSUPP) => io::Error::new(
+                io::ErrorKind::Other,
+                "Landlock is not available in this kernel",
+            ),
+            _ => err,
+        });
+    }
+    for (path, rights) in rules {
+        if let Err(e) = add_landlock_rule(ruleset, path, rights) {
+            unsafe { libc::close(ruleset) };
+            return Err(e);
+        }
+    }
+    Ok(ruleset)
+}
+
+/// An instruction of a seccomp filter, a `struct sock_filter'.
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SockFilter {
+    code: u16,
+    jt: u8,
+    jf: u8,
+    k: u32,
+}
+
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct SockFprog {
+    len: c_ushort,
+    filter: *const SockFilter,
+}
+
+#[cfg(target_os = "linux")]
+const SECCOMP_MODE_FILTER: c_ulong = 2;
+
+#[cfg(target_os = "linux")]
+mod bpf {
+    pub const LD_W_ABS: u16 = 0x20;
+    pub const JEQ_K: u16 = 0x15;
+    pub const JGE_K: u16 = 0x35;
+    pub const RET_K: u16 = 0x06;
+
+    pub const RET_KILL_PROCESS: u32 = 0x8000_0000;
+    pub const RET_ERRNO: u32 = 0x0005_0000;
+    pub const RET_ALLOW: u32 = 0x7fff_0000;
+
+    /// Offsets in `struct seccomp_data'.  Only the low half of the
+    /// first argument is looked at, which comes first on little-endian
+    /// machines.
+    pub const NR: u32 = 0;
+    pub const ARCH: u32 = 4;
+    pub const ARG0: u32 = 16;
+
+    /// System calls of the x32 ABI have this bit set on x86-64.
+    pub const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+const AUDIT_ARCH: Option<u32> = Some(0xc000_003e);
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+const AUDIT_ARCH: Option<u32> = Some(0xc000_00b7);
+#[cfg(all(
+    target_os = "linux",
+    not(any(target_arch = "x86_64", target_arch = "aarch64"))
+))]
+const AUDIT_ARCH: Option<u32> = None;
+
+/// Return a seccomp filter that makes creating IPv4 and IPv6 sockets
+/// fail with EACCES, and kills programs that make system calls of
+/// another architecture.  Unix domain sockets are still allowed.
+#[cfg(target_os = "linux")]
+fn network_filter() -> io::Result<Vec<SockFilter>> {
+    let arch = AUDIT_ARCH.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::Other,
+            "network restrictions are not supported on this architecture",
+        )
+    })?;
+    let stmt = |code, k| SockFilter {
+        code,
+        jt: 0,
+        jf: 0,
+        k,
+    };
+    let jump = |code, k, jt, jf| SockFilter { code, jt, jf, k };
+    Ok(vec![
+        /* 0 */ stmt(bpf::LD_W_ABS, bpf::ARCH),
+        /* 1 */ jump(bpf::JEQ_K, arch, 0, 8),
+        /* 2 */ stmt(bpf::LD_W_ABS, bpf::NR),
+        /* 3 */ jump(bpf::JGE_K, bpf::X32_SYSCALL_BIT, 6, 0),
+        /* 4 */ jump(bpf::JEQ_K, libc::SYS_socket as u32, 0, 3),
+        /* 5 */ stmt(bpf::LD_W_ABS, bpf::ARG0),
+        /* 6 */ jump(bpf::JEQ_K, libc::AF_INET as u32, 2, 0),
+        /* 7 */ jump(bpf::JEQ_K, libc::AF_INET6 as u32, 1, 0),
+        /* 8 */ stmt(bpf::RET_K, bpf::RET_ALLOW),
+        /* 9 */ stmt(bpf::RET_K, bpf::RET_ERRNO | libc::EACCES as u32),
+        /* 10 */ stmt(bpf::RET_K, bpf::RET_KILL_PROCESS),
+    ])
+}
+
+/// The sandbox-exec program of macOS.
+#[cfg(target_os = "macos")]
+const SANDBOX_EXEC: &str = "/usr/bin/sandbox-exec";
+
+/// Return the sandbox-exec profile that enforces SPEC.
+#[cfg(any(target_os = "macos", test))]
+fn darwin_profile(spec: &SandboxSpec) -> String {
+    let rule = |action: &str, dirs: &[PathBuf]| {
+        let mut rule = format!("(allow {} (literal \"/dev/null\")", action);
+        for dir in dirs {
+            let name = dir.to_string_lossy();
+            let name = name.replace('\\', "\\\\").replace('"', "\\\"");
+            rule.push_str(&format!(" (subpath \"{}\")", name));
+        }
+        rule.push_str(")\n");
+        rule
+    };
+
+    let mut profile = String::from("(version 1)\n(allow default)\n");
+    if let Some(ref read) = spec.read {
+        profile.push_str("(deny file-read*)\n");
+        profile.push_str(&rule("file-read*", read));
+    }
+    if let Some(ref write) = spec.write {
+        profile.push_str("(deny file-write*)\n");
+        profile.push_str(&rule("file-write*", write));
+    }
+    if !spec.network {
+        profile.push_str("(deny network-outbound (remote ip))\n");
+        profile.push_str("(deny network-inbound (local ip))\n");
+    }
+    profile
+}
+
+/// The restrictions prepared for a subprocess by `sandbox_prepare'.
+pub struct Sandbox {
+    /// The Landlock ruleset to enforce, or -1.
+    #[cfg(target_os = "linux")]
+    ruleset: c_int,
+    /// The seccomp filter to install, or an empty one.
+    #[cfg(target_os = "linux")]
+    filter: Vec<SockFilter>,
+    /// The arguments of the sandbox-exec command that runs the program,
+    /// and the null-terminated vector of pointers to them.
+    #[cfg(target_os = "macos")]
+    argv: (Vec<CString>, Vec<*mut c_char>),
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        if self.ruleset >= 0 {
+            unsafe { libc::close(self.ruleset) };
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn prepare(spec: &SandboxSpec, _argv: *mut *mut *mut c_char) -> io::Result<Sandbox> {
+    let filter = if spec.network {
+        Vec::new()
+    } else {
+        network_filter()?
+    };
+    let ruleset = landlock_ruleset(spec)?;
+    Ok(Sandbox { ruleset, filter })
+}
+
+#[cfg(target_os = "macos")]
+fn prepare(spec: &SandboxSpec, argv: *mut *mut *mut c_char) -> io::Result<Sandbox> {
+    let invalid = |_| io::Error::from(io::ErrorKind::InvalidInput);
+    let mut args = vec![
+        CString::new(SANDBOX_EXEC).unwrap(),
+        CString::new("-p").unwrap(),
+        CString::new(darwin_profile(spec)).map_err(invalid)?,
+    ];
+    unsafe {
+        let mut arg = *argv;
+        while !(*arg).is_null() {
+            args.push(CStr::from_ptr(*arg).to_owned());
+            arg = arg.offset(1);
+        }
+    }
+    let mut pointers: Vec<*mut c_char> = args.iter().map(|a| a.as_ptr() as *mut c_char).collect();
+    pointers.push(std::ptr::null_mut());
+    unsafe { *argv = pointers.as_mut_ptr() };
+    Ok(Sandbox {
+        argv: (args, pointers),
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn prepare(_spec: &SandboxSpec, _argv: *mut *mut *mut c_char) -> io::Result<Sandbox> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "sandboxing is not supported on this system",
+    ))
+}
+
+/// Prepare the restrictions of the `:sandbox' plist SPEC for a program
+/// whose null-terminated argument vector is *ARGV.  *ARGV may be
+/// replaced by one that runs the program in a sandbox, which stays
+/// valid until the result is freed with `sandbox_free'.  Signal an
+/// error if the restrictions can't be enforced.
+#[no_mangle]
+pub extern "C" fn sandbox_prepare(spec: LispObject, argv: *mut *mut *mut c_char) -> *mut Sandbox {
+    let spec = SandboxSpec::from_lisp(spec);
+    match prepare(&spec, argv) {
+        Ok(sandbox) => Box::into_raw(Box::new(sandbox)),
+        Err(e) => error!("Can't sandbox the process: {}", e),
+    }
+}
+
+/// Enforce the restrictions of SANDBOX on the current process, which is
+/// a child about to exec its program.  Only system calls are made, as
+/// the child may share the memory of Emacs.  Return false on failure,
+/// with errno set.
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn sandbox_enter(sandbox: *const Sandbox) -> bool {
+    let sandbox = &*sandbox;
+    if libc::prctl(
+        libc::PR_SET_NO_NEW_PRIVS,
+        1 as c_ulong,
+        0 as c_ulong,
+        0 as c_ulong,
+        0 as c_ulong,
+    ) != 0
+    {
+        return false;
+    }
+    if sandbox.ruleset >= 0 && libc::syscall(SYS_LANDLOCK_RESTRICT_SELF, sandbox.ruleset, 0) != 0 {
+        return false;
+    }
+    if !sandbox.filter.is_empty() {
+        let program = SockFprog {
+            len: sandbox.filter.len() as c_ushort,
+            filter: sandbox.filter.as_ptr(),
+        };
+        let program: *const SockFprog = &program;
+        if libc::prctl(libc::PR_SET_SECCOMP, SECCOMP_MODE_FILTER, program) != 0 {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(not(target_os = "linux"))]
+#[no_mangle]
+pub unsafe extern "C" fn sandbox_enter(_sandbox: *const Sandbox) -> bool {
+    true
+}
+
+/// Free SANDBOX, a result of `sandbox_prepare'.
+#[no_mangle]
+pub unsafe extern "C" fn sandbox_free(sandbox: *mut c_void) {
+    drop(Box::from_raw(sandbox as *mut Sandbox));
+}
+
+#[cfg(target_os = "linux")]
+fn supported() -> bool {
+    // Ask for the version of the Landlock ABI.
+    let abi = unsafe {
+        libc::syscall(
+            SYS_LANDLOCK_CREATE_RULESET,
+            ptr::null::<LandlockRulesetAttr>(),
+            0,
+            LANDLOCK_CREATE_RULESET_VERSION,
+        )
+    };
+    abi >= 1 && AUDIT_ARCH.is_some()
+}
+
+#[cfg(target_os = "macos")]
+fn supported() -> bool {
+    Path::new(SANDBOX_EXEC).exists()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn supported() -> bool {
+    false
+}
+
+/// Return t if `make-process' can run programs in a sandbox.
+/// See the `:sandbox' argument of `make-process'.
+#[lisp_fn]
+pub fn process_sandbox_supported_p() -> bool {
+    supported()
+}
+
+include!(concat!(env!("OUT_DIR"), "/sandbox_exports.rs"));
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_landlock_rules() {
+    let spec = SandboxSpec {
+        read: None,
+        write: Some(vec![PathBuf::from("/tmp")]),
+        network: true,
+    };
+    let (handled, rules) = landlock_rules(&spec);
+    assert_eq!(handled, access::WRITE);
+    assert_eq!(rules[0], (Path::new("/tmp"), access::WRITE));
+    assert_eq!(rules[1], (Path::new("/dev/null"), access::WRITE_FILE));
+
+    let spec = SandboxSpec {
+        read: None,
+        write: None,
+        network: false,
+    };
+    assert_eq!(landlock_rules(&spec), (0, Vec::new()));
+}
+
+#[test]
+fn test_darwin_profile() {
+    let spec = SandboxSpec {
+        read: Some(vec![PathBuf::from("/usr"), PathBuf::from("/my \"dir\"")]),
+        ..SandboxSpec::default()
+    };
+    assert_eq!(
+        darwin_profile(&spec),
+        "(version 1)\n(allow default)\n\
+         (deny file-read*)\n\
+         (allow file-read* (literal \"/dev/null\") (subpath \"/usr\") \
+         (subpath \"/my \\\"dir\\\"\"))\n\
+         (deny file-write*)\n\
+         (allow file-write* (literal \"/dev/null\"))\n\
+         (deny network-outbound (remote ip))\n\
+         (deny network-inbound (local ip))\n"
+    );
+}
@@ -1,11 +1,27 @@
 //! String search routines
 
+use std::mem;
+
+use libc::{c_uint, c_void, ptrdiff_t};
+
 use remacs_macros::lisp_fn;
 
 use crate::{
+    buffers::{validate_region, LispBufferRef},
+    editfns::save_excursion_save,
+    eval::unbind_to,
     lisp::defsubr,
     lisp::LispObject,
-    remacs_sys::{looking_at_1, match_limit, search_command, string_match_1},
+    lists::{list, LispCons},
+    marker::{set_marker, unchain_marker},
+    multibyte::LispStringRef,
+    remacs_sys::{
+        looking_at_1, record_unwind_protect, record_unwind_save_match_data, regoff_t,
+        running_asynch_code, save_excursion_restore, save_search_regs, search_command, set_point,
+        string_match_1, xrealloc, EmacsInt, EmacsUint, Fmake_marker,
+    },
+    remacs_sys::{Qnil, Qt},
+    threads::{c_specpdl_index, ThreadState},
 };
 
 /// Return t if text after point matches regular expression REGEXP.
@@ -223,6 +239,258 @@ pub fn posix_search_forward(
     unsafe { search_command(regexp, bound, noerror, count, 1, 1, true) }
 }
 
+/// Return the ranges of the matches for REGEXP between START and END.
+/// The value is a list of conses (BEG . END), in buffer order, of the
+/// non-overlapping matches in the current buffer that lie entirely
+/// between START and END.  Empty matches are skipped.
+///
+/// This finds all the matches in the visible part of a window in one
+/// call, for lazy highlighting and `hi-lock' to place their overlays.
+///
+/// If LIMIT is non-nil, return at most LIMIT matches.
+/// If PREDICATE is non-nil, it is called with the beginning and end of
+/// each match, and only the matches for which it returns non-nil are
+/// included, like `isearch-filter-predicate'.
+///
+/// Matching ignores case if `case-fold-search' is non-nil.  Point and
+/// the match data are preserved.
+#[lisp_fn(min = "3")]
+pub fn regexp_match_ranges(
+    regexp: LispStringRef,
+    mut start: LispObject,
+    mut end: LispObject,
+    limit: Option<EmacsUint>,
+    predicate: LispObject,
+) -> LispObject {
+    unsafe { validate_region(&mut start, &mut end) };
+    let end = end.as_fixnum_or_error() as ptrdiff_t;
+    let limit = limit.map_or(usize::max_value(), |limit| limit as usize);
+
+    // Restoring the match data unwinds the excursion too.
+    let _match_data = SaveMatchData::save();
+    unsafe { record_unwind_protect(Some(save_excursion_restore), save_excursion_save()) };
+
+    let mut ranges = Vec::new();
+    let mut pos = start.as_fixnum_or_error() as ptrdiff_t;
+    while ranges.len() < limit && pos <= end {
+        unsafe { set_point(pos) };
+        if re_search_forward(regexp.into(), LispObject::from(end), Qt, Qnil).is_nil() {
+            break;
+        }
+        let (beg, finish) = match MatchData::register(0) {
+            Some(range) => range,
+            None => break,
+        };
+        if beg == finish {
+            pos = finish + 1;
+            continue;
+        }
+        pos = finish;
+        if predicate.is_nil()
+            || call!(predicate, LispObject::from(beg), LispObject::from(finish)).is_not_nil()
+        {
+            ranges.push(LispObject::cons(
+                LispObject::from(beg),
+                LispObject::from(finish),
+            ));
+        }
+    }
+    list(&ranges)
+}
+
+/// Where the match data of the last successful search came from.
+#[derive(Clone, Copy)]
+pub enum MatchSource {
+    /// No search succeeded yet.
+    Nothing,
+    /// A string was matched, or the data was set from a list of
+    /// integers.
+    String,
+    /// The text of a buffer was searched.
+    Buffer(LispBufferRef),
+    /// The data was set from a list whose markers, or last element,
+    /// name a buffer.  Such a list need not describe the text of that
+    /// buffer: `match-substitute-replacement', for one, applies the
+    /// match data of a buffer search to a string.
+    List(LispBufferRef),
+}
+
+impl MatchSource {
+    /// Return the source of the current thread's match data.
+    pub fn current() -> Self {
+        Self::from(ThreadState::current_thread().m_last_thing_searched)
+    }
+
+    /// Return the representation of this source in `last_thing_searched':
+    /// a list of the buffer for data set from a list.
+    pub fn to_lisp(self) -> LispObject {
+        match self {
+            MatchSource::Nothing => Qnil,
+            MatchSource::String => Qt,
+            MatchSource::Buffer(buffer) => buffer.as_lisp_obj(),
+            MatchSource::List(buffer) => list(&[buffer.as_lisp_obj()]),
+        }
+    }
+
+    /// Return the buffer whose positions the match data holds, if any.
+    pub fn buffer(self) -> Option<LispBufferRef> {
+        match self {
+            MatchSource::Buffer(buffer) | MatchSource::List(buffer) => Some(buffer),
+            MatchSource::Nothing | MatchSource::String => None,
+        }
+    }
+}
+
+impl From<LispObject> for MatchSource {
+    fn from(object: LispObject) -> Self {
+        if let Some(buffer) = object.as_buffer() {
+            MatchSource::Buffer(buffer)
+        } else if let Some(buffer) = object.as_cons().and_then(|c| c.car().as_buffer()) {
+            MatchSource::List(buffer)
+        } else if object.is_nil() {
+            MatchSource::Nothing
+        } else {
+            MatchSource::String
+        }
+    }
+}
+
+/// The match data: the positions matched by the last successful search
+/// and its subexpressions, and what was searched.
+///
+/// The regex engine fills the registers of the current thread in
+/// place, so this is a copy of them which can be examined and
+/// installed back.
+pub struct MatchData {
+    /// The start and end of each subexpression, or None if it did not
+    /// match.
+    pub registers: Vec<Option<(ptrdiff_t, ptrdiff_t)>>,
+    pub source: MatchSource,
+}
+
+impl MatchData {
+    /// Return the number of registers of the current thread, which is
+    /// zero if no search succeeded.
+    pub fn num_registers() -> usize {
+        ThreadState::current_thread().m_search_regs.num_regs as usize
+    }
+
+    /// Return the start and end of register N of the current thread, or
+    /// None if the subexpression did not match.
+    pub fn register(n: usize) -> Option<(ptrdiff_t, ptrdiff_t)> {
+        let thread = ThreadState::current_thread();
+        let regs = &thread.m_search_regs;
+        if n >= regs.num_regs as usize {
+            return None;
+        }
+        let (start, end) = unsafe { (*regs.start.add(n), *regs.end.add(n)) };
+        if start < 0 {
+            None
+        } else {
+            Some((start as ptrdiff_t, end as ptrdiff_t))
+        }
+    }
+
+    /// Return a copy of the match data of the current thread.
+    pub fn current() -> Self {
+        Self {
+            registers: (0..Self::num_registers()).map(Self::register).collect(),
+            source: MatchSource::current(),
+        }
+    }
+
+    /// Make this the match data of the current thread.  Registers the
+    /// thread has beyond ours are marked as not matched.
+    pub fn install(&self) {
+        let mut thread = ThreadState::current_thread();
+        let regs = &mut thread.m_search_regs;
+        if self.registers.len() > regs.num_regs as usize {
+            let size = self.registers.len() * mem::size_of::<regoff_t>();
+            unsafe {
+                regs.start = xrealloc(regs.start as *mut c_void, size) as *mut regoff_t;
+                regs.end = xrealloc(regs.end as *mut c_void, size) as *mut regoff_t;
+            }
+            regs.num_regs = self.registers.len() as c_uint;
+        }
+        for i in 0..regs.num_regs as usize {
+            let (start, end) = self.registers.get(i).and_then(|r| *r).unwrap_or((-1, -1));
+            unsafe {
+                *regs.start.add(i) = start as regoff_t;
+                *regs.end.add(i) = end as regoff_t;
+            }
+        }
+        thread.m_last_thing_searched = self.source.to_lisp();
+    }
+}
+
+/// Saves the match data, and restores it when dropped, like
+/// `save-match-data'.  The data is restored by unwinding the specpdl,
+/// so it is also restored by a non-local exit.
+pub struct SaveMatchData {
+    count: ptrdiff_t,
+}
+
+impl SaveMatchData {
+    /// Save the match data of the current thread.
+    pub fn save() -> Self {
+        let count = c_specpdl_index();
+        unsafe { record_unwind_save_match_data() };
+        Self { count }
+    }
+}
+
+impl Drop for SaveMatchData {
+    fn drop(&mut self) {
+        // An enclosing `unbind_to' may have restored the data already.
+        if c_specpdl_index() > self.count {
+            unbind_to(self.count, Qnil);
+        }
+    }
+}
+
+/// Return the name of BUFFER for an error message.
+fn buffer_description(buffer: LispBufferRef) -> String {
+    buffer.name().as_string().map_or_else(
+        || "a killed buffer".to_string(),
+        |name| format!("buffer {}", name),
+    )
+}
+
+/// Signal an error if the match data, found by searching a buffer, does
+/// not describe STRING, or the current buffer if STRING is nil.  This
+/// catches a `replace-match' that would use positions from somewhere
+/// else.  Data set from a list can be used on anything.
+#[no_mangle]
+pub extern "C" fn check_match_data_source(string: LispObject) {
+    match MatchSource::current() {
+        MatchSource::Buffer(buffer) if string.is_not_nil() => error!(
+            "Last match was in {}, not in a string",
+            buffer_description(buffer)
+        ),
+        MatchSource::Buffer(buffer) if buffer != ThreadState::current_buffer() => error!(
+            "Last match was in {}, not in the current buffer",
+            buffer_description(buffer)
+        ),
+        _ => (),
+    }
+}
+
+fn match_limit(subexp: EmacsInt, beginning: bool) -> Option<EmacsInt> {
+    if subexp < 0 {
+        args_out_of_range!(LispObject::from(subexp), LispObject::from(0));
+    }
+    if MatchData::num_registers() == 0 {
+        error!("No match data, because no search succeeded");
+    }
+    MatchData::register(subexp as usize).map(|(start, end)| {
+        if beginning {
+            start as EmacsInt
+        } else {
+            end as EmacsInt
+        }
+    })
+}
+
 /// Return position of start of text matched by last search.
 /// SUBEXP, a number, specifies which parenthesized expression in the last
 ///   regexp.
@@ -232,8 +500,8 @@ pub fn posix_search_forward(
 ///
 /// Return value is undefined if the last search failed.
 #[lisp_fn]
-pub fn match_beginning(subexp: LispObject) -> LispObject {
-    unsafe { match_limit(subexp, true) }
+pub fn match_beginning(subexp: EmacsInt) -> Option<EmacsInt> {
+    match_limit(subexp, true)
 }
 
 /// Return position of end of text matched by last search.
@@ -245,8 +513,173 @@ pub fn match_beginning(subexp: LispObject) -> LispObject {
 ///
 /// Return value is undefined if the last search failed.
 #[lisp_fn]
-pub fn match_end(subexp: LispObject) -> LispObject {
-    unsafe { match_limit(subexp, false) }
+pub fn match_end(subexp: EmacsInt) -> Option<EmacsInt> {
+    match_limit(subexp, false)
+}
+
+/// Return a list describing what the last search matched.
+/// Element 2N is `(match-beginning N)'; element 2N + 1 is `(match-end N)'.
+/// All the elements are markers or nil (nil if the Nth pair didn't match)
+/// if the last match was on a buffer; integers or nil if a string was matched.
+/// Use `set-match-data' to reinstate the data in this list.
+///
+/// If INTEGERS (the optional first argument) is non-nil, always use
+/// integers (rather than markers) to represent buffer positions.  In
+/// this case, and if the last match was in a buffer, the buffer will get
+/// stored as one additional element at the end of the list.
+///
+/// If REUSE is a list, reuse it as part of the value.  If REUSE is long
+/// enough to hold all the values, and if INTEGERS is non-nil, no consing
+/// is done.
+///
+/// If optional third arg RESEAT is non-nil, any previous markers on the
+/// REUSE list will be modified to point to nowhere.
+///
+/// Return value is undefined if the last search failed.
+#[lisp_fn(min = "0")]
+pub fn match_data(integers: bool, reuse: LispObject, reseat: bool) -> LispObject {
+    if reseat {
+        for tail in reuse.iter_tails_safe() {
+            if let Some(mut marker) = tail.car().as_marker() {
+                unchain_marker(marker.as_mut());
+                tail.set_car(Qnil);
+            }
+        }
+    }
+
+    let data = MatchData::current();
+    if let MatchSource::Nothing = data.source {
+        return Qnil;
+    }
+    let buffer = data.source.buffer();
+
+    let mut values = Vec::with_capacity(2 * data.registers.len() + 1);
+    let mut len = 0;
+    for (i, register) in data.registers.iter().enumerate() {
+        match (*register, buffer) {
+            (Some((start, end)), Some(buffer)) if !integers => {
+                for &pos in &[start, end] {
+                    let marker = unsafe { Fmake_marker() };
+                    set_marker(marker, LispObject::from(pos), buffer.as_lisp_obj());
+                    values.push(marker);
+                }
+                len = 2 * i + 2;
+            }
+            (Some((start, end)), _) => {
+                values.push(LispObject::from(start));
+                values.push(LispObject::from(end));
+                len = 2 * i + 2;
+            }
+            (None, _) => {
+                values.push(Qnil);
+                values.push(Qnil);
+            }
+        }
+    }
+    values.truncate(len);
+    if let Some(buffer) = buffer.filter(|_| integers) {
+        values.push(buffer.as_lisp_obj());
+    }
+
+    // If REUSE is a list, store as many values as will fit into its
+    // elements, and cons up the rest at its end.
+    if !reuse.is_cons() {
+        return list(&values);
+    }
+    let mut rest = values.iter();
+    let mut last = None;
+    for tail in reuse.iter_tails_safe() {
+        tail.check_impure();
+        tail.set_car(rest.next().cloned().unwrap_or(Qnil));
+        last = Some(tail);
+    }
+    if let Some(last) = last {
+        if !rest.as_slice().is_empty() {
+            last.set_cdr(list(rest.as_slice()));
+        }
+    }
+    reuse
+}
+
+/// Set internal data on last search match from elements of LIST.
+/// LIST should have been created by calling `match-data' previously.
+///
+/// If optional arg RESEAT is non-nil, make markers on LIST point nowhere.
+#[lisp_fn(min = "1")]
+pub fn set_match_data(list: LispObject, reseat: bool) {
+    if unsafe { running_asynch_code } {
+        unsafe { save_search_regs() };
+    }
+
+    let tails: Vec<_> = list.iter_tails().collect();
+    let length = tails.len() / 2;
+
+    // Unless we find a marker with a buffer or an explicit buffer in
+    // LIST, assume that this match data came from a string.
+    let mut data = MatchData {
+        registers: Vec::with_capacity(length),
+        source: MatchSource::String,
+    };
+
+    let reseat_marker = |tail: LispCons| {
+        if !reseat {
+            return;
+        }
+        if let Some(mut marker) = tail.car().as_marker() {
+            unchain_marker(marker.as_mut());
+            tail.set_car(Qnil);
+        }
+    };
+
+    let mut pairs = tails.iter();
+    while let Some(&from_tail) = pairs.next() {
+        let from = from_tail.car();
+        if let Some(buffer) = from.as_buffer() {
+            data.source = MatchSource::List(buffer);
+            break;
+        }
+        if data.registers.len() >= length {
+            break;
+        }
+        if from.is_nil() {
+            data.registers.push(None);
+            pairs.next();
+            continue;
+        }
+
+        if let Some(buffer) = from.as_marker().and_then(|m| m.buffer()) {
+            data.source = MatchSource::List(buffer);
+        }
+        let start = match_position(from);
+        reseat_marker(from_tail);
+
+        let end_tail = match pairs.next() {
+            Some(&end_tail) => end_tail,
+            None => break,
+        };
+        let end = match_position(end_tail.car());
+        reseat_marker(end_tail);
+
+        let fits = |pos: EmacsInt| {
+            regoff_t::min_value() as EmacsInt <= pos && pos <= regoff_t::max_value() as EmacsInt
+        };
+        data.registers.push(if fits(start) && fits(end) {
+            Some((start as ptrdiff_t, end as ptrdiff_t))
+        } else {
+            None
+        });
+    }
+
+    data.install();
+}
+
+/// Return the position given by OBJECT, an element of a list made by
+/// `match-data'.  A marker that points nowhere means 0.
+fn match_position(object: LispObject) -> EmacsInt {
+    match object.as_marker() {
+        Some(marker) if marker.buffer().is_none() => 0,
+        _ => object.as_fixnum_coerce_marker_or_error(),
+    }
 }
 
 include!(concat!(env!("OUT_DIR"), "/search_exports.rs"));
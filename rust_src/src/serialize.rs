@@ -0,0 +1,404 @@
+//! Compact binary serialization of Lisp data.
+//!
+//! This is used wherever Lisp data is saved for a later session and
+//! printing and reading it back would be too slow, like the desktop
+//! buffers or the persistent store.  Only data that has a readable
+//! printed representation survives; see `encode_object'.
+//!
+//! The text properties of strings can be kept as well.  The interval
+//! tree that holds them is flattened into a table of the distinct
+//! property lists, followed by the length and property list index of
+//! each run of text with the same properties, from which decoding
+//! builds a balanced tree directly.
+
+use crate::{
+    data::aset,
+    lisp::LispObject,
+    multibyte::LispStringRef,
+    obarray::lisp_intern,
+    remacs_sys::{EmacsDouble, EmacsInt, Fmake_symbol, Fmake_vector},
+    remacs_sys::{Qnil, Qt},
+    shell::make_lisp_string,
+    textprop::IntervalRef,
+};
+
+/// Lists and vectors nested deeper than this are refused, so that
+/// circular structures don't exhaust the stack.
+const MAX_DEPTH: usize = 1000;
+
+const TAG_NIL: u8 = 0;
+const TAG_T: u8 = 1;
+const TAG_INT: u8 = 2;
+const TAG_FLOAT: u8 = 3;
+const TAG_UNIBYTE_STRING: u8 = 4;
+const TAG_MULTIBYTE_STRING: u8 = 5;
+const TAG_SYMBOL: u8 = 6;
+const TAG_UNINTERNED_SYMBOL: u8 = 7;
+const TAG_LIST: u8 = 8;
+const TAG_VECTOR: u8 = 9;
+const TAG_PROPERTIZED_STRING: u8 = 10;
+
+/// Append N to OUT as a variable-length integer, seven bits per byte.
+pub fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+/// Encode N so that numbers of small magnitude take few bytes,
+/// whatever their sign.
+fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+fn unzigzag(n: u64) -> i64 {
+    ((n >> 1) as i64) ^ -((n & 1) as i64)
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+fn write_string(out: &mut Vec<u8>, string: LispStringRef) {
+    out.push(if string.is_multibyte() {
+        TAG_MULTIBYTE_STRING
+    } else {
+        TAG_UNIBYTE_STRING
+    });
+    write_bytes(out, string.as_slice());
+}
+
+/// Append the text properties of the interval tree rooted at ROOT,
+/// nested DEPTH levels deep, to OUT.
+fn encode_tree(root: Option<IntervalRef>, out: &mut Vec<u8>, depth: usize) {
+    let mut plists: Vec<Vec<u8>> = Vec::new();
+    let mut runs: Vec<(u64, usize)> = Vec::new();
+    for (start, end, i) in root.into_iter().flat_map(IntervalRef::iter_ordered) {
+        let mut plist = Vec::new();
+        encode(i.plist(), &mut plist, depth + 1, true);
+        let index = match plists.iter().position(|p| *p == plist) {
+            Some(index) => index,
+            None => {
+                plists.push(plist);
+                plists.len() - 1
+            }
+        };
+        let length = (end - start) as u64;
+        match runs.last_mut() {
+            Some(last) if last.1 == index => last.0 += length,
+            _ => runs.push((length, index)),
+        }
+    }
+
+    write_varint(out, plists.len() as u64);
+    for plist in &plists {
+        out.extend_from_slice(plist);
+    }
+    write_varint(out, runs.len() as u64);
+    for &(length, index) in &runs {
+        write_varint(out, length);
+        write_varint(out, index as u64);
+    }
+}
+
+/// Append the encoding of OBJECT, nested DEPTH levels deep, to OUT.
+/// If PROPERTIES is true, keep the text properties of strings.
+fn encode(object: LispObject, out: &mut Vec<u8>, depth: usize, properties: bool) {
+    if depth > MAX_DEPTH {
+        error!("Data is too deeply nested, or circular");
+    }
+
+    if object.is_nil() {
+        out.push(TAG_NIL);
+    } else if object.eq(Qt) {
+        out.push(TAG_T);
+    } else if let Some(n) = object.as_fixnum() {
+        out.push(TAG_INT);
+        write_varint(out, zigzag(n as i64));
+    } else if let Some(f) = object.as_float() {
+        out.push(TAG_FLOAT);
+        let bits = f.to_bits();
+        out.extend((0..8).map(|i| (bits >> (8 * i)) as u8));
+    } else if let Some(string) = object.as_string() {
+        let tree = IntervalRef::from_raw(string.intervals());
+        if properties && tree.is_some() {
+            out.push(TAG_PROPERTIZED_STRING);
+            write_string(out, string);
+            encode_tree(tree, out, depth);
+        } else {
+            write_string(out, string);
+        }
+    } else if let Some(symbol) = object.as_symbol() {
+        out.push(if symbol.is_interned_in_initial_obarray() {
+            TAG_SYMBOL
+        } else {
+            TAG_UNINTERNED_SYMBOL
+        });
+        write_string(out, symbol.symbol_name().as_string_or_error());
+    } else if object.is_cons() {
+        // Write the elements first, and patch in their count later,
+        // so that long lists are walked only once.
+        out.push(TAG_LIST);
+        let mut elements = Vec::new();
+        let mut count: u64 = 0;
+        let mut tail = object;
+        let mut slow = object;
+        while let Some(cons) = tail.as_cons() {
+            encode(cons.car(), &mut elements, depth + 1, properties);
+            count += 1;
+            tail = cons.cdr();
+            if count % 2 == 0 {
+                slow = slow.as_cons_or_error().cdr();
+                if slow.eq(tail) {
+                    error!("Data is too deeply nested, or circular");
+                }
+            }
+        }
+        write_varint(out, count);
+        out.extend_from_slice(&elements);
+        encode(tail, out, depth + 1, properties);
+    } else if let Some(vector) = object.as_vector() {
+        out.push(TAG_VECTOR);
+        write_varint(out, vector.len() as u64);
+        for &element in vector.as_slice() {
+            encode(element, out, depth + 1, properties);
+        }
+    } else if let Some(marker) = object.as_marker() {
+        match marker.charpos() {
+            Some(pos) => {
+                out.push(TAG_INT);
+                write_varint(out, zigzag(pos as i64));
+            }
+            None => out.push(TAG_NIL),
+        }
+    } else {
+        out.push(TAG_NIL);
+    }
+}
+
+/// A reader over encoded data.  The methods return None when the data
+/// is truncated or malformed.
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn byte(&mut self) -> Option<u8> {
+        let b = *self.data.get(self.pos)?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    fn varint(&mut self) -> Option<u64> {
+        let mut n: u64 = 0;
+        let mut shift = 0;
+        loop {
+            let b = self.byte()?;
+            if shift >= 64 {
+                return None;
+            }
+            n |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Some(n);
+            }
+            shift += 7;
+        }
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(len)?;
+        let bytes = self.data.get(self.pos..end)?;
+        self.pos = end;
+        Some(bytes)
+    }
+
+    fn string_contents(&mut self, multibyte: bool) -> Option<LispObject> {
+        let len = self.varint()? as usize;
+        let bytes = self.bytes(len)?;
+        Some(make_lisp_string(bytes, multibyte))
+    }
+
+    fn string(&mut self) -> Option<LispObject> {
+        match self.byte()? {
+            TAG_UNIBYTE_STRING => self.string_contents(false),
+            TAG_MULTIBYTE_STRING => self.string_contents(true),
+            _ => None,
+        }
+    }
+
+    /// Read the text properties written by `encode_tree', nested DEPTH
+    /// levels deep, and give them to STRING, which has none yet.
+    fn tree(&mut self, string: LispObject, depth: usize) -> Option<()> {
+        let count = self.varint()?;
+        // Keep the property lists in a Lisp list while decoding the
+        // rest, so that the garbage collector sees them.
+        let mut reversed = Qnil;
+        for _ in 0..count {
+            reversed = LispObject::cons(self.object(depth + 1)?, reversed);
+        }
+        let mut plists: Vec<LispObject> = reversed.iter_cars_safe().collect();
+        plists.reverse();
+
+        let mut s = string.as_string_or_error();
+        let mut left = s.len_chars() as EmacsInt;
+        let mut runs = Vec::new();
+        for _ in 0..self.varint()? {
+            let length = self.varint()? as EmacsInt;
+            let plist = *plists.get(self.varint()? as usize)?;
+            if length <= 0 || length > left || !plist.is_list() {
+                return None;
+            }
+            runs.push((length, plist));
+            left -= length;
+        }
+        if left != 0 {
+            return None;
+        }
+        if let Some(mut tree) = IntervalRef::from_runs(string, &runs) {
+            s.set_intervals(tree.as_mut());
+        }
+        Some(())
+    }
+
+    fn object(&mut self, depth: usize) -> Option<LispObject> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+
+        match self.byte()? {
+            TAG_NIL => Some(Qnil),
+            TAG_T => Some(Qt),
+            TAG_INT => {
+                let n = unzigzag(self.varint()?) as EmacsInt;
+                if LispObject::fixnum_overflow(n) {
+                    return None;
+                }
+                Some(LispObject::from(n))
+            }
+            TAG_FLOAT => {
+                let bits = self
+                    .bytes(8)?
+                    .iter()
+                    .rev()
+                    .fold(0, |bits, &b| bits << 8 | u64::from(b));
+                Some(LispObject::from_float(EmacsDouble::from_bits(bits)))
+            }
+            TAG_UNIBYTE_STRING => self.string_contents(false),
+            TAG_MULTIBYTE_STRING => self.string_contents(true),
+            TAG_PROPERTIZED_STRING => {
+                let string = self.string()?;
+                self.tree(string, depth)?;
+                Some(string)
+            }
+            TAG_SYMBOL => Some(lisp_intern(self.string()?, Qnil)),
+            TAG_UNINTERNED_SYMBOL => Some(unsafe { Fmake_symbol(self.string()?) }),
+            TAG_LIST => {
+                let count = self.varint()?;
+                // Keep the elements in a Lisp list while decoding the
+                // rest, so that the garbage collector sees them.
+                let mut reversed = Qnil;
+                for _ in 0..count {
+                    reversed = LispObject::cons(self.object(depth + 1)?, reversed);
+                }
+                let mut list = self.object(depth + 1)?;
+                while let Some(cons) = reversed.as_cons() {
+                    list = LispObject::cons(cons.car(), list);
+                    reversed = cons.cdr();
+                }
+                Some(list)
+            }
+            TAG_VECTOR => {
+                let len = self.varint()? as EmacsInt;
+                if LispObject::fixnum_overflow(len) {
+                    return None;
+                }
+                let mut elements = Qnil;
+                for _ in 0..len {
+                    elements = LispObject::cons(self.object(depth + 1)?, elements);
+                }
+                let vector = unsafe { Fmake_vector(LispObject::from(len), Qnil) };
+                let mut idx = len;
+                while let Some(cons) = elements.as_cons() {
+                    idx -= 1;
+                    aset(vector, idx, cons.car());
+                    elements = cons.cdr();
+                }
+                Some(vector)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Append the encoding of OBJECT to OUT.  Text properties of strings
+/// are dropped, markers are replaced by their position, and objects
+/// that have no printed representation that can be read back, like
+/// buffers or windows, are replaced by nil.  Signal an error if OBJECT
+/// is circular.
+pub fn encode_object(object: LispObject, out: &mut Vec<u8>) {
+    encode(object, out, 0, false)
+}
+
+/// Append the encoding of OBJECT to OUT, like `encode_object', but keep
+/// the text properties of strings.
+pub fn encode_object_with_properties(object: LispObject, out: &mut Vec<u8>) {
+    encode(object, out, 0, true)
+}
+
+/// Read a variable-length integer written by `write_varint' from DATA
+/// at *POS, and advance *POS past it.  Return None if DATA is truncated.
+pub fn read_varint(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut decoder = Decoder { data, pos: *pos };
+    let n = decoder.varint()?;
+    *pos = decoder.pos;
+    Some(n)
+}
+
+/// Decode DATA, made by `encode_object', into a Lisp object.  Return
+/// None if DATA is malformed, or has trailing garbage.
+pub fn decode_object(data: &[u8]) -> Option<LispObject> {
+    let mut decoder = Decoder { data, pos: 0 };
+    decoder.object(0).and_then(|object| {
+        if decoder.pos == data.len() {
+            Some(object)
+        } else {
+            None
+        }
+    })
+}
+
+#[test]
+fn test_zigzag() {
+    for &n in &[
+        0,
+        1,
+        -1,
+        63,
+        -64,
+        1 << 40,
+        -(1 << 40),
+        i64::max_value(),
+        i64::min_value(),
+    ] {
+        assert_eq!(unzigzag(zigzag(n)), n);
+    }
+    assert_eq!(zigzag(-1), 1);
+    assert_eq!(zigzag(1), 2);
+}
+
+#[test]
+fn test_varint() {
+    let mut out = Vec::new();
+    write_varint(&mut out, 300);
+    write_varint(&mut out, u64::max_value());
+    assert_eq!(&out[..2], &[0xac, 0x02]);
+
+    let mut decoder = Decoder { data: &out, pos: 0 };
+    assert_eq!(decoder.varint(), Some(300));
+    assert_eq!(decoder.varint(), Some(u64::max_value()));
+    assert_eq!(decoder.varint(), None);
+}
@@ -0,0 +1,363 @@
+//! The emacsclient protocol, as spoken by the server.
+//!
+//! emacsclient sends its request as a single line of space separated
+//! words.  Each command word starts with a dash and is followed by its
+//! arguments, which are &-quoted so they contain neither spaces nor
+//! newlines, nor leading dashes.  See `server-process-filter' for the
+//! list of commands.  The quoting only involves ASCII characters, so
+//! it is done on the raw bytes of the strings.
+
+use libc::ptrdiff_t;
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    lisp::{defsubr, LispObject},
+    multibyte::LispStringRef,
+    obarray::intern,
+    remacs_sys::{string_byte_to_char, EmacsInt, Qnil},
+    shell::make_lisp_string,
+};
+
+/// The commands emacsclient can send, with the number of arguments
+/// each of them takes.
+const COMMANDS: &[(&str, usize)] = &[
+    ("current-frame", 0),
+    ("dir", 1),
+    ("display", 1),
+    ("env", 1),
+    ("eval", 1),
+    ("file", 1),
+    ("frame-parameters", 1),
+    ("ignore", 1),
+    ("nowait", 0),
+    ("parent-id", 1),
+    ("position", 1),
+    ("resume", 0),
+    ("suspend", 0),
+    ("tty", 2),
+    ("version", 1),
+    ("window-system", 0),
+];
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    /// -position +LINE[:COLUMN]; a missing column is 0.
+    Position(EmacsInt, EmacsInt),
+    /// Any other command, with its &-unquoted arguments.
+    Other(&'static str, Vec<Vec<u8>>),
+}
+
+#[derive(Debug, PartialEq)]
+enum ParseError {
+    Unknown(Vec<u8>),
+    MissingArgument(&'static str),
+}
+
+/// Remove &-quotation from ARG.  In multibyte text, a & followed by a
+/// non-ASCII character stands for a space, like any other unknown
+/// escape, so skip the rest of that character too.
+fn unquote_arg(arg: &[u8], multibyte: bool) -> Vec<u8> {
+    let mut unquoted = Vec::with_capacity(arg.len());
+    let mut i = 0;
+    while i < arg.len() {
+        if arg[i] != b'&' || i + 1 == arg.len() {
+            unquoted.push(arg[i]);
+            i += 1;
+            continue;
+        }
+        unquoted.push(match arg[i + 1] {
+            b'&' => b'&',
+            b'-' => b'-',
+            b'n' => b'\n',
+            _ => b' ',
+        });
+        i += 2;
+        if multibyte && arg[i - 1] >= 0x80 {
+            while i < arg.len() && arg[i] & 0xc0 == 0x80 {
+                i += 1;
+            }
+        }
+    }
+    unquoted
+}
+
+/// Insert a & before each &, space, newline and dash of ARG, and turn
+/// spaces into underscores.
+fn quote_arg(arg: &[u8]) -> Vec<u8> {
+    let mut quoted = Vec::with_capacity(arg.len());
+    for &b in arg {
+        match b {
+            b'&' => quoted.extend_from_slice(b"&&"),
+            b'-' => quoted.extend_from_slice(b"&-"),
+            b'\n' => quoted.extend_from_slice(b"&n"),
+            b' ' => quoted.extend_from_slice(b"&_"),
+            _ => quoted.push(b),
+        }
+    }
+    quoted
+}
+
+/// Parse the digits at the start of BYTES, returning the number and
+/// the count of digits, or None if there are no digits.  Numbers that
+/// don't fit are clamped.
+fn parse_number(bytes: &[u8]) -> Option<(EmacsInt, usize)> {
+    let len = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+    if len == 0 {
+        return None;
+    }
+    let n = bytes[..len].iter().fold(0 as EmacsInt, |n, &b| {
+        n.saturating_mul(10)
+            .saturating_add(EmacsInt::from(b - b'0'))
+    });
+    Some((n, len))
+}
+
+/// Parse the argument of -position, +LINE[:COLUMN].
+fn parse_position(arg: &[u8]) -> Option<(EmacsInt, EmacsInt)> {
+    (0..arg.len())
+        .filter(|&i| arg[i] == b'+')
+        .filter_map(|i| parse_number(&arg[i + 1..]).map(|(line, len)| (line, i + 1 + len)))
+        .next()
+        .map(|(line, end)| {
+            let column = match arg.get(end) {
+                Some(b':') => parse_number(&arg[end + 1..]).map_or(0, |(column, _)| column),
+                _ => 0,
+            };
+            (line, column)
+        })
+}
+
+/// Parse the words of the request LINE into commands.
+fn parse_commands(line: &[u8], multibyte: bool) -> Result<Vec<Command>, ParseError> {
+    let mut words = line
+        .split(|&b| b == b' ')
+        .filter(|word| !word.is_empty())
+        .map(|word| unquote_arg(word, multibyte));
+    let mut commands = Vec::new();
+
+    while let Some(word) = words.next() {
+        let command = if word.starts_with(b"-") {
+            COMMANDS
+                .iter()
+                .find(|&&(name, _)| name.as_bytes() == &word[1..])
+        } else {
+            None
+        };
+        let (name, nargs) = match command {
+            Some(&command) => command,
+            None => return Err(ParseError::Unknown(word)),
+        };
+
+        let mut args = Vec::with_capacity(nargs);
+        for _ in 0..nargs {
+            match words.next() {
+                Some(arg) => args.push(arg),
+                None => return Err(ParseError::MissingArgument(name)),
+            }
+        }
+
+        // A malformed position is dropped along with its argument, so
+        // the next file just opens at its start.
+        if name == "position" {
+            if let Some((line, column)) = parse_position(&args[0]) {
+                commands.push(Command::Position(line, column));
+            }
+        } else {
+            commands.push(Command::Other(name, args));
+        }
+    }
+
+    Ok(commands)
+}
+
+/// Return true if B can be part of an authentication key.
+fn is_key_byte(b: u8) -> bool {
+    b'!' <= b && b <= b'~'
+}
+
+/// Return the end of the -auth command in TEXT, if it carries KEY.
+fn authenticate(text: &[u8], key: &[u8]) -> Option<usize> {
+    const AUTH: &[u8] = b"-auth ";
+
+    let start = (0..text.len()).find(|&i| {
+        let key_start = i + AUTH.len();
+        text[i..].starts_with(AUTH) && key_start < text.len() && is_key_byte(text[key_start])
+    })? + AUTH.len();
+    let len = text[start..]
+        .iter()
+        .take_while(|&&b| is_key_byte(b))
+        .count();
+
+    // Look at every byte, so that the time this takes doesn't tell how
+    // much of the key was right.
+    let given = &text[start..start + len];
+    let differences = given.iter().zip(key).fold(0, |acc, (a, b)| acc | (a ^ b));
+    if differences != 0 || given.len() != key.len() {
+        return None;
+    }
+
+    let end = start + len;
+    Some(if text.get(end) == Some(&b'\n') {
+        end + 1
+    } else {
+        end
+    })
+}
+
+/// Remove &-quotation from ARG.
+/// See `server-quote-arg' and `server-process-filter'.
+#[lisp_fn]
+pub fn server_unquote_arg(arg: LispStringRef) -> LispObject {
+    let multibyte = arg.is_multibyte();
+    make_lisp_string(&unquote_arg(arg.as_slice(), multibyte), multibyte)
+}
+
+/// In ARG, insert a & before each &, each space, each newline, and -.
+/// Change spaces to underscores, too, so that the return value never
+/// contains a space.
+///
+/// See `server-unquote-arg' and `server-process-filter'.
+#[lisp_fn]
+pub fn server_quote_arg(arg: LispStringRef) -> LispObject {
+    make_lisp_string(&quote_arg(arg.as_slice()), arg.is_multibyte())
+}
+
+/// Check the authentication command in the client request STRING.
+/// If STRING contains an -auth command whose argument is KEY, return
+/// the position just after that command, and its newline if any.
+/// Otherwise, return nil.
+#[lisp_fn]
+pub fn server_authenticate(string: LispStringRef, key: LispObject) -> LispObject {
+    let key = match key.as_string() {
+        Some(key) => key,
+        None => return Qnil,
+    };
+    match authenticate(string.as_slice(), key.as_slice()) {
+        Some(end) => {
+            LispObject::from(
+                unsafe { string_byte_to_char(string.as_lisp_obj(), end as ptrdiff_t) } as EmacsInt,
+            )
+        }
+        None => Qnil,
+    }
+}
+
+/// Parse the client request in STRING.
+/// Return nil if STRING doesn't hold a complete line yet.  Otherwise,
+/// return (COMMANDS . REST), where REST is what follows the first line
+/// of STRING, and COMMANDS is the list of commands on that line, in
+/// order.  Each command is a list whose car is a symbol naming the
+/// command, without its dash, and whose cdr holds its &-unquoted
+/// arguments, like (file "/tmp/foo") or (tty "/dev/pts/1" "xterm").
+/// The argument of -position is parsed into two integers, giving
+/// (position LINE COLUMN); a -position whose argument isn't of the
+/// form +LINE[:COLUMN] is left out.
+///
+/// Signal an error if the line contains an unknown command or lacks
+/// some argument.
+#[lisp_fn]
+pub fn server_parse_request(string: LispStringRef) -> LispObject {
+    let multibyte = string.is_multibyte();
+    let text = string.as_slice().to_vec();
+    let newline = match text.iter().position(|&b| b == b'\n') {
+        Some(newline) => newline,
+        None => return Qnil,
+    };
+
+    let commands = match parse_commands(&text[..newline], multibyte) {
+        Ok(commands) => commands,
+        Err(ParseError::Unknown(word)) => {
+            error!("Unknown command: {}", String::from_utf8_lossy(&word))
+        }
+        Err(ParseError::MissingArgument(name)) => {
+            error!("Missing argument to -{} command in client args", name)
+        }
+    };
+
+    let mut result = Qnil;
+    for command in commands.into_iter().rev() {
+        let command = match command {
+            Command::Position(line, column) => list!(
+                intern("position").as_lisp_obj(),
+                LispObject::from(line),
+                LispObject::from(column)
+            ),
+            Command::Other(name, args) => {
+                let mut list = Qnil;
+                for arg in args.iter().rev() {
+                    list = LispObject::cons(make_lisp_string(arg, multibyte), list);
+                }
+                LispObject::cons(intern(name).as_lisp_obj(), list)
+            }
+        };
+        result = LispObject::cons(command, result);
+    }
+
+    let rest = make_lisp_string(&text[newline + 1..], multibyte);
+    LispObject::cons(result, rest)
+}
+
+include!(concat!(env!("OUT_DIR"), "/server_exports.rs"));
+
+#[test]
+fn test_quoting() {
+    assert_eq!(quote_arg(b"-a b&c\n"), b"&-a&_b&&c&n".to_vec());
+    assert_eq!(unquote_arg(b"&-a&_b&&c&n", false), b"-a b&c\n".to_vec());
+    assert_eq!(unquote_arg(b"a&", false), b"a&".to_vec());
+    // A & before a non-ASCII character stands for a space.
+    assert_eq!(unquote_arg(b"&\xc3\xa9x", true), b" x".to_vec());
+}
+
+#[test]
+fn test_parse_position() {
+    assert_eq!(parse_position(b"+12"), Some((12, 0)));
+    assert_eq!(parse_position(b"+12:7"), Some((12, 7)));
+    assert_eq!(parse_position(b"+12:"), Some((12, 0)));
+    assert_eq!(parse_position(b"x+3"), Some((3, 0)));
+    assert_eq!(parse_position(b"12"), None);
+}
+
+#[test]
+fn test_parse_commands() {
+    assert_eq!(
+        parse_commands(b"-dir /tmp/ -position +3:4 -file a&_b.txt -nowait", false),
+        Ok(vec![
+            Command::Other("dir", vec![b"/tmp/".to_vec()]),
+            Command::Position(3, 4),
+            Command::Other("file", vec![b"a b.txt".to_vec()]),
+            Command::Other("nowait", vec![]),
+        ])
+    );
+    assert_eq!(
+        parse_commands(b"-tty /dev/pts/1 xterm", false),
+        Ok(vec![Command::Other(
+            "tty",
+            vec![b"/dev/pts/1".to_vec(), b"xterm".to_vec()]
+        )])
+    );
+    assert_eq!(
+        parse_commands(b"-frobnicate", false),
+        Err(ParseError::Unknown(b"-frobnicate".to_vec()))
+    );
+    assert_eq!(
+        parse_commands(b"-eval", false),
+        Err(ParseError::MissingArgument("eval"))
+    );
+    assert_eq!(
+        parse_commands(b"-position 3 -file a", false),
+        Ok(vec![Command::Other("file", vec![b"a".to_vec()])])
+    );
+}
+
+#[test]
+fn test_authenticate() {
+    assert_eq!(
+        authenticate(b"-auth secret\n-nowait\n", b"secret"),
+        Some(13)
+    );
+    assert_eq!(authenticate(b"-auth secret", b"secret"), Some(12));
+    assert_eq!(authenticate(b"-auth secrets\n", b"secret"), None);
+    assert_eq!(authenticate(b"-auth secre\n", b"secret"), None);
+    assert_eq!(authenticate(b"-nowait\n", b"secret"), None);
+}
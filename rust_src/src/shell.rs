@@ -0,0 +1,430 @@
+//! Quoting and splitting of shell command lines.
+//!
+//! `compile', `grep' and friends build command lines out of file names
+//! and user input all the time, so these are implemented natively.  All
+//! the characters that are special to the shells are ASCII, and no byte
+//! of a non-ASCII character in Emacs's internal representation is, so
+//! the functions below work on the raw bytes of the strings and leave
+//! non-ASCII characters alone.
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    lisp::defsubr, lisp::LispObject, multibyte::LispStringRef, remacs_sys::make_specified_string,
+    strings::string_to_multibyte,
+};
+
+#[cfg(windows)]
+use crate::{eval::funcall, obarray::intern};
+
+/// Return true if B never needs quoting for a POSIX shell.
+fn is_posix_safe_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'.' || b == b'/'
+}
+
+/// Quote ARG for a POSIX shell: backslash every character that is not
+/// safe, except for newlines, which can't be escaped with a backslash
+/// and are put in single quotes instead.  MULTIBYTE says whether ARG is
+/// in the internal multibyte representation, in which case only the
+/// first byte of a non-ASCII character gets a backslash.
+pub fn quote_posix(arg: &[u8], multibyte: bool) -> Vec<u8> {
+    if arg.is_empty() {
+        return b"''".to_vec();
+    }
+
+    let mut quoted = Vec::with_capacity(arg.len() * 2);
+    for &b in arg {
+        if b == b'\n' {
+            quoted.extend_from_slice(b"'\n'");
+            continue;
+        }
+        let continuation = multibyte && b & 0xc0 == 0x80;
+        if !is_posix_safe_byte(b) && !continuation {
+            quoted.push(b'\\');
+        }
+        quoted.push(b);
+    }
+    quoted
+}
+
+/// Quote ARG so that `CommandLineToArgvW' recovers it, then protect the
+/// result from cmd.exe.  If the argument contains no cmd metacharacters
+/// that are special inside double quotes, surrounding it with double
+/// quotes is enough.  Otherwise, each metacharacter is escaped with a
+/// caret.
+pub fn quote_windows(arg: &[u8]) -> Vec<u8> {
+    let mut argv = Vec::with_capacity(arg.len() + 2);
+    let mut backslashes = 0;
+    for &b in arg {
+        match b {
+            b'\\' => backslashes += 1,
+            b'"' => {
+                // Backslashes before a quote, and the quote itself, must
+                // be escaped.
+                argv.extend(std::iter::repeat(b'\\').take(backslashes + 1));
+                backslashes = 0;
+            }
+            _ => backslashes = 0,
+        }
+        argv.push(b);
+    }
+    // Backslashes at the end would escape the closing quote.
+    argv.extend(std::iter::repeat(b'\\').take(backslashes));
+
+    let mut quoted = Vec::with_capacity(argv.len() + 4);
+    if argv.iter().any(|&b| b == b'%' || b == b'!' || b == b'"') {
+        quoted.extend_from_slice(b"^\"");
+        for &b in &argv {
+            if b"%!()\"<>&|^".contains(&b) {
+                quoted.push(b'^');
+            }
+            quoted.push(b);
+        }
+        quoted.extend_from_slice(b"^\"");
+    } else {
+        quoted.push(b'"');
+        quoted.extend_from_slice(&argv);
+        quoted.push(b'"');
+    }
+    quoted
+}
+
+/// Split COMMAND into arguments the way a POSIX shell does, honoring
+/// single quotes, double quotes and backslashes.  An unterminated quote
+/// extends to the end of COMMAND.
+pub fn split_posix(command: &[u8]) -> Vec<Vec<u8>> {
+    let mut args = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+    let mut iter = command.iter().cloned().peekable();
+
+    while let Some(b) = iter.next() {
+        match b {
+            b' ' | b'\t' | b'\n' => {
+                if let Some(arg) = current.take() {
+                    args.push(arg);
+                }
+            }
+            b'\'' => {
+                let arg = current.get_or_insert_with(Vec::new);
+                for b in &mut iter {
+                    if b == b'\'' {
+                        break;
+                    }
+                    arg.push(b);
+                }
+            }
+            b'"' => {
+                let arg = current.get_or_insert_with(Vec::new);
+                while let Some(b) = iter.next() {
+                    match b {
+                        b'"' => break,
+                        // Inside double quotes, a backslash only quotes
+                        // these characters; a quoted newline is removed.
+                        b'\\' => match iter.peek().cloned() {
+                            Some(b'\n') => {
+                                iter.next();
+                            }
+                            Some(next) if b"$`\"\\".contains(&next) => {
+                                iter.next();
+                                arg.push(next);
+                            }
+                            _ => arg.push(b),
+                        },
+                        _ => arg.push(b),
+                    }
+                }
+            }
+            b'\\' => match iter.next() {
+                // A backslash-newline is a line continuation.
+                Some(b'\n') => {}
+                Some(next) => current.get_or_insert_with(Vec::new).push(next),
+                None => current.get_or_insert_with(Vec::new).push(b),
+            },
+            _ => current.get_or_insert_with(Vec::new).push(b),
+        }
+    }
+
+    if let Some(arg) = current {
+        args.push(arg);
+    }
+    args
+}
+
+/// Split COMMAND into arguments the way `CommandLineToArgvW' does.
+pub fn split_windows(command: &[u8]) -> Vec<Vec<u8>> {
+    let mut args = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+    let mut in_quotes = false;
+    let mut i = 0;
+
+    while i < command.len() {
+        let b = command[i];
+        match b {
+            b' ' | b'\t' if !in_quotes => {
+                if let Some(arg) = current.take() {
+                    args.push(arg);
+                }
+                i += 1;
+            }
+            b'\\' => {
+                let start = i;
+                while i < command.len() && command[i] == b'\\' {
+                    i += 1;
+                }
+                let backslashes = i - start;
+                let arg = current.get_or_insert_with(Vec::new);
+                if i < command.len() && command[i] == b'"' {
+                    // 2N backslashes and a quote are N backslashes and
+                    // a delimiter, 2N+1 backslashes and a quote are N
+                    // backslashes and a literal quote.
+                    arg.extend(std::iter::repeat(b'\\').take(backslashes / 2));
+                    if backslashes % 2 == 1 {
+                        arg.push(b'"');
+                        i += 1;
+                    }
+                } else {
+                    arg.extend(std::iter::repeat(b'\\').take(backslashes));
+                }
+            }
+            b'"' => {
+                let arg = current.get_or_insert_with(Vec::new);
+                if in_quotes && command.get(i + 1) == Some(&b'"') {
+                    // A doubled quote inside quotes is a literal quote.
+                    arg.push(b'"');
+                    i += 2;
+                } else {
+                    in_quotes = !in_quotes;
+                    i += 1;
+                }
+            }
+            _ => {
+                current.get_or_insert_with(Vec::new).push(b);
+                i += 1;
+            }
+        }
+    }
+
+    if let Some(arg) = current {
+        args.push(arg);
+    }
+    args
+}
+
+/// Return true if STRING must be quoted by `combine-and-quote-strings'
+/// when joined with SEPARATOR.
+fn needs_lisp_quoting(string: &[u8], separator: &[u8]) -> bool {
+    string.iter().any(|&b| b == b'\\' || b == b'"')
+        || (!separator.is_empty() && string.windows(separator.len()).any(|w| w == separator))
+}
+
+/// Append STRING to OUT in Lisp string syntax.
+fn push_lisp_quoted(out: &mut Vec<u8>, string: &[u8]) {
+    out.push(b'"');
+    for &b in string {
+        if b == b'\\' || b == b'"' {
+            out.push(b'\\');
+        }
+        out.push(b);
+    }
+    out.push(b'"');
+}
+
+/// Make a Lisp string out of BYTES, which are in the internal
+/// representation if MULTIBYTE is true.
+pub fn make_lisp_string(bytes: &[u8], multibyte: bool) -> LispObject {
+    let nchars = if multibyte {
+        // Every character starts with a byte that is not 10xxxxxx.
+        bytes.iter().filter(|&&b| b & 0xc0 != 0x80).count()
+    } else {
+        bytes.len()
+    };
+    unsafe {
+        make_specified_string(
+            bytes.as_ptr() as *const c_char,
+            nchars as ptrdiff_t,
+            bytes.len() as ptrdiff_t,
+            multibyte,
+        )
+    }
+}
+
+/// Return true if shell commands are run by cmd.exe.
+#[cfg(windows)]
+fn shell_dos_semantics() -> bool {
+    funcall(&mut [intern("w32-shell-dos-semantics").as_lisp_obj()]).is_not_nil()
+}
+
+#[cfg(not(windows))]
+fn shell_dos_semantics() -> bool {
+    false
+}
+
+/// Quote ARGUMENT for passing as argument to an inferior shell.
+///
+/// This function is designed to work with the syntax of your system's
+/// standard shell, and might produce incorrect results with unusual shells.
+/// See Info node `(elisp)Security Considerations'.
+///
+/// If the optional POSIX argument is non-nil, ARGUMENT is quoted
+/// according to POSIX shell quoting rules, regardless of the system's
+/// shell.
+#[lisp_fn(min = "1")]
+pub fn shell_quote_argument(argument: LispStringRef, posix: bool) -> LispObject {
+    let bytes = argument.as_slice();
+    let multibyte = argument.is_multibyte();
+
+    let quoted = if !posix && shell_dos_semantics() {
+        quote_windows(bytes)
+    } else {
+        quote_posix(bytes, multibyte)
+    };
+    make_lisp_string(&quoted, multibyte)
+}
+
+/// Split STRING, a shell command, into a list of arguments.
+/// Single and double quotes and backslashes are interpreted the way the
+/// system's standard shell does, and removed.  An unterminated quote
+/// extends to the end of STRING.
+///
+/// This is the inverse of quoting each argument with
+/// `shell-quote-argument' and joining them with spaces.
+#[lisp_fn]
+pub fn split_string_shell_command(string: LispStringRef) -> LispObject {
+    let multibyte = string.is_multibyte();
+    let args = if shell_dos_semantics() {
+        split_windows(string.as_slice())
+    } else {
+        split_posix(string.as_slice())
+    };
+
+    let args: Vec<LispObject> = args
+        .iter()
+        .map(|arg| make_lisp_string(arg, multibyte))
+        .collect();
+    LispObject::from(args)
+}
+
+/// Concatenate the STRINGS, adding the SEPARATOR (default " ").
+/// This tries to quote the strings to avoid ambiguity such that
+///   (split-string-and-unquote (combine-and-quote-strings strs)) == strs
+/// Only some SEPARATORs will work properly.
+///
+/// Note that this is not intended to protect STRINGS from
+/// interpretation by shells, use `shell-quote-argument' for that.
+#[lisp_fn(min = "1")]
+pub fn combine_and_quote_strings(
+    strings: LispObject,
+    separator: Option<LispStringRef>,
+) -> LispObject {
+    let separator = separator.unwrap_or_else(|| LispObject::from(" ").as_string_or_error());
+    let mut parts: Vec<LispStringRef> = strings
+        .iter_cars()
+        .map(|s| s.as_string_or_error())
+        .collect();
+
+    // Mixing unibyte and multibyte text makes the result multibyte, and
+    // the bytes of the unibyte parts must be converted for that.
+    let multibyte = separator.is_multibyte() || parts.iter().any(|s| s.is_multibyte());
+    let separator = if multibyte && !separator.is_multibyte() {
+        string_to_multibyte(separator).as_string_or_error()
+    } else {
+        separator
+    };
+    if multibyte {
+        for part in &mut parts {
+            if !part.is_multibyte() {
+                *part = string_to_multibyte(*part).as_string_or_error();
+            }
+        }
+    }
+
+    let sep = separator.as_slice();
+    let mut result = Vec::new();
+    for (i, part) in parts.iter().enumerate() {
+        if i > 0 {
+            result.extend_from_slice(sep);
+        }
+        let bytes = part.as_slice();
+        if needs_lisp_quoting(bytes, sep) {
+            push_lisp_quoted(&mut result, bytes);
+        } else {
+            result.extend_from_slice(bytes);
+        }
+    }
+    make_lisp_string(&result, multibyte)
+}
+
+include!(concat!(env!("OUT_DIR"), "/shell_exports.rs"));
+
+#[test]
+fn test_quote_posix() {
+    assert_eq!(quote_posix(b"", false), b"''".to_vec());
+    assert_eq!(quote_posix(b"foo/bar-1.c", false), b"foo/bar-1.c".to_vec());
+    assert_eq!(quote_posix(b"a b$c'd", false), b"a\\ b\\$c\\'d".to_vec());
+    assert_eq!(quote_posix(b"a\nb", false), b"a'\n'b".to_vec());
+    assert_eq!(quote_posix(b"\xc3\xa9", true), b"\\\xc3\xa9".to_vec());
+}
+
+#[test]
+fn test_quote_windows() {
+    assert_eq!(quote_windows(b"a b"), b"\"a b\"".to_vec());
+    assert_eq!(quote_windows(b"a\\"), b"\"a\\\\\"".to_vec());
+    assert_eq!(quote_windows(b"50%"), b"^\"50^%^\"".to_vec());
+    assert_eq!(
+        quote_windows(b"say \"hi\""),
+        b"^\"say \\^\"hi\\^\"^\"".to_vec()
+    );
+}
+
+#[test]
+fn test_split_posix() {
+    assert_eq!(
+        split_posix(b"  ls  -l "),
+        vec![b"ls".to_vec(), b"-l".to_vec()]
+    );
+    assert_eq!(split_posix(b"a'b c'd"), vec![b"ab cd".to_vec()]);
+    assert_eq!(split_posix(b"\"a\\\"b\\n\""), vec![b"a\"b\\n".to_vec()]);
+    assert_eq!(
+        split_posix(b"a\\ b c\\\nd"),
+        vec![b"a b".to_vec(), b"cd".to_vec()]
+    );
+    assert_eq!(split_posix(b"'' \"\""), vec![Vec::new(), Vec::new()]);
+    assert_eq!(
+        split_posix(b"'unterminated x"),
+        vec![b"unterminated x".to_vec()]
+    );
+}
+
+#[test]
+fn test_split_windows() {
+    assert_eq!(
+        split_windows(b"a \"b c\" d"),
+        vec![b"a".to_vec(), b"b c".to_vec(), b"d".to_vec()]
+    );
+    assert_eq!(
+        split_windows(b"a\\\\b \\\"x\\\""),
+        vec![b"a\\\\b".to_vec(), b"\"x\"".to_vec()]
+    );
+    assert_eq!(
+        split_windows(b"\"a\\\\\" b"),
+        vec![b"a\\".to_vec(), b"b".to_vec()]
+    );
+    assert_eq!(
+        split_windows(b"\"say \"\"hi\"\"\""),
+        vec![b"say \"hi\"".to_vec()]
+    );
+}
+
+#[test]
+fn test_quote_round_trip() {
+    let args: Vec<&[u8]> = vec![b"", b"a b", b"it's", b"$HOME", b"x\ny", b"\\\""];
+    let command: Vec<u8> = args
+        .iter()
+        .map(|a| quote_posix(a, false))
+        .collect::<Vec<_>>()
+        .join(&b' ');
+    let split = split_posix(&command);
+    assert_eq!(split, args.iter().map(|a| a.to_vec()).collect::<Vec<_>>());
+}
@@ -0,0 +1,569 @@
+//! Helpers for rendering HTML with shr.
+//!
+//! shr lays out the text of a page by inserting it into a buffer and
+//! then folding each line with a loop of `move-to-column' and searches
+//! for a place to break, which takes seconds for large pages.
+//! `shr-fill-text' does the folding of a paragraph in one pass, for the
+//! case where characters have fixed widths, that is when
+//! `shr-use-fonts' is nil.  `html-decode-entities' replaces the
+//! character references of HTML with the characters they stand for.
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    data::aset,
+    fill::{breaks_between, char_width, greedy_breaks, Word},
+    fns::concat,
+    lisp::{defsubr, LispObject},
+    multibyte::MAX_MULTIBYTE_LENGTH,
+    multibyte::{raw_byte_codepoint, write_codepoint, Codepoint, LispStringRef},
+    remacs_sys::{EmacsInt, Fsubstring, Qnil},
+    shell::make_lisp_string,
+};
+
+/// The character U+FFFD, which replaces invalid character references.
+const REPLACEMENT_CHARACTER: Codepoint = 0xFFFD;
+
+/// The longest name of an entity in `ENTITIES'.
+const MAX_ENTITY_NAME: usize = 8;
+
+/// The named character references of HTML 4, and `&apos;', sorted by
+/// name.
+const ENTITIES: &[(&[u8], Codepoint)] = &[
+    (b"AElig", 0x00C6),
+    (b"Aacute", 0x00C1),
+    (b"Acirc", 0x00C2),
+    (b"Agrave", 0x00C0),
+    (b"Alpha", 0x0391),
+    (b"Aring", 0x00C5),
+    (b"Atilde", 0x00C3),
+    (b"Auml", 0x00C4),
+    (b"Beta", 0x0392),
+    (b"Ccedil", 0x00C7),
+    (b"Chi", 0x03A7),
+    (b"Dagger", 0x2021),
+    (b"Delta", 0x0394),
+    (b"ETH", 0x00D0),
+    (b"Eacute", 0x00C9),
+    (b"Ecirc", 0x00CA),
+    (b"Egrave", 0x00C8),
+    (b"Epsilon", 0x0395),
+    (b"Eta", 0x0397),
+    (b"Euml", 0x00CB),
+    (b"Gamma", 0x0393),
+    (b"Iacute", 0x00CD),
+    (b"Icirc", 0x00CE),
+    (b"Igrave", 0x00CC),
+    (b"Iota", 0x0399),
+    (b"Iuml", 0x00CF),
+    (b"Kappa", 0x039A),
+    (b"Lambda", 0x039B),
+    (b"Mu", 0x039C),
+    (b"Ntilde", 0x00D1),
+    (b"Nu", 0x039D),
+    (b"OElig", 0x0152),
+    (b"Oacute", 0x00D3),
+    (b"Ocirc", 0x00D4),
+    (b"Ograve", 0x00D2),
+    (b"Omega", 0x03A9),
+    (b"Omicron", 0x039F),
+    (b"Oslash", 0x00D8),
+    (b"Otilde", 0x00D5),
+    (b"Ouml", 0x00D6),
+    (b"Phi", 0x03A6),
+    (b"Pi", 0x03A0),
+    (b"Prime", 0x2033),
+    (b"Psi", 0x03A8),
+    (b"Rho", 0x03A1),
+    (b"Scaron", 0x0160),
+    (b"Sigma", 0x03A3),
+    (b"THORN", 0x00DE),
+    (b"Tau", 0x03A4),
+    (b"Theta", 0x0398),
+    (b"Uacute", 0x00DA),
+    (b"Ucirc", 0x00DB),
+    (b"Ugrave", 0x00D9),
+    (b"Upsilon", 0x03A5),
+    (b"Uuml", 0x00DC),
+    (b"Xi", 0x039E),
+    (b"Yacute", 0x00DD),
+    (b"Yuml", 0x0178),
+    (b"Zeta", 0x0396),
+    (b"aacute", 0x00E1),
+    (b"acirc", 0x00E2),
+    (b"acute", 0x00B4),
+    (b"aelig", 0x00E6),
+    (b"agrave", 0x00E0),
+    (b"alefsym", 0x2135),
+    (b"alpha", 0x03B1),
+    (b"amp", 0x0026),
+    (b"and", 0x2227),
+    (b"ang", 0x2220),
+    (b"apos", 0x0027),
+    (b"aring", 0x00E5),
+    (b"asymp", 0x2248),
+    (b"atilde", 0x00E3),
+    (b"auml", 0x00E4),
+    (b"bdquo", 0x201E),
+    (b"beta", 0x03B2),
+    (b"brvbar", 0x00A6),
+    (b"bull", 0x2022),
+    (b"cap", 0x2229),
+    (b"ccedil", 0x00E7),
+    (b"cedil", 0x00B8),
+    (b"cent", 0x00A2),
+    (b"chi", 0x03C7),
+    (b"circ", 0x02C6),
+    (b"clubs", 0x2663),
+    (b"cong", 0x2245),
+    (b"copy", 0x00A9),
+    (b"crarr", 0x21B5),
+    (b"cup", 0x222A),
+    (b"curren", 0x00A4),
+    (b"dArr", 0x21D3),
+    (b"dagger", 0x2020),
+    (b"darr", 0x2193),
+    (b"deg", 0x00B0),
+    (b"delta", 0x03B4),
+    (b"diams", 0x2666),
+    (b"divide", 0x00F7),
+    (b"eacute", 0x00E9),
+    (b"ecirc", 0x00EA),
+    (b"egrave", 0x00E8),
+    (b"empty", 0x2205),
+    (b"emsp", 0x2003),
+    (b"ensp", 0x2002),
+    (b"epsilon", 0x03B5),
+    (b"equiv", 0x2261),
+    (b"eta", 0x03B7),
+    (b"eth", 0x00F0),
+    (b"euml", 0x00EB),
+    (b"euro", 0x20AC),
+    (b"exist", 0x2203),
+    (b"fnof", 0x0192),
+    (b"forall", 0x2200),
+    (b"frac12", 0x00BD),
+    (b"frac14", 0x00BC),
+    (b"frac34", 0x00BE),
+    (b"frasl", 0x2044),
+    (b"gamma", 0x03B3),
+    (b"ge", 0x2265),
+    (b"gt", 0x003E),
+    (b"hArr", 0x21D4),
+    (b"harr", 0x2194),
+    (b"hearts", 0x2665),
+    (b"hellip", 0x2026),
+    (b"iacute", 0x00ED),
+    (b"icirc", 0x00EE),
+    (b"iexcl", 0x00A1),
+    (b"igrave", 0x00EC),
+    (b"image", 0x2111),
+    (b"infin", 0x221E),
+    (b"int", 0x222B),
+    (b"iota", 0x03B9),
+    (b"iquest", 0x00BF),
+    (b"isin", 0x2208),
+    (b"iuml", 0x00EF),
+    (b"kappa", 0x03BA),
+    (b"lArr", 0x21D0),
+    (b"lambda", 0x03BB),
+    (b"lang", 0x2329),
+    (b"laquo", 0x00AB),
+    (b"larr", 0x2190),
+    (b"lceil", 0x2308),
+    (b"ldquo", 0x201C),
+    (b"le", 0x2264),
+    (b"lfloor", 0x230A),
+    (b"lowast", 0x2217),
+    (b"loz", 0x25CA),
+    (b"lrm", 0x200E),
+    (b"lsaquo", 0x2039),
+    (b"lsquo", 0x2018),
+    (b"lt", 0x003C),
+    (b"macr", 0x00AF),
+    (b"mdash", 0x2014),
+    (b"micro", 0x00B5),
+    (b"middot", 0x00B7),
+    (b"minus", 0x2212),
+    (b"mu", 0x03BC),
+    (b"nabla", 0x2207),
+    (b"nbsp", 0x00A0),
+    (b"ndash", 0x2013),
+    (b"ne", 0x2260),
+    (b"ni", 0x220B),
+    (b"not", 0x00AC),
+    (b"notin", 0x2209),
+    (b"nsub", 0x2284),
+    (b"ntilde", 0x00F1),
+    (b"nu", 0x03BD),
+    (b"oacute", 0x00F3),
+    (b"ocirc", 0x00F4),
+    (b"oelig", 0x0153),
+    (b"ograve", 0x00F2),
+    (b"oline", 0x203E),
+    (b"omega", 0x03C9),
+    (b"omicron", 0x03BF),
+    (b"oplus", 0x2295),
+    (b"or", 0x2228),
+    (b"ordf", 0x00AA),
+    (b"ordm", 0x00BA),
+    (b"oslash", 0x00F8),
+    (b"otilde", 0x00F5),
+    (b"otimes", 0x2297),
+    (b"ouml", 0x00F6),
+    (b"para", 0x00B6),
+    (b"part", 0x2202),
+    (b"permil", 0x2030),
+    (b"perp", 0x22A5),
+    (b"phi", 0x03C6),
+    (b"pi", 0x03C0),
+    (b"piv", 0x03D6),
+    (b"plusmn", 0x00B1),
+    (b"pound", 0x00A3),
+    (b"prime", 0x2032),
+    (b"prod", 0x220F),
+    (b"prop", 0x221D),
+    (b"psi", 0x03C8),
+    (b"quot", 0x0022),
+    (b"rArr", 0x21D2),
+    (b"radic", 0x221A),
+    (b"rang", 0x232A),
+    (b"raquo", 0x00BB),
+    (b"rarr", 0x2192),
+    (b"rceil", 0x2309),
+    (b"rdquo", 0x201D),
+    (b"real", 0x211C),
+    (b"reg", 0x00AE),
+    (b"rfloor", 0x230B),
+    (b"rho", 0x03C1),
+    (b"rlm", 0x200F),
+    (b"rsaquo", 0x203A),
+    (b"rsquo", 0x2019),
+    (b"sbquo", 0x201A),
+    (b"scaron", 0x0161),
+    (b"sdot", 0x22C5),
+    (b"sect", 0x00A7),
+    (b"shy", 0x00AD),
+    (b"sigma", 0x03C3),
+    (b"sigmaf", 0x03C2),
+    (b"sim", 0x223C),
+    (b"spades", 0x2660),
+    (b"sub", 0x2282),
+    (b"sube", 0x2286),
+    (b"sum", 0x2211),
+    (b"sup", 0x2283),
+    (b"sup1", 0x00B9),
+    (b"sup2", 0x00B2),
+    (b"sup3", 0x00B3),
+    (b"supe", 0x2287),
+    (b"szlig", 0x00DF),
+    (b"tau", 0x03C4),
+    (b"there4", 0x2234),
+    (b"theta", 0x03B8),
+    (b"thetasym", 0x03D1),
+    (b"thinsp", 0x2009),
+    (b"thorn", 0x00FE),
+    (b"tilde", 0x02DC),
+    (b"times", 0x00D7),
+    (b"trade", 0x2122),
+    (b"uArr", 0x21D1),
+    (b"uacute", 0x00FA),
+    (b"uarr", 0x2191),
+    (b"ucirc", 0x00FB),
+    (b"ugrave", 0x00F9),
+    (b"uml", 0x00A8),
+    (b"upsih", 0x03D2),
+    (b"upsilon", 0x03C5),
+    (b"uuml", 0x00FC),
+    (b"weierp", 0x2118),
+    (b"xi", 0x03BE),
+    (b"yacute", 0x00FD),
+    (b"yen", 0x00A5),
+    (b"yuml", 0x00FF),
+    (b"zeta", 0x03B6),
+    (b"zwj", 0x200D),
+    (b"zwnj", 0x200C),
+];
+
+/// The characters that the references from `&#128;' to `&#159;' stand
+/// for, which are those of the bytes of windows-1252, as in HTML5.
+/// Zero means the reference is invalid.
+const WINDOWS_1252: [Codepoint; 32] = [
+    0x20AC, 0, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6, 0x2030, 0x0160, 0x2039,
+    0x0152, 0, 0x017D, 0, 0, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014, 0x02DC,
+    0x2122, 0x0161, 0x203A, 0x0153, 0, 0x017E, 0x0178,
+];
+
+fn is_ascii_digit(c: Codepoint, radix: u32) -> bool {
+    c < 0x80 && (c as u8 as char).is_digit(radix)
+}
+
+fn is_ascii_alphanumeric(c: Codepoint) -> bool {
+    c < 0x80 && (c as u8).is_ascii_alphanumeric()
+}
+
+/// Return the character that the numeric character reference with
+/// VALUE stands for.
+fn numeric_reference(value: u32) -> Codepoint {
+    match value {
+        0x80..=0x9F => match WINDOWS_1252[(value - 0x80) as usize] {
+            0 => REPLACEMENT_CHARACTER,
+            c => c,
+        },
+        0 | 0xD800..=0xDFFF => REPLACEMENT_CHARACTER,
+        c if c > 0x10_FFFF => REPLACEMENT_CHARACTER,
+        c => c,
+    }
+}
+
+/// Parse the character reference at the start of TEXT, which is the
+/// text after a `&'.  Return the character it stands for and its length,
+/// or None if it is not a reference.
+fn parse_reference(text: &[Codepoint]) -> Option<(Codepoint, usize)> {
+    if text.first() == Some(&Codepoint::from(b'#')) {
+        let (radix, start) = match text.get(1) {
+            Some(&c) if c == Codepoint::from(b'x') || c == Codepoint::from(b'X') => (16, 2),
+            _ => (10, 1),
+        };
+        let digits = text[start..]
+            .iter()
+            .take_while(|&&c| is_ascii_digit(c, radix))
+            .count();
+        if digits == 0 {
+            return None;
+        }
+        // Saturate, so that huge values are invalid.
+        let value = text[start..start + digits].iter().fold(0u32, |n, &c| {
+            let digit = (c as u8 as char).to_digit(radix).unwrap_or(0);
+            n.saturating_mul(radix).saturating_add(digit)
+        });
+        let mut length = start + digits;
+        if text.get(length) == Some(&Codepoint::from(b';')) {
+            length += 1;
+        }
+        Some((numeric_reference(value), length))
+    } else {
+        let name_length = text
+            .iter()
+            .take(MAX_ENTITY_NAME + 1)
+            .take_while(|&&c| is_ascii_alphanumeric(c))
+            .count();
+        if name_length == 0
+            || name_length > MAX_ENTITY_NAME
+            || text.get(name_length) != Some(&Codepoint::from(b';'))
+        {
+            return None;
+        }
+        let name: Vec<u8> = text[..name_length].iter().map(|&c| c as u8).collect();
+        let index = ENTITIES
+            .binary_search_by(|&(entity, _)| entity.cmp(&name[..]))
+            .ok()?;
+        Some((ENTITIES[index].1, name_length + 1))
+    }
+}
+
+/// Return TEXT with its character references replaced by the characters
+/// they stand for, or None if it has none.
+fn decode_entities(text: &[Codepoint]) -> Option<Vec<Codepoint>> {
+    let mut decoded = Vec::new();
+    let mut changed = false;
+    let mut i = 0;
+    while i < text.len() {
+        let c = text[i];
+        i += 1;
+        if c == Codepoint::from(b'&') {
+            if let Some((c, length)) = parse_reference(&text[i..]) {
+                decoded.push(c);
+                i += length;
+                changed = true;
+                continue;
+            }
+        }
+        decoded.push(c);
+    }
+    if changed {
+        Some(decoded)
+    } else {
+        None
+    }
+}
+
+/// Replace the character references in STRING with their characters.
+/// Both numeric references, like `&#233;' and `&#xE9;', and the named
+/// references of HTML 4, like `&eacute;', are recognized.  References
+/// to invalid characters are replaced by U+FFFD, and `&' followed by
+/// anything else is left alone.  Return STRING itself if it has no
+/// references, and a new string without text properties otherwise.
+#[lisp_fn]
+pub fn html_decode_entities(string: LispStringRef) -> LispObject {
+    let multibyte = string.is_multibyte();
+    let text: Vec<Codepoint> = string
+        .chars()
+        .map(|c| {
+            if multibyte || c < 0x80 {
+                c
+            } else {
+                raw_byte_codepoint(c as u8)
+            }
+        })
+        .collect();
+    let decoded = match decode_entities(&text) {
+        Some(decoded) => decoded,
+        None => return string.into(),
+    };
+    let mut bytes = Vec::with_capacity(decoded.len());
+    let mut buf = [0; MAX_MULTIBYTE_LENGTH];
+    for c in decoded {
+        let len = write_codepoint(&mut buf, c);
+        bytes.extend_from_slice(&buf[..len]);
+    }
+    make_lisp_string(&bytes, true)
+}
+
+fn is_html_space(c: Codepoint) -> bool {
+    c == Codepoint::from(b' ')
+        || c == Codepoint::from(b'\t')
+        || c == Codepoint::from(b'\n')
+        || c == Codepoint::from(b'\r')
+}
+
+/// A word of the text to fill, as the character positions of its start
+/// and end, and of the whitespace after it, if any.
+struct Span {
+    start: usize,
+    end: usize,
+    space: Option<usize>,
+}
+
+/// Split TEXT into the words between which lines can be broken.
+fn split_words(text: &[Codepoint]) -> (Vec<Word>, Vec<Span>) {
+    let mut words = Vec::new();
+    let mut spans = Vec::new();
+    let mut i = text.iter().take_while(|&&c| is_html_space(c)).count();
+    while i < text.len() {
+        let start = i;
+        i += 1;
+        while i < text.len()
+            && !is_html_space(text[i])
+            && !breaks_between(text[i - 1], text[i], true)
+        {
+            i += 1;
+        }
+        let end = i;
+        while i < text.len() && is_html_space(text[i]) {
+            i += 1;
+        }
+        let space = if i > end { Some(end) } else { None };
+        words.push(Word {
+            width: text[start..end].iter().map(|&c| char_width(c)).sum(),
+            space: if space.is_some() { 1 } else { 0 },
+            breakable: i < text.len(),
+        });
+        spans.push(Span { start, end, space });
+    }
+    (words, spans)
+}
+
+fn substring(string: LispObject, from: usize, to: usize) -> LispObject {
+    unsafe {
+        Fsubstring(
+            string,
+            LispObject::from(from as EmacsInt),
+            LispObject::from(to as EmacsInt),
+        )
+    }
+}
+
+/// Fill the text of RUNS into lines of WIDTH columns, as shr does.
+/// RUNS is a list of strings, the runs of text of a paragraph, which are
+/// concatenated.  Each stretch of whitespace in the text becomes a single
+/// space, or a line break, and the whitespace at the start and end of the
+/// text is dropped.  The first line has room for FIRST columns, which
+/// defaults to WIDTH.  Lines are broken at spaces and next to
+/// characters of the category `|', like those of Chinese and Japanese,
+/// obeying the kinsoku rules.  A word that does not fit on a line of its
+/// own gets a line anyway.  Character widths are as for `char-width'.
+///
+/// Return the list of lines, as strings without newlines.  They keep the
+/// text properties of RUNS; a space that replaces other whitespace has
+/// the properties of its first character.
+#[lisp_fn(min = "2")]
+pub fn shr_fill_text(runs: LispObject, width: EmacsInt, first: Option<EmacsInt>) -> LispObject {
+    let mut strings: Vec<LispObject> = runs
+        .iter_cars()
+        .map(|run| run.as_string_or_error().into())
+        .collect();
+    let text_obj = concat(&mut strings);
+    let text: Vec<Codepoint> = text_obj.as_string_or_error().chars().collect();
+    let (words, spans) = split_words(&text);
+    if words.is_empty() {
+        return Qnil;
+    }
+
+    let room = |width: EmacsInt| width.max(0) as usize;
+    let breaks = greedy_breaks(&words, room(first.unwrap_or(width)), room(width));
+    let mut lines = Vec::with_capacity(breaks.len() + 1);
+    let mut line_start = 0;
+    for line_end in breaks.into_iter().chain(Some(words.len())) {
+        let mut pieces = Vec::new();
+        for (k, span) in spans[line_start..line_end].iter().enumerate() {
+            pieces.push(substring(text_obj, span.start, span.end));
+            if line_start + k + 1 < line_end {
+                if let Some(space) = span.space {
+                    let piece = substring(text_obj, space, space + 1);
+                    if text[space] != Codepoint::from(b' ') {
+                        aset(piece, 0, LispObject::from(EmacsInt::from(b' ')));
+                    }
+                    pieces.push(piece);
+                }
+            }
+        }
+        lines.push(concat(&mut pieces));
+        line_start = line_end;
+    }
+    LispObject::from(lines)
+}
+
+include!(concat!(env!("OUT_DIR"), "/shr_exports.rs"));
+
+#[cfg(test)]
+fn chars(text: &str) -> Vec<Codepoint> {
+    text.chars().map(|c| c as Codepoint).collect()
+}
+
+#[cfg(test)]
+fn decode(text: &str) -> Option<String> {
+    decode_entities(&chars(text)).map(|decoded| {
+        decoded
+            .into_iter()
+            .filter_map(std::char::from_u32)
+            .collect()
+    })
+}
+
+#[test]
+fn test_entities_sorted() {
+    assert!(ENTITIES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    assert!(ENTITIES
+        .iter()
+        .all(|&(name, _)| name.len() <= MAX_ENTITY_NAME));
+}
+
+#[test]
+fn test_decode_entities() {
+    assert_eq!(decode("no references"), None);
+    assert_eq!(decode("a &amp; b &lt;c&gt;").unwrap(), "a & b <c>");
+    assert_eq!(decode("caf&eacute; &#233; &#xe9;").unwrap(), "café é é");
+    assert_eq!(decode("&AElig;&aelig;&apos;").unwrap(), "Ææ'");
+    assert_eq!(decode("&#65&#x42;").unwrap(), "AB");
+    assert_eq!(
+        decode("&#150; &#0; &#99999999999;").unwrap(),
+        "– \u{FFFD} \u{FFFD}"
+    );
+}
+
+#[test]
+fn test_decode_invalid_references() {
+    assert_eq!(decode("&"), None);
+    assert_eq!(decode("AT&T &nosuchentity; &amp &#; &#x;"), None);
+}
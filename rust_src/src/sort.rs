@@ -0,0 +1,423 @@
+//! Sorting the lines of a region.
+//!
+//! This is the core of `sort-lines', `sort-fields' and
+//! `sort-numeric-fields'.  The region is split into lines without
+//! making a string for each of them, the lines are sorted with a stable
+//! sort, and the sorted text replaces the region in a single change.
+
+use std::cmp::Ordering;
+
+use libc::ptrdiff_t;
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    buffers::{erase_buffer, set_buffer_multibyte, validate_region, LispBufferRef},
+    editfns::{buffer_chars, save_excursion_save},
+    eval::unbind_to,
+    lisp::{defsubr, LispObject},
+    marker::buf_charpos_to_bytepos,
+    multibyte::Codepoint,
+    remacs_sys::{
+        insert_from_buffer, make_buffer_string, record_unwind_current_buffer,
+        record_unwind_protect, replace_range, save_excursion_restore, set_buffer_internal_1,
+        set_point, specbind, EmacsInt,
+    },
+    remacs_sys::{Fget_buffer_create, Fkill_buffer},
+    remacs_sys::{Qcase_fold_search, Qnil, Qstringp, Qt},
+    search::{re_search_forward, MatchData, SaveMatchData},
+    threads::{c_specpdl_index, ThreadState},
+};
+
+/// The part of each line by which the lines are sorted.
+enum SortKey {
+    /// The whole line.
+    Line,
+    /// A field, counting from 1 at the left or from -1 at the right.
+    Field(EmacsInt),
+    /// The first match for a regexp.
+    Regexp(LispObject),
+}
+
+/// A line of the region, and its key, as indexes into the text of the
+/// region.
+struct Record {
+    start: usize,
+    end: usize,
+    key_start: usize,
+    key_end: usize,
+}
+
+/// Return the bounds of the lines of TEXT, without their newlines.
+/// There is no empty line after a final newline.
+fn split_lines(text: &[Codepoint]) -> Vec<(usize, usize)> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    while start < text.len() {
+        let end = text[start..]
+            .iter()
+            .position(|&c| c == Codepoint::from(b'\n'))
+            .map_or(text.len(), |n| start + n);
+        lines.push((start, end));
+        start = end + 1;
+    }
+    lines
+}
+
+fn is_blank(c: Codepoint) -> bool {
+    c == Codepoint::from(b' ') || c == Codepoint::from(b'\t')
+}
+
+/// Return the bounds of field FIELD of the line of TEXT between START
+/// and END, as `sort-skip-fields' finds it, or None if the line has too
+/// few fields.  Fields are separated by spaces and tabs.
+fn field_bounds(
+    text: &[Codepoint],
+    start: usize,
+    end: usize,
+    field: EmacsInt,
+) -> Option<(usize, usize)> {
+    let key_start = if field >= 0 {
+        let mut i = start;
+        for _ in 1..field {
+            while i < end && is_blank(text[i]) {
+                i += 1;
+            }
+            while i < end && !is_blank(text[i]) {
+                i += 1;
+            }
+        }
+        while i < end && is_blank(text[i]) {
+            i += 1;
+        }
+        if i == end {
+            return None;
+        }
+        i
+    } else {
+        let mut i = end;
+        for _ in 1..-field {
+            while i > start && is_blank(text[i - 1]) {
+                i -= 1;
+            }
+            while i > start && !is_blank(text[i - 1]) {
+                i -= 1;
+            }
+        }
+        while i > start && is_blank(text[i - 1]) {
+            i -= 1;
+        }
+        if i == start {
+            return None;
+        }
+        while i > start && !is_blank(text[i - 1]) {
+            i -= 1;
+        }
+        i
+    };
+    let mut key_end = key_start;
+    while key_end < end && !is_blank(text[key_end]) {
+        key_end += 1;
+    }
+    Some((key_start, key_end))
+}
+
+/// Return the number at the start of TEXT, like `string-to-number' in
+/// BASE.  Base 10 numbers can have a fraction and an exponent.
+fn parse_number(text: &str, base: u32) -> f64 {
+    let text = text.trim_start_matches(|c| c == ' ' || c == '\t');
+    let (sign, text) = if text.starts_with('-') {
+        (-1.0, &text[1..])
+    } else if text.starts_with('+') {
+        (1.0, &text[1..])
+    } else {
+        (1.0, text)
+    };
+
+    if base != 10 {
+        let value = text
+            .chars()
+            .map(|c| c.to_digit(base))
+            .take_while(Option::is_some)
+            .fold(0.0, |value, digit| {
+                value * f64::from(base) + f64::from(digit.unwrap_or(0))
+            });
+        return sign * value;
+    }
+
+    let bytes = text.as_bytes();
+    let digits = |from: usize| {
+        bytes[from..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count()
+    };
+    let mut len = digits(0);
+    let mut mantissa_digits = len;
+    if bytes.get(len) == Some(&b'.') {
+        let fraction = digits(len + 1);
+        mantissa_digits += fraction;
+        len += 1 + fraction;
+    }
+    if mantissa_digits == 0 {
+        return 0.0;
+    }
+    if let Some(b'e') | Some(b'E') = bytes.get(len) {
+        let mut exponent = len + 1;
+        if let Some(b'+') | Some(b'-') = bytes.get(exponent) {
+            exponent += 1;
+        }
+        let exponent_digits = digits(exponent);
+        if exponent_digits > 0 {
+            len = exponent + exponent_digits;
+        }
+    }
+    sign * text[..len].parse::<f64>().unwrap_or(0.0)
+}
+
+/// Return the key of a line for `sort-numeric-fields': the number at
+/// the start of TEXT, which is hexadecimal after "0x" and octal after
+/// "0", and in BASE otherwise.
+fn numeric_key(text: &[Codepoint], base: u32) -> f64 {
+    let text: String = text
+        .iter()
+        .filter_map(|&c| std::char::from_u32(c))
+        .collect();
+    let lower = text.to_ascii_lowercase();
+    let is_digit_in = |s: &str, base: u32| s.chars().next().map_or(false, |c| c.is_digit(base));
+    if lower.starts_with("0x") && is_digit_in(&lower[2..], 16) {
+        parse_number(&text[2..], 16)
+    } else if text.starts_with('0') && is_digit_in(&text[1..], 8) {
+        parse_number(&text[1..], 8)
+    } else {
+        parse_number(&text, base)
+    }
+}
+
+/// Return the bounds of the first match for REGEXP in the line of the
+/// region between START and END, or an empty key at the start of the
+/// line if there is none.  BEG is the start of the region.
+fn regexp_bounds(regexp: LispObject, beg: ptrdiff_t, start: usize, end: usize) -> (usize, usize) {
+    unsafe { set_point(beg + start as ptrdiff_t) };
+    let bound = LispObject::from(beg + end as ptrdiff_t);
+    if re_search_forward(regexp, bound, Qt, Qnil).is_nil() {
+        return (start, start);
+    }
+    MatchData::register(0).map_or((start, start), |(match_beg, match_end)| {
+        ((match_beg - beg) as usize, (match_end - beg) as usize)
+    })
+}
+
+/// Return the sorted text of the region from BEG to END of BUFFER, with
+/// the lines in ORDER, as a string with the text properties of the
+/// lines.  The newlines between the lines stay in place.
+fn reordered_text(
+    mut buffer: LispBufferRef,
+    beg: ptrdiff_t,
+    end: ptrdiff_t,
+    records: &[Record],
+    order: &[usize],
+) -> LispObject {
+    let count = c_specpdl_index();
+    unsafe {
+        record_unwind_current_buffer();
+        let mut temp = Fget_buffer_create(LispObject::from(" *sort*")).as_buffer_or_error();
+        set_buffer_internal_1(temp.as_mut());
+        erase_buffer();
+        set_buffer_multibyte(LispObject::from(buffer.multibyte_characters_enabled()));
+
+        let mut insert = |from: usize, to: usize| {
+            insert_from_buffer(
+                buffer.as_mut(),
+                beg + from as ptrdiff_t,
+                (to - from) as ptrdiff_t,
+                false,
+            )
+        };
+        let mut last = 0;
+        for (record, &sorted) in records.iter().zip(order) {
+            insert(last, record.start);
+            insert(records[sorted].start, records[sorted].end);
+            last = record.end;
+        }
+        insert(last, (end - beg) as usize);
+
+        let text = make_buffer_string(temp.begv, temp.zv, true);
+        unbind_to(count, Qnil);
+        Fkill_buffer(temp.as_lisp_obj());
+        text
+    }
+}
+
+/// Sort the lines in the region between BEG and END.
+/// If REVERSE is non-nil, sort them in descending order.  Lines that
+/// compare equal keep their order.
+///
+/// KEY says which part of each line it is sorted by: nil for the whole
+/// line, an integer N for the Nth field, counting from 1 at the left or
+/// from -1 at the right, and a regexp for the first match for it in the
+/// line.  Fields are separated by spaces and tabs; 0 means 1.
+///
+/// If NUMERIC is non-nil, the keys are compared as numbers, which are
+/// hexadecimal after "0x", octal after "0", and in base NUMERIC
+/// otherwise.  Else they are compared as text, ignoring case if
+/// FOLD-CASE is non-nil.
+///
+/// The sorted lines replace the region in a single change, unless they
+/// are already in order.
+#[lisp_fn(min = "2")]
+pub fn sort_lines_native(
+    mut beg: LispObject,
+    mut end: LispObject,
+    reverse: bool,
+    key: LispObject,
+    numeric: Option<EmacsInt>,
+    fold_case: bool,
+) {
+    unsafe { validate_region(&mut beg, &mut end) };
+    let beg = beg.as_fixnum_or_error() as ptrdiff_t;
+    let end = end.as_fixnum_or_error() as ptrdiff_t;
+    let mut buffer = ThreadState::current_buffer();
+
+    let key = if key.is_nil() {
+        SortKey::Line
+    } else if let Some(field) = key.as_fixnum() {
+        SortKey::Field(if field == 0 { 1 } else { field })
+    } else if key.is_string() {
+        SortKey::Regexp(key)
+    } else {
+        wrong_type!(Qstringp, key);
+    };
+    let base = match numeric {
+        Some(base) if base < 2 || base > 16 => {
+            args_out_of_range!(LispObject::from(base), Qnil);
+        }
+        Some(base) => Some(base as u32),
+        None => None,
+    };
+
+    let text = unsafe {
+        buffer_chars(
+            buffer,
+            buf_charpos_to_bytepos(buffer.as_mut(), beg),
+            buf_charpos_to_bytepos(buffer.as_mut(), end),
+        )
+    };
+
+    // Searching for the keys must not change point or the match data.
+    let _match_data = SaveMatchData::save();
+    unsafe {
+        record_unwind_protect(Some(save_excursion_restore), save_excursion_save());
+        specbind(Qcase_fold_search, LispObject::from(fold_case));
+    }
+
+    let mut records = Vec::new();
+    for (start, end) in split_lines(&text) {
+        let (key_start, key_end) = match key {
+            SortKey::Line => (start, end),
+            SortKey::Field(field) => match field_bounds(&text, start, end, field) {
+                Some(bounds) => bounds,
+                None => {
+                    let line: String = text[start..end]
+                        .iter()
+                        .filter_map(|&c| std::char::from_u32(c))
+                        .collect();
+                    error!("Line has too few fields: {}", line);
+                }
+            },
+            SortKey::Regexp(regexp) => regexp_bounds(regexp, beg, start, end),
+        };
+        records.push(Record {
+            start,
+            end,
+            key_start,
+            key_end,
+        });
+    }
+
+    let key_text = |record: &Record| &text[record.key_start..record.key_end];
+    let numbers: Vec<f64> = base.map_or_else(Vec::new, |base| {
+        records
+            .iter()
+            .map(|record| numeric_key(&text[record.key_start..record.end], base))
+            .collect()
+    });
+    let canon_table = if fold_case {
+        buffer.case_canon_table_.as_char_table()
+    } else {
+        None
+    };
+    let canon = |c: Codepoint| {
+        canon_table.map_or(c, |table| {
+            let canonical = table.get(c as isize);
+            if canonical.is_character() {
+                canonical.as_fixnum_or_error() as Codepoint
+            } else {
+                c
+            }
+        })
+    };
+
+    let mut order: Vec<usize> = (0..records.len()).collect();
+    order.sort_by(|&a, &b| {
+        let ordering = if base.is_some() {
+            numbers[a]
+                .partial_cmp(&numbers[b])
+                .unwrap_or(Ordering::Equal)
+        } else {
+            let a = key_text(&records[a]).iter().map(|&c| canon(c));
+            let b = key_text(&records[b]).iter().map(|&c| canon(c));
+            a.cmp(b)
+        };
+        if reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    if order.iter().enumerate().all(|(i, &sorted)| i == sorted) {
+        return;
+    }
+
+    let sorted = reordered_text(buffer, beg, end, &records, &order);
+    unsafe { replace_range(beg, end, sorted, true, false, true, false) };
+}
+
+include!(concat!(env!("OUT_DIR"), "/sort_exports.rs"));
+
+#[test]
+fn test_split_lines() {
+    let text: Vec<Codepoint> = "b\n\na\nc".chars().map(Codepoint::from).collect();
+    assert_eq!(split_lines(&text), vec![(0, 1), (2, 2), (3, 4), (5, 6)]);
+    let text: Vec<Codepoint> = "b\na\n".chars().map(Codepoint::from).collect();
+    assert_eq!(split_lines(&text), vec![(0, 1), (2, 3)]);
+    assert_eq!(split_lines(&[]), vec![]);
+}
+
+#[test]
+fn test_field_bounds() {
+    let text: Vec<Codepoint> = "  one two\tthree ".chars().map(Codepoint::from).collect();
+    let len = text.len();
+    assert_eq!(field_bounds(&text, 0, len, 1), Some((2, 5)));
+    assert_eq!(field_bounds(&text, 0, len, 3), Some((10, 15)));
+    assert_eq!(field_bounds(&text, 0, len, 4), None);
+    assert_eq!(field_bounds(&text, 0, len, -1), Some((10, 15)));
+    assert_eq!(field_bounds(&text, 0, len, -3), Some((2, 5)));
+    assert_eq!(field_bounds(&text, 0, len, -4), None);
+}
+
+#[test]
+fn test_numeric_key() {
+    let key = |s: &str| numeric_key(&s.chars().map(Codepoint::from).collect::<Vec<_>>(), 10);
+    assert_eq!(key("42 rest"), 42.0);
+    assert_eq!(key("-1.5e2x"), -150.0);
+    assert_eq!(key("0x1F"), 31.0);
+    assert_eq!(key("017"), 15.0);
+    assert_eq!(key("08"), 8.0);
+    assert_eq!(key("abc"), 0.0);
+    assert_eq!(key(".5"), 0.5);
+    assert_eq!(
+        numeric_key(&['f' as Codepoint, 'f' as Codepoint], 16),
+        255.0
+    );
+}
@@ -0,0 +1,721 @@
+//! Reading Hunspell dictionaries.
+//!
+//! Only the parts of the format that matter for checking words and
+//! suggesting corrections are supported: affix rules with their
+//! conditions and continuation classes, flag aliases, NEEDAFFIX,
+//! FORBIDDENWORD, TRY, REP and WORDCHARS.  Compounding rules and
+//! morphological data are ignored.  The affixes are applied when the
+//! dictionary is loaded, so that checking a word is a hash lookup.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+type Flag = u32;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FlagType {
+    /// Each character is a flag.
+    Char,
+    /// Each pair of characters is a flag.
+    Long,
+    /// Flags are decimal numbers separated by commas.
+    Num,
+}
+
+fn parse_flags(s: &str, flag_type: FlagType) -> Vec<Flag> {
+    match flag_type {
+        FlagType::Char => s.chars().map(|c| c as Flag).collect(),
+        FlagType::Long => {
+            let chars: Vec<char> = s.chars().collect();
+            chars
+                .chunks(2)
+                .map(|pair| pair.iter().fold(0, |flag, &c| (flag << 16) | c as Flag))
+                .collect()
+        }
+        FlagType::Num => s.split(',').filter_map(|n| n.trim().parse().ok()).collect(),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ConditionPart {
+    Any,
+    Char(char),
+    /// A character class; true if it is negated.
+    Class(Vec<char>, bool),
+}
+
+impl ConditionPart {
+    fn matches(&self, c: char) -> bool {
+        match *self {
+            ConditionPart::Any => true,
+            ConditionPart::Char(d) => c == d,
+            ConditionPart::Class(ref chars, negated) => chars.contains(&c) != negated,
+        }
+    }
+}
+
+/// The condition of an affix rule: a simple regular expression that
+/// the start (for prefixes) or the end (for suffixes) of a word must
+/// match.
+#[derive(Debug, PartialEq)]
+struct Condition(Vec<ConditionPart>);
+
+impl Condition {
+    fn parse(s: &str) -> Condition {
+        let mut parts = Vec::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            parts.push(match c {
+                '.' => ConditionPart::Any,
+                '[' => {
+                    let mut class: Vec<char> = chars.by_ref().take_while(|&c| c != ']').collect();
+                    let negated = class.first() == Some(&'^');
+                    if negated {
+                        class.remove(0);
+                    }
+                    ConditionPart::Class(class, negated)
+                }
+                c => ConditionPart::Char(c),
+            });
+        }
+        // A lone "." matches every word.
+        if parts == [ConditionPart::Any] {
+            parts.clear();
+        }
+        Condition(parts)
+    }
+
+    fn matches_start(&self, word: &[char]) -> bool {
+        word.len() >= self.0.len() && self.0.iter().zip(word).all(|(part, &c)| part.matches(c))
+    }
+
+    fn matches_end(&self, word: &[char]) -> bool {
+        word.len() >= self.0.len()
+            && self
+                .0
+                .iter()
+                .rev()
+                .zip(word.iter().rev())
+                .all(|(part, &c)| part.matches(c))
+    }
+}
+
+struct Affix {
+    strip: Vec<char>,
+    add: Vec<char>,
+    /// Flags of the affixes that can be added to the result.
+    continuation: Vec<Flag>,
+    condition: Condition,
+}
+
+impl Affix {
+    fn apply_prefix(&self, word: &[char]) -> Option<Vec<char>> {
+        if !word.starts_with(&self.strip) || !self.condition.matches_start(word) {
+            return None;
+        }
+        let mut result = self.add.clone();
+        result.extend_from_slice(&word[self.strip.len()..]);
+        Some(result)
+    }
+
+    fn apply_suffix(&self, word: &[char]) -> Option<Vec<char>> {
+        if !word.ends_with(&self.strip) || !self.condition.matches_end(word) {
+            return None;
+        }
+        let mut result = word[..word.len() - self.strip.len()].to_vec();
+        result.extend_from_slice(&self.add);
+        Some(result)
+    }
+}
+
+#[derive(Default)]
+struct AffixClass {
+    /// Whether prefixes and suffixes of classes that allow it can be
+    /// combined.
+    cross_product: bool,
+    entries: Vec<Affix>,
+}
+
+/// The rules of an affix file that are needed to expand the words of
+/// a dictionary.
+struct AffixRules {
+    flag_type: FlagType,
+    flag_aliases: Vec<Vec<Flag>>,
+    prefixes: HashMap<Flag, AffixClass>,
+    suffixes: HashMap<Flag, AffixClass>,
+    need_affix: Option<Flag>,
+    forbidden: Option<Flag>,
+    try_chars: Vec<char>,
+    replacements: Vec<(String, String)>,
+    word_chars: Vec<char>,
+}
+
+impl AffixRules {
+    fn parse(aff: &str) -> AffixRules {
+        let mut rules = AffixRules {
+            flag_type: FlagType::Char,
+            flag_aliases: Vec::new(),
+            prefixes: HashMap::new(),
+            suffixes: HashMap::new(),
+            need_affix: None,
+            forbidden: None,
+            try_chars: Vec::new(),
+            replacements: Vec::new(),
+            word_chars: Vec::new(),
+        };
+        // The number of entries still expected for each affix class.
+        let mut pending: HashMap<(bool, Flag), usize> = HashMap::new();
+        let mut aliases_left = 0;
+        let mut replacements_left = 0;
+
+        for line in aff.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (keyword, arg) = match (fields.get(0), fields.get(1)) {
+                (Some(&keyword), Some(&arg)) => (keyword, arg),
+                _ => continue,
+            };
+            match keyword {
+                "FLAG" => {
+                    rules.flag_type = match arg {
+                        "long" => FlagType::Long,
+                        "num" => FlagType::Num,
+                        _ => FlagType::Char,
+                    }
+                }
+                // The first AF and REP lines give the number of lines
+                // that follow.
+                "AF" if aliases_left == 0 && rules.flag_aliases.is_empty() => {
+                    aliases_left = arg.parse().unwrap_or(0);
+                }
+                "AF" if aliases_left > 0 => {
+                    rules.flag_aliases.push(parse_flags(arg, rules.flag_type));
+                    aliases_left -= 1;
+                }
+                "NEEDAFFIX" | "PSEUDOROOT" => rules.need_affix = rules.flag(arg),
+                "FORBIDDENWORD" => rules.forbidden = rules.flag(arg),
+                "TRY" => rules.try_chars = arg.chars().collect(),
+                "WORDCHARS" => rules.word_chars = arg.chars().collect(),
+                "REP" if replacements_left == 0 && rules.replacements.is_empty() => {
+                    replacements_left = arg.parse().unwrap_or(0);
+                }
+                "REP" if replacements_left > 0 && fields.len() >= 3 => {
+                    let to = fields[2];
+                    rules
+                        .replacements
+                        .push((arg.replace('_', " "), to.replace('_', " ")));
+                    replacements_left -= 1;
+                }
+                "PFX" | "SFX" if fields.len() >= 4 => {
+                    let is_prefix = keyword == "PFX";
+                    let flag = match rules.flag(arg) {
+                        Some(flag) => flag,
+                        None => continue,
+                    };
+                    let entry = if pending.get(&(is_prefix, flag)).map_or(true, |&n| n == 0) {
+                        // The header of a class: cross product and count.
+                        pending.insert((is_prefix, flag), fields[3].parse().unwrap_or(0));
+                        None
+                    } else {
+                        *pending.get_mut(&(is_prefix, flag)).unwrap() -= 1;
+                        let (add, continuation) = match fields[3].find('/') {
+                            Some(slash) => {
+                                (&fields[3][..slash], rules.flags(&fields[3][slash + 1..]))
+                            }
+                            None => (fields[3], Vec::new()),
+                        };
+                        let chars = |s: &str| {
+                            if s == "0" {
+                                Vec::new()
+                            } else {
+                                s.chars().collect()
+                            }
+                        };
+                        Some(Affix {
+                            strip: chars(fields[2]),
+                            add: chars(add),
+                            continuation,
+                            condition: Condition::parse(fields.get(4).cloned().unwrap_or(".")),
+                        })
+                    };
+                    let classes = if is_prefix {
+                        &mut rules.prefixes
+                    } else {
+                        &mut rules.suffixes
+                    };
+                    let class = classes.entry(flag).or_insert_with(AffixClass::default);
+                    match entry {
+                        Some(affix) => class.entries.push(affix),
+                        None => class.cross_product = fields[2] == "Y",
+                    }
+                }
+                _ => {}
+            }
+        }
+        rules
+    }
+
+    fn flag(&self, s: &str) -> Option<Flag> {
+        parse_flags(s, self.flag_type).first().cloned()
+    }
+
+    /// Parse the flags S of a dictionary word or affix, which can be
+    /// the number of a flag alias.
+    fn flags(&self, s: &str) -> Vec<Flag> {
+        if self.flag_aliases.is_empty() {
+            return parse_flags(s, self.flag_type);
+        }
+        s.parse::<usize>()
+            .ok()
+            .and_then(|n| n.checked_sub(1))
+            .and_then(|i| self.flag_aliases.get(i))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn has(&self, flags: &[Flag], flag: Option<Flag>) -> bool {
+        flag.map_or(false, |flag| flags.contains(&flag))
+    }
+
+    /// Call F with every form of WORD that FLAGS allow.
+    fn expand<F: FnMut(Vec<char>)>(&self, word: &[char], flags: &[Flag], mut f: F) {
+        if !self.has(flags, self.need_affix) {
+            f(word.to_vec());
+        }
+
+        let mut suffixed = Vec::new();
+        for flag in flags {
+            let class = match self.suffixes.get(flag) {
+                Some(class) => class,
+                None => continue,
+            };
+            for affix in &class.entries {
+                let form = match affix.apply_suffix(word) {
+                    Some(form) => form,
+                    None => continue,
+                };
+                // Suffixes that can follow this one.
+                for flag in &affix.continuation {
+                    for second in self.suffixes.get(flag).iter().flat_map(|c| &c.entries) {
+                        if let Some(form) = second.apply_suffix(&form) {
+                            f(form);
+                        }
+                    }
+                }
+                if class.cross_product {
+                    suffixed.push(form.clone());
+                }
+                if !self.has(&affix.continuation, self.need_affix) {
+                    f(form);
+                }
+            }
+        }
+
+        for flag in flags {
+            let class = match self.prefixes.get(flag) {
+                Some(class) => class,
+                None => continue,
+            };
+            for affix in &class.entries {
+                if let Some(form) = affix.apply_prefix(word) {
+                    f(form);
+                    if class.cross_product {
+                        for form in &suffixed {
+                            if let Some(form) = affix.apply_prefix(form) {
+                                f(form);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Decode the contents of a dictionary file in ENCODING.
+fn decode(bytes: &[u8], encoding: &str) -> String {
+    let text = match encoding.to_uppercase().as_str() {
+        "ISO8859-1" | "ISO-8859-1" | "LATIN1" => bytes.iter().map(|&b| char::from(b)).collect(),
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    };
+    text.trim_start_matches('\u{feff}').to_string()
+}
+
+/// Return the encoding named by the SET line of the affix file AFF.
+fn affix_encoding(aff: &[u8]) -> String {
+    aff.split(|&b| b == b'\n')
+        .filter_map(|line| {
+            let line = String::from_utf8_lossy(line);
+            let mut fields = line.split_whitespace();
+            match (fields.next(), fields.next()) {
+                (Some("SET"), Some(encoding)) => Some(encoding.to_string()),
+                _ => None,
+            }
+        })
+        .next()
+        .unwrap_or_else(|| "ISO8859-1".to_string())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Case {
+    Lower,
+    /// The first letter is upper case, the others lower case.
+    Capitalized,
+    Upper,
+    Mixed,
+}
+
+fn case_of(word: &str) -> Case {
+    let mut chars = word.chars().filter(|c| c.is_alphabetic());
+    let first_upper = match chars.next() {
+        Some(c) => c.is_uppercase(),
+        None => return Case::Lower,
+    };
+    let (mut upper, mut lower) = (0, 0);
+    for c in chars {
+        if c.is_uppercase() {
+            upper += 1;
+        } else if c.is_lowercase() {
+            lower += 1;
+        }
+    }
+    match (first_upper, upper, lower) {
+        (false, 0, _) => Case::Lower,
+        (true, 0, _) => Case::Capitalized,
+        (true, _, 0) => Case::Upper,
+        _ => Case::Mixed,
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// A spelling dictionary.
+pub struct Dictionary {
+    words: HashSet<String>,
+    forbidden: HashSet<String>,
+    /// The characters tried when looking for corrections, the most
+    /// frequent first.
+    try_chars: Vec<char>,
+    replacements: Vec<(String, String)>,
+    word_chars: Vec<char>,
+}
+
+impl Dictionary {
+    /// Load the dictionary whose word list is the file DIC.  The affix
+    /// file is the file with the same name and the extension ".aff".
+    pub fn load(dic: &Path) -> io::Result<Dictionary> {
+        let aff = fs::read(dic.with_extension("aff"))?;
+        let dic = fs::read(dic)?;
+        let encoding = affix_encoding(&aff);
+        Ok(Dictionary::parse(
+            &decode(&aff, &encoding),
+            &decode(&dic, &encoding),
+        ))
+    }
+
+    pub fn parse(aff: &str, dic: &str) -> Dictionary {
+        let rules = AffixRules::parse(aff);
+        let mut dictionary = Dictionary {
+            words: HashSet::new(),
+            forbidden: HashSet::new(),
+            try_chars: rules.try_chars.clone(),
+            replacements: rules.replacements.clone(),
+            word_chars: rules.word_chars.clone(),
+        };
+
+        // The first line is the number of words.
+        for line in dic.lines().skip(1) {
+            let entry = line.split(|c| c == '\t' || c == ' ').next().unwrap_or("");
+            if entry.is_empty() {
+                continue;
+            }
+            // A slash that is part of the word is escaped.
+            let (word, flags) = match entry
+                .match_indices('/')
+                .find(|&(i, _)| i == 0 || entry.as_bytes()[i - 1] != b'\\')
+            {
+                Some((i, _)) => (&entry[..i], rules.flags(&entry[i + 1..])),
+                None => (entry, Vec::new()),
+            };
+            let word: Vec<char> = word.replace("\\/", "/").chars().collect();
+
+            if rules.has(&flags, rules.forbidden) {
+                dictionary.forbidden.insert(word.iter().collect());
+                continue;
+            }
+            let words = &mut dictionary.words;
+            rules.expand(&word, &flags, |form| {
+                words.insert(form.into_iter().collect());
+            });
+        }
+
+        if dictionary.try_chars.is_empty() {
+            dictionary.try_chars = dictionary.frequent_chars();
+        }
+        dictionary
+    }
+
+    /// Return the letters of the dictionary, the most frequent first.
+    fn frequent_chars(&self) -> Vec<char> {
+        let mut counts: HashMap<char, usize> = HashMap::new();
+        for c in self.words.iter().flat_map(|w| w.chars()) {
+            if c.is_lowercase() {
+                *counts.entry(c).or_insert(0) += 1;
+            }
+        }
+        let mut chars: Vec<(char, usize)> = counts.into_iter().collect();
+        chars.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        chars.into_iter().map(|(c, _)| c).collect()
+    }
+
+    /// Add WORD to the dictionary, for this session.
+    pub fn add_word(&mut self, word: &str) {
+        self.forbidden.remove(word);
+        self.words.insert(word.to_string());
+    }
+
+    /// Return true if C can be part of a word, besides letters.
+    pub fn is_word_char(&self, c: char) -> bool {
+        self.word_chars.contains(&c)
+    }
+
+    fn known(&self, word: &str) -> bool {
+        self.words.contains(word) && !self.forbidden.contains(word)
+    }
+
+    /// Return true if WORD is spelled correctly.  Words in upper case,
+    /// or with only their first letter in upper case, are also correct
+    /// if the dictionary has them in lower case.
+    pub fn check(&self, word: &str) -> bool {
+        if self.known(word) {
+            return true;
+        }
+        match case_of(word) {
+            Case::Capitalized => self.known(&word.to_lowercase()),
+            Case::Upper => {
+                let lower = word.to_lowercase();
+                self.known(&lower) || self.known(&capitalize(&lower))
+            }
+            Case::Lower | Case::Mixed => false,
+        }
+    }
+
+    /// Return the strings that differ from WORD by one edit: a
+    /// transposition, a replacement, a deletion or an insertion.
+    fn edits(&self, word: &[char]) -> Vec<Vec<char>> {
+        let mut edits = Vec::new();
+        for i in 1..word.len() {
+            if word[i - 1] != word[i] {
+                let mut edit = word.to_vec();
+                edit.swap(i - 1, i);
+                edits.push(edit);
+            }
+        }
+        for i in 0..word.len() {
+            for &c in &self.try_chars {
+                if c != word[i] {
+                    let mut edit = word.to_vec();
+                    edit[i] = c;
+                    edits.push(edit);
+                }
+            }
+        }
+        for i in 0..word.len() {
+            let mut edit = word.to_vec();
+            edit.remove(i);
+            edits.push(edit);
+        }
+        for i in 0..=word.len() {
+            for &c in &self.try_chars {
+                let mut edit = word.to_vec();
+                edit.insert(i, c);
+                edits.push(edit);
+            }
+        }
+        edits
+    }
+
+    /// Return at most MAX corrections for the misspelled WORD, the best
+    /// ones first.
+    pub fn suggest(&self, word: &str, max: usize) -> Vec<String> {
+        let case = case_of(word);
+        let lower = word.to_lowercase();
+        let mut suggestions: Vec<String> = Vec::new();
+        let add = |candidate: &str, suggestions: &mut Vec<String>| {
+            let found = if self.known(candidate) {
+                match case {
+                    Case::Capitalized => capitalize(candidate),
+                    Case::Upper => candidate.to_uppercase(),
+                    _ => candidate.to_string(),
+                }
+            } else if self.known(&capitalize(candidate)) {
+                match case {
+                    Case::Upper => candidate.to_uppercase(),
+                    _ => capitalize(candidate),
+                }
+            } else {
+                return;
+            };
+            if found != word && !suggestions.contains(&found) {
+                suggestions.push(found);
+            }
+        };
+
+        for (from, to) in &self.replacements {
+            for (i, _) in lower.match_indices(from.as_str()) {
+                let candidate = format!("{}{}{}", &lower[..i], to, &lower[i + from.len()..]);
+                // A replacement can split the word in two.
+                if candidate.split(' ').all(|part| self.check(part)) && candidate.contains(' ') {
+                    if !suggestions.contains(&candidate) {
+                        suggestions.push(candidate);
+                    }
+                } else {
+                    add(&candidate, &mut suggestions);
+                }
+            }
+        }
+
+        let chars: Vec<char> = lower.chars().collect();
+        let edits = self.edits(&chars);
+        for edit in &edits {
+            add(&edit.iter().collect::<String>(), &mut suggestions);
+        }
+
+        // Two words run together.
+        for i in 1..chars.len() {
+            let first: String = chars[..i].iter().collect();
+            let second: String = chars[i..].iter().collect();
+            if first.chars().count() > 1 && self.check(&first) && self.check(&second) {
+                let candidate = format!("{} {}", first, second);
+                if !suggestions.contains(&candidate) {
+                    suggestions.push(candidate);
+                }
+            }
+        }
+
+        // Try two edits only if one is not enough, since there are many
+        // more of them.
+        if suggestions.is_empty() && chars.len() <= 20 {
+            for edit in &edits {
+                for edit in self.edits(edit) {
+                    add(&edit.iter().collect::<String>(), &mut suggestions);
+                    if suggestions.len() >= max {
+                        break;
+                    }
+                }
+            }
+        }
+
+        suggestions.truncate(max);
+        suggestions
+    }
+}
+
+#[cfg(test)]
+const TEST_AFF: &str = "SET UTF-8
+TRY esianrtolcdugmphbyfvkwzxjq
+WORDCHARS '
+REP 1
+REP f ph
+
+PFX U Y 1
+PFX U 0 un .
+
+SFX S Y 3
+SFX S y ies [^aeiou]y
+SFX S 0 s [aeiou]y
+SFX S 0 s [^y]
+
+SFX D Y 2
+SFX D 0 ed [^e]
+SFX D 0 d e
+
+SFX X N 1
+SFX X 0 ing/S .
+
+NEEDAFFIX Z
+FORBIDDENWORD !
+";
+
+#[cfg(test)]
+const TEST_DIC: &str = "7
+happy/U
+city/S
+day/S
+lock/UDS
+Paris
+paint/XZ
+colour/!
+";
+
+#[test]
+fn test_condition() {
+    let condition = Condition::parse("[^aeiou]y");
+    let word: Vec<char> = "city".chars().collect();
+    assert!(condition.matches_end(&word));
+    let word: Vec<char> = "day".chars().collect();
+    assert!(!condition.matches_end(&word));
+    assert!(Condition::parse(".").matches_start(&[]));
+}
+
+#[test]
+fn test_check() {
+    let dictionary = Dictionary::parse(TEST_AFF, TEST_DIC);
+    for word in &[
+        "happy",
+        "unhappy",
+        "city",
+        "cities",
+        "days",
+        "lock",
+        "locked",
+        "locks",
+        "unlock",
+        "unlocks",
+        "Paris",
+        "PARIS",
+        "Happy",
+        "UNHAPPY",
+        "paintings",
+        "painting",
+    ] {
+        assert!(dictionary.check(word), "{} should be correct", word);
+    }
+    for word in &[
+        "citys", "dayies", "paris", "hapy", "paint", "colour", "unday", "HaPpy",
+    ] {
+        assert!(!dictionary.check(word), "{} should be wrong", word);
+    }
+}
+
+#[test]
+fn test_suggest() {
+    let mut dictionary = Dictionary::parse(TEST_AFF, TEST_DIC);
+    assert_eq!(dictionary.suggest("hapy", 3), vec!["happy"]);
+    assert_eq!(dictionary.suggest("Citis", 3), vec!["Cities"]);
+    assert_eq!(dictionary.suggest("paris", 3), vec!["Paris"]);
+    assert_eq!(dictionary.suggest("happycity", 3), vec!["happy city"]);
+    assert_eq!(dictionary.suggest("lcoekd", 1), vec!["locked"]);
+    assert!(dictionary.suggest("xyzzy", 3).is_empty());
+
+    dictionary.add_word("xyzzy");
+    assert!(dictionary.check("xyzzy"));
+}
+
+#[test]
+fn test_flag_types() {
+    let aff = "FLAG long\nSFX Aa Y 1\nSFX Aa 0 s .\n";
+    let dictionary = Dictionary::parse(aff, "1\ncat/Aa\n");
+    assert!(dictionary.check("cats"));
+
+    let aff = "FLAG num\nAF 1\nAF 12,3\nSFX 12 Y 1\nSFX 12 0 s .\n";
+    let dictionary = Dictionary::parse(aff, "1\ncat/1\n");
+    assert!(dictionary.check("cats"));
+}
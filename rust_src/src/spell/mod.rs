@@ -0,0 +1,263 @@
+//! Built-in spell checking.
+//!
+//! Flyspell used to send every word to an ispell, aspell or hunspell
+//! process and parse its reply.  The functions here read Hunspell
+//! dictionaries directly, so that words can be checked without a
+//! subprocess.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use libc::ptrdiff_t;
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    buffers::validate_region,
+    callproc::lisp_file_name_to_path,
+    lisp::{defsubr, LispObject},
+    multibyte::{multibyte_char_at, LispStringRef},
+    remacs_sys::buf_charpos_to_bytepos,
+    remacs_sys::{EmacsInt, Fexpand_file_name, Qnil, Qt},
+    shell::make_lisp_string,
+    threads::ThreadState,
+};
+
+mod hunspell;
+
+use self::hunspell::Dictionary;
+
+/// The number of suggestions returned by `spell-check-word'.
+const MAX_SUGGESTIONS: usize = 10;
+
+lazy_static! {
+    /// The dictionaries loaded in this session, by file name.
+    static ref DICTIONARIES: Mutex<HashMap<PathBuf, Dictionary>> = Mutex::new(HashMap::new());
+}
+
+/// Return the file name of the word list of DICTIONARY.
+fn dictionary_path(dictionary: LispObject) -> PathBuf {
+    let path = lisp_file_name_to_path(unsafe { Fexpand_file_name(dictionary, Qnil) });
+    if path.extension().map_or(false, |ext| ext == "dic") {
+        path
+    } else {
+        let mut name = path.into_os_string();
+        name.push(".dic");
+        PathBuf::from(name)
+    }
+}
+
+/// Call F with the dictionary in the file at PATH, loading it first if
+/// this is the first time it is used.
+fn with_dictionary<T, F>(path: &Path, f: F) -> T
+where
+    F: FnOnce(&mut Dictionary) -> T,
+{
+    let result = {
+        let mut dictionaries = DICTIONARIES.lock().unwrap();
+        let loaded = if dictionaries.contains_key(path) {
+            Ok(())
+        } else {
+            Dictionary::load(path).map(|dictionary| {
+                dictionaries.insert(path.to_path_buf(), dictionary);
+            })
+        };
+        loaded.map(|()| f(dictionaries.get_mut(path).unwrap()))
+    };
+
+    // Signal only after the lock is released, since `error!' doesn't
+    // return.
+    match result {
+        Ok(value) => value,
+        Err(err) => error!("Spelling dictionary {}: {}", path.display(), err),
+    }
+}
+
+/// Convert a character code to a `char', if it is a Unicode character.
+fn to_char(c: u32) -> Option<char> {
+    std::char::from_u32(c)
+}
+
+fn string_to_word(string: LispStringRef) -> String {
+    string.chars().filter_map(to_char).collect()
+}
+
+fn word_to_string(word: &str) -> LispObject {
+    make_lisp_string(word.as_bytes(), !word.is_ascii())
+}
+
+/// Split CHARS, a sequence of characters and their positions, into
+/// words.  Return the start and end positions and the text of each word.
+/// Apostrophes are part of a word only between letters, and words with
+/// digits in them are skipped.
+fn split_words<I>(chars: I, dictionary: &Dictionary) -> Vec<(ptrdiff_t, ptrdiff_t, String)>
+where
+    I: Iterator<Item = (ptrdiff_t, Option<char>)>,
+{
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let (mut start, mut end) = (0, 0);
+    let mut has_digit = false;
+    let mut apostrophe = None;
+
+    let mut finish = |word: &mut String, start, end, has_digit: bool| {
+        if !word.is_empty() && !has_digit {
+            words.push((start, end, word.clone()));
+        }
+        word.clear();
+    };
+
+    for (pos, c) in chars {
+        match c {
+            Some(c) if c.is_alphabetic() || c.is_numeric() || dictionary.is_word_char(c) => {
+                if word.is_empty() {
+                    start = pos;
+                    has_digit = false;
+                } else if let Some(a) = apostrophe.take() {
+                    word.push(a);
+                }
+                word.push(c);
+                has_digit |= c.is_numeric();
+                end = pos + 1;
+            }
+            Some(c) if (c == '\'' || c == '\u{2019}') && !word.is_empty() => {
+                if apostrophe.is_some() {
+                    finish(&mut word, start, end, has_digit);
+                }
+                apostrophe = Some(c);
+            }
+            _ => {
+                apostrophe = None;
+                finish(&mut word, start, end, has_digit);
+            }
+        }
+    }
+    finish(&mut word, start, end, has_digit);
+    words
+}
+
+/// Check the spelling of WORD in DICTIONARY.
+/// Return t if WORD is spelled correctly, or else a list of suggested
+/// corrections, the best first.  The list is empty if there is no good
+/// suggestion.
+///
+/// DICTIONARY is the file name of a Hunspell dictionary: a word list
+/// with the extension ".dic", and its affix file with the extension
+/// ".aff" in the same directory.  The extension can be omitted.  The
+/// dictionary is loaded the first time it is used.
+///
+/// Words in upper case, or with only their first letter in upper case,
+/// are correct if the dictionary has them in lower case.
+#[lisp_fn]
+pub fn spell_check_word(word: LispStringRef, dictionary: LispObject) -> LispObject {
+    let word = string_to_word(word);
+    let path = dictionary_path(dictionary);
+    let suggestions = with_dictionary(&path, |dictionary| {
+        if dictionary.check(&word) {
+            None
+        } else {
+            Some(dictionary.suggest(&word, MAX_SUGGESTIONS))
+        }
+    });
+    match suggestions {
+        None => Qt,
+        Some(suggestions) => suggestions
+            .iter()
+            .rev()
+            .fold(Qnil, |list, s| LispObject::cons(word_to_string(s), list)),
+    }
+}
+
+/// Check the spelling of the words between START and END.
+/// Return a list of (WORD-START . WORD-END) for each misspelled word, in
+/// the order they appear in the buffer.  Words with digits in them are
+/// not checked.
+///
+/// DICTIONARY is the file name of a Hunspell dictionary, as for
+/// `spell-check-word'.
+#[lisp_fn]
+pub fn spell_check_region(
+    mut start: LispObject,
+    mut end: LispObject,
+    dictionary: LispObject,
+) -> LispObject {
+    unsafe { validate_region(&mut start, &mut end) };
+    let start = start.as_fixnum_or_error() as ptrdiff_t;
+    let end = end.as_fixnum_or_error() as ptrdiff_t;
+    let path = dictionary_path(dictionary);
+
+    let mut buffer = ThreadState::current_buffer();
+    let multibyte = buffer.multibyte_characters_enabled();
+    let (start_byte, end_byte) = unsafe {
+        (
+            buf_charpos_to_bytepos(buffer.as_mut(), start),
+            buf_charpos_to_bytepos(buffer.as_mut(), end),
+        )
+    };
+    let text = buffer.bytes_between(start_byte, end_byte);
+
+    let mut chars = Vec::with_capacity(text.len());
+    let mut offset = 0;
+    while offset < text.len() {
+        let (c, len) = if multibyte {
+            multibyte_char_at(&text[offset..])
+        } else {
+            (u32::from(text[offset]), 1)
+        };
+        chars.push((start + chars.len() as ptrdiff_t, to_char(c)));
+        offset += len;
+    }
+
+    let misspelled: Vec<(ptrdiff_t, ptrdiff_t)> = with_dictionary(&path, |dictionary| {
+        split_words(chars.into_iter(), dictionary)
+            .into_iter()
+            .filter(|&(_, _, ref word)| !dictionary.check(word))
+            .map(|(start, end, _)| (start, end))
+            .collect()
+    });
+    misspelled.iter().rev().fold(Qnil, |list, &(start, end)| {
+        let range = LispObject::cons(
+            LispObject::from(start as EmacsInt),
+            LispObject::from(end as EmacsInt),
+        );
+        LispObject::cons(range, list)
+    })
+}
+
+/// Add WORD to DICTIONARY for the rest of this session.
+/// DICTIONARY is the file name of a Hunspell dictionary, as for
+/// `spell-check-word'.  The dictionary file is not changed.
+#[lisp_fn]
+pub fn spell_add_word(word: LispStringRef, dictionary: LispObject) -> LispObject {
+    let word = string_to_word(word);
+    let path = dictionary_path(dictionary);
+    with_dictionary(&path, |dictionary| dictionary.add_word(&word));
+    Qnil
+}
+
+include!(concat!(env!("OUT_DIR"), "/spell_exports.rs"));
+
+#[test]
+fn test_split_words() {
+    let dictionary = Dictionary::parse("WORDCHARS -\n", "0\n");
+    let text = "don't  stop-gap 'quoted' r2d2 naïve's";
+    let words: Vec<String> = split_words(
+        text.chars()
+            .enumerate()
+            .map(|(i, c)| (i as ptrdiff_t + 1, Some(c))),
+        &dictionary,
+    )
+    .into_iter()
+    .map(|(start, end, word)| {
+        let chars: String = text
+            .chars()
+            .skip(start as usize - 1)
+            .take((end - start) as usize)
+            .collect();
+        assert_eq!(chars, word);
+        word
+    })
+    .collect();
+    assert_eq!(words, vec!["don't", "stop-gap", "quoted", "naïve's"]);
+}
@@ -160,6 +160,7 @@ pub fn multibyte_string_p(object: LispObject) -> bool {
 /// This makes STRING unibyte and may change its length.
 #[lisp_fn]
 pub fn clear_string(mut string: LispStringRef) {
+    string.as_lisp_obj().check_impure();
     string.clear_data();
     unsafe {
         string.set_num_chars(string.len_bytes());
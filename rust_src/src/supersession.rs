@@ -0,0 +1,152 @@
+//! Detecting visited files that changed on disk.
+//!
+//! Before the first change to a file-visiting buffer, `lock_file' asks
+//! whether the file was modified by someone else since it was visited
+//! or saved.  The recorded modification time and size of the buffer
+//! say so, but file systems with coarse time stamps, tools that touch
+//! files without changing them and checkouts that rewrite identical
+//! files make the comparison report changes that aren't there.
+//!
+//! When `supersession-content-hash-limit' allows, the hash of the file
+//! contents is remembered whenever the file is found unchanged, and a
+//! file whose time stamp moved but whose contents still hash the same
+//! is not reported.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    buffers::LispBufferRef,
+    callproc::lisp_file_name_to_path,
+    eval::unbind_to,
+    filehash::hash_file,
+    fileio::{file_exists_p, set_visited_file_modtime, verify_visited_file_modtime},
+    lisp::{defsubr, LispObject},
+    obarray::intern,
+    remacs_sys::{find_symbol_value, record_unwind_current_buffer, set_buffer_internal_1},
+    remacs_sys::{Fexpand_file_name, Ffind_file_name_handler},
+    remacs_sys::{Qnil, Qunbound},
+    threads::{c_specpdl_index, ThreadState},
+};
+
+/// What was last seen of a file that was found unchanged.
+#[derive(Clone, Debug, PartialEq)]
+struct Snapshot {
+    modified: SystemTime,
+    size: u64,
+    hash: String,
+}
+
+lazy_static! {
+    /// Snapshots of visited files, by file name.
+    static ref SNAPSHOTS: Mutex<HashMap<PathBuf, Snapshot>> = Mutex::new(HashMap::new());
+}
+
+/// Return the largest file whose contents are hashed, from the option
+/// `supersession-content-hash-limit', or None if hashing is disabled.
+fn content_hash_limit() -> Option<u64> {
+    let value =
+        unsafe { find_symbol_value(intern("supersession-content-hash-limit").as_lisp_obj()) };
+    if value.eq(Qunbound) || value.is_nil() {
+        None
+    } else {
+        value
+            .as_fixnum()
+            .filter(|&limit| limit >= 0)
+            .map(|limit| limit as u64)
+    }
+}
+
+/// Return the snapshot of the file at PATH as it is now, reusing the
+/// hash of the cached snapshot if the file's stat results still match
+/// it.  Return None if the file can't be read or is larger than LIMIT.
+fn current_snapshot(path: &PathBuf, limit: u64) -> Option<Snapshot> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?;
+    let size = metadata.len();
+    if size > limit {
+        return None;
+    }
+    if let Some(cached) = SNAPSHOTS.lock().unwrap().get(path) {
+        if cached.modified == modified && cached.size == size {
+            return Some(cached.clone());
+        }
+    }
+    let hash = hash_file(path).ok()?;
+    Some(Snapshot {
+        modified,
+        size,
+        hash,
+    })
+}
+
+/// Record the current modification time of the visited file of BUFFER.
+fn update_visited_modtime(mut buffer: LispBufferRef) {
+    let count = c_specpdl_index();
+    unsafe {
+        record_unwind_current_buffer();
+        set_buffer_internal_1(buffer.as_mut());
+    }
+    set_visited_file_modtime(Qnil);
+    unbind_to(count, Qnil);
+}
+
+/// Return non-nil if the file visited by BUFFER changed on disk since
+/// BUFFER visited or saved it.  BUFFER defaults to the current buffer.
+///
+/// This is the test made before the first change to a file-visiting
+/// buffer, which calls `ask-user-about-supersession-threat' when it
+/// succeeds.  It compares the modification time and size recorded by
+/// BUFFER, as `verify-visited-file-modtime' does, and, subject to
+/// `supersession-content-hash-limit', the contents of the file.
+#[lisp_fn(min = "0")]
+pub fn file_superseded_p(buffer: LispObject) -> bool {
+    let buffer_ref = if buffer.is_nil() {
+        ThreadState::current_buffer()
+    } else {
+        buffer.as_buffer_or_error()
+    };
+    if !buffer_ref.filename().is_string() {
+        return false;
+    }
+    let file = unsafe { Fexpand_file_name(buffer_ref.filename(), Qnil) };
+    let unchanged = verify_visited_file_modtime(buffer_ref.as_lisp_obj());
+    if !unchanged && !file_exists_p(file.as_string_or_error()) {
+        return false;
+    }
+
+    let limit = match content_hash_limit() {
+        Some(limit) => limit,
+        None => return !unchanged,
+    };
+    if unsafe { Ffind_file_name_handler(file, Qnil) }.is_not_nil() {
+        return !unchanged;
+    }
+    let path = lisp_file_name_to_path(file);
+    let snapshot = match current_snapshot(&path, limit) {
+        Some(snapshot) => snapshot,
+        None => return !unchanged,
+    };
+
+    let mut snapshots = SNAPSHOTS.lock().unwrap();
+    let same_contents = snapshots.get(&path).map_or(false, |seen| {
+        seen.size == snapshot.size && seen.hash == snapshot.hash
+    });
+    if !unchanged && !same_contents {
+        return true;
+    }
+    snapshots.insert(path, snapshot);
+    drop(snapshots);
+    if !unchanged {
+        // Only the time stamp moved; don't ask again next time.
+        update_visited_modtime(buffer_ref);
+    }
+    false
+}
+
+include!(concat!(env!("OUT_DIR"), "/supersession_exports.rs"));
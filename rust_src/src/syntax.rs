@@ -2,18 +2,21 @@
 
 use remacs_macros::lisp_fn;
 
+use std::collections::VecDeque;
+
 use crate::{
     chartable::LispCharTableRef,
     editfns::constrain_to_field,
     lisp::defsubr,
     lisp::LispObject,
+    lists::nth,
     numbers::LispNumber,
     remacs_sys::{
         buffer_defaults, scan_lists, scan_words, set_char_table_defalt, set_point, skip_chars,
         skip_syntaxes,
     },
     remacs_sys::{EmacsInt, Qnil, Qsyntax_table, Qsyntax_table_p},
-    remacs_sys::{Fcopy_sequence, Fset_char_table_parent},
+    remacs_sys::{Fcopy_sequence, Fparse_partial_sexp, Fset_char_table_parent},
     threads::ThreadState,
 };
 
@@ -61,6 +64,90 @@ pub fn scan_lists_defun(from: EmacsInt, count: EmacsInt, depth: EmacsInt) -> Lis
     unsafe { scan_lists(from, count, depth, false) }
 }
 
+/// Return how the lists that enclose position POS are closed.
+/// The value is a list of elements (OPEN . CLOSE), one for each list,
+/// innermost first: OPEN is the position of the open parenthesis of the
+/// list, before POS, and CLOSE the position of the close parenthesis
+/// after POS that brings the depth below that of the list, whether it
+/// matches OPEN or not.
+///
+/// The list ends with the first element where OPEN or CLOSE is nil.
+/// CLOSE is nil when no close parenthesis ends the list before LIMIT,
+/// which defaults to the end of the accessible part of the buffer.  OPEN
+/// is nil for a close parenthesis after POS that has no open
+/// parenthesis before POS to match.  If all the lists are closed before
+/// LIMIT and there is no such close parenthesis, there is no such
+/// element.
+///
+/// STATE is the state of the parse at POS, as returned by
+/// `parse-partial-sexp' or `syntax-ppss'.  If it is nil, POS is parsed
+/// from the beginning of the accessible part of the buffer.
+/// Strings and comments are skipped as `parse-partial-sexp' does.
+#[lisp_fn(min = "1")]
+pub fn scan_enclosing_lists(
+    pos: EmacsInt,
+    limit: Option<EmacsInt>,
+    state: LispObject,
+) -> LispObject {
+    let buffer = ThreadState::current_buffer();
+    let point = buffer.pt;
+    let limit = limit.unwrap_or(buffer.zv as EmacsInt);
+
+    let mut state = if state.is_nil() {
+        unsafe {
+            Fparse_partial_sexp(
+                (buffer.begv as EmacsInt).into(),
+                pos.into(),
+                Qnil,
+                Qnil,
+                Qnil,
+                Qnil,
+            )
+        }
+    } else {
+        state
+    };
+    let mut openers: VecDeque<LispObject> = nth(9, state).iter_cars_safe().collect();
+
+    let mut pairs = Vec::new();
+    let mut from = pos;
+    loop {
+        let depth = nth(0, state).as_fixnum_or_error();
+        state = if from < limit {
+            unsafe {
+                Fparse_partial_sexp(
+                    from.into(),
+                    limit.into(),
+                    (depth - 1).into(),
+                    Qnil,
+                    state,
+                    Qnil,
+                )
+            }
+        } else {
+            state
+        };
+        let stop = ThreadState::current_buffer().pt as EmacsInt;
+
+        if from >= limit || nth(0, state).as_fixnum_or_error() != depth - 1 {
+            // The remaining lists are not closed before LIMIT.
+            if let Some(open) = openers.pop_back() {
+                pairs.push(LispObject::cons(open, Qnil));
+            }
+            break;
+        }
+        let open = openers.pop_back().unwrap_or(Qnil);
+        pairs.push(LispObject::cons(open, (stop - 1).into()));
+        if open.is_nil() {
+            break;
+        }
+        from = stop;
+    }
+
+    unsafe { set_point(point) };
+    LispObject::from(pairs)
+}
+
 /// Select a new syntax table for the current buffer.
 /// One argument, a syntax table.
 #[lisp_fn]
@@ -0,0 +1,616 @@
+//! System status: load averages and batteries.
+//!
+//! The battery status is read from the operating system directly,
+//! through sysfs on GNU/Linux, sysctl on FreeBSD, IOKit on macOS and
+//! `GetSystemPowerStatus' on Windows, and is returned in the same form
+//! everywhere, so that `battery.el' doesn't need to parse the output
+//! of external programs.
+
+use std::collections::HashMap;
+
+use libc::{c_double, c_int};
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    lisp::{defsubr, LispObject},
+    obarray::intern,
+    remacs_sys::{EmacsInt, Qnil},
+};
+
+extern "C" {
+    fn getloadavg(loadavg: *mut c_double, nelem: c_int) -> c_int;
+}
+
+/// Return list of 1 minute, 5 minute and 15 minute load averages.
+///
+/// Each of the three load averages is multiplied by 100, then converted
+/// to integer.
+///
+/// When USE-FLOATS is non-nil, floats will be used instead of integers.
+/// These floats are not multiplied by 100.
+///
+/// If the 5-minute or 15-minute load averages are not available, return a
+/// shortened list, containing only those averages which are available.
+///
+/// An error is thrown if the load average can't be obtained.  In some
+/// cases making it work would require Emacs being installed setuid or
+/// setgid so that it can read kernel information, and that usually isn't
+/// advisable.
+#[lisp_fn(min = "0")]
+pub fn load_average(use_floats: bool) -> LispObject {
+    let mut load_ave: [c_double; 3] = [0.0; 3];
+    let loads = unsafe { getloadavg(load_ave.as_mut_ptr(), 3) };
+    if loads < 0 {
+        error!("load-average not implemented for this operating system");
+    }
+
+    load_ave[..loads as usize]
+        .iter()
+        .rev()
+        .fold(Qnil, |list, &load| {
+            let load = if use_floats {
+                LispObject::from_float(load)
+            } else {
+                LispObject::from((100.0 * load) as EmacsInt)
+            };
+            LispObject::cons(load, list)
+        })
+}
+
+/// What a battery is doing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ChargeState {
+    Charging,
+    Discharging,
+    Full,
+    NotCharging,
+    Unknown,
+}
+
+impl ChargeState {
+    /// Parse the charge state in the format of sysfs.
+    fn parse(s: &str) -> Self {
+        match s {
+            "Charging" => ChargeState::Charging,
+            "Discharging" => ChargeState::Discharging,
+            "Full" => ChargeState::Full,
+            "Not charging" => ChargeState::NotCharging,
+            _ => ChargeState::Unknown,
+        }
+    }
+
+    /// How much the state says about the batteries as a whole, for
+    /// merging the states of several batteries.
+    fn weight(self) -> u8 {
+        match self {
+            ChargeState::Charging | ChargeState::Discharging => 2,
+            ChargeState::Full | ChargeState::NotCharging => 1,
+            ChargeState::Unknown => 0,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ChargeState::Charging => "charging",
+            ChargeState::Discharging => "discharging",
+            ChargeState::Full => "full",
+            ChargeState::NotCharging => "not-charging",
+            ChargeState::Unknown => "unknown",
+        }
+    }
+}
+
+/// The status of the batteries of the system, merged together.  Fields
+/// are None when the system doesn't provide the information.
+#[derive(Clone, Debug, PartialEq)]
+struct BatteryStatus {
+    ac_online: Option<bool>,
+    state: ChargeState,
+    /// Charge, in percent of the full capacity.
+    percentage: Option<f64>,
+    /// Time to charge or discharge, in seconds.
+    remaining: Option<u64>,
+    /// Charge or discharge rate, in watts.
+    rate: Option<f64>,
+    /// Temperature, in degrees Celsius.
+    temperature: Option<f64>,
+}
+
+impl BatteryStatus {
+    fn new() -> Self {
+        Self {
+            ac_online: None,
+            state: ChargeState::Unknown,
+            percentage: None,
+            remaining: None,
+            rate: None,
+            temperature: None,
+        }
+    }
+
+    /// Return the status as a plist.
+    fn to_plist(&self) -> LispObject {
+        let mut items = vec![(":status", intern(self.state.name()).as_lisp_obj())];
+        if let Some(online) = self.ac_online {
+            let line = if online { "ac" } else { "battery" };
+            items.push((":ac-line", intern(line).as_lisp_obj()));
+        }
+        if let Some(percentage) = self.percentage {
+            items.push((":percentage", LispObject::from_float(percentage)));
+        }
+        if let Some(remaining) = self.remaining {
+            items.push((":remaining", LispObject::from(remaining as EmacsInt)));
+        }
+        if let Some(rate) = self.rate {
+            items.push((":rate", LispObject::from_float(rate)));
+        }
+        if let Some(temperature) = self.temperature {
+            items.push((":temperature", LispObject::from_float(temperature)));
+        }
+        items.iter().rev().fold(Qnil, |plist, &(key, value)| {
+            LispObject::cons(intern(key).as_lisp_obj(), LispObject::cons(value, plist))
+        })
+    }
+}
+
+/// Parse the `uevent' file of a sysfs power supply into a map from
+/// property names, without the `POWER_SUPPLY_' prefix, to values.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_uevent(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next()?;
+            let value = parts.next()?;
+            Some((
+                key.trim_start_matches("POWER_SUPPLY_").to_string(),
+                value.to_string(),
+            ))
+        })
+        .collect()
+}
+
+/// Merge the sysfs properties of the power SUPPLIES into a status, or
+/// return None if none of them is a battery that is present.
+///
+/// Batteries report either charge and current or energy and power; to
+/// combine several batteries, everything is converted to energy and
+/// power, as charges at different voltages can't be added up.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn merge_supplies(supplies: &[HashMap<String, String>]) -> Option<BatteryStatus> {
+    let number = |supply: &HashMap<String, String>, key: &str| -> Option<f64> {
+        supply.get(key).and_then(|value| value.trim().parse().ok())
+    };
+
+    let mut status = BatteryStatus::new();
+    let mut found = false;
+    let (mut energy_full, mut energy_now, mut power_now) = (0.0, 0.0, 0.0);
+    for supply in supplies {
+        match supply.get("TYPE").map(String::as_str) {
+            Some("Mains") => {
+                if let Some(online) = number(supply, "ONLINE") {
+                    status.ac_online = Some(status.ac_online.unwrap_or(false) || online > 0.0);
+                }
+            }
+            Some("Battery") if number(supply, "PRESENT") != Some(0.0) => {
+                found = true;
+                // Values are in micro units: µV, µA, µW, µAh and µWh.
+                let voltage = number(supply, "VOLTAGE_NOW").unwrap_or(10_800_000.0) / 1e6;
+                if let Some(state) = supply.get("STATUS").map(|s| ChargeState::parse(s)) {
+                    if state.weight() > status.state.weight() {
+                        status.state = state;
+                    }
+                }
+                if let Some(power) = number(supply, "POWER_NOW") {
+                    power_now += power / 1e6;
+                } else if let Some(current) = number(supply, "CURRENT_NOW") {
+                    power_now += current / 1e6 * voltage;
+                }
+                if let (Some(full), Some(now)) =
+                    (number(supply, "ENERGY_FULL"), number(supply, "ENERGY_NOW"))
+                {
+                    energy_full += full / 1e6;
+                    energy_now += now / 1e6;
+                } else if let (Some(full), Some(now)) =
+                    (number(supply, "CHARGE_FULL"), number(supply, "CHARGE_NOW"))
+                {
+                    energy_full += full / 1e6 * voltage;
+                    energy_now += now / 1e6 * voltage;
+                } else if let Some(capacity) = number(supply, "CAPACITY") {
+                    status.percentage = Some(capacity);
+                }
+                if let Some(temperature) = number(supply, "TEMP") {
+                    // Tenths of a degree.
+                    status.temperature = Some(temperature / 10.0);
+                }
+            }
+            _ => {}
+        }
+    }
+    if !found {
+        return None;
+    }
+
+    if energy_full > 0.0 {
+        status.percentage = Some((100.0 * energy_now / energy_full).min(100.0));
+    }
+    if power_now > 0.0 {
+        status.rate = Some(power_now);
+        let energy_left = match status.state {
+            ChargeState::Discharging => Some(energy_now),
+            ChargeState::Charging if energy_full > 0.0 => Some(energy_full - energy_now),
+            _ => None,
+        };
+        status.remaining = energy_left.map(|wh| (wh / power_now * 3600.0).max(0.0) as u64);
+    }
+    Some(status)
+}
+
+#[cfg(target_os = "linux")]
+fn battery_status() -> Option<BatteryStatus> {
+    use std::fs;
+
+    let entries = fs::read_dir("/sys/class/power_supply").ok()?;
+    let supplies: Vec<_> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path().join("uevent");
+            fs::read_to_string(path).ok()
+        })
+        .map(|text| parse_uevent(&text))
+        .collect();
+    merge_supplies(&supplies)
+}
+
+#[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
+fn battery_status() -> Option<BatteryStatus> {
+    use std::ffi::CString;
+    use std::mem;
+    use std::ptr;
+
+    fn sysctl_int(name: &str) -> Option<c_int> {
+        let name = CString::new(name).ok()?;
+        let mut value: c_int = 0;
+        let mut size = mem::size_of::<c_int>();
+        let rc = unsafe {
+            libc::sysctlbyname(
+                name.as_ptr(),
+                &mut value as *mut c_int as *mut libc::c_void,
+                &mut size,
+                ptr::null(),
+                0,
+            )
+        };
+        if rc == 0 {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    // ACPI_BATT_STAT_* flags.
+    const DISCHARGING: c_int = 1;
+    const CHARGING: c_int = 2;
+    const NOT_PRESENT: c_int = 7;
+
+    let state = sysctl_int("hw.acpi.battery.state")?;
+    if state == NOT_PRESENT {
+        return None;
+    }
+    let mut status = BatteryStatus::new();
+    status.ac_online = sysctl_int("hw.acpi.acline").map(|acline| acline != 0);
+    status.state = if state & CHARGING != 0 {
+        ChargeState::Charging
+    } else if state & DISCHARGING != 0 {
+        ChargeState::Discharging
+    } else if status.ac_online == Some(true) {
+        ChargeState::Full
+    } else {
+        ChargeState::Unknown
+    };
+    status.percentage = sysctl_int("hw.acpi.battery.life")
+        .filter(|&life| life >= 0)
+        .map(f64::from);
+    status.remaining = sysctl_int("hw.acpi.battery.time")
+        .filter(|&minutes| minutes >= 0)
+        .map(|minutes| minutes as u64 * 60);
+    Some(status)
+}
+
+#[cfg(target_os = "macos")]
+fn battery_status() -> Option<BatteryStatus> {
+    use libc::{c_char, c_long, c_void};
+
+    type CFTypeRef = *const c_void;
+    type CFIndex = c_long;
+
+    const UTF8_ENCODING: u32 = 0x0800_0100;
+    const SINT32_TYPE: CFIndex = 3;
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFArrayGetCount(array: CFTypeRef) -> CFIndex;
+        fn CFArrayGetValueAtIndex(array: CFTypeRef, index: CFIndex) -> CFTypeRef;
+        fn CFBooleanGetValue(boolean: CFTypeRef) -> u8;
+        fn CFDictionaryGetValue(dict: CFTypeRef, key: CFTypeRef) -> CFTypeRef;
+        fn CFEqual(a: CFTypeRef, b: CFTypeRef) -> u8;
+        fn CFNumberGetValue(number: CFTypeRef, kind: CFIndex, value: *mut c_void) -> u8;
+        fn CFRelease(object: CFTypeRef);
+        fn CFStringCreateWithCString(
+            allocator: CFTypeRef,
+            string: *const c_char,
+            encoding: u32,
+        ) -> CFTypeRef;
+    }
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IOPSCopyPowerSourcesInfo() -> CFTypeRef;
+        fn IOPSCopyPowerSourcesList(blob: CFTypeRef) -> CFTypeRef;
+        fn IOPSGetPowerSourceDescription(blob: CFTypeRef, source: CFTypeRef) -> CFTypeRef;
+    }
+
+    /// A Core Foundation string, released when dropped.
+    struct CFString(CFTypeRef);
+
+    impl CFString {
+        fn new(s: &str) -> Self {
+            let s = std::ffi::CString::new(s).unwrap();
+            CFString(unsafe {
+                CFStringCreateWithCString(std::ptr::null(), s.as_ptr(), UTF8_ENCODING)
+            })
+        }
+    }
+
+    impl Drop for CFString {
+        fn drop(&mut self) {
+            unsafe { CFRelease(self.0) }
+        }
+    }
+
+    let get = |dict: CFTypeRef, key: &str| -> Option<CFTypeRef> {
+        let value = unsafe { CFDictionaryGetValue(dict, CFString::new(key).0) };
+        if value.is_null() {
+            None
+        } else {
+            Some(value)
+        }
+    };
+    let get_int = |dict: CFTypeRef, key: &str| -> Option<i32> {
+        let number = get(dict, key)?;
+        let mut value: i32 = 0;
+        let ok =
+            unsafe { CFNumberGetValue(number, SINT32_TYPE, &mut value as *mut i32 as *mut c_void) };
+        if ok != 0 {
+            Some(value)
+        } else {
+            None
+        }
+    };
+    let get_bool = |dict: CFTypeRef, key: &str| -> Option<bool> {
+        get(dict, key).map(|value| unsafe { CFBooleanGetValue(value) } != 0)
+    };
+    let is = |dict: CFTypeRef, key: &str, expected: &str| -> bool {
+        get(dict, key).map_or(false, |value| unsafe {
+            CFEqual(value, CFString::new(expected).0) != 0
+        })
+    };
+
+    let blob = unsafe { IOPSCopyPowerSourcesInfo() };
+    if blob.is_null() {
+        return None;
+    }
+    let list = unsafe { IOPSCopyPowerSourcesList(blob) };
+    let mut result = None;
+    if !list.is_null() {
+        for i in 0..unsafe { CFArrayGetCount(list) } {
+            let source = unsafe { CFArrayGetValueAtIndex(list, i) };
+            let desc = unsafe { IOPSGetPowerSourceDescription(blob, source) };
+            if desc.is_null() || !is(desc, "Type", "InternalBattery") {
+                continue;
+            }
+            let mut status = BatteryStatus::new();
+            status.ac_online = Some(is(desc, "Power Source State", "AC Power"));
+            let charging = get_bool(desc, "Is Charging").unwrap_or(false);
+            status.state = if charging {
+                ChargeState::Charging
+            } else if status.ac_online == Some(false) {
+                ChargeState::Discharging
+            } else if get_bool(desc, "Is Charged").unwrap_or(false) {
+                ChargeState::Full
+            } else {
+                ChargeState::NotCharging
+            };
+            if let (Some(now), Some(max)) = (
+                get_int(desc, "Current Capacity"),
+                get_int(desc, "Max Capacity"),
+            ) {
+                if max > 0 {
+                    status.percentage = Some(100.0 * f64::from(now) / f64::from(max));
+                }
+            }
+            let minutes_key = if charging {
+                "Time to Full Charge"
+            } else {
+                "Time to Empty"
+            };
+            status.remaining = get_int(desc, minutes_key)
+                .filter(|&minutes| minutes >= 0)
+                .map(|minutes| minutes as u64 * 60);
+            result = Some(status);
+            break;
+        }
+        unsafe { CFRelease(list) };
+    }
+    unsafe { CFRelease(blob) };
+    result
+}
+
+#[cfg(windows)]
+fn battery_status() -> Option<BatteryStatus> {
+    #[repr(C)]
+    struct SystemPowerStatus {
+        ac_line_status: u8,
+        battery_flag: u8,
+        battery_life_percent: u8,
+        system_status_flag: u8,
+        battery_life_time: u32,
+        battery_full_life_time: u32,
+    }
+
+    extern "system" {
+        fn GetSystemPowerStatus(status: *mut SystemPowerStatus) -> c_int;
+    }
+
+    const UNKNOWN_TIME: u32 = 0xFFFF_FFFF;
+
+    let mut power = SystemPowerStatus {
+        ac_line_status: 255,
+        battery_flag: 255,
+        battery_life_percent: 255,
+        system_status_flag: 0,
+        battery_life_time: UNKNOWN_TIME,
+        battery_full_life_time: UNKNOWN_TIME,
+    };
+    if unsafe { GetSystemPowerStatus(&mut power) } == 0 || power.battery_flag & 128 != 0 {
+        return None;
+    }
+    let mut status = BatteryStatus::new();
+    status.ac_online = match power.ac_line_status {
+        0 => Some(false),
+        1 => Some(true),
+        _ => None,
+    };
+    if power.battery_life_percent <= 100 {
+        status.percentage = Some(f64::from(power.battery_life_percent));
+    }
+    status.state = if power.battery_flag == 255 {
+        ChargeState::Unknown
+    } else if power.battery_flag & 8 != 0 {
+        ChargeState::Charging
+    } else if status.ac_online == Some(false) {
+        ChargeState::Discharging
+    } else if status.percentage == Some(100.0) {
+        ChargeState::Full
+    } else {
+        ChargeState::NotCharging
+    };
+    if power.battery_life_time != UNKNOWN_TIME {
+        let mut seconds = u64::from(power.battery_life_time);
+        if status.state == ChargeState::Charging {
+            if power.battery_full_life_time == UNKNOWN_TIME {
+                seconds = 0;
+            } else {
+                seconds = u64::from(power.battery_full_life_time).saturating_sub(seconds);
+            }
+        }
+        if seconds > 0 {
+            status.remaining = Some(seconds);
+        }
+    }
+    Some(status)
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "macos",
+    windows
+)))]
+fn battery_status() -> Option<BatteryStatus> {
+    None
+}
+
+/// Return the status of the batteries of the system as a plist.
+///
+/// The properties are:
+///
+///   :status       `charging', `discharging', `full', `not-charging'
+///                 or `unknown'.
+///   :ac-line      `ac' if the system runs on external power,
+///                 `battery' if it doesn't.
+///   :percentage   The charge, in percent of the full capacity.
+///   :remaining    The time to charge or discharge, in seconds.
+///   :rate         The charge or discharge rate, in watts.
+///   :temperature  The temperature, in degrees Celsius.
+///
+/// Properties other than `:status' are omitted when the system doesn't
+/// report them.  Several batteries are reported as one.  Return nil if
+/// the system has no battery, or if its status can't be read.
+#[lisp_fn]
+pub fn battery_status_native() -> LispObject {
+    battery_status().map_or(Qnil, |status| status.to_plist())
+}
+
+include!(concat!(env!("OUT_DIR"), "/sysinfo_exports.rs"));
+
+#[cfg(test)]
+const BAT0: &str = "POWER_SUPPLY_NAME=BAT0
+POWER_SUPPLY_TYPE=Battery
+POWER_SUPPLY_STATUS=Discharging
+POWER_SUPPLY_PRESENT=1
+POWER_SUPPLY_VOLTAGE_NOW=12000000
+POWER_SUPPLY_POWER_NOW=10000000
+POWER_SUPPLY_ENERGY_FULL=50000000
+POWER_SUPPLY_ENERGY_NOW=25000000
+POWER_SUPPLY_TEMP=305
+";
+
+#[cfg(test)]
+const AC: &str = "POWER_SUPPLY_NAME=AC
+POWER_SUPPLY_TYPE=Mains
+POWER_SUPPLY_ONLINE=0
+";
+
+#[test]
+fn test_parse_uevent() {
+    let props = parse_uevent(BAT0);
+    assert_eq!(props.get("STATUS").map(String::as_str), Some("Discharging"));
+    assert_eq!(props.get("TYPE").map(String::as_str), Some("Battery"));
+    assert_eq!(
+        props.get("ENERGY_NOW").map(String::as_str),
+        Some("25000000")
+    );
+}
+
+#[test]
+fn test_merge_energy_battery() {
+    let status = merge_supplies(&[parse_uevent(BAT0), parse_uevent(AC)]).unwrap();
+    assert_eq!(status.ac_online, Some(false));
+    assert_eq!(status.state, ChargeState::Discharging);
+    assert_eq!(status.percentage, Some(50.0));
+    assert_eq!(status.rate, Some(10.0));
+    assert_eq!(status.remaining, Some(9000));
+    assert_eq!(status.temperature, Some(30.5));
+}
+
+#[test]
+fn test_merge_charge_batteries() {
+    let bat = |now: &str| {
+        parse_uevent(&format!(
+            "POWER_SUPPLY_TYPE=Battery
+POWER_SUPPLY_STATUS=Charging
+POWER_SUPPLY_VOLTAGE_NOW=10000000
+POWER_SUPPLY_CURRENT_NOW=1000000
+POWER_SUPPLY_CHARGE_FULL=4000000
+POWER_SUPPLY_CHARGE_NOW={}
+",
+            now
+        ))
+    };
+    let status = merge_supplies(&[bat("1000000"), bat("3000000")]).unwrap();
+    assert_eq!(status.ac_online, None);
+    assert_eq!(status.state, ChargeState::Charging);
+    assert_eq!(status.percentage, Some(50.0));
+    assert_eq!(status.rate, Some(20.0));
+    // 40 Wh left to charge at 20 W.
+    assert_eq!(status.remaining, Some(7200));
+}
+
+#[test]
+fn test_merge_without_battery() {
+    assert_eq!(merge_supplies(&[parse_uevent(AC)]), None);
+    let absent = parse_uevent("POWER_SUPPLY_TYPE=Battery\nPOWER_SUPPLY_PRESENT=0\n");
+    assert_eq!(merge_supplies(&[absent]), None);
+}
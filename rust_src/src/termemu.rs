@@ -0,0 +1,1011 @@
+//! A native terminal emulator core for `term-mode'.
+//!
+//! A `Terminal' is a VT100/xterm state machine: it keeps a screen of
+//! cells with SGR renditions, a cursor, a scrolling region and an
+//! alternate screen, and interprets the output of the program running
+//! in it.  Lines that scroll off the top of the main screen become
+//! scrollback.
+//!
+//! The buffer of the terminal holds the scrollback lines followed by
+//! the screen.  A redraw replaces the screen part of the buffer, after
+//! appending the lines that scrolled off since the last redraw, so the
+//! cost of output doesn't depend on the size of the scrollback.  Like
+//! the parsers of websocket.rs, terminals live on the Rust side and
+//! Lisp refers to them by number.
+
+use std::collections::{HashMap, VecDeque};
+use std::mem;
+use std::sync::Mutex;
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    ansi::SgrState,
+    editfns::goto_char,
+    lisp::{defsubr, LispObject},
+    multibyte::LispStringRef,
+    obarray::intern,
+    remacs_sys::{del_range, insert_from_string, make_specified_string},
+    remacs_sys::{EmacsInt, Fput_text_property, Qface, Qnil},
+    threads::ThreadState,
+};
+
+lazy_static! {
+    /// The terminals in use, by number.
+    static ref TERMINALS: Mutex<HashMap<EmacsInt, Terminal>> = Mutex::new(HashMap::new());
+}
+
+static mut LAST_TERMINAL_ID: EmacsInt = 0;
+
+/// The longest OSC string that is kept; longer ones are truncated.
+const MAX_OSC_LENGTH: usize = 4096;
+
+const TAB_WIDTH: usize = 8;
+
+/// One character cell of the screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Cell {
+    ch: char,
+    sgr: SgrState,
+}
+
+impl Cell {
+    /// A blank cell erased with the rendition SGR, which only keeps its
+    /// background color, as xterm does.
+    fn blank(sgr: SgrState) -> Self {
+        Cell {
+            ch: ' ',
+            sgr: SgrState {
+                background: sgr.background,
+                ..SgrState::default()
+            },
+        }
+    }
+
+    fn is_default_blank(&self) -> bool {
+        self.ch == ' ' && self.sgr.is_default()
+    }
+}
+
+type Line = Vec<Cell>;
+
+fn blank_line(width: usize, sgr: SgrState) -> Line {
+    vec![Cell::blank(sgr); width]
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Cursor {
+    row: usize,
+    col: usize,
+    sgr: SgrState,
+}
+
+/// Where the parser is in an escape sequence.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ParseState {
+    Ground,
+    Escape,
+    /// After ESC ( and friends, which take one more character.
+    Charset,
+    Csi,
+    /// In an OSC string, or, if the flag is false, in a DCS, PM or APC
+    /// string, which is ignored.
+    Osc(bool),
+    /// After an ESC in one of the strings above.
+    StringEscape(bool),
+}
+
+/// Something the program did that the Lisp side has to act upon.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// A reply to a query, to be sent to the program.
+    Reply(String),
+    /// The program set the window title.
+    Title(String),
+    Bell,
+}
+
+/// The text of part of the terminal, as it appears in the buffer.
+#[derive(Debug, Default, PartialEq)]
+struct Rendering {
+    text: String,
+    /// The length of `text' in characters.
+    chars: usize,
+    /// Runs of non-default rendition, as (start, end, rendition) in
+    /// characters.
+    runs: Vec<(usize, usize, SgrState)>,
+}
+
+impl Rendering {
+    /// Append LINE, without trailing blanks, but at least MIN_LEN cells.
+    fn push_line(&mut self, line: &[Cell], min_len: usize) {
+        let len = line
+            .iter()
+            .rposition(|cell| !cell.is_default_blank())
+            .map_or(0, |i| i + 1)
+            .max(min_len.min(line.len()));
+        for cell in &line[..len] {
+            self.text.push(cell.ch);
+            if !cell.sgr.is_default() {
+                match self.runs.last_mut() {
+                    Some(run) if run.1 == self.chars && run.2 == cell.sgr => run.1 += 1,
+                    _ => self.runs.push((self.chars, self.chars + 1, cell.sgr)),
+                }
+            }
+            self.chars += 1;
+        }
+    }
+
+    fn push_newline(&mut self) {
+        self.text.push('\n');
+        self.chars += 1;
+    }
+}
+
+/// What a redraw has to put in the buffer.
+#[derive(Debug, PartialEq)]
+struct Redraw {
+    /// The lines that scrolled off since the last redraw, each followed
+    /// by a newline, then the screen.
+    rendering: Rendering,
+    /// Where the screen starts in `rendering'.
+    screen_start: usize,
+    /// Where the cursor is in `rendering'.
+    cursor: usize,
+    /// How many characters of screen text at the end of the buffer
+    /// `rendering' replaces.
+    replaced_chars: usize,
+    /// How many characters of the oldest scrollback to delete
+    /// afterwards.
+    trimmed_chars: usize,
+    /// How many characters of scrollback are left in the buffer then.
+    scrollback_chars: usize,
+}
+
+pub struct Terminal {
+    width: usize,
+    height: usize,
+    grid: Vec<Line>,
+    /// The main screen and cursor while the alternate screen is shown.
+    main_screen: Option<(Vec<Line>, Cursor)>,
+    cursor: Cursor,
+    saved_cursor: Cursor,
+    /// Whether the cursor is past the last column, so that the next
+    /// character goes to the next line.
+    wrap_pending: bool,
+    autowrap: bool,
+    insert_mode: bool,
+    /// The scrolling region, as first row and row after the last.
+    scroll_top: usize,
+    scroll_bottom: usize,
+
+    state: ParseState,
+    params: Vec<u32>,
+    param: Option<u32>,
+    private: Option<char>,
+    intermediate: bool,
+    osc: String,
+
+    /// Lines that scrolled off the main screen since the last redraw.
+    scrolled: Vec<Line>,
+    events: Vec<Event>,
+
+    /// The length in characters of the screen text in the buffer.
+    buffer_screen_chars: usize,
+    /// The lengths in characters of the scrollback lines in the
+    /// buffer, newlines included.
+    buffer_scrollback: VecDeque<usize>,
+    max_scrollback: usize,
+}
+
+impl Terminal {
+    pub fn new(width: usize, height: usize, max_scrollback: usize) -> Self {
+        Terminal {
+            width,
+            height,
+            grid: vec![blank_line(width, SgrState::default()); height],
+            main_screen: None,
+            cursor: Cursor::default(),
+            saved_cursor: Cursor::default(),
+            wrap_pending: false,
+            autowrap: true,
+            insert_mode: false,
+            scroll_top: 0,
+            scroll_bottom: height,
+            state: ParseState::Ground,
+            params: Vec::new(),
+            param: None,
+            private: None,
+            intermediate: false,
+            osc: String::new(),
+            scrolled: Vec::new(),
+            events: Vec::new(),
+            buffer_screen_chars: 0,
+            buffer_scrollback: VecDeque::new(),
+            max_scrollback,
+        }
+    }
+
+    /// Interpret the program output INPUT.
+    pub fn feed(&mut self, input: &str) {
+        for c in input.chars() {
+            self.process(c);
+        }
+    }
+
+    /// Return the events since the last call.
+    pub fn take_events(&mut self) -> Vec<Event> {
+        mem::replace(&mut self.events, Vec::new())
+    }
+
+    fn process(&mut self, c: char) {
+        match self.state {
+            ParseState::Ground => {
+                if (c as u32) < 0x20 || c == '\x7f' {
+                    self.control(c);
+                } else if (c as u32) < 0x80 || (c as u32) > 0x9f {
+                    self.put_char(c);
+                }
+            }
+            ParseState::Escape => self.escape(c),
+            ParseState::Charset => self.state = ParseState::Ground,
+            ParseState::Csi => self.csi_char(c),
+            ParseState::Osc(keep) => match c {
+                '\x07' => self.end_string(keep),
+                '\x1b' => self.state = ParseState::StringEscape(keep),
+                _ => {
+                    if keep && self.osc.len() < MAX_OSC_LENGTH {
+                        self.osc.push(c);
+                    }
+                }
+            },
+            ParseState::StringEscape(keep) => {
+                self.end_string(keep);
+                if c != '\\' {
+                    // Not a string terminator: an escape sequence
+                    // that cuts the string short.
+                    self.state = ParseState::Escape;
+                    self.escape(c);
+                }
+            }
+        }
+    }
+
+    /// Execute the C0 control character C.
+    fn control(&mut self, c: char) {
+        match c {
+            '\x07' => self.events.push(Event::Bell),
+            '\x08' => {
+                self.wrap_pending = false;
+                self.cursor.col = self.cursor.col.saturating_sub(1);
+            }
+            '\t' => {
+                self.wrap_pending = false;
+                self.cursor.col =
+                    ((self.cursor.col / TAB_WIDTH + 1) * TAB_WIDTH).min(self.width - 1);
+            }
+            '\n' | '\x0b' | '\x0c' => self.linefeed(),
+            '\r' => {
+                self.wrap_pending = false;
+                self.cursor.col = 0;
+            }
+            '\x1b' => self.state = ParseState::Escape,
+            _ => {}
+        }
+    }
+
+    /// Interpret C, the character after an ESC.
+    fn escape(&mut self, c: char) {
+        self.state = ParseState::Ground;
+        match c {
+            '[' => {
+                self.params.clear();
+                self.param = None;
+                self.private = None;
+                self.intermediate = false;
+                self.state = ParseState::Csi;
+            }
+            ']' => {
+                self.osc.clear();
+                self.state = ParseState::Osc(true);
+            }
+            'P' | '^' | '_' | 'X' => self.state = ParseState::Osc(false),
+            '(' | ')' | '*' | '+' => self.state = ParseState::Charset,
+            '7' => self.save_cursor(),
+            '8' => self.restore_cursor(),
+            'D' => self.linefeed(),
+            'E' => {
+                self.cursor.col = 0;
+                self.linefeed();
+            }
+            'M' => self.reverse_index(),
+            'c' => self.reset(),
+            _ => {}
+        }
+    }
+
+    fn csi_char(&mut self, c: char) {
+        match c {
+            '0'..='9' => {
+                let digit = c as u32 - '0' as u32;
+                let value = self.param.unwrap_or(0);
+                self.param = Some(value.saturating_mul(10).saturating_add(digit));
+            }
+            ';' | ':' => {
+                let param = self.param.take().unwrap_or(0);
+                self.params.push(param);
+            }
+            '?' | '>' | '<' | '=' => self.private = Some(c),
+            '\x20'..='\x2f' => self.intermediate = true,
+            '\x40'..='\x7e' => {
+                if let Some(param) = self.param.take() {
+                    self.params.push(param);
+                }
+                self.state = ParseState::Ground;
+                if !self.intermediate {
+                    self.csi_dispatch(c);
+                }
+            }
+            '\x1b' => self.state = ParseState::Escape,
+            _ if (c as u32) < 0x20 => self.control(c),
+            _ => self.state = ParseState::Ground,
+        }
+    }
+
+    /// Return parameter I, or DEFAULT if it is missing or zero.
+    fn param(&self, i: usize, default: usize) -> usize {
+        match self.params.get(i) {
+            Some(&n) if n > 0 => n as usize,
+            _ => default,
+        }
+    }
+
+    fn csi_dispatch(&mut self, c: char) {
+        let n = self.param(0, 1);
+        let (row, col) = (self.cursor.row, self.cursor.col);
+        match (self.private, c) {
+            (None, 'A') => self.move_to(row.saturating_sub(n), col),
+            (None, 'B') | (None, 'e') => self.move_to(row + n, col),
+            (None, 'C') | (None, 'a') => self.move_to(row, col + n),
+            (None, 'D') => self.move_to(row, col.saturating_sub(n)),
+            (None, 'E') => self.move_to(row + n, 0),
+            (None, 'F') => self.move_to(row.saturating_sub(n), 0),
+            (None, 'G') | (None, '`') => self.move_to(row, n - 1),
+            (None, 'H') | (None, 'f') => self.move_to(n - 1, self.param(1, 1) - 1),
+            (None, 'd') => self.move_to(n - 1, col),
+            (None, 'J') | (Some('?'), 'J') => self.erase_display(self.param(0, 0)),
+            (None, 'K') | (Some('?'), 'K') => self.erase_line(self.param(0, 0)),
+            (None, 'L') => self.insert_lines(n),
+            (None, 'M') => self.delete_lines(n),
+            (None, 'P') => self.delete_chars(n),
+            (None, '@') => self.insert_blanks(n),
+            (None, 'X') => self.erase_chars(n),
+            (None, 'S') => self.scroll_up(n),
+            (None, 'T') => self.scroll_down(n),
+            (None, 'm') => self.cursor.sgr.apply(&self.params),
+            (None, 'r') => {
+                let top = self.param(0, 1) - 1;
+                let bottom = self.param(1, self.height).min(self.height);
+                if top + 1 < bottom {
+                    self.scroll_top = top;
+                    self.scroll_bottom = bottom;
+                    self.move_to(0, 0);
+                }
+            }
+            (None, 's') => self.save_cursor(),
+            (None, 'u') => self.restore_cursor(),
+            (None, 'n') => match self.param(0, 0) {
+                5 => self.reply("\x1b[0n".to_string()),
+                6 => self.reply(format!("\x1b[{};{}R", row + 1, col + 1)),
+                _ => {}
+            },
+            (None, 'c') => self.reply("\x1b[?1;2c".to_string()),
+            (None, 'h') | (None, 'l') | (Some('?'), 'h') | (Some('?'), 'l') => {
+                let set = c == 'h';
+                let params = mem::replace(&mut self.params, Vec::new());
+                for &mode in &params {
+                    self.set_mode(mode, set);
+                }
+                self.params = params;
+            }
+            _ => {}
+        }
+    }
+
+    fn set_mode(&mut self, mode: u32, set: bool) {
+        match (self.private, mode) {
+            (None, 4) => self.insert_mode = set,
+            (Some('?'), 7) => self.autowrap = set,
+            (Some('?'), 47) | (Some('?'), 1047) => self.alternate_screen(set),
+            (Some('?'), 1049) => {
+                if set {
+                    self.save_cursor();
+                    self.alternate_screen(true);
+                    self.erase_display(2);
+                } else {
+                    self.alternate_screen(false);
+                    self.restore_cursor();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn reply(&mut self, reply: String) {
+        self.events.push(Event::Reply(reply));
+    }
+
+    fn end_string(&mut self, keep: bool) {
+        self.state = ParseState::Ground;
+        if !keep {
+            return;
+        }
+        let osc = mem::replace(&mut self.osc, String::new());
+        let mut parts = osc.splitn(2, ';');
+        if let (Some(kind), Some(text)) = (parts.next(), parts.next()) {
+            if kind == "0" || kind == "2" {
+                self.events.push(Event::Title(text.to_string()));
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        let scrolled = mem::replace(&mut self.scrolled, Vec::new());
+        let events = mem::replace(&mut self.events, Vec::new());
+        let (screen_chars, scrollback) = (
+            self.buffer_screen_chars,
+            mem::replace(&mut self.buffer_scrollback, VecDeque::new()),
+        );
+        *self = Terminal::new(self.width, self.height, self.max_scrollback);
+        self.scrolled = scrolled;
+        self.events = events;
+        self.buffer_screen_chars = screen_chars;
+        self.buffer_scrollback = scrollback;
+    }
+
+    fn move_to(&mut self, row: usize, col: usize) {
+        self.wrap_pending = false;
+        self.cursor.row = row.min(self.height - 1);
+        self.cursor.col = col.min(self.width - 1);
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved_cursor = self.cursor;
+    }
+
+    fn restore_cursor(&mut self) {
+        let saved = self.saved_cursor;
+        self.cursor.sgr = saved.sgr;
+        self.move_to(saved.row, saved.col);
+    }
+
+    fn put_char(&mut self, ch: char) {
+        if self.wrap_pending {
+            self.cursor.col = 0;
+            self.linefeed();
+        }
+        let cell = Cell {
+            ch,
+            sgr: self.cursor.sgr,
+        };
+        let (width, col) = (self.width, self.cursor.col);
+        let line = &mut self.grid[self.cursor.row];
+        if self.insert_mode {
+            line.insert(col, cell);
+            line.truncate(width);
+        } else {
+            line[col] = cell;
+        }
+        if col + 1 < width {
+            self.cursor.col += 1;
+        } else {
+            self.wrap_pending = self.autowrap;
+        }
+    }
+
+    fn linefeed(&mut self) {
+        self.wrap_pending = false;
+        if self.cursor.row + 1 == self.scroll_bottom {
+            self.scroll_up(1);
+        } else if self.cursor.row + 1 < self.height {
+            self.cursor.row += 1;
+        }
+    }
+
+    fn reverse_index(&mut self) {
+        self.wrap_pending = false;
+        if self.cursor.row == self.scroll_top {
+            self.scroll_down(1);
+        } else if self.cursor.row > 0 {
+            self.cursor.row -= 1;
+        }
+    }
+
+    /// Scroll the scrolling region up by N lines.  Lines scrolled off
+    /// the top of the main screen go to the scrollback.
+    fn scroll_up(&mut self, n: usize) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        let n = n.min(bottom - top);
+        let blank = blank_line(self.width, self.cursor.sgr);
+        let removed: Vec<Line> = self.grid.drain(top..top + n).collect();
+        for _ in 0..n {
+            self.grid.insert(bottom - n, blank.clone());
+        }
+        if top == 0 && self.main_screen.is_none() {
+            self.scrolled.extend(removed);
+        }
+    }
+
+    /// Scroll the scrolling region down by N lines.
+    fn scroll_down(&mut self, n: usize) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        let n = n.min(bottom - top);
+        let blank = blank_line(self.width, self.cursor.sgr);
+        self.grid.drain(bottom - n..bottom);
+        for _ in 0..n {
+            self.grid.insert(top, blank.clone());
+        }
+    }
+
+    fn insert_lines(&mut self, n: usize) {
+        let row = self.cursor.row;
+        if row < self.scroll_top || row >= self.scroll_bottom {
+            return;
+        }
+        let top = mem::replace(&mut self.scroll_top, row);
+        self.scroll_down(n);
+        self.scroll_top = top;
+        self.cursor.col = 0;
+    }
+
+    fn delete_lines(&mut self, n: usize) {
+        let row = self.cursor.row;
+        if row < self.scroll_top || row >= self.scroll_bottom {
+            return;
+        }
+        let n = n.min(self.scroll_bottom - row);
+        let blank = blank_line(self.width, self.cursor.sgr);
+        self.grid.drain(row..row + n);
+        for _ in 0..n {
+            self.grid.insert(self.scroll_bottom - n, blank.clone());
+        }
+        self.cursor.col = 0;
+    }
+
+    fn erase_cells(&mut self, row: usize, from: usize, to: usize) {
+        let blank = Cell::blank(self.cursor.sgr);
+        for cell in &mut self.grid[row][from..to] {
+            *cell = blank;
+        }
+    }
+
+    fn erase_line(&mut self, mode: usize) {
+        let (row, col, width) = (self.cursor.row, self.cursor.col, self.width);
+        match mode {
+            0 => self.erase_cells(row, col, width),
+            1 => self.erase_cells(row, 0, col + 1),
+            2 => self.erase_cells(row, 0, width),
+            _ => {}
+        }
+    }
+
+    fn erase_display(&mut self, mode: usize) {
+        let (row, width, height) = (self.cursor.row, self.width, self.height);
+        let (rows, line_mode) = match mode {
+            0 => (row + 1..height, 0),
+            1 => (0..row, 1),
+            2 | 3 => (0..height, 2),
+            _ => return,
+        };
+        for r in rows {
+            self.erase_cells(r, 0, width);
+        }
+        if line_mode != 2 {
+            self.erase_line(line_mode);
+        }
+    }
+
+    fn delete_chars(&mut self, n: usize) {
+        let (col, width) = (self.cursor.col, self.width);
+        let n = n.min(width - col);
+        let blank = Cell::blank(self.cursor.sgr);
+        let line = &mut self.grid[self.cursor.row];
+        line.drain(col..col + n);
+        line.resize(width, blank);
+    }
+
+    fn insert_blanks(&mut self, n: usize) {
+        let (col, width) = (self.cursor.col, self.width);
+        let n = n.min(width - col);
+        let blank = Cell::blank(self.cursor.sgr);
+        let line = &mut self.grid[self.cursor.row];
+        for _ in 0..n {
+            line.insert(col, blank);
+        }
+        line.truncate(width);
+    }
+
+    fn erase_chars(&mut self, n: usize) {
+        let (row, col) = (self.cursor.row, self.cursor.col);
+        let to = (col + n).min(self.width);
+        self.erase_cells(row, col, to);
+    }
+
+    /// Switch to the alternate screen if ON, or back to the main screen.
+    fn alternate_screen(&mut self, on: bool) {
+        if on && self.main_screen.is_none() {
+            let blank = vec![blank_line(self.width, SgrState::default()); self.height];
+            let main = mem::replace(&mut self.grid, blank);
+            self.main_screen = Some((main, self.cursor));
+        } else if !on {
+            if let Some((main, cursor)) = self.main_screen.take() {
+                self.grid = main;
+                self.cursor = cursor;
+                self.wrap_pending = false;
+            }
+        }
+    }
+
+    /// Change the size of the screen to WIDTH columns and HEIGHT lines.
+    /// When the screen gets shorter, lines scroll off the top as far as
+    /// needed to keep the cursor on the screen.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        let resize_grid = |grid: &mut Vec<Line>, cursor: &mut Cursor, scrolled: &mut Vec<Line>| {
+            if cursor.row >= height {
+                let excess = cursor.row + 1 - height;
+                scrolled.extend(grid.drain(..excess));
+                cursor.row -= excess;
+            }
+            grid.resize(height, blank_line(width, SgrState::default()));
+            for line in grid.iter_mut() {
+                line.resize(width, Cell::blank(SgrState::default()));
+            }
+            cursor.col = cursor.col.min(width - 1);
+        };
+
+        let mut discarded = Vec::new();
+        if let Some((ref mut main, ref mut cursor)) = self.main_screen {
+            resize_grid(main, cursor, &mut self.scrolled);
+            resize_grid(&mut self.grid, &mut self.cursor, &mut discarded);
+        } else {
+            resize_grid(&mut self.grid, &mut self.cursor, &mut self.scrolled);
+        }
+        self.width = width;
+        self.height = height;
+        self.scroll_top = 0;
+        self.scroll_bottom = height;
+        self.wrap_pending = false;
+        self.saved_cursor.row = self.saved_cursor.row.min(height - 1);
+        self.saved_cursor.col = self.saved_cursor.col.min(width - 1);
+    }
+
+    /// Return what the buffer must show now, and forget the lines that
+    /// scrolled off since the last redraw.
+    fn redraw(&mut self) -> Redraw {
+        let mut rendering = Rendering::default();
+        for line in self.scrolled.drain(..) {
+            let start = rendering.chars;
+            rendering.push_line(&line, 0);
+            rendering.push_newline();
+            self.buffer_scrollback.push_back(rendering.chars - start);
+        }
+        let mut trimmed_chars = 0;
+        while self.buffer_scrollback.len() > self.max_scrollback {
+            trimmed_chars += self.buffer_scrollback.pop_front().unwrap_or(0);
+        }
+
+        let screen_start = rendering.chars;
+        let mut cursor = screen_start;
+        for (row, line) in self.grid.iter().enumerate() {
+            if row > 0 {
+                rendering.push_newline();
+            }
+            if row == self.cursor.row {
+                // Keep the blanks up to the cursor, so that it has a
+                // place to be.
+                cursor = rendering.chars + self.cursor.col;
+                rendering.push_line(line, self.cursor.col);
+            } else {
+                rendering.push_line(line, 0);
+            }
+        }
+        let replaced_chars = mem::replace(
+            &mut self.buffer_screen_chars,
+            rendering.chars - screen_start,
+        );
+        Redraw {
+            rendering,
+            screen_start,
+            cursor,
+            replaced_chars,
+            trimmed_chars,
+            scrollback_chars: self.buffer_scrollback.iter().sum(),
+        }
+    }
+}
+
+/// Run F on the terminal numbered TERM, and return its value.
+fn with_terminal<T>(term: EmacsInt, f: impl FnOnce(&mut Terminal) -> T) -> T {
+    // The lock must be released before signaling an error.
+    let result = TERMINALS.lock().unwrap().get_mut(&term).map(f);
+    result.unwrap_or_else(|| error!("No terminal numbered {}", term))
+}
+
+fn make_lisp_string(s: &str) -> LispObject {
+    unsafe {
+        make_specified_string(
+            s.as_ptr() as *const c_char,
+            s.chars().count() as ptrdiff_t,
+            s.len() as ptrdiff_t,
+            true,
+        )
+    }
+}
+
+fn check_size(width: EmacsInt, height: EmacsInt) -> (usize, usize) {
+    if width < 1 || height < 1 {
+        error!("Invalid terminal size {}x{}", width, height);
+    }
+    (width as usize, height as usize)
+}
+
+/// Make a terminal of WIDTH columns and HEIGHT lines, and return its
+/// number.  SCROLLBACK, if non-nil, is the most lines that are kept in
+/// the buffer above the screen.  The terminal should be freed with
+/// `term-native-free' when it is no longer needed.
+#[lisp_fn(min = "2")]
+pub fn term_native_new(width: EmacsInt, height: EmacsInt, scrollback: LispObject) -> EmacsInt {
+    let (width, height) = check_size(width, height);
+    let max_scrollback = if scrollback.is_nil() {
+        usize::max_value()
+    } else {
+        scrollback.as_natnum_or_error() as usize
+    };
+    let id = unsafe {
+        LAST_TERMINAL_ID += 1;
+        LAST_TERMINAL_ID
+    };
+    TERMINALS
+        .lock()
+        .unwrap()
+        .insert(id, Terminal::new(width, height, max_scrollback));
+    id
+}
+
+/// Free the terminal numbered TERM.
+#[lisp_fn]
+pub fn term_native_free(term: EmacsInt) {
+    TERMINALS.lock().unwrap().remove(&term);
+}
+
+/// Change the size of the terminal numbered TERM to WIDTH columns and
+/// HEIGHT lines.  The buffer shows the new size after the next
+/// `term-native-redraw'.
+#[lisp_fn]
+pub fn term_native_resize(term: EmacsInt, width: EmacsInt, height: EmacsInt) {
+    let (width, height) = check_size(width, height);
+    with_terminal(term, |terminal| terminal.resize(width, height));
+}
+
+/// Make the terminal numbered TERM interpret STRING, the output of the
+/// program running in it.  Return a list of what the program asked
+/// for, in order, where each element is one of:
+///
+/// (reply . STRING)  STRING must be sent to the program.
+/// (title . STRING)  the program set the window title to STRING.
+/// (bell)            the program rang the bell.
+///
+/// The buffer is only updated by `term-native-redraw'.
+#[lisp_fn]
+pub fn term_native_feed(term: EmacsInt, string: LispStringRef) -> LispObject {
+    let input = if string.is_multibyte() {
+        String::from_utf8_lossy(string.as_slice()).into_owned()
+    } else {
+        string.as_slice().iter().map(|&b| b as char).collect()
+    };
+    let events = with_terminal(term, |terminal| {
+        terminal.feed(&input);
+        terminal.take_events()
+    });
+
+    let events: Vec<LispObject> = events
+        .into_iter()
+        .map(|event| {
+            let (kind, data) = match event {
+                Event::Reply(reply) => ("reply", make_lisp_string(&reply)),
+                Event::Title(title) => ("title", make_lisp_string(&title)),
+                Event::Bell => ("bell", Qnil),
+            };
+            LispObject::cons(intern(kind).as_lisp_obj(), data)
+        })
+        .collect();
+    LispObject::from(events)
+}
+
+/// Bring the current buffer up to date with the terminal numbered TERM.
+/// The buffer is taken to hold the scrollback of the terminal followed
+/// by its screen, as left by the previous redraws: the screen text at
+/// the end of the accessible portion is replaced, after the lines that
+/// scrolled off since then are added to the scrollback, and the oldest
+/// lines of scrollback are deleted as needed.  Text is given a `face'
+/// property according to its rendition.  Point is left after the new
+/// text.
+///
+/// Return the position of the cursor of the terminal.
+#[lisp_fn]
+pub fn term_native_redraw(term: EmacsInt) -> EmacsInt {
+    let redraw = with_terminal(term, Terminal::redraw);
+    let rendering = &redraw.rendering;
+
+    let text = make_lisp_string(&rendering.text);
+    for &(start, end, ref sgr) in &rendering.runs {
+        unsafe {
+            Fput_text_property(
+                LispObject::from(start as EmacsInt),
+                LispObject::from(end as EmacsInt),
+                Qface,
+                sgr.to_face(),
+                text,
+            )
+        };
+    }
+
+    let buffer = ThreadState::current_buffer();
+    let (begv, zv) = (buffer.begv, buffer.zv);
+    let start = (zv - redraw.replaced_chars as ptrdiff_t).max(begv);
+    unsafe { del_range(start, zv) };
+    goto_char(LispObject::from(start as EmacsInt));
+    unsafe {
+        insert_from_string(
+            text,
+            0,
+            0,
+            rendering.chars as ptrdiff_t,
+            rendering.text.len() as ptrdiff_t,
+            false,
+        )
+    };
+
+    // The oldest lines of scrollback come right before the ones that
+    // are kept; text before them isn't the terminal's.
+    let kept_start =
+        start + redraw.screen_start as ptrdiff_t - redraw.scrollback_chars as ptrdiff_t;
+    let trim_start = (kept_start - redraw.trimmed_chars as ptrdiff_t).max(begv);
+    let trimmed = kept_start - trim_start;
+    if trimmed > 0 {
+        unsafe { del_range(trim_start, kept_start) };
+    }
+    (start + redraw.cursor as ptrdiff_t - trimmed) as EmacsInt
+}
+
+include!(concat!(env!("OUT_DIR"), "/termemu_exports.rs"));
+
+#[cfg(test)]
+fn screen_text(terminal: &Terminal) -> Vec<String> {
+    terminal
+        .grid
+        .iter()
+        .map(|line| {
+            let text: String = line.iter().map(|cell| cell.ch).collect();
+            text.trim_end().to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn terminal(input: &str) -> Terminal {
+    let mut terminal = Terminal::new(10, 3, 100);
+    terminal.feed(input);
+    terminal
+}
+
+#[test]
+fn test_cursor_movement() {
+    let t = terminal("abc\x1b[2;5Hx\x1b[A\x1b[2Dy\r\nz");
+    assert_eq!(screen_text(&t), vec!["abcy", "z   x", ""]);
+    assert_eq!((t.cursor.row, t.cursor.col), (1, 1));
+}
+
+#[test]
+fn test_wrap_and_scroll() {
+    let mut t = terminal("0123456789ab\r\nline2\r\nline3");
+    assert_eq!(screen_text(&t), vec!["ab", "line2", "line3"]);
+    assert_eq!(t.scrolled.len(), 1);
+
+    let redraw = t.redraw();
+    assert_eq!(redraw.rendering.text, "0123456789\nab\nline2\nline3");
+    assert_eq!(redraw.screen_start, 11);
+    assert_eq!(redraw.cursor, redraw.rendering.chars);
+    assert_eq!(redraw.replaced_chars, 0);
+    assert_eq!(t.buffer_screen_chars, 14);
+    assert!(t.scrolled.is_empty());
+}
+
+#[test]
+fn test_scroll_region() {
+    let t = terminal("a\r\nb\r\nc\x1b[1;2r\x1b[2;1H\nd");
+    assert_eq!(screen_text(&t), vec!["b", "d", "c"]);
+    // Lines scrolled off a region that starts at the top of the
+    // screen still go to the scrollback.
+    assert_eq!(t.scrolled.len(), 1);
+}
+
+#[test]
+fn test_erase_and_edit() {
+    let t = terminal("abcdef\x1b[3D\x1b[K\r\nxyz\x1b[2G\x1b[P\x1b[2@");
+    assert_eq!(screen_text(&t), vec!["abc", "x  z", ""]);
+
+    let t = terminal("abc\r\ndef\x1b[2J");
+    assert_eq!(screen_text(&t), vec!["", "", ""]);
+    assert_eq!((t.cursor.row, t.cursor.col), (1, 3));
+}
+
+#[test]
+fn test_rendition() {
+    let mut t = terminal("a\x1b[1;31mbc\x1b[0md");
+    assert!(t.grid[0][1].sgr.bold);
+    assert!(t.grid[0][3].sgr.is_default());
+    let redraw = t.redraw();
+    let runs = &redraw.rendering.runs;
+    assert_eq!(runs.len(), 1);
+    assert_eq!((runs[0].0, runs[0].1), (1, 3));
+}
+
+#[test]
+fn test_events() {
+    let mut t = terminal("\x1b[3;4H\x1b[6n\x07\x1b]2;title\x07\x1b]0;other\x1b\\");
+    assert_eq!(
+        t.take_events(),
+        vec![
+            Event::Reply("\x1b[3;4R".to_string()),
+            Event::Bell,
+            Event::Title("title".to_string()),
+            Event::Title("other".to_string()),
+        ]
+    );
+    assert!(t.take_events().is_empty());
+}
+
+#[test]
+fn test_sequences_split_across_feeds() {
+    let mut t = terminal("\x1b[");
+    t.feed("2");
+    t.feed("Cx");
+    assert_eq!(screen_text(&t), vec!["  x", "", ""]);
+}
+
+#[test]
+fn test_alternate_screen() {
+    let mut t = terminal("main\x1b[?1049h\x1b[Halt\r\n\n\n\n");
+    assert_eq!(screen_text(&t), vec!["", "", ""]);
+    assert!(t.scrolled.is_empty());
+    t.feed("\x1b[?1049l");
+    assert_eq!(screen_text(&t), vec!["main", "", ""]);
+    assert_eq!((t.cursor.row, t.cursor.col), (0, 4));
+}
+
+#[test]
+fn test_scrollback_limit() {
+    let mut t = Terminal::new(10, 1, 2);
+    t.feed("a\r\nbb\r\nccc\r\nd");
+    let redraw = t.redraw();
+    assert_eq!(redraw.rendering.text, "a\nbb\nccc\nd");
+    assert_eq!(redraw.trimmed_chars, 2);
+    t.feed("\r\ne");
+    let redraw = t.redraw();
+    assert_eq!(redraw.rendering.text, "d\ne");
+    assert_eq!(redraw.replaced_chars, 1);
+    assert_eq!(redraw.trimmed_chars, 3);
+    assert_eq!(redraw.scrollback_chars, 6);
+}
+
+#[test]
+fn test_resize() {
+    let mut t = terminal("a\r\nb\r\nc");
+    t.resize(5, 2);
+    assert_eq!(screen_text(&t), vec!["b", "c"]);
+    assert_eq!(t.scrolled.len(), 1);
+    assert_eq!((t.cursor.row, t.cursor.col), (1, 1));
+}
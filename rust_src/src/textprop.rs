@@ -1,15 +1,2061 @@
 //! Text property support
 
+use std::mem;
 use std::ptr;
 
+use libc::{c_void, ptrdiff_t};
+
 use remacs_macros::lisp_fn;
 
 use crate::{
-    lisp::{defsubr, LispObject},
-    remacs_sys::get_char_property_and_overlay,
-    remacs_sys::EmacsInt,
+    buffers::LispBufferRef,
+    lisp::{defsubr, ExternalPtr, LispObject},
+    lists::{assq, car, cdr, get, memq, plist_get},
+    marker::buf_charpos_to_bytepos,
+    obarray::intern,
+    remacs_sys::buffer_text,
+    remacs_sys::{create_root_interval, find_interval, get_char_property_and_overlay, interval},
+    remacs_sys::{globals, invisible_prop, make_interval, text_property_stickiness},
+    remacs_sys::{merge_properties_sticky, set_text_properties_1},
+    remacs_sys::{EmacsInt, Qbuffer_or_string_p, Qcategory, Qnil, Qrear_nonsticky, Qt},
+    remacs_sys::{Fcopy_sequence, Lisp_Buffer},
+    remacs_sys::{Fnext_char_property_change, Fprevious_char_property_change},
+    remacs_sys::{Qfront_sticky, Qintangible, Qinvisible, Qpoint_entered, Qpoint_left},
+    threads::ThreadState,
+};
+
+pub type IntervalRef = ExternalPtr<interval>;
+
+/// A stretch of text with the same properties, as (START END PLIST).
+pub type PropertyRun = (EmacsInt, EmacsInt, LispObject);
+
+/// Return true if PLIST and OTHER have the same properties with `eq'
+/// values, in any order.  A malformed plist is not equal to anything.
+/// This compares bare property lists, which carry none of the cached
+/// flags of an interval; `IntervalRef::props_equal' compares those too.
+pub fn plists_equal(plist: LispObject, other: LispObject) -> bool {
+    /// Return the value of PROP in PLIST, or None if PLIST doesn't
+    /// have it or is malformed before it.
+    fn plist_value(plist: LispObject, prop: LispObject) -> Option<LispObject> {
+        let mut tail = plist;
+        while let Some(cons) = tail.as_cons() {
+            let rest = cons.cdr().as_cons()?;
+            if cons.car().eq(prop) {
+                return Some(rest.car());
+            }
+            tail = rest.cdr();
+        }
+        None
+    }
+
+    let (mut tail, mut other_tail) = (plist, other);
+    while let (Some(cons), Some(other_cons)) = (tail.as_cons(), other_tail.as_cons()) {
+        let (prop, rest) = match cons.cdr().as_cons() {
+            Some(rest) => (cons.car(), rest),
+            None => return false,
+        };
+        match plist_value(other, prop) {
+            Some(value) if value.eq(rest.car()) => {}
+            _ => return false,
+        }
+        tail = rest.cdr();
+        other_tail = match other_cons.cdr().as_cons() {
+            Some(other_rest) => other_rest.cdr(),
+            None => return false,
+        };
+    }
+    // The plists have the same length.
+    tail.is_nil() && other_tail.is_nil()
+}
+
+bitflags! {
+    /// The cached properties of an interval, which duplicate ones on its
+    /// plist for speed: the bits of `enum interval_cache' in intervals.h.
+    #[derive(Default)]
+    pub struct IntervalFlags: u8 {
+        /// The text can't be modified.
+        const WRITE_PROTECT = 1;
+        /// The text is displayed.
+        const VISIBLE = 2;
+        /// Text inserted just before the interval goes into it.
+        const FRONT_STICKY = 4;
+        /// Text inserted just after the interval goes into it.
+        const REAR_STICKY = 8;
+    }
+}
+
+impl IntervalRef {
+    pub fn from_raw(i: *mut interval) -> Option<Self> {
+        if i.is_null() {
+            None
+        } else {
+            Some(IntervalRef::new(i))
+        }
+    }
+
+    /// The total length of the subtree at I, which may be null.
+    fn subtree_length(i: *mut interval) -> EmacsInt {
+        unsafe { i.as_ref() }.map_or(0, |i| i.total_length as EmacsInt)
+    }
+
+    /// The length of the text of this interval alone.
+    pub fn length(self) -> EmacsInt {
+        self.total_length as EmacsInt
+            - Self::subtree_length(self.left)
+            - Self::subtree_length(self.right)
+    }
+
+    /// The position of the start of this interval.  This is only valid
+    /// for the interval returned by `find_interval'.
+    pub fn position(self) -> EmacsInt {
+        self.position as EmacsInt
+    }
+
+    pub fn plist(self) -> LispObject {
+        self.plist
+    }
+
+    /// Return the value of the text property PROP of this interval.
+    pub fn get_property(self, prop: LispObject) -> LispObject {
+        textget(self.plist, prop)
+    }
+
+    pub fn flags(self) -> IntervalFlags {
+        IntervalFlags::from_bits_truncate(self.cache() as u8)
+    }
+
+    pub fn set_flags(mut self, flags: IntervalFlags) {
+        self.set_cache(flags.bits().into())
+    }
+
+    /// Make the interval TARGET have exactly the properties of this one.
+    pub fn copy_properties(self, mut target: Self) {
+        if self.plist.is_nil() && target.plist.is_nil() {
+            return;
+        }
+        target.set_flags(self.flags());
+        target.plist = unsafe { Fcopy_sequence(self.plist) };
+    }
+
+    /// Add each property of this interval to the interval TARGET, if
+    /// TARGET has no such property as yet.
+    pub fn merge_properties(self, mut target: Self) {
+        if self.plist.is_nil() && target.plist.is_nil() {
+            return;
+        }
+        target.set_flags(target.flags() | self.flags());
+
+        let mut tail = self.plist;
+        while let Some(cons) = tail.as_cons() {
+            let (prop, rest) = (cons.car(), cons.cdr().as_cons_or_error());
+            let mut props = target.plist;
+            while let Some(c) = props.as_cons() {
+                if c.car().eq(prop) {
+                    break;
+                }
+                props = c.cdr().as_cons().map_or(Qnil, |c| c.cdr());
+            }
+            if props.is_nil() {
+                target.plist = LispObject::cons(prop, LispObject::cons(rest.car(), target.plist));
+            }
+            tail = rest.cdr();
+        }
+    }
+
+    /// Return true if this interval and OTHER have the same properties
+    /// with `eq' values, in any order, and the same cached flags.  A
+    /// malformed plist is not equal to anything.
+    pub fn props_equal(self, other: Self) -> bool {
+        self.flags() == other.flags() && plists_equal(self.plist, other.plist)
+    }
+
+    /// Merge the interval containing POSITION in the tree rooted at this
+    /// interval with the intervals before and after it, if they have the
+    /// same properties, and return the interval containing POSITION
+    /// afterwards.  This can change the root of the tree.
+    pub fn coalesce_around(mut self, position: EmacsInt) -> Self {
+        let mut i = Self::new(unsafe { find_interval(self.as_mut(), position as ptrdiff_t) });
+        if let Some(prev) = previous_interval_ref(Some(i)) {
+            if prev.props_equal(i) {
+                i = i.merge_left();
+            }
+        }
+        if let Some(next) = next_interval_ref(Some(i)) {
+            if i.props_equal(next) {
+                next.merge_left();
+            }
+        }
+        i
+    }
+
+    /// Return a new interval tree with the properties of the text from
+    /// START to START + LENGTH in the tree rooted at this interval.  The
+    /// new tree is balanced, has no parent and starts at position 0.
+    /// Return None if the text has no properties.
+    pub fn copy_range(mut self, start: EmacsInt, length: EmacsInt) -> Option<Self> {
+        if length <= 0 {
+            return None;
+        }
+        if self.parent().is_none()
+            && start == self.origin()
+            && length == self.total_length as EmacsInt
+        {
+            let mut new = self.deep_clone();
+            new.position = 0;
+            return Some(new);
+        }
+        let mut i = Self::from_raw(unsafe { find_interval(self.as_mut(), start as ptrdiff_t) })?;
+
+        // If there is only one interval and it has no properties, there
+        // is nothing to copy.
+        if start - i.position() + 1 + length < i.length() && i.plist().is_nil() {
+            return None;
+        }
+
+        // Build the tree from the runs in one go, rather than by
+        // splitting, which would make a chain as long as the runs for
+        // `balance_intervals' to recurse down.
+        let mut runs = Vec::new();
+        let mut got = 0;
+        let mut offset = start - i.position();
+        loop {
+            let run = (i.length() - offset).min(length - got);
+            runs.push((run, unsafe { Fcopy_sequence(i.plist()) }));
+            got += run;
+            if got >= length {
+                break;
+            }
+            offset = 0;
+            i = next_interval_ref(Some(i)).unwrap();
+        }
+        let mut new = Self::from_runs_1(&runs)?;
+        new.position = 0;
+        Some(new)
+    }
+
+    /// Give the text of BUFFER from POSITION, which was just inserted,
+    /// the properties of the tree rooted at this interval, which must
+    /// be as long as the text.  The tree is a copy made by `copy_range',
+    /// or the whole tree of a buffer or string; its intervals are never
+    /// shared with BUFFER.
+    ///
+    /// The text is already in the interval it went into, whose properties
+    /// `offset_intervals' chose according to stickiness.  With INHERIT,
+    /// the properties of the tree are added to those; otherwise they
+    /// replace them.
+    pub fn graft(self, mut buffer: LispBufferRef, position: EmacsInt, inherit: bool) {
+        let object = buffer.as_lisp_obj();
+        if buffer.z() as EmacsInt - buffer.beg() as EmacsInt == self.total_length as EmacsInt {
+            // The inserted text is the whole buffer, so just copy the
+            // tree.
+            reproduce_tree(self.as_ptr() as *mut interval, object);
+            return;
+        }
+        let mut tree = match Self::from_raw(buffer.intervals()) {
+            Some(tree) => tree,
+            None => Self::new(unsafe { create_root_interval(object) }),
+        };
+        debug_assert!(tree.total_length > 0);
+
+        // Split the interval the text went into where the text starts,
+        // so that it starts an interval.
+        let mut under = Self::new(unsafe { find_interval(tree.as_mut(), position as ptrdiff_t) });
+        if position > under.position() {
+            let offset = position - under.position();
+            let before = under.split_left(offset);
+            under.copy_properties(before);
+            under.position = position as ptrdiff_t;
+        }
+
+        // Give each interval of the tree, OVER, to as many intervals of
+        // the buffer as it covers, splitting the last one if OVER ends
+        // inside it.
+        let mut under = Some(under);
+        for (start, end, over) in self.iter_ordered() {
+            let mut left = end - start;
+            while left > 0 {
+                let mut target = under.expect("grafting intervals past the end of the buffer");
+                if left < target.length() {
+                    let this = target.split_left(left);
+                    target.copy_properties(this);
+                    target = this;
+                }
+                if inherit {
+                    over.merge_properties(target);
+                } else {
+                    over.copy_properties(target);
+                }
+                left -= target.length();
+                under = next_interval_ref(Some(target));
+            }
+        }
+
+        buffer_balance_intervals(buffer.as_mut());
+    }
+
+    /// Return true if text inserted in the middle of this interval,
+    /// rather than at either end, can simply get its properties: that
+    /// is, if none of them is a problem for the insertion.
+    ///
+    /// Properties front-sticky and rear-nonsticky override
+    /// `text-property-default-nonsticky', so if they are t, the
+    /// properties need not be checked one by one.
+    fn inherits_insertion(self) -> bool {
+        let rear = textget(self.plist, Qrear_nonsticky);
+        if rear.is_not_nil() && !rear.is_cons() {
+            // All properties are nonsticky.
+            return false;
+        }
+        let front = textget(self.plist, Qfront_sticky);
+        if front.is_not_nil() && !front.is_cons() {
+            // All properties are sticky.
+            return true;
+        }
+
+        let default_nonsticky = unsafe { globals.Vtext_property_default_nonsticky };
+        let mut tail = self.plist;
+        while let Some(cons) = tail.as_cons() {
+            let prop = cons.car();
+            tail = cons.cdr().as_cons_or_error().cdr();
+            if front.is_cons() && memq(prop, front).is_not_nil() {
+                continue;
+            }
+            if rear.is_cons() && memq(prop, rear).is_not_nil() {
+                return false;
+            }
+            // A property is rear-sticky unless it is listed in
+            // `text-property-default-nonsticky'.
+            if assq(prop, default_nonsticky).is_cons() {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Add LENGTH to the total length of this interval and of each of
+    /// its ancestors, rebalancing them on the way up.
+    fn extend(self, length: EmacsInt) {
+        let mut i = Some(self);
+        while let Some(mut this) = i {
+            this.total_length += length as ptrdiff_t;
+            let balanced = this.balance_possible_root();
+            i = balanced.parent();
+        }
+    }
+
+    /// Account for the insertion of LENGTH characters at POSITION in the
+    /// text of the tree rooted at this interval.  The text goes into the
+    /// interval containing POSITION, which is split first if the text
+    /// must not get all of its properties.  Between two intervals, the
+    /// text gets the properties that are sticky towards it, as
+    /// `merge_properties_sticky' computes them, in a new interval if
+    /// neither neighbor has just those.
+    ///
+    /// In a buffer, an insertion that just extends an interval leaves
+    /// the lengths of its ancestors to `flush_interval_offsets', so that
+    /// a run of insertions into it, like typing, updates them only once.
+    pub fn adjust_for_insert(mut self, position: EmacsInt, length: EmacsInt) {
+        debug_assert!(self.total_length > 0);
+        let buffer = if self.up_obj() {
+            unsafe { self.up.obj }.as_buffer()
+        } else {
+            None
+        };
+        let origin = self.origin();
+
+        // Inserting at the end of the text puts POSITION out of range.
+        let end = origin + self.total_length as EmacsInt;
+        let eobp = position >= end;
+        let position = position.min(end);
+
+        let mut i = Self::new(unsafe { find_interval(self.as_mut(), position as ptrdiff_t) });
+
+        // In the middle of an interval whose properties can't all go to
+        // the insertion, split the interval where the text goes.
+        if !(position == i.position() || eobp) && !i.inherits_insertion() {
+            let offset = position - i.position();
+            let temp = i.split_right(offset);
+            i.copy_properties(temp);
+            i = temp;
+        }
+
+        if position == i.position() || eobp {
+            // Between two intervals, or at the start or end of the text.
+            let (prev, next) = if position == origin {
+                (None, Some(i))
+            } else if eobp {
+                (Some(i), None)
+            } else {
+                (previous_interval_ref(Some(i)), Some(i))
+            };
+
+            // The text goes into the left interval if there is one; a
+            // part of it is split off below if stickiness demands it.
+            prev.or(next)
+                .expect("inserting into an empty interval tree")
+                .extend(length);
+
+            let plist = unsafe {
+                merge_properties_sticky(
+                    prev.map_or(Qnil, Self::plist),
+                    next.map_or(Qnil, Self::plist),
+                )
+            };
+            match prev {
+                None => {
+                    let next = next.unwrap();
+                    if !plists_equal(next.plist, plist) {
+                        let mut new = next.split_left(length);
+                        new.plist = plist;
+                    }
+                }
+                Some(prev) => {
+                    if !plists_equal(prev.plist, plist) {
+                        let offset = position - prev.position();
+                        let mut new = prev.split_right(offset);
+                        new.plist = plist;
+                        if next.map_or(false, |next| new.props_equal(next)) {
+                            new.merge_right();
+                        }
+                    }
+                }
+            }
+        } else if let Some(buffer) = buffer {
+            // Just extend the interval; see above.
+            i.total_length += length as ptrdiff_t;
+            unsafe {
+                (*buffer.text).pending_interval = i.as_mut();
+                (*buffer.text).pending_offset = length as ptrdiff_t;
+            }
+        } else {
+            i.extend(length);
+        }
+    }
+
+    /// Account for the deletion of LENGTH characters from START in the
+    /// text of the tree rooted at this interval, which must be the tree
+    /// of a buffer.  Intervals whose text is all deleted are removed
+    /// from the tree.
+    pub fn adjust_for_delete(mut self, start: EmacsInt, length: EmacsInt) {
+        debug_assert!(self.up_obj());
+        let mut buffer = unsafe { self.up.obj }.as_buffer_or_error();
+        let origin = self.origin();
+        let total = self.total_length as EmacsInt;
+        debug_assert!(start >= origin && start + length <= origin + total);
+
+        if length == total {
+            buffer.set_intervals(ptr::null_mut());
+            return;
+        }
+        if self.left.is_null() && self.right.is_null() {
+            self.total_length -= length as ptrdiff_t;
+            debug_assert!(self.length() > 0);
+            return;
+        }
+
+        let start = start.min(origin + total);
+        let mut tree = self;
+        let mut left_to_delete = length;
+        while left_to_delete > 0 {
+            left_to_delete -= tree.delete_from(start - origin, left_to_delete);
+            tree = Self::new(buffer.intervals());
+            if left_to_delete == tree.total_length as EmacsInt {
+                buffer.set_intervals(ptr::null_mut());
+                return;
+            }
+        }
+    }
+
+    /// Delete as much as possible of AMOUNT characters from the interval
+    /// containing the character FROM characters after the start of the
+    /// tree rooted at this interval, and return how many were deleted.
+    /// The interval is removed from the tree if it has no text left.
+    fn delete_from(self, mut from: EmacsInt, amount: EmacsInt) -> EmacsInt {
+        let mut i = self;
+        let own_end = loop {
+            let left_length = Self::subtree_length(i.left);
+            let own_end = i.total_length as EmacsInt - Self::subtree_length(i.right);
+            let child = if from < left_length {
+                i.left
+            } else if from >= own_end {
+                from -= own_end;
+                i.right
+            } else {
+                break own_end;
+            };
+            i = match Self::from_raw(child) {
+                Some(child) => child,
+                None => return 0,
+            };
+        };
+
+        let amount = amount.min(own_end - from);
+        let mut ancestor = Some(i);
+        while let Some(mut this) = ancestor {
+            this.total_length -= amount as ptrdiff_t;
+            ancestor = this.parent();
+        }
+        debug_assert!(i.length() >= 0);
+        if i.length() == 0 {
+            i.delete();
+        }
+        amount
+    }
+
+    /// Merge this interval into the one before it, which must exist, and
+    /// return that interval, whose properties it keeps.  This interval
+    /// is removed from the tree.
+    pub fn merge_left(mut self) -> Self {
+        let absorb = self.length() as ptrdiff_t;
+
+        // If the predecessor is below, add ABSORB on the way down to it.
+        if let Some(mut predecessor) = Self::from_raw(self.left) {
+            predecessor.total_length += absorb;
+            while let Some(mut right) = Self::from_raw(predecessor.right) {
+                right.total_length += absorb;
+                predecessor = right;
+            }
+            debug_assert!(predecessor.length() > 0);
+            self.delete();
+            return predecessor;
+        }
+
+        // Otherwise it is above: empty this interval, and take ABSORB
+        // off its ancestors on the way up to it.
+        self.total_length -= absorb;
+        let mut predecessor = self;
+        while let Some(mut parent) = predecessor.parent() {
+            if parent.right == predecessor.as_ptr() as *mut interval {
+                self.delete();
+                return parent;
+            }
+            parent.total_length -= absorb;
+            predecessor = parent;
+        }
+        panic!("merging the first interval to the left");
+    }
+
+    /// Merge this interval into the one after it, which must exist, and
+    /// return that interval, whose properties it keeps.  This interval
+    /// is removed from the tree.
+    pub fn merge_right(mut self) -> Self {
+        let absorb = self.length() as ptrdiff_t;
+
+        // If the successor is below, add ABSORB on the way down to it.
+        if let Some(mut successor) = Self::from_raw(self.right) {
+            successor.total_length += absorb;
+            while let Some(mut left) = Self::from_raw(successor.left) {
+                left.total_length += absorb;
+                successor = left;
+            }
+            debug_assert!(successor.length() > 0);
+            self.delete();
+            return successor;
+        }
+
+        // Otherwise it is above: empty this interval, and take ABSORB
+        // off its ancestors on the way up to it.
+        self.total_length -= absorb;
+        let mut successor = self;
+        while let Some(mut parent) = successor.parent() {
+            if parent.left == successor.as_ptr() as *mut interval {
+                self.delete();
+                return parent;
+            }
+            parent.total_length -= absorb;
+            successor = parent;
+        }
+        panic!("merging the last interval to the right");
+    }
+
+    /// Remove this interval, which must have no text of its own, from
+    /// its tree, putting its subtrees, merged into one, in its place.
+    /// If it is the root of the tree of a buffer or string, the merged
+    /// subtree becomes the tree of that object.
+    pub fn delete(mut self) {
+        debug_assert_eq!(self.length(), 0);
+        self.forget_tree();
+
+        let mut subtree = self.delete_node();
+        let tree = subtree.map_or(ptr::null_mut(), |mut s| s.as_mut());
+        match self.parent() {
+            Some(mut parent) => {
+                if parent.left == self.as_ptr() as *mut interval {
+                    parent.left = tree;
+                } else {
+                    parent.right = tree;
+                }
+                if let Some(ref mut subtree) = subtree {
+                    unsafe { (*subtree.as_mut()).up.interval = parent.as_mut() };
+                }
+                check_interval_tree(parent.as_mut());
+            }
+            None if self.up_obj() => {
+                let owner = unsafe { self.up.obj };
+                if let Some(ref mut subtree) = subtree {
+                    subtree.set_up_obj(true);
+                    unsafe { (*subtree.as_mut()).up.obj = owner };
+                }
+                if let Some(mut buffer) = owner.as_buffer() {
+                    buffer.set_intervals(tree);
+                } else if let Some(mut string) = owner.as_string() {
+                    string.set_intervals(tree);
+                } else {
+                    panic!("interval tree of neither a buffer nor a string");
+                }
+                check_interval_tree(tree);
+            }
+            // A tree that doesn't belong to any object yet.
+            None => {
+                if let Some(ref mut subtree) = subtree {
+                    unsafe { (*subtree.as_mut()).up.interval = ptr::null_mut() };
+                }
+                check_interval_tree(tree);
+            }
+        }
+    }
+
+    /// Merge the subtrees of this interval into one and return it, for
+    /// the caller to put in the place of this interval.  The left
+    /// subtree goes below the first interval of the right one.
+    fn delete_node(self) -> Option<Self> {
+        let (mut migrate, right) = match (Self::from_raw(self.left), Self::from_raw(self.right)) {
+            (None, subtree) | (subtree, None) => return subtree,
+            (Some(left), Some(right)) => (left, right),
+        };
+        let amount = migrate.total_length;
+        let mut this = right;
+        this.total_length += amount;
+        while let Some(mut left) = Self::from_raw(this.left) {
+            left.total_length += amount;
+            this = left;
+        }
+        this.left = migrate.as_mut();
+        unsafe { (*migrate.as_mut()).up.interval = this.as_mut() };
+        debug_assert!(this.length() > 0 && right.length() > 0);
+        Some(right)
+    }
+
+    /// Forget the intervals remembered for the buffer whose tree this
+    /// interval is in, since the tree is about to change without the
+    /// buffer being modified.
+    fn forget_tree(self) {
+        let mut root = self;
+        while let Some(parent) = root.parent() {
+            root = parent;
+        }
+        if root.up_obj() {
+            if let Some(mut buffer) = unsafe { root.up.obj }.as_buffer() {
+                forget_buffer_intervals(buffer.as_mut());
+            }
+        }
+    }
+
+    /// Split this interval OFFSET characters after its start, and
+    /// return a new interval for the text from there on, which is put
+    /// between this interval and its right subtree.  The new interval
+    /// has no properties; this one keeps its own, and its place in the
+    /// tree, so a root stays the root.
+    pub fn split_right(mut self, offset: EmacsInt) -> Self {
+        let mut new = Self::new(unsafe { make_interval() });
+        let new_length = self.length() - offset;
+        debug_assert!(offset > 0 && new_length > 0);
+
+        self.forget_tree();
+        new.position = self.position + offset as ptrdiff_t;
+        new.set_parent(self);
+        match Self::from_raw(self.right) {
+            None => new.total_length = new_length as ptrdiff_t,
+            Some(mut right) => {
+                new.right = right.as_mut();
+                right.set_parent(new);
+                new.total_length = new_length as ptrdiff_t + right.total_length;
+            }
+        }
+        self.right = new.as_mut();
+        new.balance();
+        self.balance_possible_root();
+        check_interval_tree(new.as_mut());
+        new
+    }
+
+    /// Split this interval OFFSET characters after its start, and
+    /// return a new interval for the text before there, which is put
+    /// between this interval and its left subtree.  The new interval
+    /// has no properties; this one keeps its own, and its place in the
+    /// tree, so a root stays the root.
+    pub fn split_left(mut self, offset: EmacsInt) -> Self {
+        let mut new = Self::new(unsafe { make_interval() });
+        debug_assert!(offset > 0 && offset < self.length());
+
+        self.forget_tree();
+        new.position = self.position;
+        self.position += offset as ptrdiff_t;
+        new.set_parent(self);
+        match Self::from_raw(self.left) {
+            None => new.total_length = offset as ptrdiff_t,
+            Some(mut left) => {
+                new.left = left.as_mut();
+                left.set_parent(new);
+                new.total_length = offset as ptrdiff_t + left.total_length;
+            }
+        }
+        self.left = new.as_mut();
+        new.balance();
+        self.balance_possible_root();
+        check_interval_tree(new.as_mut());
+        new
+    }
+
+    /// Make PARENT the parent of this interval.
+    fn set_parent(mut self, mut parent: Self) {
+        self.set_up_obj(false);
+        unsafe { (*self.as_mut()).up.interval = parent.as_mut() };
+    }
+
+    /// Put INTERVAL in the place of this one in the tree: make it the
+    /// child of the parent of this interval, or the root of the tree of
+    /// its object.  The object itself is left to the caller.
+    fn replace_with(self, mut interval: Self) {
+        if let Some(mut parent) = self.parent() {
+            if parent.left == self.as_ptr() as *mut interval {
+                parent.left = interval.as_mut();
+            } else {
+                parent.right = interval.as_mut();
+            }
+        }
+        unsafe { (*interval.as_mut()).up = self.up };
+        interval.set_up_obj(self.up_obj());
+    }
+
+    /// Rotate the subtree at this interval, A, to the right, and return
+    /// its left child, B, which takes the place of A.
+    ///
+    /// ```text
+    ///     A          B
+    ///    / \        / \
+    ///   B     =>       A
+    ///  / \            / \
+    ///     c          c
+    /// ```
+    fn rotate_right(mut self) -> Self {
+        let mut b = Self::new(self.left);
+        let c = b.right;
+        let old_total = self.total_length;
+        debug_assert!(self.length() > 0 && b.length() > 0);
+
+        self.replace_with(b);
+        b.right = self.as_mut();
+        self.set_parent(b);
+        self.left = c;
+        if let Some(c) = Self::from_raw(c) {
+            c.set_parent(self);
+        }
+
+        // A loses the lengths of B and of its left subtree, and B now
+        // covers all of the text A did.
+        self.total_length -= b.total_length - Self::subtree_length(c) as ptrdiff_t;
+        b.total_length = old_total;
+        debug_assert!(self.length() > 0 && b.length() > 0);
+        check_interval_tree(b.as_mut());
+        b
+    }
+
+    /// Rotate the subtree at this interval to the left; this is the
+    /// mirror image of `rotate_right'.
+    fn rotate_left(mut self) -> Self {
+        let mut b = Self::new(self.right);
+        let c = b.left;
+        let old_total = self.total_length;
+        debug_assert!(self.length() > 0 && b.length() > 0);
+
+        self.replace_with(b);
+        b.left = self.as_mut();
+        self.set_parent(b);
+        self.right = c;
+        if let Some(c) = Self::from_raw(c) {
+            c.set_parent(self);
+        }
+
+        self.total_length -= b.total_length - Self::subtree_length(c) as ptrdiff_t;
+        b.total_length = old_total;
+        debug_assert!(self.length() > 0 && b.length() > 0);
+        check_interval_tree(b.as_mut());
+        b
+    }
+
+    /// Balance the subtree at this interval by weight (the amount of
+    /// text), assuming that its own subtrees are balanced, and return
+    /// its new root.  Rotations are made as long as they bring the
+    /// lengths on either side closer.
+    pub fn balance(self) -> Self {
+        debug_assert!(self.length() > 0);
+        let mut i = self;
+        loop {
+            let old_diff = Self::subtree_length(i.left) - Self::subtree_length(i.right);
+            if old_diff > 0 {
+                // Since the left subtree is longer, there is one.
+                let left = Self::new(i.left);
+                let new_diff = i.total_length as EmacsInt - left.total_length as EmacsInt
+                    + Self::subtree_length(left.right)
+                    - Self::subtree_length(left.left);
+                if new_diff.abs() >= old_diff {
+                    break;
+                }
+                i = i.rotate_right();
+                Self::new(i.right).balance();
+            } else if old_diff < 0 {
+                let right = Self::new(i.right);
+                let new_diff = i.total_length as EmacsInt - right.total_length as EmacsInt
+                    + Self::subtree_length(right.left)
+                    - Self::subtree_length(right.right);
+                if new_diff.abs() >= -old_diff {
+                    break;
+                }
+                i = i.rotate_left();
+                Self::new(i.left).balance();
+            } else {
+                break;
+            }
+        }
+        i
+    }
+
+    /// Balance the subtree at this interval like `balance', and if it is
+    /// the root of the tree of a buffer or string, make the new root the
+    /// tree of that object.  An interval with neither a parent nor an
+    /// object is returned as it is.
+    pub fn balance_possible_root(self) -> Self {
+        let owner = if self.up_obj() {
+            Some(unsafe { self.up.obj })
+        } else if self.parent().is_none() {
+            return self;
+        } else {
+            None
+        };
+
+        let mut i = self.balance();
+        if let Some(owner) = owner {
+            if let Some(mut buffer) = owner.as_buffer() {
+                buffer.set_intervals(i.as_mut());
+            } else if let Some(mut string) = owner.as_string() {
+                string.set_intervals(i.as_mut());
+            }
+        }
+        i
+    }
+
+    /// Balance the whole tree rooted at this interval, each subtree
+    /// before its parent, and return its new root.  The order is that of
+    /// a recursive walk, but the intervals are collected first, so that
+    /// the depth of the tree doesn't matter.
+    pub fn balance_tree(self) -> Self {
+        // Parents before their children, right subtrees before left
+        // ones, so that the reverse order is a post-order walk.
+        let mut parents_first = Vec::new();
+        let mut stack = vec![self];
+        while let Some(i) = stack.pop() {
+            parents_first.push(i);
+            stack.extend(Self::from_raw(i.left));
+            stack.extend(Self::from_raw(i.right));
+        }
+        // Balancing a subtree keeps its intervals, so each one is still
+        // in the subtree of its original parent when that is balanced.
+        let mut root = self;
+        for i in parents_first.into_iter().rev() {
+            root = i.balance();
+        }
+        root
+    }
+
+    /// Return a copy of the tree rooted at this interval, with the same
+    /// shape and a copy of the plist of each interval.  The copy has no
+    /// parent.  The tree is walked with an explicit stack, so that a
+    /// degenerate tree can't overflow the C stack.
+    pub fn deep_clone(self) -> Self {
+        fn clone_node(source: IntervalRef) -> IntervalRef {
+            let mut new = IntervalRef::new(unsafe { make_interval() });
+            new.total_length = source.total_length;
+            new.position = source.position;
+            source.copy_properties(new);
+            new
+        }
+
+        let root = clone_node(self);
+        let mut stack = vec![(self, root)];
+        while let Some((source, mut copy)) = stack.pop() {
+            if let Some(left) = Self::from_raw(source.left) {
+                let mut new = clone_node(left);
+                unsafe { (*new.as_mut()).up.interval = copy.as_mut() };
+                copy.left = new.as_mut();
+                stack.push((left, new));
+            }
+            if let Some(right) = Self::from_raw(source.right) {
+                let mut new = clone_node(right);
+                unsafe { (*new.as_mut()).up.interval = copy.as_mut() };
+                copy.right = new.as_mut();
+                stack.push((right, new));
+            }
+        }
+        root
+    }
+
+    /// Make the tree rooted at this interval the interval tree of OWNER,
+    /// a buffer or a string, in place of the one OWNER had.  If the tree
+    /// belonged to another object, that object is left without text
+    /// properties, unless it has another tree by now, as after swapping
+    /// buffer texts.  The shape of the tree is kept, and the positions
+    /// cached in its intervals are renumbered from the start of the text
+    /// of OWNER: BEG for a buffer, and 0 for a string.
+    pub fn transplant(mut self, owner: LispObject) {
+        debug_assert!(self.parent().is_none(), "transplanting a subtree");
+        let this = self.as_mut();
+        if self.up_obj() {
+            let old = unsafe { self.up.obj };
+            if let Some(mut buffer) = old.as_buffer() {
+                if buffer.intervals() == this {
+                    buffer.set_intervals(ptr::null_mut());
+                }
+                forget_buffer_intervals(buffer.as_mut());
+            } else if let Some(mut string) = old.as_string() {
+                if string.intervals() == this {
+                    string.set_intervals(ptr::null_mut());
+                }
+            }
+        }
+        if let Some(mut buffer) = owner.as_buffer() {
+            buffer.set_intervals(this);
+            forget_buffer_intervals(buffer.as_mut());
+        } else if let Some(mut string) = owner.as_string() {
+            string.set_intervals(this);
+        } else {
+            wrong_type!(Qbuffer_or_string_p, owner);
+        }
+        self.set_up_obj(true);
+        unsafe { (*this).up.obj = owner };
+        // Walking the tree in order sets the positions.
+        for _ in self.iter_ordered() {}
+    }
+
+    /// Return a new interval tree for the text of OWNER, a buffer or
+    /// string, whose intervals have the lengths and property lists of
+    /// RUNS, in order.  The tree is perfectly balanced and is built in
+    /// time linear in the number of runs, rather than by splitting and
+    /// rebalancing.  The lengths must be positive.  Return None if RUNS
+    /// is empty.
+    pub fn from_runs(owner: LispObject, runs: &[(EmacsInt, LispObject)]) -> Option<Self> {
+        let mut root = Self::from_runs_1(runs)?;
+        root.set_up_obj(true);
+        unsafe { (*root.as_mut()).up.obj = owner };
+        Some(root)
+    }
+
+    /// Build the subtree for RUNS, with the middle run at its root.
+    fn from_runs_1(runs: &[(EmacsInt, LispObject)]) -> Option<Self> {
+        if runs.is_empty() {
+            return None;
+        }
+        let mid = runs.len() / 2;
+        let (length, plist) = runs[mid];
+        debug_assert!(length > 0);
+        let mut i = Self::new(unsafe { make_interval() });
+        i.plist = plist;
+        let left = Self::from_runs_1(&runs[..mid]);
+        let right = Self::from_runs_1(&runs[mid + 1..]);
+        for &child in left.iter().chain(right.iter()) {
+            unsafe { (*child.as_ptr() as *mut interval).up.interval = i.as_mut() };
+        }
+        i.left = left.map_or(ptr::null_mut(), |mut left| left.as_mut());
+        i.right = right.map_or(ptr::null_mut(), |mut right| right.as_mut());
+        i.total_length =
+            (length + Self::subtree_length(i.left) + Self::subtree_length(i.right)) as ptrdiff_t;
+        Some(i)
+    }
+
+    /// Give the intervals of the tree rooted at this interval, in the
+    /// order of the text, the lengths taken from LENGTHS, keeping the
+    /// shape of the tree, and return the new total length.  The cached
+    /// positions are not updated.  The tree is walked with explicit
+    /// stacks, like in `iter_ordered', so that its depth doesn't matter.
+    pub fn set_lengths<I>(self, lengths: &mut I) -> EmacsInt
+    where
+        I: Iterator<Item = EmacsInt>,
+    {
+        // First give each interval its own length, in the order of the
+        // text...
+        let mut stack = Vec::new();
+        let mut next = Some(self);
+        loop {
+            while let Some(i) = next {
+                stack.push(i);
+                next = Self::from_raw(i.left);
+            }
+            let mut i = match stack.pop() {
+                Some(i) => i,
+                None => break,
+            };
+            i.total_length = lengths.next().expect("too few interval lengths") as ptrdiff_t;
+            next = Self::from_raw(i.right);
+        }
+
+        // ...then add the lengths of the subtrees, children before
+        // their parents.
+        let mut parents_first = vec![self];
+        let mut k = 0;
+        while k < parents_first.len() {
+            let i = parents_first[k];
+            parents_first.extend(Self::from_raw(i.left));
+            parents_first.extend(Self::from_raw(i.right));
+            k += 1;
+        }
+        for mut i in parents_first.into_iter().rev() {
+            i.total_length +=
+                (Self::subtree_length(i.left) + Self::subtree_length(i.right)) as ptrdiff_t;
+        }
+        self.total_length as EmacsInt
+    }
+
+    /// Return an iterator over the intervals of the tree rooted at this
+    /// interval, in the order of the text, which yields each interval
+    /// with its start and end positions.  The position cache of each
+    /// interval is updated as it is reached, so `position' is valid for
+    /// the intervals it yields.
+    pub fn iter_ordered(self) -> OrderedIter {
+        let mut iter = OrderedIter { stack: Vec::new() };
+        iter.push_left(Some(self), self.origin());
+        iter
+    }
+
+    /// The position where the text of the tree rooted at this interval
+    /// starts: BEG for the root of a buffer's tree, and 0 otherwise.
+    fn origin(self) -> EmacsInt {
+        if self.up_obj() {
+            unsafe { self.up.obj }
+                .as_buffer()
+                .map_or(0, |buffer| buffer.beg() as EmacsInt)
+        } else {
+            0
+        }
+    }
+
+    /// Return statistics about the shape of the tree rooted at this
+    /// interval.
+    pub fn stats(self) -> IntervalStats {
+        let mut count = 0;
+        let mut max_depth = 0;
+        let mut stack = vec![(self, 1)];
+        while let Some((i, depth)) = stack.pop() {
+            count += 1;
+            max_depth = max_depth.max(depth);
+            for &child in &[i.left, i.right] {
+                if let Some(child) = Self::from_raw(child) {
+                    stack.push((child, depth + 1));
+                }
+            }
+        }
+        // A balanced tree of COUNT intervals is floor(log2(COUNT)) + 1
+        // levels deep.
+        let balanced_depth = 8 * mem::size_of::<usize>() - count.leading_zeros() as usize;
+        IntervalStats {
+            count,
+            max_depth,
+            average_length: self.total_length as f64 / count as f64,
+            imbalance: max_depth as f64 / balanced_depth as f64,
+        }
+    }
+
+    /// The parent of this interval, or None if it is the root of its tree.
+    fn parent(self) -> Option<Self> {
+        if self.up_obj() {
+            None
+        } else {
+            Self::from_raw(unsafe { self.up.interval })
+        }
+    }
+
+    /// Check the structure of the subtree rooted at this interval, and
+    /// panic if it is corrupt.  The total length of each interval must
+    /// be the sum of the lengths of its children and of its own text,
+    /// the children of each interval must point back to it, and every
+    /// interval but the root of the whole tree must have some text, so
+    /// that the intervals are in increasing order of position.
+    #[cfg(debug_assertions)]
+    pub fn check_invariants(self) {
+        if let Some(parent) = self.parent() {
+            let this = self.as_ptr() as *mut interval;
+            assert!(
+                parent.left == this || parent.right == this,
+                "interval is not a child of its parent"
+            );
+        }
+        let is_root = self.parent().is_none();
+        let mut stack = vec![self];
+        while let Some(i) = stack.pop() {
+            let length = i.length();
+            assert!(
+                length > 0 || (length == 0 && is_root && i.as_ptr() == self.as_ptr()),
+                "interval of length {} in the tree",
+                length
+            );
+            for &child in &[i.left, i.right] {
+                if let Some(child) = Self::from_raw(child) {
+                    assert!(
+                        child.parent().map(Self::as_ptr) == Some(i.as_ptr()),
+                        "child interval does not point to its parent"
+                    );
+                    stack.push(child);
+                }
+            }
+        }
+    }
+}
+
+/// Return the interval after I in its tree, with its position set from
+/// that of I, or None if I is the last interval or is None.  The
+/// position of I must be valid, as after `find_interval'.  This is
+/// `next_interval' for Rust, where the null interval is None, so that
+/// loops over the intervals read like their C counterparts.
+pub fn next_interval_ref(i: Option<IntervalRef>) -> Option<IntervalRef> {
+    let interval = i?;
+    let next_position = interval.position + interval.length() as ptrdiff_t;
+    if let Some(mut next) = IntervalRef::from_raw(interval.right) {
+        while let Some(left) = IntervalRef::from_raw(next.left) {
+            next = left;
+        }
+        next.position = next_position;
+        return Some(next);
+    }
+    let mut i = interval;
+    while let Some(mut parent) = i.parent() {
+        if parent.left == i.as_ptr() as *mut interval {
+            parent.position = next_position;
+            return Some(parent);
+        }
+        i = parent;
+    }
+    None
+}
+
+/// Return the interval before I in its tree, with its position set from
+/// that of I, or None if I is the first interval or is None.  This is
+/// `previous_interval' for Rust.  See `next_interval_ref'.
+pub fn previous_interval_ref(i: Option<IntervalRef>) -> Option<IntervalRef> {
+    let interval = i?;
+    if let Some(mut previous) = IntervalRef::from_raw(interval.left) {
+        while let Some(right) = IntervalRef::from_raw(previous.right) {
+            previous = right;
+        }
+        previous.position = interval.position - previous.length() as ptrdiff_t;
+        return Some(previous);
+    }
+    let mut i = interval;
+    while let Some(mut parent) = i.parent() {
+        if parent.right == i.as_ptr() as *mut interval {
+            parent.position = interval.position - parent.length() as ptrdiff_t;
+            return Some(parent);
+        }
+        i = parent;
+    }
+    None
+}
+
+/// Find the succeeding interval (lexicographically) to INTERVAL.
+/// Sets the `position' field based on that of INTERVAL (see
+/// `find_interval').
+#[no_mangle]
+pub extern "C" fn next_interval(interval: *mut interval) -> *mut interval {
+    next_interval_ref(IntervalRef::from_raw(interval)).map_or(ptr::null_mut(), |mut i| i.as_mut())
+}
+
+/// Find the preceding interval (lexicographically) to INTERVAL.
+/// Sets the `position' field based on that of INTERVAL (see
+/// `find_interval').
+#[no_mangle]
+pub extern "C" fn previous_interval(interval: *mut interval) -> *mut interval {
+    previous_interval_ref(IntervalRef::from_raw(interval))
+        .map_or(ptr::null_mut(), |mut i| i.as_mut())
+}
+
+/// Check the interval tree rooted at I after an operation that changed
+/// its structure.  See `IntervalRef::check_invariants'.
+#[cfg(debug_assertions)]
+#[no_mangle]
+pub extern "C" fn check_interval_tree(i: *mut interval) {
+    if let Some(i) = IntervalRef::from_raw(i) {
+        i.check_invariants();
+    }
+}
+
+/// Without debug assertions, interval trees are not checked.
+#[cfg(not(debug_assertions))]
+#[no_mangle]
+pub extern "C" fn check_interval_tree(_i: *mut interval) {}
+
+/// Remove the interval I, which must be empty, from its tree.  See
+/// `IntervalRef::delete'.
+#[no_mangle]
+pub extern "C" fn delete_interval(i: *mut interval) {
+    IntervalRef::new(i).delete()
+}
+
+/// Merge the interval I into the one before it.  See
+/// `IntervalRef::merge_left'.
+#[no_mangle]
+pub extern "C" fn merge_interval_left(i: *mut interval) -> *mut interval {
+    IntervalRef::new(i).merge_left().as_mut()
+}
+
+/// Merge the interval I into the one after it.  See
+/// `IntervalRef::merge_right'.
+#[no_mangle]
+pub extern "C" fn merge_interval_right(i: *mut interval) -> *mut interval {
+    IntervalRef::new(i).merge_right().as_mut()
+}
+
+/// Split the interval I at OFFSET.  See `IntervalRef::split_right'.
+#[no_mangle]
+pub extern "C" fn split_interval_right(i: *mut interval, offset: ptrdiff_t) -> *mut interval {
+    IntervalRef::new(i).split_right(offset as EmacsInt).as_mut()
+}
+
+/// Split the interval I at OFFSET.  See `IntervalRef::split_left'.
+#[no_mangle]
+pub extern "C" fn split_interval_left(i: *mut interval, offset: ptrdiff_t) -> *mut interval {
+    IntervalRef::new(i).split_left(offset as EmacsInt).as_mut()
+}
+
+/// Balance the subtree at I.  See `IntervalRef::balance'.
+#[no_mangle]
+pub extern "C" fn balance_an_interval(i: *mut interval) -> *mut interval {
+    IntervalRef::new(i).balance().as_mut()
+}
+
+/// Balance the subtree at I.  See `IntervalRef::balance_possible_root'.
+#[no_mangle]
+pub extern "C" fn balance_possible_root_interval(i: *mut interval) -> *mut interval {
+    IntervalRef::new(i).balance_possible_root().as_mut()
+}
+
+/// Balance the whole tree rooted at TREE, which may be null, and return
+/// its new root.
+#[no_mangle]
+pub extern "C" fn balance_intervals(tree: *mut interval) -> *mut interval {
+    IntervalRef::from_raw(tree).map_or(ptr::null_mut(), |tree| tree.balance_tree().as_mut())
+}
+
+/// Rebalance the interval tree of BUFFER.
+#[no_mangle]
+pub extern "C" fn buffer_balance_intervals(buffer: *mut Lisp_Buffer) {
+    let mut buffer = LispBufferRef::new(buffer);
+    if let Some(tree) = IntervalRef::from_raw(buffer.intervals()) {
+        buffer.set_intervals(tree.balance().as_mut());
+    }
+}
+
+/// An in-order iterator over an interval tree.  See
+/// `IntervalRef::iter_ordered'.
+pub struct OrderedIter {
+    /// The intervals whose right subtree is still to be visited, with
+    /// the start position of their subtree.
+    stack: Vec<(IntervalRef, EmacsInt)>,
+}
+
+impl OrderedIter {
+    /// Push I and the chain of its left children, whose subtrees all
+    /// start at START.
+    fn push_left(&mut self, mut i: Option<IntervalRef>, start: EmacsInt) {
+        while let Some(interval) = i {
+            self.stack.push((interval, start));
+            i = IntervalRef::from_raw(interval.left);
+        }
+    }
+}
+
+impl Iterator for OrderedIter {
+    type Item = (EmacsInt, EmacsInt, IntervalRef);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (mut i, subtree_start) = self.stack.pop()?;
+        let start = subtree_start + IntervalRef::subtree_length(i.left);
+        let end = start + i.length();
+        i.position = start as ptrdiff_t;
+        self.push_left(IntervalRef::from_raw(i.right), end);
+        Some((start, end, i))
+    }
+}
+
+/// Call FUNCTION with each interval of the tree TREE and ARG, in no
+/// particular order.  This is used by the garbage collector, so the
+/// tree is walked with an explicit stack rather than by recursion,
+/// which a degenerate tree could make too deep.
+#[no_mangle]
+pub extern "C" fn traverse_intervals_noorder(
+    tree: *mut interval,
+    function: extern "C" fn(*mut interval, *mut c_void),
+    arg: *mut c_void,
+) {
+    let mut stack: Vec<IntervalRef> = IntervalRef::from_raw(tree).into_iter().collect();
+    while let Some(mut i) = stack.pop() {
+        function(i.as_mut(), arg);
+        stack.extend(IntervalRef::from_raw(i.left));
+        stack.extend(IntervalRef::from_raw(i.right));
+    }
+}
+
+/// Call FUNCTION with each interval of the tree TREE and ARG, in the
+/// order of the text, which starts at POSITION.  The position of each
+/// interval is set before FUNCTION is called.
+#[no_mangle]
+pub extern "C" fn traverse_intervals(
+    tree: *mut interval,
+    position: ptrdiff_t,
+    function: extern "C" fn(*mut interval, LispObject),
+    arg: LispObject,
+) {
+    let mut iter = OrderedIter { stack: Vec::new() };
+    iter.push_left(IntervalRef::from_raw(tree), position as EmacsInt);
+    for (_, _, mut i) in iter {
+        function(i.as_mut(), arg);
+    }
+}
+
+/// The shape of an interval tree.  See `IntervalRef::stats'.
+pub struct IntervalStats {
+    /// The number of intervals in the tree.
+    pub count: usize,
+    /// The number of intervals on the longest path from the root to a
+    /// leaf.
+    pub max_depth: usize,
+    /// The average number of characters in an interval.
+    pub average_length: f64,
+    /// The ratio of the depth of the tree to that of a balanced tree
+    /// with as many intervals, which is 1.0 if the tree is balanced.
+    pub imbalance: f64,
+}
+
+/// Get the value of property PROP from PLIST, which is the plist of an
+/// interval.  We check for direct properties, for categories with
+/// property PROP, and for PROP appearing on the
+/// `default-text-properties' list.
+#[no_mangle]
+pub extern "C" fn textget(plist: LispObject, prop: LispObject) -> LispObject {
+    lookup_char_property(plist, prop, true)
+}
+
+/// Return true if the intervals I0 and I1, either of which may be None,
+/// have the same properties.
+fn intervals_equal_ref(i0: Option<IntervalRef>, i1: Option<IntervalRef>) -> bool {
+    let non_default = |i: Option<IntervalRef>| i.filter(|i| i.plist.is_not_nil());
+    match (non_default(i0), non_default(i1)) {
+        (None, None) => true,
+        (Some(i0), Some(i1)) => i0.props_equal(i1),
+        _ => false,
+    }
+}
+
+/// Return true if the intervals I0 and I1, either of which may be null,
+/// have the same properties.
+#[no_mangle]
+pub extern "C" fn intervals_equal(i0: *mut interval, i1: *mut interval) -> bool {
+    intervals_equal_ref(IntervalRef::from_raw(i0), IntervalRef::from_raw(i1))
+}
+
+/// Return the intervals of BUFFER containing the characters after and
+/// before CHARPOS.  Either one may be None, and they may be the same.
+fn intervals_around(
+    buffer: LispBufferRef,
+    charpos: ptrdiff_t,
+) -> (Option<IntervalRef>, Option<IntervalRef>) {
+    let after = IntervalRef::from_raw(unsafe { find_interval(buffer.intervals(), charpos) });
+    let before = if charpos == buffer.begv {
+        None
+    } else if after.map_or(false, |i| i.position == charpos) {
+        previous_interval_ref(after)
+    } else {
+        after
+    };
+    (after, before)
+}
+
+/// Return true if the value PROPVAL of an `invisible' property makes
+/// text invisible in BUFFER.
+fn means_invisible(buffer: LispBufferRef, propval: LispObject) -> bool {
+    let spec = buffer.invisibility_spec_;
+    if spec.eq(Qt) {
+        propval.is_not_nil()
+    } else {
+        unsafe { invisible_prop(propval, spec) != 0 }
+    }
+}
+
+/// If there's an invisible character at position POS + TEST_OFFS in the
+/// current buffer, and the invisible property has a `stickiness' such
+/// that inserting a character at position POS would inherit the property
+/// it, return POS + ADJ, otherwise return POS.  If TEST_INTANG,
+/// intangibility is required as well as invisibility.
+///
+/// TEST_OFFS should be either 0 or -1, and ADJ should be either 1 or -1.
+///
+/// Note that `stickiness' is determined by overlay marker insertion
+/// types, if the invisible property comes from an overlay.
+fn adjust_for_invis_intang(
+    pos: ptrdiff_t,
+    test_offs: ptrdiff_t,
+    adj: ptrdiff_t,
+    test_intang: bool,
+) -> ptrdiff_t {
+    let buffer = ThreadState::current_buffer();
+    if (adj < 0 && pos + adj < buffer.begv) || (adj > 0 && pos + adj > buffer.zv) {
+        // POS + ADJ would be beyond the buffer bounds, so do no
+        // adjustment.
+        return pos;
+    }
+
+    let test_pos = LispObject::from(pos + test_offs);
+    let mut overlay = Qnil;
+    let propval =
+        unsafe { get_char_property_and_overlay(test_pos, Qinvisible, Qnil, &mut overlay) };
+    if test_intang && get_char_property(test_pos.as_fixnum_or_error(), Qintangible, Qnil).is_nil() {
+        return pos;
+    }
+    if !means_invisible(buffer, propval) {
+        return pos;
+    }
+    // Is the stickiness of the property such that an insertion at POS
+    // would inherit it?
+    let inherited = match overlay.as_overlay() {
+        // The property is a text property.
+        None => {
+            let stickiness =
+                unsafe { text_property_stickiness(Qinvisible, LispObject::from(pos), Qnil) };
+            stickiness == if test_offs == 0 { 1 } else { -1 }
+        }
+        Some(overlay) => {
+            if test_offs == 0 {
+                !overlay.start.as_marker_or_error().insertion_type()
+            } else {
+                overlay.end.as_marker_or_error().insertion_type()
+            }
+        }
+    };
+    if inherited {
+        pos + adj
+    } else {
+        pos
+    }
+}
+
+/// Set point in the current buffer to CHARPOS, which corresponds to byte
+/// position BYTEPOS.  If the target position is before an intangible
+/// character, move to an ok place, and run the `point-left' and
+/// `point-entered' properties of the text that point leaves and enters.
+#[no_mangle]
+pub extern "C" fn set_point_both(mut charpos: ptrdiff_t, mut bytepos: ptrdiff_t) {
+    let mut buffer = ThreadState::current_buffer();
+    let old_position = buffer.pt;
+    // This ensures that we move forward past intangible text when the
+    // initial position is the same as the destination, in the rare
+    // instances where this is important, e.g. in line-move-finish
+    // (simple.el).
+    let backwards = charpos < old_position;
+
+    buffer.point_before_scroll_ = Qnil;
+
+    if charpos == old_position {
+        return;
+    }
+
+    // In a single-byte buffer, the two positions must be equal.
+    debug_assert!(buffer.zv != buffer.zv_byte || charpos == bytepos);
+    // Check this now, before checking if the buffer has any intervals.
+    debug_assert!(charpos <= buffer.zv && charpos >= buffer.begv);
+
+    let have_overlays = buffer.overlays_before().is_some() || buffer.overlays_after().is_some();
+
+    // If we have no text properties and overlays, then we can do it
+    // quickly.
+    if buffer.intervals().is_null() && !have_overlays {
+        buffer.set_pt_both(charpos, bytepos);
+        return;
+    }
+
+    // TO is the interval containing the char after CHARPOS, and TOPREV
+    // the interval containing the char before it.
+    let (mut to, mut toprev) = intervals_around(buffer, charpos);
+
+    // FROM is the interval containing the char after point, and
+    // FROMPREV the interval containing the char before it.
+    let buffer_point = if old_position == buffer.zv {
+        buffer.zv - 1
+    } else {
+        old_position
+    };
+    let mut from =
+        IntervalRef::from_raw(unsafe { find_interval(buffer.intervals(), buffer_point) });
+    let fromprev = if buffer_point == buffer.begv {
+        None
+    } else if from.map_or(false, |i| i.position == old_position) {
+        previous_interval_ref(from)
+    } else if buffer_point != old_position {
+        from.take()
+    } else {
+        from
+    };
+
+    // Moving within an interval.
+    if to.map(IntervalRef::as_ptr) == from.map(IntervalRef::as_ptr)
+        && toprev.map(IntervalRef::as_ptr) == fromprev.map(IntervalRef::as_ptr)
+        && to.map_or(false, |i| i.get_property(Qinvisible).is_nil())
+        && !have_overlays
+    {
+        buffer.set_pt_both(charpos, bytepos);
+        return;
+    }
+
+    let original_position = charpos;
+    let hooks_inhibited = unsafe { globals.Vinhibit_point_motion_hooks }.is_not_nil();
+
+    // If the new position is between two intangible characters with the
+    // same intangible property value, move forward or backward until a
+    // change in that property.  Intangibility never stops us from
+    // positioning at the beginning or end of the buffer.
+    if !hooks_inhibited
+        && ((to.is_some() && toprev.is_some()) || have_overlays)
+        && charpos != buffer.begv
+        && charpos != buffer.zv
+    {
+        let intangible = |pos: ptrdiff_t| get_char_property(pos as EmacsInt, Qintangible, Qnil);
+        if backwards {
+            // If the preceding character is both intangible and
+            // invisible, and the invisible property is `rear-sticky',
+            // start the search one character earlier, so that point can
+            // never move to the end of an invisible, intangible and
+            // rear-sticky region.
+            charpos = adjust_for_invis_intang(charpos, -1, -1, true);
+
+            // If the following character is intangible, skip back over
+            // all the characters with the same intangible property.
+            let propval = intangible(charpos);
+            if propval.is_not_nil() {
+                let mut pos = charpos;
+                while pos > buffer.begv && intangible(pos - 1).eq(propval) {
+                    pos = unsafe { Fprevious_char_property_change(LispObject::from(pos), Qnil) }
+                        .as_fixnum_or_error() as ptrdiff_t;
+                }
+                // If the last intangible character skipped is also
+                // invisible and `front-sticky', stop one character
+                // earlier, so that point can never move to the beginning
+                // of such a region.
+                charpos = adjust_for_invis_intang(pos, 0, -1, false);
+            }
+        } else {
+            // The same, forward: never move to the beginning of an
+            // invisible, intangible and front-sticky region.
+            charpos = adjust_for_invis_intang(charpos, 0, 1, true);
+
+            // If the preceding character is intangible, skip forward
+            // over all the characters with the same intangible property.
+            let propval = intangible(charpos - 1);
+            if propval.is_not_nil() {
+                let mut pos = charpos;
+                while pos < buffer.zv && intangible(pos).eq(propval) {
+                    pos = unsafe { Fnext_char_property_change(LispObject::from(pos), Qnil) }
+                        .as_fixnum_or_error() as ptrdiff_t;
+                }
+                // And never move to the end of an invisible, intangible
+                // and rear-sticky region.
+                charpos = adjust_for_invis_intang(pos, -1, 1, false);
+            }
+        }
+
+        bytepos = unsafe { buf_charpos_to_bytepos(buffer.as_mut(), charpos) };
+    }
+
+    if charpos != original_position {
+        let around = intervals_around(buffer, charpos);
+        to = around.0;
+        toprev = around.1;
+    }
+
+    // Here TO is the interval after the stopping point and TOPREV is
+    // the interval before the stopping point.  One or the other may be
+    // None.
+    buffer.set_pt_both(charpos, bytepos);
+
+    // Run the point-left and point-entered hooks if the intervals are
+    // not equivalent.  These hooks take (OLD-POINT NEW-POINT) as
+    // arguments.
+    if !hooks_inhibited
+        && (!intervals_equal_ref(from, to) || !intervals_equal_ref(fromprev, toprev))
+    {
+        let hook = |i: Option<IntervalRef>, prop| i.map_or(Qnil, |i| i.get_property(prop));
+        let leave_before = hook(fromprev, Qpoint_left);
+        let leave_after = hook(from, Qpoint_left);
+        let enter_before = hook(toprev, Qpoint_entered);
+        let enter_after = hook(to, Qpoint_entered);
+
+        let (old, new) = (
+            LispObject::from(old_position as EmacsInt),
+            LispObject::from(charpos as EmacsInt),
+        );
+        for &(function, other) in &[
+            (leave_before, enter_before),
+            (leave_after, enter_after),
+            (enter_before, leave_before),
+            (enter_after, leave_after),
+        ] {
+            if !function.eq(other) && function.is_not_nil() {
+                call!(function, old, new);
+            }
+        }
+    }
+}
+
+/// Set point in the current buffer to CHARPOS.  If the target position
+/// is before an intangible character, move to an ok place.
+#[no_mangle]
+pub extern "C" fn set_point(charpos: ptrdiff_t) {
+    let mut buffer = ThreadState::current_buffer();
+    let bytepos = unsafe { buf_charpos_to_bytepos(buffer.as_mut(), charpos) };
+    set_point_both(charpos, bytepos);
+}
+
+/// Merge the intervals at START and END of OBJECT, whose text properties
+/// have just been changed between them, with their neighbors if they
+/// have the same properties, so that the interval tree doesn't grow
+/// with each change.  OBJECT is the buffer or string that was changed.
+/// As this runs after the change hooks, which may have edited OBJECT,
+/// the intervals are fetched anew for each position, and positions
+/// that are no longer in OBJECT are ignored.
+#[no_mangle]
+pub extern "C" fn coalesce_intervals(object: LispObject, start: LispObject, end: LispObject) {
+    for &position in &[start, end] {
+        let position = position.as_fixnum_coerce_marker_or_error();
+        let (root, min, max) = if let Some(buffer) = object.as_buffer() {
+            if !buffer.is_live() {
+                return;
+            }
+            (
+                buffer.intervals(),
+                buffer.beg() as EmacsInt,
+                buffer.z() as EmacsInt,
+            )
+        } else if let Some(string) = object.as_string() {
+            (string.intervals(), 0, string.len_chars() as EmacsInt)
+        } else {
+            return;
+        };
+        if let Some(root) = IntervalRef::from_raw(root) {
+            if min <= position && position <= max {
+                root.coalesce_around(position);
+            }
+        }
+    }
+}
+
+/// Like `textget', for the property list PLIST of a text property or,
+/// if TEXTPROP is false, of an overlay, to which
+/// `default-text-properties' doesn't apply.  Aliases of PROP from
+/// `char-property-alias-alist' are checked too.
+#[no_mangle]
+pub extern "C" fn lookup_char_property(
+    plist: LispObject,
+    prop: LispObject,
+    textprop: bool,
+) -> LispObject {
+    let mut fallback = Qnil;
+    let mut tail = plist;
+    while let Some(cons) = tail.as_cons() {
+        let tem = cons.car();
+        if prop.eq(tem) {
+            return car(cons.cdr());
+        }
+        if tem.eq(Qcategory) {
+            if let Some(category) = car(cons.cdr()).as_symbol() {
+                fallback = get(category, prop);
+            }
+        }
+        tail = cdr(cons.cdr());
+    }
+    if fallback.is_not_nil() {
+        return fallback;
+    }
+
+    // Check for alternative properties.
+    let aliases = assq(prop, unsafe { globals.Vchar_property_alias_alist });
+    if let Some(aliases) = aliases.as_cons() {
+        fallback = aliases
+            .cdr()
+            .iter_cars_safe()
+            .map(|alias| plist_get(plist, alias))
+            .find(|value| value.is_not_nil())
+            .unwrap_or(Qnil);
+    }
+
+    let defaults = unsafe { globals.Vdefault_text_properties };
+    if textprop && fallback.is_nil() && defaults.is_cons() {
+        fallback = plist_get(defaults, prop);
+    }
+    fallback
+}
+
+/// Produce an interval tree reflecting the intervals in TREE from
+/// START to START + LENGTH.  The new interval tree has no parent and
+/// has a starting position of 0.
+#[no_mangle]
+pub extern "C" fn copy_intervals(
+    tree: *mut interval,
+    start: ptrdiff_t,
+    length: ptrdiff_t,
+) -> *mut interval {
+    IntervalRef::from_raw(tree)
+        .and_then(|tree| tree.copy_range(start as EmacsInt, length as EmacsInt))
+        .map_or(ptr::null_mut(), |mut copy| copy.as_mut())
+}
+
+/// Give the Lisp STRING, which has no properties yet, the text
+/// properties of BUFFER from POSITION to POSITION + LENGTH.
+pub fn copy_buffer_properties_to_string(
+    string: LispObject,
+    buffer: LispBufferRef,
+    position: EmacsInt,
+    length: EmacsInt,
+) {
+    let tree = IntervalRef::from_raw(buffer.intervals());
+    if let Some(copy) = tree.and_then(|tree| tree.copy_range(position, length)) {
+        copy.transplant(string);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn copy_properties(source: *mut interval, target: *mut interval) {
+    IntervalRef::new(source).copy_properties(IntervalRef::new(target))
+}
+
+#[no_mangle]
+pub extern "C" fn merge_properties(source: *mut interval, target: *mut interval) {
+    IntervalRef::new(source).merge_properties(IntervalRef::new(target))
+}
+
+/// Make the interval tree TREE the tree of OWNER, a buffer or a string.
+/// See `IntervalRef::transplant'.
+#[no_mangle]
+pub extern "C" fn transplant_intervals(tree: *mut interval, owner: LispObject) {
+    IntervalRef::new(tree).transplant(owner)
+}
+
+/// Give the text of BUFFER from POSITION to POSITION + LENGTH, which was
+/// just inserted, the properties of the interval tree SOURCE.  See
+/// `IntervalRef::graft'.  If SOURCE is null, the text has no properties
+/// of its own: with INHERIT, it keeps those of the interval it went
+/// into, and otherwise its properties are removed.
+#[no_mangle]
+pub extern "C" fn graft_intervals_into_buffer(
+    source: *mut interval,
+    position: ptrdiff_t,
+    length: ptrdiff_t,
+    buffer: *mut Lisp_Buffer,
+    inherit: bool,
+) {
+    let mut buffer = LispBufferRef::new(buffer);
+    if let Some(source) = IntervalRef::from_raw(source) {
+        debug_assert_eq!(length, source.total_length);
+        source.graft(buffer, position as EmacsInt, inherit);
+        return;
+    }
+    let tree = buffer.intervals();
+    if !inherit && !tree.is_null() && length > 0 {
+        unsafe {
+            set_text_properties_1(
+                LispObject::from(position as EmacsInt),
+                LispObject::from((position + length) as EmacsInt),
+                Qnil,
+                buffer.as_lisp_obj(),
+                find_interval(tree, position),
+            )
+        };
+    }
+    buffer_balance_intervals(buffer.as_mut());
+}
+
+/// Make the adjustments necessary to the interval tree of BUFFER to
+/// represent an addition or deletion of LENGTH characters starting at
+/// position START.  Addition or deletion is indicated by the sign of
+/// LENGTH.
+#[no_mangle]
+pub extern "C" fn offset_intervals(buffer: *mut Lisp_Buffer, start: ptrdiff_t, length: ptrdiff_t) {
+    let buffer = LispBufferRef::new(buffer);
+    let text = buffer.text;
+
+    // Another insertion in the middle of the interval that the last one
+    // went into only needs to extend it, and its ancestors can wait.
+    if let Some(mut pending) = IntervalRef::from_raw(unsafe { (*text).pending_interval }) {
+        if length > 0
+            && pending.position < start
+            && start < pending.position + pending.length() as ptrdiff_t
+            && pending.inherits_insertion()
+        {
+            pending.total_length += length;
+            unsafe { (*text).pending_offset += length };
+            return;
+        }
+    }
+
+    let tree = match IntervalRef::from_raw(buffer.intervals()) {
+        Some(tree) if length != 0 => tree,
+        _ => return,
+    };
+    if length > 0 {
+        tree.adjust_for_insert(start as EmacsInt, length as EmacsInt);
+    } else {
+        tree.adjust_for_delete(start as EmacsInt, -length as EmacsInt);
+    }
+}
+
+/// Make a copy of the interval tree SOURCE, with the same shape, and
+/// make it the tree of OWNER, a buffer or a string whose text is as long
+/// as that of SOURCE.  Return the copy.
+#[no_mangle]
+pub extern "C" fn reproduce_tree(source: *mut interval, owner: LispObject) -> *mut interval {
+    let mut copy = IntervalRef::new(source).deep_clone();
+    copy.transplant(owner);
+    copy.as_mut()
+}
+
+/// Give STRING the properties of BUFFER from POSITION to LENGTH.
+#[no_mangle]
+pub extern "C" fn copy_intervals_to_string(
+    string: LispObject,
+    buffer: *mut Lisp_Buffer,
+    position: ptrdiff_t,
+    length: ptrdiff_t,
+) {
+    copy_buffer_properties_to_string(
+        string,
+        LispBufferRef::new(buffer),
+        position as EmacsInt,
+        length as EmacsInt,
+    );
+}
+
+/// Check that BEGIN and END, positions or markers, delimit text of
+/// OBJECT, a buffer or a string, and return the interval containing
+/// BEGIN, for a function that operates on the text between them.
+///
+/// Markers are replaced by their positions, and BEGIN and END are
+/// swapped if needed.  Positions outside of OBJECT, or outside of the
+/// accessible portion of a buffer, signal `args-out-of-range'.  Return
+/// None if the range is empty or OBJECT has no text, or if OBJECT has
+/// no intervals yet and FORCE is false; if FORCE is true, the root
+/// interval of OBJECT is created and returned instead.
+pub fn validate_interval_range(
+    object: LispObject,
+    begin: &mut LispObject,
+    end: &mut LispObject,
+    force: bool,
+) -> Option<IntervalRef> {
+    let mut b = begin.as_fixnum_coerce_marker_or_error();
+    let mut e = end.as_fixnum_coerce_marker_or_error();
+    if b == e {
+        *begin = LispObject::from(b);
+        *end = LispObject::from(e);
+        return None;
+    }
+    if b > e {
+        mem::swap(&mut b, &mut e);
+    }
+    let i = interval_at(object, b, e, force);
+    *begin = LispObject::from(b);
+    *end = LispObject::from(e);
+    i
+}
+
+/// Like `validate_interval_range', for a function that looks at the
+/// character after POSITION.
+pub fn validate_interval_position(
+    object: LispObject,
+    position: &mut LispObject,
+    force: bool,
+) -> Option<IntervalRef> {
+    let pos = position.as_fixnum_coerce_marker_or_error();
+    *position = LispObject::from(pos);
+    interval_at(object, pos, pos, force)
+}
+
+/// The number of intervals remembered for a buffer by
+/// `find_buffer_interval'.
+const INTERVAL_CACHE_SIZE: usize = 4;
+
+/// The number of buffers whose intervals are remembered.
+const INTERVAL_CACHE_BUFFERS: usize = 4;
+
+#[derive(Clone, Copy)]
+struct CachedInterval {
+    interval: IntervalRef,
+    start: EmacsInt,
+    end: EmacsInt,
+}
+
+#[derive(Clone, Copy)]
+struct IntervalCache {
+    /// The text whose intervals are remembered, which indirect buffers
+    /// share with their base buffer, or null.
+    text: *mut buffer_text,
+    /// The modification count of the text when they were found.
+    modiff: EmacsInt,
+    intervals: [Option<CachedInterval>; INTERVAL_CACHE_SIZE],
+    /// The slot of the interval found last.
+    last: usize,
+}
+
+const EMPTY_INTERVAL_CACHE: IntervalCache = IntervalCache {
+    text: 0 as *mut buffer_text,
+    modiff: 0,
+    intervals: [None; INTERVAL_CACHE_SIZE],
+    last: 0,
 };
 
+static mut INTERVAL_CACHES: [IntervalCache; INTERVAL_CACHE_BUFFERS] =
+    [EMPTY_INTERVAL_CACHE; INTERVAL_CACHE_BUFFERS];
+
+/// The cache to reuse for the next buffer whose intervals are looked up.
+static mut NEXT_INTERVAL_CACHE: usize = 0;
+
+impl IntervalCache {
+    /// Return the cache of TEXT, emptied if its intervals are not the
+    /// ones remembered, as TEXT was modified since.
+    fn of(text: *mut buffer_text, modiff: EmacsInt) -> &'static mut IntervalCache {
+        let caches = unsafe { &mut INTERVAL_CACHES };
+        let index = match caches.iter().position(|cache| cache.text == text) {
+            Some(index) => index,
+            None => unsafe {
+                let index = NEXT_INTERVAL_CACHE;
+                NEXT_INTERVAL_CACHE = (index + 1) % INTERVAL_CACHE_BUFFERS;
+                caches[index] = EMPTY_INTERVAL_CACHE;
+                caches[index].text = text;
+                index
+            },
+        };
+        let cache = &mut caches[index];
+        if cache.modiff != modiff {
+            cache.intervals = [None; INTERVAL_CACHE_SIZE];
+            cache.modiff = modiff;
+        }
+        cache
+    }
+
+    fn lookup(&self, position: EmacsInt) -> Option<usize> {
+        self.intervals
+            .iter()
+            .position(|cached| cached.map_or(false, |c| c.start <= position && position < c.end))
+    }
+
+    fn remember(&mut self, interval: IntervalRef) {
+        let start = interval.position();
+        let end = start + interval.length();
+        self.last = match self.lookup(start) {
+            Some(slot) => slot,
+            None => (self.last + 1) % INTERVAL_CACHE_SIZE,
+        };
+        self.intervals[self.last] = Some(CachedInterval {
+            interval,
+            start,
+            end,
+        });
+    }
+}
+
+/// Return the interval of BUFFER containing POSITION, like `find_interval'
+/// on its tree ROOT.  The last few intervals found in a buffer are
+/// remembered until it is modified, and the interval after the last one
+/// found is tried before descending the tree, so that looking at the
+/// properties of text in order, as redisplay does, takes constant time
+/// for each position.
+fn find_buffer_interval(
+    buffer: LispBufferRef,
+    root: *mut interval,
+    position: EmacsInt,
+) -> Option<IntervalRef> {
+    let cache = IntervalCache::of(buffer.text, buffer.modifications());
+    if let Some(slot) = cache.lookup(position) {
+        let cached = cache.intervals[slot].unwrap();
+        let mut i = cached.interval;
+        // Other lookups may have moved the position of the interval.
+        i.position = cached.start as ptrdiff_t;
+        cache.last = slot;
+        return Some(i);
+    }
+    let after_last = cache.intervals[cache.last].and_then(|cached| {
+        let mut i = cached.interval;
+        i.position = cached.start as ptrdiff_t;
+        next_interval_ref(Some(i))
+    });
+    let i = match after_last {
+        Some(next) if next.position() <= position && position < next.position() + next.length() => {
+            next
+        }
+        _ => IntervalRef::from_raw(unsafe { find_interval(root, position as ptrdiff_t) })?,
+    };
+    cache.remember(i);
+    Some(i)
+}
+
+/// Forget the intervals of BUFFER remembered by `find_buffer_interval'.
+/// This must be called when its intervals change without its
+/// modification count, like when its text is swapped with another
+/// buffer's or made unibyte, when intervals are split or deleted, which
+/// coalescing does after the change hooks have run, and when it is
+/// created, since its text may be where the text of a killed buffer
+/// was.
+#[no_mangle]
+pub extern "C" fn forget_buffer_intervals(buffer: *mut Lisp_Buffer) {
+    let text = LispBufferRef::new(buffer).text;
+    for cache in unsafe { INTERVAL_CACHES.iter_mut() } {
+        if cache.text == text {
+            *cache = EMPTY_INTERVAL_CACHE;
+        }
+    }
+}
+
+fn interval_at(
+    object: LispObject,
+    begin: EmacsInt,
+    end: EmacsInt,
+    force: bool,
+) -> Option<IntervalRef> {
+    let (root, empty) = if let Some(buffer) = object.as_buffer() {
+        let (begv, zv) = (buffer.begv as EmacsInt, buffer.zv as EmacsInt);
+        if !(begv <= begin && begin <= end && end <= zv) {
+            args_out_of_range!(LispObject::from(begin), LispObject::from(end));
+        }
+        (buffer.intervals(), begv == zv)
+    } else if let Some(string) = object.as_string() {
+        let len = string.len_chars() as EmacsInt;
+        if !(0 <= begin && begin <= end && end <= len) {
+            args_out_of_range!(LispObject::from(begin), LispObject::from(end));
+        }
+        (string.intervals(), len == 0)
+    } else {
+        wrong_type!(Qbuffer_or_string_p, object);
+    };
+
+    // If there's no text, there are no properties.
+    if empty {
+        return None;
+    }
+    if root.is_null() {
+        return if force {
+            IntervalRef::from_raw(unsafe { create_root_interval(object) })
+        } else {
+            None
+        };
+    }
+    match object.as_buffer() {
+        Some(buffer) => find_buffer_interval(buffer, root, begin),
+        None => IntervalRef::from_raw(unsafe { find_interval(root, begin as ptrdiff_t) }),
+    }
+}
+
 /// Return the value of POSITION's property PROP, in OBJECT.
 /// Both overlay properties and text properties are checked.
 /// OBJECT is optional and defaults to the current buffer.
@@ -25,4 +2071,660 @@ pub fn get_char_property(position: EmacsInt, prop: LispObject, object: LispObjec
     }
 }
 
+/// Return the list of properties of the character at POSITION in OBJECT.
+/// If the optional second argument OBJECT is a buffer (or nil, which means
+/// the current buffer), POSITION is a buffer position (integer or marker).
+/// If OBJECT is a string, POSITION is a 0-based index into it.
+/// If POSITION is at the end of OBJECT, the value is nil.
+#[lisp_fn(min = "1")]
+pub fn text_properties_at(mut position: LispObject, object: LispObject) -> LispObject {
+    let object = if object.is_nil() {
+        ThreadState::current_buffer().as_lisp_obj()
+    } else {
+        object
+    };
+    match validate_interval_position(object, &mut position, false) {
+        // There are no properties at the very end, since no character
+        // follows.
+        Some(i) if position.as_fixnum_or_error() != i.position() + i.length() => i.plist(),
+        _ => Qnil,
+    }
+}
+
+/// Check text from START to END for property PROPERTY equaling VALUE.
+/// If so, return the position of the first character whose property PROPERTY
+/// is `eq' to VALUE.  Otherwise return nil.
+/// If the optional fifth argument OBJECT is a buffer (or nil, which means
+/// the current buffer), START and END are buffer positions (integers or
+/// markers).  If OBJECT is a string, START and END are 0-based indices into it.
+#[lisp_fn(min = "4")]
+pub fn text_property_any(
+    mut start: LispObject,
+    mut end: LispObject,
+    property: LispObject,
+    value: LispObject,
+    object: LispObject,
+) -> LispObject {
+    let object = if object.is_nil() {
+        ThreadState::current_buffer().as_lisp_obj()
+    } else {
+        object
+    };
+    let mut i = match validate_interval_range(object, &mut start, &mut end, false) {
+        Some(i) => i,
+        None if value.is_nil() && !start.eq(end) => return start,
+        None => return Qnil,
+    };
+    let (start, end) = (start.as_fixnum_or_error(), end.as_fixnum_or_error());
+    while i.position() < end {
+        if i.get_property(property).eq(value) {
+            return LispObject::from(i.position().max(start));
+        }
+        match next_interval_ref(Some(i)) {
+            Some(next) => i = next,
+            None => break,
+        }
+    }
+    Qnil
+}
+
+/// Return a copy of the text properties of OBJECT, a buffer or a string.
+/// The value is a list of elements (BEG END PLIST), one for each stretch
+/// of text with the same properties, in order, where BEG and END are
+/// positions in OBJECT.  Changing the value does not change the text
+/// properties of OBJECT.
+#[lisp_fn]
+pub fn object_intervals(object: LispObject) -> LispObject {
+    let root = if let Some(buffer) = object.as_buffer() {
+        buffer.intervals()
+    } else if let Some(string) = object.as_string() {
+        string.intervals()
+    } else {
+        wrong_type!(Qbuffer_or_string_p, object);
+    };
+    let intervals: Vec<LispObject> = IntervalRef::from_raw(root)
+        .into_iter()
+        .flat_map(IntervalRef::iter_ordered)
+        .filter(|&(start, end, _)| start < end)
+        .map(|(start, end, i)| {
+            list!(LispObject::from(start), LispObject::from(end), unsafe {
+                Fcopy_sequence(i.plist())
+            })
+        })
+        .collect();
+    LispObject::from(intervals)
+}
+
+/// Return the property runs of the text from START to END, starting
+/// with the interval FIRST that contains START.  The runs are clipped
+/// to the region; stretches of text without intervals are left out.
+fn property_runs(first: Option<IntervalRef>, start: EmacsInt, end: EmacsInt) -> Vec<PropertyRun> {
+    let mut runs = Vec::new();
+    let mut next = first;
+    while let Some(mut i) = next {
+        let from = i.position().max(start);
+        if from >= end {
+            break;
+        }
+        let to = (i.position() + i.length()).min(end);
+        if from < to {
+            runs.push((from, to, i.plist()));
+        }
+        next = next_interval_ref(Some(i));
+    }
+    runs
+}
+
+/// Return the plist of the run in RUNS containing POSITION, where INDEX
+/// is the run to start looking from, which is moved forward.  RUNS are
+/// in order, and POSITION must not be less than in the previous call.
+fn run_plist_at(runs: &[PropertyRun], index: &mut usize, position: EmacsInt) -> LispObject {
+    while runs
+        .get(*index)
+        .map_or(false, |&(_, end, _)| end <= position)
+    {
+        *index += 1;
+    }
+    match runs.get(*index) {
+        Some(&(start, _, plist)) if start <= position => plist,
+        _ => Qnil,
+    }
+}
+
+/// Compare the property runs OLD and NEW of the same text, and return
+/// the stretches whose properties differ, with their properties in OLD.
+/// Text that no run covers has no properties.  Neighbouring stretches
+/// with the same old plist are merged, so the result is as short as
+/// possible without looking into the plists.
+pub fn diff_property_runs(old: &[PropertyRun], new: &[PropertyRun]) -> Vec<PropertyRun> {
+    let mut bounds = Vec::with_capacity(2 * (old.len() + new.len()));
+    for &(start, end, _) in old.iter().chain(new) {
+        bounds.push(start);
+        bounds.push(end);
+    }
+    bounds.sort();
+    bounds.dedup();
+
+    let (mut old_index, mut new_index) = (0, 0);
+    let mut changes: Vec<PropertyRun> = Vec::new();
+    for pair in bounds.windows(2) {
+        let (start, end) = (pair[0], pair[1]);
+        let plist = run_plist_at(old, &mut old_index, start);
+        if plists_equal(plist, run_plist_at(new, &mut new_index, start)) {
+            continue;
+        }
+        match changes.last_mut() {
+            Some(last) if last.1 == start && last.2.eq(plist) => last.1 = end,
+            _ => changes.push((start, end, plist)),
+        }
+    }
+    changes
+}
+
+/// Return the `buffer-undo-list' entries that undo the property changes
+/// CHANGES, as returned by `diff_property_runs', to text whose current
+/// property runs are NEW.  Each entry is (nil PROP VAL BEG . END), which
+/// sets PROP back to VAL in a stretch, for the properties the stretch
+/// had before or has now.
+pub fn property_undo_entries(changes: &[PropertyRun], new: &[PropertyRun]) -> Vec<LispObject> {
+    let mut entries = Vec::new();
+    for &(start, end, plist) in changes {
+        let mut props: Vec<LispObject> = Vec::new();
+        let current = new
+            .iter()
+            .filter(|&&(s, e, _)| s < end && e > start)
+            .map(|&(_, _, current)| current);
+        for p in Some(plist).into_iter().chain(current) {
+            for prop in p.iter_cars_safe().step_by(2) {
+                if !props.iter().any(|known| known.eq(prop)) {
+                    props.push(prop);
+                }
+            }
+        }
+        entries.extend(props.into_iter().map(|prop| {
+            let bounds = LispObject::cons(LispObject::from(start), LispObject::from(end));
+            let value = plist_get(plist, prop);
+            LispObject::cons(
+                Qnil,
+                LispObject::cons(prop, LispObject::cons(value, bounds)),
+            )
+        }));
+    }
+    entries
+}
+
+/// Return a snapshot of the text properties of OBJECT from START to END.
+/// OBJECT is a buffer or a string, and defaults to the current buffer.
+/// After changing the properties of that text, pass the snapshot to
+/// `text-properties-undo-entries' to find out how to undo the changes.
+/// The value is a list (START END RUNS...), where each run is a list
+/// (BEG END PLIST) like in `object-intervals'.
+#[lisp_fn(min = "2")]
+pub fn text_properties_snapshot(
+    mut start: LispObject,
+    mut end: LispObject,
+    object: LispObject,
+) -> LispObject {
+    let object = if object.is_nil() {
+        ThreadState::current_buffer().as_lisp_obj()
+    } else {
+        object
+    };
+    let first = validate_interval_range(object, &mut start, &mut end, false);
+    let runs: Vec<LispObject> =
+        property_runs(first, start.as_fixnum_or_error(), end.as_fixnum_or_error())
+            .into_iter()
+            .map(|(from, to, plist)| {
+                list!(LispObject::from(from), LispObject::from(to), unsafe {
+                    Fcopy_sequence(plist)
+                })
+            })
+            .collect();
+    LispObject::cons(start, LispObject::cons(end, LispObject::from(runs)))
+}
+
+/// Return the undo entries for the property changes since SNAPSHOT.
+/// SNAPSHOT is the value of `text-properties-snapshot' for OBJECT, a
+/// buffer or a string that defaults to the current buffer, and the text
+/// it covers must still be there.  The value is a list of entries
+/// (nil PROP VAL BEG . END), as few as needed, that undo the changes
+/// when pushed onto `buffer-undo-list'; it is nil if nothing changed.
+#[lisp_fn(min = "1")]
+pub fn text_properties_undo_entries(snapshot: LispObject, object: LispObject) -> LispObject {
+    let object = if object.is_nil() {
+        ThreadState::current_buffer().as_lisp_obj()
+    } else {
+        object
+    };
+    let (mut start, mut end) = (car(snapshot), car(cdr(snapshot)));
+    let old: Vec<PropertyRun> = cdr(cdr(snapshot))
+        .iter_cars()
+        .map(|run| {
+            let (from, rest) = (car(run), cdr(run));
+            (
+                from.as_fixnum_or_error(),
+                car(rest).as_fixnum_or_error(),
+                car(cdr(rest)),
+            )
+        })
+        .collect();
+    let first = validate_interval_range(object, &mut start, &mut end, false);
+    let new = property_runs(first, start.as_fixnum_or_error(), end.as_fixnum_or_error());
+    let changes = diff_property_runs(&old, &new);
+    LispObject::from(property_undo_entries(&changes, &new))
+}
+
+/// Return statistics about the interval tree of OBJECT.
+/// OBJECT is a buffer or a string, and defaults to the current buffer.
+/// The interval tree holds the text properties of OBJECT, and its shape
+/// determines how fast they can be looked up.  The value is a plist
+/// with these properties:
+///
+/// :intervals       The number of intervals in the tree.
+/// :max-depth       The length of the longest path from its root.
+/// :average-length  The average number of characters in an interval.
+/// :imbalance       How many times deeper the tree is than a balanced
+///                  tree with as many intervals.
+///
+/// The value is nil if OBJECT has never had text properties.
+#[lisp_fn(min = "0")]
+pub fn interval_tree_statistics(object: LispObject) -> LispObject {
+    let root = if object.is_nil() {
+        ThreadState::current_buffer().intervals()
+    } else if let Some(buffer) = object.as_buffer() {
+        buffer.intervals()
+    } else if let Some(string) = object.as_string() {
+        string.intervals()
+    } else {
+        wrong_type!(Qbuffer_or_string_p, object);
+    };
+    match IntervalRef::from_raw(root) {
+        Some(root) => {
+            let stats = root.stats();
+            let keyword = |name: &str| intern(name).as_lisp_obj();
+            list!(
+                keyword(":intervals"),
+                LispObject::from(stats.count as EmacsInt),
+                keyword(":max-depth"),
+                LispObject::from(stats.max_depth as EmacsInt),
+                keyword(":average-length"),
+                LispObject::from_float(stats.average_length),
+                keyword(":imbalance"),
+                LispObject::from_float(stats.imbalance)
+            )
+        }
+        None => Qnil,
+    }
+}
+
 include!(concat!(env!("OUT_DIR"), "/textprop_exports.rs"));
+
+#[test]
+fn test_interval_flags() {
+    let flags = IntervalFlags::FRONT_STICKY | IntervalFlags::REAR_STICKY;
+    assert!(flags.contains(IntervalFlags::REAR_STICKY));
+    assert!(!flags.contains(IntervalFlags::WRITE_PROTECT | IntervalFlags::FRONT_STICKY));
+    assert!(IntervalFlags::default().contains(IntervalFlags::default()));
+}
+
+#[test]
+fn test_props_equal_flags() {
+    let (i, j) = (test_interval(), test_interval());
+    assert!(i.props_equal(j));
+    // The plists match, but the flags don't.
+    i.set_flags(IntervalFlags::FRONT_STICKY);
+    assert!(plists_equal(i.plist(), j.plist()));
+    assert!(!i.props_equal(j));
+    assert!(!j.props_equal(i));
+    j.set_flags(IntervalFlags::FRONT_STICKY);
+    assert!(i.props_equal(j));
+    test_free_tree(i);
+    test_free_tree(j);
+}
+
+/// Make an interval with no properties, with the `make_interval' of
+/// functions.rs, which the tests can free.
+#[cfg(test)]
+fn test_interval() -> IntervalRef {
+    IntervalRef::new(unsafe { make_interval() })
+}
+
+/// Make CHILD the left or right child of PARENT.
+#[cfg(test)]
+fn test_attach(mut parent: IntervalRef, mut child: IntervalRef, left: bool) {
+    unsafe { (*child.as_mut()).up.interval = parent.as_mut() };
+    if left {
+        parent.left = child.as_mut();
+    } else {
+        parent.right = child.as_mut();
+    }
+}
+
+/// Free a tree made with `test_interval'.
+#[cfg(test)]
+fn test_free_tree(root: IntervalRef) {
+    let mut stack = vec![root];
+    while let Some(mut i) = stack.pop() {
+        stack.extend(IntervalRef::from_raw(i.left));
+        stack.extend(IntervalRef::from_raw(i.right));
+        drop(unsafe { Box::from_raw(i.as_mut()) });
+    }
+}
+
+#[test]
+fn test_set_lengths_deep_tree() {
+    // A chain of right children, as a tree that was never rebalanced
+    // can be, is too deep to walk by recursion.
+    let n: EmacsInt = 100_000;
+    let root = test_interval();
+    let mut last = root;
+    for _ in 1..n {
+        let next = test_interval();
+        test_attach(last, next, false);
+        last = next;
+    }
+    assert_eq!(root.set_lengths(&mut (1..=n)), n * (n + 1) / 2);
+    for (k, (start, end, _)) in root.iter_ordered().enumerate() {
+        assert_eq!(end - start, k as EmacsInt + 1);
+    }
+    #[cfg(debug_assertions)]
+    root.check_invariants();
+    test_free_tree(root);
+}
+
+/// Make a tree of random shape with an interval for each of LENGTHS, in
+/// order.
+#[cfg(test)]
+fn test_random_tree<R: rand::Rng>(rng: &mut R, lengths: &[EmacsInt]) -> IntervalRef {
+    fn shape<R: rand::Rng>(rng: &mut R, count: usize) -> Option<IntervalRef> {
+        if count == 0 {
+            return None;
+        }
+        let left = rng.gen_range(0, count);
+        let root = test_interval();
+        if let Some(child) = shape(rng, left) {
+            test_attach(root, child, true);
+        }
+        if let Some(child) = shape(rng, count - left - 1) {
+            test_attach(root, child, false);
+        }
+        Some(root)
+    }
+    let root = shape(rng, lengths.len()).unwrap();
+    root.set_lengths(&mut lengths.iter().cloned());
+    root
+}
+
+/// Check that the tree rooted at ROOT is well formed and has intervals
+/// of LENGTHS, in order, whichever way it is walked.
+#[cfg(test)]
+fn test_check_tree(root: IntervalRef, lengths: &[EmacsInt]) {
+    #[cfg(debug_assertions)]
+    root.check_invariants();
+    assert_eq!(
+        root.total_length as EmacsInt,
+        lengths.iter().sum::<EmacsInt>()
+    );
+
+    let mut end = 0;
+    let model: Vec<_> = lengths
+        .iter()
+        .map(|&length| {
+            end += length;
+            (end - length, end)
+        })
+        .collect();
+    let runs: Vec<_> = root
+        .iter_ordered()
+        .map(|(start, end, _)| (start, end))
+        .collect();
+    assert_eq!(runs, model);
+
+    let run = |i: IntervalRef| (i.position(), i.position() + i.length());
+    let mut forward = Vec::new();
+    let mut i = root.iter_ordered().next().map(|(_, _, i)| i);
+    while let Some(this) = i {
+        forward.push(run(this));
+        i = next_interval_ref(i);
+    }
+    assert_eq!(forward, model);
+    let mut backward = Vec::new();
+    let mut i = root.iter_ordered().last().map(|(_, _, i)| i);
+    while let Some(this) = i {
+        backward.push(run(this));
+        i = previous_interval_ref(i);
+    }
+    backward.reverse();
+    assert_eq!(backward, model);
+}
+
+#[test]
+fn test_random_tree_walks() {
+    use rand::{Rng, SeedableRng, StdRng};
+
+    let mut rng = StdRng::from_seed(&[2, 6, 1][..]);
+    for _ in 0..200 {
+        let count = rng.gen_range(1, 64);
+        let lengths: Vec<EmacsInt> = (0..count).map(|_| rng.gen_range(1, 10)).collect();
+        let root = test_random_tree(&mut rng, &lengths);
+        test_check_tree(root, &lengths);
+        test_free_tree(root);
+    }
+}
+
+#[test]
+fn test_random_deletions() {
+    use rand::{Rng, SeedableRng, StdRng};
+
+    let mut rng = StdRng::from_seed(&[2, 6, 1][..]);
+    for _ in 0..100 {
+        let count = rng.gen_range(1, 32);
+        let mut lengths: Vec<EmacsInt> = (0..count).map(|_| rng.gen_range(1, 20)).collect();
+        let mut root = test_random_tree(&mut rng, &lengths);
+        for _ in 0..20 {
+            let k = rng.gen_range(0, lengths.len());
+            let offset = rng.gen_range(0, lengths[k]);
+            let amount = rng.gen_range(1, 20);
+            let deleted = amount.min(lengths[k] - offset);
+            // Deleting all the text leaves no tree at all.
+            if deleted == lengths[k] && lengths.len() == 1 {
+                continue;
+            }
+            let intervals: Vec<_> = root.iter_ordered().map(|(_, _, i)| i).collect();
+            let from = lengths[..k].iter().sum::<EmacsInt>() + offset;
+            assert_eq!(root.delete_from(from, amount), deleted);
+            lengths[k] -= deleted;
+            // An interval with no text left is removed from the tree,
+            // which may have a new root then.
+            if lengths[k] == 0 {
+                lengths.remove(k);
+                root = test_root(intervals[if k == 0 { 1 } else { 0 }]);
+                drop(unsafe { Box::from_raw(intervals[k].as_ptr() as *mut interval) });
+            }
+            test_check_tree(root, &lengths);
+        }
+        test_free_tree(root);
+    }
+}
+
+/// Return the root of the tree that I is in.
+#[cfg(test)]
+fn test_root(mut i: IntervalRef) -> IntervalRef {
+    while let Some(parent) = i.parent() {
+        i = parent;
+    }
+    i
+}
+
+#[test]
+fn test_random_merges() {
+    use rand::{Rng, SeedableRng, StdRng};
+
+    let mut rng = StdRng::from_seed(&[2, 5, 1][..]);
+    for _ in 0..100 {
+        let count = rng.gen_range(2, 32);
+        let mut lengths: Vec<EmacsInt> = (0..count).map(|_| rng.gen_range(1, 20)).collect();
+        let mut root = test_random_tree(&mut rng, &lengths);
+        // Number the intervals in their plists, to tell whose
+        // properties a merged interval keeps.
+        let mut numbers: Vec<EmacsInt> = (0..count as EmacsInt).collect();
+        for (k, (_, _, mut i)) in root.iter_ordered().enumerate() {
+            i.plist = LispObject::from(k as EmacsInt);
+        }
+        while lengths.len() > 1 {
+            let k = rng.gen_range(0, lengths.len());
+            let intervals: Vec<_> = root.iter_ordered().map(|(_, _, i)| i).collect();
+            let (merged, kept) = if k > 0 && (k + 1 == lengths.len() || rng.gen()) {
+                (intervals[k].merge_left(), k - 1)
+            } else {
+                (intervals[k].merge_right(), k + 1)
+            };
+            assert_eq!(merged.as_ptr(), intervals[kept].as_ptr());
+            drop(unsafe { Box::from_raw(intervals[k].as_ptr() as *mut interval) });
+            lengths[kept] += lengths[k];
+            lengths.remove(k);
+            numbers.remove(k);
+
+            root = test_root(merged);
+            test_check_tree(root, &lengths);
+            let plists: Vec<_> = root
+                .iter_ordered()
+                .map(|(_, _, i)| i.plist().as_fixnum().unwrap())
+                .collect();
+            assert_eq!(plists, numbers);
+        }
+        test_free_tree(root);
+    }
+}
+
+/// Check that no rotation at any interval of the tree rooted at ROOT
+/// would bring the lengths on either side closer, as `balance' makes
+/// sure.
+#[cfg(test)]
+fn test_check_balanced(root: IntervalRef) {
+    let length = IntervalRef::subtree_length;
+    for (_, _, i) in root.iter_ordered() {
+        let diff = length(i.left) - length(i.right);
+        if diff > 0 {
+            let left = IntervalRef::new(i.left);
+            let rotated = i.total_length as EmacsInt - left.total_length as EmacsInt
+                + length(left.right)
+                - length(left.left);
+            assert!(rotated.abs() >= diff);
+        } else if diff < 0 {
+            let right = IntervalRef::new(i.right);
+            let rotated = i.total_length as EmacsInt - right.total_length as EmacsInt
+                + length(right.left)
+                - length(right.right);
+            assert!(rotated.abs() >= -diff);
+        }
+    }
+}
+
+#[test]
+fn test_random_operations() {
+    use rand::{Rng, SeedableRng, StdRng};
+
+    // The model of the tree is the list of its intervals, as (LENGTH
+    // NUMBER), where NUMBER is what the plist of the interval holds.
+    let mut rng = StdRng::from_seed(&[2, 6, 1, 3][..]);
+    for _ in 0..100 {
+        let count = rng.gen_range(1, 16);
+        let mut model: Vec<(EmacsInt, EmacsInt)> = (0..count)
+            .map(|k| (rng.gen_range(1, 20), k as EmacsInt))
+            .collect();
+        let lengths: Vec<EmacsInt> = model.iter().map(|&(length, _)| length).collect();
+        let mut root = test_random_tree(&mut rng, &lengths);
+        for (k, (_, _, mut i)) in root.iter_ordered().enumerate() {
+            i.plist = LispObject::from(k as EmacsInt);
+        }
+        let mut number = count as EmacsInt;
+
+        for _ in 0..50 {
+            let intervals: Vec<_> = root.iter_ordered().map(|(_, _, i)| i).collect();
+            let k = rng.gen_range(0, model.len());
+            let (length, _) = model[k];
+            let survivor = intervals[if k == 0 { model.len() - 1 } else { 0 }];
+            let free = |i: IntervalRef| drop(unsafe { Box::from_raw(i.as_ptr() as *mut interval) });
+
+            root = match rng.gen_range(0, 7) {
+                0 if length > 1 => {
+                    let offset = rng.gen_range(1, length);
+                    let mut new = intervals[k].split_right(offset);
+                    new.plist = LispObject::from(number);
+                    model[k].0 = offset;
+                    model.insert(k + 1, (length - offset, number));
+                    number += 1;
+                    test_root(new)
+                }
+                1 if length > 1 => {
+                    let offset = rng.gen_range(1, length);
+                    let mut new = intervals[k].split_left(offset);
+                    new.plist = LispObject::from(number);
+                    model[k].0 = length - offset;
+                    model.insert(k, (offset, number));
+                    number += 1;
+                    test_root(new)
+                }
+                2 if k > 0 => {
+                    let merged = intervals[k].merge_left();
+                    assert_eq!(merged.as_ptr(), intervals[k - 1].as_ptr());
+                    free(intervals[k]);
+                    model[k - 1].0 += length;
+                    model.remove(k);
+                    test_root(merged)
+                }
+                3 if k + 1 < model.len() => {
+                    let merged = intervals[k].merge_right();
+                    assert_eq!(merged.as_ptr(), intervals[k + 1].as_ptr());
+                    free(intervals[k]);
+                    model[k + 1].0 += length;
+                    model.remove(k);
+                    test_root(merged)
+                }
+                4 if model.len() > 1 || length > 1 => {
+                    // The text of the last interval is never all deleted.
+                    let offset = rng.gen_range(if model.len() > 1 { 0 } else { 1 }, length);
+                    let amount = rng.gen_range(1, length - offset + 1);
+                    let from = model[..k].iter().map(|&(l, _)| l).sum::<EmacsInt>() + offset;
+                    assert_eq!(root.delete_from(from, amount), amount);
+                    model[k].0 -= amount;
+                    if model[k].0 == 0 {
+                        free(intervals[k]);
+                        model.remove(k);
+                        test_root(survivor)
+                    } else {
+                        test_root(root)
+                    }
+                }
+                5 => {
+                    let i = intervals[k];
+                    if !i.left.is_null() {
+                        i.rotate_right();
+                    } else if !i.right.is_null() {
+                        i.rotate_left();
+                    }
+                    test_root(survivor)
+                }
+                6 => {
+                    let root = root.balance_tree();
+                    test_check_balanced(root);
+                    root
+                }
+                _ => root,
+            };
+
+            let lengths: Vec<EmacsInt> = model.iter().map(|&(length, _)| length).collect();
+            test_check_tree(root, &lengths);
+            let numbers: Vec<EmacsInt> = root
+                .iter_ordered()
+                .map(|(_, _, i)| i.plist().as_fixnum().unwrap())
+                .collect();
+            assert_eq!(
+                numbers,
+                model.iter().map(|&(_, number)| number).collect::<Vec<_>>()
+            );
+        }
+        test_free_tree(root);
+    }
+}
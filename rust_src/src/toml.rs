@@ -0,0 +1,208 @@
+//! TOML parsing and serialization.
+//!
+//! Packages that read project files like `Cargo.toml' or
+//! `pyproject.toml' use these instead of parsing the files with
+//! regexps.  The Lisp representation of the data is the one of the
+//! JSON functions: tables are hash tables or alists, arrays are
+//! vectors, and false is `:false'.
+
+use std::collections::BTreeMap;
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    data::aset,
+    hashtable::{puthash, LispHashTableRef},
+    lisp::defsubr,
+    lisp::LispObject,
+    obarray::intern,
+    remacs_sys::{EmacsInt, Fmake_vector},
+    remacs_sys::{QCsize, QCtest, Qequal, Qhash_table, Qnil, Qsymbolp, Qt},
+    shell::make_lisp_string,
+    toml_crate::{self, Value},
+};
+
+/// Tables nested deeper than this are refused when serializing, so
+/// that circular structures don't exhaust the stack.
+const MAX_DEPTH: usize = 1000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum ObjectType {
+    HashTable,
+    Alist,
+}
+
+/// Parse the keyword arguments ARGS, as for `json-parse-string'.
+fn parse_object_type(args: &[LispObject]) -> ObjectType {
+    if args.len() % 2 != 0 {
+        error!("Odd number of keyword arguments");
+    }
+    let mut object_type = ObjectType::HashTable;
+    for pair in args.chunks(2) {
+        let (key, value) = (pair[0], pair[1]);
+        if !key.eq(intern(":object-type").as_lisp_obj()) {
+            error!("Invalid keyword argument {}", key);
+        }
+        object_type = if value.eq(Qhash_table) {
+            ObjectType::HashTable
+        } else if value.eq(intern("alist").as_lisp_obj()) {
+            ObjectType::Alist
+        } else {
+            error!("Invalid :object-type {}", value);
+        };
+    }
+    object_type
+}
+
+fn string_to_lisp(s: &str) -> LispObject {
+    make_lisp_string(s.as_bytes(), !s.is_ascii())
+}
+
+/// Convert the parsed VALUE to a Lisp object.
+fn value_to_lisp(value: &Value, object_type: ObjectType) -> LispObject {
+    match value {
+        Value::String(s) => string_to_lisp(s),
+        Value::Integer(n) => LispObject::int_or_float_from_fixnum(*n as EmacsInt),
+        Value::Float(f) => LispObject::from_float(*f),
+        Value::Boolean(true) => Qt,
+        Value::Boolean(false) => intern(":false").as_lisp_obj(),
+        Value::Datetime(d) => string_to_lisp(&d.to_string()),
+        Value::Array(elements) => {
+            let vector = unsafe { Fmake_vector(LispObject::from(elements.len()), Qnil) };
+            for (i, element) in elements.iter().enumerate() {
+                aset(vector, i as EmacsInt, value_to_lisp(element, object_type));
+            }
+            vector
+        }
+        Value::Table(table) => match object_type {
+            ObjectType::HashTable => {
+                let hash_table = call!(
+                    intern("make-hash-table").as_lisp_obj(),
+                    QCtest,
+                    Qequal,
+                    QCsize,
+                    LispObject::from(table.len())
+                );
+                let hash_ref: LispHashTableRef = hash_table.as_hash_table_or_error();
+                for (key, value) in table {
+                    puthash(
+                        string_to_lisp(key),
+                        value_to_lisp(value, object_type),
+                        hash_ref,
+                    );
+                }
+                hash_table
+            }
+            ObjectType::Alist => table.iter().rev().fold(Qnil, |alist, (key, value)| {
+                let key = intern(key).as_lisp_obj();
+                LispObject::cons(
+                    LispObject::cons(key, value_to_lisp(value, object_type)),
+                    alist,
+                )
+            }),
+        },
+    }
+}
+
+/// Return the UTF-8 text of the Lisp string or symbol name OBJECT.
+fn lisp_to_string(object: LispObject) -> String {
+    let string = match object.as_symbol() {
+        Some(symbol) => symbol.symbol_name(),
+        None => object,
+    }
+    .as_string_or_error();
+    match String::from_utf8(string.as_slice().to_vec()) {
+        Ok(s) => s,
+        Err(_) => error!("Invalid UTF-8 in {}", object),
+    }
+}
+
+/// Convert the Lisp OBJECT, nested DEPTH levels deep, to a TOML value.
+fn lisp_to_value(object: LispObject, depth: usize) -> Value {
+    if depth > MAX_DEPTH {
+        error!("Data is too deeply nested, or circular");
+    }
+
+    if object.eq(Qt) {
+        Value::Boolean(true)
+    } else if object.eq(intern(":false").as_lisp_obj()) {
+        Value::Boolean(false)
+    } else if let Some(n) = object.as_fixnum() {
+        Value::Integer(n as i64)
+    } else if let Some(f) = object.as_float() {
+        Value::Float(f)
+    } else if object.is_string() {
+        Value::String(lisp_to_string(object))
+    } else if let Some(vector) = object.as_vector() {
+        Value::Array(
+            vector
+                .as_slice()
+                .iter()
+                .map(|&element| lisp_to_value(element, depth + 1))
+                .collect(),
+        )
+    } else if object.is_hash_table() {
+        let mut table = BTreeMap::new();
+        for (key, value) in object.as_hash_table_or_error().iter() {
+            table.insert(lisp_to_string(key), lisp_to_value(value, depth + 1));
+        }
+        Value::Table(table)
+    } else if object.is_nil() || object.is_cons() {
+        // An alist; as with `assq', the first binding of a key wins.
+        let mut table = BTreeMap::new();
+        for pair in object.iter_cars() {
+            let pair = pair.as_cons_or_error();
+            let key = pair.car();
+            if !key.is_symbol() {
+                wrong_type!(Qsymbolp, key);
+            }
+            table
+                .entry(lisp_to_string(key))
+                .or_insert_with(|| lisp_to_value(pair.cdr(), depth + 1));
+        }
+        Value::Table(table)
+    } else {
+        error!("Value {} can't be serialized as TOML", object)
+    }
+}
+
+/// Parse the TOML document STRING and return its data.
+///
+/// The keyword argument `:object-type' specifies how to represent
+/// tables, as with `json-parse-string': `hash-table', the default,
+/// uses hash tables with string keys, and `alist' uses alists with
+/// symbol keys.  Keys of alists appear in sorted order.
+///
+/// Arrays are returned as vectors, false as `:false', and dates and
+/// times as strings in RFC 3339 format.  Integers that don't fit a
+/// fixnum are returned as floats.
+/// usage: (toml-parse-string STRING &rest ARGS)
+#[lisp_fn(min = "1")]
+pub fn toml_parse_string(args: &[LispObject]) -> LispObject {
+    args[0].as_string_or_error();
+    let text = lisp_to_string(args[0]);
+    let object_type = parse_object_type(&args[1..]);
+    match text.parse::<Value>() {
+        Ok(value) => value_to_lisp(&value, object_type),
+        Err(err) => error!("TOML parse error: {}", err),
+    }
+}
+
+/// Return the TOML representation of OBJECT as a string.
+///
+/// OBJECT must be a table, i.e. a hash table with string keys or an
+/// alist with symbol keys.  Values can be strings, numbers, `t',
+/// `:false', vectors and nested tables.
+#[lisp_fn]
+pub fn toml_serialize(object: LispObject) -> LispObject {
+    let value = lisp_to_value(object, 0);
+    if !value.is_table() {
+        error!("Value {} is not a TOML table", object);
+    }
+    match toml_crate::to_string(&value) {
+        Ok(text) => string_to_lisp(&text),
+        Err(err) => error!("TOML serialization error: {}", err),
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/toml_exports.rs"));
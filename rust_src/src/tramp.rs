@@ -0,0 +1,336 @@
+//! Native parsing of remote (TRAMP) file names.
+//!
+//! TRAMP checks whether a file name is remote from a great many hooks
+//! (`file-remote-p', `find-file-hook' functions, mode-line updates, ...)
+//! and each of those checks goes through `tramp-dissect-file-name',
+//! which matches a handful of large regexps.  The parser below
+//! understands the default TRAMP syntax
+//!
+//!     /METHOD:USER%DOMAIN@HOST#PORT:LOCALNAME
+//!
+//! including multi-hop names such as `/ssh:gw|sudo:root@host:/etc',
+//! and keeps the results of recent parses in a cache keyed by the raw
+//! bytes of the file name.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use libc::c_char;
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    lisp::defsubr,
+    lisp::LispObject,
+    multibyte::LispStringRef,
+    remacs_sys::{make_string, make_unibyte_string},
+    remacs_sys::{EmacsInt, Qnil},
+};
+
+/// Number of parsed file names kept in the cache.  The whole cache is
+/// dropped when it grows past this, which is good enough for the
+/// access pattern of hooks checking the same few names repeatedly.
+const TRAMP_CACHE_SIZE: usize = 512;
+
+/// The components of a remote file name.  All fields hold the raw
+/// bytes of the corresponding part of the name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RemoteFileName {
+    pub method: Vec<u8>,
+    pub user: Option<Vec<u8>>,
+    pub domain: Option<Vec<u8>>,
+    pub host: Option<Vec<u8>>,
+    pub port: Option<Vec<u8>>,
+    pub localname: Vec<u8>,
+    /// The hops preceding the last one, including the trailing `|', as
+    /// TRAMP stores them.
+    pub hop: Option<Vec<u8>>,
+}
+
+lazy_static! {
+    static ref TRAMP_CACHE: Mutex<HashMap<Vec<u8>, Option<RemoteFileName>>> =
+        Mutex::new(HashMap::new());
+}
+
+fn is_method_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'-'
+}
+
+fn is_user_byte(b: u8) -> bool {
+    !(b == b'/' || b == b'|' || b == b':' || b == b' ' || b == b'\t')
+}
+
+fn is_domain_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'.' || b == b'-'
+}
+
+fn is_host_byte(b: u8) -> bool {
+    is_domain_byte(b) || b == b'%'
+}
+
+/// Return true if ADDRESS matches `tramp-ipv6-regexp': groups of
+/// alphanumerics each followed by a colon, and a last group that may
+/// contain dots as well.
+fn is_ipv6_address(address: &[u8]) -> bool {
+    match address.iter().rposition(|&b| b == b':') {
+        Some(colon) => {
+            let last = &address[colon + 1..];
+            !last.is_empty()
+                && last.iter().all(|&b| b.is_ascii_alphanumeric() || b == b'.')
+                && address[..colon]
+                    .iter()
+                    .all(|&b| b.is_ascii_alphanumeric() || b == b':')
+        }
+        None => false,
+    }
+}
+
+/// Split USER at the domain, as `tramp-user-with-domain-regexp' does:
+/// the user name extends to the last `%' that is followed by a domain.
+fn split_domain(user: &[u8]) -> (&[u8], Option<&[u8]>) {
+    let percent = (1..user.len())
+        .rev()
+        .find(|&i| user[i] == b'%' && user.get(i + 1).map_or(false, |&b| is_domain_byte(b)));
+    match percent {
+        Some(i) => {
+            let domain = &user[i + 1..];
+            let len = domain.iter().take_while(|&&b| is_domain_byte(b)).count();
+            (&user[..i], Some(&domain[..len]))
+        }
+        None => (user, None),
+    }
+}
+
+/// One `METHOD:USER%DOMAIN@HOST#PORT' hop.
+struct Hop<'a> {
+    method: &'a [u8],
+    user: Option<&'a [u8]>,
+    domain: Option<&'a [u8]>,
+    host: Option<&'a [u8]>,
+    port: Option<&'a [u8]>,
+}
+
+/// Parse a single hop starting at the beginning of NAME.  Return the
+/// hop together with the number of bytes consumed, up to but not
+/// including the `:' or `|' terminating the host part.
+fn parse_hop(name: &[u8]) -> Option<(Hop, usize)> {
+    let method_len = name.iter().take_while(|&&b| is_method_byte(b)).count();
+    if method_len == 0 || name.get(method_len) != Some(&b':') {
+        return None;
+    }
+    let method = &name[..method_len];
+    let mut pos = method_len + 1;
+
+    // The user part is only present if an `@' follows it.  User names
+    // may contain `@' themselves, host names can't, so the user part
+    // extends to the last `@'.
+    let user_len = name[pos..].iter().take_while(|&&b| is_user_byte(b)).count();
+    let (user, domain) = match name[pos..pos + user_len].iter().rposition(|&b| b == b'@') {
+        Some(at) if at > 0 => {
+            let (user, domain) = split_domain(&name[pos..pos + at]);
+            pos += at + 1;
+            (Some(user), domain)
+        }
+        _ => (None, None),
+    };
+
+    let host = if name.get(pos) == Some(&b'[') {
+        // An IPv6 address in brackets.
+        let close = name[pos..].iter().position(|&b| b == b']')?;
+        let host = &name[pos + 1..pos + close];
+        if !host.is_empty() && !is_ipv6_address(host) {
+            return None;
+        }
+        pos += close + 1;
+        host
+    } else {
+        let host_len = name[pos..].iter().take_while(|&&b| is_host_byte(b)).count();
+        let host = &name[pos..pos + host_len];
+        pos += host_len;
+        host
+    };
+
+    let port = if !host.is_empty() && name.get(pos) == Some(&b'#') {
+        let port_len = name[pos + 1..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if port_len == 0 {
+            return None;
+        }
+        let port = &name[pos + 1..pos + 1 + port_len];
+        pos += port_len + 1;
+        Some(port)
+    } else {
+        None
+    };
+
+    match name.get(pos) {
+        Some(b':') | Some(b'|') => Some((
+            Hop {
+                method,
+                user,
+                domain,
+                host: if host.is_empty() { None } else { Some(host) },
+                port,
+            },
+            pos,
+        )),
+        _ => None,
+    }
+}
+
+/// Dissect NAME into its remote components, or return `None' if NAME
+/// is not a remote file name in the default TRAMP syntax.
+pub fn parse_remote_file_name(name: &[u8]) -> Option<RemoteFileName> {
+    // Quoted file names (`/:...') are always local.
+    if name.first() != Some(&b'/') || name.get(1) == Some(&b':') {
+        return None;
+    }
+
+    let mut pos = 1;
+    loop {
+        let (hop, len) = parse_hop(&name[pos..])?;
+        let end = pos + len;
+
+        if name[end] == b'|' {
+            pos = end + 1;
+            continue;
+        }
+
+        let hop_prefix = if pos > 1 {
+            Some(name[1..pos].to_vec())
+        } else {
+            None
+        };
+        // Like `tramp-localname-regexp', stop at the end of the line.
+        let localname = &name[end + 1..];
+        let localname = localname
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(localname, |newline| &localname[..newline]);
+
+        return Some(RemoteFileName {
+            method: hop.method.to_vec(),
+            user: hop.user.map(|u| u.to_vec()),
+            domain: hop.domain.map(|d| d.to_vec()),
+            host: hop.host.map(|h| h.to_vec()),
+            port: hop.port.map(|p| p.to_vec()),
+            localname: localname.to_vec(),
+            hop: hop_prefix,
+        });
+    }
+}
+
+/// Like `parse_remote_file_name', but consult and fill the cache.
+fn cached_parse(name: &[u8]) -> Option<RemoteFileName> {
+    let mut cache = TRAMP_CACHE.lock().unwrap();
+    if let Some(parsed) = cache.get(name) {
+        return parsed.clone();
+    }
+
+    let parsed = parse_remote_file_name(name);
+    if cache.len() >= TRAMP_CACHE_SIZE {
+        cache.clear();
+    }
+    cache.insert(name.to_vec(), parsed.clone());
+    parsed
+}
+
+fn make_component(bytes: &[u8], multibyte: bool) -> LispObject {
+    let ptr = bytes.as_ptr() as *const c_char;
+    let len = bytes.len() as isize;
+    unsafe {
+        if multibyte {
+            make_string(ptr, len)
+        } else {
+            make_unibyte_string(ptr, len)
+        }
+    }
+}
+
+/// Return non-nil if NAME is a remote file name in the default TRAMP syntax.
+/// This only looks at the syntax of NAME; it does not check whether
+/// METHOD is a known method, nor does it contact the remote host.
+#[lisp_fn]
+pub fn tramp_tramp_file_p_native(name: LispObject) -> bool {
+    name.as_string()
+        .map_or(false, |s| cached_parse(s.as_slice()).is_some())
+}
+
+/// Dissect the remote file name NAME into its components.
+/// Return a list (METHOD USER DOMAIN HOST PORT LOCALNAME HOP), suitable
+/// for `make-tramp-file-name', or nil if NAME is not a remote file name.
+/// Absent components are nil.  HOP holds all hops but the last one of a
+/// multi-hop file name, including the trailing `|'.
+///
+/// Results are cached, so calling this function repeatedly on the same
+/// file name is cheap.
+#[lisp_fn]
+pub fn tramp_dissect_file_name_native(name: LispStringRef) -> LispObject {
+    let multibyte = name.is_multibyte();
+    let component = |bytes: &[u8]| make_component(bytes, multibyte);
+    let optional = |bytes: &Option<Vec<u8>>| bytes.as_ref().map_or(Qnil, |b| component(b));
+
+    match cached_parse(name.as_slice()) {
+        None => Qnil,
+        Some(parsed) => list!(
+            component(&parsed.method),
+            optional(&parsed.user),
+            optional(&parsed.domain),
+            optional(&parsed.host),
+            optional(&parsed.port),
+            component(&parsed.localname),
+            optional(&parsed.hop)
+        ),
+    }
+}
+
+/// Forget all cached results of `tramp-dissect-file-name-native'.
+/// Return the number of entries that were removed.
+#[lisp_fn]
+pub fn tramp_clear_file_name_cache_native() -> EmacsInt {
+    let mut cache = TRAMP_CACHE.lock().unwrap();
+    let count = cache.len();
+    cache.clear();
+    count as EmacsInt
+}
+
+include!(concat!(env!("OUT_DIR"), "/tramp_exports.rs"));
+
+#[test]
+fn test_parse_remote_file_name() {
+    let parsed = parse_remote_file_name(b"/ssh:user%dom@host#2222:/tmp/x").unwrap();
+    assert_eq!(parsed.method, b"ssh");
+    assert_eq!(parsed.user, Some(b"user".to_vec()));
+    assert_eq!(parsed.domain, Some(b"dom".to_vec()));
+    assert_eq!(parsed.host, Some(b"host".to_vec()));
+    assert_eq!(parsed.port, Some(b"2222".to_vec()));
+    assert_eq!(parsed.localname, b"/tmp/x");
+    assert_eq!(parsed.hop, None);
+
+    let parsed = parse_remote_file_name(b"/ssh:gw|sudo:root@box:/etc").unwrap();
+    assert_eq!(parsed.method, b"sudo");
+    assert_eq!(parsed.host, Some(b"box".to_vec()));
+    assert_eq!(parsed.hop, Some(b"ssh:gw|".to_vec()));
+
+    let parsed = parse_remote_file_name(b"/ssh:[::1]:").unwrap();
+    assert_eq!(parsed.host, Some(b"::1".to_vec()));
+    assert_eq!(parsed.localname, b"");
+
+    // User names may contain `@' and `%'.
+    let parsed = parse_remote_file_name(b"/ssh:a@b%c%dom@host:").unwrap();
+    assert_eq!(parsed.user, Some(b"a@b%c".to_vec()));
+    assert_eq!(parsed.domain, Some(b"dom".to_vec()));
+
+    let parsed = parse_remote_file_name(b"/sudo::/etc\nmore").unwrap();
+    assert_eq!(parsed.host, None);
+    assert_eq!(parsed.localname, b"/etc");
+
+    assert_eq!(parse_remote_file_name(b"/ssh:host#:/tmp"), None);
+    assert_eq!(parse_remote_file_name(b"/ssh:[fe80]:/tmp"), None);
+
+    assert_eq!(parse_remote_file_name(b"/tmp/foo"), None);
+    assert_eq!(parse_remote_file_name(b"/:/ssh:host:"), None);
+    assert_eq!(parse_remote_file_name(b"ssh:host:/tmp"), None);
+}
@@ -0,0 +1,748 @@
+//! Time zone rules read from the IANA time zone database.
+//!
+//! `format-time-string' and `decode-time' can only use the time zone
+//! rules of the C library, one zone at a time, and Lisp code that needs
+//! the offset of other zones used to run zdump and parse its output.
+//! The TZif files of the database are read here directly (RFC 8536),
+//! including the POSIX TZ string at their end that gives the rule for
+//! the times after the last transition.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    environ,
+    lisp::{defsubr, LispObject},
+    remacs_sys::EmacsInt,
+    time::float_time,
+};
+
+/// The directories searched for time zone files, after $TZDIR.
+const ZONEINFO_DIRS: &[&str] = &[
+    "/usr/share/zoneinfo",
+    "/usr/lib/zoneinfo",
+    "/usr/share/lib/zoneinfo",
+    "/etc/zoneinfo",
+];
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// A local time type: an offset from UTC and what it is called.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalTimeType {
+    /// Seconds east of UTC.
+    pub offset: i32,
+    pub is_dst: bool,
+    pub abbreviation: String,
+}
+
+/// The day of the year a POSIX TZ rule changes time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RuleDay {
+    /// Jn: day N from 1 to 365, without February 29.
+    Julian1(u16),
+    /// N: day N from 0 to 365, with February 29.
+    Julian0(u16),
+    /// Mm.w.d: day D (0 is Sunday) of week W (5 is the last) of month M.
+    MonthWeekDay(u8, u8, u8),
+}
+
+/// The daylight saving time part of a POSIX TZ rule.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DstRule {
+    pub dst: LocalTimeType,
+    pub start: RuleDay,
+    /// The local standard time of the start, in seconds after midnight.
+    pub start_time: i32,
+    pub end: RuleDay,
+    /// The local daylight saving time of the end.
+    pub end_time: i32,
+}
+
+/// A POSIX TZ rule, like "CET-1CEST,M3.5.0,M10.5.0/3".
+#[derive(Clone, Debug, PartialEq)]
+pub struct PosixRule {
+    pub std: LocalTimeType,
+    pub dst: Option<DstRule>,
+}
+
+/// The rules of a time zone.
+#[derive(Debug, PartialEq)]
+pub struct Zone {
+    /// The times at which the local time type changes, in increasing
+    /// order, and the index in `types' of the type from then on.
+    pub transitions: Vec<(i64, usize)>,
+    pub types: Vec<LocalTimeType>,
+    /// The rule for the times after the last transition.
+    pub rule: Option<PosixRule>,
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn div_floor(a: i64, b: i64) -> i64 {
+    let q = a / b;
+    if (a % b != 0) && ((a < 0) != (b < 0)) {
+        q - 1
+    } else {
+        q
+    }
+}
+
+/// The number of days from 1970-01-01 to YEAR-MONTH-DAY.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = div_floor(year, 400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The year of the day DAYS after 1970-01-01.
+fn year_from_days(days: i64) -> i64 {
+    let days = days + 719_468;
+    let era = div_floor(days, 146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let year = year_of_era + era * 400;
+    if month_index >= 10 {
+        year + 1
+    } else {
+        year
+    }
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl RuleDay {
+    /// The number of days from 1970-01-01 to this day of YEAR.
+    fn days(self, year: i64) -> i64 {
+        let january_first = days_from_civil(year, 1, 1);
+        match self {
+            RuleDay::Julian1(day) => {
+                let day = i64::from(day) - 1;
+                january_first
+                    + day
+                    + if is_leap_year(year) && day >= 59 {
+                        1
+                    } else {
+                        0
+                    }
+            }
+            RuleDay::Julian0(day) => january_first + i64::from(day),
+            RuleDay::MonthWeekDay(month, week, weekday) => {
+                let (month, week, weekday) =
+                    (i64::from(month), i64::from(week), i64::from(weekday));
+                let first = days_from_civil(year, month, 1);
+                // 1970-01-01 was a Thursday.
+                let first_weekday = first + 4 - div_floor(first + 4, 7) * 7;
+                let mut day = 1 + (weekday - first_weekday + 7) % 7 + (week - 1) * 7;
+                while day > days_in_month(year, month) {
+                    day -= 7;
+                }
+                first + day - 1
+            }
+        }
+    }
+}
+
+impl PosixRule {
+    /// The start and end of daylight saving time in YEAR, in UTC.
+    fn dst_bounds(&self, rule: &DstRule, year: i64) -> (i64, i64) {
+        let start = rule.start.days(year) * SECONDS_PER_DAY + i64::from(rule.start_time)
+            - i64::from(self.std.offset);
+        let end = rule.end.days(year) * SECONDS_PER_DAY + i64::from(rule.end_time)
+            - i64::from(rule.dst.offset);
+        (start, end)
+    }
+
+    /// The local time type at TIME.
+    pub fn find(&self, time: i64) -> &LocalTimeType {
+        let rule = match self.dst {
+            Some(ref rule) => rule,
+            None => return &self.std,
+        };
+        let local = time + i64::from(self.std.offset);
+        let year = year_from_days(div_floor(local, SECONDS_PER_DAY));
+        let (start, end) = self.dst_bounds(rule, year);
+        let in_dst = if start <= end {
+            start <= time && time < end
+        } else {
+            // Southern hemisphere: daylight saving time spans the new year.
+            time < end || start <= time
+        };
+        if in_dst {
+            &rule.dst
+        } else {
+            &self.std
+        }
+    }
+
+    /// The changes of local time type from FROM to TO, as pairs of the
+    /// time of the change and the new type.
+    pub fn transitions(&self, from: i64, to: i64) -> Vec<(i64, &LocalTimeType)> {
+        let rule = match self.dst {
+            Some(ref rule) => rule,
+            None => return Vec::new(),
+        };
+        let first = year_from_days(div_floor(from, SECONDS_PER_DAY)) - 1;
+        let last = year_from_days(div_floor(to, SECONDS_PER_DAY)) + 1;
+        let mut result = Vec::new();
+        for year in first..=last {
+            let (start, end) = self.dst_bounds(rule, year);
+            let mut changes = [(start, &rule.dst), (end, &self.std)];
+            changes.sort_by_key(|change| change.0);
+            result.extend(
+                changes
+                    .iter()
+                    .filter(|change| from <= change.0 && change.0 < to),
+            );
+        }
+        result
+    }
+}
+
+/// Parses a POSIX TZ string.
+struct RuleParser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> RuleParser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).cloned()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn name(&mut self) -> Option<String> {
+        let start = self.pos;
+        let name = if self.eat(b'<') {
+            while self.peek().map_or(false, |c| c != b'>') {
+                self.pos += 1;
+            }
+            let name = &self.text[start + 1..self.pos];
+            if !self.eat(b'>') {
+                return None;
+            }
+            name
+        } else {
+            while self.peek().map_or(false, |c| c.is_ascii_alphabetic()) {
+                self.pos += 1;
+            }
+            &self.text[start..self.pos]
+        };
+        if name.len() < 3 {
+            return None;
+        }
+        Some(String::from_utf8_lossy(name).into_owned())
+    }
+
+    fn number(&mut self) -> Option<i32> {
+        let start = self.pos;
+        let mut value: i32 = 0;
+        while let Some(c) = self.peek().filter(|c| c.is_ascii_digit()) {
+            value = value.checked_mul(10)?.checked_add(i32::from(c - b'0'))?;
+            self.pos += 1;
+        }
+        if self.pos == start {
+            None
+        } else {
+            Some(value)
+        }
+    }
+
+    /// Parse [+-]hh[:mm[:ss]], in seconds.
+    fn time(&mut self) -> Option<i32> {
+        let sign = if self.eat(b'-') {
+            -1
+        } else {
+            self.eat(b'+');
+            1
+        };
+        let mut seconds = self.number()? * 3600;
+        if self.eat(b':') {
+            seconds += self.number()? * 60;
+            if self.eat(b':') {
+                seconds += self.number()?;
+            }
+        }
+        Some(sign * seconds)
+    }
+
+    fn day(&mut self) -> Option<RuleDay> {
+        if self.eat(b'M') {
+            let month = self.number()?;
+            if !self.eat(b'.') {
+                return None;
+            }
+            let week = self.number()?;
+            if !self.eat(b'.') {
+                return None;
+            }
+            let weekday = self.number()?;
+            if month < 1 || month > 12 || week < 1 || week > 5 || weekday > 6 {
+                return None;
+            }
+            Some(RuleDay::MonthWeekDay(
+                month as u8,
+                week as u8,
+                weekday as u8,
+            ))
+        } else if self.eat(b'J') {
+            let day = self.number()?;
+            if day < 1 || day > 365 {
+                return None;
+            }
+            Some(RuleDay::Julian1(day as u16))
+        } else {
+            let day = self.number()?;
+            if day > 365 {
+                return None;
+            }
+            Some(RuleDay::Julian0(day as u16))
+        }
+    }
+
+    /// Parse ",DAY[/TIME]".
+    fn change(&mut self) -> Option<(RuleDay, i32)> {
+        if !self.eat(b',') {
+            return None;
+        }
+        let day = self.day()?;
+        let time = if self.eat(b'/') {
+            self.time()?
+        } else {
+            2 * 3600
+        };
+        Some((day, time))
+    }
+}
+
+/// Parse the POSIX TZ string TEXT.
+pub fn parse_posix_rule(text: &[u8]) -> Option<PosixRule> {
+    let mut parser = RuleParser { text, pos: 0 };
+    let std_name = parser.name()?;
+    // POSIX offsets are west of Greenwich.
+    let std = LocalTimeType {
+        offset: -parser.time()?,
+        is_dst: false,
+        abbreviation: std_name,
+    };
+    if parser.pos == text.len() {
+        return Some(PosixRule { std, dst: None });
+    }
+    let dst_name = parser.name()?;
+    let dst_offset = match parser.peek() {
+        Some(b',') | None => std.offset + 3600,
+        _ => -parser.time()?,
+    };
+    let ((start, start_time), (end, end_time)) = if parser.peek().is_none() {
+        // The rules of the United States, as the default of glibc.
+        (
+            (RuleDay::MonthWeekDay(3, 2, 0), 2 * 3600),
+            (RuleDay::MonthWeekDay(11, 1, 0), 2 * 3600),
+        )
+    } else {
+        (parser.change()?, parser.change()?)
+    };
+    if parser.pos != text.len() {
+        return None;
+    }
+    Some(PosixRule {
+        std,
+        dst: Some(DstRule {
+            dst: LocalTimeType {
+                offset: dst_offset,
+                is_dst: true,
+                abbreviation: dst_name,
+            },
+            start,
+            start_time,
+            end,
+            end_time,
+        }),
+    })
+}
+
+/// Read big-endian signed integers of SIZE bytes.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(taken)
+    }
+
+    fn int(&mut self, size: usize) -> Option<i64> {
+        let bytes = self.take(size)?;
+        let value = bytes.iter().fold(0u64, |acc, &b| acc << 8 | u64::from(b));
+        // Sign-extend.
+        let shift = 64 - 8 * size as u32;
+        Some(((value << shift) as i64) >> shift)
+    }
+
+    fn count(&mut self) -> Option<usize> {
+        let count = self.int(4)?;
+        if count < 0 {
+            None
+        } else {
+            Some(count as usize)
+        }
+    }
+}
+
+/// Parse a TZif file.
+pub fn parse_tzif(data: &[u8]) -> Option<Zone> {
+    let mut reader = Reader { data };
+    let (version, counts) = tzif_header(&mut reader)?;
+    if version == 0 {
+        return tzif_block(&mut reader, counts, 4);
+    }
+    // Skip the 32-bit block, whose data is repeated with 64-bit times.
+    let (isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt) = counts;
+    reader.take(timecnt * 5 + typecnt * 6 + charcnt + leapcnt * 8 + isstdcnt + isutcnt)?;
+    let (_, counts) = tzif_header(&mut reader)?;
+    let mut zone = tzif_block(&mut reader, counts, 8)?;
+    let footer = reader.data;
+    if footer.first() == Some(&b'\n') {
+        let end = footer[1..].iter().position(|&c| c == b'\n')? + 1;
+        if end > 1 {
+            zone.rule = Some(parse_posix_rule(&footer[1..end])?);
+        }
+    }
+    Some(zone)
+}
+
+type Counts = (usize, usize, usize, usize, usize, usize);
+
+fn tzif_header(reader: &mut Reader) -> Option<(u8, Counts)> {
+    let header = reader.take(20)?;
+    if &header[..4] != b"TZif" {
+        return None;
+    }
+    let version = match header[4] {
+        0 => 0,
+        c @ b'2'..=b'9' => c - b'0',
+        _ => return None,
+    };
+    let counts = (
+        reader.count()?,
+        reader.count()?,
+        reader.count()?,
+        reader.count()?,
+        reader.count()?,
+        reader.count()?,
+    );
+    Some((version, counts))
+}
+
+fn tzif_block(reader: &mut Reader, counts: Counts, time_size: usize) -> Option<Zone> {
+    let (isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt) = counts;
+    if typecnt == 0 {
+        return None;
+    }
+    let mut times = Vec::with_capacity(timecnt);
+    for _ in 0..timecnt {
+        times.push(reader.int(time_size)?);
+    }
+    let indices = reader.take(timecnt)?;
+    let mut raw_types = Vec::with_capacity(typecnt);
+    for _ in 0..typecnt {
+        let offset = reader.int(4)? as i32;
+        let is_dst = reader.take(1)?[0] != 0;
+        let abbreviation_index = reader.take(1)?[0] as usize;
+        raw_types.push((offset, is_dst, abbreviation_index));
+    }
+    let abbreviations = reader.take(charcnt)?;
+    reader.take(leapcnt * (time_size + 4) + isstdcnt + isutcnt)?;
+
+    let types = raw_types
+        .into_iter()
+        .map(|(offset, is_dst, index)| {
+            let rest = abbreviations.get(index..)?;
+            let end = rest.iter().position(|&c| c == 0)?;
+            Some(LocalTimeType {
+                offset,
+                is_dst,
+                abbreviation: String::from_utf8_lossy(&rest[..end]).into_owned(),
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let transitions = times
+        .into_iter()
+        .zip(indices.iter().map(|&i| i as usize))
+        .collect::<Vec<_>>();
+    if transitions.iter().any(|&(_, index)| index >= types.len()) {
+        return None;
+    }
+    Some(Zone {
+        transitions,
+        types,
+        rule: None,
+    })
+}
+
+impl Zone {
+    /// A zone with only the rule RULE.
+    pub fn from_rule(rule: PosixRule) -> Zone {
+        Zone {
+            transitions: Vec::new(),
+            types: vec![rule.std.clone()],
+            rule: Some(rule),
+        }
+    }
+
+    /// The local time type at TIME, in seconds since the epoch.
+    pub fn find(&self, time: i64) -> &LocalTimeType {
+        match self.transitions.last() {
+            Some(&(last, _)) if time >= last && self.rule.is_some() => {}
+            None if self.rule.is_some() => {}
+            _ => {
+                let index = match self.transitions.binary_search_by_key(&time, |t| t.0) {
+                    Ok(i) => Some(i),
+                    Err(0) => None,
+                    Err(i) => Some(i - 1),
+                };
+                return &self.types[index.map_or(0, |i| self.transitions[i].1)];
+            }
+        }
+        self.rule.as_ref().unwrap().find(time)
+    }
+
+    /// The local time types that local time LOCAL may be in: none if it
+    /// is skipped by a change of offset, two if it happens twice, the
+    /// earlier first.
+    pub fn find_local(&self, local: i64) -> Vec<&LocalTimeType> {
+        let mut candidates: Vec<&LocalTimeType> = Vec::new();
+        for &guess in &[local - SECONDS_PER_DAY, local + SECONDS_PER_DAY] {
+            let guess = self.find(guess).offset;
+            let found = self.find(local - i64::from(guess));
+            if found.offset == guess && !candidates.iter().any(|c| c.offset == guess) {
+                candidates.push(found);
+            }
+        }
+        // A larger offset means an earlier time.
+        candidates.sort_by_key(|c| -c.offset);
+        candidates
+    }
+
+    /// The changes of local time type from FROM to TO, as pairs of the
+    /// time of the change and the new type.
+    pub fn transitions(&self, from: i64, to: i64) -> Vec<(i64, &LocalTimeType)> {
+        let mut result: Vec<(i64, &LocalTimeType)> = self
+            .transitions
+            .iter()
+            .filter(|&&(time, _)| from <= time && time < to)
+            .map(|&(time, index)| (time, &self.types[index]))
+            .collect();
+        if let Some(ref rule) = self.rule {
+            let after = self
+                .transitions
+                .last()
+                .map_or(from, |&(last, _)| from.max(last + 1));
+            result.extend(rule.transitions(after, to));
+        }
+        result
+    }
+}
+
+lazy_static! {
+    static ref ZONES: Mutex<HashMap<String, Arc<Zone>>> = Mutex::new(HashMap::new());
+}
+
+/// The file of the zone NAME in the database, if there is one.
+fn zone_file(name: &str) -> Option<PathBuf> {
+    let name = name.trim_start_matches(':');
+    let path = Path::new(name);
+    if path.is_absolute() {
+        return Some(path.to_path_buf()).filter(|path| path.is_file());
+    }
+    if name.is_empty()
+        || path
+            .components()
+            .any(|c| c != Component::Normal(c.as_os_str()))
+    {
+        return None;
+    }
+    environ::getenv(b"TZDIR")
+        .map(|dir| PathBuf::from(dir.to_string_lossy().into_owned()))
+        .into_iter()
+        .chain(ZONEINFO_DIRS.iter().map(PathBuf::from))
+        .map(|dir| dir.join(path))
+        .find(|file| file.is_file())
+}
+
+/// The zone named ZONE, as for the ZONE argument of `format-time-string'
+/// but that a string may name any zone of the database.
+fn lisp_zone(zone: LispObject) -> Arc<Zone> {
+    let name = if zone.is_nil() {
+        env::var("TZ").unwrap_or_else(|_| "/etc/localtime".to_string())
+    } else if zone.is_t() {
+        "UTC0".to_string()
+    } else {
+        zone.as_string_or_error().to_string()
+    };
+
+    if let Some(zone) = ZONES.lock().unwrap().get(&name) {
+        return zone.clone();
+    }
+    let parsed = zone_file(&name)
+        .and_then(|file| fs::read(file).ok())
+        .and_then(|data| parse_tzif(&data))
+        .or_else(|| parse_posix_rule(name.as_bytes()).map(Zone::from_rule))
+        .unwrap_or_else(|| error!("Unknown time zone: {}", name));
+    let parsed = Arc::new(parsed);
+    ZONES.lock().unwrap().insert(name, parsed.clone());
+    parsed
+}
+
+fn lisp_seconds(time: LispObject) -> i64 {
+    float_time(time).floor() as i64
+}
+
+fn type_to_lisp(time_type: &LocalTimeType) -> LispObject {
+    list!(
+        LispObject::from(EmacsInt::from(time_type.offset)),
+        LispObject::from(time_type.is_dst),
+        LispObject::from(time_type.abbreviation.as_str())
+    )
+}
+
+/// Return the offsets from UTC of the time zone ZONE at TIME.
+/// ZONE is the name of a zone of the time zone database, like
+/// "Europe/Paris", or a POSIX TZ string; nil means the local time zone
+/// and t Universal Time.  TIME is a time value, nil meaning now.
+///
+/// The value is a list of (OFFSET DST ABBREVIATION), where OFFSET is in
+/// seconds east of UTC, DST is non-nil for daylight saving time and
+/// ABBREVIATION is the name of the offset, like "CEST".  There is one
+/// element, unless LOCAL is non-nil: then TIME is taken as a local time
+/// in ZONE, as returned by `encode-time' in Universal Time, and there is
+/// no element if this local time is skipped by a change of offset, and
+/// two, the earlier first, if it happens twice.
+#[lisp_fn(min = "2")]
+pub fn timezone_offsets_native(zone: LispObject, time: LispObject, local: bool) -> LispObject {
+    let zone = lisp_zone(zone);
+    let seconds = lisp_seconds(time);
+    let types = if local {
+        zone.find_local(seconds)
+    } else {
+        vec![zone.find(seconds)]
+    };
+    LispObject::from(types.into_iter().map(type_to_lisp).collect::<Vec<_>>())
+}
+
+/// Return the changes of offset in the time zone ZONE from FROM to TO.
+/// ZONE is as for `timezone-offsets-native', and FROM and TO are time
+/// values.  The value is a list of (TIME OFFSET DST ABBREVIATION), in
+/// increasing order, where TIME is when the zone changes, in seconds
+/// since the epoch, and the rest describes the offset from then on.
+#[lisp_fn]
+pub fn timezone_transitions_native(
+    zone: LispObject,
+    from: LispObject,
+    to: LispObject,
+) -> LispObject {
+    let zone = lisp_zone(zone);
+    let transitions: Vec<LispObject> = zone
+        .transitions(lisp_seconds(from), lisp_seconds(to))
+        .into_iter()
+        .map(|(time, time_type)| {
+            LispObject::cons(LispObject::from(time as EmacsInt), type_to_lisp(time_type))
+        })
+        .collect();
+    LispObject::from(transitions)
+}
+
+include!(concat!(env!("OUT_DIR"), "/tzfile_exports.rs"));
+
+#[test]
+fn test_civil() {
+    assert_eq!(days_from_civil(1970, 1, 1), 0);
+    assert_eq!(days_from_civil(2000, 3, 1), 11017);
+    assert_eq!(days_from_civil(1969, 12, 31), -1);
+    assert_eq!(year_from_days(11016), 2000);
+    assert_eq!(year_from_days(-1), 1969);
+    assert_eq!(year_from_days(days_from_civil(2018, 12, 31)), 2018);
+}
+
+#[test]
+fn test_posix_rule() {
+    let rule = parse_posix_rule(b"CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+    assert_eq!(rule.std.offset, 3600);
+    let dst = rule.dst.as_ref().unwrap();
+    assert_eq!(dst.dst.offset, 7200);
+    assert_eq!(dst.start, RuleDay::MonthWeekDay(3, 5, 0));
+    assert_eq!(dst.end_time, 3 * 3600);
+    // 2018-03-25 01:00 UTC and 2018-10-28 01:00 UTC.
+    assert_eq!(rule.find(1_521_939_599).abbreviation, "CET");
+    assert_eq!(rule.find(1_521_939_600).abbreviation, "CEST");
+    assert_eq!(rule.find(1_540_688_399).abbreviation, "CEST");
+    assert_eq!(rule.find(1_540_688_400).abbreviation, "CET");
+
+    let rule = parse_posix_rule(b"<+13>-13").unwrap();
+    assert_eq!(rule.std.abbreviation, "+13");
+    assert_eq!(rule.std.offset, 13 * 3600);
+    assert!(rule.dst.is_none());
+
+    // Southern hemisphere, with the change at 2018-03-31 16:00 UTC.
+    let rule = parse_posix_rule(b"AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+    assert!(rule.find(1_514_764_800).is_dst);
+    assert!(!rule.find(1_522_512_000).is_dst);
+    assert!(rule.find(1_522_511_999).is_dst);
+
+    assert!(parse_posix_rule(b"EST5EDT").unwrap().dst.is_some());
+    assert!(parse_posix_rule(b"X5").is_none());
+    assert!(parse_posix_rule(b"CET-1CEST,M13.5.0,M10.5.0").is_none());
+}
+
+#[test]
+fn test_zone() {
+    let zone = Zone::from_rule(parse_posix_rule(b"CET-1CEST,M3.5.0,M10.5.0/3").unwrap());
+    // 2018-03-25 02:30 is skipped, 2018-10-28 02:30 happens twice.
+    let skipped = 1_521_945_000;
+    assert!(zone.find_local(skipped).is_empty());
+    let twice: Vec<_> = zone
+        .find_local(1_540_693_800)
+        .iter()
+        .map(|t| t.offset)
+        .collect();
+    assert_eq!(twice, vec![7200, 3600]);
+    assert_eq!(zone.find_local(1_540_000_000).len(), 1);
+    let transitions = zone.transitions(1_514_764_800, 1_546_300_800);
+    assert_eq!(transitions.len(), 2);
+    assert_eq!(transitions[0].0, 1_521_939_600);
+    assert_eq!(transitions[1].1.abbreviation, "CET");
+}
@@ -0,0 +1,522 @@
+//! Vertical motion over screen lines.
+//!
+//! `vertical-motion' lays out the text from the start of the line with
+//! the display engine, which is slow on very long lines.  Most text
+//! that has such lines has nothing fancy in it, though: each character
+//! takes one column, and a line is broken into screen lines at fixed
+//! columns, or not at all if lines are truncated.  This moves over
+//! such text by arithmetic, and leaves anything else to the display
+//! engine.
+
+use std::ptr;
+
+use libc::ptrdiff_t;
+
+use crate::{
+    buffers::{overlay_properties, LispBufferRef},
+    frames::{window_system, LispFrameRef},
+    lisp::LispObject,
+    marker::buf_charpos_to_bytepos,
+    obarray::intern,
+    remacs_sys::{
+        find_before_next_newline, find_newline, set_point, EmacsInt, Fnext_property_change,
+        Foverlays_in, Ftext_properties_at,
+    },
+    remacs_sys::{
+        Qafter_string, Qbefore_string, Qcomposition, Qdisplay, Qface, Qinvisible, Qline_prefix,
+        Qnil, Qright_to_left, Qwrap_prefix,
+    },
+    symbols::symbol_value,
+    threads::ThreadState,
+    windows::{LispWindowLiveOrSelected, LispWindowRef},
+};
+
+/// How lines are broken into screen lines.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScreenLines {
+    /// The number of characters on the first screen line of a line, or
+    /// None if lines are truncated.
+    pub first: Option<usize>,
+    /// The number of characters on each continuation line.
+    pub rest: usize,
+    /// The width of the `line-prefix' before the first screen line.
+    pub first_prefix: usize,
+    /// The width of the prefix before the continuation lines.
+    pub rest_prefix: usize,
+}
+
+impl ScreenLines {
+    /// Return the number of screen lines of a line of LEN characters.
+    pub fn count(self, len: usize) -> usize {
+        match self.first {
+            Some(first) if len > first => 1 + (len - first + self.rest - 1) / self.rest,
+            _ => 1,
+        }
+    }
+
+    /// Return the offset of the start of screen line ROW of a line.
+    pub fn start(self, row: usize) -> usize {
+        match self.first {
+            Some(first) if row > 0 => first + (row - 1) * self.rest,
+            _ => 0,
+        }
+    }
+
+    /// Return the screen line of a line of LEN characters that shows
+    /// the character at OFFSET.  The end of the line is on the last
+    /// screen line, even when the characters before fill it.
+    pub fn row(self, offset: usize, len: usize) -> usize {
+        match self.first {
+            Some(first) if offset >= first => {
+                (1 + (offset - first) / self.rest).min(self.count(len) - 1)
+            }
+            _ => 0,
+        }
+    }
+
+    /// Return the offset on screen line ROW of a line of LEN characters
+    /// that is at column GOAL, or the start of the screen line if GOAL
+    /// is None.  Columns beyond the end of a screen line are at its last
+    /// character, or at the end of the line on its last screen line.
+    pub fn offset(self, row: usize, len: usize, goal: Option<usize>) -> usize {
+        let start = self.start(row);
+        let goal = match goal {
+            Some(goal) => goal,
+            None => return start,
+        };
+        let last = row + 1 == self.count(len);
+        let end = if last { len } else { self.start(row + 1) };
+        let prefix = if row == 0 {
+            self.first_prefix
+        } else {
+            self.rest_prefix
+        };
+        let offset = start + goal.saturating_sub(prefix);
+        if offset < end {
+            offset
+        } else if last {
+            len
+        } else {
+            end - 1
+        }
+    }
+}
+
+/// Text that cannot be laid out by `ScreenLines'.
+#[derive(Debug, PartialEq)]
+pub struct NotSimple;
+
+/// The lines of some text, seen from one of them.
+pub trait TextLines {
+    /// Return the length of the line DELTA lines after the current one,
+    /// or None if there is no such line.  DELTA moves away from 0 by
+    /// one at a time, or back to a line that was already seen.
+    fn length(&mut self, delta: isize) -> Result<Option<usize>, NotSimple>;
+}
+
+/// Return where moving LINES screen lines from the character at OFFSET
+/// in the current line of TEXT ends: the line, relative to the current
+/// one, the offset in it, and the number of screen lines moved over.
+/// The motion ends at the start of the screen line, or at column GOAL
+/// on it.  It stops at the start or the end of TEXT.
+pub fn move_screen_lines<T: TextLines>(
+    text: &mut T,
+    layout: ScreenLines,
+    offset: usize,
+    lines: isize,
+    goal: Option<usize>,
+) -> Result<(isize, usize, isize), NotSimple> {
+    let mut delta = 0;
+    let mut len = text.length(0)?.unwrap_or(0);
+    let mut row = layout.row(offset, len) as isize + lines;
+    let mut moved = lines;
+
+    while row < 0 {
+        match text.length(delta - 1)? {
+            Some(prev) => {
+                delta -= 1;
+                len = prev;
+                row += layout.count(len) as isize;
+            }
+            None => {
+                moved -= row;
+                row = 0;
+            }
+        }
+    }
+    loop {
+        let count = layout.count(len) as isize;
+        if row < count {
+            break;
+        }
+        match text.length(delta + 1)? {
+            Some(next) => {
+                delta += 1;
+                len = next;
+                row -= count;
+            }
+            None => return Ok((delta, len, moved - (row - (count - 1)))),
+        }
+    }
+    Ok((delta, layout.offset(row as usize, len, goal), moved))
+}
+
+/// The lines of the accessible portion of the current buffer.
+struct BufferLines {
+    buffer: LispBufferRef,
+    /// Whether the text is shown on a graphical frame, where faces can
+    /// change the width of characters.
+    graphic: bool,
+    /// The line DELTA lines after the one we started from starts at
+    /// START.
+    delta: isize,
+    start: ptrdiff_t,
+}
+
+impl BufferLines {
+    fn line_end(&self, pos: ptrdiff_t) -> ptrdiff_t {
+        unsafe { find_before_next_newline(pos, self.buffer.zv, 1, ptr::null_mut()) }
+    }
+
+    /// Return true if the text between START and END is shown one
+    /// column per character, with nothing else in between.
+    fn is_simple(&self, start: ptrdiff_t, end: ptrdiff_t) -> bool {
+        let mut buffer = self.buffer;
+        let (start_byte, end_byte) = unsafe {
+            (
+                buf_charpos_to_bytepos(buffer.as_mut(), start),
+                buf_charpos_to_bytepos(buffer.as_mut(), end),
+            )
+        };
+        // Multibyte characters need not be one column wide.
+        if end_byte - start_byte != end - start {
+            return false;
+        }
+        if !(start_byte..end_byte).all(|pos| {
+            let byte = buffer.fetch_byte(pos);
+            byte >= b' ' && byte <= b'~'
+        }) {
+            return false;
+        }
+
+        let mut pos = LispObject::from(start as EmacsInt);
+        let end = LispObject::from(end as EmacsInt);
+        while pos.is_not_nil() && pos != end {
+            if affects_layout(unsafe { Ftext_properties_at(pos, Qnil) }, self.graphic) {
+                return false;
+            }
+            pos = unsafe { Fnext_property_change(pos, Qnil, end) };
+        }
+        unsafe { Foverlays_in(LispObject::from(start as EmacsInt), end) }
+            .iter_cars()
+            .all(|overlay| {
+                !affects_layout(
+                    overlay_properties(overlay.as_overlay_or_error()),
+                    self.graphic,
+                )
+            })
+    }
+}
+
+impl TextLines for BufferLines {
+    fn length(&mut self, delta: isize) -> Result<Option<usize>, NotSimple> {
+        if delta > self.delta {
+            let end = self.line_end(self.start);
+            if end >= self.buffer.zv {
+                return Ok(None);
+            }
+            self.start = end + 1;
+        } else if delta < self.delta {
+            if self.start <= self.buffer.begv {
+                return Ok(None);
+            }
+            self.start = line_start(self.start - 1);
+        }
+        self.delta = delta;
+
+        let end = self.line_end(self.start);
+        if self.is_simple(self.start, end) {
+            Ok(Some((end - self.start) as usize))
+        } else {
+            Err(NotSimple)
+        }
+    }
+}
+
+/// Return the start of the line containing POS in the current buffer.
+fn line_start(pos: ptrdiff_t) -> ptrdiff_t {
+    let begv = ThreadState::current_buffer().begv;
+    unsafe {
+        find_newline(
+            pos,
+            -1,
+            begv,
+            -1,
+            -1,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            false,
+        )
+    }
+}
+
+/// Return true if the properties in PLIST can change how text is laid
+/// out.  On GRAPHIC frames, faces can change the width of characters.
+fn affects_layout(plist: LispObject, graphic: bool) -> bool {
+    let mut tail = plist;
+    while let Some(cons) = tail.as_cons() {
+        let prop = cons.car();
+        let layout_props = [
+            Qdisplay,
+            Qinvisible,
+            Qcomposition,
+            Qline_prefix,
+            Qwrap_prefix,
+            Qbefore_string,
+            Qafter_string,
+        ];
+        let face_props = [Qface, intern("font-lock-face").as_lisp_obj()];
+        if layout_props.contains(&prop) || (graphic && face_props.contains(&prop)) {
+            return true;
+        }
+        tail = cons.cdr().as_cons().map_or(Qnil, |value| value.cdr());
+    }
+    false
+}
+
+/// Return the value of the variable NAME in the current buffer.
+fn variable(name: &str) -> LispObject {
+    symbol_value(intern(name))
+}
+
+/// Return the width of the prefix PREFIX, a `line-prefix' or
+/// `wrap-prefix', if it is a plain string.
+fn prefix_width(prefix: LispObject) -> Option<usize> {
+    if prefix.is_nil() {
+        return Some(0);
+    }
+    let string = prefix.as_string()?;
+    let plain = string
+        .as_slice()
+        .iter()
+        .all(|&byte| byte >= b' ' && byte <= b'~')
+        && unsafe { Fnext_property_change(LispObject::from(0), prefix, Qnil) }.is_nil()
+        && unsafe { Ftext_properties_at(LispObject::from(0), prefix) }.is_nil();
+    if plain {
+        Some(string.len_bytes() as usize)
+    } else {
+        None
+    }
+}
+
+/// Return how the current buffer is broken into screen lines in WINDOW,
+/// or None if it is not simple enough to tell.
+fn screen_lines(window: LispWindowRef, graphic: bool) -> Option<ScreenLines> {
+    let frame: LispFrameRef = window.frame.as_frame_or_error();
+    if window.hscroll != 0
+        || variable("word-wrap").is_not_nil()
+        || variable("selective-display").is_not_nil()
+        || variable("display-line-numbers").is_not_nil()
+        || variable("buffer-display-table").is_not_nil()
+        || variable("standard-display-table").is_not_nil()
+        || window.display_table.is_not_nil()
+        || variable("bidi-paragraph-direction") == Qright_to_left
+        || (graphic
+            && (variable("face-remapping-alist").is_not_nil()
+                || variable("overflow-newline-into-fringe").is_nil()))
+    {
+        return None;
+    }
+
+    let full_width = frame
+        .root_window
+        .as_window()
+        .map_or(true, |root| root.pixel_width == window.pixel_width);
+    let partial = variable("truncate-partial-width-windows");
+    let truncate = variable("truncate-lines").is_not_nil()
+        || (!full_width
+            && partial.is_not_nil()
+            && partial
+                .as_fixnum()
+                .map_or(true, |cols| cols > EmacsInt::from(window.total_cols)));
+
+    let first_prefix = prefix_width(variable("line-prefix"))?;
+    let wrap_prefix = variable("wrap-prefix");
+    let rest_prefix = if wrap_prefix.is_nil() {
+        first_prefix
+    } else {
+        prefix_width(wrap_prefix)?
+    };
+
+    // Continuation glyphs go in the right fringe if there is one, and
+    // take the last column otherwise.
+    let right_fringe = if graphic {
+        window.right_fringe_width()
+    } else {
+        0
+    };
+    let width = window.body_width(false) as usize;
+    let width = if right_fringe > 0 { width } else { width - 1 };
+    if width <= first_prefix.max(rest_prefix) {
+        return None;
+    }
+
+    Some(ScreenLines {
+        first: if truncate {
+            None
+        } else {
+            Some(width - first_prefix)
+        },
+        rest: width - rest_prefix,
+        first_prefix,
+        rest_prefix,
+    })
+}
+
+/// Move point LINES screen lines down in WINDOW like `vertical-motion',
+/// without the display engine, if the text is simple enough for that.
+/// GOAL is the column to move to, or nil.  Return true and set *MOVED
+/// to the number of screen lines moved over if this did the motion.
+#[no_mangle]
+pub extern "C" fn simple_vertical_motion(
+    lines: EmacsInt,
+    goal: LispObject,
+    window: LispObject,
+    moved: *mut EmacsInt,
+) -> bool {
+    let goal = if goal.is_nil() {
+        None
+    } else {
+        match goal.any_to_float() {
+            Some(cols) if cols >= 0.0 => Some(cols as usize),
+            _ => return false,
+        }
+    };
+    let window: LispWindowRef = LispWindowLiveOrSelected::from(window).into();
+    let graphic = window_system(window.frame.into()).is_not_nil();
+    let layout = match screen_lines(window, graphic) {
+        Some(layout) => layout,
+        None => return false,
+    };
+
+    let buffer = ThreadState::current_buffer();
+    let start = line_start(buffer.pt);
+    let mut text = BufferLines {
+        buffer,
+        graphic,
+        delta: 0,
+        start,
+    };
+    let offset = (buffer.pt - start) as usize;
+    match move_screen_lines(&mut text, layout, offset, lines as isize, goal) {
+        Ok((delta, offset, count)) => {
+            debug_assert_eq!(delta, text.delta);
+            unsafe {
+                set_point(text.start + offset as ptrdiff_t);
+                *moved = count as EmacsInt;
+            }
+            true
+        }
+        Err(NotSimple) => false,
+    }
+}
+
+#[cfg(test)]
+struct TestLines {
+    lens: Vec<usize>,
+    current: usize,
+}
+
+#[cfg(test)]
+impl TextLines for TestLines {
+    fn length(&mut self, delta: isize) -> Result<Option<usize>, NotSimple> {
+        let line = self.current as isize + delta;
+        if line < 0 || line as usize >= self.lens.len() {
+            Ok(None)
+        } else {
+            Ok(Some(self.lens[line as usize]))
+        }
+    }
+}
+
+#[cfg(test)]
+const WRAPPED: ScreenLines = ScreenLines {
+    first: Some(4),
+    rest: 4,
+    first_prefix: 0,
+    rest_prefix: 0,
+};
+
+#[test]
+fn test_screen_lines() {
+    assert_eq!(WRAPPED.count(0), 1);
+    assert_eq!(WRAPPED.count(4), 1);
+    assert_eq!(WRAPPED.count(5), 2);
+    assert_eq!(WRAPPED.count(12), 3);
+    assert_eq!(WRAPPED.row(3, 12), 0);
+    assert_eq!(WRAPPED.row(4, 12), 1);
+    // The end of a line that fills its last screen line.
+    assert_eq!(WRAPPED.row(12, 12), 2);
+    assert_eq!(WRAPPED.row(4, 4), 0);
+    assert_eq!(WRAPPED.offset(1, 10, Some(2)), 6);
+    assert_eq!(WRAPPED.offset(1, 10, Some(9)), 7);
+    assert_eq!(WRAPPED.offset(2, 10, Some(9)), 10);
+
+    let prefixed = ScreenLines {
+        first: Some(4),
+        rest: 2,
+        first_prefix: 0,
+        rest_prefix: 2,
+    };
+    assert_eq!(prefixed.count(8), 3);
+    assert_eq!(prefixed.start(2), 6);
+    assert_eq!(prefixed.offset(1, 8, Some(1)), 4);
+    assert_eq!(prefixed.offset(1, 8, Some(3)), 5);
+
+    let truncated = ScreenLines {
+        first: None,
+        ..WRAPPED
+    };
+    assert_eq!(truncated.count(100), 1);
+    assert_eq!(truncated.row(50, 100), 0);
+    assert_eq!(truncated.offset(0, 100, Some(50)), 50);
+}
+
+#[test]
+fn test_move_screen_lines() {
+    let mut text = TestLines {
+        lens: vec![2, 10, 0, 3],
+        current: 1,
+    };
+    // Down from the middle of the second screen line of "0123456789".
+    assert_eq!(
+        move_screen_lines(&mut text, WRAPPED, 5, 1, None),
+        Ok((0, 8, 1))
+    );
+    assert_eq!(
+        move_screen_lines(&mut text, WRAPPED, 5, 2, Some(1)),
+        Ok((1, 0, 2))
+    );
+    assert_eq!(
+        move_screen_lines(&mut text, WRAPPED, 5, 3, Some(1)),
+        Ok((2, 1, 3))
+    );
+    // Past the end.
+    assert_eq!(
+        move_screen_lines(&mut text, WRAPPED, 5, 9, None),
+        Ok((2, 3, 3))
+    );
+    // Up.
+    assert_eq!(
+        move_screen_lines(&mut text, WRAPPED, 5, -1, Some(3)),
+        Ok((0, 3, -1))
+    );
+    assert_eq!(
+        move_screen_lines(&mut text, WRAPPED, 5, -2, None),
+        Ok((-1, 0, -2))
+    );
+    assert_eq!(
+        move_screen_lines(&mut text, WRAPPED, 5, -5, None),
+        Ok((-1, 0, -2))
+    );
+}
@@ -0,0 +1,445 @@
+//! The WebSocket protocol, for `make-websocket-process'.
+//!
+//! A parser made here checks the server's answer to the opening
+//! handshake, then splits what the server sends into frames and puts
+//! fragmented messages back together.  Like the HTTP response parsers
+//! of http.rs, the parsers live on the Rust side between calls, and
+//! Lisp refers to them by number.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    http::{Head, ResponseParser},
+    lisp::{defsubr, LispObject},
+    multibyte::LispStringRef,
+    obarray::intern,
+    remacs_sys::{code_convert_string_norecord, make_unibyte_string, EmacsInt},
+    remacs_sys::{Qnil, Qutf_8_unix},
+};
+
+lazy_static! {
+    /// The parsers of the connections in use, by number.
+    static ref PARSERS: Mutex<HashMap<EmacsInt, Parser>> = Mutex::new(HashMap::new());
+}
+
+static mut LAST_PARSER_ID: EmacsInt = 0;
+
+/// What the server appends to the key of the handshake before hashing
+/// it, from RFC 6455.
+const ACCEPT_GUID: &[u8] = b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const CONTINUATION: u8 = 0x0;
+const TEXT: u8 = 0x1;
+const BINARY: u8 = 0x2;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xA;
+
+/// Return the Sec-WebSocket-Accept value that answers a handshake with
+/// KEY.
+pub fn accept_key(key: &[u8]) -> String {
+    let mut hasher = sha1::Sha1::new();
+    hasher.update(key);
+    hasher.update(ACCEPT_GUID);
+    base64_crate::encode(&hasher.digest().bytes())
+}
+
+/// Something that happened on a connection.
+#[derive(Debug, PartialEq)]
+pub enum Event {
+    /// The server accepted the handshake.
+    Open,
+    Text(Vec<u8>),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    /// The server closed the connection, with a status code and a
+    /// reason.
+    Close(Option<u16>, Vec<u8>),
+}
+
+#[derive(Debug, PartialEq)]
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+fn apply_mask(payload: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+}
+
+/// Parse the frame at the start of TEXT, if it is complete, and return
+/// it with the number of bytes it takes.
+fn parse_frame(text: &[u8]) -> Result<Option<(Frame, usize)>, String> {
+    if text.len() < 2 {
+        return Ok(None);
+    }
+    if text[0] & 0x70 != 0 {
+        return Err("WebSocket frame uses an unknown extension".to_string());
+    }
+    let (len, mut pos) = match text[1] & 0x7F {
+        126 if text.len() >= 4 => (u64::from(text[2]) << 8 | u64::from(text[3]), 4),
+        127 if text.len() >= 10 => (
+            text[2..10]
+                .iter()
+                .fold(0, |len, &byte| len << 8 | u64::from(byte)),
+            10,
+        ),
+        126 | 127 => return Ok(None),
+        len => (u64::from(len), 2),
+    };
+    if len > isize::max_value() as u64 {
+        return Err("WebSocket frame too large".to_string());
+    }
+    // Servers should not mask their frames, but this costs nothing.
+    let mask = if text[1] & 0x80 != 0 {
+        if text.len() < pos + 4 {
+            return Ok(None);
+        }
+        pos += 4;
+        Some([text[pos - 4], text[pos - 3], text[pos - 2], text[pos - 1]])
+    } else {
+        None
+    };
+    let end = pos + len as usize;
+    if text.len() < end {
+        return Ok(None);
+    }
+    let mut payload = text[pos..end].to_vec();
+    if let Some(mask) = mask {
+        apply_mask(&mut payload, mask);
+    }
+    let frame = Frame {
+        fin: text[0] & 0x80 != 0,
+        opcode: text[0] & 0x0F,
+        payload,
+    };
+    Ok(Some((frame, end)))
+}
+
+/// Return a complete frame with OPCODE and PAYLOAD, masked with MASK as
+/// the frames of clients must be.
+pub fn encode_frame(opcode: u8, payload: &[u8], mask: [u8; 4]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 14);
+    frame.push(0x80 | opcode);
+    let len = payload.len() as u64;
+    if len < 126 {
+        frame.push(0x80 | len as u8);
+    } else if len <= 0xFFFF {
+        frame.push(0x80 | 126);
+        frame.extend_from_slice(&[(len >> 8) as u8, len as u8]);
+    } else {
+        frame.push(0x80 | 127);
+        frame.extend((0..8).rev().map(|i| (len >> (i * 8)) as u8));
+    }
+    frame.extend_from_slice(&mask);
+    let start = frame.len();
+    frame.extend_from_slice(payload);
+    apply_mask(&mut frame[start..], mask);
+    frame
+}
+
+/// Check the answer HEAD of the server to a handshake with KEY.
+fn check_handshake(head: &Head, key: &[u8]) -> Result<(), String> {
+    let has_token = |name: &[u8], token: &[u8]| {
+        head.header(name).map_or(false, |value| {
+            value.split(|&c| c == b',').any(|t| {
+                let t: Vec<u8> = t.iter().cloned().filter(|&c| c != b' ').collect();
+                t.eq_ignore_ascii_case(token)
+            })
+        })
+    };
+    if head.status != 101 {
+        return Err(format!(
+            "WebSocket handshake failed: {} {}",
+            head.status,
+            String::from_utf8_lossy(&head.reason)
+        ));
+    }
+    if !has_token(b"upgrade", b"websocket") || !has_token(b"connection", b"upgrade") {
+        return Err("WebSocket handshake failed: connection not upgraded".to_string());
+    }
+    if head.header(b"sec-websocket-accept") != Some(accept_key(key).as_bytes()) {
+        return Err("WebSocket handshake failed: wrong Sec-WebSocket-Accept".to_string());
+    }
+    Ok(())
+}
+
+/// A parser for what the server sends on one connection.
+pub struct Parser {
+    /// The Sec-WebSocket-Key of the handshake.
+    key: Vec<u8>,
+    /// The parser of the answer to the handshake, until it is complete.
+    handshake: Option<ResponseParser>,
+    /// Input that does not make a complete frame yet.
+    pending: Vec<u8>,
+    /// The opcode and the payload so far of a fragmented message.
+    message: Option<(u8, Vec<u8>)>,
+}
+
+impl Parser {
+    pub fn new(key: &[u8]) -> Self {
+        Self {
+            key: key.to_vec(),
+            handshake: Some(ResponseParser::new(true, false)),
+            pending: Vec::new(),
+            message: None,
+        }
+    }
+
+    fn handle(&mut self, frame: Frame, events: &mut Vec<Event>) -> Result<(), String> {
+        if frame.opcode >= CLOSE && (!frame.fin || frame.payload.len() > 125) {
+            return Err("Malformed WebSocket control frame".to_string());
+        }
+        let message = match frame.opcode {
+            CONTINUATION => match self.message.take() {
+                Some((opcode, mut payload)) => {
+                    payload.extend_from_slice(&frame.payload);
+                    (opcode, payload)
+                }
+                None => return Err("Unexpected WebSocket continuation frame".to_string()),
+            },
+            TEXT | BINARY if self.message.is_some() => {
+                return Err("WebSocket message interrupted".to_string())
+            }
+            TEXT | BINARY => (frame.opcode, frame.payload),
+            CLOSE => {
+                let payload = frame.payload;
+                let code = if payload.len() >= 2 {
+                    Some(u16::from(payload[0]) << 8 | u16::from(payload[1]))
+                } else {
+                    None
+                };
+                let reason = payload.get(2..).unwrap_or(&[]).to_vec();
+                events.push(Event::Close(code, reason));
+                return Ok(());
+            }
+            PING => {
+                events.push(Event::Ping(frame.payload));
+                return Ok(());
+            }
+            PONG => {
+                events.push(Event::Pong(frame.payload));
+                return Ok(());
+            }
+            opcode => return Err(format!("Unknown WebSocket opcode {}", opcode)),
+        };
+        match message {
+            (opcode, payload) if !frame.fin => self.message = Some((opcode, payload)),
+            (TEXT, payload) => events.push(Event::Text(payload)),
+            (_, payload) => events.push(Event::Binary(payload)),
+        }
+        Ok(())
+    }
+
+    /// Parse INPUT, which follows the input parsed so far, and append
+    /// what happened to EVENTS.
+    pub fn feed(&mut self, input: &[u8], events: &mut Vec<Event>) -> Result<(), String> {
+        match self.handshake.take() {
+            Some(mut handshake) => {
+                handshake.feed(input, &mut Vec::new())?;
+                match handshake.head() {
+                    Some(head) if handshake.is_done() => check_handshake(head, &self.key)?,
+                    _ => {
+                        self.handshake = Some(handshake);
+                        return Ok(());
+                    }
+                }
+                events.push(Event::Open);
+                self.pending = handshake.take_rest();
+            }
+            None => self.pending.extend_from_slice(input),
+        }
+
+        let mut pos = 0;
+        while let Some((frame, len)) = parse_frame(&self.pending[pos..])? {
+            pos += len;
+            self.handle(frame, events)?;
+        }
+        self.pending.drain(..pos);
+        Ok(())
+    }
+}
+
+fn make_unibyte(bytes: &[u8]) -> LispObject {
+    unsafe { make_unibyte_string(bytes.as_ptr() as *const c_char, bytes.len() as ptrdiff_t) }
+}
+
+fn decode_utf8(bytes: &[u8]) -> LispObject {
+    unsafe { code_convert_string_norecord(make_unibyte(bytes), Qutf_8_unix, false) }
+}
+
+/// Make a parser for a WebSocket connection whose opening handshake was
+/// sent with the Sec-WebSocket-Key KEY, and return its number.  The
+/// parser should be freed with `websocket-native-parser-free' when it is
+/// no longer needed.
+#[lisp_fn]
+pub fn websocket_native_parser(key: LispStringRef) -> EmacsInt {
+    let id = unsafe {
+        LAST_PARSER_ID += 1;
+        LAST_PARSER_ID
+    };
+    PARSERS
+        .lock()
+        .unwrap()
+        .insert(id, Parser::new(key.as_slice()));
+    id
+}
+
+/// Free the WebSocket parser numbered PARSER.
+#[lisp_fn]
+pub fn websocket_native_parser_free(parser: EmacsInt) {
+    PARSERS.lock().unwrap().remove(&parser);
+}
+
+/// Give the WebSocket parser numbered PARSER the next part of what the
+/// server sent, the unibyte string STRING.  Return a list of what
+/// happened, in order, where each element is one of:
+///
+/// (open)              the server accepted the handshake.
+/// (text . STRING)     a text message, decoded from UTF-8.
+/// (binary . STRING)   a binary message, as a unibyte string.
+/// (ping . STRING)     a ping, which should be answered with a pong
+///                     with the same data.
+/// (pong . STRING)     a pong.
+/// (close CODE REASON) the server closed the connection, with the
+///                     status code CODE, or nil, and the string REASON.
+///
+/// Signal an error if the server does not accept the handshake or sends
+/// malformed frames.
+#[lisp_fn]
+pub fn websocket_native_parser_feed(parser: EmacsInt, string: LispStringRef) -> LispObject {
+    let mut events = Vec::new();
+    let result = {
+        let mut parsers = PARSERS.lock().unwrap();
+        match parsers.get_mut(&parser) {
+            Some(p) => Some(p.feed(string.as_slice(), &mut events)),
+            None => None,
+        }
+    };
+    match result {
+        Some(Ok(())) => {}
+        Some(Err(message)) => error!("{}", message),
+        None => error!("No WebSocket parser numbered {}", parser),
+    }
+
+    let events: Vec<LispObject> = events
+        .into_iter()
+        .map(|event| {
+            let (kind, data) = match event {
+                Event::Open => ("open", Qnil),
+                Event::Text(text) => ("text", decode_utf8(&text)),
+                Event::Binary(data) => ("binary", make_unibyte(&data)),
+                Event::Ping(data) => ("ping", make_unibyte(&data)),
+                Event::Pong(data) => ("pong", make_unibyte(&data)),
+                Event::Close(code, reason) => (
+                    "close",
+                    list!(
+                        code.map_or(Qnil, |code| LispObject::from(EmacsInt::from(code))),
+                        decode_utf8(&reason)
+                    ),
+                ),
+            };
+            LispObject::cons(intern(kind).as_lisp_obj(), data)
+        })
+        .collect();
+    LispObject::from(events)
+}
+
+/// Return a WebSocket frame of TYPE carrying PAYLOAD, ready to be sent
+/// to the server.  TYPE is one of the symbols `text', `binary', `ping',
+/// `pong' and `close'.  PAYLOAD is a string, which is encoded in UTF-8
+/// if it is multibyte.  The frame is masked with a random key, as the
+/// frames clients send must be.
+#[lisp_fn]
+pub fn websocket_native_frame(kind: LispObject, payload: LispStringRef) -> LispObject {
+    let opcode = [
+        ("text", TEXT),
+        ("binary", BINARY),
+        ("ping", PING),
+        ("pong", PONG),
+        ("close", CLOSE),
+    ]
+    .iter()
+    .find(|(name, _)| kind.eq(intern(name).as_lisp_obj()))
+    .map_or_else(
+        || error!("Unknown WebSocket frame type"),
+        |&(_, opcode)| opcode,
+    );
+    let payload = if payload.is_multibyte() {
+        unsafe { code_convert_string_norecord(payload.as_lisp_obj(), Qutf_8_unix, true) }
+            .as_string_or_error()
+    } else {
+        payload
+    };
+    if opcode >= CLOSE && payload.len_bytes() > 125 {
+        error!("WebSocket control frames carry at most 125 bytes");
+    }
+    let mask: [u8; 4] = rand::random();
+    make_unibyte(&encode_frame(opcode, payload.as_slice(), mask))
+}
+
+include!(concat!(env!("OUT_DIR"), "/websocket_exports.rs"));
+
+#[test]
+fn test_accept_key() {
+    // The example of RFC 6455.
+    assert_eq!(
+        accept_key(b"dGhlIHNhbXBsZSBub25jZQ=="),
+        "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+    );
+}
+
+#[test]
+fn test_frames() {
+    let frame = encode_frame(TEXT, b"Hello", [0x37, 0xfa, 0x21, 0x3d]);
+    // The masked example of RFC 6455.
+    assert_eq!(
+        frame,
+        vec![0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58]
+    );
+    let (parsed, len) = parse_frame(&frame).unwrap().unwrap();
+    assert_eq!(len, frame.len());
+    assert_eq!(parsed.payload, b"Hello");
+    assert!(parse_frame(&frame[..6]).unwrap().is_none());
+
+    let long = vec![b'x'; 70000];
+    let frame = encode_frame(BINARY, &long, [1, 2, 3, 4]);
+    assert_eq!(frame[1], 0x80 | 127);
+    assert_eq!(parse_frame(&frame).unwrap().unwrap().0.payload, long);
+
+    let mut parser = Parser {
+        key: Vec::new(),
+        handshake: None,
+        pending: Vec::new(),
+        message: None,
+    };
+    let mut events = Vec::new();
+    // A fragmented text message with a ping in between, then a close.
+    parser
+        .feed(
+            &[0x01, 0x03, b'H', b'e', b'l', 0x89, 0x00, 0x80],
+            &mut events,
+        )
+        .unwrap();
+    parser
+        .feed(&[0x02, b'l', b'o', 0x88, 0x02, 0x03, 0xe8], &mut events)
+        .unwrap();
+    assert_eq!(
+        events,
+        vec![
+            Event::Ping(vec![]),
+            Event::Text(b"Hello".to_vec()),
+            Event::Close(Some(1000), vec![]),
+        ]
+    );
+    assert!(parser.feed(&[0x80, 0x00], &mut events).is_err());
+}
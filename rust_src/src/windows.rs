@@ -16,16 +16,18 @@ use crate::{
     marker::{marker_position_lisp, set_marker_restricted},
     remacs_sys::globals,
     remacs_sys::{
-        estimate_mode_line_height, minibuf_level,
+        estimate_mode_line_height, horizontal_scroll_bars_available, minibuf_level,
         minibuf_selected_window as current_minibuf_window, scroll_command, select_window,
         selected_window as current_window, set_buffer_internal, set_window_hscroll,
-        update_mode_lines, window_body_width, window_list_1, window_menu_bar_p, window_tool_bar_p,
-        wset_redisplay,
+        update_mode_lines, window_list_1, window_menu_bar_p, window_tool_bar_p, wset_redisplay,
     },
-    remacs_sys::{face_id, glyph_matrix, pvec_type, EmacsInt, Lisp_Type, Lisp_Window},
     remacs_sys::{
-        Qceiling, Qfloor, Qheader_line_format, Qmode_line_format, Qnil, Qnone, Qwindow_live_p,
-        Qwindow_valid_p, Qwindowp,
+        face_id, glyph_matrix, pvec_type, vertical_scroll_bar_type, EmacsInt, Lisp_Type,
+        Lisp_Window,
+    },
+    remacs_sys::{
+        Qbottom, Qceiling, Qfloor, Qheader_line_format, Qleft, Qmode_line_format, Qnil, Qnone,
+        Qright, Qt, Qwindow_live_p, Qwindow_valid_p, Qwindowp,
     },
     threads::ThreadState,
 };
@@ -215,6 +217,243 @@ impl LispWindowRef {
             && self.pixel_height > height
     }
 
+    /// Return the current height of the header line of window W, like
+    /// `current_mode_line_height` does for the mode line.
+    pub fn current_header_line_height(&mut self) -> i32 {
+        let header_line_height = self.header_line_height;
+        let matrix_header_line_height =
+            LispGlyphMatrixRef::new(self.current_matrix).header_line_height();
+
+        if header_line_height >= 0 {
+            header_line_height
+        } else if matrix_header_line_height != 0 {
+            self.header_line_height = matrix_header_line_height;
+            matrix_header_line_height
+        } else {
+            let mut frame = self.frame.as_frame_or_error();
+            let header_line_height =
+                unsafe { estimate_mode_line_height(frame.as_mut(), face_id::HEADER_LINE_FACE_ID) };
+            self.header_line_height = header_line_height;
+            header_line_height
+        }
+    }
+
+    /// Height in pixels of the mode line, zero if the window has none.
+    pub fn mode_line_pixel_height(mut self) -> i32 {
+        if self.wants_mode_line() {
+            self.current_mode_line_height()
+        } else {
+            0
+        }
+    }
+
+    /// Height in pixels of the header line, zero if the window has none.
+    pub fn header_line_pixel_height(mut self) -> i32 {
+        if self.wants_header_line() {
+            self.current_header_line_height()
+        } else {
+            0
+        }
+    }
+
+    /// The pixel position just right of the window.
+    pub fn right_pixel_edge(self) -> i32 {
+        self.pixel_left + self.pixel_width
+    }
+
+    /// The pixel position just below the window.
+    pub fn bottom_pixel_edge(self) -> i32 {
+        self.pixel_top + self.pixel_height
+    }
+
+    fn root_window(self) -> LispWindowRef {
+        self.frame
+            .as_frame_or_error()
+            .root_window
+            .as_window_or_error()
+    }
+
+    /// True if no other window is to the right of the window on its frame.
+    pub fn is_rightmost(self) -> bool {
+        self.right_pixel_edge() == self.root_window().right_pixel_edge()
+    }
+
+    /// True if no other window is below the window on its frame.  The
+    /// minibuffer window is not counted unless the window is the
+    /// minibuffer window itself.
+    pub fn is_bottommost(self) -> bool {
+        self.bottom_pixel_edge() == self.root_window().bottom_pixel_edge()
+    }
+
+    /// Width in pixels of the divider on the right of the window.
+    pub fn right_divider_width(self) -> i32 {
+        if self.is_rightmost() {
+            0
+        } else {
+            self.frame.as_frame_or_error().right_divider_width()
+        }
+    }
+
+    /// Height in pixels of the divider at the bottom of the window.
+    pub fn bottom_divider_width(self) -> i32 {
+        let frame = self.frame.as_frame_or_error();
+        let root = frame.root_window;
+        if (self.is_bottommost() && root.as_window_or_error().next.is_nil())
+            || self.prev.eq(root)
+            || self.is_pseudo()
+        {
+            0
+        } else {
+            frame.bottom_divider_width()
+        }
+    }
+
+    /// Width in pixels of the left fringe.
+    pub fn left_fringe_width(self) -> i32 {
+        if self.left_fringe_width >= 0 {
+            self.left_fringe_width
+        } else {
+            self.frame.as_frame_or_error().left_fringe_width()
+        }
+    }
+
+    /// Width in pixels of the right fringe.
+    pub fn right_fringe_width(self) -> i32 {
+        if self.right_fringe_width >= 0 {
+            self.right_fringe_width
+        } else {
+            self.frame.as_frame_or_error().right_fringe_width()
+        }
+    }
+
+    pub fn fringes_width(self) -> i32 {
+        self.left_fringe_width() + self.right_fringe_width()
+    }
+
+    /// Width in pixels of the left marginal area.
+    pub fn left_margin_width(self) -> i32 {
+        self.left_margin_cols * self.frame.as_frame_or_error().column_width
+    }
+
+    /// Width in pixels of the right marginal area.
+    pub fn right_margin_width(self) -> i32 {
+        self.right_margin_cols * self.frame.as_frame_or_error().column_width
+    }
+
+    pub fn margins_width(self) -> i32 {
+        self.left_margin_width() + self.right_margin_width()
+    }
+
+    /// The side of the vertical scroll bar of the window, if it has one.
+    pub fn vertical_scroll_bar_type(self) -> vertical_scroll_bar_type::Type {
+        let scroll_bar_type = self.vertical_scroll_bar_type;
+
+        if self.is_pseudo() {
+            vertical_scroll_bar_type::vertical_scroll_bar_none
+        } else if scroll_bar_type.eq(Qt) {
+            self.frame.as_frame_or_error().vertical_scroll_bar_type()
+        } else if scroll_bar_type.eq(Qleft) {
+            vertical_scroll_bar_type::vertical_scroll_bar_left
+        } else if scroll_bar_type.eq(Qright) {
+            vertical_scroll_bar_type::vertical_scroll_bar_right
+        } else {
+            vertical_scroll_bar_type::vertical_scroll_bar_none
+        }
+    }
+
+    pub fn has_vertical_scroll_bar(self) -> bool {
+        self.vertical_scroll_bar_type() != vertical_scroll_bar_type::vertical_scroll_bar_none
+    }
+
+    /// True if the window has a horizontal scroll bar.  Only the toolkit
+    /// versions have them.
+    pub fn has_horizontal_scroll_bar(self) -> bool {
+        let scroll_bar_type = self.horizontal_scroll_bar_type;
+
+        if !unsafe { horizontal_scroll_bars_available() }
+            || self.is_pseudo()
+            || (self.is_minibuffer() && self.prev.is_not_nil())
+        {
+            false
+        } else if scroll_bar_type.eq(Qt) {
+            self.frame.as_frame_or_error().has_horizontal_scroll_bars()
+        } else {
+            scroll_bar_type.eq(Qbottom)
+        }
+    }
+
+    /// Width in pixels of the vertical scroll bar area, zero if the
+    /// window has no vertical scroll bar.
+    pub fn scroll_bar_area_width(self) -> i32 {
+        if !self.has_vertical_scroll_bar() {
+            0
+        } else if self.scroll_bar_width >= 0 {
+            self.scroll_bar_width
+        } else {
+            self.frame.as_frame_or_error().config_scroll_bar_width
+        }
+    }
+
+    /// Height in pixels of the horizontal scroll bar area, zero if the
+    /// window has no horizontal scroll bar.
+    pub fn scroll_bar_area_height(self) -> i32 {
+        if !self.has_horizontal_scroll_bar() {
+            0
+        } else if self.scroll_bar_height >= 0 {
+            self.scroll_bar_height
+        } else {
+            self.frame.as_frame_or_error().config_scroll_bar_height
+        }
+    }
+
+    /// Return the number of columns, or pixels if PIXELWISE, of the
+    /// window's text area.  Scroll bars, the divider or vertical bar
+    /// separating the window from its right sibling, the margins and,
+    /// on window systems, the fringes are not counted.  Partially
+    /// visible columns are not counted either.
+    pub fn body_width(self, pixelwise: bool) -> i32 {
+        let frame = self.frame.as_frame_or_error();
+        let gui = frame.is_gui_window();
+        let divider = self.right_divider_width();
+
+        let scroll_bar = if self.has_vertical_scroll_bar() {
+            self.scroll_bar_area_width()
+        } else if !gui && !self.is_rightmost() && divider == 0 {
+            // The vertical bar of a text terminal.
+            1
+        } else {
+            0
+        };
+        let fringes = if gui { self.fringes_width() } else { 0 };
+        let width = self.pixel_width - divider - scroll_bar - self.margins_width() - fringes;
+
+        let width = if pixelwise {
+            width
+        } else {
+            width / frame.column_width
+        };
+        width.max(0)
+    }
+
+    /// Return the number of lines, or pixels if PIXELWISE, of the
+    /// window's text area.  The mode and header lines, the horizontal
+    /// scroll bar and the bottom divider are not counted, nor is a
+    /// partially visible line.
+    pub fn body_height(self, pixelwise: bool) -> i32 {
+        let height = self.pixel_height
+            - self.header_line_pixel_height()
+            - self.scroll_bar_area_height()
+            - self.mode_line_pixel_height()
+            - self.bottom_divider_width();
+
+        let height = if pixelwise {
+            height
+        } else {
+            height / self.frame.as_frame_or_error().line_height
+        };
+        height.max(0)
+    }
+
     /// True if window W is a vertical combination of windows.
     pub fn is_vertical_combination(self) -> bool {
         self.is_internal() && !self.horizontal()
@@ -309,6 +548,14 @@ impl LispGlyphMatrixRef {
             unsafe { (*self.rows.offset((self.nrows - 1) as isize)).height }
         }
     }
+
+    pub fn header_line_height(self) -> i32 {
+        if self.is_null() || self.rows.is_null() {
+            0
+        } else {
+            unsafe { (*self.rows).height }
+        }
+    }
 }
 
 pub struct LispWindowOrSelected(LispObject);
@@ -501,6 +748,89 @@ pub fn window_margins(window: LispWindowLiveOrSelected) -> LispObject {
     )
 }
 
+/// Return the height of WINDOW's text area.
+/// WINDOW must be a live window and defaults to the selected one.  Optional
+/// argument PIXELWISE non-nil means return the height of WINDOW's text area
+/// in pixels.  The return value does not include the mode line or header
+/// line or any horizontal divider.
+///
+/// If PIXELWISE is nil, return the largest integer smaller than WINDOW's
+/// pixel height divided by the character height of WINDOW's frame.  This
+/// means that if a line at the bottom of the text area is only partially
+/// visible, that line is not counted.
+#[lisp_fn(min = "0", name = "window-body-height")]
+pub fn window_body_height_lisp(window: LispWindowLiveOrSelected, pixelwise: bool) -> i32 {
+    let win: LispWindowRef = window.into();
+    win.body_height(pixelwise)
+}
+
+/// Return the width of WINDOW's text area.
+/// WINDOW must be a live window and defaults to the selected one.  Optional
+/// argument PIXELWISE non-nil means return the width in pixels.  The return
+/// value does not include any vertical dividers, fringes or marginal areas,
+/// or scroll bars.
+///
+/// If PIXELWISE is nil, return the largest integer smaller than WINDOW's
+/// pixel width divided by the character width of WINDOW's frame.  This
+/// means that if a column at the right of the text area is only partially
+/// visible, that column is not counted.
+///
+/// Note that the returned value includes the column reserved for the
+/// continuation glyph.
+#[lisp_fn(min = "0", name = "window-body-width")]
+pub fn window_body_width_lisp(window: LispWindowLiveOrSelected, pixelwise: bool) -> i32 {
+    let win: LispWindowRef = window.into();
+    win.body_width(pixelwise)
+}
+
+/// Return the height in pixels of WINDOW's mode-line.
+/// WINDOW must be a live window and defaults to the selected one.
+#[lisp_fn(min = "0")]
+pub fn window_mode_line_height(window: LispWindowLiveOrSelected) -> i32 {
+    let win: LispWindowRef = window.into();
+    win.mode_line_pixel_height()
+}
+
+/// Return the height in pixels of WINDOW's header-line.
+/// WINDOW must be a live window and defaults to the selected one.
+#[lisp_fn(min = "0")]
+pub fn window_header_line_height(window: LispWindowLiveOrSelected) -> i32 {
+    let win: LispWindowRef = window.into();
+    win.header_line_pixel_height()
+}
+
+/// Return the width in pixels of WINDOW's right divider.
+/// WINDOW must be a live window and defaults to the selected one.
+#[lisp_fn(min = "0", name = "window-right-divider-width")]
+pub fn window_right_divider_width_lisp(window: LispWindowLiveOrSelected) -> i32 {
+    let win: LispWindowRef = window.into();
+    win.right_divider_width()
+}
+
+/// Return the width in pixels of WINDOW's bottom divider.
+/// WINDOW must be a live window and defaults to the selected one.
+#[lisp_fn(min = "0", name = "window-bottom-divider-width")]
+pub fn window_bottom_divider_width_lisp(window: LispWindowLiveOrSelected) -> i32 {
+    let win: LispWindowRef = window.into();
+    win.bottom_divider_width()
+}
+
+/// Return the width in pixels of WINDOW's vertical scrollbar.
+/// WINDOW must be a live window and defaults to the selected one.
+#[lisp_fn(min = "0")]
+pub fn window_scroll_bar_width(window: LispWindowLiveOrSelected) -> i32 {
+    let win: LispWindowRef = window.into();
+    win.scroll_bar_area_width()
+}
+
+/// Return the height in pixels of WINDOW's horizontal scrollbar.
+/// WINDOW must be a live window and defaults to the selected one.
+#[lisp_fn(min = "0")]
+pub fn window_scroll_bar_height(window: LispWindowLiveOrSelected) -> i32 {
+    let win: LispWindowRef = window.into();
+    win.scroll_bar_area_height()
+}
+
 /// Return combination limit of window WINDOW.
 /// WINDOW must be a valid window used in horizontal or vertical combination.
 /// If the return value is nil, child windows of WINDOW can be recombined with
@@ -733,6 +1063,83 @@ pub extern "C" fn CURRENT_MODE_LINE_HEIGHT(mut window: LispWindowRef) -> i32 {
     window.current_mode_line_height()
 }
 
+// The geometry of the parts of a window, for the display code.
+
+#[no_mangle]
+pub extern "C" fn window_body_width(window: LispWindowRef, pixelwise: bool) -> i32 {
+    window.body_width(pixelwise)
+}
+
+#[no_mangle]
+pub extern "C" fn window_body_height(window: LispWindowRef, pixelwise: bool) -> i32 {
+    window.body_height(pixelwise)
+}
+
+#[no_mangle]
+pub extern "C" fn window_mode_line_pixel_height(window: LispWindowRef) -> i32 {
+    window.mode_line_pixel_height()
+}
+
+#[no_mangle]
+pub extern "C" fn window_header_line_pixel_height(window: LispWindowRef) -> i32 {
+    window.header_line_pixel_height()
+}
+
+#[no_mangle]
+pub extern "C" fn window_right_divider_width(window: LispWindowRef) -> i32 {
+    window.right_divider_width()
+}
+
+#[no_mangle]
+pub extern "C" fn window_bottom_divider_width(window: LispWindowRef) -> i32 {
+    window.bottom_divider_width()
+}
+
+#[no_mangle]
+pub extern "C" fn window_left_fringe_width(window: LispWindowRef) -> i32 {
+    window.left_fringe_width()
+}
+
+#[no_mangle]
+pub extern "C" fn window_right_fringe_width(window: LispWindowRef) -> i32 {
+    window.right_fringe_width()
+}
+
+#[no_mangle]
+pub extern "C" fn window_left_margin_width(window: LispWindowRef) -> i32 {
+    window.left_margin_width()
+}
+
+#[no_mangle]
+pub extern "C" fn window_right_margin_width(window: LispWindowRef) -> i32 {
+    window.right_margin_width()
+}
+
+#[no_mangle]
+pub extern "C" fn window_has_vertical_scroll_bar_on_left(window: LispWindowRef) -> bool {
+    window.vertical_scroll_bar_type() == vertical_scroll_bar_type::vertical_scroll_bar_left
+}
+
+#[no_mangle]
+pub extern "C" fn window_has_vertical_scroll_bar_on_right(window: LispWindowRef) -> bool {
+    window.vertical_scroll_bar_type() == vertical_scroll_bar_type::vertical_scroll_bar_right
+}
+
+#[no_mangle]
+pub extern "C" fn window_has_horizontal_scroll_bar(window: LispWindowRef) -> bool {
+    window.has_horizontal_scroll_bar()
+}
+
+#[no_mangle]
+pub extern "C" fn window_scroll_bar_area_width(window: LispWindowRef) -> i32 {
+    window.scroll_bar_area_width()
+}
+
+#[no_mangle]
+pub extern "C" fn window_scroll_bar_area_height(window: LispWindowRef) -> i32 {
+    window.scroll_bar_area_height()
+}
+
 /// Return a list of windows on FRAME, starting with WINDOW.
 /// FRAME nil or omitted means use the selected frame.
 /// WINDOW nil or omitted means use the window selected within FRAME.
@@ -993,7 +1400,7 @@ pub fn window_top_child(window: LispWindowValidOrSelected) -> Option<LispWindowR
 pub fn scroll_horizontally(arg: LispObject, set_minimum: LispObject, left: bool) -> LispObject {
     let mut w = selected_window().as_window_or_error();
     let requested_arg = if arg.is_nil() {
-        unsafe { EmacsInt::from(window_body_width(w.as_mut(), false)) - 2 }
+        EmacsInt::from(w.body_width(false)) - 2
     } else if left {
         prefix_numeric_value(arg)
     } else {
@@ -0,0 +1,226 @@
+//! Dynamic highlights of windows.
+//!
+//! Modes like `hl-line-mode' highlight a stretch of text that moves
+//! after almost every command, which they used to do by moving an
+//! overlay with a `window' property.  A window highlight is a face for
+//! a range of the text of a single window that redisplay merges into the
+//! faces of that text, like an overlay's, but that lives in the window
+//! instead of the buffer's overlay lists.  A window's highlights are
+//! kept in its `highlights' slot, as a list of vectors
+//! [KEY START END FACE PRIORITY] where START and END are markers.
+
+use libc::ptrdiff_t;
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    buffers::LispBufferRef,
+    lisp::{defsubr, LispObject},
+    lists::delq,
+    marker::set_marker,
+    remacs_sys::{modify_overlay, EmacsInt, Fmake_marker, Fmake_vector, Qnil},
+    threads::ThreadState,
+    vectors::LispVectorRef,
+    windows::{LispWindowLiveOrSelected, LispWindowRef},
+};
+
+const KEY: usize = 0;
+const START: usize = 1;
+const END: usize = 2;
+const FACE: usize = 3;
+const PRIORITY: usize = 4;
+const SLOTS: usize = 5;
+
+/// Return the highlights of W.
+fn highlights(w: LispWindowRef) -> impl Iterator<Item = LispVectorRef> {
+    w.highlights.iter_cars_safe().filter_map(|h| h.as_vector())
+}
+
+/// Return the highlight of W called KEY.
+fn find_highlight(w: LispWindowRef, key: LispObject) -> Option<LispVectorRef> {
+    highlights(w).find(|h| h.get(KEY).eq(key))
+}
+
+/// Return the range of H if it is in BUFFER.
+fn highlight_range(h: LispVectorRef, buffer: LispBufferRef) -> Option<(ptrdiff_t, ptrdiff_t)> {
+    let (start, end) = (h.get(START).as_marker()?, h.get(END).as_marker()?);
+    if start.buffer() != Some(buffer) {
+        return None;
+    }
+    Some((start.charpos()?, end.charpos()?))
+}
+
+/// Make redisplay show the text from START to END of BUFFER again.
+fn redisplay_range(mut buffer: LispBufferRef, start: ptrdiff_t, end: ptrdiff_t) {
+    if start != end {
+        unsafe { modify_overlay(buffer.as_mut(), start, end) };
+    }
+}
+
+/// Highlight the text from START to END in WINDOW with FACE.
+/// KEY is a symbol that names the highlight; setting a highlight of
+/// WINDOW with the same KEY again moves it, which is much cheaper than
+/// moving an overlay.  The highlight is shown only in WINDOW, which must
+/// be a live window and defaults to the selected one, and only while it
+/// displays the buffer it displayed when the highlight was set.  The
+/// highlight's FACE is merged above the faces of text properties and
+/// below those of overlays.  Where highlights overlap, the face of the
+/// one with the greater PRIORITY, an integer that defaults to 0, takes
+/// precedence.  The highlight moves with the text of the buffer like an
+/// overlay.
+#[lisp_fn(min = "5")]
+pub fn set_window_highlight(
+    window: LispWindowLiveOrSelected,
+    key: LispObject,
+    start: EmacsInt,
+    end: EmacsInt,
+    face: LispObject,
+    priority: Option<EmacsInt>,
+) {
+    let mut w: LispWindowRef = window.into();
+    let buffer_obj = w.contents;
+    let buffer = buffer_obj.as_buffer_or_error();
+    let (start, end) = if start <= end {
+        (start, end)
+    } else {
+        (end, start)
+    };
+
+    let (mut h, old) = match find_highlight(w, key) {
+        Some(h) => (h, highlight_range(h, buffer)),
+        None => {
+            let mut h = unsafe { Fmake_vector(LispObject::from(SLOTS as EmacsInt), Qnil) }
+                .as_vector_or_error();
+            h.set(KEY, key);
+            h.set(START, unsafe { Fmake_marker() });
+            h.set(END, unsafe { Fmake_marker() });
+            w.highlights = LispObject::cons(h.as_lisp_obj(), w.highlights);
+            (h, None)
+        }
+    };
+    let priority = LispObject::from(priority.unwrap_or(0));
+    let changed_face = !h.get(FACE).eq(face) || !h.get(PRIORITY).eq(priority);
+    set_marker(h.get(START), LispObject::from(start), buffer_obj);
+    set_marker(h.get(END), LispObject::from(end), buffer_obj);
+    h.set(FACE, face);
+    h.set(PRIORITY, priority);
+    let (new_start, new_end) = highlight_range(h, buffer).unwrap_or((0, 0));
+
+    // Redisplay the text the highlight has just left or enclosed, as
+    // `move-overlay' does, or all of it if its face has changed.
+    match old {
+        Some((old_start, old_end)) if !changed_face => {
+            if old_start == new_start {
+                redisplay_range(buffer, old_end.min(new_end), old_end.max(new_end));
+            } else if old_end == new_end {
+                redisplay_range(buffer, old_start.min(new_start), old_start.max(new_start));
+            } else if old_end < new_start || new_end < old_start {
+                redisplay_range(buffer, old_start, old_end);
+                redisplay_range(buffer, new_start, new_end);
+            } else {
+                redisplay_range(buffer, old_start.min(new_start), old_end.max(new_end));
+            }
+        }
+        Some((old_start, old_end)) => {
+            redisplay_range(buffer, old_start, old_end);
+            redisplay_range(buffer, new_start, new_end);
+        }
+        None => redisplay_range(buffer, new_start, new_end),
+    }
+}
+
+/// Remove the highlight of WINDOW called KEY.
+/// WINDOW defaults to the selected window.  Return non-nil if WINDOW had
+/// such a highlight.  See `set-window-highlight'.
+#[lisp_fn(min = "2")]
+pub fn remove_window_highlight(window: LispWindowLiveOrSelected, key: LispObject) -> bool {
+    let mut w: LispWindowRef = window.into();
+    let h = match find_highlight(w, key) {
+        Some(h) => h,
+        None => return false,
+    };
+    if let Some(buffer) = w.contents.as_buffer() {
+        if let Some((start, end)) = highlight_range(h, buffer) {
+            redisplay_range(buffer, start, end);
+        }
+    }
+    set_marker(h.get(START), Qnil, Qnil);
+    set_marker(h.get(END), Qnil, Qnil);
+    w.highlights = delq(h.as_lisp_obj(), w.highlights);
+    true
+}
+
+/// Return the highlight of WINDOW called KEY, or nil if there is none.
+/// WINDOW defaults to the selected window.  The value is a list
+/// (START END FACE).  See `set-window-highlight'.
+#[lisp_fn(min = "2")]
+pub fn window_highlight(window: LispWindowLiveOrSelected, key: LispObject) -> LispObject {
+    let w: LispWindowRef = window.into();
+    find_highlight(w, key)
+        .and_then(|h| {
+            let buffer = w.contents.as_buffer()?;
+            let (start, end) = highlight_range(h, buffer)?;
+            Some(list!(
+                LispObject::from(start as EmacsInt),
+                LispObject::from(end as EmacsInt),
+                h.get(FACE)
+            ))
+        })
+        .unwrap_or(Qnil)
+}
+
+/// Return the face to merge at POS for the highlights of W, and clip
+/// *ENDPTR to the next position after POS at which that face changes.
+/// The value is nil if no highlight covers POS, and a list of faces,
+/// in decreasing order of priority, if several do.
+#[no_mangle]
+pub extern "C" fn window_highlight_face(
+    w: LispWindowRef,
+    pos: ptrdiff_t,
+    endptr: *mut ptrdiff_t,
+) -> LispObject {
+    if w.is_null() || w.highlights.is_nil() {
+        return Qnil;
+    }
+    let buffer = ThreadState::current_buffer();
+    let limit = unsafe { &mut *endptr };
+    let mut faces = Vec::new();
+    for h in highlights(w) {
+        let (start, end) = match highlight_range(h, buffer) {
+            Some(range) => range,
+            None => continue,
+        };
+        if start <= pos && pos < end {
+            faces.push((h.get(PRIORITY).as_fixnum().unwrap_or(0), h.get(FACE)));
+            *limit = (*limit).min(end);
+        } else if pos < start {
+            *limit = (*limit).min(start);
+        }
+    }
+    match faces.len() {
+        0 => Qnil,
+        1 => faces[0].1,
+        _ => {
+            faces.sort_by(|a, b| b.0.cmp(&a.0));
+            LispObject::from(faces.into_iter().map(|(_, face)| face).collect::<Vec<_>>())
+        }
+    }
+}
+
+/// Return the next position after POS in the current buffer at which a
+/// highlight of W starts or ends, or PTRDIFF_MAX if there is none.
+#[no_mangle]
+pub extern "C" fn window_highlight_change(w: LispWindowRef, pos: ptrdiff_t) -> ptrdiff_t {
+    if w.is_null() || w.highlights.is_nil() {
+        return ptrdiff_t::max_value();
+    }
+    let buffer = ThreadState::current_buffer();
+    highlights(w)
+        .filter_map(|h| highlight_range(h, buffer))
+        .map(|(start, end)| if start > pos { start } else { end })
+        .filter(|&p| p > pos)
+        .min()
+        .unwrap_or_else(ptrdiff_t::max_value)
+}
+
+include!(concat!(env!("OUT_DIR"), "/winhighlight_exports.rs"));
@@ -0,0 +1,557 @@
+//! Indexes of the words of buffers.
+//!
+//! A buffer that has been asked for its words keeps an index of them,
+//! with where each occurs, for `buffer-word-index' and
+//! `dabbrev-completions'.  Before a change to its text, the words
+//! around the change are taken out of the index and the changed region
+//! is remembered, like the region caches do, and the words there are
+//! put back the next time the index is used.  The positions of the
+//! words after a change are kept relative to the end of the buffer, the
+//! way the text after the gap is, so that they need not be updated.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ptr;
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    buffers::{LispBufferOrCurrent, LispBufferRef, BEG},
+    eval::unbind_to,
+    lisp::{defsubr, LispObject},
+    marker::buf_charpos_to_bytepos,
+    multibyte::{multibyte_char_at, LispStringRef},
+    obarray::intern,
+    remacs_sys::Qnil,
+    remacs_sys::{
+        downcase, make_specified_string, record_unwind_current_buffer, set_buffer_internal_1,
+        syntax_property, syntaxcode, word_index, EmacsInt,
+    },
+    strings::string_to_multibyte,
+    threads::{c_specpdl_index, ThreadState},
+};
+
+/// Which characters make up words.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum WordSyntax {
+    /// Word constituents.
+    Word,
+    /// Word and symbol constituents, as with the default
+    /// `dabbrev-abbrev-char-regexp'.
+    Symbol,
+}
+
+impl From<LispObject> for WordSyntax {
+    fn from(syntax: LispObject) -> Self {
+        if syntax.is_nil() || syntax.eq(intern("symbol").as_lisp_obj()) {
+            WordSyntax::Symbol
+        } else if syntax.eq(intern("word").as_lisp_obj()) {
+            WordSyntax::Word
+        } else {
+            error!("Invalid word syntax")
+        }
+    }
+}
+
+/// Where a word occurs, by the positions of its starts.
+#[derive(Default)]
+struct Occurrences {
+    /// Positions before the last change, as character positions.
+    before: BTreeSet<ptrdiff_t>,
+    /// Positions after the last change, as distances from the end of the
+    /// buffer.
+    after: BTreeSet<ptrdiff_t>,
+}
+
+/// The words of a buffer, split into words the way one `WordSyntax'
+/// says.
+#[derive(Default)]
+pub struct WordTable {
+    words: BTreeMap<Vec<u8>, Occurrences>,
+    /// The words by position, for the positions in `Occurrences::before'.
+    before: BTreeMap<ptrdiff_t, Vec<u8>>,
+    /// The words by position, for the positions in `Occurrences::after'.
+    after: BTreeMap<ptrdiff_t, Vec<u8>>,
+}
+
+impl WordTable {
+    /// Add WORD, which starts at position POS.
+    fn add(&mut self, word: Vec<u8>, pos: ptrdiff_t) {
+        self.words
+            .entry(word.clone())
+            .or_insert_with(Occurrences::default)
+            .before
+            .insert(pos);
+        self.before.insert(pos, word);
+    }
+
+    /// Make the words that start before position POS the ones before
+    /// the last change, and the others the ones after it.  Z is the end
+    /// of the buffer.
+    fn split(&mut self, pos: ptrdiff_t, z: ptrdiff_t) {
+        for (start, word) in self.before.split_off(&pos) {
+            let occurrences = self.words.get_mut(&word).unwrap();
+            occurrences.before.remove(&start);
+            occurrences.after.insert(z - start);
+            self.after.insert(z - start, word);
+        }
+        for (key, word) in self.after.split_off(&(z - pos + 1)) {
+            let occurrences = self.words.get_mut(&word).unwrap();
+            occurrences.after.remove(&key);
+            occurrences.before.insert(z - key);
+            self.before.insert(z - key, word);
+        }
+    }
+
+    /// Remove the words that start between positions START and END, in
+    /// a buffer that ends at Z.
+    fn remove_between(&mut self, start: ptrdiff_t, end: ptrdiff_t, z: ptrdiff_t) {
+        self.split(start, z);
+        for (key, word) in self.after.split_off(&(z - end + 1)) {
+            let gone = {
+                let occurrences = self.words.get_mut(&word).unwrap();
+                occurrences.after.remove(&key);
+                occurrences.before.is_empty() && occurrences.after.is_empty()
+            };
+            if gone {
+                self.words.remove(&word);
+            }
+        }
+    }
+
+    /// Return the positions where WORD starts, in a buffer that ends at
+    /// Z, in increasing order.
+    pub fn positions(&self, word: &[u8], z: ptrdiff_t) -> Vec<ptrdiff_t> {
+        self.words.get(word).map_or_else(Vec::new, |occurrences| {
+            let after = occurrences.after.iter().rev().map(|key| z - key);
+            occurrences.before.iter().cloned().chain(after).collect()
+        })
+    }
+
+    /// Return the number of times WORD occurs.
+    pub fn count(&self, word: &[u8]) -> usize {
+        self.words.get(word).map_or(0, |occurrences| {
+            occurrences.before.len() + occurrences.after.len()
+        })
+    }
+
+    /// Return the words that start with PREFIX, in order.  If
+    /// IGNORE_CASE, compare the characters as downcased by the current
+    /// buffer's case table; MULTIBYTE says how they are represented.
+    pub fn matching(&self, prefix: &[u8], ignore_case: bool, multibyte: bool) -> Vec<&[u8]> {
+        if !ignore_case {
+            return self
+                .words
+                .range(prefix.to_vec()..)
+                .map(|(word, _)| word.as_slice())
+                .take_while(|word| word.starts_with(prefix))
+                .collect();
+        }
+
+        let folded = fold_case(prefix, multibyte);
+        self.words
+            .keys()
+            .filter(|word| fold_case(word, multibyte).starts_with(&folded))
+            .map(|word| word.as_slice())
+            .collect()
+    }
+}
+
+/// Return the characters of the text TEXT, downcased.
+fn fold_case(text: &[u8], multibyte: bool) -> Vec<i32> {
+    let mut chars = Vec::with_capacity(text.len());
+    let mut i = 0;
+    while i < text.len() {
+        let (c, len) = if multibyte {
+            let (c, len) = multibyte_char_at(&text[i..]);
+            (c as i32, len)
+        } else {
+            (i32::from(text[i]), 1)
+        };
+        chars.push(unsafe { downcase(c) });
+        i += len;
+    }
+    chars
+}
+
+/// Return the number of characters in the text WORD.
+pub fn word_chars(word: &[u8], multibyte: bool) -> usize {
+    if multibyte {
+        word.iter().filter(|&&b| b & 0xC0 != 0x80).count()
+    } else {
+        word.len()
+    }
+}
+
+/// Return a string with the text WORD.
+pub fn make_word_string(word: &[u8], multibyte: bool) -> LispObject {
+    unsafe {
+        make_specified_string(
+            word.as_ptr() as *const c_char,
+            word_chars(word, multibyte) as ptrdiff_t,
+            word.len() as ptrdiff_t,
+            multibyte,
+        )
+    }
+}
+
+/// Return the text of string STRING in the representation of a buffer
+/// that is MULTIBYTE, or None if it has no such representation.
+pub fn word_text(string: LispStringRef, multibyte: bool) -> Option<Vec<u8>> {
+    if string.as_slice().is_ascii() {
+        Some(string.as_slice().to_vec())
+    } else if !multibyte {
+        None
+    } else if string.is_multibyte() {
+        Some(string.as_slice().to_vec())
+    } else {
+        Some(
+            string_to_multibyte(string)
+                .as_string_or_error()
+                .as_slice()
+                .to_vec(),
+        )
+    }
+}
+
+/// Walks over the text of the current buffer, by character.
+#[derive(Clone, Copy)]
+struct Text {
+    buffer: LispBufferRef,
+    multibyte: bool,
+}
+
+impl Text {
+    fn current() -> Self {
+        let buffer = ThreadState::current_buffer();
+        Self {
+            buffer,
+            multibyte: buffer.multibyte_characters_enabled(),
+        }
+    }
+
+    fn next(self, pos: (ptrdiff_t, ptrdiff_t)) -> (ptrdiff_t, ptrdiff_t) {
+        let byte = if self.multibyte {
+            self.buffer.inc_pos(pos.1)
+        } else {
+            pos.1 + 1
+        };
+        (pos.0 + 1, byte)
+    }
+
+    fn prev(self, pos: (ptrdiff_t, ptrdiff_t)) -> (ptrdiff_t, ptrdiff_t) {
+        let byte = if self.multibyte {
+            self.buffer.dec_pos(pos.1)
+        } else {
+            pos.1 - 1
+        };
+        (pos.0 - 1, byte)
+    }
+
+    /// Return true if the character at byte position BYTE is part of
+    /// words as SYNTAX says.  Only ASCII characters are in words in
+    /// unibyte buffers.
+    fn is_word_char(self, syntax: WordSyntax, byte: ptrdiff_t) -> bool {
+        let c = self.buffer.fetch_char(byte);
+        if !self.multibyte && c >= 0x80 {
+            return false;
+        }
+        match unsafe { syntax_property(c, false) } {
+            syntaxcode::Sword => true,
+            syntaxcode::Ssymbol => syntax == WordSyntax::Symbol,
+            _ => false,
+        }
+    }
+
+    fn position(self, charpos: ptrdiff_t) -> (ptrdiff_t, ptrdiff_t) {
+        let mut buffer = self.buffer;
+        (charpos, unsafe {
+            buf_charpos_to_bytepos(buffer.as_mut(), charpos)
+        })
+    }
+
+    /// Return START moved back to the start of the word it is in, and
+    /// END moved forward to the end of its word.  Words are taken to be
+    /// as long as any `WordSyntax' makes them.
+    fn around_words(self, start: ptrdiff_t, end: ptrdiff_t) -> (ptrdiff_t, ptrdiff_t) {
+        let z = self.buffer.z();
+        let mut start = self.position(start);
+        while start.0 > BEG && self.is_word_char(WordSyntax::Symbol, self.prev(start).1) {
+            start = self.prev(start);
+        }
+        let mut end = self.position(end);
+        while end.0 < z && self.is_word_char(WordSyntax::Symbol, end.1) {
+            end = self.next(end);
+        }
+        (start.0, end.0)
+    }
+
+    /// Call F with the text and the position of each word between START
+    /// and END, which should not be inside words.
+    fn for_each_word(
+        self,
+        syntax: WordSyntax,
+        start: ptrdiff_t,
+        end: ptrdiff_t,
+        mut f: impl FnMut(Vec<u8>, ptrdiff_t),
+    ) {
+        let mut pos = self.position(start);
+        while pos.0 < end {
+            if !self.is_word_char(syntax, pos.1) {
+                pos = self.next(pos);
+                continue;
+            }
+            let word_start = pos;
+            while pos.0 < end && self.is_word_char(syntax, pos.1) {
+                pos = self.next(pos);
+            }
+            f(self.buffer.bytes_between(word_start.1, pos.1), word_start.0);
+        }
+    }
+}
+
+/// The word tables of a buffer.
+pub struct WordIndex {
+    tables: Vec<(WordSyntax, WordTable)>,
+    /// Whether the words are in the multibyte representation.
+    multibyte: bool,
+    /// The syntax table the words were found with.  Changes to the
+    /// table itself are not noticed.
+    syntax_table: LispObject,
+    /// The number of characters at the beginning and at the end of the
+    /// buffer that have not changed since the index was last brought up
+    /// to date, if the text in between has.  The words there are
+    /// missing from the tables.
+    unchanged: Option<(ptrdiff_t, ptrdiff_t)>,
+    /// The buffer's `chars_modiff' when the index was last brought up to
+    /// date.
+    chars_modiff: EmacsInt,
+}
+
+fn base_buffer(buffer: LispBufferRef) -> LispBufferRef {
+    buffer.base_buffer().unwrap_or(buffer)
+}
+
+fn index_of(buffer: LispBufferRef) -> Option<&'static mut WordIndex> {
+    unsafe { (base_buffer(buffer).word_index as *mut WordIndex).as_mut() }
+}
+
+fn drop_index(buffer: LispBufferRef) {
+    let mut buffer = base_buffer(buffer);
+    free_word_index(buffer.word_index);
+    buffer.word_index = ptr::null_mut();
+}
+
+/// Free the word index INDEX of a buffer.
+#[no_mangle]
+pub extern "C" fn free_word_index(index: *mut word_index) {
+    if !index.is_null() {
+        drop(unsafe { Box::from_raw(index as *mut WordIndex) });
+    }
+}
+
+/// Take the words around the text between START and END of buffer
+/// BUFFER, which is about to change, out of its word index.
+#[no_mangle]
+pub extern "C" fn word_index_invalidate(buffer: LispBufferRef, start: ptrdiff_t, end: ptrdiff_t) {
+    let index = match index_of(buffer) {
+        Some(index) => index,
+        None => return,
+    };
+    let text = Text::current();
+    if base_buffer(text.buffer) != base_buffer(buffer)
+        || (index.unchanged.is_none() && index.chars_modiff != buffer.char_modifications())
+    {
+        // The words cannot be found with the right syntax table, or the
+        // text changed behind our back; start over when needed.
+        drop_index(buffer);
+        return;
+    }
+
+    // Insertions from files may say where the text will end up.
+    let z = text.buffer.z();
+    let (mut start, mut end) = text.around_words(start.max(BEG).min(z), end.max(BEG).min(z));
+    if let Some((head, tail)) = index.unchanged {
+        start = start.min(BEG + head);
+        end = end.max(z - tail);
+    }
+    for (_, table) in &mut index.tables {
+        table.remove_between(start, end, z);
+    }
+    index.unchanged = Some((start - BEG, z - end));
+}
+
+/// Return the word table of the current buffer for SYNTAX, brought up to
+/// date.
+fn current_table(syntax: WordSyntax) -> &'static WordTable {
+    let text = Text::current();
+    let mut buffer = base_buffer(text.buffer);
+    let chars_modiff = buffer.char_modifications();
+    let syntax_table = text.buffer.syntax_table_;
+
+    if let Some(index) = index_of(buffer) {
+        if index.multibyte != text.multibyte
+            || index.syntax_table != syntax_table
+            || (index.unchanged.is_none() && index.chars_modiff != chars_modiff)
+        {
+            drop_index(buffer);
+        }
+    }
+    if index_of(buffer).is_none() {
+        let index = Box::new(WordIndex {
+            tables: Vec::new(),
+            multibyte: text.multibyte,
+            syntax_table,
+            unchanged: None,
+            chars_modiff,
+        });
+        buffer.word_index = Box::into_raw(index) as *mut word_index;
+    }
+
+    let index = index_of(buffer).unwrap();
+    let z = text.buffer.z();
+    if let Some((head, tail)) = index.unchanged.take() {
+        for (syntax, table) in &mut index.tables {
+            text.for_each_word(*syntax, BEG + head, z - tail, |word, pos| {
+                table.add(word, pos)
+            });
+        }
+    }
+    index.chars_modiff = chars_modiff;
+
+    if !index.tables.iter().any(|(s, _)| *s == syntax) {
+        let mut table = WordTable::default();
+        text.for_each_word(syntax, BEG, z, |word, pos| table.add(word, pos));
+        index.tables.push((syntax, table));
+    }
+    &index.tables.iter().find(|(s, _)| *s == syntax).unwrap().1
+}
+
+/// Call F with the word table of buffer BUFFER for SYNTAX, brought up to
+/// date, and the end of BUFFER, with BUFFER current.
+pub fn with_word_table<T>(
+    mut buffer: LispBufferRef,
+    syntax: WordSyntax,
+    f: impl FnOnce(&WordTable, ptrdiff_t) -> T,
+) -> T {
+    let count = c_specpdl_index();
+    unsafe {
+        record_unwind_current_buffer();
+        set_buffer_internal_1(buffer.as_mut());
+    }
+    let result = f(current_table(syntax), buffer.z());
+    unbind_to(count, Qnil);
+    result
+}
+
+/// Return the start and end of the word around position POS of the
+/// current buffer, or POS twice if there is none.
+pub fn word_around(pos: ptrdiff_t) -> (ptrdiff_t, ptrdiff_t) {
+    Text::current().around_words(pos, pos)
+}
+
+/// Return the words of BUFFER, with how many times and where they occur.
+/// The value is a list of elements (WORD COUNT . POSITIONS), where
+/// POSITIONS are the positions where WORD starts, in increasing order,
+/// sorted by WORD.  BUFFER defaults to the current buffer.
+///
+/// SYNTAX says what a word is.  The symbol `word' means a run of word
+/// constituent characters.  The symbol `symbol', or nil, means a run of
+/// word and symbol constituent characters, as with the default
+/// `dabbrev-abbrev-char-regexp'.
+///
+/// If PREFIX is non-nil, only the words that start with PREFIX are
+/// returned.
+///
+/// BUFFER keeps an index of its words, which is kept up to date as its
+/// text changes, so that it does not need to be searched again.
+#[lisp_fn(min = "0")]
+pub fn buffer_word_index(
+    buffer: LispBufferOrCurrent,
+    syntax: LispObject,
+    prefix: Option<LispStringRef>,
+) -> LispObject {
+    let buffer: LispBufferRef = buffer.into();
+    if !buffer.is_live() {
+        error!("Selecting deleted buffer");
+    }
+    let syntax = WordSyntax::from(syntax);
+    let multibyte = buffer.multibyte_characters_enabled();
+    let prefix = match prefix {
+        None => Vec::new(),
+        Some(prefix) => match word_text(prefix, multibyte) {
+            Some(prefix) => prefix,
+            None => return Qnil,
+        },
+    };
+
+    with_word_table(buffer, syntax, |table, z| {
+        let words: Vec<LispObject> = table
+            .matching(&prefix, false, multibyte)
+            .into_iter()
+            .map(|word| {
+                let positions: Vec<LispObject> = table
+                    .positions(word, z)
+                    .into_iter()
+                    .map(LispObject::from)
+                    .collect();
+                let count = LispObject::from(table.count(word));
+                LispObject::cons(
+                    make_word_string(word, multibyte),
+                    LispObject::cons(count, LispObject::from(positions)),
+                )
+            })
+            .collect();
+        LispObject::from(words)
+    })
+}
+
+include!(concat!(env!("OUT_DIR"), "/wordindex_exports.rs"));
+
+#[test]
+fn test_word_table() {
+    let mut table = WordTable::default();
+    // "foo foobar bar foobar foobaz", which ends at 29.
+    for &(word, pos) in &[
+        ("foo", 1),
+        ("foobar", 5),
+        ("bar", 12),
+        ("foobar", 16),
+        ("foobaz", 23),
+    ] {
+        table.add(word.as_bytes().to_vec(), pos);
+    }
+    let matching = |table: &WordTable, prefix: &str| -> Vec<String> {
+        table
+            .matching(prefix.as_bytes(), false, true)
+            .into_iter()
+            .map(|word| String::from_utf8(word.to_vec()).unwrap())
+            .collect()
+    };
+    assert_eq!(matching(&table, "foob"), vec!["foobar", "foobaz"]);
+    assert_eq!(matching(&table, ""), vec!["bar", "foo", "foobar", "foobaz"]);
+    assert!(matching(&table, "x").is_empty());
+    assert_eq!(table.count(b"foobar"), 2);
+    assert_eq!(table.positions(b"foobar", 29), vec![5, 16]);
+
+    // Deleting "bar " moves the words after it back.
+    table.remove_between(12, 16, 29);
+    assert_eq!(table.count(b"bar"), 0);
+    assert_eq!(matching(&table, "b"), Vec::<String>::new());
+    assert_eq!(table.positions(b"foobar", 25), vec![5, 12]);
+    assert_eq!(table.positions(b"foobaz", 25), vec![19]);
+
+    // Inserting "foo " at the start moves all of them forward.
+    table.remove_between(1, 4, 25);
+    table.add(b"foo".to_vec(), 1);
+    table.add(b"foo".to_vec(), 5);
+    assert_eq!(table.positions(b"foo", 29), vec![1, 5]);
+    assert_eq!(table.positions(b"foobar", 29), vec![9, 16]);
+
+    // A word stays as long as it occurs somewhere.
+    table.remove_between(9, 16, 29);
+    assert_eq!(table.positions(b"foobar", 29), vec![16]);
+    table.remove_between(1, 29, 29);
+    assert!(matching(&table, "").is_empty());
+}
@@ -1033,16 +1033,6 @@ dupstring (char **ptr, char const *string)
 }
 
 
-/* Like putenv, but (1) use the equivalent of xmalloc and (2) the
-   argument is a const pointer.  */
-
-void
-xputenv (char const *string)
-{
-  if (putenv ((char *) string) != 0)
-    memory_full (0);
-}
-
 /* Return a newly allocated memory block of SIZE bytes, remembering
    to free it when unwinding.  */
 void *
@@ -5928,6 +5918,7 @@ garbage_collect_1 (void *end)
   mark_finalizer_list (&doomed_finalizers);
 
   gc_sweep ();
+  gc_write_barrier_reset ();
 
   /* Clear the mark bits that we set in certain root slots.  */
   VECTOR_UNMARK (&buffer_defaults);
@@ -7050,6 +7041,8 @@ sweep_buffers (void)
       {
         VECTOR_UNMARK (buffer);
         /* Do not use buffer_(set|get)_intervals here.  */
+        if (buffer->text->pending_interval)
+          flush_interval_offsets (buffer->text);
         buffer->text->intervals = balance_intervals (buffer->text->intervals);
         total_buffers++;
         bprev = &buffer->next;
@@ -7400,6 +7393,13 @@ to users.  */);
   DEFVAR_INT ("strings-consed", strings_consed,
 	      doc: /* Number of strings that have been consed so far.  */);
 
+  DEFVAR_BOOL ("gc-allocation-accounting", gc_allocation_accounting,
+	       doc: /* Non-nil means to charge allocations to the subsystem making them.
+The reader, the evaluator and fontification each count the objects
+they allocate, as `memory-use-counts' does for all of Emacs, and
+`gc-allocation-report' returns the counts.  */);
+  gc_allocation_accounting = false;
+
   DEFVAR_LISP ("purify-flag", Vpurify_flag,
 	       doc: /* Non-nil means loading Lisp code in order to dump an executable.
 This means that certain objects should be allocated in shared (pure) space.
@@ -400,6 +400,12 @@ even if it is dead.  The return value is never nil.  */)
 
   /* An ordinary buffer uses its own struct buffer_text.  */
   b->text = &b->own_text;
+  /* allocate_buffer leaves these uninitialized, and
+     set_buffer_intervals reads them.  */
+  b->own_text.pending_interval = NULL;
+  b->own_text.pending_offset = 0;
+  /* The text may be where the text of a killed buffer was.  */
+  forget_buffer_intervals (b);
   b->base_buffer = NULL;
   /* No one shares the text with us now.  */
   b->indirections = 0;
@@ -444,9 +450,16 @@ even if it is dead.  The return value is never nil.  */)
   b->newline_cache = 0;
   b->width_run_cache = 0;
   b->bidi_paragraph_cache = 0;
+  b->word_index = 0;
   bset_width_table (b, Qnil);
   b->prevent_redisplay_optimizations_p = 1;
 
+  /* The buffer is empty, so it has one empty line.  */
+  b->line_stats_newlines = 0;
+  b->line_stats_max = 0;
+  b->line_stats_modiff = BUF_CHARS_MODIFF (b);
+  b->long_lines_reported = false;
+
   /* An ordinary buffer normally doesn't need markers
      to handle BEGV and ZV.  */
   bset_pt_marker (b, Qnil);
@@ -610,6 +623,9 @@ CLONE nil means the indirect buffer's state is reset to default values.  */)
 
   /* Use the base buffer's text object.  */
   b->text = b->base_buffer->text;
+  /* Our own text is never used, but don't leave garbage in it.  */
+  b->own_text.pending_interval = NULL;
+  b->own_text.pending_offset = 0;
   /* We have no own text.  */
   b->indirections = -1;
   /* Notify base buffer that we share the text now.  */
@@ -627,8 +643,13 @@ CLONE nil means the indirect buffer's state is reset to default values.  */)
   b->newline_cache = 0;
   b->width_run_cache = 0;
   b->bidi_paragraph_cache = 0;
+  b->word_index = 0;
   bset_width_table (b, Qnil);
 
+  b->line_stats_max = -1;
+  b->line_stats_modiff = -1;
+  b->long_lines_reported = false;
+
   name = Fcopy_sequence (name);
   set_string_intervals (name, NULL);
   bset_name (b, name);
@@ -1573,6 +1594,11 @@ cleaning up all windows currently displaying the buffer to be killed. */)
       free_region_cache (b->bidi_paragraph_cache);
       b->bidi_paragraph_cache = 0;
     }
+  if (b->word_index)
+    {
+      free_word_index (b->word_index);
+      b->word_index = 0;
+    }
   bset_width_table (b, Qnil);
   unblock_input ();
   bset_undo_list (b, Qnil);
@@ -1820,42 +1846,6 @@ set_buffer_if_live (Lisp_Object buffer)
 }
 
 
-/* Advance BYTE_POS up to a character boundary
-   and return the adjusted position.  */
-
-static ptrdiff_t
-advance_to_char_boundary (ptrdiff_t byte_pos)
-{
-  int c;
-
-  if (byte_pos == BEG)
-    /* Beginning of buffer is always a character boundary.  */
-    return BEG;
-
-  c = FETCH_BYTE (byte_pos);
-  if (! CHAR_HEAD_P (c))
-    {
-      /* We should advance BYTE_POS only when C is a constituent of a
-         multibyte sequence.  */
-      ptrdiff_t orig_byte_pos = byte_pos;
-
-      do
-	{
-	  byte_pos--;
-	  c = FETCH_BYTE (byte_pos);
-	}
-      while (! CHAR_HEAD_P (c) && byte_pos > BEG);
-      INC_POS (byte_pos);
-      if (byte_pos < orig_byte_pos)
-	byte_pos = orig_byte_pos;
-      /* If C is a constituent of a multibyte sequence, BYTE_POS was
-         surely advance to the correct character boundary.  If C is
-         not, BYTE_POS was unchanged.  */
-    }
-
-  return byte_pos;
-}
-
 DEFUN ("buffer-swap-text", Fbuffer_swap_text, Sbuffer_swap_text,
        1, 1, 0,
        doc: /* Swap the text between current buffer and BUFFER.
@@ -1901,6 +1891,11 @@ results, see Info node `(elisp)Swapping Text'.  */)
     bset_##field (current_buffer, tmp##field);			\
   } while (0)
 
+  /* Add up pending insertions while each tree is still in the text
+     of the buffer it records.  */
+  buffer_intervals (current_buffer);
+  buffer_intervals (other_buffer);
+
   swapfield (own_text, struct buffer_text);
   eassert (current_buffer->text == &current_buffer->own_text);
   eassert (other_buffer->text == &other_buffer->own_text);
@@ -1924,6 +1919,11 @@ results, see Info node `(elisp)Swapping Text'.  */)
   swapfield (newline_cache, struct region_cache *);
   swapfield (width_run_cache, struct region_cache *);
   swapfield (bidi_paragraph_cache, struct region_cache *);
+  swapfield (word_index, struct word_index *);
+  swapfield (line_stats_newlines, ptrdiff_t);
+  swapfield (line_stats_max, ptrdiff_t);
+  swapfield (line_stats_modiff, EMACS_INT);
+  swapfield (long_lines_reported, bool);
   current_buffer->prevent_redisplay_optimizations_p = 1;
   other_buffer->prevent_redisplay_optimizations_p = 1;
   swapfield (overlays_before, struct Lisp_Overlay *);
@@ -2005,304 +2005,16 @@ results, see Info node `(elisp)Swapping Text'.  */)
       }
   }
 
-  if (current_buffer->text->intervals)
-    (eassert (EQ (current_buffer->text->intervals->up.obj, buffer)),
-     XSETBUFFER (current_buffer->text->intervals->up.obj, current_buffer));
-  if (other_buffer->text->intervals)
-    (eassert (EQ (other_buffer->text->intervals->up.obj, Fcurrent_buffer ())),
-     XSETBUFFER (other_buffer->text->intervals->up.obj, other_buffer));
+  /* Each interval tree still points to the buffer it came from.  */
+  if (buffer_intervals (current_buffer))
+    transplant_intervals (buffer_intervals (current_buffer),
+			  Fcurrent_buffer ());
+  if (buffer_intervals (other_buffer))
+    transplant_intervals (buffer_intervals (other_buffer), buffer);
 
   return Qnil;
 }
 
-DEFUN ("set-buffer-multibyte", Fset_buffer_multibyte, Sset_buffer_multibyte,
-       1, 1, 0,
-       doc: /* Set the multibyte flag of the current buffer to FLAG.
-If FLAG is t, this makes the buffer a multibyte buffer.
-If FLAG is nil, this makes the buffer a single-byte buffer.
-In these cases, the buffer contents remain unchanged as a sequence of
-bytes but the contents viewed as characters do change.
-If FLAG is `to', this makes the buffer a multibyte buffer by changing
-all eight-bit bytes to eight-bit characters.
-If the multibyte flag was really changed, undo information of the
-current buffer is cleared.  */)
-  (Lisp_Object flag)
-{
-  struct Lisp_Marker *tail, *markers;
-  struct buffer *other;
-  ptrdiff_t begv, zv;
-  bool narrowed = (BEG != BEGV || Z != ZV);
-  bool modified_p = !NILP (Fbuffer_modified_p (Qnil));
-  Lisp_Object old_undo = BVAR (current_buffer, undo_list);
-
-  if (current_buffer->base_buffer)
-    error ("Cannot do `set-buffer-multibyte' on an indirect buffer");
-
-  /* Do nothing if nothing actually changes.  */
-  if (NILP (flag) == NILP (BVAR (current_buffer, enable_multibyte_characters)))
-    return flag;
-
-  /* Don't record these buffer changes.  We will put a special undo entry
-     instead.  */
-  bset_undo_list (current_buffer, Qt);
-
-  /* If the cached position is for this buffer, clear it out.  */
-  clear_charpos_cache (current_buffer);
-
-  if (NILP (flag))
-    begv = BEGV_BYTE, zv = ZV_BYTE;
-  else
-    begv = BEGV, zv = ZV;
-
-  if (narrowed)
-    error ("Changing multibyteness in a narrowed buffer");
-
-  invalidate_buffer_caches (current_buffer, BEGV, ZV);
-
-  if (NILP (flag))
-    {
-      ptrdiff_t pos, stop;
-      unsigned char *p;
-
-      /* Do this first, so it can use CHAR_TO_BYTE
-	 to calculate the old correspondences.  */
-      set_intervals_multibyte (0);
-
-      bset_enable_multibyte_characters (current_buffer, Qnil);
-
-      Z = Z_BYTE;
-      BEGV = BEGV_BYTE;
-      ZV = ZV_BYTE;
-      GPT = GPT_BYTE;
-      TEMP_SET_PT_BOTH (PT_BYTE, PT_BYTE);
-
-
-      for (tail = BUF_MARKERS (current_buffer); tail; tail = tail->next)
-	tail->charpos = tail->bytepos;
-
-      /* Convert multibyte form of 8-bit characters to unibyte.  */
-      pos = BEG;
-      stop = GPT;
-      p = BEG_ADDR;
-      while (1)
-	{
-	  int c, bytes;
-
-	  if (pos == stop)
-	    {
-	      if (pos == Z)
-		break;
-	      p = GAP_END_ADDR;
-	      stop = Z;
-	    }
-	  if (ASCII_CHAR_P (*p))
-	    p++, pos++;
-	  else if (CHAR_BYTE8_HEAD_P (*p))
-	    {
-	      c = STRING_CHAR_AND_LENGTH (p, bytes);
-	      /* Delete all bytes for this 8-bit character but the
-		 last one, and change the last one to the character
-		 code.  */
-	      bytes--;
-	      del_range_2 (pos, pos, pos + bytes, pos + bytes, 0);
-	      p = GAP_END_ADDR;
-	      *p++ = c;
-	      pos++;
-	      if (begv > pos)
-		begv -= bytes;
-	      if (zv > pos)
-		zv -= bytes;
-	      stop = Z;
-	    }
-	  else
-	    {
-	      bytes = BYTES_BY_CHAR_HEAD (*p);
-	      p += bytes, pos += bytes;
-	    }
-	}
-      if (narrowed)
-	Fnarrow_to_region (make_number (begv), make_number (zv));
-    }
-  else
-    {
-      ptrdiff_t pt = PT;
-      ptrdiff_t pos, stop;
-      unsigned char *p, *pend;
-
-      /* Be sure not to have a multibyte sequence striding over the GAP.
-	 Ex: We change this: "...abc\302 _GAP_ \241def..."
-	     to: "...abc _GAP_ \302\241def..."  */
-
-      if (EQ (flag, Qt)
-	  && GPT_BYTE > 1 && GPT_BYTE < Z_BYTE
-	  && ! CHAR_HEAD_P (*(GAP_END_ADDR)))
-	{
-	  unsigned char *q = GPT_ADDR - 1;
-
-	  while (! CHAR_HEAD_P (*q) && q > BEG_ADDR) q--;
-	  if (LEADING_CODE_P (*q))
-	    {
-	      ptrdiff_t new_gpt = GPT_BYTE - (GPT_ADDR - q);
-
-	      move_gap_both (new_gpt, new_gpt);
-	    }
-	}
-
-      /* Make the buffer contents valid as multibyte by converting
-	 8-bit characters to multibyte form.  */
-      pos = BEG;
-      stop = GPT;
-      p = BEG_ADDR;
-      pend = GPT_ADDR;
-      while (1)
-	{
-	  int bytes;
-
-	  if (pos == stop)
-	    {
-	      if (pos == Z)
-		break;
-	      p = GAP_END_ADDR;
-	      pend = Z_ADDR;
-	      stop = Z;
-	    }
-
-	  if (ASCII_CHAR_P (*p))
-	    p++, pos++;
-	  else if (EQ (flag, Qt)
-		   && ! CHAR_BYTE8_HEAD_P (*p)
-		   && (bytes = MULTIBYTE_LENGTH (p, pend)) > 0)
-	    p += bytes, pos += bytes;
-	  else
-	    {
-	      unsigned char tmp[MAX_MULTIBYTE_LENGTH];
-	      int c;
-
-	      c = BYTE8_TO_CHAR (*p);
-	      bytes = CHAR_STRING (c, tmp);
-	      *p = tmp[0];
-	      TEMP_SET_PT_BOTH (pos + 1, pos + 1);
-	      bytes--;
-	      insert_1_both ((char *) tmp + 1, bytes, bytes, 1, 0, 0);
-	      /* Now the gap is after the just inserted data.  */
-	      pos = GPT;
-	      p = GAP_END_ADDR;
-	      if (pos <= begv)
-		begv += bytes;
-	      if (pos <= zv)
-		zv += bytes;
-	      if (pos <= pt)
-		pt += bytes;
-	      pend = Z_ADDR;
-	      stop = Z;
-	    }
-	}
-
-      if (pt != PT)
-	TEMP_SET_PT (pt);
-
-      if (narrowed)
-	Fnarrow_to_region (make_number (begv), make_number (zv));
-
-      /* Do this first, so that chars_in_text asks the right question.
-	 set_intervals_multibyte needs it too.  */
-      bset_enable_multibyte_characters (current_buffer, Qt);
-
-      GPT_BYTE = advance_to_char_boundary (GPT_BYTE);
-      GPT = chars_in_text (BEG_ADDR, GPT_BYTE - BEG_BYTE) + BEG;
-
-      Z = chars_in_text (GAP_END_ADDR, Z_BYTE - GPT_BYTE) + GPT;
-
-      BEGV_BYTE = advance_to_char_boundary (BEGV_BYTE);
-      if (BEGV_BYTE > GPT_BYTE)
-	BEGV = chars_in_text (GAP_END_ADDR, BEGV_BYTE - GPT_BYTE) + GPT;
-      else
-	BEGV = chars_in_text (BEG_ADDR, BEGV_BYTE - BEG_BYTE) + BEG;
-
-      ZV_BYTE = advance_to_char_boundary (ZV_BYTE);
-      if (ZV_BYTE > GPT_BYTE)
-	ZV = chars_in_text (GAP_END_ADDR, ZV_BYTE - GPT_BYTE) + GPT;
-      else
-	ZV = chars_in_text (BEG_ADDR, ZV_BYTE - BEG_BYTE) + BEG;
-
-      {
-	ptrdiff_t byte = advance_to_char_boundary (PT_BYTE);
-	ptrdiff_t position;
-
-	if (byte > GPT_BYTE)
-	  position = chars_in_text (GAP_END_ADDR, byte - GPT_BYTE) + GPT;
-	else
-	  position = chars_in_text (BEG_ADDR, byte - BEG_BYTE) + BEG;
-	TEMP_SET_PT_BOTH (position, byte);
-      }
-
-      tail = markers = BUF_MARKERS (current_buffer);
-
-      /* This prevents BYTE_TO_CHAR (that is, buf_bytepos_to_charpos) from
-	 getting confused by the markers that have not yet been updated.
-	 It is also a signal that it should never create a marker.  */
-      BUF_MARKERS (current_buffer) = NULL;
-
-      for (; tail; tail = tail->next)
-	{
-	  tail->bytepos = advance_to_char_boundary (tail->bytepos);
-	  tail->charpos = BYTE_TO_CHAR (tail->bytepos);
-	}
-
-      /* Make sure no markers were put on the chain
-	 while the chain value was incorrect.  */
-      if (BUF_MARKERS (current_buffer))
-	emacs_abort ();
-
-      BUF_MARKERS (current_buffer) = markers;
-
-      /* Do this last, so it can calculate the new correspondences
-	 between chars and bytes.  */
-      set_intervals_multibyte (1);
-    }
-
-  if (!EQ (old_undo, Qt))
-    {
-      /* Represent all the above changes by a special undo entry.  */
-      bset_undo_list (current_buffer,
-		      Fcons (list3 (Qapply,
-				    intern ("set-buffer-multibyte"),
-				    NILP (flag) ? Qt : Qnil),
-			     old_undo));
-    }
-
-  current_buffer->prevent_redisplay_optimizations_p = 1;
-
-  /* If buffer is shown in a window, let redisplay consider other windows.  */
-  if (buffer_window_count (current_buffer))
-    windows_or_buffers_changed = 10;
-
-  /* Copy this buffer's new multibyte status
-     into all of its indirect buffers.  */
-  FOR_EACH_BUFFER (other)
-    if (other->base_buffer == current_buffer && BUFFER_LIVE_P (other))
-      {
-	BVAR (other, enable_multibyte_characters)
-	  = BVAR (current_buffer, enable_multibyte_characters);
-	other->prevent_redisplay_optimizations_p = 1;
-      }
-
-  /* Restore the modifiedness of the buffer.  */
-  if (!modified_p && !NILP (Fbuffer_modified_p (Qnil)))
-    Fset_buffer_modified_p (Qnil);
-
-  /* Update coding systems of this buffer's process (if any).  */
-  {
-    Lisp_Object process;
-
-    process = Fget_buffer_process (Fcurrent_buffer ());
-    if (PROCESSP (process))
-      setup_process_coding_systems (process);
-  }
-
-  return flag;
-}
-
 DEFUN ("kill-all-local-variables", Fkill_all_local_variables,
        Skill_all_local_variables, 0, 0, 0,
        doc: /* Switch to Fundamental mode by killing current buffer's local variables.
@@ -4665,6 +4377,10 @@ init_buffer_once (void)
   /* Prevent GC from getting confused.  */
   buffer_defaults.text = &buffer_defaults.own_text;
   buffer_local_symbols.text = &buffer_local_symbols.own_text;
+  buffer_defaults.own_text.pending_interval = NULL;
+  buffer_defaults.own_text.pending_offset = 0;
+  buffer_local_symbols.own_text.pending_interval = NULL;
+  buffer_local_symbols.own_text.pending_offset = 0;
   /* No one will share the text with these buffers, but let's play it safe.  */
   buffer_defaults.indirections = 0;
   buffer_local_symbols.indirections = 0;
@@ -5745,7 +5461,6 @@ Functions running this hook are, `get-buffer-create',
   defsubr (&Sbury_buffer_internal);
   defsubr (&Sset_buffer_major_mode);
   defsubr (&Sbuffer_swap_text);
-  defsubr (&Sset_buffer_multibyte);
   defsubr (&Skill_all_local_variables);
 
   defsubr (&Smake_overlay);
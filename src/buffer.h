@@ -479,6 +479,12 @@ struct buffer_text
     /* Properties of this buffer's text.  */
     INTERVAL intervals;
 
+    /* An interval of INTERVALS whose text grew by PENDING_OFFSET
+       characters that are not yet counted in the total lengths of its
+       ancestors, or NULL.  See flush_interval_offsets.  */
+    INTERVAL pending_interval;
+    ptrdiff_t pending_offset;
+
     /* The markers that refer to this buffer.
        This is actually a single marker ---
        successive elements in its marker `chain'
@@ -871,6 +877,27 @@ struct buffer
   struct region_cache *width_run_cache;
   struct region_cache *bidi_paragraph_cache;
 
+  /* The index of the words of the buffer for `buffer-word-index', or
+     NULL; see wordindex.rs.  */
+  struct word_index *word_index;
+
+  /* Statistics about the lines of the buffer, kept up to date by
+     insertions and deletions; see linestats.rs.  They are valid if
+     line_stats_modiff is equal to BUF_CHARS_MODIFF.  */
+
+  /* The number of newlines in the whole buffer.  */
+  ptrdiff_t line_stats_newlines;
+
+  /* The length of the longest line, or -1 if it is not known.  */
+  ptrdiff_t line_stats_max;
+
+  /* The value of BUF_CHARS_MODIFF the statistics are for.  */
+  EMACS_INT line_stats_modiff;
+
+  /* True if `long-line-functions' have run for this buffer since it
+     last had no lines longer than `long-line-threshold'.  */
+  bool long_lines_reported;
+
   /* Non-zero means disable redisplay optimizations when rebuilding the glyph
      matrices (but not when redrawing).  */
   bool_bf prevent_redisplay_optimizations_p : 1;
@@ -1207,12 +1234,18 @@ extern Lisp_Object Vbuffer_alist;
 #define FOR_EACH_LIVE_BUFFER(list_var, buf_var)			\
   FOR_EACH_ALIST_VALUE (Vbuffer_alist, list_var, buf_var)
 
-/* Get text properties of B.  */
+/* Defined in intervals.c.  */
+extern void flush_interval_offsets (struct buffer_text *);
+
+/* Get text properties of B.  Insertions whose lengths were not yet
+   added up the tree are accounted for first.  */
 
 INLINE INTERVAL
 buffer_intervals (struct buffer *b)
 {
   eassert (b->text != NULL);
+  if (b->text->pending_interval)
+    flush_interval_offsets (b->text);
   return b->text->intervals;
 }
 
@@ -1222,6 +1255,8 @@ INLINE void
 set_buffer_intervals (struct buffer *b, INTERVAL i)
 {
   eassert (b->text != NULL);
+  if (b->text->pending_interval)
+    flush_interval_offsets (b->text);
   b->text->intervals = i;
 }
 
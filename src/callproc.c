@@ -56,39 +56,6 @@ along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.  */
 
 #include "remacs-lib.h"
 
-/* Pattern used by call-process-region to make temp files.  */
-static Lisp_Object Vtemp_file_name_pattern;
-
-/* The next two variables are used while record-unwind-protect is in place
-   during call-process for a subprocess for which record_deleted_pid has
-   not yet been called.  At other times, synch_process_pid is zero and
-   synch_process_tempfile's contents are irrelevant.  Doing this via static
-   C variables is more convenient than putting them into the arguments
-   of record-unwind-protect, as they need to be updated at randomish
-   times in the code, and Lisp cannot always store these values as
-   Emacs integers.  It's safe to use static variables here, as the
-   code is never invoked reentrantly.  */
-
-/* If nonzero, a process-ID that has not been reaped.  */
-static pid_t synch_process_pid;
-
-/* If a string, the name of a temp file that has not been removed.  */
-#define synch_process_tempfile make_number (0)
-
-/* Indexes of file descriptors that need closing on call_process_kill.  */
-enum
-  {
-    /* The subsidiary process's stdout and stderr.  stdin is handled
-       separately, in either Fcall_process_region or create_temp_file.  */
-    CALLPROC_STDOUT, CALLPROC_STDERR,
-
-    /* How to read from a pipe (or substitute) from the subsidiary process.  */
-    CALLPROC_PIPEREAD,
-
-    /* A bound on the number of file descriptors.  */
-    CALLPROC_FDS
-  };
-
 
 /* Return the current buffer's working directory, or the home
    directory if it's unreachable, as a string suitable for a system call.
@@ -144,818 +111,6 @@ record_kill_process (struct Lisp_Process *p, Lisp_Object tempfile)
   unblock_child_signal (&oldset);
 }
 
-/* Clean up files, file descriptors and processes created by Fcall_process.  */
-
-static void
-delete_temp_file (Lisp_Object name)
-{
-  unlink (SSDATA (name));
-}
-
-static void
-call_process_kill (void *ptr)
-{
-  int *callproc_fd = ptr;
-  int i;
-  for (i = 0; i < CALLPROC_FDS; i++)
-    if (0 <= callproc_fd[i])
-      emacs_close (callproc_fd[i]);
-
-  if (synch_process_pid)
-    {
-      struct Lisp_Process proc;
-      proc.alive = 1;
-      proc.pid = synch_process_pid;
-      record_kill_process (&proc, synch_process_tempfile);
-      synch_process_pid = 0;
-    }
-  else if (STRINGP (synch_process_tempfile))
-    delete_temp_file (synch_process_tempfile);
-}
-
-/* Clean up when exiting Fcall_process: restore the buffer, and
-   kill the subsidiary process group if the process still exists.  */
-
-static void
-call_process_cleanup (Lisp_Object buffer)
-{
-  Fset_buffer (buffer);
-
-  if (synch_process_pid)
-    {
-      kill (-synch_process_pid, SIGINT);
-      message1 ("Waiting for process to die...(type C-g again to kill it instantly)");
-
-      /* This will quit on C-g.  */
-      bool wait_ok = wait_for_termination (synch_process_pid, NULL, true);
-      synch_process_pid = 0;
-      message1 (wait_ok
-		? "Waiting for process to die...done"
-		: "Waiting for process to die...internal error");
-    }
-}
-
-#ifdef DOS_NT
-static mode_t const default_output_mode = S_IREAD | S_IWRITE;
-#else
-static mode_t const default_output_mode = 0666;
-#endif
-
-/* Like Fcall_process (NARGS, ARGS), except use FILEFD as the input file.
-
-   If TEMPFILE_INDEX is nonnegative, it is the specpdl index of an
-   unwinder that is intended to remove the input temporary file; in
-   this case NARGS must be at least 2 and ARGS[1] is the file's name.
-
-   At entry, the specpdl stack top entry must be close_file_unwind (FILEFD).  */
-
-Lisp_Object
-call_process (ptrdiff_t nargs, Lisp_Object *args, int filefd,
-	      ptrdiff_t tempfile_index)
-{
-  Lisp_Object buffer, current_dir, path;
-  bool display_p;
-  int fd0;
-  int callproc_fd[CALLPROC_FDS];
-  int status;
-  ptrdiff_t i;
-  ptrdiff_t count = SPECPDL_INDEX ();
-  USE_SAFE_ALLOCA;
-
-  char **new_argv;
-  /* File to use for stderr in the child.
-     t means use same as standard output.  */
-  Lisp_Object error_file;
-  Lisp_Object output_file = Qnil;
-  sigset_t oldset;
-  pid_t pid;
-  int child_errno;
-  int fd_output, fd_error;
-  struct coding_system process_coding; /* coding-system of process output */
-  struct coding_system argument_coding;	/* coding-system of arguments */
-  /* Set to the return value of Ffind_operation_coding_system.  */
-  Lisp_Object coding_systems;
-  bool discard_output;
-
-  if (synch_process_pid)
-    error ("call-process invoked recursively");
-
-  /* Qt denotes that Ffind_operation_coding_system is not yet called.  */
-  coding_systems = Qt;
-
-  CHECK_STRING (args[0]);
-
-  error_file = Qt;
-
-  /* Decide the coding-system for giving arguments.  */
-  {
-    Lisp_Object val, *args2;
-
-    /* If arguments are supplied, we may have to encode them.  */
-    if (nargs >= 5)
-      {
-	bool must_encode = 0;
-	Lisp_Object coding_attrs;
-
-	for (i = 4; i < nargs; i++)
-	  CHECK_STRING (args[i]);
-
-	for (i = 4; i < nargs; i++)
-	  if (STRING_MULTIBYTE (args[i]))
-	    must_encode = 1;
-
-	if (!NILP (Vcoding_system_for_write))
-	  val = Vcoding_system_for_write;
-	else if (! must_encode)
-	  val = Qraw_text;
-	else
-	  {
-	    SAFE_NALLOCA (args2, 1, nargs + 1);
-	    args2[0] = Qcall_process;
-	    for (i = 0; i < nargs; i++) args2[i + 1] = args[i];
-	    coding_systems = Ffind_operation_coding_system (nargs + 1, args2);
-	    val = CONSP (coding_systems) ? XCDR (coding_systems) : Qnil;
-	  }
-	val = complement_process_encoding_system (val);
-	setup_coding_system (Fcheck_coding_system (val), &argument_coding);
-	coding_attrs = CODING_ID_ATTRS (argument_coding.id);
-	if (NILP (CODING_ATTR_ASCII_COMPAT (coding_attrs)))
-	  {
-	    /* We should not use an ASCII incompatible coding system.  */
-	    val = raw_text_coding_system (val);
-	    setup_coding_system (val, &argument_coding);
-	  }
-      }
-  }
-
-  if (nargs < 3)
-    buffer = Qnil;
-  else
-    {
-      buffer = args[2];
-
-      /* If BUFFER is a list, its meaning is (BUFFER-FOR-STDOUT
-	 FILE-FOR-STDERR), unless the first element is :file, in which case see
-	 the next paragraph. */
-      if (CONSP (buffer) && !EQ (XCAR (buffer), QCfile))
-	{
-	  if (CONSP (XCDR (buffer)))
-	    {
-	      Lisp_Object stderr_file;
-	      stderr_file = XCAR (XCDR (buffer));
-
-	      if (NILP (stderr_file) || EQ (Qt, stderr_file))
-		error_file = stderr_file;
-	      else
-		error_file = Fexpand_file_name (stderr_file, Qnil);
-	    }
-
-	  buffer = XCAR (buffer);
-	}
-
-      /* If the buffer is (still) a list, it might be a (:file "file") spec. */
-      if (CONSP (buffer) && EQ (XCAR (buffer), QCfile))
-	{
-	  output_file = Fexpand_file_name (XCAR (XCDR (buffer)),
-					   BVAR (current_buffer, directory));
-	  CHECK_STRING (output_file);
-	  buffer = Qnil;
-	}
-
-      if (! (NILP (buffer) || EQ (buffer, Qt) || INTEGERP (buffer)))
-	{
-	  Lisp_Object spec_buffer;
-	  spec_buffer = buffer;
-	  buffer = Fget_buffer_create (buffer);
-	  /* Mention the buffer name for a better error message.  */
-	  if (NILP (buffer))
-	    CHECK_BUFFER (spec_buffer);
-	  CHECK_BUFFER (buffer);
-	}
-    }
-
-  /* Make sure that the child will be able to chdir to the current
-     buffer's current directory, or its unhandled equivalent.  We
-     can't just have the child check for an error when it does the
-     chdir, since it's in a vfork.  */
-  current_dir = encode_current_directory ();
-
-  if (STRINGP (error_file))
-    error_file = ENCODE_FILE (error_file);
-  if (STRINGP (output_file))
-    output_file = ENCODE_FILE (output_file);
-
-  display_p = INTERACTIVE && nargs >= 4 && !NILP (args[3]);
-
-  for (i = 0; i < CALLPROC_FDS; i++)
-    callproc_fd[i] = -1;
-  record_unwind_protect_ptr (call_process_kill, callproc_fd);
-
-  /* Search for program; barf if not found.  */
-  {
-    int ok;
-
-    ok = openp (Vexec_path, args[0], Vexec_suffixes, &path,
-		make_number (X_OK), false);
-    if (ok < 0)
-      report_file_error ("Searching for program", args[0]);
-  }
-
-  /* Remove "/:" from PATH.  */
-  path = remove_slash_colon (path);
-
-  SAFE_NALLOCA (new_argv, 1, nargs < 4 ? 2 : nargs - 2);
-
-  if (nargs > 4)
-    {
-      ptrdiff_t i;
-
-      argument_coding.dst_multibyte = 0;
-      for (i = 4; i < nargs; i++)
-	{
-	  argument_coding.src_multibyte = STRING_MULTIBYTE (args[i]);
-	  if (CODING_REQUIRE_ENCODING (&argument_coding))
-	    /* We must encode this argument.  */
-	    args[i] = encode_coding_string (&argument_coding, args[i], 1);
-	}
-      for (i = 4; i < nargs; i++)
-	new_argv[i - 3] = SSDATA (args[i]);
-      new_argv[i - 3] = 0;
-    }
-  else
-    new_argv[1] = 0;
-  path = ENCODE_FILE (path);
-  new_argv[0] = SSDATA (path);
-
-  discard_output = INTEGERP (buffer) || (NILP (buffer) && NILP (output_file));
-
-  if (discard_output)
-    {
-      fd_output = emacs_open (NULL_DEVICE, O_WRONLY, 0);
-      if (fd_output < 0)
-	report_file_error ("Opening null device", Qnil);
-    }
-  else if (STRINGP (output_file))
-    {
-      fd_output = emacs_open (SSDATA (output_file),
-			      O_WRONLY | O_CREAT | O_TRUNC | O_TEXT,
-			      default_output_mode);
-      if (fd_output < 0)
-	{
-	  int open_errno = errno;
-	  output_file = DECODE_FILE (output_file);
-	  report_file_errno ("Opening process output file",
-			     output_file, open_errno);
-	}
-    }
-  else
-    {
-      int fd[2];
-      if (emacs_pipe (fd) != 0)
-	report_file_error ("Creating process pipe", Qnil);
-      callproc_fd[CALLPROC_PIPEREAD] = fd[0];
-      fd_output = fd[1];
-    }
-  callproc_fd[CALLPROC_STDOUT] = fd_output;
-
-  fd_error = fd_output;
-
-  if (STRINGP (error_file) || (NILP (error_file) && !discard_output))
-    {
-      fd_error = emacs_open ((STRINGP (error_file)
-			      ? SSDATA (error_file)
-			      : NULL_DEVICE),
-			     O_WRONLY | O_CREAT | O_TRUNC | O_TEXT,
-			     default_output_mode);
-      if (fd_error < 0)
-	{
-	  int open_errno = errno;
-	  report_file_errno ("Cannot redirect stderr",
-			     (STRINGP (error_file)
-			      ? DECODE_FILE (error_file)
-			      : build_string (NULL_DEVICE)),
-			     open_errno);
-	}
-      callproc_fd[CALLPROC_STDERR] = fd_error;
-    }
-
-  /* Do the unwind-protect now, even though the pid is not known, so
-     that no storage allocation is done in the critical section.
-     The actual PID will be filled in during the critical section.  */
-  record_unwind_protect (call_process_cleanup, Fcurrent_buffer ());
-
-  block_input ();
-  block_child_signal (&oldset);
-
-#ifdef WINDOWSNT
-  pid = child_setup (filefd, fd_output, fd_error, new_argv, 0, current_dir);
-#else  /* not WINDOWSNT */
-
-  /* vfork, and prevent local vars from being clobbered by the vfork.  */
-  {
-    Lisp_Object volatile buffer_volatile = buffer;
-    Lisp_Object volatile coding_systems_volatile = coding_systems;
-    Lisp_Object volatile current_dir_volatile = current_dir;
-    bool volatile display_p_volatile = display_p;
-    bool volatile sa_must_free_volatile = sa_must_free;
-    int volatile fd_error_volatile = fd_error;
-    int volatile filefd_volatile = filefd;
-    ptrdiff_t volatile count_volatile = count;
-    ptrdiff_t volatile sa_avail_volatile = sa_avail;
-    ptrdiff_t volatile sa_count_volatile = sa_count;
-    char **volatile new_argv_volatile = new_argv;
-    int volatile callproc_fd_volatile[CALLPROC_FDS];
-    for (i = 0; i < CALLPROC_FDS; i++)
-      callproc_fd_volatile[i] = callproc_fd[i];
-
-    pid = vfork ();
-
-    buffer = buffer_volatile;
-    coding_systems = coding_systems_volatile;
-    current_dir = current_dir_volatile;
-    display_p = display_p_volatile;
-    sa_must_free = sa_must_free_volatile;
-    fd_error = fd_error_volatile;
-    filefd = filefd_volatile;
-    count = count_volatile;
-    sa_avail = sa_avail_volatile;
-    sa_count = sa_count_volatile;
-    new_argv = new_argv_volatile;
-
-    for (i = 0; i < CALLPROC_FDS; i++)
-      callproc_fd[i] = callproc_fd_volatile[i];
-    fd_output = callproc_fd[CALLPROC_STDOUT];
-  }
-
-  if (pid == 0)
-    {
-#ifdef DARWIN_OS
-      /* Work around a macOS bug, where SIGCHLD is apparently
-	 delivered to a vforked child instead of to its parent.  See:
-	 https://lists.gnu.org/r/emacs-devel/2017-05/msg00342.html
-      */
-      signal (SIGCHLD, SIG_DFL);
-#endif
-
-      unblock_child_signal (&oldset);
-
-#ifdef DARWIN_OS
-      /* Darwin doesn't let us run setsid after a vfork, so use
-         TIOCNOTTY when necessary. */
-      int j = emacs_open (DEV_TTY, O_RDWR, 0);
-      if (j >= 0)
-        {
-          ioctl (j, TIOCNOTTY, 0);
-          emacs_close (j);
-        }
-#else
-      setsid ();
-#endif
-
-      /* Emacs ignores SIGPIPE, but the child should not.  */
-      signal (SIGPIPE, SIG_DFL);
-      /* Likewise for SIGPROF.  */
-#ifdef SIGPROF
-      signal (SIGPROF, SIG_DFL);
-#endif
-
-      child_setup (filefd, fd_output, fd_error, new_argv, 0, current_dir);
-    }
-
-#endif /* not WINDOWSNT */
-
-  child_errno = errno;
-
-  if (pid > 0)
-    {
-      synch_process_pid = pid;
-
-      if (INTEGERP (buffer))
-	{
-	  if (tempfile_index < 0)
-	    record_deleted_pid (pid, Qnil);
-	  else
-	    {
-	      eassert (1 < nargs);
-	      record_deleted_pid (pid, args[1]);
-	      clear_unwind_protect (tempfile_index);
-	    }
-	  synch_process_pid = 0;
-	}
-    }
-
-  unblock_child_signal (&oldset);
-  unblock_input ();
-
-  if (pid < 0)
-    report_file_errno ("Doing vfork", Qnil, child_errno);
-
-  /* Close our file descriptors, except for callproc_fd[CALLPROC_PIPEREAD]
-     since we will use that to read input from.  */
-  for (i = 0; i < CALLPROC_FDS; i++)
-    if (i != CALLPROC_PIPEREAD && 0 <= callproc_fd[i])
-      {
-	emacs_close (callproc_fd[i]);
-	callproc_fd[i] = -1;
-      }
-  emacs_close (filefd);
-  clear_unwind_protect (count - 1);
-
-  if (INTEGERP (buffer))
-    return unbind_to (count, Qnil);
-
-  if (BUFFERP (buffer))
-    Fset_buffer (buffer);
-
-  fd0 = callproc_fd[CALLPROC_PIPEREAD];
-
-  if (0 <= fd0)
-    {
-      Lisp_Object val, *args2;
-
-      val = Qnil;
-      if (!NILP (Vcoding_system_for_read))
-	val = Vcoding_system_for_read;
-      else
-	{
-	  if (EQ (coding_systems, Qt))
-	    {
-	      ptrdiff_t i;
-
-	      SAFE_NALLOCA (args2, 1, nargs + 1);
-	      args2[0] = Qcall_process;
-	      for (i = 0; i < nargs; i++) args2[i + 1] = args[i];
-	      coding_systems
-		= Ffind_operation_coding_system (nargs + 1, args2);
-	    }
-	  if (CONSP (coding_systems))
-	    val = XCAR (coding_systems);
-	  else if (CONSP (Vdefault_process_coding_system))
-	    val = XCAR (Vdefault_process_coding_system);
-	  else
-	    val = Qnil;
-	}
-      Fcheck_coding_system (val);
-      /* In unibyte mode, character code conversion should not take
-	 place but EOL conversion should.  So, setup raw-text or one
-	 of the subsidiary according to the information just setup.  */
-      if (NILP (BVAR (current_buffer, enable_multibyte_characters))
-	  && !NILP (val))
-	val = raw_text_coding_system (val);
-      setup_coding_system (val, &process_coding);
-      process_coding.dst_multibyte
-	= ! NILP (BVAR (current_buffer, enable_multibyte_characters));
-      process_coding.src_multibyte = 0;
-    }
-
-  if (0 <= fd0)
-    {
-      enum { CALLPROC_BUFFER_SIZE_MIN = 16 * 1024 };
-      enum { CALLPROC_BUFFER_SIZE_MAX = 4 * CALLPROC_BUFFER_SIZE_MIN };
-      char buf[CALLPROC_BUFFER_SIZE_MAX];
-      int bufsize = CALLPROC_BUFFER_SIZE_MIN;
-      int nread;
-      EMACS_INT total_read = 0;
-      int carryover = 0;
-      bool display_on_the_fly = display_p;
-      struct coding_system saved_coding = process_coding;
-
-      while (1)
-	{
-	  /* Repeatedly read until we've filled as much as possible
-	     of the buffer size we have.  But don't read
-	     less than 1024--save that for the next bufferful.  */
-	  nread = carryover;
-	  while (nread < bufsize - 1024)
-	    {
-	      int this_read = emacs_read_quit (fd0, buf + nread,
-					       bufsize - nread);
-
-	      if (this_read < 0)
-		goto give_up;
-
-	      if (this_read == 0)
-		{
-		  process_coding.mode |= CODING_MODE_LAST_BLOCK;
-		  break;
-		}
-
-	      nread += this_read;
-	      total_read += this_read;
-
-	      if (display_on_the_fly)
-		break;
-	    }
-
-	  /* Now NREAD is the total amount of data in the buffer.  */
-
-	  if (!nread)
-	    ;
-	  else if (NILP (BVAR (current_buffer, enable_multibyte_characters))
-		   && ! CODING_MAY_REQUIRE_DECODING (&process_coding))
-	    insert_1_both (buf, nread, nread, 0, 1, 0);
-	  else
-	    {			/* We have to decode the input.  */
-	      Lisp_Object curbuf;
-	      ptrdiff_t count1 = SPECPDL_INDEX ();
-
-	      XSETBUFFER (curbuf, current_buffer);
-	      /* FIXME: Call signal_after_change!  */
-	      prepare_to_modify_buffer (PT, PT, NULL);
-	      /* We cannot allow after-change-functions be run
-		 during decoding, because that might modify the
-		 buffer, while we rely on process_coding.produced to
-		 faithfully reflect inserted text until we
-		 TEMP_SET_PT_BOTH below.  */
-	      specbind (Qinhibit_modification_hooks, Qt);
-	      decode_coding_c_string (&process_coding,
-				      (unsigned char *) buf, nread, curbuf);
-	      unbind_to (count1, Qnil);
-	      if (display_on_the_fly
-		  && CODING_REQUIRE_DETECTION (&saved_coding)
-		  && ! CODING_REQUIRE_DETECTION (&process_coding))
-		{
-		  /* We have detected some coding system, but the
-		     detection may have been via insufficient data.
-		     So give up displaying on the fly.  */
-		  if (process_coding.produced > 0)
-		    del_range_2 (process_coding.dst_pos,
-				 process_coding.dst_pos_byte,
-				 (process_coding.dst_pos
-				  + process_coding.produced_char),
-				 (process_coding.dst_pos_byte
-				  + process_coding.produced),
-				 0);
-		  display_on_the_fly = false;
-		  process_coding = saved_coding;
-		  carryover = nread;
-		  /* Make the above condition always fail in the future.  */
-		  saved_coding.common_flags
-		    &= ~CODING_REQUIRE_DETECTION_MASK;
-		  continue;
-		}
-
-	      TEMP_SET_PT_BOTH (PT + process_coding.produced_char,
-				PT_BYTE + process_coding.produced);
-	      carryover = process_coding.carryover_bytes;
-	      if (carryover > 0)
-		memcpy (buf, process_coding.carryover,
-			process_coding.carryover_bytes);
-	    }
-
-	  if (process_coding.mode & CODING_MODE_LAST_BLOCK)
-	    break;
-
-	  /* Make the buffer bigger as we continue to read more data,
-	     but not past CALLPROC_BUFFER_SIZE_MAX.  */
-	  if (bufsize < CALLPROC_BUFFER_SIZE_MAX && total_read > 32 * bufsize)
-	    if ((bufsize *= 2) > CALLPROC_BUFFER_SIZE_MAX)
-	      bufsize = CALLPROC_BUFFER_SIZE_MAX;
-
-	  if (display_p)
-	    {
-	      redisplay_preserve_echo_area (1);
-	      /* This variable might have been set to 0 for code
-		 detection.  In that case, set it back to 1 because
-		 we should have already detected a coding system.  */
-	      display_on_the_fly = true;
-	    }
-	}
-    give_up: ;
-
-      Vlast_coding_system_used = CODING_ID_NAME (process_coding.id);
-      /* If the caller required, let the buffer inherit the
-	 coding-system used to decode the process output.  */
-      if (inherit_process_coding_system)
-	call1 (intern ("after-insert-file-set-buffer-file-coding-system"),
-	       make_number (total_read));
-    }
-
-  bool wait_ok = true;
-  /* Wait for it to terminate, unless it already has.  */
-  wait_ok = wait_for_termination (pid, &status, fd0 < 0);
-
-  /* Don't kill any children that the subprocess may have left behind
-     when exiting.  */
-  synch_process_pid = 0;
-
-  SAFE_FREE ();
-  unbind_to (count, Qnil);
-
-  if (!wait_ok)
-    return build_unibyte_string ("internal error");
-
-  if (WIFSIGNALED (status))
-    {
-      const char *signame;
-
-      synchronize_system_messages_locale ();
-      signame = strsignal (WTERMSIG (status));
-
-      if (signame == 0)
-	signame = "unknown";
-
-      return code_convert_string_norecord (build_string (signame),
-					   Vlocale_coding_system, 0);
-    }
-
-  eassert (WIFEXITED (status));
-  return make_number (WEXITSTATUS (status));
-}
-
-/* Create a temporary file suitable for storing the input data of
-   call-process-region.  NARGS and ARGS are the same as for
-   call-process-region.  Store into *FILENAME_STRING_PTR a Lisp string
-   naming the file, and return a file descriptor for reading.
-   Unwind-protect the file, so that the file descriptor will be closed
-   and the file removed when the caller unwinds the specpdl stack.  */
-
-static int
-create_temp_file (ptrdiff_t nargs, Lisp_Object *args,
-		  Lisp_Object *filename_string_ptr)
-{
-  int fd;
-  Lisp_Object filename_string;
-  Lisp_Object val, start, end;
-  Lisp_Object tmpdir;
-
-  if (STRINGP (Vtemporary_file_directory))
-    tmpdir = Vtemporary_file_directory;
-  else
-    {
-      char *outf;
-#ifndef DOS_NT
-      outf = getenv ("TMPDIR");
-      tmpdir = build_string (outf ? outf : "/tmp/");
-#else /* DOS_NT */
-      if ((outf = egetenv ("TMPDIR"))
-	  || (outf = egetenv ("TMP"))
-	  || (outf = egetenv ("TEMP")))
-	tmpdir = build_string (outf);
-      else
-	tmpdir = Ffile_name_as_directory (build_string ("c:/temp"));
-#endif
-    }
-
-  {
-    Lisp_Object pattern = Fexpand_file_name (Vtemp_file_name_pattern, tmpdir);
-    char *tempfile;
-    ptrdiff_t count;
-
-#ifdef WINDOWSNT
-    /* Cannot use the result of Fexpand_file_name, because it
-       downcases the XXXXXX part of the pattern, and mktemp then
-       doesn't recognize it.  */
-    if (!NILP (Vw32_downcase_file_names))
-      {
-	Lisp_Object dirname = Ffile_name_directory (pattern);
-
-	if (NILP (dirname))
-	  pattern = Vtemp_file_name_pattern;
-	else
-	  pattern = concat2 (dirname, Vtemp_file_name_pattern);
-      }
-#endif
-
-    filename_string = Fcopy_sequence (ENCODE_FILE (pattern));
-    tempfile = SSDATA (filename_string);
-
-    count = SPECPDL_INDEX ();
-    record_unwind_protect_nothing ();
-    fd = rust_make_temp (tempfile, O_BINARY | O_CLOEXEC);
-    if (fd < 0)
-      report_file_error ("Failed to open temporary file using pattern",
-			 pattern);
-    set_unwind_protect (count, delete_temp_file, filename_string);
-    record_unwind_protect_int (close_file_unwind, fd);
-  }
-
-  start = args[0];
-  end = args[1];
-  /* Decide coding-system of the contents of the temporary file.  */
-  if (!NILP (Vcoding_system_for_write))
-    val = Vcoding_system_for_write;
-  else if (NILP (BVAR (current_buffer, enable_multibyte_characters)))
-    val = Qraw_text;
-  else
-    {
-      Lisp_Object coding_systems;
-      Lisp_Object *args2;
-      USE_SAFE_ALLOCA;
-      SAFE_NALLOCA (args2, 1, nargs + 1);
-      args2[0] = Qcall_process_region;
-      memcpy (args2 + 1, args, nargs * sizeof *args);
-      coding_systems = Ffind_operation_coding_system (nargs + 1, args2);
-      val = CONSP (coding_systems) ? XCDR (coding_systems) : Qnil;
-      SAFE_FREE ();
-    }
-  val = complement_process_encoding_system (val);
-
-  {
-    ptrdiff_t count1 = SPECPDL_INDEX ();
-
-    specbind (intern ("coding-system-for-write"), val);
-    /* POSIX lets mk[s]temp use "."; don't invoke jka-compr if we
-       happen to get a ".Z" suffix.  */
-    specbind (Qfile_name_handler_alist, Qnil);
-    write_region (start, end, filename_string, Qnil, Qlambda, Qnil, Qnil, fd);
-
-    unbind_to (count1, Qnil);
-  }
-
-  if (lseek (fd, 0, SEEK_SET) < 0)
-    report_file_error ("Setting file position", filename_string);
-
-  /* Note that Fcall_process takes care of binding
-     coding-system-for-read.  */
-
-  *filename_string_ptr = filename_string;
-  return fd;
-}
-
-DEFUN ("call-process-region", Fcall_process_region, Scall_process_region,
-       3, MANY, 0,
-       doc: /* Send text from START to END to a synchronous process running PROGRAM.
-
-START and END are normally buffer positions specifying the part of the
-buffer to send to the process.
-If START is nil, that means to use the entire buffer contents; END is
-ignored.
-If START is a string, then send that string to the process
-instead of any buffer contents; END is ignored.
-The remaining arguments are optional.
-Delete the text if fourth arg DELETE is non-nil.
-
-Insert output in BUFFER before point; t means current buffer; nil for
- BUFFER means discard it; 0 means discard and don't wait; and `(:file
- FILE)', where FILE is a file name string, means that it should be
- written to that file (if the file already exists it is overwritten).
-BUFFER can also have the form (REAL-BUFFER STDERR-FILE); in that case,
-REAL-BUFFER says what to do with standard output, as above,
-while STDERR-FILE says what to do with standard error in the child.
-STDERR-FILE may be nil (discard standard error output),
-t (mix it with ordinary output), or a file name string.
-
-Sixth arg DISPLAY non-nil means redisplay buffer as output is inserted.
-Remaining args are passed to PROGRAM at startup as command args.
-
-If BUFFER is 0, `call-process-region' returns immediately with value nil.
-Otherwise it waits for PROGRAM to terminate
-and returns a numeric exit status or a signal description string.
-If you quit, the process is killed with SIGINT, or SIGKILL if you quit again.
-
-usage: (call-process-region START END PROGRAM &optional DELETE BUFFER DISPLAY &rest ARGS)  */)
-  (ptrdiff_t nargs, Lisp_Object *args)
-{
-  Lisp_Object infile, val;
-  ptrdiff_t count = SPECPDL_INDEX ();
-  Lisp_Object start = args[0];
-  Lisp_Object end = args[1];
-  bool empty_input;
-  int fd;
-
-  if (STRINGP (start))
-    empty_input = SCHARS (start) == 0;
-  else if (NILP (start))
-    empty_input = BEG == Z;
-  else
-    {
-      validate_region (&args[0], &args[1]);
-      start = args[0];
-      end = args[1];
-      empty_input = XINT (start) == XINT (end);
-    }
-
-  if (!empty_input)
-    fd = create_temp_file (nargs, args, &infile);
-  else
-    {
-      infile = Qnil;
-      fd = emacs_open (NULL_DEVICE, O_RDONLY, 0);
-      if (fd < 0)
-	report_file_error ("Opening null device", Qnil);
-      record_unwind_protect_int (close_file_unwind, fd);
-    }
-
-  if (nargs > 3 && !NILP (args[3]))
-    Fdelete_region (start, end);
-
-  if (nargs > 3)
-    {
-      args += 2;
-      nargs -= 2;
-    }
-  else
-    {
-      args[0] = args[2];
-      nargs = 2;
-    }
-  args[1] = infile;
-
-  val = call_process (nargs, args, fd, empty_input ? -1 : count);
-  return unbind_to (count, val);
-}
-
 static char **
 add_env (char **env, char **new_env, char *string)
 {
@@ -1200,130 +355,6 @@ child_setup (int in, int out, int err, char **new_argv, bool set_pgrp,
 #endif  /* not WINDOWSNT */
 }
 
-static bool
-getenv_internal_1 (const char *var, ptrdiff_t varlen, char **value,
-		   ptrdiff_t *valuelen, Lisp_Object env)
-{
-  for (; CONSP (env); env = XCDR (env))
-    {
-      Lisp_Object entry = XCAR (env);
-      if (STRINGP (entry)
-	  && SBYTES (entry) >= varlen
-#ifdef WINDOWSNT
-	  /* NT environment variables are case insensitive.  */
-	  && ! strnicmp (SSDATA (entry), var, varlen)
-#else  /* not WINDOWSNT */
-	  && ! memcmp (SDATA (entry), var, varlen)
-#endif /* not WINDOWSNT */
-	  )
-	{
-	  if (SBYTES (entry) > varlen && SREF (entry, varlen) == '=')
-	    {
-	      *value = SSDATA (entry) + (varlen + 1);
-	      *valuelen = SBYTES (entry) - (varlen + 1);
-	      return 1;
-	    }
-	  else if (SBYTES (entry) == varlen)
-	    {
-	      /* Lone variable names in Vprocess_environment mean that
-		 variable should be removed from the environment. */
-	      *value = NULL;
-	      return 1;
-	    }
-	}
-    }
-  return 0;
-}
-
-static bool
-getenv_internal (const char *var, ptrdiff_t varlen, char **value,
-		 ptrdiff_t *valuelen, Lisp_Object frame)
-{
-  /* Try to find VAR in Vprocess_environment first.  */
-  if (getenv_internal_1 (var, varlen, value, valuelen,
-			 Vprocess_environment))
-    return *value ? 1 : 0;
-
-  /* On Windows we make some modifications to Emacs' environment
-     without recording them in Vprocess_environment.  */
-#ifdef WINDOWSNT
-  {
-    char *tmpval = getenv (var);
-    if (tmpval)
-      {
-        *value = tmpval;
-        *valuelen = strlen (tmpval);
-        return 1;
-      }
-  }
-#endif
-
-  /* For DISPLAY try to get the values from the frame or the initial env.  */
-  if (strcmp (var, "DISPLAY") == 0)
-    {
-      Lisp_Object display
-	= Fframe_parameter (NILP (frame) ? selected_frame : frame, Qdisplay);
-      if (STRINGP (display))
-	{
-	  *value    = SSDATA (display);
-	  *valuelen = SBYTES (display);
-	  return 1;
-	}
-      /* If still not found, Look for DISPLAY in Vinitial_environment.  */
-      if (getenv_internal_1 (var, varlen, value, valuelen,
-			     Vinitial_environment))
-	return *value ? 1 : 0;
-    }
-
-  return 0;
-}
-
-DEFUN ("getenv-internal", Fgetenv_internal, Sgetenv_internal, 1, 2, 0,
-       doc: /* Get the value of environment variable VARIABLE.
-VARIABLE should be a string.  Value is nil if VARIABLE is undefined in
-the environment.  Otherwise, value is a string.
-
-This function searches `process-environment' for VARIABLE.
-
-If optional parameter ENV is a list, then search this list instead of
-`process-environment', and return t when encountering a negative entry
-\(an entry for a variable with no value).  */)
-  (Lisp_Object variable, Lisp_Object env)
-{
-  char *value;
-  ptrdiff_t valuelen;
-
-  CHECK_STRING (variable);
-  if (CONSP (env))
-    {
-      if (getenv_internal_1 (SSDATA (variable), SBYTES (variable),
-			     &value, &valuelen, env))
-	return value ? make_string (value, valuelen) : Qt;
-      else
-	return Qnil;
-    }
-  else if (getenv_internal (SSDATA (variable), SBYTES (variable),
-			    &value, &valuelen, env))
-    return make_string (value, valuelen);
-  else
-    return Qnil;
-}
-
-/* A version of getenv that consults the Lisp environment lists,
-   easily callable from C.  This is usually called from egetenv.  */
-char *
-egetenv_internal (const char *var, ptrdiff_t len)
-{
-  char *value;
-  ptrdiff_t valuelen;
-
-  if (getenv_internal (var, len, &value, &valuelen, Qnil))
-    return value;
-  else
-    return 0;
-}
-
-
 /* This is run before init_cmdargs.  */
 
 void
@@ -1459,28 +490,9 @@ init_callproc (void)
   Vshared_game_score_directory = gamedir;
 }
 
-void
-set_initial_environment (void)
-{
-  char **envp;
-  for (envp = environ; *envp; envp++)
-    Vprocess_environment = Fcons (build_string (*envp),
-				  Vprocess_environment);
-  /* Ideally, the `copy' shouldn't be necessary, but it seems it's frequent
-     to use `delete' and friends on process-environment.  */
-  Vinitial_environment = Fcopy_sequence (Vprocess_environment);
-}
-
 void
 syms_of_callproc (void)
 {
-#ifndef DOS_NT
-  Vtemp_file_name_pattern = build_string ("emacsXXXXXX");
-#else  /* DOS_NT */
-  Vtemp_file_name_pattern = build_string ("emXXXXXX");
-#endif
-  staticpro (&Vtemp_file_name_pattern);
-
   DEFVAR_LISP ("shell-file-name", Vshell_file_name,
 	       doc: /* File name to load inferior shells from.
 Initialized from the SHELL environment variable, or to a system-dependent
@@ -1551,7 +563,4 @@ use.
 
 See `setenv' and `getenv'.  */);
   Vprocess_environment = Qnil;
-
-  defsubr (&Sgetenv_internal);
-  defsubr (&Scall_process_region);
 }
@@ -25,7 +25,6 @@ along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.  */
 
 #include <config.h>
 
-#include <stdio.h>
 #include <limits.h>
 
 #include "lisp.h"
@@ -41,11 +40,7 @@ along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.  */
    CCL_PROG is already resolved to index numbers or not, UPDATEDP (t
    or nil) is the flat to tell if the CCL program is updated after it
    was once used.  */
-static Lisp_Object Vccl_program_table;
-
-/* Return a hash table of id number ID.  */
-#define GET_HASH_TABLE(id) \
-  (XHASH_TABLE (XCDR (AREF (Vtranslation_hash_table_vector, (id)))))
+Lisp_Object Vccl_program_table;
 
 /* CCL (Code Conversion Language) is a simple language which has
    operations on one input buffer, one output buffer, and 7 registers.
@@ -575,65 +570,6 @@ static Lisp_Object Vccl_program_table;
 					 N:SEPARATOR_z (< 0)
 				      */
 
-#define MAX_MAP_SET_LEVEL 30
-
-typedef struct
-{
-  int rest_length;
-  int orig_val;
-} tr_stack;
-
-static tr_stack mapping_stack[MAX_MAP_SET_LEVEL];
-static tr_stack *mapping_stack_pointer;
-
-/* If this variable is non-zero, it indicates the stack_idx
-   of immediately called by CCL_MapMultiple. */
-static int stack_idx_of_map_multiple;
-
-#define PUSH_MAPPING_STACK(restlen, orig)		\
-do							\
-  {							\
-    mapping_stack_pointer->rest_length = (restlen);	\
-    mapping_stack_pointer->orig_val = (orig);		\
-    mapping_stack_pointer++;				\
-  }							\
-while (0)
-
-#define POP_MAPPING_STACK(restlen, orig)		\
-do							\
-  {							\
-    mapping_stack_pointer--;				\
-    (restlen) = mapping_stack_pointer->rest_length;	\
-    (orig) = mapping_stack_pointer->orig_val;		\
-  }							\
-while (0)
-
-#define CCL_CALL_FOR_MAP_INSTRUCTION(symbol, ret_ic)		\
-do								\
-  {								\
-    struct ccl_program called_ccl;				\
-    if (stack_idx >= 256					\
-	|| ! setup_ccl_program (&called_ccl, (symbol)))		\
-      {								\
-	if (stack_idx > 0)					\
-	  {							\
-	    ccl_prog = ccl_prog_stack_struct[0].ccl_prog;	\
-	    ic = ccl_prog_stack_struct[0].ic;			\
-	    eof_ic = ccl_prog_stack_struct[0].eof_ic;		\
-	  }							\
-	CCL_INVALID_CMD;					\
-      }								\
-    ccl_prog_stack_struct[stack_idx].ccl_prog = ccl_prog;	\
-    ccl_prog_stack_struct[stack_idx].ic = (ret_ic);		\
-    ccl_prog_stack_struct[stack_idx].eof_ic = eof_ic;		\
-    stack_idx++;						\
-    ccl_prog = called_ccl.prog;					\
-    ic = CCL_HEADER_MAIN;					\
-    eof_ic = XFASTINT (ccl_prog[CCL_HEADER_EOF]);		\
-    goto ccl_repeat;						\
-  }								\
-while (0)
-
 #define CCL_MapSingle		0x12 /* Map by single code conversion map
 					1:ExtendedCOMMNDXXXRRRrrrXXXXX
 					2:MAP-ID
@@ -683,1110 +619,10 @@ while (0)
 #define CCL_ENCODE_SJIS 0x17	/* X = HIGHER_BYTE (SJIS (Y, Z))
 				   r[7] = LOWER_BYTE (SJIS (Y, Z) */
 
-/* Terminate CCL program successfully.  */
-#define CCL_SUCCESS			\
-do					\
-  {					\
-    ccl->status = CCL_STAT_SUCCESS;	\
-    goto ccl_finish;			\
-  }					\
-while (0)
-
-/* Suspend CCL program because of reading from empty input buffer or
-   writing to full output buffer.  When this program is resumed, the
-   same I/O command is executed.  */
-#define CCL_SUSPEND(stat)	\
-do				\
-  {				\
-    ic--;			\
-    ccl->status = stat;		\
-    goto ccl_finish;		\
-  }				\
-while (0)
-
-/* Terminate CCL program because of invalid command.  Should not occur
-   in the normal case.  */
-#ifndef CCL_DEBUG
-
-#define CCL_INVALID_CMD		     	\
-do					\
-  {				     	\
-    ccl->status = CCL_STAT_INVALID_CMD;	\
-    goto ccl_error_handler;	     	\
-  }					\
-while (0)
-
-#else
-
-#define CCL_INVALID_CMD		     	\
-do					\
-  {				     	\
-    ccl_debug_hook (this_ic);		\
-    ccl->status = CCL_STAT_INVALID_CMD;	\
-    goto ccl_error_handler;	     	\
-  }					\
-while (0)
-
-#endif
-
 /* Use "&" rather than "&&" to suppress a bogus GCC warning; see
    <https://gcc.gnu.org/bugzilla/show_bug.cgi?id=43772>.  */
 #define ASCENDING_ORDER(lo, med, hi) (((lo) <= (med)) & ((med) <= (hi)))
 
-#define GET_CCL_RANGE(var, ccl_prog, ic, lo, hi)		\
-  do								\
-    {								\
-      EMACS_INT prog_word = XINT ((ccl_prog)[ic]);		\
-      if (! ASCENDING_ORDER (lo, prog_word, hi))		\
-	CCL_INVALID_CMD;					\
-      (var) = prog_word;					\
-    }								\
-  while (0)
-
-#define GET_CCL_CODE(code, ccl_prog, ic)			\
-  GET_CCL_RANGE (code, ccl_prog, ic, CCL_CODE_MIN, CCL_CODE_MAX)
-
-#define IN_INT_RANGE(val) ASCENDING_ORDER (INT_MIN, val, INT_MAX)
-
-/* Encode one character CH to multibyte form and write to the current
-   output buffer.  If CH is less than 256, CH is written as is.  */
-#define CCL_WRITE_CHAR(ch)			\
-  do {						\
-    if (! dst)					\
-      CCL_INVALID_CMD;				\
-    else if (dst < dst_end)			\
-      *dst++ = (ch);				\
-    else					\
-      CCL_SUSPEND (CCL_STAT_SUSPEND_BY_DST);	\
-  } while (0)
-
-/* Write a string at ccl_prog[IC] of length LEN to the current output
-   buffer.  */
-#define CCL_WRITE_STRING(len)					\
-  do {								\
-    int ccli;							\
-    if (!dst)							\
-      CCL_INVALID_CMD;						\
-    else if (dst + len <= dst_end)				\
-      {								\
-	if (XFASTINT (ccl_prog[ic]) & 0x1000000)		\
-	  for (ccli = 0; ccli < len; ccli++)			\
-	    *dst++ = XFASTINT (ccl_prog[ic + ccli]) & 0xFFFFFF;	\
-	else							\
-	  for (ccli = 0; ccli < len; ccli++)			\
-	    *dst++ = ((XFASTINT (ccl_prog[ic + (ccli / 3)]))	\
-		      >> ((2 - (ccli % 3)) * 8)) & 0xFF;	\
-      }								\
-    else							\
-      CCL_SUSPEND (CCL_STAT_SUSPEND_BY_DST);			\
-  } while (0)
-
-/* Read one byte from the current input buffer into Rth register.  */
-#define CCL_READ_CHAR(r)			\
-  do {						\
-    if (! src)					\
-      CCL_INVALID_CMD;				\
-    else if (src < src_end)			\
-      r = *src++;				\
-    else if (ccl->last_block)			\
-      {						\
-	r = -1;					\
-	ic = ccl->eof_ic;			\
-	goto ccl_repeat;			\
-      }						\
-    else					\
-      CCL_SUSPEND (CCL_STAT_SUSPEND_BY_SRC);	\
-    } while (0)
-
-/* Decode CODE by a charset whose id is ID.  If ID is 0, return CODE
-   as is for backward compatibility.  Assume that we can use the
-   variable `charset'.  */
-
-#define CCL_DECODE_CHAR(id, code)	\
-  ((id) == 0 ? (code)			\
-   : (charset = CHARSET_FROM_ID ((id)), DECODE_CHAR (charset, (code))))
-
-/* Encode character C by some of charsets in CHARSET_LIST.  Set ID to
-   the id of the used charset, ENCODED to the result of encoding.
-   Assume that we can use the variable `charset'.  */
-
-#define CCL_ENCODE_CHAR(c, charset_list, id, encoded)		\
-  do {								\
-    unsigned ncode;						\
-								\
-    charset = char_charset ((c), (charset_list), &ncode);	\
-    if (! charset && ! NILP (charset_list))			\
-      charset = char_charset ((c), Qnil, &ncode);	  	\
-    if (charset)						\
-      {								\
-	(id) = CHARSET_ID (charset);				\
-	(encoded) = ncode;					\
-      }								\
-   } while (0)
-
-/* Execute CCL code on characters at SOURCE (length SRC_SIZE).  The
-   resulting text goes to a place pointed by DESTINATION, the length
-   of which should not exceed DST_SIZE.  As a side effect, how many
-   characters are consumed and produced are recorded in CCL->consumed
-   and CCL->produced, and the contents of CCL registers are updated.
-   If SOURCE or DESTINATION is NULL, only operations on registers are
-   permitted.  */
-
-#ifdef CCL_DEBUG
-#define CCL_DEBUG_BACKTRACE_LEN 256
-int ccl_backtrace_table[CCL_DEBUG_BACKTRACE_LEN];
-int ccl_backtrace_idx;
-
-int
-ccl_debug_hook (int ic)
-{
-  return ic;
-}
-
-#endif
-
-struct ccl_prog_stack
-  {
-    Lisp_Object *ccl_prog;	/* Pointer to an array of CCL code.  */
-    int ic;			/* Instruction Counter.  */
-    int eof_ic;			/* Instruction Counter to jump on EOF.  */
-  };
-
-/* For the moment, we only support depth 256 of stack.  */
-static struct ccl_prog_stack ccl_prog_stack_struct[256];
-
-void
-ccl_driver (struct ccl_program *ccl, int *source, int *destination, int src_size, int dst_size, Lisp_Object charset_list)
-{
-  register int *reg = ccl->reg;
-  register int ic = ccl->ic;
-  register int code = 0, field1, field2;
-  register Lisp_Object *ccl_prog = ccl->prog;
-  int *src = source, *src_end = src + src_size;
-  int *dst = destination, *dst_end = dst + dst_size;
-  int jump_address;
-  int i = 0, j, op;
-  int stack_idx = ccl->stack_idx;
-  /* Instruction counter of the current CCL code. */
-  int this_ic = 0;
-  struct charset *charset;
-  int eof_ic = ccl->eof_ic;
-  int eof_hit = 0;
-
-  if (ccl->buf_magnification == 0) /* We can't read/produce any bytes.  */
-    dst = NULL;
-
-  /* Set mapping stack pointer. */
-  mapping_stack_pointer = mapping_stack;
-
-#ifdef CCL_DEBUG
-  ccl_backtrace_idx = 0;
-#endif
-
-  for (;;)
-    {
-    ccl_repeat:
-#ifdef CCL_DEBUG
-      ccl_backtrace_table[ccl_backtrace_idx++] = ic;
-      if (ccl_backtrace_idx >= CCL_DEBUG_BACKTRACE_LEN)
-	ccl_backtrace_idx = 0;
-      ccl_backtrace_table[ccl_backtrace_idx] = 0;
-#endif
-
-      if (!NILP (Vquit_flag) && NILP (Vinhibit_quit))
-	{
-	  /* We can't just signal Qquit, instead break the loop as if
-             the whole data is processed.  Don't reset Vquit_flag, it
-             must be handled later at a safer place.  */
-	  if (src)
-	    src = source + src_size;
-	  ccl->status = CCL_STAT_QUIT;
-	  break;
-	}
-
-      this_ic = ic;
-      GET_CCL_CODE (code, ccl_prog, ic++);
-      field1 = code >> 8;
-      field2 = (code & 0xFF) >> 5;
-
-#define rrr field2
-#define RRR (field1 & 7)
-#define Rrr ((field1 >> 3) & 7)
-#define ADDR field1
-#define EXCMD (field1 >> 6)
-
-      switch (code & 0x1F)
-	{
-	case CCL_SetRegister:	/* 00000000000000000RRRrrrXXXXX */
-	  reg[rrr] = reg[RRR];
-	  break;
-
-	case CCL_SetShortConst:	/* CCCCCCCCCCCCCCCCCCCCrrrXXXXX */
-	  reg[rrr] = field1;
-	  break;
-
-	case CCL_SetConst:	/* 00000000000000000000rrrXXXXX */
-	  reg[rrr] = XINT (ccl_prog[ic++]);
-	  break;
-
-	case CCL_SetArray:	/* CCCCCCCCCCCCCCCCCCCCRRRrrrXXXXX */
-	  i = reg[RRR];
-	  j = field1 >> 3;
-	  if (0 <= i && i < j)
-	    reg[rrr] = XINT (ccl_prog[ic + i]);
-	  ic += j;
-	  break;
-
-	case CCL_Jump:		/* A--D--D--R--E--S--S-000XXXXX */
-	  ic += ADDR;
-	  break;
-
-	case CCL_JumpCond:	/* A--D--D--R--E--S--S-rrrXXXXX */
-	  if (!reg[rrr])
-	    ic += ADDR;
-	  break;
-
-	case CCL_WriteRegisterJump: /* A--D--D--R--E--S--S-rrrXXXXX */
-	  i = reg[rrr];
-	  CCL_WRITE_CHAR (i);
-	  ic += ADDR;
-	  break;
-
-	case CCL_WriteRegisterReadJump: /* A--D--D--R--E--S--S-rrrXXXXX */
-	  i = reg[rrr];
-	  CCL_WRITE_CHAR (i);
-	  ic++;
-	  CCL_READ_CHAR (reg[rrr]);
-	  ic += ADDR - 1;
-	  break;
-
-	case CCL_WriteConstJump: /* A--D--D--R--E--S--S-000XXXXX */
-	  i = XINT (ccl_prog[ic]);
-	  CCL_WRITE_CHAR (i);
-	  ic += ADDR;
-	  break;
-
-	case CCL_WriteConstReadJump: /* A--D--D--R--E--S--S-rrrXXXXX */
-	  i = XINT (ccl_prog[ic]);
-	  CCL_WRITE_CHAR (i);
-	  ic++;
-	  CCL_READ_CHAR (reg[rrr]);
-	  ic += ADDR - 1;
-	  break;
-
-	case CCL_WriteStringJump: /* A--D--D--R--E--S--S-000XXXXX */
-	  j = XINT (ccl_prog[ic++]);
-	  CCL_WRITE_STRING (j);
-	  ic += ADDR - 1;
-	  break;
-
-	case CCL_WriteArrayReadJump: /* A--D--D--R--E--S--S-rrrXXXXX */
-	  i = reg[rrr];
-	  j = XINT (ccl_prog[ic]);
-	  if (0 <= i && i < j)
-	    {
-	      i = XINT (ccl_prog[ic + 1 + i]);
-	      CCL_WRITE_CHAR (i);
-	    }
-	  ic += j + 2;
-	  CCL_READ_CHAR (reg[rrr]);
-	  ic += ADDR - (j + 2);
-	  break;
-
-	case CCL_ReadJump:	/* A--D--D--R--E--S--S-rrrYYYYY */
-	  CCL_READ_CHAR (reg[rrr]);
-	  ic += ADDR;
-	  break;
-
-	case CCL_ReadBranch:	/* CCCCCCCCCCCCCCCCCCCCrrrXXXXX */
-	  CCL_READ_CHAR (reg[rrr]);
-	  FALLTHROUGH;
-	case CCL_Branch:	/* CCCCCCCCCCCCCCCCCCCCrrrXXXXX */
-	{
-	  int ioff = 0 <= reg[rrr] && reg[rrr] < field1 ? reg[rrr] : field1;
-	  int incr = XINT (ccl_prog[ic + ioff]);
-	  ic += incr;
-	}
-	  break;
-
-	case CCL_ReadRegister:	/* CCCCCCCCCCCCCCCCCCCCrrXXXXX */
-	  while (1)
-	    {
-	      CCL_READ_CHAR (reg[rrr]);
-	      if (!field1) break;
-	      GET_CCL_CODE (code, ccl_prog, ic++);
-	      field1 = code >> 8;
-	      field2 = (code & 0xFF) >> 5;
-	    }
-	  break;
-
-	case CCL_WriteExprConst:  /* 1:00000OPERATION000RRR000XXXXX */
-	  rrr = 7;
-	  i = reg[RRR];
-	  j = XINT (ccl_prog[ic]);
-	  op = field1 >> 6;
-	  jump_address = ic + 1;
-	  goto ccl_set_expr;
-
-	case CCL_WriteRegister:	/* CCCCCCCCCCCCCCCCCCCrrrXXXXX */
-	  while (1)
-	    {
-	      i = reg[rrr];
-	      CCL_WRITE_CHAR (i);
-	      if (!field1) break;
-	      GET_CCL_CODE (code, ccl_prog, ic++);
-	      field1 = code >> 8;
-	      field2 = (code & 0xFF) >> 5;
-	    }
-	  break;
-
-	case CCL_WriteExprRegister: /* 1:00000OPERATIONRrrRRR000XXXXX */
-	  rrr = 7;
-	  i = reg[RRR];
-	  j = reg[Rrr];
-	  op = field1 >> 6;
-	  jump_address = ic;
-	  goto ccl_set_expr;
-
-	case CCL_Call:		/* 1:CCCCCCCCCCCCCCCCCCCCFFFXXXXX */
-	  {
-	    Lisp_Object slot;
-	    int prog_id;
-
-	    /* If FFF is nonzero, the CCL program ID is in the
-               following code.  */
-	    if (rrr)
-	      prog_id = XINT (ccl_prog[ic++]);
-	    else
-	      prog_id = field1;
-
-	    if (stack_idx >= 256
-		|| prog_id < 0
-		|| prog_id >= ASIZE (Vccl_program_table)
-		|| (slot = AREF (Vccl_program_table, prog_id), !VECTORP (slot))
-		|| !VECTORP (AREF (slot, 1)))
-	      {
-		if (stack_idx > 0)
-		  {
-		    ccl_prog = ccl_prog_stack_struct[0].ccl_prog;
-		    ic = ccl_prog_stack_struct[0].ic;
-		    eof_ic = ccl_prog_stack_struct[0].eof_ic;
-		  }
-		CCL_INVALID_CMD;
-	      }
-
-	    ccl_prog_stack_struct[stack_idx].ccl_prog = ccl_prog;
-	    ccl_prog_stack_struct[stack_idx].ic = ic;
-	    ccl_prog_stack_struct[stack_idx].eof_ic = eof_ic;
-	    stack_idx++;
-	    ccl_prog = XVECTOR (AREF (slot, 1))->contents;
-	    ic = CCL_HEADER_MAIN;
-	    eof_ic = XFASTINT (ccl_prog[CCL_HEADER_EOF]);
-	  }
-	  break;
-
-	case CCL_WriteConstString: /* CCCCCCCCCCCCCCCCCCCCrrrXXXXX */
-	  if (!rrr)
-	    CCL_WRITE_CHAR (field1);
-	  else
-	    {
-	      CCL_WRITE_STRING (field1);
-	      ic += (field1 + 2) / 3;
-	    }
-	  break;
-
-	case CCL_WriteArray:	/* CCCCCCCCCCCCCCCCCCCCrrrXXXXX */
-	  i = reg[rrr];
-	  if (0 <= i && i < field1)
-	    {
-	      j = XINT (ccl_prog[ic + i]);
-	      CCL_WRITE_CHAR (j);
-	    }
-	  ic += field1;
-	  break;
-
-	case CCL_End:		/* 0000000000000000000000XXXXX */
-	  if (stack_idx > 0)
-	    {
-	      stack_idx--;
-	      ccl_prog = ccl_prog_stack_struct[stack_idx].ccl_prog;
-	      ic = ccl_prog_stack_struct[stack_idx].ic;
-	      eof_ic = ccl_prog_stack_struct[stack_idx].eof_ic;
-	      if (eof_hit)
-		ic = eof_ic;
-	      break;
-	    }
-	  if (src)
-	    src = src_end;
-	  /* ccl->ic should points to this command code again to
-             suppress further processing.  */
-	  ic--;
-	  CCL_SUCCESS;
-
-	case CCL_ExprSelfConst: /* 00000OPERATION000000rrrXXXXX */
-	  i = XINT (ccl_prog[ic++]);
-	  op = field1 >> 6;
-	  goto ccl_expr_self;
-
-	case CCL_ExprSelfReg:	/* 00000OPERATION000RRRrrrXXXXX */
-	  i = reg[RRR];
-	  op = field1 >> 6;
-
-	ccl_expr_self:
-	  switch (op)
-	    {
-	    case CCL_PLUS: reg[rrr] += i; break;
-	    case CCL_MINUS: reg[rrr] -= i; break;
-	    case CCL_MUL: reg[rrr] *= i; break;
-	    case CCL_DIV: reg[rrr] /= i; break;
-	    case CCL_MOD: reg[rrr] %= i; break;
-	    case CCL_AND: reg[rrr] &= i; break;
-	    case CCL_OR: reg[rrr] |= i; break;
-	    case CCL_XOR: reg[rrr] ^= i; break;
-	    case CCL_LSH: reg[rrr] <<= i; break;
-	    case CCL_RSH: reg[rrr] >>= i; break;
-	    case CCL_LSH8: reg[rrr] <<= 8; reg[rrr] |= i; break;
-	    case CCL_RSH8: reg[7] = reg[rrr] & 0xFF; reg[rrr] >>= 8; break;
-	    case CCL_DIVMOD: reg[7] = reg[rrr] % i; reg[rrr] /= i; break;
-	    case CCL_LS: reg[rrr] = reg[rrr] < i; break;
-	    case CCL_GT: reg[rrr] = reg[rrr] > i; break;
-	    case CCL_EQ: reg[rrr] = reg[rrr] == i; break;
-	    case CCL_LE: reg[rrr] = reg[rrr] <= i; break;
-	    case CCL_GE: reg[rrr] = reg[rrr] >= i; break;
-	    case CCL_NE: reg[rrr] = reg[rrr] != i; break;
-	    default: CCL_INVALID_CMD;
-	    }
-	  break;
-
-	case CCL_SetExprConst:	/* 00000OPERATION000RRRrrrXXXXX */
-	  i = reg[RRR];
-	  j = XINT (ccl_prog[ic++]);
-	  op = field1 >> 6;
-	  jump_address = ic;
-	  goto ccl_set_expr;
-
-	case CCL_SetExprReg:	/* 00000OPERATIONRrrRRRrrrXXXXX */
-	  i = reg[RRR];
-	  j = reg[Rrr];
-	  op = field1 >> 6;
-	  jump_address = ic;
-	  goto ccl_set_expr;
-
-	case CCL_ReadJumpCondExprConst: /* A--D--D--R--E--S--S-rrrXXXXX */
-	  CCL_READ_CHAR (reg[rrr]);
-	  FALLTHROUGH;
-	case CCL_JumpCondExprConst: /* A--D--D--R--E--S--S-rrrXXXXX */
-	  i = reg[rrr];
-	  jump_address = ic + ADDR;
-	  op = XINT (ccl_prog[ic++]);
-	  j = XINT (ccl_prog[ic++]);
-	  rrr = 7;
-	  goto ccl_set_expr;
-
-	case CCL_ReadJumpCondExprReg: /* A--D--D--R--E--S--S-rrrXXXXX */
-	  CCL_READ_CHAR (reg[rrr]);
-	  FALLTHROUGH;
-	case CCL_JumpCondExprReg:
-	  i = reg[rrr];
-	  jump_address = ic + ADDR;
-	  op = XINT (ccl_prog[ic++]);
-	  GET_CCL_RANGE (j, ccl_prog, ic++, 0, 7);
-	  j = reg[j];
-	  rrr = 7;
-
-	ccl_set_expr:
-	  switch (op)
-	    {
-	    case CCL_PLUS: reg[rrr] = i + j; break;
-	    case CCL_MINUS: reg[rrr] = i - j; break;
-	    case CCL_MUL: reg[rrr] = i * j; break;
-	    case CCL_DIV: reg[rrr] = i / j; break;
-	    case CCL_MOD: reg[rrr] = i % j; break;
-	    case CCL_AND: reg[rrr] = i & j; break;
-	    case CCL_OR: reg[rrr] = i | j; break;
-	    case CCL_XOR: reg[rrr] = i ^ j; break;
-	    case CCL_LSH: reg[rrr] = i << j; break;
-	    case CCL_RSH: reg[rrr] = i >> j; break;
-	    case CCL_LSH8: reg[rrr] = (i << 8) | j; break;
-	    case CCL_RSH8: reg[rrr] = i >> 8; reg[7] = i & 0xFF; break;
-	    case CCL_DIVMOD: reg[rrr] = i / j; reg[7] = i % j; break;
-	    case CCL_LS: reg[rrr] = i < j; break;
-	    case CCL_GT: reg[rrr] = i > j; break;
-	    case CCL_EQ: reg[rrr] = i == j; break;
-	    case CCL_LE: reg[rrr] = i <= j; break;
-	    case CCL_GE: reg[rrr] = i >= j; break;
-	    case CCL_NE: reg[rrr] = i != j; break;
-	    case CCL_DECODE_SJIS:
-	      {
-		i = (i << 8) | j;
-		SJIS_TO_JIS (i);
-		reg[rrr] = i >> 8;
-		reg[7] = i & 0xFF;
-		break;
-	      }
-	    case CCL_ENCODE_SJIS:
-	      {
-		i = (i << 8) | j;
-		JIS_TO_SJIS (i);
-		reg[rrr] = i >> 8;
-		reg[7] = i & 0xFF;
-		break;
-	      }
-	    default: CCL_INVALID_CMD;
-	    }
-	  code &= 0x1F;
-	  if (code == CCL_WriteExprConst || code == CCL_WriteExprRegister)
-	    {
-	      i = reg[rrr];
-	      CCL_WRITE_CHAR (i);
-	      ic = jump_address;
-	    }
-	  else if (!reg[rrr])
-	    ic = jump_address;
-	  break;
-
-	case CCL_Extension:
-	  switch (EXCMD)
-	    {
-	    case CCL_ReadMultibyteChar2:
-	      if (!src)
-		CCL_INVALID_CMD;
-	      CCL_READ_CHAR (i);
-	      CCL_ENCODE_CHAR (i, charset_list, reg[RRR], reg[rrr]);
-	      break;
-
-	    case CCL_WriteMultibyteChar2:
-	      if (! dst)
-		CCL_INVALID_CMD;
-	      i = CCL_DECODE_CHAR (reg[RRR], reg[rrr]);
-	      CCL_WRITE_CHAR (i);
-	      break;
-
-	    case CCL_TranslateCharacter:
-	      i = CCL_DECODE_CHAR (reg[RRR], reg[rrr]);
-	      op = translate_char (GET_TRANSLATION_TABLE (reg[Rrr]), i);
-	      CCL_ENCODE_CHAR (op, charset_list, reg[RRR], reg[rrr]);
-	      break;
-
-	    case CCL_TranslateCharacterConstTbl:
-	      {
-		ptrdiff_t eop;
-		GET_CCL_RANGE (eop, ccl_prog, ic++, 0,
-			       (VECTORP (Vtranslation_table_vector)
-				? ASIZE (Vtranslation_table_vector)
-				: -1));
-		i = CCL_DECODE_CHAR (reg[RRR], reg[rrr]);
-		op = translate_char (GET_TRANSLATION_TABLE (eop), i);
-		CCL_ENCODE_CHAR (op, charset_list, reg[RRR], reg[rrr]);
-	      }
-	      break;
-
-	    case CCL_LookupIntConstTbl:
-	      {
-		ptrdiff_t eop;
-		struct Lisp_Hash_Table *h;
-		GET_CCL_RANGE (eop, ccl_prog, ic++, 0,
-			       (VECTORP (Vtranslation_hash_table_vector)
-				? ASIZE (Vtranslation_hash_table_vector)
-				: -1));
-		h = GET_HASH_TABLE (eop);
-
-		eop = hash_lookup (h, make_number (reg[RRR]), NULL);
-		if (eop >= 0)
-		  {
-		    Lisp_Object opl;
-		    opl = HASH_VALUE (h, eop);
-		    if (! (IN_INT_RANGE (eop) && CHARACTERP (opl)))
-		      CCL_INVALID_CMD;
-		    reg[RRR] = charset_unicode;
-		    reg[rrr] = eop;
-		    reg[7] = 1; /* r7 true for success */
-		  }
-		else
-		  reg[7] = 0;
-	      }
-	      break;
-
-	    case CCL_LookupCharConstTbl:
-	      {
-		ptrdiff_t eop;
-		struct Lisp_Hash_Table *h;
-		GET_CCL_RANGE (eop, ccl_prog, ic++, 0,
-			       (VECTORP (Vtranslation_hash_table_vector)
-				? ASIZE (Vtranslation_hash_table_vector)
-				: -1));
-		i = CCL_DECODE_CHAR (reg[RRR], reg[rrr]);
-		h = GET_HASH_TABLE (eop);
-
-		eop = hash_lookup (h, make_number (i), NULL);
-		if (eop >= 0)
-		  {
-		    Lisp_Object opl;
-		    opl = HASH_VALUE (h, eop);
-		    if (! (INTEGERP (opl) && IN_INT_RANGE (XINT (opl))))
-		      CCL_INVALID_CMD;
-		    reg[RRR] = XINT (opl);
-		    reg[7] = 1; /* r7 true for success */
-		  }
-		else
-		  reg[7] = 0;
-	      }
-	      break;
-
-	    case CCL_IterateMultipleMap:
-	      {
-		Lisp_Object map, content, attrib, value;
-		EMACS_INT point;
-		ptrdiff_t size;
-		int fin_ic;
-
-		j = XINT (ccl_prog[ic++]); /* number of maps. */
-		fin_ic = ic + j;
-		op = reg[rrr];
-		if ((j > reg[RRR]) && (j >= 0))
-		  {
-		    ic += reg[RRR];
-		    i = reg[RRR];
-		  }
-		else
-		  {
-		    reg[RRR] = -1;
-		    ic = fin_ic;
-		    break;
-		  }
-
-		for (;i < j;i++)
-		  {
-		    if (!VECTORP (Vcode_conversion_map_vector)) continue;
-		    size = ASIZE (Vcode_conversion_map_vector);
-		    point = XINT (ccl_prog[ic++]);
-		    if (! (0 <= point && point < size)) continue;
-		    map = AREF (Vcode_conversion_map_vector, point);
-
-		    /* Check map validity.  */
-		    if (!CONSP (map)) continue;
-		    map = XCDR (map);
-		    if (!VECTORP (map)) continue;
-		    size = ASIZE (map);
-		    if (size <= 1) continue;
-
-		    content = AREF (map, 0);
-
-		    /* check map type,
-		       [STARTPOINT VAL1 VAL2 ...] or
-		       [t ELEMENT STARTPOINT ENDPOINT]  */
-		    if (INTEGERP (content))
-		      {
-			point = XINT (content);
-			if (!(point <= op && op - point + 1 < size)) continue;
-			content = AREF (map, op - point + 1);
-		      }
-		    else if (EQ (content, Qt))
-		      {
-			if (size != 4) continue;
-			if (INTEGERP (AREF (map, 2))
-			    && XINT (AREF (map, 2)) <= op
-			    && INTEGERP (AREF (map, 3))
-			    && op < XINT (AREF (map, 3)))
-			  content = AREF (map, 1);
-			else
-			  continue;
-		      }
-		    else
-		      continue;
-
-		    if (NILP (content))
-		      continue;
-		    else if (INTEGERP (content) && IN_INT_RANGE (XINT (content)))
-		      {
-			reg[RRR] = i;
-			reg[rrr] = XINT (content);
-			break;
-		      }
-		    else if (EQ (content, Qt) || EQ (content, Qlambda))
-		      {
-			reg[RRR] = i;
-			break;
-		      }
-		    else if (CONSP (content))
-		      {
-			attrib = XCAR (content);
-			value = XCDR (content);
-			if (! (INTEGERP (attrib) && INTEGERP (value)
-			       && IN_INT_RANGE (XINT (value))))
-			  continue;
-			reg[RRR] = i;
-			reg[rrr] = XINT (value);
-			break;
-		      }
-		    else if (SYMBOLP (content))
-		      CCL_CALL_FOR_MAP_INSTRUCTION (content, fin_ic);
-		    else
-		      CCL_INVALID_CMD;
-		  }
-		if (i == j)
-		  reg[RRR] = -1;
-		ic = fin_ic;
-	      }
-	      break;
-
-	    case CCL_MapMultiple:
-	      {
-		Lisp_Object map, content, attrib, value;
-		EMACS_INT point;
-		ptrdiff_t size, map_vector_size;
-		int map_set_rest_length, fin_ic;
-		int current_ic = this_ic;
-
-		/* inhibit recursive call on MapMultiple. */
-		if (stack_idx_of_map_multiple > 0)
-		  {
-		    if (stack_idx_of_map_multiple <= stack_idx)
-		      {
-			stack_idx_of_map_multiple = 0;
-			mapping_stack_pointer = mapping_stack;
-			CCL_INVALID_CMD;
-		      }
-		  }
-		else
-		  mapping_stack_pointer = mapping_stack;
-		stack_idx_of_map_multiple = 0;
-
-		/* Get number of maps and separators.  */
-		map_set_rest_length = XINT (ccl_prog[ic++]);
-
-		fin_ic = ic + map_set_rest_length;
-		op = reg[rrr];
-
-		if ((map_set_rest_length > reg[RRR]) && (reg[RRR] >= 0))
-		  {
-		    ic += reg[RRR];
-		    i = reg[RRR];
-		    map_set_rest_length -= i;
-		  }
-		else
-		  {
-		    ic = fin_ic;
-		    reg[RRR] = -1;
-		    mapping_stack_pointer = mapping_stack;
-		    break;
-		  }
-
-		if (mapping_stack_pointer <= (mapping_stack + 1))
-		  {
-		    /* Set up initial state. */
-		    mapping_stack_pointer = mapping_stack;
-		    PUSH_MAPPING_STACK (0, op);
-		    reg[RRR] = -1;
-		  }
-		else
-		  {
-		    /* Recover after calling other ccl program. */
-		    int orig_op;
-
-		    POP_MAPPING_STACK (map_set_rest_length, orig_op);
-		    POP_MAPPING_STACK (map_set_rest_length, reg[rrr]);
-		    switch (op)
-		      {
-		      case -1:
-			/* Regard it as Qnil. */
-			op = orig_op;
-			i++;
-			ic++;
-			map_set_rest_length--;
-			break;
-		      case -2:
-			/* Regard it as Qt. */
-			op = reg[rrr];
-			i++;
-			ic++;
-			map_set_rest_length--;
-			break;
-		      case -3:
-			/* Regard it as Qlambda. */
-			op = orig_op;
-			i += map_set_rest_length;
-			ic += map_set_rest_length;
-			map_set_rest_length = 0;
-			break;
-		      default:
-			/* Regard it as normal mapping. */
-			i += map_set_rest_length;
-			ic += map_set_rest_length;
-			POP_MAPPING_STACK (map_set_rest_length, reg[rrr]);
-			break;
-		      }
-		  }
-		if (!VECTORP (Vcode_conversion_map_vector))
-		  CCL_INVALID_CMD;
-		map_vector_size = ASIZE (Vcode_conversion_map_vector);
-
-		do {
-		  for (;map_set_rest_length > 0;i++, ic++, map_set_rest_length--)
-		    {
-		      point = XINT (ccl_prog[ic]);
-		      if (point < 0)
-			{
-			  /* +1 is for including separator. */
-			  point = -point + 1;
-			  if (mapping_stack_pointer
-			      >= &mapping_stack[MAX_MAP_SET_LEVEL])
-			    CCL_INVALID_CMD;
-			  PUSH_MAPPING_STACK (map_set_rest_length - point,
-					      reg[rrr]);
-			  map_set_rest_length = point;
-			  reg[rrr] = op;
-			  continue;
-			}
-
-		      if (point >= map_vector_size) continue;
-		      map = AREF (Vcode_conversion_map_vector, point);
-
-		      /* Check map validity.  */
-		      if (!CONSP (map)) continue;
-		      map = XCDR (map);
-		      if (!VECTORP (map)) continue;
-		      size = ASIZE (map);
-		      if (size <= 1) continue;
-
-		      content = AREF (map, 0);
-
-		      /* check map type,
-			 [STARTPOINT VAL1 VAL2 ...] or
-			 [t ELEMENT STARTPOINT ENDPOINT]  */
-		      if (INTEGERP (content))
-			{
-			  point = XINT (content);
-			  if (!(point <= op && op - point + 1 < size)) continue;
-			  content = AREF (map, op - point + 1);
-			}
-		      else if (EQ (content, Qt))
-			{
-			  if (size != 4) continue;
-			  if (INTEGERP (AREF (map, 2))
-			      && XINT (AREF (map, 2)) <= op
-			      && INTEGERP (AREF (map, 3))
-			      && op < XINT (AREF (map, 3)))
-			    content = AREF (map, 1);
-			  else
-			    continue;
-			}
-		      else
-			continue;
-
-		      if (NILP (content))
-			continue;
-
-		      reg[RRR] = i;
-		      if (INTEGERP (content) && IN_INT_RANGE (XINT (content)))
-			{
-			  op = XINT (content);
-			  i += map_set_rest_length - 1;
-			  ic += map_set_rest_length - 1;
-			  POP_MAPPING_STACK (map_set_rest_length, reg[rrr]);
-			  map_set_rest_length++;
-			}
-		      else if (CONSP (content))
-			{
-			  attrib = XCAR (content);
-			  value = XCDR (content);
-			  if (! (INTEGERP (attrib) && INTEGERP (value)
-				 && IN_INT_RANGE (XINT (value))))
-			    continue;
-			  op = XINT (value);
-			  i += map_set_rest_length - 1;
-			  ic += map_set_rest_length - 1;
-			  POP_MAPPING_STACK (map_set_rest_length, reg[rrr]);
-			  map_set_rest_length++;
-			}
-		      else if (EQ (content, Qt))
-			{
-			  op = reg[rrr];
-			}
-		      else if (EQ (content, Qlambda))
-			{
-			  i += map_set_rest_length;
-			  ic += map_set_rest_length;
-			  break;
-			}
-		      else if (SYMBOLP (content))
-			{
-			  if (mapping_stack_pointer
-			      >= &mapping_stack[MAX_MAP_SET_LEVEL])
-			    CCL_INVALID_CMD;
-			  PUSH_MAPPING_STACK (map_set_rest_length, reg[rrr]);
-			  PUSH_MAPPING_STACK (map_set_rest_length, op);
-			  stack_idx_of_map_multiple = stack_idx + 1;
-			  CCL_CALL_FOR_MAP_INSTRUCTION (content, current_ic);
-			}
-		      else
-			CCL_INVALID_CMD;
-		    }
-		  if (mapping_stack_pointer <= (mapping_stack + 1))
-		    break;
-		  POP_MAPPING_STACK (map_set_rest_length, reg[rrr]);
-		  i += map_set_rest_length;
-		  ic += map_set_rest_length;
-		  POP_MAPPING_STACK (map_set_rest_length, reg[rrr]);
-		} while (1);
-
-		ic = fin_ic;
-	      }
-	      reg[rrr] = op;
-	      break;
-
-	    case CCL_MapSingle:
-	      {
-		Lisp_Object map, attrib, value, content;
-		int point;
-		j = XINT (ccl_prog[ic++]); /* map_id */
-		op = reg[rrr];
-		if (! (VECTORP (Vcode_conversion_map_vector)
-		       && j < ASIZE (Vcode_conversion_map_vector)))
-		  {
-		    reg[RRR] = -1;
-		    break;
-		  }
-		map = AREF (Vcode_conversion_map_vector, j);
-		if (!CONSP (map))
-		  {
-		    reg[RRR] = -1;
-		    break;
-		  }
-		map = XCDR (map);
-		if (! (VECTORP (map)
-		       && 0 < ASIZE (map)
-		       && INTEGERP (AREF (map, 0))
-		       && XINT (AREF (map, 0)) <= op
-		       && op - XINT (AREF (map, 0)) + 1 < ASIZE (map)))
-		  {
-		    reg[RRR] = -1;
-		    break;
-		  }
-		point = op - XINT (AREF (map, 0)) + 1;
-		reg[RRR] = 0;
-		content = AREF (map, point);
-		if (NILP (content))
-		  reg[RRR] = -1;
-		else if (TYPE_RANGED_INTEGERP (int, content))
-		  reg[rrr] = XINT (content);
-		else if (EQ (content, Qt));
-		else if (CONSP (content))
-		  {
-		    attrib = XCAR (content);
-		    value = XCDR (content);
-		    if (!INTEGERP (attrib)
-			|| !TYPE_RANGED_INTEGERP (int, value))
-		      continue;
-		    reg[rrr] = XINT (value);
-		    break;
-		  }
-		else if (SYMBOLP (content))
-		  CCL_CALL_FOR_MAP_INSTRUCTION (content, ic);
-		else
-		  reg[RRR] = -1;
-	      }
-	      break;
-
-	    default:
-	      CCL_INVALID_CMD;
-	    }
-	  break;
-
-	default:
-	  CCL_INVALID_CMD;
-	}
-    }
-
- ccl_error_handler:
-  if (destination)
-    {
-      /* We can insert an error message only if DESTINATION is
-         specified and we still have a room to store the message
-         there.  */
-      char msg[256];
-      int msglen;
-
-      if (!dst)
-	dst = destination;
-
-      switch (ccl->status)
-	{
-	case CCL_STAT_INVALID_CMD:
-	  msglen = sprintf (msg,
-			    "\nCCL: Invalid command %x (ccl_code = %x) at %d.",
-			    code & 0x1Fu, code + 0u, this_ic);
-#ifdef CCL_DEBUG
-	  {
-	    int i = ccl_backtrace_idx - 1;
-	    int j;
-
-	    if (dst + msglen <= (dst_bytes ? dst_end : src))
-	      {
-		memcpy (dst, msg, msglen);
-		dst += msglen;
-	      }
-
-	    for (j = 0; j < CCL_DEBUG_BACKTRACE_LEN; j++, i--)
-	      {
-		if (i < 0) i = CCL_DEBUG_BACKTRACE_LEN - 1;
-		if (ccl_backtrace_table[i] == 0)
-		  break;
-		msglen = sprintf (msg, " %d", ccl_backtrace_table[i]);
-		if (dst + msglen > (dst_bytes ? dst_end : src))
-		  break;
-		memcpy (dst, msg, msglen);
-		dst += msglen;
-	      }
-	    goto ccl_finish;
-	  }
-#endif
-	  break;
-
-	case CCL_STAT_QUIT:
-	  msglen = ccl->quit_silently ? 0 : sprintf (msg, "\nCCL: Quitted.");
-	  break;
-
-	default:
-	  msglen = sprintf (msg, "\nCCL: Unknown error type (%d)", ccl->status);
-	}
-
-      if (msglen <= dst_end - dst)
-	{
-	  for (i = 0; i < msglen; i++)
-	    *dst++ = msg[i];
-	}
-
-      if (ccl->status == CCL_STAT_INVALID_CMD)
-	{
-#if 0 /* If the remaining bytes contain 0x80..0x9F, copying them
-	 results in an invalid multibyte sequence.  */
-
-	  /* Copy the remaining source data.  */
-	  int i = src_end - src;
-	  if (dst_bytes && (dst_end - dst) < i)
-	    i = dst_end - dst;
-	  memcpy (dst, src, i);
-	  src += i;
-	  dst += i;
-#else
-	  /* Signal that we've consumed everything.  */
-	  src = src_end;
-#endif
-	}
-    }
-
- ccl_finish:
-  ccl->ic = ic;
-  ccl->stack_idx = stack_idx;
-  ccl->prog = ccl_prog;
-  ccl->consumed = src - source;
-  if (dst != NULL)
-    ccl->produced = dst - destination;
-  else
-    ccl->produced = 0;
-}
-
 /* Resolve symbols in the specified CCL code (Lisp vector).  This
    function converts symbols of code conversion maps and character
    translation tables embedded in the CCL code into their ID numbers.
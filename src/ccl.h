@@ -76,6 +76,9 @@ struct ccl_spec {
    execution of ccl program CCL_PROG (symbol or vector).  */
 extern bool setup_ccl_program (struct ccl_program *, Lisp_Object);
 
+extern Lisp_Object Vccl_program_table;
+
+/* Defined in ccl.rs.  */
 extern void ccl_driver (struct ccl_program *, int *, int *, int, int,
                         Lisp_Object);
 
@@ -203,16 +203,7 @@ static struct
    text and a string data may be relocated.  */
 bool charset_map_loaded;
 
-struct charset_map_entries
-{
-  struct {
-    unsigned from, to;
-    int c;
-  } entry[0x10000];
-  struct charset_map_entries *next;
-};
-
-/* Load the mapping information of CHARSET from ENTRIES for
+/* Load the mapping information of CHARSET from the N_ENTRIES ENTRIES for
    initializing (CONTROL_FLAG == 0), decoding (CONTROL_FLAG == 1), and
    encoding (CONTROL_FLAG == 2).
 
@@ -238,7 +229,9 @@ struct charset_map_entries
 */
 
 static void
-load_charset_map (struct charset *charset, struct charset_map_entries *entries, int n_entries, int control_flag)
+load_charset_map (struct charset *charset,
+		  const struct charset_map_entry *entries, int n_entries,
+		  int control_flag)
 {
   Lisp_Object vec UNINIT;
   Lisp_Object table UNINIT;
@@ -302,20 +295,17 @@ load_charset_map (struct charset *charset, struct charset_map_entries *entries,
       charset_map_loaded = 1;
     }
 
-  min_char = max_char = entries->entry[0].c;
+  min_char = max_char = entries[0].c;
   nonascii_min_char = MAX_CHAR;
   for (i = 0; i < n_entries; i++)
     {
       unsigned from, to;
       int from_index, to_index, lim_index;
       int from_c, to_c;
-      int idx = i % 0x10000;
 
-      if (i > 0 && idx == 0)
-	entries = entries->next;
-      from = entries->entry[idx].from;
-      to = entries->entry[idx].to;
-      from_c = entries->entry[idx].c;
+      from = entries[i].from;
+      to = entries[i].to;
+      from_c = entries[i].c;
       from_index = CODE_POINT_TO_INDEX (charset, from);
       if (from == to)
 	{
@@ -406,66 +396,16 @@ load_charset_map (struct charset *charset, struct charset_map_entries *entries,
 }
 
 
-/* Read a hexadecimal number (preceded by "0x") from the file FP while
-   paying attention to comment character '#'.  LOOKAHEAD is the
-   lookahead byte if it is nonnegative.  Store into *TERMINATOR the
-   input byte after the number, or EOF if an end-of-file or input
-   error occurred.  Set *OVERFLOW if the number overflows.  */
-
-static unsigned
-read_hex (FILE *fp, int lookahead, int *terminator, bool *overflow)
-{
-  int c = lookahead < 0 ? getc_unlocked (fp) : lookahead;
-
-  while (true)
-    {
-      if (c == '#')
-	do
-	  c = getc_unlocked (fp);
-	while (0 <= c && c != '\n');
-      else if (c == '0')
-	{
-	  c = getc_unlocked (fp);
-	  if (c < 0 || c == 'x')
-	    break;
-	}
-      if (c < 0)
-	break;
-      c = getc_unlocked (fp);
-    }
-
-  unsigned n = 0;
-  bool v = false;
-
-  if (0 <= c)
-    while (true)
-      {
-	c = getc_unlocked (fp);
-	int digit = char_hexdigit (c);
-	if (digit < 0)
-	  break;
-	v |= INT_LEFT_SHIFT_OVERFLOW (n, 4);
-	n = (n << 4) + digit;
-      }
-
-  *terminator = c;
-  *overflow |= v;
-  return n;
-}
-
-/* Return a mapping vector for CHARSET loaded from MAPFILE.
+/* Load the mapping information of CHARSET from MAPFILE.
    Each line of MAPFILE has this form
 	0xAAAA 0xCCCC
    where 0xAAAA is a code-point and 0xCCCC is the corresponding
    character code, or this form
 	0xAAAA-0xBBBB 0xCCCC
    where 0xAAAA and 0xBBBB are code-points specifying a range, and
-   0xCCCC is the first character code of the range.
-
-   The returned vector has this form:
-	[ CODE1 CHAR1 CODE2 CHAR2 .... ]
-   where CODE1 is a code-point or a cons of code-points specifying a
-   range.
+   0xCCCC is the first character code of the range.  The file is
+   parsed by `read_charset_map', which keeps the entries of the files
+   it has read.
 
    Note that this function uses `openp' to open MAPFILE but ignores
    `file-name-handler-alist' to avoid running any Lisp code.  */
@@ -477,78 +417,23 @@ load_charset_map_from_file (struct charset *charset, Lisp_Object mapfile,
   unsigned min_code = CHARSET_MIN_CODE (charset);
   unsigned max_code = CHARSET_MAX_CODE (charset);
   int fd;
-  FILE *fp;
-  struct charset_map_entries *head, *entries;
+  Lisp_Object found;
+  const struct charset_map_entry *entries;
   int n_entries;
   AUTO_STRING (map, ".map");
   AUTO_STRING (txt, ".txt");
   AUTO_LIST2 (suffixes, map, txt);
   ptrdiff_t count = SPECPDL_INDEX ();
-  record_unwind_protect_nothing ();
   specbind (Qfile_name_handler_alist, Qnil);
-  fd = openp (Vcharset_map_path, mapfile, suffixes, NULL, Qnil, false);
-  fp = fd < 0 ? 0 : fdopen (fd, "r");
-  if (!fp)
-    {
-      int open_errno = errno;
-      emacs_close (fd);
-      report_file_errno ("Loading charset map", mapfile, open_errno);
-    }
-  set_unwind_protect_ptr (count, fclose_unwind, fp);
-  unbind_to (count + 1, Qnil);
-
-  /* Use record_xmalloc, as `charset_map_entries' is
-     large (larger than MAX_ALLOCA).  */
-  head = record_xmalloc (sizeof *head);
-  entries = head;
-  memset (entries, 0, sizeof (struct charset_map_entries));
-
-  n_entries = 0;
-  int ch = -1;
-  while (true)
-    {
-      bool overflow = false;
-      unsigned from = read_hex (fp, ch, &ch, &overflow), to;
-      if (ch < 0)
-	break;
-      if (ch == '-')
-	{
-	  to = read_hex (fp, -1, &ch, &overflow);
-	  if (ch < 0)
-	    break;
-	}
-      else
-	{
-	  to = from;
-	  ch = -1;
-	}
-      unsigned c = read_hex (fp, ch, &ch, &overflow);
-      if (ch < 0)
-	break;
+  fd = openp (Vcharset_map_path, mapfile, suffixes, &found, Qnil, false);
+  if (fd < 0)
+    report_file_errno ("Loading charset map", mapfile, errno);
+  record_unwind_protect_int (close_file_unwind, fd);
 
-      if (overflow)
-	continue;
-      if (from < min_code || to > max_code || from > to || c > MAX_CHAR)
-	continue;
-
-      if (n_entries == 0x10000)
-	{
-	  entries->next = record_xmalloc (sizeof *entries->next);
-	  entries = entries->next;
-	  memset (entries, 0, sizeof (struct charset_map_entries));
-	  n_entries = 0;
-	}
-      int idx = n_entries;
-      entries->entry[idx].from = from;
-      entries->entry[idx].to = to;
-      entries->entry[idx].c = c;
-      n_entries++;
-    }
-  fclose (fp);
-  clear_unwind_protect (count);
-
-  load_charset_map (charset, head, n_entries, control_flag);
+  entries = read_charset_map (found, fd, min_code, max_code, &n_entries);
   unbind_to (count, Qnil);
+
+  load_charset_map (charset, entries, n_entries, control_flag);
 }
 
 static void
@@ -556,7 +441,7 @@ load_charset_map_from_vector (struct charset *charset, Lisp_Object vec, int cont
 {
   unsigned min_code = CHARSET_MIN_CODE (charset);
   unsigned max_code = CHARSET_MAX_CODE (charset);
-  struct charset_map_entries *head, *entries;
+  struct charset_map_entry *entries;
   int n_entries;
   int len = ASIZE (vec);
   int i;
@@ -568,11 +453,7 @@ load_charset_map_from_vector (struct charset *charset, Lisp_Object vec, int cont
       return;
     }
 
-  /* Use SAFE_ALLOCA instead of alloca, as `charset_map_entries' is
-     large (larger than MAX_ALLOCA).  */
-  head = SAFE_ALLOCA (sizeof *head);
-  entries = head;
-  memset (entries, 0, sizeof (struct charset_map_entries));
+  SAFE_NALLOCA (entries, 1, len / 2);
 
   n_entries = 0;
   for (i = 0; i < len; i += 2)
@@ -580,7 +461,6 @@ load_charset_map_from_vector (struct charset *charset, Lisp_Object vec, int cont
       Lisp_Object val, val2;
       unsigned from, to;
       EMACS_INT c;
-      int idx;
 
       val = AREF (vec, i);
       if (CONSP (val))
@@ -599,20 +479,13 @@ load_charset_map_from_vector (struct charset *charset, Lisp_Object vec, int cont
       if (from < min_code || to > max_code || from > to || c > MAX_CHAR)
 	continue;
 
-      if (n_entries > 0 && (n_entries % 0x10000) == 0)
-	{
-	  entries->next = SAFE_ALLOCA (sizeof *entries->next);
-	  entries = entries->next;
-	  memset (entries, 0, sizeof (struct charset_map_entries));
-	}
-      idx = n_entries % 0x10000;
-      entries->entry[idx].from = from;
-      entries->entry[idx].to = to;
-      entries->entry[idx].c = c;
+      entries[n_entries].from = from;
+      entries[n_entries].to = to;
+      entries[n_entries].c = c;
       n_entries++;
     }
 
-  load_charset_map (charset, head, n_entries, control_flag);
+  load_charset_map (charset, entries, n_entries, control_flag);
   SAFE_FREE ();
 }
 
@@ -2105,7 +1978,8 @@ DEFUN ("clear-charset-maps", Fclear_charset_maps, Sclear_charset_maps,
        0, 0, 0,
        doc: /*
 Internal use only.
-Clear temporary charset mapping tables.
+Clear temporary charset mapping tables, and the contents of the charset
+map files that have been read.
 It should be called only from temacs invoked for dumping.  */)
   (void)
 {
@@ -2114,6 +1988,7 @@ It should be called only from temacs invoked for dumping.  */)
       xfree (temp_charset_work);
       temp_charset_work = NULL;
     }
+  clear_charset_map_cache ();
 
   if (CHAR_TABLE_P (Vchar_unify_table))
     Foptimize_char_table (Vchar_unify_table, Qnil);
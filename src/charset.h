@@ -540,6 +540,20 @@ extern void map_charset_chars (void (*) (Lisp_Object, Lisp_Object),
                                Lisp_Object, Lisp_Object,
                                struct charset *, unsigned, unsigned);
 
+/* An entry of a charset map: the code-points FROM to TO map to the
+   characters starting with C.  */
+struct charset_map_entry
+{
+  unsigned from, to;
+  int c;
+};
+
+/* Defined in charset.rs.  */
+extern const struct charset_map_entry *read_charset_map (Lisp_Object, int,
+                                                         unsigned, unsigned,
+                                                         int *);
+extern void clear_charset_map_cache (void);
+
 INLINE_HEADER_END
 
 #endif /* EMACS_CHARSET_H */
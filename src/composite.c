@@ -694,6 +694,7 @@ Clear composition cache.  */)
 {
   Lisp_Object args[] = {QCtest, Qequal, QCsize, make_number (311)};
   gstring_hash_table = CALLMANY (Fmake_hash_table, args);
+  autocmp_clear_failures ();
   /* Fixme: We call Fclear_face_cache to force complete re-building of
      display glyphs.  But, it may be better to call this function from
      Fclear_face_cache instead.  */
@@ -921,12 +922,21 @@ autocmp_chars (Lisp_Object rule, ptrdiff_t charpos, ptrdiff_t bytepos,
 				       string);
   if (NILP (LGSTRING_ID (lgstring)))
     {
+      Lisp_Object func = AREF (rule, 2);
+      /* LGSTRING may be reused by the time lisp returns.  */
+      Lisp_Object header = Fcopy_sequence (LGSTRING_HEADER (lgstring));
+
+      /* Don't call out to lisp again for text it failed to compose.  */
+      if (autocmp_failed_p (func, header))
+	return unbind_to (count, Qnil);
       /* Save point as marker before calling out to lisp.  */
       if (NILP (string))
 	record_unwind_protect (restore_point_unwind,
 			       build_marker (current_buffer, pt, pt_byte));
-      lgstring = safe_call (6, Vauto_composition_function, AREF (rule, 2),
+      lgstring = safe_call (6, Vauto_composition_function, func,
 			    pos, make_number (to), font_object, string);
+      if (! composition_gstring_p (lgstring))
+	autocmp_note_failure (func, header);
     }
   return unbind_to (count, lgstring);
 }
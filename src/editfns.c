@@ -1295,85 +1295,6 @@ former.  */)
   return Qnil;
 }
 
-/* A buffer holding a string of the form "TZ=value", intended
-   to be part of the environment.  If TZ is supposed to be unset,
-   the buffer string is "tZ=".  */
- static char *tzvalbuf;
-
-/* Get the local time zone rule.  */
-char *
-emacs_getenv_TZ (void)
-{
-  return tzvalbuf[0] == 'T' ? tzvalbuf + tzeqlen : 0;
-}
-
-/* Set the local time zone rule to TZSTRING, which can be null to
-   denote wall clock time.  Do not record the setting in LOCAL_TZ.
-
-   This function is not thread-safe, in theory because putenv is not,
-   but mostly because of the static storage it updates.  Other threads
-   that invoke localtime etc. may be adversely affected while this
-   function is executing.  */
-
-int
-emacs_setenv_TZ (const char *tzstring)
-{
-  static ptrdiff_t tzvalbufsize;
-  ptrdiff_t tzstringlen = tzstring ? strlen (tzstring) : 0;
-  char *tzval = tzvalbuf;
-  bool new_tzvalbuf = tzvalbufsize <= tzeqlen + tzstringlen;
-
-  if (new_tzvalbuf)
-    {
-      /* Do not attempt to free the old tzvalbuf, since another thread
-	 may be using it.  In practice, the first allocation is large
-	 enough and memory does not leak.  */
-      tzval = xpalloc (NULL, &tzvalbufsize,
-		       tzeqlen + tzstringlen - tzvalbufsize + 1, -1, 1);
-      tzvalbuf = tzval;
-      tzval[1] = 'Z';
-      tzval[2] = '=';
-    }
-
-  if (tzstring)
-    {
-      /* Modify TZVAL in place.  Although this is dicey in a
-	 multithreaded environment, we know of no portable alternative.
-	 Calling putenv or setenv could crash some other thread.  */
-      tzval[0] = 'T';
-      strcpy (tzval + tzeqlen, tzstring);
-    }
-  else
-    {
-      /* Turn 'TZ=whatever' into an empty environment variable 'tZ='.
-	 Although this is also dicey, calling unsetenv here can crash Emacs.
-	 See Bug#8705.  */
-      tzval[0] = 't';
-      tzval[tzeqlen] = 0;
-    }
-
-
-#ifndef WINDOWSNT
-  /* Modifying *TZVAL merely requires calling tzset (which is the
-     caller's responsibility).  However, modifying TZVAL requires
-     calling putenv; although this is not thread-safe, in practice this
-     runs only on startup when there is only one thread.  */
-  bool need_putenv = new_tzvalbuf;
-#else
-  /* MS-Windows 'putenv' copies the argument string into a block it
-     allocates, so modifying *TZVAL will not change the environment.
-     However, the other threads run by Emacs on MS-Windows never call
-     'xputenv' or 'putenv' or 'unsetenv', so the original cause for the
-     dicey in-place modification technique doesn't exist there in the
-     first place.  */
-  bool need_putenv = true;
-#endif
-  if (need_putenv)
-    xputenv (tzval);
-
-  return 0;
-}
-
 /* Insert NARGS Lisp objects in the array ARGS by calling INSERT_FUNC
    (if a type of object is Lisp_Int) or INSERT_FROM_STRING_FUNC (if a
    type of object is Lisp_String).  INHERIT is passed to
@@ -1787,206 +1708,6 @@ determines whether case is significant or ignored.  */)
   return make_number (0);
 }
 
-
-/* Set up necessary definitions for diffseq.h; see comments in
-   diffseq.h for explanation.  */
-
-#undef ELEMENT
-#undef EQUAL
-
-#define XVECREF_YVECREF_EQUAL(ctx, xoff, yoff)  \
-  buffer_chars_equal ((ctx), (xoff), (yoff))
-
-#define OFFSET ptrdiff_t
-
-#define EXTRA_CONTEXT_FIELDS                    \
-  /* Buffers to compare.  */                    \
-  struct buffer *buffer_a;                      \
-  struct buffer *buffer_b;                      \
-  /* Bit vectors recording for each character whether it was deleted
-     or inserted.  */                           \
-  unsigned char *deletions;                     \
-  unsigned char *insertions;
-
-#define NOTE_DELETE(ctx, xoff) set_bit ((ctx)->deletions, (xoff))
-#define NOTE_INSERT(ctx, yoff) set_bit ((ctx)->insertions, (yoff))
-
-struct context;
-static void set_bit (unsigned char *, OFFSET);
-static bool bit_is_set (const unsigned char *, OFFSET);
-static bool buffer_chars_equal (struct context *, OFFSET, OFFSET);
-
-#include "minmax.h"
-#include "diffseq.h"
-
-DEFUN ("replace-buffer-contents", Freplace_buffer_contents,
-       Sreplace_buffer_contents, 1, 1, "bSource buffer: ",
-       doc: /* Replace accessible portion of current buffer with that of SOURCE.
-SOURCE can be a buffer or a string that names a buffer.
-Interactively, prompt for SOURCE.
-As far as possible the replacement is non-destructive, i.e. existing
-buffer contents, markers, properties, and overlays in the current
-buffer stay intact.  */)
-  (Lisp_Object source)
-{
-  struct buffer *a = current_buffer;
-  Lisp_Object source_buffer = Fget_buffer (source);
-  if (NILP (source_buffer))
-    nsberror (source);
-  struct buffer *b = XBUFFER (source_buffer);
-  if (! BUFFER_LIVE_P (b))
-    error ("Selecting deleted buffer");
-  if (a == b)
-    error ("Cannot replace a buffer with itself");
-
-  ptrdiff_t min_a = BEGV;
-  ptrdiff_t min_b = BUF_BEGV (b);
-  ptrdiff_t size_a = ZV - min_a;
-  ptrdiff_t size_b = BUF_ZV (b) - min_b;
-  eassume (size_a >= 0);
-  eassume (size_b >= 0);
-  bool a_empty = size_a == 0;
-  bool b_empty = size_b == 0;
-
-  /* Handle trivial cases where at least one accessible portion is
-     empty.  */
-
-  if (a_empty && b_empty)
-    return Qnil;
-
-  if (a_empty)
-    return Finsert_buffer_substring (source, Qnil, Qnil);
-
-  if (b_empty)
-    {
-      del_range_both (BEGV, BEGV_BYTE, ZV, ZV_BYTE, true);
-      return Qnil;
-    }
-
-  /* FIXME: It is not documented how to initialize the contents of the
-     context structure.  This code cargo-cults from the existing
-     caller in src/analyze.c of GNU Diffutils, which appears to
-     work.  */
-
-  ptrdiff_t diags = size_a + size_b + 3;
-  ptrdiff_t *buffer;
-  USE_SAFE_ALLOCA;
-  SAFE_NALLOCA (buffer, 2, diags);
-  /* Micro-optimization: Casting to size_t generates much better
-     code.  */
-  ptrdiff_t del_bytes = (size_t) size_a / CHAR_BIT + 1;
-  ptrdiff_t ins_bytes = (size_t) size_b / CHAR_BIT + 1;
-  struct context ctx = {
-    .buffer_a = a,
-    .buffer_b = b,
-    .deletions = SAFE_ALLOCA (del_bytes),
-    .insertions = SAFE_ALLOCA (ins_bytes),
-    .fdiag = buffer + size_b + 1,
-    .bdiag = buffer + diags + size_b + 1,
-    /* FIXME: Find a good number for .too_expensive.  */
-    .too_expensive = 1000000,
-  };
-  memclear (ctx.deletions, del_bytes);
-  memclear (ctx.insertions, ins_bytes);
-  /* compareseq requires indices to be zero-based.  We add BEGV back
-     later.  */
-  bool early_abort = compareseq (0, size_a, 0, size_b, false, &ctx);
-  /* Since we didn’t define EARLY_ABORT, we should never abort
-     early.  */
-  eassert (! early_abort);
-  SAFE_FREE ();
-
-  Fundo_boundary ();
-  ptrdiff_t count = SPECPDL_INDEX ();
-  record_unwind_protect (save_excursion_restore, save_excursion_save ());
-
-  ptrdiff_t i = size_a;
-  ptrdiff_t j = size_b;
-  /* Walk backwards through the lists of changes.  This was also
-     cargo-culted from src/analyze.c in GNU Diffutils.  Because we
-     walk backwards, we don’t have to keep the positions in sync.  */
-  while (i >= 0 || j >= 0)
-    {
-      /* Check whether there is a change (insertion or deletion)
-         before the current position.  */
-      if ((i > 0 && bit_is_set (ctx.deletions, i - 1)) ||
-          (j > 0 && bit_is_set (ctx.insertions, j - 1)))
-	{
-          ptrdiff_t end_a = min_a + i;
-          ptrdiff_t end_b = min_b + j;
-          /* Find the beginning of the current change run.  */
-	  while (i > 0 && bit_is_set (ctx.deletions, i - 1))
-            --i;
-	  while (j > 0 && bit_is_set (ctx.insertions, j - 1))
-            --j;
-          ptrdiff_t beg_a = min_a + i;
-          ptrdiff_t beg_b = min_b + j;
-          eassert (beg_a >= BEGV);
-          eassert (beg_b >= BUF_BEGV (b));
-          eassert (beg_a <= end_a);
-          eassert (beg_b <= end_b);
-          eassert (end_a <= ZV);
-          eassert (end_b <= BUF_ZV (b));
-          eassert (beg_a < end_a || beg_b < end_b);
-          if (beg_a < end_a)
-            del_range (beg_a, end_a);
-          if (beg_b < end_b)
-            {
-              SET_PT (beg_a);
-              Finsert_buffer_substring (source, make_natnum (beg_b),
-                                        make_natnum (end_b));
-            }
-	}
-      --i;
-      --j;
-    }
-
-  return unbind_to (count, Qnil);
-}
-
-static void
-set_bit (unsigned char *a, ptrdiff_t i)
-{
-  eassert (i >= 0);
-  /* Micro-optimization: Casting to size_t generates much better
-     code.  */
-  size_t j = i;
-  a[j / CHAR_BIT] |= (1 << (j % CHAR_BIT));
-}
-
-static bool
-bit_is_set (const unsigned char *a, ptrdiff_t i)
-{
-  eassert (i >= 0);
-  /* Micro-optimization: Casting to size_t generates much better
-     code.  */
-  size_t j = i;
-  return a[j / CHAR_BIT] & (1 << (j % CHAR_BIT));
-}
-
-/* Return true if the characters at position POS_A of buffer
-   CTX->buffer_a and at position POS_B of buffer CTX->buffer_b are
-   equal.  POS_A and POS_B are zero-based.  Text properties are
-   ignored.  */
-
-static bool
-buffer_chars_equal (struct context *ctx,
-                    ptrdiff_t pos_a, ptrdiff_t pos_b)
-{
-  eassert (pos_a >= 0);
-  pos_a += BUF_BEGV (ctx->buffer_a);
-  eassert (pos_a >= BUF_BEGV (ctx->buffer_a));
-  eassert (pos_a < BUF_ZV (ctx->buffer_a));
-
-  eassert (pos_b >= 0);
-  pos_b += BUF_BEGV (ctx->buffer_b);
-  eassert (pos_b >= BUF_BEGV (ctx->buffer_b));
-  eassert (pos_b < BUF_ZV (ctx->buffer_b));
-
-  return BUF_FETCH_CHAR_AS_MULTIBYTE (ctx->buffer_a, pos_a)
-    == BUF_FETCH_CHAR_AS_MULTIBYTE (ctx->buffer_b, pos_b);
-}
-
 
 static void
 subst_char_in_region_unwind (Lisp_Object arg)
@@ -2181,240 +1902,6 @@ Both characters must have the same length of multi-byte form.  */)
 }
 
 
-static Lisp_Object check_translation (ptrdiff_t, ptrdiff_t, ptrdiff_t,
-				      Lisp_Object);
-
-/* Helper function for Ftranslate_region_internal.
-
-   Check if a character sequence at POS (POS_BYTE) matches an element
-   of VAL.  VAL is a list (([FROM-CHAR ...] . TO) ...).  If a matching
-   element is found, return it.  Otherwise return Qnil.  */
-
-static Lisp_Object
-check_translation (ptrdiff_t pos, ptrdiff_t pos_byte, ptrdiff_t end,
-		   Lisp_Object val)
-{
-  int initial_buf[16];
-  int *buf = initial_buf;
-  ptrdiff_t buf_size = ARRAYELTS (initial_buf);
-  int *bufalloc = 0;
-  ptrdiff_t buf_used = 0;
-  Lisp_Object result = Qnil;
-
-  for (; CONSP (val); val = XCDR (val))
-    {
-      Lisp_Object elt;
-      ptrdiff_t len, i;
-
-      elt = XCAR (val);
-      if (! CONSP (elt))
-	continue;
-      elt = XCAR (elt);
-      if (! VECTORP (elt))
-	continue;
-      len = ASIZE (elt);
-      if (len <= end - pos)
-	{
-	  for (i = 0; i < len; i++)
-	    {
-	      if (buf_used <= i)
-		{
-		  unsigned char *p = BYTE_POS_ADDR (pos_byte);
-		  int len1;
-
-		  if (buf_used == buf_size)
-		    {
-		      bufalloc = xpalloc (bufalloc, &buf_size, 1, -1,
-					  sizeof *bufalloc);
-		      if (buf == initial_buf)
-			memcpy (bufalloc, buf, sizeof initial_buf);
-		      buf = bufalloc;
-		    }
-		  buf[buf_used++] = STRING_CHAR_AND_LENGTH (p, len1);
-		  pos_byte += len1;
-		}
-	      if (XINT (AREF (elt, i)) != buf[i])
-		break;
-	    }
-	  if (i == len)
-	    {
-	      result = XCAR (val);
-	      break;
-	    }
-	}
-    }
-
-  xfree (bufalloc);
-  return result;
-}
-
-
-DEFUN ("translate-region-internal", Ftranslate_region_internal,
-       Stranslate_region_internal, 3, 3, 0,
-       doc: /* Internal use only.
-From START to END, translate characters according to TABLE.
-TABLE is a string or a char-table; the Nth character in it is the
-mapping for the character with code N.
-It returns the number of characters changed.  */)
-  (Lisp_Object start, Lisp_Object end, register Lisp_Object table)
-{
-  register unsigned char *tt;	/* Trans table. */
-  register int nc;		/* New character. */
-  int cnt;			/* Number of changes made. */
-  ptrdiff_t size;		/* Size of translate table. */
-  ptrdiff_t pos, pos_byte, end_pos;
-  bool multibyte = !NILP (BVAR (current_buffer, enable_multibyte_characters));
-  bool string_multibyte UNINIT;
-
-  validate_region (&start, &end);
-  if (CHAR_TABLE_P (table))
-    {
-      if (! EQ (XCHAR_TABLE (table)->purpose, Qtranslation_table))
-	error ("Not a translation table");
-      size = MAX_CHAR;
-      tt = NULL;
-    }
-  else
-    {
-      CHECK_STRING (table);
-
-      if (! multibyte && (SCHARS (table) < SBYTES (table)))
-	table = string_make_unibyte (table);
-      string_multibyte = SCHARS (table) < SBYTES (table);
-      size = SBYTES (table);
-      tt = SDATA (table);
-    }
-
-  pos = XINT (start);
-  pos_byte = CHAR_TO_BYTE (pos);
-  end_pos = XINT (end);
-  modify_text (pos, end_pos);
-
-  cnt = 0;
-  for (; pos < end_pos; )
-    {
-      unsigned char *p = BYTE_POS_ADDR (pos_byte);
-      unsigned char *str UNINIT;
-      unsigned char buf[MAX_MULTIBYTE_LENGTH];
-      int len, str_len;
-      int oc;
-      Lisp_Object val;
-
-      if (multibyte)
-	oc = STRING_CHAR_AND_LENGTH (p, len);
-      else
-	oc = *p, len = 1;
-      if (oc < size)
-	{
-	  if (tt)
-	    {
-	      /* Reload as signal_after_change in last iteration may GC.  */
-	      tt = SDATA (table);
-	      if (string_multibyte)
-		{
-		  str = tt + string_char_to_byte (table, oc);
-		  nc = STRING_CHAR_AND_LENGTH (str, str_len);
-		}
-	      else
-		{
-		  nc = tt[oc];
-		  if (! ASCII_CHAR_P (nc) && multibyte)
-		    {
-		      str_len = BYTE8_STRING (nc, buf);
-		      str = buf;
-		    }
-		  else
-		    {
-		      str_len = 1;
-		      str = tt + oc;
-		    }
-		}
-	    }
-	  else
-	    {
-	      nc = oc;
-	      val = CHAR_TABLE_REF (table, oc);
-	      if (CHARACTERP (val))
-		{
-		  nc = XFASTINT (val);
-		  str_len = CHAR_STRING (nc, buf);
-		  str = buf;
-		}
-	      else if (VECTORP (val) || (CONSP (val)))
-		{
-		  /* VAL is [TO_CHAR ...] or (([FROM-CHAR ...] .  TO) ...)
-		     where TO is TO-CHAR or [TO-CHAR ...].  */
-		  nc = -1;
-		}
-	    }
-
-	  if (nc != oc && nc >= 0)
-	    {
-	      /* Simple one char to one char translation.  */
-	      if (len != str_len)
-		{
-		  Lisp_Object string;
-
-		  /* This is less efficient, because it moves the gap,
-		     but it should handle multibyte characters correctly.  */
-		  string = make_multibyte_string ((char *) str, 1, str_len);
-		  replace_range (pos, pos + 1, string, 1, 0, 1, 0);
-		  len = str_len;
-		}
-	      else
-		{
-		  record_change (pos, 1);
-		  while (str_len-- > 0)
-		    *p++ = *str++;
-		  signal_after_change (pos, 1, 1);
-		  update_compositions (pos, pos + 1, CHECK_BORDER);
-		}
-	      ++cnt;
-	    }
-	  else if (nc < 0)
-	    {
-	      Lisp_Object string;
-
-	      if (CONSP (val))
-		{
-		  val = check_translation (pos, pos_byte, end_pos, val);
-		  if (NILP (val))
-		    {
-		      pos_byte += len;
-		      pos++;
-		      continue;
-		    }
-		  /* VAL is ([FROM-CHAR ...] . TO).  */
-		  len = ASIZE (XCAR (val));
-		  val = XCDR (val);
-		}
-	      else
-		len = 1;
-
-	      if (VECTORP (val))
-		{
-		  string = Fconcat (1, &val);
-		}
-	      else
-		{
-		  string = Fmake_string (make_number (1), val, Qnil);
-		}
-	      replace_range (pos, pos + len, string, 1, 0, 1, 0);
-	      pos_byte += SBYTES (string);
-	      pos += SCHARS (string);
-	      cnt += SCHARS (string);
-	      end_pos += SCHARS (string) - len;
-	      continue;
-	    }
-	}
-      pos_byte += len;
-      pos++;
-    }
-
-  return make_number (cnt);
-}
-
-
 DEFUN ("widen", Fwiden, Swiden, 0, 0, "",
        doc: /* Remove restrictions (narrowing) from current buffer.
 This allows the buffer's full text to be seen and edited.  */)
@@ -3874,9 +3361,7 @@ functions if all the text being accessed has this property.  */);
   defsubr (&Ssystem_name);
 
   defsubr (&Scompare_buffer_substrings);
-  defsubr (&Sreplace_buffer_contents);
   defsubr (&Ssubst_char_in_region);
-  defsubr (&Stranslate_region_internal);
   defsubr (&Swiden);
   defsubr (&Snarrow_to_region);
   defsubr (&Stranspose_regions);
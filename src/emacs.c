@@ -176,6 +176,10 @@ bool no_site_lisp;
 /* True means put details like time stamps into builds.  */
 bool build_details;
 
+/* True means to record the stores of the generational collector's
+   write barrier.  */
+bool experimental_gc;
+
 /* Name for the server started by the daemon.*/
 static char *daemon_name;
 
@@ -219,6 +223,7 @@ Initialization options:\n\
 --fg-daemon[=NAME]          start a (named) server in the foreground\n\
 --debug-init                enable Emacs Lisp debugger for init file\n\
 --display, -d DISPLAY       use X server DISPLAY\n\
+--experimental-gc           enable the generational collector's write barrier\n\
 ",
 #ifdef HAVE_MODULES
     "\
@@ -370,6 +375,7 @@ terminate_due_to_signal (int sig, int backtrace_limit)
           if (sig == SIGTERM || sig == SIGHUP || sig == SIGINT)
             Fkill_emacs (make_number (sig));
 
+          write_crash_report (sig);
           shut_down_emacs (sig, Qnil);
           emacs_backtrace (backtrace_limit);
         }
@@ -1147,6 +1153,7 @@ Using an Emacs configured with --with-x-toolkit=lucid does not have this problem
 #endif
 
   init_signals (dumping);
+  init_crash ();
 
   noninteractive1 = noninteractive;
 
@@ -1229,6 +1236,9 @@ Using an Emacs configured with --with-x-toolkit=lucid does not have this problem
   build_details = ! argmatch (argv, argc, "-no-build-details",
 			      "--no-build-details", 7, NULL, &skip_args);
 
+  experimental_gc = argmatch (argv, argc, "-experimental-gc",
+			      "--experimental-gc", 6, NULL, &skip_args);
+
 #ifdef HAVE_MODULES
   bool module_assertions
     = argmatch (argv, argc, "-module-assertions", "--module-assertions", 15,
@@ -1431,21 +1441,29 @@ Using an Emacs configured with --with-x-toolkit=lucid does not have this problem
       syms_of_category ();
       syms_of_ccl ();
       syms_of_character ();
+      syms_of_cmdhist ();
       syms_of_cmds ();
+      syms_of_crash ();
       syms_of_dired ();
       syms_of_display ();
       syms_of_doc ();
       syms_of_editfns ();
       syms_of_emacs ();
       syms_of_filelock ();
+      syms_of_hookprof ();
       syms_of_indent ();
       syms_of_insdel ();
+      syms_of_latency ();
       /* syms_of_keymap (); */
+      syms_of_lineedit ();
+      syms_of_linestats ();
       syms_of_macros ();
       syms_of_minibuf ();
+      syms_of_msgcat ();
       syms_of_process ();
       syms_of_search ();
       syms_of_frame ();
+      syms_of_frametitle ();
       syms_of_syntax ();
       syms_of_terminal ();
       syms_of_term ();
@@ -1683,6 +1701,7 @@ static const struct standard_args standard_args[] =
   { "-nl", "--no-loadup", 70, 0 },
   { "-nsl", "--no-site-lisp", 65, 0 },
   { "-no-build-details", "--no-build-details", 63, 0 },
+  { "-experimental-gc", "--experimental-gc", 63, 0 },
 #ifdef HAVE_MODULES
   { "-module-assertions", "--module-assertions", 62, 0 },
 #endif
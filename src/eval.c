@@ -1700,77 +1700,6 @@ usage: (apply FUNCTION &rest ARGUMENTS)  */)
 
 /* Run hook variables in various ways.  */
 
-/* ARGS[0] should be a hook symbol.
-   Call each of the functions in the hook value, passing each of them
-   as arguments all the rest of ARGS (all NARGS - 1 elements).
-   FUNCALL specifies how to call each function on the hook.  */
-
-Lisp_Object
-run_hook_with_args (ptrdiff_t nargs, Lisp_Object *args,
-		    Lisp_Object (*funcall) (ptrdiff_t nargs, Lisp_Object *args))
-{
-  Lisp_Object sym, val, ret = Qnil;
-
-  /* If we are dying or still initializing,
-     don't do anything--it would probably crash if we tried.  */
-  if (NILP (Vrun_hooks))
-    return Qnil;
-
-  sym = args[0];
-  val = find_symbol_value (sym);
-
-  if (EQ (val, Qunbound) || NILP (val))
-    return ret;
-  else if (!CONSP (val) || FUNCTIONP (val))
-    {
-      args[0] = val;
-      return funcall (nargs, args);
-    }
-  else
-    {
-      Lisp_Object global_vals = Qnil;
-
-      for (;
-	   CONSP (val) && NILP (ret);
-	   val = XCDR (val))
-	{
-	  if (EQ (XCAR (val), Qt))
-	    {
-	      /* t indicates this hook has a local binding;
-		 it means to run the global binding too.  */
-	      global_vals = Fdefault_value (sym);
-	      if (NILP (global_vals)) continue;
-
-	      if (!CONSP (global_vals) || EQ (XCAR (global_vals), Qlambda))
-		{
-		  args[0] = global_vals;
-		  ret = funcall (nargs, args);
-		}
-	      else
-		{
-		  for (;
-		       CONSP (global_vals) && NILP (ret);
-		       global_vals = XCDR (global_vals))
-		    {
-		      args[0] = XCAR (global_vals);
-		      /* In a global value, t should not occur.  If it does, we
-			 must ignore it to avoid an endless loop.  */
-		      if (!EQ (args[0], Qt))
-			ret = funcall (nargs, args);
-		    }
-		}
-	    }
-	  else
-	    {
-	      args[0] = XCAR (val);
-	      ret = funcall (nargs, args);
-	    }
-	}
-
-      return ret;
-    }
-}
-
 /* Run the hook HOOK, giving each function the two args ARG1 and ARG2.  */
 
 void
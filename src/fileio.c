@@ -5214,112 +5214,6 @@ e_write (int desc, Lisp_Object string, ptrdiff_t start, ptrdiff_t end,
   return 1;
 }
 
-
-DEFUN ("verify-visited-file-modtime", Fverify_visited_file_modtime,
-       Sverify_visited_file_modtime, 0, 1, 0,
-       doc: /* Return t if last mod time of BUF's visited file matches what BUF records.
-This means that the file has not been changed since it was visited or saved.
-If BUF is omitted or nil, it defaults to the current buffer.
-See Info node `(elisp)Modification Time' for more details.  */)
-  (Lisp_Object buf)
-{
-  struct buffer *b = decode_buffer (buf);
-  struct stat st;
-  Lisp_Object handler;
-  Lisp_Object filename;
-  struct timespec mtime;
-
-  if (!STRINGP (BVAR (b, filename))) return Qt;
-  if (b->modtime.tv_nsec == UNKNOWN_MODTIME_NSECS) return Qt;
-
-  /* If the file name has special constructs in it,
-     call the corresponding file handler.  */
-  handler = Ffind_file_name_handler (BVAR (b, filename),
-				     Qverify_visited_file_modtime);
-  if (!NILP (handler))
-    return call2 (handler, Qverify_visited_file_modtime, buf);
-
-  filename = ENCODE_FILE (BVAR (b, filename));
-
-  mtime = (stat (SSDATA (filename), &st) == 0
-	   ? get_stat_mtime (&st)
-	   : time_error_value (errno));
-  if (timespec_cmp (mtime, b->modtime) == 0
-      && (b->modtime_size < 0
-	  || st.st_size == b->modtime_size))
-    return Qt;
-  return Qnil;
-}
-
-DEFUN ("visited-file-modtime", Fvisited_file_modtime,
-       Svisited_file_modtime, 0, 0, 0,
-       doc: /* Return the current buffer's recorded visited file modification time.
-The value is a list of the form (HIGH LOW USEC PSEC), like the time values that
-`file-attributes' returns.  If the current buffer has no recorded file
-modification time, this function returns 0.  If the visited file
-doesn't exist, return -1.
-See Info node `(elisp)Modification Time' for more details.  */)
-  (void)
-{
-  int ns = current_buffer->modtime.tv_nsec;
-  if (ns < 0)
-    return make_number (UNKNOWN_MODTIME_NSECS - ns);
-  return make_lisp_time (current_buffer->modtime);
-}
-
-DEFUN ("set-visited-file-modtime", Fset_visited_file_modtime,
-       Sset_visited_file_modtime, 0, 1, 0,
-       doc: /* Update buffer's recorded modification time from the visited file's time.
-Useful if the buffer was not read from the file normally
-or if the file itself has been changed for some known benign reason.
-An argument specifies the modification time value to use
-\(instead of that of the visited file), in the form of a list
-\(HIGH LOW USEC PSEC) or an integer flag as returned by
-`visited-file-modtime'.  */)
-  (Lisp_Object time_flag)
-{
-  if (!NILP (time_flag))
-    {
-      struct timespec mtime;
-      if (INTEGERP (time_flag))
-	{
-	  CHECK_RANGED_INTEGER (time_flag, -1, 0);
-	  mtime = make_timespec (0, UNKNOWN_MODTIME_NSECS - XINT (time_flag));
-	}
-      else
-	mtime = lisp_time_argument (time_flag);
-
-      current_buffer->modtime = mtime;
-      current_buffer->modtime_size = -1;
-    }
-  else
-    {
-      register Lisp_Object filename;
-      struct stat st;
-      Lisp_Object handler;
-
-      filename = Fexpand_file_name (BVAR (current_buffer, filename), Qnil);
-
-      /* If the file name has special constructs in it,
-	 call the corresponding file handler.  */
-      handler = Ffind_file_name_handler (filename, Qset_visited_file_modtime);
-      if (!NILP (handler))
-	/* The handler can find the file name the same way we did.  */
-	return call2 (handler, Qset_visited_file_modtime, Qnil);
-
-      filename = ENCODE_FILE (filename);
-
-      if (stat (SSDATA (filename), &st) >= 0)
-        {
-	  current_buffer->modtime = get_stat_mtime (&st);
-          current_buffer->modtime_size = st.st_size;
-        }
-    }
-
-  return Qnil;
-}
-
-
 static Lisp_Object
 auto_save_error (Lisp_Object error_val)
 {
@@ -6051,9 +5945,6 @@ This includes interactive calls to `delete-file' and
   defsubr (&Sfile_newer_than_file_p);
   defsubr (&Sinsert_file_contents);
   defsubr (&Swrite_region);
-  defsubr (&Sverify_visited_file_modtime);
-  defsubr (&Svisited_file_modtime);
-  defsubr (&Sset_visited_file_modtime);
   defsubr (&Sdo_auto_save);
   defsubr (&Sset_buffer_auto_saved);
 
@@ -687,8 +687,7 @@ lock_file (Lisp_Object fn)
     subject_buf = get_truename_buffer (orig_fn);
 
     if (!NILP (subject_buf)
-	&& NILP (Fverify_visited_file_modtime (subject_buf))
-	&& !NILP (Ffile_exists_p (fn)))
+	&& !NILP (Ffile_superseded_p (subject_buf)))
       call1 (intern ("userlock--ask-user-about-supersession-threat"), fn);
 
   }
@@ -187,6 +187,22 @@ frame_size_history_add (struct frame *f, Lisp_Object fun_symbol,
 }
 
 
+/* Return the side of the vertical scroll bars of the windows of frame
+   F that use the frame's default.  */
+enum vertical_scroll_bar_type
+frame_vertical_scroll_bar_type (struct frame *f)
+{
+  return FRAME_VERTICAL_SCROLL_BAR_TYPE (f);
+}
+
+/* Return true if the windows of frame F that use the frame's default
+   have horizontal scroll bars.  */
+bool
+frame_has_horizontal_scroll_bars (struct frame *f)
+{
+  return FRAME_HAS_HORIZONTAL_SCROLL_BARS (f);
+}
+
 /* Return 1 if `frame-inhibit-implied-resize' is non-nil or fullscreen
    state of frame F would be affected by a vertical (horizontal if
    HORIZONTAL is true) resize.  PARAMETER is the symbol of the frame
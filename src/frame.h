@@ -1242,6 +1242,9 @@ extern void frame_make_pointer_invisible (struct frame *);
 extern void frame_make_pointer_visible (struct frame *);
 extern Lisp_Object delete_frame (Lisp_Object, Lisp_Object);
 extern bool frame_inhibit_resize (struct frame *, bool, Lisp_Object);
+extern enum vertical_scroll_bar_type
+  frame_vertical_scroll_bar_type (struct frame *);
+extern bool frame_has_horizontal_scroll_bars (struct frame *);
 extern void adjust_frame_size (struct frame *, int, int, int, bool, Lisp_Object);
 extern void frame_size_history_add (struct frame *f, Lisp_Object fun_symbol,
 				    int width, int height, Lisp_Object rest);
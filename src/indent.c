@@ -2069,6 +2069,15 @@ whether or not it is currently displayed in some window.  */)
   CHECK_NUMBER (lines);
   w = decode_live_window (window);
 
+  /* Simple text is laid out without the display engine.  */
+  if (NILP (cur_col))
+    {
+      EMACS_INT moved;
+      if (simple_vertical_motion (XINT (lines), lcols_given ? lcols : Qnil,
+				  window, &moved))
+	return make_number (moved);
+    }
+
   if (XBUFFER (w->contents) != current_buffer)
     {
       /* Set the window's buffer temporarily to the current buffer.  */
@@ -311,6 +311,8 @@ adjust_markers_for_insert (ptrdiff_t from, ptrdiff_t from_byte,
       fix_start_end_in_overlays (from, to);
       fix_overlays_before (current_buffer, from, to);
     }
+
+  line_stats_insert (from, to);
 }
 
 /* Adjust point for an insertion of NBYTES bytes, which are NCHARS characters.
@@ -902,6 +904,7 @@ insert_1_both (const char *string,
      the insertion.  This, together with recording the insertion,
      will add up to the right stuff in the undo list.  */
   record_insert (PT, nchars);
+  line_stats_before_insert ();
   MODIFF++;
   CHARS_MODIFF = MODIFF;
 
@@ -1030,6 +1033,7 @@ insert_from_string_1 (Lisp_Object string, ptrdiff_t pos, ptrdiff_t pos_byte,
 #endif
 
   record_insert (PT, nchars);
+  line_stats_before_insert ();
   MODIFF++;
   CHARS_MODIFF = MODIFF;
 
@@ -1087,7 +1091,9 @@ insert_from_gap (ptrdiff_t nchars, ptrdiff_t nbytes, bool text_at_gap_tail)
      of this dance.  */
   invalidate_buffer_caches (current_buffer, GPT, GPT);
   record_insert (GPT, nchars);
+  line_stats_before_insert ();
   MODIFF++;
+  CHARS_MODIFF = MODIFF;
 
   GAP_SIZE -= nbytes;
   if (! text_at_gap_tail)
@@ -1227,6 +1233,7 @@ insert_from_buffer_1 (struct buffer *buf,
 #endif
 
   record_insert (PT, nchars);
+  line_stats_before_insert ();
   MODIFF++;
   CHARS_MODIFF = MODIFF;
 
@@ -1800,6 +1807,7 @@ del_range_2 (ptrdiff_t from, ptrdiff_t from_byte,
   Lisp_Object deletion;
 
   check_markers ();
+  line_stats_delete (from, to);
 
   nchars_del = to - from;
   nbytes_del = to_byte - from_byte;
@@ -2039,6 +2047,8 @@ invalidate_buffer_caches (struct buffer *buf, ptrdiff_t start, ptrdiff_t end)
     invalidate_region_cache (buf,
                              buf->width_run_cache,
                              start - BUF_BEG (buf), BUF_Z (buf) - end);
+  if (buf->word_index)
+    word_index_invalidate (buf, start, end);
 }
 
 /* These macros work with an argument named `preserve_ptr'
@@ -51,17 +51,23 @@ struct interval
   bool_bf gcmarkbit : 1;
 
   /* The remaining components are `properties' of the interval.
-     The first four are duplicates for things which can be on the list,
-     for purposes of speed.  */
-
-  bool_bf write_protect : 1;	    /* True means can't modify.  */
-  bool_bf visible : 1;		    /* False means don't display.  */
-  bool_bf front_sticky : 1;	    /* True means text inserted just
-				       before this interval goes into it.  */
-  bool_bf rear_sticky : 1;	    /* Likewise for just after it.  */
+     The first is a cache of things which can be on the list, for
+     purposes of speed, as the bits of enum interval_cache.  */
+
+  unsigned cache : 4;
   Lisp_Object plist;		    /* Other properties.  */
 };
 
+/* The bits of the `cache' of an interval.  */
+enum interval_cache
+  {
+    INTERVAL_WRITE_PROTECT = 1,	    /* Can't modify.  */
+    INTERVAL_VISIBLE = 2,	    /* Unset means don't display.  */
+    INTERVAL_FRONT_STICKY = 4,	    /* Text inserted just before this
+				       interval goes into it.  */
+    INTERVAL_REAR_STICKY = 8	    /* Likewise for just after it.  */
+  };
+
 /* These are macros for dealing with the interval tree.  */
 
 /* True if this interval has no right child.  */
@@ -170,30 +176,10 @@ set_interval_plist (INTERVAL i, Lisp_Object plist)
   (i)->total_length = (i)->position = 0;      \
   (i)->left = (i)->right = NULL;	      \
   set_interval_parent (i, NULL);	      \
-  (i)->write_protect = false;		      \
-  (i)->visible = false;			      \
-  (i)->front_sticky = (i)->rear_sticky = false;	\
+  (i)->cache = 0;			      \
   set_interval_plist (i, Qnil);		      \
  } while (false)
 
-/* Copy the cached property values of interval FROM to interval TO.  */
-#define COPY_INTERVAL_CACHE(from,to)		\
- do {						\
-  (to)->write_protect = (from)->write_protect;	\
-  (to)->visible = (from)->visible;		\
-  (to)->front_sticky = (from)->front_sticky;	\
-  (to)->rear_sticky = (from)->rear_sticky;	\
- } while (false)
-
-/* Copy only the set bits of FROM's cache.  */
-#define MERGE_INTERVAL_CACHE(from,to)				\
- do {								\
-  if ((from)->write_protect) (to)->write_protect = true;	\
-  if ((from)->visible) (to)->visible = true;			\
-  if ((from)->front_sticky) (to)->front_sticky = true;		\
-  if ((from)->rear_sticky) (to)->rear_sticky = true;		\
- } while (false)
-
 /* Is this interval visible?  Replace later with cache access.  */
 #define INTERVAL_VISIBLE_P(i) \
   (i && NILP (textget ((i)->plist, Qinvisible)))
@@ -236,31 +222,10 @@ extern INTERVAL make_interval (void);
 /* Declared in intervals.c.  */
 
 extern INTERVAL create_root_interval (Lisp_Object);
-extern void copy_properties (INTERVAL, INTERVAL);
-extern bool intervals_equal (INTERVAL, INTERVAL);
-extern void traverse_intervals (INTERVAL, ptrdiff_t,
-                                void (*) (INTERVAL, Lisp_Object),
-                                Lisp_Object);
-extern void traverse_intervals_noorder (INTERVAL,
-					void (*) (INTERVAL, void *), void *);
-extern INTERVAL split_interval_right (INTERVAL, ptrdiff_t);
-extern INTERVAL split_interval_left (INTERVAL, ptrdiff_t);
 extern INTERVAL find_interval (INTERVAL, ptrdiff_t);
-extern INTERVAL next_interval (INTERVAL);
-extern INTERVAL previous_interval (INTERVAL);
-extern INTERVAL merge_interval_left (INTERVAL);
-extern void offset_intervals (struct buffer *, ptrdiff_t, ptrdiff_t);
-extern void graft_intervals_into_buffer (INTERVAL, ptrdiff_t, ptrdiff_t,
-                                         struct buffer *, bool);
 extern void verify_interval_modification (struct buffer *,
 					  ptrdiff_t, ptrdiff_t);
-extern INTERVAL balance_intervals (INTERVAL);
-extern void copy_intervals_to_string (Lisp_Object, struct buffer *,
-                                             ptrdiff_t, ptrdiff_t);
-extern INTERVAL copy_intervals (INTERVAL, ptrdiff_t, ptrdiff_t);
 extern bool compare_string_intervals (Lisp_Object, Lisp_Object);
-extern Lisp_Object textget (Lisp_Object, Lisp_Object);
-extern Lisp_Object lookup_char_property (Lisp_Object, Lisp_Object, bool);
 extern void move_if_not_intangible (ptrdiff_t);
 extern bool get_property_and_range (ptrdiff_t, Lisp_Object, Lisp_Object *,
 				    ptrdiff_t *, ptrdiff_t *, Lisp_Object);
@@ -270,6 +235,7 @@ extern void set_intervals_multibyte (bool);
 extern INTERVAL validate_interval_range (Lisp_Object, Lisp_Object *,
                                          Lisp_Object *, bool);
 extern INTERVAL interval_of (ptrdiff_t, Lisp_Object);
+extern Lisp_Object merge_properties_sticky (Lisp_Object, Lisp_Object);
 
 /* Defined in xdisp.c.  */
 extern int invisible_prop (Lisp_Object, Lisp_Object);
@@ -295,6 +261,40 @@ extern int text_property_stickiness (Lisp_Object prop, Lisp_Object pos,
 
 extern void syms_of_textprop (void);
 
+/* Defined in textprop.rs.  */
+extern Lisp_Object textget (Lisp_Object, Lisp_Object);
+extern bool intervals_equal (INTERVAL, INTERVAL);
+extern Lisp_Object lookup_char_property (Lisp_Object, Lisp_Object, bool);
+extern void check_interval_tree (INTERVAL);
+extern INTERVAL next_interval (INTERVAL);
+extern INTERVAL previous_interval (INTERVAL);
+extern INTERVAL copy_intervals (INTERVAL, ptrdiff_t, ptrdiff_t);
+extern void copy_intervals_to_string (Lisp_Object, struct buffer *,
+                                      ptrdiff_t, ptrdiff_t);
+extern void copy_properties (INTERVAL, INTERVAL);
+extern void merge_properties (INTERVAL, INTERVAL);
+extern void traverse_intervals_noorder (INTERVAL,
+					void (*) (INTERVAL, void *), void *);
+extern void traverse_intervals (INTERVAL, ptrdiff_t,
+                                void (*) (INTERVAL, Lisp_Object),
+                                Lisp_Object);
+extern void transplant_intervals (INTERVAL, Lisp_Object);
+extern INTERVAL reproduce_tree (INTERVAL, Lisp_Object);
+extern void graft_intervals_into_buffer (INTERVAL, ptrdiff_t, ptrdiff_t,
+                                         struct buffer *, bool);
+extern void forget_buffer_intervals (struct buffer *);
+extern void offset_intervals (struct buffer *, ptrdiff_t, ptrdiff_t);
+extern void delete_interval (INTERVAL);
+extern INTERVAL merge_interval_left (INTERVAL);
+extern INTERVAL merge_interval_right (INTERVAL);
+extern INTERVAL split_interval_right (INTERVAL, ptrdiff_t);
+extern INTERVAL split_interval_left (INTERVAL, ptrdiff_t);
+extern INTERVAL balance_an_interval (INTERVAL);
+extern INTERVAL balance_possible_root_interval (INTERVAL);
+extern INTERVAL balance_intervals (INTERVAL);
+extern void buffer_balance_intervals (struct buffer *);
+extern void coalesce_intervals (Lisp_Object, Lisp_Object, Lisp_Object);
+
 INLINE_HEADER_END
 
 #endif /* EMACS_INTERVALS_H */
@@ -1282,6 +1282,8 @@ command_loop_1 (void)
 	  && !NILP (Ffboundp (Qrecompute_lucid_menubar)))
 	call0 (Qrecompute_lucid_menubar);
 
+      latency_phase_start (LATENCY_INPUT);
+
       Vthis_command = Qnil;
       Vreal_this_command = Qnil;
       Vthis_original_command = Qnil;
@@ -1291,6 +1293,7 @@ command_loop_1 (void)
       raw_keybuf_count = 0;
       i = read_key_sequence (keybuf, ARRAYELTS (keybuf),
 			     Qnil, 0, 1, 1, 0);
+      latency_phase_start (LATENCY_COMMAND);
 
       /* A filter may have run while we were reading the input.  */
       if (! FRAME_LIVE_P (XFRAME (selected_frame)))
@@ -1760,7 +1763,7 @@ static Lisp_Object
 safe_run_hook_funcall (ptrdiff_t nargs, Lisp_Object *args)
 {
   eassert (nargs == 2);
-  /* Yes, run_hook_with_args works with args in the other order.  */
+  /* Yes, run_hook_with_funcall works with args in the other order.  */
   internal_condition_case_n (safe_run_hooks_1,
 			     2, ((Lisp_Object []) {args[1], args[0]}),
 			     Qt, safe_run_hooks_error);
@@ -1777,7 +1780,8 @@ safe_run_hooks (Lisp_Object hook)
   ptrdiff_t count = SPECPDL_INDEX ();
 
   specbind (Qinhibit_quit, Qt);
-  run_hook_with_args (2, ((Lisp_Object []) {hook, hook}), safe_run_hook_funcall);
+  run_hook_with_funcall (2, ((Lisp_Object []) {hook, hook}),
+			 safe_run_hook_funcall);
   unbind_to (count, Qnil);
 }
 
@@ -9880,6 +9884,20 @@ represented as events of the form (nil . COMMAND).  */)
     }
 }
 
+/* Return the Nth of the last few events, counting from the oldest, or
+   nil if there are no more.  This only reads memory, so crash reports
+   can use it in a signal handler.  */
+
+Lisp_Object
+recent_key (int n)
+{
+  int start = total_keys < NUM_RECENT_KEYS ? 0 : recent_keys_index;
+
+  if (n < 0 || n >= total_keys)
+    return Qnil;
+  return AREF (recent_keys, (start + n) % NUM_RECENT_KEYS);
+}
+
 DEFUN ("this-command-keys", Fthis_command_keys, Sthis_command_keys, 0, 0, 0,
        doc: /* Return the key sequence that invoked this command.
 However, if the command has called `read-key-sequence', it returns
@@ -491,6 +491,7 @@ extern bool volatile pending_signals;
 extern void process_pending_signals (void);
 extern struct timespec timer_check (void);
 extern void mark_kboards (void);
+extern Lisp_Object recent_key (int);
 
 #ifdef HAVE_NTGUI
 extern const char *const lispy_function_keys[];
@@ -501,6 +502,17 @@ extern char const DEV_TTY[];
 Lisp_Object
 make_lispy_position (struct frame *f, Lisp_Object x, Lisp_Object y, Time t);
 
+/* The phases of the command loop timed by the latency watchdog.  */
+enum latency_phase
+  {
+    LATENCY_INPUT,		/* Waiting for a key sequence.  */
+    LATENCY_COMMAND,		/* Running a command and its hooks.  */
+    LATENCY_REDISPLAY		/* Redisplaying.  */
+  };
+
+/* Defined in latency.rs.  */
+extern enum latency_phase latency_phase_start (enum latency_phase);
+
 INLINE_HEADER_END
 
 #endif /* EMACS_KEYBOARD_H */
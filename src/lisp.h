@@ -3504,6 +3504,16 @@ aset_multibyte_string(register Lisp_Object array, EMACS_INT idxval, int c);
 extern void syms_of_cmds (void);
 extern void keys_of_cmds (void);
 
+/* Defined in composite.rs.  */
+extern bool autocmp_failed_p (Lisp_Object, Lisp_Object);
+extern void autocmp_note_failure (Lisp_Object, Lisp_Object);
+extern void autocmp_clear_failures (void);
+
+/* Defined in crash.rs.  */
+extern void init_crash (void);
+extern void write_crash_report (int);
+extern void syms_of_crash (void);
+
 /* Defined in coding.c.  */
 extern Lisp_Object detect_coding_system (const unsigned char *, ptrdiff_t,
                                          ptrdiff_t, bool, bool, Lisp_Object);
@@ -3667,6 +3677,7 @@ extern void truncate_echo_area (ptrdiff_t);
 extern void redisplay (void);
 
 void set_frame_cursor_types (struct frame *, Lisp_Object);
+extern Lisp_Object format_frame_title (struct frame *, Lisp_Object);
 extern void syms_of_xdisp (void);
 extern void init_xdisp (void);
 extern Lisp_Object safe_eval (Lisp_Object);
@@ -3865,6 +3876,21 @@ extern void check_cons_list (void);
 INLINE void (check_cons_list) (void) { lisp_h_check_cons_list (); }
 #endif
 
+/* Defined in alloc.rs.  */
+
+/* The subsystems that the allocations are charged to while
+   `gc-allocation-accounting' is non-nil.  */
+enum alloc_context
+{
+  ALLOC_OTHER,
+  ALLOC_READER,
+  ALLOC_EVALUATOR,
+  ALLOC_FONTIFICATION,
+  ALLOC_CONTEXTS
+};
+extern void push_alloc_context (enum alloc_context);
+extern void gc_write_barrier_reset (void);
+
 /* Defined in gmalloc.c.  */
 #if !defined DOUG_LEA_MALLOC && !defined HYBRID_MALLOC && !defined SYSTEM_MALLOC
 extern size_t __malloc_extra_blocks;
@@ -3979,9 +4005,9 @@ extern void do_one_unbind (union specbinding *, bool, enum Set_Internal_Bind);
    should no longer be used.  */
 extern void run_hook (Lisp_Object);
 extern void run_hook_with_args_2 (Lisp_Object, Lisp_Object, Lisp_Object);
-extern Lisp_Object run_hook_with_args (ptrdiff_t nargs, Lisp_Object *args,
-				       Lisp_Object (*funcall)
-				       (ptrdiff_t nargs, Lisp_Object *args));
+extern Lisp_Object run_hook_with_funcall (ptrdiff_t nargs, Lisp_Object *args,
+					  Lisp_Object (*funcall)
+					  (ptrdiff_t nargs, Lisp_Object *args));
 extern Lisp_Object quit (void);
 INLINE _Noreturn void
 xsignal (Lisp_Object error_symbol, Lisp_Object data)
@@ -4185,6 +4211,7 @@ extern void restore_search_regs (void);
 extern void update_search_regs (ptrdiff_t oldstart,
                                 ptrdiff_t oldend, ptrdiff_t newend);
 extern void record_unwind_save_match_data (void);
+extern void save_search_regs (void);
 struct re_registers;
 extern struct re_pattern_buffer *compile_pattern (Lisp_Object,
 						  struct re_registers *,
@@ -4221,10 +4248,51 @@ extern void syms_of_search (void);
 extern void clear_regexp_cache (void);
 
 Lisp_Object looking_at_1 (Lisp_Object string, bool posix);
-Lisp_Object match_limit (Lisp_Object num, bool beginningp);
 Lisp_Object search_command (Lisp_Object string, Lisp_Object bound, Lisp_Object noerror, Lisp_Object count, int direction, int RE, bool posix);
 Lisp_Object string_match_1 (Lisp_Object regexp, Lisp_Object string, Lisp_Object start, bool posix);
 
+/* Defined in search.rs.  */
+extern void check_match_data_source (Lisp_Object);
+
+/* Defined in cmdhist.rs.  */
+extern void syms_of_cmdhist (void);
+
+/* Defined in environ.rs.  */
+extern void xputenv (const char *);
+extern void set_initial_environment (void);
+
+/* Defined in frametitle.rs.  */
+extern Lisp_Object frame_title_update (struct frame *);
+extern void syms_of_frametitle (void);
+
+/* Defined in hookprof.rs.  */
+extern void syms_of_hookprof (void);
+
+/* Defined in latency.rs.  */
+extern void syms_of_latency (void);
+
+/* Defined in lineedit.rs.  */
+extern bool read_line_edited (Lisp_Object, Lisp_Object, Lisp_Object *);
+extern void syms_of_lineedit (void);
+
+/* Defined in linestats.rs.  */
+extern void line_stats_before_insert (void);
+extern void line_stats_insert (ptrdiff_t, ptrdiff_t);
+extern void line_stats_delete (ptrdiff_t, ptrdiff_t);
+extern void syms_of_linestats (void);
+
+/* Defined in msgcat.rs.  */
+extern void syms_of_msgcat (void);
+
+/* Defined in vmotion.rs.  */
+extern bool simple_vertical_motion (EMACS_INT, Lisp_Object, Lisp_Object,
+				    EMACS_INT *);
+
+/* Defined in wordindex.rs.  */
+struct word_index;
+extern void free_word_index (struct word_index *);
+extern void word_index_invalidate (struct buffer *, ptrdiff_t, ptrdiff_t);
+
 /* Defined in minibuf.c.  */
 
 extern Lisp_Object Vminibuffer_list;
@@ -4326,6 +4394,10 @@ extern bool noninteractive;
 /* True means remove site-lisp directories from load-path.  */
 extern bool no_site_lisp;
 
+/* True means to record the stores of the generational collector's
+   write barrier.  */
+extern bool experimental_gc;
+
 /* True means put details like time stamps into builds.  */
 extern bool build_details;
 
@@ -4383,9 +4455,7 @@ extern void setup_process_coding_systems (Lisp_Object);
 extern CHILD_SETUP_TYPE child_setup (int, int, int, char **, bool, Lisp_Object);
 extern void init_callproc_1 (void);
 extern void init_callproc (void);
-extern void set_initial_environment (void);
 extern void syms_of_callproc (void);
-extern Lisp_Object call_process (ptrdiff_t, Lisp_Object *, int, ptrdiff_t);
 
 /* Defined in doc.c.  */
 enum text_quoting_style
@@ -4459,6 +4529,9 @@ extern void reset_all_sys_modes (void);
 extern void child_setup_tty (int);
 extern void setup_pty (int);
 extern int set_window_size (int, int, int);
+extern int rust_allocate_pty (char *, size_t);
+extern Lisp_Object accumulate_process_output (Lisp_Object, Lisp_Object, bool);
+extern bool flush_throttled_process_output (void);
 extern EMACS_INT get_random (void);
 extern void seed_random (void *, ptrdiff_t);
 extern void init_random (void);
@@ -4631,8 +4704,6 @@ lispstpcpy (char *dest, Lisp_Object string)
   return dest + len;
 }
 
-extern void xputenv (const char *);
-
 extern char *egetenv_internal (const char *, ptrdiff_t);
 
 INLINE char *
@@ -4901,6 +4972,10 @@ maybe_gc (void)
 Lisp_Object funcall_lambda (Lisp_Object, ptrdiff_t, Lisp_Object *);
 
 bool backtrace_debug_on_exit (union specbinding *pdl);
+Lisp_Object backtrace_function (union specbinding *pdl);
+bool backtrace_p (union specbinding *pdl);
+union specbinding *backtrace_top (void);
+union specbinding *backtrace_next (union specbinding *pdl);
 
 void do_debug_on_call (Lisp_Object code, ptrdiff_t count);
 
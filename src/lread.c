@@ -2037,10 +2037,13 @@ readevalloop (Lisp_Object readcharfun,
       unbind_to (count1, Qnil);
 
       /* Now eval what we just read.  */
+      count1 = SPECPDL_INDEX ();
+      push_alloc_context (ALLOC_EVALUATOR);
       if (!NILP (macroexpand))
         val = readevalloop_eager_expand_eval (val, macroexpand);
       else
         val = eval_sub (val);
+      unbind_to (count1, Qnil);
 
       if (printflag)
 	{
@@ -2136,7 +2139,9 @@ Lisp_Object
 read_internal_start (Lisp_Object stream, Lisp_Object start, Lisp_Object end)
 {
   Lisp_Object retval;
+  ptrdiff_t count = SPECPDL_INDEX ();
 
+  push_alloc_context (ALLOC_READER);
   readchar_count = 0;
   new_backquote_flag = force_new_style_backquotes;
   /* We can get called from readevalloop which may have set these
@@ -2185,7 +2190,7 @@ read_internal_start (Lisp_Object stream, Lisp_Object start, Lisp_Object end)
   if (HASH_TABLE_P (read_objects_completed)
       && XHASH_TABLE (read_objects_completed)->count > 0)
     read_objects_completed = Qnil;
-  return retval;
+  return unbind_to (count, retval);
 }
 
 
@@ -190,6 +190,14 @@ read_minibuf_noninteractive (Lisp_Object map, Lisp_Object initial,
   fwrite_unlocked (SDATA (prompt), 1, SBYTES (prompt), stdout);
   fflush_unlocked (stdout);
 
+  /* Let the user edit the line if reading from a terminal.  */
+  if (!hide_char && read_line_edited (prompt, histvar, &val))
+    {
+      if (NILP (val))
+	error ("Error reading from stdin");
+      goto parse;
+    }
+
   val = Qnil;
   size = 100;
   len = 0;
@@ -234,6 +242,7 @@ read_minibuf_noninteractive (Lisp_Object map, Lisp_Object initial,
       error ("Error reading from stdin");
     }
 
+ parse:
   /* If Lisp form desired instead of string, parse it.  */
   if (expflag)
     val = string_to_object (val, CONSP (defalt) ? XCAR (defalt) : defalt);
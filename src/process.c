@@ -256,7 +256,8 @@ static int process_output_delay_count;
 static bool process_output_skip;
 
 static void start_process_unwind (Lisp_Object);
-static void create_process (Lisp_Object, char **, Lisp_Object);
+static void create_process (Lisp_Object, char **, Lisp_Object,
+			    struct sandbox *);
 #ifdef USABLE_SIGIO
 static bool keyboard_bit_set (fd_set *);
 #endif
@@ -785,65 +786,17 @@ enum { PTY_NAME_SIZE = 24 };
 
 /* Open an available pty, returning a file descriptor.
    Store into PTY_NAME the file name of the terminal corresponding to the pty.
-   Return -1 on failure.  */
+   Return -1 on failure.  The work is done by rust_allocate_pty in
+   rust_src/src/pty.rs.  */
 
 static int
 allocate_pty (char pty_name[PTY_NAME_SIZE])
 {
 #ifdef HAVE_PTYS
-  int fd;
-
-#ifdef PTY_ITERATION
-  PTY_ITERATION
-#else
-  register int c, i;
-  for (c = FIRST_PTY_LETTER; c <= 'z'; c++)
-    for (i = 0; i < 16; i++)
-#endif
-      {
-#ifdef PTY_NAME_SPRINTF
-	PTY_NAME_SPRINTF
+  return rust_allocate_pty (pty_name, PTY_NAME_SIZE);
 #else
-	sprintf (pty_name, "/dev/pty%c%x", c, i);
-#endif /* no PTY_NAME_SPRINTF */
-
-#ifdef PTY_OPEN
-	PTY_OPEN;
-#else /* no PTY_OPEN */
-	fd = emacs_open (pty_name, O_RDWR | O_NONBLOCK, 0);
-#endif /* no PTY_OPEN */
-
-	if (fd >= 0)
-	  {
-#ifdef PTY_TTY_NAME_SPRINTF
-	    PTY_TTY_NAME_SPRINTF
-#else
-	    sprintf (pty_name, "/dev/tty%c%x", c, i);
-#endif /* no PTY_TTY_NAME_SPRINTF */
-
-	    /* Set FD's close-on-exec flag.  This is needed even if
-	       PT_OPEN calls posix_openpt with O_CLOEXEC, since POSIX
-	       doesn't require support for that combination.
-	       Do this after PTY_TTY_NAME_SPRINTF, which on some platforms
-	       doesn't work if the close-on-exec flag is set (Bug#20555).
-	       Multithreaded platforms where posix_openpt ignores
-	       O_CLOEXEC (or where PTY_OPEN doesn't call posix_openpt)
-	       have a race condition between the PTY_OPEN and here.  */
-	    fcntl (fd, F_SETFD, FD_CLOEXEC);
-
-	    /* Check to make certain that both sides are available.
-	       This avoids a nasty yet stupid bug in rlogins.  */
-	    if (faccessat (AT_FDCWD, pty_name, R_OK | W_OK, AT_EACCESS) != 0)
-	      {
-		emacs_close (fd);
-		continue;
-	      }
-	    setup_pty (fd);
-	    return fd;
-	  }
-      }
-#endif /* HAVE_PTYS */
   return -1;
+#endif /* HAVE_PTYS */
 }
 
 /* Allocate basically initialized process.  */
@@ -1069,29 +1022,6 @@ If THREAD is nil, the process is unlocked.  */)
   return thread;
 }
 
-DEFUN ("set-process-window-size", Fset_process_window_size,
-       Sset_process_window_size, 3, 3, 0,
-       doc: /* Tell PROCESS that it has logical window size WIDTH by HEIGHT.
-Value is t if PROCESS was successfully told about the window size,
-nil otherwise.  */)
-  (Lisp_Object process, Lisp_Object height, Lisp_Object width)
-{
-  CHECK_PROCESS (process);
-
-  /* All known platforms store window sizes as 'unsigned short'.  */
-  CHECK_RANGED_INTEGER (height, 0, USHRT_MAX);
-  CHECK_RANGED_INTEGER (width, 0, USHRT_MAX);
-
-  if (NETCONN_P (process)
-      || XPROCESS (process)->infd < 0
-      || (set_window_size (XPROCESS (process)->infd,
-			   XINT (height), XINT (width))
-	  < 0))
-    return Qnil;
-  else
-    return Qt;
-}
-
 DEFUN ("set-process-inherit-coding-system-flag",
        Fset_process_inherit_coding_system_flag,
        Sset_process_inherit_coding_system_flag, 2, 2, 0,
@@ -1297,6 +1227,15 @@ to use a pty, or nil to use the default specified through
 to the standard error of subprocess.  Specifying this implies
 `:connection-type' is set to `pipe'.
 
+:sandbox SANDBOX -- Run the program with restricted access to files
+and to the network.  SANDBOX is a plist: `:read DIRS' lets the program
+read and execute only the files in the directories of the list DIRS,
+or everywhere if DIRS is t, the default; `:write DIRS' likewise lets
+it write files only in DIRS, and by default nowhere; and `:network'
+non-nil lets it use the network, which it can't by default.  The
+program can always read and write /dev/null.  An error is signaled if
+the sandbox can't be set up; see `process-sandbox-supported-p'.
+
 usage: (make-process &rest ARGS)  */)
   (ptrdiff_t nargs, Lisp_Object *args)
 {
@@ -1555,7 +1494,16 @@ usage: (make-process &rest ARGS)  */)
 	  tem = XCDR (tem);
 	}
 
-      create_process (proc, new_argv, current_dir);
+      struct sandbox *sandbox = NULL;
+      Lisp_Object sandbox_spec = Fplist_get (contact, QCsandbox);
+      if (!NILP (sandbox_spec))
+	{
+	  /* This may signal an error, and may replace NEW_ARGV.  */
+	  sandbox = sandbox_prepare (sandbox_spec, &new_argv);
+	  record_unwind_protect_ptr (sandbox_free, sandbox);
+	}
+
+      create_process (proc, new_argv, current_dir, sandbox);
     }
   else
     create_pty (proc);
@@ -1606,7 +1554,8 @@ enum
 verify (PROCESS_OPEN_FDS == EXEC_MONITOR_OUTPUT + 1);
 
 static void
-create_process (Lisp_Object process, char **new_argv, Lisp_Object current_dir)
+create_process (Lisp_Object process, char **new_argv, Lisp_Object current_dir,
+		struct sandbox *sandbox)
 {
   struct Lisp_Process *p = XPROCESS (process);
   int inchannel, outchannel;
@@ -1702,6 +1651,7 @@ create_process (Lisp_Object process, char **new_argv, Lisp_Object current_dir)
   int volatile forkout_volatile = forkout;
   int volatile forkerr_volatile = forkerr;
   struct Lisp_Process *p_volatile = p;
+  struct sandbox *volatile sandbox_volatile = sandbox;
 
 #ifdef DARWIN_OS
   /* Darwin doesn't let us run setsid after a vfork, so use fork when
@@ -1726,6 +1676,7 @@ create_process (Lisp_Object process, char **new_argv, Lisp_Object current_dir)
   forkout = forkout_volatile;
   forkerr = forkerr_volatile;
   p = p_volatile;
+  sandbox = sandbox_volatile;
 
   pty_flag = p->pty_flag;
 
@@ -1832,6 +1783,13 @@ create_process (Lisp_Object process, char **new_argv, Lisp_Object current_dir)
 
       if (forkerr < 0)
 	forkerr = forkout;
+
+      if (sandbox && !sandbox_enter (sandbox))
+	{
+	  emacs_perror ("sandbox");
+	  _exit (EXIT_CANCELED);
+	}
+
 #ifdef WINDOWSNT
       pid = child_setup (forkin, forkout, forkerr, new_argv, 1, current_dir);
 #else  /* not WINDOWSNT */
@@ -4698,6 +4656,13 @@ wait_reading_process_output (intmax_t time_limit, int nsecs, int read_kbd,
       if (! NILP (wait_for_cell) && ! NILP (XCAR (wait_for_cell)))
 	break;
 
+      /* Deliver some of the output held back from line-buffered
+	 processes that produce it faster than their filters consume
+	 it, resuming reading from them once they have caught up.  */
+      if (NILP (wait_for_cell) && flush_throttled_process_output ()
+	  && got_some_output < 1)
+	got_some_output = 1;
+
 #if defined HAVE_GETADDRINFO_A || defined HAVE_GNUTLS
       {
 	Lisp_Object process_list_head, aproc;
@@ -5551,39 +5516,7 @@ read_and_dispose_of_process_output (struct Lisp_Process *p, char *chars,
 				    ssize_t nbytes,
 				    struct coding_system *coding)
 {
-  Lisp_Object outstream = p->filter;
   Lisp_Object text;
-  bool outer_running_asynch_code = running_asynch_code;
-  int waiting = waiting_for_user_input_p;
-
-#if 0
-  Lisp_Object obuffer, okeymap;
-  XSETBUFFER (obuffer, current_buffer);
-  okeymap = BVAR (current_buffer, keymap);
-#endif
-
-  /* We inhibit quit here instead of just catching it so that
-     hitting ^G when a filter happens to be running won't screw
-     it up.  */
-  specbind (Qinhibit_quit, Qt);
-  specbind (Qlast_nonmenu_event, Qt);
-
-  /* In case we get recursively called,
-     and we already saved the match data nonrecursively,
-     save the same match data in safely recursive fashion.  */
-  if (outer_running_asynch_code)
-    {
-      Lisp_Object tem;
-      /* Don't clobber the CURRENT match data, either!  */
-      tem = Fmatch_data (Qnil, Qnil, Qnil);
-      restore_search_regs ();
-      record_unwind_save_match_data ();
-      Fset_match_data (tem, Qt);
-    }
-
-  /* For speed, if a search happens within this code,
-     save the match data in a special nonrecursive fashion.  */
-  running_asynch_code = 1;
 
   decode_coding_c_string (coding, (unsigned char *) chars, nbytes, Qt);
   text = coding->dst_object;
@@ -5622,14 +5555,59 @@ read_and_dispose_of_process_output (struct Lisp_Process *p, char *chars,
 	      coding->carryover_bytes);
       p->decoding_carryover = coding->carryover_bytes;
     }
+
+  /* With line buffering, only pass complete lines to the filter and
+     keep the rest for later.  */
+  if (p->line_buffering_max > 0 || !NILP (p->pending_output))
+    text = accumulate_process_output (make_lisp_proc (p), text,
+				      coding->mode & CODING_MODE_LAST_BLOCK);
+
   if (SBYTES (text) > 0)
-    /* FIXME: It's wrong to wrap or not based on debug-on-error, and
-       sometimes it's simply wrong to wrap (e.g. when called from
-       accept-process-output).  */
-    internal_condition_case_1 (read_process_output_call,
-			       list3 (outstream, make_lisp_proc (p), text),
-			       !NILP (Vdebug_on_error) ? Qnil : Qerror,
-			       read_process_output_error_handler);
+    deliver_process_output (p, text);
+}
+
+/* Pass TEXT, which was read from process P, to P's filter.  */
+
+void
+deliver_process_output (struct Lisp_Process *p, Lisp_Object text)
+{
+  Lisp_Object outstream = p->filter;
+  bool outer_running_asynch_code = running_asynch_code;
+  int waiting = waiting_for_user_input_p;
+  ptrdiff_t count = SPECPDL_INDEX ();
+
+  record_unwind_current_buffer ();
+
+  /* We inhibit quit here instead of just catching it so that
+     hitting ^G when a filter happens to be running won't screw
+     it up.  */
+  specbind (Qinhibit_quit, Qt);
+  specbind (Qlast_nonmenu_event, Qt);
+
+  /* In case we get recursively called,
+     and we already saved the match data nonrecursively,
+     save the same match data in safely recursive fashion.  */
+  if (outer_running_asynch_code)
+    {
+      Lisp_Object tem;
+      /* Don't clobber the CURRENT match data, either!  */
+      tem = Fmatch_data (Qnil, Qnil, Qnil);
+      restore_search_regs ();
+      record_unwind_save_match_data ();
+      Fset_match_data (tem, Qt);
+    }
+
+  /* For speed, if a search happens within this code,
+     save the match data in a special nonrecursive fashion.  */
+  running_asynch_code = 1;
+
+  /* FIXME: It's wrong to wrap or not based on debug-on-error, and
+     sometimes it's simply wrong to wrap (e.g. when called from
+     accept-process-output).  */
+  internal_condition_case_1 (read_process_output_call,
+			     list3 (outstream, make_lisp_proc (p), text),
+			     !NILP (Vdebug_on_error) ? Qnil : Qerror,
+			     read_process_output_error_handler);
 
   /* If we saved the match data nonrecursively, restore it now.  */
   restore_search_regs ();
@@ -5639,17 +5617,15 @@ read_and_dispose_of_process_output (struct Lisp_Process *p, char *chars,
      when we were called, in case the filter clobbered it.  */
   waiting_for_user_input_p = waiting;
 
-#if 0 /* Call record_asynch_buffer_change unconditionally,
-	 because we might have changed minor modes or other things
-	 that affect key bindings.  */
-  if (! EQ (Fcurrent_buffer (), obuffer)
-      || ! EQ (current_buffer->keymap, okeymap))
-#endif
-    /* But do it only if the caller is actually going to read events.
-       Otherwise there's no need to make him wake up, and it could
-       cause trouble (for example it would make sit_for return).  */
-    if (waiting_for_user_input_p == -1)
-      record_asynch_buffer_change ();
+  /* Call record_asynch_buffer_change unconditionally, because we
+     might have changed minor modes or other things that affect key
+     bindings.  But do it only if the caller is actually going to read
+     events.  Otherwise there's no need to make him wake up, and it
+     could cause trouble (for example it would make sit_for return).  */
+  if (waiting_for_user_input_p == -1)
+    record_asynch_buffer_change ();
+
+  unbind_to (count, Qnil);
 }
 
 DEFUN ("internal-default-process-filter", Finternal_default_process_filter,
@@ -7417,6 +7393,7 @@ syms_of_process (void)
   DEFSYM (QCcommand, ":command");
   DEFSYM (QCconnection_type, ":connection-type");
   DEFSYM (QCstderr, ":stderr");
+  DEFSYM (QCsandbox, ":sandbox");
   DEFSYM (Qpty, "pty");
   DEFSYM (Qpipe, "pipe");
 
@@ -7507,7 +7484,6 @@ returns non-`nil'.  */);
 
   defsubr (&Sdelete_process);
   defsubr (&Sset_process_thread);
-  defsubr (&Sset_process_window_size);
   defsubr (&Sset_process_inherit_coding_system_flag);
   defsubr (&Sprocess_contact);
   defsubr (&Smake_process);
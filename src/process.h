@@ -118,6 +118,10 @@ struct Lisp_Process
     /* The thread a process is linked to, or nil for any thread.  */
     Lisp_Object thread;
 
+    /* Decoded output held back from the filter when line buffering,
+       or nil.  */
+    Lisp_Object pending_output;
+
     /* After this point, there are no Lisp_Objects any more.  */
     /* alloc.c assumes that `pid' is the first such non-Lisp slot.  */
 
@@ -180,6 +184,12 @@ struct Lisp_Process
     int port;
     /* The socket type. */
     int socktype;
+    /* If positive, the filter receives output in complete lines, at
+       most this many bytes at a time.  Zero disables line buffering.  */
+    ptrdiff_t line_buffering_max;
+    /* True if reading from this process is suspended until its pending
+       output has been delivered.  */
+    bool_bf output_throttled : 1;
 
 #ifdef HAVE_GETADDRINFO_A
     /* Whether the socket is waiting for response from an asynchronous
@@ -298,6 +308,13 @@ extern Lisp_Object remove_slash_colon (Lisp_Object);
 
 extern void update_processes_for_thread_death (Lisp_Object);
 
+/* Defined in sandbox.rs.  */
+
+struct sandbox;
+extern struct sandbox *sandbox_prepare (Lisp_Object, char ***);
+extern bool sandbox_enter (struct sandbox *);
+extern void sandbox_free (void *);
+
 INLINE_HEADER_END
 
 Lisp_Object
@@ -313,5 +330,6 @@ send_process (Lisp_Object proc, const char *buf, ptrdiff_t len,
 void pset_filter (struct Lisp_Process *, Lisp_Object);
 void pset_sentinel (struct Lisp_Process *, Lisp_Object);
 void set_process_filter_masks (struct Lisp_Process *);
+void deliver_process_output (struct Lisp_Process *, Lisp_Object);
 
 #endif /* EMACS_PROCESS_H */
@@ -82,7 +82,6 @@ static struct regexp_cache *searchbuf_head;
 /* static Lisp_Object last_thing_searched; */
 
 static void set_search_regs (ptrdiff_t, ptrdiff_t);
-static void save_search_regs (void);
 static EMACS_INT simple_search (EMACS_INT, unsigned char *, ptrdiff_t,
 				ptrdiff_t, Lisp_Object, ptrdiff_t, ptrdiff_t,
                                 ptrdiff_t, ptrdiff_t);
@@ -2186,6 +2185,7 @@ since only regular expressions have distinguished subexpressions.  */)
 
   if (search_regs.num_regs <= 0)
     error ("`replace-match' called before any match found");
+  check_match_data_source (string);
 
   if (NILP (subexp))
     sub = 0;
@@ -2575,260 +2575,6 @@ since only regular expressions have distinguished subexpressions.  */)
   return Qnil;
 }
 
-Lisp_Object
-match_limit (Lisp_Object num, bool beginningp)
-{
-  EMACS_INT n;
-
-  CHECK_NUMBER (num);
-  n = XINT (num);
-  if (n < 0)
-    args_out_of_range (num, make_number (0));
-  if (search_regs.num_regs <= 0)
-    error ("No match data, because no search succeeded");
-  if (n >= search_regs.num_regs
-      || search_regs.start[n] < 0)
-    return Qnil;
-  return (make_number ((beginningp) ? search_regs.start[n]
-		                    : search_regs.end[n]));
-}
-
-DEFUN ("match-data", Fmatch_data, Smatch_data, 0, 3, 0,
-       doc: /* Return a list describing what the last search matched.
-Element 2N is `(match-beginning N)'; element 2N + 1 is `(match-end N)'.
-All the elements are markers or nil (nil if the Nth pair didn't match)
-if the last match was on a buffer; integers or nil if a string was matched.
-Use `set-match-data' to reinstate the data in this list.
-
-If INTEGERS (the optional first argument) is non-nil, always use
-integers (rather than markers) to represent buffer positions.  In
-this case, and if the last match was in a buffer, the buffer will get
-stored as one additional element at the end of the list.
-
-If REUSE is a list, reuse it as part of the value.  If REUSE is long
-enough to hold all the values, and if INTEGERS is non-nil, no consing
-is done.
-
-If optional third arg RESEAT is non-nil, any previous markers on the
-REUSE list will be modified to point to nowhere.
-
-Return value is undefined if the last search failed.  */)
-  (Lisp_Object integers, Lisp_Object reuse, Lisp_Object reseat)
-{
-  Lisp_Object tail, prev;
-  Lisp_Object *data;
-  ptrdiff_t i, len;
-
-  if (!NILP (reseat))
-    for (tail = reuse; CONSP (tail); tail = XCDR (tail))
-      if (MARKERP (XCAR (tail)))
-	{
-	  unchain_marker (XMARKER (XCAR (tail)));
-	  XSETCAR (tail, Qnil);
-	}
-
-  if (NILP (last_thing_searched))
-    return Qnil;
-
-  prev = Qnil;
-
-  USE_SAFE_ALLOCA;
-  SAFE_NALLOCA (data, 1, 2 * search_regs.num_regs + 1);
-
-  len = 0;
-  for (i = 0; i < search_regs.num_regs; i++)
-    {
-      ptrdiff_t start = search_regs.start[i];
-      if (start >= 0)
-	{
-	  if (EQ (last_thing_searched, Qt)
-	      || ! NILP (integers))
-	    {
-	      XSETFASTINT (data[2 * i], start);
-	      XSETFASTINT (data[2 * i + 1], search_regs.end[i]);
-	    }
-	  else if (BUFFERP (last_thing_searched))
-	    {
-	      data[2 * i] = Fmake_marker ();
-	      Fset_marker (data[2 * i],
-			   make_number (start),
-			   last_thing_searched);
-	      data[2 * i + 1] = Fmake_marker ();
-	      Fset_marker (data[2 * i + 1],
-			   make_number (search_regs.end[i]),
-			   last_thing_searched);
-	    }
-	  else
-	    /* last_thing_searched must always be Qt, a buffer, or Qnil.  */
-	    emacs_abort ();
-
-	  len = 2 * i + 2;
-	}
-      else
-	data[2 * i] = data[2 * i + 1] = Qnil;
-    }
-
-  if (BUFFERP (last_thing_searched) && !NILP (integers))
-    {
-      data[len] = last_thing_searched;
-      len++;
-    }
-
-  /* If REUSE is not usable, cons up the values and return them.  */
-  if (! CONSP (reuse))
-    reuse = Flist (len, data);
-  else
-    {
-      /* If REUSE is a list, store as many value elements as will fit
-	 into the elements of REUSE.  */
-      for (i = 0, tail = reuse; CONSP (tail);
-	   i++, tail = XCDR (tail))
-	{
-	  if (i < len)
-	    XSETCAR (tail, data[i]);
-	  else
-	    XSETCAR (tail, Qnil);
-	  prev = tail;
-	}
-
-      /* If we couldn't fit all value elements into REUSE,
-	 cons up the rest of them and add them to the end of REUSE.  */
-      if (i < len)
-	XSETCDR (prev, Flist (len - i, data + i));
-    }
-
-  SAFE_FREE ();
-  return reuse;
-}
-
-/* We used to have an internal use variant of `reseat' described as:
-
-      If RESEAT is `evaporate', put the markers back on the free list
-      immediately.  No other references to the markers must exist in this
-      case, so it is used only internally on the unwind stack and
-      save-match-data from Lisp.
-
-   But it was ill-conceived: those supposedly-internal markers get exposed via
-   the undo-list, so freeing them here is unsafe.  */
-
-DEFUN ("set-match-data", Fset_match_data, Sset_match_data, 1, 2, 0,
-       doc: /* Set internal data on last search match from elements of LIST.
-LIST should have been created by calling `match-data' previously.
-
-If optional arg RESEAT is non-nil, make markers on LIST point nowhere.  */)
-  (register Lisp_Object list, Lisp_Object reseat)
-{
-  ptrdiff_t i;
-  register Lisp_Object marker;
-
-  if (running_asynch_code)
-    save_search_regs ();
-
-  CHECK_LIST (list);
-
-  /* Unless we find a marker with a buffer or an explicit buffer
-     in LIST, assume that this match data came from a string.  */
-  last_thing_searched = Qt;
-
-  /* Allocate registers if they don't already exist.  */
-  {
-    EMACS_INT length = XFASTINT (Flength (list)) / 2;
-
-    if (length > search_regs.num_regs)
-      {
-	ptrdiff_t num_regs = search_regs.num_regs;
-	if (PTRDIFF_MAX < length)
-	  memory_full (SIZE_MAX);
-	search_regs.start =
-	  xpalloc (search_regs.start, &num_regs, length - num_regs,
-		   min (PTRDIFF_MAX, UINT_MAX), sizeof (regoff_t));
-	search_regs.end =
-	  xrealloc (search_regs.end, num_regs * sizeof (regoff_t));
-
-	for (i = search_regs.num_regs; i < num_regs; i++)
-	  search_regs.start[i] = -1;
-
-	search_regs.num_regs = num_regs;
-      }
-
-    for (i = 0; CONSP (list); i++)
-      {
-	marker = XCAR (list);
-	if (BUFFERP (marker))
-	  {
-	    last_thing_searched = marker;
-	    break;
-	  }
-	if (i >= length)
-	  break;
-	if (NILP (marker))
-	  {
-	    search_regs.start[i] = -1;
-	    list = XCDR (list);
-	  }
-	else
-	  {
-	    Lisp_Object from;
-	    Lisp_Object m;
-
-	    m = marker;
-	    if (MARKERP (marker))
-	      {
-		if (XMARKER (marker)->buffer == 0)
-		  XSETFASTINT (marker, 0);
-		else
-		  XSETBUFFER (last_thing_searched, XMARKER (marker)->buffer);
-	      }
-
-	    CHECK_NUMBER_COERCE_MARKER (marker);
-	    from = marker;
-
-	    if (!NILP (reseat) && MARKERP (m))
-	      {
-		unchain_marker (XMARKER (m));
-		XSETCAR (list, Qnil);
-	      }
-
-	    if ((list = XCDR (list), !CONSP (list)))
-	      break;
-
-	    m = marker = XCAR (list);
-
-	    if (MARKERP (marker) && XMARKER (marker)->buffer == 0)
-	      XSETFASTINT (marker, 0);
-
-	    CHECK_NUMBER_COERCE_MARKER (marker);
-	    if ((XINT (from) < 0
-		 ? TYPE_MINIMUM (regoff_t) <= XINT (from)
-		 : XINT (from) <= TYPE_MAXIMUM (regoff_t))
-		&& (XINT (marker) < 0
-		    ? TYPE_MINIMUM (regoff_t) <= XINT (marker)
-		    : XINT (marker) <= TYPE_MAXIMUM (regoff_t)))
-	      {
-		search_regs.start[i] = XINT (from);
-		search_regs.end[i] = XINT (marker);
-	      }
-	    else
-	      {
-		search_regs.start[i] = -1;
-	      }
-
-	    if (!NILP (reseat) && MARKERP (m))
-	      {
-		unchain_marker (XMARKER (m));
-		XSETCAR (list, Qnil);
-	      }
-	  }
-	list = XCDR (list);
-      }
-
-    for (; i < search_regs.num_regs; i++)
-      search_regs.start[i] = -1;
-  }
-
-  return Qnil;
-}
-
 /* If true the match data have been saved in saved_search_regs
    during the execution of a sentinel or filter. */
 /* static bool search_regs_saved; */
@@ -2837,7 +2583,7 @@ If optional arg RESEAT is non-nil, make markers on LIST point nowhere.  */)
 
 /* Called from Flooking_at, Fstring_match, search_buffer, Fstore_match_data
    if asynchronous code (filter or sentinel) is running. */
-static void
+void
 save_search_regs (void)
 {
   if (!search_regs_saved)
@@ -3200,8 +2946,6 @@ is to bind it with `let' around a small expression.  */);
   Vinhibit_changing_match_data = Qnil;
 
   defsubr (&Sreplace_match);
-  defsubr (&Smatch_data);
-  defsubr (&Sset_match_data);
   defsubr (&Sregexp_quote);
   defsubr (&Snewline_cache_check);
 }
@@ -2863,6 +2863,29 @@ scan_lists (EMACS_INT from, EMACS_INT count, EMACS_INT depth, bool sexpflag)
 	    make_number (last_good), make_number (from));
 }
 
+static Lisp_Object
+scan_sexps_1 (Lisp_Object from, Lisp_Object count)
+{
+  return scan_lists (XINT (from), XINT (count), 0, true);
+}
+
+static Lisp_Object
+scan_sexps_lose (Lisp_Object err)
+{
+  return Qt;
+}
+
+/* Scan from FROM by COUNT balanced expressions, like `scan-sexps', but
+   return t instead of signaling a `scan-error'.  */
+
+Lisp_Object
+scan_sexps_noerror (EMACS_INT from, EMACS_INT count)
+{
+  return internal_condition_case_2 (scan_sexps_1, make_number (from),
+				    make_number (count), Qscan_error,
+				    scan_sexps_lose);
+}
+
 DEFUN ("scan-sexps", Fscan_sexps, Sscan_sexps, 2, 2, 0,
        doc: /* Scan from character number FROM by COUNT balanced expressions.
 If COUNT is negative, scan backwards.
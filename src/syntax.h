@@ -230,6 +230,7 @@ SETUP_BUFFER_SYNTAX_TABLE (void)
 }
 
 extern ptrdiff_t scan_words (ptrdiff_t, EMACS_INT);
+extern Lisp_Object scan_sexps_noerror (EMACS_INT, EMACS_INT);
 extern void SETUP_SYNTAX_TABLE_FOR_OBJECT (Lisp_Object, ptrdiff_t, ptrdiff_t);
 extern void check_syntax_table (Lisp_Object obj);
 
@@ -481,100 +481,6 @@ child_status_changed (pid_t child, int *status, int options)
   return get_child_status (child, status, WNOHANG | options, 0);
 }
 
-
-/*  Set up the terminal at the other end of a pseudo-terminal that
-    we will be controlling an inferior through.
-    It should not echo or do line-editing, since that is done
-    in Emacs.  No padding needed for insertion into an Emacs buffer.  */
-
-void
-child_setup_tty (int out)
-{
-#ifndef WINDOWSNT
-  struct emacs_tty s;
-
-  emacs_get_tty (out, &s);
-  s.main.c_oflag |= OPOST;	/* Enable output postprocessing */
-  s.main.c_oflag &= ~ONLCR;	/* Disable map of NL to CR-NL on output */
-#ifdef NLDLY
-  /* https://lists.gnu.org/r/emacs-devel/2008-05/msg00406.html
-     Some versions of GNU Hurd do not have FFDLY?  */
-#ifdef FFDLY
-  s.main.c_oflag &= ~(NLDLY|CRDLY|TABDLY|BSDLY|VTDLY|FFDLY);
-  				/* No output delays */
-#else
-  s.main.c_oflag &= ~(NLDLY|CRDLY|TABDLY|BSDLY|VTDLY);
-  				/* No output delays */
-#endif
-#endif
-  s.main.c_lflag &= ~ECHO;	/* Disable echo */
-  s.main.c_lflag |= ISIG;	/* Enable signals */
-#ifdef IUCLC
-  s.main.c_iflag &= ~IUCLC;	/* Disable downcasing on input.  */
-#endif
-#ifdef ISTRIP
-  s.main.c_iflag &= ~ISTRIP;	/* don't strip 8th bit on input */
-#endif
-#ifdef OLCUC
-  s.main.c_oflag &= ~OLCUC;	/* Disable upcasing on output.  */
-#endif
-  s.main.c_oflag &= ~TAB3;	/* Disable tab expansion */
-  s.main.c_cflag = (s.main.c_cflag & ~CSIZE) | CS8; /* Don't strip 8th bit */
-  s.main.c_cc[VERASE] = CDISABLE;	/* disable erase processing */
-  s.main.c_cc[VKILL] = CDISABLE;	/* disable kill processing */
-
-#ifdef HPUX
-  s.main.c_cflag = (s.main.c_cflag & ~CBAUD) | B9600; /* baud rate sanity */
-#endif /* HPUX */
-
-#ifdef SIGNALS_VIA_CHARACTERS
-  /* the QUIT and INTR character are used in process_send_signal
-     so set them here to something useful.  */
-  if (s.main.c_cc[VQUIT] == CDISABLE)
-    s.main.c_cc[VQUIT] = '\\'&037;	/* Control-\ */
-  if (s.main.c_cc[VINTR] == CDISABLE)
-    s.main.c_cc[VINTR] = 'C'&037;	/* Control-C */
-#endif /* not SIGNALS_VIA_CHARACTERS */
-
-#ifdef AIX
-  /* Also, PTY overloads NUL and BREAK.
-     don't ignore break, but don't signal either, so it looks like NUL.  */
-  s.main.c_iflag &= ~IGNBRK;
-  s.main.c_iflag &= ~BRKINT;
-  /* rms: Formerly it set s.main.c_cc[VINTR] to 0377 here
-     unconditionally.  Then a SIGNALS_VIA_CHARACTERS conditional
-     would force it to 0377.  That looks like duplicated code.  */
-  s.main.c_cflag = (s.main.c_cflag & ~CBAUD) | B9600; /* baud rate sanity */
-#endif /* AIX */
-
-  /* We originally enabled ICANON (and set VEOF to 04), and then had
-     process.c send additional EOF chars to flush the output when faced
-     with long lines, but this leads to weird effects when the
-     subprocess has disabled ICANON and ends up seeing those spurious
-     extra EOFs.  So we don't send EOFs any more in
-     process.c:send_process.  First we tried to disable ICANON by
-     default, so if a subsprocess sets up ICANON, it's his problem (or
-     the Elisp package that talks to it) to deal with lines that are
-     too long.  But this disables some features, such as the ability
-     to send EOF signals.  So we re-enabled ICANON but there is no
-     more "send eof to flush" going on (which is wrong and unportable
-     in itself).  The correct way to handle too much output is to
-     buffer what could not be written and then write it again when
-     select returns ok for writing.  This has it own set of
-     problems.  Write is now asynchronous, is that a problem?  How much
-     do we buffer, and what do we do when that limit is reached?  */
-
-  s.main.c_lflag |= ICANON;	/* Enable line editing and eof processing */
-  s.main.c_cc[VEOF] = 'D'&037;	/* Control-D */
-#if 0	    /* These settings only apply to non-ICANON mode. */
-  s.main.c_cc[VMIN] = 1;
-  s.main.c_cc[VTIME] = 0;
-#endif
-
-  emacs_set_tty (out, &s, 0);
-#endif /* not WINDOWSNT */
-}
-
 
 /* Record a signal code and the action for it.  */
 struct save_signal
@@ -1362,39 +1268,6 @@ get_tty_size (int fd, int *widthp, int *heightp)
 #endif
 }
 
-/* Set the logical window size associated with descriptor FD
-   to HEIGHT and WIDTH.  This is used mainly with ptys.
-   Return a negative value on failure.  */
-
-int
-set_window_size (int fd, int height, int width)
-{
-#ifdef TIOCSWINSZ
-
-  /* BSD-style.  */
-  struct winsize size;
-  size.ws_row = height;
-  size.ws_col = width;
-
-  return ioctl (fd, TIOCSWINSZ, &size);
-
-#else
-#ifdef TIOCSSIZE
-
-  /* SunOS - style.  */
-  struct ttysize size;
-  size.ts_lines = height;
-  size.ts_cols = width;
-
-  return ioctl (fd, TIOCGSIZE, &size);
-#else
-  return -1;
-#endif /* not SunOS-style */
-#endif /* not BSD-style */
-}
-
-
-
 /* Prepare all terminal devices for exiting Emacs. */
 
 void
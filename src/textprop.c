@@ -551,33 +551,6 @@ interval_of (ptrdiff_t position, Lisp_Object object)
   return find_interval (i, position);
 }
 
-DEFUN ("text-properties-at", Ftext_properties_at,
-       Stext_properties_at, 1, 2, 0,
-       doc: /* Return the list of properties of the character at POSITION in OBJECT.
-If the optional second argument OBJECT is a buffer (or nil, which means
-the current buffer), POSITION is a buffer position (integer or marker).
-If OBJECT is a string, POSITION is a 0-based index into it.
-If POSITION is at the end of OBJECT, the value is nil.  */)
-  (Lisp_Object position, Lisp_Object object)
-{
-  register INTERVAL i;
-
-  if (NILP (object))
-    XSETBUFFER (object, current_buffer);
-
-  i = validate_interval_range (object, &position, &position, soft);
-  if (!i)
-    return Qnil;
-  /* If POSITION is at the end of the interval,
-     it means it's the end of OBJECT.
-     There are no properties at the very end,
-     since no character follows.  */
-  if (XINT (position) == LENGTH (i) + i->position)
-    return Qnil;
-
-  return i->plist;
-}
-
 DEFUN ("get-text-property", Fget_text_property, Sget_text_property, 2, 3, 0,
        doc: /* Return the value of POSITION's property PROP, in OBJECT.
 OBJECT should be a buffer or a string; if omitted or nil, it defaults
@@ -1254,8 +1227,14 @@ Return t if any property value actually changed, nil otherwise.  */)
   (Lisp_Object start, Lisp_Object end, Lisp_Object properties,
    Lisp_Object object)
 {
-  return add_text_properties_1 (start, end, properties, object,
-				TEXT_PROPERTY_REPLACE);
+  /* Resolve OBJECT now, as the change hooks may switch buffers.  */
+  if (NILP (object))
+    XSETBUFFER (object, current_buffer);
+  Lisp_Object value = add_text_properties_1 (start, end, properties, object,
+					     TEXT_PROPERTY_REPLACE);
+  if (!NILP (value))
+    coalesce_intervals (object, start, end);
+  return value;
 }
 
 /* Callers note, this can GC when OBJECT is a buffer (or nil).  */
@@ -1287,7 +1266,12 @@ If PROPERTIES is nil, the effect is to remove all properties from
 the designated part of OBJECT.  */)
   (Lisp_Object start, Lisp_Object end, Lisp_Object properties, Lisp_Object object)
 {
-  return set_text_properties (start, end, properties, object, Qt);
+  if (NILP (object))
+    XSETBUFFER (object, current_buffer);
+  Lisp_Object value = set_text_properties (start, end, properties, object, Qt);
+  if (!NILP (value))
+    coalesce_intervals (object, start, end);
+  return value;
 }
 
 
@@ -1314,10 +1298,13 @@ into it.  */)
    Lisp_Object append, Lisp_Object object)
 {
   AUTO_LIST2 (properties, Qface, face);
-  add_text_properties_1 (start, end, properties, object,
-			 (NILP (append)
-			  ? TEXT_PROPERTY_PREPEND
-			  : TEXT_PROPERTY_APPEND));
+  if (NILP (object))
+    XSETBUFFER (object, current_buffer);
+  if (!NILP (add_text_properties_1 (start, end, properties, object,
+				    (NILP (append)
+				     ? TEXT_PROPERTY_PREPEND
+				     : TEXT_PROPERTY_APPEND))))
+    coalesce_intervals (object, start, end);
   return Qnil;
 }
 
@@ -1474,19 +1461,11 @@ set_text_properties_1 (Lisp_Object start, Lisp_Object end, Lisp_Object propertie
   while (len > 0);
 }
 
-DEFUN ("remove-text-properties", Fremove_text_properties,
-       Sremove_text_properties, 3, 4, 0,
-       doc: /* Remove some properties from text from START to END.
-The third argument PROPERTIES is a property list
-whose property names specify the properties to remove.
-\(The values stored in PROPERTIES are ignored.)
-If the optional fourth argument OBJECT is a buffer (or nil, which means
-the current buffer), START and END are buffer positions (integers or
-markers).  If OBJECT is a string, START and END are 0-based indices into it.
-Return t if any property was actually removed, nil otherwise.
+/* The body of `remove-text-properties'.  */
 
-Use `set-text-properties' if you want to remove all text properties.  */)
-  (Lisp_Object start, Lisp_Object end, Lisp_Object properties, Lisp_Object object)
+static Lisp_Object
+remove_text_properties_1 (Lisp_Object start, Lisp_Object end,
+			  Lisp_Object properties, Lisp_Object object)
 {
   INTERVAL i, unchanged;
   ptrdiff_t s, len;
@@ -1590,15 +1569,12 @@ Use `set-text-properties' if you want to remove all text properties.  */)
     }
 }
 
-DEFUN ("remove-list-of-text-properties", Fremove_list_of_text_properties,
-       Sremove_list_of_text_properties, 3, 4, 0,
-       doc: /* Remove some properties from text from START to END.
-The third argument LIST-OF-PROPERTIES is a list of property names to remove.
-If the optional fourth argument OBJECT is a buffer (or nil, which means
-the current buffer), START and END are buffer positions (integers or
-markers).  If OBJECT is a string, START and END are 0-based indices into it.
-Return t if any property was actually removed, nil otherwise.  */)
-  (Lisp_Object start, Lisp_Object end, Lisp_Object list_of_properties, Lisp_Object object)
+/* The body of `remove-list-of-text-properties'.  */
+
+static Lisp_Object
+remove_list_of_text_properties_1 (Lisp_Object start, Lisp_Object end,
+				  Lisp_Object list_of_properties,
+				  Lisp_Object object)
 {
   INTERVAL i, unchanged;
   ptrdiff_t s, len;
@@ -1713,43 +1689,49 @@ Return t if any property was actually removed, nil otherwise.  */)
         }
     }
 }
-
-DEFUN ("text-property-any", Ftext_property_any,
-       Stext_property_any, 4, 5, 0,
-       doc: /* Check text from START to END for property PROPERTY equaling VALUE.
-If so, return the position of the first character whose property PROPERTY
-is `eq' to VALUE.  Otherwise return nil.
-If the optional fifth argument OBJECT is a buffer (or nil, which means
+
+DEFUN ("remove-text-properties", Fremove_text_properties,
+       Sremove_text_properties, 3, 4, 0,
+       doc: /* Remove some properties from text from START to END.
+The third argument PROPERTIES is a property list
+whose property names specify the properties to remove.
+\(The values stored in PROPERTIES are ignored.)
+If the optional fourth argument OBJECT is a buffer (or nil, which means
 the current buffer), START and END are buffer positions (integers or
-markers).  If OBJECT is a string, START and END are 0-based indices into it.  */)
-  (Lisp_Object start, Lisp_Object end, Lisp_Object property, Lisp_Object value, Lisp_Object object)
-{
-  register INTERVAL i;
-  register ptrdiff_t e, pos;
+markers).  If OBJECT is a string, START and END are 0-based indices into it.
+Return t if any property was actually removed, nil otherwise.
 
+Use `set-text-properties' if you want to remove all text properties.  */)
+  (Lisp_Object start, Lisp_Object end, Lisp_Object properties, Lisp_Object object)
+{
   if (NILP (object))
     XSETBUFFER (object, current_buffer);
-  i = validate_interval_range (object, &start, &end, soft);
-  if (!i)
-    return (!NILP (value) || EQ (start, end) ? Qnil : start);
-  e = XINT (end);
-
-  while (i)
-    {
-      if (i->position >= e)
-	break;
-      if (EQ (textget (i->plist, property), value))
-	{
-	  pos = i->position;
-	  if (pos < XINT (start))
-	    pos = XINT (start);
-	  return make_number (pos);
-	}
-      i = next_interval (i);
-    }
-  return Qnil;
+  Lisp_Object value = remove_text_properties_1 (start, end, properties, object);
+  if (!NILP (value))
+    coalesce_intervals (object, start, end);
+  return value;
 }
 
+DEFUN ("remove-list-of-text-properties", Fremove_list_of_text_properties,
+       Sremove_list_of_text_properties, 3, 4, 0,
+       doc: /* Remove some properties from text from START to END.
+The third argument LIST-OF-PROPERTIES is a list of property names to remove.
+If the optional fourth argument OBJECT is a buffer (or nil, which means
+the current buffer), START and END are buffer positions (integers or
+markers).  If OBJECT is a string, START and END are 0-based indices into it.
+Return t if any property was actually removed, nil otherwise.  */)
+  (Lisp_Object start, Lisp_Object end, Lisp_Object list_of_properties, Lisp_Object object)
+{
+  if (NILP (object))
+    XSETBUFFER (object, current_buffer);
+  Lisp_Object value = remove_list_of_text_properties_1 (start, end,
+							 list_of_properties,
+							 object);
+  if (!NILP (value))
+    coalesce_intervals (object, start, end);
+  return value;
+}
+
 DEFUN ("text-property-not-all", Ftext_property_not_all,
        Stext_property_not_all, 4, 5, 0,
        doc: /* Check text from START to END for property PROPERTY not equaling VALUE.
@@ -2351,7 +2333,6 @@ inherits it if NONSTICKINESS is nil.  The `front-sticky' and
   DEFSYM (Qpoint_left, "point-left");
   DEFSYM (Qpoint_entered, "point-entered");
 
-  defsubr (&Stext_properties_at);
   defsubr (&Sget_text_property);
   defsubr (&Sget_char_property_and_overlay);
   defsubr (&Snext_char_property_change);
@@ -2368,6 +2349,5 @@ inherits it if NONSTICKINESS is nil.  The `front-sticky' and
   defsubr (&Sadd_face_text_property);
   defsubr (&Sremove_text_properties);
   defsubr (&Sremove_list_of_text_properties);
-  defsubr (&Stext_property_any);
   defsubr (&Stext_property_not_all);
 }
@@ -181,6 +181,10 @@ struct window
     /* The help echo text for this window.  Qnil if there's none.  */
     Lisp_Object mode_line_help_echo;
 
+    /* The dynamic highlights of this window, maintained by
+       winhighlight.rs.  */
+    Lisp_Object highlights;
+
     /* No Lisp data may follow below this point without changing
        mark_object in alloc.c.  The member current_matrix must be the
        first non-Lisp member.  */
@@ -639,18 +643,10 @@ wget_pseudo_window_p(struct window *w);
        (XWINDOW (FRAME_ROOT_WINDOW (WINDOW_XFRAME (W))))))	\
 
 /* Width of right divider of window W.  */
-#define WINDOW_RIGHT_DIVIDER_WIDTH(W)				\
-  (WINDOW_RIGHTMOST_P (W)					\
-   ? 0 : FRAME_RIGHT_DIVIDER_WIDTH (WINDOW_XFRAME (W)))
+#define WINDOW_RIGHT_DIVIDER_WIDTH(W) window_right_divider_width (W)
 
 /* Width of bottom divider of window W.  */
-#define WINDOW_BOTTOM_DIVIDER_WIDTH(W)					\
-  (((WINDOW_BOTTOMMOST_P (W)						\
-     && NILP ((XWINDOW (FRAME_ROOT_WINDOW				\
-			(WINDOW_XFRAME (W))))->next))			\
-    || EQ ((W)->prev, FRAME_ROOT_WINDOW (WINDOW_XFRAME (W)))		\
-    || (W)->pseudo_window_p)						\
-   ? 0 : FRAME_BOTTOM_DIVIDER_WIDTH (WINDOW_XFRAME (W)))
+#define WINDOW_BOTTOM_DIVIDER_WIDTH(W) window_bottom_divider_width (W)
 
 /* Return the canonical frame column at which window W starts.
    This includes a left-hand scroll bar, if any.  */
@@ -759,26 +755,18 @@ window_tool_bar_p(struct window *W);
    + WINDOW_RIGHT_MARGIN_COLS (W))
 
 /* Widths of marginal areas in pixels.  */
-#define WINDOW_LEFT_MARGIN_WIDTH(W)			\
-  (W->left_margin_cols * WINDOW_FRAME_COLUMN_WIDTH (W))
+#define WINDOW_LEFT_MARGIN_WIDTH(W) window_left_margin_width (W)
 
-#define WINDOW_RIGHT_MARGIN_WIDTH(W)				\
-  (W->right_margin_cols * WINDOW_FRAME_COLUMN_WIDTH (W))
+#define WINDOW_RIGHT_MARGIN_WIDTH(W) window_right_margin_width (W)
 
 #define WINDOW_MARGINS_WIDTH(W)			\
   (WINDOW_LEFT_MARGIN_WIDTH (W)			\
    + WINDOW_RIGHT_MARGIN_WIDTH (W))
 
 /* Pixel-widths of fringes.  */
-#define WINDOW_LEFT_FRINGE_WIDTH(W)			\
-  (W->left_fringe_width >= 0				\
-   ? W->left_fringe_width				\
-   : FRAME_LEFT_FRINGE_WIDTH (WINDOW_XFRAME (W)))
+#define WINDOW_LEFT_FRINGE_WIDTH(W) window_left_fringe_width (W)
 
-#define WINDOW_RIGHT_FRINGE_WIDTH(W)			\
-  (W->right_fringe_width >= 0				\
-   ? W->right_fringe_width				\
-   : FRAME_RIGHT_FRINGE_WIDTH (WINDOW_XFRAME (W)))
+#define WINDOW_RIGHT_FRINGE_WIDTH(W) window_right_fringe_width (W)
 
 #define WINDOW_FRINGES_WIDTH(W)		\
   (WINDOW_LEFT_FRINGE_WIDTH (W) + WINDOW_RIGHT_FRINGE_WIDTH (W))
@@ -789,22 +777,11 @@ window_tool_bar_p(struct window *W);
 
 /* Say whether vertical scroll bars are currently enabled for window W,
    and which side they are on.  */
-#define WINDOW_VERTICAL_SCROLL_BAR_TYPE(W)		\
-  (WINDOW_PSEUDO_P (W)					\
-   ? vertical_scroll_bar_none				\
-   : EQ (W->vertical_scroll_bar_type, Qt)		\
-   ? FRAME_VERTICAL_SCROLL_BAR_TYPE (WINDOW_XFRAME (W))	\
-   : EQ (W->vertical_scroll_bar_type, Qleft)		\
-   ? vertical_scroll_bar_left				\
-   : EQ (W->vertical_scroll_bar_type, Qright)		\
-   ? vertical_scroll_bar_right				\
-   : vertical_scroll_bar_none)
-
-#define WINDOW_HAS_VERTICAL_SCROLL_BAR_ON_LEFT(W)			\
-  (WINDOW_VERTICAL_SCROLL_BAR_TYPE (W) == vertical_scroll_bar_left)
-
-#define WINDOW_HAS_VERTICAL_SCROLL_BAR_ON_RIGHT(W)			\
-  (WINDOW_VERTICAL_SCROLL_BAR_TYPE (W) == vertical_scroll_bar_right)
+#define WINDOW_HAS_VERTICAL_SCROLL_BAR_ON_LEFT(W)	\
+  window_has_vertical_scroll_bar_on_left (W)
+
+#define WINDOW_HAS_VERTICAL_SCROLL_BAR_ON_RIGHT(W)	\
+  window_has_vertical_scroll_bar_on_right (W)
 
 #define WINDOW_HAS_VERTICAL_SCROLL_BAR(W)		\
   (WINDOW_HAS_VERTICAL_SCROLL_BAR_ON_LEFT (W)		\
@@ -819,14 +796,7 @@ window_tool_bar_p(struct window *W);
 /* Say whether horizontal scroll bars are currently enabled for window
    W.  Horizontal scrollbars exist for toolkit versions only.  */
 #if USE_HORIZONTAL_SCROLL_BARS
-#define WINDOW_HAS_HORIZONTAL_SCROLL_BAR(W)			\
-  ((WINDOW_PSEUDO_P (W) || MINI_NON_ONLY_WINDOW_P (W))		\
-   ? false							\
-   : EQ (W->horizontal_scroll_bar_type, Qt)			\
-   ? FRAME_HAS_HORIZONTAL_SCROLL_BARS (WINDOW_XFRAME (W))	\
-   : EQ (W->horizontal_scroll_bar_type, Qbottom)		\
-   ? true							\
-   : false)
+#define WINDOW_HAS_HORIZONTAL_SCROLL_BAR(W) window_has_horizontal_scroll_bar (W)
 #else
 #define WINDOW_HAS_HORIZONTAL_SCROLL_BAR(W) false
 #endif
@@ -884,10 +854,7 @@ window_tool_bar_p(struct window *W);
    : 0)
 
 /* Width of scroll bar area in window W, measured in pixels.  */
-#define WINDOW_SCROLL_BAR_AREA_WIDTH(W)					\
-  (WINDOW_HAS_VERTICAL_SCROLL_BAR (W)					\
-   ? WINDOW_CONFIG_SCROLL_BAR_WIDTH (W)					\
-   : 0)
+#define WINDOW_SCROLL_BAR_AREA_WIDTH(W) window_scroll_bar_area_width (W)
 
 /* Return the frame position where the vertical scroll bar of window W
    starts.  */
@@ -921,27 +888,18 @@ window_tool_bar_p(struct window *W);
    : 0)
 
 /* Height of scroll bar area in window W, measured in pixels.  */
-#define WINDOW_SCROLL_BAR_AREA_HEIGHT(W)	\
-  (WINDOW_HAS_HORIZONTAL_SCROLL_BAR (W)		\
-   ? WINDOW_CONFIG_SCROLL_BAR_HEIGHT (W)	\
-   : 0)
+#define WINDOW_SCROLL_BAR_AREA_HEIGHT(W) window_scroll_bar_area_height (W)
 
 /* Height in pixels of the mode line.
    May be zero if W doesn't have a mode line.  */
-#define WINDOW_MODE_LINE_HEIGHT(W)	\
-  (window_wants_mode_line ((W))		\
-   ? CURRENT_MODE_LINE_HEIGHT (W)	\
-   : 0)
+#define WINDOW_MODE_LINE_HEIGHT(W) window_mode_line_pixel_height (W)
 
 #define WINDOW_MODE_LINE_LINES(W)	\
   window_wants_mode_line (W)
 
 /* Height in pixels of the header line.
    Zero if W doesn't have a header line.  */
-#define WINDOW_HEADER_LINE_HEIGHT(W)	\
-  (window_wants_header_line (W)		\
-   ? CURRENT_HEADER_LINE_HEIGHT (W)	\
-   : 0)
+#define WINDOW_HEADER_LINE_HEIGHT(W) window_header_line_pixel_height (W)
 
 #define WINDOW_HEADER_LINE_LINES(W)	\
   window_wants_header_line (W)
@@ -1109,7 +1067,30 @@ extern void mark_window_cursors_off (struct window *);
 extern bool window_wants_mode_line (struct window *);
 extern bool window_wants_header_line (struct window *);
 extern int window_internal_height (struct window *);
+extern bool horizontal_scroll_bars_available (void);
+
+/* Defined in windows.rs.  */
 extern int window_body_width (struct window *w, bool);
+extern int window_body_height (struct window *w, bool);
+extern int window_mode_line_pixel_height (struct window *);
+extern int window_header_line_pixel_height (struct window *);
+extern int window_right_divider_width (struct window *);
+extern int window_bottom_divider_width (struct window *);
+extern int window_left_fringe_width (struct window *);
+extern int window_right_fringe_width (struct window *);
+extern int window_left_margin_width (struct window *);
+extern int window_right_margin_width (struct window *);
+extern bool window_has_vertical_scroll_bar_on_left (struct window *);
+extern bool window_has_vertical_scroll_bar_on_right (struct window *);
+extern bool window_has_horizontal_scroll_bar (struct window *);
+extern int window_scroll_bar_area_width (struct window *);
+extern int window_scroll_bar_area_height (struct window *);
+
+/* Defined in winhighlight.rs.  */
+extern Lisp_Object window_highlight_face (struct window *, ptrdiff_t,
+                                          ptrdiff_t *);
+extern ptrdiff_t window_highlight_change (struct window *, ptrdiff_t);
+
 enum margin_unit { MARGIN_IN_LINES, MARGIN_IN_PIXELS };
 extern int window_scroll_margin (struct window *, enum margin_unit);
 extern void temp_output_buffer_show (Lisp_Object);
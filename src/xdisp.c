@@ -3552,6 +3552,11 @@ compute_stop_pos (struct it *it)
       if (pos < it->stop_charpos)
 	it->stop_charpos = pos;
 
+      /* Likewise for the start or end of a highlight of the window.  */
+      pos = window_highlight_change (it->w, charpos);
+      if (pos < it->stop_charpos)
+	it->stop_charpos = pos;
+
       /* Set up variables for computing the stop position from text
          property changes.  */
       XSETBUFFER (object, current_buffer);
@@ -3841,6 +3846,7 @@ handle_fontified_prop (struct it *it)
 
       val = Vfontification_functions;
       specbind (Qfontification_functions, Qnil);
+      push_alloc_context (ALLOC_FONTIFICATION);
 
       eassert (it->end_charpos == ZV);
 
@@ -11874,11 +11880,50 @@ store_mode_line_noprop (const char *string, int field_width, int precision)
 			     Frame Titles
  ***********************************************************************/
 
+/* Return the title of frame F formatted from FMT, the way the mode
+   line would be, for the selected window of F and its buffer.  */
+
+Lisp_Object
+format_frame_title (struct frame *f, Lisp_Object fmt)
+{
+  ptrdiff_t title_start;
+  struct it it;
+  ptrdiff_t count = SPECPDL_INDEX ();
+  Lisp_Object title;
+
+  /* Switch to the buffer of selected window of the frame.  Set up
+     mode_line_target so that display_mode_element will output into
+     mode_line_noprop_buf; then display the title.  */
+  record_unwind_protect (unwind_format_mode_line,
+			 format_mode_line_unwind_data
+			   (f, current_buffer, selected_window, false));
+  /* select-frame calls resize_mini_window, which could resize the
+     mini-window and by that undo the effect of this redisplay
+     cycle wrt minibuffer and echo-area display.  Binding
+     inhibit-redisplay to t makes the call to resize_mini_window a
+     no-op, thus avoiding the adverse side effects.  */
+  specbind (Qinhibit_redisplay, Qt);
+
+  Fselect_window (f->selected_window, Qt);
+  set_buffer_internal_1
+    (XBUFFER (XWINDOW (f->selected_window)->contents));
+
+  mode_line_target = MODE_LINE_TITLE;
+  title_start = MODE_LINE_NOPROP_LEN (0);
+  init_iterator (&it, XWINDOW (f->selected_window), -1, -1,
+		 NULL, DEFAULT_FACE_ID);
+  display_mode_element (&it, 0, -1, -1, fmt, Qnil, false);
+  title = make_string (mode_line_noprop_buf + title_start,
+		       MODE_LINE_NOPROP_LEN (title_start));
+  return unbind_to (count, title);
+}
+
 #ifdef HAVE_WINDOW_SYSTEM
 
 /* Set the title of FRAME, if it has changed.  The title format is
    Vicon_title_format if FRAME is iconified, otherwise it is
-   frame_title_format.  */
+   frame_title_format.  How often the title may change is up to
+   frame_title_update.  */
 
 static void
 x_consider_frame_title (Lisp_Object frame)
@@ -11891,12 +11936,7 @@ x_consider_frame_title (Lisp_Object frame)
       && !FRAME_TOOLTIP_P (f))
     {
       /* Do we have more than one visible frame on this X display?  */
-      Lisp_Object tail, other_frame, fmt;
-      ptrdiff_t title_start;
-      char *title;
-      ptrdiff_t len;
-      struct it it;
-      ptrdiff_t count = SPECPDL_INDEX ();
+      Lisp_Object tail, other_frame, title;
 
       FOR_EACH_FRAME (tail, other_frame)
 	{
@@ -11914,42 +11954,9 @@ x_consider_frame_title (Lisp_Object frame)
       /* Set global variable indicating that multiple frames exist.  */
       multiple_frames = CONSP (tail);
 
-      /* Switch to the buffer of selected window of the frame.  Set up
-	 mode_line_target so that display_mode_element will output into
-	 mode_line_noprop_buf; then display the title.  */
-      record_unwind_protect (unwind_format_mode_line,
-			     format_mode_line_unwind_data
-			       (f, current_buffer, selected_window, false));
-      /* select-frame calls resize_mini_window, which could resize the
-	 mini-window and by that undo the effect of this redisplay
-	 cycle wrt minibuffer and echo-area display.  Binding
-	 inhibit-redisplay to t makes the call to resize_mini_window a
-	 no-op, thus avoiding the adverse side effects.  */
-      specbind (Qinhibit_redisplay, Qt);
-
-      Fselect_window (f->selected_window, Qt);
-      set_buffer_internal_1
-	(XBUFFER (XWINDOW (f->selected_window)->contents));
-      fmt = FRAME_ICONIFIED_P (f) ? Vicon_title_format : Vframe_title_format;
-
-      mode_line_target = MODE_LINE_TITLE;
-      title_start = MODE_LINE_NOPROP_LEN (0);
-      init_iterator (&it, XWINDOW (f->selected_window), -1, -1,
-		     NULL, DEFAULT_FACE_ID);
-      display_mode_element (&it, 0, -1, -1, fmt, Qnil, false);
-      len = MODE_LINE_NOPROP_LEN (title_start);
-      title = mode_line_noprop_buf + title_start;
-      unbind_to (count, Qnil);
-
-      /* Set the title only if it's changed.  This avoids consing in
-	 the common case where it hasn't.  (If it turns out that we've
-	 already wasted too much time by walking through the list with
-	 display_mode_element, then we might need to optimize at a
-	 higher level than this.)  */
-      if (! STRINGP (f->name)
-	  || SBYTES (f->name) != len
-	  || memcmp (title, SDATA (f->name), len) != 0)
-	x_implicitly_set_name (f, make_string (title, len), Qnil);
+      title = frame_title_update (f);
+      if (!NILP (title))
+	x_implicitly_set_name (f, title, Qnil);
     }
 }
 
@@ -13515,7 +13522,9 @@ text_outside_line_unchanged_p (struct window *w,
 void
 redisplay (void)
 {
+  enum latency_phase phase = latency_phase_start (LATENCY_REDISPLAY);
   redisplay_internal ();
+  latency_phase_start (phase);
 }
 
 
@@ -14619,6 +14628,7 @@ redisplay_preserve_echo_area (int from_where)
   record_unwind_protect_void (unwind_redisplay_preserve_echo_area);
   block_buffer_flips ();
   unblock_input ();
+  enum latency_phase phase = latency_phase_start (LATENCY_REDISPLAY);
 
   if (!NILP (echo_area_buffer[1]))
     {
@@ -14632,6 +14642,7 @@ redisplay_preserve_echo_area (int from_where)
     redisplay_internal ();
 
   flush_frame (SELECTED_FRAME ());
+  latency_phase_start (phase);
   unbind_to (count, Qnil);
 }
 
@@ -5718,7 +5718,7 @@ face_at_buffer_position (struct window *w, ptrdiff_t pos,
 {
   struct frame *f = XFRAME (w->frame);
   Lisp_Object attrs[LFACE_VECTOR_SIZE];
-  Lisp_Object prop, position;
+  Lisp_Object prop, position, highlight;
   ptrdiff_t i, noverlays;
   Lisp_Object *overlay_vec;
   ptrdiff_t endpos;
@@ -5752,6 +5752,9 @@ face_at_buffer_position (struct window *w, ptrdiff_t pos,
       endpos = next_overlay;
   }
 
+  /* Look at the highlights of W.  */
+  highlight = mouse ? Qnil : window_highlight_face (w, pos, &endpos);
+
   *endptr = endpos;
 
   {
@@ -5769,7 +5772,8 @@ face_at_buffer_position (struct window *w, ptrdiff_t pos,
 
   /* Optimize common cases where we can use the default face.  */
   if (noverlays == 0
-      && NILP (prop))
+      && NILP (prop)
+      && NILP (highlight))
     {
       SAFE_FREE ();
       return default_face->id;
@@ -5782,6 +5786,10 @@ face_at_buffer_position (struct window *w, ptrdiff_t pos,
   if (!NILP (prop))
     merge_face_ref (f, prop, attrs, true, 0);
 
+  /* Merge in the highlights of W, which are below overlays.  */
+  if (!NILP (highlight))
+    merge_face_ref (f, highlight, attrs, true, 0);
+
   /* Now merge the overlay data.  */
   noverlays = sort_overlays (overlay_vec, noverlays, w);
   /* For mouse-face, we need only the single highest-priority face
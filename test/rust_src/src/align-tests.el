@@ -0,0 +1,74 @@
+;;; align-tests.el --- Tests for align.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+(require 'align)
+
+(defun align-tests--aligned (text &rest rule)
+  "Return TEXT after aligning it by `align-region-native' with RULE.
+RULE is a property list that defaults to one spacing per group, and
+no tab stops."
+  (with-temp-buffer
+    (setq indent-tabs-mode nil)
+    (insert text)
+    (goto-char 2)
+    (align-region-native (point-min) (point-max)
+                         (append rule '(:group (1) :spacing (1) :tab-stop (nil))))
+    (should (= (point) 2))
+    (buffer-string)))
+
+(ert-deftest align-tests--columns ()
+  (should (equal (align-tests--aligned "a = 1\nbbb = 2\ncc = 3\n"
+                                       :regexp "\\(\\s-*\\)=")
+                 "a   = 1\nbbb = 2\ncc  = 3\n"))
+  ;; Text that is aligned already is left alone.
+  (should (equal (align-tests--aligned "a   = 1\nbbb = 2\n"
+                                       :regexp "\\(\\s-*\\)=")
+                 "a   = 1\nbbb = 2\n"))
+  ;; A fixed column.
+  (should (equal (align-tests--aligned "a = 1\nbbb = 2\n"
+                                       :regexp "\\(\\s-*\\)=" :column 6)
+                 "a     = 1\nbbb   = 2\n")))
+
+(ert-deftest align-tests--repeat ()
+  (should (equal (align-tests--aligned "a,b,c\nxx,yy,zz\n"
+                                       :regexp "\\(\\s-*\\)," :repeat t)
+                 "a  ,b  ,c\nxx ,yy ,zz\n"))
+  (should (equal (align-tests--aligned "a,b,c\nxx,yy,zz\n"
+                                       :regexp "\\(\\s-*\\),")
+                 "a  ,b,c\nxx ,yy,zz\n")))
+
+(ert-deftest align-tests--sections ()
+  (let ((text "a = 1\nbb = 2\n\nccc = 3\nd = 4\n"))
+    (should (equal (align-tests--aligned text :regexp "\\(\\s-*\\)="
+                                         :separate 'group)
+                   "a  = 1\nbb = 2\n\nccc = 3\nd   = 4\n"))
+    (should (equal (align-tests--aligned text :regexp "\\(\\s-*\\)="
+                                         :separate "^$")
+                   "a  = 1\nbb = 2\n\nccc = 3\nd   = 4\n"))
+    (should (equal (align-tests--aligned text :regexp "\\(\\s-*\\)=")
+                   "a   = 1\nbb  = 2\n\nccc = 3\nd   = 4\n"))))
+
+(ert-deftest align-tests--justify-and-exclude ()
+  (should (equal (align-tests--aligned "x = 1\nyy = 22\n"
+                                       :regexp "=\\(\\s-*[0-9]+\\)" :justify t)
+                 "x =   1\nyy = 22\n"))
+  ;; The group on the second line is excluded.
+  (should (equal (align-tests--aligned "a = 1\nbbb = 2\ncc = 3\n"
+                                       :regexp "\\(\\s-*\\)=" :exclude '((9 . 11)))
+                 "a  = 1\nbbb = 2\ncc = 3\n")))
+
+(ert-deftest align-tests--align-regexp ()
+  (with-temp-buffer
+    (setq indent-tabs-mode nil)
+    (insert "Fred (123) 456-7890\nAlice (123) 456-7890\nJoe (123) 456-7890\n")
+    (align-regexp (point-min) (point-max) "\\(\\s-*\\)(")
+    (should (equal (buffer-string)
+                   (concat "Fred  (123) 456-7890\n"
+                           "Alice (123) 456-7890\n"
+                           "Joe   (123) 456-7890\n")))))
+
+(provide 'align-tests)
+
+;;; align-tests.el ends here
@@ -11,5 +11,32 @@
 (ert-deftest bool-vector ()
   (should (bool-vector)))
 
+(ert-deftest gc-allocation-report ()
+  (gc-allocation-report-reset)
+  (let ((gc-allocation-accounting t))
+    (read "(a b c d e f g h)")
+    (eval '(make-list 100 nil)))
+  (let ((report (gc-allocation-report)))
+    (should (equal (mapcar #'car report)
+                   '(other reader evaluator fontification)))
+    (dolist (entry report)
+      (should (= 8 (length (cdr entry)))))
+    (should (>= (nth 1 (assq 'reader report)) 8))
+    (should (>= (nth 1 (assq 'evaluator report)) 100)))
+  (gc-allocation-report-reset)
+  (should (= 0 (nth 1 (assq 'reader (gc-allocation-report))))))
+
+(ert-deftest gc-write-barrier-statistics ()
+  (let ((stats (gc-write-barrier-statistics)))
+    ;; The statistics are nil unless Emacs runs with --experimental-gc.
+    (when stats
+      (let ((stores (plist-get stats :stores))
+            (cell (list 1)))
+        (setcar cell (list 2))
+        (setcar cell 3)
+        (should (= (plist-get (gc-write-barrier-statistics) :stores)
+                   (1+ stores)))
+        (should (> (plist-get (gc-write-barrier-statistics) :recent) 0))))))
+
 (provide 'alloc-tests)
 ;;; alloc-tests.el ends here
@@ -0,0 +1,116 @@
+;;; ansi-tests.el --- tests for ansi.rs functions
+
+;;; Code:
+
+(require 'ert)
+(require 'ansi-color)
+
+(ert-deftest ansi-color-apply-native ()
+  (let* ((result (ansi-color-apply-native "a\e[1;31mbc\e[0md"))
+         (text (car result)))
+    (should (equal (substring-no-properties text) "abcd"))
+    (should-not (get-text-property 0 'face text))
+    (should (equal (get-text-property 1 'face text)
+                   '(:weight bold :foreground "#cd0000")))
+    (should (equal (get-text-property 2 'face text)
+                   '(:weight bold :foreground "#cd0000")))
+    (should-not (get-text-property 3 'face text))))
+
+(ert-deftest ansi-color-apply-native-multibyte ()
+  ;; Runs are found in bytes, but properties are put in characters.
+  (let ((text (car (ansi-color-apply-native "éé\e[31mr\e[0mé"))))
+    (should (equal (substring-no-properties text) "ééré"))
+    (should-not (get-text-property 1 'face text))
+    (should (equal (get-text-property 2 'face text)
+                   '(:foreground "#cd0000")))
+    (should (= (next-single-property-change 2 'face text) 3))))
+
+(ert-deftest ansi-color-apply-native-extended-colors ()
+  (let ((text (car (ansi-color-apply-native "\e[38;5;196;48;2;1;2;3mx"))))
+    (should (equal (get-text-property 0 'face text)
+                   '(:foreground "#ff0000" :background "#010203")))))
+
+(ert-deftest ansi-color-apply-native-context ()
+  (let* ((first (ansi-color-apply-native "a\e[3"))
+         (second (ansi-color-apply-native "mb" (cdr first))))
+    (should (equal (car first) "a"))
+    (should (equal (substring-no-properties (car second)) "b"))
+    (should (equal (get-text-property 0 'face (car second))
+                   '(:slant italic)))
+    ;; The rendition carries over to the next chunk.
+    (let ((third (ansi-color-apply-native "c" (cdr second))))
+      (should (equal (get-text-property 0 'face (car third))
+                     '(:slant italic))))))
+
+(ert-deftest ansi-color-apply-on-region-native ()
+  (with-temp-buffer
+    (insert "a\e[4mb\e[Kc\e[24md")
+    (ansi-color-apply-on-region-native (point-min) (point-max))
+    (should (equal (buffer-string) "abcd"))
+    (should (equal (get-text-property 2 'face) '(:underline t)))
+    (should (equal (get-text-property 3 'face) '(:underline t)))
+    (should-not (get-text-property 4 'face))))
+
+(ert-deftest ansi-color-filter-native ()
+  (should (equal (ansi-color-filter-native "\e[1mbold\e[0m \e[2Jplain")
+                 "bold plain")))
+
+(ert-deftest ansi-color-apply-native-hyperlink ()
+  (let* ((first (ansi-color-apply-native
+                 "see \e]8;;http://example.com/\e\\exam"))
+         (second (ansi-color-apply-native "ple\e]8;;\a." (cdr first)))
+         (text (concat (car first) (car second))))
+    (should (equal (substring-no-properties text) "see example."))
+    (should-not (get-text-property 3 'ansi-color-hyperlink text))
+    ;; The link carries over to the next chunk.
+    (dolist (pos '(4 10))
+      (should (equal (get-text-property pos 'ansi-color-hyperlink text)
+                     "http://example.com/"))
+      (should (equal (get-text-property pos 'help-echo text)
+                     "http://example.com/")))
+    (should-not (get-text-property 11 'ansi-color-hyperlink text))
+    (with-temp-buffer
+      (insert text)
+      (should (eq (button-type (button-at 5)) 'ansi-color-hyperlink)))))
+
+(ert-deftest ansi-color-apply-osc-on-region-native ()
+  (with-temp-buffer
+    (insert "\e]2;my title\a\e[1mx\e]8;;file:///tmp/\ay\e]8;;\a\e]7;z\a")
+    (let ((context (ansi-color-apply-osc-on-region-native
+                    (point-min) (point-max))))
+      (should (equal (buffer-string) "\e[1mxy"))
+      (should (equal ansi-color-window-title "my title"))
+      (should (equal (get-text-property 6 'ansi-color-hyperlink)
+                     "file:///tmp/"))
+      (should-not (get-text-property 5 'ansi-color-hyperlink))
+      ;; An incomplete OSC sequence is carried over.
+      (let ((start (point-max)))
+        (insert "\e]8;;u")
+        (setq context (ansi-color-apply-osc-on-region-native
+                       start (point-max) context))
+        (should (equal (buffer-string) "\e[1mxy"))
+        (insert "ri\e\\w")
+        (ansi-color-apply-osc-on-region-native start (point-max) context)
+        (should (equal (buffer-string) "\e[1mxyw"))
+        (should (equal (get-text-property 7 'ansi-color-hyperlink)
+                       "uri"))))))
+
+(ert-deftest ansi-color-apply-on-region-native-overlong-osc ()
+  (with-temp-buffer
+    (insert "x\e]0;" (make-string 3000 ?a))
+    (let ((context (ansi-color-apply-on-region-native
+                    (point-min) (point-max)))
+          (start (point-max)))
+      ;; The unterminated OSC sequence is carried over.
+      (should (equal (buffer-string) "x"))
+      (insert (make-string 2000 ?b))
+      ;; Once it is too long to be one, only its `ESC ]' is dropped,
+      ;; and what followed it is put back.
+      (ansi-color-apply-on-region-native start (point-max) context)
+      (should (equal (buffer-string)
+                     (concat "x0;" (make-string 3000 ?a)
+                             (make-string 2000 ?b)))))))
+
+(provide 'ansi-tests)
+
+;;; ansi-tests.el ends here
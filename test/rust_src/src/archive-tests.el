@@ -0,0 +1,61 @@
+;;; archive-tests.el --- Tests for archive.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+(require 'seq)
+
+(defun archive-tests-make (program extension &rest args)
+  "Make an archive of a file \"dir/a.txt\" with PROGRAM and ARGS.
+Return the archive file name, with EXTENSION."
+  (let* ((dir (make-temp-file "archive-tests" t))
+         (archive (expand-file-name (concat "test" extension) dir))
+         (default-directory (file-name-as-directory dir)))
+    (make-directory "dir")
+    (with-temp-file "dir/a.txt"
+      (insert "contents\n"))
+    (apply #'call-process program nil nil nil
+           (append args (list archive "dir/a.txt")))
+    archive))
+
+(defun archive-tests-check (archive)
+  (unwind-protect
+      (let ((entry (seq-find (lambda (entry)
+                               (equal (plist-get entry :name) "dir/a.txt"))
+                             (archive-list-entries-native archive))))
+        (should entry)
+        (should (= (plist-get entry :size) 9))
+        (should (eq (plist-get entry :type) 'file))
+        ;; Zip files store times with a resolution of two seconds.
+        (should (< (abs (float-time
+                         (time-subtract
+                          (plist-get entry :mtime)
+                          (file-attribute-modification-time
+                           (file-attributes
+                            (expand-file-name "dir/a.txt"
+                                              (file-name-directory archive)))))))
+                   2.5))
+        (with-temp-buffer
+          (should (= (archive-extract-entry-native archive "dir/a.txt") 9))
+          (should (equal (buffer-string) "contents\n")))
+        (should-error (archive-extract-entry-native archive "missing")))
+    (delete-directory (file-name-directory archive) t)))
+
+(ert-deftest archive-tar ()
+  (skip-unless (executable-find "tar"))
+  (archive-tests-check (archive-tests-make "tar" ".tar" "-cf")))
+
+(ert-deftest archive-tar-gz ()
+  (skip-unless (executable-find "tar"))
+  (archive-tests-check (archive-tests-make "tar" ".tar.gz" "-czf")))
+
+(ert-deftest archive-zip ()
+  (skip-unless (executable-find "zip"))
+  (archive-tests-check (archive-tests-make "zip" ".zip" "-q")))
+
+(ert-deftest archive-list-entries-native-errors ()
+  (should-error (archive-list-entries-native "/nonexistent/archive.tar")))
+
+(provide 'archive-tests)
+
+;;; archive-tests.el ends here
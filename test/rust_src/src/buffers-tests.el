@@ -63,6 +63,66 @@
     (should (equal (delq nil (delete-dups the-buffers))
                    the-buffers))))
 
+;; "é" is two bytes in a multibyte buffer, as is the eight-bit
+;; character for the byte \377.
+(ert-deftest test-set-buffer-multibyte ()
+  (with-temp-buffer
+    (insert "aéb" (string-to-multibyte "\377") "c")
+    (set-buffer-modified-p nil)
+    (set-buffer-multibyte nil)
+    (should-not enable-multibyte-characters)
+    (should (equal (buffer-string) "a\303\251b\377c"))
+    (should-not (buffer-modified-p))
+    (set-buffer-multibyte t)
+    (should (equal (buffer-string)
+                   (concat "aéb" (string-to-multibyte "\377") "c")))
+    (should (= (buffer-size) 5))))
+
+(ert-deftest test-set-buffer-multibyte-to ()
+  (with-temp-buffer
+    (set-buffer-multibyte nil)
+    (insert "a\303\251")
+    (set-buffer-multibyte 'to)
+    (should (= (buffer-size) 3))
+    (should (= (char-after 2) (unibyte-char-to-multibyte #o303)))))
+
+(ert-deftest test-set-buffer-multibyte-positions ()
+  (with-temp-buffer
+    (insert "ééab")
+    (goto-char 3)
+    (let ((marker (copy-marker 4)))
+      (set-buffer-multibyte nil)
+      (should (= (point) 5))
+      (should (= marker 6))
+      (should (eq (char-after) ?a))
+      (set-buffer-multibyte t)
+      (should (= (point) 3))
+      (should (= marker 4)))))
+
+(ert-deftest test-set-buffer-multibyte-text-properties ()
+  (with-temp-buffer
+    (insert "é" (propertize "ab" 'face 'bold) "é")
+    (set-buffer-multibyte nil)
+    (should-not (get-text-property 2 'face))
+    (should (eq (get-text-property 3 'face) 'bold))
+    (should (= (next-single-property-change 1 'face) 3))
+    (should (= (next-single-property-change 3 'face) 5))
+    (set-buffer-multibyte t)
+    (should (eq (get-text-property 2 'face) 'bold))
+    (should (= (next-single-property-change 2 'face) 4))))
+
+(ert-deftest test-set-buffer-multibyte-undo-and-errors ()
+  (with-temp-buffer
+    (buffer-enable-undo)
+    (insert "é")
+    (set-buffer-multibyte nil)
+    (should (equal (car buffer-undo-list) '(apply set-buffer-multibyte t)))
+    (insert "ab")
+    (narrow-to-region 1 2)
+    (should-error (set-buffer-multibyte t))
+    (widen)
+    (should-not enable-multibyte-characters)))
+
 (provide 'buffers-tests)
 
 ;;; buffers-tests.el ends here
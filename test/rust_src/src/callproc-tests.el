@@ -0,0 +1,118 @@
+;;; callproc-tests.el --- tests for callproc.rs functions
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest getenv-internal ()
+  (let ((process-environment '("FOO=bar" "BAZ" "FOO=ignored")))
+    (should (equal (getenv-internal "FOO") "bar"))
+    (should-not (getenv-internal "BAZ"))
+    (should-not (getenv-internal "QUUX")))
+  (should (equal (getenv-internal "A" '("A=1")) "1"))
+  (should (eq (getenv-internal "A" '("A")) t))
+  (should-not (getenv-internal "B" '("A=1")))
+  (should-error (getenv-internal 'foo) :type 'wrong-type-argument))
+
+(ert-deftest process-environment-block ()
+  (let ((process-environment '("FOO=bar" "BAZ" "FOO=ignored" "BAZ=1" "QUUX=")))
+    (let ((block (process-environment-block)))
+      (should (member "FOO=bar" block))
+      (should (member "QUUX=" block))
+      (should-not (member "FOO=ignored" block))
+      (should-not (seq-find (lambda (s) (string-prefix-p "BAZ" s)) block)))))
+
+(ert-deftest executable-find-internal ()
+  (unless (eq system-type 'windows-nt)
+    (let ((exec-path (list (file-name-directory (locate-file "sh" exec-path))))
+          (exec-suffixes '("")))
+      (should (file-executable-p (executable-find-internal "sh")))
+      (should (equal (executable-find-internal "sh")
+                     (locate-file "sh" exec-path exec-suffixes 1)))
+      (should-not (executable-find-internal "no-such-program-surely")))))
+
+(ert-deftest executable-find-internal-not-cached ()
+  (unless (eq system-type 'windows-nt)
+    (let* ((dir (file-name-as-directory (make-temp-file "exec" t)))
+           (other (file-name-as-directory (make-temp-file "exec" t)))
+           (program (expand-file-name "prog" dir))
+           (exec-path (list dir))
+           (exec-suffixes '("")))
+      (unwind-protect
+          (progn
+            ;; A program that was not found is found once installed.
+            (should-not (executable-find-internal "prog"))
+            (write-region "" nil program)
+            (set-file-modes program #o755)
+            (should (equal (executable-find-internal "prog") program))
+            ;; Like `locate-file', relative names with a directory
+            ;; part are looked up in `exec-path'.
+            (let ((default-directory other))
+              (should (equal (executable-find-internal "./prog") program)))
+            (let ((exec-path '(".")))
+              (let ((default-directory dir))
+                (should (equal (executable-find-internal "prog") program)))
+              (let ((default-directory other))
+                (should-not (executable-find-internal "prog")))))
+        (delete-directory dir t)
+        (delete-directory other t)))))
+
+(ert-deftest executable-find-internal-directory-part ()
+  (unless (eq system-type 'windows-nt)
+    (let* ((dir (file-name-as-directory (make-temp-file "exec" t)))
+           (program (expand-file-name "bin/prog" dir))
+           (exec-suffixes '("")))
+      (unwind-protect
+          (progn
+            (make-directory (file-name-directory program))
+            (write-region "" nil program)
+            (set-file-modes program #o755)
+            (let ((exec-path (list dir))
+                  (default-directory temporary-file-directory))
+              (should (equal (executable-find-internal "bin/prog") program))
+              (should (equal (executable-find-internal "bin/prog")
+                             (locate-file "bin/prog" exec-path exec-suffixes 1)))
+              (should (equal (executable-find-internal program) program)))
+            (let ((exec-path (list temporary-file-directory))
+                  (default-directory dir))
+              (should-not (executable-find-internal "bin/prog"))
+              (should-not (locate-file "bin/prog" exec-path exec-suffixes 1))))
+        (delete-directory dir t)))))
+
+(ert-deftest call-process ()
+  (unless (eq system-type 'windows-nt)
+    (with-temp-buffer
+      (should (eq (call-process "sh" nil t nil "-c" "echo out; echo err >&2; exit 3") 3))
+      (should (equal (buffer-string) "out\nerr\n")))
+    (with-temp-buffer
+      (should (eq (call-process "sh" nil '(t nil) nil "-c" "echo out; echo err >&2") 0))
+      (should (equal (buffer-string) "out\n")))
+    (should-not (call-process "true" nil 0))
+    (should (stringp (call-process "sh" nil nil nil "-c" "kill -9 $$")))
+    (should-error (call-process "no-such-program-surely") :type 'file-error)))
+
+(ert-deftest call-process-region ()
+  (unless (eq system-type 'windows-nt)
+    (with-temp-buffer
+      (insert "b\na\n")
+      (should (eq (call-process-region nil nil "sort" t t) 0))
+      (should (equal (buffer-string) "a\nb\n")))
+    (with-temp-buffer
+      (should (eq (call-process-region "hello" nil "cat" nil t) 0))
+      (should (equal (buffer-string) "hello")))
+    ;; The input is dropped once a child that doesn't read it exits.
+    (should (eq (call-process-region (make-string 1000000 ?x) nil "true") 0))
+    ;; A process we don't wait for still gets all of its input.
+    (let ((file (make-temp-file "callproc-tests")))
+      (unwind-protect
+          (progn
+            (should-not (call-process-region "hello" nil "sh" nil 0 nil
+                                             "-c" (concat "cat > "
+                                                         (shell-quote-argument file))))
+            (with-timeout (10 (ert-fail "No output from the process"))
+              (while (< (file-attribute-size (file-attributes file)) 5)
+                (sleep-for 0.1)))
+            (with-temp-buffer
+              (insert-file-contents file)
+              (should (equal (buffer-string) "hello"))))
+        (delete-file file)))))
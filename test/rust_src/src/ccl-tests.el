@@ -0,0 +1,57 @@
+;;; ccl-tests.el --- Tests for ccl.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+(require 'ccl)
+
+(defun ccl-tests--run (code &rest registers)
+  "Run the CCL CODE with REGISTERS set, and return the registers."
+  (let ((regs (make-vector 8 0)))
+    (dotimes (i (length registers))
+      (aset regs i (nth i registers)))
+    (ccl-execute (ccl-compile (list 0 code)) regs)
+    regs))
+
+(ert-deftest ccl-arithmetic ()
+  (should (equal (ccl-tests--run '((r2 = r0 * r1) (r3 = r0 - r1)) 3 4)
+                 [3 4 12 -1 0 0 0 0]))
+  (should (equal (ccl-tests--run '((r0 += 5) (r1 <<= 2) (r2 = r0 < r1)) 1 3)
+                 [6 12 1 0 0 0 0 0]))
+  ;; `//' leaves the remainder in r7.
+  (should (equal (ccl-tests--run '((r0 = r0 // 3)) 14)
+                 [4 0 0 0 0 0 0 2]))
+  (should (equal (ccl-tests--run '((if (r0 > 10) (r1 = 1) (r1 = 2))) 11)
+                 [11 1 0 0 0 0 0 1])))
+
+(ert-deftest ccl-division-by-zero ()
+  (should-error (ccl-tests--run '((r0 /= r1)) 1 0))
+  (should-error (ccl-tests--run '((r2 = r0 % r1)) 1 0)))
+
+(ert-deftest ccl-execute-on-string-loop ()
+  (let ((upcase (ccl-compile
+                 '(1 ((loop (read r0)
+                            (if (r0 >= ?a) (if (r0 <= ?z) (r0 -= 32)))
+                            (write r0)
+                            (repeat)))))))
+    (should (equal (ccl-execute-on-string upcase (make-vector 9 nil)
+                                          "Hello, world")
+                   "HELLO, WORLD")))
+  (let ((greeting (ccl-compile '(1 ((write "hello") (r0 = 3)))))
+        (status (make-vector 9 nil)))
+    (should (equal (ccl-execute-on-string greeting status "") "hello"))
+    (should (= (aref status 0) 3))))
+
+(ert-deftest ccl-lookup-integer ()
+  (let ((table (make-hash-table)))
+    (puthash 1 ?a table)
+    (define-translation-hash-table 'ccl-tests--table table)
+    (let ((regs (ccl-tests--run '((lookup-integer ccl-tests--table r0 r1)) 1)))
+      (should (= (aref regs 1) ?a))
+      (should (= (aref regs 7) 1)))
+    (let ((regs (ccl-tests--run '((lookup-integer ccl-tests--table r0 r1)) 2)))
+      (should (= (aref regs 7) 0)))))
+
+(provide 'ccl-tests)
+
+;;; ccl-tests.el ends here
@@ -0,0 +1,21 @@
+;;; charset-tests.el --- Tests for charset.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest charset-tests--map-file ()
+  ;; `windows-1252' gets its characters from the map file CP1252.
+  (should (eq (decode-char 'windows-1252 #x80) ?€))
+  (should (eq (encode-char ?€ 'windows-1252) #x80))
+  (should (eq (decode-char 'windows-1252 #x41) ?A))
+  (should-not (encode-char ?一 'windows-1252)))
+
+(ert-deftest charset-tests--unify-map-file ()
+  ;; `big5' is unified with Unicode by the map file BIG5.
+  (should (eq (decode-char 'big5 #xA440) ?一))
+  (should (eq (encode-char ?一 'big5) #xA440)))
+
+(provide 'charset-tests)
+
+;;; charset-tests.el ends here
@@ -0,0 +1,44 @@
+;;; cmdhist-tests.el --- Tests for cmdhist.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest cmdhist-record-and-sort ()
+  (let ((command-usage-store nil))
+    (command-usage-reset)
+    (should (= (command-usage-record 'forward-char) 1))
+    (should (= (command-usage-record "forward-char") 2))
+    (should (= (command-usage-record 'backward-char) 1))
+    (should (equal (command-usage-sort '("next-line" "backward-char"
+                                         "forward-char" "beginning-of-line"))
+                   '("forward-char" "backward-char"
+                     "beginning-of-line" "next-line")))
+    (let ((stats (command-usage-statistics)))
+      (should (equal (mapcar #'car stats) '("forward-char" "backward-char")))
+      (should (equal (nth 1 (car stats)) 2))
+      (should (floatp (nth 3 (car stats)))))
+    (command-usage-reset)
+    (should-not (command-usage-statistics))))
+
+(ert-deftest cmdhist-persistent-store ()
+  (let* ((file (make-temp-file "cmdhist-tests"))
+         (command-usage-store file))
+    (unwind-protect
+        (progn
+          (delete-file file)
+          (command-usage-reset)
+          (command-usage-record 'undo)
+          (command-usage-record 'undo)
+          (should (equal (car (persistent-store-get "command-usage:undo" nil file))
+                         2))
+          (command-usage-reset)
+          (should-not (persistent-store-get "command-usage:undo" nil file)))
+      (when (file-exists-p file)
+        (delete-file file))
+      (let ((command-usage-store nil))
+        (command-usage-reset)))))
+
+(provide 'cmdhist-tests)
+
+;;; cmdhist-tests.el ends here
@@ -0,0 +1,62 @@
+;;; dabbrev-tests.el --- Tests for dabbrev.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest dabbrev-completions-nearest-first ()
+  (with-temp-buffer
+    (insert "footnote foobar fooled\nfoo")
+    (insert " foolish food")
+    (goto-char 27)
+    (should (equal (dabbrev-completions "foo")
+                   '("foolish" "fooled" "food" "foobar" "footnote")))
+    (should (equal (dabbrev-completions "foo" nil 2) '("foolish" "fooled")))
+    (should (equal (dabbrev-completions "fool") '("foolish" "fooled")))
+    ;; The word at point is the one being completed.
+    (goto-char (point-max))
+    (should-not (member "food" (dabbrev-completions "foo")))
+    (should-not (dabbrev-completions "bar"))))
+
+(ert-deftest dabbrev-completions-other-buffers ()
+  (let ((other (generate-new-buffer "dabbrev-tests")))
+    (unwind-protect
+        (with-temp-buffer
+          (with-current-buffer other
+            (insert "frobnicate frobs frobozz"))
+          (insert "frobs fr")
+          (should (equal (dabbrev-completions "fr") '("frobs")))
+          (should (equal (dabbrev-completions "fr" (list other))
+                         '("frobs" "frobnicate" "frobozz")))
+          (should (member "frobozz" (dabbrev-completions "frob" t))))
+      (kill-buffer other))))
+
+(ert-deftest dabbrev-completions-after-changes ()
+  (with-temp-buffer
+    (insert "alpha alphabet\n")
+    (should (equal (dabbrev-completions "alph") '("alphabet" "alpha")))
+    (goto-char (point-min))
+    (insert "alphanumeric ")
+    (goto-char (point-max))
+    (should (equal (dabbrev-completions "alph")
+                   '("alphabet" "alpha" "alphanumeric")))
+    ;; Joining two words.
+    (goto-char (point-min))
+    (search-forward "alpha ")
+    (delete-char -1)
+    (goto-char (point-max))
+    (should (equal (dabbrev-completions "alph")
+                   '("alphaalphabet" "alphanumeric")))
+    (erase-buffer)
+    (should-not (dabbrev-completions "alph"))))
+
+(ert-deftest dabbrev-completions-ignore-case ()
+  (with-temp-buffer
+    (insert "Hello helium HELP ")
+    (should (equal (dabbrev-completions "hel") '("helium")))
+    (should (equal (dabbrev-completions "hel" nil nil t)
+                   '("HELP" "helium" "Hello")))))
+
+(provide 'dabbrev-tests)
+
+;;; dabbrev-tests.el ends here
@@ -62,6 +62,19 @@
                    (set-buffer-multibyte nil)
                    (zlib-decompress-region (point-min) (point-max)))))))
 
+(ert-deftest brotli--decompress ()
+  "Test decompressing Brotli data."
+  (with-temp-buffer
+    (set-buffer-multibyte nil)
+    ;; "foo\n" in an uncompressed meta-block.
+    (insert (unibyte-string #x30 #x00 #x10 ?f ?o ?o ?\n #x03))
+    (should (brotli-decompress-region (point-min) (point-max)))
+    (should (equal (buffer-string) "foo\n"))
+    (erase-buffer)
+    (insert "not brotli")
+    (should-not (brotli-decompress-region (point-min) (point-max)))
+    (should (equal (buffer-string) "not brotli"))))
+
 (provide 'decompress-tests)
 
 ;;; decompress-tests.el ends here.
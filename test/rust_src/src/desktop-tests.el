@@ -0,0 +1,76 @@
+;;; desktop-tests.el --- Tests for desktop.rs
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest desktop-tests--binary-round-trip ()
+  (dolist (object (list nil t 0 -1 most-positive-fixnum most-negative-fixnum
+                        1.5 -0.25 "" "unibyte" "multibyte é" 'foo :key
+                        '(1 2 3) '(a . b) '(1 (2 (3 . "x")) [4 5])
+                        [] [nil t "s"]))
+    (should (equal (desktop-binary-decode (desktop-binary-encode object))
+                   object))))
+
+(ert-deftest desktop-tests--binary-encode-unreadable ()
+  (with-temp-buffer
+    (insert "abc")
+    (let ((data (desktop-binary-encode (list (current-buffer) (point-marker)
+                                             (propertize "x" 'face 'bold)))))
+      (should-not (multibyte-string-p data))
+      (let ((decoded (desktop-binary-decode data)))
+        (should (equal decoded '(nil 4 "x")))
+        (should-not (text-properties-at 0 (nth 2 decoded)))))))
+
+(ert-deftest desktop-tests--binary-encode-properties ()
+  (let* ((string (concat (propertize "ab" 'face 'bold)
+                         "cd"
+                         (propertize "ef" 'face 'bold 'help-echo "e")
+                         (propertize "g" 'face 'bold)))
+         (decoded (desktop-binary-decode
+                   (desktop-binary-encode (list string [x "y"]) t))))
+    (should (equal decoded (list string [x "y"])))
+    (should (equal (object-intervals (car decoded))
+                   (object-intervals string)))
+    (should-not (object-intervals (aref (cadr decoded) 1)))))
+
+(ert-deftest desktop-tests--binary-encode-many-properties ()
+  (let ((string (make-string 300 ?x)))
+    (dotimes (i 100)
+      (put-text-property (* 3 i) (+ (* 3 i) 1 (% i 2)) 'face (% i 3) string))
+    (let ((decoded (desktop-binary-decode (desktop-binary-encode string t))))
+      (should (equal (object-intervals decoded) (object-intervals string))))))
+
+(ert-deftest desktop-tests--binary-encode-uninterned ()
+  (let ((decoded (desktop-binary-decode (desktop-binary-encode (make-symbol "foo")))))
+    (should (symbolp decoded))
+    (should (equal (symbol-name decoded) "foo"))
+    (should-not (eq decoded 'foo))))
+
+(ert-deftest desktop-tests--binary-decode-invalid ()
+  (should-error (desktop-binary-decode "not desktop data"))
+  (let ((data (desktop-binary-encode '(1 2 3))))
+    (should-error (desktop-binary-decode (substring data 0 -1)))))
+
+(ert-deftest desktop-tests--binary-encode-circular ()
+  (let ((list (list 1 2 3)))
+    (setcdr (last list) list)
+    (should-error (desktop-binary-encode list))))
+
+(ert-deftest desktop-tests--buffer-state ()
+  (with-temp-buffer
+    (insert "hello world")
+    (should-not (desktop-buffer-state (current-buffer)))
+    (put-text-property 1 6 'face 'bold)
+    (put-text-property 1 6 'help-echo "hi")
+    (push (copy-marker 3) mark-ring)
+    (push (copy-marker 7) mark-ring)
+    (narrow-to-region 2 8)
+    (should (equal (desktop-buffer-state (current-buffer) '(face))
+                   '(:mark-ring (7 3)
+                     :restriction (2 . 8)
+                     :text-properties ((1 6 (face bold))))))))
+
+(provide 'desktop-tests)
+
+;;; desktop-tests.el ends here
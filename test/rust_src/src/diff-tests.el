@@ -0,0 +1,76 @@
+;;; diff-tests.el --- Tests for diff.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest diff-tests--strings ()
+  (should (equal (diff-regions-native "one\ntwo\nthree\n" "one\ntwo\nthree\n")
+                 nil))
+  (should (equal (diff-regions-native "one\ntwo\nthree\n" "one\n2\nthree\nfour\n")
+                 '((4 8 4 6 nil) (14 14 12 17 nil))))
+  (should (equal (diff-regions-native "abc" "axc" 'chars)
+                 '((1 2 1 2 nil))))
+  (should (equal (diff-regions-native "the quick fox" "the slow fox" 'words)
+                 '((4 9 4 8 nil)))))
+
+(ert-deftest diff-tests--buffers ()
+  (let ((a (generate-new-buffer "diff-tests-a"))
+        (b (generate-new-buffer "diff-tests-b")))
+    (unwind-protect
+        (progn
+          (with-current-buffer a
+            (insert "header\nlet x = foo(1);\nfooter\n"))
+          (with-current-buffer b
+            (insert "header\nlet y = foo(2);\nfooter\n"))
+          ;; Positions are buffer positions, and hunks are refined into
+          ;; words.
+          (should (equal (diff-regions-native a b nil nil t)
+                         '((8 24 8 24 ((12 13 12 13) (20 21 20 21))))))
+          ;; Regions of buffers.
+          (should (equal (diff-regions-native (list a 8 24) (list b 8 24) 'words)
+                         '((12 13 12 13 nil) (20 21 20 21 nil))))
+          (with-current-buffer a
+            (narrow-to-region 1 8))
+          (should (equal (diff-regions-native a "header\n") nil))
+          (should-error (diff-regions-native (list b 1 100) "")
+                        :type 'args-out-of-range))
+      (kill-buffer a)
+      (kill-buffer b))))
+
+(ert-deftest diff-tests--patience ()
+  (let ((a "fn a() {\n    x\n}\nfn b() {\n    y\n}\n")
+        (b "fn b() {\n    y\n}\nfn a() {\n    x\n}\n"))
+    ;; Patience diff moves one of the functions as a whole.
+    (should (equal (diff-regions-native a b 'lines 'patience)
+                   '((0 17 0 0 nil) (32 32 15 32 nil))))
+    (should-error (diff-regions-native a b 'lines 'unknown))))
+
+(ert-deftest diff-tests--ignore-whitespace ()
+  (should (equal (diff-regions-native "a  b\nc\n" "a b\nc\n" nil nil nil t)
+                 nil))
+  (should (equal (diff-regions-native "a  b\nc\n" "a b\nc\n")
+                 '((0 5 0 4 nil)))))
+
+(ert-deftest diff-tests--merge ()
+  (with-temp-buffer
+    (should (= (merge-regions-native "a\nB\nc\nd\n" "a\nb\nc\nd\n" "a\nb\nc\nD\n")
+               0))
+    (should (equal (buffer-string) "a\nB\nc\nD\n")))
+  (with-temp-buffer
+    (insert "before\n")
+    (let ((target (current-buffer)))
+      (with-temp-buffer
+        (insert "x\nmine\ny\n")
+        (should (= (merge-regions-native (current-buffer) "x\nbase\ny\n"
+                                         "x\ntheirs\ny\n" target
+                                         '("HEAD" "base" "feature")
+                                         t)
+                   1))))
+    (should (equal (buffer-string)
+                   (concat "before\nx\n<<<<<<< HEAD\nmine\n||||||| base\nbase\n"
+                           "=======\ntheirs\n>>>>>>> feature\ny\n")))))
+
+(provide 'diff-tests)
+
+;;; diff-tests.el ends here
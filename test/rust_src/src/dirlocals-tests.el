@@ -0,0 +1,72 @@
+;;; dirlocals-tests.el --- Tests for dirlocals.rs
+
+;;; Code:
+
+(require 'ert)
+
+(defmacro dirlocals-tests--with-tree (files &rest body)
+  "Create FILES, an alist of names and contents, in a temporary directory.
+Evaluate BODY with `root' bound to the directory."
+  (declare (indent 1))
+  `(let ((root (file-name-as-directory (make-temp-file "dirlocals" t))))
+     (unwind-protect
+         (progn
+           (dolist (file ,files)
+             (let ((name (expand-file-name (car file) root)))
+               (make-directory (file-name-directory name) t)
+               (write-region (cdr file) nil name nil 'silent)))
+           ,@body)
+       (delete-directory root t))))
+
+(ert-deftest dirlocals-tests--dir-locals-resolve ()
+  (dirlocals-tests--with-tree
+      '((".dir-locals.el" . "((nil . ((fill-column . 70)))
+ (emacs-lisp-mode . ((indent-tabs-mode . nil)))
+ (\"sub/\" . ((nil . ((fill-column . 60))))))")
+        ("sub/a.el" . "")
+        ("b.txt" . ""))
+    (should (equal (dir-locals-resolve (expand-file-name "b.txt" root) 'text-mode)
+                   (cons root '((fill-column . 70)))))
+    (let ((resolved (dir-locals-resolve (expand-file-name "sub/a.el" root)
+                                        'emacs-lisp-mode)))
+      (should (equal (car resolved) root))
+      (should (equal (sort (cdr resolved)
+                           (lambda (a b) (string< (car a) (car b))))
+                     '((fill-column . 60) (indent-tabs-mode)))))))
+
+(ert-deftest dirlocals-tests--dir-locals-resolve-2 ()
+  (dirlocals-tests--with-tree
+      '((".dir-locals.el" . "((nil . ((fill-column . 70) (tab-width . 4))))")
+        (".dir-locals-2.el" . "((nil . ((tab-width . 2))))"))
+    (let ((variables (cdr (dir-locals-resolve (expand-file-name "x" root) nil))))
+      (should (equal (cdr (assq 'fill-column variables)) 70))
+      (should (equal (cdr (assq 'tab-width variables)) 2)))))
+
+(ert-deftest dirlocals-tests--dir-locals-resolve-invalidation ()
+  (dirlocals-tests--with-tree
+      '((".dir-locals.el" . "((nil . ((fill-column . 70))))"))
+    (let ((file (expand-file-name "x" root)))
+      (should (equal (cdr (dir-locals-resolve file nil)) '((fill-column . 70))))
+      (write-region "((nil . ((fill-column . 100))))" nil
+                    (expand-file-name ".dir-locals.el" root) nil 'silent)
+      (should (equal (cdr (dir-locals-resolve file nil)) '((fill-column . 100))))
+      (delete-file (expand-file-name ".dir-locals.el" root))
+      (should-not (dir-locals-resolve file nil)))))
+
+(ert-deftest dirlocals-tests--dir-locals-resolve-editorconfig ()
+  (dirlocals-tests--with-tree
+      '((".editorconfig" . "root = true\n[*]\nindent_style = space\nindent_size = 2\n[Makefile]\nindent_style = tab\n")
+        ("src/.dir-locals.el" . "((nil . ((tab-width . 8))))"))
+    (let ((file (expand-file-name "src/main.c" root)))
+      (should-not (dir-locals-resolve (expand-file-name "Makefile" root) nil))
+      (should (equal (dir-locals-resolve (expand-file-name "Makefile" root) nil t)
+                     (cons root '((standard-indent . 2) (tab-width . 2)
+                                  (indent-tabs-mode . t)))))
+      (let ((resolved (dir-locals-resolve file nil t)))
+        (should (equal (car resolved) (expand-file-name "src/" root)))
+        (should (equal (cdr (assq 'tab-width (cdr resolved))) 8))
+        (should (eq (cdr (assq 'indent-tabs-mode (cdr resolved))) nil))))))
+
+(provide 'dirlocals-tests)
+
+;;; dirlocals-tests.el ends here
@@ -83,3 +83,90 @@
       (insert payload)
       (should (equal (delete-and-extract-region 12 5) " buffer"))
       (should (equal (buffer-string) "test contents")))))
+
+(ert-deftest test-translate-region-internal--string-table ()
+  (require 'rot13)
+  (with-temp-buffer
+    (insert "Hello, world")
+    (should (= (translate-region-internal (point-min) (point-max)
+                                          rot13-translate-table)
+               10))
+    (should (equal (buffer-string) "Uryyb, jbeyq"))))
+
+(ert-deftest test-translate-region-internal--change-hooks ()
+  (with-temp-buffer
+    (buffer-enable-undo)
+    (insert "abc abc abc")
+    (undo-boundary)
+    (let* ((changes 0)
+           (after-change-functions
+            (list (lambda (beg end _len)
+                    (setq changes (1+ changes))
+                    (should (equal (list beg end) '(1 12)))))))
+      (should (= (translate-region-internal
+                  (point-min) (point-max)
+                  (make-translation-table-from-alist '((?a . ?x) (?c . ?z))))
+                 6))
+      (should (= changes 1)))
+    (should (equal (buffer-string) "xbz xbz xbz"))
+    (primitive-undo 1 buffer-undo-list)
+    (should (equal (buffer-string) "abc abc abc"))))
+
+(ert-deftest test-translate-region-internal--length-changes ()
+  (with-temp-buffer
+    (insert "a ab abc c")
+    (should (= (translate-region-internal
+                (point-min) (point-max)
+                (make-translation-table-from-alist
+                 '((?a . ?α) ([?b ?c] . [?B ?C ?!]) (?c . [?x ?y]))))
+               8))
+    (should (equal (buffer-string) "α αb αBC! xy"))))
+
+(defmacro editfns-tests--with-source (text &rest body)
+  "Run BODY in a temporary buffer, with `source' bound to a buffer with TEXT."
+  (declare (indent 1))
+  `(let ((source (generate-new-buffer " *source*")))
+     (unwind-protect
+         (progn
+           (with-current-buffer source
+             (insert ,text))
+           (with-temp-buffer
+             ,@body))
+       (kill-buffer source))))
+
+(ert-deftest test-replace-buffer-contents--markers ()
+  (editfns-tests--with-source "(defun foo (x)\n  (bar x))\n"
+    (insert "(defun  foo(x)\n(bar x))\n")
+    (let ((marker (copy-marker 17)))
+      (goto-char 19)
+      (should (eq (replace-buffer-contents source) t))
+      (should (equal (buffer-string) "(defun foo (x)\n  (bar x))\n"))
+      ;; The marker stays before "b", and point before "r".
+      (should (equal (char-after marker) ?b))
+      (should (= marker 19))
+      (should (= (point) 21)))))
+
+(ert-deftest test-replace-buffer-contents--change-hooks ()
+  (editfns-tests--with-source "one two three four"
+    (insert "one 2 three 4")
+    (let* ((changes nil)
+           (after-change-functions
+            (list (lambda (beg end len)
+                    (push (list beg end len) changes)))))
+      (replace-buffer-contents source)
+      (should (equal changes '((1 19 13)))))
+    (should (equal (buffer-string) "one two three four"))))
+
+(ert-deftest test-replace-buffer-contents--budgets ()
+  (editfns-tests--with-source "abcdefghij"
+    (insert "jihgfedcba")
+    ;; Too low a cost limit still gives the right text.
+    (should (eq (replace-buffer-contents source nil 1) t))
+    (should (equal (buffer-string) "abcdefghij"))
+    (erase-buffer)
+    (insert "jihgfedcba")
+    ;; Without any time, the text is replaced as a whole.
+    (should (eq (replace-buffer-contents source 0) nil))
+    (should (equal (buffer-string) "abcdefghij"))
+    (should-error (replace-buffer-contents source nil -1))
+    (should-error (replace-buffer-contents (current-buffer)))))
@@ -0,0 +1,35 @@
+;;; filehash-tests.el --- Tests for filehash.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(defun filehash-tests--hash (file)
+  "Return the result of `file-content-hash' for FILE, waiting for it."
+  (let ((result 'pending))
+    (file-content-hash file (lambda (name hash) (setq result (cons name hash))))
+    (with-timeout (10 (error "Timed out waiting for the hash of %s" file))
+      (while (eq result 'pending)
+        (accept-process-output nil 0.05)))
+    result))
+
+(ert-deftest filehash-tests--file-content-hash ()
+  (let ((file (make-temp-file "filehash")))
+    (unwind-protect
+        (progn
+          (let ((coding-system-for-write 'no-conversion))
+            (write-region "abc" nil file nil 'silent))
+          (should (equal (filehash-tests--hash file)
+                         (cons file "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85")))
+          (write-region "" nil file nil 'silent)
+          (should (equal (cdr (filehash-tests--hash file))
+                         "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262")))
+      (delete-file file))))
+
+(ert-deftest filehash-tests--file-content-hash-missing ()
+  (let ((file (make-temp-name (expand-file-name "filehash" temporary-file-directory))))
+    (should (equal (filehash-tests--hash file) (cons file nil)))))
+
+(provide 'filehash-tests)
+
+;;; filehash-tests.el ends here
@@ -39,3 +39,35 @@
       (should-not (file-name-case-insensitive-p file)))
     (when (eq system-type 'darwin)
       (should (file-name-case-insensitive-p file)))))
+
+(ert-deftest test-visited-file-modtime-flags ()
+  (with-temp-buffer
+    (set-visited-file-modtime 0)
+    (should (equal (visited-file-modtime) 0))
+    (set-visited-file-modtime -1)
+    (should (equal (visited-file-modtime) -1))
+    (should-error (set-visited-file-modtime 1) :type 'args-out-of-range)
+    (should-error (set-visited-file-modtime -2) :type 'args-out-of-range)))
+
+(ert-deftest test-verify-visited-file-modtime ()
+  (let ((file (make-temp-file "fileio")))
+    (unwind-protect
+        (with-current-buffer (find-file-noselect file)
+          (unwind-protect
+              (let ((mtime (file-attribute-modification-time
+                            (file-attributes file))))
+                (should (verify-visited-file-modtime))
+                (should (equal (visited-file-modtime) mtime))
+                ;; The times are compared to the nanosecond.
+                (set-visited-file-modtime (time-add mtime '(0 0 0 1000)))
+                (should-not (verify-visited-file-modtime))
+                (set-visited-file-modtime mtime)
+                (should (verify-visited-file-modtime (current-buffer)))
+                (set-visited-file-modtime)
+                (should (equal (visited-file-modtime) mtime))
+                ;; An unknown modification time matches anything.
+                (set-visited-file-modtime 0)
+                (should (verify-visited-file-modtime)))
+            (set-buffer-modified-p nil)
+            (kill-buffer)))
+      (delete-file file))))
@@ -0,0 +1,62 @@
+;;; filelocals-tests.el --- Tests for filelocals.rs
+
+;;; Code:
+
+(require 'ert)
+
+(defun filelocals-tests--prop-line (text &optional handle-mode)
+  "Parse the -*- line TEXT as `hack-local-variables-prop-line' does."
+  (with-temp-buffer
+    (insert text)
+    (goto-char (point-min))
+    (let ((end (set-auto-mode-1)))
+      (file-local-variables-prop-line (point) end handle-mode))))
+
+(ert-deftest filelocals-tests--prop-line ()
+  (should (equal (filelocals-tests--prop-line
+                  ";; -*- Mode: Emacs-Lisp; fill-column: 70; coding: utf-8 -*-\n")
+                 '((fill-column . 70) (mode . Emacs-Lisp))))
+  (should (equal (filelocals-tests--prop-line
+                  ";; -*- mode: c; eval: (foo) -*-\n" 'no-mode)
+                 '((eval foo))))
+  (should (eq (filelocals-tests--prop-line
+               ";; -*- Mode: Emacs-Lisp; fill-column: 70 -*-\n" t)
+              'emacs-lisp-mode))
+  (should (eq (filelocals-tests--prop-line "/* -*- c++ -*- */\n" t) 'c++-mode))
+  (should-not (filelocals-tests--prop-line "/* -*- c++ -*- */\n"))
+  (should-not (filelocals-tests--prop-line ";; -*- not a variable -*-\n")))
+
+(ert-deftest filelocals-tests--block ()
+  (with-temp-buffer
+    (insert "text\n\f\n/* Local Variables: */\n/* mode: c */\n"
+            "/* tab-width: 4 */\n/* coding: utf-8 */\n/* End: */\n")
+    (should (equal (file-local-variables-block)
+                   '((tab-width . 4) (mode . c))))
+    (should (equal (file-local-variables-block 'no-mode)
+                   '((tab-width . 4))))
+    (should (eq (file-local-variables-block t) 'c-mode)))
+  (with-temp-buffer
+    (insert ";; Local Variables:\n;; mode: auto-fill-minor\n;; End:\n")
+    (should-not (file-local-variables-block t)))
+  (with-temp-buffer
+    (insert "# Local Variables:\n# fill-column: 70\nfoo: 1\n# End:\n")
+    (should-error (file-local-variables-block))))
+
+(ert-deftest filelocals-tests--coding-tags ()
+  (with-temp-buffer
+    (insert ";; -*- coding: latin-1; enable-character-translation: nil -*-\n")
+    (goto-char (point-min))
+    (let ((end (set-auto-mode-1)))
+      (should (equal (file-local-coding-tags (point) end 1 1)
+                     '(latin-1 "nil" nil)))))
+  (with-temp-buffer
+    (insert "text\n;; Local Variables:\n;; coding: utf-8\n"
+            ";; unibyte: t\n;; End:\n")
+    (should (equal (file-local-coding-tags nil nil (point-min) (point-max))
+                   '(utf-8 nil nil)))
+    (should (equal (file-local-coding-tags nil nil (point-min) (point-max) t)
+                   '(raw-text nil tail)))))
+
+(provide 'filelocals-tests)
+
+;;; filelocals-tests.el ends here
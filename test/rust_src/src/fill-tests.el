@@ -0,0 +1,74 @@
+;;; fill-tests.el --- Tests for fill.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(defun fill-tests--breaks (text column &rest args)
+  "Return the line breaks of TEXT filled to COLUMN, as strings.
+ARGS are the remaining arguments of `fill-line-breaks'."
+  (with-temp-buffer
+    (insert text)
+    (mapcar (lambda (pos) (buffer-substring pos (point-max)))
+            (apply #'fill-line-breaks (point-min) (point-max) column args))))
+
+(ert-deftest fill-tests--greedy ()
+  (should (equal (fill-tests--breaks "aaa bb cc dddd e" 6)
+                 '("cc dddd e" "dddd e")))
+  (should (equal (fill-tests--breaks "aaa bb cc dddd e" 20) nil))
+  (should (equal (fill-tests--breaks "aaa bb cc dddd e" 8)
+                 '("cc dddd e" "e")))
+  ;; The lines after the first start at INDENT.
+  (should (equal (fill-tests--breaks "aaa bb cc dddd e" 8 3)
+                 '("cc dddd e" "dddd e" "e")))
+  ;; Wide characters.
+  (should (equal (fill-tests--breaks "一一 一 aa" 5)
+                 '("一 aa"))))
+
+(ert-deftest fill-tests--optimal ()
+  (should (equal (fill-tests--breaks "aaa bb cc ddddd" 6)
+                 '("cc ddddd" "ddddd")))
+  (should (equal (fill-tests--breaks "aaa bb cc ddddd" 6 0 t)
+                 '("bb cc ddddd" "ddddd"))))
+
+(ert-deftest fill-tests--nobreak ()
+  (let ((sentence-end-double-space t))
+    (should (equal (fill-tests--breaks "Mr. Smith  Go." 8)
+                   '("Go."))))
+  (let ((sentence-end-double-space nil))
+    (should (equal (fill-tests--breaks "Mr. Smith  Go." 8)
+                   '("Smith  Go." "Go.")))))
+
+(ert-deftest fill-tests--cjk ()
+  (should (equal (fill-tests--breaks "一二三四五六" 6) '("四五六")))
+  (should (equal (fill-tests--breaks "abc 一二三四" 8) '("三四")))
+  (let ((enable-kinsoku nil))
+    (should (equal (fill-tests--breaks "一二三。四五" 6) '("。四五")))))
+
+(ert-deftest fill-tests--kinsoku ()
+  (autoload-do-load (symbol-function 'kinsoku) 'kinsoku)
+  (let ((enable-kinsoku t))
+    ;; A full stop doesn't start a line.
+    (should (equal (fill-tests--breaks "一二三。四五" 6)
+                   '("三。四五" "五")))
+    ;; An opening bracket doesn't end one.
+    (should (equal (fill-tests--breaks "一二「三四」" 6)
+                   '("「三四」" "四」")))))
+
+(ert-deftest fill-tests--fill-paragraph ()
+  (with-temp-buffer
+    (insert "The quick brown fox jumps over the lazy dog.")
+    (setq fill-column 15)
+    (fill-paragraph)
+    (should (equal (buffer-string)
+                   "The quick brown\nfox jumps over\nthe lazy dog.")))
+  (with-temp-buffer
+    (insert ";; one two three four five six")
+    (setq fill-column 14)
+    (let ((fill-prefix ";; "))
+      (fill-region-as-paragraph (point-min) (point-max)))
+    (should (equal (buffer-string) ";; one two\n;; three four\n;; five six"))))
+
+(provide 'fill-tests)
+
+;;; fill-tests.el ends here
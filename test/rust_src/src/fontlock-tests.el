@@ -0,0 +1,62 @@
+;;; fontlock-tests.el --- Tests for fontlock.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+(require 'cl-lib)
+(require 'font-lock)
+
+(defconst fontlock-tests-text
+  "(defun foo (x)\n  \"A string.\"\n  (when x\n    (foo-bar 1 \"two\")))\n")
+
+(defun fontlock-tests-fontify (keywords native)
+  "Fontify `fontlock-tests-text' with KEYWORDS and return its intervals.
+If NATIVE is nil, `font-lock-fontify-keyword-native' is not used."
+  (with-temp-buffer
+    (insert fontlock-tests-text)
+    (setq-local font-lock-set-defaults t)
+    (setq-local font-lock-keywords (font-lock-compile-keywords keywords))
+    (put-text-property 20 25 'face 'italic)
+    (if native
+        (font-lock-fontify-keywords-region (point-min) (point-max))
+      (cl-letf (((symbol-function 'font-lock-fontify-keyword-native) nil))
+        (font-lock-fontify-keywords-region (point-min) (point-max))))
+    (object-intervals (current-buffer))))
+
+(ert-deftest font-lock-native-matches-lisp ()
+  (dolist (keywords
+           '((("(\\(defun\\) \\(\\sw+\\)"
+               (1 font-lock-keyword-face) (2 font-lock-function-name-face)))
+             (("\\_<when\\_>" . font-lock-keyword-face)
+              ("\"[^\"]*\"" 0 'bold t)
+              ("[0-9]+" . 'underline))
+             (("A \\(str\\)?\\(x\\)?" (1 'bold keep) (2 'italic nil t))
+              ("x*" . font-lock-variable-name-face))
+             (("foo\\(-bar\\)?" (0 'bold) (1 'italic t t))
+              (ignore 0 'bold))))
+    (should (equal (fontlock-tests-fontify keywords t)
+                   (fontlock-tests-fontify keywords nil)))))
+
+(ert-deftest font-lock-native-keyword-kinds ()
+  (with-temp-buffer
+    (insert "foo bar")
+    (should (font-lock-fontify-keyword-native
+             '("bar" (0 font-lock-keyword-face)) 1 8))
+    (should (eq (get-text-property 5 'face) 'font-lock-keyword-face))
+    ;; Function matchers, anchored highlights and computed faces are
+    ;; left to Lisp.
+    (should-not (font-lock-fontify-keyword-native
+                 '(ignore (0 'bold)) 1 8))
+    (should-not (font-lock-fontify-keyword-native
+                 '("foo" ("bar" nil nil (0 'bold))) 1 8))
+    (should-not (font-lock-fontify-keyword-native
+                 '("foo" (0 (if t 'bold 'italic))) 1 8))
+    (should-not (font-lock-fontify-keyword-native
+                 '("foo" (0 'bold prepend)) 1 8))
+    (should-not (get-text-property 1 'face))
+    (should-error (font-lock-fontify-keyword-native
+                   '("foo" (1 'bold)) 1 8))))
+
+(provide 'fontlock-tests)
+
+;;; fontlock-tests.el ends here
@@ -0,0 +1,63 @@
+;;; glob-tests.el --- Tests for glob.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(defmacro glob-tests-with-tree (&rest body)
+  "Run BODY in a temporary directory with a few files."
+  (declare (indent 0))
+  `(let* ((dir (file-name-as-directory (make-temp-file "glob-tests" t)))
+          (default-directory dir))
+     (unwind-protect
+         (progn
+           (make-directory "sub/deep" t)
+           (dolist (file '("a.c" "b.h" "B.txt" ".hidden.c" "sub/c.c"
+                           "sub/deep/d.c"))
+             (write-region "" nil file))
+           ,@body)
+       (delete-directory dir t))))
+
+(ert-deftest file-expand-wildcards-native ()
+  (glob-tests-with-tree
+    (should (equal (file-expand-wildcards-native "*.c") '(".hidden.c" "a.c")))
+    (should (equal (file-expand-wildcards-native "[ab].?") '("a.c" "b.h")))
+    (should (equal (file-expand-wildcards-native "[!a].*") '("B.txt" "b.h")))
+    (should (equal (file-expand-wildcards-native "s*/*.c") '("sub/c.c")))
+    (should (equal (file-expand-wildcards-native "s*/") '("sub/")))
+    (should (equal (file-expand-wildcards-native "a.c") '("a.c")))
+    (should-not (file-expand-wildcards-native "x*"))
+    (should (equal (file-expand-wildcards-native "sub/*.c" t)
+                   (list (expand-file-name "sub/c.c"))))
+    (should (equal (file-expand-wildcards-native (expand-file-name "*.h"))
+                   (list (expand-file-name "b.h"))))))
+
+(ert-deftest file-expand-wildcards-native-recursive ()
+  (glob-tests-with-tree
+    (should (equal (file-expand-wildcards-native "**/*.c")
+                   '(".hidden.c" "a.c" "sub/c.c" "sub/deep/d.c")))
+    (should (equal (file-expand-wildcards-native "sub/**")
+                   '("sub/c.c" "sub/deep" "sub/deep/d.c")))))
+
+(ert-deftest file-expand-wildcards-native-options ()
+  (glob-tests-with-tree
+    (let ((wildcard-match-hidden-files nil))
+      (should (equal (file-expand-wildcards-native "*.c") '("a.c")))
+      (should (equal (file-expand-wildcards-native ".*.c") '(".hidden.c"))))
+    (let ((wildcard-ignore-case t))
+      (should (equal (file-expand-wildcards-native "b.*") '("B.txt" "b.h"))))
+    (let ((wildcard-ignore-case nil))
+      (should (equal (file-expand-wildcards-native "b.*") '("b.h"))))
+    (should-not (file-expand-wildcards-native "*.{c,h}"))
+    (let ((wildcard-brace-expansion t))
+      (should (equal (file-expand-wildcards-native "{a,b}.{c,h}")
+                     '("a.c" "b.h"))))))
+
+(ert-deftest file-expand-wildcards-uses-native ()
+  (glob-tests-with-tree
+    (should (equal (file-expand-wildcards "*.[ch]") '(".hidden.c" "a.c" "b.h")))
+    (should (equal (file-expand-wildcards "*/*.c") '("sub/c.c")))))
+
+(provide 'glob-tests)
+
+;;; glob-tests.el ends here
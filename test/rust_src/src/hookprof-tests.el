@@ -0,0 +1,52 @@
+;;; hookprof-tests.el --- Tests for hookprof.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+(require 'cl-lib)
+
+(defvar hookprof-tests--hook nil)
+
+(defun hookprof-tests--t (&rest _)
+  t)
+
+(defun hookprof-tests--entry (function)
+  "Return what was recorded for FUNCTION on `hookprof-tests--hook'."
+  (cl-find-if (lambda (entry)
+                (and (eq (car entry) 'hookprof-tests--hook)
+                     (eq (cadr entry) function)))
+              (hook-profile-data)))
+
+(ert-deftest hookprof-count-calls ()
+  (let ((hookprof-tests--hook (list #'ignore #'hookprof-tests--t))
+        (hook-profiling nil))
+    (hook-profile-reset)
+    (run-hooks 'hookprof-tests--hook)
+    (should-not (hookprof-tests--entry #'ignore))
+    (setq hook-profiling t)
+    (run-hooks 'hookprof-tests--hook)
+    (run-hook-with-args 'hookprof-tests--hook 1)
+    (run-hook-with-args-until-failure 'hookprof-tests--hook 2)
+    (setq hook-profiling nil)
+    (let ((entry (hookprof-tests--entry #'ignore)))
+      (should (= (nth 2 entry) 3))
+      (should (floatp (nth 3 entry))))
+    ;; `ignore' returned nil and stopped the last hook.
+    (should (= (nth 2 (hookprof-tests--entry #'hookprof-tests--t)) 2))
+    (hook-profile-reset)
+    (should-not (hookprof-tests--entry #'ignore))))
+
+(ert-deftest hookprof-sorted ()
+  (let ((hookprof-tests--hook (list #'ignore (lambda () (sleep-for 0.01))))
+        (hook-profiling t))
+    (hook-profile-reset)
+    (run-hooks 'hookprof-tests--hook)
+    (setq hook-profiling nil)
+    (let ((data (hook-profile-data)))
+      (should (= (length data) 2))
+      (should (>= (nth 3 (car data)) (nth 3 (cadr data))))
+      (should (functionp (cadr (car data)))))))
+
+(provide 'hookprof-tests)
+
+;;; hookprof-tests.el ends here
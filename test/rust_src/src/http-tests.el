@@ -0,0 +1,109 @@
+;;; http-tests.el --- Tests for http.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+(require 'url-http-native)
+
+(ert-deftest http-response-parser-chunked ()
+  (let ((parser (http-response-parser)))
+    (unwind-protect
+        (progn
+          (should (equal (http-response-parser-feed
+                          parser "HTTP/1.1 200 OK\r\nTransfer-Encoding: chu")
+                         ""))
+          (should-not (http-response-parser-head parser))
+          (should (equal (http-response-parser-feed
+                          parser "nked\r\nX-Y:  z \r\n\r\n3\r\nabc\r\n2\r\nde")
+                         "abcde"))
+          (should (equal (http-response-parser-head parser)
+                         '(200 "OK" (("transfer-encoding" . "chunked")
+                                     ("x-y" . "z"))
+                               "HTTP/1.1")))
+          (should-not (http-response-parser-done-p parser))
+          (should (equal (http-response-parser-feed parser "\r\n0\r\n\r\n")
+                         ""))
+          (should (http-response-parser-done-p parser)))
+      (http-response-parser-free parser)))
+  (should-error (http-response-parser-feed 0 "")))
+
+(ert-deftest http-response-parser-lengths ()
+  (let ((parser (http-response-parser)))
+    (http-response-parser-feed parser "HTTP/1.0 404 Not Found\r\n\r\nno")
+    (should-not (http-response-parser-done-p parser))
+    (should (http-response-parser-finish parser))
+    (http-response-parser-free parser))
+  (let ((parser (http-response-parser)))
+    (http-response-parser-feed
+     parser "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nshort")
+    (should-not (http-response-parser-finish parser))
+    (http-response-parser-free parser))
+  (let ((parser (http-response-parser t)))
+    (http-response-parser-feed
+     parser "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n")
+    (should (http-response-parser-done-p parser))
+    (should-error (http-response-parser-feed (http-response-parser) "FOO\n\n"))
+    (http-response-parser-free parser)))
+
+(ert-deftest http-response-parser-content-encoding ()
+  (let ((gzipped (with-temp-buffer
+                   (set-buffer-multibyte nil)
+                   (insert-file-contents-literally
+                    (expand-file-name "data/decompress/foo.gz"
+                                      (getenv "EMACS_TEST_DIRECTORY")))
+                   (buffer-string)))
+        (parser (http-response-parser nil t)))
+    (unwind-protect
+        (should (equal (concat
+                        (http-response-parser-feed
+                         parser
+                         (format (concat "HTTP/1.1 200 OK\r\n"
+                                         "Content-Encoding: gzip\r\n"
+                                         "Content-Length: %d\r\n\r\n")
+                                 (length gzipped)))
+                        (http-response-parser-feed parser
+                                                   (substring gzipped 0 10))
+                        (http-response-parser-feed parser
+                                                   (substring gzipped 10)))
+                       "foo\n"))
+      (http-response-parser-free parser))))
+
+(ert-deftest url-http-native-local-server ()
+  (let* ((requests nil)
+         (server
+          (make-network-process
+           :name "http-tests" :server t :host 'local :service t
+           :coding 'binary
+           :filter
+           (lambda (process string)
+             (push string requests)
+             (process-send-string
+              process
+              (if (string-match-p "\\`GET /old " string)
+                  "HTTP/1.1 301 Moved\r\nLocation: /new\r\n\r\n"
+                (concat "HTTP/1.1 200 OK\r\n"
+                        "Transfer-Encoding: chunked\r\n\r\n"
+                        "5\r\nhello\r\n0\r\n\r\n")))
+             (delete-process process))))
+         (port (process-contact server :service))
+         (status nil))
+    (unwind-protect
+        (let ((buffer (url-http-native
+                       (format "http://127.0.0.1:%d/old" port)
+                       (lambda (s) (setq status s))
+                       :proxy 'direct :timeout 10)))
+          (with-timeout (10 (ert-fail "No response"))
+            (while (not status)
+              (accept-process-output nil 0.05)))
+          (should (equal (plist-get status :status) 200))
+          (should (equal (plist-get status :url)
+                         (format "http://127.0.0.1:%d/new" port)))
+          (should (string-prefix-p "GET /new HTTP/1.1\r\n" (car requests)))
+          (should (equal (with-current-buffer buffer (buffer-string))
+                         "hello"))
+          (kill-buffer buffer))
+      (delete-process server))))
+
+(provide 'http-tests)
+
+;;; http-tests.el ends here
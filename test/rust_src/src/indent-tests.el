@@ -14,4 +14,21 @@
   (insert "\tsome more text")
   (should (equal (current-indentation) 8)))
 
+;; Four columns per level, with tabs for eight.
+(defconst indent-tests--code
+  "int\nf ()\n{\n    if (x)\n\t{\n\t    y ();\n\t}\n    return 0;\n}\n")
+
+(ert-deftest test-detect-indentation ()
+  (with-temp-buffer
+    (insert indent-tests--code)
+    (should (equal (detect-indentation) '(4 1.0)))
+    ;; Too few lines to tell the offset.
+    (should (equal (detect-indentation nil 4) '(nil nil)))
+    (let ((buffer (current-buffer)))
+      (with-temp-buffer
+        (should (equal (detect-indentation buffer) '(4 1.0))))))
+  (with-temp-buffer
+    (insert (replace-regexp-in-string "\t" "        " indent-tests--code))
+    (should (equal (detect-indentation) '(4 0.0)))))
+
 ;;; indent-tests.el ends here
@@ -0,0 +1,60 @@
+;;; jitlock-tests.el --- Tests for jitlock.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+(require 'cl-lib)
+(require 'jit-lock)
+
+(defmacro jitlock-tests-with-buffer (fontified &rest body)
+  "Run BODY in a buffer with 100 characters and FONTIFIED properties.
+FONTIFIED is a list of (START END VALUE)."
+  (declare (indent 1))
+  `(with-temp-buffer
+     (insert (make-string 100 ?x))
+     (dolist (run ,fontified)
+       (apply #'put-text-property
+              (append (butlast run) (list 'fontified (car (last run))))))
+     ,@body))
+
+(ert-deftest jit-lock-stealth-chunk-start-native-matches-lisp ()
+  (let ((jit-lock-chunk-size 10))
+    (dolist (fontified '(()
+                         ((1 101 t))
+                         ((1 50 t))
+                         ((50 101 t))
+                         ((20 40 t) (60 70 t))
+                         ((1 30 t) (30 40 defer) (40 101 t))))
+      (jitlock-tests-with-buffer fontified
+        (dolist (around '(1 2 20 30 45 50 55 65 90 101))
+          (should (equal (jit-lock-stealth-chunk-start-native around 10)
+                         (cl-letf (((symbol-function
+                                     'jit-lock-stealth-chunk-start-native)
+                                    nil))
+                           (jit-lock-stealth-chunk-start around))))))))
+  (with-temp-buffer
+    (should-not (jit-lock-stealth-chunk-start-native 1 500))))
+
+(ert-deftest jit-lock-defer-native ()
+  (jitlock-tests-with-buffer '((30 40 t))
+    (jit-lock-defer-native 10 50)
+    (should (equal (object-intervals (current-buffer))
+                   '((1 10 nil) (10 30 (fontified defer))
+                     (30 40 (fontified t)) (40 101 nil))))
+    (jit-lock-defer-native 50 5)
+    (should (eq (get-text-property 54 'fontified) 'defer))
+    (should-not (get-text-property 55 'fontified))))
+
+(ert-deftest jit-lock-undefer-native ()
+  (jitlock-tests-with-buffer '((1 10 defer) (10 20 t) (20 30 defer) (90 101 defer))
+    (narrow-to-region 1 95)
+    (jit-lock-undefer-native)
+    (widen)
+    (should (equal (object-intervals (current-buffer))
+                   '((1 10 (fontified nil)) (10 20 (fontified t))
+                     (20 30 (fontified nil)) (30 90 nil)
+                     (90 95 (fontified nil)) (95 101 (fontified defer)))))))
+
+(provide 'jitlock-tests)
+
+;;; jitlock-tests.el ends here
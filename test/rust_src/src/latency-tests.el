@@ -0,0 +1,25 @@
+;;; latency-tests.el --- Tests for latency.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest latency-report ()
+  (latency-report-reset)
+  (let ((report (latency-report)))
+    (dolist (phase '(:input :command :redisplay))
+      (should (equal (plist-get report phase) (make-list 12 0))))
+    (should-not (plist-get report :worst))))
+
+(ert-deftest latency-watchdog-redisplay ()
+  (let ((latency-watchdog t))
+    (latency-report-reset)
+    ;; Redisplay outside of the command loop is timed, but it is only
+    ;; counted when the next command starts.
+    (redisplay t)
+    (should (equal (plist-get (latency-report) :redisplay)
+                   (make-list 12 0)))))
+
+(provide 'latency-tests)
+
+;;; latency-tests.el ends here
@@ -0,0 +1,58 @@
+;;; linestats-tests.el --- Tests for linestats.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest linestats-tests--statistics ()
+  (with-temp-buffer
+    (should (equal (buffer-line-statistics) '(1 0 0.0)))
+    (insert "a\nbbb\ncc")
+    (should (equal (buffer-line-statistics) '(3 3 2.0)))
+    ;; Splitting the longest line.
+    (goto-char 5)
+    (insert "\n")
+    (should (equal (buffer-line-statistics) '(4 2 1.5)))
+    ;; Joining lines.
+    (delete-region 2 4)
+    (should (equal (buffer-line-statistics) '(3 2 1.6666666666666667)))
+    (should (equal (buffer-string) "ab\nb\ncc"))
+    ;; Narrowing is ignored.
+    (narrow-to-region 1 3)
+    (should (equal (buffer-line-statistics) '(3 2 1.6666666666666667)))
+    (widen)
+    (erase-buffer)
+    (should (equal (buffer-line-statistics) '(1 0 0.0)))))
+
+(ert-deftest linestats-tests--other-changes ()
+  ;; Changes that are not plain insertions and deletions are seen too.
+  (with-temp-buffer
+    (insert "abc\nde")
+    (buffer-line-statistics)
+    (subst-char-in-region (point-min) (point-max) ?\n ?x)
+    (should (equal (buffer-line-statistics) '(1 6 6.0)))
+    (let ((buffer (current-buffer)))
+      (with-temp-buffer
+        (should (equal (buffer-line-statistics buffer) '(1 6 6.0)))))))
+
+(ert-deftest linestats-tests--long-line-functions ()
+  (let* ((long-line-threshold 10)
+         (reported nil)
+         (long-line-functions (list (lambda (buffer) (push buffer reported)))))
+    (with-temp-buffer
+      (insert "short\n")
+      (accept-process-output nil 0.01)
+      (should-not reported)
+      (insert (make-string 20 ?x))
+      (insert "more")
+      (accept-process-output nil 0.01)
+      (should (equal reported (list (current-buffer))))
+      ;; Once the long line is gone, a new one is reported again.
+      (delete-region 7 (point-max))
+      (insert (make-string 20 ?y))
+      (accept-process-output nil 0.01)
+      (should (equal reported (list (current-buffer) (current-buffer)))))))
+
+(provide 'linestats-tests)
+
+;;; linestats-tests.el ends here
@@ -0,0 +1,61 @@
+;;; locations-tests.el --- Tests for locations.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest locations-tests--record ()
+  (with-temp-buffer
+    (insert "first line\nsecond line\n")
+    (should (equal (location-record 12)
+                   '(12 23 "second line\n" "first line\n")))
+    (goto-char (point-min))
+    (should (equal (location-record) '(1 23 "first line\nsecond line\n" "")))
+    ;; Positions outside the buffer are clipped.
+    (should (equal (car (location-record 100)) 24))))
+
+(ert-deftest locations-tests--reconcile-unchanged ()
+  (with-temp-buffer
+    (insert "first line\nsecond line\n")
+    (let ((record (location-record 12)))
+      (should (= (location-reconcile record) 12)))))
+
+(ert-deftest locations-tests--reconcile-edited ()
+  (with-temp-buffer
+    (insert "first line\nsecond line\nthird line\n")
+    (let ((record (location-record 12)))
+      ;; Insert text before the position.
+      (goto-char (point-min))
+      (insert "a new line\n")
+      (should (= (location-reconcile record) 23))
+      ;; Change the text before the position, too.
+      (delete-region (point-min) 17)
+      (should (equal (buffer-substring 1 7) " line\n"))
+      (should (= (location-reconcile record) 7)))))
+
+(ert-deftest locations-tests--reconcile-other-buffer ()
+  (let ((buffer (generate-new-buffer "locations-tests")))
+    (unwind-protect
+        (let (record)
+          (with-current-buffer buffer
+            (insert "αβγ\nδεζ\n")
+            (setq record (location-record 5)))
+          (with-current-buffer buffer
+            (goto-char (point-min))
+            (insert "ηθ\n"))
+          (should (= (location-reconcile record buffer) 8)))
+      (kill-buffer buffer))))
+
+(ert-deftest locations-tests--reconcile-without-context ()
+  (with-temp-buffer
+    (insert (make-string 200 ?x))
+    ;; The position is scaled with the size of the buffer.
+    (should (= (location-reconcile '(51 100 nil nil)) 101))
+    (should (= (location-reconcile '(51 nil nil nil)) 51))
+    (should (= (location-reconcile '(500)) 201))
+    (narrow-to-region 10 20)
+    (should (= (location-reconcile '(51 nil "gone" "gone")) 20))))
+
+(provide 'locations-tests)
+
+;;; locations-tests.el ends here
@@ -0,0 +1,54 @@
+;;; mail-tests.el --- Tests for mail.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest mail-scan-folder-mbox ()
+  (let ((file (make-temp-file "mail-tests")))
+    (unwind-protect
+        (progn
+          (with-temp-file file
+            (insert "From a@b Mon Jan  1 00:00:00 2018\n"
+                    "Subject: =?utf-8?q?caf=C3=A9?=\n"
+                    "  au lait\n"
+                    "From: A <a@b>\n\n"
+                    "body\n\n"
+                    "From c@d Tue Jan  2 00:00:00 2018\n"
+                    "subject: two\n\n"))
+          (let ((messages (mail-scan-folder file '("Subject" "To"))))
+            (should (= (length messages) 2))
+            (should (equal (car messages)
+                           '(:start 0 :body 90 :end 96
+                                    :headers (("subject" . "café  au lait")))))
+            (should (equal (plist-get (cadr messages) :start) 96))
+            (should (equal (plist-get (cadr messages) :headers)
+                           '(("subject" . "two")))))
+          (should (equal (cdr (assoc "from"
+                                     (plist-get (car (mail-scan-folder file))
+                                                :headers)))
+                         "A <a@b>")))
+      (delete-file file))))
+
+(ert-deftest mail-scan-folder-maildir ()
+  (let ((dir (make-temp-file "mail-tests" t)))
+    (unwind-protect
+        (progn
+          (make-directory (expand-file-name "cur" dir))
+          (make-directory (expand-file-name "new" dir))
+          (write-region "Subject: seen\n\nbody\n" nil
+                        (expand-file-name "cur/1.a.host:2,S" dir))
+          (write-region "Subject: new\n" nil
+                        (expand-file-name "new/2.b.host" dir))
+          (let ((messages (mail-scan-folder dir '("subject"))))
+            (should (equal (mapcar (lambda (m) (plist-get m :flags)) messages)
+                           '("S" "")))
+            (should (equal (plist-get (car messages) :file)
+                           (expand-file-name "cur/1.a.host:2,S" dir)))
+            (should (equal (plist-get (cadr messages) :headers)
+                           '(("subject" . "new"))))))
+      (delete-directory dir t))))
+
+(provide 'mail-tests)
+
+;;; mail-tests.el ends here
@@ -0,0 +1,70 @@
+;;; mime-tests.el --- Tests for mime.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+(require 'qp)
+(require 'rfc2047)
+
+(defvar mm-use-ultra-safe-encoding)
+
+(ert-deftest quoted-printable-decode-region-native ()
+  (with-temp-buffer
+    (set-buffer-multibyte nil)
+    (insert "caf=C3=a9 =3D=\nx=zz")
+    (should (quoted-printable-decode-region-native (point-min) (point-max)))
+    (should (equal (buffer-string) "caf\303\251 =x=zz")))
+  (with-temp-buffer
+    (insert "é=E9")
+    (should-not (quoted-printable-decode-region-native (point-min) (point-max)))
+    (should (equal (buffer-string) (string-to-multibyte "é\351"))))
+  (should (equal (quoted-printable-decode-string "a=3Db=\n") "a=b")))
+
+(ert-deftest quoted-printable-encode-region-native ()
+  (should (equal (quoted-printable-encode-string "a=b \n\tc\351\r\t")
+                 "a=3Db=20\n\tc=E9=0D=09"))
+  (with-temp-buffer
+    (insert (make-string 80 ?x))
+    (quoted-printable-encode-region (point-min) (point-max) t)
+    (should (equal (buffer-string)
+                   (concat (make-string 75 ?x) "=\n" (make-string 5 ?x)))))
+  (with-temp-buffer
+    (insert "From me\n-- \n")
+    (let ((mm-use-ultra-safe-encoding t))
+      (quoted-printable-encode-region (point-min) (point-max)))
+    (should (equal (buffer-string) "From=20me\n=2D-=20\n")))
+  (with-temp-buffer
+    (insert "é")
+    (should-error (quoted-printable-encode-region-native
+                   (point-min) (point-max)))))
+
+(ert-deftest rfc2047-decode-region-native ()
+  (with-temp-buffer
+    (insert "Re: =?utf-8?q?caf=C3?=\n =?UTF-8?B?qQ==?= x =?bogus?Q?a?= y")
+    (should (equal (rfc2047-decode-region-native
+                    (point-min) (point-max)
+                    (lambda (charset)
+                      (and (equal (downcase charset) "utf-8") 'utf-8)))
+                   '((5 . 9) (12 . 25))))
+    (should (equal (buffer-string) "Re: café x =?bogus?Q?a?= y")))
+  (with-temp-buffer
+    (insert "=?us-ascii?Q?a?b?= =?us-ascii?Q?c_d?=")
+    (rfc2047-decode-region-native (point-min) (point-max)
+                                  (lambda (_) 'us-ascii))
+    (should (equal (buffer-string) "=?us-ascii?Q?a?b?= c d"))
+    (erase-buffer)
+    (insert "=?us-ascii?Q?a?b?=")
+    (rfc2047-decode-region-native (point-min) (point-max)
+                                  (lambda (_) 'us-ascii) t)
+    (should (equal (buffer-string) "a?b"))))
+
+(ert-deftest rfc2047-decode-string-native ()
+  (should (equal (rfc2047-decode-string "=?iso-8859-1?q?caf=E9?= au lait")
+                 "café au lait"))
+  (should (equal (rfc2047-decode-string
+                  "=?utf-8?B?w6k=?= =?utf-8?B?w6k?=")
+                 "éé")))
+
+(provide 'mime-tests)
+
+;;; mime-tests.el ends here
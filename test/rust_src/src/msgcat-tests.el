@@ -0,0 +1,63 @@
+;;; msgcat-tests.el --- Tests for msgcat.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(defun msgcat-tests-make-catalog (entries)
+  "Return the contents of a little-endian `.mo' file with ENTRIES.
+ENTRIES is a list of (MESSAGE . TRANSLATION)."
+  (let* ((n (length entries))
+         (start (+ 28 (* 16 n)))
+         (words (list #x950412de 0 n 28 (+ 28 (* 8 n)) 0 0))
+         (strings ""))
+    (dolist (column (list (mapcar #'car entries) (mapcar #'cdr entries)))
+      (dolist (string column)
+        (let ((bytes (encode-coding-string string 'utf-8)))
+          (setq words (append words (list (length bytes)
+                                          (+ start (length strings)))))
+          (setq strings (concat strings bytes "\0")))))
+    (concat (mapconcat (lambda (word)
+                         (unibyte-string (logand word 255)
+                                         (logand (ash word -8) 255)
+                                         (logand (ash word -16) 255)
+                                         (logand (ash word -24) 255)))
+                       words "")
+            strings)))
+
+(defmacro msgcat-tests-with-catalog (contents &rest body)
+  "Run BODY with a file that has CONTENTS bound to `file'."
+  (declare (indent 1))
+  `(let ((file (make-temp-file "msgcat" nil ".mo")))
+     (unwind-protect
+         (progn
+           (let ((coding-system-for-write 'no-conversion))
+             (write-region ,contents nil file nil 'silent))
+           ,@body)
+       (clear-message-catalogs)
+       (delete-file file))))
+
+(ert-deftest msgcat-translate-message ()
+  (msgcat-tests-with-catalog
+      (msgcat-tests-make-catalog '(("" . "Content-Type: text/plain; charset=UTF-8\n")
+                                   ("Quit" . "Abbruch")
+                                   ("%d files" . "%d Dateien")
+                                   ("menu\4Open" . "Öffnen")))
+    (should (equal (translate-message "Quit") "Quit"))
+    (should (= (load-message-catalog file) 3))
+    (should (equal (translate-message "Quit") "Abbruch"))
+    (should (equal (translate-message "Open") "Open"))
+    (should (equal (translate-message "Open" "menu") "Öffnen"))
+    (should (equal (format-message "%d files" 3) "3 files"))
+    (let ((translate-messages t))
+      (should (equal (format-message "%d files" 3) "3 Dateien"))
+      (should (equal (format "%d files" 3) "3 files")))))
+
+(ert-deftest msgcat-load-invalid-catalog ()
+  (msgcat-tests-with-catalog "not a message catalog"
+    (should-error (load-message-catalog file))
+    (should (equal (translate-message "Quit") "Quit"))))
+
+(provide 'msgcat-tests)
+
+;;; msgcat-tests.el ends here
@@ -0,0 +1,63 @@
+;;; occur-tests.el --- Tests for occur.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest occur-tests--collect-matches ()
+  (with-temp-buffer
+    (insert "alpha\nbeta\n\ngamma alpha alpha\ndelta\n")
+    (goto-char 3)
+    (should (equal (collect-matches "alpha" (current-buffer))
+                   `((,(current-buffer)
+                      (1 1 1 6 ((1 . 6)) nil nil)
+                      (4 4 13 30 ((19 . 24) (25 . 30)) nil nil)))))
+    ;; Point is preserved.
+    (should (= (point) 3))
+    ;; Context lines.
+    (should (equal (cdar (collect-matches "gamma" (current-buffer) 1))
+                   '((4 4 13 30 ((13 . 18)) ((12 . 12)) ((31 . 36))))))
+    ;; Empty lines have one empty match.
+    (should (equal (cdar (collect-matches "^$" (current-buffer)))
+                   '((3 3 12 12 ((12 . 12)) nil nil)
+                     (6 6 37 37 ((37 . 37)) nil nil))))
+    ;; Matches spanning lines.
+    (should (equal (cdar (collect-matches "beta\n\ng" (current-buffer)))
+                   '((2 4 7 30 ((7 . 14)) nil nil))))
+    (should (equal (length (cdar (collect-matches "a" (current-buffer) 0 nil 2)))
+                   2))))
+
+(ert-deftest occur-tests--case-fold ()
+  (with-temp-buffer
+    (insert "Foo\nfoo\n")
+    (should (= (length (cdar (collect-matches "foo" (current-buffer) 0 t))) 2))
+    (should (= (length (cdar (collect-matches "foo" (current-buffer) 0 nil))) 1))))
+
+(ert-deftest occur-tests--several-buffers ()
+  (let ((a (generate-new-buffer "occur-tests-a"))
+        (b (generate-new-buffer "occur-tests-b"))
+        (dead (generate-new-buffer "occur-tests-dead")))
+    (kill-buffer dead)
+    (unwind-protect
+        (progn
+          (with-current-buffer a (insert "x\ny\n"))
+          (with-current-buffer b (insert "y\n"))
+          (should (equal (collect-matches "y" (list a dead b))
+                         `((,a (2 2 3 4 ((3 . 4)) nil nil))
+                           (,b (1 1 1 2 ((1 . 2)) nil nil))))))
+      (kill-buffer a)
+      (kill-buffer b))))
+
+(ert-deftest occur-tests--occur ()
+  (with-temp-buffer
+    (insert "one\ntwo\nthree two\n")
+    (occur "two")
+    (with-current-buffer "*Occur*"
+      (should (string-match-p "\\`2 matches for" (buffer-string)))
+      (should (string-match-p "      2:two" (buffer-string)))
+      (should (string-match-p "      3:three two" (buffer-string))))
+    (kill-buffer "*Occur*")))
+
+(provide 'occur-tests)
+
+;;; occur-tests.el ends here
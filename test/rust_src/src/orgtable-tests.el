@@ -0,0 +1,44 @@
+;;; orgtable-tests.el --- Tests for orgtable.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+(require 'org-table)
+
+(defconst orgtable-tests-table
+  "| a | b |\n|---+---|\n| 1 | 2.5 |\n| 3 |   |\n|---+---|\n| 4 | x |\n")
+
+(defun orgtable-tests-eval (formula line column &optional numbers keep-empty)
+  (with-temp-buffer
+    (insert orgtable-tests-table)
+    (org-table-eval-native formula (point-min) (point-max) line column
+                           numbers keep-empty)))
+
+(ert-deftest org-table-eval-native ()
+  (should (equal (orgtable-tests-eval "$1*10+1/3" 2 2) "10.333333"))
+  (should (equal (orgtable-tests-eval "vsum(@I$1..@II$1)" 5 2) "4"))
+  (should (equal (orgtable-tests-eval "vmean(@I..@II)" 5 2) "2.5"))
+  (should (equal (orgtable-tests-eval "@-1+@-2" 5 1) "4"))
+  (should (equal (orgtable-tests-eval "vcount($1..$2)" 3 1) "1"))
+  (should (equal (orgtable-tests-eval "$1+$2" 5 1 t) "4"))
+  ;; Left to Calc.
+  (should-not (orgtable-tests-eval "$1+$2" 5 1))
+  (should-not (orgtable-tests-eval "vcount($1..$2)" 3 1 nil t))
+  (should-not (orgtable-tests-eval "sin($1)" 2 2))
+  (should-not (orgtable-tests-eval "$3" 2 2)))
+
+(ert-deftest org-table-eval-native-recalculate ()
+  (with-temp-buffer
+    (org-mode)
+    (insert "| 1 | 2 |   |   |\n"
+            "| 3 | 4 |   |   |\n"
+            "#+TBLFM: $3=$1/$2::$4=vsum($1..$2);%.1f\n")
+    (goto-char (point-min))
+    (org-table-recalculate t)
+    (goto-char (point-min))
+    (should (equal (org-table-to-lisp)
+                   '(("1" "2" "0.5" "3.0") ("3" "4" "0.75" "7.0"))))))
+
+(provide 'orgtable-tests)
+
+;;; orgtable-tests.el ends here
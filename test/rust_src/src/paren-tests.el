@@ -0,0 +1,42 @@
+;;; paren-tests.el --- Tests for paren.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest find-matching-paren-native-match ()
+  (with-temp-buffer
+    (set-syntax-table emacs-lisp-mode-syntax-table)
+    (insert "(a [b] c)")
+    (should (equal (find-matching-paren-native 1 1) '(10)))
+    (should (equal (find-matching-paren-native 10 -1) '(1)))
+    (should (equal (find-matching-paren-native 7 -1) '(4)))
+    (erase-buffer)
+    (insert "(a]")
+    (should (equal (find-matching-paren-native 1 1) '(4 . t)))
+    (erase-buffer)
+    (insert "(a b")
+    (should (eq (find-matching-paren-native 1 1) t))))
+
+(ert-deftest find-matching-paren-native-distance-and-budget ()
+  (with-temp-buffer
+    (set-syntax-table emacs-lisp-mode-syntax-table)
+    (insert "(" (make-string 10000 ?a) ")")
+    (should (equal (find-matching-paren-native 1 1) '(10003)))
+    (should (eq (find-matching-paren-native 1 1 100) t))
+    ;; Too far away to be found without time to widen the search.
+    (should-not (find-matching-paren-native 1 1 nil 0))))
+
+(ert-deftest find-matching-paren-native-syntax-properties ()
+  (with-temp-buffer
+    (set-syntax-table emacs-lisp-mode-syntax-table)
+    (insert "((a)")
+    (should (eq (find-matching-paren-native 1 1) t))
+    (put-text-property 1 2 'syntax-table (string-to-syntax "."))
+    (let ((parse-sexp-lookup-properties nil))
+      (should-not (find-matching-paren-native 1 1))
+      (should (equal (find-matching-paren-native 5 -1) '(2))))))
+
+(provide 'paren-tests)
+
+;;; paren-tests.el ends here
@@ -0,0 +1,68 @@
+;;; persistent-tests.el --- Tests for persistent.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(defmacro persistent-tests--with-store (var &rest body)
+  "Bind VAR to the name of a fresh store file, and run BODY."
+  (declare (indent 1))
+  `(let ((,var (make-temp-name
+                (expand-file-name "persistent" temporary-file-directory))))
+     (unwind-protect
+         (progn ,@body)
+       (when (file-exists-p ,var)
+         (delete-file ,var)))))
+
+(ert-deftest persistent-tests--put-get ()
+  (persistent-tests--with-store store
+    (should (eq (persistent-store-get "missing" 'default store) 'default))
+    (should (equal (persistent-store-put "list" '(1 "two" (three . 4.0) [5])
+                                         store)
+                   '(1 "two" (three . 4.0) [5])))
+    (should (equal (persistent-store-get "list" nil store)
+                   '(1 "two" (three . 4.0) [5])))
+    ;; Symbols are the same keys as their names.
+    (persistent-store-put 'list "replaced" store)
+    (should (equal (persistent-store-get "list" nil store) "replaced"))))
+
+(ert-deftest persistent-tests--delete ()
+  (persistent-tests--with-store store
+    (persistent-store-put "key" 1 store)
+    (should (persistent-store-delete "key" store))
+    (should-not (persistent-store-delete "key" store))
+    (should (eq (persistent-store-get "key" 'gone store) 'gone))))
+
+(ert-deftest persistent-tests--reload ()
+  (persistent-tests--with-store store
+    (persistent-store-put "a" "first" store)
+    (persistent-store-put "b" '(x y) store)
+    (persistent-store-put "a" "second" store)
+    ;; Another session appending to the file is noticed.
+    (let ((copy (concat store "-copy")))
+      (unwind-protect
+          (progn
+            (copy-file store copy)
+            (persistent-store-put "c" 3 copy)
+            (copy-file copy store t))
+        (delete-file copy)))
+    (should (equal (persistent-store-get "a" nil store) "second"))
+    (should (equal (persistent-store-get "c" nil store) 3))))
+
+(ert-deftest persistent-tests--compact ()
+  (persistent-tests--with-store store
+    (dotimes (i 100)
+      (persistent-store-put "counter" (make-string 1000 ?x) store)
+      (persistent-store-put "counter" i store))
+    (should (< (file-attribute-size (file-attributes store)) 65536))
+    (persistent-store-compact store)
+    (should (equal (persistent-store-get "counter" nil store) 99))))
+
+(ert-deftest persistent-tests--not-a-store ()
+  (persistent-tests--with-store store
+    (write-region "(setq recentf-list nil)" nil store nil 'silent)
+    (should-error (persistent-store-get "key" nil store))))
+
+(provide 'persistent-tests)
+
+;;; persistent-tests.el ends here
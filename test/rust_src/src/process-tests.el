@@ -26,3 +26,40 @@
     (delete-process network-proc)
     (delete-process pipe-proc)
     (delete-process buffer-proc)))
+
+(ert-deftest process-tests--process-window-size ()
+  (unless (eq system-type 'windows-nt)
+    (let* ((process-connection-type t)
+           (proc (start-process "test-pty" nil "sleep" "5")))
+      (unwind-protect
+          (progn
+            (should (set-process-window-size proc 24 80))
+            (should (equal (process-window-size proc) '(24 . 80)))
+            (should (set-process-window-size proc 40 132))
+            (should (equal (process-window-size proc) '(40 . 132)))
+            (should-error (set-process-window-size proc -1 80)
+                          :type 'args-out-of-range)
+            (should-error (set-process-window-size proc 24 65536)
+                          :type 'args-out-of-range))
+        (delete-process proc)))))
+
+(ert-deftest process-tests--process-line-buffering ()
+  (unless (eq system-type 'windows-nt)
+    (let* ((chunks nil)
+           (proc (make-process :name "test-lines" :noquery t
+                               :command '("printf" "one\\ntwo\\nthree")
+                               :filter (lambda (_proc text)
+                                         (push text chunks)))))
+      (should (not (process-line-buffering proc)))
+      (should (equal (set-process-line-buffering proc t) t))
+      (should (equal (process-line-buffering proc) (* 64 1024)))
+      (set-process-line-buffering proc 1024)
+      (should (equal (process-line-buffering proc) 1024))
+      (should-error (set-process-line-buffering proc 0)
+                    :type 'args-out-of-range)
+      (while (accept-process-output proc 1))
+      (should (not (process-pending-output proc)))
+      (setq chunks (nreverse chunks))
+      (should (equal (apply #'concat chunks) "one\ntwo\nthree"))
+      (dolist (chunk (butlast chunks))
+        (should (string-suffix-p "\n" chunk))))))
@@ -0,0 +1,33 @@
+;;; quail-tests.el --- Tests for quail.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(defun quail-tests-make-map (key len)
+  (list (format "%s:%d" key len)))
+
+(defun quail-tests-make-alist (_key _len)
+  (list (cons ?y (list "XY"))))
+
+(ert-deftest quail-lookup-map-native ()
+  (let ((map (list nil
+                   (cons ?a (list "A" (cons ?b (list "AB"))))
+                   (cons ?c 'quail-tests-make-map)
+                   (cons ?x (cons "X" 'quail-tests-make-alist)))))
+    (should (equal (quail-lookup-map-native map "a" 1 nil)
+                   '("A" (?b "AB"))))
+    (should (equal (quail-lookup-map-native map "ab" 2 nil) '("AB")))
+    (should (equal (quail-lookup-map-native map "abz" 2 nil) '("AB")))
+    (should-not (quail-lookup-map-native map "abz" 3 nil))
+    (should-not (quail-lookup-map-native map "z" 1 nil))
+    (should (eq (quail-lookup-map-native map "" 0 nil) map))
+    ;; Parts of the map made on demand are stored into it.
+    (should (equal (quail-lookup-map-native map "c" 1 nil) '("c:1")))
+    (should (equal (cdr (assq ?c (cdr map))) '("c:1")))
+    (should (equal (quail-lookup-map-native map [?x ?y] 2 nil) '("XY")))
+    (should (equal (cdr (assq ?x (cdr map))) '("X" (?y "XY"))))))
+
+(provide 'quail-tests)
+
+;;; quail-tests.el ends here
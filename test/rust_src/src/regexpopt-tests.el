@@ -0,0 +1,48 @@
+;;; regexpopt-tests.el --- Tests for regexpopt.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+(require 'regexp-opt)
+
+(defconst regexpopt-tests-lists
+  '(()
+    ("abc")
+    ("" "a")
+    ("a" "b" "ab")
+    ("cat" "car" "cart" "dog")
+    ("ad" "d" "bd")
+    ("]" "^" "-" "a" "b" "c" "d" "z")
+    ("^")
+    ("-" "^")
+    ("foo" "foo" "foobar" "bar")
+    ("a.b" "a*b" "a+b" "a$")
+    ("été" "étui" "ete"))
+  "Lists of strings for which the native and Lisp regexps are compared.")
+
+(ert-deftest regexp-opt-native-matches-lisp ()
+  (dolist (strings regexpopt-tests-lists)
+    (dolist (paren '(nil t words symbols "\\(?2:"))
+      (should (equal (regexp-opt-native strings paren)
+                     (regexp-opt--lisp strings paren))))))
+
+(ert-deftest regexp-opt-native-keywords ()
+  (let ((keywords (let (words)
+                    (mapatoms (lambda (sym)
+                                (when (and (fboundp sym)
+                                           (< (length words) 500))
+                                  (push (symbol-name sym) words))))
+                    words)))
+    (should (equal (regexp-opt-native keywords 'symbols)
+                   (regexp-opt--lisp keywords 'symbols)))
+    (let ((re (regexp-opt keywords 'symbols)))
+      (dolist (word keywords)
+        (should (string-match-p (concat "\\`" re "\\'") word))))))
+
+(ert-deftest regexp-opt-native-errors ()
+  (should-error (regexp-opt-native '("a" 1)) :type 'wrong-type-argument)
+  (should-error (regexp-opt-native '("a" . "b")) :type 'wrong-type-argument))
+
+(provide 'regexpopt-tests)
+
+;;; regexpopt-tests.el ends here
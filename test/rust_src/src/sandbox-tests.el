@@ -0,0 +1,40 @@
+;;; sandbox-tests.el --- Tests for sandbox.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(defun sandbox-tests--run (sandbox command)
+  "Run the shell COMMAND with SANDBOX and return its exit status."
+  (let ((process (make-process :name "sandbox-tests"
+                               :command (list "/bin/sh" "-c" command)
+                               :sandbox sandbox
+                               :noquery t)))
+    (while (process-live-p process)
+      (accept-process-output process 0.1))
+    (process-exit-status process)))
+
+(ert-deftest sandbox-unknown-keyword ()
+  (should-error (make-process :name "sandbox-tests"
+                              :command '("/bin/true")
+                              :sandbox '(:bogus t))))
+
+(ert-deftest sandbox-write ()
+  (skip-unless (process-sandbox-supported-p))
+  (let* ((dir (make-temp-file "sandbox-tests" t))
+         (default-directory (file-name-as-directory dir)))
+    (unwind-protect
+        (progn
+          (should-not (zerop (sandbox-tests--run
+                              '(:network t) "echo x > file")))
+          (should-not (file-exists-p "file"))
+          (should (zerop (sandbox-tests--run
+                          `(:write (,dir) :network t) "echo x > file")))
+          (should (file-exists-p "file"))
+          (should (zerop (sandbox-tests--run
+                          '(:network t) "echo x > /dev/null"))))
+      (delete-directory dir t))))
+
+(provide 'sandbox-tests)
+
+;;; sandbox-tests.el ends here
@@ -0,0 +1,129 @@
+;;; search-tests.el --- Tests for search.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest search-tests--match-data-buffer ()
+  (with-temp-buffer
+    (insert "foo bar baz")
+    (goto-char (point-min))
+    (should (re-search-forward "\\(bar\\) \\(x\\)?\\(baz\\)" nil t))
+    (let ((data (match-data)))
+      (should (= (length data) 8))
+      (should (markerp (car data)))
+      (should (eq (marker-buffer (car data)) (current-buffer)))
+      (should (equal (mapcar (lambda (m) (and m (marker-position m))) data)
+                     '(5 12 5 8 nil nil 9 12))))
+    (should (equal (match-data t) (list 5 12 5 8 nil nil 9 12 (current-buffer))))
+    (should (= (match-beginning 3) 9))
+    (should (= (match-end 1) 8))
+    (should-not (match-beginning 2))
+    (should-not (match-end 10))
+    (should-error (match-beginning -1) :type 'args-out-of-range)))
+
+(ert-deftest search-tests--match-data-reuse ()
+  (should (string-match "b\\(c\\)" "abcd"))
+  (let* ((reuse (list 'x 'x 'x 'x 'x 'x))
+         (data (match-data nil reuse)))
+    (should (eq data reuse))
+    (should (equal data '(1 3 2 3 nil nil))))
+  (let* ((reuse (list 'x))
+         (data (match-data nil reuse)))
+    (should (eq data reuse))
+    (should (equal data '(1 3 2 3)))))
+
+(ert-deftest search-tests--set-match-data ()
+  (set-match-data '(2 5 nil nil 3 4))
+  (should (equal (match-data) '(2 5 nil nil 3 4)))
+  (should-not (match-beginning 1))
+  (should (= (match-end 2) 4))
+  (with-temp-buffer
+    (insert "hello world")
+    (set-match-data (list 1 6 (current-buffer)))
+    (should (markerp (car (match-data))))
+    (let ((markers (list (copy-marker 7) (copy-marker 12))))
+      (set-match-data markers t)
+      (should (equal (match-data t) (list 7 12 (current-buffer))))
+      ;; RESEAT made the markers point nowhere.
+      (should (equal markers '(nil nil)))))
+  (should-error (set-match-data '(1 . 2)) :type 'wrong-type-argument))
+
+(ert-deftest search-tests--save-match-data ()
+  (string-match "b" "abc")
+  (save-match-data
+    (string-match "c" "abc")
+    (should (= (match-beginning 0) 2)))
+  (should (= (match-beginning 0) 1))
+  (ignore-errors
+    (save-match-data
+      (string-match "c" "abc")
+      (error "Exit")))
+  (should (= (match-beginning 0) 1)))
+
+(ert-deftest search-tests--replace-match-source ()
+  (with-temp-buffer
+    (insert "one two")
+    (goto-char (point-min))
+    (should (re-search-forward "two" nil t))
+    ;; The match was in a buffer, so it cannot be used on a string.
+    (should-error (replace-match "2" t t "one two"))
+    (let ((buffer (current-buffer)))
+      (with-temp-buffer
+        (insert "one two")
+        ;; Nor on another buffer.
+        (should-error (replace-match "2"))
+        (should (equal (buffer-string) "one two")))
+      (should (eq (current-buffer) buffer)))
+    (replace-match "2")
+    (should (equal (buffer-string) "one 2")))
+  ;; Match data set from integers can be used anywhere.
+  (with-temp-buffer
+    (insert "abc")
+    (set-match-data '(2 3))
+    (replace-match "x")
+    (should (equal (buffer-string) "axc")))
+  (should (string-match "b" "abc"))
+  (should (equal (replace-match "x" t t "abc") "axc")))
+
+(ert-deftest search-tests--match-substitute-replacement ()
+  ;; `match-substitute-replacement' reinstates the match data of a
+  ;; buffer search, shifted, and applies it to the matched string.
+  (with-temp-buffer
+    (insert "foo bar-baz")
+    (goto-char (point-min))
+    (should (re-search-forward "\\(bar\\)-\\(baz\\)" nil t))
+    (should (equal (match-substitute-replacement "\\2-\\1") "baz-bar"))
+    ;; The match data is the buffer's again afterwards.
+    (should (= (match-beginning 0) 5))
+    (should (markerp (car (match-data))))
+    (replace-match "x")
+    (should (equal (buffer-string) "foo x"))))
+
+(ert-deftest search-tests--regexp-match-ranges ()
+  (with-temp-buffer
+    (insert "foo bar foo baz foo")
+    (goto-char 5)
+    (string-match "x" "x")
+    (should (equal (regexp-match-ranges "fo+" (point-min) (point-max))
+                   '((1 . 4) (9 . 12) (17 . 20))))
+    ;; Point and the match data are preserved.
+    (should (= (point) 5))
+    (should (equal (match-data) '(0 1)))
+    ;; Matches must lie within the region.
+    (should (equal (regexp-match-ranges "fo+" 18 2) '((9 . 12))))
+    (should (equal (regexp-match-ranges "fo+" 1 20 2) '((1 . 4) (9 . 12))))
+    ;; Empty matches are skipped.
+    (should (equal (regexp-match-ranges "a*" 1 20) '((6 . 7) (14 . 15))))
+    (should (equal (regexp-match-ranges "ba." 1 20 nil
+                                        (lambda (beg _end) (> beg 10)))
+                   '((13 . 16))))
+    (let ((case-fold-search t))
+      (should (equal (regexp-match-ranges "BAR" 1 20) '((5 . 8)))))
+    (let ((case-fold-search nil))
+      (should-not (regexp-match-ranges "BAR" 1 20)))
+    (should-error (regexp-match-ranges "foo" 1 100) :type 'args-out-of-range)))
+
+(provide 'search-tests)
+
+;;; search-tests.el ends here
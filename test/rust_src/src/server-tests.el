@@ -0,0 +1,40 @@
+;;; server-tests.el --- Tests for server.rs
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest server-tests--quote-arg ()
+  (should (equal (server-quote-arg "-a b&c\n") "&-a&_b&&c&n"))
+  (should (equal (server-unquote-arg "&-a&_b&&c&n") "-a b&c\n"))
+  (should (equal (server-unquote-arg (server-quote-arg "-é ü&\n"))
+                 "-é ü&\n")))
+
+(ert-deftest server-tests--authenticate ()
+  (let ((request "-auth s3cr3t\n-nowait \n"))
+    (should (equal (server-authenticate request "s3cr3t") 13))
+    (should-not (server-authenticate request "s3cr3"))
+    (should-not (server-authenticate request nil))
+    (should-not (server-authenticate "-nowait \n" "s3cr3t"))))
+
+(ert-deftest server-tests--parse-request ()
+  (should-not (server-parse-request "-dir /tmp/ -file fo"))
+  (should (equal (server-parse-request
+                  "-dir /tmp/ -position +3:4 -file a&_b -nowait -tty /dev/pts/1 xterm \n")
+                 (cons '((dir "/tmp/")
+                         (position 3 4)
+                         (file "a b")
+                         (nowait)
+                         (tty "/dev/pts/1" "xterm"))
+                       "")))
+  (should (equal (server-parse-request "-position +7 -eval (+&_1&_2)\nrest")
+                 (cons '((position 7 0) (eval "(+ 1 2)")) "rest")))
+  (should-error (server-parse-request "-frobnicate\n"))
+  (should-error (server-parse-request "-file\n"))
+  ;; A malformed position is ignored.
+  (should (equal (server-parse-request "-position 3 -file a\n")
+                 (cons '((file "a")) ""))))
+
+(provide 'server-tests)
+
+;;; server-tests.el ends here
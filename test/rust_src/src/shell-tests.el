@@ -0,0 +1,37 @@
+;;; shell-tests.el --- Tests for shell.rs
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest shell-tests--shell-quote-argument ()
+  (should (equal (shell-quote-argument "" t) "''"))
+  (should (equal (shell-quote-argument "foo/bar.c" t) "foo/bar.c"))
+  (should (equal (shell-quote-argument "a b;c" t) "a\\ b\\;c"))
+  (should (equal (shell-quote-argument "a\nb" t) "a'\n'b"))
+  (should (equal (shell-quote-argument "café" t) "caf\\é"))
+  (should-error (shell-quote-argument 'foo) :type 'wrong-type-argument))
+
+(ert-deftest shell-tests--split-string-shell-command ()
+  (unless (eq system-type 'windows-nt)
+    (should (equal (split-string-shell-command "  grep -n 'a b' \"c\\\"d\" e\\ f ")
+                   '("grep" "-n" "a b" "c\"d" "e f")))
+    (should (equal (split-string-shell-command "'' \"\"") '("" "")))
+    (should-not (split-string-shell-command "   "))
+    (let ((args '("" "it's" "$HOME" "a\nb" "\\\"")))
+      (should (equal (split-string-shell-command
+                      (mapconcat #'shell-quote-argument args " "))
+                     args)))))
+
+(ert-deftest shell-tests--combine-and-quote-strings ()
+  (should (equal (combine-and-quote-strings '("a" "b c" "d\"e")) "a \"b c\" \"d\\\"e\""))
+  (should (equal (combine-and-quote-strings '("a" "b c") ",") "a,b c"))
+  (should (equal (combine-and-quote-strings '("a,b" "c") ",") "\"a,b\",c"))
+  (should (equal (combine-and-quote-strings nil) ""))
+  (let ((strs '("x" "y z" "\\back" "q\"uote" "café")))
+    (should (equal (split-string-and-unquote (combine-and-quote-strings strs))
+                   strs))))
+
+(provide 'shell-tests)
+
+;;; shell-tests.el ends here
@@ -0,0 +1,68 @@
+;;; shr-tests.el --- Tests for shr.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+(require 'cl-lib)
+(require 'shr)
+
+(ert-deftest html-decode-entities ()
+  (should (equal (html-decode-entities "a &amp; b &lt;c&gt;") "a & b <c>"))
+  (should (equal (html-decode-entities "caf&eacute; &#233; &#xE9;")
+                 "café é é"))
+  ;; Windows-1252 references, as in HTML5, and invalid ones.
+  (should (equal (html-decode-entities "&#150;&#0;") "–\uFFFD"))
+  (should (equal (html-decode-entities "AT&T &amp &nosuchentity;")
+                 "AT&T &amp &nosuchentity;"))
+  (let ((string "no references"))
+    (should (eq (html-decode-entities string) string))))
+
+(ert-deftest shr-fill-text ()
+  (should (equal (shr-fill-text '("one two  three\nfour") 9)
+                 '("one two" "three" "four")))
+  (should (equal (shr-fill-text '("one two three") 10 3)
+                 '("one" "two three")))
+  (should (equal (shr-fill-text '("unbreakable words") 5)
+                 '("unbreakable" "words")))
+  (should (equal (shr-fill-text '("日本語") 4) '("日本" "語")))
+  (should-not (shr-fill-text '("  \n") 10)))
+
+(ert-deftest shr-fill-text-properties ()
+  (let ((lines (shr-fill-text
+                (list "a " (propertize "link text" 'face 'bold) " b") 6)))
+    (should (equal lines '("a link" "text b")))
+    (should (eq (get-text-property 2 'face (car lines)) 'bold))
+    (should (eq (get-text-property 0 'face (cadr lines)) 'bold))
+    (should-not (get-text-property 5 'face (cadr lines)))))
+
+(defun shr-tests--render (html natively)
+  "Render HTML with fixed-width characters and return the text.
+If NATIVELY is nil, fold lines without `shr-fill-text'."
+  (with-temp-buffer
+    (insert html)
+    (let ((dom (libxml-parse-html-region (point-min) (point-max)))
+          (shr-use-fonts nil)
+          (shr-width 30))
+      (erase-buffer)
+      (cl-letf (((symbol-function 'shr-fill-line-natively)
+                 (if natively
+                     (symbol-function 'shr-fill-line-natively)
+                   #'shr-fill-line-by-motion)))
+        (shr-insert-document dom))
+      (buffer-substring-no-properties (point-min) (point-max)))))
+
+(ert-deftest shr-fill-line-natively ()
+  (skip-unless (fboundp 'libxml-parse-html-region))
+  (let* ((html (concat "<p>Lorem ipsum dolor sit amet, consectetur "
+                       "adipiscing elit, sed do eiusmod tempor.</p>"
+                       "<ul><li>one two three four five six seven eight "
+                       "nine ten eleven twelve</li></ul>"))
+         (native (shr-tests--render html t)))
+    (dolist (line (split-string native "\n"))
+      (should (<= (string-width line) 30)))
+    (should (equal (split-string native)
+                   (split-string (shr-tests--render html nil))))))
+
+(provide 'shr-tests)
+
+;;; shr-tests.el ends here
@@ -0,0 +1,64 @@
+;;; sort-tests.el --- Tests for sort.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(defun sort-tests--sorted (text &rest args)
+  "Return TEXT after sorting its lines by `sort-lines-native' with ARGS."
+  (with-temp-buffer
+    (insert text)
+    (apply #'sort-lines-native (point-min) (point-max) args)
+    (buffer-string)))
+
+(ert-deftest sort-tests--lines ()
+  (should (equal (sort-tests--sorted "b\nc\na\n") "a\nb\nc\n"))
+  (should (equal (sort-tests--sorted "b\nc\na\n" t) "c\nb\na\n"))
+  ;; The last line need not end in a newline.
+  (should (equal (sort-tests--sorted "b\na") "a\nb"))
+  ;; Equal lines keep their order.
+  (should (equal (sort-tests--sorted "B\na\nb\n" nil nil nil t) "a\nB\nb\n"))
+  (should (equal (sort-tests--sorted "B\na\nb\n") "B\na\nb\n")))
+
+(ert-deftest sort-tests--fields ()
+  (should (equal (sort-tests--sorted "x 2 b\ny 1 c\nz 3 a\n" nil 2)
+                 "y 1 c\nx 2 b\nz 3 a\n"))
+  (should (equal (sort-tests--sorted "x 2 b\ny 1 c\nz 3 a\n" nil -1)
+                 "z 3 a\nx 2 b\ny 1 c\n"))
+  (should (equal (sort-tests--sorted "b x\na y\n" nil 0) "a y\nb x\n"))
+  (should-error (sort-tests--sorted "a b\nc\n" nil 2)))
+
+(ert-deftest sort-tests--numeric ()
+  (should (equal (sort-tests--sorted "10 a\n9 b\n-1 c\n" nil 1 10)
+                 "-1 c\n9 b\n10 a\n"))
+  ;; "0x" starts a hexadecimal number and "0" an octal one.
+  (should (equal (sort-tests--sorted "0x10\n017\n14\n" nil 1 10)
+                 "14\n017\n0x10\n"))
+  (should (equal (sort-tests--sorted "a\nf\n10\n" nil 1 16) "a\nf\n10\n"))
+  (should-error (sort-tests--sorted "1\n" nil 1 1) :type 'args-out-of-range))
+
+(ert-deftest sort-tests--regexp ()
+  (should (equal (sort-tests--sorted "x=3\ny=1\nz=2\n" nil "[0-9]")
+                 "y=1\nz=2\nx=3\n"))
+  ;; Searching for the keys leaves the match data alone.
+  (string-match "foo" "foo")
+  (sort-tests--sorted "b\na\n" nil "[a-z]")
+  (should (equal (match-data) '(0 3))))
+
+(ert-deftest sort-tests--buffer ()
+  (with-temp-buffer
+    (insert "head\n" (propertize "b" 'face 'bold) "\na\ntail\n")
+    (set-buffer-modified-p nil)
+    (goto-char 3)
+    ;; Lines already in order leave the buffer unmodified.
+    (sort-lines-native 6 10 t)
+    (should (equal (buffer-string) "head\nb\na\ntail\n"))
+    (should-not (buffer-modified-p))
+    (sort-lines-native 6 10)
+    (should (equal (buffer-string) "head\na\nb\ntail\n"))
+    (should (eq (get-text-property 8 'face) 'bold))
+    (should (= (point) 3))))
+
+(provide 'sort-tests)
+
+;;; sort-tests.el ends here
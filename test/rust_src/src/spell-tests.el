@@ -0,0 +1,61 @@
+;;; spell-tests.el --- Tests for spell/mod.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(defmacro spell-tests--with-dictionary (var &rest body)
+  "Bind VAR to the name of a small Hunspell dictionary, and run BODY."
+  (declare (indent 1))
+  `(let* ((dir (make-temp-file "spell" t))
+          (,var (expand-file-name "test.dic" dir)))
+     (unwind-protect
+         (progn
+           (with-temp-file (expand-file-name "test.aff" dir)
+             (insert "SET UTF-8\n"
+                     "TRY esianrtolcdugmphbyfvkwzxjq\n"
+                     "WORDCHARS -\n"
+                     "PFX U Y 1\n"
+                     "PFX U 0 un .\n"
+                     "SFX S Y 2\n"
+                     "SFX S y ies [^aeiou]y\n"
+                     "SFX S 0 s [^y]\n"))
+           (with-temp-file ,var
+             (insert "5\nhappy/U\ncity/S\nlock/US\nParis\nnaïve\n"))
+           ,@body)
+       (delete-directory dir t))))
+
+(ert-deftest spell-tests--check-word ()
+  (spell-tests--with-dictionary dict
+    (should (eq (spell-check-word "unhappy" dict) t))
+    (should (eq (spell-check-word "cities" dict) t))
+    (should (eq (spell-check-word "Unlocks" dict) t))
+    (should (eq (spell-check-word "PARIS" dict) t))
+    (should (eq (spell-check-word "naïve" dict) t))
+    ;; The extension of the dictionary can be omitted.
+    (should (eq (spell-check-word "locks" (file-name-sans-extension dict)) t))
+    (should (equal (spell-check-word "hapy" dict) '("happy")))
+    (should (equal (spell-check-word "paris" dict) '("Paris")))
+    (should (equal (spell-check-word "xyzzy" dict) nil))))
+
+(ert-deftest spell-tests--check-region ()
+  (spell-tests--with-dictionary dict
+    (with-temp-buffer
+      (insert "Happy citys, unlocked locks unhappy Pariss 2nd naïve")
+      (should (equal (spell-check-region (point-min) (point-max) dict)
+                     '((7 . 12) (14 . 22) (37 . 43))))
+      (should (equal (spell-check-region 1 13 dict) '((7 . 12)))))))
+
+(ert-deftest spell-tests--add-word ()
+  (spell-tests--with-dictionary dict
+    (should-not (eq (spell-check-word "flyspell" dict) t))
+    (spell-add-word "flyspell" dict)
+    (should (eq (spell-check-word "flyspell" dict) t))
+    (should (eq (spell-check-word "Flyspell" dict) t))))
+
+(ert-deftest spell-tests--missing-dictionary ()
+  (should-error (spell-check-word "word" "/nonexistent/dictionary.dic")))
+
+(provide 'spell-tests)
+
+;;; spell-tests.el ends here
@@ -0,0 +1,55 @@
+;;; supersession-tests.el --- Tests for supersession.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(defmacro supersession-tests-with-file (&rest body)
+  "Run BODY in a buffer visiting a new file FILE."
+  (declare (indent 0))
+  `(let ((file (make-temp-file "supersession-tests" nil nil "abc\n")))
+     (unwind-protect
+         (with-current-buffer (find-file-noselect file)
+           (unwind-protect
+               (progn ,@body)
+             (set-buffer-modified-p nil)
+             (kill-buffer)))
+       (delete-file file))))
+
+(defun supersession-tests-touch (file &optional contents)
+  "Move the modification time of FILE, replacing its CONTENTS if non-nil."
+  (when contents
+    (let ((coding-system-for-write 'no-conversion))
+      (write-region contents nil file nil 'silent)))
+  (set-file-times file (time-add (current-time) 100)))
+
+(ert-deftest file-superseded-p-same-contents ()
+  (supersession-tests-with-file
+    (should-not (file-superseded-p))
+    (supersession-tests-touch file)
+    (let ((supersession-content-hash-limit nil))
+      (should (file-superseded-p)))
+    (should-not (file-superseded-p))
+    ;; The new time stamp was recorded.
+    (should (verify-visited-file-modtime))))
+
+(ert-deftest file-superseded-p-changed-contents ()
+  (supersession-tests-with-file
+    (should-not (file-superseded-p))
+    (supersession-tests-touch file "xyz\n")
+    (should (file-superseded-p))
+    (should-not (verify-visited-file-modtime))))
+
+(ert-deftest file-superseded-p-deleted ()
+  (supersession-tests-with-file
+    (delete-file file)
+    (should-not (file-superseded-p))
+    (write-region "abc\n" nil file nil 'silent)))
+
+(ert-deftest file-superseded-p-non-file-buffer ()
+  (with-temp-buffer
+    (should-not (file-superseded-p))))
+
+(provide 'supersession-tests)
+
+;;; supersession-tests.el ends here
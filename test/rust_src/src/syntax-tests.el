@@ -24,3 +24,21 @@
       (forward-word 2)
       (should (looking-back "forward-word"))
       (should (eq (forward-word 1) nil)))))
+
+(ert-deftest test-scan-enclosing-lists ()
+  (with-temp-buffer
+    (set-syntax-table emacs-lisp-mode-syntax-table)
+    (insert "(a [b c) d]")
+    (should (equal (scan-enclosing-lists 6) '((4 . 8) (1 . 11))))
+    (should (equal (scan-enclosing-lists 6 nil (syntax-ppss 6))
+                   '((4 . 8) (1 . 11))))
+    (should (equal (scan-enclosing-lists 6 9) '((4 . 8) (1))))
+    (erase-buffer)
+    ;; Strings and comments are skipped.
+    (insert "(a \"(\" ; )\n b)")
+    (should (equal (scan-enclosing-lists 3) '((1 . 14))))
+    (should (equal (scan-enclosing-lists 3 10) '((1))))
+    (erase-buffer)
+    (insert "a) b")
+    (should (equal (scan-enclosing-lists 1) '((nil . 2))))
+    (should-not (scan-enclosing-lists 3))))
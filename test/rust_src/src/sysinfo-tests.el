@@ -0,0 +1,45 @@
+;;; sysinfo-tests.el --- Tests for sysinfo.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+(require 'cl-lib)
+(require 'battery)
+
+(ert-deftest sysinfo-load-average ()
+  (let ((loads (condition-case nil (load-average) (error 'unsupported))))
+    (unless (eq loads 'unsupported)
+      (should (<= 1 (length loads) 3))
+      (should (cl-every #'integerp loads))
+      (should (cl-every #'floatp (load-average t))))))
+
+(ert-deftest sysinfo-battery-status-native ()
+  (let ((status (battery-status-native)))
+    (when status
+      (should (memq (plist-get status :status)
+                    '(charging discharging full not-charging unknown)))
+      (should (memq (plist-get status :ac-line) '(nil ac battery)))
+      (let ((percentage (plist-get status :percentage)))
+        (should (or (null percentage) (<= 0 percentage 100))))
+      (let ((remaining (plist-get status :remaining)))
+        (should (or (null remaining) (natnump remaining)))))))
+
+(ert-deftest sysinfo-battery-native-format ()
+  (cl-letf (((symbol-function 'battery-status-native)
+             (lambda ()
+               '(:status discharging :ac-line battery :percentage 42.0
+                 :remaining 5430 :rate 8.5))))
+    (let ((data (battery-native)))
+      (should (equal (battery-format "%L %B %b%p%% %t %r %d" data)
+                     "BAT discharging 42% 1:30 8.5 N/A")))))
+
+(ert-deftest sysinfo-battery-native-critical ()
+  (cl-letf (((symbol-function 'battery-status-native)
+             (lambda () '(:status not-charging :percentage 3.0))))
+    (let ((data (battery-native)))
+      (should (equal (battery-format "%L %B %b%p" data)
+                     "N/A not charging !3")))))
+
+(provide 'sysinfo-tests)
+
+;;; sysinfo-tests.el ends here
@@ -0,0 +1,57 @@
+;;; termemu-tests.el --- Tests for termemu.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(defmacro termemu-tests-with-terminal (spec &rest body)
+  "Run BODY in a temporary buffer with the terminal in (VAR WIDTH HEIGHT)."
+  (declare (indent 1))
+  (let ((var (car spec)))
+    `(let ((,var (term-native-new ,@(cdr spec))))
+       (unwind-protect
+           (with-temp-buffer ,@body)
+         (term-native-free ,var)))))
+
+(ert-deftest term-native-redraw-screen ()
+  (termemu-tests-with-terminal (term 10 3)
+    (should-not (term-native-feed term "hello\r\n\e[1;31mworld\e[m"))
+    (let ((cursor (term-native-redraw term)))
+      (should (equal (buffer-string) "hello\nworld\n"))
+      (should (= cursor 12))
+      (should (= (point) (point-max)))
+      (should (get-text-property 7 'face))
+      (should-not (get-text-property 1 'face)))
+    ;; Only the screen is replaced.
+    (term-native-feed term "\e[2J\e[Hnew")
+    (should (= (term-native-redraw term) 4))
+    (should (equal (buffer-string) "new\n\n"))))
+
+(ert-deftest term-native-redraw-scrollback ()
+  (termemu-tests-with-terminal (term 10 2 2)
+    (insert "prompt> ")
+    (term-native-feed term "1\r\n2\r\n3\r\n4")
+    (term-native-redraw term)
+    (should (equal (buffer-string) "prompt> 1\n2\n3\n4"))
+    ;; Text that was there before the terminal is left alone.
+    (term-native-feed term "\r\n5")
+    (should (= (term-native-redraw term) 16))
+    (should (equal (buffer-string) "prompt> 2\n3\n4\n5"))))
+
+(ert-deftest term-native-feed-events ()
+  (termemu-tests-with-terminal (term 80 24)
+    (should (equal (term-native-feed term "\e[2;3H\e[6n\a\e]2;top\a")
+                   '((reply . "\e[2;3R") (bell) (title . "top"))))
+    ;; Sequences may be split between chunks of output.
+    (term-native-feed term "\e[")
+    (should (equal (term-native-feed term "5n") '((reply . "\e[0n"))))))
+
+(ert-deftest term-native-errors ()
+  (should-error (term-native-new 0 10))
+  (let ((term (term-native-new 10 10)))
+    (term-native-free term)
+    (should-error (term-native-feed term "x"))))
+
+(provide 'termemu-tests)
+
+;;; termemu-tests.el ends here
@@ -0,0 +1,573 @@
+;;; textprop-tests.el --- Tests for textprop.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+(require 'benchmark)
+
+(ert-deftest text-properties-at ()
+  (let ((string (concat "ab" (propertize "cd" 'face 'bold))))
+    (should-not (text-properties-at 0 string))
+    (should (equal (text-properties-at 2 string) '(face bold)))
+    ;; No character follows the end.
+    (should-not (text-properties-at 4 string))
+    (should-error (text-properties-at 5 string) :type 'args-out-of-range)
+    (should-error (text-properties-at 0 'string) :type 'wrong-type-argument))
+  (with-temp-buffer
+    (should-not (text-properties-at 1))
+    (insert "x" (propertize "y" 'invisible t))
+    (should (equal (text-properties-at 2) '(invisible t)))
+    (should (equal (text-properties-at (copy-marker 2)) '(invisible t)))
+    (narrow-to-region 1 2)
+    (should-error (text-properties-at 3) :type 'args-out-of-range)))
+
+(ert-deftest text-property-any ()
+  (let ((string (concat "ab" (propertize "cd" 'face 'bold) "e")))
+    (should (= (text-property-any 0 5 'face 'bold string) 2))
+    (should (= (text-property-any 3 5 'face 'bold string) 3))
+    (should (= (text-property-any 5 0 'face 'bold string) 2))
+    (should-not (text-property-any 0 2 'face 'bold string))
+    (should (= (text-property-any 1 5 'face nil string) 1))
+    (should-not (text-property-any 1 1 'face nil string)))
+  (let ((plain "abc"))
+    (should (= (text-property-any 1 3 'face nil plain) 1))
+    (should-not (text-property-any 1 3 'face 'bold plain)))
+  (with-temp-buffer
+    (insert "ab" (propertize "cd" 'face 'bold))
+    (should (= (text-property-any (point-min) (point-max) 'face 'bold) 3))))
+
+;; `get-text-property' looks properties up with `textget'.
+(ert-deftest textprop-textget-fallbacks ()
+  (let ((string (propertize "ab" 'face 'bold 'category 'textprop-tests-cat)))
+    (put 'textprop-tests-cat 'mouse-face 'highlight)
+    (unwind-protect
+        (progn
+          (should (eq (get-text-property 0 'face string) 'bold))
+          (should (eq (get-text-property 0 'mouse-face string) 'highlight))
+          (let ((char-property-alias-alist '((font-lock-face face))))
+            (should (eq (get-text-property 0 'font-lock-face string) 'bold)))
+          (let ((default-text-properties '(line-spacing 2)))
+            (should (eql (get-text-property 0 'line-spacing string) 2))
+            (should (eql (get-text-property 0 'line-spacing "ab") 2))))
+      (put 'textprop-tests-cat 'mouse-face nil))))
+
+(ert-deftest textprop-overlay-ignores-default-text-properties ()
+  (with-temp-buffer
+    (insert "abc")
+    (let ((overlay (make-overlay 1 3))
+          (default-text-properties '(line-spacing 2)))
+      (overlay-put overlay 'face 'bold)
+      (should (eq (overlay-get overlay 'face) 'bold))
+      (should-not (overlay-get overlay 'line-spacing)))))
+
+(ert-deftest textprop-object-intervals ()
+  (should-not (object-intervals "abc"))
+  (let ((string (concat "a" (propertize "bc" 'face 'bold) "d")))
+    (should (equal (object-intervals string)
+                   '((0 1 nil) (1 3 (face bold)) (3 4 nil))))
+    ;; The value is a copy.
+    (setcar (nth 2 (nth 1 (object-intervals string))) 'mouse-face)
+    (should (eq (get-text-property 1 'face string) 'bold)))
+  (with-temp-buffer
+    (insert "ab" (propertize "cd" 'face 'italic))
+    (should (equal (object-intervals (current-buffer))
+                   '((1 3 nil) (3 5 (face italic)))))))
+
+(ert-deftest textprop-intervals-equal ()
+  (let ((string (copy-sequence "abcde"))
+        (echo "x"))
+    ;; The order of the properties doesn't matter.
+    (set-text-properties 0 2 (list 'face 'bold 'help-echo echo) string)
+    (set-text-properties 2 4 (list 'help-echo echo 'face 'bold) string)
+    (should (eq (next-property-change 0 string) 4))
+    ;; Values are compared with `eq'.
+    (put-text-property 2 4 'help-echo (copy-sequence echo) string)
+    (should (eq (next-property-change 0 string) 2))
+    ;; An interval with more properties is different.
+    (put-text-property 2 4 'help-echo echo string)
+    (put-text-property 3 4 'mouse-face 'highlight string)
+    (should (eq (next-property-change 0 string) 3))))
+
+(ert-deftest textprop-coalesce-intervals ()
+  (let ((string (copy-sequence "abcdef")))
+    (put-text-property 0 2 'face 'bold string)
+    (put-text-property 4 6 'face 'bold string)
+    (put-text-property 2 4 'face 'bold string)
+    (should (equal (object-intervals string) '((0 6 (face bold)))))
+    (put-text-property 2 3 'mouse-face 'highlight string)
+    (remove-text-properties 2 3 '(mouse-face nil) string)
+    (should (equal (object-intervals string) '((0 6 (face bold))))))
+  (with-temp-buffer
+    (insert "abcdef")
+    (add-text-properties 1 3 '(face italic))
+    (set-text-properties 3 5 '(face italic))
+    (should (equal (object-intervals (current-buffer))
+                   '((1 5 (face italic)) (5 7 nil))))
+    (remove-list-of-text-properties 1 3 '(face))
+    (should (equal (object-intervals (current-buffer))
+                   '((1 3 nil) (3 5 (face italic)) (5 7 nil)))))
+  ;; The buffer that was changed is coalesced, even if a change hook
+  ;; switches to another one.
+  (let ((other (generate-new-buffer " *other*")))
+    (unwind-protect
+        (with-temp-buffer
+          (let ((buffer (current-buffer)))
+            (insert "abcdef")
+            (put-text-property 1 3 'face 'bold)
+            (let ((after-change-functions
+                   (list (lambda (&rest _) (set-buffer other)))))
+              (put-text-property 3 5 'face 'bold))
+            (should (equal (object-intervals buffer)
+                           '((1 5 (face bold)) (5 7 nil))))))
+      (kill-buffer other))))
+
+(ert-deftest textprop-interval-tree-statistics ()
+  (should-not (interval-tree-statistics "abc"))
+  (with-temp-buffer
+    (insert (make-string 100 ?x))
+    (should-not (interval-tree-statistics))
+    (dotimes (i 50)
+      (put-text-property (+ 1 (* 2 i)) (+ 2 (* 2 i)) 'face 'bold))
+    (let ((stats (interval-tree-statistics (current-buffer))))
+      (should (= (plist-get stats :intervals) 100))
+      (should (= (plist-get stats :average-length) 1.0))
+      ;; No tree of 100 intervals is less than 7 levels deep.
+      (should (>= (plist-get stats :max-depth) 7))
+      (should (>= (plist-get stats :imbalance) 1.0)))))
+
+;; Random edits of the text properties of buffers and strings, checked
+;; against a model that has the value of the property `p' of each
+;; character.  They exercise the splitting, merging, deletion and
+;; balancing of interval trees.
+
+(defun textprop-tests-check (object values origin)
+  "Check that the intervals of OBJECT match the model VALUES, a vector.
+ORIGIN is the position of the start of OBJECT."
+  (let ((intervals (object-intervals object))
+        (pos origin)
+        runs model-runs)
+    ;; The intervals cover the text in order, and none is empty.
+    (when intervals
+      (dolist (interval intervals)
+        (should (= (nth 0 interval) pos))
+        (should (< (nth 0 interval) (nth 1 interval)))
+        (setq pos (nth 1 interval)))
+      (should (= pos (+ origin (length values)))))
+    (dolist (interval intervals)
+      (push (list (nth 0 interval) (nth 1 interval)
+                  (plist-get (nth 2 interval) 'p))
+            runs))
+    (dotimes (i (length values))
+      (push (list (+ origin i) (+ origin i 1) (aref values i)) model-runs))
+    (should (equal (textprop-tests-merge-runs (nreverse runs))
+                   (textprop-tests-merge-runs (nreverse model-runs))))))
+
+(defun textprop-tests-merge-runs (runs)
+  "Merge the adjacent RUNS (START END VALUE) with the same value.
+Drop the runs whose value is nil."
+  (let (merged)
+    (dolist (run runs)
+      (let ((last (car merged)))
+        (cond ((null (nth 2 run)))
+              ((and last (= (nth 1 last) (nth 0 run))
+                    (eq (nth 2 last) (nth 2 run)))
+               (setcar (cdr last) (nth 1 run)))
+              (t (push (copy-sequence run) merged)))))
+    (nreverse merged)))
+
+(defun textprop-tests-fill (values start end value)
+  "Set the elements of VALUES from START to END to VALUE."
+  (while (< start end)
+    (aset values start value)
+    (setq start (1+ start))))
+
+(defun textprop-tests-random-value ()
+  (nth (random 3) '(a b c)))
+
+(ert-deftest textprop-random-buffer-edits ()
+  (dolist (seed '("one" "two" "three" "four"))
+    (random seed)
+    (with-temp-buffer
+      (let ((values (make-vector 20 nil)))
+        (insert (make-string 20 ?x))
+        (dotimes (_ 300)
+          (let* ((length (length values))
+                 (a (random (1+ length)))
+                 (b (random (1+ length)))
+                 (start (min a b))
+                 (end (max a b))
+                 (value (textprop-tests-random-value)))
+            ;; Delete more often when the text grows long.
+            (pcase (random (if (> length 60) 8 6))
+              ((or 0 1)
+               (put-text-property (1+ start) (1+ end) 'p value)
+               (textprop-tests-fill values start end value))
+              (2
+               (remove-text-properties (1+ start) (1+ end) '(p nil))
+               (textprop-tests-fill values start end nil))
+              (3
+               (let ((n (random 5))
+                     (props (and (zerop (random 2)) (list 'p value))))
+                 (goto-char (1+ start))
+                 (insert (apply #'propertize (make-string n ?y) props))
+                 (setq values (vconcat (substring values 0 start)
+                                       (make-vector n (cadr props))
+                                       (substring values start)))))
+              (4
+               (let ((text (buffer-substring (1+ start) (1+ end))))
+                 (goto-char (1+ b))
+                 (insert text)
+                 (setq values (vconcat (substring values 0 b)
+                                       (substring values start end)
+                                       (substring values b)))))
+              (_
+               (delete-region (1+ start) (1+ end))
+               (setq values (vconcat (substring values 0 start)
+                                     (substring values end)))))
+            (textprop-tests-check (current-buffer) values 1)))))))
+
+(ert-deftest textprop-random-string-edits ()
+  (dolist (seed '("one" "two" "three"))
+    (random seed)
+    (let ((string (make-string 20 ?x))
+          (values (make-vector 20 nil)))
+      (dotimes (_ 300)
+        (let* ((length (length values))
+               (a (random (1+ length)))
+               (b (random (1+ length)))
+               (start (min a b))
+               (end (max a b))
+               (value (textprop-tests-random-value)))
+          (pcase (random (if (> length 60) 5 4))
+            ((or 0 1)
+             (put-text-property start end 'p value string)
+             (textprop-tests-fill values start end value))
+            (2
+             (remove-text-properties start end '(p nil) string)
+             (textprop-tests-fill values start end nil))
+            (3
+             ;; Append a copy of part of the string.
+             (setq string (concat string (substring string start end)))
+             (setq values (vconcat values (substring values start end))))
+            (_
+             (setq string (concat (substring string 0 start)
+                                  (substring string end)))
+             (setq values (vconcat (substring values 0 start)
+                                   (substring values end)))))
+          (textprop-tests-check string values 0))))))
+
+(ert-deftest textprop-undo-entries ()
+  (with-temp-buffer
+    (insert "abcdefghij")
+    (put-text-property 1 5 'face 'bold)
+    (let ((snapshot (text-properties-snapshot 1 11)))
+      (should-not (text-properties-undo-entries snapshot))
+      (put-text-property 3 8 'face 'italic)
+      (put-text-property 6 8 'mouse-face 'highlight)
+      (let ((entries (text-properties-undo-entries snapshot)))
+        (should (member '(nil face bold 3 . 5) entries))
+        (should (member '(nil face nil 5 . 8) entries))
+        (should (member '(nil mouse-face nil 5 . 8) entries))
+        (should (= (length entries) 3))
+        ;; Undoing the entries restores the snapshot.
+        (dolist (entry entries)
+          (pcase-let ((`(nil ,prop ,value ,beg . ,end) entry))
+            (put-text-property beg end prop value)))
+        (should (eq (get-text-property 4 'face) 'bold))
+        (should-not (get-text-property 6 'face))))))
+
+(ert-deftest textprop-snapshot-string ()
+  (let* ((string (propertize "abc" 'face 'bold))
+         (snapshot (text-properties-snapshot 1 3 string)))
+    (should (equal snapshot '(1 3 (1 3 (face bold)))))
+    (remove-text-properties 0 2 '(face nil) string)
+    (should (equal (text-properties-undo-entries snapshot string)
+                   '((nil face bold 1 . 2))))))
+
+(ert-deftest textprop-copy-many-runs ()
+  (let* ((string (apply #'concat
+                        (mapcar (lambda (i) (propertize "x" 'n i))
+                                (number-sequence 0 99999))))
+         (copy (copy-sequence string))
+         (part (substring string 1 -1)))
+    (should (= (get-text-property 500 'n copy) 500))
+    (should (= (get-text-property 500 'n part) 501))
+    (should (= (plist-get (interval-tree-statistics part) :intervals)
+               99998))
+    (put-text-property 0 1 'n 'changed copy)
+    (should (= (get-text-property 0 'n string) 0))
+    (garbage-collect)
+    (should (= (get-text-property 99999 'n copy) 99999))))
+
+(ert-deftest textprop-insertions-into-interval ()
+  (with-temp-buffer
+    (insert (propertize "aaaa" 'face 'bold) "bbbb" (propertize "cc" 'p 1))
+    (goto-char 3)
+    (dotimes (_ 100)
+      (insert "x"))
+    ;; The insertions are counted before the tree is looked at.
+    (should (equal (next-single-property-change 1 'face) 105))
+    (should (eq (get-text-property 104 'face) 'bold))
+    (should (equal (text-property-any 1 (point-max) 'p 1) 109))
+    (goto-char 107)
+    (insert "yy")
+    (should (equal (text-properties-at 111) '(p 1)))
+    (should (= (plist-get (interval-tree-statistics) :intervals) 3))
+    (delete-region 3 103)
+    (should (equal (next-single-property-change 1 'face) 5))
+    (should (eq (get-text-property 4 'face) 'bold))))
+
+(ert-deftest textprop-insertion-stickiness ()
+  (with-temp-buffer
+    (insert (propertize "aa" 'p 1 'rear-nonsticky t)
+            (propertize "bb" 'q 2 'front-sticky '(q)))
+    (goto-char 3)
+    (insert "x")
+    (should (equal (get-text-property 3 'q) 2))
+    (should-not (get-text-property 3 'p))
+    (goto-char 2)
+    (insert "y")
+    (should-not (text-properties-at 2))
+    (should (equal (get-text-property 1 'p) 1))
+    (goto-char (point-max))
+    (insert "z")
+    (should (equal (get-text-property 7 'q) 2))
+    (goto-char (point-min))
+    (insert "w")
+    (should-not (text-properties-at 1)))
+  (with-temp-buffer
+    (let ((text-property-default-nonsticky '((p . t))))
+      (insert (propertize "aaaa" 'p 1))
+      (goto-char 3)
+      (insert "x")
+      (should-not (get-text-property 3 'p))
+      (should (equal (get-text-property 4 'p) 1)))))
+
+(ert-deftest textprop-deletion-across-intervals ()
+  (with-temp-buffer
+    (dotimes (i 10)
+      (insert (propertize "abcd" 'n i)))
+    (delete-region 3 15)
+    (should (= (buffer-size) 28))
+    (should (equal (get-text-property 2 'n) 0))
+    (should (equal (get-text-property 3 'n) 3))
+    (should (= (next-single-property-change 1 'n) 3))
+    (should (= (next-single-property-change 3 'n) 5))
+    (should (= (plist-get (interval-tree-statistics) :intervals) 8))
+    (delete-region 1 (point-max))
+    (should-not (object-intervals (current-buffer)))))
+
+(ert-deftest textprop-swap-text ()
+  (let ((a (generate-new-buffer "a"))
+        (b (generate-new-buffer "b")))
+    (unwind-protect
+        (progn
+          (with-current-buffer a
+            (insert "plain " (propertize "bold" 'face 'bold)))
+          (with-current-buffer b
+            (insert (propertize "italic" 'face 'italic) " text")
+            (buffer-swap-text a)
+            (should (eq (get-text-property 7 'face) 'bold))
+            (put-text-property 1 3 'p 1)
+            (should (equal (get-text-property 1 'p) 1)))
+          (with-current-buffer a
+            (should (eq (get-text-property 1 'face) 'italic))
+            (should-not (get-text-property 1 'p))
+            (should-not (get-text-property 8 'face))))
+      (kill-buffer a)
+      (kill-buffer b))))
+
+(ert-deftest textprop-swap-text-pending-insertions ()
+  (let ((a (generate-new-buffer "a"))
+        (b (generate-new-buffer "b")))
+    (unwind-protect
+        (progn
+          (dolist (buffer (list a b))
+            (with-current-buffer buffer
+              (dotimes (i 20)
+                (insert (propertize "abcd" 'n i)))
+              ;; Insertions into the middle of an interval, whose
+              ;; lengths are not added up the tree yet.
+              (goto-char 3)
+              (insert (if (eq buffer a) "xx" "yyyy"))))
+          (with-current-buffer a
+            (buffer-swap-text b)
+            (should (= (buffer-size) 84))
+            (should (equal (get-text-property 3 'n) 0))
+            (should (equal (get-text-property 84 'n) 19))
+            (should (= (next-single-property-change 1 'n) 9)))
+          (with-current-buffer b
+            (should (= (buffer-size) 82))
+            (should (equal (get-text-property 82 'n) 19))
+            (should (= (next-single-property-change 1 'n) 7))))
+      (kill-buffer a)
+      (kill-buffer b))))
+
+(ert-deftest textprop-whole-buffer-insertion ()
+  (let ((string (concat (propertize "ab" 'face 'bold) "cd"
+                        (propertize "ef" 'face 'italic))))
+    (with-temp-buffer
+      (insert string)
+      (should (equal-including-properties (buffer-string) string))
+      (should (eq (get-text-property 6 'face) 'italic))
+      (should (equal (next-single-property-change 1 'face) 3)))))
+
+(ert-deftest textprop-graft-into-interval ()
+  (let ((string (concat (propertize "ab" 'p 1) "c" (propertize "d" 'p 2))))
+    (with-temp-buffer
+      (insert (propertize "xxxx" 'face 'bold))
+      (goto-char 3)
+      (insert string)
+      (should (equal-including-properties
+               (buffer-string)
+               (concat (propertize "xx" 'face 'bold) string
+                       (propertize "xx" 'face 'bold)))))
+    (with-temp-buffer
+      (insert (propertize "xxxx" 'face 'bold))
+      (goto-char 3)
+      (insert-and-inherit string)
+      (should (equal (text-properties-at 3) '(p 1 face bold)))
+      (should (equal (text-properties-at 5) '(face bold)))
+      (should (equal (text-properties-at 6) '(p 2 face bold)))
+      (should (equal (next-single-property-change 1 'face) nil))
+      (should (eq (get-text-property 8 'face) 'bold)))
+    (with-temp-buffer
+      (insert (propertize "xxxx" 'face 'bold))
+      (goto-char 3)
+      (insert "yy")
+      (should-not (text-properties-at 3))
+      (insert-and-inherit "zz")
+      (should-not (text-properties-at 5)))))
+
+(ert-deftest textprop-walk-intervals ()
+  (with-temp-buffer
+    (dotimes (i 50)
+      (insert (propertize "ab" 'n i)))
+    (let ((pos 1) (forward nil) (backward nil))
+      (while pos
+        (push pos forward)
+        (setq pos (next-property-change pos)))
+      (setq pos (point-max))
+      (while (and pos (> pos 1))
+        (setq pos (previous-property-change pos))
+        (push (or pos 1) backward))
+      (should (equal (nreverse forward) (number-sequence 1 99 2)))
+      (should (equal backward (number-sequence 1 99 2))))))
+
+(ert-deftest textprop-point-intangible ()
+  (with-temp-buffer
+    (insert "abc" (propertize "def" 'intangible t) "ghi")
+    (let ((inhibit-point-motion-hooks nil))
+      (goto-char 5)
+      (should (= (point) 4))
+      (goto-char 5)
+      (should (= (point) 7)))
+    (let ((inhibit-point-motion-hooks t))
+      (goto-char 5)
+      (should (= (point) 5)))))
+
+(ert-deftest textprop-point-entered-left ()
+  (with-temp-buffer
+    (let ((log nil)
+          (inhibit-point-motion-hooks nil))
+      (insert "ab"
+              (propertize "cd"
+                          'point-entered (lambda (old new)
+                                           (push (list 'entered old new) log))
+                          'point-left (lambda (old new)
+                                        (push (list 'left old new) log)))
+              "ef")
+      (goto-char 1)
+      (setq log nil)
+      (goto-char 4)
+      (goto-char 6)
+      ;; The characters before and after point are both in the
+      ;; interval, so each function runs twice.
+      (should (equal (nreverse log)
+                     '((entered 1 4) (entered 1 4) (left 4 6) (left 4 6)))))))
+
+;; Intervals found by property lookups are remembered until the buffer
+;; changes, so scan the text, change it and scan it again.
+(ert-deftest textprop-interval-cache ()
+  (with-temp-buffer
+    (insert "aaabbbccc")
+    (put-text-property 1 4 'p 'a)
+    (put-text-property 4 7 'p 'b)
+    (let ((scan (lambda ()
+                  (mapcar (lambda (pos) (get-char-property pos 'p))
+                          (number-sequence 1 9)))))
+      (should (equal (funcall scan) '(a a a b b b nil nil nil)))
+      (put-text-property 5 8 'p 'c)
+      (should (equal (funcall scan) '(a a a b c c c nil nil)))
+      (with-silent-modifications
+        (remove-text-properties 1 3 '(p nil)))
+      (should (equal (funcall scan) '(nil nil a b c c c nil nil)))
+      (goto-char 1)
+      (insert "x")
+      (should (equal (get-char-property 4 'p) 'a))
+      (should (equal (get-char-property 5 'p) 'b)))))
+
+;; Intervals merged after the change hooks have run do not change the
+;; modification count, so looking at them from a hook must not leave
+;; them remembered.
+(ert-deftest textprop-interval-cache-coalesce ()
+  (with-temp-buffer
+    (insert "aaabbbccc")
+    (put-text-property 1 4 'p 'a)
+    (put-text-property 7 10 'p 'a)
+    (let ((after-change-functions
+           (list (lambda (_beg _end _len)
+                   (dotimes (i 9)
+                     (get-text-property (1+ i) 'p))))))
+      (put-text-property 4 7 'p 'a))
+    (should (eq (get-text-property 5 'p) 'a))
+    (should (eq (get-text-property 8 'p) 'a))
+    (should-not (next-single-property-change 1 'p))))
+
+(ert-deftest textprop-interval-cache-swap-text ()
+  (let ((other (generate-new-buffer "other")))
+    (unwind-protect
+        (with-temp-buffer
+          (insert (propertize "abc" 'p 1))
+          (with-current-buffer other
+            (insert (propertize "abc" 'p 2)))
+          (should (equal (get-char-property 2 'p) 1))
+          (buffer-swap-text other)
+          (should (equal (get-char-property 2 'p) 2))
+          (should (equal (get-char-property 2 'p other) 1)))
+      (kill-buffer other))))
+
+(ert-deftest textprop-interval-cache-multibyte ()
+  (with-temp-buffer
+    (insert "\u00e9\u00e9" (propertize "ab" 'p 1))
+    (should (equal (get-char-property 3 'p) 1))
+    (should-not (get-char-property 2 'p))
+    (set-buffer-multibyte nil)
+    ;; Each character became two bytes.
+    (should-not (get-char-property 3 'p))
+    (should (equal (get-char-property 5 'p) 1))))
+
+(defun textprop-tests-benchmark-lookups (&optional runs lookups)
+  "Time text property lookups in a buffer with many intervals.
+The buffer has RUNS stretches of text with different properties, 10000
+by default, and LOOKUPS lookups at random positions are made, 100000 by
+default.  This is not a test; run it in different builds to compare
+the cost of finding intervals."
+  (interactive)
+  (let ((runs (or runs 10000))
+        (lookups (or lookups 100000)))
+    (with-temp-buffer
+      (insert (make-string (* 2 runs) ?x))
+      (dotimes (i runs)
+        (put-text-property (1+ (* 2 i)) (+ 2 (* 2 i)) 'p i))
+      (random "textprop")
+      (let ((result (benchmark-run 1
+                      (dotimes (_ lookups)
+                        (get-text-property (1+ (random (* 2 runs))) 'p)))))
+        (message "%d lookups in %.3fs, %d GCs"
+                 lookups (car result) (cadr result))
+        result))))
+
+(provide 'textprop-tests)
+
+;;; textprop-tests.el ends here
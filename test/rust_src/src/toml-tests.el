@@ -0,0 +1,62 @@
+;;; toml-tests.el --- Tests for toml.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(defconst toml-tests-manifest
+  "[package]
+name = \"remacs\"
+version = \"0.1.0\"
+authors = [\"a\", \"b\"]
+publish = false
+
+[dependencies]
+libc = \"0.2\"
+")
+
+(ert-deftest toml-parse-string-hash-table ()
+  (let* ((data (toml-parse-string toml-tests-manifest))
+         (package (gethash "package" data)))
+    (should (hash-table-p package))
+    (should (equal (gethash "name" package) "remacs"))
+    (should (equal (gethash "authors" package) ["a" "b"]))
+    (should (eq (gethash "publish" package) :false))
+    (should (equal (gethash "libc" (gethash "dependencies" data)) "0.2"))))
+
+(ert-deftest toml-parse-string-alist ()
+  (should (equal (toml-parse-string toml-tests-manifest :object-type 'alist)
+                 '((dependencies (libc . "0.2"))
+                   (package (authors . ["a" "b"]) (name . "remacs")
+                            (publish . :false) (version . "0.1.0")))))
+  (should (equal (toml-parse-string "a = 1\nb = 2.5\nc = true\nd = 1979-05-27"
+                                    :object-type 'alist)
+                 '((a . 1) (b . 2.5) (c . t) (d . "1979-05-27"))))
+  (should (equal (toml-parse-string "s = \"\\u00e9t\\u00e9\"" :object-type 'alist)
+                 '((s . "été")))))
+
+(ert-deftest toml-parse-string-errors ()
+  (should-error (toml-parse-string "a = "))
+  (should-error (toml-parse-string "a = 1\na = 2"))
+  (should-error (toml-parse-string "a = 1" :object-type 'plist))
+  (should-error (toml-parse-string 'a)))
+
+(ert-deftest toml-serialize ()
+  (should (equal (toml-serialize '((b . 1) (a . "x")))
+                 "a = \"x\"\nb = 1\n"))
+  (should (equal (toml-serialize '((t1 (k . t)) (a . [1 2]) (a . 3)))
+                 "a = [1, 2]\n\n[t1]\nk = true\n"))
+  (let ((table (make-hash-table :test 'equal)))
+    (puthash "f" :false table)
+    (should (equal (toml-serialize table) "f = false\n")))
+  (should-error (toml-serialize [1 2]))
+  (should-error (toml-serialize '((a . :null)))))
+
+(ert-deftest toml-round-trip ()
+  (let ((data (toml-parse-string toml-tests-manifest :object-type 'alist)))
+    (should (equal (toml-parse-string (toml-serialize data) :object-type 'alist)
+                   data))))
+
+(provide 'toml-tests)
+
+;;; toml-tests.el ends here
@@ -0,0 +1,43 @@
+;;; tramp-tests.el --- tests for tramp.rs functions
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest tramp-tramp-file-p-native ()
+  (should (tramp-tramp-file-p-native "/ssh:host:/tmp"))
+  (should (tramp-tramp-file-p-native "/ssh:gw|sudo:root@host:"))
+  (should-not (tramp-tramp-file-p-native "/tmp/foo"))
+  (should-not (tramp-tramp-file-p-native "/:/ssh:host:/tmp"))
+  (should-not (tramp-tramp-file-p-native nil)))
+
+(ert-deftest tramp-dissect-file-name-native ()
+  (should (equal (tramp-dissect-file-name-native "/ssh:user%dom@host#22:/tmp/x")
+                 '("ssh" "user" "dom" "host" "22" "/tmp/x" nil)))
+  (should (equal (tramp-dissect-file-name-native "/ssh:gw|sudo:root@box:/etc")
+                 '("sudo" "root" nil "box" nil "/etc" "ssh:gw|")))
+  (should (equal (tramp-dissect-file-name-native "/ssh:[::1]:")
+                 '("ssh" nil nil "::1" nil "" nil)))
+  (should (equal (tramp-dissect-file-name-native "/sudo::/etc")
+                 '("sudo" nil nil nil nil "/etc" nil)))
+  (should-not (tramp-dissect-file-name-native "/ssh:host#:/tmp"))
+  (should-not (tramp-dissect-file-name-native "/home/user"))
+  (should-error (tramp-dissect-file-name-native 'foo) :type 'wrong-type-argument))
+
+(ert-deftest tramp-dissect-file-name-uses-native ()
+  (require 'tramp)
+  (should (tramp-native-syntax-p))
+  (let* ((name "/ssh:user%dom@host#22:/tmp/x")
+         (vec (tramp-dissect-file-name name t)))
+    (should (equal (tramp-file-name-user vec) "user"))
+    (should (equal (tramp-file-name-domain vec) "dom"))
+    (should (equal (tramp-file-name-port vec) "22"))
+    (should (equal (tramp-file-name-localname vec) "/tmp/x"))
+    ;; The native parser agrees with the regexps of the syntax.
+    (should (equal (tramp-dissect-file-name-native name)
+                   (tramp-dissect-file-name-regexp name)))))
+
+(ert-deftest tramp-clear-file-name-cache-native ()
+  (tramp-dissect-file-name-native "/ssh:host:/tmp")
+  (should (> (tramp-clear-file-name-cache-native) 0))
+  (should (= (tramp-clear-file-name-cache-native) 0)))
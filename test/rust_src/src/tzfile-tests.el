@@ -0,0 +1,48 @@
+;;; tzfile-tests.el --- Tests for tzfile.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(defconst tzfile-tests-paris "CET-1CEST,M3.5.0,M10.5.0/3")
+
+(ert-deftest timezone-offsets-native-rule ()
+  ;; 2018-03-25 01:00 UTC.
+  (should (equal (timezone-offsets-native tzfile-tests-paris 1521939599)
+                 '((3600 nil "CET"))))
+  (should (equal (timezone-offsets-native tzfile-tests-paris 1521939600)
+                 '((7200 t "CEST"))))
+  (should (equal (timezone-offsets-native t 0) '((0 nil "UTC"))))
+  (should (equal (timezone-offsets-native "<+0530>-5:30" '(0 0))
+                 '((19800 nil "+0530"))))
+  (should-error (timezone-offsets-native "No/Such_Zone" 0)))
+
+(ert-deftest timezone-offsets-native-local ()
+  ;; 2018-03-25 02:30 is skipped, 2018-10-28 02:30 happens twice.
+  (let ((skipped (encode-time 0 30 2 25 3 2018 t))
+        (twice (encode-time 0 30 2 28 10 2018 t)))
+    (should-not (timezone-offsets-native tzfile-tests-paris skipped t))
+    (should (equal (timezone-offsets-native tzfile-tests-paris twice t)
+                   '((7200 t "CEST") (3600 nil "CET"))))))
+
+(ert-deftest timezone-transitions-native ()
+  (should (equal (timezone-transitions-native
+                  tzfile-tests-paris
+                  (encode-time 0 0 0 1 1 2018 t)
+                  (encode-time 0 0 0 1 1 2019 t))
+                 '((1521939600 7200 t "CEST")
+                   (1540688400 3600 nil "CET"))))
+  (should-not (timezone-transitions-native "UTC0" 0 1e9)))
+
+(ert-deftest timezone-offsets-native-database ()
+  (skip-unless (file-exists-p "/usr/share/zoneinfo/America/New_York"))
+  (should (equal (timezone-offsets-native "America/New_York" 1531000000)
+                 '((-14400 t "EDT"))))
+  ;; Far in the future, from the rule at the end of the file.
+  (should (equal (timezone-offsets-native "America/New_York" 4102444800)
+                 '((-18000 nil "EST"))))
+  (should-error (timezone-offsets-native "../New_York" 0)))
+
+(provide 'tzfile-tests)
+
+;;; tzfile-tests.el ends here
@@ -0,0 +1,47 @@
+;;; vmotion-tests.el --- Tests for vmotion.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(defun vmotion-tests--motion (from lines &optional slow)
+  "Return point and the value of `vertical-motion' LINES from FROM.
+If SLOW is non-nil, use a display table to make `vertical-motion'
+lay out the text the slow way."
+  (let ((buffer-display-table (and slow (make-display-table))))
+    (goto-char from)
+    (let ((moved (vertical-motion lines)))
+      (list (point) moved))))
+
+(ert-deftest vmotion-tests--continuation ()
+  (with-temp-buffer
+    (let ((width (1- (window-body-width))))
+      (insert (make-string (* 3 width) ?x) "\nabc\n\nd")
+      (should (equal (vmotion-tests--motion 1 1) (list (+ 1 width) 1)))
+      (should (equal (vmotion-tests--motion 1 3) (list (+ 2 (* 3 width)) 3)))
+      (should (equal (vmotion-tests--motion (point-max) -4)
+                     (list (+ 1 width) -4)))
+      ;; Moving past the end of the buffer.
+      (should (equal (vmotion-tests--motion 1 10) (list (point-max) 5)))
+      ;; Moving to a column.
+      (goto-char 1)
+      (should (= (vertical-motion '(2 . 1)) 1))
+      (should (= (point) (+ 3 width)))
+      ;; The same as the display engine does.
+      (dolist (from (list 1 (+ 2 width) (* 3 width) (- (point-max) 2)))
+        (dolist (lines '(-3 -1 0 1 2 4))
+          (should (equal (vmotion-tests--motion from lines)
+                         (vmotion-tests--motion from lines t))))))))
+
+(ert-deftest vmotion-tests--truncated ()
+  (with-temp-buffer
+    (setq truncate-lines t)
+    (insert (make-string (* 3 (window-body-width)) ?x) "\nabc\n")
+    (should (equal (vmotion-tests--motion 5 1) (list (- (point-max) 4) 1)))
+    (goto-char 5)
+    (should (= (vertical-motion '(2 . 1)) 1))
+    (should (= (point) (- (point-max) 2)))))
+
+(provide 'vmotion-tests)
+
+;;; vmotion-tests.el ends here
@@ -0,0 +1,109 @@
+;;; websocket-tests.el --- Tests for websocket.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+(require 'websocket-process)
+
+(defconst websocket-tests-key "dGhlIHNhbXBsZSBub25jZQ==")
+
+(defun websocket-tests-accept (key)
+  (base64-encode-string
+   (secure-hash 'sha1 (concat key "258EAFA5-E914-47DA-95CA-C5AB0DC85B11")
+                nil nil t)))
+
+(defun websocket-tests-handshake (key)
+  (concat "HTTP/1.1 101 Switching Protocols\r\n"
+          "Upgrade: websocket\r\nConnection: Upgrade\r\n"
+          "Sec-WebSocket-Accept: " (websocket-tests-accept key) "\r\n\r\n"))
+
+(defun websocket-tests-unmask (frame)
+  "Return the payload of FRAME, a short masked frame."
+  (let ((mask (substring frame 2 6)))
+    (apply #'unibyte-string
+           (cl-loop for byte across (substring frame 6)
+                    for i from 0
+                    collect (logxor byte (aref mask (% i 4)))))))
+
+(ert-deftest websocket-native-parser-events ()
+  (should (equal (websocket-tests-accept websocket-tests-key)
+                 "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="))
+  (let ((parser (websocket-native-parser websocket-tests-key)))
+    (unwind-protect
+        (progn
+          (should-not (websocket-native-parser-feed
+                       parser "HTTP/1.1 101 Switching Protocols\r\n"))
+          (should (equal (websocket-native-parser-feed
+                          parser
+                          (concat (substring (websocket-tests-handshake
+                                              websocket-tests-key)
+                                             34)
+                                  "\x81\x02hi\x01\x03hel"))
+                         '((open) (text . "hi"))))
+          (should (equal (websocket-native-parser-feed
+                          parser "\x89\x00\x80\x02lo\x82\x01\xff")
+                         '((ping . "") (text . "hello") (binary . "\377"))))
+          (should (equal (websocket-native-parser-feed
+                          parser "\x88\x04\x03\xe8ok")
+                         '((close 1000 "ok")))))
+      (websocket-native-parser-free parser))))
+
+(ert-deftest websocket-native-parser-bad-handshake ()
+  (let ((parser (websocket-native-parser websocket-tests-key)))
+    (unwind-protect
+        (should-error (websocket-native-parser-feed
+                       parser
+                       (websocket-tests-handshake "AAAAAAAAAAAAAAAAAAAAAA==")))
+      (websocket-native-parser-free parser)))
+  (let ((parser (websocket-native-parser websocket-tests-key)))
+    (unwind-protect
+        (should-error (websocket-native-parser-feed
+                       parser "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"))
+      (websocket-native-parser-free parser))))
+
+(ert-deftest websocket-native-frame ()
+  (let ((frame (websocket-native-frame 'text "é")))
+    (should (equal (substring frame 0 2) "\x81\x82"))
+    (should (= (length frame) 8))
+    (should (equal (websocket-tests-unmask frame) "\303\251")))
+  (should (equal (substring (websocket-native-frame 'ping "") 0 2) "\x89\x80"))
+  (should-error (websocket-native-frame 'bogus "")))
+
+(ert-deftest websocket-process-local-server ()
+  (let* ((received nil)
+         (server
+          (make-network-process
+           :name "websocket-tests" :server t :host 'local :service t
+           :coding 'binary
+           :filter
+           (lambda (process string)
+             (if (string-match "Sec-WebSocket-Key: \\([^\r]+\\)\r\n" string)
+                 (process-send-string
+                  process (concat (websocket-tests-handshake
+                                   (match-string 1 string))
+                                  "\x81\x02hi"))
+               (push (websocket-tests-unmask string) received)))))
+         (port (process-contact server :service))
+         (messages nil)
+         (events nil))
+    (unwind-protect
+        (let ((process (make-websocket-process
+                        :url (format "ws://127.0.0.1:%d/chat" port)
+                        :keepalive nil
+                        :filter (lambda (_process message type)
+                                  (push (cons type message) messages))
+                        :sentinel (lambda (_process event)
+                                    (push event events)))))
+          (websocket-process-send process "hello")
+          (with-timeout (10 (ert-fail "No messages"))
+            (while (not (and messages received))
+              (accept-process-output nil 0.05)))
+          (should (equal events '("open\n")))
+          (should (equal messages '((text . "hi"))))
+          (should (equal received '("hello")))
+          (delete-process process))
+      (delete-process server))))
+
+(provide 'websocket-tests)
+
+;;; websocket-tests.el ends here
@@ -80,3 +80,31 @@
     (delete-window w2)
     (select-window w1)
     (should (eq (get-mru-window) w1))))
+
+(ert-deftest window-body-geometry ()
+  (let ((width (window-body-width))
+        (height (window-body-height)))
+    (should (<= (* width (frame-char-width)) (window-body-width nil t)))
+    (should (<= (* height (frame-char-height)) (window-body-height nil t)))
+    (should (= (window-right-divider-width) 0))
+    (should (>= (window-mode-line-height) 0))
+    (should (= (window-header-line-height) 0))
+    ;; Margins are taken out of the text area.
+    (save-window-excursion
+      (with-temp-buffer
+        (set-window-buffer nil (current-buffer))
+        (let ((width (window-body-width)))
+          (set-window-margins nil 2 3)
+          (should (= (window-body-width) (- width 5)))
+          (set-window-margins nil nil nil)
+          (should (= (window-body-width) width)))))))
+
+(ert-deftest window-body-geometry-side-by-side ()
+  (let* ((w1 (selected-window))
+         (w2 (split-window-right)))
+    (unwind-protect
+        (progn
+          (should (<= (+ (window-body-width w1) (window-body-width w2))
+                      (window-total-width (window-parent w1))))
+          (should (= (window-body-height w1) (window-body-height w2))))
+      (delete-window w2))))
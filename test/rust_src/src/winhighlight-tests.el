@@ -0,0 +1,46 @@
+;;; winhighlight-tests.el --- Tests for winhighlight.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest window-highlight-set-and-move ()
+  (with-temp-buffer
+    (set-window-buffer nil (current-buffer))
+    (insert "one\ntwo\nthree\n")
+    (unwind-protect
+        (progn
+          (set-window-highlight nil 'test 5 9 'highlight)
+          (should (equal (window-highlight nil 'test) '(5 9 highlight)))
+          ;; The bounds are reordered, and clipped to the buffer.
+          (set-window-highlight nil 'test 100 9 'region 10)
+          (should (equal (window-highlight nil 'test)
+                         (list 9 (point-max) 'region)))
+          ;; The highlight follows the text.
+          (goto-char 1)
+          (insert "zero\n")
+          (should (equal (window-highlight nil 'test)
+                         (list 14 (point-max) 'region)))
+          (should-not (window-highlight nil 'other)))
+      (should (remove-window-highlight nil 'test))
+      (should-not (remove-window-highlight nil 'test))
+      (should-not (window-highlight nil 'test)))))
+
+(ert-deftest window-highlight-other-buffer ()
+  (let ((buffer (get-buffer-create " *winhighlight-tests*")))
+    (unwind-protect
+        (with-temp-buffer
+          (set-window-buffer nil (current-buffer))
+          (insert "some text")
+          (set-window-highlight nil 'test 1 5 'highlight)
+          ;; The highlight is not shown with another buffer.
+          (set-window-buffer nil buffer)
+          (should-not (window-highlight nil 'test))
+          (set-window-buffer nil (current-buffer))
+          (should (equal (window-highlight nil 'test) '(1 5 highlight)))
+          (remove-window-highlight nil 'test))
+      (kill-buffer buffer))))
+
+(provide 'winhighlight-tests)
+
+;;; winhighlight-tests.el ends here
@@ -0,0 +1,54 @@
+;;; wordindex-tests.el --- Tests for wordindex.rs  -*- lexical-binding: t -*-
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest buffer-word-index-counts-and-positions ()
+  (with-temp-buffer
+    (insert "foo bar foo-bar foo")
+    (should (equal (buffer-word-index)
+                   '(("bar" 1 5) ("foo" 2 1 17) ("foo-bar" 1 9))))
+    (should (equal (buffer-word-index nil 'word)
+                   '(("bar" 2 5 13) ("foo" 3 1 9 17))))
+    (should (equal (buffer-word-index nil nil "foo")
+                   '(("foo" 2 1 17) ("foo-bar" 1 9))))
+    (should-not (buffer-word-index nil nil "x"))
+    (should-error (buffer-word-index nil 'line))))
+
+(ert-deftest buffer-word-index-after-changes ()
+  (with-temp-buffer
+    (insert "one two three")
+    (should (equal (buffer-word-index nil 'word)
+                   '(("one" 1 1) ("three" 1 9) ("two" 1 5))))
+    (goto-char (point-min))
+    (insert "zero ")
+    (should (equal (buffer-word-index nil 'word)
+                   '(("one" 1 6) ("three" 1 14) ("two" 1 10) ("zero" 1 1))))
+    ;; Both syntaxes are kept up to date.
+    (should (equal (buffer-word-index nil 'symbol)
+                   '(("one" 1 6) ("three" 1 14) ("two" 1 10) ("zero" 1 1))))
+    (search-forward "two")
+    (insert "-two")
+    (goto-char (point-max))
+    (insert " two")
+    (should (equal (buffer-word-index nil 'word)
+                   '(("one" 1 6) ("three" 1 18) ("two" 3 10 14 24)
+                     ("zero" 1 1))))
+    (should (equal (buffer-word-index nil 'symbol)
+                   '(("one" 1 6) ("three" 1 18) ("two" 1 24)
+                     ("two-two" 1 10) ("zero" 1 1))))))
+
+(ert-deftest buffer-word-index-other-buffer ()
+  (let ((buffer (generate-new-buffer "wordindex-tests")))
+    (unwind-protect
+        (progn
+          (with-current-buffer buffer
+            (insert "hello"))
+          (should (equal (buffer-word-index buffer) '(("hello" 1 1)))))
+      (kill-buffer buffer))
+    (should-error (buffer-word-index buffer))))
+
+(provide 'wordindex-tests)
+
+;;; wordindex-tests.el ends here